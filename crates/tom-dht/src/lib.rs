@@ -17,6 +17,9 @@ use serde::{Deserialize, Serialize};
 /// Salt for BEP-0044 namespace isolation — prevents collisions with other DHT users.
 const SALT: &[u8] = b"tom-addr-v1";
 
+/// Salt for pairing rendezvous records (opaque bytes under a code-derived key).
+const PAIRING_SALT: &[u8] = b"tom-pair-v1";
+
 /// Max age for DHT records (2 hours). Older records are considered stale.
 const MAX_DHT_AGE_MS: u64 = 2 * 3600 * 1000;
 
@@ -173,6 +176,32 @@ pub async fn dht_lookup(dht: &AsyncDht, public_key: &[u8; 32]) -> Result<Option<
     Ok(Some(addr))
 }
 
+/// Publish an opaque pairing record under a throwaway rendezvous key.
+///
+/// The key is derived from a short code by the caller; whoever knows the
+/// code derives the same public key and reads the record back with
+/// [`dht_lookup_pairing`]. The current time is the sequence number, so a
+/// re-publish under the same key always supersedes the previous one.
+pub async fn dht_publish_pairing(
+    dht: &AsyncDht,
+    rendezvous_seed: &[u8; 32],
+    payload: &[u8],
+) -> Result<()> {
+    let signer = SigningKey::from_bytes(rendezvous_seed);
+    let item = MutableItem::new(signer, payload, now_ms() as i64, Some(PAIRING_SALT));
+    dht.put_mutable(item, None)
+        .await
+        .map_err(|e| anyhow::anyhow!("DHT put_mutable failed: {e}"))?;
+    Ok(())
+}
+
+/// Look up a pairing record by rendezvous public key (raw bytes, unchecked).
+pub async fn dht_lookup_pairing(dht: &AsyncDht, public_key: &[u8; 32]) -> Option<Vec<u8>> {
+    dht.get_mutable_most_recent(public_key, Some(PAIRING_SALT))
+        .await
+        .map(|item| item.value().to_vec())
+}
+
 fn now_ms() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -296,6 +325,29 @@ mod tests {
         futures_lite::future::block_on(test());
     }
 
+    #[test]
+    fn test_pairing_record_roundtrip() {
+        async fn test() {
+            let testnet = Testnet::builder(10).build().unwrap();
+            let publisher = make_dht(&testnet);
+            let reader = make_dht(&testnet);
+
+            let seed = [77u8; 32];
+            let public_key = SigningKey::from_bytes(&seed).verifying_key().to_bytes();
+
+            dht_publish_pairing(&publisher.async_dht(), &seed, b"pairing")
+                .await
+                .expect("publish failed");
+            let found = dht_lookup_pairing(&reader.async_dht(), &public_key).await;
+            assert_eq!(found.as_deref(), Some(&b"pairing"[..]));
+
+            // Address records under the same key live in another namespace
+            assert!(reader.lookup(&public_key).await.unwrap().is_none());
+        }
+
+        futures_lite::future::block_on(test());
+    }
+
     #[test]
     fn test_stale_record_filtered() {
         async fn test() {
@@ -0,0 +1,524 @@
+//! UniFFI bindings for tom-protocol — Swift/Kotlin access to TomNode + ProtocolRuntime
+//!
+//! Architecture:
+//! - The app builds a `NodeConfig` and calls `TomNode.start(config, listener)`
+//!   → binds the transport and spawns the ProtocolRuntime
+//! - Delivered messages, status changes and protocol events are pushed to the
+//!   app's `EventListener` on a dedicated thread (no polling); the listener
+//!   may call back into the node from there
+//! - Commands (`send_message`, `create_group`, ...) block until the runtime
+//!   accepted them
+//! - `shutdown()` stops the runtime; the node is unusable afterwards
+//!
+//! Bindings are generated from the built library, e.g.
+//! `uniffi-bindgen generate --library libtom_ffi.dylib --language swift`.
+//!
+//! All async operations run on an internal tokio runtime.
+
+use std::sync::Arc;
+
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+use tom_protocol::{
+    DeliveredMessage, ProtocolEvent, ProtocolRuntime, RuntimeConfig, RuntimeHandle, StatusChange,
+};
+use tom_transport::TomNodeConfig;
+
+uniffi::setup_scaffolding!();
+
+/// Errors surfaced to Swift/Kotlin.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum TomError {
+    #[error("invalid argument: {reason}")]
+    InvalidArgument { reason: String },
+    #[error("failed to bind node: {reason}")]
+    Bind { reason: String },
+    #[error("runtime error: {reason}")]
+    Runtime { reason: String },
+}
+
+impl From<tom_protocol::TomProtocolError> for TomError {
+    fn from(e: tom_protocol::TomProtocolError) -> Self {
+        Self::Runtime { reason: e.to_string() }
+    }
+}
+
+/// Node configuration (transport + protocol layers).
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct NodeConfig {
+    /// Local username for group membership
+    pub username: String,
+    /// Custom relay URL (overrides TOM_RELAY_URL env var)
+    pub relay_url: Option<String>,
+    /// Path to persistent identity file (32-byte Ed25519 secret key)
+    pub identity_path: Option<String>,
+    /// Directory for persistent state (SQLite)
+    pub data_dir: Option<String>,
+    /// Enable n0-computer address discovery (Pkarr/DNS), default true
+    pub n0_discovery: Option<bool>,
+    /// Enable E2E encryption for outbound messages, default true
+    pub encryption: Option<bool>,
+    /// Enable DHT-based peer discovery, default true
+    pub enable_dht: Option<bool>,
+    /// Gossip bootstrap peers (hex NodeId strings)
+    pub gossip_bootstrap_peers: Vec<String>,
+}
+
+/// A 1-1 message delivered to this node.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct Message {
+    pub from: String,
+    pub payload: Vec<u8>,
+    pub envelope_id: String,
+    pub timestamp: u64,
+    pub signature_valid: bool,
+    pub was_encrypted: bool,
+    pub content_type: Option<String>,
+}
+
+impl From<DeliveredMessage> for Message {
+    fn from(msg: DeliveredMessage) -> Self {
+        Self {
+            from: msg.from.to_string(),
+            payload: msg.payload,
+            envelope_id: msg.envelope_id,
+            timestamp: msg.timestamp,
+            signature_valid: msg.signature_valid,
+            was_encrypted: msg.was_encrypted,
+            content_type: msg.content_type,
+        }
+    }
+}
+
+/// Delivery status of a sent message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum MessageStatus {
+    Pending,
+    Queued,
+    Sent,
+    Relayed,
+    Delivered,
+    Read,
+    Failed,
+}
+
+impl From<tom_protocol::MessageStatus> for MessageStatus {
+    fn from(status: tom_protocol::MessageStatus) -> Self {
+        use tom_protocol::MessageStatus as S;
+        match status {
+            S::Pending => Self::Pending,
+            S::Queued => Self::Queued,
+            S::Sent => Self::Sent,
+            S::Relayed => Self::Relayed,
+            S::Delivered => Self::Delivered,
+            S::Read => Self::Read,
+            S::Failed => Self::Failed,
+        }
+    }
+}
+
+/// Coarse grouping of protocol events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum EventCategory {
+    Peer,
+    Group,
+    Delivery,
+    Backup,
+    Role,
+    Subnet,
+    AntiSpam,
+    Network,
+    Realtime,
+}
+
+impl From<tom_protocol::EventCategory> for EventCategory {
+    fn from(category: tom_protocol::EventCategory) -> Self {
+        use tom_protocol::EventCategory as C;
+        match category {
+            C::Peer => Self::Peer,
+            C::Group => Self::Group,
+            C::Delivery => Self::Delivery,
+            C::Backup => Self::Backup,
+            C::Role => Self::Role,
+            C::Subnet => Self::Subnet,
+            C::AntiSpam => Self::AntiSpam,
+            C::Network => Self::Network,
+            C::Realtime => Self::Realtime,
+        }
+    }
+}
+
+/// Protocol events, flattened for foreign languages.
+///
+/// The common ones get their own variant; everything else arrives as
+/// `Other`: its category, its variant name (`kind`) and its fields as a
+/// JSON object keyed by field name.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum Event {
+    PeerDiscovered { node_id: String, username: String },
+    PeerOnline { node_id: String },
+    PeerOffline { node_id: String },
+    PeerTyping { node_id: String, typing: bool },
+    GroupCreated { group_id: String, name: String },
+    GroupMessage {
+        group_id: String,
+        message_id: String,
+        sender_id: String,
+        sender_username: String,
+        text: String,
+    },
+    Error { description: String },
+    Other {
+        category: EventCategory,
+        kind: String,
+        fields_json: String,
+    },
+}
+
+impl From<ProtocolEvent> for Event {
+    fn from(event: ProtocolEvent) -> Self {
+        match event {
+            ProtocolEvent::PeerDiscovered { node_id, username, .. } => Self::PeerDiscovered {
+                node_id: node_id.to_string(),
+                username,
+            },
+            ProtocolEvent::PeerOnline { node_id } => Self::PeerOnline {
+                node_id: node_id.to_string(),
+            },
+            ProtocolEvent::PeerOffline { node_id } => Self::PeerOffline {
+                node_id: node_id.to_string(),
+            },
+            ProtocolEvent::PeerTyping { node_id, typing } => Self::PeerTyping {
+                node_id: node_id.to_string(),
+                typing,
+            },
+            ProtocolEvent::GroupCreated { group } => Self::GroupCreated {
+                group_id: group.group_id.0,
+                name: group.name,
+            },
+            ProtocolEvent::GroupMessageReceived { message, .. } => Self::GroupMessage {
+                group_id: message.group_id.0,
+                message_id: message.message_id,
+                sender_id: message.sender_id.to_string(),
+                sender_username: message.sender_username,
+                text: message.text,
+            },
+            ProtocolEvent::Error { description } => Self::Error { description },
+            other => {
+                let mut tagged = serde_json::to_value(&other).unwrap_or_default();
+                Self::Other {
+                    category: other.category().into(),
+                    kind: tagged["kind"].as_str().unwrap_or_default().to_string(),
+                    fields_json: tagged["fields"].take().to_string(),
+                }
+            }
+        }
+    }
+}
+
+/// Implemented by the app to receive everything the runtime produces.
+///
+/// Called on a dedicated thread, one call at a time; implementations may
+/// call `TomNode` methods, but shouldn't block for long (the next messages
+/// wait).
+#[uniffi::export(with_foreign)]
+pub trait EventListener: Send + Sync {
+    fn on_message(&self, message: Message);
+    /// Status of a sent message changed (`Sent`, `Delivered`, `Read`, ...).
+    fn on_status(&self, message_id: String, status: MessageStatus);
+    fn on_event(&self, event: Event);
+}
+
+/// A running TOM protocol node.
+#[derive(uniffi::Object)]
+pub struct TomNode {
+    runtime: Runtime,
+    handle: RuntimeHandle,
+    node_id: String,
+}
+
+#[uniffi::export]
+impl TomNode {
+    /// Bind the transport, spawn the protocol runtime and start pushing
+    /// messages and events to `listener`.
+    #[uniffi::constructor]
+    pub fn start(config: NodeConfig, listener: Arc<dyn EventListener>) -> Result<Arc<Self>, TomError> {
+        let mut transport_config =
+            TomNodeConfig::new().n0_discovery(config.n0_discovery.unwrap_or(true));
+        if let Some(relay_url) = &config.relay_url {
+            let url = relay_url.parse().map_err(|e| TomError::InvalidArgument {
+                reason: format!("relay_url: {e}"),
+            })?;
+            transport_config = transport_config.relay_url(url);
+        }
+        if let Some(identity_path) = &config.identity_path {
+            transport_config = transport_config.identity_path(identity_path.into());
+        }
+
+        let gossip_bootstrap_peers = config
+            .gossip_bootstrap_peers
+            .iter()
+            .map(|id| parse_node_id(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let protocol_config = RuntimeConfig {
+            username: config.username,
+            encryption: config.encryption.unwrap_or(true),
+            enable_dht: config.enable_dht.unwrap_or(true),
+            data_dir: config.data_dir.map(Into::into),
+            gossip_bootstrap_peers,
+            ..Default::default()
+        };
+
+        let runtime = Runtime::new().map_err(|e| TomError::Runtime {
+            reason: format!("failed to create tokio runtime: {e}"),
+        })?;
+
+        let (channels, node_id) = runtime.block_on(async {
+            let node = tom_transport::TomNode::bind(transport_config)
+                .await
+                .map_err(|e| TomError::Bind { reason: e.to_string() })?;
+            let node_id = node.id().to_string();
+            tracing::info!("TomNode bound: {}", node_id);
+
+            let channels = ProtocolRuntime::spawn(node, protocol_config);
+            Ok::<_, TomError>((channels, node_id))
+        })?;
+        spawn_listener(
+            &runtime,
+            channels.messages,
+            channels.status_changes,
+            channels.events,
+            listener,
+        )?;
+        let handle = channels.handle;
+
+        Ok(Arc::new(Self {
+            runtime,
+            handle,
+            node_id,
+        }))
+    }
+
+    /// This node's id (hex string).
+    pub fn node_id(&self) -> String {
+        self.node_id.clone()
+    }
+
+    /// Send a 1-1 message; returns its envelope id (the `message_id` of
+    /// its `on_status` updates).
+    pub fn send_message(&self, to: String, payload: Vec<u8>) -> Result<String, TomError> {
+        let to = parse_node_id(&to)?;
+        Ok(self.runtime.block_on(self.handle.send_message(to, payload))?)
+    }
+
+    /// Teach the node how to reach a peer (relay URL and/or direct addresses).
+    pub fn add_peer_addr(
+        &self,
+        node_id: String,
+        relay_url: Option<String>,
+        direct_addrs: Vec<String>,
+    ) -> Result<(), TomError> {
+        let node_id = parse_node_id(&node_id)?;
+        let mut addr = tom_connect::EndpointAddr::new(*node_id.as_endpoint_id());
+        if let Some(relay_url) = relay_url {
+            let url = relay_url.parse().map_err(|e| TomError::InvalidArgument {
+                reason: format!("relay_url: {e}"),
+            })?;
+            addr = addr.with_relay_url(url);
+        }
+        for direct in direct_addrs {
+            let ip = direct.parse().map_err(|e| TomError::InvalidArgument {
+                reason: format!("direct address {direct}: {e}"),
+            })?;
+            addr = addr.with_ip_addr(ip);
+        }
+        self.runtime.block_on(self.handle.add_peer_addr(addr));
+        Ok(())
+    }
+
+    /// Peers with an open connection (hex node ids).
+    pub fn connected_peers(&self) -> Vec<String> {
+        self.runtime
+            .block_on(self.handle.connected_peers())
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Create a group; its id arrives with `Event::GroupCreated`.
+    pub fn create_group(
+        &self,
+        name: String,
+        hub_relay_id: String,
+        initial_members: Vec<String>,
+        invite_only: bool,
+    ) -> Result<(), TomError> {
+        let hub_relay_id = parse_node_id(&hub_relay_id)?;
+        let initial_members = initial_members
+            .iter()
+            .map(|id| parse_node_id(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.runtime.block_on(async {
+            if invite_only {
+                self.handle
+                    .create_group_invite_only(name, hub_relay_id, initial_members)
+                    .await
+            } else {
+                self.handle.create_group(name, hub_relay_id, initial_members).await
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Send a text message to a group.
+    pub fn send_group_message(&self, group_id: String, text: String) -> Result<(), TomError> {
+        self.runtime.block_on(
+            self.handle
+                .send_group_message(tom_protocol::group::GroupId(group_id), text),
+        )?;
+        Ok(())
+    }
+
+    /// Stop the runtime. Further commands fail.
+    pub fn shutdown(&self) {
+        tracing::info!("Shutting down TOM protocol node...");
+        let _ = self.runtime.block_on(self.handle.shutdown());
+    }
+}
+
+fn parse_node_id(id: &str) -> Result<tom_protocol::types::NodeId, TomError> {
+    id.parse().map_err(|e| TomError::InvalidArgument {
+        reason: format!("node id {id}: {e}"),
+    })
+}
+
+/// Capacity of the queue between the runtime and the listener thread.
+const LISTENER_QUEUE: usize = 1024;
+
+/// What the listener thread hands to the app.
+enum Output {
+    Message(DeliveredMessage),
+    Status(StatusChange),
+    Event(Box<ProtocolEvent>),
+}
+
+/// Forward runtime output to `listener` on a dedicated thread, so its
+/// callbacks can block on the node (they'd panic on a runtime worker).
+/// Stops once every channel closes.
+fn spawn_listener(
+    runtime: &Runtime,
+    messages: mpsc::Receiver<DeliveredMessage>,
+    status_changes: mpsc::Receiver<StatusChange>,
+    events: mpsc::Receiver<ProtocolEvent>,
+    listener: Arc<dyn EventListener>,
+) -> Result<(), TomError> {
+    let (tx, mut rx) = mpsc::channel(LISTENER_QUEUE);
+    std::thread::Builder::new()
+        .name("tom-ffi-listener".into())
+        .spawn(move || {
+            while let Some(output) = rx.blocking_recv() {
+                match output {
+                    Output::Message(msg) => listener.on_message(msg.into()),
+                    Output::Status(change) => listener.on_status(change.message_id, change.current.into()),
+                    Output::Event(event) => listener.on_event((*event).into()),
+                }
+            }
+        })
+        .map_err(|e| TomError::Runtime {
+            reason: format!("failed to start the listener thread: {e}"),
+        })?;
+    runtime.spawn(pump(messages, status_changes, events, tx));
+    Ok(())
+}
+
+/// Merge runtime output into the listener thread's queue until every
+/// channel closes (or the thread is gone).
+async fn pump(
+    mut messages: mpsc::Receiver<DeliveredMessage>,
+    mut status_changes: mpsc::Receiver<StatusChange>,
+    mut events: mpsc::Receiver<ProtocolEvent>,
+    tx: mpsc::Sender<Output>,
+) {
+    loop {
+        let output = tokio::select! {
+            Some(msg) = messages.recv() => Output::Message(msg),
+            Some(change) = status_changes.recv() => Output::Status(change),
+            Some(event) = events.recv() => Output::Event(Box::new(event)),
+            else => break,
+        };
+        if tx.send(output).await.is_err() {
+            break;
+        }
+    }
+    tracing::warn!("Message/event pump stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_flattened_for_foreign_languages() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let id: tom_protocol::types::NodeId = tom_connect::SecretKey::generate(&mut rng)
+            .public()
+            .to_string()
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            Event::from(ProtocolEvent::PeerTyping { node_id: id, typing: true }),
+            Event::PeerTyping {
+                node_id: id.to_string(),
+                typing: true,
+            }
+        );
+        assert_eq!(
+            Event::from(ProtocolEvent::EventsDropped { count: 3 }),
+            Event::Other {
+                category: EventCategory::Network,
+                kind: "EventsDropped".into(),
+                fields_json: r#"{"count":3}"#.into(),
+            }
+        );
+        assert!(matches!(parse_node_id("nope"), Err(TomError::InvalidArgument { .. })));
+    }
+
+    #[test]
+    fn listener_may_block_on_the_node() {
+        struct Blocking {
+            runtime: tokio::runtime::Handle,
+            seen: std::sync::mpsc::Sender<MessageStatus>,
+        }
+        impl EventListener for Blocking {
+            fn on_message(&self, _: Message) {}
+            fn on_status(&self, _: String, status: MessageStatus) {
+                // What a listener calling back into TomNode does
+                self.runtime.block_on(async {});
+                let _ = self.seen.send(status);
+            }
+            fn on_event(&self, _: Event) {}
+        }
+
+        let runtime = Runtime::new().unwrap();
+        let (_msg_tx, messages) = mpsc::channel(1);
+        let (status_tx, status_changes) = mpsc::channel(1);
+        let (_event_tx, events) = mpsc::channel(1);
+        let (seen_tx, seen) = std::sync::mpsc::channel();
+        let listener = Blocking {
+            runtime: runtime.handle().clone(),
+            seen: seen_tx,
+        };
+        spawn_listener(&runtime, messages, status_changes, events, Arc::new(listener)).unwrap();
+
+        let change = StatusChange {
+            message_id: "m".into(),
+            previous: tom_protocol::MessageStatus::Sent,
+            current: tom_protocol::MessageStatus::Delivered,
+        };
+        runtime.block_on(status_tx.send(change)).unwrap();
+        let status = seen.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert_eq!(status, MessageStatus::Delivered);
+    }
+}
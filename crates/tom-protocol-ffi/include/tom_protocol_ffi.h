@@ -1,15 +1,30 @@
 #ifndef TOM_PROTOCOL_FFI_H
 #define TOM_PROTOCOL_FFI_H
 
+#include <stddef.h>
 #include <stdint.h>
 
 #ifdef __cplusplus
 extern "C" {
 #endif
 
+/// Version of the C ABI described by this header.
+/// Compare with tom_ffi_abi_version() before using the library.
+#define TOM_FFI_ABI_VERSION 1
+
 /// Opaque handle to TOM protocol node
 typedef void* TomNodeHandle;
 
+/// Owned byte buffer (MessagePack output)
+/// Caller must release it with tom_buffer_free()
+typedef struct TomBuffer {
+    uint8_t* data;
+    size_t len;
+} TomBuffer;
+
+/// Version of the C ABI implemented by the loaded library
+uint32_t tom_ffi_abi_version(void);
+
 /// Create a TOM protocol node (config as JSON string)
 ///
 /// Example config JSON:
@@ -97,6 +112,27 @@ int32_t tom_node_send_group_message(
 /// Caller must free returned string with tom_node_free_string()
 char* tom_node_receive_messages(const TomNodeHandle handle);
 
+/// Receive messages as MessagePack (same layout as tom_node_receive_messages)
+///
+/// Returns: buffer with the encoded array, data == NULL on error
+/// Caller must free returned buffer with tom_buffer_free()
+TomBuffer tom_node_receive_messages_msgpack(const TomNodeHandle handle);
+
+/// Poll protocol events buffered since the last call
+///
+/// Returns: JSON array of events (empty array if none)
+/// Example: [{"category":"Peer","kind":"PeerOnline","fields":{"node_id":"..."}}]
+/// `fields` holds the event's fields by name (see ProtocolEvent in tom-protocol)
+/// At most 4096 events are buffered between polls (oldest dropped first)
+///
+/// Caller must free returned string with tom_node_free_string()
+char* tom_node_poll_events(const TomNodeHandle handle);
+
+/// Poll protocol events as MessagePack (same layout as tom_node_poll_events)
+///
+/// Caller must free returned buffer with tom_buffer_free()
+TomBuffer tom_node_poll_events_msgpack(const TomNodeHandle handle);
+
 /// Get node status
 ///
 /// Returns: JSON string with node_id, status, peers_count, groups_count
@@ -141,6 +177,9 @@ char* tom_node_discovered_peers(const TomNodeHandle handle);
 /// Free a string returned by FFI functions
 void tom_node_free_string(char* s);
 
+/// Free a buffer returned by *_msgpack functions
+void tom_buffer_free(TomBuffer buffer);
+
 #ifdef __cplusplus
 }
 #endif
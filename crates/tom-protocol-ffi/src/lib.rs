@@ -4,10 +4,15 @@
 //! - Swift calls tom_node_create() with JSON config → returns opaque TomNodeHandle
 //! - Swift calls tom_node_start() → spawns ProtocolRuntime
 //! - Swift polls tom_node_receive_messages() → batch JSON of incoming messages
+//! - Swift polls tom_node_poll_events() → batch JSON of protocol events
+//!   (`*_msgpack` variants return the same batches as MessagePack)
 //! - Swift calls tom_node_send_message() / tom_node_create_group() → commands
 //! - Swift calls tom_node_stop() → shutdown + cleanup
 //!
 //! All async operations are managed by an internal tokio runtime.
+//!
+//! The C ABI is versioned: callers from C++, Go or Python (cffi) check
+//! tom_ffi_abi_version() against the header's TOM_FFI_ABI_VERSION.
 
 use std::collections::{HashMap, VecDeque};
 use std::ffi::{CStr, CString};
@@ -22,12 +27,21 @@ use tom_protocol::{DeliveredMessage, ProtocolEvent, ProtocolRuntime, RuntimeChan
 use tom_transport::TomNodeConfig;
 
 mod types;
-use types::{DeliveredMessageFFI, DiscoveredPeerFFI, GroupConfigFFI, NodeConfigFFI, PeerAddrFFI, RuntimeConfigFFI};
+pub use types::TomBuffer;
+use types::{DeliveredMessageFFI, DiscoveredPeerFFI, EventFFI, GroupConfigFFI, NodeConfigFFI, PeerAddrFFI, RuntimeConfigFFI};
+
+/// Version of this C ABI. Bumped on any incompatible change to exported
+/// functions or JSON/msgpack layouts.
+pub const TOM_FFI_ABI_VERSION: u32 = 1;
 
 /// Hard cap for discovered peer cache exposed over FFI.
 /// Prevents unbounded growth on long-running tvOS sessions.
 const MAX_DISCOVERED_PEERS: usize = 2048;
 
+/// Hard cap for buffered events between two polls (oldest dropped first).
+/// Callers that never poll events must not leak memory.
+const MAX_QUEUED_EVENTS: usize = 4096;
+
 /// Opaque handle to the TOM protocol node (passed to/from Swift as void*)
 pub struct TomNodeHandle {
     runtime: Runtime,
@@ -280,7 +294,11 @@ pub unsafe extern "C" fn tom_node_start(
                                 }
                             }
                         }
-                        event_queue_clone.lock().await.push_back(event);
+                        let mut events = event_queue_clone.lock().await;
+                        if events.len() >= MAX_QUEUED_EVENTS {
+                            events.pop_front();
+                        }
+                        events.push_back(event);
                     }
                     else => break,
                 }
@@ -548,29 +566,110 @@ pub unsafe extern "C" fn tom_node_receive_messages(
 
     let handle_ref = unsafe { &*handle };
 
-    let messages_json = handle_ref.runtime.block_on(async {
-        let mut queue = handle_ref.message_queue.lock().await;
-        let batch: Vec<DeliveredMessage> = queue.drain(..).collect();
+    let ffi_batch = drain_messages(handle_ref);
+    let messages_json = match serde_json::to_string(&ffi_batch) {
+        Ok(json) => json,
+        Err(e) => {
+            tracing::error!("Failed to serialize messages: {}", e);
+            "[]".to_string()
+        }
+    };
 
-        // Convert to FFI-serializable type
-        let ffi_batch: Vec<DeliveredMessageFFI> = batch.into_iter().map(Into::into).collect();
+    match CString::new(messages_json) {
+        Ok(c_str) => c_str.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
 
-        // Serialize to JSON
-        match serde_json::to_string(&ffi_batch) {
-            Ok(json) => json,
-            Err(e) => {
-                tracing::error!("Failed to serialize messages: {}", e);
-                "[]".to_string()
-            }
+/// Receive messages as a MessagePack array (same layout as the JSON variant)
+///
+/// # Returns
+/// * Buffer with the encoded batch (an empty array if no messages)
+/// * Null buffer (`data == NULL`) on error
+///
+/// # Safety
+/// * Caller must free the returned buffer with `tom_buffer_free()`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tom_node_receive_messages_msgpack(
+    handle: *const TomNodeHandle,
+) -> TomBuffer {
+    if handle.is_null() {
+        return TomBuffer::null();
+    }
+
+    let handle_ref = unsafe { &*handle };
+    encode_msgpack(&drain_messages(handle_ref))
+}
+
+/// Poll protocol events buffered since the last call
+///
+/// # Returns
+/// * JSON array of events: `[{"category": "Peer", "kind": "PeerDiscovered", "fields": {...}}, ...]`
+/// * Empty array `[]` if no events
+/// * NULL on error
+///
+/// # Safety
+/// * Caller must free returned string with `tom_node_free_string()`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tom_node_poll_events(handle: *const TomNodeHandle) -> *mut c_char {
+    if handle.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let handle_ref = unsafe { &*handle };
+
+    let events_json = match serde_json::to_string(&drain_events(handle_ref)) {
+        Ok(json) => json,
+        Err(e) => {
+            tracing::error!("Failed to serialize events: {}", e);
+            "[]".to_string()
         }
-    });
+    };
 
-    match CString::new(messages_json) {
+    match CString::new(events_json) {
         Ok(c_str) => c_str.into_raw(),
         Err(_) => std::ptr::null_mut(),
     }
 }
 
+/// Poll protocol events as a MessagePack array (same layout as the JSON variant)
+///
+/// # Safety
+/// * Caller must free the returned buffer with `tom_buffer_free()`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tom_node_poll_events_msgpack(handle: *const TomNodeHandle) -> TomBuffer {
+    if handle.is_null() {
+        return TomBuffer::null();
+    }
+
+    let handle_ref = unsafe { &*handle };
+    encode_msgpack(&drain_events(handle_ref))
+}
+
+fn drain_messages(handle_ref: &TomNodeHandle) -> Vec<DeliveredMessageFFI> {
+    handle_ref.runtime.block_on(async {
+        let mut queue = handle_ref.message_queue.lock().await;
+        queue.drain(..).map(Into::into).collect()
+    })
+}
+
+fn drain_events(handle_ref: &TomNodeHandle) -> Vec<EventFFI> {
+    handle_ref.runtime.block_on(async {
+        let mut queue = handle_ref.event_queue.lock().await;
+        queue.drain(..).map(|event| EventFFI::from(&event)).collect()
+    })
+}
+
+fn encode_msgpack<T: serde::Serialize>(value: &T) -> TomBuffer {
+    match rmp_serde::to_vec_named(value) {
+        Ok(bytes) => TomBuffer::from_vec(bytes),
+        Err(e) => {
+            tracing::error!("Failed to encode msgpack: {}", e);
+            TomBuffer::null()
+        }
+    }
+}
+
 /// Get node status
 ///
 /// # Returns
@@ -788,6 +887,25 @@ pub unsafe extern "C" fn tom_node_discovered_peers(
     }
 }
 
+/// Version of the C ABI implemented by this library (`TOM_FFI_ABI_VERSION`)
+#[unsafe(no_mangle)]
+pub extern "C" fn tom_ffi_abi_version() -> u32 {
+    TOM_FFI_ABI_VERSION
+}
+
+/// Free a buffer returned by `*_msgpack` functions
+///
+/// # Safety
+/// * `buffer` must have been returned by a `tom_node_*_msgpack` function
+///   and not freed before
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tom_buffer_free(buffer: TomBuffer) {
+    if !buffer.data.is_null() {
+        let slice = std::ptr::slice_from_raw_parts_mut(buffer.data, buffer.len);
+        let _ = unsafe { Box::from_raw(slice) };
+    }
+}
+
 /// Free a string returned by FFI functions
 ///
 /// # Safety
@@ -803,6 +921,15 @@ pub unsafe extern "C" fn tom_node_free_string(s: *mut c_char) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_event_ffi_kind() {
+        let event = ProtocolEvent::EventsDropped { count: 2 };
+        let ffi = EventFFI::from(&event);
+        assert_eq!(ffi.kind, "EventsDropped");
+        assert_eq!(ffi.category, "Network");
+        assert_eq!(ffi.fields, serde_json::json!({"count": 2}));
+    }
+
     #[test]
     fn test_node_lifecycle() {
         use std::ffi::CString;
@@ -824,6 +951,7 @@ mod tests {
             identity_path: None,
             n0_discovery: Some(false),
             data_dir: None,
+            gossip_bootstrap_peers: Vec::new(),
         };
         let runtime_config_json = serde_json::to_string(&runtime_config).unwrap();
         let runtime_config_cstr = CString::new(runtime_config_json).unwrap();
@@ -847,6 +975,22 @@ mod tests {
             assert!(status_str.contains("Running"));
 
             tom_node_free_string(status_ptr);
+
+            let events_ptr = tom_node_poll_events(handle);
+            assert!(!events_ptr.is_null(), "tom_node_poll_events should return valid pointer");
+            let events: Vec<EventFFI> =
+                serde_json::from_str(CStr::from_ptr(events_ptr).to_str().unwrap()).unwrap();
+            assert!(events.iter().all(|e| !e.kind.is_empty()));
+            tom_node_free_string(events_ptr);
+
+            let buffer = tom_node_receive_messages_msgpack(handle);
+            assert!(!buffer.data.is_null());
+            let bytes = std::slice::from_raw_parts(buffer.data, buffer.len);
+            let messages: Vec<DeliveredMessageFFI> = rmp_serde::from_slice(bytes).unwrap();
+            assert!(messages.is_empty());
+            tom_buffer_free(buffer);
+
+            assert_eq!(tom_ffi_abi_version(), TOM_FFI_ABI_VERSION);
             tom_node_stop(handle);
         }
     }
@@ -29,6 +29,63 @@ impl From<tom_protocol::DeliveredMessage> for DeliveredMessageFFI {
     }
 }
 
+/// Serializable protocol event for FFI
+/// `kind` is the variant name (e.g. "PeerDiscovered"), `fields` its fields
+/// by name (node ids as strings, byte payloads as arrays), `category` the
+/// `EventCategory` name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventFFI {
+    pub category: String,
+    pub kind: String,
+    pub fields: serde_json::Value,
+}
+
+impl From<&tom_protocol::ProtocolEvent> for EventFFI {
+    fn from(event: &tom_protocol::ProtocolEvent) -> Self {
+        let mut tagged = serde_json::to_value(event).unwrap_or_default();
+        let kind = match tagged["kind"].take() {
+            serde_json::Value::String(kind) => kind,
+            _ => String::new(),
+        };
+        let category = match serde_json::to_value(event.category()) {
+            Ok(serde_json::Value::String(category)) => category,
+            _ => String::new(),
+        };
+        Self {
+            category,
+            kind,
+            fields: tagged["fields"].take(),
+        }
+    }
+}
+
+/// Owned byte buffer handed across the C ABI (msgpack output)
+/// Must be released with `tom_buffer_free()`
+#[repr(C)]
+#[derive(Debug)]
+pub struct TomBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl TomBuffer {
+    pub fn null() -> Self {
+        Self {
+            data: std::ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    pub fn from_vec(bytes: Vec<u8>) -> Self {
+        let boxed = bytes.into_boxed_slice();
+        let len = boxed.len();
+        Self {
+            data: Box::into_raw(boxed) as *mut u8,
+            len,
+        }
+    }
+}
+
 /// Peer address for add_peer_addr FFI
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PeerAddrFFI {
@@ -0,0 +1,65 @@
+//! Receive path: what each incoming protocol frame costs before the
+//! runtime handles it.
+//!
+//! Run with `cargo bench -p tom-protocol --bench receive_path`. Each group
+//! sets the former path against the current one:
+//! - `classify_frame`: the transport told protocol frames from its own
+//!   envelopes by failing to decode them; it now looks at the first byte.
+//! - `hosted_recipient`: the runtime decoded the whole envelope to route
+//!   it to a hosted identity; it now reads the recipient in place.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::SeedableRng;
+use tom_protocol::{Envelope, MessageType, NodeId};
+use tom_transport::{Bytes, MessageEnvelope};
+
+const PAYLOAD_SIZES: [usize; 3] = [64, 1024, 16 * 1024];
+
+fn node_id(seed: u64) -> NodeId {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    tom_connect::SecretKey::generate(&mut rng)
+        .public()
+        .to_string()
+        .parse()
+        .unwrap()
+}
+
+fn frame(payload_size: usize) -> Bytes {
+    let mut envelope = Envelope::new(node_id(1), node_id(2), MessageType::Chat, vec![0xab; payload_size]);
+    envelope.via = vec![node_id(3)];
+    envelope.signature = vec![0x5a; 64];
+    Bytes::from(envelope.to_bytes().unwrap())
+}
+
+fn classify_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("classify_frame");
+    for size in PAYLOAD_SIZES {
+        let data = frame(size);
+        group.bench_with_input(BenchmarkId::new("trial_decode", size), &data, |b, data| {
+            b.iter(|| MessageEnvelope::from_bytes(black_box(data)).is_err())
+        });
+        group.bench_with_input(BenchmarkId::new("first_byte", size), &data, |b, data| {
+            b.iter(|| !MessageEnvelope::is_encoded(black_box(data)))
+        });
+    }
+    group.finish();
+}
+
+fn hosted_recipient(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hosted_recipient");
+    for size in PAYLOAD_SIZES {
+        let data = frame(size);
+        group.bench_with_input(BenchmarkId::new("full_decode", size), &data, |b, data| {
+            b.iter(|| Envelope::from_bytes(black_box(data)).unwrap().to)
+        });
+        group.bench_with_input(BenchmarkId::new("in_place", size), &data, |b, data| {
+            b.iter(|| Envelope::recipient(black_box(data)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, classify_frame, hosted_recipient);
+criterion_main!(benches);
@@ -0,0 +1,194 @@
+/// Bloom filter over message IDs for backup set reconciliation.
+///
+/// A querying node sends a filter of the message IDs it already holds for a
+/// recipient; each backup holder answers with only the IDs the filter does
+/// not contain. One small round trip per holder, however many messages.
+///
+/// False positives make a holder skip a message the querier lacks; it is
+/// still delivered when the holder sees the recipient online, or found by a
+/// later query (filters are rebuilt each time).
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Target false-positive rate for query filters.
+pub const BLOOM_FALSE_POSITIVE_RATE: f64 = 0.001;
+
+/// Largest filter we build or accept (bytes). ~9k IDs at the target rate.
+pub const MAX_BLOOM_BYTES: usize = 16 * 1024;
+
+/// Most hash functions we accept in a received filter.
+pub const MAX_BLOOM_HASHES: u8 = 16;
+
+/// Smallest filter we build (bytes).
+const MIN_BLOOM_BYTES: usize = 8;
+
+/// A fixed-size Bloom filter of strings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BloomFilter {
+    /// Bit array (little-endian bit order within each byte).
+    #[serde(with = "as_bin")]
+    bits: Vec<u8>,
+    /// Number of hash functions.
+    num_hashes: u8,
+}
+
+impl BloomFilter {
+    /// Empty filter sized for `expected_items` at the default false-positive rate.
+    pub fn new(expected_items: usize) -> Self {
+        Self::with_rate(expected_items, BLOOM_FALSE_POSITIVE_RATE)
+    }
+
+    /// Empty filter sized for `expected_items` at `fp_rate`, capped at
+    /// [`MAX_BLOOM_BYTES`].
+    pub fn with_rate(expected_items: usize, fp_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-n * fp_rate.ln() / (ln2 * ln2)).ceil() as usize;
+        let bytes = bits.div_ceil(8).clamp(MIN_BLOOM_BYTES, MAX_BLOOM_BYTES);
+        let k = ((bytes * 8) as f64 / n * ln2).round() as u8;
+        Self {
+            bits: vec![0; bytes],
+            num_hashes: k.clamp(1, MAX_BLOOM_HASHES),
+        }
+    }
+
+    /// Filter containing every item of `items`.
+    pub fn from_items<'a>(items: impl ExactSizeIterator<Item = &'a str>) -> Self {
+        let mut filter = Self::new(items.len());
+        for item in items {
+            filter.insert(item);
+        }
+        filter
+    }
+
+    /// Add an item.
+    pub fn insert(&mut self, item: &str) {
+        for bit in self.bit_indexes(item) {
+            self.bits[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    /// Whether the item may be in the set (false = definitely not).
+    pub fn contains(&self, item: &str) -> bool {
+        self.bit_indexes(item)
+            .all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    /// Check a filter received from the network is within limits.
+    pub fn is_well_formed(&self) -> bool {
+        !self.bits.is_empty()
+            && self.bits.len() <= MAX_BLOOM_BYTES
+            && (1..=MAX_BLOOM_HASHES).contains(&self.num_hashes)
+    }
+
+    /// Size of the bit array in bytes.
+    pub fn byte_len(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Bit positions for an item (Kirsch–Mitzenmacher double hashing).
+    fn bit_indexes(&self, item: &str) -> impl Iterator<Item = usize> {
+        let digest = Sha256::digest(item.as_bytes());
+        let h1 = u64::from_le_bytes(digest[..8].try_into().expect("8 bytes"));
+        let h2 = u64::from_le_bytes(digest[8..16].try_into().expect("8 bytes")) | 1;
+        let m = (self.bits.len() * 8) as u64;
+        (0..self.num_hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % m) as usize)
+    }
+}
+
+/// Serialize the bit array as a msgpack `bin` (one byte per byte) rather
+/// than an array of integers.
+mod as_bin {
+    use serde::de::{Deserializer, SeqAccess, Visitor};
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(bits: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(bits)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a byte array")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+                let mut out = Vec::new();
+                while let Some(b) = seq.next_element()? {
+                    out.push(b);
+                }
+                Ok(out)
+            }
+        }
+
+        d.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_false_negatives() {
+        let ids: Vec<String> = (0..1000).map(|i| format!("msg-{i}")).collect();
+        let filter = BloomFilter::from_items(ids.iter().map(String::as_str));
+        assert!(ids.iter().all(|id| filter.contains(id)));
+    }
+
+    #[test]
+    fn false_positive_rate_near_target() {
+        let ids: Vec<String> = (0..1000).map(|i| format!("msg-{i}")).collect();
+        let filter = BloomFilter::from_items(ids.iter().map(String::as_str));
+        let false_positives = (0..10_000)
+            .filter(|i| filter.contains(&format!("other-{i}")))
+            .count();
+        // Target is 0.1%; allow generous slack.
+        assert!(false_positives < 50, "{false_positives} false positives");
+    }
+
+    #[test]
+    fn empty_filter_contains_nothing() {
+        let filter = BloomFilter::new(0);
+        assert!(filter.is_well_formed());
+        assert!(!filter.contains("msg-1"));
+        assert_eq!(filter.byte_len(), MIN_BLOOM_BYTES);
+    }
+
+    #[test]
+    fn size_is_capped_and_validated() {
+        let filter = BloomFilter::new(1_000_000);
+        assert_eq!(filter.byte_len(), MAX_BLOOM_BYTES);
+        assert!(filter.is_well_formed());
+
+        let oversized = BloomFilter {
+            bits: vec![0; MAX_BLOOM_BYTES + 1],
+            num_hashes: 4,
+        };
+        assert!(!oversized.is_well_formed());
+        let no_hashes = BloomFilter {
+            bits: vec![0; 8],
+            num_hashes: 0,
+        };
+        assert!(!no_hashes.is_well_formed());
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let mut filter = BloomFilter::new(10);
+        filter.insert("msg-1");
+        let bytes = rmp_serde::to_vec(&filter).unwrap();
+        assert!(bytes.len() < filter.byte_len() + 8, "bits should encode as bin");
+        let decoded: BloomFilter = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, filter);
+        assert!(decoded.contains("msg-1"));
+    }
+}
@@ -5,10 +5,19 @@
 ///
 /// Three responsibilities:
 /// 1. Query: when a peer comes online, query the network for their pending messages
-/// 2. Replicate: spread messages to other nodes for redundancy
-/// 3. Confirm: when delivery succeeds, notify all replica holders to clean up
-use std::collections::{HashMap, HashSet};
-
+/// 2. Replicate: spread messages (or erasure-coded shards of them) to
+///    well-scored, diverse holders for redundancy
+/// 3. Deliver: hand held messages to a reconnected recipient in paced,
+///    oldest-first batches
+/// 4. Confirm: when delivery succeeds, notify all replica holders to clean up
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::backup::bloom::BloomFilter;
+use crate::backup::erasure::{self, ShardInfo};
+use crate::backup::placement::{
+    select_targets, AvailabilityHistory, ReplicaCandidate, DESIRED_REPLICAS, MIN_HOLDER_SCORE,
+};
+use crate::backup::receipt::StorageReceipt;
 use crate::backup::store::BackupStore;
 use crate::backup::types::*;
 use crate::types::NodeId;
@@ -25,6 +34,22 @@ pub struct BackupCoordinator {
     last_query_time: HashMap<NodeId, u64>,
     /// Pending replications: message_id → (target, sent_at).
     pending_replications: HashMap<String, Vec<(NodeId, u64)>>,
+    /// How often each peer has been seen online (placement input).
+    availability: AvailabilityHistory,
+    /// TTL bounds we apply to everything we hold.
+    policy: BackupPolicy,
+    /// Verified storage receipts for our entries: message_id → receipts.
+    receipts: HashMap<String, Vec<StorageReceipt>>,
+    /// Shard index handed to each holder of our sharded entries.
+    shard_assignments: HashMap<String, HashMap<NodeId, u8>>,
+    /// Shards received for messages addressed to us, awaiting k of n.
+    shard_buffers: HashMap<String, ShardBuffer>,
+    /// Messages we rebuilt (→ expiry), so late shards are ignored.
+    reassembled: HashMap<String, u64>,
+    /// Paced deliveries under way to reconnected recipients.
+    delivery_queues: HashMap<NodeId, DeliveryQueue>,
+    /// Announced backup preferences of recipients that restrict holders.
+    preferences: HashMap<NodeId, BackupPreference>,
 }
 
 struct QueryState {
@@ -32,18 +57,62 @@ struct QueryState {
     received_ids: HashSet<String>,
 }
 
+struct ShardBuffer {
+    expires_at: u64,
+    shards: Vec<(ShardInfo, Vec<u8>)>,
+}
+
+#[derive(Default)]
+struct DeliveryQueue {
+    /// Not sent yet, oldest first.
+    pending: VecDeque<String>,
+    /// Everything queued during this delivery, sent or not.
+    queued: HashSet<String>,
+}
+
+/// Most partially received sharded messages we buffer at once.
+const MAX_SHARD_BUFFERS: usize = 256;
+
+/// Most restrictive backup preferences we remember.
+const MAX_PREFERENCES: usize = 4096;
+
 impl BackupCoordinator {
     /// Create a new coordinator.
     pub fn new(local_id: NodeId) -> Self {
+        Self::with_store(local_id, BackupStore::new())
+    }
+
+    /// Create a coordinator around an existing (e.g. persistent) store.
+    pub fn with_store(local_id: NodeId, store: BackupStore) -> Self {
         Self {
             local_id,
-            store: BackupStore::new(),
+            store,
             active_queries: HashMap::new(),
             last_query_time: HashMap::new(),
             pending_replications: HashMap::new(),
+            availability: AvailabilityHistory::new(),
+            policy: BackupPolicy::default(),
+            receipts: HashMap::new(),
+            shard_assignments: HashMap::new(),
+            shard_buffers: HashMap::new(),
+            reassembled: HashMap::new(),
+            delivery_queues: HashMap::new(),
+            preferences: HashMap::new(),
         }
     }
 
+    /// Apply an operator policy's TTL bounds (its quota belongs to the store,
+    /// see [`BackupStore::with_quota`]).
+    pub fn with_policy(mut self, policy: BackupPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The policy in force.
+    pub fn policy(&self) -> &BackupPolicy {
+        &self.policy
+    }
+
     /// Access the underlying store (read-only).
     pub fn store(&self) -> &BackupStore {
         &self.store
@@ -57,6 +126,8 @@ impl BackupCoordinator {
     // ── Store operations (delegated) ─────────────────────────────────────
 
     /// Store a message for an offline recipient.
+    ///
+    /// `ttl_ms` is the sender's requested TTL, bounded by our policy.
     pub fn store_message(
         &mut self,
         message_id: String,
@@ -66,15 +137,69 @@ impl BackupCoordinator {
         now: u64,
         ttl_ms: Option<u64>,
     ) -> Vec<BackupAction> {
+        let ttl = self.policy.ttl_for(ttl_ms);
         self.store
-            .store(message_id, payload, recipient_id, sender_id, now, ttl_ms)
+            .store(message_id, payload, recipient_id, sender_id, now, Some(ttl))
+            .into_iter()
+            .map(BackupAction::Event)
+            .collect()
+    }
+
+    /// Store a group message for a member the hub couldn't reach.
+    ///
+    /// `message_id` must be unique per recipient (one entry per member).
+    pub fn store_group_message(
+        &mut self,
+        message_id: String,
+        payload: Vec<u8>,
+        recipient_id: NodeId,
+        sender_id: NodeId,
+        now: u64,
+        ttl_ms: Option<u64>,
+    ) -> Vec<BackupAction> {
+        if let Some(refused) = self.check_sender_quota(&message_id, sender_id, payload.len()) {
+            return vec![refused];
+        }
+        let ttl = self.policy.ttl_for(ttl_ms);
+        let entry = BackupEntry::new(message_id, payload, recipient_id, sender_id, now, Some(ttl))
+            .with_msg_type(crate::types::MessageType::GroupMessage);
+        self.store
+            .store_entry(entry, now)
             .into_iter()
             .map(BackupAction::Event)
             .collect()
     }
 
     /// Handle an incoming replication payload from another node.
+    ///
+    /// We keep it no longer than our policy allows, whatever the origin asked.
     pub fn handle_replication(&mut self, payload: &ReplicationPayload, from: NodeId, now: u64) -> Vec<BackupAction> {
+        let max_expiry = now.saturating_add(self.policy.max_ttl());
+        let bounded;
+        let payload = if payload.expires_at > max_expiry {
+            bounded = ReplicationPayload {
+                expires_at: max_expiry,
+                ..payload.clone()
+            };
+            &bounded
+        } else {
+            payload
+        };
+        if !self.store.has(&payload.message_id) {
+            if !self.may_hold(&payload.recipient_id, &self.local_id) {
+                tracing::debug!(
+                    "refusing backup {}: recipient {} doesn't allow us as a holder",
+                    payload.message_id,
+                    payload.recipient_id
+                );
+                return vec![];
+            }
+            if let Some(refused) =
+                self.check_sender_quota(&payload.message_id, payload.sender_id, payload.payload.len())
+            {
+                return vec![refused];
+            }
+        }
         let mut actions: Vec<BackupAction> = self.store
             .store_replica(payload, now)
             .into_iter()
@@ -96,6 +221,25 @@ impl BackupCoordinator {
         actions
     }
 
+    /// Refuse a message whose sender already fills its per-sender quota,
+    /// so no single node can take over our donated storage.
+    fn check_sender_quota(
+        &self,
+        message_id: &str,
+        sender_id: NodeId,
+        bytes: usize,
+    ) -> Option<BackupAction> {
+        let used = self.store.sender_bytes(&sender_id);
+        if used + bytes as u64 <= self.store.quota().max_bytes_per_sender {
+            return None;
+        }
+        tracing::debug!("refusing backup {message_id}: sender {sender_id} over quota ({used} bytes held)");
+        Some(BackupAction::Event(BackupEvent::SenderQuotaExceeded {
+            message_id: message_id.to_string(),
+            sender_id,
+        }))
+    }
+
     // ── Query: peer comes online ─────────────────────────────────────────
 
     /// A peer came online — query network for their pending messages.
@@ -117,20 +261,30 @@ impl BackupCoordinator {
             },
         );
 
-        // Also check our own store
-        let mut actions = vec![];
-        let local_msgs = self.store.get_for_recipient(&recipient_id);
-        if !local_msgs.is_empty() {
-            // We have messages locally — no need to query network for these
-            for msg in &local_msgs {
-                if let Some(query) = self.active_queries.get_mut(&recipient_id) {
-                    query.received_ids.insert(msg.message_id.clone());
-                }
-            }
+        // Messages we already hold don't need to come back from the network
+        let local_ids: Vec<String> = self
+            .store
+            .get_for_recipient(&recipient_id)
+            .into_iter()
+            .map(|m| m.message_id.clone())
+            .collect();
+        let known = BloomFilter::from_items(local_ids.iter().map(String::as_str));
+        if let Some(query) = self.active_queries.get_mut(&recipient_id) {
+            query.received_ids.extend(local_ids);
         }
 
-        actions.push(BackupAction::QueryPending { recipient_id });
-        actions
+        vec![BackupAction::QueryPending { recipient_id, known }]
+    }
+
+    /// Answer another node's query: our IDs for the recipient that are
+    /// not in its filter.
+    pub fn answer_query(&self, query: &BackupQueryPayload) -> Vec<String> {
+        self.store
+            .get_for_recipient(&query.recipient_id)
+            .into_iter()
+            .filter(|m| !query.known.contains(&m.message_id))
+            .map(|m| m.message_id.clone())
+            .collect()
     }
 
     /// Handle response to a pending query: another node has messages for recipient.
@@ -160,6 +314,89 @@ impl BackupCoordinator {
             .retain(|_, state| now.saturating_sub(state.started_at) < QUERY_TIMEOUT_MS);
     }
 
+    // ── Delivery ─────────────────────────────────────────────────────────
+
+    /// Recipient came online — queue everything we hold for it, oldest
+    /// first, and return the first batch. The rest follow one batch per
+    /// [`tick_deliveries`](Self::tick_deliveries) so a long absence doesn't
+    /// turn into a burst on reconnect. While a delivery is under way, only
+    /// newly held messages are appended.
+    pub fn queue_delivery(&mut self, recipient_id: NodeId, now: u64) -> Vec<BackupAction> {
+        let started = !self.delivery_queues.contains_key(&recipient_id);
+        let queue = self.delivery_queues.entry(recipient_id).or_default();
+        let mut held: Vec<&BackupEntry> = self
+            .store
+            .get_for_recipient(&recipient_id)
+            .into_iter()
+            .filter(|e| !queue.queued.contains(&e.message_id))
+            .collect();
+        held.sort_by(|a, b| {
+            a.stored_at
+                .cmp(&b.stored_at)
+                .then_with(|| a.message_id.cmp(&b.message_id))
+        });
+        for entry in held {
+            queue.queued.insert(entry.message_id.clone());
+            queue.pending.push_back(entry.message_id.clone());
+        }
+
+        if !started {
+            return Vec::new();
+        }
+        self.next_delivery_batch(recipient_id, now).into_iter().collect()
+    }
+
+    /// Next batch for every recipient with queued deliveries.
+    pub fn tick_deliveries(&mut self, now: u64) -> Vec<BackupAction> {
+        let recipients: Vec<NodeId> = self.delivery_queues.keys().copied().collect();
+        recipients
+            .into_iter()
+            .filter_map(|recipient_id| self.next_delivery_batch(recipient_id, now))
+            .collect()
+    }
+
+    /// Recipient went offline — stop handing it messages (they stay held).
+    pub fn cancel_delivery(&mut self, recipient_id: &NodeId) {
+        self.delivery_queues.remove(recipient_id);
+    }
+
+    /// Messages still waiting to be sent to `recipient_id`.
+    pub fn queued_delivery_count(&self, recipient_id: &NodeId) -> usize {
+        self.delivery_queues.get(recipient_id).map_or(0, |q| q.pending.len())
+    }
+
+    fn next_delivery_batch(&mut self, recipient_id: NodeId, now: u64) -> Option<BackupAction> {
+        let pacing = self.policy.delivery;
+        let queue = self.delivery_queues.get_mut(&recipient_id)?;
+        let mut entries = Vec::new();
+        let mut bytes = 0;
+        while entries.len() < pacing.max_batch_entries.max(1) {
+            let Some(message_id) = queue.pending.front() else { break };
+            // Delivered, expired or evicted since it was queued
+            let Some(payload) = self.store.create_replication_payload(message_id) else {
+                queue.pending.pop_front();
+                continue;
+            };
+            if !entries.is_empty() && bytes + payload.payload.len() > pacing.max_batch_bytes {
+                break;
+            }
+            queue.pending.pop_front();
+            self.store.touch(&payload.message_id, now);
+            bytes += payload.payload.len();
+            entries.push(payload);
+        }
+        if queue.pending.is_empty() {
+            self.delivery_queues.remove(&recipient_id);
+        }
+        if entries.is_empty() {
+            return None;
+        }
+        Some(BackupAction::DeliverBatch {
+            recipient_id,
+            entries,
+        })
+    }
+
     // ── Delivery confirmation ────────────────────────────────────────────
 
     /// Recipient confirmed delivery — clear backups and notify replicas.
@@ -177,6 +414,8 @@ impl BackupCoordinator {
         // Cancel pending replications
         for id in message_ids {
             self.pending_replications.remove(id);
+            self.receipts.remove(id);
+            self.shard_assignments.remove(id);
         }
 
         // Notify other replica holders
@@ -203,6 +442,44 @@ impl BackupCoordinator {
             .collect()
     }
 
+    // ── Recipient preferences ────────────────────────────────────────────
+
+    /// Record the backup preference a node announced. Allowlists beyond
+    /// [`MAX_ALLOWED_HOLDERS`] are cut short.
+    pub fn set_backup_preference(&mut self, node_id: NodeId, preference: BackupPreference) {
+        let preference = match preference {
+            BackupPreference::Open => {
+                self.preferences.remove(&node_id);
+                return;
+            }
+            BackupPreference::Allowlist(mut holders) => {
+                holders.truncate(MAX_ALLOWED_HOLDERS);
+                BackupPreference::Allowlist(holders)
+            }
+            other => other,
+        };
+        if self.preferences.len() >= MAX_PREFERENCES && !self.preferences.contains_key(&node_id) {
+            return;
+        }
+        self.preferences.insert(node_id, preference);
+    }
+
+    /// Whether `recipient` lets `holder` keep its messages.
+    pub fn may_hold(&self, recipient: &NodeId, holder: &NodeId) -> bool {
+        self.preferences
+            .get(recipient)
+            .is_none_or(|preference| preference.permits(holder))
+    }
+
+    /// Candidates `recipient` doesn't allow as holders.
+    fn barred_holders(&self, recipient: &NodeId, candidates: &[ReplicaCandidate]) -> Vec<NodeId> {
+        candidates
+            .iter()
+            .map(|c| c.node_id)
+            .filter(|n| !self.may_hold(recipient, n))
+            .collect()
+    }
+
     // ── Replication ──────────────────────────────────────────────────────
 
     /// Initiate replication of a message to a target node.
@@ -226,6 +503,10 @@ impl BackupCoordinator {
             return vec![];
         }
 
+        if !self.may_hold(&entry.recipient_id, &target) {
+            return vec![];
+        }
+
         let Some(payload) = self.store.create_replication_payload(message_id) else {
             return vec![];
         };
@@ -239,6 +520,334 @@ impl BackupCoordinator {
         vec![BackupAction::Replicate { target, payload }]
     }
 
+    /// Our own message keeps failing to reach its recipient: push it to
+    /// the best [`DESIRED_REPLICAS`] of `relays` (best first) rather than
+    /// waiting for viability to drop. Relays already holding it, or with a
+    /// replication in flight, are skipped, so escalating again is harmless.
+    pub fn escalate(&mut self, message_id: &str, relays: &[NodeId], now: u64) -> Vec<BackupAction> {
+        let Some(entry) = self.store.get(message_id) else {
+            return vec![];
+        };
+        if entry.sender_id != self.local_id {
+            return vec![];
+        }
+        let mut busy: HashSet<NodeId> = entry.replicated_to.clone();
+        if let Some(pending) = self.pending_replications.get(message_id) {
+            busy.extend(pending.iter().map(|(target, _)| *target));
+        }
+        let targets: Vec<NodeId> = relays
+            .iter()
+            .filter(|r| **r != entry.recipient_id && **r != self.local_id)
+            .take(DESIRED_REPLICAS)
+            .filter(|r| !busy.contains(r))
+            .copied()
+            .collect();
+
+        let mut actions = vec![];
+        for target in targets {
+            actions.extend(self.replicate_to(message_id, target, now));
+        }
+        actions
+    }
+
+    /// Record whether a peer is online right now (feeds placement scoring).
+    pub fn record_availability(&mut self, node_id: NodeId, online: bool) {
+        self.availability.record(node_id, online);
+    }
+
+    /// Historical availability of a peer (0–100).
+    pub fn availability(&self, node_id: &NodeId) -> u8 {
+        self.availability.get(node_id)
+    }
+
+    /// Choose replica holders for messages that need them.
+    ///
+    /// A message needs holders when its viability is low, or when one of its
+    /// holders scores below [`MIN_HOLDER_SCORE`] — that holder is forgotten
+    /// and replaced. Messages are topped up to [`DESIRED_REPLICAS`] holders
+    /// (confirmed + pending), picked by [`select_targets`].
+    ///
+    /// With erasure coding on, large messages instead get one holder per
+    /// shard, and a lost holder is replaced by a holder of the same shard.
+    pub fn plan_replication(
+        &mut self,
+        candidates: &[ReplicaCandidate],
+        now: u64,
+    ) -> Vec<BackupAction> {
+        let by_id: HashMap<NodeId, &ReplicaCandidate> =
+            candidates.iter().map(|c| (c.node_id, c)).collect();
+        let needs: HashSet<String> = self
+            .store
+            .check_viability()
+            .into_iter()
+            .filter_map(|event| match event {
+                BackupEvent::ReplicationNeeded { message_id, .. } => Some(message_id),
+                _ => None,
+            })
+            .collect();
+
+        let mut actions = vec![];
+        for message_id in self.store.message_ids() {
+            let Some(entry) = self.store.get(&message_id) else {
+                continue;
+            };
+            let degraded: Vec<NodeId> = entry
+                .replicated_to
+                .iter()
+                .filter(|n| by_id.get(n).is_some_and(|c| c.score() < MIN_HOLDER_SCORE))
+                .copied()
+                .collect();
+            if degraded.is_empty() && !needs.contains(&message_id) {
+                continue;
+            }
+            for node in &degraded {
+                self.store.forget_replica(&message_id, node);
+                if let Some(assigned) = self.shard_assignments.get_mut(&message_id) {
+                    assigned.remove(node);
+                }
+            }
+
+            let Some(entry) = self.store.get(&message_id) else {
+                continue;
+            };
+            if !degraded.is_empty() {
+                actions.push(BackupAction::Event(BackupEvent::ReplicationDegraded {
+                    message_id: message_id.clone(),
+                    recipient_id: entry.recipient_id,
+                    remaining_holders: self.other_holders(entry),
+                }));
+            }
+            let mut holders: HashSet<NodeId> = entry.replicated_to.clone();
+            if let Some(pending) = self.pending_replications.get(&message_id) {
+                holders.extend(pending.iter().map(|(target, _)| *target));
+            }
+            holders.remove(&self.local_id);
+
+            let erasure = self.erasure_for(entry);
+            let missing_shards: Vec<u8> = match erasure {
+                Some(cfg) => {
+                    let assigned = self.shard_assignments.get(&message_id);
+                    (0..cfg.total_shards() as u8)
+                        .filter(|index| {
+                            !assigned.is_some_and(|a| {
+                                a.iter().any(|(node, i)| i == index && holders.contains(node))
+                            })
+                        })
+                        .collect()
+                }
+                None => Vec::new(),
+            };
+            let wanted = match erasure {
+                Some(_) => missing_shards.len(),
+                None => DESIRED_REPLICAS.saturating_sub(holders.len()),
+            };
+            if wanted == 0 {
+                continue;
+            }
+
+            let used_clusters: HashSet<String> = holders
+                .iter()
+                .filter_map(|n| by_id.get(n).and_then(|c| c.cluster.clone()))
+                .collect();
+            let mut exclude = holders.clone();
+            exclude.extend(degraded);
+            exclude.extend([self.local_id, entry.recipient_id, entry.sender_id]);
+            exclude.extend(self.barred_holders(&entry.recipient_id, candidates));
+
+            let targets = select_targets(candidates, &exclude, &used_clusters, wanted);
+            match erasure {
+                Some(cfg) => {
+                    let assignment: Vec<(NodeId, u8)> =
+                        targets.into_iter().zip(missing_shards).collect();
+                    actions.extend(self.replicate_shards(&message_id, cfg, &assignment, now));
+                }
+                None => {
+                    for target in targets {
+                        actions.extend(self.replicate_to(&message_id, target, now));
+                    }
+                }
+            }
+        }
+        actions
+    }
+
+    /// We're shutting down cleanly: pass each entry we hold to one more
+    /// online holder, so its replica count doesn't silently drop until the
+    /// viability check notices. Shards are handed over as the same shard.
+    ///
+    /// Each entry goes to the least-loaded of its best-placed candidates, so
+    /// our whole store doesn't land on a single node.
+    pub fn hand_off(&mut self, candidates: &[ReplicaCandidate], now: u64) -> Vec<BackupAction> {
+        let by_id: HashMap<NodeId, &ReplicaCandidate> =
+            candidates.iter().map(|c| (c.node_id, c)).collect();
+        let mut load: HashMap<NodeId, usize> = HashMap::new();
+        let mut actions = vec![];
+        for message_id in self.store.message_ids() {
+            let Some(entry) = self.store.get(&message_id) else {
+                continue;
+            };
+            if entry.recipient_id == self.local_id || entry.is_expired(now) {
+                continue;
+            }
+            let used_clusters: HashSet<String> = entry
+                .replicated_to
+                .iter()
+                .filter_map(|n| by_id.get(n).and_then(|c| c.cluster.clone()))
+                .collect();
+            let mut exclude = entry.replicated_to.clone();
+            exclude.extend([self.local_id, entry.recipient_id, entry.sender_id]);
+            exclude.extend(self.barred_holders(&entry.recipient_id, candidates));
+
+            let Some(target) = select_targets(candidates, &exclude, &used_clusters, DESIRED_REPLICAS)
+                .into_iter()
+                .min_by_key(|n| load.get(n).copied().unwrap_or(0))
+            else {
+                continue;
+            };
+            let Some(payload) = self.store.create_replication_payload(&message_id) else {
+                continue;
+            };
+            *load.entry(target).or_default() += 1;
+            actions.push(BackupAction::Handoff { target, payload });
+        }
+        actions
+    }
+
+    /// A departing holder handed us one of its entries. Held like any
+    /// replica, except the departing node no longer counts as a holder.
+    pub fn handle_handoff(&mut self, payload: &ReplicationPayload, from: NodeId, now: u64) -> Vec<BackupAction> {
+        let actions = self.handle_replication(payload, from, now);
+        self.store.forget_replica(&payload.message_id, &from);
+        actions
+    }
+
+    /// Erasure settings to use for an entry: our policy's, if the entry is a
+    /// whole message large enough to be worth sharding.
+    fn erasure_for(&self, entry: &BackupEntry) -> Option<ErasureConfig> {
+        self.policy
+            .erasure
+            .filter(|cfg| entry.shard.is_none() && entry.size_bytes() >= cfg.min_payload_bytes)
+    }
+
+    /// Send shard `index` of a message to each `(target, index)`.
+    fn replicate_shards(
+        &mut self,
+        message_id: &str,
+        cfg: ErasureConfig,
+        assignment: &[(NodeId, u8)],
+        now: u64,
+    ) -> Vec<BackupAction> {
+        let Some(base) = self.store.create_replication_payload(message_id) else {
+            return vec![];
+        };
+        let Some(shards) =
+            erasure::encode(&base.payload, cfg.data_shards as usize, cfg.total_shards())
+        else {
+            tracing::warn!("invalid erasure settings {cfg:?}, not sharding {message_id}");
+            return vec![];
+        };
+
+        let mut actions = vec![];
+        for &(target, index) in assignment {
+            let Some((info, bytes)) = shards.get(index as usize) else {
+                continue;
+            };
+            self.pending_replications
+                .entry(message_id.to_string())
+                .or_default()
+                .push((target, now));
+            self.shard_assignments
+                .entry(message_id.to_string())
+                .or_default()
+                .insert(target, index);
+            actions.push(BackupAction::Replicate {
+                target,
+                payload: ReplicationPayload {
+                    payload: bytes.clone(),
+                    shard: Some(*info),
+                    ..base.clone()
+                },
+            });
+        }
+        actions
+    }
+
+    /// Shard index assigned to a holder of one of our sharded entries.
+    pub fn shard_assignment(&self, message_id: &str, holder: &NodeId) -> Option<u8> {
+        self.shard_assignments.get(message_id)?.get(holder).copied()
+    }
+
+    /// Buffer a shard of a message addressed to us. Once `data_shards`
+    /// distinct shards are in, returns the rebuilt whole-message payload.
+    pub fn collect_shard(&mut self, payload: &ReplicationPayload, now: u64) -> Option<ReplicationPayload> {
+        let info = payload.shard.filter(ShardInfo::is_valid)?;
+        if now >= payload.expires_at || self.reassembled.contains_key(&payload.message_id) {
+            return None;
+        }
+        if !self.shard_buffers.contains_key(&payload.message_id)
+            && self.shard_buffers.len() >= MAX_SHARD_BUFFERS
+        {
+            tracing::debug!("shard buffer full, dropping shard of {}", payload.message_id);
+            return None;
+        }
+
+        let buffer = self
+            .shard_buffers
+            .entry(payload.message_id.clone())
+            .or_insert_with(|| ShardBuffer {
+                expires_at: payload.expires_at,
+                shards: Vec::new(),
+            });
+        if buffer.shards.iter().any(|(i, _)| i.index == info.index) {
+            return None;
+        }
+        buffer.shards.push((info, payload.payload.clone()));
+        if buffer.shards.len() < info.data_shards as usize {
+            return None;
+        }
+
+        let buffer = self.shard_buffers.remove(&payload.message_id)?;
+        let Some(rebuilt) = erasure::decode(&buffer.shards) else {
+            tracing::debug!("inconsistent shards for {}, discarding", payload.message_id);
+            return None;
+        };
+        self.reassembled
+            .insert(payload.message_id.clone(), payload.expires_at);
+        Some(ReplicationPayload {
+            payload: rebuilt,
+            shard: None,
+            ..payload.clone()
+        })
+    }
+
+    /// Handle a holder's storage receipt (carried by the replication ACK).
+    ///
+    /// Only a receipt signed by the node that sent it, for a message we
+    /// hold, counts: the holder is then recorded and the receipt kept.
+    pub fn handle_storage_receipt(
+        &mut self,
+        receipt: StorageReceipt,
+        from: NodeId,
+    ) -> Vec<BackupAction> {
+        if receipt.holder_id != from || !receipt.verify_signature() {
+            tracing::debug!("ignoring invalid storage receipt from {from}");
+            return vec![];
+        }
+        if !self.store.has(&receipt.message_id) {
+            return vec![];
+        }
+        let message_id = receipt.message_id.clone();
+        let receipts = self.receipts.entry(message_id.clone()).or_default();
+        receipts.retain(|r| r.holder_id != from);
+        receipts.push(receipt);
+        self.handle_replication_ack(&message_id, from)
+    }
+
+    /// Verified storage receipts held for one of our entries.
+    pub fn receipts(&self, message_id: &str) -> &[StorageReceipt] {
+        self.receipts.get(message_id).map_or(&[], Vec::as_slice)
+    }
+
     /// Handle replication ACK — mark as replicated.
     pub fn handle_replication_ack(
         &mut self,
@@ -284,9 +893,76 @@ impl BackupCoordinator {
         }
         self.pending_replications.retain(|_, v| !v.is_empty());
 
+        // Receipts and shard assignments for entries we no longer hold
+        self.receipts.retain(|id, _| self.store.has(id));
+        self.shard_assignments.retain(|id, _| self.store.has(id));
+
+        // Unfinished or finished reassemblies past their expiry
+        self.shard_buffers.retain(|_, b| now < b.expires_at);
+        self.reassembled.retain(|_, expires_at| now < *expires_at);
+
         actions
     }
 
+    /// Integrity pass over the store: drop corrupted entries (and anything
+    /// still pending for them), then compact the backend.
+    pub fn verify_store(&mut self) -> Vec<BackupAction> {
+        let events = self.store.verify_integrity();
+        for event in &events {
+            if let BackupEvent::MessageCorrupted { message_id, .. } = event {
+                self.pending_replications.remove(message_id);
+                self.receipts.remove(message_id);
+                self.shard_assignments.remove(message_id);
+            }
+        }
+        events.into_iter().map(BackupAction::Event).collect()
+    }
+
+    // ── Observability ────────────────────────────────────────────────────
+
+    /// What we hold and how well it is replicated.
+    pub fn stats(&self) -> BackupStats {
+        let mut per_recipient: HashMap<NodeId, usize> = HashMap::new();
+        let (mut healthy, mut under_replicated, mut unreplicated) = (0, 0, 0);
+        for message_id in self.store.message_ids() {
+            let Some(entry) = self.store.get(&message_id) else {
+                continue;
+            };
+            *per_recipient.entry(entry.recipient_id).or_default() += 1;
+            let wanted = self
+                .erasure_for(entry)
+                .map_or(DESIRED_REPLICAS, |cfg| cfg.total_shards());
+            match self.other_holders(entry) {
+                0 => unreplicated += 1,
+                n if n < wanted => under_replicated += 1,
+                _ => healthy += 1,
+            }
+        }
+        let mut per_recipient: Vec<(NodeId, usize)> = per_recipient.into_iter().collect();
+        per_recipient.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.to_string().cmp(&b.0.to_string())));
+
+        BackupStats {
+            entries: self.store.message_count(),
+            bytes: self.store.total_bytes(),
+            quota: self.store.quota(),
+            per_recipient,
+            healthy,
+            under_replicated,
+            unreplicated,
+            pending_replications: self.pending_replications.values().map(Vec::len).sum(),
+            queued_deliveries: self.delivery_queues.values().map(|q| q.pending.len()).sum(),
+        }
+    }
+
+    /// Holders of an entry other than ourselves.
+    fn other_holders(&self, entry: &BackupEntry) -> usize {
+        entry
+            .replicated_to
+            .iter()
+            .filter(|n| **n != self.local_id)
+            .count()
+    }
+
     /// Number of active queries.
     pub fn active_query_count(&self) -> usize {
         self.active_queries.len()
@@ -367,6 +1043,351 @@ mod tests {
         assert_eq!(new[0], "msg-3");
     }
 
+    #[test]
+    fn answer_query_skips_ids_in_filter() {
+        let (mut holder, _local, alice, bob) = setup();
+        let mut querier = BackupCoordinator::new(node_id(3));
+        let now = 10_000u64;
+
+        for id in ["msg-1", "msg-2", "msg-3"] {
+            holder.store_message(id.into(), vec![], alice, bob, now, None);
+        }
+        querier.store_message("msg-2".into(), vec![], alice, bob, now, None);
+
+        let actions = querier.query_pending(alice, now);
+        let BackupAction::QueryPending { recipient_id, known } = &actions[0] else {
+            panic!("expected QueryPending");
+        };
+        assert!(known.contains("msg-2"));
+
+        let query = BackupQueryPayload {
+            recipient_id: *recipient_id,
+            known: known.clone(),
+        };
+        let mut missing = holder.answer_query(&query);
+        missing.sort();
+        assert_eq!(missing, vec!["msg-1".to_string(), "msg-3".to_string()]);
+
+        // msg-2 was already recorded locally, so only the rest are new
+        let new = querier.handle_query_response(&alice, &missing, now);
+        assert_eq!(new.len(), 2);
+    }
+
+    fn candidate(node: NodeId, quality: u8) -> ReplicaCandidate {
+        ReplicaCandidate {
+            node_id: node,
+            factors: HostFactors {
+                stability: quality,
+                bandwidth: quality,
+                contribution: quality,
+            },
+            is_relay: false,
+            is_storage: false,
+            online: true,
+            availability: quality,
+            cluster: None,
+        }
+    }
+
+    fn replicate_targets(actions: &[BackupAction]) -> Vec<NodeId> {
+        actions
+            .iter()
+            .filter_map(|a| match a {
+                BackupAction::Replicate { target, .. } => Some(*target),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plan_replication_picks_best_holders_when_viability_low() {
+        let (mut coord, _local, alice, bob) = setup();
+        let now = 10_000u64;
+        coord.store_message("msg-1".into(), vec![], alice, bob, now, None);
+
+        let candidates: Vec<ReplicaCandidate> = (3..8)
+            .map(|seed| candidate(node_id(seed), 40 + seed * 5))
+            .chain([candidate(alice, 100), candidate(bob, 100)])
+            .collect();
+
+        // Healthy host: nothing to do
+        assert!(coord.plan_replication(&candidates, now).is_empty());
+
+        coord.store_mut().update_host_factors(HostFactors {
+            stability: 0,
+            bandwidth: 0,
+            contribution: 0,
+        });
+        let mut targets = replicate_targets(&coord.plan_replication(&candidates, now));
+        targets.sort_by_key(|n| n.to_string());
+        let mut expected = vec![node_id(7), node_id(6), node_id(5)];
+        expected.sort_by_key(|n| n.to_string());
+        assert_eq!(targets, expected, "top scorers, never sender/recipient");
+
+        // Pending replications count as holders — no duplicate sends
+        assert!(replicate_targets(&coord.plan_replication(&candidates, now)).is_empty());
+    }
+
+    #[test]
+    fn recipient_preference_limits_holders() {
+        let (mut coord, local, alice, bob) = setup();
+        let now = 10_000u64;
+        coord.store_message("msg-1".into(), vec![], alice, bob, now, None);
+        coord.store_mut().update_host_factors(HostFactors {
+            stability: 0,
+            bandwidth: 0,
+            contribution: 0,
+        });
+        let candidates: Vec<ReplicaCandidate> =
+            (3..8).map(|seed| candidate(node_id(seed), 90)).collect();
+
+        // Opted out: nobody is picked, not even by hand
+        coord.set_backup_preference(alice, BackupPreference::OptOut);
+        assert!(coord.plan_replication(&candidates, now).is_empty());
+        assert!(coord.replicate_to("msg-1", node_id(3), now).is_empty());
+
+        // Allowlist: only listed holders
+        coord.set_backup_preference(alice, BackupPreference::Allowlist(vec![node_id(4)]));
+        assert_eq!(replicate_targets(&coord.plan_replication(&candidates, now)), vec![node_id(4)]);
+
+        // We aren't on bob's allowlist: his messages are refused
+        coord.set_backup_preference(bob, BackupPreference::Allowlist(vec![node_id(9)]));
+        let payload = ReplicationPayload {
+            message_id: "for-bob".into(),
+            payload: vec![1],
+            recipient_id: bob,
+            sender_id: alice,
+            expires_at: now + DEFAULT_TTL_MS,
+            viability_score: 50,
+            replicated_to: vec![],
+            msg_type: crate::types::MessageType::Chat,
+            shard: None,
+        };
+        assert!(coord.handle_replication(&payload, alice, now).is_empty());
+        assert!(!coord.store().has("for-bob"));
+
+        coord.set_backup_preference(bob, BackupPreference::Open);
+        assert!(coord.may_hold(&bob, &local));
+        coord.handle_replication(&payload, alice, now);
+        assert!(coord.store().has("for-bob"));
+    }
+
+    #[test]
+    fn plan_replication_replaces_degraded_holder() {
+        let (mut coord, _local, alice, bob) = setup();
+        let now = 10_000u64;
+        coord.store_message("msg-1".into(), vec![], alice, bob, now, None);
+        let (h1, h2, h3, spare) = (node_id(3), node_id(4), node_id(5), node_id(6));
+        for h in [h1, h2, h3] {
+            coord.handle_replication_ack("msg-1", h);
+        }
+
+        let mut candidates = vec![
+            candidate(h1, 80),
+            candidate(h2, 80),
+            candidate(h3, 80),
+            candidate(spare, 70),
+        ];
+        assert!(coord.plan_replication(&candidates, now).is_empty());
+
+        // h3 keeps dropping off the network
+        candidates[2] = candidate(h3, 10);
+        let actions = coord.plan_replication(&candidates, now);
+        assert_eq!(replicate_targets(&actions), vec![spare]);
+        assert!(!coord.store().get("msg-1").unwrap().replicated_to.contains(&h3));
+        assert!(actions.iter().any(|a| matches!(a,
+            BackupAction::Event(BackupEvent::ReplicationDegraded { remaining_holders: 2, .. }))));
+    }
+
+    #[test]
+    fn stats_report_holdings_and_health() {
+        let (mut coord, local, alice, bob) = setup();
+        let now = 10_000u64;
+        let carol = node_id(7);
+        coord.store_message("a1".into(), vec![0; 10], alice, bob, now, None);
+        coord.store_message("a2".into(), vec![0; 20], alice, bob, now, None);
+        coord.store_message("c1".into(), vec![0; 5], carol, bob, now, None);
+        for h in [node_id(3), node_id(4), node_id(5)] {
+            coord.handle_replication_ack("a1", h);
+        }
+        // We count ourselves as a holder of replicas; stats don't
+        coord.store_mut().record_replication("a2", local);
+        coord.store_mut().record_replication("a2", node_id(3));
+        coord.replicate_to("c1", node_id(4), now);
+
+        let stats = coord.stats();
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.bytes, 35);
+        assert_eq!(stats.quota, BackupQuota::default());
+        assert_eq!(stats.per_recipient, vec![(alice, 2), (carol, 1)]);
+        assert_eq!((stats.healthy, stats.under_replicated, stats.unreplicated), (1, 1, 1));
+        assert_eq!(stats.pending_replications, 1);
+        assert_eq!(stats.queued_deliveries, 0);
+    }
+
+    #[test]
+    fn escalation_targets_best_relays_for_own_messages() {
+        let (mut coord, local, alice, bob) = setup();
+        let now = 10_000u64;
+        let (r1, r2, r3, r4) = (node_id(3), node_id(4), node_id(5), node_id(6));
+        coord.store_message("mine".into(), vec![1], alice, local, now, None);
+        coord.store_message("theirs".into(), vec![1], alice, bob, now, None);
+        coord.handle_replication_ack("mine", r2);
+
+        // Only the best three count; r2 already holds it
+        let actions = coord.escalate("mine", &[r1, r2, alice, r3, r4], now);
+        assert_eq!(replicate_targets(&actions), vec![r1, r3]);
+        assert!(coord.escalate("mine", &[r1, r2, r3, r4], now).is_empty());
+
+        // Only our own messages are escalated
+        assert!(coord.escalate("theirs", &[r1], now).is_empty());
+    }
+
+    #[test]
+    fn shutdown_hands_entries_to_new_holders() {
+        let (mut coord, local, alice, bob) = setup();
+        let (h1, h2, h3) = (node_id(10), node_id(11), node_id(12));
+        let now = 10_000u64;
+        for id in ["m1", "m2", "m3", "m4"] {
+            coord.store_message(id.into(), vec![1], alice, bob, now, None);
+            coord.store_mut().record_replication(id, h1);
+        }
+        coord.store_message("old".into(), vec![1], alice, bob, now, Some(1_000));
+
+        // h1 already holds everything; alice and bob are recipient and origin
+        let candidates = [
+            candidate(h1, 90),
+            candidate(h2, 80),
+            candidate(h3, 70),
+            candidate(alice, 90),
+            candidate(bob, 90),
+            candidate(local, 90),
+        ];
+        let actions = coord.hand_off(&candidates, now + 5_000);
+        let mut per_target: HashMap<NodeId, Vec<String>> = HashMap::new();
+        for action in &actions {
+            let BackupAction::Handoff { target, payload } = action else {
+                panic!("expected only handoffs, got {action:?}");
+            };
+            per_target.entry(*target).or_default().push(payload.message_id.clone());
+        }
+        // Expired entries aren't passed on; the rest are spread out
+        assert_eq!(actions.len(), 4);
+        assert_eq!(per_target.len(), 2);
+        assert_eq!(per_target[&h2].len(), 2);
+        assert_eq!(per_target[&h3].len(), 2);
+    }
+
+    #[test]
+    fn handoff_replaces_departing_holder() {
+        let (mut coord, _local, alice, bob) = setup();
+        let departing = node_id(10);
+        let payload = ReplicationPayload {
+            message_id: "msg-1".into(),
+            payload: vec![1, 2, 3],
+            recipient_id: alice,
+            sender_id: bob,
+            expires_at: 10_000 + DEFAULT_TTL_MS,
+            viability_score: 50,
+            replicated_to: vec![departing],
+            msg_type: crate::types::MessageType::Chat,
+            shard: None,
+        };
+        coord.handle_handoff(&payload, departing, 10_000);
+        let entry = coord.store().get("msg-1").unwrap();
+        assert!(!entry.replicated_to.contains(&departing));
+    }
+
+    #[test]
+    fn erasure_shards_large_messages_across_holders() {
+        let (coord, _local, alice, bob) = setup();
+        let mut coord = coord.with_policy(BackupPolicy {
+            erasure: Some(ErasureConfig {
+                min_payload_bytes: 1024,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let now = 10_000u64;
+        let big: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+        coord.store_message("big".into(), big.clone(), alice, bob, now, None);
+        coord.store_message("small".into(), vec![1; 100], alice, bob, now, None);
+        coord.store_mut().update_host_factors(HostFactors {
+            stability: 0,
+            bandwidth: 0,
+            contribution: 0,
+        });
+        let candidates: Vec<ReplicaCandidate> =
+            (3..10).map(|seed| candidate(node_id(seed), 80)).collect();
+
+        let actions = coord.plan_replication(&candidates, now);
+        let shards: Vec<(NodeId, ReplicationPayload)> = actions
+            .iter()
+            .filter_map(|a| match a {
+                BackupAction::Replicate { target, payload } if payload.message_id == "big" => {
+                    Some((*target, payload.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        // One holder per shard, each holding a third of the payload
+        assert_eq!(shards.len(), 5);
+        let mut indexes: Vec<u8> = shards.iter().map(|(_, p)| p.shard.unwrap().index).collect();
+        indexes.sort();
+        assert_eq!(indexes, vec![0, 1, 2, 3, 4]);
+        assert!(shards.iter().all(|(_, p)| p.payload.len() == 1000));
+        for (target, p) in &shards {
+            assert_eq!(coord.shard_assignment("big", target), Some(p.shard.unwrap().index));
+        }
+        // Small messages are still copied whole
+        let whole = actions.iter().filter(|a| matches!(a,
+            BackupAction::Replicate { payload, .. } if payload.message_id == "small" && payload.shard.is_none()));
+        assert_eq!(whole.count(), DESIRED_REPLICAS);
+
+        // The recipient rebuilds from any three
+        let mut recipient = BackupCoordinator::new(alice);
+        assert!(recipient.collect_shard(&shards[4].1, now).is_none());
+        assert!(recipient.collect_shard(&shards[1].1, now).is_none());
+        assert!(recipient.collect_shard(&shards[1].1, now).is_none()); // duplicate
+        let rebuilt = recipient.collect_shard(&shards[2].1, now).unwrap();
+        assert_eq!(rebuilt.payload, big);
+        assert!(rebuilt.shard.is_none());
+        // Stragglers after reassembly are ignored
+        assert!(recipient.collect_shard(&shards[0].1, now).is_none());
+        assert!(recipient.collect_shard(&shards[3].1, now).is_none());
+    }
+
+    #[test]
+    fn lost_shard_holder_replaced_with_same_shard() {
+        let (coord, _local, alice, bob) = setup();
+        let mut coord = coord.with_policy(BackupPolicy {
+            erasure: Some(ErasureConfig {
+                min_payload_bytes: 0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let now = 10_000u64;
+        coord.store_message("msg-1".into(), vec![7; 300], alice, bob, now, None);
+        coord.store_mut().update_viability("msg-1", 20);
+        let mut candidates: Vec<ReplicaCandidate> =
+            (3..8).map(|seed| candidate(node_id(seed), 80)).collect();
+
+        for target in replicate_targets(&coord.plan_replication(&candidates, now)) {
+            coord.handle_replication_ack("msg-1", target);
+        }
+        assert!(coord.plan_replication(&candidates, now).is_empty());
+
+        let lost = node_id(5);
+        let lost_index = coord.shard_assignment("msg-1", &lost).unwrap();
+        candidates[2] = candidate(lost, 10);
+        candidates.push(candidate(node_id(8), 70));
+        let actions = coord.plan_replication(&candidates, now);
+        assert!(matches!(&actions[..],
+            [BackupAction::Event(BackupEvent::ReplicationDegraded { .. }), BackupAction::Replicate { target, payload }]
+                if *target == node_id(8) && payload.shard.unwrap().index == lost_index));
+    }
+
     #[test]
     fn confirm_delivery_clears_store() {
         let (mut coord, _local, alice, bob) = setup();
@@ -387,6 +1408,95 @@ mod tests {
         assert!(coord.store().has("msg-2"));
     }
 
+    fn batch_ids(actions: &[BackupAction]) -> Vec<Vec<String>> {
+        actions
+            .iter()
+            .filter_map(|a| match a {
+                BackupAction::DeliverBatch { entries, .. } => {
+                    Some(entries.iter().map(|e| e.message_id.clone()).collect())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reconnect_delivery_is_batched_oldest_first() {
+        let (coord, _local, alice, bob) = setup();
+        let mut coord = coord.with_policy(BackupPolicy {
+            delivery: DeliveryPacing {
+                max_batch_entries: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        for (id, stored_at) in [("m3", 3_000), ("m1", 1_000), ("m5", 5_000), ("m2", 2_000), ("m4", 4_000)] {
+            coord.store_message(id.into(), vec![1], alice, bob, stored_at, None);
+        }
+
+        // First batch goes out at once, the rest wait for delivery ticks
+        let actions = coord.queue_delivery(alice, 10_000);
+        assert_eq!(batch_ids(&actions), vec![vec!["m1", "m2"]]);
+        assert_eq!(coord.queued_delivery_count(&alice), 3);
+
+        // A repeated "online" mid-delivery only appends newly held messages
+        assert!(coord.queue_delivery(alice, 10_500).is_empty());
+        coord.store_message("m6".into(), vec![1], alice, bob, 6_000, None);
+        assert!(coord.queue_delivery(alice, 10_600).is_empty());
+        assert_eq!(coord.queued_delivery_count(&alice), 4);
+
+        // Delivered in the meantime: skipped
+        coord.handle_delivery_confirmation(&["m3".into()]);
+        assert_eq!(batch_ids(&coord.tick_deliveries(11_000)), vec![vec!["m4", "m5"]]);
+        assert_eq!(batch_ids(&coord.tick_deliveries(12_000)), vec![vec!["m6"]]);
+        assert_eq!(coord.queued_delivery_count(&alice), 0);
+        assert!(coord.tick_deliveries(13_000).is_empty());
+    }
+
+    #[test]
+    fn delivery_batches_respect_byte_budget() {
+        let (coord, _local, alice, bob) = setup();
+        let mut coord = coord.with_policy(BackupPolicy {
+            delivery: DeliveryPacing {
+                max_batch_entries: 10,
+                max_batch_bytes: 100,
+            },
+            ..Default::default()
+        });
+        coord.store_message("big".into(), vec![0; 300], alice, bob, 1_000, None);
+        coord.store_message("a".into(), vec![0; 60], alice, bob, 2_000, None);
+        coord.store_message("b".into(), vec![0; 30], alice, bob, 3_000, None);
+        coord.store_message("c".into(), vec![0; 30], alice, bob, 4_000, None);
+
+        // An oversized message still goes, alone
+        let mut batches = batch_ids(&coord.queue_delivery(alice, 10_000));
+        batches.extend(batch_ids(&coord.tick_deliveries(11_000)));
+        batches.extend(batch_ids(&coord.tick_deliveries(12_000)));
+        assert_eq!(batches, vec![vec!["big"], vec!["a", "b"], vec!["c"]]);
+    }
+
+    #[test]
+    fn cancelled_delivery_keeps_messages_held() {
+        let (coord, _local, alice, bob) = setup();
+        let mut coord = coord.with_policy(BackupPolicy {
+            delivery: DeliveryPacing {
+                max_batch_entries: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        coord.store_message("m1".into(), vec![1], alice, bob, 1_000, None);
+        coord.store_message("m2".into(), vec![1], alice, bob, 2_000, None);
+
+        coord.queue_delivery(alice, 10_000);
+        coord.cancel_delivery(&alice);
+        assert!(coord.tick_deliveries(11_000).is_empty());
+        assert!(coord.store().has("m2"));
+
+        // Back online: starts over from the oldest still held
+        assert_eq!(batch_ids(&coord.queue_delivery(alice, 12_000)), vec![vec!["m1"]]);
+    }
+
     #[test]
     fn handle_delivery_confirmation_from_network() {
         let (mut coord, _local, alice, bob) = setup();
@@ -453,6 +1563,8 @@ mod tests {
             expires_at: now + 60_000,
             viability_score: 75,
             replicated_to: vec![],
+            msg_type: crate::types::MessageType::Chat,
+            shard: None,
         };
 
         let from = node_id(5);
@@ -470,6 +1582,83 @@ mod tests {
         assert!(replicated >= 1);
     }
 
+    #[test]
+    fn policy_bounds_stored_and_replicated_ttl() {
+        let (coord, _local, alice, bob) = setup();
+        let mut coord = coord.with_policy(BackupPolicy {
+            default_ttl_ms: 60_000,
+            max_ttl_ms: 120_000,
+            ..Default::default()
+        });
+        let now = 10_000u64;
+
+        coord.store_message("default".into(), vec![], alice, bob, now, None);
+        coord.store_message("short".into(), vec![], alice, bob, now, Some(5_000));
+        coord.store_message("long".into(), vec![], alice, bob, now, Some(MAX_TTL_MS));
+        assert_eq!(coord.store().get("default").unwrap().expires_at, now + 60_000);
+        assert_eq!(coord.store().get("short").unwrap().expires_at, now + 5_000);
+        assert_eq!(coord.store().get("long").unwrap().expires_at, now + 120_000);
+
+        // A replica asking for longer than we donate is cut short
+        let payload = ReplicationPayload {
+            message_id: "replica".into(),
+            payload: vec![42],
+            recipient_id: alice,
+            sender_id: bob,
+            expires_at: now + MAX_TTL_MS,
+            viability_score: 75,
+            replicated_to: vec![],
+            msg_type: crate::types::MessageType::Chat,
+            shard: None,
+        };
+        coord.handle_replication(&payload, node_id(5), now);
+        assert_eq!(coord.store().get("replica").unwrap().expires_at, now + 120_000);
+    }
+
+    #[test]
+    fn sender_quota_limits_others_not_ourselves() {
+        let local = node_id(0);
+        let alice = node_id(1);
+        let spammer = node_id(2);
+        let store = BackupStore::with_quota(BackupQuota {
+            max_bytes_per_sender: 8,
+            ..Default::default()
+        });
+        let mut coord = BackupCoordinator::with_store(local, store);
+        let now = 10_000u64;
+        let replica = |id: &str| ReplicationPayload {
+            message_id: id.into(),
+            payload: vec![0; 5],
+            recipient_id: alice,
+            sender_id: spammer,
+            expires_at: now + 60_000,
+            viability_score: 75,
+            replicated_to: vec![],
+            msg_type: crate::types::MessageType::Chat,
+            shard: None,
+        };
+
+        coord.handle_replication(&replica("msg-1"), spammer, now);
+        assert!(coord.store().has("msg-1"));
+
+        let actions = coord.handle_replication(&replica("msg-2"), spammer, now);
+        assert!(!coord.store().has("msg-2"));
+        assert!(matches!(&actions[..],
+            [BackupAction::Event(BackupEvent::SenderQuotaExceeded { sender_id, .. })] if *sender_id == spammer));
+
+        // Re-sending a replica we already hold isn't refused
+        assert!(!coord
+            .handle_replication(&replica("msg-1"), spammer, now)
+            .iter()
+            .any(|a| matches!(a, BackupAction::Event(BackupEvent::SenderQuotaExceeded { .. }))));
+
+        // Our own outgoing backups are exempt
+        for i in 0..3 {
+            coord.store_message(format!("own-{i}"), vec![0; 5], alice, local, now, None);
+        }
+        assert_eq!(coord.store().sender_bytes(&local), 15);
+    }
+
     #[test]
     fn tick_cleans_expired() {
         let (mut coord, _local, alice, bob) = setup();
@@ -0,0 +1,258 @@
+/// Reed-Solomon erasure coding for backup payloads.
+///
+/// A payload is split into `data_shards` equal pieces and extended with
+/// `parity_shards` more; any `data_shards` of the `total` shards rebuild it.
+/// Holders then store one shard each instead of a full copy: total storage
+/// is `total / data_shards` times the payload rather than one per replica.
+///
+/// Systematic code over GF(2^8): shard `i < k` is the data itself, parity
+/// rows come from a Cauchy matrix, so every k×k submatrix is invertible.
+/// Pure functions. No I/O.
+use serde::{Deserialize, Serialize};
+
+/// Most shards a payload can be split into (field size limit).
+pub const MAX_TOTAL_SHARDS: usize = 255;
+
+/// Reducing polynomial x^8 + x^4 + x^3 + x^2 + 1.
+const GF_POLY: u16 = 0x11d;
+
+/// Log / antilog tables for GF(2^8), built at compile time.
+static GF_TABLES: ([u8; 512], [u8; 256]) = {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= GF_POLY;
+        }
+        i += 1;
+    }
+    while i < 512 {
+        exp[i] = exp[i - 255];
+        i += 1;
+    }
+    (exp, log)
+};
+
+fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    let (exp, log) = &GF_TABLES;
+    exp[log[a as usize] as usize + log[b as usize] as usize]
+}
+
+fn gf_inv(a: u8) -> u8 {
+    debug_assert!(a != 0, "zero has no inverse");
+    let (exp, log) = &GF_TABLES;
+    exp[255 - log[a as usize] as usize]
+}
+
+/// Coding parameters and where a shard sits in the set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardInfo {
+    /// This shard's position (0..total_shards).
+    pub index: u8,
+    /// Shards needed to rebuild the payload (k).
+    pub data_shards: u8,
+    /// Shards produced (n).
+    pub total_shards: u8,
+    /// Payload length before padding.
+    pub original_len: u32,
+}
+
+impl ShardInfo {
+    /// Whether the parameters are usable (1 ≤ k ≤ n, index < n).
+    pub fn is_valid(&self) -> bool {
+        self.data_shards >= 1
+            && self.data_shards <= self.total_shards
+            && self.index < self.total_shards
+    }
+}
+
+/// Row `index` of the systematic generator matrix (k columns).
+fn generator_row(index: usize, data_shards: usize) -> Vec<u8> {
+    if index < data_shards {
+        let mut row = vec![0; data_shards];
+        row[index] = 1;
+        return row;
+    }
+    // Cauchy: 1 / (x_r + y_c), x_r = index, y_c = c; disjoint so never zero.
+    (0..data_shards)
+        .map(|c| gf_inv(index as u8 ^ c as u8))
+        .collect()
+}
+
+/// Split `payload` into `total_shards` shards, any `data_shards` of which
+/// rebuild it. None if the parameters are out of range.
+pub fn encode(payload: &[u8], data_shards: usize, total_shards: usize) -> Option<Vec<(ShardInfo, Vec<u8>)>> {
+    if data_shards == 0 || data_shards > total_shards || total_shards > MAX_TOTAL_SHARDS {
+        return None;
+    }
+    let original_len = u32::try_from(payload.len()).ok()?;
+    let shard_len = payload.len().div_ceil(data_shards).max(1);
+    let mut padded = payload.to_vec();
+    padded.resize(shard_len * data_shards, 0);
+    let data: Vec<&[u8]> = padded.chunks(shard_len).collect();
+
+    let shards = (0..total_shards)
+        .map(|index| {
+            let info = ShardInfo {
+                index: index as u8,
+                data_shards: data_shards as u8,
+                total_shards: total_shards as u8,
+                original_len,
+            };
+            let bytes = if index < data_shards {
+                data[index].to_vec()
+            } else {
+                combine(&generator_row(index, data_shards), &data, shard_len)
+            };
+            (info, bytes)
+        })
+        .collect();
+    Some(shards)
+}
+
+/// Rebuild the payload from at least `data_shards` distinct, consistent
+/// shards. None if there aren't enough or they disagree on the parameters.
+pub fn decode(shards: &[(ShardInfo, Vec<u8>)]) -> Option<Vec<u8>> {
+    let (first, first_bytes) = shards.first()?;
+    let k = first.data_shards as usize;
+    let shard_len = first_bytes.len();
+    let mut chosen: Vec<&(ShardInfo, Vec<u8>)> = Vec::with_capacity(k);
+    for shard in shards {
+        let (info, bytes) = shard;
+        if !info.is_valid()
+            || info.data_shards != first.data_shards
+            || info.total_shards != first.total_shards
+            || info.original_len != first.original_len
+            || bytes.len() != shard_len
+        {
+            return None;
+        }
+        if chosen.len() < k && !chosen.iter().any(|(c, _)| c.index == info.index) {
+            chosen.push(shard);
+        }
+    }
+    if chosen.len() < k || (first.original_len as usize) > shard_len * k {
+        return None;
+    }
+
+    // Solve generator_rows · data = chosen for data.
+    let matrix: Vec<Vec<u8>> = chosen
+        .iter()
+        .map(|(info, _)| generator_row(info.index as usize, k))
+        .collect();
+    let inverse = invert(matrix)?;
+    let inputs: Vec<&[u8]> = chosen.iter().map(|(_, bytes)| bytes.as_slice()).collect();
+    let mut payload = Vec::with_capacity(shard_len * k);
+    for row in &inverse {
+        payload.extend(combine(row, &inputs, shard_len));
+    }
+    payload.truncate(first.original_len as usize);
+    Some(payload)
+}
+
+/// Linear combination Σ coeffs[i] · shards[i] over GF(2^8).
+fn combine(coeffs: &[u8], shards: &[&[u8]], shard_len: usize) -> Vec<u8> {
+    let mut out = vec![0u8; shard_len];
+    for (&coeff, shard) in coeffs.iter().zip(shards) {
+        if coeff == 0 {
+            continue;
+        }
+        for (o, &b) in out.iter_mut().zip(shard.iter()) {
+            *o ^= gf_mul(coeff, b);
+        }
+    }
+    out
+}
+
+/// Gauss–Jordan inversion of a square matrix over GF(2^8).
+fn invert(mut m: Vec<Vec<u8>>) -> Option<Vec<Vec<u8>>> {
+    let n = m.len();
+    let mut inv: Vec<Vec<u8>> = (0..n)
+        .map(|r| (0..n).map(|c| u8::from(r == c)).collect())
+        .collect();
+    for col in 0..n {
+        let pivot = (col..n).find(|&r| m[r][col] != 0)?;
+        m.swap(col, pivot);
+        inv.swap(col, pivot);
+        let scale = gf_inv(m[col][col]);
+        for c in 0..n {
+            m[col][c] = gf_mul(m[col][c], scale);
+            inv[col][c] = gf_mul(inv[col][c], scale);
+        }
+        for r in 0..n {
+            let factor = m[r][col];
+            if r == col || factor == 0 {
+                continue;
+            }
+            for c in 0..n {
+                m[r][c] ^= gf_mul(factor, m[col][c]);
+                inv[r][c] ^= gf_mul(factor, inv[col][c]);
+            }
+        }
+    }
+    Some(inv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + 3) as u8).collect()
+    }
+
+    #[test]
+    fn field_inverse_roundtrip() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn any_k_of_n_rebuilds() {
+        let data = payload(1001);
+        let shards = encode(&data, 3, 5).unwrap();
+        assert_eq!(shards.len(), 5);
+        // Each shard is a third of the payload (rounded up)
+        assert!(shards.iter().all(|(_, s)| s.len() == 334));
+
+        for a in 0..5 {
+            for b in a + 1..5 {
+                for c in b + 1..5 {
+                    let subset = vec![shards[a].clone(), shards[b].clone(), shards[c].clone()];
+                    assert_eq!(decode(&subset).unwrap(), data, "shards {a},{b},{c}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn too_few_or_mismatched_shards_fail() {
+        let data = payload(100);
+        let shards = encode(&data, 3, 5).unwrap();
+        assert!(decode(&shards[..2]).is_none());
+        // Duplicates don't count twice
+        assert!(decode(&[shards[0].clone(), shards[0].clone(), shards[1].clone()]).is_none());
+
+        let other = encode(&payload(90), 3, 5).unwrap();
+        assert!(decode(&[shards[0].clone(), shards[1].clone(), other[2].clone()]).is_none());
+    }
+
+    #[test]
+    fn bad_parameters_rejected() {
+        assert!(encode(b"x", 0, 3).is_none());
+        assert!(encode(b"x", 4, 3).is_none());
+        assert!(encode(b"x", 2, 256).is_none());
+        // Empty payloads still round-trip
+        let shards = encode(b"", 2, 3).unwrap();
+        assert_eq!(decode(&shards[1..]).unwrap(), Vec::<u8>::new());
+    }
+}
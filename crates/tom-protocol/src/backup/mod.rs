@@ -3,18 +3,38 @@
 /// Messages for offline recipients self-replicate across backup nodes
 /// and self-delete when delivered or after 24h TTL.
 ///
-/// Three layers:
-/// - **Store**: holds messages, tracks replicas, manages TTL
+/// Layers:
+/// - **Store**: holds messages, tracks replicas, manages TTL and quotas
+/// - **Persistence**: optional durable backend mirrored by the store
+/// - **Placement**: scores candidate holders and picks diverse replica targets
+/// - **Bloom**: compact "IDs I already have" filters for backup queries
+/// - **Erasure**: Reed-Solomon k-of-n sharding of large payloads
+/// - **Receipt**: holders' signed proof that they accepted a replica
 /// - **Coordinator**: orchestrates queries, replication, delivery confirmation
 /// - **Types**: data structures, constants, events
+pub mod bloom;
 pub mod coordinator;
+pub mod erasure;
+pub mod persistence;
+pub mod placement;
+pub mod receipt;
 pub mod store;
 pub mod types;
 
+pub use bloom::BloomFilter;
 pub use coordinator::BackupCoordinator;
+pub use erasure::ShardInfo;
+pub use persistence::{BackupPersistence, CompactionReport};
+#[cfg(feature = "native")]
+pub use persistence::SqliteBackupPersistence;
+pub use placement::{ReplicaCandidate, DESIRED_REPLICAS, MIN_HOLDER_SCORE};
+pub use receipt::StorageReceipt;
 pub use store::BackupStore;
 pub use types::{
-    BackupAction, BackupEntry, BackupEvent, HostFactors, ReplicationPayload, CLEANUP_INTERVAL_MS,
-    DEFAULT_TTL_MS, DELETION_THRESHOLD, MAX_REPLICAS, MAX_TTL_MS, QUERY_DEBOUNCE_MS,
-    QUERY_TIMEOUT_MS, REPLICATION_THRESHOLD, VIABILITY_CHECK_INTERVAL_MS,
+    BackupAction, BackupDeliverBatchPayload, BackupEntry, BackupEvent, BackupHandoffPayload, BackupPreference,
+    BackupQueryPayload, BackupQueryResponsePayload, BackupPolicy, BackupQuota, BackupStats,
+    DeliveryPacing, ErasureConfig, QuotaLimit, HostFactors, ReplicationPayload,
+    CLEANUP_INTERVAL_MS, DEFAULT_MAX_BYTES, DEFAULT_MAX_BYTES_PER_SENDER, DEFAULT_MAX_ENTRIES, DEFAULT_TTL_MS,
+    DELETION_THRESHOLD, MAX_ALLOWED_HOLDERS, MAX_REPLICAS, MAX_TTL_MS, QUERY_DEBOUNCE_MS, QUERY_TIMEOUT_MS,
+    REPLICATION_THRESHOLD, VIABILITY_CHECK_INTERVAL_MS,
 };
@@ -0,0 +1,374 @@
+/// Backup persistence — durable storage for BackupStore entries.
+///
+/// BackupStore stays the source of truth in memory; a backend only mirrors
+/// every mutation (write-through) so held messages survive a relay restart.
+/// On startup the store reloads everything via [`BackupPersistence::load_all`].
+#[cfg(feature = "native")]
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::path::Path;
+#[cfg(feature = "native")]
+use std::sync::Mutex;
+
+#[cfg(feature = "native")]
+use rusqlite::{params, Connection};
+#[cfg(feature = "native")]
+use sha2::{Digest, Sha256};
+
+use crate::backup::types::BackupEntry;
+#[cfg(feature = "native")]
+use crate::crypto;
+use crate::storage::StorageError;
+
+/// Key-derivation context for row encryption.
+#[cfg(feature = "native")]
+const DATA_KEY_CONTEXT: &[u8] = b"tom-backup-at-rest-v1";
+
+/// Key-derivation context for row keys (keyed hash of the message ID).
+#[cfg(feature = "native")]
+const ROW_KEY_CONTEXT: &[u8] = b"tom-backup-row-id-v1";
+
+/// A raw sealed row: (row key, nonce, ciphertext).
+#[cfg(feature = "native")]
+type SealedRow = (String, Vec<u8>, Vec<u8>);
+
+/// Pluggable storage backend for backup entries.
+///
+/// Implementations must be cheap to call per mutation; errors are reported
+/// to the caller, which logs and carries on with the in-memory copy.
+pub trait BackupPersistence: Send + Sync {
+    /// Insert or replace an entry.
+    fn save(&self, entry: &BackupEntry) -> Result<(), StorageError>;
+
+    /// Remove an entry (no-op if absent).
+    fn remove(&self, message_id: &str) -> Result<(), StorageError>;
+
+    /// Load every stored entry (startup recovery).
+    fn load_all(&self) -> Result<Vec<BackupEntry>, StorageError>;
+
+    /// Drop rows for anything not in `live_ids` (leftovers, unreadable or
+    /// damaged rows) and reclaim their space. The default does nothing.
+    fn compact(&self, live_ids: &[String]) -> Result<CompactionReport, StorageError> {
+        let _ = live_ids;
+        Ok(CompactionReport::default())
+    }
+}
+
+/// Outcome of [`BackupPersistence::compact`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Rows deleted.
+    pub removed_rows: usize,
+    /// Live entries with no readable row; the caller writes them again.
+    pub missing: Vec<String>,
+}
+
+/// SQLite-backed persistence: one sealed row per entry.
+///
+/// Each entry is msgpack-encoded and encrypted with XChaCha20-Poly1305
+/// under a key derived from the host's secret seed. Rows are keyed by a
+/// keyed hash of the message ID, so the file reveals neither payloads nor
+/// who the messages are for — only how many there are and their sizes.
+///
+/// Wraps Connection in Mutex for Sync (same reason as `SqliteStateStore`).
+#[cfg(feature = "native")]
+pub struct SqliteBackupPersistence {
+    conn: Mutex<Connection>,
+    data_key: [u8; 32],
+    row_key: [u8; 32],
+}
+
+#[cfg(feature = "native")]
+impl SqliteBackupPersistence {
+    /// Open (or create) a backup database at the given path, sealed with
+    /// keys derived from `secret_seed`.
+    pub fn open(path: &Path, secret_seed: &[u8; 32]) -> Result<Self, StorageError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        Self::init(conn, secret_seed)
+    }
+
+    /// Open an in-memory database (for testing).
+    #[cfg(test)]
+    pub fn open_memory(secret_seed: &[u8; 32]) -> Result<Self, StorageError> {
+        Self::init(Connection::open_in_memory()?, secret_seed)
+    }
+
+    fn init(conn: Connection, secret_seed: &[u8; 32]) -> Result<Self, StorageError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sealed_backup_entries (
+                row_key TEXT PRIMARY KEY,
+                nonce BLOB NOT NULL,
+                data BLOB NOT NULL
+            );",
+        )?;
+        let this = Self {
+            conn: Mutex::new(conn),
+            data_key: crypto::derive_storage_key(secret_seed, DATA_KEY_CONTEXT),
+            row_key: crypto::derive_storage_key(secret_seed, ROW_KEY_CONTEXT),
+        };
+        this.migrate_plaintext()?;
+        Ok(this)
+    }
+
+    /// Seal rows left in the clear by earlier versions, then drop them.
+    fn migrate_plaintext(&self) -> Result<(), StorageError> {
+        let legacy: Vec<Vec<u8>> = {
+            let conn = self.conn.lock().unwrap();
+            let exists: bool = conn.query_row(
+                "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'backup_entries'",
+                [],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Ok(());
+            }
+            let mut stmt = conn.prepare("SELECT data FROM backup_entries")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let mut migrated = 0;
+        for data in legacy {
+            if let Ok(entry) = rmp_serde::from_slice::<BackupEntry>(&data) {
+                self.save(&entry)?;
+                migrated += 1;
+            }
+        }
+        self.conn
+            .lock()
+            .unwrap()
+            .execute_batch("DROP TABLE backup_entries;")?;
+        tracing::info!("sealed {migrated} plaintext backup entries");
+        Ok(())
+    }
+
+    /// Keyed hash of a message ID (hex) — the row's primary key.
+    fn row_key_for(&self, message_id: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.row_key);
+        hasher.update(message_id.as_bytes());
+        data_encoding::HEXLOWER.encode(&hasher.finalize())
+    }
+
+    /// Decrypt and decode a row. None if it is unreadable, was moved under
+    /// another message's key, or its payload fails the hash check.
+    fn open_row(&self, key: &str, nonce: &[u8], data: &[u8]) -> Option<BackupEntry> {
+        let nonce = <[u8; 24]>::try_from(nonce).ok()?;
+        let plain = crypto::decrypt_group_message(data, &nonce, &self.data_key).ok()?;
+        let entry = rmp_serde::from_slice::<BackupEntry>(&plain).ok()?;
+        (self.row_key_for(&entry.message_id) == key && entry.is_intact()).then_some(entry)
+    }
+
+    /// Every sealed row.
+    fn all_rows(&self) -> Result<Vec<SealedRow>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT row_key, nonce, data FROM sealed_backup_entries")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect()
+    }
+}
+
+#[cfg(feature = "native")]
+impl BackupPersistence for SqliteBackupPersistence {
+    fn save(&self, entry: &BackupEntry) -> Result<(), StorageError> {
+        let plain = rmp_serde::to_vec(entry).map_err(|e| StorageError::Backend(e.to_string()))?;
+        let (data, nonce) = crypto::encrypt_group_message(&plain, &self.data_key);
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO sealed_backup_entries (row_key, nonce, data) VALUES (?1, ?2, ?3)",
+            params![self.row_key_for(&entry.message_id), nonce.as_slice(), data],
+        )?;
+        Ok(())
+    }
+
+    fn remove(&self, message_id: &str) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM sealed_backup_entries WHERE row_key = ?1",
+            params![self.row_key_for(message_id)],
+        )?;
+        Ok(())
+    }
+
+    fn load_all(&self) -> Result<Vec<BackupEntry>, StorageError> {
+        let mut entries = Vec::new();
+        for (key, nonce, data) in self.all_rows()? {
+            match self.open_row(&key, &nonce, &data) {
+                Some(entry) => entries.push(entry),
+                None => tracing::warn!("skipping unreadable backup row {key}"),
+            }
+        }
+        Ok(entries)
+    }
+
+    fn compact(&self, live_ids: &[String]) -> Result<CompactionReport, StorageError> {
+        let mut live: HashMap<String, &String> =
+            live_ids.iter().map(|id| (self.row_key_for(id), id)).collect();
+
+        let mut doomed = Vec::new();
+        for (key, nonce, data) in self.all_rows()? {
+            let readable = live.contains_key(&key) && self.open_row(&key, &nonce, &data).is_some();
+            if readable {
+                live.remove(&key);
+            } else {
+                doomed.push(key);
+            }
+        }
+
+        let conn = self.conn.lock().unwrap();
+        for key in &doomed {
+            conn.execute("DELETE FROM sealed_backup_entries WHERE row_key = ?1", params![key])?;
+        }
+        conn.execute_batch("VACUUM;")?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+
+        Ok(CompactionReport {
+            removed_rows: doomed.len(),
+            missing: live.into_values().cloned().collect(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+    use crate::types::NodeId;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    const SEED: [u8; 32] = [7; 32];
+
+    #[test]
+    fn save_load_remove_roundtrip() {
+        let db = SqliteBackupPersistence::open_memory(&SEED).unwrap();
+        let mut entry = BackupEntry::new("msg-1".into(), vec![1, 2, 3], node_id(1), node_id(2), 10_000, None);
+        entry.replicated_to.insert(node_id(3));
+
+        db.save(&entry).unwrap();
+        db.save(&entry).unwrap(); // Replace, not duplicate
+
+        let loaded = db.load_all().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].message_id, "msg-1");
+        assert_eq!(loaded[0].payload, vec![1, 2, 3]);
+        assert_eq!(loaded[0].expires_at, entry.expires_at);
+        assert!(loaded[0].replicated_to.contains(&node_id(3)));
+
+        db.remove("msg-1").unwrap();
+        assert!(db.load_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn compact_drops_stale_and_damaged_rows() {
+        let db = SqliteBackupPersistence::open_memory(&SEED).unwrap();
+        for id in ["msg-1", "msg-2", "gone"] {
+            db.save(&BackupEntry::new(id.into(), vec![1], node_id(1), node_id(2), 10_000, None))
+                .unwrap();
+        }
+        db.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE sealed_backup_entries SET data = x'00' WHERE row_key = ?1",
+                params![db.row_key_for("msg-2")],
+            )
+            .unwrap();
+
+        let live: Vec<String> = vec!["msg-1".into(), "msg-2".into(), "unsaved".into()];
+        let mut report = db.compact(&live).unwrap();
+        report.missing.sort();
+        assert_eq!(report.removed_rows, 2);
+        assert_eq!(report.missing, vec!["msg-2".to_string(), "unsaved".to_string()]);
+
+        let loaded = db.load_all().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].message_id, "msg-1");
+    }
+
+    #[test]
+    fn survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("backup.db");
+        {
+            let db = SqliteBackupPersistence::open(&path, &SEED).unwrap();
+            let entry = BackupEntry::new("msg-1".into(), vec![9], node_id(1), node_id(2), 10_000, None);
+            db.save(&entry).unwrap();
+        }
+        let db = SqliteBackupPersistence::open(&path, &SEED).unwrap();
+        let loaded = db.load_all().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].payload, vec![9]);
+    }
+
+    #[test]
+    fn file_is_opaque_without_the_host_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("backup.db");
+        let marker = b"very-secret-payload".to_vec();
+        {
+            let db = SqliteBackupPersistence::open(&path, &SEED).unwrap();
+            let entry = BackupEntry::new("msg-visible-id".into(), marker.clone(), node_id(1), node_id(2), 10_000, None);
+            db.save(&entry).unwrap();
+        }
+
+        // Neither the payload nor the message ID appear in the raw rows.
+        let conn = Connection::open(&path).unwrap();
+        let (key, data): (String, Vec<u8>) = conn
+            .query_row("SELECT row_key, data FROM sealed_backup_entries", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert!(!key.contains("msg-visible-id"));
+        assert!(!data.windows(marker.len()).any(|w| w == marker.as_slice()));
+        drop(conn);
+
+        // A different host key reads nothing.
+        let other = SqliteBackupPersistence::open(&path, &[8; 32]).unwrap();
+        assert!(other.load_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn plaintext_rows_are_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("backup.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE backup_entries (message_id TEXT PRIMARY KEY, data BLOB NOT NULL);",
+            )
+            .unwrap();
+            let entry = BackupEntry::new("msg-1".into(), vec![5], node_id(1), node_id(2), 10_000, None);
+            conn.execute(
+                "INSERT INTO backup_entries (message_id, data) VALUES (?1, ?2)",
+                params![entry.message_id, rmp_serde::to_vec(&entry).unwrap()],
+            )
+            .unwrap();
+        }
+
+        let db = SqliteBackupPersistence::open(&path, &SEED).unwrap();
+        let loaded = db.load_all().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].payload, vec![5]);
+
+        let conn = db.conn.lock().unwrap();
+        let legacy: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'backup_entries'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(legacy, 0);
+    }
+}
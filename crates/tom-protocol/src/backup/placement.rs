@@ -0,0 +1,253 @@
+/// Replica placement — choosing which peers should hold backup copies.
+///
+/// Candidates are scored from their HostFactors (uptime stability,
+/// bandwidth, contribution), their role, and how often we have seen them
+/// online. Selection is greedy but prefers diversity: a candidate in the
+/// same cluster (ephemeral subnet) as an existing holder is penalized, so
+/// copies don't all vanish together when one neighbourhood goes dark.
+///
+/// Pure functions + a small availability tracker. No I/O.
+use std::collections::{HashMap, HashSet};
+
+use crate::backup::types::HostFactors;
+use crate::types::NodeId;
+
+/// Replicas we aim to keep for a message that needs replication.
+pub const DESIRED_REPLICAS: usize = 3;
+
+/// Minimum score (0–100) for a node to be chosen as, or remain, a holder.
+pub const MIN_HOLDER_SCORE: u8 = 40;
+
+/// Score penalty for sharing a cluster with an already chosen holder.
+pub const DIVERSITY_PENALTY: u8 = 25;
+
+/// Score bonus for relay nodes (always-on infrastructure).
+const RELAY_BONUS: f64 = 15.0;
+
+/// Score bonus for nodes announcing the Storage role.
+const STORAGE_BONUS: f64 = 15.0;
+
+/// Weight of each availability sample in the moving average.
+const AVAILABILITY_ALPHA: f64 = 0.1;
+
+/// Availability assumed for nodes we have never sampled.
+const DEFAULT_AVAILABILITY: f64 = 50.0;
+
+/// What we know about a potential replica holder.
+#[derive(Debug, Clone)]
+pub struct ReplicaCandidate {
+    pub node_id: NodeId,
+    /// Host quality as observed by us.
+    pub factors: HostFactors,
+    /// Whether the node is a relay.
+    pub is_relay: bool,
+    /// Whether the node announces the Storage role.
+    pub is_storage: bool,
+    /// Whether the node is reachable right now (only online nodes are chosen).
+    pub online: bool,
+    /// Historical availability (0–100), see [`AvailabilityHistory`].
+    pub availability: u8,
+    /// Cluster the node belongs to (e.g. ephemeral subnet), if any.
+    pub cluster: Option<String>,
+}
+
+impl ReplicaCandidate {
+    /// Placement score (0–100).
+    ///
+    /// Weighted: availability 35%, stability 20%, bandwidth 15%,
+    /// contribution 15%, plus 15 points for relays and 15 for Storage nodes.
+    pub fn score(&self) -> u8 {
+        let f = &self.factors;
+        let mut score = self.availability as f64 * 0.35
+            + f.stability as f64 * 0.20
+            + f.bandwidth as f64 * 0.15
+            + f.contribution as f64 * 0.15;
+        if self.is_relay {
+            score += RELAY_BONUS;
+        }
+        if self.is_storage {
+            score += STORAGE_BONUS;
+        }
+        (score.round() as u8).min(100)
+    }
+}
+
+/// Pick up to `count` online holders, best score first, never from
+/// `exclude`, skipping anyone below [`MIN_HOLDER_SCORE`].
+///
+/// `used_clusters` are the clusters of existing holders; each pick adds its
+/// own, and candidates in a used cluster lose [`DIVERSITY_PENALTY`] points.
+pub fn select_targets(
+    candidates: &[ReplicaCandidate],
+    exclude: &HashSet<NodeId>,
+    used_clusters: &HashSet<String>,
+    count: usize,
+) -> Vec<NodeId> {
+    let mut used = used_clusters.clone();
+    let mut pool: Vec<&ReplicaCandidate> = candidates
+        .iter()
+        .filter(|c| c.online && !exclude.contains(&c.node_id))
+        .collect();
+    let mut chosen = Vec::new();
+
+    while chosen.len() < count {
+        let adjusted = |c: &ReplicaCandidate| {
+            let penalty = match &c.cluster {
+                Some(cluster) if used.contains(cluster) => DIVERSITY_PENALTY,
+                _ => 0,
+            };
+            c.score().saturating_sub(penalty)
+        };
+        let Some((idx, best)) = pool
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, c)| (adjusted(c), c.node_id.to_string()))
+            .map(|(i, c)| (i, *c))
+        else {
+            break;
+        };
+        if adjusted(best) < MIN_HOLDER_SCORE {
+            break;
+        }
+        if let Some(cluster) = &best.cluster {
+            used.insert(cluster.clone());
+        }
+        chosen.push(best.node_id);
+        pool.swap_remove(idx);
+    }
+
+    chosen
+}
+
+/// Exponential moving average of how often each peer is online.
+#[derive(Debug, Default)]
+pub struct AvailabilityHistory {
+    samples: HashMap<NodeId, f64>,
+}
+
+impl AvailabilityHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one observation of a peer's status.
+    pub fn record(&mut self, node_id: NodeId, online: bool) {
+        let sample = if online { 100.0 } else { 0.0 };
+        let avg = self.samples.entry(node_id).or_insert(DEFAULT_AVAILABILITY);
+        *avg += AVAILABILITY_ALPHA * (sample - *avg);
+    }
+
+    /// Availability (0–100); unseen nodes get a neutral 50.
+    pub fn get(&self, node_id: &NodeId) -> u8 {
+        self.samples
+            .get(node_id)
+            .copied()
+            .unwrap_or(DEFAULT_AVAILABILITY)
+            .round() as u8
+    }
+
+    /// Forget a peer entirely.
+    pub fn remove(&mut self, node_id: &NodeId) {
+        self.samples.remove(node_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    fn candidate(seed: u8, quality: u8, cluster: Option<&str>) -> ReplicaCandidate {
+        ReplicaCandidate {
+            node_id: node_id(seed),
+            factors: HostFactors {
+                stability: quality,
+                bandwidth: quality,
+                contribution: quality,
+            },
+            is_relay: false,
+            is_storage: false,
+            online: true,
+            availability: quality,
+            cluster: cluster.map(String::from),
+        }
+    }
+
+    #[test]
+    fn score_rewards_quality_and_relays() {
+        let weak = candidate(1, 20, None);
+        let strong = candidate(2, 90, None);
+        assert!(strong.score() > weak.score());
+
+        let mut relay = strong.clone();
+        relay.is_relay = true;
+        assert_eq!(relay.score(), (strong.score() + 15).min(100));
+
+        let mut storage = weak.clone();
+        storage.is_storage = true;
+        assert_eq!(storage.score(), weak.score() + 15);
+    }
+
+    #[test]
+    fn select_best_online_above_threshold() {
+        let mut offline = candidate(4, 100, None);
+        offline.online = false;
+        let candidates = vec![
+            candidate(1, 60, None),
+            candidate(2, 90, None),
+            candidate(3, 10, None), // below MIN_HOLDER_SCORE
+            offline,
+        ];
+
+        let picked = select_targets(&candidates, &HashSet::new(), &HashSet::new(), 3);
+        assert_eq!(picked, vec![node_id(2), node_id(1)]);
+
+        let exclude: HashSet<_> = [node_id(2)].into();
+        let picked = select_targets(&candidates, &exclude, &HashSet::new(), 3);
+        assert_eq!(picked, vec![node_id(1)]);
+    }
+
+    #[test]
+    fn select_prefers_diverse_clusters() {
+        let candidates = vec![
+            candidate(1, 90, Some("a")),
+            candidate(2, 85, Some("a")),
+            candidate(3, 75, Some("b")),
+        ];
+
+        // Second pick skips the other "a" node for the slightly weaker "b" one.
+        let picked = select_targets(&candidates, &HashSet::new(), &HashSet::new(), 2);
+        assert_eq!(picked, vec![node_id(1), node_id(3)]);
+
+        // Existing holders' clusters count too.
+        let used: HashSet<String> = ["a".to_string()].into();
+        let picked = select_targets(&candidates, &HashSet::new(), &used, 1);
+        assert_eq!(picked, vec![node_id(3)]);
+    }
+
+    #[test]
+    fn availability_tracks_history() {
+        let mut history = AvailabilityHistory::new();
+        let n = node_id(1);
+        assert_eq!(history.get(&n), 50);
+
+        for _ in 0..30 {
+            history.record(n, true);
+        }
+        assert!(history.get(&n) > 90);
+
+        for _ in 0..30 {
+            history.record(n, false);
+        }
+        assert!(history.get(&n) < 10);
+
+        history.remove(&n);
+        assert_eq!(history.get(&n), 50);
+    }
+}
@@ -1,18 +1,17 @@
 /// BackupStore — message storage for offline recipients.
 ///
-/// Pure state machine: store messages, track replicas, expire TTL.
-/// No I/O — caller drives cleanup timer and handles actions.
+/// State machine: store messages, track replicas, expire TTL, enforce
+/// entry/byte quotas. The caller drives the cleanup timer and handles
+/// actions; the only I/O is the optional write-through persistence backend.
 ///
 /// The "virus" lives here: messages self-replicate across nodes
 /// and self-delete when delivered or when viability drops too low.
 use std::collections::{HashMap, HashSet};
 
+use crate::backup::persistence::BackupPersistence;
 use crate::backup::types::*;
 use crate::types::NodeId;
 
-/// Maximum total messages across all recipients (memory protection).
-const MAX_TOTAL_MESSAGES: usize = 10_000;
-
 /// Stores backup messages for offline recipients.
 pub struct BackupStore {
     /// Messages by ID.
@@ -21,18 +20,70 @@ pub struct BackupStore {
     by_recipient: HashMap<NodeId, HashSet<String>>,
     /// Host factors for viability computation.
     host_factors: HostFactors,
+    /// Entry/byte limits.
+    quota: BackupQuota,
+    /// Sum of payload bytes across all entries.
+    total_bytes: u64,
+    /// Payload bytes held per sender (for per-sender quotas).
+    bytes_by_sender: HashMap<NodeId, u64>,
+    /// Durable mirror of `messages` (None = memory only).
+    persistence: Option<Box<dyn BackupPersistence>>,
 }
 
 impl BackupStore {
-    /// Create a new empty store.
+    /// Create a new empty in-memory store with the default quota.
     pub fn new() -> Self {
+        Self::with_quota(BackupQuota::default())
+    }
+
+    /// Create a new empty in-memory store with the given quota.
+    pub fn with_quota(quota: BackupQuota) -> Self {
         Self {
             messages: HashMap::new(),
             by_recipient: HashMap::new(),
             host_factors: HostFactors::default(),
+            quota,
+            total_bytes: 0,
+            bytes_by_sender: HashMap::new(),
+            persistence: None,
         }
     }
 
+    /// Create a store backed by `persistence`, recovering its entries.
+    ///
+    /// Expired entries are dropped and the quota is enforced right away,
+    /// so a restart with a tighter quota shrinks the store.
+    pub fn with_persistence(
+        persistence: Box<dyn BackupPersistence>,
+        quota: BackupQuota,
+        now: u64,
+    ) -> Self {
+        let entries = persistence.load_all().unwrap_or_else(|e| {
+            tracing::warn!("failed to load backup entries: {e}");
+            Vec::new()
+        });
+
+        let mut store = Self::with_quota(quota);
+        store.persistence = Some(persistence);
+
+        let mut expired = 0;
+        for entry in entries {
+            if entry.is_expired(now) {
+                store.unpersist(&entry.message_id);
+                expired += 1;
+                continue;
+            }
+            store.insert_entry(entry);
+        }
+        let evicted = store.make_room(0, 0, now).len();
+
+        tracing::info!(
+            "recovered {} backup messages ({expired} expired, {evicted} over quota)",
+            store.messages.len()
+        );
+        store
+    }
+
     /// Store a message for an offline recipient.
     pub fn store(
         &mut self,
@@ -43,32 +94,17 @@ impl BackupStore {
         now: u64,
         ttl_ms: Option<u64>,
     ) -> Vec<BackupEvent> {
-        let mut events = vec![];
+        let entry = BackupEntry::new(message_id, payload, recipient_id, sender_id, now, ttl_ms);
+        self.store_entry(entry, now)
+    }
 
+    /// Store a prepared entry (e.g. a group message with its own `msg_type`).
+    pub fn store_entry(&mut self, entry: BackupEntry, now: u64) -> Vec<BackupEvent> {
         // Dedup
-        if self.messages.contains_key(&message_id) {
-            return events;
-        }
-
-        // Memory protection
-        if self.messages.len() >= MAX_TOTAL_MESSAGES {
-            self.evict_oldest(now);
+        if self.messages.contains_key(&entry.message_id) {
+            return vec![];
         }
-
-        let entry = BackupEntry::new(message_id.clone(), payload, recipient_id, sender_id, now, ttl_ms);
-
-        self.by_recipient
-            .entry(recipient_id)
-            .or_default()
-            .insert(message_id.clone());
-
-        events.push(BackupEvent::MessageStored {
-            message_id: message_id.clone(),
-            recipient_id,
-        });
-
-        self.messages.insert(message_id, entry);
-        events
+        self.admit(entry, now)
     }
 
     /// Store from a replication payload (received from another node).
@@ -82,13 +118,14 @@ impl BackupStore {
             return vec![];
         }
 
-        // Already have it
-        if self.messages.contains_key(&payload.message_id) {
-            // But record the replications we didn't know about
-            if let Some(entry) = self.messages.get_mut(&payload.message_id) {
-                for node in &payload.replicated_to {
-                    entry.replicated_to.insert(*node);
-                }
+        // Already have it — but record the replications we didn't know about
+        if let Some(entry) = self.messages.get_mut(&payload.message_id) {
+            let mut changed = false;
+            for node in &payload.replicated_to {
+                changed |= entry.replicated_to.insert(*node);
+            }
+            if changed {
+                self.persist(&payload.message_id);
             }
             return vec![];
         }
@@ -103,24 +140,15 @@ impl BackupStore {
             payload.sender_id,
             now,
             Some(remaining_ttl),
-        );
+        )
+        .with_msg_type(payload.msg_type);
         entry.viability_score = payload.viability_score;
+        entry.shard = payload.shard;
         for node in &payload.replicated_to {
             entry.replicated_to.insert(*node);
         }
 
-        self.by_recipient
-            .entry(payload.recipient_id)
-            .or_default()
-            .insert(payload.message_id.clone());
-
-        let events = vec![BackupEvent::MessageStored {
-            message_id: payload.message_id.clone(),
-            recipient_id: payload.recipient_id,
-        }];
-
-        self.messages.insert(payload.message_id.clone(), entry);
-        events
+        self.admit(entry, now)
     }
 
     /// Get all messages for a recipient.
@@ -145,17 +173,10 @@ impl BackupStore {
 
     /// Mark message as delivered — remove from store.
     pub fn mark_delivered(&mut self, message_id: &str) -> Vec<BackupEvent> {
-        let Some(entry) = self.messages.remove(message_id) else {
+        let Some(entry) = self.remove_entry(message_id) else {
             return vec![];
         };
 
-        if let Some(ids) = self.by_recipient.get_mut(&entry.recipient_id) {
-            ids.remove(message_id);
-            if ids.is_empty() {
-                self.by_recipient.remove(&entry.recipient_id);
-            }
-        }
-
         vec![BackupEvent::MessageDelivered {
             message_id: message_id.to_string(),
             recipient_id: entry.recipient_id,
@@ -174,7 +195,18 @@ impl BackupStore {
     /// Record that a message was replicated to a node.
     pub fn record_replication(&mut self, message_id: &str, target: NodeId) {
         if let Some(entry) = self.messages.get_mut(message_id) {
-            entry.replicated_to.insert(target);
+            if entry.replicated_to.insert(target) {
+                self.persist(message_id);
+            }
+        }
+    }
+
+    /// Forget that a node holds a replica (e.g. its placement score dropped).
+    pub fn forget_replica(&mut self, message_id: &str, node: &NodeId) {
+        if let Some(entry) = self.messages.get_mut(message_id) {
+            if entry.replicated_to.remove(node) {
+                self.persist(message_id);
+            }
         }
     }
 
@@ -182,6 +214,15 @@ impl BackupStore {
     pub fn update_viability(&mut self, message_id: &str, score: u8) {
         if let Some(entry) = self.messages.get_mut(message_id) {
             entry.viability_score = score.min(100);
+            self.persist(message_id);
+        }
+    }
+
+    /// Record that a message was just served (refreshes its LRU position).
+    pub fn touch(&mut self, message_id: &str, now: u64) {
+        if let Some(entry) = self.messages.get_mut(message_id) {
+            entry.last_accessed = now;
+            self.persist(message_id);
         }
     }
 
@@ -206,13 +247,7 @@ impl BackupStore {
             .collect();
 
         for id in expired {
-            if let Some(entry) = self.messages.remove(&id) {
-                if let Some(ids) = self.by_recipient.get_mut(&entry.recipient_id) {
-                    ids.remove(&id);
-                    if ids.is_empty() {
-                        self.by_recipient.remove(&entry.recipient_id);
-                    }
-                }
+            if let Some(entry) = self.remove_entry(&id) {
                 events.push(BackupEvent::MessageExpired {
                     message_id: id,
                     recipient_id: entry.recipient_id,
@@ -254,19 +289,47 @@ impl BackupStore {
         events
     }
 
-    /// Delete a message (self-deletion when viability is too low).
-    pub fn delete(&mut self, message_id: &str) -> bool {
-        if let Some(entry) = self.messages.remove(message_id) {
-            if let Some(ids) = self.by_recipient.get_mut(&entry.recipient_id) {
-                ids.remove(message_id);
-                if ids.is_empty() {
-                    self.by_recipient.remove(&entry.recipient_id);
-                }
+    /// Integrity pass: drop entries whose payload no longer matches its
+    /// hash, hash entries stored before hashes were kept, then compact the
+    /// persistent backend. Returns an event per dropped entry.
+    pub fn verify_integrity(&mut self) -> Vec<BackupEvent> {
+        let mut events = vec![];
+        let corrupted: Vec<String> = self
+            .messages
+            .values()
+            .filter(|entry| !entry.is_intact())
+            .map(|entry| entry.message_id.clone())
+            .collect();
+        for id in corrupted {
+            if let Some(entry) = self.remove_entry(&id) {
+                tracing::warn!("backup message {id} failed its integrity check, dropped");
+                events.push(BackupEvent::MessageCorrupted {
+                    message_id: id,
+                    recipient_id: entry.recipient_id,
+                });
+            }
+        }
+
+        let unhashed: Vec<String> = self
+            .messages
+            .values()
+            .filter(|entry| entry.payload_hash.is_none())
+            .map(|entry| entry.message_id.clone())
+            .collect();
+        for id in unhashed {
+            if let Some(entry) = self.messages.get_mut(&id) {
+                entry.payload_hash = Some(payload_digest(&entry.payload));
+                self.persist(&id);
             }
-            true
-        } else {
-            false
         }
+
+        self.compact();
+        events
+    }
+
+    /// Delete a message (self-deletion when viability is too low).
+    pub fn delete(&mut self, message_id: &str) -> bool {
+        self.remove_entry(message_id).is_some()
     }
 
     /// Create a replication payload for sending to another node.
@@ -280,6 +343,8 @@ impl BackupStore {
             expires_at: entry.expires_at,
             viability_score: entry.viability_score,
             replicated_to: entry.replicated_to.iter().copied().collect(),
+            msg_type: entry.msg_type,
+            shard: entry.shard,
         })
     }
 
@@ -293,6 +358,21 @@ impl BackupStore {
         self.by_recipient.len()
     }
 
+    /// Total payload bytes held.
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Payload bytes held on behalf of `sender_id`.
+    pub fn sender_bytes(&self, sender_id: &NodeId) -> u64 {
+        self.bytes_by_sender.get(sender_id).copied().unwrap_or(0)
+    }
+
+    /// Current storage limits.
+    pub fn quota(&self) -> BackupQuota {
+        self.quota
+    }
+
     /// All message IDs (for iteration).
     pub fn message_ids(&self) -> Vec<String> {
         self.messages.keys().cloned().collect()
@@ -311,15 +391,135 @@ impl BackupStore {
         (score as u8).min(100)
     }
 
-    /// Evict the oldest message to make room.
-    fn evict_oldest(&mut self, _now: u64) {
-        if let Some((oldest_id, _)) = self
-            .messages
-            .iter()
-            .min_by_key(|(_, entry)| entry.stored_at)
+    /// Insert a new entry, evicting as needed to fit the quota.
+    fn admit(&mut self, entry: BackupEntry, now: u64) -> Vec<BackupEvent> {
+        if entry.size_bytes() > self.quota.max_bytes {
+            tracing::warn!(
+                "backup message {} ({} bytes) exceeds the byte quota, not stored",
+                entry.message_id,
+                entry.size_bytes()
+            );
+            return vec![BackupEvent::StoreQuotaExceeded {
+                message_id: entry.message_id,
+                sender_id: entry.sender_id,
+            }];
+        }
+
+        let mut events = self.make_room(1, entry.size_bytes(), now);
+        events.push(BackupEvent::MessageStored {
+            message_id: entry.message_id.clone(),
+            recipient_id: entry.recipient_id,
+        });
+
+        let message_id = entry.message_id.clone();
+        self.insert_entry(entry);
+        self.persist(&message_id);
+        events
+    }
+
+    /// Evict until `incoming` more entries/bytes fit the quota.
+    /// Expired entries go first, then the least recently accessed.
+    fn make_room(&mut self, incoming_entries: usize, incoming_bytes: u64, now: u64) -> Vec<BackupEvent> {
+        let mut events = vec![];
+        while !self.messages.is_empty()
+            && (self.messages.len() + incoming_entries > self.quota.max_entries
+                || self.total_bytes + incoming_bytes > self.quota.max_bytes)
         {
-            let oldest_id = oldest_id.clone();
-            self.delete(&oldest_id);
+            let Some(victim) = self
+                .messages
+                .values()
+                .min_by_key(|e| (!e.is_expired(now), e.last_accessed))
+                .map(|e| e.message_id.clone())
+            else {
+                break;
+            };
+            let Some(entry) = self.remove_entry(&victim) else {
+                break;
+            };
+            events.push(if entry.is_expired(now) {
+                BackupEvent::MessageExpired {
+                    message_id: victim,
+                    recipient_id: entry.recipient_id,
+                }
+            } else {
+                BackupEvent::MessageEvicted {
+                    message_id: victim,
+                    recipient_id: entry.recipient_id,
+                }
+            });
+        }
+        events
+    }
+
+    /// Add an entry to the maps and byte counter (no persistence).
+    fn insert_entry(&mut self, entry: BackupEntry) {
+        self.total_bytes += entry.size_bytes();
+        *self.bytes_by_sender.entry(entry.sender_id).or_default() += entry.size_bytes();
+        self.by_recipient
+            .entry(entry.recipient_id)
+            .or_default()
+            .insert(entry.message_id.clone());
+        self.messages.insert(entry.message_id.clone(), entry);
+    }
+
+    /// Remove an entry from the maps, byte counter and backend.
+    fn remove_entry(&mut self, message_id: &str) -> Option<BackupEntry> {
+        let entry = self.messages.remove(message_id)?;
+        self.total_bytes = self.total_bytes.saturating_sub(entry.size_bytes());
+        if let Some(bytes) = self.bytes_by_sender.get_mut(&entry.sender_id) {
+            *bytes = bytes.saturating_sub(entry.size_bytes());
+            if *bytes == 0 {
+                self.bytes_by_sender.remove(&entry.sender_id);
+            }
+        }
+        if let Some(ids) = self.by_recipient.get_mut(&entry.recipient_id) {
+            ids.remove(message_id);
+            if ids.is_empty() {
+                self.by_recipient.remove(&entry.recipient_id);
+            }
+        }
+        self.unpersist(message_id);
+        Some(entry)
+    }
+
+    /// Compact the backend and rewrite live entries whose rows were lost.
+    fn compact(&self) {
+        let Some(backend) = &self.persistence else {
+            return;
+        };
+        match backend.compact(&self.message_ids()) {
+            Ok(report) => {
+                for id in &report.missing {
+                    self.persist(id);
+                }
+                if report.removed_rows > 0 || !report.missing.is_empty() {
+                    tracing::info!(
+                        "compacted backup store: {} stale rows removed, {} rewritten",
+                        report.removed_rows,
+                        report.missing.len()
+                    );
+                }
+            }
+            Err(e) => tracing::warn!("failed to compact backup store: {e}"),
+        }
+    }
+
+    /// Write an entry through to the backend, if any.
+    fn persist(&self, message_id: &str) {
+        let (Some(backend), Some(entry)) = (&self.persistence, self.messages.get(message_id)) else {
+            return;
+        };
+        if let Err(e) = backend.save(entry) {
+            tracing::warn!("failed to persist backup message {message_id}: {e}");
+        }
+    }
+
+    /// Remove an entry from the backend, if any.
+    fn unpersist(&self, message_id: &str) {
+        if let Some(backend) = &self.persistence {
+            if let Err(e) = backend.remove(message_id) {
+                tracing::warn!("failed to remove persisted backup message {message_id}: {e}");
+            }
         }
     }
 }
@@ -431,6 +631,8 @@ mod tests {
             expires_at: 20_000,
             viability_score: 80,
             replicated_to: vec![relay],
+            msg_type: crate::types::MessageType::Chat,
+            shard: None,
         };
 
         let events = store.store_replica(&payload, 15_000);
@@ -455,6 +657,8 @@ mod tests {
             expires_at: 10_000,
             viability_score: 50,
             replicated_to: vec![],
+            msg_type: crate::types::MessageType::Chat,
+            shard: None,
         };
 
         let events = store.store_replica(&payload, 15_000); // Already expired
@@ -588,4 +792,119 @@ mod tests {
         store.mark_delivered("msg-3");
         assert_eq!(store.recipient_count(), 1);
     }
+
+    #[test]
+    fn entry_quota_evicts_least_recently_accessed() {
+        let mut store = BackupStore::with_quota(BackupQuota {
+            max_entries: 2,
+            ..Default::default()
+        });
+        let r = node_id(1);
+        let s = node_id(2);
+
+        store.store("msg-1".into(), vec![], r, s, 10_000, None);
+        store.store("msg-2".into(), vec![], r, s, 11_000, None);
+        store.touch("msg-1", 12_000); // msg-2 is now least recently used
+
+        let events = store.store("msg-3".into(), vec![], r, s, 13_000, None);
+        assert!(matches!(&events[0], BackupEvent::MessageEvicted { message_id, .. } if message_id == "msg-2"));
+        assert!(matches!(events[1], BackupEvent::MessageStored { .. }));
+        assert!(store.has("msg-1"));
+        assert!(store.has("msg-3"));
+        assert_eq!(store.message_count(), 2);
+    }
+
+    #[test]
+    fn byte_quota_prefers_expired_and_rejects_oversized() {
+        let mut store = BackupStore::with_quota(BackupQuota {
+            max_entries: 100,
+            max_bytes: 10,
+            ..Default::default()
+        });
+        let r = node_id(1);
+        let s = node_id(2);
+
+        store.store("msg-1".into(), vec![0; 4], r, s, 10_000, None);
+        store.store("msg-2".into(), vec![0; 4], r, s, 11_000, Some(1000));
+        assert_eq!(store.total_bytes(), 8);
+        assert_eq!(store.sender_bytes(&s), 8);
+
+        // msg-2 expired at 12_000 — it goes before the older msg-1
+        let events = store.store("msg-3".into(), vec![0; 4], r, s, 13_000, None);
+        assert!(matches!(&events[0], BackupEvent::MessageExpired { message_id, .. } if message_id == "msg-2"));
+        assert!(store.has("msg-1"));
+        assert_eq!(store.total_bytes(), 8);
+
+        // Larger than the whole quota: refused without evicting anything
+        let events = store.store("big".into(), vec![0; 11], r, s, 14_000, None);
+        assert!(matches!(&events[..],
+            [BackupEvent::StoreQuotaExceeded { message_id, .. }] if message_id == "big"));
+        assert!(!store.has("big"));
+        assert_eq!(store.message_count(), 2);
+
+        store.delete("msg-1");
+        store.delete("msg-3");
+        assert_eq!(store.sender_bytes(&s), 0);
+    }
+
+    #[test]
+    fn integrity_pass_drops_corrupted_entries() {
+        let mut store = BackupStore::new();
+        let r = node_id(1);
+        let s = node_id(2);
+        store.store("msg-1".into(), vec![1, 2, 3], r, s, 10_000, None);
+        store.store("msg-2".into(), vec![4, 5, 6], r, s, 10_000, None);
+        store.store("legacy".into(), vec![7], r, s, 10_000, None);
+        store.messages.get_mut("msg-2").unwrap().payload[0] ^= 0xff;
+        store.messages.get_mut("legacy").unwrap().payload_hash = None;
+
+        let events = store.verify_integrity();
+        assert!(matches!(&events[..],
+            [BackupEvent::MessageCorrupted { message_id, recipient_id }] if message_id == "msg-2" && *recipient_id == r));
+        assert!(!store.has("msg-2"));
+        assert_eq!(store.total_bytes(), 4);
+        // Entries from before hashing are hashed, not dropped
+        assert!(store.get("legacy").unwrap().payload_hash.is_some());
+        assert!(store.verify_integrity().is_empty());
+    }
+
+    #[test]
+    fn persistent_store_recovers_after_restart() {
+        use crate::backup::persistence::SqliteBackupPersistence;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("backup.db");
+        let open = || Box::new(SqliteBackupPersistence::open(&path, &[7; 32]).unwrap());
+        let r = node_id(1);
+        let s = node_id(2);
+        let relay = node_id(3);
+
+        {
+            let mut store = BackupStore::with_persistence(open(), BackupQuota::default(), 10_000);
+            store.store("msg-1".into(), vec![1], r, s, 10_000, None);
+            store.store("msg-2".into(), vec![2], r, s, 10_000, Some(1000));
+            store.store("msg-3".into(), vec![3], r, s, 10_000, None);
+            store.record_replication("msg-1", relay);
+            store.mark_delivered("msg-3");
+        }
+
+        // msg-2 expired while we were down; msg-3 was delivered before.
+        let store = BackupStore::with_persistence(open(), BackupQuota::default(), 20_000);
+        assert_eq!(store.message_count(), 1);
+        let entry = store.get("msg-1").unwrap();
+        assert_eq!(entry.payload, vec![1]);
+        assert!(entry.replicated_to.contains(&relay));
+        assert_eq!(store.get_for_recipient(&r).len(), 1);
+        assert_eq!(store.total_bytes(), 1);
+
+        // A tighter quota on restart shrinks the store on disk too.
+        drop(store);
+        let store = BackupStore::with_persistence(
+            open(),
+            BackupQuota { max_entries: 0, ..Default::default() },
+            20_000,
+        );
+        assert_eq!(store.message_count(), 0);
+        assert!(open().load_all().unwrap().is_empty());
+    }
 }
@@ -5,15 +5,20 @@
 use std::collections::HashSet;
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::types::NodeId;
+use crate::backup::bloom::BloomFilter;
+use crate::backup::erasure::ShardInfo;
+use crate::types::{MessageType, NodeId};
 
 // ── Constants ────────────────────────────────────────────────────────────
 
 /// Maximum TTL: 24 hours (design decision #2 — non-negotiable).
+///
+/// Operators may donate less time ([`BackupPolicy::max_ttl_ms`]), never more.
 pub const MAX_TTL_MS: u64 = 24 * 60 * 60 * 1000;
 
-/// Default TTL (same as max).
+/// Default TTL when the sender doesn't request one (same as max).
 pub const DEFAULT_TTL_MS: u64 = MAX_TTL_MS;
 
 /// Cleanup interval (60 seconds). Caller uses this as timer interval.
@@ -37,14 +42,26 @@ pub const QUERY_TIMEOUT_MS: u64 = 30_000;
 /// Query debounce window (5 seconds).
 pub const QUERY_DEBOUNCE_MS: u64 = 5_000;
 
+/// Default cap on stored messages across all recipients.
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// Default cap on stored payload bytes across all recipients (64 MiB).
+pub const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Default cap on payload bytes held for any one sender (4 MiB).
+pub const DEFAULT_MAX_BYTES_PER_SENDER: u64 = 4 * 1024 * 1024;
+
+/// Most holders a node may list in its [`BackupPreference::Allowlist`].
+pub const MAX_ALLOWED_HOLDERS: usize = 32;
+
 // ── Types ────────────────────────────────────────────────────────────────
 
 /// A backed-up message held for an offline recipient.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupEntry {
     /// Original message ID (from Envelope).
     pub message_id: String,
-    /// Encrypted payload bytes (opaque — we never decrypt).
+    /// Payload sealed to the recipient (`EncryptedPayload` bytes — we never decrypt).
     pub payload: Vec<u8>,
     /// Who this message is for.
     pub recipient_id: NodeId,
@@ -58,6 +75,24 @@ pub struct BackupEntry {
     pub viability_score: u8,
     /// Nodes that have confirmed replicas.
     pub replicated_to: HashSet<NodeId>,
+    /// Last time the entry was stored or served (Unix ms). Drives LRU eviction.
+    #[serde(default)]
+    pub last_accessed: u64,
+    /// Envelope type the payload is delivered as (Chat or GroupMessage).
+    #[serde(default = "default_msg_type")]
+    pub msg_type: MessageType,
+    /// Set when `payload` is one erasure-coded shard, not the whole message.
+    #[serde(default)]
+    pub shard: Option<ShardInfo>,
+    /// SHA-256 of `payload` when stored (None for entries stored before
+    /// hashes were kept; the integrity pass fills it in).
+    #[serde(default)]
+    pub payload_hash: Option<[u8; 32]>,
+}
+
+/// Entries stored before `msg_type` existed were all chats.
+fn default_msg_type() -> MessageType {
+    MessageType::Chat
 }
 
 impl BackupEntry {
@@ -71,6 +106,7 @@ impl BackupEntry {
         ttl_ms: Option<u64>,
     ) -> Self {
         let ttl = ttl_ms.unwrap_or(DEFAULT_TTL_MS).min(MAX_TTL_MS);
+        let payload_hash = Some(payload_digest(&payload));
         Self {
             message_id,
             payload,
@@ -80,9 +116,19 @@ impl BackupEntry {
             expires_at: now + ttl,
             viability_score: 100,
             replicated_to: HashSet::new(),
+            last_accessed: now,
+            msg_type: MessageType::Chat,
+            shard: None,
+            payload_hash,
         }
     }
 
+    /// Set the envelope type the payload is delivered as.
+    pub fn with_msg_type(mut self, msg_type: MessageType) -> Self {
+        self.msg_type = msg_type;
+        self
+    }
+
     /// Whether this entry has expired.
     pub fn is_expired(&self, now: u64) -> bool {
         now >= self.expires_at
@@ -97,6 +143,196 @@ impl BackupEntry {
     pub fn replica_count(&self) -> usize {
         self.replicated_to.len()
     }
+
+    /// Bytes this entry counts against the store's byte quota.
+    pub fn size_bytes(&self) -> u64 {
+        self.payload.len() as u64
+    }
+
+    /// Whether the payload still matches its stored hash (entries without
+    /// a hash can't be checked and count as intact).
+    pub fn is_intact(&self) -> bool {
+        self.payload_hash
+            .is_none_or(|hash| hash == payload_digest(&self.payload))
+    }
+}
+
+/// SHA-256 of a backup payload.
+pub fn payload_digest(payload: &[u8]) -> [u8; 32] {
+    Sha256::digest(payload).into()
+}
+
+/// Storage limits for a BackupStore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupQuota {
+    /// Maximum number of stored messages.
+    pub max_entries: usize,
+    /// Maximum total payload bytes.
+    pub max_bytes: u64,
+    /// Maximum payload bytes held on behalf of a single sender (other
+    /// nodes' messages only — our own outgoing backups are exempt).
+    pub max_bytes_per_sender: u64,
+}
+
+impl Default for BackupQuota {
+    fn default() -> Self {
+        Self {
+            max_entries: DEFAULT_MAX_ENTRIES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_bytes_per_sender: DEFAULT_MAX_BYTES_PER_SENDER,
+        }
+    }
+}
+
+/// Who a node lets hold its messages while it is offline, announced in
+/// its `PeerAnnounce`. Senders and holders honour it when picking
+/// replication targets; the sender's own copy is never third-party.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupPreference {
+    /// Any node may hold our messages.
+    #[default]
+    Open,
+    /// No third-party backups at all.
+    OptOut,
+    /// Only these nodes (at most [`MAX_ALLOWED_HOLDERS`]) may hold them.
+    Allowlist(Vec<NodeId>),
+}
+
+impl BackupPreference {
+    /// Whether `holder` may keep messages for the announcing node.
+    pub fn permits(&self, holder: &NodeId) -> bool {
+        match self {
+            Self::Open => true,
+            Self::OptOut => false,
+            Self::Allowlist(holders) => holders.contains(holder),
+        }
+    }
+}
+
+/// Which storage limit refused a backup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum QuotaLimit {
+    /// The store's total byte quota.
+    Store,
+    /// The per-sender share of it.
+    Sender,
+}
+
+/// Snapshot of what the backup system holds, for operators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupStats {
+    /// Entries held (our own backups and replicas for others).
+    pub entries: usize,
+    /// Payload bytes held.
+    pub bytes: u64,
+    /// Limits the entries count against.
+    pub quota: BackupQuota,
+    /// Entries held per recipient, most first.
+    pub per_recipient: Vec<(NodeId, usize)>,
+    /// Entries with as many other holders as wanted (replicas or shards).
+    pub healthy: usize,
+    /// Entries with some other holders, but too few.
+    pub under_replicated: usize,
+    /// Entries no other node holds.
+    pub unreplicated: usize,
+    /// Replications sent and awaiting a receipt.
+    pub pending_replications: usize,
+    /// Messages waiting in paced delivery queues.
+    pub queued_deliveries: usize,
+}
+
+/// What a node donates to holding messages for others: storage (quota)
+/// and time (TTL bounds). Enforced by BackupCoordinator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupPolicy {
+    /// TTL applied when the sender doesn't request one.
+    pub default_ttl_ms: u64,
+    /// Longest TTL we honour, for our own backups and replicas we accept.
+    /// Capped at [`MAX_TTL_MS`].
+    pub max_ttl_ms: u64,
+    /// Storage limits.
+    pub quota: BackupQuota,
+    /// Shard large payloads across holders instead of copying them
+    /// (None = full copies only).
+    pub erasure: Option<ErasureConfig>,
+    /// How held messages are batched when their recipient reconnects.
+    pub delivery: DeliveryPacing,
+}
+
+/// Batching of held messages handed to a reconnecting recipient: one batch
+/// goes out immediately, the rest one per delivery tick, oldest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeliveryPacing {
+    /// Most messages per batch envelope.
+    pub max_batch_entries: usize,
+    /// Most payload bytes per batch (a larger message still goes alone).
+    pub max_batch_bytes: usize,
+}
+
+impl Default for DeliveryPacing {
+    fn default() -> Self {
+        Self {
+            max_batch_entries: 32,
+            max_batch_bytes: 256 * 1024,
+        }
+    }
+}
+
+/// Reed-Solomon sharding of backup payloads: any `data_shards` of the
+/// `data_shards + parity_shards` holders can rebuild the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErasureConfig {
+    /// Shards needed to rebuild (k).
+    pub data_shards: u8,
+    /// Extra shards tolerated lost (n - k).
+    pub parity_shards: u8,
+    /// Smaller payloads are copied whole (sharding overhead isn't worth it).
+    pub min_payload_bytes: u64,
+}
+
+impl ErasureConfig {
+    /// Total shards (n).
+    pub fn total_shards(&self) -> usize {
+        self.data_shards as usize + self.parity_shards as usize
+    }
+}
+
+impl Default for ErasureConfig {
+    /// 3-of-5: survives two lost holders while storing 5/3 of the payload
+    /// in total (three full copies would store 3×).
+    fn default() -> Self {
+        Self {
+            data_shards: 3,
+            parity_shards: 2,
+            min_payload_bytes: 16 * 1024,
+        }
+    }
+}
+
+impl BackupPolicy {
+    /// Effective TTL ceiling (operator setting, capped at [`MAX_TTL_MS`]).
+    pub fn max_ttl(&self) -> u64 {
+        self.max_ttl_ms.min(MAX_TTL_MS)
+    }
+
+    /// TTL for a message whose sender requested `requested_ms` (None = default).
+    pub fn ttl_for(&self, requested_ms: Option<u64>) -> u64 {
+        requested_ms
+            .unwrap_or(self.default_ttl_ms)
+            .min(self.max_ttl())
+    }
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            default_ttl_ms: DEFAULT_TTL_MS,
+            max_ttl_ms: MAX_TTL_MS,
+            quota: BackupQuota::default(),
+            erasure: None,
+            delivery: DeliveryPacing::default(),
+        }
+    }
 }
 
 /// Events emitted by the backup system.
@@ -120,6 +356,12 @@ pub enum BackupEvent {
         recipient_id: NodeId,
     },
 
+    /// A message was evicted to stay within the storage quota.
+    MessageEvicted {
+        message_id: String,
+        recipient_id: NodeId,
+    },
+
     /// Viability dropped — replication needed (score <= 30%).
     ReplicationNeeded {
         message_id: String,
@@ -134,6 +376,32 @@ pub enum BackupEvent {
         score: u8,
     },
 
+    /// Refused: the sender already uses its whole per-sender quota here.
+    SenderQuotaExceeded {
+        message_id: String,
+        sender_id: NodeId,
+    },
+
+    /// Refused: the message alone is larger than the store's byte quota.
+    StoreQuotaExceeded {
+        message_id: String,
+        sender_id: NodeId,
+    },
+
+    /// The payload no longer matches its hash; the entry was dropped.
+    MessageCorrupted {
+        message_id: String,
+        recipient_id: NodeId,
+    },
+
+    /// Holders were dropped for scoring too low; replacements are sought.
+    ReplicationDegraded {
+        message_id: String,
+        recipient_id: NodeId,
+        /// Holders left besides us.
+        remaining_holders: usize,
+    },
+
     /// Message was successfully replicated to another node.
     MessageReplicated {
         message_id: String,
@@ -151,6 +419,41 @@ pub struct ReplicationPayload {
     pub expires_at: u64,
     pub viability_score: u8,
     pub replicated_to: Vec<NodeId>,
+    #[serde(default = "default_msg_type")]
+    pub msg_type: MessageType,
+    #[serde(default)]
+    pub shard: Option<ShardInfo>,
+}
+
+/// Query for a recipient's pending messages.
+///
+/// `known` holds the message IDs the querier already has; holders reply
+/// with only the IDs it does not contain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupQueryPayload {
+    pub recipient_id: NodeId,
+    pub known: BloomFilter,
+}
+
+/// Reply to a [`BackupQueryPayload`]: the holder's IDs missing from the filter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupQueryResponsePayload {
+    pub recipient_id: NodeId,
+    pub message_ids: Vec<String>,
+}
+
+/// A replica passed on by a holder that is shutting down, so the message
+/// keeps its replica count.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupHandoffPayload {
+    pub replica: ReplicationPayload,
+}
+
+/// Held messages handed to their recipient in one envelope, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupDeliverBatchPayload {
+    pub recipient_id: NodeId,
+    pub entries: Vec<ReplicationPayload>,
 }
 
 /// Actions returned by the backup system for the caller to execute.
@@ -162,6 +465,18 @@ pub enum BackupAction {
         payload: ReplicationPayload,
     },
 
+    /// Pass a held message on to another holder before we shut down.
+    Handoff {
+        target: NodeId,
+        payload: ReplicationPayload,
+    },
+
+    /// Hand a batch of held messages to their (reconnected) recipient.
+    DeliverBatch {
+        recipient_id: NodeId,
+        entries: Vec<ReplicationPayload>,
+    },
+
     /// Broadcast delivery confirmation to all backup nodes.
     ConfirmDelivery {
         message_ids: Vec<String>,
@@ -171,6 +486,8 @@ pub enum BackupAction {
     /// Query network for pending messages for a recipient.
     QueryPending {
         recipient_id: NodeId,
+        /// Message IDs we already hold for this recipient.
+        known: BloomFilter,
     },
 
     /// Emit an event (for the application layer).
@@ -238,6 +555,26 @@ mod tests {
         assert_eq!(entry.expires_at, 10_000 + MAX_TTL_MS);
     }
 
+    #[test]
+    fn policy_bounds_requested_ttl() {
+        let policy = BackupPolicy {
+            default_ttl_ms: 60_000,
+            max_ttl_ms: 3_600_000,
+            ..Default::default()
+        };
+        assert_eq!(policy.ttl_for(None), 60_000);
+        assert_eq!(policy.ttl_for(Some(5_000)), 5_000);
+        assert_eq!(policy.ttl_for(Some(MAX_TTL_MS)), 3_600_000);
+
+        // Operators can't donate more than the protocol maximum
+        let generous = BackupPolicy {
+            default_ttl_ms: MAX_TTL_MS * 7,
+            max_ttl_ms: MAX_TTL_MS * 7,
+            ..Default::default()
+        };
+        assert_eq!(generous.ttl_for(None), MAX_TTL_MS);
+    }
+
     #[test]
     fn expiry_check() {
         let entry = BackupEntry::new(
@@ -280,12 +617,15 @@ mod tests {
             expires_at: 100_000,
             viability_score: 75,
             replicated_to: vec![node_id(3)],
+            msg_type: MessageType::GroupMessage,
+            shard: None,
         };
         let bytes = rmp_serde::to_vec(&payload).unwrap();
         let decoded: ReplicationPayload = rmp_serde::from_slice(&bytes).unwrap();
         assert_eq!(decoded.message_id, "msg-1");
         assert_eq!(decoded.viability_score, 75);
         assert_eq!(decoded.replicated_to.len(), 1);
+        assert_eq!(decoded.msg_type, MessageType::GroupMessage);
     }
 
     #[test]
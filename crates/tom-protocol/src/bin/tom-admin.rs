@@ -0,0 +1,76 @@
+//! tom-admin — query or drive a running node through its admin socket
+//! (`RuntimeConfig::admin_socket`).
+//!
+//! ```text
+//! tom-admin <socket> status | peers | groups | backups | roles
+//! tom-admin <socket> send <node_id> <text>
+//! tom-admin <socket> send-group <group_id> <text>
+//! ```
+
+use std::process::ExitCode;
+
+use tom_protocol::{AdminRequest, AdminResponse};
+
+const USAGE: &str = "usage: tom-admin <socket> <status|peers|groups|backups|roles|send <node_id> <text>|send-group <group_id> <text>>";
+
+fn parse(args: &[String]) -> Option<AdminRequest> {
+    let request = match args {
+        [cmd] if cmd == "status" => AdminRequest::Status,
+        [cmd] if cmd == "peers" => AdminRequest::Peers,
+        [cmd] if cmd == "groups" => AdminRequest::Groups,
+        [cmd] if cmd == "backups" => AdminRequest::Backups,
+        [cmd] if cmd == "roles" => AdminRequest::RoleScores,
+        [cmd, to, text @ ..] if cmd == "send" && !text.is_empty() => AdminRequest::SendMessage {
+            to: to.clone(),
+            text: text.join(" "),
+        },
+        [cmd, group_id, text @ ..] if cmd == "send-group" && !text.is_empty() => {
+            AdminRequest::SendGroupMessage {
+                group_id: group_id.clone(),
+                text: text.join(" "),
+            }
+        }
+        _ => return None,
+    };
+    Some(request)
+}
+
+#[cfg(unix)]
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (Some(socket), Some(request)) = (args.first(), args.get(1..).and_then(parse)) else {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    };
+
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("tom-admin: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match runtime.block_on(tom_protocol::admin_request(socket, &request)) {
+        Ok(AdminResponse::Ok(result)) => {
+            if !result.is_null() {
+                println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default());
+            }
+            ExitCode::SUCCESS
+        }
+        Ok(AdminResponse::Error(e)) => {
+            eprintln!("tom-admin: {e}");
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("tom-admin: {socket}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn main() -> ExitCode {
+    let _ = parse;
+    eprintln!("tom-admin: Unix domain sockets are not supported on this platform");
+    ExitCode::FAILURE
+}
@@ -0,0 +1,124 @@
+/// Capability handshake — protocol version and optional features per peer.
+///
+/// On first contact each side sends a signed `MessageType::Capabilities`
+/// envelope; the answer carries the other side's. The result is cached in
+/// the topology so senders pick encodings the peer understands instead of
+/// guessing (e.g. no real-time datagrams to a peer without them).
+///
+/// Features travel as a bitmask: bits a node doesn't know are kept but
+/// ignored, so newer peers can announce features older ones never heard of.
+use serde::{Deserialize, Serialize};
+
+/// Version of the wire protocol spoken by this build.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// How long before an unanswered handshake is offered again (ms): peers
+/// too old to answer aren't asked on every envelope.
+pub const CAPABILITY_RETRY_MS: u64 = 60 * 60 * 1000;
+
+/// Most unanswered handshakes remembered.
+pub const MAX_CAPABILITY_OFFERS: usize = 4096;
+
+/// Optional features a peer may support.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Features(u64);
+
+impl Features {
+    /// Compressed payloads.
+    pub const COMPRESSION: Self = Self(1 << 0);
+    /// Payloads split across several envelopes.
+    pub const FRAGMENTATION: Self = Self(1 << 1);
+    /// Real-time lane over QUIC datagrams.
+    pub const DATAGRAMS: Self = Self(1 << 2);
+    /// Post-quantum key exchange.
+    pub const PQ_CRYPTO: Self = Self(1 << 3);
+    /// File transfers over dedicated QUIC streams.
+    pub const FILE_TRANSFER: Self = Self(1 << 4);
+
+    /// What this build supports.
+    pub const LOCAL: Self = Self(Self::DATAGRAMS.0 | Self::FILE_TRANSFER.0);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Whether every feature of `other` is in `self`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Features both sides have.
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+impl std::ops::BitOr for Features {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// What a peer told us it speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerCapabilities {
+    pub protocol_version: u16,
+    pub features: Features,
+}
+
+impl PeerCapabilities {
+    /// This build's capabilities.
+    pub fn local() -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            features: Features::LOCAL,
+        }
+    }
+
+    pub fn supports(&self, feature: Features) -> bool {
+        self.features.contains(feature)
+    }
+}
+
+/// Payload of a `MessageType::Capabilities` envelope.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityHandshake {
+    pub capabilities: PeerCapabilities,
+    /// Answer to their handshake (not answered again).
+    pub reply: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_feature_bits_survive_and_are_ignored() {
+        let newer = Features::from_bits(Features::LOCAL.bits() | 1 << 40);
+        let handshake = CapabilityHandshake {
+            capabilities: PeerCapabilities {
+                protocol_version: PROTOCOL_VERSION + 1,
+                features: newer,
+            },
+            reply: false,
+        };
+        let bytes = rmp_serde::to_vec(&handshake).unwrap();
+        let decoded: CapabilityHandshake = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, handshake);
+
+        assert!(decoded.capabilities.supports(Features::DATAGRAMS));
+        assert!(!decoded.capabilities.supports(Features::PQ_CRYPTO));
+        assert_eq!(newer.intersection(Features::LOCAL), Features::LOCAL);
+        assert!((Features::COMPRESSION | Features::FRAGMENTATION).contains(Features::COMPRESSION));
+    }
+}
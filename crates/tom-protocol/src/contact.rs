@@ -0,0 +1,488 @@
+/// Contact cards — introduce one peer to another.
+///
+/// A card carries a node's identity (NodeId + display name) and a short
+/// verification fingerprint derived from the NodeId, which two people can
+/// compare out of band. Cards travel as typed chat payloads 1:1 or as
+/// `tom-contact:` text inside a group message; the receiver decides whether
+/// to import them into its contact roster.
+///
+/// The roster itself is a [`ContactStore`]: each entry wraps the card with
+/// what only we know about the contact — alias, trust, block flag, last seen
+/// and how the conversation is encrypted.
+///
+/// Encryption is negotiated per conversation: each side sends an
+/// [`EncryptionHandshake`] with the mode it wants, and both use the stronger
+/// of the two. Either side can insist on encryption; turning it off takes both.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::payload::PayloadSchema;
+use crate::types::NodeId;
+use crate::TomProtocolError;
+
+/// Text prefix of an encoded contact card (group messages, copy/paste).
+pub const CONTACT_CARD_PREFIX: &str = "tom-contact:";
+
+/// Content type of contact cards sent as typed chat payloads.
+pub const CONTENT_TYPE_CONTACT_CARD: &str = "tom/contact-card";
+
+/// Maximum length of a contact's display name (bytes).
+pub const MAX_CONTACT_USERNAME_LEN: usize = 64;
+
+/// Maximum length of a local alias (bytes).
+pub const MAX_CONTACT_ALIAS_LEN: usize = 64;
+
+/// Most entries in the roster (blocked peers included).
+pub const MAX_CONTACTS: usize = 10_000;
+
+/// Domain separator for fingerprints.
+const FINGERPRINT_DOMAIN: &[u8] = b"tom-contact-fingerprint-v1";
+
+/// A shareable identity card for a node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContactCard {
+    pub node_id: NodeId,
+    pub username: String,
+    /// Verification fingerprint of `node_id` (see [`fingerprint`]).
+    pub fingerprint: String,
+}
+
+impl ContactCard {
+    /// Card for `node_id`, with its fingerprint computed.
+    pub fn new(node_id: NodeId, username: String) -> Self {
+        Self {
+            fingerprint: fingerprint(&node_id),
+            node_id,
+            username,
+        }
+    }
+
+    /// Check the fingerprint matches the NodeId and the name fits.
+    pub fn validate(&self) -> Result<(), TomProtocolError> {
+        if self.username.len() > MAX_CONTACT_USERNAME_LEN {
+            return Err(TomProtocolError::InvalidContactCard {
+                reason: format!("username longer than {MAX_CONTACT_USERNAME_LEN} bytes"),
+            });
+        }
+        if self.fingerprint != fingerprint(&self.node_id) {
+            return Err(TomProtocolError::InvalidContactCard {
+                reason: "fingerprint mismatch".into(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Encode as text (`tom-contact:<base64url>`), e.g. to post in a group.
+    pub fn encode(&self) -> Result<String, TomProtocolError> {
+        let bytes = rmp_serde::to_vec(self)?;
+        Ok(format!(
+            "{CONTACT_CARD_PREFIX}{}",
+            data_encoding::BASE64URL_NOPAD.encode(&bytes)
+        ))
+    }
+
+    /// Decode text produced by [`ContactCard::encode`]. Does not check the
+    /// fingerprint — use [`ContactCard::validate`] for that.
+    pub fn decode(s: &str) -> Result<Self, TomProtocolError> {
+        let body = s.trim().strip_prefix(CONTACT_CARD_PREFIX).ok_or_else(|| {
+            TomProtocolError::InvalidContactCard {
+                reason: "missing prefix".into(),
+            }
+        })?;
+        let bytes = data_encoding::BASE64URL_NOPAD
+            .decode(body.as_bytes())
+            .map_err(|e| TomProtocolError::InvalidContactCard {
+                reason: format!("bad encoding: {e}"),
+            })?;
+        Ok(rmp_serde::from_slice(&bytes)?)
+    }
+}
+
+impl PayloadSchema for ContactCard {
+    const CONTENT_TYPE: &'static str = CONTENT_TYPE_CONTACT_CARD;
+}
+
+/// How far we trust that a contact is who they claim to be.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrustLevel {
+    /// Imported from a card nobody checked.
+    #[default]
+    Unverified,
+    /// Fingerprint compared out of band.
+    Verified,
+}
+
+/// How a conversation's envelopes are protected. Ordered weakest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum EncryptionMode {
+    /// Signed only.
+    Plaintext,
+    /// Signed and sealed to the recipient's static key.
+    Static,
+}
+
+impl EncryptionMode {
+    /// What the global `encryption` flag means for conversations without
+    /// a setting of their own.
+    pub fn from_flag(encrypt: bool) -> Self {
+        if encrypt {
+            Self::Static
+        } else {
+            Self::Plaintext
+        }
+    }
+}
+
+/// Modes this node can speak, weakest first.
+pub const SUPPORTED_ENCRYPTION_MODES: &[EncryptionMode] =
+    &[EncryptionMode::Plaintext, EncryptionMode::Static];
+
+/// Capability handshake: the mode we want for the conversation and every
+/// mode we can speak (room for a ratchet mode later).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptionHandshake {
+    pub preferred: EncryptionMode,
+    pub supported: Vec<EncryptionMode>,
+    /// Answer to their handshake (not answered again).
+    pub reply: bool,
+}
+
+/// A roster entry: the contact's card plus our local view of it.
+///
+/// Serialized flat, so rows written as plain cards still load.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contact {
+    #[serde(flatten)]
+    pub card: ContactCard,
+    /// Local display name overriding the card's username.
+    #[serde(default)]
+    pub alias: Option<String>,
+    #[serde(default)]
+    pub trust: TrustLevel,
+    /// Envelopes from blocked contacts are dropped on arrival.
+    #[serde(default)]
+    pub blocked: bool,
+    /// Last time we received a signed envelope from them (ms).
+    #[serde(default)]
+    pub last_seen: Option<u64>,
+    /// Encryption we want with them (None = the global `encryption` flag).
+    #[serde(default)]
+    pub encryption: Option<EncryptionMode>,
+    /// Encryption they asked for in their last handshake.
+    #[serde(default)]
+    pub peer_encryption: Option<EncryptionMode>,
+}
+
+impl Contact {
+    pub fn new(card: ContactCard) -> Self {
+        Self {
+            card,
+            alias: None,
+            trust: TrustLevel::Unverified,
+            blocked: false,
+            last_seen: None,
+            encryption: None,
+            peer_encryption: None,
+        }
+    }
+
+    pub fn node_id(&self) -> NodeId {
+        self.card.node_id
+    }
+
+    /// Alias if set, else the card's username.
+    pub fn display_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.card.username)
+    }
+}
+
+/// The contact roster. Pure state: the runtime persists it.
+#[derive(Debug, Default)]
+pub struct ContactStore {
+    contacts: HashMap<NodeId, Contact>,
+}
+
+impl ContactStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a contact or refresh its card, keeping alias/trust/block/last seen.
+    pub fn add(&mut self, card: ContactCard) -> Result<&Contact, TomProtocolError> {
+        card.validate()?;
+        if !self.contacts.contains_key(&card.node_id) && self.contacts.len() >= MAX_CONTACTS {
+            return Err(TomProtocolError::InvalidContactCard {
+                reason: format!("roster full ({MAX_CONTACTS} contacts)"),
+            });
+        }
+        let node_id = card.node_id;
+        let contact = self
+            .contacts
+            .entry(node_id)
+            .and_modify(|c| c.card = card.clone())
+            .or_insert_with(|| Contact::new(card));
+        Ok(contact)
+    }
+
+    /// Remove a contact (also lifts a block). Returns it if it existed.
+    pub fn remove(&mut self, node_id: &NodeId) -> Option<Contact> {
+        self.contacts.remove(node_id)
+    }
+
+    /// Block or unblock a node. Blocking someone not in the roster adds a
+    /// nameless entry so the block persists. Returns the entry if changed.
+    pub fn set_blocked(&mut self, node_id: NodeId, blocked: bool) -> Option<&Contact> {
+        if !self.contacts.contains_key(&node_id) {
+            if !blocked || self.contacts.len() >= MAX_CONTACTS {
+                return None;
+            }
+            self.contacts
+                .insert(node_id, Contact::new(ContactCard::new(node_id, String::new())));
+        }
+        let contact = self.contacts.get_mut(&node_id)?;
+        if contact.blocked == blocked {
+            return None;
+        }
+        contact.blocked = blocked;
+        Some(contact)
+    }
+
+    /// Set or clear a contact's alias (trimmed, empty clears).
+    pub fn set_alias(&mut self, node_id: &NodeId, alias: Option<String>) -> Result<&Contact, TomProtocolError> {
+        let alias = alias.map(|a| a.trim().to_string()).filter(|a| !a.is_empty());
+        if alias.as_ref().is_some_and(|a| a.len() > MAX_CONTACT_ALIAS_LEN) {
+            return Err(TomProtocolError::InvalidContactCard {
+                reason: format!("alias longer than {MAX_CONTACT_ALIAS_LEN} bytes"),
+            });
+        }
+        let contact = self.get_mut(node_id)?;
+        contact.alias = alias;
+        Ok(contact)
+    }
+
+    /// Mark a contact verified (or back to unverified).
+    pub fn set_trust(&mut self, node_id: &NodeId, trust: TrustLevel) -> Result<&Contact, TomProtocolError> {
+        let contact = self.get_mut(node_id)?;
+        contact.trust = trust;
+        Ok(contact)
+    }
+
+    /// Set or clear (None) the encryption we want with a contact.
+    pub fn set_encryption(
+        &mut self,
+        node_id: &NodeId,
+        mode: Option<EncryptionMode>,
+    ) -> Result<&Contact, TomProtocolError> {
+        let contact = self.get_mut(node_id)?;
+        contact.encryption = mode;
+        Ok(contact)
+    }
+
+    /// Record the mode a contact asked for. Returns the entry if changed
+    /// (strangers' handshakes aren't remembered).
+    pub fn set_peer_encryption(&mut self, node_id: &NodeId, mode: EncryptionMode) -> Option<&Contact> {
+        let contact = self.contacts.get_mut(node_id)?;
+        if contact.peer_encryption == Some(mode) {
+            return None;
+        }
+        contact.peer_encryption = Some(mode);
+        Some(contact)
+    }
+
+    /// The negotiated mode with `node_id`: the stronger of what we and they
+    /// asked for, `default` standing in for our side when unset.
+    pub fn encryption_mode(&self, node_id: &NodeId, default: EncryptionMode) -> EncryptionMode {
+        match self.contacts.get(node_id) {
+            Some(contact) => {
+                let ours = contact.encryption.unwrap_or(default);
+                contact.peer_encryption.map_or(ours, |theirs| ours.max(theirs))
+            }
+            None => default,
+        }
+    }
+
+    /// Record that we heard from a contact (no-op for strangers).
+    pub fn touch(&mut self, node_id: &NodeId, now: u64) {
+        if let Some(contact) = self.contacts.get_mut(node_id) {
+            contact.last_seen = Some(now);
+        }
+    }
+
+    pub fn get(&self, node_id: &NodeId) -> Option<&Contact> {
+        self.contacts.get(node_id)
+    }
+
+    pub fn is_blocked(&self, node_id: &NodeId) -> bool {
+        self.contacts.get(node_id).is_some_and(|c| c.blocked)
+    }
+
+    /// Blocked nodes.
+    pub fn blocked(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.contacts.values().filter(|c| c.blocked).map(Contact::node_id)
+    }
+
+    pub fn contacts(&self) -> impl Iterator<Item = &Contact> {
+        self.contacts.values()
+    }
+
+    /// Full roster, for snapshots.
+    pub fn snapshot(&self) -> HashMap<NodeId, Contact> {
+        self.contacts.clone()
+    }
+
+    /// Restore a persisted roster (replaces the current one).
+    pub fn restore(&mut self, contacts: HashMap<NodeId, Contact>) {
+        self.contacts = contacts;
+    }
+
+    pub fn len(&self) -> usize {
+        self.contacts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contacts.is_empty()
+    }
+
+    fn get_mut(&mut self, node_id: &NodeId) -> Result<&mut Contact, TomProtocolError> {
+        self.contacts
+            .get_mut(node_id)
+            .ok_or_else(|| TomProtocolError::InvalidContactCard {
+                reason: format!("unknown contact {node_id}"),
+            })
+    }
+}
+
+/// Human-comparable fingerprint of a NodeId: the first 10 bytes of a
+/// domain-separated SHA-256, as five groups of four hex digits.
+pub fn fingerprint(node_id: &NodeId) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(FINGERPRINT_DOMAIN);
+    hasher.update(node_id.as_bytes());
+    let digest = hasher.finalize();
+    digest[..10]
+        .chunks(2)
+        .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn fingerprint_is_stable_and_distinct() {
+        let fp = fingerprint(&node_id(1));
+        assert_eq!(fp, fingerprint(&node_id(1)));
+        assert_ne!(fp, fingerprint(&node_id(2)));
+        assert_eq!(fp.len(), 24);
+        assert_eq!(fp.split(' ').count(), 5);
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let card = ContactCard::new(node_id(1), "alice".into());
+        let encoded = card.encode().unwrap();
+        assert!(encoded.starts_with(CONTACT_CARD_PREFIX));
+        let decoded = ContactCard::decode(&encoded).unwrap();
+        assert_eq!(decoded, card);
+        assert!(decoded.validate().is_ok());
+    }
+
+    #[test]
+    fn tampered_card_fails_validation() {
+        let mut card = ContactCard::new(node_id(1), "alice".into());
+        card.node_id = node_id(2);
+        assert!(matches!(
+            card.validate(),
+            Err(TomProtocolError::InvalidContactCard { .. })
+        ));
+
+        let long = ContactCard::new(node_id(1), "x".repeat(MAX_CONTACT_USERNAME_LEN + 1));
+        assert!(long.validate().is_err());
+    }
+
+    #[test]
+    fn store_refresh_keeps_local_state() {
+        let mut store = ContactStore::new();
+        let carol = node_id(3);
+        store.add(ContactCard::new(carol, "carol".into())).unwrap();
+        store.set_alias(&carol, Some("  C  ".into())).unwrap();
+        store.set_trust(&carol, TrustLevel::Verified).unwrap();
+        store.touch(&carol, 42);
+
+        // A fresher card updates the username only
+        store.add(ContactCard::new(carol, "caroline".into())).unwrap();
+        let contact = store.get(&carol).unwrap();
+        assert_eq!(contact.card.username, "caroline");
+        assert_eq!(contact.display_name(), "C");
+        assert_eq!(contact.trust, TrustLevel::Verified);
+        assert_eq!(contact.last_seen, Some(42));
+
+        assert!(store.set_alias(&node_id(4), Some("x".into())).is_err());
+        assert!(store.set_alias(&carol, Some("x".repeat(MAX_CONTACT_ALIAS_LEN + 1))).is_err());
+    }
+
+    #[test]
+    fn blocking_strangers_adds_entries_and_unblock_keeps_contacts() {
+        let mut store = ContactStore::new();
+        let mallory = node_id(5);
+        assert!(store.set_blocked(mallory, false).is_none());
+        assert!(store.set_blocked(mallory, true).is_some());
+        assert!(store.is_blocked(&mallory));
+        assert_eq!(store.blocked().collect::<Vec<_>>(), vec![mallory]);
+
+        assert!(store.set_blocked(mallory, false).is_some());
+        assert!(!store.is_blocked(&mallory));
+        assert!(store.remove(&mallory).is_some());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn plain_card_json_loads_as_contact() {
+        let card = ContactCard::new(node_id(1), "alice".into());
+        let json = serde_json::to_string(&card).unwrap();
+        let contact: Contact = serde_json::from_str(&json).unwrap();
+        assert_eq!(contact, Contact::new(card));
+    }
+
+    #[test]
+    fn encryption_negotiates_to_the_stronger_side() {
+        use EncryptionMode::*;
+        let mut store = ContactStore::new();
+        let dave = node_id(6);
+        assert_eq!(store.encryption_mode(&dave, Static), Static);
+        assert!(store.set_encryption(&dave, Some(Plaintext)).is_err());
+        assert!(store.set_peer_encryption(&dave, Plaintext).is_none());
+
+        store.add(ContactCard::new(dave, "dave".into())).unwrap();
+        store.set_encryption(&dave, Some(Plaintext)).unwrap();
+        assert_eq!(store.encryption_mode(&dave, Static), Plaintext);
+
+        // They insist: encrypted anyway
+        assert!(store.set_peer_encryption(&dave, Static).is_some());
+        assert!(store.set_peer_encryption(&dave, Static).is_none());
+        assert_eq!(store.encryption_mode(&dave, Static), Static);
+
+        // Off only when both want it off
+        store.set_peer_encryption(&dave, Plaintext);
+        assert_eq!(store.encryption_mode(&dave, Static), Plaintext);
+        store.set_encryption(&dave, None).unwrap();
+        assert_eq!(store.encryption_mode(&dave, Static), Static);
+    }
+
+    #[test]
+    fn decode_rejects_garbage() {
+        assert!(ContactCard::decode("hello").is_err());
+        assert!(ContactCard::decode("tom-contact:!!!").is_err());
+    }
+}
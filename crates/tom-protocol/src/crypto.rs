@@ -156,6 +156,16 @@ pub fn decrypt(
     Ok(plaintext)
 }
 
+/// Derive a symmetric key for local at-rest encryption from the node's
+/// Ed25519 seed. `context` separates independent uses of the same seed.
+pub fn derive_storage_key(secret_seed: &[u8; 32], context: &[u8]) -> [u8; 32] {
+    let hkdf = Hkdf::<Sha256>::new(Some(b"tom-protocol-at-rest"), secret_seed);
+    let mut key = [0u8; 32];
+    hkdf.expand(context, &mut key)
+        .expect("HKDF-SHA256 expand to 32 bytes always succeeds");
+    key
+}
+
 /// Generate a random 32-byte Sender Key for group encryption.
 pub fn generate_sender_key() -> [u8; 32] {
     use chacha20poly1305::aead::rand_core::{OsRng, RngCore};
@@ -404,4 +414,14 @@ mod tests {
         let decrypted = decrypt_group_message(&ciphertext, &nonce, &key).unwrap();
         assert_eq!(decrypted, b"");
     }
+
+    #[test]
+    fn storage_key_is_deterministic_and_separated() {
+        let (seed_a, _) = ed25519_keypair(1);
+        let (seed_b, _) = ed25519_keypair(2);
+        let k = derive_storage_key(&seed_a, b"ctx-1");
+        assert_eq!(k, derive_storage_key(&seed_a, b"ctx-1"));
+        assert_ne!(k, derive_storage_key(&seed_a, b"ctx-2"));
+        assert_ne!(k, derive_storage_key(&seed_b, b"ctx-1"));
+    }
 }
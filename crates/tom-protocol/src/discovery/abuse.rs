@@ -0,0 +1,216 @@
+//! Abuse reports — signed evidence of a security violation, shared via gossip.
+//!
+//! A report names the offender, the kind of violation, and carries the
+//! offending envelope as evidence. Receivers check the reporter's signature
+//! and that the evidence really is attributable to the offender, then apply
+//! a local, decaying reputation penalty. Nobody is ever hard-banned
+//! (design decision #4).
+
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+
+use crate::envelope::Envelope;
+use crate::types::NodeId;
+
+use super::types::MAX_FUTURE_DRIFT_MS;
+
+/// Largest evidence accepted in a report (the offending envelope).
+pub const MAX_EVIDENCE_BYTES: usize = 16 * 1024;
+
+/// Reports older than this are ignored (1 hour).
+pub const MAX_REPORT_AGE_MS: u64 = 60 * 60 * 1000;
+
+/// At most one report per (reporter, offender) counts per interval (10 min).
+pub const REPORT_INTERVAL_MS: u64 = 10 * 60 * 1000;
+
+/// The security violation a report refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ViolationKind {
+    /// An envelope claiming to be from the offender failed verification.
+    BadSignature,
+    /// The offender re-sent an envelope it had already sent.
+    Replay,
+    /// The offender exceeded its rate limit.
+    Spam,
+}
+
+/// Signed report of a violation by `offender`, broadcast by `reporter`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbuseReport {
+    pub reporter: NodeId,
+    pub offender: NodeId,
+    pub kind: ViolationKind,
+    /// The offending envelope, as received.
+    pub evidence: Vec<u8>,
+    pub timestamp: u64,
+    pub signature: Vec<u8>,
+}
+
+impl AbuseReport {
+    /// Create and sign a report.
+    pub fn new(
+        reporter: NodeId,
+        offender: NodeId,
+        kind: ViolationKind,
+        evidence: Vec<u8>,
+        timestamp: u64,
+        secret_seed: &[u8; 32],
+    ) -> Self {
+        let mut report = Self {
+            reporter,
+            offender,
+            kind,
+            evidence,
+            timestamp,
+            signature: Vec::new(),
+        };
+        let signing_key = SigningKey::from_bytes(secret_seed);
+        report.signature = signing_key.sign(&report.signing_bytes()).to_bytes().to_vec();
+        report
+    }
+
+    /// Verify the signature against the reporter (public key).
+    pub fn verify_signature(&self) -> bool {
+        use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+        let Ok(verifying_key) = VerifyingKey::from_bytes(&self.reporter.as_bytes()) else {
+            return false;
+        };
+        let Ok(sig_bytes) = <[u8; 64]>::try_from(self.signature.as_slice()) else {
+            return false;
+        };
+        verifying_key
+            .verify(&self.signing_bytes(), &Signature::from_bytes(&sig_bytes))
+            .is_ok()
+    }
+
+    /// Whether the evidence backs the claim: an envelope from the offender
+    /// whose signature fails for `BadSignature`, and is valid otherwise —
+    /// replayed or spammed envelopes were genuinely signed by the offender.
+    pub fn evidence_matches(&self) -> bool {
+        if self.evidence.len() > MAX_EVIDENCE_BYTES {
+            return false;
+        }
+        let Ok(envelope) = Envelope::from_bytes(&self.evidence) else {
+            return false;
+        };
+        if envelope.from != self.offender || !envelope.is_signed() {
+            return false;
+        }
+        let signed_by_offender = envelope.verify_signature().is_ok();
+        match self.kind {
+            ViolationKind::BadSignature => !signed_by_offender,
+            ViolationKind::Replay | ViolationKind::Spam => signed_by_offender,
+        }
+    }
+
+    /// Reject reports from the future or older than `MAX_REPORT_AGE_MS`.
+    pub fn is_timestamp_valid(&self, now: u64) -> bool {
+        self.timestamp <= now + MAX_FUTURE_DRIFT_MS
+            && now.saturating_sub(self.timestamp) <= MAX_REPORT_AGE_MS
+    }
+
+    /// Get bytes to sign (excludes signature field).
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 + 32 + 1 + 8 + self.evidence.len());
+        bytes.extend_from_slice(&self.reporter.as_bytes());
+        bytes.extend_from_slice(&self.offender.as_bytes());
+        bytes.push(match self.kind {
+            ViolationKind::BadSignature => 0,
+            ViolationKind::Replay => 1,
+            ViolationKind::Spam => 2,
+        });
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes.extend_from_slice(&self.evidence);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MessageType;
+    use rand::SeedableRng;
+
+    fn identity(seed: u64) -> (NodeId, [u8; 32]) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        (secret.public().to_string().parse().unwrap(), secret.to_bytes())
+    }
+
+    fn signed_envelope(from: NodeId, seed: &[u8; 32], to: NodeId) -> Envelope {
+        let mut envelope = Envelope::new(from, to, MessageType::Chat, b"hi".to_vec());
+        envelope.sign(seed);
+        envelope
+    }
+
+    #[test]
+    fn sign_verify_and_tamper() {
+        let (reporter, reporter_seed) = identity(1);
+        let (offender, offender_seed) = identity(2);
+        let evidence = signed_envelope(offender, &offender_seed, reporter).to_bytes().unwrap();
+
+        let mut report =
+            AbuseReport::new(reporter, offender, ViolationKind::Spam, evidence, 1_000, &reporter_seed);
+        assert!(report.verify_signature());
+        assert!(report.evidence_matches());
+
+        report.kind = ViolationKind::Replay;
+        assert!(!report.verify_signature());
+    }
+
+    #[test]
+    fn evidence_must_be_attributable_to_offender() {
+        let (reporter, reporter_seed) = identity(1);
+        let (offender, offender_seed) = identity(2);
+        let (other, other_seed) = identity(3);
+
+        // Someone else's envelope proves nothing about the offender
+        let foreign = signed_envelope(other, &other_seed, reporter).to_bytes().unwrap();
+        let report =
+            AbuseReport::new(reporter, offender, ViolationKind::Spam, foreign, 1_000, &reporter_seed);
+        assert!(!report.evidence_matches());
+
+        // A valid envelope is not evidence of a bad signature...
+        let valid = signed_envelope(offender, &offender_seed, reporter);
+        let report = AbuseReport::new(
+            reporter,
+            offender,
+            ViolationKind::BadSignature,
+            valid.to_bytes().unwrap(),
+            1_000,
+            &reporter_seed,
+        );
+        assert!(!report.evidence_matches());
+
+        // ...but a tampered one is
+        let mut tampered = valid;
+        tampered.payload = b"changed".to_vec();
+        let report = AbuseReport::new(
+            reporter,
+            offender,
+            ViolationKind::BadSignature,
+            tampered.to_bytes().unwrap(),
+            1_000,
+            &reporter_seed,
+        );
+        assert!(report.evidence_matches());
+    }
+
+    #[test]
+    fn oversized_or_stale_reports_rejected() {
+        let (reporter, reporter_seed) = identity(1);
+        let (offender, _) = identity(2);
+        let report = AbuseReport::new(
+            reporter,
+            offender,
+            ViolationKind::Spam,
+            vec![0; MAX_EVIDENCE_BYTES + 1],
+            1_000,
+            &reporter_seed,
+        );
+        assert!(!report.evidence_matches());
+        assert!(report.is_timestamp_valid(1_000 + MAX_REPORT_AGE_MS));
+        assert!(!report.is_timestamp_valid(1_001 + MAX_REPORT_AGE_MS));
+    }
+}
@@ -0,0 +1,134 @@
+//! Relay attestations — "these relays forwarded my messages", via gossip.
+//!
+//! Local scoring only sees relays we used ourselves. Each node periodically
+//! signs and gossips how many of its messages each relay forwarded since
+//! the last attestation, so scores converge across the network. Receivers
+//! weight an attestation by the attester's own standing (see
+//! `RoleManager::record_attestation`): fresh identities count for nothing.
+
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+
+use crate::types::NodeId;
+
+use super::types::MAX_FUTURE_DRIFT_MS;
+
+/// Most relays listed in one attestation.
+pub const MAX_ATTESTED_RELAYS: usize = 32;
+
+/// At most one attestation per attester counts per interval (1 minute).
+pub const ATTESTATION_INTERVAL_MS: u64 = 60_000;
+
+/// Attestations older than this are ignored (10 minutes).
+pub const MAX_ATTESTATION_AGE_MS: u64 = 10 * 60 * 1000;
+
+/// Signed batch of relays that forwarded `attester`'s messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayAttestation {
+    pub attester: NodeId,
+    /// (relay, messages it forwarded for us since the last attestation).
+    pub relays: Vec<(NodeId, u32)>,
+    pub timestamp: u64,
+    pub signature: Vec<u8>,
+}
+
+impl RelayAttestation {
+    /// Create and sign an attestation (truncated to `MAX_ATTESTED_RELAYS`).
+    pub fn new(
+        attester: NodeId,
+        mut relays: Vec<(NodeId, u32)>,
+        timestamp: u64,
+        secret_seed: &[u8; 32],
+    ) -> Self {
+        relays.truncate(MAX_ATTESTED_RELAYS);
+        let mut attestation = Self {
+            attester,
+            relays,
+            timestamp,
+            signature: Vec::new(),
+        };
+        let signing_key = SigningKey::from_bytes(secret_seed);
+        attestation.signature = signing_key
+            .sign(&attestation.signing_bytes())
+            .to_bytes()
+            .to_vec();
+        attestation
+    }
+
+    /// Verify the signature against the attester (public key).
+    pub fn verify_signature(&self) -> bool {
+        use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+        let Ok(verifying_key) = VerifyingKey::from_bytes(&self.attester.as_bytes()) else {
+            return false;
+        };
+        let Ok(sig_bytes) = <[u8; 64]>::try_from(self.signature.as_slice()) else {
+            return false;
+        };
+        verifying_key
+            .verify(&self.signing_bytes(), &Signature::from_bytes(&sig_bytes))
+            .is_ok()
+    }
+
+    /// Well-formed: within `MAX_ATTESTED_RELAYS`, no self-attestation,
+    /// timestamp neither from the future nor older than `MAX_ATTESTATION_AGE_MS`.
+    pub fn is_valid(&self, now: u64) -> bool {
+        self.relays.len() <= MAX_ATTESTED_RELAYS
+            && self.relays.iter().all(|(relay, _)| *relay != self.attester)
+            && self.timestamp <= now + MAX_FUTURE_DRIFT_MS
+            && now.saturating_sub(self.timestamp) <= MAX_ATTESTATION_AGE_MS
+    }
+
+    /// Get bytes to sign (excludes signature field).
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 + 8 + self.relays.len() * 36);
+        bytes.extend_from_slice(&self.attester.as_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        for (relay, count) in &self.relays {
+            bytes.extend_from_slice(&relay.as_bytes());
+            bytes.extend_from_slice(&count.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn identity(seed: u64) -> (NodeId, [u8; 32]) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        (secret.public().to_string().parse().unwrap(), secret.to_bytes())
+    }
+
+    #[test]
+    fn sign_verify_and_tamper() {
+        let (attester, seed) = identity(1);
+        let (relay, _) = identity(2);
+        let mut attestation = RelayAttestation::new(attester, vec![(relay, 3)], 1_000, &seed);
+        assert!(attestation.verify_signature());
+        assert!(attestation.is_valid(1_000));
+
+        attestation.relays[0].1 = 300;
+        assert!(!attestation.verify_signature());
+    }
+
+    #[test]
+    fn malformed_attestations_are_invalid() {
+        let (attester, seed) = identity(1);
+        let (relay, _) = identity(2);
+
+        let selfish = RelayAttestation::new(attester, vec![(attester, 5)], 1_000, &seed);
+        assert!(!selfish.is_valid(1_000));
+
+        let old = RelayAttestation::new(attester, vec![(relay, 1)], 1_000, &seed);
+        assert!(!old.is_valid(1_001 + MAX_ATTESTATION_AGE_MS));
+
+        let many = (0..MAX_ATTESTED_RELAYS as u64 + 5).map(|i| (identity(10 + i).0, 1)).collect();
+        let capped = RelayAttestation::new(attester, many, 1_000, &seed);
+        assert_eq!(capped.relays.len(), MAX_ATTESTED_RELAYS);
+        assert!(capped.verify_signature());
+    }
+}
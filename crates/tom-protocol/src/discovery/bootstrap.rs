@@ -0,0 +1,391 @@
+/// Startup bootstrap — a fixed fallback chain for finding the first peers.
+///
+/// Stages run one at a time, in order, each until it hears from a peer or
+/// its timeout passes:
+///
+/// 1. **Static** — dial the configured `static_peers` addresses.
+/// 2. **LocalNetwork** — multicast our addresses on the LAN and dial every
+///    node that answers (an mDNS-style beacon on [`LAN_DISCOVERY_GROUP`]).
+/// 3. **Dht** — resolve `gossip_bootstrap_peers` ids through the DHT.
+/// 4. **Gossip** — rejoin the discovery topic and wait for a neighbor.
+///
+/// Stages without configuration are skipped. A peer "heard from" is a
+/// gossip `NeighborUp` or an incoming envelope. The first stage that hears
+/// from anyone ends the pipeline with `BootstrapCompleted { source }`; if
+/// none does, it ends with `source: None` and the node keeps listening.
+///
+/// The pipeline itself is pure (time passed in); the runtime loop performs
+/// each stage's action when the pipeline enters it.
+use std::collections::{HashSet, VecDeque};
+use std::net::{Ipv4Addr, SocketAddr};
+#[cfg(feature = "native")]
+use std::net::SocketAddrV4;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tom_base::{EndpointAddr, TransportAddr};
+
+use crate::types::NodeId;
+
+/// Multicast group of LAN beacons (organization-local scope).
+pub const LAN_DISCOVERY_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 77, 77);
+
+/// Default UDP port of LAN beacons.
+pub const LAN_DISCOVERY_PORT: u16 = 45_477;
+
+/// How often we multicast our beacon during the LocalNetwork stage.
+#[cfg(feature = "native")]
+const LAN_BEACON_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Prefix of every beacon datagram (anything else on the port is ignored).
+const LAN_BEACON_MAGIC: &[u8; 6] = b"TOMLAN";
+
+/// Largest beacon we read.
+#[cfg(feature = "native")]
+const MAX_BEACON_BYTES: usize = 1024;
+
+/// Most addresses a beacon carries.
+const MAX_BEACON_ADDRS: usize = 8;
+
+/// Where the first peers came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BootstrapSource {
+    Static,
+    LocalNetwork,
+    Dht,
+    Gossip,
+}
+
+/// Bootstrap stages and their timeouts.
+#[derive(Debug, Clone)]
+pub struct BootstrapConfig {
+    /// Peers dialed first, by address (e.g. a known server).
+    pub static_peers: Vec<EndpointAddr>,
+    /// Look for peers on the local network (UDP multicast beacons).
+    pub local_network: bool,
+    /// UDP port of LAN beacons.
+    pub local_network_port: u16,
+    pub static_timeout: Duration,
+    pub local_network_timeout: Duration,
+    pub dht_timeout: Duration,
+    pub gossip_timeout: Duration,
+}
+
+impl Default for BootstrapConfig {
+    fn default() -> Self {
+        Self {
+            static_peers: Vec::new(),
+            local_network: false,
+            local_network_port: LAN_DISCOVERY_PORT,
+            static_timeout: Duration::from_secs(5),
+            local_network_timeout: Duration::from_secs(3),
+            dht_timeout: Duration::from_secs(10),
+            gossip_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl BootstrapConfig {
+    /// The stages that apply, in order, with their timeouts. The DHT stage
+    /// needs the DHT and ids to resolve; Gossip always runs last.
+    pub fn stages(&self, dht_enabled: bool, has_bootstrap_ids: bool) -> Vec<(BootstrapSource, Duration)> {
+        let mut stages = Vec::with_capacity(4);
+        if !self.static_peers.is_empty() {
+            stages.push((BootstrapSource::Static, self.static_timeout));
+        }
+        if self.local_network {
+            stages.push((BootstrapSource::LocalNetwork, self.local_network_timeout));
+        }
+        if dht_enabled && has_bootstrap_ids {
+            stages.push((BootstrapSource::Dht, self.dht_timeout));
+        }
+        stages.push((BootstrapSource::Gossip, self.gossip_timeout));
+        stages
+    }
+}
+
+/// What the runtime must do next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BootstrapStep {
+    /// Perform this stage's action.
+    Enter(BootstrapSource),
+    /// Done: emit `BootstrapCompleted`.
+    Completed {
+        source: Option<BootstrapSource>,
+        peers_found: usize,
+    },
+}
+
+/// The fallback chain's progress.
+#[derive(Debug, Default)]
+pub struct BootstrapPipeline {
+    pending: VecDeque<(BootstrapSource, Duration)>,
+    current: Option<BootstrapSource>,
+    /// Peers the current stage targets; it ends early once all are heard.
+    expected: HashSet<NodeId>,
+    found: HashSet<NodeId>,
+    deadline: u64,
+    started: bool,
+}
+
+impl BootstrapPipeline {
+    pub fn new(stages: Vec<(BootstrapSource, Duration)>) -> Self {
+        Self {
+            pending: stages.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Enter the first stage.
+    pub fn start(&mut self, now: u64) -> BootstrapStep {
+        self.started = true;
+        self.advance(now)
+    }
+
+    /// Peers the current stage dials: it completes as soon as all of them
+    /// have been heard from, without waiting for its timeout.
+    pub fn expect(&mut self, peers: impl IntoIterator<Item = NodeId>) {
+        self.expected.extend(peers);
+    }
+
+    /// We heard from `peer`.
+    pub fn on_peer(&mut self, peer: NodeId) -> Option<BootstrapStep> {
+        let source = self.current?;
+        self.found.insert(peer);
+        if self.expected.iter().all(|id| self.found.contains(id)) {
+            return Some(self.complete(Some(source)));
+        }
+        None
+    }
+
+    /// Check the current stage's timeout.
+    pub fn tick(&mut self, now: u64) -> Option<BootstrapStep> {
+        let source = self.current?;
+        if now < self.deadline {
+            return None;
+        }
+        if !self.found.is_empty() {
+            return Some(self.complete(Some(source)));
+        }
+        Some(self.advance(now))
+    }
+
+    fn advance(&mut self, now: u64) -> BootstrapStep {
+        self.expected.clear();
+        match self.pending.pop_front() {
+            Some((source, timeout)) => {
+                self.current = Some(source);
+                self.deadline = now + timeout.as_millis() as u64;
+                BootstrapStep::Enter(source)
+            }
+            None => self.complete(None),
+        }
+    }
+
+    fn complete(&mut self, source: Option<BootstrapSource>) -> BootstrapStep {
+        self.current = None;
+        self.pending.clear();
+        BootstrapStep::Completed {
+            source,
+            peers_found: self.found.len(),
+        }
+    }
+}
+
+/// LAN beacon: who we are and where to dial us directly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LanBeacon {
+    pub node_id: NodeId,
+    pub direct_addrs: Vec<SocketAddr>,
+}
+
+impl LanBeacon {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = LAN_BEACON_MAGIC.to_vec();
+        // Serializing a NodeId and socket addresses can't fail
+        buf.extend(rmp_serde::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let body = bytes.strip_prefix(LAN_BEACON_MAGIC)?;
+        let beacon: Self = rmp_serde::from_slice(body).ok()?;
+        (beacon.direct_addrs.len() <= MAX_BEACON_ADDRS).then_some(beacon)
+    }
+
+    /// The address to dial. Unauthenticated: QUIC verifies the node id
+    /// when we connect, so a forged beacon only wastes a dial.
+    pub fn to_endpoint_addr(&self) -> EndpointAddr {
+        EndpointAddr {
+            id: *self.node_id.as_endpoint_id(),
+            addrs: self.direct_addrs.iter().copied().map(TransportAddr::Ip).collect(),
+        }
+    }
+}
+
+/// Multicast `local` on the LAN for `duration` and pass on every other
+/// node's beacon. Without the port (another ToM node on this host holds
+/// it) we still announce, so that node dials us.
+#[cfg(feature = "native")]
+pub async fn run_lan_discovery(
+    mut local: LanBeacon,
+    port: u16,
+    duration: Duration,
+    found: tokio::sync::mpsc::Sender<EndpointAddr>,
+) {
+    local.direct_addrs.truncate(MAX_BEACON_ADDRS);
+    let target = SocketAddrV4::new(LAN_DISCOVERY_GROUP, port);
+    let listener = match tokio::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)).await {
+        Ok(socket) => match socket.join_multicast_v4(LAN_DISCOVERY_GROUP, Ipv4Addr::UNSPECIFIED) {
+            Ok(()) => Some(socket),
+            Err(e) => {
+                tracing::debug!("lan discovery: join {LAN_DISCOVERY_GROUP} failed: {e}");
+                None
+            }
+        },
+        Err(e) => {
+            tracing::debug!("lan discovery: port {port} unavailable ({e}), announcing only");
+            None
+        }
+    };
+    let sender = match tokio::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            tracing::warn!("lan discovery: no socket: {e}");
+            return;
+        }
+    };
+
+    let beacon = local.to_bytes();
+    let mut seen = HashSet::new();
+    let mut announce = tokio::time::interval(LAN_BEACON_INTERVAL);
+    let mut buf = vec![0u8; MAX_BEACON_BYTES];
+    let stop = tokio::time::sleep(duration);
+    tokio::pin!(stop);
+    loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = announce.tick() => {
+                if let Err(e) = sender.send_to(&beacon, target).await {
+                    tracing::debug!("lan discovery: beacon send failed: {e}");
+                }
+            }
+            received = async {
+                match listener.as_ref() {
+                    Some(socket) => socket.recv_from(&mut buf).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                let Ok((len, _)) = received else { continue };
+                let Some(beacon) = LanBeacon::from_bytes(&buf[..len]) else { continue };
+                if beacon.node_id == local.node_id || !seen.insert(beacon.node_id) {
+                    continue;
+                }
+                if found.send(beacon.to_endpoint_addr()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn stages_skip_what_isnt_configured() {
+        let mut config = BootstrapConfig::default();
+        let sources = |c: &BootstrapConfig, dht, ids| -> Vec<BootstrapSource> {
+            c.stages(dht, ids).into_iter().map(|(s, _)| s).collect()
+        };
+        assert_eq!(sources(&config, true, false), vec![BootstrapSource::Gossip]);
+
+        config.static_peers.push(EndpointAddr::new(*node_id(1).as_endpoint_id()));
+        config.local_network = true;
+        assert_eq!(
+            sources(&config, true, true),
+            vec![
+                BootstrapSource::Static,
+                BootstrapSource::LocalNetwork,
+                BootstrapSource::Dht,
+                BootstrapSource::Gossip,
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_until_a_stage_hears_from_a_peer() {
+        let mut pipeline = BootstrapPipeline::new(vec![
+            (BootstrapSource::Static, SECOND),
+            (BootstrapSource::Dht, SECOND),
+            (BootstrapSource::Gossip, SECOND),
+        ]);
+        assert!(pipeline.on_peer(node_id(9)).is_none()); // not started
+        assert_eq!(pipeline.start(0), BootstrapStep::Enter(BootstrapSource::Static));
+        pipeline.expect([node_id(1)]);
+        assert_eq!(pipeline.tick(999), None);
+        assert_eq!(pipeline.tick(1_000), Some(BootstrapStep::Enter(BootstrapSource::Dht)));
+
+        // Two of three resolved peers answer before the timeout
+        pipeline.expect([node_id(2), node_id(3), node_id(4)]);
+        assert_eq!(pipeline.on_peer(node_id(2)), None);
+        assert_eq!(pipeline.on_peer(node_id(3)), None);
+        assert_eq!(
+            pipeline.tick(2_000),
+            Some(BootstrapStep::Completed { source: Some(BootstrapSource::Dht), peers_found: 2 })
+        );
+        assert!(!pipeline.is_running());
+        assert_eq!(pipeline.tick(5_000), None);
+    }
+
+    #[test]
+    fn ends_early_once_expected_peers_answer_or_without_source() {
+        let mut pipeline = BootstrapPipeline::new(vec![(BootstrapSource::Static, SECOND)]);
+        pipeline.start(0);
+        pipeline.expect([node_id(1)]);
+        assert_eq!(
+            pipeline.on_peer(node_id(1)),
+            Some(BootstrapStep::Completed { source: Some(BootstrapSource::Static), peers_found: 1 })
+        );
+
+        let mut pipeline = BootstrapPipeline::new(vec![(BootstrapSource::Gossip, SECOND)]);
+        pipeline.start(0);
+        assert_eq!(
+            pipeline.tick(1_000),
+            Some(BootstrapStep::Completed { source: None, peers_found: 0 })
+        );
+    }
+
+    #[test]
+    fn lan_beacons_roundtrip() {
+        let beacon = LanBeacon {
+            node_id: node_id(1),
+            direct_addrs: vec!["192.168.1.20:4433".parse().unwrap()],
+        };
+        let bytes = beacon.to_bytes();
+        assert_eq!(LanBeacon::from_bytes(&bytes), Some(beacon.clone()));
+        assert_eq!(LanBeacon::from_bytes(&bytes[1..]), None);
+
+        let addr = beacon.to_endpoint_addr();
+        assert_eq!(NodeId::from_endpoint_id(addr.id), beacon.node_id);
+        assert_eq!(addr.addrs.len(), 1);
+    }
+}
@@ -0,0 +1,113 @@
+//! Departure announces — "I'm going offline", via gossip.
+//!
+//! A node shutting down cleanly signs and gossips one of these as its last
+//! word, so peers mark it offline right away instead of waiting out the
+//! offline threshold (minutes for Mobile and LowPower nodes).
+
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+
+use crate::types::NodeId;
+
+use super::types::MAX_FUTURE_DRIFT_MS;
+
+/// Departures older than this are ignored (1 minute): a replayed one must
+/// not knock a node that came back offline.
+pub const MAX_DEPARTURE_AGE_MS: u64 = 60_000;
+
+/// Domain tag, so a departure signature can't be mistaken for another
+/// signed gossip message.
+const DEPARTURE_TAG: &[u8] = b"tom-departure-v1";
+
+/// Signed notice that `node_id` is going offline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepartureAnnounce {
+    pub node_id: NodeId,
+    pub timestamp: u64,
+    pub signature: Vec<u8>,
+}
+
+impl DepartureAnnounce {
+    /// Create and sign a departure.
+    pub fn new(node_id: NodeId, timestamp: u64, secret_seed: &[u8; 32]) -> Self {
+        let mut departure = Self {
+            node_id,
+            timestamp,
+            signature: Vec::new(),
+        };
+        let signing_key = SigningKey::from_bytes(secret_seed);
+        departure.signature = signing_key
+            .sign(&departure.signing_bytes())
+            .to_bytes()
+            .to_vec();
+        departure
+    }
+
+    /// Verify the signature against the departing node (public key).
+    pub fn verify_signature(&self) -> bool {
+        use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+        let Ok(verifying_key) = VerifyingKey::from_bytes(&self.node_id.as_bytes()) else {
+            return false;
+        };
+        let Ok(sig_bytes) = <[u8; 64]>::try_from(self.signature.as_slice()) else {
+            return false;
+        };
+        verifying_key
+            .verify(&self.signing_bytes(), &Signature::from_bytes(&sig_bytes))
+            .is_ok()
+    }
+
+    /// Timestamp neither from the future nor older than `MAX_DEPARTURE_AGE_MS`.
+    pub fn is_valid(&self, now: u64) -> bool {
+        self.timestamp <= now + MAX_FUTURE_DRIFT_MS
+            && now.saturating_sub(self.timestamp) <= MAX_DEPARTURE_AGE_MS
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        rmp_serde::to_vec(self).expect("DepartureAnnounce serialization cannot fail")
+    }
+
+    /// Get bytes to sign (excludes signature field).
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(DEPARTURE_TAG.len() + 32 + 8);
+        bytes.extend_from_slice(DEPARTURE_TAG);
+        bytes.extend_from_slice(&self.node_id.as_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn identity(seed: u64) -> (NodeId, [u8; 32]) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        (secret.public().to_string().parse().unwrap(), secret.to_bytes())
+    }
+
+    #[test]
+    fn sign_verify_and_tamper() {
+        let (node, seed) = identity(1);
+        let (other, _) = identity(2);
+        let mut departure = DepartureAnnounce::new(node, 1_000, &seed);
+        assert!(departure.verify_signature());
+        assert!(departure.is_valid(1_000));
+
+        departure.node_id = other;
+        assert!(!departure.verify_signature(), "can't be pinned on another node");
+    }
+
+    #[test]
+    fn stale_departures_are_invalid() {
+        let (node, seed) = identity(1);
+        let departure = DepartureAnnounce::new(node, 1_000, &seed);
+        assert!(!departure.is_valid(1_001 + MAX_DEPARTURE_AGE_MS));
+
+        let future = DepartureAnnounce::new(node, 1_001 + MAX_FUTURE_DRIFT_MS, &seed);
+        assert!(!future.is_valid(0));
+    }
+}
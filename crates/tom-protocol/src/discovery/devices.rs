@@ -0,0 +1,351 @@
+//! Device linking — one user identity, several device keys.
+//!
+//! A user identity (its own Ed25519 key, kept off the network) authorizes
+//! each of its devices with a signed [`DeviceCertificate`]. Devices carry
+//! their certificate in their `PeerAnnounce`, so peers learn which nodes
+//! belong to the same user: messages to the user fan out to every linked
+//! device, and a device brings its siblings into its groups. A lost device
+//! is cut off with a [`DeviceRevocation`], also signed by the user key and
+//! gossiped by the remaining devices.
+
+use std::collections::HashMap;
+
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+
+use crate::types::NodeId;
+
+use super::types::MAX_FUTURE_DRIFT_MS;
+
+/// Most devices linked to one user.
+pub const MAX_DEVICES_PER_USER: usize = 16;
+
+/// Most revocations read from one announce.
+pub const MAX_ANNOUNCED_REVOCATIONS: usize = 16;
+
+/// Most certificates (and as many revocations) a directory keeps.
+const MAX_KNOWN_DEVICES: usize = 4096;
+
+/// Domain tags, so these signatures can't be mistaken for another signed
+/// message.
+const CERTIFICATE_TAG: &[u8] = b"tom-device-v1";
+const REVOCATION_TAG: &[u8] = b"tom-device-revoke-v1";
+
+/// Signed statement by `user` that `device` is one of its devices.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceCertificate {
+    pub user: NodeId,
+    pub device: NodeId,
+    /// Display name of the device ("laptop", "phone").
+    pub device_name: String,
+    pub issued_at: u64,
+    pub signature: Vec<u8>,
+}
+
+impl DeviceCertificate {
+    /// Create a certificate, signed with the user's key.
+    pub fn new(
+        user_seed: &[u8; 32],
+        device: NodeId,
+        device_name: String,
+        issued_at: u64,
+    ) -> Self {
+        let signing_key = SigningKey::from_bytes(user_seed);
+        let mut cert = Self {
+            user: user_id(user_seed),
+            device,
+            device_name,
+            issued_at,
+            signature: Vec::new(),
+        };
+        cert.signature = signing_key.sign(&cert.signing_bytes()).to_bytes().to_vec();
+        cert
+    }
+
+    /// Verify the signature against the user (public key).
+    pub fn verify_signature(&self) -> bool {
+        verify(&self.user, &self.signing_bytes(), &self.signature)
+    }
+
+    /// Not issued in the future (certificates don't expire; revoke instead).
+    pub fn is_valid(&self, now: u64) -> bool {
+        self.issued_at <= now + MAX_FUTURE_DRIFT_MS && self.user != self.device
+    }
+
+    /// Get bytes to sign (excludes signature field).
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(CERTIFICATE_TAG.len() + 64 + 8 + self.device_name.len());
+        bytes.extend_from_slice(CERTIFICATE_TAG);
+        bytes.extend_from_slice(&self.user.as_bytes());
+        bytes.extend_from_slice(&self.device.as_bytes());
+        bytes.extend_from_slice(&self.issued_at.to_le_bytes());
+        bytes.extend_from_slice(self.device_name.as_bytes());
+        bytes
+    }
+}
+
+/// Signed statement by `user` that `device` is no longer one of its
+/// devices. Voids every certificate for the device issued up to
+/// `revoked_at`, so the device can be re-linked later with a new one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceRevocation {
+    pub user: NodeId,
+    pub device: NodeId,
+    pub revoked_at: u64,
+    pub signature: Vec<u8>,
+}
+
+impl DeviceRevocation {
+    /// Create a revocation, signed with the user's key.
+    pub fn new(user_seed: &[u8; 32], device: NodeId, revoked_at: u64) -> Self {
+        let signing_key = SigningKey::from_bytes(user_seed);
+        let mut revocation = Self {
+            user: user_id(user_seed),
+            device,
+            revoked_at,
+            signature: Vec::new(),
+        };
+        revocation.signature = signing_key
+            .sign(&revocation.signing_bytes())
+            .to_bytes()
+            .to_vec();
+        revocation
+    }
+
+    /// Verify the signature against the user (public key).
+    pub fn verify_signature(&self) -> bool {
+        verify(&self.user, &self.signing_bytes(), &self.signature)
+    }
+
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(REVOCATION_TAG.len() + 64 + 8);
+        bytes.extend_from_slice(REVOCATION_TAG);
+        bytes.extend_from_slice(&self.user.as_bytes());
+        bytes.extend_from_slice(&self.device.as_bytes());
+        bytes.extend_from_slice(&self.revoked_at.to_le_bytes());
+        bytes
+    }
+}
+
+/// Node id of the user key.
+fn user_id(user_seed: &[u8; 32]) -> NodeId {
+    NodeId::from_endpoint_id(tom_base::SecretKey::from_bytes(user_seed).public())
+}
+
+fn verify(signer: &NodeId, message: &[u8], signature: &[u8]) -> bool {
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    let Ok(verifying_key) = VerifyingKey::from_bytes(&signer.as_bytes()) else {
+        return false;
+    };
+    let Ok(sig_bytes) = <[u8; 64]>::try_from(signature) else {
+        return false;
+    };
+    verifying_key
+        .verify(message, &Signature::from_bytes(&sig_bytes))
+        .is_ok()
+}
+
+/// The devices we know to be linked, by user, minus revoked ones.
+#[derive(Debug, Default)]
+pub struct DeviceDirectory {
+    /// Current certificate of each linked device.
+    certificates: HashMap<NodeId, DeviceCertificate>,
+    /// Latest revocation of each device, by (user, device).
+    revocations: HashMap<(NodeId, NodeId), DeviceRevocation>,
+}
+
+impl DeviceDirectory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a device's certificate. Returns true when it links a device
+    /// that wasn't linked to this user before.
+    pub fn record_certificate(&mut self, cert: DeviceCertificate, now: u64) -> bool {
+        if !cert.is_valid(now) || !cert.verify_signature() {
+            return false;
+        }
+        if self
+            .revocations
+            .get(&(cert.user, cert.device))
+            .is_some_and(|r| cert.issued_at <= r.revoked_at)
+        {
+            return false;
+        }
+        let newly_linked = match self.certificates.get(&cert.device) {
+            Some(current) if current.issued_at > cert.issued_at => return false,
+            Some(current) => current.user != cert.user,
+            None => true,
+        };
+        if newly_linked
+            && (self.certificates.len() >= MAX_KNOWN_DEVICES
+                || self.devices_of(&cert.user).len() >= MAX_DEVICES_PER_USER)
+        {
+            return false;
+        }
+        self.certificates.insert(cert.device, cert);
+        newly_linked
+    }
+
+    /// Apply a revocation. Returns true when it unlinks a device.
+    pub fn apply_revocation(&mut self, revocation: DeviceRevocation) -> bool {
+        if !revocation.verify_signature() {
+            return false;
+        }
+        if self
+            .revocations
+            .get(&(revocation.user, revocation.device))
+            .is_some_and(|r| r.revoked_at >= revocation.revoked_at)
+        {
+            return false;
+        }
+        if !self.revocations.contains_key(&(revocation.user, revocation.device))
+            && self.revocations.len() >= MAX_KNOWN_DEVICES
+        {
+            return false;
+        }
+        let unlinked = self.certificates.get(&revocation.device).is_some_and(|c| {
+            c.user == revocation.user && c.issued_at <= revocation.revoked_at
+        });
+        if unlinked {
+            self.certificates.remove(&revocation.device);
+        }
+        self.revocations
+            .insert((revocation.user, revocation.device), revocation);
+        unlinked
+    }
+
+    /// The user a device is linked to.
+    pub fn user_of(&self, device: &NodeId) -> Option<NodeId> {
+        self.certificates.get(device).map(|c| c.user)
+    }
+
+    /// Certificate of a linked device.
+    pub fn certificate(&self, device: &NodeId) -> Option<&DeviceCertificate> {
+        self.certificates.get(device)
+    }
+
+    /// Linked devices of a user, sorted.
+    pub fn devices_of(&self, user: &NodeId) -> Vec<NodeId> {
+        let mut devices: Vec<NodeId> = self
+            .certificates
+            .values()
+            .filter(|c| c.user == *user)
+            .map(|c| c.device)
+            .collect();
+        devices.sort_by_key(|d| d.as_bytes());
+        devices
+    }
+
+    /// Whether two distinct nodes belong to the same user. A node using the
+    /// user key itself counts as one of its devices.
+    pub fn are_linked(&self, a: &NodeId, b: &NodeId) -> bool {
+        let owner = |node: &NodeId| self.user_of(node).unwrap_or(*node);
+        a != b && owner(a) == owner(b)
+    }
+
+    /// Revocations signed by a user, most recent first, for its devices to
+    /// gossip.
+    pub fn revocations_of(&self, user: &NodeId) -> Vec<DeviceRevocation> {
+        let mut revocations: Vec<DeviceRevocation> = self
+            .revocations
+            .values()
+            .filter(|r| r.user == *user)
+            .cloned()
+            .collect();
+        revocations.sort_by_key(|r| std::cmp::Reverse(r.revoked_at));
+        revocations.truncate(MAX_ANNOUNCED_REVOCATIONS);
+        revocations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn identity(seed: u64) -> (NodeId, [u8; 32]) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        (secret.public().to_string().parse().unwrap(), secret.to_bytes())
+    }
+
+    #[test]
+    fn sign_verify_and_tamper() {
+        let (user, user_seed) = identity(1);
+        let (laptop, laptop_seed) = identity(2);
+        let (phone, _) = identity(3);
+        let cert = DeviceCertificate::new(&user_seed, laptop, "laptop".into(), 1_000);
+        assert_eq!(cert.user, user);
+        assert!(cert.verify_signature());
+        assert!(cert.is_valid(1_000));
+
+        let mut moved = cert.clone();
+        moved.device = phone;
+        assert!(!moved.verify_signature(), "a certificate names one device");
+
+        let self_signed = DeviceCertificate::new(&laptop_seed, laptop, "laptop".into(), 1_000);
+        assert!(self_signed.verify_signature());
+        assert!(!self_signed.is_valid(1_000), "a device can't be its own user");
+
+        let revocation = DeviceRevocation::new(&user_seed, laptop, 2_000);
+        assert!(revocation.verify_signature());
+        let mut forged = DeviceRevocation::new(&laptop_seed, phone, 2_000);
+        forged.user = user;
+        assert!(!forged.verify_signature());
+    }
+
+    #[test]
+    fn directory_links_siblings() {
+        let (user, user_seed) = identity(1);
+        let (laptop, _) = identity(2);
+        let (phone, _) = identity(3);
+        let (stranger, _) = identity(4);
+        let mut directory = DeviceDirectory::new();
+
+        let cert = DeviceCertificate::new(&user_seed, laptop, "laptop".into(), 1_000);
+        assert!(directory.record_certificate(cert.clone(), 1_000));
+        assert!(!directory.record_certificate(cert, 1_000), "already linked");
+        assert!(directory.record_certificate(
+            DeviceCertificate::new(&user_seed, phone, "phone".into(), 1_000),
+            1_000
+        ));
+
+        assert_eq!(directory.user_of(&laptop), Some(user));
+        let mut expected = vec![laptop, phone];
+        expected.sort_by_key(|d| d.as_bytes());
+        assert_eq!(directory.devices_of(&user), expected);
+        assert!(directory.are_linked(&laptop, &phone));
+        assert!(directory.are_linked(&user, &phone), "the user key counts as a device");
+        assert!(!directory.are_linked(&laptop, &laptop));
+        assert!(!directory.are_linked(&laptop, &stranger));
+    }
+
+    #[test]
+    fn revocation_unlinks_until_reissued() {
+        let (user, user_seed) = identity(1);
+        let (laptop, _) = identity(2);
+        let mut directory = DeviceDirectory::new();
+        directory.record_certificate(
+            DeviceCertificate::new(&user_seed, laptop, "laptop".into(), 1_000),
+            1_000,
+        );
+
+        assert!(directory.apply_revocation(DeviceRevocation::new(&user_seed, laptop, 2_000)));
+        assert_eq!(directory.user_of(&laptop), None);
+        assert_eq!(directory.revocations_of(&user).len(), 1);
+
+        // The old certificate stays void; a newer one links the device again
+        assert!(!directory.record_certificate(
+            DeviceCertificate::new(&user_seed, laptop, "laptop".into(), 1_500),
+            3_000
+        ));
+        assert!(directory.record_certificate(
+            DeviceCertificate::new(&user_seed, laptop, "laptop".into(), 2_500),
+            3_000
+        ));
+        assert_eq!(directory.user_of(&laptop), Some(user));
+    }
+}
@@ -0,0 +1,250 @@
+//! Username directory — find peers by name instead of hex keys.
+//!
+//! Nodes attach a signed [`UsernameClaim`] to their PeerAnnounce. Each node
+//! keeps its own directory: the first valid claim it sees for a name owns
+//! it, later claimants are kept behind the owner and reported as conflicts.
+//! Signatures stop anyone from claiming a name on another node's behalf;
+//! they cannot stop two nodes from wanting the same name, hence the
+//! conflict events and `resolve` returning every claimant.
+
+use std::collections::HashMap;
+
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+
+use crate::types::NodeId;
+
+use super::types::MAX_FUTURE_DRIFT_MS;
+
+/// Longest claimable name (chars, after normalization).
+pub const MAX_CLAIM_NAME_CHARS: usize = 32;
+
+/// Most nodes the directory tracks; claims from new nodes beyond it are ignored.
+pub const MAX_DIRECTORY_NODES: usize = 4_096;
+
+/// Domain separator for claim signatures.
+const CLAIM_DOMAIN: &[u8] = b"tom-username-claim-v1";
+
+/// The runtime's default username — shared by every unconfigured node,
+/// so it is never claimed.
+const DEFAULT_USERNAME: &str = "anonymous";
+
+/// Canonical form of a name for claims and lookups (trimmed, lowercase).
+///
+/// None if the name is empty, too long, or the runtime default.
+pub fn normalize_username(name: &str) -> Option<String> {
+    let name = name.trim().to_lowercase();
+    if name.is_empty() || name.chars().count() > MAX_CLAIM_NAME_CHARS || name == DEFAULT_USERNAME {
+        return None;
+    }
+    Some(name)
+}
+
+/// A node's signed claim on a (normalized) username.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsernameClaim {
+    pub node_id: NodeId,
+    pub name: String,
+    pub claimed_at: u64,
+    pub signature: Vec<u8>,
+}
+
+impl UsernameClaim {
+    /// Create and sign a claim. None if the name can't be claimed.
+    pub fn new(node_id: NodeId, name: &str, claimed_at: u64, secret_seed: &[u8; 32]) -> Option<Self> {
+        let mut claim = Self {
+            node_id,
+            name: normalize_username(name)?,
+            claimed_at,
+            signature: Vec::new(),
+        };
+        let signing_key = SigningKey::from_bytes(secret_seed);
+        claim.signature = signing_key.sign(&claim.signing_bytes()).to_bytes().to_vec();
+        Some(claim)
+    }
+
+    /// Verify the signature against the node_id (public key) and that the
+    /// name is in canonical form.
+    pub fn verify_signature(&self) -> bool {
+        use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+        if normalize_username(&self.name).as_deref() != Some(self.name.as_str()) {
+            return false;
+        }
+        let verifying_key = match VerifyingKey::from_bytes(&self.node_id.as_bytes()) {
+            Ok(k) => k,
+            Err(_) => return false,
+        };
+        let Ok(sig_bytes) = <[u8; 64]>::try_from(self.signature.as_slice()) else {
+            return false;
+        };
+        verifying_key
+            .verify(&self.signing_bytes(), &Signature::from_bytes(&sig_bytes))
+            .is_ok()
+    }
+
+    /// Get bytes to sign (excludes signature field).
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(CLAIM_DOMAIN.len() + 40 + self.name.len());
+        bytes.extend_from_slice(CLAIM_DOMAIN);
+        bytes.extend_from_slice(&self.node_id.as_bytes());
+        bytes.extend_from_slice(&self.claimed_at.to_le_bytes());
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes
+    }
+}
+
+/// What recording a claim did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClaimOutcome {
+    /// Bad signature, non-canonical name, future timestamp, or directory full.
+    Rejected,
+    /// Already recorded for this node.
+    Unchanged,
+    /// The node now owns the name.
+    Owner,
+    /// Someone else saw the name first; the node is listed behind `owner`.
+    Conflict { owner: NodeId },
+}
+
+/// Local view of who claimed which name, in first-seen order.
+#[derive(Debug, Default)]
+pub struct UsernameDirectory {
+    /// Normalized name → claimants, owner first.
+    names: HashMap<String, Vec<NodeId>>,
+    /// Node → the name it currently claims.
+    by_node: HashMap<NodeId, String>,
+}
+
+impl UsernameDirectory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a claim seen at `now`. A node claiming a new name releases its old one.
+    pub fn record(&mut self, claim: &UsernameClaim, now: u64) -> ClaimOutcome {
+        if claim.claimed_at > now + MAX_FUTURE_DRIFT_MS || !claim.verify_signature() {
+            return ClaimOutcome::Rejected;
+        }
+        match self.by_node.get(&claim.node_id) {
+            Some(name) if *name == claim.name => return ClaimOutcome::Unchanged,
+            Some(_) => self.forget(&claim.node_id),
+            None if self.by_node.len() >= MAX_DIRECTORY_NODES => return ClaimOutcome::Rejected,
+            None => {}
+        }
+        self.by_node.insert(claim.node_id, claim.name.clone());
+        let claimants = self.names.entry(claim.name.clone()).or_default();
+        claimants.push(claim.node_id);
+        match claimants[0] {
+            owner if owner == claim.node_id => ClaimOutcome::Owner,
+            owner => ClaimOutcome::Conflict { owner },
+        }
+    }
+
+    /// Nodes claiming `name`, owner first. Empty if nobody does.
+    pub fn resolve(&self, name: &str) -> Vec<NodeId> {
+        normalize_username(name)
+            .and_then(|name| self.names.get(&name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The name a node claims, if any.
+    pub fn name_of(&self, node_id: &NodeId) -> Option<&str> {
+        self.by_node.get(node_id).map(String::as_str)
+    }
+
+    /// Drop a node's claim (the next claimant, if any, becomes owner).
+    pub fn forget(&mut self, node_id: &NodeId) {
+        let Some(name) = self.by_node.remove(node_id) else {
+            return;
+        };
+        if let Some(claimants) = self.names.get_mut(&name) {
+            claimants.retain(|n| n != node_id);
+            if claimants.is_empty() {
+                self.names.remove(&name);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_node.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_node.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn identity(seed: u64) -> (NodeId, [u8; 32]) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        (secret.public().to_string().parse().unwrap(), secret.to_bytes())
+    }
+
+    fn claim(seed: u64, name: &str) -> UsernameClaim {
+        let (node_id, secret) = identity(seed);
+        UsernameClaim::new(node_id, name, 1_000, &secret).unwrap()
+    }
+
+    #[test]
+    fn names_are_normalized_and_defaults_unclaimable() {
+        assert_eq!(normalize_username("  Alice "), Some("alice".into()));
+        assert_eq!(normalize_username("   "), None);
+        assert_eq!(normalize_username("Anonymous"), None);
+        assert_eq!(normalize_username(&"x".repeat(MAX_CLAIM_NAME_CHARS + 1)), None);
+    }
+
+    #[test]
+    fn signed_claim_verifies_and_tampering_fails() {
+        let mut c = claim(1, "Alice");
+        assert_eq!(c.name, "alice");
+        assert!(c.verify_signature());
+
+        c.name = "bob".into();
+        assert!(!c.verify_signature());
+
+        let mut forged = claim(1, "alice");
+        forged.node_id = identity(2).0;
+        assert!(!forged.verify_signature());
+    }
+
+    #[test]
+    fn first_seen_owns_and_later_claimants_conflict() {
+        let mut dir = UsernameDirectory::new();
+        let alice = claim(1, "alice");
+        let impostor = claim(2, "ALICE");
+
+        assert_eq!(dir.record(&alice, 2_000), ClaimOutcome::Owner);
+        assert_eq!(dir.record(&alice, 3_000), ClaimOutcome::Unchanged);
+        assert_eq!(
+            dir.record(&impostor, 3_000),
+            ClaimOutcome::Conflict { owner: alice.node_id }
+        );
+        assert_eq!(dir.resolve("Alice"), vec![alice.node_id, impostor.node_id]);
+
+        // Owner renames: the next claimant takes over
+        let renamed = claim(1, "alicia");
+        assert_eq!(dir.record(&renamed, 4_000), ClaimOutcome::Owner);
+        assert_eq!(dir.resolve("alice"), vec![impostor.node_id]);
+        assert_eq!(dir.name_of(&alice.node_id), Some("alicia"));
+    }
+
+    #[test]
+    fn rejects_forged_and_future_claims() {
+        let mut dir = UsernameDirectory::new();
+        let mut forged = claim(1, "alice");
+        forged.claimed_at += 1;
+        assert_eq!(dir.record(&forged, 2_000), ClaimOutcome::Rejected);
+
+        let (node_id, secret) = identity(3);
+        let future = UsernameClaim::new(node_id, "carol", 1_000 + MAX_FUTURE_DRIFT_MS + 1, &secret).unwrap();
+        assert_eq!(dir.record(&future, 0), ClaimOutcome::Rejected);
+        assert!(dir.is_empty());
+    }
+}
@@ -2,8 +2,12 @@
 ///
 /// Pure state machine: record heartbeats, check liveness.
 /// Two-tier timeout: Stale (1x threshold) → Departed (2x threshold).
+/// Thresholds come from a `LivenessConfig` and stretch per peer for peers
+/// announcing slower than we do.
+/// Any authenticated envelope counts as a heartbeat, and traffic we send is
+/// recorded so periodic probes to peers it already reached can be skipped.
 /// Tracks discovery source for new peers (consumed on PeerDiscovered emission).
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 use crate::discovery::types::*;
 use crate::relay::{PeerStatus, Topology};
@@ -17,36 +21,83 @@ pub struct HeartbeatTracker {
     stale_threshold: u64,
     /// Offline threshold in ms.
     offline_threshold: u64,
+    /// Our announce interval in ms (the pace the thresholds are sized for).
+    announce_interval: u64,
+    /// Announce interval each peer advertised, when slower than ours.
+    peer_interval: HashMap<NodeId, u64>,
     /// Pending discovery sources (consumed when PeerDiscovered emitted).
     pending_source: HashMap<NodeId, DiscoverySource>,
     /// Pending usernames (consumed when PeerDiscovered emitted).
     pending_username: HashMap<NodeId, String>,
-    /// Peers that have been discovered (PeerDiscovered already emitted).
-    discovered: HashSet<NodeId>,
+    /// Peers that have been discovered (PeerDiscovered already emitted),
+    /// with how we first learned about them.
+    discovered: HashMap<NodeId, DiscoverySource>,
+    /// When we last sent each peer an envelope (Unix ms).
+    last_sent: HashMap<NodeId, u64>,
 }
 
 impl HeartbeatTracker {
-    /// Create a new tracker with default thresholds.
+    /// Create a new tracker with default (Desktop) thresholds.
     pub fn new() -> Self {
+        Self::with_config(&LivenessConfig::default())
+    }
+
+    /// Create with the thresholds of a liveness profile.
+    pub fn with_config(config: &LivenessConfig) -> Self {
         Self {
             last_heartbeat: HashMap::new(),
-            stale_threshold: STALE_THRESHOLD_MS,
-            offline_threshold: OFFLINE_THRESHOLD_MS,
+            stale_threshold: config.stale_threshold_ms,
+            offline_threshold: config.offline_threshold_ms,
+            announce_interval: config.announce_interval_ms.max(1),
+            peer_interval: HashMap::new(),
             pending_source: HashMap::new(),
             pending_username: HashMap::new(),
-            discovered: HashSet::new(),
+            discovered: HashMap::new(),
+            last_sent: HashMap::new(),
         }
     }
 
     /// Create with custom thresholds (for testing).
     pub fn with_thresholds(stale_ms: u64, offline_ms: u64) -> Self {
-        Self {
-            last_heartbeat: HashMap::new(),
-            stale_threshold: stale_ms,
-            offline_threshold: offline_ms,
-            pending_source: HashMap::new(),
-            pending_username: HashMap::new(),
-            discovered: HashSet::new(),
+        Self::with_config(&LivenessConfig {
+            stale_threshold_ms: stale_ms,
+            offline_threshold_ms: offline_ms,
+            ..LivenessConfig::default()
+        })
+    }
+
+    /// Record the announce interval a peer advertised (capped at
+    /// `MAX_ANNOUNCE_INTERVAL_MS`). A peer slower than us gets proportionally
+    /// longer thresholds: it may miss as many announces as ours allow.
+    pub fn set_peer_interval(&mut self, node_id: NodeId, interval_ms: u64) {
+        let interval = interval_ms.min(MAX_ANNOUNCE_INTERVAL_MS);
+        if interval > self.announce_interval {
+            self.peer_interval.insert(node_id, interval);
+        } else {
+            self.peer_interval.remove(&node_id);
+        }
+    }
+
+    /// Stale and offline thresholds for a peer.
+    fn thresholds(&self, node_id: &NodeId) -> (u64, u64) {
+        match self.peer_interval.get(node_id) {
+            Some(&interval) => (
+                self.stale_threshold * interval / self.announce_interval,
+                self.offline_threshold * interval / self.announce_interval,
+            ),
+            None => (self.stale_threshold, self.offline_threshold),
+        }
+    }
+
+    /// Classify a silence of `elapsed` ms for a peer.
+    fn liveness_after(&self, node_id: &NodeId, elapsed: u64) -> LivenessState {
+        let (stale, offline) = self.thresholds(node_id);
+        if elapsed >= offline {
+            LivenessState::Departed
+        } else if elapsed >= stale {
+            LivenessState::Stale
+        } else {
+            LivenessState::Alive
         }
     }
 
@@ -75,6 +126,22 @@ impl HeartbeatTracker {
         self.record_heartbeat(node_id);
     }
 
+    /// Record that we sent `node_id` an envelope at `now`.
+    pub fn record_sent(&mut self, node_id: NodeId, now: u64) {
+        if self.last_sent.len() >= crate::relay::MAX_PEERS && !self.last_sent.contains_key(&node_id) {
+            return;
+        }
+        self.last_sent.insert(node_id, now);
+    }
+
+    /// Whether we sent `node_id` anything within `window_ms` of `now` —
+    /// if so, a liveness probe to it would be redundant.
+    pub fn sent_within(&self, node_id: &NodeId, now: u64, window_ms: u64) -> bool {
+        self.last_sent
+            .get(node_id)
+            .is_some_and(|&sent| now.saturating_sub(sent) < window_ms)
+    }
+
     /// Start tracking a peer (initial registration).
     pub fn track_peer(&mut self, node_id: NodeId) {
         self.last_heartbeat.entry(node_id).or_insert_with(now_ms);
@@ -83,6 +150,35 @@ impl HeartbeatTracker {
     /// Stop tracking a peer.
     pub fn untrack_peer(&mut self, node_id: &NodeId) {
         self.last_heartbeat.remove(node_id);
+        self.peer_interval.remove(node_id);
+        self.last_sent.remove(node_id);
+    }
+
+    /// Treat a tracked peer as departed as of `now` (it announced it was
+    /// going offline). A later heartbeat brings it back as usual.
+    pub fn mark_departed(&mut self, node_id: &NodeId, now: u64) {
+        let departed_at = now.saturating_sub(self.thresholds(node_id).1);
+        if let Some(last) = self.last_heartbeat.get_mut(node_id) {
+            *last = (*last).min(departed_at);
+        }
+    }
+
+    /// Last heartbeat (or authenticated traffic) from a peer, Unix ms.
+    pub fn last_seen(&self, node_id: &NodeId) -> Option<u64> {
+        self.last_heartbeat.get(node_id).copied()
+    }
+
+    /// How we learned about a peer (pending until PeerDiscovered is emitted).
+    pub fn source(&self, node_id: &NodeId) -> Option<DiscoverySource> {
+        self.discovered
+            .get(node_id)
+            .or_else(|| self.pending_source.get(node_id))
+            .copied()
+    }
+
+    /// All tracked peers.
+    pub fn tracked_peers(&self) -> impl Iterator<Item = &NodeId> {
+        self.last_heartbeat.keys()
     }
 
     /// Check the liveness state of a specific peer.
@@ -91,16 +187,7 @@ impl HeartbeatTracker {
             return LivenessState::Departed;
         };
 
-        let now = now_ms();
-        let elapsed = now.saturating_sub(last);
-
-        if elapsed >= self.offline_threshold {
-            LivenessState::Departed
-        } else if elapsed >= self.stale_threshold {
-            LivenessState::Stale
-        } else {
-            LivenessState::Alive
-        }
+        self.liveness_after(node_id, now_ms().saturating_sub(last))
     }
 
     /// Check liveness at a specific time (for testing).
@@ -109,15 +196,7 @@ impl HeartbeatTracker {
             return LivenessState::Departed;
         };
 
-        let elapsed = now.saturating_sub(last);
-
-        if elapsed >= self.offline_threshold {
-            LivenessState::Departed
-        } else if elapsed >= self.stale_threshold {
-            LivenessState::Stale
-        } else {
-            LivenessState::Alive
-        }
+        self.liveness_after(node_id, now.saturating_sub(last))
     }
 
     /// Check all peers and return events for state transitions.
@@ -129,10 +208,10 @@ impl HeartbeatTracker {
         let now = now_ms();
 
         for (&node_id, &last) in &self.last_heartbeat {
-            let elapsed = now.saturating_sub(last);
+            let liveness = self.liveness_after(&node_id, now.saturating_sub(last));
             let current_status = topology.get(&node_id).map(|p| p.status);
 
-            if elapsed >= self.offline_threshold {
+            if liveness == LivenessState::Departed {
                 // Departed
                 if current_status != Some(PeerStatus::Offline) {
                     if let Some(peer) = topology.get(&node_id) {
@@ -142,7 +221,7 @@ impl HeartbeatTracker {
                     }
                     events.push(DiscoveryEvent::PeerOffline { node_id });
                 }
-            } else if elapsed >= self.stale_threshold {
+            } else if liveness == LivenessState::Stale {
                 // Stale
                 if current_status != Some(PeerStatus::Stale) {
                     if let Some(peer) = topology.get(&node_id) {
@@ -152,7 +231,7 @@ impl HeartbeatTracker {
                     }
                     events.push(DiscoveryEvent::PeerStale { node_id });
                 }
-            } else if !self.discovered.contains(&node_id) {
+            } else if !self.discovered.contains_key(&node_id) {
                 // Alive + first time seen → PeerDiscovered
                 let source = self.pending_source
                     .remove(&node_id)
@@ -185,8 +264,8 @@ impl HeartbeatTracker {
 
         // Mark newly discovered peers
         for event in &events {
-            if let DiscoveryEvent::PeerDiscovered { node_id, .. } = event {
-                self.discovered.insert(*node_id);
+            if let DiscoveryEvent::PeerDiscovered { node_id, source, .. } = event {
+                self.discovered.insert(*node_id, *source);
             }
         }
 
@@ -198,9 +277,10 @@ impl HeartbeatTracker {
         let now = now_ms();
         let mut removed = vec![];
 
-        self.last_heartbeat.retain(|&node_id, &mut last| {
+        let mut last_heartbeat = std::mem::take(&mut self.last_heartbeat);
+        last_heartbeat.retain(|&node_id, &mut last| {
             let elapsed = now.saturating_sub(last);
-            if elapsed >= self.offline_threshold * 3 {
+            if elapsed >= self.thresholds(&node_id).1 * 3 {
                 removed.push(node_id);
                 false
             } else {
@@ -208,8 +288,12 @@ impl HeartbeatTracker {
             }
         });
 
+        self.last_heartbeat = last_heartbeat;
+
         // Clean up discovered + pending for removed peers
         for id in &removed {
+            self.peer_interval.remove(id);
+            self.last_sent.remove(id);
             self.discovered.remove(id);
             self.pending_source.remove(id);
             self.pending_username.remove(id);
@@ -423,4 +507,60 @@ mod tests {
         tracker.track_peer(node_id(2));
         assert_eq!(tracker.tracked_count(), 2);
     }
+
+    #[test]
+    fn profiles_set_thresholds() {
+        let mut server =
+            HeartbeatTracker::with_config(&LivenessConfig::for_profile(DeploymentProfile::Server));
+        let mut mobile =
+            HeartbeatTracker::with_config(&LivenessConfig::for_profile(DeploymentProfile::Mobile));
+        let alice = node_id(1);
+        server.record_heartbeat_at(alice, 0);
+        mobile.record_heartbeat_at(alice, 0);
+
+        assert_eq!(server.liveness_at(&alice, OFFLINE_THRESHOLD_MS), LivenessState::Departed);
+        assert_eq!(mobile.liveness_at(&alice, OFFLINE_THRESHOLD_MS), LivenessState::Alive);
+
+        let low_power = LivenessConfig::for_profile(DeploymentProfile::LowPower);
+        let mobile = LivenessConfig::for_profile(DeploymentProfile::Mobile);
+        assert!(low_power.announce_interval_ms > mobile.announce_interval_ms);
+        assert!(low_power.announce_interval_ms <= MAX_ANNOUNCE_INTERVAL_MS);
+        assert!(low_power.offline_threshold_ms > low_power.announce_interval_ms * 4);
+    }
+
+    #[test]
+    fn slow_announcers_get_stretched_thresholds() {
+        let mut tracker = HeartbeatTracker::new();
+        let (phone, laptop) = (node_id(1), node_id(2));
+        let mobile = LivenessConfig::for_profile(DeploymentProfile::Mobile);
+        tracker.set_peer_interval(phone, mobile.announce_interval_ms);
+        tracker.set_peer_interval(laptop, 1_000); // faster than us: our thresholds
+        tracker.record_heartbeat_at(phone, 0);
+        tracker.record_heartbeat_at(laptop, 0);
+
+        // A phone missing one slow announce isn't even stale
+        let silence = mobile.announce_interval_ms + 1_000;
+        assert_eq!(tracker.liveness_at(&phone, silence), LivenessState::Alive);
+        assert_eq!(tracker.liveness_at(&laptop, silence), LivenessState::Stale);
+        assert_eq!(tracker.liveness_at(&phone, 3 * OFFLINE_THRESHOLD_MS), LivenessState::Departed);
+
+        // Absurd intervals are capped
+        tracker.set_peer_interval(phone, u64::MAX);
+        let cap = OFFLINE_THRESHOLD_MS * MAX_ANNOUNCE_INTERVAL_MS / GOSSIP_INTERVAL_MS;
+        assert_eq!(tracker.liveness_at(&phone, cap), LivenessState::Departed);
+    }
+
+    #[test]
+    fn sent_traffic_is_remembered_per_peer() {
+        let mut tracker = HeartbeatTracker::new();
+        let (alice, bob) = (node_id(1), node_id(2));
+        tracker.record_sent(alice, 1_000);
+
+        assert!(tracker.sent_within(&alice, 1_500, 1_000));
+        assert!(!tracker.sent_within(&alice, 2_000, 1_000));
+        assert!(!tracker.sent_within(&bob, 1_500, 1_000));
+
+        tracker.untrack_peer(&alice);
+        assert!(!tracker.sent_within(&alice, 1_500, 1_000));
+    }
 }
@@ -0,0 +1,131 @@
+//! Identity bindings — extra identities served by one node.
+//!
+//! A runtime can host several identities (personal, work) over a single
+//! endpoint. Hosted identities have no endpoint of their own: the host lists
+//! them in its `PeerAnnounce`, each with a binding signed by the identity's
+//! key, and peers route to them through the host.
+
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+
+use crate::types::NodeId;
+
+use super::types::MAX_FUTURE_DRIFT_MS;
+
+/// Bindings older than this are ignored (1 hour, like announces): a host
+/// that stopped serving an identity can't keep claiming it.
+pub const MAX_BINDING_AGE_MS: u64 = 60 * 60 * 1000;
+
+/// Most hosted identities read from one announce.
+pub const MAX_HOSTED_IDENTITIES: usize = 16;
+
+/// Domain tag, so a binding signature can't be mistaken for another
+/// signed message.
+const BINDING_TAG: &[u8] = b"tom-host-v1";
+
+/// Signed statement by `identity` that `host` serves it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentityBinding {
+    pub identity: NodeId,
+    pub host: NodeId,
+    /// Display name of the hosted identity.
+    pub username: String,
+    pub timestamp: u64,
+    pub signature: Vec<u8>,
+}
+
+impl IdentityBinding {
+    /// Create a binding, signed with the hosted identity's key.
+    pub fn new(
+        identity: NodeId,
+        host: NodeId,
+        username: String,
+        timestamp: u64,
+        secret_seed: &[u8; 32],
+    ) -> Self {
+        let mut binding = Self {
+            identity,
+            host,
+            username,
+            timestamp,
+            signature: Vec::new(),
+        };
+        let signing_key = SigningKey::from_bytes(secret_seed);
+        binding.signature = signing_key
+            .sign(&binding.signing_bytes())
+            .to_bytes()
+            .to_vec();
+        binding
+    }
+
+    /// Verify the signature against the hosted identity (public key).
+    pub fn verify_signature(&self) -> bool {
+        use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+        let Ok(verifying_key) = VerifyingKey::from_bytes(&self.identity.as_bytes()) else {
+            return false;
+        };
+        let Ok(sig_bytes) = <[u8; 64]>::try_from(self.signature.as_slice()) else {
+            return false;
+        };
+        verifying_key
+            .verify(&self.signing_bytes(), &Signature::from_bytes(&sig_bytes))
+            .is_ok()
+    }
+
+    /// Timestamp neither from the future nor older than `MAX_BINDING_AGE_MS`.
+    pub fn is_valid(&self, now: u64) -> bool {
+        self.timestamp <= now + MAX_FUTURE_DRIFT_MS
+            && now.saturating_sub(self.timestamp) <= MAX_BINDING_AGE_MS
+    }
+
+    /// Get bytes to sign (excludes signature field).
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(BINDING_TAG.len() + 64 + 8 + self.username.len());
+        bytes.extend_from_slice(BINDING_TAG);
+        bytes.extend_from_slice(&self.identity.as_bytes());
+        bytes.extend_from_slice(&self.host.as_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes.extend_from_slice(self.username.as_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn identity(seed: u64) -> (NodeId, [u8; 32]) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        (secret.public().to_string().parse().unwrap(), secret.to_bytes())
+    }
+
+    #[test]
+    fn sign_verify_and_tamper() {
+        let (work, seed) = identity(1);
+        let (host, _) = identity(2);
+        let (other, _) = identity(3);
+        let binding = IdentityBinding::new(work, host, "alice-work".into(), 1_000, &seed);
+        assert!(binding.verify_signature());
+        assert!(binding.is_valid(1_000));
+
+        let mut stolen = binding.clone();
+        stolen.host = other;
+        assert!(!stolen.verify_signature(), "another host can't claim the identity");
+
+        let mut renamed = binding;
+        renamed.username = "mallory".into();
+        assert!(!renamed.verify_signature());
+    }
+
+    #[test]
+    fn stale_bindings_are_invalid() {
+        let (work, seed) = identity(1);
+        let (host, _) = identity(2);
+        let binding = IdentityBinding::new(work, host, String::new(), 1_000, &seed);
+        assert!(!binding.is_valid(1_001 + MAX_BINDING_AGE_MS));
+    }
+}
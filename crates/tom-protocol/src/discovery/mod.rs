@@ -3,18 +3,44 @@
 /// Application-level peer discovery on top of iroh's low-level
 /// address resolution. Handles: announcements, heartbeats,
 /// liveness tracking, and ephemeral subnet clustering.
+pub mod abuse;
+pub mod attestation;
+pub mod bootstrap;
+pub mod departure;
+pub mod devices;
+pub mod directory;
 pub mod heartbeat;
+pub mod hosting;
+pub mod pex;
 pub mod role_sync;
 pub mod subnet;
 pub mod types;
 
+pub use abuse::{AbuseReport, ViolationKind, MAX_EVIDENCE_BYTES, REPORT_INTERVAL_MS};
+pub use attestation::{RelayAttestation, ATTESTATION_INTERVAL_MS, MAX_ATTESTED_RELAYS};
+pub use bootstrap::{
+    BootstrapConfig, BootstrapPipeline, BootstrapSource, BootstrapStep, LanBeacon,
+    LAN_DISCOVERY_GROUP, LAN_DISCOVERY_PORT,
+};
+pub use departure::{DepartureAnnounce, MAX_DEPARTURE_AGE_MS};
+pub use devices::{
+    DeviceCertificate, DeviceDirectory, DeviceRevocation, MAX_ANNOUNCED_REVOCATIONS,
+    MAX_DEVICES_PER_USER,
+};
+pub use directory::{
+    normalize_username, ClaimOutcome, UsernameClaim, UsernameDirectory, MAX_CLAIM_NAME_CHARS,
+};
 pub use heartbeat::HeartbeatTracker;
+pub use hosting::{IdentityBinding, MAX_BINDING_AGE_MS, MAX_HOSTED_IDENTITIES};
+pub use pex::{merge_pex_peers, select_pex_peers, PexEntry, MAX_PEX_TOPOLOGY};
 pub use role_sync::RoleChangeAnnounce;
 pub use subnet::{
-    CommunicationEdge, DissolveReason, EphemeralSubnetManager, SubnetEvent, SubnetInfo,
+    CommunicationEdge, DissolveReason, EphemeralSubnetManager, SubnetConfig, SubnetEvent,
+    SubnetInfo, SubnetStats,
 };
 pub use types::{
-    DiscoveryEvent, DiscoverySource, LivenessState, PeerAnnounce, GOSSIP_INTERVAL_MS,
-    HEARTBEAT_INTERVAL_MS, MAX_FUTURE_DRIFT_MS, MAX_PEERS_PER_GOSSIP, OFFLINE_THRESHOLD_MS,
+    DeploymentProfile, DiscoveryEvent, DiscoverySource, LivenessConfig, LivenessState,
+    PeerAnnounce, Presence, GOSSIP_INTERVAL_MS, HEARTBEAT_INTERVAL_MS, MAX_ANNOUNCE_INTERVAL_MS,
+    MAX_FUTURE_DRIFT_MS, MAX_PEERS_PER_GOSSIP, MAX_PRESENCE_TEXT_CHARS, OFFLINE_THRESHOLD_MS,
     STALE_THRESHOLD_MS,
 };
@@ -0,0 +1,175 @@
+/// Peer exchange (PEX) — share known-good peers inside PeerAnnounce.
+///
+/// On the shared gossip topic a node only learns about peers whose
+/// announces reach it, which is slow in sparse networks. With PEX each
+/// announce also lists up to `MAX_PEERS_PER_GOSSIP` peers the announcer
+/// verified itself — heard from within the stale threshold — with their roles.
+///
+/// Received entries are hints, not observations: unknown peers enter the
+/// topology as Offline (like imported contacts) and only go Online once we
+/// hear from them directly. Known peers are never overwritten by hearsay.
+use serde::{Deserialize, Serialize};
+
+use crate::relay::{PeerInfo, PeerRole, PeerStatus, Topology, MAX_PEERS};
+use crate::types::NodeId;
+
+use super::heartbeat::HeartbeatTracker;
+use super::types::{LivenessState, MAX_FUTURE_DRIFT_MS, MAX_PEERS_PER_GOSSIP, STALE_THRESHOLD_MS};
+
+/// Topology size beyond which PEX hints are ignored, leaving the rest of
+/// the topology to peers we actually talk to. Smaller topologies stop at
+/// half their own capacity.
+pub const MAX_PEX_TOPOLOGY: usize = MAX_PEERS / 2;
+
+/// One shared peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PexEntry {
+    pub node_id: NodeId,
+    pub role: PeerRole,
+    /// When the announcer last heard from it (Unix ms).
+    pub last_seen: u64,
+}
+
+/// Peers worth sharing: online, heard from directly within the stale
+/// threshold, most recently seen first. Excludes us.
+pub fn select_pex_peers(
+    topology: &Topology,
+    heartbeat: &HeartbeatTracker,
+    local_id: NodeId,
+    now: u64,
+) -> Vec<PexEntry> {
+    let mut peers: Vec<&PeerInfo> = topology
+        .peers()
+        .filter(|p| {
+            p.node_id != local_id
+                && p.status == PeerStatus::Online
+                && heartbeat.liveness_at(&p.node_id, now) == LivenessState::Alive
+        })
+        .collect();
+    peers.sort_by_key(|p| std::cmp::Reverse(p.last_seen));
+    peers
+        .into_iter()
+        .take(MAX_PEERS_PER_GOSSIP)
+        .map(|p| PexEntry {
+            node_id: p.node_id,
+            role: p.role,
+            last_seen: p.last_seen,
+        })
+        .collect()
+}
+
+/// Add the unknown peers from an announcer's PEX list to the topology.
+///
+/// Skips us, the announcer, entries not seen recently (or from the
+/// future), and everything past `MAX_PEERS_PER_GOSSIP` entries.
+/// Returns the peers added.
+pub fn merge_pex_peers(
+    topology: &mut Topology,
+    announcer: NodeId,
+    entries: &[PexEntry],
+    local_id: NodeId,
+    now: u64,
+) -> Vec<NodeId> {
+    let mut added = Vec::new();
+    for entry in entries.iter().take(MAX_PEERS_PER_GOSSIP) {
+        if topology.len() >= MAX_PEX_TOPOLOGY.min(topology.max_peers() / 2) {
+            break;
+        }
+        let recent = entry.last_seen <= now + MAX_FUTURE_DRIFT_MS
+            && now.saturating_sub(entry.last_seen) < STALE_THRESHOLD_MS;
+        if entry.node_id == local_id
+            || entry.node_id == announcer
+            || !recent
+            || topology.get(&entry.node_id).is_some()
+        {
+            continue;
+        }
+        let inserted = topology.upsert(PeerInfo {
+            node_id: entry.node_id,
+            role: entry.role,
+            status: PeerStatus::Offline,
+            last_seen: 0,
+        });
+        if inserted {
+            added.push(entry.node_id);
+        }
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    fn online(topology: &mut Topology, id: NodeId, role: PeerRole, last_seen: u64) {
+        topology.upsert(PeerInfo {
+            node_id: id,
+            role,
+            status: PeerStatus::Online,
+            last_seen,
+        });
+    }
+
+    #[test]
+    fn selects_only_verified_recent_peers_newest_first() {
+        let me = node_id(1);
+        let (fresh, older, hearsay, stale) = (node_id(2), node_id(3), node_id(4), node_id(5));
+        let now = 100_000;
+        let mut topology = Topology::new();
+        let mut heartbeat = HeartbeatTracker::new();
+
+        online(&mut topology, fresh, PeerRole::Relay, now - 1_000);
+        heartbeat.record_heartbeat_at(fresh, now - 1_000);
+        online(&mut topology, older, PeerRole::Peer, now - 5_000);
+        heartbeat.record_heartbeat_at(older, now - 5_000);
+        // In the topology but never heard from directly
+        online(&mut topology, hearsay, PeerRole::Peer, now);
+        // Heard from, but too long ago
+        online(&mut topology, stale, PeerRole::Peer, now - STALE_THRESHOLD_MS);
+        heartbeat.record_heartbeat_at(stale, now - STALE_THRESHOLD_MS);
+
+        let shared = select_pex_peers(&topology, &heartbeat, me, now);
+        let ids: Vec<NodeId> = shared.iter().map(|e| e.node_id).collect();
+        assert_eq!(ids, vec![fresh, older]);
+        assert_eq!(shared[0].role, PeerRole::Relay);
+    }
+
+    #[test]
+    fn merge_adds_unknown_recent_peers_as_offline_hints() {
+        let me = node_id(1);
+        let announcer = node_id(2);
+        let (known, new_relay, old) = (node_id(3), node_id(4), node_id(5));
+        let now = 100_000;
+        let mut topology = Topology::new();
+        online(&mut topology, known, PeerRole::Peer, now - 10);
+
+        let entry = |node_id, role, last_seen| PexEntry { node_id, role, last_seen };
+        let added = merge_pex_peers(
+            &mut topology,
+            announcer,
+            &[
+                entry(me, PeerRole::Peer, now),
+                entry(announcer, PeerRole::Relay, now),
+                entry(known, PeerRole::Relay, now),
+                entry(new_relay, PeerRole::Relay, now - 1_000),
+                entry(old, PeerRole::Peer, now - STALE_THRESHOLD_MS),
+            ],
+            me,
+            now,
+        );
+
+        assert_eq!(added, vec![new_relay]);
+        let hint = topology.get(&new_relay).unwrap();
+        assert_eq!(hint.status, PeerStatus::Offline);
+        assert_eq!(hint.role, PeerRole::Relay);
+        // Our own observation wins over hearsay
+        assert_eq!(topology.get(&known).unwrap().role, PeerRole::Peer);
+    }
+}
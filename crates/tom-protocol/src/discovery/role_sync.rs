@@ -74,6 +74,8 @@ impl RoleChangeAnnounce {
         bytes.push(match self.new_role {
             PeerRole::Peer => 0,
             PeerRole::Relay => 1,
+            PeerRole::Storage => 2,
+            PeerRole::Bootstrap => 3,
         });
         bytes.extend_from_slice(&self.score.to_le_bytes());
         bytes.extend_from_slice(&self.timestamp.to_le_bytes());
@@ -5,6 +5,8 @@
 ///
 /// Algorithm: BFS clustering over a weighted communication graph.
 /// Edges decay linearly over time, subnets dissolve on inactivity.
+/// Measured round-trip times to our peers steer clustering: slow links
+/// don't count as edges, and low-latency neighbors join first.
 use std::collections::{HashMap, HashSet, VecDeque};
 
 use crate::types::NodeId;
@@ -29,6 +31,48 @@ pub const EDGE_DECAY_MS: u64 = 10 * 60 * 1000;
 /// How often to run evaluation (30 seconds). Caller uses this as interval.
 pub const EVALUATION_INTERVAL_MS: u64 = 30_000;
 
+/// Links slower than this (round trip) don't count toward clustering.
+pub const MAX_LINK_RTT_MS: u64 = 250;
+
+/// Weight of a new round-trip sample in the moving average.
+const RTT_ALPHA: f64 = 0.3;
+
+// ── Config ───────────────────────────────────────────────────────────────
+
+/// Formation/dissolution thresholds (defaults are the constants above).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubnetConfig {
+    /// Minimum messages between two nodes to consider them connected.
+    pub min_edge_weight: u32,
+    /// Minimum cluster size to form a subnet (a subnet dissolves below it).
+    pub min_subnet_size: usize,
+    /// Maximum cluster size (BFS stops here).
+    pub max_subnet_size: usize,
+    /// Dissolve a subnet after this long without traffic between members (ms).
+    pub inactivity_timeout_ms: u64,
+    /// Edge decay starts after this age (ms).
+    pub edge_decay_ms: u64,
+    /// How often the caller runs `evaluate` (ms).
+    pub evaluation_interval_ms: u64,
+    /// Measured links slower than this (round trip, ms) are left out of
+    /// clustering, however busy.
+    pub max_link_rtt_ms: u64,
+}
+
+impl Default for SubnetConfig {
+    fn default() -> Self {
+        Self {
+            min_edge_weight: MIN_EDGE_WEIGHT,
+            min_subnet_size: MIN_SUBNET_SIZE,
+            max_subnet_size: MAX_SUBNET_SIZE,
+            inactivity_timeout_ms: INACTIVITY_TIMEOUT_MS,
+            edge_decay_ms: EDGE_DECAY_MS,
+            evaluation_interval_ms: EVALUATION_INTERVAL_MS,
+            max_link_rtt_ms: MAX_LINK_RTT_MS,
+        }
+    }
+}
+
 // ── Types ────────────────────────────────────────────────────────────────
 
 /// A communication edge between two nodes.
@@ -48,12 +92,48 @@ pub struct SubnetInfo {
     pub formed_at: u64,
     pub last_activity: u64,
     pub density_score: f64,
+    /// Messages exchanged between members since formation.
+    pub message_count: u64,
+    /// Members lost since formation (departed or removed).
+    pub members_left: u32,
+    /// Average measured round trip from us to its members, at formation.
+    /// None if none was measured.
+    pub avg_rtt_ms: Option<u64>,
 }
 
 impl SubnetInfo {
     pub fn member_count(&self) -> usize {
         self.members.len()
     }
+
+    /// Time since formation.
+    pub fn age_ms(&self, now: u64) -> u64 {
+        now.saturating_sub(self.formed_at)
+    }
+
+    /// Time since the last message between members.
+    pub fn idle_ms(&self, now: u64) -> u64 {
+        now.saturating_sub(self.last_activity)
+    }
+}
+
+/// Lifetime totals of a subnet manager.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubnetStats {
+    /// Subnets active now.
+    pub active: usize,
+    /// Nodes currently in a subnet.
+    pub nodes_in_subnets: usize,
+    /// Tracked communication edges.
+    pub edges: usize,
+    /// Subnets formed so far.
+    pub formed: u64,
+    /// Subnets dissolved for inactivity.
+    pub dissolved_inactive: u64,
+    /// Subnets dissolved for falling below the minimum size.
+    pub dissolved_undersize: u64,
+    /// Members that left a subnet (including dissolutions).
+    pub members_left: u64,
 }
 
 /// Events emitted by the subnet manager.
@@ -78,8 +158,7 @@ pub enum DissolveReason {
 ///
 /// Pure state machine — caller drives evaluation and handles events.
 pub struct EphemeralSubnetManager {
-    /// Local node identity (used by higher-level orchestration).
-    #[allow(dead_code)]
+    /// Local node identity (for `local_subnet`).
     self_node_id: NodeId,
     /// Communication graph: edge_key → edge.
     edges: HashMap<String, CommunicationEdge>,
@@ -89,17 +168,67 @@ pub struct EphemeralSubnetManager {
     node_subnets: HashMap<NodeId, String>,
     /// Counter for deterministic subnet IDs.
     next_subnet_seq: u64,
+    /// Formation/dissolution thresholds.
+    config: SubnetConfig,
+    /// Lifetime totals (the live counts are filled in by `stats`).
+    totals: SubnetStats,
+    /// Moving average round trip (ms) from us to each peer.
+    rtt_ms: HashMap<NodeId, f64>,
 }
 
 impl EphemeralSubnetManager {
-    /// Create a new subnet manager.
+    /// Create a new subnet manager with default thresholds.
     pub fn new(self_node_id: NodeId) -> Self {
+        Self::with_config(self_node_id, SubnetConfig::default())
+    }
+
+    /// Create a subnet manager with custom thresholds.
+    pub fn with_config(self_node_id: NodeId, config: SubnetConfig) -> Self {
         Self {
             self_node_id,
             edges: HashMap::new(),
             subnets: HashMap::new(),
             node_subnets: HashMap::new(),
             next_subnet_seq: 0,
+            config,
+            totals: SubnetStats::default(),
+            rtt_ms: HashMap::new(),
+        }
+    }
+
+    /// Thresholds in use.
+    pub fn config(&self) -> &SubnetConfig {
+        &self.config
+    }
+
+    /// Fold a measured round trip (ms) from us to `peer` into its average.
+    pub fn record_rtt(&mut self, peer: NodeId, sample_ms: u64) {
+        if peer == self.self_node_id
+            || (self.rtt_ms.len() >= crate::relay::MAX_PEERS && !self.rtt_ms.contains_key(&peer))
+        {
+            return;
+        }
+        let sample = sample_ms as f64;
+        self.rtt_ms
+            .entry(peer)
+            .and_modify(|avg| *avg += RTT_ALPHA * (sample - *avg))
+            .or_insert(sample);
+    }
+
+    /// Average round trip from us to `peer`, if measured.
+    pub fn rtt_ms(&self, peer: &NodeId) -> Option<u64> {
+        self.rtt_ms.get(peer).map(|avg| avg.round() as u64)
+    }
+
+    /// Round trip of the link between `a` and `b`, if it's one of ours
+    /// and was measured.
+    fn link_rtt(&self, a: &NodeId, b: &NodeId) -> Option<u64> {
+        if *a == self.self_node_id {
+            self.rtt_ms(b)
+        } else if *b == self.self_node_id {
+            self.rtt_ms(a)
+        } else {
+            None
         }
     }
 
@@ -122,6 +251,7 @@ impl EphemeralSubnetManager {
             if self.node_subnets.get(&to) == Some(subnet_id) {
                 if let Some(subnet) = self.subnets.get_mut(subnet_id) {
                     subnet.last_activity = now;
+                    subnet.message_count = subnet.message_count.saturating_add(1);
                 }
             }
         }
@@ -155,12 +285,16 @@ impl EphemeralSubnetManager {
                 node_id: *node_id,
             });
 
+            self.totals.members_left += 1;
             if let Some(subnet) = self.subnets.get_mut(&subnet_id) {
                 subnet.members.remove(node_id);
+                subnet.members_left = subnet.members_left.saturating_add(1);
 
-                if subnet.members.len() < MIN_SUBNET_SIZE {
+                if subnet.members.len() < self.config.min_subnet_size {
                     // Dissolve undersize subnet
                     let members: Vec<NodeId> = subnet.members.iter().copied().collect();
+                    self.totals.members_left += members.len() as u64;
+                    self.totals.dissolved_undersize += 1;
                     for member in &members {
                         self.node_subnets.remove(member);
                         events.push(SubnetEvent::NodeLeftSubnet {
@@ -178,6 +312,7 @@ impl EphemeralSubnetManager {
         }
 
         // Remove edges involving this node
+        self.rtt_ms.remove(node_id);
         let node_str = node_id.to_string();
         self.edges.retain(|key, _| !key.contains(&node_str));
 
@@ -198,6 +333,20 @@ impl EphemeralSubnetManager {
         }
     }
 
+    /// Get the subnet the local node belongs to.
+    pub fn local_subnet(&self) -> Option<&SubnetInfo> {
+        self.get_node_subnet(&self.self_node_id)
+    }
+
+    /// Members of the subnets of `a` and `b` (empty if neither is in one).
+    pub fn members_around(&self, a: &NodeId, b: &NodeId) -> HashSet<NodeId> {
+        [a, b]
+            .into_iter()
+            .filter_map(|n| self.get_node_subnet(n))
+            .flat_map(|s| s.members.iter().copied())
+            .collect()
+    }
+
     /// Get all active subnets.
     pub fn all_subnets(&self) -> Vec<&SubnetInfo> {
         self.subnets.values().collect()
@@ -213,6 +362,16 @@ impl EphemeralSubnetManager {
         self.edges.len()
     }
 
+    /// Live counts and lifetime totals.
+    pub fn stats(&self) -> SubnetStats {
+        SubnetStats {
+            active: self.subnets.len(),
+            nodes_in_subnets: self.node_subnets.len(),
+            edges: self.edges.len(),
+            ..self.totals
+        }
+    }
+
     /// Number of nodes currently in subnets.
     pub fn nodes_in_subnets(&self) -> usize {
         self.node_subnets.len()
@@ -220,16 +379,17 @@ impl EphemeralSubnetManager {
 
     // ── Internal ─────────────────────────────────────────────────────────
 
-    /// Decay edges that are older than EDGE_DECAY_MS.
+    /// Decay edges that are older than `edge_decay_ms`.
     fn decay_edges(&mut self, now: u64) {
+        let decay_ms = self.config.edge_decay_ms.max(1);
         self.edges.retain(|_, edge| {
             let age = now.saturating_sub(edge.last_seen);
-            if age <= EDGE_DECAY_MS {
+            if age <= decay_ms {
                 return true; // Not old enough to decay
             }
 
-            // Linear decay: factor = max(0, 1 - (age/EDGE_DECAY_MS - 1))
-            let ratio = age as f64 / EDGE_DECAY_MS as f64;
+            // Linear decay: factor = max(0, 1 - (age/decay_ms - 1))
+            let ratio = age as f64 / decay_ms as f64;
             let factor = (2.0 - ratio).max(0.0);
             edge.message_count = (edge.message_count as f64 * factor) as u32;
 
@@ -245,15 +405,20 @@ impl EphemeralSubnetManager {
         let mut to_dissolve = vec![];
 
         for (id, subnet) in &self.subnets {
-            if subnet.members.len() < MIN_SUBNET_SIZE {
+            if subnet.members.len() < self.config.min_subnet_size {
                 to_dissolve.push((id.clone(), DissolveReason::InsufficientMembers));
-            } else if now.saturating_sub(subnet.last_activity) > INACTIVITY_TIMEOUT_MS {
+            } else if now.saturating_sub(subnet.last_activity) > self.config.inactivity_timeout_ms {
                 to_dissolve.push((id.clone(), DissolveReason::Inactive));
             }
         }
 
         for (subnet_id, reason) in to_dissolve {
             if let Some(subnet) = self.subnets.remove(&subnet_id) {
+                match reason {
+                    DissolveReason::Inactive => self.totals.dissolved_inactive += 1,
+                    DissolveReason::InsufficientMembers => self.totals.dissolved_undersize += 1,
+                }
+                self.totals.members_left += subnet.members.len() as u64;
                 for member in &subnet.members {
                     self.node_subnets.remove(member);
                     dissolved_nodes.insert(*member);
@@ -274,18 +439,30 @@ impl EphemeralSubnetManager {
     fn form_new_subnets(&mut self, now: u64, skip_nodes: &HashSet<NodeId>) -> Vec<SubnetEvent> {
         let mut events = vec![];
 
-        // Build adjacency list from strong edges
+        // Build adjacency list from strong edges on links that aren't slow
         let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
         for edge in self.edges.values() {
-            if edge.message_count >= MIN_EDGE_WEIGHT {
+            let fast = self
+                .link_rtt(&edge.from, &edge.to)
+                .is_none_or(|rtt| rtt <= self.config.max_link_rtt_ms);
+            if edge.message_count >= self.config.min_edge_weight && fast {
                 adjacency.entry(edge.from).or_default().push(edge.to);
                 adjacency.entry(edge.to).or_default().push(edge.from);
             }
         }
+        // Lowest-latency neighbors first (unmeasured last), so capped
+        // clusters fill up with the closest nodes
+        for (node, neighbors) in adjacency.iter_mut() {
+            neighbors.sort_by_key(|n| self.link_rtt(node, n).unwrap_or(u64::MAX));
+        }
 
         // BFS from each unvisited, unsubnetted node
         let mut visited: HashSet<NodeId> = HashSet::new();
-        let all_nodes: Vec<NodeId> = adjacency.keys().copied().collect();
+        // Start from us, then our closest peers, so our links seed clusters
+        let mut all_nodes: Vec<NodeId> = adjacency.keys().copied().collect();
+        all_nodes.sort_by_key(|n| {
+            (*n != self.self_node_id, self.link_rtt(&self.self_node_id, n).unwrap_or(u64::MAX))
+        });
 
         for start in all_nodes {
             if visited.contains(&start)
@@ -302,7 +479,7 @@ impl EphemeralSubnetManager {
             visited.insert(start);
 
             while let Some(node) = queue.pop_front() {
-                if cluster.len() >= MAX_SUBNET_SIZE {
+                if cluster.len() >= self.config.max_subnet_size {
                     break;
                 }
                 cluster.push(node);
@@ -312,7 +489,7 @@ impl EphemeralSubnetManager {
                         if !visited.contains(&neighbor)
                             && !self.node_subnets.contains_key(&neighbor)
                             && !skip_nodes.contains(&neighbor)
-                            && cluster.len() < MAX_SUBNET_SIZE
+                            && cluster.len() < self.config.max_subnet_size
                         {
                             visited.insert(neighbor);
                             queue.push_back(neighbor);
@@ -322,10 +499,13 @@ impl EphemeralSubnetManager {
             }
 
             // Only form subnet if large enough
-            if cluster.len() >= MIN_SUBNET_SIZE {
+            if cluster.len() >= self.config.min_subnet_size {
                 let subnet_id = self.generate_subnet_id();
                 let members: HashSet<NodeId> = cluster.iter().copied().collect();
                 let density = self.calculate_density(&members);
+                let rtts: Vec<u64> = members.iter().filter_map(|m| self.rtt_ms(m)).collect();
+                let avg_rtt_ms = (!rtts.is_empty())
+                    .then(|| rtts.iter().sum::<u64>() / rtts.len() as u64);
 
                 let subnet = SubnetInfo {
                     subnet_id: subnet_id.clone(),
@@ -333,7 +513,11 @@ impl EphemeralSubnetManager {
                     formed_at: now,
                     last_activity: now,
                     density_score: density,
+                    message_count: 0,
+                    members_left: 0,
+                    avg_rtt_ms,
                 };
+                self.totals.formed += 1;
 
                 for &member in &members {
                     self.node_subnets.insert(member, subnet_id.clone());
@@ -390,13 +574,6 @@ fn edge_key(a: &NodeId, b: &NodeId) -> String {
     }
 }
 
-fn _now_ms() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .expect("system time before epoch")
-        .as_millis() as u64
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -690,6 +867,24 @@ mod tests {
         assert_eq!(formed, 0);
     }
 
+    #[test]
+    fn local_subnet_and_members_around() {
+        let me = node_id(0);
+        let (a, b, c, d) = (node_id(1), node_id(2), node_id(3), node_id(4));
+        let mut mgr = EphemeralSubnetManager::new(me);
+        let now = 10_000u64;
+
+        communicate(&mut mgr, me, a, 5, now);
+        communicate(&mut mgr, a, b, 5, now);
+        communicate(&mut mgr, me, b, 5, now);
+        mgr.evaluate(now);
+
+        let local = mgr.local_subnet().expect("we're in a subnet");
+        assert!(local.members.contains(&a));
+        assert_eq!(mgr.members_around(&me, &c), HashSet::from([me, a, b]));
+        assert!(mgr.members_around(&c, &d).is_empty());
+    }
+
     #[test]
     fn stats() {
         let me = node_id(0);
@@ -710,4 +905,89 @@ mod tests {
         assert_eq!(mgr.subnet_count(), 1);
         assert_eq!(mgr.nodes_in_subnets(), 3);
     }
+
+    #[test]
+    fn per_subnet_metrics_and_totals() {
+        let me = node_id(0);
+        let (a, b, c) = (node_id(1), node_id(2), node_id(3));
+        let mut mgr = EphemeralSubnetManager::new(me);
+        let now = 10_000u64;
+
+        communicate(&mut mgr, a, b, 5, now);
+        communicate(&mut mgr, b, c, 5, now);
+        communicate(&mut mgr, a, c, 5, now);
+        mgr.evaluate(now);
+
+        communicate(&mut mgr, a, b, 2, now + 1_000);
+        let subnet = mgr.get_node_subnet(&a).unwrap();
+        assert_eq!(subnet.message_count, 2);
+        assert_eq!(subnet.age_ms(now + 5_000), 5_000);
+        assert_eq!(subnet.idle_ms(now + 5_000), 4_000);
+
+        // Losing a member drops the subnet below the minimum size
+        mgr.remove_node(&c);
+        let stats = mgr.stats();
+        assert_eq!(stats.formed, 1);
+        assert_eq!(stats.dissolved_undersize, 1);
+        assert_eq!(stats.members_left, 3);
+        assert_eq!(stats.active, 0);
+    }
+
+    #[test]
+    fn thresholds_are_configurable() {
+        let me = node_id(0);
+        let (a, b) = (node_id(1), node_id(2));
+        let config = SubnetConfig {
+            min_edge_weight: 1,
+            min_subnet_size: 2,
+            inactivity_timeout_ms: 1_000,
+            ..SubnetConfig::default()
+        };
+        let mut mgr = EphemeralSubnetManager::with_config(me, config);
+        let now = 10_000u64;
+
+        // A single message between two nodes is enough here
+        communicate(&mut mgr, a, b, 1, now);
+        mgr.evaluate(now);
+        assert!(mgr.are_in_same_subnet(&a, &b));
+
+        mgr.evaluate(now + 1_001);
+        assert_eq!(mgr.subnet_count(), 0);
+        assert_eq!(mgr.stats().dissolved_inactive, 1);
+    }
+
+    #[test]
+    fn slow_links_do_not_cluster_and_fast_ones_join_first() {
+        let me = node_id(0);
+        let (near, far, other) = (node_id(1), node_id(2), node_id(3));
+        let now = 10_000u64;
+
+        // Busy enough for a subnet, but our link to `far` is slow
+        let mut mgr = EphemeralSubnetManager::new(me);
+        mgr.record_rtt(near, 15);
+        mgr.record_rtt(far, MAX_LINK_RTT_MS + 100);
+        communicate(&mut mgr, me, near, 5, now);
+        communicate(&mut mgr, me, far, 5, now);
+        mgr.evaluate(now);
+        assert_eq!(mgr.subnet_count(), 0);
+
+        // Capped at three members: the low-latency neighbors get in
+        let config = SubnetConfig {
+            max_subnet_size: 3,
+            ..SubnetConfig::default()
+        };
+        let mut mgr = EphemeralSubnetManager::with_config(me, config);
+        mgr.record_rtt(near, 10);
+        mgr.record_rtt(other, 30);
+        mgr.record_rtt(far, 200);
+        for peer in [near, far, other] {
+            communicate(&mut mgr, me, peer, 5, now);
+        }
+        let events = mgr.evaluate(now);
+        assert!(events.iter().any(|e| matches!(e, SubnetEvent::SubnetFormed { .. })));
+        let subnet = mgr.local_subnet().expect("we're in it");
+        assert!(subnet.members.contains(&near) && subnet.members.contains(&other));
+        assert!(!subnet.members.contains(&far));
+        assert_eq!(subnet.avg_rtt_ms, Some(20));
+    }
 }
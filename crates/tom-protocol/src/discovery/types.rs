@@ -5,20 +5,29 @@
 /// what a node announces about itself (username, roles, capabilities).
 use serde::{Deserialize, Serialize};
 
+use crate::backup::BackupPreference;
+use super::devices::{DeviceCertificate, DeviceRevocation, MAX_ANNOUNCED_REVOCATIONS};
+use super::directory::UsernameClaim;
+use super::hosting::{IdentityBinding, MAX_HOSTED_IDENTITIES};
+use super::pex::PexEntry;
 use crate::relay::PeerRole;
 use crate::types::{now_ms, NodeId};
 
 // ── Constants ────────────────────────────────────────────────────────────
 
-/// Heartbeat interval (5 seconds).
+/// Heartbeat interval (5 seconds, Desktop profile).
 pub const HEARTBEAT_INTERVAL_MS: u64 = 5_000;
 
-/// Stale threshold — peer becomes stale after missing 2 gossip announces (20s).
+/// Stale threshold — peer becomes stale after missing 2 gossip announces (20s, Desktop profile).
 pub const STALE_THRESHOLD_MS: u64 = 20_000;
 
-/// Offline threshold — peer becomes offline after missing ~4 gossip announces (45s).
+/// Offline threshold — peer becomes offline after missing ~4 gossip announces (45s, Desktop profile).
 pub const OFFLINE_THRESHOLD_MS: u64 = 45_000;
 
+/// Longest announce interval we accept from a peer (5 minutes) — bounds
+/// how long a silent peer can stay online on our side.
+pub const MAX_ANNOUNCE_INTERVAL_MS: u64 = 5 * 60 * 1000;
+
 /// Maximum allowed clock drift for timestamps (5 minutes).
 pub const MAX_FUTURE_DRIFT_MS: u64 = 5 * 60 * 1000;
 
@@ -28,6 +37,109 @@ pub const GOSSIP_INTERVAL_MS: u64 = 10_000;
 /// Max peers returned in a single gossip response.
 pub const MAX_PEERS_PER_GOSSIP: usize = 20;
 
+/// Longest custom presence text carried in an announce (chars).
+pub const MAX_PRESENCE_TEXT_CHARS: usize = 64;
+
+// ── Liveness profiles ────────────────────────────────────────────────────
+
+/// What kind of device a node runs on, for picking liveness timing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeploymentProfile {
+    /// Battery-constrained: slow keepalives, patient thresholds.
+    Mobile,
+    #[default]
+    Desktop,
+    /// Always-on infrastructure (relays): fast failure detection.
+    Server,
+    /// Battery saver: the slowest keepalives, for devices that mostly sleep.
+    LowPower,
+}
+
+/// Liveness timing: how often we check peers and announce ourselves, and
+/// how long a peer may stay silent before it goes stale, then offline.
+///
+/// Peers announce their own interval, so a Mobile node's slow keepalives
+/// don't get it declared offline by a Desktop or Server node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LivenessConfig {
+    /// Interval for heartbeat liveness checks (ms).
+    pub heartbeat_interval_ms: u64,
+    /// Interval for our gossip announces — the keepalive peers see (ms).
+    pub announce_interval_ms: u64,
+    /// Silence after which a peer is stale (ms).
+    pub stale_threshold_ms: u64,
+    /// Silence after which a peer is offline (ms).
+    pub offline_threshold_ms: u64,
+}
+
+impl LivenessConfig {
+    /// Preset timing for a deployment profile.
+    pub fn for_profile(profile: DeploymentProfile) -> Self {
+        match profile {
+            DeploymentProfile::Mobile => Self {
+                heartbeat_interval_ms: 15_000,
+                announce_interval_ms: 30_000,
+                stale_threshold_ms: 60_000,
+                offline_threshold_ms: 135_000,
+            },
+            DeploymentProfile::Desktop => Self {
+                heartbeat_interval_ms: HEARTBEAT_INTERVAL_MS,
+                announce_interval_ms: GOSSIP_INTERVAL_MS,
+                stale_threshold_ms: STALE_THRESHOLD_MS,
+                offline_threshold_ms: OFFLINE_THRESHOLD_MS,
+            },
+            DeploymentProfile::Server => Self {
+                heartbeat_interval_ms: 2_000,
+                announce_interval_ms: 5_000,
+                stale_threshold_ms: 10_000,
+                offline_threshold_ms: 25_000,
+            },
+            DeploymentProfile::LowPower => Self {
+                heartbeat_interval_ms: 60_000,
+                announce_interval_ms: 120_000,
+                stale_threshold_ms: 240_000,
+                offline_threshold_ms: 540_000,
+            },
+        }
+    }
+}
+
+impl Default for LivenessConfig {
+    fn default() -> Self {
+        Self::for_profile(DeploymentProfile::Desktop)
+    }
+}
+
+// ── Presence ─────────────────────────────────────────────────────────────
+
+/// Availability a node advertises to chat UIs (independent of liveness).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Presence {
+    #[default]
+    Online,
+    Away,
+    Busy,
+    /// Free-form status text, capped at `MAX_PRESENCE_TEXT_CHARS`.
+    Custom(String),
+}
+
+impl Presence {
+    /// Trim and cap custom text; empty text falls back to `Online`.
+    pub fn normalized(self) -> Self {
+        match self {
+            Presence::Custom(text) => {
+                let text: String = text.trim().chars().take(MAX_PRESENCE_TEXT_CHARS).collect();
+                if text.is_empty() {
+                    Presence::Online
+                } else {
+                    Presence::Custom(text)
+                }
+            }
+            other => other,
+        }
+    }
+}
+
 // ── PeerAnnounce ─────────────────────────────────────────────────────────
 
 /// Payload for PeerAnnounce messages — what a node broadcasts about itself.
@@ -45,6 +157,34 @@ pub struct PeerAnnounce {
     pub encryption_key: Option<[u8; 32]>,
     /// Announcement timestamp (Unix ms).
     pub timestamp: u64,
+    /// Who may hold this node's messages while it is offline.
+    #[serde(default)]
+    pub backup: BackupPreference,
+    /// Availability shown to chat UIs.
+    #[serde(default)]
+    pub presence: Presence,
+    /// Signed claim on `username` for the directory (None = not claimed).
+    #[serde(default)]
+    pub claim: Option<UsernameClaim>,
+    /// Peer exchange: recently verified peers (empty when PEX is off).
+    #[serde(default)]
+    pub peers: Vec<PexEntry>,
+    /// How often this node announces (ms); None = the Desktop default.
+    #[serde(default)]
+    pub announce_interval_ms: Option<u64>,
+    /// Forwarding load over the last minute, percent of capacity.
+    #[serde(default)]
+    pub relay_load: Option<u8>,
+    /// Extra identities this node serves, each bound by its own signature.
+    #[serde(default)]
+    pub hosted: Vec<IdentityBinding>,
+    /// Our user's certificate for this node, when it is one of a user's
+    /// linked devices.
+    #[serde(default)]
+    pub device: Option<DeviceCertificate>,
+    /// Devices our user has revoked, for peers to stop trusting them.
+    #[serde(default)]
+    pub revocations: Vec<DeviceRevocation>,
 }
 
 impl PeerAnnounce {
@@ -56,9 +196,75 @@ impl PeerAnnounce {
             roles,
             encryption_key: Some(node_id.as_bytes()),
             timestamp: now_ms(),
+            backup: BackupPreference::Open,
+            presence: Presence::Online,
+            claim: None,
+            peers: Vec::new(),
+            announce_interval_ms: None,
+            relay_load: None,
+            hosted: Vec::new(),
+            device: None,
+            revocations: Vec::new(),
         }
     }
 
+    /// Announce who may hold our messages while we're offline.
+    pub fn with_backup_preference(mut self, backup: BackupPreference) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// Announce our availability (custom text is normalized).
+    pub fn with_presence(mut self, presence: Presence) -> Self {
+        self.presence = presence.normalized();
+        self
+    }
+
+    /// Attach our signed username claim.
+    pub fn with_username_claim(mut self, claim: Option<UsernameClaim>) -> Self {
+        self.claim = claim;
+        self
+    }
+
+    /// Share known-good peers (PEX), capped at `MAX_PEERS_PER_GOSSIP`.
+    pub fn with_pex_peers(mut self, mut peers: Vec<PexEntry>) -> Self {
+        peers.truncate(MAX_PEERS_PER_GOSSIP);
+        self.peers = peers;
+        self
+    }
+
+    /// Advertise our announce interval so peers scale their thresholds.
+    pub fn with_announce_interval(mut self, interval_ms: u64) -> Self {
+        self.announce_interval_ms = Some(interval_ms);
+        self
+    }
+
+    /// Advertise how loaded we are as a relay (percent, capped at 100).
+    pub fn with_relay_load(mut self, percent: u8) -> Self {
+        self.relay_load = Some(percent.min(100));
+        self
+    }
+
+    /// List the identities we host, capped at `MAX_HOSTED_IDENTITIES`.
+    pub fn with_hosted(mut self, mut hosted: Vec<IdentityBinding>) -> Self {
+        hosted.truncate(MAX_HOSTED_IDENTITIES);
+        self.hosted = hosted;
+        self
+    }
+
+    /// Present our device certificate and our user's revocations (capped at
+    /// `MAX_ANNOUNCED_REVOCATIONS`).
+    pub fn with_device(
+        mut self,
+        device: Option<DeviceCertificate>,
+        mut revocations: Vec<DeviceRevocation>,
+    ) -> Self {
+        revocations.truncate(MAX_ANNOUNCED_REVOCATIONS);
+        self.device = device;
+        self.revocations = revocations;
+        self
+    }
+
     /// Whether this announcement is within acceptable clock drift.
     pub fn is_timestamp_valid(&self, now: u64) -> bool {
         // Not too far in the future
@@ -186,6 +392,21 @@ mod tests {
         assert!(!announce.is_timestamp_valid(now));
     }
 
+    #[test]
+    fn presence_roundtrips_and_normalizes() {
+        let id = node_id(1);
+        let announce = PeerAnnounce::new(id, "alice".into(), vec![])
+            .with_presence(Presence::Custom(format!("  {}  ", "x".repeat(100))));
+        assert_eq!(announce.presence, Presence::Custom("x".repeat(MAX_PRESENCE_TEXT_CHARS)));
+
+        let bytes = rmp_serde::to_vec(&announce).expect("serialize");
+        let decoded: PeerAnnounce = rmp_serde::from_slice(&bytes).expect("deserialize");
+        assert_eq!(decoded.presence, announce.presence);
+
+        assert_eq!(Presence::Custom("   ".into()).normalized(), Presence::Online);
+        assert_eq!(Presence::Busy.normalized(), Presence::Busy);
+    }
+
     #[test]
     fn discovery_source_roundtrip() {
         for source in [DiscoverySource::Direct, DiscoverySource::Gossip, DiscoverySource::Announce, DiscoverySource::Dht] {
@@ -1,4 +1,5 @@
 use ed25519_dalek::Signer;
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
 
 use crate::crypto;
@@ -83,6 +84,15 @@ impl Envelope {
         rmp_serde::from_slice(data).map_err(Into::into)
     }
 
+    /// Final recipient of an encoded envelope, read in place: the payload,
+    /// relay chain and signature are skipped rather than copied out.
+    pub fn recipient(data: &[u8]) -> Result<NodeId, TomProtocolError> {
+        let header: RoutingHeader<'_> = rmp_serde::from_slice(data)?;
+        header.to.parse().map_err(|_| TomProtocolError::InvalidEnvelope {
+            reason: format!("invalid recipient: {}", header.to),
+        })
+    }
+
     /// Produce the canonical bytes to sign/verify.
     ///
     /// Includes all fields except `signature` to avoid circular dependency.
@@ -252,6 +262,15 @@ impl EnvelopeBuilder {
         env
     }
 
+    /// Build and sign an envelope whose payload is already a serialized
+    /// `EncryptedPayload` for the recipient (e.g. a backed-up message).
+    pub fn sign_sealed(self, secret_seed: &[u8; 32]) -> Envelope {
+        let mut env = self.build();
+        env.encrypted = true;
+        env.sign(secret_seed);
+        env
+    }
+
     /// Encrypt the payload, then build and sign.
     ///
     /// Order: encrypt → sign (sign covers the ciphertext, so relays can
@@ -283,6 +302,23 @@ struct SignableEnvelope<'a> {
     encrypted: bool,
 }
 
+/// Routing view of an encoded [`Envelope`]: the recipient borrowed from
+/// the input, every other field skipped. Same layout as `Envelope`.
+#[derive(Deserialize)]
+#[allow(dead_code)] // skipped fields only keep the layout
+struct RoutingHeader<'a> {
+    id: IgnoredAny,
+    from: IgnoredAny,
+    to: &'a str,
+    via: IgnoredAny,
+    msg_type: IgnoredAny,
+    payload: IgnoredAny,
+    timestamp: IgnoredAny,
+    signature: IgnoredAny,
+    ttl: IgnoredAny,
+    encrypted: IgnoredAny,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -323,6 +359,22 @@ mod tests {
         assert_eq!(env, decoded);
     }
 
+    #[test]
+    fn recipient_reads_the_header_in_place() {
+        let mut env = make_envelope(MessageType::Chat, vec![7; 4096]);
+        env.via = vec![node_id(3)];
+        env.signature = vec![1; 64];
+        let bytes = env.to_bytes().unwrap();
+        assert_eq!(Envelope::recipient(&bytes).unwrap(), env.to);
+
+        // Named (map) encoding decodes too
+        let named = rmp_serde::to_vec_named(&env).unwrap();
+        assert_eq!(Envelope::recipient(&named).unwrap(), env.to);
+
+        assert!(Envelope::recipient(&bytes[..bytes.len() / 2]).is_err());
+        assert!(Envelope::recipient(b"not an envelope").is_err());
+    }
+
     #[test]
     fn roundtrip_all_message_types() {
         let types = [
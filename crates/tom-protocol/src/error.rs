@@ -4,6 +4,7 @@
 /// (crypto, routing, serialization).
 #[derive(Debug, thiserror::Error)]
 pub enum TomProtocolError {
+    #[cfg(feature = "native")]
     #[error("transport error: {0}")]
     Transport(#[from] tom_transport::TomTransportError),
 
@@ -27,6 +28,42 @@ pub enum TomProtocolError {
 
     #[error("relay rejected message: {reason}")]
     RelayRejected { reason: String },
+
+    #[error("invalid invite token: {reason}")]
+    InvalidInviteToken { reason: String },
+
+    #[error("unknown content type: {content_type}")]
+    UnknownContentType { content_type: String },
+
+    #[error("invalid channel: {reason}")]
+    InvalidChannel { reason: String },
+
+    #[error("invalid shared state: {reason}")]
+    InvalidSharedState { reason: String },
+
+    #[error("invalid mesh group: {reason}")]
+    InvalidMeshGroup { reason: String },
+
+    #[error("invalid contact card: {reason}")]
+    InvalidContactCard { reason: String },
+
+    #[error("invalid pairing: {reason}")]
+    InvalidPairing { reason: String },
+
+    #[error("invalid config: {reason}")]
+    InvalidConfig { reason: String },
+
+    #[error("request timed out: {request_id}")]
+    RequestTimeout { request_id: String },
+
+    #[error("request failed: {reason}")]
+    RequestFailed { reason: String },
+
+    #[error("file transfer failed: {reason}")]
+    Transfer { reason: String },
+
+    #[error("payload too large: {size} bytes (max {max})")]
+    PayloadTooLarge { size: usize, max: usize },
 }
 
 impl From<rmp_serde::encode::Error> for TomProtocolError {
@@ -80,4 +117,52 @@ mod tests {
         };
         assert_eq!(err.to_string(), "relay rejected message: ttl exceeded");
     }
+
+    #[test]
+    fn test_display_invalid_invite_token() {
+        let err = TomProtocolError::InvalidInviteToken {
+            reason: "expired".into(),
+        };
+        assert_eq!(err.to_string(), "invalid invite token: expired");
+    }
+
+    #[test]
+    fn test_display_unknown_content_type() {
+        let err = TomProtocolError::UnknownContentType {
+            content_type: "app/poll".into(),
+        };
+        assert_eq!(err.to_string(), "unknown content type: app/poll");
+    }
+
+    #[test]
+    fn test_display_invalid_channel() {
+        let err = TomProtocolError::InvalidChannel {
+            reason: "not joined".into(),
+        };
+        assert_eq!(err.to_string(), "invalid channel: not joined");
+    }
+
+    #[test]
+    fn test_display_invalid_shared_state() {
+        let err = TomProtocolError::InvalidSharedState {
+            reason: "value too large".into(),
+        };
+        assert_eq!(err.to_string(), "invalid shared state: value too large");
+    }
+
+    #[test]
+    fn test_display_invalid_mesh_group() {
+        let err = TomProtocolError::InvalidMeshGroup {
+            reason: "at most 8 members".into(),
+        };
+        assert_eq!(err.to_string(), "invalid mesh group: at most 8 members");
+    }
+
+    #[test]
+    fn test_display_invalid_contact_card() {
+        let err = TomProtocolError::InvalidContactCard {
+            reason: "fingerprint mismatch".into(),
+        };
+        assert_eq!(err.to_string(), "invalid contact card: fingerprint mismatch");
+    }
 }
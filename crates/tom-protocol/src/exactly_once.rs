@@ -0,0 +1,454 @@
+/// Exactly-once delivery — opt-in mode for payloads where duplicates hurt.
+///
+/// Regular chat is at-least-once: the router dedup cache is in memory and
+/// the sender gives up after a few retries. This mode adds three pieces:
+///
+/// - **Outbox** (sender): the signed envelope is persisted and re-sent with
+///   backoff until the receiver commits it, or until `EXACTLY_ONCE_MAX_AGE_MS`.
+/// - **Inbox** (receiver): `(sender, message_id)` is recorded *before* the
+///   message reaches the application, and kept for
+///   `EXACTLY_ONCE_DEDUP_RETENTION_MS` — longer than any sender retries.
+/// - **Commit**: the application calls `commit_message` once it has durably
+///   processed the payload; only then does the receiver send a signed commit
+///   ack, which clears the sender's outbox entry.
+///
+/// Failure-mode boundaries:
+/// - Guarantees survive restarts on both sides only with a `data_dir`
+///   (SQLite). Without one, a restart forgets the outbox and the inbox.
+/// - If the receiving app crashes after delivery but before committing, the
+///   message is delivered again after restart (once per process lifetime).
+///   Apps must commit atomically with their side effects, or key them by
+///   `envelope_id`.
+/// - A sender that hits `EXACTLY_ONCE_MAX_AGE_MS` reports the message as
+///   expired: it may or may not have been processed.
+/// - Retries arriving after the receiver's dedup retention window would be
+///   seen as new. The retention is the sender's max age (the 24h message
+///   lifespan) plus `EXACTLY_ONCE_CLOCK_SKEW_MS`, so a sender whose clock
+///   runs up to that much behind the receiver's is still covered.
+/// - The inbox keeps at most `MAX_INBOX_RECORDS_PER_SENDER` records per
+///   sender (`MAX_INBOX_RECORDS` overall). Past that, new messages are
+///   refused unrecorded, and the sender retries until old records age out.
+/// - A message the app's channel drops (overflow policy) is delivered
+///   again on the sender's next retry.
+///
+/// Pure state: the runtime writes through to the store on every change.
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{NodeId, MESSAGE_LIFESPAN_MS};
+
+/// Sender gives up after this long without a commit (the 24h lifespan).
+pub const EXACTLY_ONCE_MAX_AGE_MS: u64 = MESSAGE_LIFESPAN_MS;
+
+/// Clock-skew margin between sender and receiver covered by the dedup
+/// retention (1h). The sender ages its outbox on its own clock, the
+/// receiver its inbox on its own; a slower sender clock keeps retrying
+/// past the receiver's 24h mark.
+pub const EXACTLY_ONCE_CLOCK_SKEW_MS: u64 = 60 * 60 * 1000;
+
+/// Receiver keeps dedup records this long (25h: lifespan plus skew margin).
+pub const EXACTLY_ONCE_DEDUP_RETENTION_MS: u64 =
+    EXACTLY_ONCE_MAX_AGE_MS + EXACTLY_ONCE_CLOCK_SKEW_MS;
+
+/// Most dedup records kept for one sender; its new messages are refused
+/// (and retried by the sender) until old records age out.
+pub const MAX_INBOX_RECORDS_PER_SENDER: usize = 10_000;
+
+/// Most dedup records kept across all senders.
+pub const MAX_INBOX_RECORDS: usize = 100_000;
+
+/// First retry delay; doubles per attempt.
+const RETRY_BASE_MS: u64 = 5_000;
+
+/// Retry delay cap (5 min).
+const RETRY_MAX_MS: u64 = 5 * 60 * 1000;
+
+/// Delay before the next retry after `attempts` sends.
+pub fn retry_delay_ms(attempts: u32) -> u64 {
+    RETRY_BASE_MS
+        .saturating_mul(1u64 << attempts.saturating_sub(1).min(16))
+        .min(RETRY_MAX_MS)
+}
+
+/// Commit ack payload (receiver → sender, signed envelope).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitPayload {
+    pub message_id: String,
+}
+
+// ── Outbox ──────────────────────────────────────────────────────────────
+
+/// A message waiting for its commit ack.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub message_id: String,
+    pub to: NodeId,
+    /// Serialized signed envelope (re-sent verbatim so the id never changes).
+    pub envelope: Vec<u8>,
+    pub created_ms: u64,
+    pub attempts: u32,
+    pub next_retry_ms: u64,
+}
+
+/// Result of a retry pass.
+#[derive(Debug, Default)]
+pub struct RetryPass {
+    /// Entries to re-send now (already rescheduled).
+    pub resend: Vec<OutboxEntry>,
+    /// Entries dropped after `EXACTLY_ONCE_MAX_AGE_MS`.
+    pub expired: Vec<OutboxEntry>,
+}
+
+/// Sender-side outbox.
+#[derive(Debug, Default)]
+pub struct ExactlyOnceOutbox {
+    entries: HashMap<String, OutboxEntry>,
+}
+
+impl ExactlyOnceOutbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a freshly sent message (first attempt already made).
+    pub fn insert(
+        &mut self,
+        message_id: String,
+        to: NodeId,
+        envelope: Vec<u8>,
+        now_ms: u64,
+    ) -> OutboxEntry {
+        let entry = OutboxEntry {
+            message_id: message_id.clone(),
+            to,
+            envelope,
+            created_ms: now_ms,
+            attempts: 1,
+            next_retry_ms: now_ms + retry_delay_ms(1),
+        };
+        self.entries.insert(message_id, entry.clone());
+        entry
+    }
+
+    /// Handle a commit ack. Only the original recipient can commit.
+    pub fn commit(&mut self, message_id: &str, from: NodeId) -> Option<OutboxEntry> {
+        if self.entries.get(message_id)?.to != from {
+            return None;
+        }
+        self.entries.remove(message_id)
+    }
+
+    /// Collect due retries and expired entries.
+    pub fn retry_pass(&mut self, now_ms: u64) -> RetryPass {
+        let mut pass = RetryPass::default();
+        let expired: Vec<String> = self
+            .entries
+            .values()
+            .filter(|e| e.created_ms + EXACTLY_ONCE_MAX_AGE_MS <= now_ms)
+            .map(|e| e.message_id.clone())
+            .collect();
+        for id in expired {
+            if let Some(entry) = self.entries.remove(&id) {
+                pass.expired.push(entry);
+            }
+        }
+        for entry in self.entries.values_mut() {
+            if entry.next_retry_ms <= now_ms {
+                entry.attempts += 1;
+                entry.next_retry_ms = now_ms + retry_delay_ms(entry.attempts);
+                pass.resend.push(entry.clone());
+            }
+        }
+        pass
+    }
+
+    /// Whether a message is still waiting for its commit.
+    pub fn contains(&self, message_id: &str) -> bool {
+        self.entries.contains_key(message_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Restore persisted entries (retries resume on the next pass).
+    pub fn restore(&mut self, entries: Vec<OutboxEntry>) {
+        for entry in entries {
+            self.entries.insert(entry.message_id.clone(), entry);
+        }
+    }
+}
+
+// ── Inbox ───────────────────────────────────────────────────────────────
+
+/// Receiver-side dedup record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InboxRecord {
+    pub received_ms: u64,
+    pub committed: bool,
+}
+
+/// What to do with an incoming exactly-once message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboxDecision {
+    /// New (or uncommitted from a previous run): record, then deliver.
+    Deliver,
+    /// Already committed: re-send the commit ack, don't deliver.
+    AckAgain,
+    /// Delivered this session, app hasn't committed yet: ignore.
+    Hold,
+    /// New, but the inbox is full (for this sender or overall): drop it
+    /// unrecorded; the sender retries later.
+    Refuse,
+}
+
+/// Receiver-side dedup inbox.
+#[derive(Debug, Default)]
+pub struct ExactlyOnceInbox {
+    records: HashMap<(NodeId, String), InboxRecord>,
+    /// Record count per sender, for `MAX_INBOX_RECORDS_PER_SENDER`.
+    per_sender: HashMap<NodeId, usize>,
+    /// Handed to the application since this process started.
+    handed_out: HashSet<(NodeId, String)>,
+}
+
+impl ExactlyOnceInbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decide on an incoming message. `Deliver` records it as received.
+    pub fn on_receive(&mut self, from: NodeId, message_id: &str, now_ms: u64) -> InboxDecision {
+        let key = (from, message_id.to_string());
+        match self.records.get(&key) {
+            Some(record) if record.committed => InboxDecision::AckAgain,
+            Some(_) if self.handed_out.contains(&key) => InboxDecision::Hold,
+            Some(_) => {
+                self.handed_out.insert(key);
+                InboxDecision::Deliver
+            }
+            None => {
+                let from_sender = self.per_sender.get(&from).copied().unwrap_or(0);
+                if from_sender >= MAX_INBOX_RECORDS_PER_SENDER || self.records.len() >= MAX_INBOX_RECORDS {
+                    return InboxDecision::Refuse;
+                }
+                self.insert(
+                    key.clone(),
+                    InboxRecord {
+                        received_ms: now_ms,
+                        committed: false,
+                    },
+                );
+                self.handed_out.insert(key);
+                InboxDecision::Deliver
+            }
+        }
+    }
+
+    /// Forget a receive that couldn't be persisted (the sender will retry).
+    pub fn forget(&mut self, from: NodeId, message_id: &str) {
+        let key = (from, message_id.to_string());
+        if self.records.remove(&key).is_some() {
+            self.uncount(&from);
+        }
+        self.handed_out.remove(&key);
+    }
+
+    /// The application never got a delivered message (its channel was
+    /// full): keep the record, but deliver again on the next retry.
+    pub fn release(&mut self, from: NodeId, message_id: &str) {
+        self.handed_out.remove(&(from, message_id.to_string()));
+    }
+
+    /// Mark a delivered message as committed by the application.
+    ///
+    /// Returns the record to persist, or None for unknown messages.
+    pub fn commit(&mut self, from: NodeId, message_id: &str) -> Option<InboxRecord> {
+        let key = (from, message_id.to_string());
+        let record = self.records.get_mut(&key)?;
+        record.committed = true;
+        self.handed_out.remove(&key);
+        Some(*record)
+    }
+
+    /// Current record for a message.
+    pub fn record(&self, from: NodeId, message_id: &str) -> Option<InboxRecord> {
+        self.records.get(&(from, message_id.to_string())).copied()
+    }
+
+    /// Drop records older than the retention window. Returns how many.
+    pub fn purge(&mut self, now_ms: u64) -> usize {
+        let before = self.records.len();
+        let per_sender = &mut self.per_sender;
+        self.records.retain(|(from, _), r| {
+            let keep = r.received_ms + EXACTLY_ONCE_DEDUP_RETENTION_MS > now_ms;
+            if !keep {
+                if let Some(count) = per_sender.get_mut(from) {
+                    *count -= 1;
+                }
+            }
+            keep
+        });
+        self.per_sender.retain(|_, count| *count > 0);
+        let records = &self.records;
+        self.handed_out.retain(|k| records.contains_key(k));
+        before - self.records.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Restore persisted records (nothing counts as handed out yet).
+    pub fn restore(&mut self, records: Vec<(NodeId, String, InboxRecord)>) {
+        for (from, message_id, record) in records {
+            self.insert((from, message_id), record);
+        }
+    }
+
+    fn insert(&mut self, key: (NodeId, String), record: InboxRecord) {
+        let from = key.0;
+        if self.records.insert(key, record).is_none() {
+            *self.per_sender.entry(from).or_default() += 1;
+        }
+    }
+
+    fn uncount(&mut self, from: &NodeId) {
+        if let Some(count) = self.per_sender.get_mut(from) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_sender.remove(from);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn retry_delay_backs_off_and_caps() {
+        assert_eq!(retry_delay_ms(1), 5_000);
+        assert_eq!(retry_delay_ms(2), 10_000);
+        assert_eq!(retry_delay_ms(3), 20_000);
+        assert_eq!(retry_delay_ms(40), RETRY_MAX_MS);
+    }
+
+    #[test]
+    fn outbox_retries_until_commit_from_recipient() {
+        let (bob, eve) = (node_id(2), node_id(3));
+        let mut outbox = ExactlyOnceOutbox::new();
+        outbox.insert("m1".into(), bob, vec![1, 2, 3], 1_000);
+
+        assert!(outbox.retry_pass(1_000).resend.is_empty());
+        let pass = outbox.retry_pass(6_000);
+        assert_eq!(pass.resend.len(), 1);
+        assert_eq!(pass.resend[0].attempts, 2);
+        // Rescheduled: not due again right away
+        assert!(outbox.retry_pass(6_001).resend.is_empty());
+
+        assert!(outbox.commit("m1", eve).is_none(), "only the recipient commits");
+        assert!(outbox.commit("m1", bob).is_some());
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn outbox_expires_old_entries() {
+        let bob = node_id(2);
+        let mut outbox = ExactlyOnceOutbox::new();
+        outbox.insert("m1".into(), bob, vec![], 0);
+        let pass = outbox.retry_pass(EXACTLY_ONCE_MAX_AGE_MS);
+        assert_eq!(pass.expired.len(), 1);
+        assert!(pass.resend.is_empty());
+        assert!(!outbox.contains("m1"));
+    }
+
+    #[test]
+    fn inbox_delivers_once_and_reacks_after_commit() {
+        let alice = node_id(1);
+        let mut inbox = ExactlyOnceInbox::new();
+
+        assert_eq!(inbox.on_receive(alice, "m1", 0), InboxDecision::Deliver);
+        assert_eq!(inbox.on_receive(alice, "m1", 1), InboxDecision::Hold);
+        assert!(inbox.commit(alice, "m1").is_some());
+        assert_eq!(inbox.on_receive(alice, "m1", 2), InboxDecision::AckAgain);
+        assert!(inbox.commit(alice, "unknown").is_none());
+    }
+
+    #[test]
+    fn inbox_redelivers_uncommitted_after_restart() {
+        let alice = node_id(1);
+        let mut inbox = ExactlyOnceInbox::new();
+        inbox.restore(vec![(
+            alice,
+            "m1".into(),
+            InboxRecord {
+                received_ms: 0,
+                committed: false,
+            },
+        )]);
+        assert_eq!(inbox.on_receive(alice, "m1", 1), InboxDecision::Deliver);
+        assert_eq!(inbox.on_receive(alice, "m1", 2), InboxDecision::Hold);
+    }
+
+    #[test]
+    fn inbox_refuses_past_its_caps() {
+        let (alice, bob) = (node_id(1), node_id(2));
+        let mut inbox = ExactlyOnceInbox::new();
+        for i in 0..MAX_INBOX_RECORDS_PER_SENDER {
+            assert_eq!(inbox.on_receive(alice, &format!("m{i}"), 0), InboxDecision::Deliver);
+        }
+        assert_eq!(inbox.on_receive(alice, "one-more", 0), InboxDecision::Refuse);
+        assert!(inbox.record(alice, "one-more").is_none());
+        // Other senders are not affected
+        assert_eq!(inbox.on_receive(bob, "m0", 0), InboxDecision::Deliver);
+
+        // Room comes back as records age out
+        inbox.forget(alice, "m0");
+        assert_eq!(inbox.on_receive(alice, "one-more", 0), InboxDecision::Deliver);
+        assert_eq!(inbox.purge(EXACTLY_ONCE_DEDUP_RETENTION_MS), MAX_INBOX_RECORDS_PER_SENDER + 1);
+        assert!(inbox.per_sender.is_empty());
+    }
+
+    #[test]
+    fn inbox_redelivers_released_message() {
+        let alice = node_id(1);
+        let mut inbox = ExactlyOnceInbox::new();
+        assert_eq!(inbox.on_receive(alice, "m1", 0), InboxDecision::Deliver);
+        // The app's channel dropped it
+        inbox.release(alice, "m1");
+        assert!(inbox.record(alice, "m1").is_some());
+        assert_eq!(inbox.on_receive(alice, "m1", 1), InboxDecision::Deliver);
+        assert_eq!(inbox.on_receive(alice, "m1", 2), InboxDecision::Hold);
+    }
+
+    #[test]
+    fn inbox_purges_after_retention() {
+        let alice = node_id(1);
+        let mut inbox = ExactlyOnceInbox::new();
+        inbox.on_receive(alice, "m1", 0);
+        // Still held past the sender's max age, for a sender clock running behind
+        assert_eq!(inbox.purge(EXACTLY_ONCE_MAX_AGE_MS + EXACTLY_ONCE_CLOCK_SKEW_MS / 2), 0);
+        assert_eq!(inbox.on_receive(alice, "m1", EXACTLY_ONCE_MAX_AGE_MS), InboxDecision::Hold);
+        assert_eq!(inbox.purge(EXACTLY_ONCE_DEDUP_RETENTION_MS - 1), 0);
+        assert_eq!(inbox.purge(EXACTLY_ONCE_DEDUP_RETENTION_MS), 1);
+        assert!(inbox.is_empty());
+    }
+}
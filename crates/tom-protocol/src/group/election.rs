@@ -41,14 +41,38 @@ pub fn elect_hub(
     group: &GroupInfo,
     failed_hub: &NodeId,
     topology: &Topology,
+) -> ElectionResult {
+    elect_among(group.backup_hub_id, failed_hub, topology, |_| true)
+}
+
+/// Elect the sub-hub relaying fan-out for one shard of a large group.
+///
+/// Same algorithm as [`elect_hub`], restricted to the shard's members:
+/// the current sub-hub plays the backup role (kept while online), the
+/// primary hub is never elected. The primary runs this on every heartbeat.
+pub fn elect_sub_hub(
+    shard: &[NodeId],
+    current: Option<NodeId>,
+    primary_hub: &NodeId,
+    topology: &Topology,
+) -> ElectionResult {
+    let current = current.filter(|id| shard.contains(id));
+    elect_among(current, primary_hub, topology, |id| shard.contains(id))
+}
+
+fn elect_among(
+    backup: Option<NodeId>,
+    excluded: &NodeId,
+    topology: &Topology,
+    eligible: impl Fn(&NodeId) -> bool,
 ) -> ElectionResult {
     // 1. Check backup hub first
-    if let Some(backup) = &group.backup_hub_id {
-        if *backup != *failed_hub {
-            if let Some(peer) = topology.get(backup) {
+    if let Some(backup) = backup {
+        if backup != *excluded {
+            if let Some(peer) = topology.get(&backup) {
                 if peer.status == PeerStatus::Online {
                     return ElectionResult {
-                        new_hub_id: Some(*backup),
+                        new_hub_id: Some(backup),
                         reason: ElectionReason::Backup,
                         candidate_count: 1,
                     };
@@ -62,9 +86,10 @@ pub fn elect_hub(
         .online_relays()
         .into_iter()
         .filter(|peer| {
-            peer.node_id != *failed_hub
+            peer.node_id != *excluded
                 && peer.role == PeerRole::Relay
                 && peer.status == PeerStatus::Online
+                && eligible(&peer.node_id)
         })
         .map(|peer| peer.node_id)
         .collect();
@@ -115,6 +140,13 @@ mod tests {
             shadow_id: None,
             candidate_id: None,
             invite_only: false,
+            description: String::new(),
+            avatar_hash: None,
+            config_version: 0,
+            bans: Vec::new(),
+            message_ttl_ms: None,
+            polls: Vec::new(),
+            settings: GroupSettings::default(),
         }
     }
 
@@ -245,4 +277,32 @@ mod tests {
             assert_eq!(result.new_hub_id, first.new_hub_id);
         }
     }
+
+    #[test]
+    fn sub_hub_elected_within_shard_only() {
+        let primary = node_id(10);
+        let shard = vec![node_id(30), node_id(31), node_id(32)];
+
+        let mut topology = Topology::new();
+        add_relay(&mut topology, primary);
+        add_relay(&mut topology, node_id(20)); // relay outside the shard
+        let result = elect_sub_hub(&shard, None, &primary, &topology);
+        assert_eq!(result.reason, ElectionReason::NoCandidates);
+
+        add_relay(&mut topology, shard[1]);
+        add_relay(&mut topology, shard[2]);
+        let first = elect_sub_hub(&shard, None, &primary, &topology);
+        assert!(first.new_hub_id.is_some_and(|id| shard.contains(&id)));
+        assert_eq!(first.candidate_count, 2);
+
+        // An online current sub-hub is kept (no churn on every tick)
+        let other = if first.new_hub_id == Some(shard[1]) { shard[2] } else { shard[1] };
+        let kept = elect_sub_hub(&shard, Some(other), &primary, &topology);
+        assert_eq!(kept.new_hub_id, Some(other));
+        assert_eq!(kept.reason, ElectionReason::Backup);
+
+        // A current sub-hub that left the shard is not
+        let moved = elect_sub_hub(&shard, Some(node_id(20)), &primary, &topology);
+        assert_eq!(moved.new_hub_id, first.new_hub_id);
+    }
 }
@@ -5,16 +5,20 @@
 ///
 /// Responsibilities:
 /// - Manage group membership (create, join, leave, kick)
-/// - Fan out messages to all members except sender
+/// - Fan out messages to all members except sender (sharded across
+///   elected member sub-hubs beyond `MAX_DIRECT_FANOUT` members)
 /// - Rate limiting per sender per group
 /// - Message dedup via nonce/ID tracking
 /// - Message history for sync to new members
+/// - Reaction fan-out (deduplicated per member/emoji)
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::time::Instant;
 
 use serde::{Deserialize, Serialize};
 
+use crate::group::election::elect_sub_hub;
 use crate::group::types::*;
+use crate::relay::Topology;
+use crate::time::Instant;
 use crate::types::{now_ms, NodeId};
 
 /// Serializable snapshot of GroupHub's persistent state.
@@ -27,6 +31,9 @@ pub struct GroupHubSnapshot {
     /// Next sequence number per group (for offline delivery gap-fill).
     #[serde(default)]
     pub next_seqs: HashMap<GroupId, u64>,
+    /// Scheduled messages not yet released, per group.
+    #[serde(default)]
+    pub scheduled: HashMap<GroupId, Vec<GroupMessage>>,
 }
 
 /// Maximum age of a group message before the hub rejects it (5 minutes).
@@ -35,6 +42,9 @@ const MESSAGE_MAX_AGE_MS: u64 = 5 * 60 * 1000;
 /// Maximum clock skew (future) allowed for group messages (30 seconds).
 const MESSAGE_MAX_FUTURE_MS: u64 = 30 * 1000;
 
+/// Maximum outstanding invite tokens per group.
+const MAX_INVITE_TOKENS_PER_GROUP: usize = 32;
+
 /// Hub-side state for a single group.
 struct HubGroup {
     info: GroupInfo,
@@ -50,6 +60,9 @@ struct HubGroup {
     seen_nonces: HashSet<[u8; 24]>,
     /// Nodes that have been invited (for invite-only groups).
     invited_set: HashSet<NodeId>,
+    /// Registered invite tokens: join secret hash → expires_at.
+    /// Ephemeral (24h max) — not persisted across hub restarts.
+    invite_tokens: HashMap<[u8; 32], u64>,
     /// Latest sender key payload per sender (for proactive re-sync fanout).
     latest_sender_keys: HashMap<NodeId, (u32, Vec<EncryptedSenderKey>)>,
     /// Epoch state per sender.
@@ -58,6 +71,53 @@ struct HubGroup {
     group_msg_since_rotation: u64,
     /// Last rotation trigger timestamp.
     last_rotation_trigger_ms: u64,
+    /// Per-member delivery tracking for recent messages (message_id → state).
+    /// Ephemeral — not persisted across hub restarts.
+    deliveries: HashMap<String, MessageDelivery>,
+    /// Insertion order of `deliveries` (for bounded eviction).
+    delivery_order: VecDeque<String>,
+    /// Current reactions: message_id → emoji → reactors.
+    /// Ephemeral — not persisted across hub restarts.
+    reactions: HashMap<String, HashMap<String, HashSet<NodeId>>>,
+    /// Insertion order of `reactions` (for bounded eviction).
+    reaction_order: VecDeque<String>,
+    /// Elected sub-hub per shard of `info.members` (shard i = members
+    /// `i * MAX_DIRECT_FANOUT..`). Empty for small groups.
+    /// Ephemeral — re-elected on every heartbeat tick.
+    sub_hubs: Vec<Option<NodeId>>,
+    /// Messages held until their `deliver_at`, soonest first.
+    scheduled: Vec<GroupMessage>,
+    /// Join requests awaiting an admin decision, oldest first.
+    /// Ephemeral — not persisted across hub restarts.
+    join_requests: Vec<GroupJoinRequest>,
+    /// When each member last got an explicit heartbeat.
+    /// Ephemeral — a restarted hub heartbeats everyone.
+    heartbeat_sent: HashMap<NodeId, u64>,
+    /// Shadow carried by the last heartbeat round (a change is sent to all).
+    heartbeat_shadow: Option<NodeId>,
+}
+
+impl HubGroup {
+    /// Drop the oldest history beyond the group's history limit
+    /// (`default_limit` unless an admin set one). Returns how many went.
+    fn trim_history(&mut self, default_limit: usize) -> usize {
+        let limit = self
+            .info
+            .settings
+            .history_limit
+            .map_or(default_limit, |n| n as usize);
+        let excess = self.message_history.len().saturating_sub(limit);
+        self.message_history.drain(..excess);
+        excess
+    }
+}
+
+/// Who a fanned-out message went to, and who has confirmed it.
+struct MessageDelivery {
+    sender: NodeId,
+    recipients: HashSet<NodeId>,
+    delivered: HashSet<NodeId>,
+    read: HashSet<NodeId>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -121,6 +181,16 @@ impl GroupHub {
         self.groups.get(group_id).map(|g| &g.message_history)
     }
 
+    /// Delivery status of a message fanned out by this hub.
+    pub fn delivery_status(&self, group_id: &GroupId, message_id: &str) -> Option<GroupDeliveryStatus> {
+        let delivery = self.groups.get(group_id)?.deliveries.get(message_id)?;
+        Some(GroupDeliveryStatus {
+            delivered: delivery.delivered.iter().copied().collect(),
+            read: delivery.read.iter().copied().collect(),
+            all_delivered: delivery.delivered.len() == delivery.recipients.len(),
+        })
+    }
+
     /// Remove messages older than `max_age_ms` from in-memory history.
     /// Returns number of messages purged across all groups.
     pub fn cleanup_expired_messages(&mut self, now_ms: u64, max_age_ms: u64) -> usize {
@@ -161,7 +231,8 @@ impl GroupHub {
             GroupPayload::DeliveryAck {
                 group_id,
                 message_id,
-            } => self.handle_delivery_ack(from, &group_id, &message_id),
+                read,
+            } => self.handle_delivery_ack(from, &group_id, &message_id, read),
 
             GroupPayload::SenderKeyDistribution {
                 ref group_id,
@@ -189,6 +260,83 @@ impl GroupHub {
                 target_id,
             } => self.invite_member(group_id, &from, target_id),
 
+            GroupPayload::BanMember {
+                ref group_id,
+                target_id,
+                duration_ms,
+            } => self.ban_member(group_id, &from, target_id, duration_ms),
+
+            GroupPayload::UnbanMember {
+                ref group_id,
+                ref target_id,
+            } => self.unban_member(group_id, &from, target_id),
+
+            GroupPayload::UpdateMetadata {
+                ref group_id,
+                name,
+                description,
+                avatar_hash,
+            } => self.update_metadata(group_id, &from, name, description, avatar_hash),
+
+            GroupPayload::SetMessageTtl {
+                ref group_id,
+                ttl_ms,
+            } => self.set_message_ttl(group_id, &from, ttl_ms),
+
+            GroupPayload::UpdateSettings {
+                ref group_id,
+                settings,
+            } => self.update_settings(group_id, &from, settings),
+
+            // Invite tokens
+            GroupPayload::RegisterInviteToken {
+                ref group_id,
+                secret_hash,
+                expires_at,
+            } => self.register_invite_token(group_id, &from, secret_hash, expires_at),
+
+            GroupPayload::JoinWithToken {
+                ref group_id,
+                username,
+                ref join_secret,
+            } => self.handle_join_with_token(from, group_id, username, join_secret),
+
+            // Join approval
+            GroupPayload::JoinRequest { ref group_id, username } => {
+                self.handle_join_request(from, group_id, username)
+            }
+
+            GroupPayload::ApproveJoin {
+                ref group_id,
+                requester,
+            } => self.approve_join(group_id, &from, requester),
+
+            GroupPayload::DenyJoin {
+                ref group_id,
+                requester,
+            } => self.deny_join(group_id, &from, requester),
+
+            GroupPayload::Reaction {
+                ref group_id,
+                ref message_id,
+                emoji,
+                add,
+                reactor: _,
+            } => self.handle_reaction(from, group_id, message_id, emoji, add),
+
+            GroupPayload::CancelScheduled {
+                ref group_id,
+                ref message_id,
+            } => self.handle_cancel_scheduled(from, group_id, message_id),
+
+            GroupPayload::Poll { ref group_id, poll } => self.handle_poll(from, group_id, poll),
+
+            GroupPayload::Vote {
+                ref group_id,
+                ref poll_id,
+                option,
+            } => self.handle_vote(from, group_id, poll_id, option),
+
             // Hub doesn't process these (they're outgoing from hub or failover-specific)
             GroupPayload::Created { .. }
             | GroupPayload::Invite { .. }
@@ -196,15 +344,25 @@ impl GroupHub {
             | GroupPayload::MemberJoined { .. }
             | GroupPayload::MemberLeft { .. }
             | GroupPayload::MemberRoleChanged { .. }
+            | GroupPayload::MetadataChanged { .. }
+            | GroupPayload::MessageTtlChanged { .. }
+            | GroupPayload::SettingsChanged { .. }
+            | GroupPayload::JoinRequested { .. }
+            | GroupPayload::JoinRequestResolved { .. }
+            | GroupPayload::DeliveryReceipt { .. }
             | GroupPayload::HubMigration { .. }
+            | GroupPayload::ShardRelay { .. }
             | GroupPayload::HubHeartbeat { .. }
             | GroupPayload::HubPong { .. }
             | GroupPayload::HubShadowSync { .. }
             | GroupPayload::CandidateAssigned { .. }
             | GroupPayload::HubUnreachable { .. }
-            // SyncRequest/SyncResponse handled by runtime, not hub
+            // SyncRequest/SyncResponse/FetchHistory handled by runtime, not hub
+            // (they need the persistent store)
             | GroupPayload::SyncRequest { .. }
-            | GroupPayload::SyncResponse { .. } => vec![],
+            | GroupPayload::SyncResponse { .. }
+            | GroupPayload::FetchHistory { .. }
+            | GroupPayload::HistoryPage { .. } => vec![],
         }
     }
 
@@ -241,6 +399,13 @@ impl GroupHub {
             shadow_id: None,
             candidate_id: None,
             invite_only,
+            description: String::new(),
+            avatar_hash: None,
+            config_version: 0,
+            bans: Vec::new(),
+            message_ttl_ms: None,
+            polls: Vec::new(),
+            settings: GroupSettings::default(),
         };
 
         // Build invited set from initial members (for invite-only enforcement)
@@ -254,10 +419,20 @@ impl GroupHub {
             seen_message_ids: HashSet::new(),
             seen_nonces: HashSet::new(),
             invited_set,
+            invite_tokens: HashMap::new(),
             latest_sender_keys: HashMap::new(),
             sender_epoch_state: HashMap::new(),
             group_msg_since_rotation: 0,
             last_rotation_trigger_ms: 0,
+            deliveries: HashMap::new(),
+            delivery_order: VecDeque::new(),
+            reactions: HashMap::new(),
+            reaction_order: VecDeque::new(),
+            sub_hubs: Vec::new(),
+            scheduled: Vec::new(),
+            join_requests: Vec::new(),
+            heartbeat_sent: HashMap::new(),
+            heartbeat_shadow: None,
         };
 
         self.groups.insert(group_id.clone(), hub_group);
@@ -303,6 +478,11 @@ impl GroupHub {
             return vec![]; // Group doesn't exist
         };
 
+        // Banned nodes can't rejoin (tokens and invites don't override a ban)
+        if hub_group.info.is_banned(&joiner, now_ms()) {
+            return vec![];
+        }
+
         // Already a member? Re-sync them (they may have restarted).
         if hub_group.info.is_member(&joiner) {
             let recent: Vec<GroupMessage> = hub_group.message_history.iter().cloned().collect();
@@ -446,7 +626,7 @@ impl GroupHub {
 
     // ── Message Fanout ───────────────────────────────────────────────────
 
-    fn handle_message(&mut self, from: NodeId, mut msg: GroupMessage) -> Vec<GroupAction> {
+    fn handle_message(&mut self, from: NodeId, msg: GroupMessage) -> Vec<GroupAction> {
         let group_id = msg.group_id.clone();
         let message_id = msg.message_id.clone();
 
@@ -498,6 +678,38 @@ impl GroupHub {
                 reason: "message timestamp in the future (>30s)".into(),
             })];
         }
+        if msg.deliver_at.is_some_and(|at| at > msg.sent_at + MAX_SCHEDULE_AHEAD_MS) {
+            return vec![GroupAction::Event(GroupEvent::SecurityViolation {
+                group_id,
+                node_id: from,
+                reason: "message scheduled too far ahead (>12h)".into(),
+            })];
+        }
+
+        // Threads: only reference messages this hub relayed in the group
+        if let Some(ref reply_to) = msg.reply_to {
+            if *reply_to == message_id || !self.knows_message(&group_id, reply_to) {
+                return vec![GroupAction::Event(GroupEvent::SecurityViolation {
+                    group_id,
+                    node_id: from,
+                    reason: "reply to unknown message".into(),
+                })];
+            }
+        }
+
+        // Mentions: bounded, and only of current members
+        if !msg.mentions.is_empty() {
+            let info = &self.groups[&group_id].info;
+            if msg.mentions.len() > MAX_MENTIONS
+                || msg.mentions.iter().any(|m| !info.is_member(m))
+            {
+                return vec![GroupAction::Event(GroupEvent::SecurityViolation {
+                    group_id,
+                    node_id: from,
+                    reason: "mention of non-member".into(),
+                })];
+            }
+        }
 
         // Nonce anti-replay for encrypted messages
         if msg.encrypted && !self.check_nonce(&group_id, &msg.nonce) {
@@ -534,7 +746,21 @@ impl GroupHub {
             return vec![];
         }
 
+        // Scheduled: hold until due (released by `release_scheduled`)
+        if msg.deliver_at.is_some_and(|at| at > now) {
+            return self.schedule(from, msg);
+        }
+
+        self.publish(from, msg)
+    }
+
+    /// Assign a sequence number, store and fan out an accepted message.
+    fn publish(&mut self, from: NodeId, mut msg: GroupMessage) -> Vec<GroupAction> {
+        let group_id = msg.group_id.clone();
+        let message_id = msg.message_id.clone();
+
         // Assign monotonic sequence number and store
+        let mut original = None;
         let recipients = {
             let hub_group = self.groups.get_mut(&group_id).unwrap();
             hub_group.info.last_activity_at = now_ms();
@@ -543,15 +769,19 @@ impl GroupHub {
             msg.seq = hub_group.next_seq;
             hub_group.next_seq += 1;
 
+            // Anonymous group: history and members only see the stripped copy
+            if hub_group.info.settings.anonymous {
+                original = Some(msg.clone());
+                msg.anonymize(self.hub_id);
+            }
+
             // Store in history
             hub_group.message_history.push_back(msg.clone());
             self.total_messages += 1;
 
             // Trim per-group history
-            while hub_group.message_history.len() > self.max_messages_per_group {
-                hub_group.message_history.pop_front();
-                self.total_messages = self.total_messages.saturating_sub(1);
-            }
+            let dropped = hub_group.trim_history(self.max_messages_per_group);
+            self.total_messages = self.total_messages.saturating_sub(dropped);
 
             // Collect recipients before releasing borrow
             hub_group
@@ -570,174 +800,564 @@ impl GroupHub {
 
         let mut actions = Vec::new();
         if !recipients.is_empty() {
-            actions.push(GroupAction::Broadcast {
-                to: recipients,
-                payload: GroupPayload::Message(msg),
-            });
+            self.track_delivery(&group_id, &message_id, from, &recipients);
+            let mut recipients = recipients;
+            // Admins get the attributed original so they can moderate
+            if let Some(original) = original {
+                let info = &self.groups[&group_id].info;
+                let (admins, others): (Vec<NodeId>, Vec<NodeId>) =
+                    recipients.into_iter().partition(|id| info.is_admin(id));
+                actions.extend(admins.into_iter().map(|to| GroupAction::Send {
+                    to,
+                    payload: GroupPayload::Message(original.clone()),
+                }));
+                recipients = others;
+            }
+            if !recipients.is_empty() {
+                actions.extend(self.fan_out(&group_id, recipients, msg));
+            }
         }
         actions.extend(self.maybe_trigger_rotation(&group_id));
         actions
     }
 
-    // ── Delivery ACK ─────────────────────────────────────────────────────
+    // ── Scheduled messages ───────────────────────────────────────────────
 
-    fn handle_delivery_ack(
-        &self,
-        _from: NodeId,
-        _group_id: &GroupId,
-        _message_id: &str,
-    ) -> Vec<GroupAction> {
-        // Track delivery confirmation (for future delivery tracking)
-        // Currently a no-op — will be used for read receipts / delivery status
+    /// Hold a validated message until its `deliver_at` (bounded per group).
+    ///
+    /// The hold is part of the message's lifespan: `deliver_at` is at most
+    /// `MAX_SCHEDULE_AHEAD_MS` past `sent_at`, and a held message that
+    /// outlives its group's timer is dropped instead of released.
+    fn schedule(&mut self, from: NodeId, msg: GroupMessage) -> Vec<GroupAction> {
+        let group_id = msg.group_id.clone();
+        let hub_group = self.groups.get_mut(&group_id).unwrap();
+        if hub_group.scheduled.len() >= MAX_SCHEDULED_PER_GROUP {
+            return vec![GroupAction::Event(GroupEvent::SecurityViolation {
+                group_id,
+                node_id: from,
+                reason: "too many scheduled messages".into(),
+            })];
+        }
+        let at = hub_group.scheduled.partition_point(|m| m.deliver_at <= msg.deliver_at);
+        hub_group.scheduled.insert(at, msg);
         vec![]
     }
 
-    // ── Sender Key Distribution ─────────────────────────────────────────
-
-    /// Fan out sender key distribution to individual recipients.
-    ///
-    /// The hub cannot read the keys (they are encrypted per-recipient).
-    /// It simply delivers each encrypted key to the intended recipient.
-    fn handle_sender_key_distribution(
+    /// Sender cancels one of its scheduled messages.
+    fn handle_cancel_scheduled(
         &mut self,
         from: NodeId,
         group_id: &GroupId,
-        epoch: u32,
-        encrypted_keys: &[EncryptedSenderKey],
+        message_id: &str,
     ) -> Vec<GroupAction> {
-        let Some(hub_group) = self.groups.get_mut(group_id) else {
-            return vec![];
-        };
-
-        if !hub_group.info.is_member(&from) {
-            return vec![GroupAction::Event(GroupEvent::SecurityViolation {
-                group_id: group_id.clone(),
-                node_id: from,
-                reason: "non-member sent sender key distribution".into(),
-            })];
+        if let Some(hub_group) = self.groups.get_mut(group_id) {
+            hub_group
+                .scheduled
+                .retain(|m| m.message_id != message_id || m.sender_id != from);
         }
+        vec![]
+    }
 
-        // Cache latest distribution + epoch transition state.
-        if let Some(state) = hub_group.sender_epoch_state.get_mut(&from) {
-            if epoch > state.current_epoch {
-                state.previous_epoch = Some(state.current_epoch);
-                state.current_epoch = epoch;
-                state.grace_until_ms = now_ms().saturating_add(SENDER_KEY_EPOCH_GRACE_MS);
-            }
-        } else {
-            hub_group.sender_epoch_state.insert(
-                from,
-                SenderEpochState {
-                    current_epoch: epoch,
-                    previous_epoch: None,
-                    grace_until_ms: 0,
-                },
-            );
+    /// Fan out every scheduled message due at `now`.
+    ///
+    /// Messages whose sender left the group in the meantime, or whose
+    /// lifespan ran out while held (e.g. across a hub restart), are dropped.
+    pub fn release_scheduled(&mut self, now: u64) -> Vec<GroupAction> {
+        let mut due = Vec::new();
+        for hub_group in self.groups.values_mut() {
+            let count = hub_group
+                .scheduled
+                .partition_point(|m| m.deliver_at.is_none_or(|at| at <= now));
+            let info = &hub_group.info;
+            due.extend(hub_group.scheduled.drain(..count).filter(|m| {
+                info.is_member(&m.sender_id)
+                    && !info.is_message_expired(m, now)
+                    && m.sent_at + crate::types::MESSAGE_LIFESPAN_MS > now
+            }));
         }
-        hub_group
-            .latest_sender_keys
-            .insert(from, (epoch, encrypted_keys.to_vec()));
-
         let mut actions = Vec::new();
-        for ek in encrypted_keys {
-            if hub_group.info.is_member(&ek.recipient_id) {
-                actions.push(GroupAction::Send {
-                    to: ek.recipient_id,
-                    payload: GroupPayload::SenderKeyDistribution {
-                        group_id: group_id.clone(),
-                        from,
-                        epoch,
-                        encrypted_keys: vec![ek.clone()],
-                    },
-                });
-            }
+        for msg in due {
+            actions.extend(self.publish(msg.sender_id, msg));
         }
         actions
     }
 
-    fn check_epoch_policy(
+    /// Scheduled messages held for a group, soonest first.
+    pub fn scheduled(&self, group_id: &GroupId) -> &[GroupMessage] {
+        self.groups
+            .get(group_id)
+            .map(|g| g.scheduled.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Fan a message out to `recipients`.
+    ///
+    /// Small groups get one direct Broadcast. Large ones hand each shard to
+    /// its sub-hub via `ShardRelay`; shards without a usable sub-hub (none
+    /// elected, or it's the sender) are served directly.
+    fn fan_out(
         &self,
         group_id: &GroupId,
-        sender: &NodeId,
-        msg_epoch: u32,
-    ) -> EpochDecision {
-        let Some(hub_group) = self.groups.get(group_id) else {
-            return EpochDecision::Reject;
-        };
-        let Some(state) = hub_group.sender_epoch_state.get(sender) else {
-            return EpochDecision::Accept;
-        };
+        recipients: Vec<NodeId>,
+        msg: GroupMessage,
+    ) -> Vec<GroupAction> {
+        let hub_group = &self.groups[group_id];
+        if hub_group.sub_hubs.iter().all(Option::is_none) {
+            return vec![GroupAction::Broadcast {
+                to: recipients,
+                payload: GroupPayload::Message(msg),
+            }];
+        }
 
-        if msg_epoch == state.current_epoch {
-            return EpochDecision::Accept;
+        let wanted: HashSet<NodeId> = recipients.into_iter().collect();
+        let mut direct = Vec::new();
+        let mut relays = Vec::new();
+        for (i, shard) in hub_group.info.members.chunks(MAX_DIRECT_FANOUT).enumerate() {
+            let shard: Vec<NodeId> = shard
+                .iter()
+                .map(|m| m.node_id)
+                .filter(|id| wanted.contains(id))
+                .collect();
+            match hub_group.sub_hubs.get(i).copied().flatten() {
+                Some(sub_hub) if shard.contains(&sub_hub) => {
+                    relays.push(GroupAction::Send {
+                        to: sub_hub,
+                        payload: GroupPayload::ShardRelay {
+                            group_id: group_id.clone(),
+                            recipients: shard.into_iter().filter(|id| *id != sub_hub).collect(),
+                            message: msg.clone(),
+                        },
+                    });
+                }
+                _ => direct.extend(shard),
+            }
         }
-        if state.previous_epoch == Some(msg_epoch) && now_ms() <= state.grace_until_ms {
-            return EpochDecision::Accept;
+
+        let mut actions = Vec::new();
+        if !direct.is_empty() {
+            actions.push(GroupAction::Broadcast {
+                to: direct,
+                payload: GroupPayload::Message(msg),
+            });
         }
-        EpochDecision::Reject
+        actions.extend(relays);
+        actions
     }
 
-    fn build_proactive_sender_key_replay(&self, group_id: &GroupId, target: NodeId) -> Vec<GroupAction> {
-        let Some(hub_group) = self.groups.get(group_id) else {
-            return vec![];
-        };
-
-        let mut actions = Vec::new();
-        for (sender_id, (epoch, encrypted_keys)) in &hub_group.latest_sender_keys {
-            if let Some(ek) = encrypted_keys.iter().find(|ek| ek.recipient_id == target) {
-                actions.push(GroupAction::Send {
-                    to: target,
-                    payload: GroupPayload::SenderKeyDistribution {
-                        group_id: group_id.clone(),
-                        from: *sender_id,
-                        epoch: *epoch,
-                        encrypted_keys: vec![ek.clone()],
-                    },
-                });
+    /// Re-elect sub-hubs for every group larger than `MAX_DIRECT_FANOUT`.
+    ///
+    /// Each shard keeps its sub-hub while it stays an online relay; small
+    /// groups drop theirs and go back to direct fan-out.
+    pub fn assign_sub_hubs(&mut self, topology: &Topology) {
+        let hub_id = self.hub_id;
+        for hub_group in self.groups.values_mut() {
+            if hub_group.info.members.len() <= MAX_DIRECT_FANOUT {
+                hub_group.sub_hubs.clear();
+                continue;
             }
+            let sub_hubs = hub_group
+                .info
+                .members
+                .chunks(MAX_DIRECT_FANOUT)
+                .enumerate()
+                .map(|(i, shard)| {
+                    let shard: Vec<NodeId> = shard.iter().map(|m| m.node_id).collect();
+                    let current = hub_group.sub_hubs.get(i).copied().flatten();
+                    elect_sub_hub(&shard, current, &hub_id, topology).new_hub_id
+                })
+                .collect();
+            hub_group.sub_hubs = sub_hubs;
         }
-        actions
     }
 
-    fn maybe_trigger_rotation(&mut self, group_id: &GroupId) -> Vec<GroupAction> {
+    /// Current sub-hub per shard of a group (empty if not sharded).
+    pub fn sub_hubs(&self, group_id: &GroupId) -> &[Option<NodeId>] {
+        self.groups
+            .get(group_id)
+            .map(|g| g.sub_hubs.as_slice())
+            .unwrap_or_default()
+    }
+
+    // ── Delivery ACK ─────────────────────────────────────────────────────
+
+    /// Start tracking who a fanned-out message must reach.
+    fn track_delivery(
+        &mut self,
+        group_id: &GroupId,
+        message_id: &str,
+        sender: NodeId,
+        recipients: &[NodeId],
+    ) {
         let Some(hub_group) = self.groups.get_mut(group_id) else {
-            return vec![];
+            return;
         };
-        if hub_group.last_rotation_trigger_ms == 0 {
-            hub_group.last_rotation_trigger_ms = now_ms();
+        hub_group.deliveries.insert(
+            message_id.to_string(),
+            MessageDelivery {
+                sender,
+                recipients: recipients.iter().copied().collect(),
+                delivered: HashSet::new(),
+                read: HashSet::new(),
+            },
+        );
+        hub_group.delivery_order.push_back(message_id.to_string());
+        while hub_group.delivery_order.len() > MAX_TRACKED_DELIVERIES {
+            if let Some(old) = hub_group.delivery_order.pop_front() {
+                hub_group.deliveries.remove(&old);
+            }
         }
-        hub_group.group_msg_since_rotation = hub_group.group_msg_since_rotation.saturating_add(1);
-        let now = now_ms();
-        let elapsed = now.saturating_sub(hub_group.last_rotation_trigger_ms);
+    }
 
-        let should_trigger = hub_group.group_msg_since_rotation >= SENDER_KEY_ROTATE_MAX_MESSAGES
-            || elapsed >= SENDER_KEY_ROTATE_MAX_AGE_MS;
-        if !should_trigger {
+    /// Record a member's delivery/read confirmation and tell the sender.
+    ///
+    /// Only recipients of the original fan-out count; repeated acks are
+    /// silent so the sender sees one receipt per state change.
+    fn handle_delivery_ack(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        message_id: &str,
+        read: bool,
+    ) -> Vec<GroupAction> {
+        let Some(delivery) = self
+            .groups
+            .get_mut(group_id)
+            .and_then(|g| g.deliveries.get_mut(message_id))
+        else {
+            return vec![];
+        };
+        if !delivery.recipients.contains(&from) {
             return vec![];
         }
 
-        // Anti-spam: max one trigger per hour.
-        if hub_group.last_rotation_trigger_ms > 0 && elapsed < SENDER_KEY_ROTATE_RATE_LIMIT_MS {
+        let newly_delivered = delivery.delivered.insert(from);
+        let newly_read = read && delivery.read.insert(from);
+        if !newly_delivered && !newly_read {
             return vec![];
         }
 
-        hub_group.last_rotation_trigger_ms = now;
-        hub_group.group_msg_since_rotation = 0;
+        vec![GroupAction::Send {
+            to: delivery.sender,
+            payload: GroupPayload::DeliveryReceipt {
+                group_id: group_id.clone(),
+                message_id: message_id.to_string(),
+                member: from,
+                read: delivery.read.contains(&from),
+                all_delivered: delivery.delivered.len() == delivery.recipients.len(),
+            },
+        }]
+    }
 
-        let group_members = hub_group.info.members.clone();
-        let latest_sender_keys = hub_group.latest_sender_keys.clone();
+    // ── Reactions ────────────────────────────────────────────────────────
 
-        let mut actions = Vec::new();
-        for (sender_id, (epoch, encrypted_keys)) in latest_sender_keys {
-            for ek in encrypted_keys {
-                if group_members.iter().any(|m| m.node_id == ek.recipient_id) {
-                    actions.push(GroupAction::Send {
-                        to: ek.recipient_id,
-                        payload: GroupPayload::SenderKeyDistribution {
-                            group_id: group_id.clone(),
-                            from: sender_id,
-                            epoch,
-                            encrypted_keys: vec![ek],
+    /// Apply a member's reaction and fan it out to every member (reactor included).
+    ///
+    /// Reactions share the message rate limit. Repeated adds/removes are
+    /// dropped, as are reactions to messages this hub never relayed.
+    fn handle_reaction(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        message_id: &str,
+        emoji: String,
+        add: bool,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get(group_id) else {
+            return vec![];
+        };
+        if !hub_group.info.is_member(&from) {
+            return vec![GroupAction::Event(GroupEvent::SecurityViolation {
+                group_id: group_id.clone(),
+                node_id: from,
+                reason: "non-member attempted to react".into(),
+            })];
+        }
+        let valid_emoji = !emoji.is_empty()
+            && emoji.len() <= MAX_REACTION_LEN
+            && !emoji.chars().any(|c| c.is_whitespace() || c.is_control());
+        if !valid_emoji || !self.knows_message(group_id, message_id) {
+            return vec![];
+        }
+        if !self.check_rate_limit(group_id, &from) {
+            return vec![];
+        }
+
+        let hub_group = self.groups.get_mut(group_id).unwrap();
+        if !hub_group.reactions.contains_key(message_id) {
+            if !add {
+                return vec![];
+            }
+            hub_group.reaction_order.push_back(message_id.to_string());
+            while hub_group.reaction_order.len() > MAX_TRACKED_REACTIONS {
+                if let Some(old) = hub_group.reaction_order.pop_front() {
+                    hub_group.reactions.remove(&old);
+                }
+            }
+        }
+        let by_emoji = hub_group.reactions.entry(message_id.to_string()).or_default();
+        let changed = if add {
+            by_emoji.entry(emoji.clone()).or_default().insert(from)
+        } else {
+            let removed = by_emoji.get_mut(&emoji).is_some_and(|r| r.remove(&from));
+            by_emoji.retain(|_, reactors| !reactors.is_empty());
+            removed
+        };
+        if !changed {
+            return vec![];
+        }
+
+        vec![GroupAction::Broadcast {
+            to: hub_group.info.members.iter().map(|m| m.node_id).collect(),
+            payload: GroupPayload::Reaction {
+                group_id: group_id.clone(),
+                message_id: message_id.to_string(),
+                emoji,
+                add,
+                reactor: from,
+            },
+        }]
+    }
+
+    // ── Polls ───────────────────────────────────────────────────────────
+
+    /// Open a poll on behalf of a member and publish it with empty tallies.
+    fn handle_poll(&mut self, from: NodeId, group_id: &GroupId, poll: GroupPoll) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get(group_id) else {
+            return vec![];
+        };
+        if !hub_group.info.is_member(&from) {
+            return vec![GroupAction::Event(GroupEvent::SecurityViolation {
+                group_id: group_id.clone(),
+                node_id: from,
+                reason: "non-member attempted to open a poll".into(),
+            })];
+        }
+        let question = poll.question.trim().to_string();
+        let valid = !question.is_empty()
+            && question.len() <= MAX_POLL_QUESTION_LEN
+            && (2..=MAX_POLL_OPTIONS).contains(&poll.options.len())
+            && poll
+                .options
+                .iter()
+                .all(|o| !o.trim().is_empty() && o.len() <= MAX_POLL_OPTION_LEN);
+        if !valid || hub_group.info.polls.iter().any(|p| p.poll_id == poll.poll_id) {
+            return vec![];
+        }
+        if !self.check_rate_limit(group_id, &from) {
+            return vec![];
+        }
+
+        let poll = GroupPoll {
+            poll_id: poll.poll_id,
+            question,
+            tallies: vec![0; poll.options.len()],
+            options: poll.options,
+            voters: Vec::new(),
+            created_by: from,
+            created_at: now_ms(),
+        };
+        let info = &mut self.groups.get_mut(group_id).unwrap().info;
+        info.polls.push(poll.clone());
+        if info.polls.len() > MAX_POLLS_PER_GROUP {
+            info.polls.remove(0);
+        }
+        info.last_activity_at = now_ms();
+
+        vec![GroupAction::Broadcast {
+            to: info.members.iter().map(|m| m.node_id).collect(),
+            payload: GroupPayload::Poll {
+                group_id: group_id.clone(),
+                poll,
+            },
+        }]
+    }
+
+    /// Count a member's vote (once per poll) and publish the new tally.
+    fn handle_vote(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        poll_id: &str,
+        option: u32,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+        if !hub_group.info.is_member(&from) {
+            return vec![GroupAction::Event(GroupEvent::SecurityViolation {
+                group_id: group_id.clone(),
+                node_id: from,
+                reason: "non-member attempted to vote".into(),
+            })];
+        }
+        let recipients: Vec<NodeId> = hub_group.info.members.iter().map(|m| m.node_id).collect();
+        let Some(poll) = hub_group.info.polls.iter_mut().find(|p| p.poll_id == poll_id) else {
+            return vec![];
+        };
+        let Some(tally) = poll.tallies.get_mut(option as usize) else {
+            return vec![];
+        };
+        if poll.voters.contains(&from) {
+            return vec![];
+        }
+        *tally += 1;
+        poll.voters.push(from);
+
+        vec![GroupAction::Broadcast {
+            to: recipients,
+            payload: GroupPayload::Poll {
+                group_id: group_id.clone(),
+                poll: poll.clone(),
+            },
+        }]
+    }
+
+    // ── Sender Key Distribution ─────────────────────────────────────────
+
+    /// Fan out sender key distribution to individual recipients.
+    ///
+    /// The hub cannot read the keys (they are encrypted per-recipient).
+    /// It simply delivers each encrypted key to the intended recipient.
+    fn handle_sender_key_distribution(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        epoch: u32,
+        encrypted_keys: &[EncryptedSenderKey],
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        if !hub_group.info.is_member(&from) {
+            return vec![GroupAction::Event(GroupEvent::SecurityViolation {
+                group_id: group_id.clone(),
+                node_id: from,
+                reason: "non-member sent sender key distribution".into(),
+            })];
+        }
+
+        // Cache latest distribution + epoch transition state.
+        if let Some(state) = hub_group.sender_epoch_state.get_mut(&from) {
+            if epoch > state.current_epoch {
+                state.previous_epoch = Some(state.current_epoch);
+                state.current_epoch = epoch;
+                state.grace_until_ms = now_ms().saturating_add(SENDER_KEY_EPOCH_GRACE_MS);
+            }
+        } else {
+            hub_group.sender_epoch_state.insert(
+                from,
+                SenderEpochState {
+                    current_epoch: epoch,
+                    previous_epoch: None,
+                    grace_until_ms: 0,
+                },
+            );
+        }
+        hub_group
+            .latest_sender_keys
+            .insert(from, (epoch, encrypted_keys.to_vec()));
+
+        let mut actions = Vec::new();
+        for ek in encrypted_keys {
+            if hub_group.info.is_member(&ek.recipient_id) {
+                actions.push(GroupAction::Send {
+                    to: ek.recipient_id,
+                    payload: GroupPayload::SenderKeyDistribution {
+                        group_id: group_id.clone(),
+                        from,
+                        epoch,
+                        encrypted_keys: vec![ek.clone()],
+                    },
+                });
+            }
+        }
+        actions
+    }
+
+    fn check_epoch_policy(
+        &self,
+        group_id: &GroupId,
+        sender: &NodeId,
+        msg_epoch: u32,
+    ) -> EpochDecision {
+        let Some(hub_group) = self.groups.get(group_id) else {
+            return EpochDecision::Reject;
+        };
+        let Some(state) = hub_group.sender_epoch_state.get(sender) else {
+            return EpochDecision::Accept;
+        };
+
+        if msg_epoch == state.current_epoch {
+            return EpochDecision::Accept;
+        }
+        if state.previous_epoch == Some(msg_epoch) && now_ms() <= state.grace_until_ms {
+            return EpochDecision::Accept;
+        }
+        EpochDecision::Reject
+    }
+
+    fn build_proactive_sender_key_replay(&self, group_id: &GroupId, target: NodeId) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get(group_id) else {
+            return vec![];
+        };
+
+        let mut actions = Vec::new();
+        for (sender_id, (epoch, encrypted_keys)) in &hub_group.latest_sender_keys {
+            if let Some(ek) = encrypted_keys.iter().find(|ek| ek.recipient_id == target) {
+                actions.push(GroupAction::Send {
+                    to: target,
+                    payload: GroupPayload::SenderKeyDistribution {
+                        group_id: group_id.clone(),
+                        from: *sender_id,
+                        epoch: *epoch,
+                        encrypted_keys: vec![ek.clone()],
+                    },
+                });
+            }
+        }
+        actions
+    }
+
+    fn maybe_trigger_rotation(&mut self, group_id: &GroupId) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+        if hub_group.last_rotation_trigger_ms == 0 {
+            hub_group.last_rotation_trigger_ms = now_ms();
+        }
+        hub_group.group_msg_since_rotation = hub_group.group_msg_since_rotation.saturating_add(1);
+        let now = now_ms();
+        let elapsed = now.saturating_sub(hub_group.last_rotation_trigger_ms);
+
+        let should_trigger = hub_group.group_msg_since_rotation >= SENDER_KEY_ROTATE_MAX_MESSAGES
+            || elapsed >= SENDER_KEY_ROTATE_MAX_AGE_MS;
+        if !should_trigger {
+            return vec![];
+        }
+
+        // Anti-spam: max one trigger per hour.
+        if hub_group.last_rotation_trigger_ms > 0 && elapsed < SENDER_KEY_ROTATE_RATE_LIMIT_MS {
+            return vec![];
+        }
+
+        hub_group.last_rotation_trigger_ms = now;
+        hub_group.group_msg_since_rotation = 0;
+
+        let group_members = hub_group.info.members.clone();
+        let latest_sender_keys = hub_group.latest_sender_keys.clone();
+
+        let mut actions = Vec::new();
+        for (sender_id, (epoch, encrypted_keys)) in latest_sender_keys {
+            for ek in encrypted_keys {
+                if group_members.iter().any(|m| m.node_id == ek.recipient_id) {
+                    actions.push(GroupAction::Send {
+                        to: ek.recipient_id,
+                        payload: GroupPayload::SenderKeyDistribution {
+                            group_id: group_id.clone(),
+                            from: sender_id,
+                            epoch,
+                            encrypted_keys: vec![ek],
                         },
                     });
                 }
@@ -771,6 +1391,11 @@ impl GroupHub {
             return false;
         };
 
+        let limit = hub_group
+            .info
+            .settings
+            .rate_limit_per_second
+            .unwrap_or(GROUP_RATE_LIMIT_PER_SECOND);
         let now = Instant::now();
         let entry = hub_group.rate_limits.entry(*sender).or_insert((now, 0));
 
@@ -781,7 +1406,7 @@ impl GroupHub {
         }
 
         entry.1 += 1;
-        entry.1 <= GROUP_RATE_LIMIT_PER_SECOND
+        entry.1 <= limit
     }
 
     // ── Nonce Anti-Replay ──────────────────────────────────────────────
@@ -806,6 +1431,18 @@ impl GroupHub {
 
     // ── Dedup ────────────────────────────────────────────────────────────
 
+    /// Whether a message id was relayed in this group (history or dedup set).
+    fn knows_message(&self, group_id: &GroupId, message_id: &str) -> bool {
+        let Some(hub_group) = self.groups.get(group_id) else {
+            return false;
+        };
+        hub_group.seen_message_ids.contains(message_id)
+            || hub_group
+                .message_history
+                .iter()
+                .any(|m| m.message_id == message_id)
+    }
+
     fn check_dedup(&mut self, group_id: &GroupId, message_id: &str) -> bool {
         let Some(hub_group) = self.groups.get_mut(group_id) else {
             return false;
@@ -879,10 +1516,20 @@ impl GroupHub {
             seen_message_ids: HashSet::new(),
             seen_nonces: HashSet::new(),
             invited_set: HashSet::new(),
+            invite_tokens: HashMap::new(),
             latest_sender_keys: HashMap::new(),
             sender_epoch_state: HashMap::new(),
             group_msg_since_rotation: 0,
             last_rotation_trigger_ms: 0,
+            deliveries: HashMap::new(),
+            delivery_order: VecDeque::new(),
+            reactions: HashMap::new(),
+            reaction_order: VecDeque::new(),
+            sub_hubs: Vec::new(),
+            scheduled: Vec::new(),
+            join_requests: Vec::new(),
+            heartbeat_sent: HashMap::new(),
+            heartbeat_shadow: None,
         };
 
         self.groups.insert(group_id, hub_group);
@@ -901,195 +1548,771 @@ impl GroupHub {
                 .map(|m| m.node_id)
                 .collect();
 
-            if !recipients.is_empty() {
-                actions.push(GroupAction::Broadcast {
-                    to: recipients,
-                    payload: GroupPayload::HubHeartbeat {
-                        group_id: hub_group.info.group_id.clone(),
-                        member_count: hub_group.info.member_count(),
-                    },
-                });
-            }
+            if !recipients.is_empty() {
+                actions.push(GroupAction::Broadcast {
+                    to: recipients,
+                    payload: GroupPayload::HubHeartbeat {
+                        group_id: hub_group.info.group_id.clone(),
+                        member_count: hub_group.info.member_count(),
+                        shadow_id: hub_group.info.shadow_id,
+                    },
+                });
+            }
+        }
+
+        actions
+    }
+
+    /// Heartbeat actions that skip members recent traffic already reached
+    /// (`reached`): any authenticated envelope from us proves we're alive.
+    ///
+    /// Each member still gets one at least every
+    /// `HUB_HEARTBEAT_MAX_SUPPRESSION_MS`, and every member does when the
+    /// shadow changed since the last round (heartbeats carry it).
+    pub fn heartbeat_actions_at(
+        &mut self,
+        now: u64,
+        reached: impl Fn(&NodeId) -> bool,
+    ) -> Vec<GroupAction> {
+        let mut actions = vec![];
+
+        for hub_group in self.groups.values_mut() {
+            let shadow_changed = hub_group.heartbeat_shadow != hub_group.info.shadow_id;
+            hub_group.heartbeat_shadow = hub_group.info.shadow_id;
+            let member_ids: HashSet<NodeId> =
+                hub_group.info.members.iter().map(|m| m.node_id).collect();
+            hub_group.heartbeat_sent.retain(|id, _| member_ids.contains(id));
+
+            let mut recipients = Vec::new();
+            for member in &hub_group.info.members {
+                let overdue = hub_group
+                    .heartbeat_sent
+                    .get(&member.node_id)
+                    .is_none_or(|&sent| now.saturating_sub(sent) >= HUB_HEARTBEAT_MAX_SUPPRESSION_MS);
+                if shadow_changed || overdue || !reached(&member.node_id) {
+                    hub_group.heartbeat_sent.insert(member.node_id, now);
+                    recipients.push(member.node_id);
+                }
+            }
+
+            if !recipients.is_empty() {
+                actions.push(GroupAction::Broadcast {
+                    to: recipients,
+                    payload: GroupPayload::HubHeartbeat {
+                        group_id: hub_group.info.group_id.clone(),
+                        member_count: hub_group.info.member_count(),
+                        shadow_id: hub_group.info.shadow_id,
+                    },
+                });
+            }
+        }
+
+        actions
+    }
+
+    /// Kick a member from a group (admin action, initiated externally).
+    pub fn kick_member(
+        &mut self,
+        group_id: &GroupId,
+        admin: &NodeId,
+        target: &NodeId,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        // Only admins can kick
+        if !hub_group.info.is_admin(admin) {
+            return vec![];
+        }
+
+        // Can't kick yourself
+        if admin == target {
+            return vec![];
+        }
+
+        let username = hub_group
+            .info
+            .get_member(target)
+            .map(|m| m.username.clone())
+            .unwrap_or_default();
+
+        hub_group.info.members.retain(|m| m.node_id != *target);
+        hub_group.info.last_activity_at = now_ms();
+
+        // Notify all remaining members (including the kicked person)
+        let mut recipients: Vec<NodeId> = hub_group
+            .info
+            .members
+            .iter()
+            .map(|m| m.node_id)
+            .collect();
+        recipients.push(*target); // Notify the kicked member too
+
+        vec![GroupAction::Broadcast {
+            to: recipients,
+            payload: GroupPayload::MemberLeft {
+                group_id: group_id.clone(),
+                node_id: *target,
+                username,
+                reason: LeaveReason::Kicked,
+            },
+        }]
+    }
+
+    // ── Update Member Role (R11.3) ──────────────────────────────────────
+
+    /// Change a member's role (admin action).
+    ///
+    /// Last-admin protection: cannot demote the only admin.
+    pub fn update_member_role(
+        &mut self,
+        group_id: &GroupId,
+        admin: &NodeId,
+        target: &NodeId,
+        new_role: GroupMemberRole,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        // Only admins can change roles
+        if !hub_group.info.is_admin(admin) {
+            return vec![];
+        }
+
+        // Target must be a member
+        let Some(member) = hub_group.info.members.iter().find(|m| m.node_id == *target) else {
+            return vec![];
+        };
+
+        // No-op if already the target role
+        if member.role == new_role {
+            return vec![];
+        }
+
+        // Last-admin protection: can't demote the only admin
+        if new_role == GroupMemberRole::Member {
+            let admin_count = hub_group
+                .info
+                .members
+                .iter()
+                .filter(|m| m.role == GroupMemberRole::Admin)
+                .count();
+            if admin_count <= 1 {
+                return vec![];
+            }
+        }
+
+        // Apply the role change
+        if let Some(m) = hub_group.info.members.iter_mut().find(|m| m.node_id == *target) {
+            m.role = new_role;
+        }
+        hub_group.info.last_activity_at = now_ms();
+
+        // Broadcast to all members
+        let recipients: Vec<NodeId> = hub_group
+            .info
+            .members
+            .iter()
+            .map(|m| m.node_id)
+            .collect();
+
+        let mut actions = vec![GroupAction::Broadcast {
+            to: recipients,
+            payload: GroupPayload::MemberRoleChanged {
+                group_id: group_id.clone(),
+                node_id: *target,
+                new_role,
+            },
+        }];
+
+        // Sync shadow
+        let group_id_for_sync = group_id.clone();
+        if let Some((target_node, payload)) = self.build_shadow_sync(&group_id_for_sync) {
+            actions.push(GroupAction::Send { to: target_node, payload });
+        }
+
+        actions
+    }
+
+    // ── Invite Member (R11.3) ─────────────────────────────────────────
+
+    /// Admin invites a new member to an existing group.
+    pub fn invite_member(
+        &mut self,
+        group_id: &GroupId,
+        admin: &NodeId,
+        target: NodeId,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        // Only admins can invite
+        if !hub_group.info.is_admin(admin) {
+            return vec![];
+        }
+
+        Self::send_invite(hub_group, group_id, admin, target)
+    }
+
+    /// A member brings in another of its user's devices. The caller has
+    /// checked the device certificates; any member may do this, admin or not.
+    pub fn invite_linked_device(
+        &mut self,
+        group_id: &GroupId,
+        member: &NodeId,
+        device: NodeId,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        if !hub_group.info.is_member(member) {
+            return vec![];
+        }
+
+        Self::send_invite(hub_group, group_id, member, device)
+    }
+
+    fn send_invite(
+        hub_group: &mut HubGroup,
+        group_id: &GroupId,
+        inviter: &NodeId,
+        target: NodeId,
+    ) -> Vec<GroupAction> {
+        // Already a member, or banned (unban first)
+        if hub_group.info.is_member(&target) || hub_group.info.is_banned(&target, now_ms()) {
+            return vec![];
+        }
+
+        // Group full
+        if hub_group.info.is_full() {
+            return vec![];
+        }
+
+        // Track invitation (for invite-only enforcement)
+        hub_group.invited_set.insert(target);
+
+        // Get inviter username for the invite
+        let inviter_username = hub_group
+            .info
+            .get_member(inviter)
+            .map(|m| m.username.clone())
+            .unwrap_or_default();
+
+        vec![GroupAction::Send {
+            to: target,
+            payload: GroupPayload::Invite {
+                group_id: group_id.clone(),
+                group_name: hub_group.info.name.clone(),
+                inviter_id: *inviter,
+                inviter_username,
+            },
+        }]
+    }
+
+    // ── Bans ────────────────────────────────────────────────────────────
+
+    /// Ban a node from the group (admin action), kicking it if it's a member.
+    ///
+    /// Re-banning replaces the previous ban (e.g. to extend it). Expired bans
+    /// are pruned before enforcing [`MAX_BANS_PER_GROUP`].
+    pub fn ban_member(
+        &mut self,
+        group_id: &GroupId,
+        admin: &NodeId,
+        target: NodeId,
+        duration_ms: Option<u64>,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        // Only admins can ban, and not themselves
+        if !hub_group.info.is_admin(admin) || *admin == target {
+            return vec![];
+        }
+
+        let now = now_ms();
+        let bans = &mut hub_group.info.bans;
+        bans.retain(|b| b.is_active(now) && b.node_id != target);
+        if bans.len() >= MAX_BANS_PER_GROUP {
+            return vec![];
+        }
+        bans.push(GroupBan {
+            node_id: target,
+            banned_by: *admin,
+            banned_at: now,
+            expires_at: duration_ms.map(|d| now.saturating_add(d)),
+        });
+        hub_group.invited_set.remove(&target);
+        hub_group.join_requests.retain(|r| r.requester != target);
+
+        let mut actions = if hub_group.info.is_member(&target) {
+            self.kick_member(group_id, admin, &target)
+        } else {
+            vec![]
+        };
+
+        // Sync shadow so the ban survives failover
+        if let Some((target_node, payload)) = self.build_shadow_sync(group_id) {
+            actions.push(GroupAction::Send { to: target_node, payload });
+        }
+
+        actions
+    }
+
+    /// Lift a ban (admin action).
+    pub fn unban_member(
+        &mut self,
+        group_id: &GroupId,
+        admin: &NodeId,
+        target: &NodeId,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        if !hub_group.info.is_admin(admin) {
+            return vec![];
+        }
+
+        let before = hub_group.info.bans.len();
+        hub_group.info.bans.retain(|b| b.node_id != *target);
+        if hub_group.info.bans.len() == before {
+            return vec![];
+        }
+
+        self.build_shadow_sync(group_id)
+            .map(|(target_node, payload)| GroupAction::Send { to: target_node, payload })
+            .into_iter()
+            .collect()
+    }
+
+    /// Active bans for a group.
+    pub fn bans(&self, group_id: &GroupId) -> Vec<GroupBan> {
+        let now = now_ms();
+        self.groups
+            .get(group_id)
+            .map(|g| g.info.bans.iter().filter(|b| b.is_active(now)).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // ── Group Metadata ──────────────────────────────────────────────────
+
+    /// Update the group name, description and avatar hash (admin action).
+    ///
+    /// Bumps `config_version` so members can discard stale or replayed updates.
+    pub fn update_metadata(
+        &mut self,
+        group_id: &GroupId,
+        admin: &NodeId,
+        name: String,
+        description: String,
+        avatar_hash: Option<[u8; 32]>,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        // Only admins can change metadata
+        if !hub_group.info.is_admin(admin) {
+            return vec![];
+        }
+
+        let name = name.trim().to_string();
+        if name.is_empty()
+            || name.len() > MAX_GROUP_NAME_LEN
+            || description.len() > MAX_GROUP_DESCRIPTION_LEN
+        {
+            return vec![];
+        }
+
+        // No-op if nothing changed
+        let info = &mut hub_group.info;
+        if info.name == name && info.description == description && info.avatar_hash == avatar_hash {
+            return vec![];
+        }
+
+        info.name = name.clone();
+        info.description = description.clone();
+        info.avatar_hash = avatar_hash;
+        info.config_version += 1;
+        info.last_activity_at = now_ms();
+
+        let recipients: Vec<NodeId> = info.members.iter().map(|m| m.node_id).collect();
+
+        let mut actions = vec![GroupAction::Broadcast {
+            to: recipients,
+            payload: GroupPayload::MetadataChanged {
+                group_id: group_id.clone(),
+                name,
+                description,
+                avatar_hash,
+                config_version: info.config_version,
+                updated_by: *admin,
+            },
+        }];
+
+        // Sync shadow
+        if let Some((target_node, payload)) = self.build_shadow_sync(group_id) {
+            actions.push(GroupAction::Send { to: target_node, payload });
+        }
+
+        actions
+    }
+
+    // ── Disappearing Messages ───────────────────────────────────────────
+
+    /// Set or clear the disappearing-message timer (admin action).
+    ///
+    /// Timers outside `MIN_MESSAGE_TTL_MS..=MAX_MESSAGE_TTL_MS` are rejected:
+    /// nothing may outlive the 24h protocol lifespan.
+    /// Shares `config_version` with metadata updates so members apply
+    /// both kinds of change in order.
+    pub fn set_message_ttl(
+        &mut self,
+        group_id: &GroupId,
+        admin: &NodeId,
+        ttl_ms: Option<u64>,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        if !hub_group.info.is_admin(admin) {
+            return vec![];
+        }
+
+        if ttl_ms.is_some_and(|ttl| !(MIN_MESSAGE_TTL_MS..=MAX_MESSAGE_TTL_MS).contains(&ttl)) {
+            return vec![];
+        }
+
+        let info = &mut hub_group.info;
+        if info.message_ttl_ms == ttl_ms {
+            return vec![];
+        }
+
+        info.message_ttl_ms = ttl_ms;
+        info.config_version += 1;
+        info.last_activity_at = now_ms();
+
+        let recipients: Vec<NodeId> = info.members.iter().map(|m| m.node_id).collect();
+
+        let mut actions = vec![GroupAction::Broadcast {
+            to: recipients,
+            payload: GroupPayload::MessageTtlChanged {
+                group_id: group_id.clone(),
+                ttl_ms,
+                config_version: info.config_version,
+                updated_by: *admin,
+            },
+        }];
+
+        if let Some((target_node, payload)) = self.build_shadow_sync(group_id) {
+            actions.push(GroupAction::Send { to: target_node, payload });
+        }
+
+        actions
+    }
+
+    // ── Settings ─────────────────────────────────────────────────────────
+
+    /// Replace the group's rate/history settings (admin action).
+    ///
+    /// A lower history limit trims the stored history right away.
+    pub fn update_settings(
+        &mut self,
+        group_id: &GroupId,
+        admin: &NodeId,
+        settings: GroupSettings,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        if !hub_group.info.is_admin(admin) || !settings.is_valid() {
+            return vec![];
+        }
+
+        if hub_group.info.settings == settings {
+            return vec![];
+        }
+
+        hub_group.info.settings = settings;
+        hub_group.info.config_version += 1;
+        hub_group.info.last_activity_at = now_ms();
+        let dropped = hub_group.trim_history(self.max_messages_per_group);
+        self.total_messages = self.total_messages.saturating_sub(dropped);
+
+        let info = &hub_group.info;
+        let recipients: Vec<NodeId> = info.members.iter().map(|m| m.node_id).collect();
+
+        let mut actions = vec![GroupAction::Broadcast {
+            to: recipients,
+            payload: GroupPayload::SettingsChanged {
+                group_id: group_id.clone(),
+                settings,
+                config_version: info.config_version,
+                updated_by: *admin,
+            },
+        }];
+
+        if let Some((target_node, payload)) = self.build_shadow_sync(group_id) {
+            actions.push(GroupAction::Send { to: target_node, payload });
+        }
+
+        actions
+    }
+
+    /// Drop history messages that outlived their group's disappearing timer.
+    ///
+    /// Only touches the in-memory history; persisted copies are expired by
+    /// timestamp in the store. Returns the number of messages dropped.
+    pub fn purge_ephemeral_messages(&mut self, now: u64) -> usize {
+        let mut total = 0;
+        for hub_group in self.groups.values_mut() {
+            if hub_group.info.message_ttl_ms.is_none() {
+                continue;
+            }
+            let info = &hub_group.info;
+            let before = hub_group.message_history.len();
+            hub_group
+                .message_history
+                .retain(|msg| !info.is_message_expired(msg, now));
+            total += before - hub_group.message_history.len();
+        }
+        total
+    }
+
+    // ── Invite Tokens ───────────────────────────────────────────────────
+
+    /// Register an invite token's secret hash (admin action).
+    ///
+    /// Expiry is capped at [`INVITE_TTL_MS`] from now regardless of what the
+    /// admin asked for. Expired tokens are pruned before enforcing the cap.
+    pub fn register_invite_token(
+        &mut self,
+        group_id: &GroupId,
+        admin: &NodeId,
+        secret_hash: [u8; 32],
+        expires_at: u64,
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        if !hub_group.info.is_admin(admin) {
+            return vec![];
+        }
+
+        let now = now_ms();
+        let expires_at = expires_at.min(now + INVITE_TTL_MS);
+        if expires_at <= now {
+            return vec![];
+        }
+
+        hub_group.invite_tokens.retain(|_, exp| *exp > now);
+        if hub_group.invite_tokens.len() >= MAX_INVITE_TOKENS_PER_GROUP
+            && !hub_group.invite_tokens.contains_key(&secret_hash)
+        {
+            return vec![];
+        }
+        hub_group.invite_tokens.insert(secret_hash, expires_at);
+        vec![]
+    }
+
+    /// Join via invite token: a valid, unexpired secret counts as an invitation.
+    fn handle_join_with_token(
+        &mut self,
+        joiner: NodeId,
+        group_id: &GroupId,
+        username: String,
+        join_secret: &[u8; 32],
+    ) -> Vec<GroupAction> {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        let now = now_ms();
+        if hub_group.info.is_banned(&joiner, now) {
+            return vec![];
+        }
+
+        let secret_hash = crate::group::invite_token::hash_join_secret(join_secret);
+        match hub_group.invite_tokens.get(&secret_hash) {
+            Some(expires_at) if *expires_at > now => {}
+            _ => return vec![],
         }
 
-        actions
+        if !hub_group.info.is_member(&joiner) {
+            hub_group.invited_set.insert(joiner);
+        }
+        self.handle_join(joiner, group_id, username)
     }
 
-    /// Kick a member from a group (admin action, initiated externally).
-    pub fn kick_member(
+    // ── Join Approval ───────────────────────────────────────────────────
+
+    /// Queue a join request for the admins to decide on.
+    ///
+    /// Members are re-synced and invited nodes join right away, as with a
+    /// plain `Join`. A repeated request refreshes the username without
+    /// notifying the admins again.
+    fn handle_join_request(
         &mut self,
+        requester: NodeId,
         group_id: &GroupId,
-        admin: &NodeId,
-        target: &NodeId,
+        username: String,
     ) -> Vec<GroupAction> {
         let Some(hub_group) = self.groups.get_mut(group_id) else {
             return vec![];
         };
 
-        // Only admins can kick
-        if !hub_group.info.is_admin(admin) {
+        let now = now_ms();
+        if hub_group.info.is_banned(&requester, now) {
             return vec![];
         }
 
-        // Can't kick yourself
-        if admin == target {
-            return vec![];
+        if hub_group.info.is_member(&requester)
+            || requester == hub_group.info.created_by
+            || hub_group.invited_set.contains(&requester)
+        {
+            return self.handle_join(requester, group_id, username);
         }
 
-        let username = hub_group
-            .info
-            .get_member(target)
-            .map(|m| m.username.clone())
-            .unwrap_or_default();
+        if hub_group.info.is_full() {
+            return vec![];
+        }
 
-        hub_group.info.members.retain(|m| m.node_id != *target);
-        hub_group.info.last_activity_at = now_ms();
+        if let Some(pending) = hub_group
+            .join_requests
+            .iter_mut()
+            .find(|r| r.requester == requester)
+        {
+            pending.username = username;
+            return vec![];
+        }
 
-        // Notify all remaining members (including the kicked person)
-        let mut recipients: Vec<NodeId> = hub_group
-            .info
-            .members
-            .iter()
-            .map(|m| m.node_id)
-            .collect();
-        recipients.push(*target); // Notify the kicked member too
+        let request = GroupJoinRequest {
+            requester,
+            username,
+            requested_at: now,
+        };
+        hub_group.join_requests.push(request.clone());
+        if hub_group.join_requests.len() > MAX_PENDING_JOIN_REQUESTS {
+            hub_group.join_requests.remove(0);
+        }
 
         vec![GroupAction::Broadcast {
-            to: recipients,
-            payload: GroupPayload::MemberLeft {
+            to: hub_group.info.admin_ids(),
+            payload: GroupPayload::JoinRequested {
                 group_id: group_id.clone(),
-                node_id: *target,
-                username,
-                reason: LeaveReason::Kicked,
+                request,
             },
         }]
     }
 
-    // ── Update Member Role (R11.3) ──────────────────────────────────────
-
-    /// Change a member's role (admin action).
-    ///
-    /// Last-admin protection: cannot demote the only admin.
-    pub fn update_member_role(
+    /// Admin accepts a pending join request: the requester joins as if invited.
+    pub fn approve_join(
         &mut self,
         group_id: &GroupId,
         admin: &NodeId,
-        target: &NodeId,
-        new_role: GroupMemberRole,
+        requester: NodeId,
     ) -> Vec<GroupAction> {
         let Some(hub_group) = self.groups.get_mut(group_id) else {
             return vec![];
         };
 
-        // Only admins can change roles
         if !hub_group.info.is_admin(admin) {
             return vec![];
         }
 
-        // Target must be a member
-        let Some(member) = hub_group.info.members.iter().find(|m| m.node_id == *target) else {
-            return vec![];
-        };
-
-        // No-op if already the target role
-        if member.role == new_role {
+        // Keep the request queued while the group is full
+        if hub_group.info.is_full() {
             return vec![];
         }
 
-        // Last-admin protection: can't demote the only admin
-        if new_role == GroupMemberRole::Member {
-            let admin_count = hub_group
-                .info
-                .members
-                .iter()
-                .filter(|m| m.role == GroupMemberRole::Admin)
-                .count();
-            if admin_count <= 1 {
-                return vec![];
-            }
-        }
-
-        // Apply the role change
-        if let Some(m) = hub_group.info.members.iter_mut().find(|m| m.node_id == *target) {
-            m.role = new_role;
-        }
-        hub_group.info.last_activity_at = now_ms();
-
-        // Broadcast to all members
-        let recipients: Vec<NodeId> = hub_group
-            .info
-            .members
+        let Some(pos) = hub_group
+            .join_requests
             .iter()
-            .map(|m| m.node_id)
-            .collect();
+            .position(|r| r.requester == requester)
+        else {
+            return vec![];
+        };
+        let request = hub_group.join_requests.remove(pos);
+        hub_group.invited_set.insert(requester);
 
-        let mut actions = vec![GroupAction::Broadcast {
-            to: recipients,
-            payload: GroupPayload::MemberRoleChanged {
-                group_id: group_id.clone(),
-                node_id: *target,
-                new_role,
-            },
-        }];
+        let mut actions = self.handle_join(requester, group_id, request.username);
 
-        // Sync shadow
-        let group_id_for_sync = group_id.clone();
-        if let Some((target_node, payload)) = self.build_shadow_sync(&group_id_for_sync) {
-            actions.push(GroupAction::Send { to: target_node, payload });
+        if let Some(hub_group) = self.groups.get(group_id) {
+            actions.push(GroupAction::Broadcast {
+                to: hub_group.info.admin_ids(),
+                payload: GroupPayload::JoinRequestResolved {
+                    group_id: group_id.clone(),
+                    requester,
+                    approved: true,
+                    resolved_by: *admin,
+                },
+            });
         }
 
         actions
     }
 
-    // ── Invite Member (R11.3) ─────────────────────────────────────────
-
-    /// Admin invites a new member to an existing group.
-    pub fn invite_member(
+    /// Admin rejects a pending join request; the requester is told.
+    pub fn deny_join(
         &mut self,
         group_id: &GroupId,
         admin: &NodeId,
-        target: NodeId,
+        requester: NodeId,
     ) -> Vec<GroupAction> {
         let Some(hub_group) = self.groups.get_mut(group_id) else {
             return vec![];
         };
 
-        // Only admins can invite
         if !hub_group.info.is_admin(admin) {
             return vec![];
         }
 
-        // Already a member
-        if hub_group.info.is_member(&target) {
+        let before = hub_group.join_requests.len();
+        hub_group.join_requests.retain(|r| r.requester != requester);
+        if hub_group.join_requests.len() == before {
             return vec![];
         }
 
-        // Group full
-        if hub_group.info.is_full() {
-            return vec![];
-        }
-
-        // Track invitation (for invite-only enforcement)
-        hub_group.invited_set.insert(target);
-
-        // Get admin username for the invite
-        let admin_username = hub_group
-            .info
-            .get_member(admin)
-            .map(|m| m.username.clone())
-            .unwrap_or_default();
+        let mut recipients = hub_group.info.admin_ids();
+        recipients.push(requester);
 
-        vec![GroupAction::Send {
-            to: target,
-            payload: GroupPayload::Invite {
+        vec![GroupAction::Broadcast {
+            to: recipients,
+            payload: GroupPayload::JoinRequestResolved {
                 group_id: group_id.clone(),
-                group_name: hub_group.info.name.clone(),
-                inviter_id: *admin,
-                inviter_username: admin_username,
+                requester,
+                approved: false,
+                resolved_by: *admin,
             },
         }]
     }
 
+    /// Join requests awaiting a decision for a group, oldest first.
+    pub fn pending_join_requests(&self, group_id: &GroupId) -> &[GroupJoinRequest] {
+        self.groups
+            .get(group_id)
+            .map(|g| g.join_requests.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Number of live invite tokens for a group.
+    pub fn invite_token_count(&self, group_id: &GroupId) -> usize {
+        let now = now_ms();
+        self.groups
+            .get(group_id)
+            .map(|g| g.invite_tokens.values().filter(|exp| **exp > now).count())
+            .unwrap_or(0)
+    }
+
     // ── Hub Failover (Primary Side) ─────────────────────────────────────
 
     /// Assign a shadow for a group. Uses deterministic election (lowest NodeId
@@ -1124,6 +2347,7 @@ impl GroupHub {
                     members: hub_group.info.members.clone(),
                     candidate_id,
                     config_version: hub_group.info.last_activity_at,
+                    bans: hub_group.info.bans.clone(),
                 },
             }];
 
@@ -1153,6 +2377,7 @@ impl GroupHub {
                 members: hub_group.info.members.clone(),
                 candidate_id: hub_group.info.candidate_id,
                 config_version: hub_group.info.last_activity_at,
+                bans: hub_group.info.bans.clone(),
             },
         ))
     }
@@ -1192,6 +2417,10 @@ impl GroupHub {
             next_seqs: self.groups.iter()
                 .map(|(id, hg)| (id.clone(), hg.next_seq))
                 .collect(),
+            scheduled: self.groups.iter()
+                .filter(|(_, hg)| !hg.scheduled.is_empty())
+                .map(|(id, hg)| (id.clone(), hg.scheduled.clone()))
+                .collect(),
         }
     }
 
@@ -1206,6 +2435,10 @@ impl GroupHub {
                 .get(&group_id)
                 .copied()
                 .unwrap_or(0);
+            let scheduled = snapshot.scheduled
+                .get(&group_id)
+                .cloned()
+                .unwrap_or_default();
             let hub_group = HubGroup {
                 info,
                 message_history: VecDeque::new(),
@@ -1214,14 +2447,44 @@ impl GroupHub {
                 seen_message_ids: HashSet::new(),
                 seen_nonces: HashSet::new(),
                 invited_set,
+                invite_tokens: HashMap::new(),
                 latest_sender_keys: HashMap::new(),
                 sender_epoch_state: HashMap::new(),
                 group_msg_since_rotation: 0,
                 last_rotation_trigger_ms: 0,
+                deliveries: HashMap::new(),
+                delivery_order: VecDeque::new(),
+                reactions: HashMap::new(),
+                reaction_order: VecDeque::new(),
+                sub_hubs: Vec::new(),
+                scheduled,
+                join_requests: Vec::new(),
+                heartbeat_sent: HashMap::new(),
+                heartbeat_shadow: None,
             };
             self.groups.insert(group_id, hub_group);
         }
     }
+
+    /// Reload a restored group's recent history (persisted message by message).
+    ///
+    /// Called after `restore` so replies, reactions and dedup keep working
+    /// across a restart; `next_seq` never falls behind a seq already handed out.
+    pub fn restore_history(&mut self, group_id: &GroupId, messages: Vec<GroupMessage>) {
+        let Some(hub_group) = self.groups.get_mut(group_id) else {
+            return;
+        };
+        if let Some(max_seq) = messages.iter().map(|m| m.seq).max() {
+            hub_group.next_seq = hub_group.next_seq.max(max_seq + 1);
+        }
+        for msg in messages {
+            hub_group.seen_message_ids.insert(msg.message_id.clone());
+            hub_group.message_history.push_back(msg);
+            self.total_messages += 1;
+        }
+        let dropped = hub_group.trim_history(self.max_messages_per_group);
+        self.total_messages = self.total_messages.saturating_sub(dropped);
+    }
 }
 
 #[cfg(test)]
@@ -1419,19 +2682,229 @@ mod tests {
             },
             alice,
         );
-        let gid = hub.groups.keys().next().unwrap().clone();
+        let gid = hub.groups.keys().next().unwrap().clone();
+
+        let actions = hub.handle_leave(alice, &gid);
+        assert!(actions.is_empty()); // No one to notify
+        assert_eq!(hub.group_count(), 0);
+    }
+
+    #[test]
+    fn message_fanout() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let charlie = node_id(3);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Chat".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+        hub.handle_join(charlie, &gid, "charlie".into());
+
+        // Alice sends a signed message
+        let msg = signed_msg(gid.clone(), 1, "Hello!");
+        let actions = hub.handle_message(alice, msg);
+
+        assert_eq!(actions.len(), 1);
+        match &actions[0] {
+            GroupAction::Broadcast { to, payload } => {
+                // Should go to bob and charlie, NOT alice
+                assert_eq!(to.len(), 2);
+                assert!(to.contains(&bob));
+                assert!(to.contains(&charlie));
+                assert!(!to.contains(&alice));
+                assert!(matches!(payload, GroupPayload::Message(_)));
+            }
+            _ => panic!("expected Broadcast action"),
+        }
+    }
+
+    #[test]
+    fn large_group_fans_out_through_sub_hubs() {
+        use crate::relay::{PeerInfo, PeerRole, PeerStatus};
+
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Town hall".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        for seed in 100..220 {
+            hub.handle_join(node_id(seed), &gid, format!("m{seed}"));
+        }
+        assert_eq!(hub.get_group(&gid).unwrap().member_count(), 121);
+
+        // Relays in shards 0 and 1; shard 2 has none
+        let mut topology = Topology::new();
+        for seed in [110, 160] {
+            topology.upsert(PeerInfo {
+                node_id: node_id(seed),
+                role: PeerRole::Relay,
+                status: PeerStatus::Online,
+                last_seen: now_ms(),
+            });
+        }
+        hub.assign_sub_hubs(&topology);
+        assert_eq!(
+            hub.sub_hubs(&gid),
+            &[Some(node_id(110)), Some(node_id(160)), None]
+        );
+
+        let actions = hub.handle_message(alice, signed_msg(gid.clone(), 1, "Hello all"));
+        let mut reached = HashSet::new();
+        for action in &actions {
+            match action {
+                GroupAction::Broadcast { to, payload: GroupPayload::Message(_) } => {
+                    assert_eq!(to.len(), 21, "shard without sub-hub served directly");
+                    reached.extend(to.iter().copied());
+                }
+                GroupAction::Send { to, payload: GroupPayload::ShardRelay { recipients, .. } } => {
+                    assert!(recipients.len() <= MAX_DIRECT_FANOUT);
+                    assert!(!recipients.contains(to));
+                    reached.insert(*to);
+                    reached.extend(recipients.iter().copied());
+                }
+                other => panic!("unexpected action: {other:?}"),
+            }
+        }
+        assert_eq!(actions.len(), 3);
+        assert_eq!(reached.len(), 120);
+        assert!(!reached.contains(&alice));
+
+        // A sub-hub's own message: its shard is served directly
+        let actions = hub.handle_message(node_id(160), signed_msg(gid.clone(), 160, "hi"));
+        assert!(actions.iter().all(|a| !matches!(a,
+            GroupAction::Send { to, .. } if *to == node_id(160))));
+
+        // Back under the threshold: direct fan-out again
+        for seed in 130..220 {
+            hub.handle_leave(node_id(seed), &gid);
+        }
+        hub.assign_sub_hubs(&topology);
+        assert!(hub.sub_hubs(&gid).is_empty());
+    }
+
+    #[test]
+    fn scheduled_message_held_until_due() {
+        let mut hub = make_hub();
+        let (alice, alice_secret) = keypair(1);
+        let bob = node_id(2);
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Later".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+
+        let scheduled = |text: &str, deliver_at: u64| {
+            let mut msg = GroupMessage::new(gid.clone(), alice, "alice".into(), text.into())
+                .with_deliver_at(deliver_at);
+            msg.sign(&alice_secret);
+            msg
+        };
+        let now = now_ms();
+
+        // Too far ahead: the cap counts from `sent_at`
+        let actions = hub.handle_message(alice, scheduled("x", now + MAX_SCHEDULE_AHEAD_MS + 60_000));
+        assert!(matches!(&actions[..], [GroupAction::Event(GroupEvent::SecurityViolation { .. })]));
+        let mut edge = GroupMessage::new(gid.clone(), alice, "alice".into(), "edge".into());
+        edge.deliver_at = Some(edge.sent_at + MAX_SCHEDULE_AHEAD_MS + 1);
+        edge.sign(&alice_secret);
+        let actions = hub.handle_message(alice, edge.clone());
+        assert!(matches!(&actions[..], [GroupAction::Event(GroupEvent::SecurityViolation { .. })]));
+        edge.deliver_at = Some(edge.sent_at + MAX_SCHEDULE_AHEAD_MS);
+        edge.sign(&alice_secret);
+        assert!(hub.handle_message(alice, edge.clone()).is_empty());
+        hub.handle_payload(
+            GroupPayload::CancelScheduled { group_id: gid.clone(), message_id: edge.message_id },
+            alice,
+        );
+
+        // Held, not fanned out, no sequence number consumed
+        let first = scheduled("in an hour", now + 3_600_000);
+        let second = scheduled("in a minute", now + 60_000);
+        assert!(hub.handle_message(alice, first.clone()).is_empty());
+        assert!(hub.handle_message(alice, second.clone()).is_empty());
+        assert_eq!(hub.scheduled(&gid).len(), 2);
+        assert_eq!(hub.scheduled(&gid)[0].message_id, second.message_id);
+        assert!(hub.release_scheduled(now).is_empty());
+
+        // Only the sender may cancel
+        hub.handle_payload(
+            GroupPayload::CancelScheduled {
+                group_id: gid.clone(),
+                message_id: first.message_id.clone(),
+            },
+            bob,
+        );
+        assert_eq!(hub.scheduled(&gid).len(), 2);
+        hub.handle_payload(
+            GroupPayload::CancelScheduled {
+                group_id: gid.clone(),
+                message_id: first.message_id.clone(),
+            },
+            alice,
+        );
+        assert_eq!(hub.scheduled(&gid).len(), 1);
 
-        let actions = hub.handle_leave(alice, &gid);
-        assert!(actions.is_empty()); // No one to notify
-        assert_eq!(hub.group_count(), 0);
+        // Due: fanned out like a regular message
+        let actions = hub.release_scheduled(now + 60_000);
+        match &actions[0] {
+            GroupAction::Broadcast { to, payload: GroupPayload::Message(m) } => {
+                assert_eq!(to, &vec![bob]);
+                assert_eq!(m.message_id, second.message_id);
+                assert_eq!(m.seq, 0);
+            }
+            other => panic!("expected Broadcast, got {other:?}"),
+        }
+        assert!(hub.scheduled(&gid).is_empty());
+
+        // Survives a hub restart
+        hub.handle_message(alice, scheduled("after restart", now + 60_000));
+        let mut restored = make_hub();
+        restored.restore(hub.snapshot());
+        assert_eq!(restored.scheduled(&gid).len(), 1);
+        assert_eq!(restored.release_scheduled(now + 60_000).len(), 1);
+
+        // A hub down past the lifespan drops the held message instead of
+        // releasing it
+        hub.handle_message(alice, scheduled("stale", now + 60_000));
+        assert!(hub.release_scheduled(now + crate::types::MESSAGE_LIFESPAN_MS + 60_000).is_empty());
+        assert!(hub.scheduled(&gid).is_empty());
+
+        // The hold counts against the group's disappearing timer too
+        hub.set_message_ttl(&gid, &alice, Some(60_000));
+        hub.handle_message(alice, scheduled("gone before due", now + 120_000));
+        assert!(hub.release_scheduled(now + 120_000).is_empty());
+        assert!(hub.scheduled(&gid).is_empty());
     }
 
     #[test]
-    fn message_fanout() {
+    fn delivery_acks_produce_receipts_for_sender() {
         let mut hub = make_hub();
         let alice = node_id(1);
         let bob = node_id(2);
         let charlie = node_id(3);
+        let stranger = node_id(99);
 
         hub.handle_payload(
             GroupPayload::Create {
@@ -1446,22 +2919,40 @@ mod tests {
         hub.handle_join(bob, &gid, "bob".into());
         hub.handle_join(charlie, &gid, "charlie".into());
 
-        // Alice sends a signed message
         let msg = signed_msg(gid.clone(), 1, "Hello!");
-        let actions = hub.handle_message(alice, msg);
+        let mid = msg.message_id.clone();
+        hub.handle_message(alice, msg);
 
-        assert_eq!(actions.len(), 1);
-        match &actions[0] {
-            GroupAction::Broadcast { to, payload } => {
-                // Should go to bob and charlie, NOT alice
-                assert_eq!(to.len(), 2);
-                assert!(to.contains(&bob));
-                assert!(to.contains(&charlie));
-                assert!(!to.contains(&alice));
-                assert!(matches!(payload, GroupPayload::Message(_)));
-            }
-            _ => panic!("expected Broadcast action"),
-        }
+        let ack = |read| GroupPayload::DeliveryAck {
+            group_id: gid.clone(),
+            message_id: mid.clone(),
+            read,
+        };
+
+        // Non-recipients (sender included) are ignored
+        assert!(hub.handle_payload(ack(false), stranger).is_empty());
+        assert!(hub.handle_payload(ack(false), alice).is_empty());
+
+        let actions = hub.handle_payload(ack(false), bob);
+        assert!(matches!(&actions[..], [GroupAction::Send {
+            to,
+            payload: GroupPayload::DeliveryReceipt { member, read: false, all_delivered: false, .. },
+        }] if *to == alice && *member == bob));
+
+        // Duplicate ack is silent
+        assert!(hub.handle_payload(ack(false), bob).is_empty());
+
+        // Charlie reads directly: delivered + read, everyone has it now
+        let actions = hub.handle_payload(ack(true), charlie);
+        assert!(matches!(&actions[..], [GroupAction::Send {
+            payload: GroupPayload::DeliveryReceipt { member, read: true, all_delivered: true, .. },
+            ..
+        }] if *member == charlie));
+
+        let status = hub.delivery_status(&gid, &mid).unwrap();
+        assert_eq!(status.delivered.len(), 2);
+        assert_eq!(status.read, vec![charlie]);
+        assert!(status.all_delivered);
     }
 
     #[test]
@@ -1551,6 +3042,9 @@ mod tests {
             sent_at: now_ms(),
             sender_signature: Vec::new(),
             seq: 0,
+            reply_to: None,
+            mentions: Vec::new(),
+            deliver_at: None,
         };
         msg.sign(&alice_secret);
 
@@ -1602,6 +3096,158 @@ mod tests {
         }
     }
 
+    #[test]
+    fn reply_to_must_reference_known_message() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let (bob, bob_secret) = keypair(2);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Test".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+
+        let original = signed_msg(gid.clone(), 1, "lunch?");
+        let original_id = original.message_id.clone();
+        hub.handle_message(alice, original);
+
+        // Reply to a relayed message: fanned out with the reference intact
+        let mut reply = GroupMessage::new(gid.clone(), bob, "bob".into(), "yes".into())
+            .with_reply_to(original_id.clone());
+        reply.sign(&bob_secret);
+        let actions = hub.handle_message(bob, reply);
+        let relayed = actions.iter().find_map(|a| match a {
+            GroupAction::Broadcast { payload: GroupPayload::Message(m), .. } => Some(m),
+            _ => None,
+        });
+        assert_eq!(relayed.unwrap().reply_to.as_deref(), Some(original_id.as_str()));
+
+        // Dangling reference: rejected
+        let mut dangling = GroupMessage::new(gid.clone(), bob, "bob".into(), "huh".into())
+            .with_reply_to("never-sent");
+        dangling.sign(&bob_secret);
+        let actions = hub.handle_message(bob, dangling);
+        assert!(matches!(
+            actions.as_slice(),
+            [GroupAction::Event(GroupEvent::SecurityViolation { .. })]
+        ));
+
+        // History served to new members keeps the thread
+        let actions = hub.handle_join(node_id(3), &gid, "charlie".into());
+        let synced = actions.iter().find_map(|a| match a {
+            GroupAction::Send { payload: GroupPayload::Sync { recent_messages, .. }, .. } => {
+                Some(recent_messages)
+            }
+            _ => None,
+        });
+        assert_eq!(synced.unwrap()[1].reply_to.as_deref(), Some(original_id.as_str()));
+    }
+
+    #[test]
+    fn mentions_must_be_members() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let (bob, bob_secret) = keypair(2);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Test".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+
+        let mut msg = GroupMessage::new(gid.clone(), bob, "bob".into(), "@alice ping".into())
+            .with_mentions(vec![alice]);
+        msg.sign(&bob_secret);
+        let actions = hub.handle_message(bob, msg);
+        let relayed = actions.iter().find_map(|a| match a {
+            GroupAction::Broadcast { payload: GroupPayload::Message(m), .. } => Some(m),
+            _ => None,
+        });
+        assert_eq!(relayed.unwrap().mentions, vec![alice]);
+
+        let mut outsider = GroupMessage::new(gid.clone(), bob, "bob".into(), "@eve".into())
+            .with_mentions(vec![alice, node_id(9)]);
+        outsider.sign(&bob_secret);
+        assert!(matches!(
+            hub.handle_message(bob, outsider).as_slice(),
+            [GroupAction::Event(GroupEvent::SecurityViolation { .. })]
+        ));
+    }
+
+    #[test]
+    fn reactions_are_deduplicated_and_fanned_out() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Test".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+
+        let msg = signed_msg(gid.clone(), 1, "ship it");
+        let message_id = msg.message_id.clone();
+        hub.handle_message(alice, msg);
+
+        let react = |emoji: &str, add: bool| GroupPayload::Reaction {
+            group_id: gid.clone(),
+            message_id: message_id.clone(),
+            emoji: emoji.into(),
+            add,
+            // Claimed reactor is ignored: the hub uses the sender.
+            reactor: alice,
+        };
+
+        let actions = hub.handle_payload(react("🎉", true), bob);
+        match actions.as_slice() {
+            [GroupAction::Broadcast { to, payload: GroupPayload::Reaction { reactor, .. } }] => {
+                assert_eq!(*reactor, bob);
+                assert!(to.contains(&alice) && to.contains(&bob));
+            }
+            other => panic!("expected reaction broadcast, got {other:?}"),
+        }
+
+        // Same reaction again, removing one that isn't there: dropped
+        assert!(hub.handle_payload(react("🎉", true), bob).is_empty());
+        assert!(hub.handle_payload(react("👍", false), bob).is_empty());
+        assert_eq!(hub.handle_payload(react("🎉", false), bob).len(), 1);
+
+        // Whitespace, unknown messages, non-members
+        assert!(hub.handle_payload(react("a b", true), bob).is_empty());
+        let unknown = GroupPayload::Reaction {
+            group_id: gid.clone(),
+            message_id: "never-sent".into(),
+            emoji: "🎉".into(),
+            add: true,
+            reactor: bob,
+        };
+        assert!(hub.handle_payload(unknown, bob).is_empty());
+        assert!(matches!(
+            hub.handle_payload(react("🎉", true), node_id(3)).as_slice(),
+            [GroupAction::Event(GroupEvent::SecurityViolation { .. })]
+        ));
+    }
+
     #[test]
     fn kick_member() {
         let mut hub = make_hub();
@@ -1691,6 +3337,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn heartbeats_skip_members_reached_by_traffic() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Test".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+        let recipients = |actions: &[GroupAction]| match actions {
+            [GroupAction::Broadcast { to, .. }] => to.clone(),
+            [] => Vec::new(),
+            other => panic!("unexpected actions: {other:?}"),
+        };
+
+        // First round: everyone, whatever the traffic
+        assert_eq!(recipients(&hub.heartbeat_actions_at(0, |_| true)).len(), 2);
+
+        // Bob was reached by traffic, Alice wasn't
+        let t1 = HUB_HEARTBEAT_INTERVAL_MS;
+        assert_eq!(recipients(&hub.heartbeat_actions_at(t1, |id| *id == bob)), vec![alice]);
+
+        // Bob's suppression runs out before his failure deadline
+        let t2 = t1 + HUB_HEARTBEAT_INTERVAL_MS;
+        assert!(t2 >= HUB_HEARTBEAT_MAX_SUPPRESSION_MS);
+        assert!(HUB_HEARTBEAT_MAX_SUPPRESSION_MS < HUB_HEARTBEAT_INTERVAL_MS * HUB_FAILURE_THRESHOLD as u64);
+        assert_eq!(recipients(&hub.heartbeat_actions_at(t2, |_| true)), vec![bob]);
+
+        // A new shadow reaches everyone at once
+        hub.groups.get_mut(&gid).unwrap().info.shadow_id = Some(bob);
+        assert_eq!(recipients(&hub.heartbeat_actions_at(t2 + 1, |_| true)).len(), 2);
+        assert!(hub.heartbeat_actions_at(t2 + 2, |_| true).is_empty());
+    }
+
     #[test]
     fn export_import_migration() {
         let mut hub1 = make_hub();
@@ -2189,6 +3877,9 @@ mod tests {
             sent_at: now_ms(),
             sender_signature: Vec::new(),
             seq: 0,
+            reply_to: None,
+            mentions: Vec::new(),
+            deliver_at: None,
         };
         msg1.sign(&alice_secret);
         let actions = hub.handle_message(alice, msg1);
@@ -2208,6 +3899,9 @@ mod tests {
             sent_at: now_ms(),
             sender_signature: Vec::new(),
             seq: 0,
+            reply_to: None,
+            mentions: Vec::new(),
+            deliver_at: None,
         };
         msg2.sign(&alice_secret);
         let actions = hub.handle_message(alice, msg2);
@@ -2302,6 +3996,93 @@ mod tests {
         assert_eq!(hub.get_group(&gid).unwrap().member_count(), 1);
     }
 
+    #[test]
+    fn ban_kicks_and_prevents_rejoin() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let charlie = node_id(3);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Test".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+        hub.handle_join(charlie, &gid, "charlie".into());
+
+        let ban = |target_id, duration_ms| GroupPayload::BanMember {
+            group_id: gid.clone(),
+            target_id,
+            duration_ms,
+        };
+
+        // Non-admins can't ban
+        assert!(hub.handle_payload(ban(bob, None), charlie).is_empty());
+
+        let actions = hub.handle_payload(ban(bob, None), alice);
+        assert!(matches!(
+            actions.as_slice(),
+            [GroupAction::Broadcast { payload: GroupPayload::MemberLeft { reason: LeaveReason::Kicked, .. }, .. }]
+        ));
+        assert!(!hub.get_group(&gid).unwrap().is_member(&bob));
+
+        // Rejoin and invites are refused while banned
+        assert!(hub.handle_join(bob, &gid, "bob".into()).is_empty());
+        assert!(hub.invite_member(&gid, &alice, bob).is_empty());
+        assert_eq!(hub.bans(&gid).len(), 1);
+
+        // Unban: bob can come back
+        hub.handle_payload(
+            GroupPayload::UnbanMember {
+                group_id: gid.clone(),
+                target_id: bob,
+            },
+            alice,
+        );
+        assert!(hub.bans(&gid).is_empty());
+        assert!(!hub.handle_join(bob, &gid, "bob".into()).is_empty());
+
+        // A lapsed ban doesn't block
+        hub.handle_payload(ban(charlie, Some(0)), alice);
+        assert!(!hub.handle_join(charlie, &gid, "charlie".into()).is_empty());
+    }
+
+    #[test]
+    fn ban_synced_to_shadow() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Test".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+        hub.assign_shadow(&gid);
+
+        let actions = hub.ban_member(&gid, &alice, node_id(9), Some(60_000));
+        let synced = actions.iter().find_map(|a| match a {
+            GroupAction::Send { payload: GroupPayload::HubShadowSync { bans, .. }, .. } => Some(bans),
+            _ => None,
+        });
+        let bans = synced.expect("shadow sync after ban");
+        assert_eq!(bans.len(), 1);
+        assert_eq!(bans[0].node_id, node_id(9));
+        assert!(bans[0].expires_at.is_some());
+    }
+
     #[test]
     fn update_member_role_promote() {
         let mut hub = make_hub();
@@ -2433,6 +4214,41 @@ mod tests {
         assert!(hub.groups.get(&gid).unwrap().invited_set.contains(&bob));
     }
 
+    #[test]
+    fn members_invite_their_linked_devices() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let bob_phone = node_id(3);
+        let charlie = node_id(4);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Private".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![bob],
+                invite_only: true,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+
+        // Not an admin, but a member: bob may bring in his phone
+        assert!(hub.invite_member(&gid, &bob, bob_phone).is_empty());
+        let actions = hub.invite_linked_device(&gid, &bob, bob_phone);
+        assert!(matches!(
+            &actions[..],
+            [GroupAction::Send { to, payload: GroupPayload::Invite { inviter_id, .. } }]
+                if *to == bob_phone && *inviter_id == bob
+        ));
+        assert!(!hub.handle_join(bob_phone, &gid, "bob".into()).is_empty());
+        assert_eq!(hub.get_group(&gid).unwrap().member_count(), 3);
+
+        // Non-members can't
+        assert!(hub.invite_linked_device(&gid, &charlie, node_id(5)).is_empty());
+    }
+
     #[test]
     fn invite_only_rejects_uninvited_join() {
         let mut hub = make_hub();
@@ -2468,6 +4284,124 @@ mod tests {
         assert_eq!(hub.get_group(&gid).unwrap().member_count(), 3);
     }
 
+    #[test]
+    fn join_request_waits_for_admin_approval() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Club".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: true,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+
+        // Knock → admins notified, bob not yet a member
+        let actions = hub.handle_payload(
+            GroupPayload::JoinRequest { group_id: gid.clone(), username: "bob".into() },
+            bob,
+        );
+        assert_eq!(actions.len(), 1);
+        match &actions[0] {
+            GroupAction::Broadcast {
+                to,
+                payload: GroupPayload::JoinRequested { request, .. },
+            } => {
+                assert_eq!(to, &vec![alice]);
+                assert_eq!(request.requester, bob);
+                assert_eq!(request.username, "bob");
+            }
+            other => panic!("expected JoinRequested broadcast, got {other:?}"),
+        }
+        assert!(!hub.get_group(&gid).unwrap().is_member(&bob));
+        assert_eq!(hub.pending_join_requests(&gid).len(), 1);
+
+        // Repeat knock doesn't re-notify
+        let actions = hub.handle_payload(
+            GroupPayload::JoinRequest { group_id: gid.clone(), username: "bobby".into() },
+            bob,
+        );
+        assert!(actions.is_empty());
+        assert_eq!(hub.pending_join_requests(&gid)[0].username, "bobby");
+
+        // Non-admin can't approve
+        assert!(hub.approve_join(&gid, &bob, bob).is_empty());
+
+        // Admin approves → bob synced in, admins told
+        let actions = hub.approve_join(&gid, &alice, bob);
+        assert!(actions.iter().any(|a| matches!(
+            a,
+            GroupAction::Send { to, payload: GroupPayload::Sync { .. } } if *to == bob
+        )));
+        assert!(actions.iter().any(|a| matches!(
+            a,
+            GroupAction::Broadcast {
+                payload: GroupPayload::JoinRequestResolved { approved: true, .. },
+                ..
+            }
+        )));
+        let group = hub.get_group(&gid).unwrap();
+        assert_eq!(group.get_member(&bob).unwrap().username, "bobby");
+        assert!(hub.pending_join_requests(&gid).is_empty());
+    }
+
+    #[test]
+    fn denied_join_request_notifies_requester() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let carol = node_id(3);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Club".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: true,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+
+        hub.handle_payload(
+            GroupPayload::JoinRequest { group_id: gid.clone(), username: "carol".into() },
+            carol,
+        );
+        let actions = hub.deny_join(&gid, &alice, carol);
+        assert_eq!(actions.len(), 1);
+        match &actions[0] {
+            GroupAction::Broadcast {
+                to,
+                payload: GroupPayload::JoinRequestResolved { approved, requester, .. },
+            } => {
+                assert!(!approved);
+                assert_eq!(*requester, carol);
+                assert!(to.contains(&alice) && to.contains(&carol));
+            }
+            other => panic!("expected JoinRequestResolved broadcast, got {other:?}"),
+        }
+        assert!(!hub.get_group(&gid).unwrap().is_member(&carol));
+        assert!(hub.pending_join_requests(&gid).is_empty());
+
+        // Nothing left to decide on
+        assert!(hub.deny_join(&gid, &alice, carol).is_empty());
+        assert!(hub.approve_join(&gid, &alice, carol).is_empty());
+
+        // Banned nodes can't knock
+        hub.ban_member(&gid, &alice, carol, None);
+        assert!(hub
+            .handle_payload(
+                GroupPayload::JoinRequest { group_id: gid.clone(), username: "carol".into() },
+                carol,
+            )
+            .is_empty());
+        assert!(hub.pending_join_requests(&gid).is_empty());
+    }
+
     // ── R13.1: Sequence number tests ──────────────────────────────────
 
     #[test]
@@ -2556,6 +4490,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn restore_history_reloads_messages_and_dedup() {
+        let mut hub = make_hub();
+        let (alice, alice_secret) = keypair(1);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Restart".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        let mut msg = GroupMessage::new(gid.clone(), alice, "alice".into(), "x".into());
+        msg.sign(&alice_secret);
+        hub.handle_message(alice, msg.clone());
+        let history: Vec<GroupMessage> = hub.groups[&gid].message_history.iter().cloned().collect();
+
+        // Snapshot lags behind the persisted history (next_seq still 0)
+        let mut snapshot = hub.snapshot();
+        snapshot.next_seqs.insert(gid.clone(), 0);
+        let mut hub2 = GroupHub::new(alice);
+        hub2.restore(snapshot);
+        hub2.restore_history(&gid, history);
+
+        assert_eq!(hub2.message_history(&gid).unwrap().len(), 1);
+        assert_eq!(hub2.groups[&gid].next_seq, 1);
+        assert!(hub2.handle_message(alice, msg).is_empty(), "replay is deduped");
+    }
+
     #[test]
     fn import_group_derives_next_seq_from_history() {
         let mut hub = GroupHub::new(node_id(1));
@@ -2573,6 +4539,13 @@ mod tests {
             shadow_id: None,
             candidate_id: None,
             invite_only: false,
+            description: String::new(),
+            avatar_hash: None,
+            config_version: 0,
+            bans: Vec::new(),
+            message_ttl_ms: None,
+            polls: Vec::new(),
+            settings: GroupSettings::default(),
         };
 
         let mut messages = vec![];
@@ -2667,4 +4640,405 @@ mod tests {
         assert_eq!(purged, 3, "should purge 3 old messages");
         assert_eq!(hub.message_history(&gid).unwrap().len(), 2);
     }
+
+    // ── Group metadata tests ─────────────────────────────────────────
+
+    #[test]
+    fn update_metadata_broadcasts_and_bumps_version() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Old".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![bob],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_payload(
+            GroupPayload::Join { group_id: gid.clone(), username: "bob".into() },
+            bob,
+        );
+
+        let actions = hub.handle_payload(
+            GroupPayload::UpdateMetadata {
+                group_id: gid.clone(),
+                name: "New".into(),
+                description: "Weekend plans".into(),
+                avatar_hash: Some([1u8; 32]),
+            },
+            alice,
+        );
+        assert_eq!(actions.len(), 1);
+        match &actions[0] {
+            GroupAction::Broadcast { to, payload } => {
+                assert_eq!(to.len(), 2);
+                assert!(matches!(
+                    payload,
+                    GroupPayload::MetadataChanged { name, config_version: 1, updated_by, .. }
+                        if name == "New" && *updated_by == alice
+                ));
+            }
+            _ => panic!("expected Broadcast(MetadataChanged)"),
+        }
+
+        let info = hub.get_group(&gid).unwrap();
+        assert_eq!(info.name, "New");
+        assert_eq!(info.description, "Weekend plans");
+        assert_eq!(info.avatar_hash, Some([1u8; 32]));
+        assert_eq!(info.config_version, 1);
+    }
+
+    #[test]
+    fn update_metadata_rejected_for_non_admin() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Test".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![bob],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_payload(
+            GroupPayload::Join { group_id: gid.clone(), username: "bob".into() },
+            bob,
+        );
+
+        let actions = hub.update_metadata(&gid, &bob, "Hijacked".into(), String::new(), None);
+        assert!(actions.is_empty(), "members cannot rename the group");
+        assert_eq!(hub.get_group(&gid).unwrap().name, "Test");
+        assert_eq!(hub.get_group(&gid).unwrap().config_version, 0);
+    }
+
+    #[test]
+    fn update_metadata_validates_and_skips_noop() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Test".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+
+        // Empty name
+        assert!(hub.update_metadata(&gid, &alice, "  ".into(), String::new(), None).is_empty());
+        // Oversized description
+        let long = "x".repeat(MAX_GROUP_DESCRIPTION_LEN + 1);
+        assert!(hub.update_metadata(&gid, &alice, "Test".into(), long, None).is_empty());
+        // Unchanged metadata
+        assert!(hub.update_metadata(&gid, &alice, "Test".into(), String::new(), None).is_empty());
+        assert_eq!(hub.get_group(&gid).unwrap().config_version, 0);
+    }
+
+    #[test]
+    fn settings_update_enforces_per_group_limits() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Quiet".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+
+        let settings = GroupSettings {
+            rate_limit_per_second: Some(2),
+            history_limit: Some(MIN_GROUP_HISTORY_LIMIT),
+            ..Default::default()
+        };
+
+        // Non-admin and out-of-range settings are rejected
+        assert!(hub.update_settings(&gid, &bob, settings).is_empty());
+        let too_fast = GroupSettings { rate_limit_per_second: Some(MAX_GROUP_RATE_LIMIT + 1), ..settings };
+        assert!(hub.update_settings(&gid, &alice, too_fast).is_empty());
+
+        // Fill history past the new limit before lowering it
+        for i in 0..12 {
+            let mut m = signed_msg(gid.clone(), 1, &format!("old-{i}"));
+            m.seq = i;
+            hub.groups.get_mut(&gid).unwrap().message_history.push_back(m);
+        }
+
+        let actions = hub.update_settings(&gid, &alice, settings);
+        assert!(matches!(
+            &actions[0],
+            GroupAction::Broadcast { payload: GroupPayload::SettingsChanged { config_version: 1, .. }, .. }
+        ));
+        assert!(hub.update_settings(&gid, &alice, settings).is_empty(), "unchanged is a no-op");
+        let history = hub.message_history(&gid).unwrap();
+        assert_eq!(history.len(), MIN_GROUP_HISTORY_LIMIT as usize);
+        assert_eq!(history[0].seq, 2, "oldest messages trimmed first");
+
+        // Two messages per second, then throttled
+        for i in 0..2 {
+            let msg = signed_msg(gid.clone(), 1, &format!("msg-{i}"));
+            assert_eq!(hub.handle_message(alice, msg).len(), 1);
+        }
+        assert!(hub.handle_message(alice, signed_msg(gid.clone(), 1, "spam")).is_empty());
+    }
+
+    #[test]
+    fn anonymous_group_strips_author_except_for_admins() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let carol = node_id(3);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Confessions".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_join(bob, &gid, "bob".into());
+        hub.handle_join(carol, &gid, "carol".into());
+        let anonymous = GroupSettings { anonymous: true, ..Default::default() };
+        assert!(!hub.update_settings(&gid, &alice, anonymous).is_empty());
+
+        // Bob posts: signature is still checked against his id
+        let mut forged = signed_msg(gid.clone(), 2, "forged");
+        forged.sender_id = carol;
+        assert!(hub.handle_message(bob, forged).iter().all(|a| matches!(
+            a,
+            GroupAction::Event(GroupEvent::SecurityViolation { .. })
+        )));
+
+        let msg = signed_msg(gid.clone(), 2, "psst");
+        let actions = hub.handle_message(bob, msg);
+
+        let to_alice = actions.iter().find_map(|a| match a {
+            GroupAction::Send { to, payload: GroupPayload::Message(m) } if *to == alice => Some(m),
+            _ => None,
+        });
+        assert_eq!(to_alice.expect("admin copy").sender_id, bob);
+
+        let to_members = actions.iter().find_map(|a| match a {
+            GroupAction::Broadcast { to, payload: GroupPayload::Message(m) } => Some((to, m)),
+            _ => None,
+        });
+        let (to, stripped) = to_members.expect("member fan-out");
+        assert_eq!(to, &vec![carol]);
+        assert_eq!(stripped.sender_id, node_id(10));
+        assert!(stripped.sender_username.is_empty());
+        assert!(stripped.sender_signature.is_empty());
+
+        let history = hub.message_history(&gid).unwrap();
+        assert_eq!(history.back().unwrap().sender_id, node_id(10), "history is stripped");
+    }
+
+    #[test]
+    fn message_ttl_set_by_admin_and_purges_history() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Ephemeral".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_payload(GroupPayload::Join { group_id: gid.clone(), username: "bob".into() }, bob);
+
+        // Non-admin and out-of-range timers are rejected
+        assert!(hub.set_message_ttl(&gid, &bob, Some(60_000)).is_empty());
+        assert!(hub.set_message_ttl(&gid, &alice, Some(MIN_MESSAGE_TTL_MS - 1)).is_empty());
+        assert!(hub.set_message_ttl(&gid, &alice, Some(MAX_MESSAGE_TTL_MS + 1)).is_empty());
+        assert!(hub.set_message_ttl(&gid, &alice, Some(30 * 24 * 60 * 60 * 1000)).is_empty());
+
+        let actions = hub.set_message_ttl(&gid, &alice, Some(60_000));
+        match &actions[0] {
+            GroupAction::Broadcast { to, payload } => {
+                assert!(to.contains(&bob));
+                assert!(matches!(
+                    payload,
+                    GroupPayload::MessageTtlChanged { ttl_ms: Some(60_000), config_version: 1, .. }
+                ));
+            }
+            _ => panic!("expected Broadcast(MessageTtlChanged)"),
+        }
+        // Same timer again is a no-op
+        assert!(hub.set_message_ttl(&gid, &alice, Some(60_000)).is_empty());
+
+        let messages = (0..3u64)
+            .map(|i| {
+                let mut m = GroupMessage::new(gid.clone(), alice, "alice".into(), format!("m{i}"));
+                m.seq = i;
+                m.sent_at = 1_000_000 + i * 50_000;
+                m
+            })
+            .collect();
+        hub.groups.get_mut(&gid).unwrap().message_history = messages;
+
+        // At 1_100_000: m0 (expires 1_060_000) is gone, m1 (1_110_000) stays
+        assert_eq!(hub.purge_ephemeral_messages(1_100_000), 1);
+        assert_eq!(hub.message_history(&gid).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn poll_counts_one_vote_per_member() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let eve = node_id(3);
+
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Polls".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: false,
+            },
+            alice,
+        );
+        let gid = hub.groups.keys().next().unwrap().clone();
+        hub.handle_payload(GroupPayload::Join { group_id: gid.clone(), username: "bob".into() }, bob);
+
+        // A single option is not a poll
+        let bad = GroupPoll::new("Lunch?".into(), vec!["Pizza".into()], bob);
+        assert!(hub.handle_payload(GroupPayload::Poll { group_id: gid.clone(), poll: bad }, bob).is_empty());
+
+        // Bob opens a poll; the hub resets any forged tally
+        let mut poll = GroupPoll::new("Lunch?".into(), vec!["Pizza".into(), "Sushi".into()], alice);
+        poll.tallies = vec![99, 0];
+        let poll_id = poll.poll_id.clone();
+        let actions = hub.handle_payload(GroupPayload::Poll { group_id: gid.clone(), poll }, bob);
+        match &actions[0] {
+            GroupAction::Broadcast { payload: GroupPayload::Poll { poll, .. }, .. } => {
+                assert_eq!(poll.tallies, vec![0, 0]);
+                assert_eq!(poll.created_by, bob);
+            }
+            other => panic!("expected Broadcast(Poll), got {other:?}"),
+        }
+
+        let vote = |option| GroupPayload::Vote { group_id: gid.clone(), poll_id: poll_id.clone(), option };
+        let actions = hub.handle_payload(vote(1), alice);
+        assert!(matches!(&actions[0],
+            GroupAction::Broadcast { payload: GroupPayload::Poll { poll, .. }, .. } if poll.tallies == vec![0, 1]));
+
+        // Second vote, out-of-range option and non-member are refused
+        assert!(hub.handle_payload(vote(0), alice).is_empty());
+        assert!(hub.handle_payload(vote(5), bob).is_empty());
+        assert!(matches!(
+            &hub.handle_payload(vote(0), eve)[0],
+            GroupAction::Event(GroupEvent::SecurityViolation { .. })
+        ));
+        assert_eq!(hub.get_group(&gid).unwrap().polls[0].voters, vec![alice]);
+    }
+
+    // ── Invite token tests ───────────────────────────────────────────
+
+    fn create_invite_only(hub: &mut GroupHub, admin: NodeId) -> GroupId {
+        hub.handle_payload(
+            GroupPayload::Create {
+                group_name: "Private".into(),
+                creator_username: "alice".into(),
+                initial_members: vec![],
+                invite_only: true,
+            },
+            admin,
+        );
+        hub.groups.keys().next().unwrap().clone()
+    }
+
+    #[test]
+    fn join_with_registered_token_bypasses_invite_only() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let carol = node_id(3);
+        let gid = create_invite_only(&mut hub, alice);
+
+        let secret = [5u8; 32];
+        let hash = crate::group::hash_join_secret(&secret);
+        hub.handle_payload(
+            GroupPayload::RegisterInviteToken {
+                group_id: gid.clone(),
+                secret_hash: hash,
+                expires_at: now_ms() + 60_000,
+            },
+            alice,
+        );
+        assert_eq!(hub.invite_token_count(&gid), 1);
+
+        let actions = hub.handle_payload(
+            GroupPayload::JoinWithToken {
+                group_id: gid.clone(),
+                username: "carol".into(),
+                join_secret: secret,
+            },
+            carol,
+        );
+        assert!(matches!(&actions[0], GroupAction::Send { to, payload: GroupPayload::Sync { .. } } if *to == carol));
+        assert!(hub.get_group(&gid).unwrap().is_member(&carol));
+    }
+
+    #[test]
+    fn join_with_unknown_secret_rejected() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let carol = node_id(3);
+        let gid = create_invite_only(&mut hub, alice);
+
+        let actions = hub.handle_payload(
+            GroupPayload::JoinWithToken {
+                group_id: gid.clone(),
+                username: "carol".into(),
+                join_secret: [9u8; 32],
+            },
+            carol,
+        );
+        assert!(actions.is_empty());
+        assert!(!hub.get_group(&gid).unwrap().is_member(&carol));
+    }
+
+    #[test]
+    fn register_invite_token_requires_admin_and_future_expiry() {
+        let mut hub = make_hub();
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let gid = create_invite_only(&mut hub, alice);
+        let hash = crate::group::hash_join_secret(&[1u8; 32]);
+
+        hub.register_invite_token(&gid, &bob, hash, now_ms() + 60_000);
+        assert_eq!(hub.invite_token_count(&gid), 0, "non-admin cannot register");
+
+        hub.register_invite_token(&gid, &alice, hash, 1);
+        assert_eq!(hub.invite_token_count(&gid), 0, "already-expired token ignored");
+
+        hub.register_invite_token(&gid, &alice, hash, u64::MAX);
+        assert_eq!(hub.invite_token_count(&gid), 1);
+        let exp = hub.groups[&gid].invite_tokens[&hash];
+        assert!(exp <= now_ms() + INVITE_TTL_MS, "expiry capped at 24h");
+    }
 }
@@ -0,0 +1,221 @@
+/// Invite tokens — shareable join codes for groups.
+///
+/// A token encodes everything a stranger needs to join: the group, the hub
+/// to dial, and a one-off join secret the hub checks against its registry.
+/// Tokens are signed by the issuing admin and expire (24h max, ToM design
+/// decision #2), so a leaked code stops working on its own.
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::group::types::{GroupId, INVITE_TTL_MS};
+use crate::types::NodeId;
+use crate::TomProtocolError;
+
+/// Text prefix of an encoded invite token.
+pub const INVITE_TOKEN_PREFIX: &str = "tom-join:";
+
+/// Domain separator for token signatures.
+const INVITE_TOKEN_DOMAIN: &[u8] = b"tom-invite-token-v1";
+
+/// A signed, expiring invitation that anyone holding it can redeem.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupInviteToken {
+    pub group_id: GroupId,
+    pub group_name: String,
+    pub hub_id: NodeId,
+    pub issuer_id: NodeId,
+    pub join_secret: [u8; 32],
+    pub expires_at: u64,
+    pub signature: Vec<u8>,
+}
+
+impl GroupInviteToken {
+    /// Create an unsigned token with a fresh random join secret.
+    ///
+    /// `ttl_ms` is clamped to [`INVITE_TTL_MS`].
+    pub fn new(
+        group_id: GroupId,
+        group_name: String,
+        hub_id: NodeId,
+        issuer_id: NodeId,
+        now_ms: u64,
+        ttl_ms: u64,
+    ) -> Self {
+        use chacha20poly1305::aead::rand_core::{OsRng, RngCore};
+        let mut join_secret = [0u8; 32];
+        OsRng.fill_bytes(&mut join_secret);
+        Self {
+            group_id,
+            group_name,
+            hub_id,
+            issuer_id,
+            join_secret,
+            expires_at: now_ms + ttl_ms.min(INVITE_TTL_MS),
+            signature: Vec::new(),
+        }
+    }
+
+    /// Hash of the join secret — what the hub stores and compares against.
+    pub fn secret_hash(&self) -> [u8; 32] {
+        hash_join_secret(&self.join_secret)
+    }
+
+    /// Whether this token has expired.
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        now_ms >= self.expires_at
+    }
+
+    /// Deterministic bytes covered by the issuer signature.
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(INVITE_TOKEN_DOMAIN);
+        buf.extend_from_slice(self.group_id.0.as_bytes());
+        buf.extend_from_slice(self.group_name.as_bytes());
+        buf.extend_from_slice(&self.hub_id.as_bytes());
+        buf.extend_from_slice(&self.issuer_id.as_bytes());
+        buf.extend_from_slice(&self.join_secret);
+        buf.extend_from_slice(&self.expires_at.to_le_bytes());
+        buf
+    }
+
+    /// Sign this token with the issuer's Ed25519 secret key seed.
+    pub fn sign(&mut self, secret_seed: &[u8; 32]) {
+        let signing_key = SigningKey::from_bytes(secret_seed);
+        self.signature = signing_key.sign(&self.signing_bytes()).to_bytes().to_vec();
+    }
+
+    /// Verify the issuer signature against `issuer_id`.
+    pub fn verify_signature(&self) -> bool {
+        let Ok(verifying_key) = VerifyingKey::from_bytes(&self.issuer_id.as_bytes()) else {
+            return false;
+        };
+        let Ok(sig_array): Result<&[u8; 64], _> = self.signature.as_slice().try_into() else {
+            return false;
+        };
+        let sig = Signature::from_bytes(sig_array);
+        verifying_key.verify(&self.signing_bytes(), &sig).is_ok()
+    }
+
+    /// Encode as a shareable string (`tom-join:<base64url>`).
+    pub fn encode(&self) -> Result<String, TomProtocolError> {
+        let bytes = rmp_serde::to_vec(self)?;
+        Ok(format!(
+            "{INVITE_TOKEN_PREFIX}{}",
+            data_encoding::BASE64URL_NOPAD.encode(&bytes)
+        ))
+    }
+
+    /// Decode a shareable string. Does not check signature or expiry —
+    /// use [`GroupInviteToken::validate`] for that.
+    pub fn decode(s: &str) -> Result<Self, TomProtocolError> {
+        let body = s.trim().strip_prefix(INVITE_TOKEN_PREFIX).ok_or_else(|| {
+            TomProtocolError::InvalidInviteToken {
+                reason: "missing prefix".into(),
+            }
+        })?;
+        let bytes = data_encoding::BASE64URL_NOPAD
+            .decode(body.as_bytes())
+            .map_err(|e| TomProtocolError::InvalidInviteToken {
+                reason: format!("bad encoding: {e}"),
+            })?;
+        Ok(rmp_serde::from_slice(&bytes)?)
+    }
+
+    /// Check signature and expiry.
+    pub fn validate(&self, now_ms: u64) -> Result<(), TomProtocolError> {
+        if !self.verify_signature() {
+            return Err(TomProtocolError::InvalidSignature);
+        }
+        if self.is_expired(now_ms) {
+            return Err(TomProtocolError::InvalidInviteToken {
+                reason: "expired".into(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// SHA-256 of a join secret.
+pub fn hash_join_secret(secret: &[u8; 32]) -> [u8; 32] {
+    Sha256::digest(secret).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypair(seed: u8) -> (NodeId, [u8; 32]) {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        let node_id: NodeId = secret.public().to_string().parse().unwrap();
+        (node_id, secret.to_bytes())
+    }
+
+    fn signed_token(now: u64) -> GroupInviteToken {
+        let (admin, admin_seed) = keypair(1);
+        let (hub, _) = keypair(10);
+        let mut token = GroupInviteToken::new(
+            GroupId::from("grp-1".to_string()),
+            "Climbing".into(),
+            hub,
+            admin,
+            now,
+            60_000,
+        );
+        token.sign(&admin_seed);
+        token
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let token = signed_token(1000);
+        let encoded = token.encode().unwrap();
+        assert!(encoded.starts_with(INVITE_TOKEN_PREFIX));
+        let decoded = GroupInviteToken::decode(&encoded).unwrap();
+        assert_eq!(decoded, token);
+        assert!(decoded.validate(1000).is_ok());
+    }
+
+    #[test]
+    fn tampered_token_fails_validation() {
+        let mut token = signed_token(1000);
+        let (other_hub, _) = keypair(11);
+        token.hub_id = other_hub;
+        assert!(matches!(
+            token.validate(1000),
+            Err(TomProtocolError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn expired_token_fails_validation() {
+        let token = signed_token(1000);
+        assert!(token.validate(1000 + 59_999).is_ok());
+        assert!(matches!(
+            token.validate(1000 + 60_000),
+            Err(TomProtocolError::InvalidInviteToken { .. })
+        ));
+    }
+
+    #[test]
+    fn ttl_clamped_to_invite_ttl() {
+        let (admin, _) = keypair(1);
+        let token = GroupInviteToken::new(
+            GroupId::from("grp-1".to_string()),
+            "G".into(),
+            admin,
+            admin,
+            0,
+            u64::MAX,
+        );
+        assert_eq!(token.expires_at, INVITE_TTL_MS);
+    }
+
+    #[test]
+    fn decode_rejects_garbage() {
+        assert!(GroupInviteToken::decode("not-a-token").is_err());
+        assert!(GroupInviteToken::decode("tom-join:!!!").is_err());
+    }
+}
@@ -4,7 +4,7 @@
 /// caller executes via the transport layer.
 ///
 /// Tracks: groups we belong to, pending invites, message history.
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use serde::{Deserialize, Serialize};
 
@@ -30,6 +30,9 @@ pub struct GroupManagerSnapshot {
     pub message_history: HashMap<GroupId, Vec<GroupMessage>>,
     /// Last received sequence number per group (for offline gap-fill).
     pub last_seqs: HashMap<GroupId, u64>,
+    /// Local notification preferences per group.
+    #[serde(default)]
+    pub notification_prefs: HashMap<GroupId, GroupNotificationPrefs>,
 }
 
 /// State for a group where we are the shadow.
@@ -42,10 +45,14 @@ struct ShadowState {
     candidate_id: Option<NodeId>,
     /// Config version from primary.
     config_version: u64,
+    /// Ban list from primary (carried over on promotion).
+    bans: Vec<GroupBan>,
     /// Consecutive ping failures.
     ping_failures: u32,
-    /// Number of HubUnreachable reports received.
-    unreachable_reports: u32,
+    /// When our outstanding HubPing was sent (None once answered).
+    ping_sent_at: Option<u64>,
+    /// Distinct members that reported the hub unreachable.
+    unreachable_reporters: HashSet<NodeId>,
 }
 
 /// Member-side group state manager.
@@ -80,6 +87,35 @@ pub struct GroupManager {
     last_seqs: HashMap<GroupId, u64>,
     /// Groups where we are the shadow (group_id -> ShadowState).
     shadow_state: HashMap<GroupId, ShadowState>,
+    /// Delivery receipts for our own sent messages, per group (not persisted).
+    delivery_receipts: HashMap<GroupId, DeliveryReceipts>,
+    /// Reactions on recent messages, per group (not persisted).
+    reactions: HashMap<GroupId, MessageReactions>,
+    /// Local mute / mentions-only preferences per group.
+    notification_prefs: HashMap<GroupId, GroupNotificationPrefs>,
+    /// Last time we heard our hub's heartbeat, per group (not persisted).
+    hub_last_seen: HashMap<GroupId, u64>,
+    /// Groups whose hub we already reported unreachable to the shadow.
+    hub_reported: HashSet<GroupId>,
+    /// Join requests we knocked with, awaiting a decision (group_id → hub).
+    outgoing_join_requests: HashMap<GroupId, NodeId>,
+    /// Join requests awaiting a decision in groups we administer (not persisted).
+    join_requests: HashMap<GroupId, Vec<GroupJoinRequest>>,
+}
+
+/// Receipts collected for our recent messages in one group (bounded).
+#[derive(Debug, Default)]
+struct DeliveryReceipts {
+    order: VecDeque<String>,
+    by_message: HashMap<String, GroupDeliveryStatus>,
+}
+
+/// Reactions seen on recent messages in one group (bounded).
+#[derive(Debug, Default)]
+struct MessageReactions {
+    order: VecDeque<String>,
+    /// message_id → emoji → reactors (in reaction order).
+    by_message: HashMap<String, HashMap<String, Vec<NodeId>>>,
 }
 
 impl GroupManager {
@@ -99,6 +135,13 @@ impl GroupManager {
             pending_decrypt: HashMap::new(),
             last_seqs: HashMap::new(),
             shadow_state: HashMap::new(),
+            delivery_receipts: HashMap::new(),
+            reactions: HashMap::new(),
+            notification_prefs: HashMap::new(),
+            hub_last_seen: HashMap::new(),
+            hub_reported: HashSet::new(),
+            outgoing_join_requests: HashMap::new(),
+            join_requests: HashMap::new(),
         }
     }
 
@@ -129,6 +172,14 @@ impl GroupManager {
         self.pending_invites.values().collect()
     }
 
+    /// Join requests awaiting an admin decision, oldest first (admins only).
+    pub fn pending_join_requests(&self, group_id: &GroupId) -> &[GroupJoinRequest] {
+        self.join_requests
+            .get(group_id)
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
     /// Get message history for a group.
     pub fn message_history(&self, group_id: &GroupId) -> &[GroupMessage] {
         self.message_history
@@ -137,6 +188,14 @@ impl GroupManager {
             .unwrap_or(&[])
     }
 
+    /// Messages in our local history that reply to `message_id`, oldest first.
+    pub fn replies_to(&self, group_id: &GroupId, message_id: &str) -> Vec<&GroupMessage> {
+        self.message_history(group_id)
+            .iter()
+            .filter(|m| m.reply_to.as_deref() == Some(message_id))
+            .collect()
+    }
+
     /// Get recent messages for sync (when we're also a hub).
     pub fn messages_for_sync(&self, group_id: &GroupId) -> Vec<GroupMessage> {
         self.message_history
@@ -277,6 +336,143 @@ impl GroupManager {
             .collect()
     }
 
+    /// Mint a signed invite token for a group we administer.
+    ///
+    /// Returns the token plus the action registering its secret with the hub,
+    /// or `None` if the group is unknown or we're not an admin.
+    pub fn create_invite_token(
+        &self,
+        group_id: &GroupId,
+        secret_seed: &[u8; 32],
+        ttl_ms: u64,
+    ) -> Option<(crate::group::GroupInviteToken, Vec<GroupAction>)> {
+        let group = self.groups.get(group_id)?;
+        if !group.is_admin(&self.local_id) {
+            return None;
+        }
+
+        let mut token = crate::group::GroupInviteToken::new(
+            group_id.clone(),
+            group.name.clone(),
+            group.hub_relay_id,
+            self.local_id,
+            now_ms(),
+            ttl_ms,
+        );
+        token.sign(secret_seed);
+
+        let actions = vec![GroupAction::Send {
+            to: group.hub_relay_id,
+            payload: GroupPayload::RegisterInviteToken {
+                group_id: group_id.clone(),
+                secret_hash: token.secret_hash(),
+                expires_at: token.expires_at,
+            },
+        }];
+        Some((token, actions))
+    }
+
+    /// Redeem an invite token. Caller is responsible for validating it first.
+    pub fn join_by_token(&self, token: &crate::group::GroupInviteToken) -> Vec<GroupAction> {
+        if self.groups.contains_key(&token.group_id) {
+            return vec![];
+        }
+
+        vec![GroupAction::Send {
+            to: token.hub_id,
+            payload: GroupPayload::JoinWithToken {
+                group_id: token.group_id.clone(),
+                username: self.local_username.clone(),
+                join_secret: token.join_secret,
+            },
+        }]
+    }
+
+    // ── Join Approval ────────────────────────────────────────────────────
+
+    /// Ask a group's hub to let us in; an admin decides.
+    pub fn request_join(&mut self, group_id: &GroupId, hub_id: NodeId) -> Vec<GroupAction> {
+        if self.groups.contains_key(group_id) {
+            return vec![];
+        }
+
+        self.outgoing_join_requests.insert(group_id.clone(), hub_id);
+        vec![GroupAction::Send {
+            to: hub_id,
+            payload: GroupPayload::JoinRequest {
+                group_id: group_id.clone(),
+                username: self.local_username.clone(),
+            },
+        }]
+    }
+
+    /// Handle a join request forwarded by the hub (we're an admin).
+    pub fn handle_join_requested(
+        &mut self,
+        group_id: &GroupId,
+        request: GroupJoinRequest,
+    ) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get(group_id) else {
+            return vec![];
+        };
+        if !group.is_admin(&self.local_id) {
+            return vec![];
+        }
+
+        let pending = self.join_requests.entry(group_id.clone()).or_default();
+        pending.retain(|r| r.requester != request.requester);
+        pending.push(request.clone());
+        if pending.len() > MAX_PENDING_JOIN_REQUESTS {
+            pending.remove(0);
+        }
+
+        vec![GroupAction::Event(GroupEvent::JoinRequested {
+            group_id: group_id.clone(),
+            request,
+        })]
+    }
+
+    /// Handle a decision on a join request.
+    ///
+    /// A denial addressed to us is only trusted from the hub we knocked on.
+    pub fn handle_join_request_resolved(
+        &mut self,
+        group_id: &GroupId,
+        requester: NodeId,
+        approved: bool,
+        resolved_by: NodeId,
+        from: NodeId,
+    ) -> Vec<GroupAction> {
+        if requester == self.local_id {
+            if approved || self.outgoing_join_requests.get(group_id) != Some(&from) {
+                return vec![];
+            }
+            self.outgoing_join_requests.remove(group_id);
+            return vec![GroupAction::Event(GroupEvent::JoinDenied {
+                group_id: group_id.clone(),
+            })];
+        }
+
+        let Some(pending) = self.join_requests.get_mut(group_id) else {
+            return vec![];
+        };
+        let before = pending.len();
+        pending.retain(|r| r.requester != requester);
+        if pending.len() == before {
+            return vec![];
+        }
+        if pending.is_empty() {
+            self.join_requests.remove(group_id);
+        }
+
+        vec![GroupAction::Event(GroupEvent::JoinRequestResolved {
+            group_id: group_id.clone(),
+            requester,
+            approved,
+            resolved_by,
+        })]
+    }
+
     /// Decline a pending invitation.
     pub fn decline_invite(&mut self, group_id: &GroupId) -> bool {
         self.pending_invites.remove(group_id).is_some()
@@ -301,11 +497,13 @@ impl GroupManager {
         let group_id = group.group_id.clone();
         let group_name = group.name.clone();
         self.groups.insert(group_id.clone(), group);
+        self.outgoing_join_requests.remove(&group_id);
 
         // Store synced messages
+        let limit = self.history_limit(&group_id);
         let history = self.message_history.entry(group_id.clone()).or_default();
         for msg in recent_messages {
-            if history.len() < self.max_history_per_group {
+            if history.len() < limit {
                 history.push(msg);
             }
         }
@@ -359,6 +557,8 @@ impl GroupManager {
         if *node_id == self.local_id && reason == LeaveReason::Kicked {
             self.groups.remove(group_id);
             self.message_history.remove(group_id);
+            self.notification_prefs.remove(group_id);
+            self.join_requests.remove(group_id);
             self.cleanup_group_keys(group_id);
             self.shadow_state.remove(group_id);
             return vec![GroupAction::Event(GroupEvent::MemberLeft {
@@ -414,171 +614,765 @@ impl GroupManager {
         })]
     }
 
-    /// Leave a group voluntarily. Returns actions to notify the hub.
-    pub fn leave_group(&mut self, group_id: &GroupId) -> Vec<GroupAction> {
-        let Some(group) = self.groups.remove(group_id) else {
-            return vec![];
-        };
-
-        self.message_history.remove(group_id);
-        self.cleanup_group_keys(group_id);
-        self.shadow_state.remove(group_id);
-
-        vec![GroupAction::Send {
-            to: group.hub_relay_id,
-            payload: GroupPayload::Leave {
-                group_id: group_id.clone(),
-            },
-        }]
-    }
-
-    // ── Messages ─────────────────────────────────────────────────────────
-
-    /// Handle an incoming group message.
-    pub fn handle_message(&mut self, message: GroupMessage) -> Vec<GroupAction> {
-        let group_id = &message.group_id;
-        if !self.groups.contains_key(group_id) {
-            return vec![];
-        }
-        self.try_decrypt_and_deliver(message)
+    /// Whether a versioned config change (metadata, timer, settings) applies.
+    ///
+    /// It must come from the group's hub (the promoted shadow after a
+    /// failover), be made by an admin, and move `config_version` forward by
+    /// at most [`MAX_CONFIG_VERSION_STEP`], so a forged huge version can't
+    /// freeze the config.
+    fn accepts_config_update(
+        group: &GroupInfo,
+        from: NodeId,
+        updated_by: &NodeId,
+        config_version: u64,
+    ) -> bool {
+        from == group.hub_relay_id
+            && group.is_admin(updated_by)
+            && config_version > group.config_version
+            && config_version - group.config_version <= MAX_CONFIG_VERSION_STEP
     }
 
-    // ── Hub Migration ────────────────────────────────────────────────────
-
-    /// Handle hub migration notification.
-    pub fn handle_hub_migration(
+    /// Handle a metadata broadcast from the hub (rename, topic, avatar).
+    ///
+    /// Updates are applied only if they pass [`Self::accepts_config_update`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn handle_metadata_changed(
         &mut self,
+        from: NodeId,
         group_id: &GroupId,
-        new_hub_id: NodeId,
+        name: String,
+        description: String,
+        avatar_hash: Option<[u8; 32]>,
+        config_version: u64,
+        updated_by: NodeId,
     ) -> Vec<GroupAction> {
         let Some(group) = self.groups.get_mut(group_id) else {
             return vec![];
         };
+        if !Self::accepts_config_update(group, from, &updated_by, config_version) {
+            return vec![];
+        }
 
-        group.hub_relay_id = new_hub_id;
+        group.name = name.clone();
+        group.description = description.clone();
+        group.avatar_hash = avatar_hash;
+        group.config_version = config_version;
         group.last_activity_at = now_ms();
 
-        vec![GroupAction::Event(GroupEvent::HubMigrated {
+        vec![GroupAction::Event(GroupEvent::MetadataChanged {
             group_id: group_id.clone(),
-            new_hub_id,
+            name,
+            description,
+            avatar_hash,
+            config_version,
+            updated_by,
         })]
     }
 
-    // ── Sender Key Management ─────────────────────────────────────────
-
-    /// Get our current sender key for a group.
-    pub fn local_sender_key(&self, group_id: &GroupId) -> Option<&SenderKeyEntry> {
-        self.local_sender_keys.get(group_id)
-    }
-
-    /// Get a member's sender key for a group.
-    pub fn get_sender_key(&self, group_id: &GroupId, sender_id: &NodeId) -> Option<&SenderKeyEntry> {
-        self.sender_keys.get(group_id)?.get(sender_id)
-    }
-
-    /// Generate a new sender key for ourselves in this group.
-    fn generate_local_sender_key(&mut self, group_id: &GroupId) -> SenderKeyEntry {
-        let old_epoch = self
-            .local_sender_keys
-            .get(group_id)
-            .map(|e| e.epoch)
-            .unwrap_or(0);
-        let entry = SenderKeyEntry {
-            owner_id: self.local_id,
-            key: crate::crypto::generate_sender_key(),
-            epoch: old_epoch + 1,
-            created_at: now_ms(),
+    /// Handle a disappearing-message timer change from the hub.
+    ///
+    /// Shares `config_version` with metadata updates and the same checks.
+    pub fn handle_message_ttl_changed(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        ttl_ms: Option<u64>,
+        config_version: u64,
+        updated_by: NodeId,
+    ) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return vec![];
         };
-        self.local_sender_keys
-            .insert(group_id.clone(), entry.clone());
-        self.local_sender_message_counts.insert(group_id.clone(), 0);
-        entry
-    }
+        if !Self::accepts_config_update(group, from, &updated_by, config_version) {
+            return vec![];
+        }
+        if ttl_ms.is_some_and(|ttl| !(MIN_MESSAGE_TTL_MS..=MAX_MESSAGE_TTL_MS).contains(&ttl)) {
+            return vec![];
+        }
 
-    /// Current local sender key epoch for a group.
-    pub fn local_sender_epoch(&self, group_id: &GroupId) -> Option<u32> {
-        self.local_sender_keys.get(group_id).map(|k| k.epoch)
-    }
+        group.message_ttl_ms = ttl_ms;
+        group.config_version = config_version;
+        group.last_activity_at = now_ms();
 
-    /// Increment local sender message count for dual-trigger key rotation.
-    pub fn note_local_message_sent(&mut self, group_id: &GroupId) {
-        let count = self
-            .local_sender_message_counts
-            .entry(group_id.clone())
-            .or_insert(0);
-        *count = count.saturating_add(1);
+        vec![GroupAction::Event(GroupEvent::MessageTtlChanged {
+            group_id: group_id.clone(),
+            ttl_ms,
+            updated_by,
+        })]
     }
 
-    /// Rotate local sender key when one trigger is reached:
-    /// - 24h elapsed since current key creation, OR
-    /// - 10_000 local messages sent in current epoch.
+    /// Handle a settings change from the hub (trims history to the new limit).
     ///
-    /// Returns distribution actions when rotation happened.
-    pub fn maybe_rotate_local_sender_key(&mut self, group_id: &GroupId) -> Vec<GroupAction> {
-        let Some(current) = self.local_sender_keys.get(group_id).cloned() else {
+    /// Shares `config_version` with metadata updates and the same checks.
+    pub fn handle_settings_changed(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        settings: GroupSettings,
+        config_version: u64,
+        updated_by: NodeId,
+    ) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get_mut(group_id) else {
             return vec![];
         };
-        let now = now_ms();
-        let age_ms = now.saturating_sub(current.created_at);
-        let msg_count = self
-            .local_sender_message_counts
-            .get(group_id)
-            .copied()
-            .unwrap_or(0);
+        if !Self::accepts_config_update(group, from, &updated_by, config_version) {
+            return vec![];
+        }
 
-        if age_ms >= SENDER_KEY_ROTATE_MAX_AGE_MS || msg_count >= SENDER_KEY_ROTATE_MAX_MESSAGES {
-            self.generate_local_sender_key(group_id);
-            return self.build_sender_key_distribution(group_id);
+        group.settings = settings;
+        group.config_version = config_version;
+        group.last_activity_at = now_ms();
+
+        let limit = self.history_limit(group_id);
+        if let Some(history) = self.message_history.get_mut(group_id) {
+            let excess = history.len().saturating_sub(limit);
+            history.drain(..excess);
         }
-        vec![]
+
+        vec![GroupAction::Event(GroupEvent::SettingsChanged {
+            group_id: group_id.clone(),
+            settings,
+            updated_by,
+        })]
     }
 
-    /// Build SenderKeyDistribution actions to send our key to all members.
-    pub fn build_sender_key_distribution(&self, group_id: &GroupId) -> Vec<GroupAction> {
-        let Some(group) = self.groups.get(group_id) else {
-            return vec![];
-        };
-        let Some(local_key) = self.local_sender_keys.get(group_id) else {
-            return vec![];
-        };
+    /// History size kept for a group (admin setting, else our default).
+    fn history_limit(&self, group_id: &GroupId) -> usize {
+        self.groups
+            .get(group_id)
+            .and_then(|g| g.settings.history_limit)
+            .map_or(self.max_history_per_group, |n| n as usize)
+    }
 
-        let mut encrypted_keys = Vec::new();
-        for member in &group.members {
-            if member.node_id == self.local_id {
+    /// Remove history messages that outlived their group's disappearing timer.
+    ///
+    /// Emits one `MessagesExpired` event per group so clients delete the
+    /// same messages from view.
+    pub fn expire_messages(&mut self, now: u64) -> Vec<GroupAction> {
+        let mut actions = Vec::new();
+        for (group_id, group) in &self.groups {
+            if group.message_ttl_ms.is_none() {
                 continue;
             }
-            let recipient_pk = member.node_id.as_bytes();
-            match crate::crypto::encrypt(&local_key.key, &recipient_pk) {
-                Ok(encrypted) => {
-                    encrypted_keys.push(EncryptedSenderKey {
-                        recipient_id: member.node_id,
-                        encrypted_key: encrypted,
-                    });
-                }
-                Err(e) => {
-                    tracing::warn!("sender key encrypt for {} failed: {e}", member.node_id);
+            let Some(history) = self.message_history.get_mut(group_id) else {
+                continue;
+            };
+            let mut message_ids = Vec::new();
+            history.retain(|msg| {
+                let expired = group.is_message_expired(msg, now);
+                if expired {
+                    message_ids.push(msg.message_id.clone());
                 }
+                !expired
+            });
+            if !message_ids.is_empty() {
+                actions.push(GroupAction::Event(GroupEvent::MessagesExpired {
+                    group_id: group_id.clone(),
+                    message_ids,
+                }));
             }
         }
+        actions
+    }
 
-        if encrypted_keys.is_empty() {
+    /// Ask the hub for a page of older messages (`seq < before`).
+    pub fn request_history(&self, group_id: &GroupId, before: u64, limit: u32) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get(group_id) else {
             return vec![];
-        }
-
+        };
         vec![GroupAction::Send {
             to: group.hub_relay_id,
-            payload: GroupPayload::SenderKeyDistribution {
+            payload: GroupPayload::FetchHistory {
                 group_id: group_id.clone(),
-                from: self.local_id,
-                epoch: local_key.epoch,
-                encrypted_keys,
+                before,
+                limit: limit.min(MAX_HISTORY_PAGE as u32),
             },
         }]
     }
 
-    /// Handle an incoming SenderKeyDistribution -- decrypt and store the sender's key.
-    pub fn handle_sender_key_distribution(
-        &mut self,
+    /// Handle a history page from the hub.
+    ///
+    /// Encrypted messages are decrypted with the sender keys we hold; those
+    /// from epochs we never had keys for are dropped from the page.
+    pub fn handle_history_page(
+        &self,
+        group_id: &GroupId,
+        messages: Vec<GroupMessage>,
+        has_more: bool,
+    ) -> Vec<GroupAction> {
+        if !self.groups.contains_key(group_id) {
+            return vec![];
+        }
+        let messages = messages
+            .into_iter()
+            .filter(|m| &m.group_id == group_id)
+            .filter_map(|m| self.decrypt_history_message(m))
+            .collect();
+        vec![GroupAction::Event(GroupEvent::HistoryPage {
+            group_id: group_id.clone(),
+            messages,
+            has_more,
+        })]
+    }
+
+    /// Decrypt one history message with the current or previous sender key.
+    fn decrypt_history_message(&self, mut message: GroupMessage) -> Option<GroupMessage> {
+        if !message.encrypted {
+            return Some(message);
+        }
+        let current = self
+            .sender_keys
+            .get(&message.group_id)
+            .and_then(|keys| keys.get(&message.sender_id))
+            .filter(|k| k.epoch == message.key_epoch);
+        let previous = self
+            .previous_sender_keys
+            .get(&message.group_id)
+            .and_then(|m| m.get(&message.sender_id))
+            .map(|prev| &prev.entry)
+            .filter(|k| k.epoch == message.key_epoch);
+        let key = current.or(previous)?.key;
+        let content = message.decrypt(&key).ok()?;
+        message.sender_username = content.username;
+        message.text = content.text;
+        Some(message)
+    }
+
+    /// Leave a group voluntarily. Returns actions to notify the hub.
+    pub fn leave_group(&mut self, group_id: &GroupId) -> Vec<GroupAction> {
+        let Some(group) = self.groups.remove(group_id) else {
+            return vec![];
+        };
+
+        self.message_history.remove(group_id);
+        self.delivery_receipts.remove(group_id);
+        self.reactions.remove(group_id);
+        self.notification_prefs.remove(group_id);
+        self.join_requests.remove(group_id);
+        self.cleanup_group_keys(group_id);
+        self.shadow_state.remove(group_id);
+
+        vec![GroupAction::Send {
+            to: group.hub_relay_id,
+            payload: GroupPayload::Leave {
+                group_id: group_id.clone(),
+            },
+        }]
+    }
+
+    // ── Messages ─────────────────────────────────────────────────────────
+
+    /// Handle an incoming group message.
+    pub fn handle_message(&mut self, message: GroupMessage) -> Vec<GroupAction> {
+        let group_id = &message.group_id;
+        if !self.groups.contains_key(group_id) {
+            return vec![];
+        }
+        self.try_decrypt_and_deliver(message)
+    }
+
+    /// Relay one shard of a large group's fan-out on behalf of the hub.
+    ///
+    /// Only the group's hub may delegate to us. Our own copy is delivered
+    /// like any other message; the rest is forwarded to `recipients`.
+    pub fn handle_shard_relay(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        recipients: Vec<NodeId>,
+        message: GroupMessage,
+    ) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get(group_id) else {
+            return vec![];
+        };
+        if from != group.hub_relay_id
+            || message.group_id != *group_id
+            || recipients.len() > MAX_DIRECT_FANOUT
+        {
+            return vec![];
+        }
+
+        let to: Vec<NodeId> = recipients
+            .into_iter()
+            .filter(|id| *id != self.local_id)
+            .collect();
+        let mut actions = Vec::new();
+        if !to.is_empty() {
+            actions.push(GroupAction::Broadcast {
+                to,
+                payload: GroupPayload::Message(message.clone()),
+            });
+        }
+        actions.extend(self.handle_message(message));
+        actions
+    }
+
+    // ── Delivery / read receipts ─────────────────────────────────────────
+
+    /// Build the DeliveryAck for a message we received (`read` once displayed).
+    ///
+    /// None for our own messages or groups we're not in.
+    pub fn delivery_ack(
+        &self,
+        group_id: &GroupId,
+        sender_id: &NodeId,
+        message_id: &str,
+        read: bool,
+    ) -> Option<GroupAction> {
+        if *sender_id == self.local_id {
+            return None;
+        }
+        let group = self.groups.get(group_id)?;
+        Some(GroupAction::Send {
+            to: group.hub_relay_id,
+            payload: GroupPayload::DeliveryAck {
+                group_id: group_id.clone(),
+                message_id: message_id.to_string(),
+                read,
+            },
+        })
+    }
+
+    /// Handle a delivery receipt for one of our messages (from the hub).
+    pub fn handle_delivery_receipt(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        message_id: String,
+        member: NodeId,
+        read: bool,
+        all_delivered: bool,
+    ) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get(group_id) else {
+            return vec![];
+        };
+        if from != group.hub_relay_id {
+            return vec![];
+        }
+
+        let receipts = self.delivery_receipts.entry(group_id.clone()).or_default();
+        let status = match receipts.by_message.get_mut(&message_id) {
+            Some(status) => status,
+            None => {
+                receipts.order.push_back(message_id.clone());
+                while receipts.order.len() > MAX_TRACKED_DELIVERIES {
+                    if let Some(old) = receipts.order.pop_front() {
+                        receipts.by_message.remove(&old);
+                    }
+                }
+                receipts.by_message.entry(message_id.clone()).or_default()
+            }
+        };
+
+        if !status.delivered.contains(&member) {
+            status.delivered.push(member);
+        }
+        if read && !status.read.contains(&member) {
+            status.read.push(member);
+        }
+        let newly_complete = all_delivered && !status.all_delivered;
+        status.all_delivered |= all_delivered;
+
+        let mut actions = vec![GroupAction::Event(GroupEvent::DeliveryUpdated {
+            group_id: group_id.clone(),
+            message_id: message_id.clone(),
+            member,
+            read,
+        })];
+        if newly_complete {
+            actions.push(GroupAction::Event(GroupEvent::AllDelivered {
+                group_id: group_id.clone(),
+                message_id,
+            }));
+        }
+        actions
+    }
+
+    /// Who has received / read one of our messages (None if no receipt yet).
+    pub fn delivery_status(
+        &self,
+        group_id: &GroupId,
+        message_id: &str,
+    ) -> Option<&GroupDeliveryStatus> {
+        self.delivery_receipts.get(group_id)?.by_message.get(message_id)
+    }
+
+    // ── Reactions ────────────────────────────────────────────────────────
+
+    /// Build a reaction request for the hub (None if we're not in the group).
+    pub fn reaction_request(
+        &self,
+        group_id: &GroupId,
+        message_id: &str,
+        emoji: &str,
+        add: bool,
+    ) -> Option<GroupAction> {
+        let group = self.groups.get(group_id)?;
+        Some(GroupAction::Send {
+            to: group.hub_relay_id,
+            payload: GroupPayload::Reaction {
+                group_id: group_id.clone(),
+                message_id: message_id.to_string(),
+                emoji: emoji.to_string(),
+                add,
+                reactor: self.local_id,
+            },
+        })
+    }
+
+    /// Apply a reaction fanned out by the hub.
+    pub fn handle_reaction(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        message_id: String,
+        emoji: String,
+        add: bool,
+        reactor: NodeId,
+    ) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get(group_id) else {
+            return vec![];
+        };
+        if from != group.hub_relay_id {
+            return vec![];
+        }
+
+        let reactions = self.reactions.entry(group_id.clone()).or_default();
+        if !reactions.by_message.contains_key(&message_id) {
+            reactions.order.push_back(message_id.clone());
+            while reactions.order.len() > MAX_TRACKED_REACTIONS {
+                if let Some(old) = reactions.order.pop_front() {
+                    reactions.by_message.remove(&old);
+                }
+            }
+        }
+        let by_emoji = reactions.by_message.entry(message_id.clone()).or_default();
+        let reactors = by_emoji.entry(emoji.clone()).or_default();
+        let changed = if add {
+            let new = !reactors.contains(&reactor);
+            if new {
+                reactors.push(reactor);
+            }
+            new
+        } else {
+            let before = reactors.len();
+            reactors.retain(|r| *r != reactor);
+            reactors.len() != before
+        };
+        let count = reactors.len();
+        by_emoji.retain(|_, reactors| !reactors.is_empty());
+        if !changed {
+            return vec![];
+        }
+
+        vec![GroupAction::Event(GroupEvent::ReactionChanged {
+            group_id: group_id.clone(),
+            message_id,
+            emoji,
+            reactor,
+            added: add,
+            count,
+        })]
+    }
+
+    /// Reaction counts for a message, most popular first (ties by emoji).
+    pub fn reaction_counts(&self, group_id: &GroupId, message_id: &str) -> Vec<(String, usize)> {
+        let Some(by_emoji) = self
+            .reactions
+            .get(group_id)
+            .and_then(|r| r.by_message.get(message_id))
+        else {
+            return Vec::new();
+        };
+        let mut counts: Vec<(String, usize)> = by_emoji
+            .iter()
+            .map(|(emoji, reactors)| (emoji.clone(), reactors.len()))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Who reacted to a message with `emoji`, in reaction order.
+    pub fn reactors(&self, group_id: &GroupId, message_id: &str, emoji: &str) -> &[NodeId] {
+        self.reactions
+            .get(group_id)
+            .and_then(|r| r.by_message.get(message_id))
+            .and_then(|by_emoji| by_emoji.get(emoji))
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Whether we reacted to a message with `emoji` (to render a toggle).
+    pub fn has_reacted(&self, group_id: &GroupId, message_id: &str, emoji: &str) -> bool {
+        self.reactors(group_id, message_id, emoji).contains(&self.local_id)
+    }
+
+    // ── Polls ────────────────────────────────────────────────────────────
+
+    /// Build a request asking the hub to open a poll.
+    ///
+    /// Returns the new poll id with the action (None if we're not in the group).
+    pub fn poll_request(
+        &self,
+        group_id: &GroupId,
+        question: String,
+        options: Vec<String>,
+    ) -> Option<(String, GroupAction)> {
+        let group = self.groups.get(group_id)?;
+        let poll = GroupPoll::new(question, options, self.local_id);
+        Some((
+            poll.poll_id.clone(),
+            GroupAction::Send {
+                to: group.hub_relay_id,
+                payload: GroupPayload::Poll {
+                    group_id: group_id.clone(),
+                    poll,
+                },
+            },
+        ))
+    }
+
+    /// Build a vote request for the hub (None if we're not in the group).
+    pub fn vote_request(&self, group_id: &GroupId, poll_id: &str, option: u32) -> Option<GroupAction> {
+        let group = self.groups.get(group_id)?;
+        Some(GroupAction::Send {
+            to: group.hub_relay_id,
+            payload: GroupPayload::Vote {
+                group_id: group_id.clone(),
+                poll_id: poll_id.to_string(),
+                option,
+            },
+        })
+    }
+
+    /// Apply a poll tally published by the hub.
+    pub fn handle_poll(&mut self, from: NodeId, group_id: &GroupId, poll: GroupPoll) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+        if from != group.hub_relay_id {
+            return vec![];
+        }
+
+        match group.polls.iter_mut().find(|p| p.poll_id == poll.poll_id) {
+            Some(existing) => *existing = poll.clone(),
+            None => {
+                group.polls.push(poll.clone());
+                if group.polls.len() > MAX_POLLS_PER_GROUP {
+                    group.polls.remove(0);
+                }
+            }
+        }
+
+        vec![GroupAction::Event(GroupEvent::PollUpdated {
+            group_id: group_id.clone(),
+            poll,
+        })]
+    }
+
+    /// Polls known for a group, oldest first.
+    pub fn polls(&self, group_id: &GroupId) -> &[GroupPoll] {
+        self.groups.get(group_id).map(|g| g.polls.as_slice()).unwrap_or(&[])
+    }
+
+    // ── Notification preferences ─────────────────────────────────────────
+
+    /// Set local notification preferences for a group.
+    ///
+    /// Returns false if we're not a member. Default preferences are dropped.
+    pub fn set_notification_prefs(
+        &mut self,
+        group_id: &GroupId,
+        prefs: GroupNotificationPrefs,
+    ) -> bool {
+        if !self.groups.contains_key(group_id) {
+            return false;
+        }
+        if prefs == GroupNotificationPrefs::default() {
+            self.notification_prefs.remove(group_id);
+        } else {
+            self.notification_prefs.insert(group_id.clone(), prefs);
+        }
+        true
+    }
+
+    /// Local notification preferences for a group (defaults if unset).
+    pub fn notification_prefs(&self, group_id: &GroupId) -> GroupNotificationPrefs {
+        self.notification_prefs
+            .get(group_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Whether a received message should stay silent under our preferences.
+    pub fn is_muted(&self, msg: &GroupMessage, now: u64) -> bool {
+        self.notification_prefs
+            .get(&msg.group_id)
+            .is_some_and(|prefs| prefs.is_muted(msg, &self.local_id, now))
+    }
+
+    // ── Hub Migration ────────────────────────────────────────────────────
+
+    /// Handle hub migration notification.
+    ///
+    /// Migrations follow a failover, so the dead hub leaves the roster (as it
+    /// does on the promoted shadow's side).
+    pub fn handle_hub_migration(
+        &mut self,
+        group_id: &GroupId,
+        new_hub_id: NodeId,
+    ) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+
+        let old_hub_id = group.hub_relay_id;
+        if old_hub_id != new_hub_id {
+            group.members.retain(|m| m.node_id != old_hub_id);
+        }
+        group.hub_relay_id = new_hub_id;
+        group.shadow_id = None;
+        group.last_activity_at = now_ms();
+        self.hub_last_seen.remove(group_id);
+        self.hub_reported.remove(group_id);
+
+        vec![GroupAction::Event(GroupEvent::HubMigrated {
+            group_id: group_id.clone(),
+            new_hub_id,
+        })]
+    }
+
+    /// Ask our hub for everything after our last seen seq (gap-fill).
+    ///
+    /// Used after a hub migration so nothing sent during the outage is lost.
+    pub fn sync_request(&self, group_id: &GroupId) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get(group_id) else {
+            return vec![];
+        };
+        vec![GroupAction::Send {
+            to: group.hub_relay_id,
+            payload: GroupPayload::SyncRequest {
+                group_id: group_id.clone(),
+                since_seq: self.last_seq(group_id),
+            },
+        }]
+    }
+
+    // ── Sender Key Management ─────────────────────────────────────────
+
+    /// Get our current sender key for a group.
+    pub fn local_sender_key(&self, group_id: &GroupId) -> Option<&SenderKeyEntry> {
+        self.local_sender_keys.get(group_id)
+    }
+
+    /// Get a member's sender key for a group.
+    pub fn get_sender_key(&self, group_id: &GroupId, sender_id: &NodeId) -> Option<&SenderKeyEntry> {
+        self.sender_keys.get(group_id)?.get(sender_id)
+    }
+
+    /// Generate a new sender key for ourselves in this group.
+    fn generate_local_sender_key(&mut self, group_id: &GroupId) -> SenderKeyEntry {
+        let old_epoch = self
+            .local_sender_keys
+            .get(group_id)
+            .map(|e| e.epoch)
+            .unwrap_or(0);
+        let entry = SenderKeyEntry {
+            owner_id: self.local_id,
+            key: crate::crypto::generate_sender_key(),
+            epoch: old_epoch + 1,
+            created_at: now_ms(),
+        };
+        self.local_sender_keys
+            .insert(group_id.clone(), entry.clone());
+        self.local_sender_message_counts.insert(group_id.clone(), 0);
+        entry
+    }
+
+    /// Current local sender key epoch for a group.
+    pub fn local_sender_epoch(&self, group_id: &GroupId) -> Option<u32> {
+        self.local_sender_keys.get(group_id).map(|k| k.epoch)
+    }
+
+    /// Increment local sender message count for dual-trigger key rotation.
+    pub fn note_local_message_sent(&mut self, group_id: &GroupId) {
+        let count = self
+            .local_sender_message_counts
+            .entry(group_id.clone())
+            .or_insert(0);
+        *count = count.saturating_add(1);
+    }
+
+    /// Rotate local sender key when one trigger is reached:
+    /// - 24h elapsed since current key creation, OR
+    /// - 10_000 local messages sent in current epoch.
+    ///
+    /// Returns distribution actions when rotation happened.
+    pub fn maybe_rotate_local_sender_key(&mut self, group_id: &GroupId) -> Vec<GroupAction> {
+        let Some(current) = self.local_sender_keys.get(group_id).cloned() else {
+            return vec![];
+        };
+        let now = now_ms();
+        let age_ms = now.saturating_sub(current.created_at);
+        let msg_count = self
+            .local_sender_message_counts
+            .get(group_id)
+            .copied()
+            .unwrap_or(0);
+
+        if age_ms >= SENDER_KEY_ROTATE_MAX_AGE_MS || msg_count >= SENDER_KEY_ROTATE_MAX_MESSAGES {
+            self.generate_local_sender_key(group_id);
+            return self.build_sender_key_distribution(group_id);
+        }
+        vec![]
+    }
+
+    /// Build SenderKeyDistribution actions to send our key to all members.
+    pub fn build_sender_key_distribution(&self, group_id: &GroupId) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get(group_id) else {
+            return vec![];
+        };
+        let Some(local_key) = self.local_sender_keys.get(group_id) else {
+            return vec![];
+        };
+
+        let mut encrypted_keys = Vec::new();
+        for member in &group.members {
+            if member.node_id == self.local_id {
+                continue;
+            }
+            let recipient_pk = member.node_id.as_bytes();
+            match crate::crypto::encrypt(&local_key.key, &recipient_pk) {
+                Ok(encrypted) => {
+                    encrypted_keys.push(EncryptedSenderKey {
+                        recipient_id: member.node_id,
+                        encrypted_key: encrypted,
+                    });
+                }
+                Err(e) => {
+                    tracing::warn!("sender key encrypt for {} failed: {e}", member.node_id);
+                }
+            }
+        }
+
+        if encrypted_keys.is_empty() {
+            return vec![];
+        }
+
+        vec![GroupAction::Send {
+            to: group.hub_relay_id,
+            payload: GroupPayload::SenderKeyDistribution {
+                group_id: group_id.clone(),
+                from: self.local_id,
+                epoch: local_key.epoch,
+                encrypted_keys,
+            },
+        }]
+    }
+
+    /// Handle an incoming SenderKeyDistribution -- decrypt and store the sender's key.
+    pub fn handle_sender_key_distribution(
+        &mut self,
         group_id: &GroupId,
         from: NodeId,
         epoch: u32,
@@ -662,6 +1456,9 @@ impl GroupManager {
         if !message.encrypted {
             return self.deliver_message(message);
         }
+        if self.is_anonymous_post(&message) {
+            return self.decrypt_anonymous_post(message);
+        }
         let group_id = &message.group_id;
         let sender_id = &message.sender_id;
         if let Some(sender_key) = self
@@ -701,17 +1498,50 @@ impl GroupManager {
         vec![]
     }
 
-    fn build_epoch_resync_actions(&self, group_id: &GroupId) -> Vec<GroupAction> {
-        let Some(group) = self.groups.get(group_id) else {
+    /// Anonymous groups' posts name the hub as sender (see `GroupMessage::anonymize`).
+    fn is_anonymous_post(&self, message: &GroupMessage) -> bool {
+        self.groups.get(&message.group_id).is_some_and(|g| {
+            g.settings.anonymous && message.sender_id == g.hub_relay_id
+        })
+    }
+
+    /// Decrypt an anonymous post by trying every sender key of its epoch
+    /// (the AEAD tag rejects the wrong ones).
+    ///
+    /// The key that opens it is the author's, so members learn who posted:
+    /// anonymity in encrypted groups holds against outsiders only.
+    fn decrypt_anonymous_post(&mut self, mut message: GroupMessage) -> Vec<GroupAction> {
+        let group_id = &message.group_id;
+        let now = now_ms();
+        let current = self
+            .sender_keys
+            .get(group_id)
+            .into_iter()
+            .flat_map(|keys| keys.values())
+            .filter(|k| k.epoch == message.key_epoch)
+            .map(|k| k.key);
+        let previous = self
+            .previous_sender_keys
+            .get(group_id)
+            .into_iter()
+            .flat_map(|keys| keys.values())
+            .filter(|p| p.entry.epoch == message.key_epoch && now <= p.grace_until_ms)
+            .map(|p| p.entry.key);
+        let content = current
+            .chain(previous)
+            .find_map(|key| message.decrypt(&key).ok());
+
+        let Some(content) = content else {
+            tracing::warn!("anonymous group message: no sender key decrypts it");
             return vec![];
         };
-        vec![GroupAction::Send {
-            to: group.hub_relay_id,
-            payload: GroupPayload::SyncRequest {
-                group_id: group_id.clone(),
-                since_seq: self.last_seq(group_id),
-            },
-        }]
+        message.sender_username = content.username;
+        message.text = content.text;
+        self.deliver_message(message)
+    }
+
+    fn build_epoch_resync_actions(&self, group_id: &GroupId) -> Vec<GroupAction> {
+        self.sync_request(group_id)
     }
 
     /// Decrypt and deliver (internal helper).
@@ -739,6 +1569,14 @@ impl GroupManager {
         if !self.groups.contains_key(group_id) {
             return vec![];
         }
+        // Already delivered (e.g. live and again from a backup holder)
+        if self
+            .message_history
+            .get(group_id)
+            .is_some_and(|h| h.iter().any(|m| m.message_id == message.message_id))
+        {
+            return vec![];
+        }
         if let Some(group) = self.groups.get_mut(group_id) {
             group.last_activity_at = now_ms();
         }
@@ -749,13 +1587,36 @@ impl GroupManager {
                 *last = message.seq;
             }
         }
+        let limit = self.history_limit(group_id);
         let history = self.message_history.entry(group_id.clone()).or_default();
         history.push(message.clone());
-        if history.len() > self.max_history_per_group {
-            let excess = history.len() - self.max_history_per_group;
+        if history.len() > limit {
+            let excess = history.len() - limit;
             history.drain(..excess);
         }
-        vec![GroupAction::Event(GroupEvent::MessageReceived(message))]
+        let mentioned = message.sender_id != self.local_id && message.mentions(&self.local_id);
+        let mention = mentioned.then(|| {
+            GroupAction::Event(GroupEvent::Mentioned {
+                group_id: group_id.clone(),
+                message_id: message.message_id.clone(),
+                sender_id: message.sender_id,
+                sender_username: message.sender_username.clone(),
+            })
+        });
+        let shared_card = crate::contact::ContactCard::decode(&message.text)
+            .ok()
+            .filter(|card| message.sender_id != self.local_id && card.validate().is_ok())
+            .map(|card| {
+                GroupAction::Event(GroupEvent::ContactShared {
+                    group_id: group_id.clone(),
+                    sender_id: message.sender_id,
+                    card,
+                })
+            });
+        let mut actions = vec![GroupAction::Event(GroupEvent::MessageReceived(message))];
+        actions.extend(mention);
+        actions.extend(shared_card);
+        actions
     }
 
     /// Rotate our sender key (called when a member leaves).
@@ -823,6 +1684,7 @@ impl GroupManager {
         members: Vec<GroupMember>,
         candidate_id: Option<NodeId>,
         config_version: u64,
+        bans: Vec<GroupBan>,
     ) -> Vec<GroupAction> {
         if !self.groups.contains_key(group_id) {
             return vec![];
@@ -834,14 +1696,52 @@ impl GroupManager {
                 members,
                 candidate_id,
                 config_version,
+                bans,
                 ping_failures: 0,
-                unreachable_reports: 0,
+                ping_sent_at: None,
+                unreachable_reporters: HashSet::new(),
             },
         );
 
         vec![]
     }
 
+    /// Shadow watchdog: count an unanswered HubPing as a failure, then ping again.
+    ///
+    /// A ping is unanswered once `SHADOW_PING_TIMEOUT_MS` passed without a pong.
+    /// Returns the next pings, or the promotion actions if the hub is deemed dead.
+    pub fn shadow_ping_tick(&mut self, now: u64) -> Vec<GroupAction> {
+        let group_ids: Vec<GroupId> = self.shadow_state.keys().cloned().collect();
+        let mut actions = Vec::new();
+        for group_id in group_ids {
+            let Some(state) = self.shadow_state.get_mut(&group_id) else {
+                continue;
+            };
+            if let Some(sent_at) = state.ping_sent_at {
+                if now.saturating_sub(sent_at) < SHADOW_PING_TIMEOUT_MS {
+                    continue;
+                }
+                state.ping_sent_at = None;
+                let promotion = self.record_ping_failure(&group_id);
+                if !promotion.is_empty() {
+                    actions.extend(promotion);
+                    continue;
+                }
+            }
+            let Some(hub_id) = self.groups.get(&group_id).map(|g| g.hub_relay_id) else {
+                continue;
+            };
+            if let Some(state) = self.shadow_state.get_mut(&group_id) {
+                state.ping_sent_at = Some(now);
+            }
+            actions.push(GroupAction::Send {
+                to: hub_id,
+                payload: GroupPayload::HubPing { group_id },
+            });
+        }
+        actions
+    }
+
     /// Record a ping failure (no pong received). Returns promotion actions if threshold hit.
     pub fn record_ping_failure(&mut self, group_id: &GroupId) -> Vec<GroupAction> {
         let Some(state) = self.shadow_state.get_mut(group_id) else {
@@ -858,16 +1758,21 @@ impl GroupManager {
     }
 
     /// Handle HubUnreachable report from a member. Combined with ping failures, may trigger promotion.
+    ///
+    /// Only replicated members count, and each member counts once.
     pub fn handle_hub_unreachable(
         &mut self,
         group_id: &GroupId,
-        _reporter: NodeId,
+        reporter: NodeId,
     ) -> Vec<GroupAction> {
         let Some(state) = self.shadow_state.get_mut(group_id) else {
             return vec![];
         };
+        if !state.members.iter().any(|m| m.node_id == reporter) {
+            return vec![];
+        }
 
-        state.unreachable_reports += 1;
+        state.unreachable_reporters.insert(reporter);
 
         if self.should_promote(group_id) {
             self.promote_to_primary(group_id)
@@ -876,19 +1781,28 @@ impl GroupManager {
         }
     }
 
-    /// Check if we should promote: 2 ping failures, OR 1 ping failure + 1 unreachable report.
+    /// Check if we should promote: 2 ping failures, OR 1 ping failure plus
+    /// HubUnreachable reports from a majority of the other members.
     fn should_promote(&self, group_id: &GroupId) -> bool {
         let Some(state) = self.shadow_state.get(group_id) else {
             return false;
         };
-        let total_signals = state.ping_failures + state.unreachable_reports;
+        let hub_id = self.groups.get(group_id).map(|g| g.hub_relay_id);
+        let others = state
+            .members
+            .iter()
+            .filter(|m| m.node_id != self.local_id && Some(m.node_id) != hub_id)
+            .count();
+        let quorum = others / 2 + 1;
         state.ping_failures >= SHADOW_PING_FAILURE_THRESHOLD
-            || (state.ping_failures >= 1 && total_signals >= 2)
+            || (state.ping_failures >= 1 && state.unreachable_reporters.len() >= quorum)
     }
 
     /// Promote ourselves from shadow to primary hub.
+    ///
+    /// The dead hub is dropped from the roster; it can rejoin once it's back.
     fn promote_to_primary(&mut self, group_id: &GroupId) -> Vec<GroupAction> {
-        let Some(state) = self.shadow_state.remove(group_id) else {
+        let Some(mut state) = self.shadow_state.remove(group_id) else {
             return vec![];
         };
         let Some(group) = self.groups.get_mut(group_id) else {
@@ -896,10 +1810,14 @@ impl GroupManager {
         };
 
         let old_hub_id = group.hub_relay_id;
+        state.members.retain(|m| m.node_id != old_hub_id);
         group.hub_relay_id = self.local_id;
         group.members = state.members;
+        group.bans = state.bans;
         group.shadow_id = None;
         group.candidate_id = None;
+        self.hub_last_seen.remove(group_id);
+        self.hub_reported.remove(group_id);
 
         // Broadcast HubMigration to all members
         let recipients: Vec<NodeId> = group
@@ -909,21 +1827,28 @@ impl GroupManager {
             .filter(|id| *id != self.local_id)
             .collect();
 
-        vec![GroupAction::Broadcast {
-            to: recipients,
-            payload: GroupPayload::HubMigration {
+        vec![
+            GroupAction::Broadcast {
+                to: recipients,
+                payload: GroupPayload::HubMigration {
+                    group_id: group_id.clone(),
+                    new_hub_id: self.local_id,
+                    old_hub_id,
+                },
+            },
+            GroupAction::Event(GroupEvent::HubMigrated {
                 group_id: group_id.clone(),
                 new_hub_id: self.local_id,
-                old_hub_id,
-            },
-        }]
+            }),
+        ]
     }
 
     /// Reset ping failure counter (called when pong is received).
     pub fn reset_ping_failures(&mut self, group_id: &GroupId) {
         if let Some(state) = self.shadow_state.get_mut(group_id) {
             state.ping_failures = 0;
-            state.unreachable_reports = 0;
+            state.ping_sent_at = None;
+            state.unreachable_reporters.clear();
         }
     }
 
@@ -938,6 +1863,70 @@ impl GroupManager {
             .collect()
     }
 
+    // ── Hub liveness (member side) ───────────────────────────────────────
+
+    /// Handle a heartbeat from our hub: note it alive and learn the current shadow.
+    pub fn handle_hub_heartbeat(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        shadow_id: Option<NodeId>,
+        now: u64,
+    ) -> Vec<GroupAction> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+        if from != group.hub_relay_id {
+            return vec![];
+        }
+        group.shadow_id = shadow_id;
+        self.hub_last_seen.insert(group_id.clone(), now);
+        self.hub_reported.remove(group_id);
+        vec![]
+    }
+
+    /// Any authenticated envelope from `from` proves it alive: refresh the
+    /// liveness of every group it hosts for us (hubs skip heartbeats to
+    /// members their traffic already reached).
+    pub fn note_hub_activity(&mut self, from: NodeId, now: u64) {
+        for (group_id, group) in &self.groups {
+            if group.hub_relay_id == from && from != self.local_id {
+                self.hub_last_seen.insert(group_id.clone(), now);
+                self.hub_reported.remove(group_id);
+            }
+        }
+    }
+
+    /// Report hubs that missed `HUB_FAILURE_THRESHOLD` heartbeats to their shadow.
+    ///
+    /// Each outage is reported once; the shadow promotes itself on quorum.
+    /// Groups we host or shadow (the shadow pings instead) are skipped.
+    pub fn check_hub_liveness(&mut self, now: u64) -> Vec<GroupAction> {
+        let deadline = HUB_HEARTBEAT_INTERVAL_MS * HUB_FAILURE_THRESHOLD as u64;
+        let mut actions = Vec::new();
+        for (group_id, group) in &self.groups {
+            if group.hub_relay_id == self.local_id || self.shadow_state.contains_key(group_id) {
+                continue;
+            }
+            // First check after startup/join starts the clock
+            let last_seen = *self.hub_last_seen.entry(group_id.clone()).or_insert(now);
+            if now.saturating_sub(last_seen) < deadline || self.hub_reported.contains(group_id) {
+                continue;
+            }
+            let Some(shadow) = group.shadow_id.filter(|s| *s != self.local_id) else {
+                continue;
+            };
+            self.hub_reported.insert(group_id.clone());
+            actions.push(GroupAction::Send {
+                to: shadow,
+                payload: GroupPayload::HubUnreachable {
+                    group_id: group_id.clone(),
+                },
+            });
+        }
+        actions
+    }
+
     // ── Persistence ──────────────────────────────────────────────────────
 
     /// Extract a serializable snapshot of persistent state.
@@ -950,6 +1939,7 @@ impl GroupManager {
             local_sender_message_counts: self.local_sender_message_counts.clone(),
             message_history: self.message_history.clone(),
             last_seqs: self.last_seqs.clone(),
+            notification_prefs: self.notification_prefs.clone(),
         }
     }
 
@@ -962,6 +1952,7 @@ impl GroupManager {
         self.local_sender_message_counts = snapshot.local_sender_message_counts;
         self.message_history = snapshot.message_history;
         self.last_seqs = snapshot.last_seqs;
+        self.notification_prefs = snapshot.notification_prefs;
         // Initialize message_history entries for any groups missing them
         for gid in self.groups.keys() {
             self.message_history.entry(gid.clone()).or_default();
@@ -1013,6 +2004,13 @@ mod tests {
             shadow_id: None,
             candidate_id: None,
             invite_only: false,
+            description: String::new(),
+            avatar_hash: None,
+            config_version: 0,
+            bans: Vec::new(),
+            message_ttl_ms: None,
+            polls: Vec::new(),
+            settings: GroupSettings::default(),
         }
     }
 
@@ -1111,6 +2109,70 @@ mod tests {
         assert!(!mgr.decline_invite(&gid));
     }
 
+    #[test]
+    fn admin_tracks_pending_join_requests() {
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        let request = GroupJoinRequest {
+            requester: bob,
+            username: "bob".into(),
+            requested_at: now_ms(),
+        };
+        let actions = mgr.handle_join_requested(&gid, request.clone());
+        assert!(matches!(&actions[0], GroupAction::Event(GroupEvent::JoinRequested { .. })));
+        mgr.handle_join_requested(&gid, request);
+        assert_eq!(mgr.pending_join_requests(&gid).len(), 1, "deduped by requester");
+
+        let actions = mgr.handle_join_request_resolved(&gid, bob, true, alice, hub);
+        assert!(matches!(
+            &actions[0],
+            GroupAction::Event(GroupEvent::JoinRequestResolved { approved: true, .. })
+        ));
+        assert!(mgr.pending_join_requests(&gid).is_empty());
+
+        // Non-admins don't queue requests
+        let mut member = GroupManager::new(bob, "bob".into());
+        member.handle_group_created(make_test_group(alice, hub));
+        let req = GroupJoinRequest { requester: node_id(3), username: "carol".into(), requested_at: 0 };
+        assert!(member.handle_join_requested(&gid, req).is_empty());
+    }
+
+    #[test]
+    fn join_denial_only_trusted_from_knocked_hub() {
+        let carol = node_id(3);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(carol, "carol".into());
+        let gid = GroupId::from("grp-knock".to_string());
+
+        let actions = mgr.request_join(&gid, hub);
+        match &actions[0] {
+            GroupAction::Send { to, payload: GroupPayload::JoinRequest { username, .. } } => {
+                assert_eq!(*to, hub);
+                assert_eq!(username, "carol");
+            }
+            other => panic!("expected JoinRequest send, got {other:?}"),
+        }
+
+        // Spoofed denial from another node is ignored
+        assert!(mgr
+            .handle_join_request_resolved(&gid, carol, false, node_id(1), node_id(66))
+            .is_empty());
+
+        let actions = mgr.handle_join_request_resolved(&gid, carol, false, node_id(1), hub);
+        assert!(matches!(&actions[0], GroupAction::Event(GroupEvent::JoinDenied { .. })));
+
+        // Already resolved
+        assert!(mgr
+            .handle_join_request_resolved(&gid, carol, false, node_id(1), hub)
+            .is_empty());
+    }
+
     #[test]
     fn ignore_invite_if_already_member() {
         let mut mgr = make_manager();
@@ -1145,6 +2207,9 @@ mod tests {
             sent_at: 1000,
             sender_signature: Vec::new(),
             seq: 0,
+            reply_to: None,
+            mentions: Vec::new(),
+            deliver_at: None,
         };
 
         let actions = mgr.handle_group_sync(group, vec![msg]);
@@ -1248,9 +2313,81 @@ mod tests {
         mgr.handle_group_created(group);
 
         let msg = GroupMessage::new(gid.clone(), node_id(2), "bob".into(), "Hello!".into());
-        let actions = mgr.handle_message(msg);
+        let actions = mgr.handle_message(msg.clone());
         assert_eq!(actions.len(), 1);
         assert_eq!(mgr.message_history(&gid).len(), 1);
+
+        // Same message again (live + backup): delivered once
+        assert!(mgr.handle_message(msg).is_empty());
+        assert_eq!(mgr.message_history(&gid).len(), 1);
+    }
+
+    #[test]
+    fn shard_relay_forwards_only_for_hub() {
+        let mut mgr = make_manager();
+        let hub = node_id(10);
+        let group = make_test_group(node_id(1), hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        let msg = GroupMessage::new(gid.clone(), node_id(2), "bob".into(), "Hello!".into());
+        let recipients = vec![node_id(3), node_id(4), mgr.local_id];
+
+        // Not from our hub: ignored
+        assert!(mgr
+            .handle_shard_relay(node_id(5), &gid, recipients.clone(), msg.clone())
+            .is_empty());
+
+        let actions = mgr.handle_shard_relay(hub, &gid, recipients, msg);
+        assert!(matches!(&actions[0],
+            GroupAction::Broadcast { to, payload: GroupPayload::Message(_) }
+                if *to == vec![node_id(3), node_id(4)]));
+        assert!(actions.iter().any(|a| matches!(a, GroupAction::Event(GroupEvent::MessageReceived(_)))));
+        assert_eq!(mgr.message_history(&gid).len(), 1);
+    }
+
+    #[test]
+    fn mention_raises_event() {
+        let mut mgr = make_manager();
+        let hub = node_id(10);
+        let group = make_test_group(node_id(1), hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        let msg = GroupMessage::new(gid.clone(), node_id(2), "bob".into(), "@alice".into())
+            .with_mentions(vec![node_id(1)]);
+        let actions = mgr.handle_message(msg);
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(&actions[1],
+            GroupAction::Event(GroupEvent::Mentioned { sender_id, .. }) if *sender_id == node_id(2)));
+
+        // Someone else mentioned: no hint for us
+        let msg = GroupMessage::new(gid.clone(), node_id(2), "bob".into(), "@carol".into())
+            .with_mentions(vec![node_id(3)]);
+        assert_eq!(mgr.handle_message(msg).len(), 1);
+    }
+
+    #[test]
+    fn posted_contact_card_raises_event() {
+        let mut mgr = make_manager();
+        let hub = node_id(10);
+        let group = make_test_group(node_id(1), hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        let card = crate::contact::ContactCard::new(node_id(5), "eve".into());
+        let msg = GroupMessage::new(gid.clone(), node_id(2), "bob".into(), card.encode().unwrap());
+        let actions = mgr.handle_message(msg);
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(&actions[1],
+            GroupAction::Event(GroupEvent::ContactShared { sender_id, card: c, .. })
+                if *sender_id == node_id(2) && *c == card));
+
+        // A forged fingerprint is just text
+        let mut forged = card.clone();
+        forged.node_id = node_id(6);
+        let msg = GroupMessage::new(gid, node_id(2), "bob".into(), forged.encode().unwrap());
+        assert_eq!(mgr.handle_message(msg).len(), 1);
     }
 
     #[test]
@@ -1277,6 +2414,9 @@ mod tests {
                 sent_at: 1000 + i as u64,
                 sender_signature: Vec::new(),
                 seq: i as u64,
+                reply_to: None,
+                mentions: Vec::new(),
+                deliver_at: None,
             };
             mgr.handle_message(msg);
         }
@@ -1481,6 +2621,54 @@ mod tests {
         assert_eq!(bob_mgr.message_history(&gid)[0].text, "Secret!");
     }
 
+    #[test]
+    fn anonymous_post_decrypted_with_matching_sender_key() {
+        let alice_id = node_id(1);
+        let bob_id = node_id(2);
+        let carol_id = node_id(3);
+        let bob_seed = secret_seed(2);
+        let hub = node_id(10);
+
+        let mut bob_mgr = GroupManager::new(bob_id, "bob".into());
+        let mut group = make_test_group(alice_id, hub);
+        group.settings.anonymous = true;
+        let gid = group.group_id.clone();
+        bob_mgr.handle_group_created(group);
+
+        // Bob holds Alice's and Carol's keys
+        for (sender, key) in [(alice_id, [42u8; 32]), (carol_id, [7u8; 32])] {
+            let encrypted_key = crate::crypto::encrypt(&key, &bob_id.as_bytes()).unwrap();
+            bob_mgr.handle_sender_key_distribution(
+                &gid,
+                sender,
+                1,
+                &[EncryptedSenderKey { recipient_id: bob_id, encrypted_key }],
+                &bob_seed,
+            );
+        }
+
+        // Carol posts anonymously; the hub strips her id
+        let mut msg = GroupMessage::new_encrypted(
+            gid.clone(),
+            carol_id,
+            String::new(),
+            "confession".into(),
+            &[7u8; 32],
+            1,
+        );
+        msg.anonymize(hub);
+
+        let actions = bob_mgr.handle_message(msg);
+        match &actions[..] {
+            [GroupAction::Event(GroupEvent::MessageReceived(m)), ..] => {
+                assert_eq!(m.text, "confession");
+                assert_eq!(m.sender_id, hub);
+                assert!(m.sender_username.is_empty());
+            }
+            other => panic!("expected MessageReceived, got {other:?}"),
+        }
+    }
+
     #[test]
     fn key_rotation_on_member_leave() {
         let mut mgr = make_manager();
@@ -1711,6 +2899,7 @@ mod tests {
             ],
             Some(node_id(3)),
             1,
+            vec![],
         );
 
         assert!(mgr.is_shadow_for(&gid));
@@ -1735,6 +2924,12 @@ mod tests {
             }],
             Some(node_id(3)),
             1,
+            vec![GroupBan {
+                node_id: node_id(9),
+                banned_by: node_id(1),
+                banned_at: 1000,
+                expires_at: None,
+            }],
         );
 
         // Simulate 2 ping failures
@@ -1752,6 +2947,9 @@ mod tests {
 
         // Should no longer be shadow (now we're the hub)
         assert!(!mgr.is_shadow_for(&gid));
+
+        // Bans replicated by the old primary carry over
+        assert!(mgr.get_group(&gid).unwrap().is_banned(&node_id(9), now_ms()));
     }
 
     #[test]
@@ -1772,6 +2970,7 @@ mod tests {
             }],
             Some(node_id(3)),
             1,
+            vec![],
         );
 
         // 1 ping failure + 1 HubUnreachable = promotion
@@ -1784,6 +2983,129 @@ mod tests {
         assert!(has_migration, "unreachable + 1 ping failure should promote");
     }
 
+    fn shadow_with_members(members: &[u8]) -> (GroupManager, GroupId) {
+        let mut mgr = GroupManager::new(node_id(2), "shadow".into());
+        let group = make_test_group(node_id(1), node_id(10));
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+        let roster = members
+            .iter()
+            .map(|&seed| GroupMember {
+                node_id: node_id(seed),
+                username: format!("m{seed}"),
+                joined_at: 1000,
+                role: GroupMemberRole::Member,
+            })
+            .collect();
+        mgr.handle_shadow_sync(&gid, roster, None, 1, vec![]);
+        (mgr, gid)
+    }
+
+    #[test]
+    fn shadow_ping_tick_counts_unanswered_pings() {
+        let (mut mgr, gid) = shadow_with_members(&[1, 2, 10]);
+        let t0 = 1_000_000;
+
+        let actions = mgr.shadow_ping_tick(t0);
+        assert!(matches!(
+            &actions[..],
+            [GroupAction::Send { to, payload: GroupPayload::HubPing { .. } }] if *to == node_id(10)
+        ));
+
+        // Still within the pong window: nothing new
+        assert!(mgr.shadow_ping_tick(t0 + SHADOW_PING_TIMEOUT_MS - 1).is_empty());
+
+        // First timeout pings again, second one promotes
+        let actions = mgr.shadow_ping_tick(t0 + SHADOW_PING_TIMEOUT_MS);
+        assert!(matches!(&actions[..], [GroupAction::Send { payload: GroupPayload::HubPing { .. }, .. }]));
+        let actions = mgr.shadow_ping_tick(t0 + 2 * SHADOW_PING_TIMEOUT_MS);
+        let migration = actions.iter().find_map(|a| match a {
+            GroupAction::Broadcast { to, payload: GroupPayload::HubMigration { new_hub_id, old_hub_id, .. } } => {
+                Some((to.clone(), *new_hub_id, *old_hub_id))
+            }
+            _ => None,
+        });
+        let (to, new_hub, old_hub) = migration.expect("should promote");
+        assert_eq!(new_hub, node_id(2));
+        assert_eq!(old_hub, node_id(10));
+        assert_eq!(to, vec![node_id(1)], "dead hub is dropped from the roster");
+        assert_eq!(mgr.get_group(&gid).unwrap().hub_relay_id, node_id(2));
+        assert!(!mgr.get_group(&gid).unwrap().is_member(&node_id(10)));
+    }
+
+    #[test]
+    fn pong_resets_ping_window() {
+        let (mut mgr, gid) = shadow_with_members(&[1, 2, 10]);
+        mgr.shadow_ping_tick(1_000);
+        mgr.shadow_ping_tick(1_000 + SHADOW_PING_TIMEOUT_MS);
+        mgr.reset_ping_failures(&gid);
+
+        let actions = mgr.shadow_ping_tick(1_000 + 2 * SHADOW_PING_TIMEOUT_MS);
+        assert!(matches!(&actions[..], [GroupAction::Send { payload: GroupPayload::HubPing { .. }, .. }]));
+        assert!(mgr.is_shadow_for(&gid));
+    }
+
+    #[test]
+    fn unreachable_quorum_needs_distinct_members() {
+        // Others (excluding shadow and hub): 1, 3, 4 → quorum of 2
+        let (mut mgr, gid) = shadow_with_members(&[1, 2, 3, 4, 10]);
+        mgr.record_ping_failure(&gid);
+
+        assert!(mgr.handle_hub_unreachable(&gid, node_id(1)).is_empty());
+        assert!(
+            mgr.handle_hub_unreachable(&gid, node_id(1)).is_empty(),
+            "a repeated report counts once"
+        );
+        assert!(
+            mgr.handle_hub_unreachable(&gid, node_id(99)).is_empty(),
+            "non-members are ignored"
+        );
+        let actions = mgr.handle_hub_unreachable(&gid, node_id(3));
+        assert!(actions
+            .iter()
+            .any(|a| matches!(a, GroupAction::Broadcast { payload: GroupPayload::HubMigration { .. }, .. })));
+    }
+
+    #[test]
+    fn unreachable_reports_alone_do_not_promote() {
+        let (mut mgr, gid) = shadow_with_members(&[1, 2, 3, 10]);
+        assert!(mgr.handle_hub_unreachable(&gid, node_id(1)).is_empty());
+        assert!(mgr.handle_hub_unreachable(&gid, node_id(3)).is_empty());
+        assert!(mgr.is_shadow_for(&gid));
+    }
+
+    #[test]
+    fn silent_hub_reported_to_shadow_once() {
+        let alice = node_id(1);
+        let hub = node_id(10);
+        let shadow = node_id(2);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        // Heartbeats from anyone but the hub are ignored
+        mgr.handle_hub_heartbeat(node_id(7), &gid, Some(node_id(7)), 0);
+        assert_eq!(mgr.get_group(&gid).unwrap().shadow_id, None);
+        mgr.handle_hub_heartbeat(hub, &gid, Some(shadow), 0);
+        assert_eq!(mgr.get_group(&gid).unwrap().shadow_id, Some(shadow));
+
+        let deadline = HUB_HEARTBEAT_INTERVAL_MS * HUB_FAILURE_THRESHOLD as u64;
+        assert!(mgr.check_hub_liveness(deadline - 1).is_empty());
+
+        let actions = mgr.check_hub_liveness(deadline);
+        assert!(matches!(
+            &actions[..],
+            [GroupAction::Send { to, payload: GroupPayload::HubUnreachable { .. } }] if *to == shadow
+        ));
+        assert!(mgr.check_hub_liveness(deadline + 1).is_empty(), "reported once");
+
+        // A heartbeat re-arms reporting
+        mgr.handle_hub_heartbeat(hub, &gid, Some(shadow), deadline + 2);
+        assert!(mgr.check_hub_liveness(deadline + 3).is_empty());
+        assert_eq!(mgr.check_hub_liveness(2 * deadline + 2).len(), 1);
+    }
+
     // ── R10.1: Rejoin tests ──────────────────────────────────────────
 
     #[test]
@@ -1900,4 +3222,413 @@ mod tests {
         let actions = mgr.handle_member_role_changed(&gid, &bob, GroupMemberRole::Admin);
         assert!(actions.is_empty(), "unknown group should be silently ignored");
     }
+
+    #[test]
+    fn handle_metadata_changed_applies_newer_version() {
+        let alice = node_id(1);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        let actions = mgr.handle_metadata_changed(
+            hub, &gid, "Renamed".into(), "Topic".into(), Some([3u8; 32]), 1, alice,
+        );
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(
+            &actions[0],
+            GroupAction::Event(GroupEvent::MetadataChanged { name, config_version: 1, .. })
+                if name == "Renamed"
+        ));
+        let info = mgr.get_group(&gid).unwrap();
+        assert_eq!(info.name, "Renamed");
+        assert_eq!(info.description, "Topic");
+        assert_eq!(info.avatar_hash, Some([3u8; 32]));
+    }
+
+    #[test]
+    fn handle_metadata_changed_ignores_stale_version() {
+        let alice = node_id(1);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        mgr.handle_metadata_changed(hub, &gid, "v2".into(), String::new(), None, 2, alice);
+        let actions = mgr.handle_metadata_changed(hub, &gid, "v1".into(), String::new(), None, 1, alice);
+        assert!(actions.is_empty(), "older version should be ignored");
+        assert_eq!(mgr.get_group(&gid).unwrap().name, "v2");
+    }
+
+    #[test]
+    fn handle_metadata_changed_only_from_hub_by_admin() {
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        // A member or stranger can't rename the group, whoever it claims
+        assert!(mgr.handle_metadata_changed(bob, &gid, "pwned".into(), String::new(), None, 1, alice).is_empty());
+        // Nor can the hub on behalf of a non-admin
+        assert!(mgr.handle_metadata_changed(hub, &gid, "pwned".into(), String::new(), None, 1, bob).is_empty());
+        assert!(mgr.handle_message_ttl_changed(bob, &gid, Some(5_000), 1, alice).is_empty());
+        assert_eq!(mgr.get_group(&gid).unwrap().config_version, 0);
+    }
+
+    #[test]
+    fn handle_metadata_changed_rejects_huge_version_jump() {
+        let alice = node_id(1);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        // u64::MAX would freeze metadata and the timer for good
+        assert!(mgr
+            .handle_metadata_changed(hub, &gid, "frozen".into(), String::new(), None, u64::MAX, alice)
+            .is_empty());
+        assert!(mgr.handle_message_ttl_changed(hub, &gid, Some(5_000), u64::MAX, alice).is_empty());
+        assert!(mgr
+            .handle_metadata_changed(
+                hub, &gid, "too far".into(), String::new(), None, MAX_CONFIG_VERSION_STEP + 1, alice,
+            )
+            .is_empty());
+
+        // A bounded gap (missed updates) still applies
+        let actions = mgr.handle_metadata_changed(
+            hub, &gid, "caught up".into(), String::new(), None, MAX_CONFIG_VERSION_STEP, alice,
+        );
+        assert_eq!(actions.len(), 1);
+        assert_eq!(mgr.get_group(&gid).unwrap().name, "caught up");
+    }
+
+    #[test]
+    fn notification_prefs_mute_and_mentions_only() {
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, node_id(10));
+        let gid = group.group_id.clone();
+
+        // Not a member yet
+        assert!(!mgr.set_notification_prefs(&gid, GroupNotificationPrefs::default()));
+        mgr.handle_group_created(group);
+
+        let plain = GroupMessage::new(gid.clone(), bob, "bob".into(), "hi".into());
+        let mention = plain.clone().with_mentions(vec![alice]);
+        assert!(!mgr.is_muted(&plain, 1_000));
+
+        let prefs = GroupNotificationPrefs { muted_until: None, mentions_only: true };
+        assert!(mgr.set_notification_prefs(&gid, prefs));
+        assert!(mgr.is_muted(&plain, 1_000));
+        assert!(!mgr.is_muted(&mention, 1_000));
+
+        // Timed mute silences mentions too, until it lapses
+        let prefs = GroupNotificationPrefs { muted_until: Some(2_000), mentions_only: false };
+        mgr.set_notification_prefs(&gid, prefs);
+        assert!(mgr.is_muted(&mention, 1_000));
+        assert!(!mgr.is_muted(&plain, 2_000));
+
+        mgr.leave_group(&gid);
+        assert_eq!(mgr.notification_prefs(&gid), GroupNotificationPrefs::default());
+    }
+
+    #[test]
+    fn handle_poll_accepts_hub_updates_only() {
+        let alice = node_id(1);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        let (poll_id, _) = mgr
+            .poll_request(&gid, "Lunch?".into(), vec!["Pizza".into(), "Sushi".into()])
+            .unwrap();
+        let mut poll = GroupPoll::new("Lunch?".into(), vec!["Pizza".into(), "Sushi".into()], alice);
+        poll.poll_id = poll_id;
+
+        // Forged tally from a non-hub node is ignored
+        assert!(mgr.handle_poll(node_id(2), &gid, poll.clone()).is_empty());
+
+        let actions = mgr.handle_poll(hub, &gid, poll.clone());
+        assert!(matches!(&actions[0], GroupAction::Event(GroupEvent::PollUpdated { .. })));
+
+        poll.tallies = vec![1, 0];
+        poll.voters = vec![node_id(2)];
+        mgr.handle_poll(hub, &gid, poll);
+        assert_eq!(mgr.polls(&gid).len(), 1);
+        assert_eq!(mgr.polls(&gid)[0].tallies, vec![1, 0]);
+    }
+
+    #[test]
+    fn expire_messages_follows_group_ttl() {
+        let alice = node_id(1);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        for i in 0..2u64 {
+            let mut msg = GroupMessage::new(gid.clone(), node_id(2), "bob".into(), format!("m{i}"));
+            msg.message_id = format!("m{i}");
+            msg.sent_at = 1_000 + i * 10_000;
+            mgr.deliver_message(msg);
+        }
+
+        // No timer: nothing expires
+        assert!(mgr.expire_messages(u64::MAX).is_empty());
+
+        let actions = mgr.handle_message_ttl_changed(hub, &gid, Some(5_000), 1, alice);
+        assert!(matches!(
+            &actions[0],
+            GroupAction::Event(GroupEvent::MessageTtlChanged { ttl_ms: Some(5_000), .. })
+        ));
+
+        let actions = mgr.expire_messages(8_000);
+        match &actions[..] {
+            [GroupAction::Event(GroupEvent::MessagesExpired { message_ids, .. })] => {
+                assert_eq!(message_ids, &vec!["m0".to_string()]);
+            }
+            other => panic!("expected MessagesExpired, got {other:?}"),
+        }
+        assert_eq!(mgr.message_history(&gid).len(), 1);
+
+        // Stale timer update is ignored
+        assert!(mgr.handle_message_ttl_changed(hub, &gid, None, 1, alice).is_empty());
+        // A timer beyond the 24h lifespan is refused even from the hub
+        assert!(mgr.handle_message_ttl_changed(hub, &gid, Some(MAX_MESSAGE_TTL_MS + 1), 2, alice).is_empty());
+    }
+
+    #[test]
+    fn settings_change_caps_local_history() {
+        let alice = node_id(1);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        for i in 0..15 {
+            let msg = GroupMessage::new(gid.clone(), node_id(2), "bob".into(), format!("m{i}"));
+            mgr.deliver_message(msg);
+        }
+
+        let settings = GroupSettings {
+            history_limit: Some(10),
+            ..Default::default()
+        };
+        let actions = mgr.handle_settings_changed(hub, &gid, settings, 1, alice);
+        assert!(matches!(&actions[0], GroupAction::Event(GroupEvent::SettingsChanged { .. })));
+        assert_eq!(mgr.get_group(&gid).unwrap().settings, settings);
+        assert_eq!(mgr.message_history(&gid).len(), 10);
+        assert_eq!(mgr.message_history(&gid)[0].text, "m5");
+
+        // New messages stay within the limit
+        mgr.deliver_message(GroupMessage::new(gid.clone(), node_id(2), "bob".into(), "new".into()));
+        assert_eq!(mgr.message_history(&gid).len(), 10);
+
+        // Stale update is ignored
+        assert!(mgr
+            .handle_settings_changed(hub, &gid, GroupSettings::default(), 1, alice)
+            .is_empty());
+    }
+
+    #[test]
+    fn delivery_receipts_track_members_and_completion() {
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let charlie = node_id(3);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        // Receipts only accepted from the group's hub
+        assert!(mgr
+            .handle_delivery_receipt(bob, &gid, "m1".into(), bob, true, true)
+            .is_empty());
+        assert!(mgr.delivery_status(&gid, "m1").is_none());
+
+        let actions = mgr.handle_delivery_receipt(hub, &gid, "m1".into(), bob, false, false);
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(&actions[0],
+            GroupAction::Event(GroupEvent::DeliveryUpdated { member, read: false, .. }) if *member == bob));
+
+        let actions = mgr.handle_delivery_receipt(hub, &gid, "m1".into(), charlie, true, true);
+        assert!(matches!(&actions[1],
+            GroupAction::Event(GroupEvent::AllDelivered { message_id, .. }) if message_id == "m1"));
+
+        let status = mgr.delivery_status(&gid, "m1").unwrap();
+        assert_eq!(status.delivered, vec![bob, charlie]);
+        assert_eq!(status.read, vec![charlie]);
+        assert!(status.all_delivered);
+
+        // A later read receipt doesn't re-announce completion
+        let actions = mgr.handle_delivery_receipt(hub, &gid, "m1".into(), bob, true, true);
+        assert_eq!(actions.len(), 1);
+    }
+
+    #[test]
+    fn reactions_aggregate_per_message() {
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let charlie = node_id(3);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        let mut react = |from: NodeId, emoji: &str, add: bool, reactor: NodeId| {
+            mgr.handle_reaction(from, &gid, "m1".into(), emoji.into(), add, reactor)
+        };
+
+        // Only the hub's fan-out counts
+        assert!(react(bob, "👍", true, bob).is_empty());
+
+        let actions = react(hub, "👍", true, bob);
+        assert!(matches!(&actions[0],
+            GroupAction::Event(GroupEvent::ReactionChanged { count: 1, added: true, .. })));
+        react(hub, "👍", true, alice);
+        react(hub, "❤️", true, charlie);
+        // Duplicate: no event
+        assert!(react(hub, "❤️", true, charlie).is_empty());
+
+        assert_eq!(
+            mgr.reaction_counts(&gid, "m1"),
+            vec![("👍".to_string(), 2), ("❤️".to_string(), 1)]
+        );
+        assert_eq!(mgr.reactors(&gid, "m1", "👍"), &[bob, alice]);
+        assert!(mgr.has_reacted(&gid, "m1", "👍"));
+
+        let actions = mgr.handle_reaction(hub, &gid, "m1".into(), "❤️".into(), false, charlie);
+        assert!(matches!(&actions[0],
+            GroupAction::Event(GroupEvent::ReactionChanged { count: 0, added: false, .. })));
+        assert_eq!(mgr.reaction_counts(&gid, "m1"), vec![("👍".to_string(), 2)]);
+        assert!(mgr.reaction_counts(&gid, "other").is_empty());
+    }
+
+    #[test]
+    fn delivery_ack_skips_own_messages() {
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        assert!(mgr.delivery_ack(&gid, &alice, "m1", false).is_none());
+        assert!(matches!(
+            mgr.delivery_ack(&gid, &bob, "m1", true),
+            Some(GroupAction::Send { to, payload: GroupPayload::DeliveryAck { read: true, .. } }) if to == hub
+        ));
+    }
+
+    #[test]
+    fn create_invite_token_admin_only() {
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let hub = node_id(10);
+        let seed = secret_seed(1);
+
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group.clone());
+
+        let (token, actions) = mgr.create_invite_token(&gid, &seed, 60_000).unwrap();
+        assert!(token.validate(now_ms()).is_ok());
+        assert_eq!(token.hub_id, hub);
+        assert!(matches!(
+            &actions[0],
+            GroupAction::Send { to, payload: GroupPayload::RegisterInviteToken { secret_hash, .. } }
+                if *to == hub && *secret_hash == token.secret_hash()
+        ));
+
+        // Bob is not an admin of the group
+        let mut bob_mgr = GroupManager::new(bob, "bob".into());
+        bob_mgr.handle_group_sync(group, vec![]);
+        assert!(bob_mgr.create_invite_token(&gid, &secret_seed(2), 60_000).is_none());
+    }
+
+    #[test]
+    fn join_by_token_sends_secret_to_hub() {
+        let alice = node_id(1);
+        let carol = node_id(3);
+        let hub = node_id(10);
+
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+        let (token, _) = mgr.create_invite_token(&gid, &secret_seed(1), 60_000).unwrap();
+
+        let carol_mgr = GroupManager::new(carol, "carol".into());
+        let actions = carol_mgr.join_by_token(&token);
+        assert!(matches!(
+            &actions[0],
+            GroupAction::Send { to, payload: GroupPayload::JoinWithToken { username, join_secret, .. } }
+                if *to == hub && username == "carol" && *join_secret == token.join_secret
+        ));
+
+        // Already a member: nothing to do
+        assert!(mgr.join_by_token(&token).is_empty());
+    }
+
+    #[test]
+    fn request_history_targets_hub_and_caps_limit() {
+        let alice = node_id(1);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        let actions = mgr.request_history(&gid, 42, 10_000);
+        assert!(matches!(
+            &actions[0],
+            GroupAction::Send { to, payload: GroupPayload::FetchHistory { before: 42, limit, .. } }
+                if *to == hub && *limit == MAX_HISTORY_PAGE as u32
+        ));
+        assert!(mgr.request_history(&GroupId::from("nope".to_string()), 0, 10).is_empty());
+    }
+
+    #[test]
+    fn history_page_skips_undecryptable_and_keeps_local_history() {
+        let alice = node_id(1);
+        let bob = node_id(2);
+        let hub = node_id(10);
+        let mut mgr = GroupManager::new(alice, "alice".into());
+        let group = make_test_group(alice, hub);
+        let gid = group.group_id.clone();
+        mgr.handle_group_created(group);
+
+        let mut plain = GroupMessage::new(gid.clone(), bob, "bob".into(), "old news".into());
+        plain.seq = 3;
+        // Encrypted with a key we never received
+        let mut sealed =
+            GroupMessage::new_encrypted(gid.clone(), bob, "bob".into(), "secret".into(), &[9u8; 32], 1);
+        sealed.seq = 4;
+
+        let actions = mgr.handle_history_page(&gid, vec![plain, sealed], true);
+        match &actions[0] {
+            GroupAction::Event(GroupEvent::HistoryPage { messages, has_more, .. }) => {
+                assert!(*has_more);
+                assert_eq!(messages.len(), 1);
+                assert_eq!(messages[0].text, "old news");
+            }
+            other => panic!("expected HistoryPage event, got {other:?}"),
+        }
+        assert!(mgr.message_history(&gid).is_empty(), "history pages are not new messages");
+    }
 }
@@ -0,0 +1,936 @@
+/// Mesh groups — hubless mode for tiny private groups.
+///
+/// Up to [`MAX_MESH_MEMBERS`] members fan out messages directly to each
+/// other: no hub, so no single point of failure. Messages are the usual
+/// signed [`GroupMessage`]s, always encrypted with the sender's Sender Key.
+/// Membership is an [`LwwMap`] document (`name` + one `m/<node_id>` key per
+/// member, value = username) pushed in full on every change, so concurrent
+/// adds and leaves converge like shared state does.
+///
+/// Trust model: any current member may add members or relay the document;
+/// only the member itself (or the creator) may remove it.
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::group::types::*;
+use crate::shared_state::{LwwEntry, LwwMap, LwwStamp};
+use crate::types::{now_ms, NodeId};
+use crate::TomProtocolError;
+
+/// Max members in a mesh group (each message costs one envelope per member).
+pub const MAX_MESH_MEMBERS: usize = 8;
+
+/// Messages kept per mesh group.
+const MAX_MESH_HISTORY: usize = 200;
+
+/// Messages buffered per group while waiting for their sender's key.
+const MAX_MESH_PENDING: usize = 64;
+
+/// Message ids remembered per group for dedup.
+const MAX_MESH_SEEN: usize = 1024;
+
+/// Document key holding the group name.
+const NAME_KEY: &str = "name";
+
+/// Document key prefix for members.
+const MEMBER_PREFIX: &str = "m/";
+
+fn member_key(node_id: &NodeId) -> String {
+    format!("{MEMBER_PREFIX}{node_id}")
+}
+
+/// Wire payloads between mesh members (always sent directly).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MeshPayload {
+    /// Full membership document. Receiving one that lists us is an invitation.
+    Membership {
+        group_id: GroupId,
+        created_by: NodeId,
+        entries: Vec<(String, LwwEntry)>,
+    },
+    /// The sender's current Sender Key, encrypted for the recipient.
+    SenderKey {
+        group_id: GroupId,
+        epoch: u32,
+        encrypted_key: EncryptedSenderKey,
+    },
+    /// A group message fanned out by its sender.
+    Message(GroupMessage),
+}
+
+/// Actions returned by MeshGroupManager.
+#[derive(Debug, Clone)]
+pub enum MeshAction {
+    /// Send a payload to one member.
+    Send { to: NodeId, payload: MeshPayload },
+    /// Notify the application (same events as hub groups).
+    Event(GroupEvent),
+}
+
+/// One mesh group as seen by this node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshGroup {
+    pub group_id: GroupId,
+    pub created_by: NodeId,
+    /// Membership document.
+    pub doc: LwwMap,
+    local_key: SenderKeyEntry,
+    sender_keys: HashMap<NodeId, SenderKeyEntry>,
+    history: Vec<GroupMessage>,
+    #[serde(skip)]
+    seen: VecDeque<String>,
+    #[serde(skip)]
+    pending: Vec<GroupMessage>,
+}
+
+impl MeshGroup {
+    fn new(group_id: GroupId, created_by: NodeId, local_id: NodeId) -> Self {
+        Self {
+            group_id,
+            created_by,
+            doc: LwwMap::new(),
+            local_key: SenderKeyEntry {
+                owner_id: local_id,
+                key: crate::crypto::generate_sender_key(),
+                epoch: 1,
+                created_at: now_ms(),
+            },
+            sender_keys: HashMap::new(),
+            history: Vec::new(),
+            seen: VecDeque::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Group name.
+    pub fn name(&self) -> &str {
+        self.doc
+            .get(NAME_KEY)
+            .and_then(|v| std::str::from_utf8(v).ok())
+            .unwrap_or_default()
+    }
+
+    /// Current members, oldest first (username empty until they accept).
+    pub fn members(&self) -> Vec<GroupMember> {
+        let mut members: Vec<GroupMember> = self
+            .doc
+            .entries()
+            .filter_map(|(key, entry)| {
+                let node_id = key.strip_prefix(MEMBER_PREFIX)?.parse().ok()?;
+                let username = entry.value.as_deref()?;
+                Some(GroupMember {
+                    node_id,
+                    username: String::from_utf8_lossy(username).into_owned(),
+                    joined_at: entry.stamp.millis,
+                    role: if node_id == self.created_by {
+                        GroupMemberRole::Admin
+                    } else {
+                        GroupMemberRole::Member
+                    },
+                })
+            })
+            .collect();
+        members.sort_by_key(|m| m.joined_at);
+        members
+    }
+
+    /// Whether a node is a current member.
+    pub fn is_member(&self, node_id: &NodeId) -> bool {
+        self.doc.get(&member_key(node_id)).is_some()
+    }
+
+    fn member_ids(&self) -> HashSet<NodeId> {
+        self.members().into_iter().map(|m| m.node_id).collect()
+    }
+
+    /// Messages received or sent in this group, oldest first.
+    pub fn history(&self) -> &[GroupMessage] {
+        &self.history
+    }
+
+    fn full_state(&self) -> Vec<(String, LwwEntry)> {
+        self.doc
+            .entries()
+            .map(|(k, e)| (k.clone(), e.clone()))
+            .collect()
+    }
+
+    /// Write one document key with a monotonic stamp.
+    fn write(&mut self, key: String, value: Option<Vec<u8>>, writer: NodeId) {
+        let millis = now_ms().max(self.doc.max_millis() + 1);
+        self.doc.apply(
+            key,
+            LwwEntry {
+                value,
+                stamp: LwwStamp { millis, writer },
+            },
+        );
+    }
+
+    /// Whether `entry` may be merged into our document.
+    fn accepts(&self, key: &str, entry: &LwwEntry, bootstrap: bool) -> bool {
+        let writer = entry.stamp.writer;
+        if key == NAME_KEY {
+            let valid = entry
+                .value
+                .as_ref()
+                .is_some_and(|v| !v.is_empty() && v.len() <= MAX_GROUP_NAME_LEN);
+            return valid && (bootstrap || self.is_member(&writer));
+        }
+        let Some(node_id) = key
+            .strip_prefix(MEMBER_PREFIX)
+            .and_then(|id| id.parse::<NodeId>().ok())
+        else {
+            return false;
+        };
+        if !bootstrap && !self.is_member(&writer) && writer != node_id {
+            return false;
+        }
+        match entry.value {
+            Some(ref username) => {
+                username.len() <= MAX_GROUP_NAME_LEN
+                    && (self.is_member(&node_id) || self.member_ids().len() < MAX_MESH_MEMBERS)
+            }
+            None => writer == node_id || writer == self.created_by,
+        }
+    }
+
+    fn remember(&mut self, message_id: &str) -> bool {
+        if self.seen.iter().any(|id| id == message_id)
+            || self.history.iter().any(|m| m.message_id == message_id)
+        {
+            return false;
+        }
+        self.seen.push_back(message_id.to_string());
+        while self.seen.len() > MAX_MESH_SEEN {
+            self.seen.pop_front();
+        }
+        true
+    }
+
+    fn push_history(&mut self, message: GroupMessage) {
+        self.history.push(message);
+        if self.history.len() > MAX_MESH_HISTORY {
+            let excess = self.history.len() - MAX_MESH_HISTORY;
+            self.history.drain(..excess);
+        }
+    }
+}
+
+/// Serializable state for persistence.
+#[derive(Debug, Clone, Default)]
+pub struct MeshGroupSnapshot {
+    pub groups: HashMap<GroupId, MeshGroup>,
+}
+
+/// Mesh groups this node belongs to.
+pub struct MeshGroupManager {
+    local_id: NodeId,
+    local_username: String,
+    groups: HashMap<GroupId, MeshGroup>,
+}
+
+impl MeshGroupManager {
+    pub fn new(local_id: NodeId, local_username: String) -> Self {
+        Self {
+            local_id,
+            local_username,
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Get a mesh group.
+    pub fn get(&self, group_id: &GroupId) -> Option<&MeshGroup> {
+        self.groups.get(group_id)
+    }
+
+    /// Whether `group_id` is one of our mesh groups.
+    pub fn is_mesh(&self, group_id: &GroupId) -> bool {
+        self.groups.contains_key(group_id)
+    }
+
+    /// All mesh groups.
+    pub fn groups(&self) -> impl Iterator<Item = &MeshGroup> {
+        self.groups.values()
+    }
+
+    /// Create a mesh group with `members` (we are added as creator).
+    pub fn create(
+        &mut self,
+        name: &str,
+        members: Vec<NodeId>,
+    ) -> Result<(GroupId, Vec<MeshAction>), TomProtocolError> {
+        let name = name.trim();
+        if name.is_empty() || name.len() > MAX_GROUP_NAME_LEN {
+            return Err(TomProtocolError::InvalidMeshGroup {
+                reason: "group name length out of range".into(),
+            });
+        }
+        let mut others: Vec<NodeId> = Vec::new();
+        for member in members {
+            if member != self.local_id && !others.contains(&member) {
+                others.push(member);
+            }
+        }
+        if others.len() + 1 > MAX_MESH_MEMBERS {
+            return Err(TomProtocolError::InvalidMeshGroup {
+                reason: format!("at most {MAX_MESH_MEMBERS} members"),
+            });
+        }
+
+        let group_id = GroupId::new();
+        let mut group = MeshGroup::new(group_id.clone(), self.local_id, self.local_id);
+        group.write(NAME_KEY.into(), Some(name.as_bytes().to_vec()), self.local_id);
+        group.write(
+            member_key(&self.local_id),
+            Some(self.local_username.as_bytes().to_vec()),
+            self.local_id,
+        );
+        for member in &others {
+            group.write(member_key(member), Some(Vec::new()), self.local_id);
+        }
+
+        let mut actions = Vec::new();
+        for member in &others {
+            actions.push(membership_to(&group, *member));
+            actions.extend(sender_key_to(&group, *member));
+        }
+        actions.push(MeshAction::Event(GroupEvent::Joined {
+            group_id: group_id.clone(),
+            group_name: name.to_string(),
+        }));
+        self.groups.insert(group_id.clone(), group);
+        Ok((group_id, actions))
+    }
+
+    /// Add a member (any member may). Pushes the document to everyone.
+    pub fn add_member(
+        &mut self,
+        group_id: &GroupId,
+        node_id: NodeId,
+    ) -> Result<Vec<MeshAction>, TomProtocolError> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return Err(TomProtocolError::InvalidMeshGroup {
+                reason: format!("unknown mesh group {group_id}"),
+            });
+        };
+        if group.is_member(&node_id) {
+            return Ok(vec![]);
+        }
+        if group.member_ids().len() >= MAX_MESH_MEMBERS {
+            return Err(TomProtocolError::InvalidMeshGroup {
+                reason: format!("at most {MAX_MESH_MEMBERS} members"),
+            });
+        }
+        group.write(member_key(&node_id), Some(Vec::new()), self.local_id);
+
+        let mut actions = broadcast_membership(group, self.local_id);
+        actions.extend(sender_key_to(group, node_id));
+        actions.push(MeshAction::Event(GroupEvent::MemberJoined {
+            group_id: group_id.clone(),
+            member: GroupMember {
+                node_id,
+                username: String::new(),
+                joined_at: now_ms(),
+                role: GroupMemberRole::Member,
+            },
+        }));
+        Ok(actions)
+    }
+
+    /// Remove a member (creator only). Everyone drops it on merge.
+    pub fn remove_member(
+        &mut self,
+        group_id: &GroupId,
+        node_id: NodeId,
+    ) -> Result<Vec<MeshAction>, TomProtocolError> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return Err(TomProtocolError::InvalidMeshGroup {
+                reason: format!("unknown mesh group {group_id}"),
+            });
+        };
+        if group.created_by != self.local_id || node_id == self.local_id {
+            return Err(TomProtocolError::InvalidMeshGroup {
+                reason: "only the creator can remove other members".into(),
+            });
+        }
+        if !group.is_member(&node_id) {
+            return Ok(vec![]);
+        }
+        // Tell the removed member too, before it disappears from the list
+        let mut actions = vec![];
+        group.write(member_key(&node_id), None, self.local_id);
+        actions.push(membership_to(group, node_id));
+        actions.extend(broadcast_membership(group, self.local_id));
+        Ok(actions)
+    }
+
+    /// Leave a mesh group: tombstone ourselves, tell the others, forget it.
+    pub fn leave(&mut self, group_id: &GroupId) -> Vec<MeshAction> {
+        let Some(mut group) = self.groups.remove(group_id) else {
+            return vec![];
+        };
+        group.write(member_key(&self.local_id), None, self.local_id);
+        broadcast_membership(&group, self.local_id)
+    }
+
+    /// Encrypt, sign and fan out a message to every other member.
+    pub fn send_message(
+        &mut self,
+        group_id: &GroupId,
+        text: String,
+        secret_seed: &[u8; 32],
+    ) -> Result<Vec<MeshAction>, TomProtocolError> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return Err(TomProtocolError::InvalidMeshGroup {
+                reason: format!("unknown mesh group {group_id}"),
+            });
+        };
+        let mut msg = GroupMessage::new_encrypted(
+            group_id.clone(),
+            self.local_id,
+            self.local_username.clone(),
+            text.clone(),
+            &group.local_key.key,
+            group.local_key.epoch,
+        );
+        msg.sign(secret_seed);
+        group.remember(&msg.message_id);
+
+        let actions = group
+            .member_ids()
+            .into_iter()
+            .filter(|id| *id != self.local_id)
+            .map(|to| MeshAction::Send {
+                to,
+                payload: MeshPayload::Message(msg.clone()),
+            })
+            .collect();
+
+        // Keep the readable version locally
+        msg.sender_username = self.local_username.clone();
+        msg.text = text;
+        group.push_history(msg);
+        Ok(actions)
+    }
+
+    /// Handle a payload from another node.
+    pub fn handle_payload(
+        &mut self,
+        from: NodeId,
+        payload: MeshPayload,
+        secret_seed: &[u8; 32],
+    ) -> Vec<MeshAction> {
+        match payload {
+            MeshPayload::Membership {
+                group_id,
+                created_by,
+                entries,
+            } => self.handle_membership(from, group_id, created_by, entries),
+            MeshPayload::SenderKey {
+                group_id,
+                epoch,
+                encrypted_key,
+            } => self.handle_sender_key(from, &group_id, epoch, &encrypted_key, secret_seed),
+            MeshPayload::Message(msg) => self.handle_message(from, msg),
+        }
+    }
+
+    fn handle_membership(
+        &mut self,
+        from: NodeId,
+        group_id: GroupId,
+        created_by: NodeId,
+        entries: Vec<(String, LwwEntry)>,
+    ) -> Vec<MeshAction> {
+        let local_id = self.local_id;
+        let joining = !self.groups.contains_key(&group_id);
+        if joining {
+            // Invitation: only from a listed member, and only if we're listed
+            let listed = |id: &NodeId| {
+                entries
+                    .iter()
+                    .any(|(k, e)| *k == member_key(id) && e.value.is_some())
+            };
+            if !listed(&from) || !listed(&local_id) {
+                return vec![];
+            }
+            self.groups.insert(
+                group_id.clone(),
+                MeshGroup::new(group_id.clone(), created_by, local_id),
+            );
+        }
+        let group = self.groups.get_mut(&group_id).unwrap();
+        if !joining && !group.is_member(&from) {
+            return vec![];
+        }
+
+        let before = group.member_ids();
+        for (key, entry) in entries {
+            if group.accepts(&key, &entry, joining) {
+                group.doc.apply(key, entry);
+            }
+        }
+        let after = group.member_ids();
+
+        // We were removed (or the invitation didn't hold up)
+        if !after.contains(&local_id) {
+            self.groups.remove(&group_id);
+            if joining {
+                return vec![];
+            }
+            return vec![MeshAction::Event(GroupEvent::MemberLeft {
+                group_id,
+                node_id: local_id,
+                username: self.local_username.clone(),
+                reason: LeaveReason::Kicked,
+            })];
+        }
+
+        let mut actions = Vec::new();
+        if joining {
+            // Accept: publish our username and hand our key to everyone
+            group.write(
+                member_key(&local_id),
+                Some(self.local_username.as_bytes().to_vec()),
+                local_id,
+            );
+            actions.extend(broadcast_membership(group, local_id));
+            for member in after.iter().filter(|id| **id != local_id) {
+                actions.extend(sender_key_to(group, *member));
+            }
+            actions.push(MeshAction::Event(GroupEvent::Joined {
+                group_id: group_id.clone(),
+                group_name: group.name().to_string(),
+            }));
+            return actions;
+        }
+
+        for member in group.members() {
+            if !before.contains(&member.node_id) {
+                actions.extend(sender_key_to(group, member.node_id));
+                actions.push(MeshAction::Event(GroupEvent::MemberJoined {
+                    group_id: group_id.clone(),
+                    member,
+                }));
+            }
+        }
+        let removed: Vec<NodeId> = before.difference(&after).copied().collect();
+        if !removed.is_empty() {
+            // Departed members must not read what comes next
+            group.local_key = SenderKeyEntry {
+                owner_id: local_id,
+                key: crate::crypto::generate_sender_key(),
+                epoch: group.local_key.epoch + 1,
+                created_at: now_ms(),
+            };
+            for member in after.iter().filter(|id| **id != local_id) {
+                actions.extend(sender_key_to(group, *member));
+            }
+        }
+        for node_id in removed {
+            group.sender_keys.remove(&node_id);
+            actions.push(MeshAction::Event(GroupEvent::MemberLeft {
+                group_id: group_id.clone(),
+                node_id,
+                username: String::new(),
+                reason: LeaveReason::Voluntary,
+            }));
+        }
+        actions
+    }
+
+    fn handle_sender_key(
+        &mut self,
+        from: NodeId,
+        group_id: &GroupId,
+        epoch: u32,
+        encrypted_key: &EncryptedSenderKey,
+        secret_seed: &[u8; 32],
+    ) -> Vec<MeshAction> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return vec![];
+        };
+        if !group.is_member(&from) || encrypted_key.recipient_id != self.local_id {
+            return vec![];
+        }
+        if group.sender_keys.get(&from).is_some_and(|k| k.epoch >= epoch) {
+            return vec![];
+        }
+        let key = match crate::crypto::decrypt(&encrypted_key.encrypted_key, secret_seed) {
+            Ok(bytes) if bytes.len() == 32 => {
+                let mut key = [0u8; 32];
+                key.copy_from_slice(&bytes);
+                key
+            }
+            Ok(_) | Err(_) => {
+                tracing::warn!("mesh sender key from {from} rejected");
+                return vec![];
+            }
+        };
+        group.sender_keys.insert(
+            from,
+            SenderKeyEntry {
+                owner_id: from,
+                key,
+                epoch,
+                created_at: now_ms(),
+            },
+        );
+
+        // Retry messages that were waiting for this key
+        let (ready, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut group.pending)
+            .into_iter()
+            .partition(|m| m.sender_id == from && m.key_epoch == epoch);
+        group.pending = waiting;
+        ready
+            .into_iter()
+            .flat_map(|msg| deliver(group, msg))
+            .collect()
+    }
+
+    fn handle_message(&mut self, from: NodeId, msg: GroupMessage) -> Vec<MeshAction> {
+        let Some(group) = self.groups.get_mut(&msg.group_id) else {
+            return vec![];
+        };
+        if msg.sender_id != from || !group.is_member(&from) {
+            return vec![MeshAction::Event(GroupEvent::SecurityViolation {
+                group_id: msg.group_id,
+                node_id: from,
+                reason: "non-member attempted to send message".into(),
+            })];
+        }
+        if !msg.encrypted || !msg.verify_signature() {
+            return vec![MeshAction::Event(GroupEvent::SecurityViolation {
+                group_id: msg.group_id,
+                node_id: from,
+                reason: "invalid message signature".into(),
+            })];
+        }
+        if !group.remember(&msg.message_id) {
+            return vec![];
+        }
+
+        match group.sender_keys.get(&from) {
+            Some(key) if key.epoch == msg.key_epoch => deliver(group, msg),
+            Some(key) if key.epoch > msg.key_epoch => vec![],
+            _ => {
+                if group.pending.len() < MAX_MESH_PENDING {
+                    group.pending.push(msg);
+                }
+                vec![]
+            }
+        }
+    }
+
+    /// Export state for persistence.
+    pub fn snapshot(&self) -> MeshGroupSnapshot {
+        MeshGroupSnapshot {
+            groups: self.groups.clone(),
+        }
+    }
+
+    /// Restore persisted state (replaces current groups).
+    pub fn restore(&mut self, snapshot: MeshGroupSnapshot) {
+        self.groups = snapshot.groups;
+    }
+}
+
+/// Decrypt with the sender's current key, record and surface a message.
+fn deliver(group: &mut MeshGroup, mut msg: GroupMessage) -> Vec<MeshAction> {
+    let Some(key) = group.sender_keys.get(&msg.sender_id) else {
+        return vec![];
+    };
+    match msg.decrypt(&key.key) {
+        Ok(content) => {
+            msg.sender_username = content.username;
+            msg.text = content.text;
+            group.push_history(msg.clone());
+            vec![MeshAction::Event(GroupEvent::MessageReceived(msg))]
+        }
+        Err(e) => {
+            tracing::warn!("mesh message decrypt failed: {e}");
+            vec![]
+        }
+    }
+}
+
+fn membership_to(group: &MeshGroup, to: NodeId) -> MeshAction {
+    MeshAction::Send {
+        to,
+        payload: MeshPayload::Membership {
+            group_id: group.group_id.clone(),
+            created_by: group.created_by,
+            entries: group.full_state(),
+        },
+    }
+}
+
+/// Push the full document to every member except ourselves.
+fn broadcast_membership(group: &MeshGroup, local_id: NodeId) -> Vec<MeshAction> {
+    group
+        .member_ids()
+        .into_iter()
+        .filter(|id| *id != local_id)
+        .map(|to| membership_to(group, to))
+        .collect()
+}
+
+/// Our Sender Key, encrypted for one member.
+fn sender_key_to(group: &MeshGroup, to: NodeId) -> Option<MeshAction> {
+    match crate::crypto::encrypt(&group.local_key.key, &to.as_bytes()) {
+        Ok(encrypted_key) => Some(MeshAction::Send {
+            to,
+            payload: MeshPayload::SenderKey {
+                group_id: group.group_id.clone(),
+                epoch: group.local_key.epoch,
+                encrypted_key: EncryptedSenderKey {
+                    recipient_id: to,
+                    encrypted_key,
+                },
+            },
+        }),
+        Err(e) => {
+            tracing::warn!("mesh sender key encrypt for {to} failed: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypair(seed: u8) -> (NodeId, [u8; 32]) {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        let node_id: NodeId = secret.public().to_string().parse().unwrap();
+        (node_id, secret.to_bytes())
+    }
+
+    struct Node {
+        mgr: MeshGroupManager,
+        seed: [u8; 32],
+        events: Vec<GroupEvent>,
+    }
+
+    fn node(seed: u8, name: &str) -> Node {
+        let (id, seed) = keypair(seed);
+        Node {
+            mgr: MeshGroupManager::new(id, name.into()),
+            seed,
+            events: Vec::new(),
+        }
+    }
+
+    /// Deliver every Send action to the matching node until quiet.
+    fn pump(nodes: &mut [&mut Node], from: NodeId, actions: Vec<MeshAction>) {
+        let mut pending: VecDeque<(NodeId, MeshAction)> =
+            actions.into_iter().map(|a| (from, a)).collect();
+        while let Some((from, action)) = pending.pop_front() {
+            if let MeshAction::Send { to, payload } = action {
+                if let Some(target) = nodes.iter_mut().find(|n| n.mgr.local_id == to) {
+                    for out in target.mgr.handle_payload(from, payload, &target.seed) {
+                        match out {
+                            MeshAction::Event(e) => target.events.push(e),
+                            send => pending.push_back((to, send)),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn received(node: &Node) -> Vec<String> {
+        node.events
+            .iter()
+            .filter_map(|e| match e {
+                GroupEvent::MessageReceived(m) => Some(m.text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn create_invites_and_fans_out_messages() {
+        let (mut alice, mut bob, mut carol) = (node(1, "alice"), node(2, "bob"), node(3, "carol"));
+        let (bob_id, carol_id) = (bob.mgr.local_id, carol.mgr.local_id);
+        let alice_id = alice.mgr.local_id;
+
+        let (gid, out) = alice.mgr.create("trio", vec![bob_id, carol_id]).unwrap();
+        pump(&mut [&mut alice, &mut bob, &mut carol], alice_id, out);
+
+        for n in [&alice, &bob, &carol] {
+            let group = n.mgr.get(&gid).unwrap();
+            assert_eq!(group.name(), "trio");
+            let mut names: Vec<_> = group.members().into_iter().map(|m| m.username).collect();
+            names.sort();
+            assert_eq!(names, vec!["alice", "bob", "carol"]);
+        }
+        assert!(bob
+            .events
+            .iter()
+            .any(|e| matches!(e, GroupEvent::Joined { group_name, .. } if group_name == "trio")));
+
+        let out = bob.mgr.send_message(&gid, "hi all".into(), &bob.seed).unwrap();
+        assert_eq!(out.len(), 2);
+        assert!(out.iter().all(|a| matches!(a,
+            MeshAction::Send { payload: MeshPayload::Message(m), .. } if m.encrypted && m.text.is_empty())));
+        pump(&mut [&mut alice, &mut bob, &mut carol], bob_id, out);
+
+        assert_eq!(received(&alice), vec!["hi all"]);
+        assert_eq!(received(&carol), vec!["hi all"]);
+        assert!(received(&bob).is_empty());
+        assert_eq!(bob.mgr.get(&gid).unwrap().history()[0].text, "hi all");
+    }
+
+    #[test]
+    fn message_before_key_is_held_until_key_arrives() {
+        let (mut alice, mut bob) = (node(1, "alice"), node(2, "bob"));
+        let (alice_id, bob_id) = (alice.mgr.local_id, bob.mgr.local_id);
+
+        let (gid, out) = alice.mgr.create("duo", vec![bob_id]).unwrap();
+        // Hold back Alice's key, deliver the membership only
+        let (key, rest): (Vec<_>, Vec<_>) = out.into_iter().partition(|a| {
+            matches!(a, MeshAction::Send { payload: MeshPayload::SenderKey { .. }, .. })
+        });
+        pump(&mut [&mut alice, &mut bob], alice_id, rest);
+
+        let msg = alice.mgr.send_message(&gid, "early".into(), &alice.seed).unwrap();
+        pump(&mut [&mut alice, &mut bob], alice_id, msg.clone());
+        assert!(received(&bob).is_empty());
+
+        pump(&mut [&mut alice, &mut bob], alice_id, key);
+        assert_eq!(received(&bob), vec!["early"]);
+
+        // Duplicate delivery is dropped
+        pump(&mut [&mut alice, &mut bob], alice_id, msg);
+        assert_eq!(received(&bob).len(), 1);
+    }
+
+    #[test]
+    fn leave_propagates_and_rotates_keys() {
+        let (mut alice, mut bob, mut carol) = (node(1, "alice"), node(2, "bob"), node(3, "carol"));
+        let (alice_id, bob_id, carol_id) = (alice.mgr.local_id, bob.mgr.local_id, carol.mgr.local_id);
+
+        let (gid, out) = alice.mgr.create("trio", vec![bob_id, carol_id]).unwrap();
+        pump(&mut [&mut alice, &mut bob, &mut carol], alice_id, out);
+        let epoch = alice.mgr.get(&gid).unwrap().local_key.epoch;
+
+        let out = carol.mgr.leave(&gid);
+        pump(&mut [&mut alice, &mut bob, &mut carol], carol_id, out);
+
+        assert!(carol.mgr.get(&gid).is_none());
+        assert!(!alice.mgr.get(&gid).unwrap().is_member(&carol_id));
+        assert!(!bob.mgr.get(&gid).unwrap().is_member(&carol_id));
+        assert!(alice.mgr.get(&gid).unwrap().local_key.epoch > epoch);
+        assert!(bob.events.iter().any(|e| matches!(e,
+            GroupEvent::MemberLeft { node_id, .. } if *node_id == carol_id)));
+
+        // Still talking after the rotation
+        let out = alice.mgr.send_message(&gid, "two now".into(), &alice.seed).unwrap();
+        assert_eq!(out.len(), 1);
+        pump(&mut [&mut alice, &mut bob, &mut carol], alice_id, out);
+        assert_eq!(received(&bob), vec!["two now"]);
+    }
+
+    #[test]
+    fn creator_removes_member() {
+        let (mut alice, mut bob, mut carol) = (node(1, "alice"), node(2, "bob"), node(3, "carol"));
+        let (alice_id, bob_id, carol_id) = (alice.mgr.local_id, bob.mgr.local_id, carol.mgr.local_id);
+
+        let (gid, out) = alice.mgr.create("trio", vec![bob_id, carol_id]).unwrap();
+        pump(&mut [&mut alice, &mut bob, &mut carol], alice_id, out);
+
+        assert!(bob.mgr.remove_member(&gid, carol_id).is_err());
+        let out = alice.mgr.remove_member(&gid, carol_id).unwrap();
+        pump(&mut [&mut alice, &mut bob, &mut carol], alice_id, out);
+
+        assert!(carol.mgr.get(&gid).is_none());
+        assert!(carol.events.iter().any(|e| matches!(e,
+            GroupEvent::MemberLeft { reason: LeaveReason::Kicked, .. })));
+        assert!(!bob.mgr.get(&gid).unwrap().is_member(&carol_id));
+    }
+
+    #[test]
+    fn rejects_strangers_and_forged_removals() {
+        let (mut alice, mut bob) = (node(1, "alice"), node(2, "bob"));
+        let (alice_id, bob_id) = (alice.mgr.local_id, bob.mgr.local_id);
+        let (eve_id, eve_seed) = keypair(9);
+
+        let (gid, out) = alice.mgr.create("duo", vec![bob_id]).unwrap();
+        pump(&mut [&mut alice, &mut bob], alice_id, out);
+
+        // Eve isn't a member: her message is flagged
+        let mut msg = GroupMessage::new_encrypted(
+            gid.clone(), eve_id, "eve".into(), "spam".into(), &[7u8; 32], 1,
+        );
+        msg.sign(&eve_seed);
+        let out = alice.mgr.handle_payload(eve_id, MeshPayload::Message(msg), &alice.seed);
+        assert!(matches!(&out[..], [MeshAction::Event(GroupEvent::SecurityViolation { .. })]));
+
+        // Bob can't remove Alice; his tombstone for her is ignored
+        let forged = LwwEntry {
+            value: None,
+            stamp: LwwStamp { millis: u64::MAX, writer: bob_id },
+        };
+        let out = alice.mgr.handle_payload(
+            bob_id,
+            MeshPayload::Membership {
+                group_id: gid.clone(),
+                created_by: alice_id,
+                entries: vec![(member_key(&alice_id), forged)],
+            },
+            &alice.seed,
+        );
+        assert!(out.is_empty());
+        assert!(alice.mgr.get(&gid).unwrap().is_member(&alice_id));
+
+        // An invitation not listing us is ignored
+        let (gid2, out) = alice.mgr.create("other", vec![]).unwrap();
+        assert!(out.iter().all(|a| matches!(a, MeshAction::Event(_))));
+        let entries = alice.mgr.get(&gid2).unwrap().full_state();
+        let out = bob.mgr.handle_payload(
+            alice_id,
+            MeshPayload::Membership { group_id: gid2.clone(), created_by: alice_id, entries },
+            &bob.seed,
+        );
+        assert!(out.is_empty());
+        assert!(!bob.mgr.is_mesh(&gid2));
+    }
+
+    #[test]
+    fn member_cap_enforced() {
+        let mut alice = node(1, "alice");
+        let others: Vec<NodeId> = (10..10 + MAX_MESH_MEMBERS as u8).map(|s| keypair(s).0).collect();
+        assert!(alice.mgr.create("big", others.clone()).is_err());
+
+        let (gid, _) = alice
+            .mgr
+            .create("full", others[..MAX_MESH_MEMBERS - 1].to_vec())
+            .unwrap();
+        assert_eq!(alice.mgr.get(&gid).unwrap().members().len(), MAX_MESH_MEMBERS);
+        assert!(alice.mgr.add_member(&gid, others[MAX_MESH_MEMBERS - 1]).is_err());
+    }
+
+    #[test]
+    fn snapshot_restore_roundtrip() {
+        let (mut alice, mut bob) = (node(1, "alice"), node(2, "bob"));
+        let (alice_id, bob_id) = (alice.mgr.local_id, bob.mgr.local_id);
+        let (gid, out) = alice.mgr.create("duo", vec![bob_id]).unwrap();
+        pump(&mut [&mut alice, &mut bob], alice_id, out);
+
+        let json = serde_json::to_string(&bob.mgr.snapshot().groups).unwrap();
+        let mut restored = MeshGroupManager::new(bob_id, "bob".into());
+        restored.restore(MeshGroupSnapshot {
+            groups: serde_json::from_str(&json).unwrap(),
+        });
+        bob.mgr = restored;
+
+        let out = alice.mgr.send_message(&gid, "after restart".into(), &alice.seed).unwrap();
+        pump(&mut [&mut alice, &mut bob], alice_id, out);
+        assert_eq!(received(&bob), vec!["after restart"]);
+    }
+}
@@ -1,19 +1,27 @@
 /// Group messaging for ToM protocol.
 ///
 /// Hub-and-spoke topology: one relay node acts as hub per group,
-/// fanning out messages to all members. Pure state machines — no I/O.
+/// fanning out messages to all members. Tiny private groups can instead
+/// run hubless (`mesh`), members fanning out directly. Pure state machines — no I/O.
 pub mod election;
 pub mod hub;
+pub mod invite_token;
 pub mod manager;
+pub mod mesh;
 pub mod types;
 
-pub use election::{elect_hub, ElectionReason, ElectionResult};
+pub use election::{elect_hub, elect_sub_hub, ElectionReason, ElectionResult};
 pub use hub::{GroupHub, GroupHubSnapshot};
+pub use invite_token::{hash_join_secret, GroupInviteToken, INVITE_TOKEN_PREFIX};
 pub use manager::{GroupManager, GroupManagerSnapshot};
+pub use mesh::{
+    MeshAction, MeshGroup, MeshGroupManager, MeshGroupSnapshot, MeshPayload, MAX_MESH_MEMBERS,
+};
 pub use types::{
-    EncryptedSenderKey, GroupAction, GroupEvent, GroupId, GroupInfo, GroupInvite, GroupMember,
-    GroupMemberRole, GroupMessage, GroupMessageContent, GroupPayload, LeaveReason, SenderKeyEntry,
-    CANDIDATE_ORPHAN_TIMEOUT_MS, HUB_ACK_TIMEOUT_MS, SHADOW_PING_FAILURE_THRESHOLD,
+    EncryptedSenderKey, GroupAction, GroupBan, GroupDeliveryStatus, GroupEvent, GroupId,
+    GroupInfo, GroupInvite, GroupJoinRequest, GroupMember, GroupMemberRole, GroupMessage, GroupMessageContent, GroupPayload,
+    GroupNotificationPrefs, GroupPoll, GroupSettings, LeaveReason, SenderKeyEntry,
+    CANDIDATE_ORPHAN_TIMEOUT_MS, HUB_ACK_TIMEOUT_MS, MAX_PENDING_JOIN_REQUESTS, MAX_SYNC_MESSAGES, SHADOW_PING_FAILURE_THRESHOLD,
     SHADOW_PING_INTERVAL_MS, SHADOW_PING_TIMEOUT_MS, SENDER_KEY_EPOCH_GRACE_MS,
     SENDER_KEY_PURGE_MAX_AGE_MS, SENDER_KEY_ROTATE_MAX_AGE_MS,
     SENDER_KEY_ROTATE_MAX_MESSAGES, SENDER_KEY_ROTATE_RATE_LIMIT_MS,
@@ -10,7 +10,13 @@ use crate::types::{now_ms, NodeId};
 // ── Constants ────────────────────────────────────────────────────────────
 
 /// Maximum members per group.
-pub const MAX_GROUP_MEMBERS: usize = 50;
+///
+/// Beyond [`MAX_DIRECT_FANOUT`] the hub shards message fan-out across sub-hubs.
+pub const MAX_GROUP_MEMBERS: usize = 500;
+
+/// Members a hub fans a message out to directly. Larger groups are split
+/// into shards of this size, each relayed by an elected member sub-hub.
+pub const MAX_DIRECT_FANOUT: usize = 50;
 
 /// Invite TTL (24 hours, matching ToM design decision #2).
 pub const INVITE_TTL_MS: u64 = 24 * 60 * 60 * 1000;
@@ -21,12 +27,33 @@ pub const HUB_HEARTBEAT_INTERVAL_MS: u64 = 30_000;
 /// Missed heartbeats before hub is considered failed.
 pub const HUB_FAILURE_THRESHOLD: u32 = 3;
 
+/// Longest a hub skips a member's heartbeat because group traffic already
+/// reached it — leaves a full interval of margin before the member's
+/// `HUB_FAILURE_THRESHOLD` deadline.
+pub const HUB_HEARTBEAT_MAX_SUPPRESSION_MS: u64 =
+    HUB_HEARTBEAT_INTERVAL_MS * (HUB_FAILURE_THRESHOLD as u64 - 1) - HUB_HEARTBEAT_INTERVAL_MS / 2;
+
 /// Max messages kept in hub history for sync to new members.
 pub const MAX_SYNC_MESSAGES: usize = 100;
 
+/// Max messages returned in one history page (FetchHistory).
+pub const MAX_HISTORY_PAGE: usize = 100;
+
+/// Max messages per group whose per-member delivery the hub (and the
+/// sender) keeps track of. Oldest entries are evicted first.
+pub const MAX_TRACKED_DELIVERIES: usize = 256;
+
 /// Rate limit: messages per second per sender in a group.
 pub const GROUP_RATE_LIMIT_PER_SECOND: u32 = 5;
 
+/// Per-group rate limit bounds an admin may set (messages/second/sender).
+pub const MIN_GROUP_RATE_LIMIT: u32 = 1;
+pub const MAX_GROUP_RATE_LIMIT: u32 = 50;
+
+/// Per-group history size bounds an admin may set (messages).
+pub const MIN_GROUP_HISTORY_LIMIT: u32 = 10;
+pub const MAX_GROUP_HISTORY_LIMIT: u32 = 1_000;
+
 /// Shadow pings primary every 3s.
 pub const SHADOW_PING_INTERVAL_MS: u64 = 3_000;
 
@@ -57,6 +84,61 @@ pub const SENDER_KEY_PURGE_MAX_AGE_MS: u64 = 7 * 24 * 60 * 60 * 1000;
 /// Rotation anti-spam: max one rotation trigger per group per hour.
 pub const SENDER_KEY_ROTATE_RATE_LIMIT_MS: u64 = 60 * 60 * 1000;
 
+/// Maximum group name length in bytes.
+pub const MAX_GROUP_NAME_LEN: usize = 64;
+
+/// Maximum group description/topic length in bytes.
+pub const MAX_GROUP_DESCRIPTION_LEN: usize = 512;
+
+/// Maximum reaction length in bytes (one emoji, ZWJ sequences included).
+pub const MAX_REACTION_LEN: usize = 32;
+
+/// Max messages per group whose reactions are tracked (hub and members).
+pub const MAX_TRACKED_REACTIONS: usize = 256;
+
+/// Maximum number of members a single group message may mention.
+pub const MAX_MENTIONS: usize = 64;
+
+/// Maximum number of active bans per group.
+pub const MAX_BANS_PER_GROUP: usize = 256;
+
+/// How far past its `sent_at` a group message may be scheduled (12 hours).
+///
+/// The hold counts against the 24h lifespan, so a scheduled message still
+/// lives at least half of it once delivered.
+pub const MAX_SCHEDULE_AHEAD_MS: u64 = crate::types::MESSAGE_LIFESPAN_MS / 2;
+
+/// Maximum scheduled messages a hub holds per group.
+pub const MAX_SCHEDULED_PER_GROUP: usize = 256;
+
+/// Shortest disappearing-message timer an admin may set (5 seconds).
+pub const MIN_MESSAGE_TTL_MS: u64 = 5_000;
+
+/// Largest `config_version` jump a member applies from one update.
+///
+/// The hub bumps the version by one per change; a member that missed more
+/// than this catches up through the next full group sync instead.
+pub const MAX_CONFIG_VERSION_STEP: u64 = 64;
+
+/// Longest disappearing-message timer an admin may set (24 hours, the
+/// protocol-wide lifespan of design decision #2).
+pub const MAX_MESSAGE_TTL_MS: u64 = crate::types::MESSAGE_LIFESPAN_MS;
+
+/// Maximum length of a poll question (bytes).
+pub const MAX_POLL_QUESTION_LEN: usize = 256;
+
+/// Maximum options per poll.
+pub const MAX_POLL_OPTIONS: usize = 10;
+
+/// Maximum length of a poll option (bytes).
+pub const MAX_POLL_OPTION_LEN: usize = 100;
+
+/// Polls kept per group (oldest dropped first).
+pub const MAX_POLLS_PER_GROUP: usize = 16;
+
+/// Join requests awaiting an admin decision, per group (oldest dropped first).
+pub const MAX_PENDING_JOIN_REQUESTS: usize = 64;
+
 // ── GroupId ──────────────────────────────────────────────────────────────
 
 /// Unique group identifier (e.g., "grp-<uuid>").
@@ -116,6 +198,25 @@ pub struct GroupMember {
     pub role: GroupMemberRole,
 }
 
+// ── GroupBan ─────────────────────────────────────────────────────────────
+
+/// A node barred from (re)joining a group.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupBan {
+    pub node_id: NodeId,
+    pub banned_by: NodeId,
+    pub banned_at: u64,
+    /// Unix ms after which the ban lapses (None = permanent).
+    pub expires_at: Option<u64>,
+}
+
+impl GroupBan {
+    /// Whether the ban is still in force at `now` (unix ms).
+    pub fn is_active(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|exp| exp > now)
+    }
+}
+
 // ── GroupInfo ────────────────────────────────────────────────────────────
 
 /// Full group state — shared between manager and hub.
@@ -139,6 +240,29 @@ pub struct GroupInfo {
     /// Whether this group requires invitations to join (R11.3).
     #[serde(default)]
     pub invite_only: bool,
+    /// Free-form topic/description shown under the group name.
+    #[serde(default)]
+    pub description: String,
+    /// Content hash of the group avatar (the image itself travels out of band).
+    #[serde(default)]
+    pub avatar_hash: Option<[u8; 32]>,
+    /// Metadata version, bumped by the hub on every metadata update.
+    /// Members ignore updates that are not newer than what they hold.
+    #[serde(default)]
+    pub config_version: u64,
+    /// Nodes barred from joining (admin controlled, enforced by the hub).
+    #[serde(default)]
+    pub bans: Vec<GroupBan>,
+    /// Disappearing-message timer: messages older than this are purged
+    /// by the hub and by every member. `None` keeps messages.
+    #[serde(default)]
+    pub message_ttl_ms: Option<u64>,
+    /// Recent polls with their tallies (hub authoritative, oldest first).
+    #[serde(default)]
+    pub polls: Vec<GroupPoll>,
+    /// Admin-tuned limits (rate, history size) enforced by the hub.
+    #[serde(default)]
+    pub settings: GroupSettings,
 }
 
 impl GroupInfo {
@@ -154,6 +278,15 @@ impl GroupInfo {
             .any(|m| m.node_id == *node_id && m.role == GroupMemberRole::Admin)
     }
 
+    /// Node IDs of all admins.
+    pub fn admin_ids(&self) -> Vec<NodeId> {
+        self.members
+            .iter()
+            .filter(|m| m.role == GroupMemberRole::Admin)
+            .map(|m| m.node_id)
+            .collect()
+    }
+
     /// Get a member by node ID.
     pub fn get_member(&self, node_id: &NodeId) -> Option<&GroupMember> {
         self.members.iter().find(|m| m.node_id == *node_id)
@@ -168,6 +301,114 @@ impl GroupInfo {
     pub fn is_full(&self) -> bool {
         self.members.len() >= self.max_members
     }
+
+    /// Whether a node is under an active ban at `now` (unix ms).
+    pub fn is_banned(&self, node_id: &NodeId, now: u64) -> bool {
+        self.bans
+            .iter()
+            .any(|b| b.node_id == *node_id && b.is_active(now))
+    }
+
+    /// Whether `msg` has outlived the group's disappearing-message timer.
+    ///
+    /// The timer runs from `sent_at`, like the 24h lifespan: time a
+    /// scheduled message spends held at the hub counts against it.
+    pub fn is_message_expired(&self, msg: &GroupMessage, now: u64) -> bool {
+        self.message_ttl_ms
+            .is_some_and(|ttl| msg.sent_at.saturating_add(ttl) <= now)
+    }
+}
+
+// ── GroupSettings ────────────────────────────────────────────────────────
+
+/// Per-group limits, admin adjustable. `None` keeps the protocol default
+/// (`GROUP_RATE_LIMIT_PER_SECOND`, `MAX_SYNC_MESSAGES`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupSettings {
+    /// Messages per second a single member may send.
+    #[serde(default)]
+    pub rate_limit_per_second: Option<u32>,
+    /// Messages kept in history for sync to new and returning members.
+    #[serde(default)]
+    pub history_limit: Option<u32>,
+    /// Anonymous posting: the hub verifies the author, then strips sender
+    /// id and username before fan-out. Admins still see the author.
+    ///
+    /// Posts stay anonymous to outsiders and to the hub's history, not to
+    /// members of an encrypted group: each post is sealed under its
+    /// author's sender key, and members find that key by trial decryption.
+    #[serde(default)]
+    pub anonymous: bool,
+}
+
+impl GroupSettings {
+    /// Whether every set value is within the allowed bounds.
+    pub fn is_valid(&self) -> bool {
+        self.rate_limit_per_second
+            .is_none_or(|r| (MIN_GROUP_RATE_LIMIT..=MAX_GROUP_RATE_LIMIT).contains(&r))
+            && self
+                .history_limit
+                .is_none_or(|h| (MIN_GROUP_HISTORY_LIMIT..=MAX_GROUP_HISTORY_LIMIT).contains(&h))
+    }
+}
+
+// ── GroupPoll ────────────────────────────────────────────────────────────
+
+/// A poll and its running tally. Each member votes once.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupPoll {
+    pub poll_id: String,
+    pub question: String,
+    pub options: Vec<String>,
+    /// Vote count per option (same order as `options`).
+    pub tallies: Vec<u32>,
+    /// Members who already voted.
+    pub voters: Vec<NodeId>,
+    pub created_by: NodeId,
+    pub created_at: u64,
+}
+
+impl GroupPoll {
+    /// Create an empty poll (tallies start at zero).
+    pub fn new(question: String, options: Vec<String>, created_by: NodeId) -> Self {
+        Self {
+            poll_id: uuid::Uuid::new_v4().to_string(),
+            tallies: vec![0; options.len()],
+            question,
+            options,
+            voters: Vec::new(),
+            created_by,
+            created_at: now_ms(),
+        }
+    }
+
+    /// Whether a member has already voted.
+    pub fn has_voted(&self, node_id: &NodeId) -> bool {
+        self.voters.contains(node_id)
+    }
+}
+
+// ── Notification preferences ─────────────────────────────────────────────
+
+/// Local, per-group notification preferences (never sent to the hub).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupNotificationPrefs {
+    /// Silence the group until this time (unix ms); `u64::MAX` mutes indefinitely.
+    #[serde(default)]
+    pub muted_until: Option<u64>,
+    /// Only messages that mention us notify.
+    #[serde(default)]
+    pub mentions_only: bool,
+}
+
+impl GroupNotificationPrefs {
+    /// Whether a received message should stay silent at `now` (unix ms).
+    pub fn is_muted(&self, msg: &GroupMessage, local_id: &NodeId, now: u64) -> bool {
+        if self.muted_until.is_some_and(|until| now < until) {
+            return true;
+        }
+        self.mentions_only && !msg.mentions(local_id)
+    }
 }
 
 // ── GroupInvite ──────────────────────────────────────────────────────────
@@ -191,6 +432,16 @@ impl GroupInvite {
     }
 }
 
+// ── GroupJoinRequest ─────────────────────────────────────────────────────
+
+/// A node asking to join a group, waiting for an admin to approve or deny.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupJoinRequest {
+    pub requester: NodeId,
+    pub username: String,
+    pub requested_at: u64,
+}
+
 // ── GroupPayload ─────────────────────────────────────────────────────────
 
 /// Group-specific payload — serialized into `Envelope.payload`.
@@ -255,9 +506,23 @@ pub enum GroupPayload {
     },
 
     /// Member confirms receipt of a group message (member → hub).
+    ///
+    /// `read: true` means the member also displayed it (implies delivered).
     DeliveryAck {
         group_id: GroupId,
         message_id: String,
+        #[serde(default)]
+        read: bool,
+    },
+
+    /// Hub reports a member's delivery/read of a message to its sender (hub → sender).
+    DeliveryReceipt {
+        group_id: GroupId,
+        message_id: String,
+        member: NodeId,
+        read: bool,
+        /// Every member the message was fanned out to has now received it.
+        all_delivered: bool,
     },
 
     /// Hub announces migration to a new hub (hub → members).
@@ -268,9 +533,13 @@ pub enum GroupPayload {
     },
 
     /// Hub health check (hub → members).
+    ///
+    /// Carries the current shadow so members know whom to report an outage to.
     HubHeartbeat {
         group_id: GroupId,
         member_count: usize,
+        #[serde(default)]
+        shadow_id: Option<NodeId>,
     },
 
     /// Distribution of a member's Sender Key to other members.
@@ -293,6 +562,8 @@ pub enum GroupPayload {
         members: Vec<GroupMember>,
         candidate_id: Option<NodeId>,
         config_version: u64,
+        #[serde(default)]
+        bans: Vec<GroupBan>,
     },
 
     /// Candidate role assignment (shadow -> candidate).
@@ -329,6 +600,20 @@ pub enum GroupPayload {
         target_id: NodeId,
     },
 
+    /// Admin bans a node, kicking it if it is a member (admin → hub).
+    /// `duration_ms: None` bans permanently.
+    BanMember {
+        group_id: GroupId,
+        target_id: NodeId,
+        duration_ms: Option<u64>,
+    },
+
+    /// Admin lifts a ban (admin → hub).
+    UnbanMember {
+        group_id: GroupId,
+        target_id: NodeId,
+    },
+
     // ── Offline delivery gap-fill (R13) ─────────────────────────────
 
     /// Member requests missed messages since a sequence number (member → hub).
@@ -343,6 +628,158 @@ pub enum GroupPayload {
         messages: Vec<GroupMessage>,
         latest_seq: u64,
     },
+
+    // ── Paged history ────────────────────────────────────────────────
+
+    /// Member asks for older messages, `seq < before` (member → hub).
+    /// `before = u64::MAX` fetches the most recent page.
+    FetchHistory {
+        group_id: GroupId,
+        before: u64,
+        limit: u32,
+    },
+
+    /// Hub returns one page of history, ascending by seq (hub → member).
+    HistoryPage {
+        group_id: GroupId,
+        messages: Vec<GroupMessage>,
+        has_more: bool,
+    },
+
+    // ── Group metadata ───────────────────────────────────────────────
+
+    /// Admin updates the group name, description or avatar (admin → hub).
+    UpdateMetadata {
+        group_id: GroupId,
+        name: String,
+        description: String,
+        avatar_hash: Option<[u8; 32]>,
+    },
+
+    /// Hub broadcasts the new metadata (hub → members).
+    MetadataChanged {
+        group_id: GroupId,
+        name: String,
+        description: String,
+        avatar_hash: Option<[u8; 32]>,
+        config_version: u64,
+        updated_by: NodeId,
+    },
+
+    // ── Invite tokens ────────────────────────────────────────────────
+
+    /// Admin registers an invite token's secret hash (admin → hub).
+    RegisterInviteToken {
+        group_id: GroupId,
+        secret_hash: [u8; 32],
+        expires_at: u64,
+    },
+
+    /// Token holder asks to join, presenting the join secret (joiner → hub).
+    JoinWithToken {
+        group_id: GroupId,
+        username: String,
+        join_secret: [u8; 32],
+    },
+
+    // ── Join approval ────────────────────────────────────────────────
+
+    /// Ask to join; an admin must approve before the hub adds us (requester → hub).
+    JoinRequest { group_id: GroupId, username: String },
+
+    /// Hub forwards a new join request to the admins (hub → admins).
+    JoinRequested {
+        group_id: GroupId,
+        request: GroupJoinRequest,
+    },
+
+    /// Admin accepts a pending join request (admin → hub).
+    ApproveJoin { group_id: GroupId, requester: NodeId },
+
+    /// Admin rejects a pending join request (admin → hub).
+    DenyJoin { group_id: GroupId, requester: NodeId },
+
+    /// Hub reports a decision (hub → admins, and the requester if denied).
+    JoinRequestResolved {
+        group_id: GroupId,
+        requester: NodeId,
+        approved: bool,
+        resolved_by: NodeId,
+    },
+
+    // ── Reactions ────────────────────────────────────────────────────
+
+    /// Add or remove an emoji reaction on a message (member → hub, hub → members).
+    ///
+    /// The hub overwrites `reactor` with the envelope sender before fanning out.
+    Reaction {
+        group_id: GroupId,
+        message_id: String,
+        emoji: String,
+        add: bool,
+        reactor: NodeId,
+    },
+
+    /// Primary hub delegates one shard of a message's fan-out (hub → sub-hub).
+    ///
+    /// The sub-hub delivers `message` locally and forwards it to `recipients`.
+    ShardRelay {
+        group_id: GroupId,
+        recipients: Vec<NodeId>,
+        message: GroupMessage,
+    },
+
+    /// Sender withdraws one of its scheduled messages (member → hub).
+    CancelScheduled {
+        group_id: GroupId,
+        message_id: String,
+    },
+
+    // ── Disappearing messages ────────────────────────────────────────
+
+    /// Admin sets or clears the disappearing-message timer (admin → hub).
+    SetMessageTtl {
+        group_id: GroupId,
+        ttl_ms: Option<u64>,
+    },
+
+    /// Hub broadcasts the new timer (hub → members).
+    MessageTtlChanged {
+        group_id: GroupId,
+        ttl_ms: Option<u64>,
+        config_version: u64,
+        updated_by: NodeId,
+    },
+
+    // ── Settings ─────────────────────────────────────────────────────
+
+    /// Admin replaces the group's rate/history settings (admin → hub).
+    UpdateSettings {
+        group_id: GroupId,
+        settings: GroupSettings,
+    },
+
+    /// Hub broadcasts the new settings (hub → members).
+    SettingsChanged {
+        group_id: GroupId,
+        settings: GroupSettings,
+        config_version: u64,
+        updated_by: NodeId,
+    },
+
+    // ── Polls ────────────────────────────────────────────────────────
+
+    /// Open a poll (member → hub) or publish its current tally (hub → members).
+    ///
+    /// The hub resets `created_by`, `tallies` and `voters` on creation.
+    Poll { group_id: GroupId, poll: GroupPoll },
+
+    /// Cast a vote for option `option` of a poll (member → hub).
+    Vote {
+        group_id: GroupId,
+        poll_id: String,
+        option: u32,
+    },
 }
 
 // ── GroupMessage ──────────────────────────────────────────────────────────
@@ -375,6 +812,19 @@ pub struct GroupMessage {
     /// 0 means not yet assigned (sender-side, before hub processing).
     #[serde(default)]
     pub seq: u64,
+    /// Message this one replies to (threads). The hub only accepts
+    /// references to messages it has relayed in the same group.
+    #[serde(default)]
+    pub reply_to: Option<String>,
+    /// Members this message mentions (notification hints). The hub only
+    /// accepts current members; order is the sender's.
+    #[serde(default)]
+    pub mentions: Vec<NodeId>,
+    /// Scheduled delivery time (unix ms). The hub holds the message and
+    /// fans it out then; at most `MAX_SCHEDULE_AHEAD_MS` past `sent_at`,
+    /// since the hold counts against the message's 24h lifespan.
+    #[serde(default)]
+    pub deliver_at: Option<u64>,
 }
 
 impl GroupMessage {
@@ -398,6 +848,9 @@ impl GroupMessage {
             sent_at: now_ms(),
             sender_signature: Vec::new(),
             seq: 0,
+            reply_to: None,
+            mentions: Vec::new(),
+            deliver_at: None,
         }
     }
 
@@ -426,7 +879,49 @@ impl GroupMessage {
             sent_at: now_ms(),
             sender_signature: Vec::new(),
             seq: 0,
+            reply_to: None,
+            mentions: Vec::new(),
+            deliver_at: None,
+        }
+    }
+
+    /// Mark this message as a reply to `message_id` (call before signing).
+    pub fn with_reply_to(mut self, message_id: impl Into<String>) -> Self {
+        self.reply_to = Some(message_id.into());
+        self
+    }
+
+    /// Mention these members (call before signing). Duplicates are dropped.
+    pub fn with_mentions(mut self, mentions: Vec<NodeId>) -> Self {
+        for node_id in mentions {
+            if !self.mentions.contains(&node_id) {
+                self.mentions.push(node_id);
+            }
         }
+        self
+    }
+
+    /// Schedule this message for delivery at `deliver_at` (call before signing).
+    pub fn with_deliver_at(mut self, deliver_at: u64) -> Self {
+        self.deliver_at = Some(deliver_at);
+        self
+    }
+
+    /// Strip the author (anonymous groups, hub side after verification).
+    ///
+    /// The hub becomes the visible sender and the signature no longer
+    /// applies. Encrypted content is opaque to the hub, so senders leave the
+    /// username out of it themselves. The ciphertext still only opens with
+    /// the author's sender key, which members can tell apart.
+    pub fn anonymize(&mut self, hub_id: NodeId) {
+        self.sender_id = hub_id;
+        self.sender_username.clear();
+        self.sender_signature.clear();
+    }
+
+    /// Whether this message mentions `node_id`.
+    pub fn mentions(&self, node_id: &NodeId) -> bool {
+        self.mentions.contains(node_id)
     }
 
     /// Decrypt this message's ciphertext using the sender's key.
@@ -463,6 +958,16 @@ impl GroupMessage {
             buf.extend_from_slice(self.text.as_bytes());
         }
         buf.extend_from_slice(&self.sent_at.to_le_bytes());
+        // Appended only when set, so signatures on plain messages are unchanged.
+        if let Some(ref reply_to) = self.reply_to {
+            buf.extend_from_slice(reply_to.as_bytes());
+        }
+        for node_id in &self.mentions {
+            buf.extend_from_slice(&node_id.as_bytes());
+        }
+        if let Some(deliver_at) = self.deliver_at {
+            buf.extend_from_slice(&deliver_at.to_le_bytes());
+        }
         buf
     }
 
@@ -531,6 +1036,16 @@ pub struct EncryptedSenderKey {
     pub encrypted_key: crate::crypto::EncryptedPayload,
 }
 
+/// Who has received / read one of our group messages.
+///
+/// Recipients are the members at fan-out time; `read` is a subset of `delivered`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupDeliveryStatus {
+    pub delivered: Vec<NodeId>,
+    pub read: Vec<NodeId>,
+    pub all_delivered: bool,
+}
+
 /// Plaintext content inside an encrypted group message.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GroupMessageContent {
@@ -611,6 +1126,106 @@ pub enum GroupEvent {
         new_role: GroupMemberRole,
     },
 
+    /// Group name, description or avatar changed.
+    MetadataChanged {
+        group_id: GroupId,
+        name: String,
+        description: String,
+        avatar_hash: Option<[u8; 32]>,
+        config_version: u64,
+        updated_by: NodeId,
+    },
+
+    /// A page of older messages arrived (answer to a history fetch).
+    ///
+    /// Messages are decrypted when we hold the sender key, ascending by seq.
+    /// They're not added to local history or counted as new.
+    HistoryPage {
+        group_id: GroupId,
+        messages: Vec<GroupMessage>,
+        has_more: bool,
+    },
+
+    /// A member received (or read) one of our messages.
+    DeliveryUpdated {
+        group_id: GroupId,
+        message_id: String,
+        member: NodeId,
+        read: bool,
+    },
+
+    /// Every recipient of one of our messages has received it.
+    AllDelivered {
+        group_id: GroupId,
+        message_id: String,
+    },
+
+    /// A received message mentions us (raised alongside `MessageReceived`).
+    Mentioned {
+        group_id: GroupId,
+        message_id: String,
+        sender_id: NodeId,
+        sender_username: String,
+    },
+
+    /// A member added or removed a reaction; `count` is the new total for `emoji`.
+    ReactionChanged {
+        group_id: GroupId,
+        message_id: String,
+        emoji: String,
+        reactor: NodeId,
+        added: bool,
+        count: usize,
+    },
+
+    /// The disappearing-message timer was set or cleared by an admin.
+    MessageTtlChanged {
+        group_id: GroupId,
+        ttl_ms: Option<u64>,
+        updated_by: NodeId,
+    },
+
+    /// An admin changed the group's rate/history settings.
+    SettingsChanged {
+        group_id: GroupId,
+        settings: GroupSettings,
+        updated_by: NodeId,
+    },
+
+    /// A poll was opened or its tally changed.
+    PollUpdated { group_id: GroupId, poll: GroupPoll },
+
+    /// Someone asked to join a group we administer.
+    JoinRequested {
+        group_id: GroupId,
+        request: GroupJoinRequest,
+    },
+
+    /// A pending join request was approved or denied (admin view).
+    JoinRequestResolved {
+        group_id: GroupId,
+        requester: NodeId,
+        approved: bool,
+        resolved_by: NodeId,
+    },
+
+    /// An admin denied our request to join.
+    JoinDenied { group_id: GroupId },
+
+    /// A member posted a valid contact card (follows `MessageReceived`).
+    ContactShared {
+        group_id: GroupId,
+        sender_id: NodeId,
+        card: crate::contact::ContactCard,
+    },
+
+    /// Messages outlived the disappearing-message timer and were removed
+    /// from local history; clients should delete them from view.
+    MessagesExpired {
+        group_id: GroupId,
+        message_ids: Vec<String>,
+    },
+
     /// Security violation detected (non-member or invalid signature).
     SecurityViolation {
         group_id: GroupId,
@@ -677,6 +1292,13 @@ mod tests {
             shadow_id: None,
             candidate_id: None,
             invite_only: false,
+            description: String::new(),
+            avatar_hash: None,
+            config_version: 0,
+            bans: Vec::new(),
+            message_ttl_ms: None,
+            polls: Vec::new(),
+            settings: GroupSettings::default(),
         }
     }
 
@@ -758,10 +1380,39 @@ mod tests {
             GroupPayload::DeliveryAck {
                 group_id: GroupId::from("grp-1".to_string()),
                 message_id: "msg-1".into(),
+                read: true,
+            },
+            GroupPayload::DeliveryReceipt {
+                group_id: GroupId::from("grp-1".to_string()),
+                message_id: "msg-1".into(),
+                member: node_id(1),
+                read: false,
+                all_delivered: true,
             },
             GroupPayload::HubHeartbeat {
                 group_id: GroupId::from("grp-1".to_string()),
                 member_count: 5,
+                shadow_id: Some(node_id(2)),
+            },
+            GroupPayload::ShardRelay {
+                group_id: GroupId::from("grp-1".to_string()),
+                recipients: vec![node_id(3), node_id(4)],
+                message: GroupMessage::new(
+                    GroupId::from("grp-1".to_string()),
+                    node_id(1),
+                    "alice".into(),
+                    "Hi".into(),
+                ),
+            },
+            GroupPayload::Poll {
+                group_id: GroupId::from("grp-1".to_string()),
+                poll: GroupPoll::new("Lunch?".into(), vec!["Pizza".into(), "Sushi".into()], node_id(1)),
+            },
+            GroupPayload::MessageTtlChanged {
+                group_id: GroupId::from("grp-1".to_string()),
+                ttl_ms: Some(60_000),
+                config_version: 3,
+                updated_by: node_id(1),
             },
         ];
 
@@ -862,6 +1513,71 @@ mod tests {
         assert!(!msg.verify_signature(), "wrong sender key should fail");
     }
 
+    #[test]
+    fn group_message_reply_to_is_signed() {
+        let seed = secret_seed(1);
+        let mut msg = GroupMessage::new(
+            GroupId::from("grp-1".to_string()),
+            node_id(1),
+            "alice".into(),
+            "Agreed".into(),
+        )
+        .with_reply_to("msg-1");
+        msg.sign(&seed);
+
+        let bytes = rmp_serde::to_vec(&msg).expect("serialize");
+        let mut decoded: GroupMessage = rmp_serde::from_slice(&bytes).expect("deserialize");
+        assert_eq!(decoded.reply_to.as_deref(), Some("msg-1"));
+        assert!(decoded.verify_signature());
+
+        decoded.reply_to = Some("msg-2".into());
+        assert!(!decoded.verify_signature(), "re-threading should fail verification");
+    }
+
+    #[test]
+    fn group_message_deliver_at_is_signed() {
+        let seed = secret_seed(1);
+        let mut msg = GroupMessage::new(
+            GroupId::from("grp-1".to_string()),
+            node_id(1),
+            "alice".into(),
+            "Happy new year!".into(),
+        )
+        .with_deliver_at(1_767_225_600_000);
+        msg.sign(&seed);
+
+        let bytes = rmp_serde::to_vec(&msg).expect("serialize");
+        let mut decoded: GroupMessage = rmp_serde::from_slice(&bytes).expect("deserialize");
+        assert_eq!(decoded.deliver_at, Some(1_767_225_600_000));
+        assert!(decoded.verify_signature());
+
+        decoded.deliver_at = Some(1_767_225_600_001);
+        assert!(!decoded.verify_signature(), "rescheduling should fail verification");
+    }
+
+    #[test]
+    fn group_message_mentions_are_signed() {
+        let seed = secret_seed(1);
+        let mut msg = GroupMessage::new(
+            GroupId::from("grp-1".to_string()),
+            node_id(1),
+            "alice".into(),
+            "@bob @carol review please".into(),
+        )
+        .with_mentions(vec![node_id(2), node_id(3), node_id(2)]);
+        msg.sign(&seed);
+        assert_eq!(msg.mentions, vec![node_id(2), node_id(3)]);
+
+        let bytes = rmp_serde::to_vec(&msg).expect("serialize");
+        let mut decoded: GroupMessage = rmp_serde::from_slice(&bytes).expect("deserialize");
+        assert!(decoded.mentions(&node_id(3)));
+        assert!(!decoded.mentions(&node_id(1)));
+        assert!(decoded.verify_signature());
+
+        decoded.mentions.push(node_id(4));
+        assert!(!decoded.verify_signature(), "added mention should fail verification");
+    }
+
     #[test]
     fn group_message_signed_roundtrip() {
         let seed = secret_seed(1);
@@ -983,6 +1699,12 @@ mod tests {
             }],
             candidate_id: Some(node_id(3)),
             config_version: 1,
+            bans: vec![GroupBan {
+                node_id: node_id(4),
+                banned_by: node_id(1),
+                banned_at: 1000,
+                expires_at: None,
+            }],
         };
         let bytes = rmp_serde::to_vec(&payload).expect("serialize");
         let decoded: GroupPayload = rmp_serde::from_slice(&bytes).expect("deserialize");
@@ -1039,6 +1761,47 @@ mod tests {
         assert_eq!(payload, decoded);
     }
 
+    #[test]
+    fn ban_payloads_roundtrip() {
+        let payloads = vec![
+            GroupPayload::BanMember {
+                group_id: GroupId::from("grp-1".to_string()),
+                target_id: node_id(2),
+                duration_ms: Some(60_000),
+            },
+            GroupPayload::UnbanMember {
+                group_id: GroupId::from("grp-1".to_string()),
+                target_id: node_id(2),
+            },
+        ];
+        for payload in payloads {
+            let bytes = rmp_serde::to_vec(&payload).expect("serialize");
+            let decoded: GroupPayload = rmp_serde::from_slice(&bytes).expect("deserialize");
+            assert_eq!(payload, decoded);
+        }
+    }
+
+    #[test]
+    fn ban_expiry() {
+        let mut group = make_group();
+        group.bans.push(GroupBan {
+            node_id: node_id(5),
+            banned_by: node_id(1),
+            banned_at: 1000,
+            expires_at: Some(2000),
+        });
+        group.bans.push(GroupBan {
+            node_id: node_id(6),
+            banned_by: node_id(1),
+            banned_at: 1000,
+            expires_at: None,
+        });
+        assert!(group.is_banned(&node_id(5), 1999));
+        assert!(!group.is_banned(&node_id(5), 2000));
+        assert!(group.is_banned(&node_id(6), u64::MAX));
+        assert!(!group.is_banned(&node_id(7), 0));
+    }
+
     #[test]
     fn member_role_changed_roundtrip() {
         let payloads = vec![
@@ -1063,4 +1826,115 @@ mod tests {
             assert_eq!(*payload, decoded, "roundtrip failed for {:?}", payload);
         }
     }
+
+    #[test]
+    fn reaction_payload_roundtrip() {
+        let payload = GroupPayload::Reaction {
+            group_id: GroupId::from("grp-1".to_string()),
+            message_id: "msg-1".into(),
+            emoji: "👍🏽".into(),
+            add: true,
+            reactor: node_id(2),
+        };
+        let bytes = rmp_serde::to_vec(&payload).expect("serialize");
+        let decoded: GroupPayload = rmp_serde::from_slice(&bytes).expect("deserialize");
+        assert_eq!(payload, decoded);
+    }
+
+    #[test]
+    fn metadata_payloads_roundtrip() {
+        let payloads = vec![
+            GroupPayload::UpdateMetadata {
+                group_id: GroupId::from("grp-1".to_string()),
+                name: "Renamed".into(),
+                description: "New topic".into(),
+                avatar_hash: Some([9u8; 32]),
+            },
+            GroupPayload::MetadataChanged {
+                group_id: GroupId::from("grp-1".to_string()),
+                name: "Renamed".into(),
+                description: String::new(),
+                avatar_hash: None,
+                config_version: 3,
+                updated_by: node_id(1),
+            },
+        ];
+        for payload in &payloads {
+            let bytes = rmp_serde::to_vec(payload).expect("serialize");
+            let decoded: GroupPayload = rmp_serde::from_slice(&bytes).expect("deserialize");
+            assert_eq!(*payload, decoded, "roundtrip failed for {:?}", payload);
+        }
+    }
+
+    #[test]
+    fn settings_payloads_roundtrip_and_bounds() {
+        let settings = GroupSettings {
+            rate_limit_per_second: Some(20),
+            ..Default::default()
+        };
+        assert!(settings.is_valid());
+        assert!(GroupSettings::default().is_valid());
+        assert!(!GroupSettings { rate_limit_per_second: Some(0), ..Default::default() }.is_valid());
+        assert!(!GroupSettings {
+            history_limit: Some(MAX_GROUP_HISTORY_LIMIT + 1),
+            ..Default::default()
+        }
+        .is_valid());
+
+        let payloads = vec![
+            GroupPayload::UpdateSettings {
+                group_id: GroupId::from("grp-1".to_string()),
+                settings,
+            },
+            GroupPayload::SettingsChanged {
+                group_id: GroupId::from("grp-1".to_string()),
+                settings,
+                config_version: 4,
+                updated_by: node_id(1),
+            },
+        ];
+        for payload in &payloads {
+            let bytes = rmp_serde::to_vec(payload).expect("serialize");
+            let decoded: GroupPayload = rmp_serde::from_slice(&bytes).expect("deserialize");
+            assert_eq!(*payload, decoded, "roundtrip failed for {:?}", payload);
+        }
+    }
+
+    #[test]
+    fn join_approval_payloads_roundtrip() {
+        let gid = GroupId::from("grp-1".to_string());
+        let payloads = vec![
+            GroupPayload::JoinRequest {
+                group_id: gid.clone(),
+                username: "bob".into(),
+            },
+            GroupPayload::JoinRequested {
+                group_id: gid.clone(),
+                request: GroupJoinRequest {
+                    requester: node_id(2),
+                    username: "bob".into(),
+                    requested_at: 1_000,
+                },
+            },
+            GroupPayload::ApproveJoin {
+                group_id: gid.clone(),
+                requester: node_id(2),
+            },
+            GroupPayload::DenyJoin {
+                group_id: gid.clone(),
+                requester: node_id(2),
+            },
+            GroupPayload::JoinRequestResolved {
+                group_id: gid,
+                requester: node_id(2),
+                approved: false,
+                resolved_by: node_id(1),
+            },
+        ];
+        for payload in &payloads {
+            let bytes = rmp_serde::to_vec(payload).expect("serialize");
+            let decoded: GroupPayload = rmp_serde::from_slice(&bytes).expect("deserialize");
+            assert_eq!(*payload, decoded, "roundtrip failed for {:?}", payload);
+        }
+    }
 }
@@ -0,0 +1,242 @@
+/// History sync between a user's own devices.
+///
+/// Linked devices (see `discovery::devices`) keep each other's history. A
+/// 1:1 message a device sends or receives is forwarded to its siblings as
+/// it happens; when two devices get linked, each asks the other for what it
+/// holds: the 1:1 log kept here plus the history of its groups. Entries go
+/// in `MessageType::HistorySync` envelopes, encrypted to the sibling like
+/// chat and untracked; only linked devices' are accepted. The app gets what
+/// it didn't have as `HistorySynced` events.
+use std::collections::{HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::TomProtocolError;
+use crate::group::GroupMessage;
+use crate::types::NodeId;
+
+/// Most 1:1 messages kept in the local log (oldest dropped first).
+pub const MAX_HISTORY_ENTRIES: usize = 5_000;
+
+/// Most entries sent in answer to one sync request (the newest).
+pub const MAX_SYNC_ENTRIES: usize = 5_000;
+
+/// Payload budget of one page of entries, well under the envelope limit.
+pub const MAX_PAGE_BYTES: usize = 128 * 1024;
+
+/// A 1:1 message, from the point of view of the user's devices.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectEntry {
+    /// The other side of the conversation.
+    pub peer: NodeId,
+    /// Sent by one of our devices (true) or received from `peer`.
+    pub outgoing: bool,
+    pub message_id: String,
+    /// Raw bytes for untyped payloads, or the unwrapped body of a typed one.
+    pub payload: Vec<u8>,
+    /// Content type of a typed payload. None = legacy untyped bytes.
+    pub content_type: Option<String>,
+    pub timestamp: u64,
+}
+
+/// One history item exchanged between devices.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum HistoryEntry {
+    Direct(DirectEntry),
+    Group(GroupMessage),
+}
+
+impl HistoryEntry {
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            Self::Direct(entry) => entry.timestamp,
+            Self::Group(message) => message.sent_at,
+        }
+    }
+}
+
+/// Wire payload of `MessageType::HistorySync` envelopes (before encryption).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum HistorySyncPayload {
+    /// Ask for everything newer than `since` (unix ms).
+    Request { since: u64 },
+    /// Entries, oldest first. `done` marks the last page of an answer to a
+    /// `Request`; live forwards are never `done`.
+    Entries { entries: Vec<HistoryEntry>, done: bool },
+}
+
+impl HistorySyncPayload {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        rmp_serde::to_vec(self).expect("HistorySyncPayload serialization cannot fail")
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, TomProtocolError> {
+        rmp_serde::from_slice(data).map_err(Into::into)
+    }
+}
+
+/// Split entries (oldest first) into `Entries` pages of at most
+/// `MAX_PAGE_BYTES` of payload each; the last one is `done`. No entries
+/// still make one (empty, done) page, so the asker knows it has everything.
+pub fn paginate(entries: Vec<HistoryEntry>) -> Vec<HistorySyncPayload> {
+    let mut pages = Vec::new();
+    let mut page = Vec::new();
+    let mut page_bytes = 0;
+    for entry in entries {
+        let size = match &entry {
+            HistoryEntry::Direct(entry) => entry.payload.len(),
+            HistoryEntry::Group(message) => message.text.len() + message.ciphertext.len(),
+        } + 256;
+        if size > MAX_PAGE_BYTES {
+            continue;
+        }
+        if page_bytes + size > MAX_PAGE_BYTES {
+            pages.push(std::mem::take(&mut page));
+            page_bytes = 0;
+        }
+        page_bytes += size;
+        page.push(entry);
+    }
+    pages.push(page);
+    let last = pages.len() - 1;
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(i, entries)| HistorySyncPayload::Entries {
+            entries,
+            done: i == last,
+        })
+        .collect()
+}
+
+/// Bounded log of the 1:1 messages this device sent or received (or got
+/// from a sibling), ordered by timestamp.
+#[derive(Debug)]
+pub struct ConversationLog {
+    entries: VecDeque<DirectEntry>,
+    ids: HashSet<String>,
+    capacity: usize,
+}
+
+impl ConversationLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            ids: HashSet::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Add an entry unless already known. Returns whether it was added
+    /// (an entry older than a full log's oldest is not).
+    pub fn record(&mut self, entry: DirectEntry) -> bool {
+        if self.ids.contains(&entry.message_id) {
+            return false;
+        }
+        if self.entries.len() >= self.capacity {
+            if self.entries.front().is_some_and(|oldest| entry.timestamp < oldest.timestamp) {
+                return false;
+            }
+            if let Some(evicted) = self.entries.pop_front() {
+                self.ids.remove(&evicted.message_id);
+            }
+        }
+        let at = self.entries.partition_point(|e| e.timestamp <= entry.timestamp);
+        self.ids.insert(entry.message_id.clone());
+        self.entries.insert(at, entry);
+        true
+    }
+
+    pub fn contains(&self, message_id: &str) -> bool {
+        self.ids.contains(message_id)
+    }
+
+    /// Entries newer than `since`, oldest first.
+    pub fn since(&self, since: u64) -> impl Iterator<Item = &DirectEntry> {
+        let start = self.entries.partition_point(|e| e.timestamp <= since);
+        self.entries.range(start..)
+    }
+
+    /// Timestamp of the oldest entry kept.
+    pub fn oldest_timestamp(&self) -> Option<u64> {
+        self.entries.front().map(|e| e.timestamp)
+    }
+
+    /// Timestamp of the newest entry.
+    pub fn newest_timestamp(&self) -> Option<u64> {
+        self.entries.back().map(|e| e.timestamp)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    fn entry(id: &str, timestamp: u64, bytes: usize) -> DirectEntry {
+        DirectEntry {
+            peer: node_id(1),
+            outgoing: false,
+            message_id: id.into(),
+            payload: vec![0; bytes],
+            content_type: None,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn log_orders_dedups_and_evicts() {
+        let mut log = ConversationLog::new(3);
+        assert!(log.record(entry("b", 20, 1)));
+        assert!(log.record(entry("a", 10, 1)));
+        assert!(!log.record(entry("a", 10, 1)), "duplicate");
+        assert!(log.record(entry("c", 30, 1)));
+        let ids: Vec<&str> = log.since(0).map(|e| e.message_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+        assert_eq!(log.since(20).count(), 1);
+
+        // Full: a newer entry evicts the oldest, an older one is refused
+        assert!(log.record(entry("d", 40, 1)));
+        assert!(!log.contains("a"));
+        assert!(!log.record(entry("z", 5, 1)));
+        assert_eq!((log.oldest_timestamp(), log.newest_timestamp()), (Some(20), Some(40)));
+        assert_eq!(log.len(), 3);
+    }
+
+    #[test]
+    fn pages_stay_under_budget() {
+        let entries: Vec<HistoryEntry> = (0..10)
+            .map(|i| HistoryEntry::Direct(entry(&i.to_string(), i, MAX_PAGE_BYTES / 4)))
+            .collect();
+        let pages = paginate(entries);
+        assert_eq!(pages.len(), 4);
+        let sizes: Vec<(usize, bool)> = pages
+            .iter()
+            .map(|p| match p {
+                HistorySyncPayload::Entries { entries, done } => (entries.len(), *done),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(sizes, [(3, false), (3, false), (3, false), (1, true)]);
+
+        let empty = paginate(Vec::new());
+        assert_eq!(empty, [HistorySyncPayload::Entries { entries: Vec::new(), done: true }]);
+
+        let page = &pages[0];
+        assert_eq!(&HistorySyncPayload::from_bytes(&page.to_bytes()).unwrap(), page);
+    }
+}
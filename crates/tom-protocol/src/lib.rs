@@ -7,42 +7,91 @@
 //! Crypto: Ed25519 signatures + XChaCha20-Poly1305 encryption.
 
 pub mod backup;
+pub mod capabilities;
+pub mod contact;
 pub mod crypto;
 pub mod discovery;
 pub mod envelope;
 pub mod error;
+pub mod exactly_once;
 pub mod group;
+pub mod history_sync;
+pub mod logging;
+pub mod ordering;
+pub mod outbox;
+pub mod pairing;
+pub mod payload;
+pub mod pubsub;
+pub mod realtime;
 pub mod relay;
 pub mod roles;
 pub mod router;
+pub mod rpc;
 pub mod runtime;
+pub mod shared_state;
+pub mod sim;
 pub mod storage;
+pub mod time;
+pub mod trace;
 pub mod tracker;
+#[cfg(feature = "native")]
+pub mod transfer;
 pub mod types;
+pub mod typing;
 
 pub use backup::{
-    BackupAction, BackupCoordinator, BackupEntry, BackupEvent, BackupStore, HostFactors,
-    ReplicationPayload,
+    BackupAction, BackupCoordinator, BackupEntry, BackupEvent, BackupPreference, BackupStore,
+    HostFactors, ReplicationPayload,
 };
+pub use capabilities::{CapabilityHandshake, Features, PeerCapabilities, PROTOCOL_VERSION};
+pub use contact::{Contact, ContactCard, ContactStore, EncryptionHandshake, EncryptionMode, TrustLevel};
 pub use crypto::EncryptedPayload;
 pub use discovery::{
-    DiscoveryEvent, DiscoverySource, DissolveReason, EphemeralSubnetManager, HeartbeatTracker,
-    LivenessState, PeerAnnounce, RoleChangeAnnounce, SubnetEvent, SubnetInfo,
+    AbuseReport, RelayAttestation, ViolationKind, DepartureAnnounce, DeploymentProfile, DeviceCertificate, DeviceDirectory, DeviceRevocation, DiscoveryEvent, DiscoverySource, DissolveReason, EphemeralSubnetManager,
+    HeartbeatTracker, IdentityBinding, LivenessConfig, LivenessState, PeerAnnounce, Presence, RoleChangeAnnounce, SubnetConfig, SubnetEvent, SubnetInfo,
+    SubnetStats, PexEntry, UsernameClaim, UsernameDirectory,
 };
 pub use envelope::{Envelope, EnvelopeBuilder};
 pub use error::TomProtocolError;
+pub use exactly_once::{ExactlyOnceInbox, ExactlyOnceOutbox};
 pub use group::{
-    elect_hub, ElectionReason, ElectionResult, EncryptedSenderKey, GroupAction, GroupEvent,
-    GroupHub, GroupId, GroupInfo, GroupInvite, GroupMember, GroupManager, GroupMemberRole,
-    GroupMessage, GroupMessageContent, GroupPayload, LeaveReason, SenderKeyEntry,
+    elect_hub, ElectionReason, ElectionResult, EncryptedSenderKey, GroupAction,
+    GroupBan, GroupDeliveryStatus, GroupEvent, GroupHub, GroupId, GroupInfo, GroupInvite, GroupInviteToken, GroupJoinRequest, GroupMember, GroupManager, GroupMemberRole,
+    GroupMessage, GroupMessageContent, GroupPayload, GroupSettings, LeaveReason, MeshGroup, MeshGroupManager,
+    SenderKeyEntry,
 };
-pub use relay::{PeerInfo, PeerRole, PeerStatus, RelaySelector, Topology};
-pub use roles::{AntiSpamConfig, ContributionMetrics, RoleAction, RoleManager, RoleMetrics};
-pub use router::{AckPayload, AckType, ReadReceiptPayload, Router, RoutingAction};
+pub use history_sync::{ConversationLog, DirectEntry, HistoryEntry, HistorySyncPayload};
+pub use ordering::{OrderingConfig, ReorderBuffer, Sequencer};
+pub use outbox::{OfflineOutbox, QueuedMessage};
+pub use pairing::{PairingCode, PairingInfo, PairingRecord, RendezvousCode};
+pub use payload::{PayloadRegistry, PayloadSchema, TextPayload, TypedPayload};
+pub use pubsub::{ChannelPublication, ChannelRegistry};
+pub use realtime::{RealtimeStats, MAX_REALTIME_PAYLOAD};
+pub use relay::{
+    ObservedPath, PeerInfo, PeerRole, PeerSnapshot, PeerStatus, RelaySelector, RoutingOverride, SubnetSnapshot,
+    Topology, TopologySnapshot,
+};
+pub use roles::{
+    AntiSpamConfig, ContributionMetrics, RoleAction, RoleConfig, RoleManager, RoleMetrics, ScoreSample,
+};
+pub use router::{
+    AckPayload, AckType, ReadReceiptPayload, RelayPolicy, RelayStanding, Router, RoutingAction,
+};
+pub use rpc::{Response, RpcPayload};
+pub use trace::{PendingTraces, TraceHop, TraceHopKind, TracePayload};
 pub use tracker::{MessageTracker, StatusChange};
 pub use runtime::{
-    DeliveredMessage, GossipInput, MetricsSnapshot, ProtocolEvent, ProtocolMetrics, ProtocolRuntime,
-    RuntimeChannels, RuntimeCommand, RuntimeConfig, RuntimeEffect, RuntimeHandle, RuntimeState,
+    AdminRequest, AdminResponse, ChannelPolicy, DeliveredMessage, EventCategory, EventFilter, GossipInput, HookAction, HostedIdentity, MetricsSnapshot, OverflowPolicy, PeerLiveness, ProtocolEvent,
+    ProtocolMetrics, RuntimeChannels, RuntimeCommand, RuntimeConfig, RuntimeEffect, RuntimeHandle,
+    RuntimeHook, RuntimeState, SendOptions, ShutdownReport,
 };
-pub use storage::{StateStore, StateSnapshot};
+#[cfg(feature = "native")]
+pub use runtime::{transport_config_for_profile, ProtocolRuntime};
+#[cfg(all(unix, feature = "native"))]
+pub use runtime::admin_request;
+pub use shared_state::{LwwMap, SharedDoc, SharedStateManager};
+#[cfg(feature = "native")]
+pub use storage::SqliteStateStore;
+pub use storage::{StateSnapshot, StateStore, StorageError};
 pub use types::{now_ms, MessageStatus, MessageType, NodeId};
+pub use typing::{TypingLimiter, TypingPayload};
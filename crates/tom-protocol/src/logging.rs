@@ -0,0 +1,239 @@
+/// Structured logging: envelope spans and an optional JSON layer.
+///
+/// The runtime, router and executor log inside an `envelope` span carrying
+/// the envelope's `envelope_id`, type and endpoints, plus a `message_id`:
+/// the envelope's own id, or for ACKs and read receipts the id of the
+/// message they confirm. Filtering on `message_id` follows one message
+/// through send, relays, ACKs and read receipt, across nodes. The transport
+/// logs its sends and accepted streams in spans keyed by `peer`.
+///
+/// With the `json-log` feature, [`JsonLayer`] writes every log event as a
+/// JSON object, the fields of its enclosing spans included.
+use crate::envelope::Envelope;
+use crate::router::{AckPayload, ReadReceiptPayload};
+use crate::types::MessageType;
+
+/// The id of the message an envelope is about (see the module docs).
+pub fn correlation_id(envelope: &Envelope) -> String {
+    let confirmed = match envelope.msg_type {
+        MessageType::Ack => AckPayload::from_bytes(&envelope.payload)
+            .ok()
+            .map(|ack| ack.original_message_id),
+        MessageType::ReadReceipt => ReadReceiptPayload::from_bytes(&envelope.payload)
+            .ok()
+            .map(|rr| rr.original_message_id),
+        _ => None,
+    };
+    confirmed.unwrap_or_else(|| envelope.id.clone())
+}
+
+/// Debug-level span for work on one envelope. Fields are only computed
+/// when the span is enabled.
+pub fn envelope_span(envelope: &Envelope) -> tracing::Span {
+    tracing::debug_span!(
+        "envelope",
+        envelope_id = %envelope.id,
+        message_id = %correlation_id(envelope),
+        msg_type = ?envelope.msg_type,
+        from = %envelope.from,
+        to = %envelope.to,
+    )
+}
+
+#[cfg(feature = "json-log")]
+pub use json::JsonLayer;
+
+#[cfg(feature = "json-log")]
+mod json {
+    use std::io::Write;
+
+    use serde_json::{Map, Value};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::{Context, Layer};
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// A `tracing_subscriber` layer writing one JSON object per event:
+    /// `timestamp` (ms), `level`, `target`, `message`, the event's `fields`
+    /// and its `spans` (outermost first, each with its `name` and fields).
+    ///
+    /// ```ignore
+    /// use tracing_subscriber::prelude::*;
+    /// tracing_subscriber::registry()
+    ///     .with(tom_protocol::logging::JsonLayer::new(std::io::stderr))
+    ///     .init();
+    /// ```
+    pub struct JsonLayer<W> {
+        make_writer: W,
+    }
+
+    impl<W> JsonLayer<W>
+    where
+        W: for<'w> MakeWriter<'w> + 'static,
+    {
+        pub fn new(make_writer: W) -> Self {
+            Self { make_writer }
+        }
+    }
+
+    /// Recorded fields of a span, kept in its extensions.
+    struct SpanFields(Map<String, Value>);
+
+    struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+    impl Visit for JsonVisitor<'_> {
+        fn record_f64(&mut self, field: &Field, value: f64) {
+            self.0.insert(field.name().into(), value.into());
+        }
+
+        fn record_i64(&mut self, field: &Field, value: i64) {
+            self.0.insert(field.name().into(), value.into());
+        }
+
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.insert(field.name().into(), value.into());
+        }
+
+        fn record_bool(&mut self, field: &Field, value: bool) {
+            self.0.insert(field.name().into(), value.into());
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().into(), value.into());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().into(), format!("{value:?}").into());
+        }
+    }
+
+    impl<S, W> Layer<S> for JsonLayer<W>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        W: for<'w> MakeWriter<'w> + 'static,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = Map::new();
+            attrs.record(&mut JsonVisitor(&mut fields));
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(SpanFields(fields));
+            }
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                if let Some(fields) = span.extensions_mut().get_mut::<SpanFields>() {
+                    values.record(&mut JsonVisitor(&mut fields.0));
+                }
+            }
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let mut fields = Map::new();
+            event.record(&mut JsonVisitor(&mut fields));
+            let message = fields.remove("message").unwrap_or_default();
+
+            let spans: Vec<Value> = ctx
+                .event_scope(event)
+                .into_iter()
+                .flat_map(|scope| scope.from_root())
+                .map(|span| {
+                    let mut entry = Map::new();
+                    entry.insert("name".into(), span.name().into());
+                    if let Some(fields) = span.extensions().get::<SpanFields>() {
+                        entry.extend(fields.0.clone());
+                    }
+                    Value::Object(entry)
+                })
+                .collect();
+
+            let metadata = event.metadata();
+            let mut line = Map::new();
+            line.insert("timestamp".into(), crate::types::now_ms().into());
+            line.insert("level".into(), metadata.level().as_str().into());
+            line.insert("target".into(), metadata.target().into());
+            line.insert("message".into(), message);
+            line.insert("fields".into(), Value::Object(fields));
+            line.insert("spans".into(), Value::Array(spans));
+
+            let mut writer = self.make_writer.make_writer_for(metadata);
+            let _ = writeln!(writer, "{}", Value::Object(line));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::EnvelopeBuilder;
+    use crate::router::AckType;
+    use crate::types::NodeId;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn acks_and_receipts_correlate_to_their_message() {
+        let chat = EnvelopeBuilder::new(node_id(1), node_id(2), MessageType::Chat, b"hi".to_vec()).build();
+        assert_eq!(correlation_id(&chat), chat.id);
+
+        let ack_payload = AckPayload {
+            original_message_id: chat.id.clone(),
+            ack_type: AckType::RecipientReceived,
+        };
+        let ack = EnvelopeBuilder::new(node_id(2), node_id(1), MessageType::Ack, ack_payload.to_bytes()).build();
+        assert_eq!(correlation_id(&ack), chat.id);
+
+        let receipt = ReadReceiptPayload {
+            original_message_id: chat.id.clone(),
+            read_at: 1_000,
+        };
+        let read = EnvelopeBuilder::new(node_id(2), node_id(1), MessageType::ReadReceipt, receipt.to_bytes()).build();
+        assert_eq!(correlation_id(&read), chat.id);
+
+        let garbled = EnvelopeBuilder::new(node_id(2), node_id(1), MessageType::Ack, b"?".to_vec()).build();
+        assert_eq!(correlation_id(&garbled), garbled.id);
+    }
+
+    #[cfg(feature = "json-log")]
+    #[test]
+    fn json_layer_writes_span_fields() {
+        use std::sync::{Arc, Mutex};
+        use tracing_subscriber::prelude::*;
+
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(JsonLayer::new(move || writer.clone()));
+        let chat = EnvelopeBuilder::new(node_id(1), node_id(2), MessageType::Chat, b"hi".to_vec()).build();
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = envelope_span(&chat).entered();
+            tracing::info!(hop = 1u64, "relayed");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["message"], "relayed");
+        assert_eq!(line["fields"]["hop"], 1);
+        assert_eq!(line["spans"][0]["name"], "envelope");
+        assert_eq!(line["spans"][0]["message_id"], chat.id.as_str());
+    }
+}
@@ -0,0 +1,317 @@
+/// Ordered delivery — per-conversation sequence numbers + reorder buffer.
+///
+/// Chat is delivered as envelopes arrive, and retries, redundant paths and
+/// backups can make a peer's messages arrive out of order. With
+/// `RuntimeConfig::ordered_delivery` set, the sender numbers its chat
+/// payloads per recipient and the receiver holds early arrivals until the
+/// missing ones show up.
+///
+/// - The number travels inside the (encrypted) payload, behind a marker
+///   like typed payloads; receivers without ordering just strip it.
+/// - Each sender session picks a random stream id, so a restarted sender
+///   (numbering from 1 again) is recognized as a new stream.
+/// - The first message seen on a stream starts it: a receiver that
+///   restarted mid-conversation doesn't wait for numbers it will never get.
+/// - A gap is given up on once `gap_timeout` passes or more than `window`
+///   messages wait behind it; what was held is released and the gap is
+///   reported. A missing message turning up later is delivered right away.
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::types::NodeId;
+
+/// Wire marker prepended to sequenced payloads (see `payload.rs`: `0xC1`
+/// never starts MessagePack or UTF-8).
+const SEQUENCED_PAYLOAD_MAGIC: [u8; 4] = [0xC1, b'S', b'Q', 1];
+
+/// Marker + stream id + sequence number.
+const HEADER_LEN: usize = SEQUENCED_PAYLOAD_MAGIC.len() + 16;
+
+/// Most senders with a reorder state; idle ones are forgotten past it.
+const MAX_ORDERED_PEERS: usize = 4096;
+
+/// Reorder buffer settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderingConfig {
+    /// Most messages held behind a gap per sender.
+    pub window: usize,
+    /// How long a gap may hold messages back.
+    pub gap_timeout: Duration,
+}
+
+impl Default for OrderingConfig {
+    fn default() -> Self {
+        Self {
+            window: 64,
+            gap_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Position of a message in its sender's stream to one recipient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sequence {
+    pub stream: u64,
+    pub seq: u64,
+}
+
+/// Prefix `payload` with its sequence number.
+pub fn wrap(sequence: Sequence, payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_LEN + payload.len());
+    buf.extend_from_slice(&SEQUENCED_PAYLOAD_MAGIC);
+    buf.extend_from_slice(&sequence.stream.to_be_bytes());
+    buf.extend_from_slice(&sequence.seq.to_be_bytes());
+    buf.extend_from_slice(payload);
+    buf
+}
+
+/// Split a sequenced payload into its number and the original payload.
+/// Unsequenced payloads come back as-is.
+pub fn unwrap(mut payload: Vec<u8>) -> (Option<Sequence>, Vec<u8>) {
+    if payload.len() < HEADER_LEN || !payload.starts_with(&SEQUENCED_PAYLOAD_MAGIC) {
+        return (None, payload);
+    }
+    let field = |at: usize| u64::from_be_bytes(payload[at..at + 8].try_into().expect("8 bytes"));
+    let sequence = Sequence {
+        stream: field(4),
+        seq: field(12),
+    };
+    payload.drain(..HEADER_LEN);
+    (Some(sequence), payload)
+}
+
+/// Sender side: numbers outgoing messages per recipient, from 1.
+#[derive(Debug)]
+pub struct Sequencer {
+    stream: u64,
+    next: HashMap<NodeId, u64>,
+}
+
+impl Sequencer {
+    pub fn new() -> Self {
+        use chacha20poly1305::aead::rand_core::{OsRng, RngCore};
+        Self {
+            stream: OsRng.next_u64(),
+            next: HashMap::new(),
+        }
+    }
+
+    pub fn next(&mut self, to: NodeId) -> Sequence {
+        let next = self.next.entry(to).or_insert(1);
+        let seq = *next;
+        *next += 1;
+        Sequence {
+            stream: self.stream,
+            seq,
+        }
+    }
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Messages a sender skipped: `first..first + count` never arrived in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub from: NodeId,
+    pub first: u64,
+    pub count: u64,
+}
+
+/// What a push or a tick lets through, in delivery order.
+#[derive(Debug)]
+pub struct Released<T> {
+    pub ready: Vec<T>,
+    pub gaps: Vec<Gap>,
+}
+
+impl<T> Default for Released<T> {
+    fn default() -> Self {
+        Self {
+            ready: Vec::new(),
+            gaps: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PeerStream<T> {
+    stream: u64,
+    /// Next sequence number to deliver.
+    next: u64,
+    /// Early arrivals: seq → (item, arrived at unix ms).
+    held: BTreeMap<u64, (T, u64)>,
+}
+
+impl<T> PeerStream<T> {
+    /// Deliver the run of held items starting at `next`.
+    fn drain_ready(&mut self, ready: &mut Vec<T>) {
+        while let Some((item, _)) = self.held.remove(&self.next) {
+            ready.push(item);
+            self.next += 1;
+        }
+    }
+
+    /// Give up on the gap before the oldest held item.
+    fn skip_gap(&mut self, from: NodeId, released: &mut Released<T>) {
+        let Some(&first_held) = self.held.keys().next() else {
+            return;
+        };
+        released.gaps.push(Gap {
+            from,
+            first: self.next,
+            count: first_held - self.next,
+        });
+        self.next = first_held;
+        self.drain_ready(&mut released.ready);
+    }
+}
+
+/// Receiver side: holds each sender's early arrivals until their turn.
+#[derive(Debug)]
+pub struct ReorderBuffer<T> {
+    config: OrderingConfig,
+    peers: HashMap<NodeId, PeerStream<T>>,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub fn new(config: OrderingConfig) -> Self {
+        Self {
+            config,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Take a message numbered `sequence` from `from`; returns what can be
+    /// delivered now, in order.
+    pub fn push(&mut self, from: NodeId, sequence: Sequence, item: T, now: u64) -> Released<T> {
+        let mut released = Released::default();
+        if !self.peers.contains_key(&from) && self.peers.len() >= MAX_ORDERED_PEERS {
+            let idle = self.peers.iter().find(|(_, p)| p.held.is_empty()).map(|(id, _)| *id);
+            match idle {
+                Some(idle) => {
+                    self.peers.remove(&idle);
+                }
+                None => {
+                    released.ready.push(item);
+                    return released;
+                }
+            }
+        }
+        let peer = self.peers.entry(from).or_insert_with(|| PeerStream {
+            stream: sequence.stream,
+            next: sequence.seq,
+            held: BTreeMap::new(),
+        });
+        if peer.stream != sequence.stream {
+            // The sender restarted: flush the old stream, start the new one
+            released.ready.extend(std::mem::take(&mut peer.held).into_values().map(|(item, _)| item));
+            peer.stream = sequence.stream;
+            peer.next = sequence.seq;
+        }
+
+        if sequence.seq < peer.next {
+            // Late: its gap was already given up on
+            released.ready.push(item);
+            return released;
+        }
+        peer.held.entry(sequence.seq).or_insert((item, now));
+        peer.drain_ready(&mut released.ready);
+        while peer.held.len() > self.config.window {
+            peer.skip_gap(from, &mut released);
+        }
+        released
+    }
+
+    /// Give up on gaps that held messages back longer than `gap_timeout`.
+    pub fn tick(&mut self, now: u64) -> Released<T> {
+        let timeout = self.config.gap_timeout.as_millis() as u64;
+        let mut released = Released::default();
+        for (from, peer) in &mut self.peers {
+            while peer
+                .held
+                .values()
+                .map(|(_, arrived)| *arrived)
+                .min()
+                .is_some_and(|oldest| now.saturating_sub(oldest) >= timeout)
+            {
+                peer.skip_gap(*from, &mut released);
+            }
+        }
+        released
+    }
+
+    /// Messages currently held back.
+    pub fn held(&self) -> usize {
+        self.peers.values().map(|p| p.held.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    fn seq(seq: u64) -> Sequence {
+        Sequence { stream: 7, seq }
+    }
+
+    #[test]
+    fn wrap_roundtrip_and_passthrough() {
+        let wrapped = wrap(seq(42), b"hello");
+        assert_eq!(unwrap(wrapped), (Some(seq(42)), b"hello".to_vec()));
+        assert_eq!(unwrap(b"plain".to_vec()), (None, b"plain".to_vec()));
+
+        let mut sequencer = Sequencer::new();
+        let (a, b) = (node_id(1), node_id(2));
+        assert_eq!(sequencer.next(a).seq, 1);
+        assert_eq!(sequencer.next(a).seq, 2);
+        assert_eq!(sequencer.next(b).seq, 1);
+    }
+
+    #[test]
+    fn reorders_and_reports_gaps() {
+        let from = node_id(1);
+        let config = OrderingConfig {
+            window: 3,
+            gap_timeout: Duration::from_secs(5),
+        };
+        let mut buffer = ReorderBuffer::new(config);
+
+        // 1 starts the stream; 3 waits for 2
+        assert_eq!(buffer.push(from, seq(1), 1, 0).ready, [1]);
+        assert!(buffer.push(from, seq(3), 3, 0).ready.is_empty());
+        assert_eq!(buffer.push(from, seq(2), 2, 0).ready, [2, 3]);
+
+        // 5 waits for 4 until the timeout, then 4 arrives late
+        assert!(buffer.push(from, seq(5), 5, 1_000).ready.is_empty());
+        assert!(buffer.tick(5_999).ready.is_empty());
+        let released = buffer.tick(6_000);
+        assert_eq!(released.ready, [5]);
+        assert_eq!(released.gaps, [Gap { from, first: 4, count: 1 }]);
+        assert_eq!(buffer.push(from, seq(4), 4, 6_001).ready, [4]);
+
+        // Past the window the gap is skipped at once
+        for n in 8..=10 {
+            assert!(buffer.push(from, seq(n), n, 7_000).ready.is_empty());
+        }
+        let released = buffer.push(from, seq(11), 11, 7_000);
+        assert_eq!(released.ready, [8, 9, 10, 11]);
+        assert_eq!(released.gaps, [Gap { from, first: 6, count: 2 }]);
+
+        // A restarted sender starts a new stream
+        let restarted = Sequence { stream: 8, seq: 1 };
+        assert!(buffer.push(from, seq(13), 13, 8_000).ready.is_empty());
+        assert_eq!(buffer.push(from, restarted, 1, 8_000).ready, [13, 1]);
+        assert_eq!(buffer.held(), 0);
+    }
+}
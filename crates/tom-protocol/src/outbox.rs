@@ -0,0 +1,122 @@
+/// Offline outbox — chats composed while the node has no connectivity.
+///
+/// Sending to the transport while fully offline can only fail, and the
+/// fallback (backing the message up with ourselves) has nowhere to
+/// replicate it. Instead the signed envelope is queued, written through to
+/// the state store, and sent verbatim — same id — oldest first once
+/// connectivity returns. Its status goes Queued → Sent at that point.
+///
+/// Pure state: the runtime writes through to the store on every change.
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::NodeId;
+
+/// Most messages held while offline; beyond it, sends go out as usual.
+pub const OFFLINE_OUTBOX_CAPACITY: usize = 1_000;
+
+/// A message waiting for connectivity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    pub message_id: String,
+    pub to: NodeId,
+    /// Serialized signed envelope (sent verbatim so the id never changes).
+    pub envelope: Vec<u8>,
+    /// Backup TTL requested by the sender (None = holders' default).
+    pub backup_ttl_ms: Option<u64>,
+    pub queued_ms: u64,
+}
+
+/// Sender-side queue of messages composed while offline, oldest first.
+#[derive(Debug, Default)]
+pub struct OfflineOutbox {
+    queue: VecDeque<QueuedMessage>,
+}
+
+impl OfflineOutbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a message. Returns false (and drops nothing) when full.
+    pub fn push(&mut self, message: QueuedMessage) -> bool {
+        if self.queue.len() >= OFFLINE_OUTBOX_CAPACITY {
+            return false;
+        }
+        self.queue.push_back(message);
+        true
+    }
+
+    /// Take every queued message, oldest first.
+    pub fn drain(&mut self) -> Vec<QueuedMessage> {
+        self.queue.drain(..).collect()
+    }
+
+    /// Queued messages, oldest first.
+    pub fn messages(&self) -> impl Iterator<Item = &QueuedMessage> {
+        self.queue.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Restore persisted messages (flushed with the next reconnect).
+    pub fn restore(&mut self, mut messages: Vec<QueuedMessage>) {
+        messages.sort_by(|a, b| a.queued_ms.cmp(&b.queued_ms).then_with(|| a.message_id.cmp(&b.message_id)));
+        for message in messages {
+            if !self.push(message) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    fn queued(id: &str, queued_ms: u64) -> QueuedMessage {
+        QueuedMessage {
+            message_id: id.into(),
+            to: node_id(2),
+            envelope: vec![1],
+            backup_ttl_ms: None,
+            queued_ms,
+        }
+    }
+
+    #[test]
+    fn drains_oldest_first_and_restores_in_order() {
+        let mut outbox = OfflineOutbox::new();
+        outbox.restore(vec![queued("b", 2000), queued("a", 1000)]);
+        assert!(outbox.push(queued("c", 3000)));
+        assert_eq!(outbox.len(), 3);
+
+        let ids: Vec<String> = outbox.drain().into_iter().map(|m| m.message_id).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn refuses_beyond_capacity() {
+        let mut outbox = OfflineOutbox::new();
+        for i in 0..OFFLINE_OUTBOX_CAPACITY {
+            assert!(outbox.push(queued(&format!("m{i}"), i as u64)));
+        }
+        assert!(!outbox.push(queued("overflow", 0)));
+        assert_eq!(outbox.len(), OFFLINE_OUTBOX_CAPACITY);
+    }
+}
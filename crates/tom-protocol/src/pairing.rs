@@ -0,0 +1,376 @@
+/// Pairing — add a peer without copying a 64-hex NodeId.
+///
+/// Two flows, both ending in the peer's address being injected into the
+/// transport (like `add_peer_addr`):
+///
+/// - **Pairing string / QR**: [`PairingInfo`] packs NodeId + relay URL +
+///   direct addresses into compact bytes (the QR payload) or a `TOM1:`
+///   string. The string is base32 uppercase, which QR codes store in their
+///   dense alphanumeric mode.
+/// - **Rendezvous code**: an 8-digit [`RendezvousCode`] someone can read
+///   out loud. The code and the current time window derive a throwaway
+///   DHT key; the inviter publishes a [`PairingRecord`] under it and the
+///   invitee derives the same key to fetch it. Codes work for the window
+///   they were created in and the next one.
+///
+/// A record is signed by the inviter's identity key, so a code can't be
+/// used to pass off another node's NodeId — but anyone holding the code
+/// could race the inviter with their *own* record. Check the contact
+/// fingerprint after pairing when that matters.
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::types::NodeId;
+use crate::TomProtocolError;
+
+/// Prefix of an encoded pairing string (uppercase for QR alphanumeric mode).
+pub const PAIRING_PREFIX: &str = "TOM1:";
+
+/// Digits in a rendezvous code.
+pub const RENDEZVOUS_DIGITS: usize = 8;
+
+/// Length of a rendezvous time window; a code lives one to two windows.
+pub const RENDEZVOUS_WINDOW_MS: u64 = 10 * 60 * 1000;
+
+/// Most direct addresses carried (keeps QR codes scannable).
+pub const MAX_PAIRING_ADDRS: usize = 4;
+
+/// Domain separator for rendezvous key derivation.
+const RENDEZVOUS_DOMAIN: &[u8] = b"tom-pairing-rendezvous-v1";
+
+/// Domain separator for pairing record signatures.
+const RECORD_DOMAIN: &[u8] = b"tom-pairing-record-v1";
+
+fn invalid(reason: impl Into<String>) -> TomProtocolError {
+    TomProtocolError::InvalidPairing {
+        reason: reason.into(),
+    }
+}
+
+// ── Pairing info ─────────────────────────────────────────────────────────
+
+/// Everything needed to reach a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairingInfo {
+    pub node_id: NodeId,
+    pub relay_url: Option<String>,
+    pub direct_addrs: Vec<String>,
+}
+
+/// Wire form after the raw 32-byte NodeId.
+#[derive(Serialize, Deserialize)]
+struct PairingTail(Option<String>, Vec<String>);
+
+impl PairingInfo {
+    /// Build pairing info, keeping at most `MAX_PAIRING_ADDRS` direct addresses.
+    pub fn new(node_id: NodeId, relay_url: Option<String>, mut direct_addrs: Vec<String>) -> Self {
+        direct_addrs.truncate(MAX_PAIRING_ADDRS);
+        Self {
+            node_id,
+            relay_url,
+            direct_addrs,
+        }
+    }
+
+    /// Compact bytes: raw NodeId, then MessagePack `(relay_url, direct_addrs)`.
+    /// Suitable as a binary QR payload.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TomProtocolError> {
+        let mut bytes = self.node_id.as_bytes().to_vec();
+        bytes.extend(rmp_serde::to_vec(&PairingTail(
+            self.relay_url.clone(),
+            self.direct_addrs.clone(),
+        ))?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TomProtocolError> {
+        if bytes.len() < 32 {
+            return Err(invalid("payload too short"));
+        }
+        let (id, tail) = bytes.split_at(32);
+        let id: [u8; 32] = id.try_into().expect("split at 32");
+        let endpoint_id =
+            tom_base::EndpointId::from_bytes(&id).map_err(|_| invalid("bad node id"))?;
+        let PairingTail(relay_url, direct_addrs) = rmp_serde::from_slice(tail)?;
+        Ok(Self::new(
+            NodeId::from_endpoint_id(endpoint_id),
+            relay_url,
+            direct_addrs,
+        ))
+    }
+
+    /// Encode as a `TOM1:<base32>` string (for QR codes or copy/paste).
+    pub fn encode(&self) -> Result<String, TomProtocolError> {
+        Ok(format!(
+            "{PAIRING_PREFIX}{}",
+            data_encoding::BASE32_NOPAD.encode(&self.to_bytes()?)
+        ))
+    }
+
+    /// Decode a string produced by [`PairingInfo::encode`] (case-insensitive).
+    pub fn decode(s: &str) -> Result<Self, TomProtocolError> {
+        let s = s.trim().to_ascii_uppercase();
+        let body = s
+            .strip_prefix(PAIRING_PREFIX)
+            .ok_or_else(|| invalid("missing prefix"))?;
+        let bytes = data_encoding::BASE32_NOPAD
+            .decode(body.as_bytes())
+            .map_err(|e| invalid(format!("bad encoding: {e}")))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Transport address to inject (unparseable entries are skipped).
+    pub fn to_endpoint_addr(&self) -> tom_base::EndpointAddr {
+        use tom_base::TransportAddr;
+
+        let mut addrs = std::collections::BTreeSet::new();
+        if let Some(url) = self
+            .relay_url
+            .as_ref()
+            .and_then(|u| u.parse::<tom_base::RelayUrl>().ok())
+        {
+            addrs.insert(TransportAddr::Relay(url));
+        }
+        for direct in &self.direct_addrs {
+            if let Ok(sa) = direct.parse::<std::net::SocketAddr>() {
+                addrs.insert(TransportAddr::Ip(sa));
+            }
+        }
+        tom_base::EndpointAddr {
+            id: *self.node_id.as_endpoint_id(),
+            addrs,
+        }
+    }
+}
+
+// ── Rendezvous codes ─────────────────────────────────────────────────────
+
+/// A short numeric code, shown as `1234-5678`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RendezvousCode(String);
+
+impl RendezvousCode {
+    /// A fresh random code.
+    pub fn generate() -> Self {
+        let random = u64::from_le_bytes(
+            uuid::Uuid::new_v4().as_bytes()[..8]
+                .try_into()
+                .expect("8 bytes"),
+        );
+        Self(format!(
+            "{:0width$}",
+            random % 10u64.pow(RENDEZVOUS_DIGITS as u32),
+            width = RENDEZVOUS_DIGITS
+        ))
+    }
+
+    /// Parse user input, ignoring spaces and dashes.
+    pub fn parse(s: &str) -> Result<Self, TomProtocolError> {
+        let digits: String = s.chars().filter(|c| !matches!(c, ' ' | '-')).collect();
+        if digits.len() != RENDEZVOUS_DIGITS || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid(format!("code must be {RENDEZVOUS_DIGITS} digits")));
+        }
+        Ok(Self(digits))
+    }
+
+    /// Secret seed of the DHT key for this code in `window`.
+    pub fn seed(&self, window: u64) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(RENDEZVOUS_DOMAIN);
+        hasher.update(self.0.as_bytes());
+        hasher.update(window.to_le_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Public DHT key for this code in `window`.
+    pub fn public_key(&self, window: u64) -> [u8; 32] {
+        SigningKey::from_bytes(&self.seed(window))
+            .verifying_key()
+            .to_bytes()
+    }
+}
+
+impl std::fmt::Display for RendezvousCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (a, b) = self.0.split_at(RENDEZVOUS_DIGITS / 2);
+        write!(f, "{a}-{b}")
+    }
+}
+
+/// Rendezvous window containing `now`.
+pub fn rendezvous_window(now: u64) -> u64 {
+    now / RENDEZVOUS_WINDOW_MS
+}
+
+/// A code handed to the user, with when it stops working.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairingCode {
+    pub code: RendezvousCode,
+    /// Window the record was published in.
+    pub window: u64,
+    /// Unix ms after which redeemers no longer look for it.
+    pub expires_at: u64,
+}
+
+impl PairingCode {
+    /// A new code for the window containing `now`.
+    pub fn new(now: u64) -> Self {
+        let window = rendezvous_window(now);
+        Self {
+            code: RendezvousCode::generate(),
+            window,
+            expires_at: (window + 2) * RENDEZVOUS_WINDOW_MS,
+        }
+    }
+}
+
+// ── Pairing records ──────────────────────────────────────────────────────
+
+/// What the inviter publishes under a rendezvous key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairingRecord {
+    /// [`PairingInfo::to_bytes`] of the inviter.
+    pub info: Vec<u8>,
+    pub published_at: u64,
+    /// Inviter's identity signature over info + timestamp.
+    pub signature: Vec<u8>,
+}
+
+impl PairingRecord {
+    pub fn new(
+        info: &PairingInfo,
+        published_at: u64,
+        secret_seed: &[u8; 32],
+    ) -> Result<Self, TomProtocolError> {
+        let info = info.to_bytes()?;
+        let signature = SigningKey::from_bytes(secret_seed)
+            .sign(&Self::signing_bytes(&info, published_at))
+            .to_bytes()
+            .to_vec();
+        Ok(Self {
+            info,
+            published_at,
+            signature,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TomProtocolError> {
+        Ok(rmp_serde::to_vec(self)?)
+    }
+
+    /// Parse a record fetched from the DHT and check it: signed by the
+    /// NodeId it carries, and published within the code's lifetime.
+    pub fn open(bytes: &[u8], now: u64) -> Result<PairingInfo, TomProtocolError> {
+        let record: Self = rmp_serde::from_slice(bytes)?;
+        let info = PairingInfo::from_bytes(&record.info)?;
+        let age = now.saturating_sub(record.published_at);
+        if record.published_at > now + crate::discovery::MAX_FUTURE_DRIFT_MS
+            || age > 2 * RENDEZVOUS_WINDOW_MS
+        {
+            return Err(invalid("record expired"));
+        }
+        let key = VerifyingKey::from_bytes(&info.node_id.as_bytes())
+            .map_err(|_| TomProtocolError::InvalidSignature)?;
+        let sig: [u8; 64] = record
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| TomProtocolError::InvalidSignature)?;
+        key.verify(
+            &Self::signing_bytes(&record.info, record.published_at),
+            &Signature::from_bytes(&sig),
+        )
+        .map_err(|_| TomProtocolError::InvalidSignature)?;
+        Ok(info)
+    }
+
+    fn signing_bytes(info: &[u8], published_at: u64) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(RECORD_DOMAIN.len() + info.len() + 8);
+        bytes.extend_from_slice(RECORD_DOMAIN);
+        bytes.extend_from_slice(info);
+        bytes.extend_from_slice(&published_at.to_le_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(seed: u64) -> (NodeId, [u8; 32]) {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        (secret.public().to_string().parse().unwrap(), secret.to_bytes())
+    }
+
+    fn info(seed: u64) -> PairingInfo {
+        PairingInfo::new(
+            identity(seed).0,
+            Some("https://relay.example.com./".into()),
+            vec!["192.168.1.20:3340".into(), "[2001:db8::1]:3340".into()],
+        )
+    }
+
+    #[test]
+    fn pairing_string_roundtrip_is_compact_and_qr_friendly() {
+        let info = info(1);
+        let encoded = info.encode().unwrap();
+        assert!(encoded.starts_with(PAIRING_PREFIX));
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == ':'));
+
+        // Bare id fits in fewer characters than its hex form
+        let bare = PairingInfo::new(info.node_id, None, vec![]).encode().unwrap();
+        assert!(bare.len() < info.node_id.to_string().len(), "{bare}");
+
+        assert_eq!(PairingInfo::decode(&encoded.to_lowercase()).unwrap(), info);
+        assert_eq!(PairingInfo::from_bytes(&info.to_bytes().unwrap()).unwrap(), info);
+
+        let addr = info.to_endpoint_addr();
+        assert_eq!(addr.addrs.len(), 3);
+    }
+
+    #[test]
+    fn decode_rejects_garbage() {
+        assert!(PairingInfo::decode("hello").is_err());
+        assert!(PairingInfo::decode("TOM1:!!!").is_err());
+        assert!(PairingInfo::from_bytes(&[0u8; 8]).is_err());
+    }
+
+    #[test]
+    fn rendezvous_codes_parse_and_derive_per_window_keys() {
+        let code = RendezvousCode::generate();
+        assert_eq!(code.to_string().len(), RENDEZVOUS_DIGITS + 1);
+        assert_eq!(RendezvousCode::parse(&code.to_string()).unwrap(), code);
+        assert_eq!(RendezvousCode::parse(" 1234 5678 ").unwrap().to_string(), "1234-5678");
+        assert!(RendezvousCode::parse("1234-567").is_err());
+        assert!(RendezvousCode::parse("1234-567a").is_err());
+
+        assert_eq!(code.public_key(7), code.public_key(7));
+        assert_ne!(code.public_key(7), code.public_key(8));
+        let other = RendezvousCode::parse("0000-0001").unwrap();
+        assert_ne!(code.public_key(7), other.public_key(7));
+    }
+
+    #[test]
+    fn record_is_signed_by_the_paired_node_and_expires() {
+        let (node_id, secret) = identity(1);
+        let info = PairingInfo::new(node_id, None, vec![]);
+        let now = 10 * RENDEZVOUS_WINDOW_MS;
+        let bytes = PairingRecord::new(&info, now, &secret).unwrap().to_bytes().unwrap();
+
+        assert_eq!(PairingRecord::open(&bytes, now + 1_000).unwrap(), info);
+        assert!(PairingRecord::open(&bytes, now + 2 * RENDEZVOUS_WINDOW_MS + 1).is_err());
+
+        // Someone else's key can't vouch for this NodeId
+        let (_, mallory) = identity(2);
+        let forged = PairingRecord::new(&info, now, &mallory).unwrap().to_bytes().unwrap();
+        assert!(matches!(
+            PairingRecord::open(&forged, now),
+            Err(TomProtocolError::InvalidSignature)
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+/// Typed application payloads — content-type tagging + schema registry.
+///
+/// Chat payloads used to be opaque bytes: some apps sent raw UTF-8, others
+/// JSON blobs, and receivers had to guess. A [`TypedPayload`] wraps a
+/// MessagePack body with a content-type string, and a [`PayloadRegistry`]
+/// lists the content types this node understands. The runtime unwraps
+/// typed payloads on delivery and politely rejects unknown ones instead of
+/// handing garbage to the application.
+///
+/// Untagged (legacy) payloads are still delivered as-is, with no content type.
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::TomProtocolError;
+
+/// Wire marker prepended to typed payloads.
+///
+/// `0xC1` is never used in MessagePack and is not a valid UTF-8 lead byte,
+/// so legacy text or msgpack payloads can't be mistaken for typed ones.
+const TYPED_PAYLOAD_MAGIC: [u8; 4] = [0xC1, b'T', b'P', 1];
+
+/// Content type of the built-in [`TextPayload`].
+pub const CONTENT_TYPE_TEXT: &str = "text/plain";
+
+/// An application payload type with a stable content-type identifier.
+///
+/// Content types should be namespaced (e.g. `"myapp/poll"`) to avoid clashes.
+pub trait PayloadSchema: Serialize + DeserializeOwned {
+    const CONTENT_TYPE: &'static str;
+}
+
+/// Built-in plain text payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextPayload {
+    pub text: String,
+}
+
+impl PayloadSchema for TextPayload {
+    const CONTENT_TYPE: &'static str = CONTENT_TYPE_TEXT;
+}
+
+/// A payload tagged with its content type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypedPayload {
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl TypedPayload {
+    /// Serialize `value` under its schema's content type.
+    pub fn encode<T: PayloadSchema>(value: &T) -> Result<Self, TomProtocolError> {
+        Ok(Self {
+            content_type: T::CONTENT_TYPE.to_string(),
+            body: rmp_serde::to_vec(value)?,
+        })
+    }
+
+    /// Decode the body as `T`, checking the content type matches.
+    pub fn decode<T: PayloadSchema>(&self) -> Result<T, TomProtocolError> {
+        decode_body::<T>(&self.content_type, &self.body)
+    }
+
+    /// Whether raw payload bytes carry the typed-payload marker.
+    pub fn is_typed(bytes: &[u8]) -> bool {
+        bytes.starts_with(&TYPED_PAYLOAD_MAGIC)
+    }
+
+    /// Serialize to wire bytes (marker + MessagePack).
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TomProtocolError> {
+        let mut buf = TYPED_PAYLOAD_MAGIC.to_vec();
+        buf.extend(rmp_serde::to_vec(self)?);
+        Ok(buf)
+    }
+
+    /// Parse wire bytes produced by [`TypedPayload::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TomProtocolError> {
+        let body = bytes.strip_prefix(&TYPED_PAYLOAD_MAGIC).ok_or_else(|| {
+            TomProtocolError::Deserialization("missing typed payload marker".into())
+        })?;
+        Ok(rmp_serde::from_slice(body)?)
+    }
+}
+
+/// Decode a typed body as `T`, checking the content type matches.
+pub(crate) fn decode_body<T: PayloadSchema>(
+    content_type: &str,
+    body: &[u8],
+) -> Result<T, TomProtocolError> {
+    if content_type != T::CONTENT_TYPE {
+        return Err(TomProtocolError::Deserialization(format!(
+            "expected content type {}, got {content_type}",
+            T::CONTENT_TYPE
+        )));
+    }
+    Ok(rmp_serde::from_slice(body)?)
+}
+
+/// Validate a body against schema `T` (monomorphized into the registry).
+fn validate_as<T: PayloadSchema>(body: &[u8]) -> bool {
+    rmp_serde::from_slice::<T>(body).is_ok()
+}
+
+/// Content types this node accepts, with a body validator for each.
+///
+/// The default registry knows [`TextPayload`] and
+/// [`ContactCard`](crate::contact::ContactCard).
+#[derive(Debug, Clone)]
+pub struct PayloadRegistry {
+    schemas: HashMap<String, fn(&[u8]) -> bool>,
+}
+
+impl Default for PayloadRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register::<TextPayload>();
+        registry.register::<crate::contact::ContactCard>();
+        registry
+    }
+}
+
+impl PayloadRegistry {
+    /// A registry with no content types at all.
+    pub fn empty() -> Self {
+        Self {
+            schemas: HashMap::new(),
+        }
+    }
+
+    /// Declare a payload schema. Re-registering a content type replaces it.
+    pub fn register<T: PayloadSchema>(&mut self) -> &mut Self {
+        self.schemas
+            .insert(T::CONTENT_TYPE.to_string(), validate_as::<T>);
+        self
+    }
+
+    /// Whether a content type is registered.
+    pub fn contains(&self, content_type: &str) -> bool {
+        self.schemas.contains_key(content_type)
+    }
+
+    /// Registered content types.
+    pub fn content_types(&self) -> impl Iterator<Item = &str> {
+        self.schemas.keys().map(String::as_str)
+    }
+
+    /// Check a typed payload is registered and its body matches the schema.
+    pub fn validate(&self, payload: &TypedPayload) -> Result<(), TomProtocolError> {
+        let Some(validate) = self.schemas.get(&payload.content_type) else {
+            return Err(TomProtocolError::UnknownContentType {
+                content_type: payload.content_type.clone(),
+            });
+        };
+        if !validate(&payload.body) {
+            return Err(TomProtocolError::Deserialization(format!(
+                "payload does not match schema {}",
+                payload.content_type
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Poll {
+        question: String,
+        options: Vec<String>,
+    }
+
+    impl PayloadSchema for Poll {
+        const CONTENT_TYPE: &'static str = "test/poll";
+    }
+
+    #[test]
+    fn typed_payload_wire_roundtrip() {
+        let poll = Poll {
+            question: "Lunch?".into(),
+            options: vec!["yes".into(), "no".into()],
+        };
+        let typed = TypedPayload::encode(&poll).unwrap();
+        let bytes = typed.to_bytes().unwrap();
+        assert!(TypedPayload::is_typed(&bytes));
+
+        let parsed = TypedPayload::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.content_type, "test/poll");
+        assert_eq!(parsed.decode::<Poll>().unwrap(), poll);
+    }
+
+    #[test]
+    fn legacy_bytes_are_not_typed() {
+        assert!(!TypedPayload::is_typed(b"hello"));
+        assert!(!TypedPayload::is_typed(&rmp_serde::to_vec("hello").unwrap()));
+        assert!(TypedPayload::from_bytes(b"hello").is_err());
+    }
+
+    #[test]
+    fn decode_rejects_wrong_content_type() {
+        let typed = TypedPayload::encode(&TextPayload { text: "hi".into() }).unwrap();
+        assert!(typed.decode::<Poll>().is_err());
+    }
+
+    #[test]
+    fn registry_rejects_unknown_and_malformed() {
+        let mut registry = PayloadRegistry::default();
+        let poll = TypedPayload::encode(&Poll {
+            question: "?".into(),
+            options: vec![],
+        })
+        .unwrap();
+        assert!(matches!(
+            registry.validate(&poll),
+            Err(TomProtocolError::UnknownContentType { .. })
+        ));
+
+        registry.register::<Poll>();
+        assert!(registry.validate(&poll).is_ok());
+
+        let bad = TypedPayload {
+            content_type: "test/poll".into(),
+            body: vec![0xFF, 0x00],
+        };
+        assert!(matches!(
+            registry.validate(&bad),
+            Err(TomProtocolError::Deserialization(_))
+        ));
+    }
+
+    #[test]
+    fn default_registry_knows_text() {
+        let registry = PayloadRegistry::default();
+        assert!(registry.contains(CONTENT_TYPE_TEXT));
+        assert!(registry.contains(crate::contact::CONTENT_TYPE_CONTACT_CARD));
+        assert!(!PayloadRegistry::empty().contains(CONTENT_TYPE_TEXT));
+    }
+}
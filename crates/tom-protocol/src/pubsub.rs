@@ -0,0 +1,423 @@
+/// Pub/sub channels — application broadcasts over gossip topics.
+///
+/// A channel is a name (optionally plus a join secret) hashed into a gossip
+/// `TopicId`. Anyone who knows the name — and the secret, for private
+/// channels — can subscribe and publish small signed payloads. Unlike
+/// groups there is no hub and no membership list: this is for sensor feeds,
+/// presence and status broadcasts, not conversations.
+///
+/// Pure state: the runtime loop owns the gossip subscriptions and feeds
+/// received bytes back through [`ChannelRegistry::accept`].
+use std::collections::{HashMap, VecDeque};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::types::NodeId;
+use crate::TomProtocolError;
+
+/// Max payload bytes per publication (channels are for small messages).
+pub const MAX_CHANNEL_PAYLOAD: usize = 4096;
+
+/// Max channel name length in bytes.
+pub const MAX_CHANNEL_NAME_LEN: usize = 64;
+
+/// Max age of a publication before it's dropped as stale/replayed (5 min).
+pub const CHANNEL_MAX_AGE_MS: u64 = 5 * 60 * 1000;
+
+/// How far ahead of our clock a publication's `sent_at` may be (30s).
+pub const CHANNEL_MAX_FUTURE_MS: u64 = 30 * 1000;
+
+/// Signatures remembered per channel to drop replays within the age window.
+pub const MAX_SEEN_PER_CHANNEL: usize = 1024;
+
+const CHANNEL_TOPIC_DOMAIN: &[u8] = b"tom-pubsub-topic-v1";
+const CHANNEL_AUTH_DOMAIN: &[u8] = b"tom-pubsub-auth-v1";
+const CHANNEL_SIGN_DOMAIN: &[u8] = b"tom-pubsub-sign-v1";
+
+/// Derive the gossip topic for a channel.
+///
+/// With a secret, the topic can't be guessed from the name alone.
+pub fn channel_topic(name: &str, secret: Option<&[u8]>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(CHANNEL_TOPIC_DOMAIN);
+    hasher.update((name.len() as u32).to_le_bytes());
+    hasher.update(name.as_bytes());
+    if let Some(secret) = secret {
+        hasher.update(secret);
+    }
+    hasher.finalize().into()
+}
+
+/// Check a channel name is usable.
+pub fn validate_channel_name(name: &str) -> Result<(), TomProtocolError> {
+    if name.is_empty() || name.len() > MAX_CHANNEL_NAME_LEN {
+        return Err(TomProtocolError::InvalidChannel {
+            reason: format!("name must be 1..={MAX_CHANNEL_NAME_LEN} bytes"),
+        });
+    }
+    Ok(())
+}
+
+/// One message published on a channel (wire format, MessagePack).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelPublication {
+    pub channel: String,
+    pub from: NodeId,
+    pub payload: Vec<u8>,
+    pub sent_at: u64,
+    /// Proof of knowing the join secret (private channels only).
+    pub auth_tag: Option<[u8; 32]>,
+    /// Ed25519 signature by `from` over `signing_bytes()`.
+    pub signature: Vec<u8>,
+}
+
+impl ChannelPublication {
+    /// Deterministic bytes covered by the signature and the auth tag.
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(CHANNEL_SIGN_DOMAIN);
+        buf.extend_from_slice(&(self.channel.len() as u32).to_le_bytes());
+        buf.extend_from_slice(self.channel.as_bytes());
+        buf.extend_from_slice(&self.from.as_bytes());
+        buf.extend_from_slice(&self.sent_at.to_le_bytes());
+        buf.extend_from_slice(&self.payload);
+        buf
+    }
+
+    fn compute_auth_tag(&self, secret: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(CHANNEL_AUTH_DOMAIN);
+        hasher.update((secret.len() as u32).to_le_bytes());
+        hasher.update(secret);
+        hasher.update(self.signing_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Verify the publisher signature against `from`.
+    pub fn verify_signature(&self) -> bool {
+        let Ok(verifying_key) = VerifyingKey::from_bytes(&self.from.as_bytes()) else {
+            return false;
+        };
+        let Ok(sig_array): Result<&[u8; 64], _> = self.signature.as_slice().try_into() else {
+            return false;
+        };
+        let sig = Signature::from_bytes(sig_array);
+        verifying_key.verify(&self.signing_bytes(), &sig).is_ok()
+    }
+}
+
+/// A channel we're subscribed to.
+#[derive(Debug, Clone)]
+struct JoinedChannel {
+    topic: [u8; 32],
+    secret: Option<Vec<u8>>,
+    /// Signatures of accepted publications with their `sent_at`, bounded
+    /// by age and by [`MAX_SEEN_PER_CHANNEL`].
+    seen: VecDeque<(u64, Vec<u8>)>,
+}
+
+impl JoinedChannel {
+    fn new(topic: [u8; 32], secret: Option<Vec<u8>>) -> Self {
+        Self {
+            topic,
+            secret,
+            seen: VecDeque::new(),
+        }
+    }
+
+    /// Record a signature; `false` if it was already seen (a replay).
+    fn remember(&mut self, sent_at: u64, signature: &[u8], now_ms: u64) -> bool {
+        let horizon = now_ms.saturating_sub(CHANNEL_MAX_AGE_MS);
+        self.seen.retain(|(at, _)| *at >= horizon);
+        if self.seen.iter().any(|(_, sig)| sig == signature) {
+            return false;
+        }
+        if self.seen.len() >= MAX_SEEN_PER_CHANNEL {
+            // Evict the oldest publication; it's the next to go stale anyway
+            if let Some(oldest) = (0..self.seen.len()).min_by_key(|&i| self.seen[i].0) {
+                self.seen.remove(oldest);
+            }
+        }
+        self.seen.push_back((sent_at, signature.to_vec()));
+        true
+    }
+}
+
+/// Channels this node has joined, keyed by name.
+#[derive(Debug, Default)]
+pub struct ChannelRegistry {
+    channels: HashMap<String, JoinedChannel>,
+}
+
+impl ChannelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Join a channel. Returns its gossip topic.
+    ///
+    /// Re-joining with another secret replaces the previous subscription;
+    /// the caller should drop the old topic (see the returned `Option`).
+    pub fn join(
+        &mut self,
+        name: &str,
+        secret: Option<Vec<u8>>,
+    ) -> Result<([u8; 32], Option<[u8; 32]>), TomProtocolError> {
+        validate_channel_name(name)?;
+        let topic = channel_topic(name, secret.as_deref());
+        let previous = self
+            .channels
+            .insert(name.to_string(), JoinedChannel::new(topic, secret))
+            .map(|c| c.topic)
+            .filter(|old| *old != topic);
+        Ok((topic, previous))
+    }
+
+    /// Leave a channel. Returns its topic if we were subscribed.
+    pub fn leave(&mut self, name: &str) -> Option<[u8; 32]> {
+        self.channels.remove(name).map(|c| c.topic)
+    }
+
+    /// Whether we've joined a channel.
+    pub fn is_joined(&self, name: &str) -> bool {
+        self.channels.contains_key(name)
+    }
+
+    /// Names of joined channels.
+    pub fn channel_names(&self) -> impl Iterator<Item = &str> {
+        self.channels.keys().map(String::as_str)
+    }
+
+    /// Build and sign a publication. Returns the topic and wire bytes.
+    pub fn build_publication(
+        &self,
+        name: &str,
+        from: NodeId,
+        secret_seed: &[u8; 32],
+        payload: Vec<u8>,
+        now_ms: u64,
+    ) -> Result<([u8; 32], Vec<u8>), TomProtocolError> {
+        let Some(joined) = self.channels.get(name) else {
+            return Err(TomProtocolError::InvalidChannel {
+                reason: format!("not joined to {name}"),
+            });
+        };
+        if payload.len() > MAX_CHANNEL_PAYLOAD {
+            return Err(TomProtocolError::InvalidChannel {
+                reason: format!("payload exceeds {MAX_CHANNEL_PAYLOAD} bytes"),
+            });
+        }
+
+        let mut publication = ChannelPublication {
+            channel: name.to_string(),
+            from,
+            payload,
+            sent_at: now_ms,
+            auth_tag: None,
+            signature: Vec::new(),
+        };
+        publication.auth_tag = joined
+            .secret
+            .as_deref()
+            .map(|s| publication.compute_auth_tag(s));
+        let signing_key = SigningKey::from_bytes(secret_seed);
+        publication.signature = signing_key
+            .sign(&publication.signing_bytes())
+            .to_bytes()
+            .to_vec();
+
+        Ok((joined.topic, rmp_serde::to_vec(&publication)?))
+    }
+
+    /// Decode and check a publication received on `topic`.
+    ///
+    /// A publication already accepted on the channel is rejected as a replay.
+    pub fn accept(
+        &mut self,
+        topic: [u8; 32],
+        bytes: &[u8],
+        now_ms: u64,
+    ) -> Result<ChannelPublication, TomProtocolError> {
+        let reject = |reason: &str| TomProtocolError::InvalidChannel {
+            reason: reason.to_string(),
+        };
+
+        let publication: ChannelPublication = rmp_serde::from_slice(bytes)?;
+        let Some(joined) = self.channels.get_mut(&publication.channel) else {
+            return Err(reject("not subscribed"));
+        };
+        if joined.topic != topic {
+            return Err(reject("topic mismatch"));
+        }
+        if publication.payload.len() > MAX_CHANNEL_PAYLOAD {
+            return Err(reject("payload too large"));
+        }
+        if now_ms.saturating_sub(publication.sent_at) > CHANNEL_MAX_AGE_MS {
+            return Err(reject("stale publication"));
+        }
+        if publication.sent_at > now_ms.saturating_add(CHANNEL_MAX_FUTURE_MS) {
+            return Err(reject("publication from the future"));
+        }
+        if let Some(ref secret) = joined.secret {
+            if publication.auth_tag != Some(publication.compute_auth_tag(secret)) {
+                return Err(reject("bad auth tag"));
+            }
+        }
+        if !publication.verify_signature() {
+            return Err(TomProtocolError::InvalidSignature);
+        }
+        if !joined.remember(publication.sent_at, &publication.signature, now_ms) {
+            return Err(reject("replayed publication"));
+        }
+        Ok(publication)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypair(seed: u8) -> (NodeId, [u8; 32]) {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        let node_id: NodeId = secret.public().to_string().parse().unwrap();
+        (node_id, secret.to_bytes())
+    }
+
+    #[test]
+    fn topic_depends_on_secret() {
+        let public = channel_topic("sensors", None);
+        let private = channel_topic("sensors", Some(b"s3cret"));
+        assert_ne!(public, private);
+        assert_eq!(public, channel_topic("sensors", None));
+    }
+
+    #[test]
+    fn publish_accept_roundtrip() {
+        let (alice, alice_seed) = keypair(1);
+        let mut reg = ChannelRegistry::new();
+        let (topic, _) = reg.join("weather", None).unwrap();
+
+        let (pub_topic, bytes) = reg
+            .build_publication("weather", alice, &alice_seed, b"21C".to_vec(), 1000)
+            .unwrap();
+        assert_eq!(pub_topic, topic);
+
+        let publication = reg.accept(topic, &bytes, 1000).unwrap();
+        assert_eq!(publication.from, alice);
+        assert_eq!(publication.payload, b"21C");
+    }
+
+    #[test]
+    fn private_channel_rejects_wrong_secret() {
+        let (alice, alice_seed) = keypair(1);
+        let mut sender = ChannelRegistry::new();
+        sender.join("ops", Some(b"wrong".to_vec())).unwrap();
+        let (_, bytes) = sender
+            .build_publication("ops", alice, &alice_seed, b"hi".to_vec(), 1000)
+            .unwrap();
+
+        // Receiver knows the real secret; pretend the bytes reached its topic.
+        let mut receiver = ChannelRegistry::new();
+        let (topic, _) = receiver.join("ops", Some(b"right".to_vec())).unwrap();
+        assert!(receiver.accept(topic, &bytes, 1000).is_err());
+    }
+
+    #[test]
+    fn accept_rejects_tampered_and_stale() {
+        let (alice, alice_seed) = keypair(1);
+        let mut reg = ChannelRegistry::new();
+        let (topic, _) = reg.join("feed", None).unwrap();
+        let (_, bytes) = reg
+            .build_publication("feed", alice, &alice_seed, b"ok".to_vec(), 1000)
+            .unwrap();
+
+        let mut tampered: ChannelPublication = rmp_serde::from_slice(&bytes).unwrap();
+        tampered.payload = b"evil".to_vec();
+        let tampered = rmp_serde::to_vec(&tampered).unwrap();
+        assert!(matches!(
+            reg.accept(topic, &tampered, 1000),
+            Err(TomProtocolError::InvalidSignature)
+        ));
+
+        assert!(reg.accept(topic, &bytes, 1000 + CHANNEL_MAX_AGE_MS + 1).is_err());
+    }
+
+    #[test]
+    fn accept_rejects_far_future_and_overflowing_stamps() {
+        let (alice, alice_seed) = keypair(1);
+        let mut reg = ChannelRegistry::new();
+        let (topic, _) = reg.join("feed", None).unwrap();
+
+        let ahead_at = 1000 + CHANNEL_MAX_FUTURE_MS + 1;
+        let (_, ahead) = reg
+            .build_publication("feed", alice, &alice_seed, b"a".to_vec(), ahead_at)
+            .unwrap();
+        assert!(reg.accept(topic, &ahead, 1000).is_err());
+
+        // A sent_at near u64::MAX used to wrap the age check
+        let (_, wrapping) = reg
+            .build_publication("feed", alice, &alice_seed, b"b".to_vec(), u64::MAX - 10)
+            .unwrap();
+        assert!(reg.accept(topic, &wrapping, 1000).is_err());
+    }
+
+    #[test]
+    fn accept_rejects_replays() {
+        let (alice, alice_seed) = keypair(1);
+        let mut reg = ChannelRegistry::new();
+        let (topic, _) = reg.join("feed", None).unwrap();
+        let (_, bytes) = reg
+            .build_publication("feed", alice, &alice_seed, b"once".to_vec(), 1000)
+            .unwrap();
+
+        assert!(reg.accept(topic, &bytes, 1000).is_ok());
+        assert!(reg.accept(topic, &bytes, 2000).is_err());
+    }
+
+    #[test]
+    fn seen_set_stays_bounded() {
+        let (alice, alice_seed) = keypair(1);
+        let mut reg = ChannelRegistry::new();
+        let (topic, _) = reg.join("feed", None).unwrap();
+        for i in 0..(MAX_SEEN_PER_CHANNEL as u64 + 10) {
+            let payload = i.to_le_bytes().to_vec();
+            let (_, bytes) = reg
+                .build_publication("feed", alice, &alice_seed, payload, 1000 + i)
+                .unwrap();
+            reg.accept(topic, &bytes, 1000 + i).unwrap();
+        }
+        assert_eq!(reg.channels["feed"].seen.len(), MAX_SEEN_PER_CHANNEL);
+    }
+
+    #[test]
+    fn publish_requires_join_and_small_payload() {
+        let (alice, alice_seed) = keypair(1);
+        let mut reg = ChannelRegistry::new();
+        assert!(reg
+            .build_publication("feed", alice, &alice_seed, vec![], 0)
+            .is_err());
+
+        reg.join("feed", None).unwrap();
+        assert!(reg
+            .build_publication("feed", alice, &alice_seed, vec![0; MAX_CHANNEL_PAYLOAD + 1], 0)
+            .is_err());
+
+        assert_eq!(reg.leave("feed"), Some(channel_topic("feed", None)));
+        assert!(!reg.is_joined("feed"));
+    }
+
+    #[test]
+    fn rejoin_with_new_secret_reports_old_topic() {
+        let mut reg = ChannelRegistry::new();
+        let (first, prev) = reg.join("room", None).unwrap();
+        assert!(prev.is_none());
+        let (second, prev) = reg.join("room", Some(b"k".to_vec())).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(prev, Some(first));
+        assert!(reg.join("", None).is_err());
+    }
+}
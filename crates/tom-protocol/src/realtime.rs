@@ -0,0 +1,187 @@
+/// Real-time lane — unreliable, unordered payloads over QUIC datagrams.
+///
+/// For audio frames, game state and anything else where late is as bad as
+/// lost: no ACK, no tracker, no retry, no backup, no relaying. Each
+/// payload travels as one signed `MessageType::Realtime` envelope in one
+/// datagram straight to the peer (QUIC encrypts it end to end, so the
+/// envelope isn't sealed too).
+///
+/// Frames carry a per-recipient sequence number; the receiver derives
+/// RTP-style stats from it (RFC 3550): expected vs received for loss,
+/// backward steps for reordering, and interarrival jitter from envelope
+/// timestamps (the clock offset between peers cancels out).
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::NodeId;
+
+/// Largest payload accepted by `send_realtime`: one datagram holds the
+/// payload plus ~300 bytes of envelope, within the ~1200 bytes every path
+/// carries.
+pub const MAX_REALTIME_PAYLOAD: usize = 800;
+
+/// Most peers with real-time stats; the longest silent is forgotten past it.
+const MAX_REALTIME_PEERS: usize = 1024;
+
+/// Prefix `payload` with its sequence number.
+pub fn encode_frame(seq: u64, payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(8 + payload.len());
+    buf.extend_from_slice(&seq.to_be_bytes());
+    buf.extend_from_slice(payload);
+    buf
+}
+
+/// Split a frame into its sequence number and payload.
+pub fn decode_frame(frame: &[u8]) -> Option<(u64, &[u8])> {
+    if frame.len() < 8 {
+        return None;
+    }
+    let (seq, payload) = frame.split_at(8);
+    Some((u64::from_be_bytes(seq.try_into().ok()?), payload))
+}
+
+/// Real-time traffic with one peer.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RealtimeStats {
+    /// Frames we sent them.
+    pub sent: u64,
+    /// Frames received from them (duplicates included).
+    pub received: u64,
+    /// Frames missing: expected (from the sequence span) minus received.
+    pub lost: u64,
+    /// Frames that arrived after a later one.
+    pub reordered: u64,
+    /// Interarrival jitter, in milliseconds.
+    pub jitter_ms: f64,
+    /// When their last frame arrived (unix ms; 0 = never).
+    pub last_received_at: u64,
+}
+
+impl RealtimeStats {
+    /// Share of expected frames that never arrived (0.0 to 1.0).
+    pub fn loss_ratio(&self) -> f64 {
+        let expected = self.received + self.lost;
+        if expected == 0 {
+            0.0
+        } else {
+            self.lost as f64 / expected as f64
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct PeerLane {
+    next_seq: u64,
+    first_seq: Option<u64>,
+    highest_seq: u64,
+    /// Last frame's transit time (arrival − sender timestamp), for jitter.
+    last_transit: Option<i64>,
+    stats: RealtimeStats,
+}
+
+/// Sequence numbers out and stats in, per peer.
+#[derive(Debug, Default)]
+pub struct RealtimeLane {
+    peers: HashMap<NodeId, PeerLane>,
+}
+
+impl RealtimeLane {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number the next frame to `to` (from 1).
+    pub fn next_seq(&mut self, to: NodeId) -> u64 {
+        let lane = self.lane(to);
+        lane.next_seq += 1;
+        lane.stats.sent += 1;
+        lane.next_seq
+    }
+
+    /// Account for a frame from `from` numbered `seq`, sent at `sent_at`
+    /// (their clock) and arrived at `now` (ours).
+    pub fn on_receive(&mut self, from: NodeId, seq: u64, sent_at: u64, now: u64) {
+        let lane = self.lane(from);
+        let stats = &mut lane.stats;
+        stats.received += 1;
+        stats.last_received_at = now;
+
+        let first = *lane.first_seq.get_or_insert(seq);
+        if seq < first {
+            // Older than the first one seen: widen the span
+            lane.first_seq = Some(seq);
+            stats.reordered += 1;
+        } else if seq < lane.highest_seq {
+            stats.reordered += 1;
+        }
+        lane.highest_seq = lane.highest_seq.max(seq);
+        let expected = lane.highest_seq - lane.first_seq.unwrap_or(seq) + 1;
+        stats.lost = expected.saturating_sub(stats.received);
+
+        // RFC 3550 §6.4.1: J += (|D| − J) / 16
+        let transit = now as i64 - sent_at as i64;
+        if let Some(last) = lane.last_transit {
+            let d = (transit - last).unsigned_abs() as f64;
+            stats.jitter_ms += (d - stats.jitter_ms) / 16.0;
+        }
+        lane.last_transit = Some(transit);
+    }
+
+    pub fn stats(&self, peer: &NodeId) -> Option<RealtimeStats> {
+        self.peers.get(peer).map(|lane| lane.stats.clone())
+    }
+
+    fn lane(&mut self, peer: NodeId) -> &mut PeerLane {
+        if !self.peers.contains_key(&peer) && self.peers.len() >= MAX_REALTIME_PEERS {
+            let quietest = self
+                .peers
+                .iter()
+                .min_by_key(|(_, lane)| lane.stats.last_received_at)
+                .map(|(id, _)| *id);
+            if let Some(quietest) = quietest {
+                self.peers.remove(&quietest);
+            }
+        }
+        self.peers.entry(peer).or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn frames_roundtrip() {
+        let frame = encode_frame(7, b"opus");
+        assert_eq!(decode_frame(&frame), Some((7, &b"opus"[..])));
+        assert_eq!(decode_frame(&[1, 2]), None);
+    }
+
+    #[test]
+    fn stats_count_loss_reordering_and_jitter() {
+        let peer = node_id(1);
+        let mut lane = RealtimeLane::new();
+        assert_eq!(lane.next_seq(peer), 1);
+        assert_eq!(lane.next_seq(peer), 2);
+        assert_eq!(lane.stats(&peer).unwrap().sent, 2);
+
+        // 1, 2, 4, 3, 6 (5 lost); steady 20ms transit then one 60ms
+        for (seq, sent, arrived) in [(1, 0, 20), (2, 20, 40), (4, 60, 80), (3, 40, 85), (6, 100, 160)] {
+            lane.on_receive(peer, seq, sent, arrived);
+        }
+        let stats = lane.stats(&peer).unwrap();
+        assert_eq!((stats.received, stats.lost, stats.reordered), (5, 1, 1));
+        assert_eq!(stats.last_received_at, 160);
+        assert!((stats.loss_ratio() - 1.0 / 6.0).abs() < 1e-9);
+        assert!(stats.jitter_ms > 0.0 && stats.jitter_ms < 60.0);
+        assert!(lane.stats(&node_id(2)).is_none());
+    }
+}
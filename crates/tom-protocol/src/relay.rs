@@ -1,9 +1,11 @@
 /// Relay selection for ToM protocol.
 ///
-/// Chooses the best relay node based on network topology: role,
-/// online status, and last-seen timestamp.
+/// Chooses the best relay node based on network topology (role, online
+/// status, last-seen timestamp) and measured path quality (round trip,
+/// forward success, announced load).
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use crate::discovery::EphemeralSubnetManager;
 use crate::types::NodeId;
 
 /// Maximum relay depth for path selection.
@@ -21,6 +23,28 @@ pub enum PeerRole {
     Peer,
     /// Relay-capable — can forward messages for others.
     Relay,
+    /// Holds backups for offline peers (on top of Peer or Relay).
+    Storage,
+    /// Gossip entry point for joining nodes (on top of Peer or Relay).
+    Bootstrap,
+}
+
+impl PeerRole {
+    /// Storage and Bootstrap are held alongside the primary Peer/Relay role.
+    pub fn is_extra(&self) -> bool {
+        matches!(self, PeerRole::Storage | PeerRole::Bootstrap)
+    }
+}
+
+/// Routing pinned for a specific peer, overriding relay selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoutingOverride {
+    /// Always send to it directly, never through a relay.
+    AlwaysDirect,
+    /// Always send to it through this relay.
+    AlwaysVia(NodeId),
+    /// Never forward its traffic when acting as a relay.
+    NeverRelayFor,
 }
 
 /// Current status of a known peer.
@@ -48,9 +72,27 @@ pub struct PeerInfo {
 ///
 /// Updated by the discovery layer (gossip). The RelaySelector reads
 /// this to make routing decisions.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Topology {
     peers: HashMap<NodeId, PeerInfo>,
+    /// Capacity; once reached, the longest-departed peer makes room.
+    max_peers: usize,
+    /// Peers dropped to make room, waiting for the owner to collect them.
+    evicted: Vec<NodeId>,
+    /// Storage/Bootstrap roles peers announced, beside their primary role.
+    extra_roles: HashMap<NodeId, Vec<PeerRole>>,
+    /// Routing pinned by the user, kept even while the peer is unknown.
+    routing_overrides: HashMap<NodeId, RoutingOverride>,
+    /// Hosted identities (no endpoint of their own) → the node serving them.
+    hosts: HashMap<NodeId, NodeId>,
+    /// Protocol version and features from peers' capability handshakes.
+    capabilities: HashMap<NodeId, crate::capabilities::PeerCapabilities>,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Self::with_max_peers(MAX_PEERS)
+    }
 }
 
 impl Topology {
@@ -58,18 +100,133 @@ impl Topology {
         Self::default()
     }
 
-    /// Add or update a peer. Returns false if at capacity and peer is new.
+    /// Topology holding at most `max_peers` peers (at least one).
+    pub fn with_max_peers(max_peers: usize) -> Self {
+        Self {
+            peers: HashMap::new(),
+            max_peers: max_peers.max(1),
+            evicted: Vec::new(),
+            extra_roles: HashMap::new(),
+            routing_overrides: HashMap::new(),
+            hosts: HashMap::new(),
+            capabilities: HashMap::new(),
+        }
+    }
+
+    pub fn max_peers(&self) -> usize {
+        self.max_peers
+    }
+
+    /// Add or update a peer. At capacity, a new peer evicts the offline peer
+    /// seen longest ago; returns false if every known peer is still around.
     pub fn upsert(&mut self, info: PeerInfo) -> bool {
-        if self.peers.len() >= MAX_PEERS && !self.peers.contains_key(&info.node_id) {
-            return false;
+        if self.peers.len() >= self.max_peers && !self.peers.contains_key(&info.node_id) {
+            let Some(oldest) = self
+                .peers
+                .values()
+                .filter(|p| p.status == PeerStatus::Offline)
+                .min_by_key(|p| p.last_seen)
+                .map(|p| p.node_id)
+            else {
+                return false;
+            };
+            self.remove(&oldest);
+            self.evicted.push(oldest);
         }
         self.peers.insert(info.node_id, info);
         true
     }
 
+    /// Drop offline peers not seen for `ttl_ms`, returning their ids.
+    pub fn prune_departed(&mut self, now: u64, ttl_ms: u64) -> Vec<NodeId> {
+        let expired: Vec<NodeId> = self
+            .peers
+            .values()
+            .filter(|p| p.status == PeerStatus::Offline && now.saturating_sub(p.last_seen) >= ttl_ms)
+            .map(|p| p.node_id)
+            .collect();
+        for id in &expired {
+            self.remove(id);
+        }
+        expired
+    }
+
+    /// Peers evicted by `upsert` since the last call.
+    pub fn take_evicted(&mut self) -> Vec<NodeId> {
+        std::mem::take(&mut self.evicted)
+    }
+
     /// Remove a peer.
     pub fn remove(&mut self, node_id: &NodeId) {
         self.peers.remove(node_id);
+        self.extra_roles.remove(node_id);
+        self.hosts.remove(node_id);
+        self.capabilities.remove(node_id);
+    }
+
+    /// Cache what a known peer said it supports (ignored for strangers).
+    pub fn set_capabilities(&mut self, node_id: NodeId, capabilities: crate::capabilities::PeerCapabilities) {
+        if self.peers.contains_key(&node_id) {
+            self.capabilities.insert(node_id, capabilities);
+        }
+    }
+
+    /// What a peer supports, if it told us.
+    pub fn capabilities(&self, node_id: &NodeId) -> Option<crate::capabilities::PeerCapabilities> {
+        self.capabilities.get(node_id).copied()
+    }
+
+    /// Record the extra roles (Storage, Bootstrap) a known peer announced;
+    /// anything else in `roles` is ignored.
+    pub fn set_extra_roles(&mut self, node_id: NodeId, roles: &[PeerRole]) {
+        let extras: Vec<PeerRole> = roles.iter().copied().filter(PeerRole::is_extra).collect();
+        if extras.is_empty() || !self.peers.contains_key(&node_id) {
+            self.extra_roles.remove(&node_id);
+        } else {
+            self.extra_roles.insert(node_id, extras);
+        }
+    }
+
+    /// Pin (or with `None`, unpin) routing for a peer.
+    pub fn set_routing_override(&mut self, node_id: NodeId, policy: Option<RoutingOverride>) {
+        match policy {
+            Some(policy) => {
+                self.routing_overrides.insert(node_id, policy);
+            }
+            None => {
+                self.routing_overrides.remove(&node_id);
+            }
+        }
+    }
+
+    /// The routing pinned for a peer, if any.
+    pub fn routing_override(&self, node_id: &NodeId) -> Option<RoutingOverride> {
+        self.routing_overrides.get(node_id).copied()
+    }
+
+    /// Record that `identity` is served by `host`: traffic for it goes
+    /// through the host.
+    pub fn set_host(&mut self, identity: NodeId, host: NodeId) {
+        self.hosts.insert(identity, host);
+    }
+
+    /// The node hosting `identity`, if it is a hosted identity.
+    pub fn host_of(&self, identity: &NodeId) -> Option<NodeId> {
+        self.hosts.get(identity).copied()
+    }
+
+    /// All pinned routing policies.
+    pub fn routing_overrides(&self) -> impl Iterator<Item = (NodeId, RoutingOverride)> + '_ {
+        self.routing_overrides.iter().map(|(id, policy)| (*id, *policy))
+    }
+
+    /// Whether a peer holds `role`, as its primary role or an extra one.
+    pub fn has_role(&self, node_id: &NodeId, role: PeerRole) -> bool {
+        self.peers.get(node_id).is_some_and(|p| p.role == role)
+            || self
+                .extra_roles
+                .get(node_id)
+                .is_some_and(|extras| extras.contains(&role))
     }
 
     /// Get info for a specific peer.
@@ -111,6 +268,171 @@ impl Topology {
         relays.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
         relays
     }
+
+    /// All online peers holding `role`, sorted by most recently seen.
+    pub fn online_with_role(&self, role: PeerRole) -> Vec<&PeerInfo> {
+        let mut peers: Vec<&PeerInfo> = self
+            .peers
+            .values()
+            .filter(|p| p.status == PeerStatus::Online && self.has_role(&p.node_id, role))
+            .collect();
+        peers.sort_by_key(|p| std::cmp::Reverse(p.last_seen));
+        peers
+    }
+
+    /// Serializable view of the known peers and their roles; add subnets
+    /// and observed paths with the [`TopologySnapshot`] builders.
+    pub fn snapshot(&self) -> TopologySnapshot {
+        let mut peers: Vec<PeerSnapshot> = self
+            .peers
+            .values()
+            .map(|p| PeerSnapshot {
+                node_id: p.node_id,
+                role: p.role,
+                extra_roles: self.extra_roles.get(&p.node_id).cloned().unwrap_or_default(),
+                status: p.status,
+                last_seen: p.last_seen,
+            })
+            .collect();
+        peers.sort_by_key(|p| p.node_id.to_string());
+        TopologySnapshot {
+            peers,
+            ..Default::default()
+        }
+    }
+
+    /// Rebuild a topology from a snapshot's peers (e.g. exported by
+    /// another node); subnets and paths are not topology state.
+    pub fn from_snapshot(snapshot: &TopologySnapshot) -> Self {
+        let mut topology = Self::new();
+        for peer in &snapshot.peers {
+            topology.upsert(PeerInfo {
+                node_id: peer.node_id,
+                role: peer.role,
+                status: peer.status,
+                last_seen: peer.last_seen,
+            });
+            topology.set_extra_roles(peer.node_id, &peer.extra_roles);
+        }
+        topology
+    }
+}
+
+// ── Topology snapshot ──────────────────────────────────────────────────
+
+/// A peer as recorded in a [`TopologySnapshot`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerSnapshot {
+    pub node_id: NodeId,
+    pub role: PeerRole,
+    /// Storage/Bootstrap roles it announced.
+    pub extra_roles: Vec<PeerRole>,
+    /// Liveness as last observed.
+    pub status: PeerStatus,
+    pub last_seen: u64,
+}
+
+/// An ephemeral subnet in a [`TopologySnapshot`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubnetSnapshot {
+    pub subnet_id: String,
+    pub members: Vec<NodeId>,
+}
+
+/// A peer and the relay it last reached the observer through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObservedPath {
+    pub peer: NodeId,
+    pub relay: NodeId,
+}
+
+/// The network as one node sees it: peers with roles and liveness,
+/// subnets and observed relay paths. Serializable, and ordered
+/// deterministically, so external tools can render network maps and
+/// tests can compare converged topologies.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TopologySnapshot {
+    /// The node that took the snapshot, if recorded.
+    pub observer: Option<NodeId>,
+    /// Unix ms when it was taken (0 if not recorded).
+    pub taken_at: u64,
+    pub peers: Vec<PeerSnapshot>,
+    pub subnets: Vec<SubnetSnapshot>,
+    pub paths: Vec<ObservedPath>,
+}
+
+impl TopologySnapshot {
+    /// Record who took the snapshot, and when.
+    pub fn with_observer(mut self, observer: NodeId, taken_at: u64) -> Self {
+        self.observer = Some(observer);
+        self.taken_at = taken_at;
+        self
+    }
+
+    /// Add the active ephemeral subnets.
+    pub fn with_subnets(mut self, subnets: &EphemeralSubnetManager) -> Self {
+        self.subnets = subnets
+            .all_subnets()
+            .into_iter()
+            .map(|s| {
+                let mut members: Vec<NodeId> = s.members.iter().copied().collect();
+                members.sort_by_key(|m| m.to_string());
+                SubnetSnapshot {
+                    subnet_id: s.subnet_id.clone(),
+                    members,
+                }
+            })
+            .collect();
+        self.subnets.sort_by(|a, b| a.subnet_id.cmp(&b.subnet_id));
+        self
+    }
+
+    /// Add the relay paths peers last reached us through.
+    pub fn with_paths(mut self, metrics: &RelayMetrics) -> Self {
+        self.paths = metrics
+            .paths()
+            .map(|(peer, relay)| ObservedPath { peer, relay })
+            .collect();
+        self.paths.sort_by_key(|p| p.peer.to_string());
+        self
+    }
+
+    pub fn peer(&self, node_id: &NodeId) -> Option<&PeerSnapshot> {
+        self.peers.iter().find(|p| p.node_id == *node_id)
+    }
+
+    /// Graphviz DOT rendering: peers as nodes (relays boxed, offline ones
+    /// dashed), subnets as clusters, observed paths as edges.
+    pub fn to_dot(&self) -> String {
+        use std::fmt::Write;
+
+        let mut dot = String::from("graph tom {\n");
+        if let Some(observer) = self.observer {
+            let _ = writeln!(dot, "  \"{observer}\" [shape=doublecircle];");
+        }
+        for peer in &self.peers {
+            let shape = if peer.role == PeerRole::Relay { "box" } else { "ellipse" };
+            let style = match peer.status {
+                PeerStatus::Online => "solid",
+                PeerStatus::Stale => "dotted",
+                PeerStatus::Offline => "dashed",
+            };
+            let _ = writeln!(dot, "  \"{}\" [shape={shape}, style={style}];", peer.node_id);
+        }
+        for (i, subnet) in self.subnets.iter().enumerate() {
+            let _ = writeln!(dot, "  subgraph cluster_{i} {{");
+            let _ = writeln!(dot, "    label=\"{}\";", subnet.subnet_id);
+            for member in &subnet.members {
+                let _ = writeln!(dot, "    \"{member}\";");
+            }
+            dot.push_str("  }\n");
+        }
+        for path in &self.paths {
+            let _ = writeln!(dot, "  \"{}\" -- \"{}\";", path.peer, path.relay);
+        }
+        dot.push_str("}\n");
+        dot
+    }
 }
 
 // ── Relay selection ────────────────────────────────────────────────────
@@ -124,6 +446,12 @@ pub enum SelectionReason {
     OnlyOption,
     /// Alternate after primary failed.
     Alternate,
+    /// Online relay in our or the target's active subnet.
+    SubnetLocal,
+    /// Online relay with the lowest measured round trip from us.
+    LowLatency,
+    /// Weighted random pick by round trip, forward success and load.
+    PathQuality,
     /// No relay available.
     NoRelayAvailable,
 }
@@ -210,6 +538,194 @@ impl RelaySelector {
         }
     }
 
+    /// Select the best relay to reach `target`, preferring one inside our
+    /// or the target's active subnet, then the one with the lowest measured
+    /// round trip, and falling back to [`select_best`](Self::select_best).
+    /// Among equals, the most recently seen wins.
+    pub fn select_best_local(
+        &self,
+        target: NodeId,
+        topology: &Topology,
+        subnets: &EphemeralSubnetManager,
+    ) -> RelaySelection {
+        let local = subnets.members_around(&self.self_id, &target);
+        let mut candidates: Vec<&PeerInfo> = topology
+            .online_relays()
+            .into_iter()
+            .filter(|p| p.node_id != self.self_id && p.node_id != target)
+            .collect();
+        // Stable sort keeps most-recently-seen first among equals
+        candidates.sort_by_key(|p| subnets.rtt_ms(&p.node_id).unwrap_or(u64::MAX));
+
+        if let Some(relay) = candidates.iter().find(|p| local.contains(&p.node_id)) {
+            return RelaySelection {
+                relay_id: Some(relay.node_id),
+                reason: SelectionReason::SubnetLocal,
+            };
+        }
+        match candidates.first() {
+            Some(relay) if subnets.rtt_ms(&relay.node_id).is_some() => RelaySelection {
+                relay_id: Some(relay.node_id),
+                reason: SelectionReason::LowLatency,
+            },
+            _ => self.select_best(target, topology),
+        }
+    }
+
+    /// Subnet-aware [`select_path`](Self::select_path): an online target
+    /// in our own subnet is reached directly (empty path); otherwise one
+    /// hop through [`select_best_local`](Self::select_best_local).
+    pub fn select_path_local(
+        &self,
+        target: NodeId,
+        topology: &Topology,
+        subnets: &EphemeralSubnetManager,
+    ) -> Vec<NodeId> {
+        if let Some(path) = self.pinned_path(target, topology) {
+            return path;
+        }
+        let online = topology
+            .get(&target)
+            .is_some_and(|p| p.status == PeerStatus::Online);
+        if online && subnets.are_in_same_subnet(&self.self_id, &target) {
+            return Vec::new();
+        }
+        match self.select_best_local(target, topology, subnets).relay_id {
+            Some(relay) => vec![relay],
+            None => Vec::new(),
+        }
+    }
+
+    /// Pick a relay to reach `target` at random, weighted by path quality
+    /// (see [`RelayMetrics::quality`]), so traffic spreads over good relays
+    /// instead of hammering the single best one. Relays in our or the
+    /// target's subnet are drawn from first. `roll` is uniform in `[0, 1)`.
+    pub fn select_weighted(
+        &self,
+        target: NodeId,
+        topology: &Topology,
+        subnets: &EphemeralSubnetManager,
+        metrics: &RelayMetrics,
+        roll: f64,
+    ) -> RelaySelection {
+        self.select_weighted_excluding(target, topology, subnets, metrics, &[], roll)
+    }
+
+    /// [`select_weighted`](Self::select_weighted), never picking a relay in
+    /// `exclude`.
+    pub fn select_weighted_excluding(
+        &self,
+        target: NodeId,
+        topology: &Topology,
+        subnets: &EphemeralSubnetManager,
+        metrics: &RelayMetrics,
+        exclude: &[NodeId],
+        roll: f64,
+    ) -> RelaySelection {
+        let local = subnets.members_around(&self.self_id, &target);
+        let candidates: Vec<NodeId> = topology
+            .online_relays()
+            .into_iter()
+            .map(|p| p.node_id)
+            .filter(|id| *id != self.self_id && *id != target && !exclude.contains(id))
+            .collect();
+        let in_subnet: Vec<NodeId> =
+            candidates.iter().copied().filter(|id| local.contains(id)).collect();
+        let (pool, reason) = if in_subnet.is_empty() {
+            (candidates, SelectionReason::PathQuality)
+        } else {
+            (in_subnet, SelectionReason::SubnetLocal)
+        };
+        if pool.len() <= 1 {
+            return match pool.first() {
+                Some(&relay) => RelaySelection {
+                    relay_id: Some(relay),
+                    reason: SelectionReason::OnlyOption,
+                },
+                None => RelaySelection {
+                    relay_id: None,
+                    reason: SelectionReason::NoRelayAvailable,
+                },
+            };
+        }
+
+        let rtt = |id: &NodeId| metrics.rtt_ms(id).or_else(|| subnets.rtt_ms(id));
+        let weights: Vec<f64> = pool.iter().map(|id| metrics.quality(id, rtt(id))).collect();
+        let mut remaining = roll.clamp(0.0, 1.0) * weights.iter().sum::<f64>();
+        let mut picked = pool[pool.len() - 1];
+        for (id, weight) in pool.iter().zip(&weights) {
+            if remaining < *weight {
+                picked = *id;
+                break;
+            }
+            remaining -= weight;
+        }
+        RelaySelection {
+            relay_id: Some(picked),
+            reason,
+        }
+    }
+
+    /// Quality-aware [`select_path_local`](Self::select_path_local): an
+    /// online target in our own subnet is reached directly (empty path);
+    /// otherwise one hop through [`select_weighted`](Self::select_weighted)
+    /// with a fresh random roll.
+    pub fn select_path_weighted(
+        &self,
+        target: NodeId,
+        topology: &Topology,
+        subnets: &EphemeralSubnetManager,
+        metrics: &RelayMetrics,
+    ) -> Vec<NodeId> {
+        if let Some(path) = self.pinned_path(target, topology) {
+            return path;
+        }
+        let online = topology
+            .get(&target)
+            .is_some_and(|p| p.status == PeerStatus::Online);
+        if online && subnets.are_in_same_subnet(&self.self_id, &target) {
+            return Vec::new();
+        }
+        match self
+            .select_weighted(target, topology, subnets, metrics, random_roll())
+            .relay_id
+        {
+            Some(relay) => vec![relay],
+            None => Vec::new(),
+        }
+    }
+
+    /// Up to two disjoint paths to `target` for a redundant send: the
+    /// [`select_path_weighted`](Self::select_path_weighted) one, then another
+    /// relay — or, if no other relay is up, the direct path to an online
+    /// target. A single path means no disjoint alternative exists, or the
+    /// target's routing is pinned.
+    pub fn select_disjoint_paths(
+        &self,
+        target: NodeId,
+        topology: &Topology,
+        subnets: &EphemeralSubnetManager,
+        metrics: &RelayMetrics,
+    ) -> Vec<Vec<NodeId>> {
+        if let Some(path) = self.pinned_path(target, topology) {
+            return vec![path];
+        }
+        let first = self.select_path_weighted(target, topology, subnets, metrics);
+        let second = self
+            .select_weighted_excluding(target, topology, subnets, metrics, &first, random_roll())
+            .relay_id
+            .map(|relay| vec![relay]);
+        let online = topology
+            .get(&target)
+            .is_some_and(|p| p.status == PeerStatus::Online);
+        let second = match second {
+            Some(path) => Some(path),
+            None if online && !first.is_empty() => Some(Vec::new()),
+            None => None,
+        };
+        std::iter::once(first).chain(second).collect()
+    }
+
     /// Build a multi-hop relay path to reach `target` (BFS through relay nodes).
     ///
     /// Returns a `via` chain of relay NodeIds, capped at `MAX_RELAY_DEPTH`.
@@ -219,12 +735,212 @@ impl RelaySelector {
         target: NodeId,
         topology: &Topology,
     ) -> Vec<NodeId> {
+        if let Some(path) = self.pinned_path(target, topology) {
+            return path;
+        }
         // PoC: single-hop relay selection
         match self.select_best(target, topology).relay_id {
             Some(relay) => vec![relay],
             None => Vec::new(),
         }
     }
+
+    /// The path a [`RoutingOverride`] pins for `target`, if any. A pinned
+    /// relay that is us or the target itself means direct. A hosted identity
+    /// is always reached through its host.
+    fn pinned_path(&self, target: NodeId, topology: &Topology) -> Option<Vec<NodeId>> {
+        if let Some(host) = topology.host_of(&target) {
+            return Some(if host == self.self_id { Vec::new() } else { vec![host] });
+        }
+        match topology.routing_override(&target)? {
+            RoutingOverride::AlwaysDirect => Some(Vec::new()),
+            RoutingOverride::AlwaysVia(relay) if relay == self.self_id || relay == target => {
+                Some(Vec::new())
+            }
+            RoutingOverride::AlwaysVia(relay) => Some(vec![relay]),
+            RoutingOverride::NeverRelayFor => None,
+        }
+    }
+}
+
+/// Uniform roll in `[0, 1)` for weighted picks.
+fn random_roll() -> f64 {
+    let random = u64::from_le_bytes(
+        uuid::Uuid::new_v4().as_bytes()[..8]
+            .try_into()
+            .expect("8 bytes"),
+    );
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// ── Relay metrics ──────────────────────────────────────────────────────
+
+/// Weight of a new round-trip sample in the moving average.
+const RTT_ALPHA: f64 = 0.3;
+
+/// Weight of a new forward outcome in the success-rate moving average.
+const SUCCESS_ALPHA: f64 = 0.2;
+
+/// Round trip at which a relay's latency factor halves; unmeasured relays
+/// are assumed this far away.
+const REFERENCE_RTT_MS: f64 = 100.0;
+
+/// Floor on a relay's selection weight, so none is starved of traffic
+/// (and of the chance to prove it has recovered).
+const MIN_RELAY_WEIGHT: f64 = 0.05;
+
+/// Forwards per minute at which we announce ourselves fully loaded.
+pub const RELAY_CAPACITY_PER_MIN: u64 = 600;
+
+/// What we've learnt about how peers are reached: round-trip times to
+/// relays (from their forwarding ACKs), how reliably they forward, the
+/// load they announce, and the relay each peer last reached us through.
+/// Also meters our own forwarding load for our announces.
+#[derive(Debug, Default)]
+pub struct RelayMetrics {
+    rtt_ms: HashMap<NodeId, f64>,
+    last_relay: HashMap<NodeId, NodeId>,
+    /// Moving average of forward outcomes (1.0 = always acknowledged).
+    success: HashMap<NodeId, f64>,
+    /// Load (percent of capacity) relays announced.
+    load: HashMap<NodeId, u8>,
+    /// Our forwards: (minute index, count this minute, count last minute).
+    local_forwards: (u64, u64, u64),
+}
+
+impl RelayMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold a round-trip sample (ms) for `relay` into its moving average.
+    pub fn record_rtt(&mut self, relay: NodeId, sample_ms: u64) {
+        if self.rtt_ms.len() >= MAX_PEERS && !self.rtt_ms.contains_key(&relay) {
+            return;
+        }
+        let sample = sample_ms as f64;
+        self.rtt_ms
+            .entry(relay)
+            .and_modify(|avg| *avg += RTT_ALPHA * (sample - *avg))
+            .or_insert(sample);
+    }
+
+    /// Average round-trip time to `relay`, if we've measured it.
+    pub fn rtt_ms(&self, relay: &NodeId) -> Option<u64> {
+        self.rtt_ms.get(relay).map(|avg| avg.round() as u64)
+    }
+
+    /// `relay` acknowledged (`true`) or failed to acknowledge one of our
+    /// messages before its deadline.
+    pub fn record_forward(&mut self, relay: NodeId, acknowledged: bool) {
+        if self.success.len() >= MAX_PEERS && !self.success.contains_key(&relay) {
+            return;
+        }
+        let sample = if acknowledged { 1.0 } else { 0.0 };
+        self.success
+            .entry(relay)
+            .and_modify(|avg| *avg += SUCCESS_ALPHA * (sample - *avg))
+            .or_insert(sample);
+    }
+
+    /// Recent share of our messages `relay` acknowledged, if it carried any.
+    pub fn success_rate(&self, relay: &NodeId) -> Option<f64> {
+        self.success.get(relay).copied()
+    }
+
+    /// Record the load a relay announced (percent, capped at 100).
+    pub fn record_load(&mut self, relay: NodeId, percent: u8) {
+        if self.load.len() >= MAX_PEERS && !self.load.contains_key(&relay) {
+            return;
+        }
+        self.load.insert(relay, percent.min(100));
+    }
+
+    /// Load `relay` last announced (percent).
+    pub fn load(&self, relay: &NodeId) -> Option<u8> {
+        self.load.get(relay).copied()
+    }
+
+    /// Selection weight of `relay`: latency factor × success rate × spare
+    /// capacity, floored at `MIN_RELAY_WEIGHT`. Unknowns are neutral:
+    /// `REFERENCE_RTT_MS` away, fully reliable, idle.
+    pub fn quality(&self, relay: &NodeId, rtt_ms: Option<u64>) -> f64 {
+        let rtt = rtt_ms.map_or(REFERENCE_RTT_MS, |ms| ms as f64);
+        let latency = REFERENCE_RTT_MS / (REFERENCE_RTT_MS + rtt);
+        let success = self.success_rate(relay).unwrap_or(1.0);
+        let spare = 1.0 - self.load(relay).unwrap_or(0) as f64 / 100.0;
+        (latency * success * spare).max(MIN_RELAY_WEIGHT)
+    }
+
+    /// We forwarded an envelope for someone.
+    pub fn record_local_forward(&mut self, now: u64) {
+        self.roll_local_window(now);
+        self.local_forwards.1 += 1;
+    }
+
+    /// Our forwarding load over the last full minute, as a percentage of
+    /// `RELAY_CAPACITY_PER_MIN` (capped at 100).
+    pub fn local_load(&self, now: u64) -> u8 {
+        let minute = now / 60_000;
+        let (current, count, previous) = self.local_forwards;
+        let last_minute = if minute == current {
+            previous
+        } else if minute == current + 1 {
+            count
+        } else {
+            0
+        };
+        (last_minute * 100 / RELAY_CAPACITY_PER_MIN).min(100) as u8
+    }
+
+    fn roll_local_window(&mut self, now: u64) {
+        let minute = now / 60_000;
+        let (current, count, _) = self.local_forwards;
+        if minute == current + 1 {
+            self.local_forwards = (minute, 0, count);
+        } else if minute != current {
+            self.local_forwards = (minute, 0, 0);
+        }
+    }
+
+    /// `peer` reached us through `relay` (its first hop).
+    pub fn record_path(&mut self, peer: NodeId, relay: NodeId) {
+        if self.last_relay.len() >= MAX_PEERS && !self.last_relay.contains_key(&peer) {
+            return;
+        }
+        self.last_relay.insert(peer, relay);
+    }
+
+    /// The relay `peer` last reached us through.
+    pub fn last_relay(&self, peer: &NodeId) -> Option<NodeId> {
+        self.last_relay.get(peer).copied()
+    }
+
+    /// Every (peer, relay it last reached us through) pair.
+    pub fn paths(&self) -> impl Iterator<Item = (NodeId, NodeId)> + '_ {
+        self.last_relay.iter().map(|(peer, relay)| (*peer, *relay))
+    }
+
+    /// Online relays best placed to get a message to `target`: the relay it
+    /// last used first (it's likely to come back through it), then the
+    /// others by round-trip time, unmeasured ones last.
+    pub fn relays_toward(&self, target: NodeId, self_id: NodeId, topology: &Topology) -> Vec<NodeId> {
+        let home = self
+            .last_relay(&target)
+            .filter(|r| topology.get(r).is_some_and(|p| p.status == PeerStatus::Online));
+        let mut others: Vec<&PeerInfo> = topology
+            .online_relays()
+            .into_iter()
+            .filter(|p| p.node_id != self_id && p.node_id != target && Some(p.node_id) != home)
+            .collect();
+        // Stable sort keeps most-recently-seen first among equals
+        others.sort_by_key(|p| self.rtt_ms(&p.node_id).unwrap_or(u64::MAX));
+
+        home.filter(|r| *r != self_id && *r != target)
+            .into_iter()
+            .chain(others.into_iter().map(|p| p.node_id))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -424,6 +1140,262 @@ mod tests {
         assert!(path.is_empty());
     }
 
+    /// Subnet of `members`, formed by a burst of messages between each pair.
+    fn subnet_of(me: NodeId, members: &[NodeId]) -> EphemeralSubnetManager {
+        let mut subnets = EphemeralSubnetManager::new(me);
+        for (i, a) in members.iter().enumerate() {
+            for b in &members[i + 1..] {
+                for _ in 0..5 {
+                    subnets.record_communication(*a, *b, 10_000);
+                }
+            }
+        }
+        subnets.evaluate(10_000);
+        subnets
+    }
+
+    #[test]
+    fn select_best_local_prefers_subnet_relays() {
+        let me = node_id(100);
+        let target = node_id(200);
+        let selector = RelaySelector::new(me);
+
+        let mut topo = Topology::new();
+        topo.upsert(make_relay(1, 3000)); // most recent, outside
+        topo.upsert(make_relay(2, 1000)); // in the target's subnet
+
+        let subnets = subnet_of(me, &[target, node_id(2), node_id(3)]);
+        let result = selector.select_best_local(target, &topo, &subnets);
+        assert_eq!(result.relay_id, Some(node_id(2)));
+        assert_eq!(result.reason, SelectionReason::SubnetLocal);
+
+        // No subnet: plain selection
+        let none = EphemeralSubnetManager::new(me);
+        let result = selector.select_best_local(target, &topo, &none);
+        assert_eq!(result.relay_id, Some(node_id(1)));
+        assert_eq!(result.reason, SelectionReason::MostRecent);
+    }
+
+    #[test]
+    fn select_best_local_prefers_low_latency_relays() {
+        let me = node_id(100);
+        let target = node_id(200);
+        let selector = RelaySelector::new(me);
+
+        let mut topo = Topology::new();
+        topo.upsert(make_relay(1, 3000)); // most recent, slow
+        topo.upsert(make_relay(2, 2000)); // fast
+        topo.upsert(make_relay(3, 1000)); // unmeasured
+
+        let mut subnets = EphemeralSubnetManager::new(me);
+        subnets.record_rtt(node_id(1), 180);
+        subnets.record_rtt(node_id(2), 20);
+        let result = selector.select_best_local(target, &topo, &subnets);
+        assert_eq!(result.relay_id, Some(node_id(2)));
+        assert_eq!(result.reason, SelectionReason::LowLatency);
+
+        // Within the subnet, the fastest member relay wins too
+        let mut subnets = subnet_of(me, &[target, node_id(1), node_id(3)]);
+        subnets.record_rtt(node_id(1), 180);
+        subnets.record_rtt(node_id(3), 40);
+        let result = selector.select_best_local(target, &topo, &subnets);
+        assert_eq!(result.relay_id, Some(node_id(3)));
+        assert_eq!(result.reason, SelectionReason::SubnetLocal);
+    }
+
+    #[test]
+    fn select_path_local_goes_direct_within_our_subnet() {
+        let me = node_id(100);
+        let target = node_id(200);
+        let selector = RelaySelector::new(me);
+
+        let mut topo = Topology::new();
+        topo.upsert(make_relay(1, 3000));
+        let subnets = subnet_of(me, &[me, target, node_id(3)]);
+
+        // Target unknown to the topology: keep relaying
+        assert_eq!(selector.select_path_local(target, &topo, &subnets), vec![node_id(1)]);
+
+        topo.upsert(make_peer(200));
+        assert!(selector.select_path_local(target, &topo, &subnets).is_empty());
+    }
+
+    #[test]
+    fn select_weighted_spreads_by_path_quality() {
+        let me = node_id(100);
+        let target = node_id(200);
+        let selector = RelaySelector::new(me);
+        let subnets = EphemeralSubnetManager::new(me);
+
+        let mut topo = Topology::new();
+        topo.upsert(make_relay(1, 3000)); // fast, reliable
+        topo.upsert(make_relay(2, 2000)); // slow, flaky, busy
+        let mut metrics = RelayMetrics::new();
+        metrics.record_rtt(node_id(1), 20);
+        metrics.record_rtt(node_id(2), 400);
+        metrics.record_forward(node_id(2), false);
+        metrics.record_load(node_id(2), 90);
+
+        let picks: Vec<NodeId> = (0..100)
+            .map(|i| {
+                let selection = selector.select_weighted(target, &topo, &subnets, &metrics, i as f64 / 100.0);
+                assert_eq!(selection.reason, SelectionReason::PathQuality);
+                selection.relay_id.unwrap()
+            })
+            .collect();
+        let to_fast = picks.iter().filter(|id| **id == node_id(1)).count();
+        // The poor relay still gets the floor share, never more
+        assert!(to_fast > 90 && to_fast < 100, "fast relay picked {to_fast}/100");
+
+        // Subnet members are drawn from first
+        let subnets = subnet_of(me, &[target, node_id(2), node_id(3)]);
+        let selection = selector.select_weighted(target, &topo, &subnets, &metrics, 0.0);
+        assert_eq!(selection.relay_id, Some(node_id(2)));
+        assert_eq!(selection.reason, SelectionReason::OnlyOption);
+    }
+
+    #[test]
+    fn select_disjoint_paths_never_share_a_relay() {
+        let me = node_id(100);
+        let target = node_id(200);
+        let selector = RelaySelector::new(me);
+        let subnets = EphemeralSubnetManager::new(me);
+        let metrics = RelayMetrics::new();
+
+        let mut topo = Topology::new();
+        topo.upsert(make_relay(1, 3000));
+        topo.upsert(make_relay(2, 2000));
+        for _ in 0..20 {
+            let paths = selector.select_disjoint_paths(target, &topo, &subnets, &metrics);
+            assert_eq!(paths.len(), 2);
+            assert_eq!(paths[0].len(), 1);
+            assert_eq!(paths[1].len(), 1);
+            assert_ne!(paths[0], paths[1]);
+        }
+
+        // One relay: the second path is direct, if the target is up
+        topo.remove(&node_id(2));
+        assert_eq!(selector.select_disjoint_paths(target, &topo, &subnets, &metrics).len(), 1);
+        topo.upsert(make_peer(200));
+        let paths = selector.select_disjoint_paths(target, &topo, &subnets, &metrics);
+        assert_eq!(paths, vec![vec![node_id(1)], vec![]]);
+    }
+
+    #[test]
+    fn relay_metrics_success_load_and_local_meter() {
+        let relay = node_id(1);
+        let mut metrics = RelayMetrics::new();
+        assert!((metrics.quality(&relay, None) - 0.5).abs() < 1e-9, "unknowns are neutral");
+
+        metrics.record_forward(relay, true);
+        metrics.record_forward(relay, false);
+        assert!((metrics.success_rate(&relay).unwrap() - 0.8).abs() < 1e-9);
+        metrics.record_load(relay, 250);
+        assert_eq!(metrics.load(&relay), Some(100));
+        assert_eq!(metrics.quality(&relay, Some(0)), MIN_RELAY_WEIGHT);
+
+        // Load is last minute's forwards against capacity
+        for _ in 0..RELAY_CAPACITY_PER_MIN / 2 {
+            metrics.record_local_forward(60_000);
+        }
+        assert_eq!(metrics.local_load(60_000), 0);
+        assert_eq!(metrics.local_load(120_000), 50);
+        assert_eq!(metrics.local_load(180_000), 0);
+    }
+
+    #[test]
+    fn snapshot_roundtrips_and_renders() {
+        let me = node_id(100);
+        let (relay, peer) = (node_id(1), node_id(2));
+        let mut topo = Topology::new();
+        topo.upsert(make_relay(1, 3000));
+        topo.upsert(PeerInfo {
+            status: PeerStatus::Offline,
+            ..make_peer(2)
+        });
+        topo.set_extra_roles(relay, &[PeerRole::Storage]);
+        let mut metrics = RelayMetrics::new();
+        metrics.record_path(peer, relay);
+        let subnets = subnet_of(me, &[me, relay, peer]);
+
+        let snapshot = topo
+            .snapshot()
+            .with_observer(me, 5_000)
+            .with_subnets(&subnets)
+            .with_paths(&metrics);
+        assert_eq!(snapshot.peers.len(), 2);
+        assert_eq!(snapshot.peer(&relay).unwrap().extra_roles, vec![PeerRole::Storage]);
+        assert_eq!(snapshot.subnets.len(), 1);
+        assert_eq!(snapshot.subnets[0].members.len(), 3);
+        assert_eq!(snapshot.paths, vec![ObservedPath { peer, relay }]);
+
+        // Export, import: same peers and roles
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let imported: TopologySnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(imported, snapshot);
+        let rebuilt = Topology::from_snapshot(&imported);
+        assert_eq!(rebuilt.snapshot().peers, snapshot.peers);
+        assert!(rebuilt.has_role(&relay, PeerRole::Storage));
+
+        let dot = snapshot.to_dot();
+        assert!(dot.starts_with("graph tom {"));
+        assert!(dot.contains(&format!("\"{relay}\" [shape=box, style=solid]")));
+        assert!(dot.contains(&format!("\"{peer}\" [shape=ellipse, style=dashed]")));
+        assert!(dot.contains(&format!("\"{peer}\" -- \"{relay}\"")));
+        assert!(dot.contains("subgraph cluster_0"));
+    }
+
+    #[test]
+    fn routing_overrides_pin_paths() {
+        let me = node_id(100);
+        let target = node_id(200);
+        let selector = RelaySelector::new(me);
+        let subnets = EphemeralSubnetManager::new(me);
+        let metrics = RelayMetrics::new();
+        let mut topo = Topology::new();
+        topo.upsert(make_relay(1, 3000));
+        topo.upsert(make_relay(2, 1000));
+
+        topo.set_routing_override(target, Some(RoutingOverride::AlwaysDirect));
+        assert!(selector.select_path(target, &topo).is_empty());
+        assert_eq!(selector.select_disjoint_paths(target, &topo, &subnets, &metrics), vec![vec![]]);
+
+        // Pinned even to a relay that isn't the best (or even online)
+        topo.set_routing_override(target, Some(RoutingOverride::AlwaysVia(node_id(2))));
+        assert_eq!(selector.select_path_weighted(target, &topo, &subnets, &metrics), vec![node_id(2)]);
+        topo.set_routing_override(target, Some(RoutingOverride::AlwaysVia(node_id(9))));
+        assert_eq!(selector.select_path_local(target, &topo, &subnets), vec![node_id(9)]);
+
+        // Not a path policy: normal selection
+        topo.set_routing_override(target, Some(RoutingOverride::NeverRelayFor));
+        assert_eq!(selector.select_path(target, &topo), vec![node_id(1)]);
+        topo.set_routing_override(target, None);
+        assert_eq!(topo.routing_override(&target), None);
+    }
+
+    #[test]
+    fn hosted_identities_route_through_their_host() {
+        let selector = RelaySelector::new(node_id(100));
+        let subnets = EphemeralSubnetManager::new(node_id(100));
+        let metrics = RelayMetrics::new();
+        let mut topo = Topology::new();
+        let identity = node_id(50);
+        topo.upsert(make_relay(1, 1000));
+        topo.upsert(make_peer(2));
+
+        // Unknown as a peer, still routed to its host
+        topo.set_host(identity, node_id(2));
+        assert_eq!(selector.select_path(identity, &topo), vec![node_id(2)]);
+        assert_eq!(selector.select_path_weighted(identity, &topo, &subnets, &metrics), vec![node_id(2)]);
+
+        // Hosted by us: no hop
+        topo.set_host(identity, node_id(100));
+        assert!(selector.select_path_local(identity, &topo, &subnets).is_empty());
+
+        topo.remove(&identity);
+        assert_eq!(topo.host_of(&identity), None);
+    }
+
     #[test]
     fn topology_max_peers_cap() {
         use rand::SeedableRng;
@@ -466,6 +1438,51 @@ mod tests {
         assert_eq!(topo.get(&existing_id).unwrap().role, PeerRole::Relay);
     }
 
+    #[test]
+    fn topology_at_capacity_evicts_longest_departed() {
+        let mut topo = Topology::with_max_peers(3);
+        let peer = |seed: u8, status, last_seen| PeerInfo {
+            node_id: node_id(seed),
+            role: PeerRole::Peer,
+            status,
+            last_seen,
+        };
+        topo.upsert(peer(1, PeerStatus::Offline, 500));
+        topo.upsert(peer(2, PeerStatus::Offline, 100));
+        topo.upsert(peer(3, PeerStatus::Online, 50));
+
+        assert!(topo.upsert(peer(4, PeerStatus::Online, 1000)));
+        assert_eq!(topo.len(), 3);
+        assert!(topo.get(&node_id(2)).is_none(), "oldest offline peer evicted");
+        assert!(topo.get(&node_id(3)).is_some(), "online peers are never evicted");
+        assert_eq!(topo.take_evicted(), vec![node_id(2)]);
+        assert!(topo.take_evicted().is_empty());
+
+        assert!(topo.upsert(peer(5, PeerStatus::Online, 1000)));
+        assert!(!topo.upsert(peer(6, PeerStatus::Online, 1000)), "nobody left to evict");
+        assert_eq!(topo.take_evicted(), vec![node_id(1)]);
+    }
+
+    #[test]
+    fn topology_prunes_departed_after_ttl() {
+        let mut topo = Topology::new();
+        for (seed, status, last_seen) in [
+            (1, PeerStatus::Offline, 1_000),
+            (2, PeerStatus::Offline, 9_000),
+            (3, PeerStatus::Online, 1_000),
+        ] {
+            topo.upsert(PeerInfo {
+                node_id: node_id(seed),
+                role: PeerRole::Peer,
+                status,
+                last_seen,
+            });
+        }
+        assert_eq!(topo.prune_departed(10_000, 5_000), vec![node_id(1)]);
+        assert_eq!(topo.len(), 2);
+        assert!(topo.prune_departed(10_000, 5_000).is_empty());
+    }
+
     #[test]
     fn topology_upsert_updates_existing() {
         let mut topo = Topology::new();
@@ -489,4 +1506,41 @@ mod tests {
         assert_eq!(topo.get(&id).unwrap().status, PeerStatus::Offline);
         assert_eq!(topo.get(&id).unwrap().last_seen, 2000);
     }
+
+    // ── Metrics tests ──────────────────────────────────────────────────
+
+    #[test]
+    fn rtt_is_a_moving_average() {
+        let mut metrics = RelayMetrics::new();
+        let relay = node_id(1);
+        assert_eq!(metrics.rtt_ms(&relay), None);
+        metrics.record_rtt(relay, 100);
+        metrics.record_rtt(relay, 200);
+        assert_eq!(metrics.rtt_ms(&relay), Some(130));
+    }
+
+    #[test]
+    fn relays_toward_prefers_last_relay_then_lowest_rtt() {
+        let mut topo = Topology::new();
+        let (r1, r2, r3, home) = (node_id(1), node_id(2), node_id(3), node_id(4));
+        topo.upsert(make_relay(1, 3000));
+        topo.upsert(make_relay(2, 2000));
+        topo.upsert(make_relay(3, 1000));
+        // The target's relay needn't be marked a relay in our topology
+        topo.upsert(make_peer(4));
+        let target = node_id(9);
+        let self_id = node_id(10);
+
+        let mut metrics = RelayMetrics::new();
+        metrics.record_rtt(r2, 50);
+        metrics.record_rtt(r3, 20);
+        assert_eq!(metrics.relays_toward(target, self_id, &topo), vec![r3, r2, r1]);
+
+        metrics.record_path(target, home);
+        assert_eq!(metrics.relays_toward(target, self_id, &topo), vec![home, r3, r2, r1]);
+
+        // An offline last relay is skipped
+        topo.get_mut(&home).unwrap().status = PeerStatus::Offline;
+        assert_eq!(metrics.relays_toward(target, self_id, &topo)[0], r3);
+    }
 }
@@ -7,6 +7,10 @@
 //!   effective_rate = min_rate + (max_rate - min_rate) * score / (score + midpoint)
 //!
 //! At score=0: 10 msg/sec (never blocked). At score=10: 30 msg/sec. At score=50: 43 msg/sec.
+//!
+//! 1:1 chat also has fixed per-sender budgets ([`ChatThrottle`]): one for
+//! messages delivered to us, one for messages we forward on a sender's
+//! behalf, so no single peer can flood our inbox or our relay capacity.
 
 use std::num::NonZeroUsize;
 
@@ -46,6 +50,28 @@ impl Default for AntiSpamConfig {
     }
 }
 
+/// Most senders with a 1:1 chat budget per scope (LRU-evicted past it).
+const MAX_THROTTLED_SENDERS: usize = 10_000;
+
+/// Per-sender 1:1 chat limits, in messages/sec (bursts of twice that).
+/// 0 disables a limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChatRateLimits {
+    /// Chat messages from one sender delivered to us.
+    pub delivery_rate: f64,
+    /// Chat messages from one sender we relay to someone else.
+    pub forward_rate: f64,
+}
+
+impl Default for ChatRateLimits {
+    fn default() -> Self {
+        Self {
+            delivery_rate: 10.0,
+            forward_rate: 30.0,
+        }
+    }
+}
+
 // ── Token Bucket ───────────────────────────────────────────────────────
 
 /// Token bucket for rate limiting a single sender.
@@ -173,6 +199,71 @@ impl AntiSpam {
     }
 }
 
+// ── 1:1 chat throttle ──────────────────────────────────────────────────
+
+/// Which 1:1 chat budget a sender exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+pub enum ThrottleScope {
+    Delivery,
+    Forwarding,
+}
+
+/// Outcome of a [`ChatThrottle`] check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleVerdict {
+    Allowed,
+    /// Over budget. `first` marks the first drop since the sender was last
+    /// allowed, so a flood is reported once, not per message.
+    Dropped { first: bool },
+}
+
+#[derive(Debug, Clone)]
+struct SenderBudget {
+    bucket: TokenBucket,
+    throttled: bool,
+}
+
+/// Fixed per-sender token buckets for 1:1 chat delivery and forwarding.
+pub struct ChatThrottle {
+    limits: ChatRateLimits,
+    delivery: LruCache<NodeId, SenderBudget>,
+    forwarding: LruCache<NodeId, SenderBudget>,
+}
+
+impl ChatThrottle {
+    pub fn new(limits: ChatRateLimits) -> Self {
+        let cap = NonZeroUsize::new(MAX_THROTTLED_SENDERS).expect("non-zero");
+        Self {
+            limits,
+            delivery: LruCache::new(cap),
+            forwarding: LruCache::new(cap),
+        }
+    }
+
+    /// Spend one message of `sender`'s budget in `scope`.
+    pub fn check(&mut self, scope: ThrottleScope, sender: NodeId, now: u64) -> ThrottleVerdict {
+        let (budgets, rate) = match scope {
+            ThrottleScope::Delivery => (&mut self.delivery, self.limits.delivery_rate),
+            ThrottleScope::Forwarding => (&mut self.forwarding, self.limits.forward_rate),
+        };
+        if rate <= 0.0 {
+            return ThrottleVerdict::Allowed;
+        }
+        let budget = budgets.get_or_insert_mut(sender, || SenderBudget {
+            bucket: TokenBucket::new(rate, now),
+            throttled: false,
+        });
+        if budget.bucket.try_consume(now) {
+            budget.throttled = false;
+            ThrottleVerdict::Allowed
+        } else {
+            let first = !budget.throttled;
+            budget.throttled = true;
+            ThrottleVerdict::Dropped { first }
+        }
+    }
+}
+
 // ── Tests ──────────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -299,6 +390,44 @@ mod tests {
         assert!(AntiSpam::validate_size(&huge, max).is_err());
     }
 
+    // ── 1:1 chat throttle ──────────────────────────────────────────
+
+    #[test]
+    fn chat_throttle_budgets_are_per_sender_and_scope() {
+        let mut throttle = ChatThrottle::new(ChatRateLimits {
+            delivery_rate: 2.0,
+            forward_rate: 0.0,
+        });
+        let (flooder, other) = (test_node_id(1), test_node_id(2));
+
+        // Burst of 4, then one report per episode
+        for _ in 0..4 {
+            assert_eq!(throttle.check(ThrottleScope::Delivery, flooder, 0), ThrottleVerdict::Allowed);
+        }
+        assert_eq!(
+            throttle.check(ThrottleScope::Delivery, flooder, 0),
+            ThrottleVerdict::Dropped { first: true }
+        );
+        assert_eq!(
+            throttle.check(ThrottleScope::Delivery, flooder, 0),
+            ThrottleVerdict::Dropped { first: false }
+        );
+        assert_eq!(throttle.check(ThrottleScope::Delivery, other, 0), ThrottleVerdict::Allowed);
+
+        // Refilled after a second; a new flood is reported again
+        assert_eq!(throttle.check(ThrottleScope::Delivery, flooder, 1000), ThrottleVerdict::Allowed);
+        assert_eq!(throttle.check(ThrottleScope::Delivery, flooder, 1000), ThrottleVerdict::Allowed);
+        assert_eq!(
+            throttle.check(ThrottleScope::Delivery, flooder, 1000),
+            ThrottleVerdict::Dropped { first: true }
+        );
+
+        // Rate 0: unlimited
+        for _ in 0..100 {
+            assert_eq!(throttle.check(ThrottleScope::Forwarding, flooder, 0), ThrottleVerdict::Allowed);
+        }
+    }
+
     #[test]
     fn lru_eviction() {
         let mut config = AntiSpamConfig::default();
@@ -1,19 +1,91 @@
 /// Role manager — evaluates contribution scores and triggers role changes.
 ///
 /// Periodically called by the runtime to check if any node should be
-/// promoted (Peer → Relay) or demoted (Relay → Peer).
-use std::collections::HashMap;
+/// promoted (Peer → Relay) or demoted (Relay → Peer), and whether the local
+/// node should take on or drop the extra Storage and Bootstrap roles.
+/// Remote nodes' extra roles come from their own announces.
+use std::collections::{HashMap, VecDeque};
 
+use crate::error::TomProtocolError;
 use crate::relay::{PeerRole, Topology};
 use crate::types::NodeId;
 
-use super::scoring::ContributionMetrics;
+use super::metrics::{ScoreSample, SCORE_HISTORY_BUCKETS, SCORE_HISTORY_BUCKET_MS};
+use super::scoring::{ContributionMetrics, ATTESTED_RELAY_WEIGHT, DEFAULT_DECAY_PER_HOUR};
 
-/// Score threshold for promotion to Relay.
-const PROMOTION_THRESHOLD: f64 = 10.0;
+/// Default score threshold for promotion to Relay.
+pub const PROMOTION_THRESHOLD: f64 = 10.0;
 
-/// Score threshold below which a Relay is demoted back to Peer.
-const DEMOTION_THRESHOLD: f64 = 2.0;
+/// Default score threshold below which a Relay is demoted back to Peer.
+pub const DEMOTION_THRESHOLD: f64 = 2.0;
+
+/// Relay promotion/demotion thresholds and score decay.
+///
+/// Small networks can promote sooner (lower `promotion_threshold`), large
+/// ones can be more conservative.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoleConfig {
+    /// Score at which a Peer is promoted to Relay.
+    pub promotion_threshold: f64,
+    /// Score below which a Relay is demoted back to Peer.
+    pub demotion_threshold: f64,
+    /// Fraction of score (and penalties, join assists) lost per idle hour.
+    pub decay_per_hour: f64,
+}
+
+impl Default for RoleConfig {
+    fn default() -> Self {
+        Self {
+            promotion_threshold: PROMOTION_THRESHOLD,
+            demotion_threshold: DEMOTION_THRESHOLD,
+            decay_per_hour: DEFAULT_DECAY_PER_HOUR,
+        }
+    }
+}
+
+impl RoleConfig {
+    /// Thresholds must be finite with `0 <= demotion < promotion`, and the
+    /// decay rate within `0..=1` per hour.
+    pub fn validate(&self) -> Result<(), TomProtocolError> {
+        let invalid = |reason: &str| {
+            Err(TomProtocolError::InvalidConfig {
+                reason: reason.to_string(),
+            })
+        };
+        if !self.promotion_threshold.is_finite() || !self.demotion_threshold.is_finite() {
+            return invalid("role thresholds must be finite");
+        }
+        if self.demotion_threshold < 0.0 {
+            return invalid("demotion threshold must not be negative");
+        }
+        if self.demotion_threshold >= self.promotion_threshold {
+            return invalid("demotion threshold must be below the promotion threshold");
+        }
+        if !(0.0..=1.0).contains(&self.decay_per_hour) {
+            return invalid("decay rate must be between 0 and 1 per hour");
+        }
+        Ok(())
+    }
+}
+
+/// Attester score at which its attestations count half (Sybil resistance:
+/// a zero-score identity's attestations count for nothing).
+const ATTESTER_WEIGHT_MIDPOINT: f64 = 10.0;
+
+/// Most forwards one attestation can credit to a relay.
+const MAX_ATTESTED_FORWARDS: u32 = 50;
+
+/// Backup bytes held for others to take on the Storage role (16 MiB).
+const STORAGE_PROMOTION_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Below this, the Storage role is dropped (4 MiB).
+const STORAGE_DEMOTION_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Decayed join assists to take on the Bootstrap role.
+const BOOTSTRAP_PROMOTION_ASSISTS: f64 = 5.0;
+
+/// Below this, the Bootstrap role is dropped.
+const BOOTSTRAP_DEMOTION_ASSISTS: f64 = 1.0;
 
 /// Actions the runtime should execute after a role evaluation.
 #[derive(Debug, Clone, PartialEq)]
@@ -24,22 +96,73 @@ pub enum RoleAction {
     Demoted { node_id: NodeId, score: f64 },
     /// Our local role changed — update gossip announces.
     LocalRoleChanged { new_role: PeerRole },
+    /// We took on an extra role (Storage, Bootstrap).
+    LocalRoleGranted { role: PeerRole },
+    /// We dropped an extra role.
+    LocalRoleRevoked { role: PeerRole },
 }
 
 /// Manages contribution scores and role transitions.
 pub struct RoleManager {
     local_id: NodeId,
+    config: RoleConfig,
     scores: HashMap<NodeId, ContributionMetrics>,
+    /// Ring buffer of hourly score samples per node, taken at evaluation.
+    history: HashMap<NodeId, VecDeque<ScoreSample>>,
+    /// Extra roles the local node currently holds.
+    local_extra_roles: Vec<PeerRole>,
 }
 
 impl RoleManager {
     pub fn new(local_id: NodeId) -> Self {
+        Self::with_config(local_id, RoleConfig::default())
+    }
+
+    /// Create a manager with custom thresholds and decay. An invalid
+    /// config (see [`RoleConfig::validate`]) falls back to the defaults.
+    pub fn with_config(local_id: NodeId, config: RoleConfig) -> Self {
+        let config = match config.validate() {
+            Ok(()) => config,
+            Err(e) => {
+                tracing::warn!("{e}, using default role thresholds");
+                RoleConfig::default()
+            }
+        };
         Self {
             local_id,
+            config,
             scores: HashMap::new(),
+            history: HashMap::new(),
+            local_extra_roles: Vec::new(),
         }
     }
 
+    /// Thresholds and decay in use.
+    pub fn config(&self) -> &RoleConfig {
+        &self.config
+    }
+
+    /// Extra roles (Storage, Bootstrap) the local node holds.
+    pub fn local_extra_roles(&self) -> &[PeerRole] {
+        &self.local_extra_roles
+    }
+
+    /// Set the backup bytes a node currently holds for others.
+    pub fn record_bytes_stored(&mut self, node_id: NodeId, bytes: u64, now: u64) {
+        self.scores
+            .entry(node_id)
+            .or_insert_with(|| ContributionMetrics::new(now))
+            .bytes_stored = bytes;
+    }
+
+    /// Record a node joining the network through `node_id`.
+    pub fn record_join_assist(&mut self, node_id: NodeId, now: u64) {
+        self.scores
+            .entry(node_id)
+            .or_insert_with(|| ContributionMetrics::new(now))
+            .record_join_assist_with_decay(now, self.config.decay_per_hour);
+    }
+
     /// Record a successful relay by a node.
     pub fn record_relay(&mut self, node_id: NodeId, now: u64) {
         self.scores
@@ -56,11 +179,47 @@ impl RoleManager {
             .record_relay_failure(now);
     }
 
+    /// Penalize a node for a security violation (observed or reported).
+    /// The penalty decays like the score — a reputation hit, never a ban.
+    pub fn record_violation(&mut self, node_id: NodeId, points: f64, now: u64) {
+        self.scores
+            .entry(node_id)
+            .or_insert_with(|| ContributionMetrics::new(now))
+            .record_penalty_with_decay(points, now, self.config.decay_per_hour);
+    }
+
+    /// Credit `relay` with `forwards` attested by `attester`, weighted by
+    /// the attester's own score: `score / (score + midpoint)`. Unknown or
+    /// zero-score attesters (e.g. fresh Sybil identities) add nothing.
+    /// Returns the points credited.
+    pub fn record_attestation(
+        &mut self,
+        attester: NodeId,
+        relay: NodeId,
+        forwards: u32,
+        now: u64,
+    ) -> f64 {
+        if attester == relay {
+            return 0.0;
+        }
+        let standing = self.score(&attester, now);
+        let weight = standing / (standing + ATTESTER_WEIGHT_MIDPOINT);
+        let points = forwards.min(MAX_ATTESTED_FORWARDS) as f64 * ATTESTED_RELAY_WEIGHT * weight;
+        if points <= 0.0 {
+            return 0.0;
+        }
+        self.scores
+            .entry(relay)
+            .or_insert_with(|| ContributionMetrics::new(now))
+            .record_corroboration_with_decay(points, now, self.config.decay_per_hour);
+        points
+    }
+
     /// Get the current contribution score for a node.
     pub fn score(&self, node_id: &NodeId, now: u64) -> f64 {
         self.scores
             .get(node_id)
-            .map(|m| m.score(now))
+            .map(|m| m.score_with_decay(now, self.config.decay_per_hour))
             .unwrap_or(0.0)
     }
 
@@ -86,6 +245,39 @@ impl RoleManager {
     /// Remove all metrics for a departed node.
     pub fn remove_node(&mut self, node_id: &NodeId) {
         self.scores.remove(node_id);
+        self.history.remove(node_id);
+    }
+
+    /// Hourly score samples for a node over the last 24 hours, oldest first.
+    pub fn score_history(&self, node_id: &NodeId) -> Vec<ScoreSample> {
+        self.history
+            .get(node_id)
+            .map(|samples| samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Record every tracked node's current score in the bucket containing
+    /// `now`, replacing an earlier sample from the same bucket and
+    /// dropping samples older than the history window.
+    fn sample_scores(&mut self, now: u64) {
+        let bucket_start = now - now % SCORE_HISTORY_BUCKET_MS;
+        let oldest = bucket_start
+            .saturating_sub((SCORE_HISTORY_BUCKETS as u64 - 1) * SCORE_HISTORY_BUCKET_MS);
+        for (node_id, metrics) in &self.scores {
+            let score = metrics.score_with_decay(now, self.config.decay_per_hour);
+            let samples = self.history.entry(*node_id).or_default();
+            match samples.back_mut() {
+                Some(last) if last.bucket_start == bucket_start => last.score = score,
+                _ => samples.push_back(ScoreSample { bucket_start, score }),
+            }
+            while samples.front().is_some_and(|s| s.bucket_start < oldest)
+                || samples.len() > SCORE_HISTORY_BUCKETS
+            {
+                samples.pop_front();
+            }
+        }
+        let scores = &self.scores;
+        self.history.retain(|node_id, _| scores.contains_key(node_id));
     }
 
     /// Access the raw scores map (for persistence).
@@ -136,6 +328,7 @@ impl RoleManager {
             uptime_hours: metrics.total_uptime_ms as f64 / 3_600_000.0,
             first_seen: metrics.first_seen,
             last_activity: metrics.last_activity,
+            score_history: self.score_history(node_id),
         })
     }
 
@@ -156,17 +349,19 @@ impl RoleManager {
 
     /// Evaluate all tracked nodes and update topology roles.
     ///
-    /// Returns a list of actions (promotions, demotions, local role change).
+    /// Returns a list of actions (promotions, demotions, local role changes).
     /// The runtime executes these actions and surfaces events to the application.
-    pub fn evaluate(&self, topology: &mut Topology, now: u64) -> Vec<RoleAction> {
+    /// Also samples every score into its hourly history.
+    pub fn evaluate(&mut self, topology: &mut Topology, now: u64) -> Vec<RoleAction> {
+        self.sample_scores(now);
         let mut actions = Vec::new();
 
         for (node_id, metrics) in &self.scores {
-            let score = metrics.score(now);
+            let score = metrics.score_with_decay(now, self.config.decay_per_hour);
             let current_role = topology.get(node_id).map(|p| p.role);
 
             match current_role {
-                Some(PeerRole::Peer) if score >= PROMOTION_THRESHOLD => {
+                Some(PeerRole::Peer) if score >= self.config.promotion_threshold => {
                     // Promote: update topology role
                     if let Some(peer) = topology.get_mut(node_id) {
                         peer.role = PeerRole::Relay;
@@ -183,7 +378,7 @@ impl RoleManager {
                     };
                     actions.push(action);
                 }
-                Some(PeerRole::Relay) if score < DEMOTION_THRESHOLD => {
+                Some(PeerRole::Relay) if score < self.config.demotion_threshold => {
                     // Demote: update topology role
                     if let Some(peer) = topology.get_mut(node_id) {
                         peer.role = PeerRole::Peer;
@@ -204,6 +399,38 @@ impl RoleManager {
             }
         }
 
+        actions.extend(self.evaluate_local_extra_roles(now));
+        actions
+    }
+
+    /// Take on or drop Storage and Bootstrap, with hysteresis between the
+    /// promotion and demotion thresholds.
+    fn evaluate_local_extra_roles(&mut self, now: u64) -> Vec<RoleAction> {
+        let (stored, assists) = self
+            .scores
+            .get(&self.local_id)
+            .map(|m| (m.bytes_stored, m.join_assist_score_with_decay(now, self.config.decay_per_hour)))
+            .unwrap_or((0, 0.0));
+        let wanted = [
+            (PeerRole::Storage, stored >= STORAGE_PROMOTION_BYTES, stored < STORAGE_DEMOTION_BYTES),
+            (
+                PeerRole::Bootstrap,
+                assists >= BOOTSTRAP_PROMOTION_ASSISTS,
+                assists < BOOTSTRAP_DEMOTION_ASSISTS,
+            ),
+        ];
+
+        let mut actions = Vec::new();
+        for (role, promote, demote) in wanted {
+            let held = self.local_extra_roles.contains(&role);
+            if !held && promote {
+                self.local_extra_roles.push(role);
+                actions.push(RoleAction::LocalRoleGranted { role });
+            } else if held && demote {
+                self.local_extra_roles.retain(|r| *r != role);
+                actions.push(RoleAction::LocalRoleRevoked { role });
+            }
+        }
         actions
     }
 }
@@ -328,6 +555,158 @@ mod tests {
         );
     }
 
+    #[test]
+    fn violations_hold_back_promotion_until_they_decay() {
+        let local = test_node_id(1);
+        let node = test_node_id(2);
+        let mut mgr = RoleManager::new(local);
+        let mut topo = make_topology(&[(node, PeerRole::Peer)]);
+
+        for i in 0..20 {
+            mgr.record_relay(node, 1000 + i * 1000);
+        }
+        let clean = mgr.score(&node, 20_000);
+        for _ in 0..4 {
+            mgr.record_violation(node, crate::roles::scoring::LOCAL_VIOLATION_PENALTY, 20_000);
+        }
+        assert!(mgr.score(&node, 20_000) < clean);
+        assert!(mgr.evaluate(&mut topo, 20_000).is_empty());
+
+        // Keeps relaying: the penalty fades and promotion follows
+        for i in 0..40 {
+            mgr.record_relay(node, 20_000 + 50 * 3_600_000 + i * 1000);
+        }
+        let now = 20_000 + 50 * 3_600_000 + 40_000;
+        assert!(mgr.score(&node, now) >= 10.0);
+    }
+
+    #[test]
+    fn local_storage_and_bootstrap_roles_follow_their_criteria() {
+        let local = test_node_id(1);
+        let mut mgr = RoleManager::new(local);
+        let mut topo = Topology::new();
+
+        mgr.record_bytes_stored(local, STORAGE_PROMOTION_BYTES, 1000);
+        for _ in 0..5 {
+            mgr.record_join_assist(local, 1000);
+        }
+        let actions = mgr.evaluate(&mut topo, 1000);
+        assert!(actions.contains(&RoleAction::LocalRoleGranted { role: PeerRole::Storage }));
+        assert!(actions.contains(&RoleAction::LocalRoleGranted { role: PeerRole::Bootstrap }));
+        assert_eq!(mgr.local_extra_roles(), &[PeerRole::Storage, PeerRole::Bootstrap]);
+
+        // Between the thresholds: kept
+        mgr.record_bytes_stored(local, STORAGE_DEMOTION_BYTES, 2000);
+        assert!(mgr.evaluate(&mut topo, 2000).is_empty());
+
+        // Storage emptied, and no one joined through us for two days
+        mgr.record_bytes_stored(local, 0, 3000);
+        let later = 3000 + 48 * 3_600_000;
+        let actions = mgr.evaluate(&mut topo, later);
+        assert!(actions.contains(&RoleAction::LocalRoleRevoked { role: PeerRole::Storage }));
+        assert!(actions.contains(&RoleAction::LocalRoleRevoked { role: PeerRole::Bootstrap }));
+        assert!(mgr.local_extra_roles().is_empty());
+    }
+
+    #[test]
+    fn custom_thresholds_promote_sooner() {
+        let local = test_node_id(1);
+        let node = test_node_id(2);
+        let config = RoleConfig {
+            promotion_threshold: 5.0,
+            demotion_threshold: 1.0,
+            ..RoleConfig::default()
+        };
+        let mut mgr = RoleManager::with_config(local, config);
+        let mut topo = make_topology(&[(node, PeerRole::Peer)]);
+
+        // One relay: ~6 points, under the default threshold of 10
+        mgr.record_relay(node, 1000);
+        let actions = mgr.evaluate(&mut topo, 1000);
+        assert!(matches!(&actions[..], [RoleAction::Promoted { .. }]));
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let inverted = RoleConfig {
+            promotion_threshold: 1.0,
+            demotion_threshold: 2.0,
+            ..RoleConfig::default()
+        };
+        assert!(inverted.validate().is_err());
+        let runaway = RoleConfig {
+            decay_per_hour: 1.5,
+            ..RoleConfig::default()
+        };
+        assert!(runaway.validate().is_err());
+        let nan = RoleConfig {
+            promotion_threshold: f64::NAN,
+            ..RoleConfig::default()
+        };
+        assert!(nan.validate().is_err());
+        assert!(RoleConfig::default().validate().is_ok());
+
+        // Falls back to the defaults
+        let mgr = RoleManager::with_config(test_node_id(1), inverted);
+        assert_eq!(mgr.config(), &RoleConfig::default());
+    }
+
+    #[test]
+    fn attestations_count_by_attester_standing() {
+        let local = test_node_id(1);
+        let (trusted, sybil, relay) = (test_node_id(2), test_node_id(3), test_node_id(4));
+        let mut mgr = RoleManager::new(local);
+        for i in 0..10 {
+            mgr.record_relay(trusted, 1000 + i * 1000);
+        }
+
+        // A fresh identity can't vouch for anyone
+        assert_eq!(mgr.record_attestation(sybil, relay, 50, 11_000), 0.0);
+        assert_eq!(mgr.score(&relay, 11_000), 0.0);
+
+        // An established node can, capped per attestation
+        let credited = mgr.record_attestation(trusted, relay, 1_000, 11_000);
+        assert!(credited > 0.0 && credited < MAX_ATTESTED_FORWARDS as f64 * ATTESTED_RELAY_WEIGHT);
+        assert!((mgr.score(&relay, 11_000) - credited).abs() < 1e-9);
+
+        // Enough corroboration promotes a relay we never used
+        let mut topo = make_topology(&[(relay, PeerRole::Peer)]);
+        mgr.record_attestation(trusted, relay, 50, 11_000);
+        let actions = mgr.evaluate(&mut topo, 11_000);
+        assert!(actions.iter().any(|a| matches!(a, RoleAction::Promoted { node_id, .. } if *node_id == relay)));
+    }
+
+    #[test]
+    fn score_history_keeps_one_sample_per_hour_for_a_day() {
+        let node = test_node_id(2);
+        let mut mgr = RoleManager::new(test_node_id(1));
+        let mut topo = make_topology(&[(node, PeerRole::Peer)]);
+        let hour = SCORE_HISTORY_BUCKET_MS;
+        mgr.record_relay(node, 0);
+
+        // Two evaluations in the same hour share a bucket; the later wins
+        mgr.evaluate(&mut topo, 10);
+        mgr.record_relay(node, hour / 2);
+        mgr.evaluate(&mut topo, hour / 2);
+        let history = mgr.score_history(&node);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].bucket_start, 0);
+        assert!((history[0].score - mgr.score(&node, hour / 2)).abs() < 1e-9);
+
+        // A day and a half of hourly evaluations: only the last 24 remain
+        for h in 1..36 {
+            mgr.evaluate(&mut topo, h * hour + 1);
+        }
+        let history = mgr.score_history(&node);
+        assert_eq!(history.len(), SCORE_HISTORY_BUCKETS);
+        assert_eq!(history.first().unwrap().bucket_start, 12 * hour);
+        assert_eq!(history.last().unwrap().bucket_start, 35 * hour);
+        assert!(history.windows(2).all(|w| w[0].score >= w[1].score), "idle scores decay");
+
+        mgr.remove_node(&node);
+        assert!(mgr.score_history(&node).is_empty());
+    }
+
     #[test]
     fn remove_node_clears_metrics() {
         let local = test_node_id(1);
@@ -3,6 +3,20 @@
 use crate::relay::PeerRole;
 use crate::types::NodeId;
 
+/// Width of one score history bucket (1 hour).
+pub const SCORE_HISTORY_BUCKET_MS: u64 = 60 * 60 * 1000;
+
+/// Buckets kept in a score history (24 hours).
+pub const SCORE_HISTORY_BUCKETS: usize = 24;
+
+/// A node's score as last sampled within one history bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreSample {
+    /// Start of the bucket (Unix ms, a multiple of `SCORE_HISTORY_BUCKET_MS`).
+    pub bucket_start: u64,
+    pub score: f64,
+}
+
 /// Complete role metrics snapshot for a peer (debug/observability).
 #[derive(Debug, Clone)]
 pub struct RoleMetrics {
@@ -18,4 +32,7 @@ pub struct RoleMetrics {
     pub uptime_hours: f64,
     pub first_seen: u64,
     pub last_activity: u64,
+    /// Hourly score samples over the last 24 hours, oldest first. Buckets
+    /// without a role evaluation are missing.
+    pub score_history: Vec<ScoreSample>,
 }
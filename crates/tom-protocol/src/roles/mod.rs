@@ -8,7 +8,9 @@ pub mod manager;
 pub mod metrics;
 pub mod scoring;
 
-pub use antispam::{AntiSpam, AntiSpamConfig};
-pub use manager::{RoleAction, RoleManager};
-pub use metrics::RoleMetrics;
+pub use antispam::{
+    AntiSpam, AntiSpamConfig, ChatRateLimits, ChatThrottle, ThrottleScope, ThrottleVerdict,
+};
+pub use manager::{RoleAction, RoleConfig, RoleManager};
+pub use metrics::{RoleMetrics, ScoreSample, SCORE_HISTORY_BUCKETS, SCORE_HISTORY_BUCKET_MS};
 pub use scoring::ContributionMetrics;
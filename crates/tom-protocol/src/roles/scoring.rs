@@ -4,8 +4,13 @@
 //! with progressive decay (5%/hour since last activity). Scores are always
 //! recoverable — no permanent bans (design decision #4).
 
-/// Decay rate: 5% per hour (expressed as fraction per ms).
-const DECAY_RATE_PER_MS: f64 = 0.05 / 3_600_000.0;
+/// Default decay rate: 5% per hour.
+pub const DEFAULT_DECAY_PER_HOUR: f64 = 0.05;
+
+/// Decay factor after `idle_ms` at `per_hour`.
+fn decay_factor(per_hour: f64, idle_ms: u64) -> f64 {
+    (-per_hour / 3_600_000.0 * idle_ms as f64).exp()
+}
 
 // Scoring weight constants (tunable based on beta testing)
 
@@ -24,6 +29,16 @@ pub const BANDWIDTH_MB_WEIGHT: f64 = 0.2;
 /// Weight for give/take bandwidth ratio in score calculation.
 pub const BANDWIDTH_RATIO_WEIGHT: f64 = 1.5;
 
+/// Weight of one relay attested by a peer, before weighting by the
+/// attester's standing (a relay we saw ourselves counts `RELAY_COUNT_WEIGHT`).
+pub const ATTESTED_RELAY_WEIGHT: f64 = 0.5;
+
+/// Penalty for a violation we observed ourselves.
+pub const LOCAL_VIOLATION_PENALTY: f64 = 5.0;
+
+/// Penalty for a violation reported by another node (per reporter).
+pub const REPORTED_VIOLATION_PENALTY: f64 = 2.0;
+
 /// Contribution metrics for a single node.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ContributionMetrics {
@@ -41,6 +56,28 @@ pub struct ContributionMetrics {
     pub bytes_relayed: u64,
     /// Total bytes received from the network.
     pub bytes_received: u64,
+    /// Abuse penalty, subtracted from the score; decays like the score.
+    #[serde(default)]
+    pub abuse_penalty: f64,
+    /// Unix ms timestamp the penalty was last updated.
+    #[serde(default)]
+    pub penalty_updated: u64,
+    /// Backup bytes currently held for other peers.
+    #[serde(default)]
+    pub bytes_stored: u64,
+    /// Nodes that joined the network through this one (earlier assists
+    /// decayed each time a new one is recorded).
+    #[serde(default)]
+    pub join_assists: u64,
+    /// Unix ms timestamp of the last join assist.
+    #[serde(default)]
+    pub last_join_assist: u64,
+    /// Points from other nodes' relay attestations; decays like the score.
+    #[serde(default)]
+    pub corroboration: f64,
+    /// Unix ms timestamp the corroboration was last updated.
+    #[serde(default)]
+    pub corroboration_updated: u64,
 }
 
 impl ContributionMetrics {
@@ -54,9 +91,50 @@ impl ContributionMetrics {
             total_uptime_ms: 0,
             bytes_relayed: 0,
             bytes_received: 0,
+            abuse_penalty: 0.0,
+            penalty_updated: now,
+            bytes_stored: 0,
+            join_assists: 0,
+            last_join_assist: now,
+            corroboration: 0.0,
+            corroboration_updated: now,
         }
     }
 
+    /// Add points from a peer's relay attestation, at a given decay rate.
+    pub fn record_corroboration_with_decay(&mut self, points: f64, now: u64, decay_per_hour: f64) {
+        self.corroboration = self.corroboration_with_decay(now, decay_per_hour) + points;
+        self.corroboration_updated = now;
+    }
+
+    /// Attested points remaining at the given timestamp.
+    pub fn corroboration_with_decay(&self, now: u64, decay_per_hour: f64) -> f64 {
+        let idle_ms = now.saturating_sub(self.corroboration_updated);
+        self.corroboration * decay_factor(decay_per_hour, idle_ms)
+    }
+
+    /// Record a node joining the network through this one.
+    pub fn record_join_assist(&mut self, now: u64) {
+        self.record_join_assist_with_decay(now, DEFAULT_DECAY_PER_HOUR);
+    }
+
+    /// [`record_join_assist`](Self::record_join_assist) at a custom decay rate.
+    pub fn record_join_assist_with_decay(&mut self, now: u64, decay_per_hour: f64) {
+        self.join_assists = self.join_assist_score_with_decay(now, decay_per_hour).round() as u64 + 1;
+        self.last_join_assist = now;
+    }
+
+    /// Join assists, decayed by 5%/hour since the last one.
+    pub fn join_assist_score(&self, now: u64) -> f64 {
+        self.join_assist_score_with_decay(now, DEFAULT_DECAY_PER_HOUR)
+    }
+
+    /// [`join_assist_score`](Self::join_assist_score) at a custom decay rate.
+    pub fn join_assist_score_with_decay(&self, now: u64, decay_per_hour: f64) -> f64 {
+        let idle_ms = now.saturating_sub(self.last_join_assist);
+        self.join_assists as f64 * decay_factor(decay_per_hour, idle_ms)
+    }
+
     /// Record a successful relay.
     pub fn record_relay(&mut self, now: u64) {
         self.messages_relayed += 1;
@@ -73,11 +151,39 @@ impl ContributionMetrics {
         self.last_activity = now;
     }
 
+    /// Add an abuse penalty (on top of what is left of earlier ones).
+    pub fn record_penalty(&mut self, points: f64, now: u64) {
+        self.record_penalty_with_decay(points, now, DEFAULT_DECAY_PER_HOUR);
+    }
+
+    /// [`record_penalty`](Self::record_penalty) at a custom decay rate.
+    pub fn record_penalty_with_decay(&mut self, points: f64, now: u64, decay_per_hour: f64) {
+        self.abuse_penalty = self.penalty_with_decay(now, decay_per_hour) + points;
+        self.penalty_updated = now;
+    }
+
+    /// The abuse penalty remaining at the given timestamp.
+    pub fn penalty_at(&self, now: u64) -> f64 {
+        self.penalty_with_decay(now, DEFAULT_DECAY_PER_HOUR)
+    }
+
+    /// [`penalty_at`](Self::penalty_at) at a custom decay rate.
+    pub fn penalty_with_decay(&self, now: u64, decay_per_hour: f64) -> f64 {
+        let idle_ms = now.saturating_sub(self.penalty_updated);
+        self.abuse_penalty * decay_factor(decay_per_hour, idle_ms)
+    }
+
     /// Compute the contribution score at the given timestamp.
     ///
     /// The raw score is: relay_count * W_relay + success_rate * W_success + uptime_hours * W_uptime
-    /// Then decayed by 5%/hour since last_activity.
+    /// Then decayed by 5%/hour since last_activity, plus points attested by
+    /// peers, minus the abuse penalty (both decaying alike), never below zero.
     pub fn score(&self, now: u64) -> f64 {
+        self.score_with_decay(now, DEFAULT_DECAY_PER_HOUR)
+    }
+
+    /// [`score`](Self::score) at a custom decay rate (fraction per hour).
+    pub fn score_with_decay(&self, now: u64, decay_per_hour: f64) -> f64 {
         let total_attempts = self.messages_relayed + self.relay_failures;
         let success_rate = if total_attempts == 0 {
             0.0
@@ -106,10 +212,11 @@ impl ContributionMetrics {
             + bandwidth_ratio * BANDWIDTH_RATIO_WEIGHT;
 
         // Progressive decay since last activity
-        let idle_ms = now.saturating_sub(self.last_activity) as f64;
-        let decay = (-DECAY_RATE_PER_MS * idle_ms).exp();
+        let decay = decay_factor(decay_per_hour, now.saturating_sub(self.last_activity));
 
-        raw * decay
+        (raw * decay + self.corroboration_with_decay(now, decay_per_hour)
+            - self.penalty_with_decay(now, decay_per_hour))
+            .max(0.0)
     }
 }
 
@@ -208,6 +315,33 @@ mod tests {
         assert!(score < 40.0, "Expected score < 40 with bandwidth, got {}", score);
     }
 
+    #[test]
+    fn penalty_lowers_score_and_decays() {
+        let mut m = ContributionMetrics::new(0);
+        m.record_relay(1000);
+        let clean = m.score(1000);
+
+        m.record_penalty(LOCAL_VIOLATION_PENALTY, 1000);
+        assert_eq!(m.score(1000), (clean - LOCAL_VIOLATION_PENALTY).max(0.0));
+        m.record_penalty(LOCAL_VIOLATION_PENALTY, 1000);
+        assert_eq!(m.score(1000), 0.0, "score never goes negative");
+
+        // Penalties stack on what is left, and fade over time
+        let later = 1000 + 24 * 3_600_000;
+        assert!(m.penalty_at(later) < 2.0 * LOCAL_VIOLATION_PENALTY);
+        assert!(m.penalty_at(later) > 0.0);
+    }
+
+    #[test]
+    fn faster_decay_fades_scores_sooner() {
+        let mut m = ContributionMetrics::new(0);
+        m.record_relay(1000);
+        let later = 1000 + 10 * 3_600_000;
+        assert_eq!(m.score(later), m.score_with_decay(later, DEFAULT_DECAY_PER_HOUR));
+        assert!(m.score_with_decay(later, 0.2) < m.score(later));
+        assert_eq!(m.score_with_decay(later, 0.0), m.score(1000));
+    }
+
     #[test]
     fn uptime_contributes_to_score() {
         let mut m = ContributionMetrics::new(0);
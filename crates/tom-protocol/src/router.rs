@@ -3,15 +3,15 @@
 /// Pure decision logic — receives an envelope, returns a `RoutingAction`
 /// telling the caller what to do (deliver, forward, reject, drop).
 /// No I/O, no transport dependency.
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::num::NonZeroUsize;
-use std::time::{Duration, Instant};
 
 use lru::LruCache;
 use serde::{Deserialize, Serialize};
 
 use crate::envelope::Envelope;
 use crate::error::TomProtocolError;
+use crate::time::{Duration, Instant};
 use crate::types::{now_ms, MessageType, NodeId};
 
 /// Maximum relay chain depth (ToM design decision #2).
@@ -67,6 +67,48 @@ pub enum RoutingAction {
     Drop,
 }
 
+// ── Relay policy ───────────────────────────────────────────────────────
+
+/// Who we relay for. The requester is whoever handed us the envelope:
+/// the previous relay in the chain, or the sender for the first hop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RelayPolicy {
+    /// Relay for anyone (free relay).
+    Open,
+    /// Relay for any peer we know; unknown nodes are refused.
+    #[default]
+    KnownPeers,
+    /// Relay only for Relay-role peers and explicitly trusted contacts.
+    RelaysAndTrusted,
+}
+
+/// How we regard a relay requester, as supplied by the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayStanding {
+    /// Not in our topology or contacts.
+    Unknown,
+    /// Known, with the Peer role.
+    Peer,
+    /// Known, with the Relay role.
+    Relay,
+    /// Contact we explicitly trust.
+    Trusted,
+}
+
+impl RelayPolicy {
+    /// Why a requester is refused, or `None` if we relay for it.
+    fn refusal(self, requester: NodeId, standing: RelayStanding) -> Option<String> {
+        match (self, standing) {
+            (Self::Open, _) | (_, RelayStanding::Relay | RelayStanding::Trusted) => None,
+            (_, RelayStanding::Unknown) => Some(format!("relay refused: unknown peer {requester}")),
+            (Self::KnownPeers, RelayStanding::Peer) => None,
+            (Self::RelaysAndTrusted, RelayStanding::Peer) => Some(format!(
+                "relay refused: {requester} is neither a relay nor trusted"
+            )),
+        }
+    }
+}
+
 // ── ACK types ──────────────────────────────────────────────────────────
 
 /// ACK subtypes for message status pipeline.
@@ -125,6 +167,16 @@ pub struct Router {
     ack_cache: HashMap<String, Instant>,
     /// Nonce anti-replay cache for encrypted 1-1 messages (R11.2).
     nonce_cache: LruCache<[u8; 24], ()>,
+    /// Senders whose envelopes are dropped on arrival (blocked contacts).
+    blocked: HashSet<NodeId>,
+    /// Envelopes dropped because their sender is blocked.
+    blocked_drops: u64,
+    /// Who we relay for.
+    relay_policy: RelayPolicy,
+    /// Senders whose traffic we never forward, whatever the policy.
+    never_relay_for: HashSet<NodeId>,
+    /// Forwards refused by the relay policy.
+    relays_refused: u64,
 }
 
 impl Router {
@@ -136,6 +188,11 @@ impl Router {
             nonce_cache: LruCache::new(
                 NonZeroUsize::new(MAX_NONCE_CACHE).expect("MAX_NONCE_CACHE > 0"),
             ),
+            blocked: HashSet::new(),
+            blocked_drops: 0,
+            relay_policy: RelayPolicy::Open,
+            never_relay_for: HashSet::new(),
+            relays_refused: 0,
         }
     }
 
@@ -147,8 +204,23 @@ impl Router {
     /// Route an incoming envelope. Returns the action to take.
     ///
     /// All returned envelopes (ACKs) are **unsigned** — the caller must
-    /// sign them before sending.
+    /// sign them before sending. Every relay requester counts as unknown;
+    /// use `route_with_standing` under a policy other than `Open`.
     pub fn route(&mut self, envelope: Envelope) -> RoutingAction {
+        self.route_with_standing(envelope, |_| RelayStanding::Unknown)
+    }
+
+    /// Route an incoming envelope, checking forwards against the relay
+    /// policy with `standing` telling how we regard the requester.
+    pub fn route_with_standing(
+        &mut self,
+        envelope: Envelope,
+        standing: impl Fn(&NodeId) -> RelayStanding,
+    ) -> RoutingAction {
+        if self.drop_if_blocked(&envelope.from) {
+            return RoutingAction::Drop;
+        }
+
         // Guard: relay chain too deep
         if envelope.via.len() > MAX_RELAY_DEPTH {
             return RoutingAction::Reject {
@@ -165,8 +237,25 @@ impl Router {
             return self.handle_local(envelope);
         }
 
+        // Anything else asks us to relay
+        let position = envelope.via.iter().position(|id| *id == self.local_id);
+        let requester = match position {
+            Some(pos) if pos > 0 => envelope.via[pos - 1],
+            _ => envelope.from,
+        };
+        let refusal = [envelope.from, requester]
+            .into_iter()
+            .find(|id| self.never_relay_for.contains(id))
+            .map(|id| format!("relay refused: never relaying for {id}"))
+            .or_else(|| self.relay_policy.refusal(requester, standing(&requester)));
+        if let Some(reason) = refusal {
+            self.relays_refused += 1;
+            tracing::debug!(%requester, %reason, "relay refused");
+            return RoutingAction::Reject { reason };
+        }
+
         // Are we in the relay chain?
-        if let Some(pos) = envelope.via.iter().position(|id| *id == self.local_id) {
+        if let Some(pos) = position {
             return self.handle_forward_in_chain(envelope, pos);
         }
 
@@ -174,6 +263,59 @@ impl Router {
         self.handle_direct_forward(envelope)
     }
 
+    /// Block (or unblock) a sender: its envelopes are dropped, relayed or not.
+    pub fn set_blocked(&mut self, node_id: NodeId, blocked: bool) {
+        if blocked {
+            self.blocked.insert(node_id);
+        } else {
+            self.blocked.remove(&node_id);
+        }
+    }
+
+    pub fn is_blocked(&self, node_id: &NodeId) -> bool {
+        self.blocked.contains(node_id)
+    }
+
+    /// Whether to drop an envelope from `from`; counts the drop if so.
+    ///
+    /// For callers screening envelopes that never reach `route()`.
+    pub fn drop_if_blocked(&mut self, from: &NodeId) -> bool {
+        if !self.blocked.contains(from) {
+            return false;
+        }
+        self.blocked_drops += 1;
+        true
+    }
+
+    /// Envelopes dropped so far because their sender is blocked.
+    pub fn blocked_drops(&self) -> u64 {
+        self.blocked_drops
+    }
+
+    /// Set who we relay for (`Open` on a new router).
+    pub fn set_relay_policy(&mut self, policy: RelayPolicy) {
+        self.relay_policy = policy;
+    }
+
+    pub fn relay_policy(&self) -> RelayPolicy {
+        self.relay_policy
+    }
+
+    /// Never forward (or resume forwarding) traffic from or handed over by
+    /// `node_id`, whatever the relay policy.
+    pub fn set_never_relay_for(&mut self, node_id: NodeId, never: bool) {
+        if never {
+            self.never_relay_for.insert(node_id);
+        } else {
+            self.never_relay_for.remove(&node_id);
+        }
+    }
+
+    /// Forwards refused so far by the relay policy.
+    pub fn relays_refused(&self) -> u64 {
+        self.relays_refused
+    }
+
     /// Evict expired entries from both caches.
     pub fn cleanup_caches(&mut self) {
         let now = Instant::now();
@@ -202,6 +344,7 @@ impl Router {
         // Dedup check
         let cache_key = format!("{}:{}", envelope.id, envelope.from);
         if self.message_cache.contains_key(&cache_key) {
+            tracing::debug!("duplicate dropped");
             return RoutingAction::Drop;
         }
 
@@ -224,6 +367,7 @@ impl Router {
         // Create delivery ACK (via reversed relay chain)
         let response = self.create_delivery_ack(&envelope);
 
+        tracing::debug!("delivering");
         RoutingAction::Deliver { envelope, response }
     }
 
@@ -250,6 +394,7 @@ impl Router {
         }
         self.ack_cache.insert(cache_key, Instant::now());
 
+        tracing::debug!(ack_type = ?ack.ack_type, "ack received");
         RoutingAction::Ack {
             original_message_id: ack.original_message_id,
             ack_type: ack.ack_type,
@@ -280,6 +425,7 @@ impl Router {
         // Clamp read_at: not future, not older than 7 days
         let now = now_ms();
         let read_at = rr.read_at.min(now).max(now.saturating_sub(READ_RECEIPT_MAX_AGE_MS));
+        tracing::debug!(read_at, "read receipt received");
 
         RoutingAction::ReadReceipt {
             original_message_id: rr.original_message_id,
@@ -306,6 +452,7 @@ impl Router {
 
         let relay_ack = self.create_relay_ack(&envelope);
 
+        tracing::debug!(%next_hop, "forwarding in relay chain");
         RoutingAction::Forward {
             envelope,
             next_hop,
@@ -322,6 +469,7 @@ impl Router {
 
         let next_hop = envelope.to;
         let relay_ack = self.create_relay_ack(&envelope);
+        tracing::debug!(%next_hop, "forwarding direct");
 
         RoutingAction::Forward {
             envelope,
@@ -464,6 +612,23 @@ mod tests {
         assert!(matches!(router.route(env2), RoutingAction::Drop));
     }
 
+    #[test]
+    fn blocked_sender_dropped_and_counted() {
+        let me = node_id(1);
+        let sender = node_id(2);
+        let mut router = Router::new(me);
+        router.set_blocked(sender, true);
+
+        assert!(matches!(router.route(chat(sender, me, b"hi")), RoutingAction::Drop));
+        // Not relayed either
+        assert!(matches!(router.route(chat(sender, node_id(3), b"hi")), RoutingAction::Drop));
+        assert_eq!(router.blocked_drops(), 2);
+
+        router.set_blocked(sender, false);
+        assert!(matches!(router.route(chat(sender, me, b"hi")), RoutingAction::Deliver { .. }));
+        assert_eq!(router.blocked_drops(), 2);
+    }
+
     // ── Forward tests ──────────────────────────────────────────────────
 
     #[test]
@@ -516,6 +681,77 @@ mod tests {
         }
     }
 
+    #[test]
+    fn relay_policy_refuses_and_counts() {
+        let me = node_id(10);
+        let (stranger, peer, relay, friend) = (node_id(1), node_id(3), node_id(11), node_id(4));
+        let recipient = node_id(2);
+        let standing = |id: &NodeId| match *id {
+            id if id == peer => RelayStanding::Peer,
+            id if id == relay => RelayStanding::Relay,
+            id if id == friend => RelayStanding::Trusted,
+            _ => RelayStanding::Unknown,
+        };
+        let mut router = Router::new(me);
+        router.set_relay_policy(RelayPolicy::KnownPeers);
+
+        let via_me = |from| {
+            let mut env = chat(from, recipient, b"relay me");
+            env.via = vec![me];
+            env
+        };
+        match router.route_with_standing(via_me(stranger), standing) {
+            RoutingAction::Reject { reason } => assert!(reason.contains("unknown peer")),
+            other => panic!("expected Reject, got {:?}", other),
+        }
+        assert!(matches!(router.route_with_standing(via_me(peer), standing), RoutingAction::Forward { .. }));
+
+        // Stricter: only relays and trusted contacts
+        router.set_relay_policy(RelayPolicy::RelaysAndTrusted);
+        match router.route_with_standing(chat(peer, recipient, b"free ride"), standing) {
+            RoutingAction::Reject { reason } => assert!(reason.contains("neither a relay nor trusted")),
+            other => panic!("expected Reject, got {:?}", other),
+        }
+        assert!(matches!(router.route_with_standing(via_me(friend), standing), RoutingAction::Forward { .. }));
+
+        // Mid-chain, the requester is the previous relay, not the sender
+        let mut env = chat(stranger, recipient, b"chained");
+        env.via = vec![relay, me];
+        assert!(matches!(router.route_with_standing(env, standing), RoutingAction::Forward { .. }));
+
+        // Messages for us are never subject to the policy
+        assert!(matches!(router.route_with_standing(chat(stranger, me, b"hi"), standing), RoutingAction::Deliver { .. }));
+        assert_eq!(router.relays_refused(), 2);
+    }
+
+    #[test]
+    fn never_relay_for_beats_trusted_standing() {
+        let me = node_id(10);
+        let (friend, recipient) = (node_id(4), node_id(2));
+        let standing = |id: &NodeId| match *id {
+            id if id == friend => RelayStanding::Trusted,
+            _ => RelayStanding::Unknown,
+        };
+        let mut router = Router::new(me);
+        router.set_relay_policy(RelayPolicy::RelaysAndTrusted);
+
+        let via_me = || {
+            let mut env = chat(friend, recipient, b"relay me");
+            env.via = vec![me];
+            env
+        };
+        router.set_never_relay_for(friend, true);
+        match router.route_with_standing(via_me(), standing) {
+            RoutingAction::Reject { reason } => assert!(reason.contains("never relaying for")),
+            other => panic!("expected Reject, got {:?}", other),
+        }
+        assert_eq!(router.relays_refused(), 1);
+
+        router.set_never_relay_for(friend, false);
+        assert!(matches!(router.route_with_standing(via_me(), standing), RoutingAction::Forward { .. }));
+        assert_eq!(router.relays_refused(), 1);
+    }
+
     #[test]
     fn direct_forward_not_in_chain() {
         let me = node_id(10);
@@ -0,0 +1,66 @@
+/// Request/response RPC over envelopes.
+///
+/// `RuntimeHandle::request` sends a `MessageType::Request` envelope carrying
+/// a fresh request id and waits for the matching `MessageType::Response`.
+/// The callee sees a `RequestReceived` event and answers with
+/// `RuntimeHandle::respond`, echoing the id. Both directions are routed,
+/// signed and encrypted like chat, but untracked: no ACK, no retry, no
+/// backup. A lost request or response surfaces as a timeout.
+///
+/// The runtime loop keeps the waiting callers and fails each one at its
+/// deadline; a response arriving after that, or from anyone but the node
+/// the request went to, is dropped.
+use serde::{Deserialize, Serialize};
+
+use crate::error::TomProtocolError;
+use crate::types::NodeId;
+
+/// Most requests awaiting their response at once.
+pub const MAX_PENDING_REQUESTS: usize = 256;
+
+/// Longest timeout a request may ask for.
+pub const MAX_REQUEST_TIMEOUT_MS: u64 = 5 * 60 * 1000;
+
+/// Wire payload of `MessageType::Request` and `MessageType::Response`
+/// envelopes (before encryption).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcPayload {
+    /// Correlation id, chosen by the requester and echoed in the response.
+    pub request_id: String,
+    /// Application bytes.
+    pub body: Vec<u8>,
+}
+
+impl RpcPayload {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        rmp_serde::to_vec(self).expect("RpcPayload serialization cannot fail")
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, TomProtocolError> {
+        rmp_serde::from_slice(data).map_err(Into::into)
+    }
+}
+
+/// A response to one of our requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// The node that answered (always the one the request went to).
+    pub from: NodeId,
+    pub request_id: String,
+    pub payload: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_roundtrip() {
+        let payload = RpcPayload {
+            request_id: "req-1".into(),
+            body: b"ping".to_vec(),
+        };
+        assert_eq!(RpcPayload::from_bytes(&payload.to_bytes()).unwrap(), payload);
+        assert!(RpcPayload::from_bytes(b"garbage").is_err());
+    }
+}
@@ -0,0 +1,381 @@
+//! Local admin socket — control a running node from another process.
+//!
+//! With `RuntimeConfig::admin_socket` set, the runtime listens on a Unix
+//! domain socket (mode 0600: only the node's user can connect). Each line
+//! a client writes is one JSON [`AdminRequest`]; the runtime answers each
+//! with one JSON [`AdminResponse`] line, in order:
+//!
+//! ```text
+//! > {"command":"peers"}
+//! < {"ok":[{"node_id":"…","liveness":"Online",…}]}
+//! > {"command":"send_message","to":"…","text":"hello"}
+//! < {"ok":null}
+//! ```
+//!
+//! The `tom-admin` binary is a client; [`admin_request`] is the same for
+//! programs. The socket closes with the runtime.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+#[cfg(all(unix, feature = "native"))]
+use serde_json::json;
+
+#[cfg(all(unix, feature = "native"))]
+use crate::group::GroupId;
+#[cfg(all(unix, feature = "native"))]
+use crate::types::NodeId;
+
+#[cfg(all(unix, feature = "native"))]
+use super::RuntimeHandle;
+
+/// One request to a node's admin socket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum AdminRequest {
+    /// Our identities and a metrics snapshot.
+    Status,
+    /// Every known peer with its liveness, role and path.
+    Peers,
+    /// The groups we're in.
+    Groups,
+    /// What the backup system holds.
+    Backups,
+    /// Every known peer with its role score.
+    RoleScores,
+    SendMessage { to: String, text: String },
+    SendGroupMessage { group_id: String, text: String },
+}
+
+/// Answer to one [`AdminRequest`]: `{"ok": …}` or `{"error": "…"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminResponse {
+    Ok(Value),
+    Error(String),
+}
+
+/// Run one request against the runtime.
+#[cfg(all(unix, feature = "native"))]
+pub(crate) async fn handle_admin_request(handle: &RuntimeHandle, request: AdminRequest) -> AdminResponse {
+    match request {
+        AdminRequest::Status => AdminResponse::Ok(json!({
+            "local_id": handle.local_id(),
+            "identities": handle.identities(),
+            "metrics": handle.metrics(),
+        })),
+        AdminRequest::Peers => {
+            let peers: Vec<Value> = handle
+                .all_peers()
+                .await
+                .into_iter()
+                .map(|p| {
+                    json!({
+                        "node_id": p.node_id,
+                        "liveness": format!("{:?}", p.liveness),
+                        "last_seen": p.last_seen,
+                        "source": p.source.map(|s| format!("{s:?}")),
+                        "role": p.role.map(|r| format!("{r:?}")),
+                        "path": format!("{:?}", p.path),
+                        "rtt_ms": p.rtt_ms,
+                    })
+                })
+                .collect();
+            AdminResponse::Ok(peers.into())
+        }
+        AdminRequest::Groups => match serde_json::to_value(handle.groups().await) {
+            Ok(groups) => AdminResponse::Ok(groups),
+            Err(e) => AdminResponse::Error(e.to_string()),
+        },
+        AdminRequest::Backups => match handle.backup_stats().await {
+            Some(stats) => AdminResponse::Ok(json!({
+                "entries": stats.entries,
+                "bytes": stats.bytes,
+                "healthy": stats.healthy,
+                "under_replicated": stats.under_replicated,
+                "unreplicated": stats.unreplicated,
+                "pending_replications": stats.pending_replications,
+                "queued_deliveries": stats.queued_deliveries,
+                "per_recipient": stats.per_recipient,
+            })),
+            None => AdminResponse::Error("runtime stopped".into()),
+        },
+        AdminRequest::RoleScores => {
+            let scores: Vec<Value> = handle
+                .get_all_role_scores()
+                .await
+                .into_iter()
+                .map(|(node_id, score, role)| {
+                    json!({ "node_id": node_id, "score": score, "role": format!("{role:?}") })
+                })
+                .collect();
+            AdminResponse::Ok(scores.into())
+        }
+        AdminRequest::SendMessage { to, text } => {
+            let Ok(to) = to.parse::<NodeId>() else {
+                return AdminResponse::Error(format!("invalid node id: {to}"));
+            };
+            match handle.send_message(to, text.into_bytes()).await {
+                Ok(_) => AdminResponse::Ok(Value::Null),
+                Err(e) => AdminResponse::Error(e.to_string()),
+            }
+        }
+        AdminRequest::SendGroupMessage { group_id, text } => {
+            match handle.send_group_message(GroupId(group_id), text).await {
+                Ok(()) => AdminResponse::Ok(Value::Null),
+                Err(e) => AdminResponse::Error(e.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "native"))]
+pub use unix::admin_request;
+#[cfg(all(unix, feature = "native"))]
+pub(crate) use unix::bind_admin_socket;
+
+#[cfg(all(unix, feature = "native"))]
+mod unix {
+    use std::path::{Path, PathBuf};
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::unix::OwnedWriteHalf;
+    use tokio::net::{UnixListener, UnixStream};
+
+    use super::{handle_admin_request, AdminRequest, AdminResponse};
+    use crate::runtime::RuntimeHandle;
+
+    /// Longest request line accepted.
+    pub(super) const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+    /// Bind the socket at `path` and serve it until the runtime stops.
+    ///
+    /// A stale socket (nobody answers on it) is replaced; a live one, or
+    /// anything that isn't a socket, is an error.
+    pub(crate) fn bind_admin_socket(path: &Path, handle: RuntimeHandle) -> std::io::Result<()> {
+        use std::io::{Error, ErrorKind};
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        if let Ok(meta) = std::fs::symlink_metadata(path) {
+            if !meta.file_type().is_socket() {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(Error::new(
+                    ErrorKind::AddrInUse,
+                    format!("{} is served by another process", path.display()),
+                ));
+            }
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        tokio::spawn(serve(listener, path.to_path_buf(), handle));
+        Ok(())
+    }
+
+    async fn serve(listener: UnixListener, path: PathBuf, handle: RuntimeHandle) {
+        loop {
+            tokio::select! {
+                _ = handle.cmd_tx.tx.closed() => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        tokio::spawn(serve_client(stream, handle.clone()));
+                    }
+                    Err(e) => {
+                        tracing::debug!("admin socket: accept failed: {e}");
+                        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    }
+                },
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    async fn serve_client(stream: UnixStream, handle: RuntimeHandle) {
+        let (read, mut write) = stream.into_split();
+        let mut reader = BufReader::new(read);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            // Never buffer more than one request's worth of a line
+            let limit = MAX_REQUEST_BYTES as u64 + 1;
+            match (&mut reader).take(limit).read_until(b'\n', &mut line).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            if line.len() > MAX_REQUEST_BYTES {
+                // The rest of the line is still unread: answer and hang up
+                let _ = respond(&mut write, &AdminResponse::Error("request too large".into())).await;
+                break;
+            }
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_slice::<AdminRequest>(&line) {
+                Ok(request) => handle_admin_request(&handle, request).await,
+                Err(e) => AdminResponse::Error(format!("invalid request: {e}")),
+            };
+            if !respond(&mut write, &response).await {
+                break;
+            }
+        }
+    }
+
+    /// Write one response line; `false` if the client is gone.
+    async fn respond(write: &mut OwnedWriteHalf, response: &AdminResponse) -> bool {
+        let Ok(mut out) = serde_json::to_vec(response) else {
+            return false;
+        };
+        out.push(b'\n');
+        write.write_all(&out).await.is_ok()
+    }
+
+    /// Send one request to the admin socket at `path` and read the answer.
+    pub async fn admin_request(path: impl AsRef<Path>, request: &AdminRequest) -> std::io::Result<AdminResponse> {
+        let stream = UnixStream::connect(path).await?;
+        let (read, mut write) = stream.into_split();
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        write.write_all(&line).await?;
+
+        let mut answer = String::new();
+        BufReader::new(read).read_line(&mut answer).await?;
+        Ok(serde_json::from_str(&answer)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_format() {
+        let request = AdminRequest::SendMessage {
+            to: "abc".into(),
+            text: "hi".into(),
+        };
+        let line = serde_json::to_string(&request).unwrap();
+        assert_eq!(line, r#"{"command":"send_message","to":"abc","text":"hi"}"#);
+        assert_eq!(serde_json::from_str::<AdminRequest>(r#"{"command":"peers"}"#).unwrap(), AdminRequest::Peers);
+
+        let response = AdminResponse::Error("nope".into());
+        assert_eq!(serde_json::to_string(&response).unwrap(), r#"{"error":"nope"}"#);
+        assert_eq!(
+            serde_json::from_str::<AdminResponse>(r#"{"ok":[1]}"#).unwrap(),
+            AdminResponse::Ok(json!([1]))
+        );
+    }
+
+    #[cfg(unix)]
+    fn test_handle() -> (RuntimeHandle, tokio::sync::mpsc::Receiver<crate::runtime::RuntimeCommand>) {
+        use crate::runtime::{events::EventBus, CommandSender, ProtocolMetrics};
+        use tokio::sync::mpsc;
+
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let local_id = {
+            use rand::SeedableRng;
+            let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+            tom_connect::SecretKey::generate(&mut rng).public().to_string().parse().unwrap()
+        };
+        let handle = RuntimeHandle {
+            cmd_tx: CommandSender { tx: cmd_tx.clone(), control: cmd_tx, identity: None },
+            local_id,
+            identities: vec![local_id],
+            metrics: ProtocolMetrics::new(),
+            events: EventBus::new(mpsc::channel(1).0.into()),
+        };
+        (handle, cmd_rx)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn socket_round_trip() {
+        use crate::runtime::RuntimeCommand;
+
+        let (handle, mut cmd_rx) = test_handle();
+        let local_id = handle.local_id;
+        // Stand-in runtime: no peers; gone when told to stop
+        let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let runtime = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut stop_rx => break,
+                    Some(command) = cmd_rx.recv() => {
+                        if let RuntimeCommand::GetAllPeers { reply } = command {
+                            let _ = reply.send(Vec::new());
+                        }
+                    }
+                }
+            }
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("admin.sock");
+        bind_admin_socket(&path, handle).unwrap();
+
+        let peers = admin_request(&path, &AdminRequest::Peers).await.unwrap();
+        assert_eq!(peers, AdminResponse::Ok(json!([])));
+        let send = AdminRequest::SendMessage {
+            to: "not-a-node".into(),
+            text: "hi".into(),
+        };
+        assert!(matches!(admin_request(&path, &send).await.unwrap(), AdminResponse::Error(_)));
+        let AdminResponse::Ok(status) = admin_request(&path, &AdminRequest::Status).await.unwrap() else {
+            panic!("status failed");
+        };
+        assert_eq!(status["local_id"], local_id.to_string());
+
+        // An endless line is cut off at the cap, not buffered
+        {
+            use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+            let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let huge = vec![b'x'; unix::MAX_REQUEST_BYTES + 1];
+            let _ = write.write_all(&huge).await;
+            let mut answer = String::new();
+            BufReader::new(read).read_line(&mut answer).await.unwrap();
+            assert_eq!(
+                serde_json::from_str::<AdminResponse>(&answer).unwrap(),
+                AdminResponse::Error("request too large".into())
+            );
+        }
+
+        // A live socket is not taken over
+        let err = bind_admin_socket(&path, test_handle().0).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+
+        // The socket goes away with the runtime
+        stop_tx.send(()).unwrap();
+        runtime.await.unwrap();
+        for _ in 0..50 {
+            if !path.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn bind_replaces_only_stale_sockets() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // A regular file is never removed
+        let file = dir.path().join("admin.sock");
+        std::fs::write(&file, b"keep me").unwrap();
+        let err = bind_admin_socket(&file, test_handle().0).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&file).unwrap(), b"keep me");
+
+        // A socket nobody listens on is stale and gets replaced
+        let stale = dir.path().join("stale.sock");
+        drop(std::os::unix::net::UnixListener::bind(&stale).unwrap());
+        let (handle, _cmd_rx) = test_handle();
+        bind_admin_socket(&stale, handle).unwrap();
+        assert!(tokio::net::UnixStream::connect(&stale).await.is_ok());
+    }
+}
@@ -0,0 +1,305 @@
+//! App-facing channels with a configurable overflow policy.
+//!
+//! Delivered messages, status changes and events reach the application over
+//! bounded channels. What happens when the app falls behind is chosen per
+//! channel with a [`ChannelPolicy`]:
+//! - `DropNew` (default): the runtime never waits; what doesn't fit is lost;
+//! - `DropOldest`: the runtime never waits; the oldest unread item makes room;
+//! - `Block`: the runtime waits for the app (and stops processing meanwhile).
+//!
+//! Lost events are reported in order with a `ProtocolEvent::EventsDropped`
+//! as soon as there is room again. Lost messages count in the metrics.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{mpsc, Notify};
+
+/// What a full channel does with one more item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait until the app makes room (backpressure into the runtime loop).
+    Block,
+    /// Drop the oldest unread item.
+    DropOldest,
+    /// Drop the new item.
+    #[default]
+    DropNew,
+}
+
+/// Capacity and overflow policy of one app-facing channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelPolicy {
+    /// Items buffered before the overflow policy applies (at least 1).
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl ChannelPolicy {
+    pub fn new(capacity: usize, overflow: OverflowPolicy) -> Self {
+        Self { capacity, overflow }
+    }
+}
+
+/// Builds the notice for `count` lost items, sent ahead of the next one.
+type Notice<T> = fn(u64) -> T;
+
+/// Sending half of an app-facing channel. Cheap to clone.
+pub(crate) struct AppSender<T> {
+    inner: Inner<T>,
+    dropped: Arc<AtomicU64>,
+    notice: Option<Notice<T>>,
+}
+
+enum Inner<T> {
+    /// Straight into the app's receiver (`Block`, `DropNew`).
+    Direct {
+        tx: mpsc::Sender<T>,
+        overflow: OverflowPolicy,
+    },
+    /// Through a ring buffer drained by a forwarder task (`DropOldest`).
+    Ring {
+        ring: Arc<Ring<T>>,
+        _alive: Arc<Alive<T>>,
+    },
+}
+
+struct Ring<T> {
+    items: Mutex<VecDeque<T>>,
+    capacity: usize,
+    ready: Notify,
+    closed: AtomicBool,
+}
+
+/// Dropped with the last sender: closes the ring so the forwarder exits.
+struct Alive<T>(Arc<Ring<T>>);
+
+impl<T> Drop for Alive<T> {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Release);
+        self.0.ready.notify_one();
+    }
+}
+
+impl<T> Clone for AppSender<T> {
+    fn clone(&self) -> Self {
+        let inner = match &self.inner {
+            Inner::Direct { tx, overflow } => Inner::Direct {
+                tx: tx.clone(),
+                overflow: *overflow,
+            },
+            Inner::Ring { ring, _alive } => Inner::Ring {
+                ring: ring.clone(),
+                _alive: _alive.clone(),
+            },
+        };
+        Self {
+            inner,
+            dropped: self.dropped.clone(),
+            notice: self.notice,
+        }
+    }
+}
+
+/// `DropNew` over an existing channel.
+impl<T> From<mpsc::Sender<T>> for AppSender<T> {
+    fn from(tx: mpsc::Sender<T>) -> Self {
+        Self {
+            inner: Inner::Direct {
+                tx,
+                overflow: OverflowPolicy::DropNew,
+            },
+            dropped: Arc::default(),
+            notice: None,
+        }
+    }
+}
+
+/// Open an app-facing channel. With `notice`, lost items are reported
+/// in the stream; `DropOldest` spawns its forwarder on the current runtime.
+pub(crate) fn app_channel<T: Send + 'static>(
+    policy: ChannelPolicy,
+    notice: Option<Notice<T>>,
+) -> (AppSender<T>, mpsc::Receiver<T>) {
+    let capacity = policy.capacity.max(1);
+    let dropped = Arc::new(AtomicU64::new(0));
+    match policy.overflow {
+        OverflowPolicy::DropOldest => {
+            let (tx, rx) = mpsc::channel(1);
+            let ring = Arc::new(Ring {
+                items: Mutex::new(VecDeque::with_capacity(capacity.min(1024))),
+                capacity,
+                ready: Notify::new(),
+                closed: AtomicBool::new(false),
+            });
+            tokio::spawn(forward(ring.clone(), tx, dropped.clone(), notice));
+            let alive = Arc::new(Alive(ring.clone()));
+            let sender = AppSender {
+                inner: Inner::Ring { ring, _alive: alive },
+                dropped,
+                notice,
+            };
+            (sender, rx)
+        }
+        overflow => {
+            let (tx, rx) = mpsc::channel(capacity);
+            let sender = AppSender {
+                inner: Inner::Direct { tx, overflow },
+                dropped,
+                notice,
+            };
+            (sender, rx)
+        }
+    }
+}
+
+impl<T> AppSender<T> {
+    /// Deliver by the channel's policy. False if the item was dropped
+    /// (or the receiver is gone).
+    pub(crate) async fn send(&self, item: T) -> bool {
+        self.send_reclaim(item).await.0
+    }
+
+    /// Like `send`, also handing back the item that was lost, if any: the
+    /// new one, or the oldest unread one it pushed out (`DropOldest`).
+    pub(crate) async fn send_reclaim(&self, item: T) -> (bool, Option<T>) {
+        match &self.inner {
+            Inner::Direct {
+                tx,
+                overflow: OverflowPolicy::Block,
+            } => match tx.send(item).await {
+                Ok(()) => (true, None),
+                Err(mpsc::error::SendError(item)) => (false, Some(item)),
+            },
+            _ => self.try_send_reclaim(item),
+        }
+    }
+
+    /// Deliver without waiting (`Block` drops the new item when full).
+    fn try_send_reclaim(&self, item: T) -> (bool, Option<T>) {
+        match &self.inner {
+            Inner::Direct { tx, .. } => {
+                if let Some(notice) = self.notice {
+                    let count = self.dropped.load(Ordering::Relaxed);
+                    if count > 0 && tx.try_send(notice(count)).is_ok() {
+                        self.dropped.fetch_sub(count, Ordering::Relaxed);
+                    }
+                }
+                match tx.try_send(item) {
+                    Ok(()) => (true, None),
+                    Err(mpsc::error::TrySendError::Full(item)) => {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        (false, Some(item))
+                    }
+                    Err(mpsc::error::TrySendError::Closed(item)) => (false, Some(item)),
+                }
+            }
+            Inner::Ring { ring, .. } => {
+                let evicted = {
+                    let mut items = ring.items.lock().unwrap();
+                    let evicted = if items.len() >= ring.capacity {
+                        items.pop_front()
+                    } else {
+                        None
+                    };
+                    items.push_back(item);
+                    evicted
+                };
+                if evicted.is_some() {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                ring.ready.notify_one();
+                (true, evicted)
+            }
+        }
+    }
+}
+
+/// Move ring items into the app's receiver, reporting evictions first.
+async fn forward<T>(
+    ring: Arc<Ring<T>>,
+    tx: mpsc::Sender<T>,
+    dropped: Arc<AtomicU64>,
+    notice: Option<Notice<T>>,
+) {
+    loop {
+        let next = ring.items.lock().unwrap().pop_front();
+        let Some(item) = next else {
+            if ring.closed.load(Ordering::Acquire) {
+                return;
+            }
+            ring.ready.notified().await;
+            continue;
+        };
+        if let Some(notice) = notice {
+            let count = dropped.swap(0, Ordering::Relaxed);
+            if count > 0 && tx.send(notice(count)).await.is_err() {
+                return;
+            }
+        }
+        if tx.send(item).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Item {
+        Value(u32),
+        Dropped(u64),
+    }
+
+    fn notice(count: u64) -> Item {
+        Item::Dropped(count)
+    }
+
+    #[tokio::test]
+    async fn drop_new_reports_losses_when_room_returns() {
+        let (tx, mut rx) = app_channel(ChannelPolicy::new(2, OverflowPolicy::DropNew), Some(notice));
+        assert!(tx.send(Item::Value(1)).await);
+        assert!(tx.send(Item::Value(2)).await);
+        assert!(!tx.send(Item::Value(3)).await);
+        assert!(!tx.send(Item::Value(4)).await);
+
+        assert_eq!(rx.recv().await, Some(Item::Value(1)));
+        assert_eq!(rx.recv().await, Some(Item::Value(2)));
+        assert!(tx.send(Item::Value(5)).await);
+        assert_eq!(rx.recv().await, Some(Item::Dropped(2)));
+        assert_eq!(rx.recv().await, Some(Item::Value(5)));
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_the_latest() {
+        let (tx, mut rx) = app_channel(ChannelPolicy::new(2, OverflowPolicy::DropOldest), Some(notice));
+        // The forwarder hasn't run yet: everything sits in the ring
+        for n in 1..=5 {
+            let evicted = (n > 2).then(|| Item::Value(n - 2));
+            assert_eq!(tx.try_send_reclaim(Item::Value(n)), (true, evicted));
+        }
+        drop(tx);
+
+        let mut received = Vec::new();
+        while let Some(item) = rx.recv().await {
+            received.push(item);
+        }
+        assert_eq!(received, vec![Item::Dropped(3), Item::Value(4), Item::Value(5)]);
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_the_app() {
+        let (tx, mut rx) = app_channel(ChannelPolicy::new(1, OverflowPolicy::Block), Some(notice));
+        assert!(tx.send(Item::Value(1)).await);
+        let blocked = tokio::spawn(async move { tx.send(Item::Value(2)).await });
+        tokio::task::yield_now().await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(rx.recv().await, Some(Item::Value(1)));
+        assert!(blocked.await.unwrap());
+        assert_eq!(rx.recv().await, Some(Item::Value(2)));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::discovery::RoleChangeAnnounce;
+use crate::discovery::{AbuseReport, RelayAttestation, RoleChangeAnnounce};
 use crate::envelope::Envelope;
 use crate::tracker::StatusChange;
 use crate::types::NodeId;
@@ -39,4 +39,29 @@ pub enum RuntimeEffect {
 
     /// Broadcast a role change via gossip to all neighbors.
     BroadcastRoleChange(RoleChangeAnnounce),
+
+    /// Broadcast a signed abuse report via gossip to all neighbors.
+    BroadcastAbuseReport(AbuseReport),
+
+    /// Broadcast a signed relay attestation via gossip to all neighbors.
+    BroadcastRelayAttestation(RelayAttestation),
+
+    /// Broadcast a serialized PeerAnnounce now instead of on the next tick.
+    BroadcastAnnounce(Vec<u8>),
+
+    /// Subscribe to a pub/sub channel's gossip topic.
+    SubscribeChannel { topic: [u8; 32] },
+
+    /// Drop the subscription to a pub/sub channel's gossip topic.
+    UnsubscribeChannel { topic: [u8; 32] },
+
+    /// Broadcast a signed publication on a channel's gossip topic.
+    PublishChannel { topic: [u8; 32], bytes: Vec<u8> },
+
+    /// Hand a response to the caller waiting on its request.
+    CompleteRequest(crate::rpc::Response),
+
+    /// Send one datagram straight to `to` (real-time lane: unreliable,
+    /// never retried).
+    SendDatagram { to: NodeId, bytes: Vec<u8> },
 }
@@ -0,0 +1,435 @@
+//! Event fan-out — every `ProtocolEvent` to every interested subscriber.
+//!
+//! `RuntimeChannels::events` keeps receiving everything. Further consumers
+//! (a logger, a metrics exporter, a second UI pane) call
+//! `RuntimeHandle::subscribe_events` with an [`EventFilter`] and get their
+//! own receiver. Subscribers never slow the runtime down: a full receiver
+//! loses the event, a dropped one is unsubscribed on the next emit.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc;
+
+use crate::types::{NodeId, PathEvent};
+
+use super::channel::AppSender;
+use super::ProtocolEvent;
+
+/// Coarse grouping of events, for filtering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+pub enum EventCategory {
+    /// Peer liveness, presence, typing, usernames, contact cards, conversation
+    /// encryption, gossip neighbors.
+    Peer,
+    /// Everything about groups (hubbed and mesh).
+    Group,
+    /// Message fate: rejections, forwards, retries, timeouts, exactly-once,
+    /// shared state and channel publications.
+    Delivery,
+    /// Messages held for offline peers.
+    Backup,
+    /// Relay promotion/demotion, of peers and of this node.
+    Role,
+    /// Ephemeral subnet formation and dissolution.
+    Subnet,
+    /// Throttled senders and abuse reports.
+    AntiSpam,
+    /// Transport paths, route traces, pause/resume, lost events, bootstrap,
+    /// errors.
+    Network,
+    /// Real-time lane payloads.
+    Realtime,
+}
+
+/// `PathChanged` as bindings see it: the peer, the path kind and the RTT
+/// (the event's `Instant` means nothing outside this process).
+pub(super) fn serialize_path_event<S: serde::Serializer>(
+    event: &PathEvent,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeStruct;
+
+    let mut fields = serializer.serialize_struct("PathEvent", 3)?;
+    fields.serialize_field("remote", &event.remote)?;
+    fields.serialize_field("kind", &event.kind.to_string())?;
+    fields.serialize_field("rtt_ms", &(event.rtt.as_millis() as u64))?;
+    fields.end()
+}
+
+impl ProtocolEvent {
+    pub fn category(&self) -> EventCategory {
+        use ProtocolEvent::*;
+        match self {
+            PeerDiscovered { .. }
+            | PeerStale { .. }
+            | PeerOffline { .. }
+            | PeersEvicted { .. }
+            | PeerOnline { .. }
+            | PeerPresenceChanged { .. }
+            | PeerTyping { .. }
+            | UsernameConflict { .. }
+            | DeviceLinked { .. }
+            | DeviceRevoked { .. }
+            | ContactCardReceived { .. }
+            | ConversationEncryptionChanged { .. }
+            | GossipNeighborUp { .. }
+            | GossipNeighborDown { .. } => EventCategory::Peer,
+
+            GroupCreated { .. }
+            | GroupInviteReceived { .. }
+            | GroupJoined { .. }
+            | GroupMemberJoined { .. }
+            | GroupMemberLeft { .. }
+            | GroupMessageReceived { .. }
+            | GroupMentioned { .. }
+            | GroupHubMigrated { .. }
+            | GroupSecurityViolation { .. }
+            | GroupMemberRoleChanged { .. }
+            | GroupMetadataChanged { .. }
+            | GroupMessageTtlChanged { .. }
+            | GroupSettingsChanged { .. }
+            | GroupJoinRequested { .. }
+            | GroupJoinRequestResolved { .. }
+            | GroupJoinDenied { .. }
+            | GroupMessagesExpired { .. }
+            | GroupHistoryPage { .. }
+            | GroupDeliveryUpdated { .. }
+            | GroupMessageDeliveredToAll { .. }
+            | GroupReactionChanged { .. }
+            | GroupPollUpdated { .. }
+            | GroupShadowPromoted { .. }
+            | GroupCandidateAssigned { .. }
+            | GroupHubChainRestored { .. } => EventCategory::Group,
+
+            MessageRejected { .. }
+            | Forwarded { .. }
+            | ExactlyOnceCommitted { .. }
+            | ExactlyOnceExpired { .. }
+            | MessageGap { .. }
+            | TransferProgress { .. }
+            | TransferComplete { .. }
+            | TransferFailed { .. }
+            | SharedStateChanged { .. }
+            | ChannelMessage { .. }
+            | RequestReceived { .. }
+            | HistorySynced { .. }
+            | DeliveryRetry { .. }
+            | DeliveryEscalated { .. }
+            | DeliveryTimeout { .. } => EventCategory::Delivery,
+
+            BackupStored { .. }
+            | BackupDelivered { .. }
+            | BackupExpired { .. }
+            | BackupEvicted { .. }
+            | BackupCorrupted { .. }
+            | ReplicationDegraded { .. }
+            | StorageQuotaExceeded { .. } => EventCategory::Backup,
+
+            RolePromoted { .. }
+            | RoleDemoted { .. }
+            | LocalRoleChanged { .. }
+            | LocalRolesChanged { .. } => EventCategory::Role,
+
+            SubnetFormed { .. } | SubnetDissolved { .. } => EventCategory::Subnet,
+
+            SenderThrottled { .. } | ChatThrottled { .. } | AbuseReported { .. } => {
+                EventCategory::AntiSpam
+            }
+
+            PathChanged { .. }
+            | RouteTraced { .. }
+            | RuntimePaused { .. }
+            | EventsDropped { .. }
+            | RuntimeRestarted { .. }
+            | BootstrapCompleted { .. }
+            | Error { .. } => EventCategory::Network,
+
+            RealtimeReceived { .. } => EventCategory::Realtime,
+
+            Hosted { event, .. } => event.category(),
+        }
+    }
+
+    /// Whether the event is about `peer` (as subject, sender, recipient,
+    /// member or relay).
+    pub fn involves(&self, peer: &NodeId) -> bool {
+        use ProtocolEvent::*;
+        match self {
+            PeerDiscovered { node_id, .. }
+            | PeerStale { node_id }
+            | PeerOffline { node_id }
+            | PeerOnline { node_id }
+            | PeerPresenceChanged { node_id, .. }
+            | PeerTyping { node_id, .. }
+            | ConversationEncryptionChanged { node_id, .. }
+            | GroupSecurityViolation { node_id, .. }
+            | GroupMemberRoleChanged { node_id, .. }
+            | GroupMemberLeft { node_id, .. }
+            | GossipNeighborUp { node_id }
+            | GossipNeighborDown { node_id }
+            | RolePromoted { node_id, .. }
+            | RoleDemoted { node_id, .. }
+            | SenderThrottled { node_id, .. }
+            | ChatThrottled { node_id, .. } => node_id == peer,
+
+            PeersEvicted { node_ids } => node_ids.contains(peer),
+            RouteTraced { target, hops, .. } => {
+                target == peer || hops.iter().any(|h| h.node_id == *peer)
+            }
+            UsernameConflict { owner, claimant, .. } => owner == peer || claimant == peer,
+            DeviceLinked { user, device, .. } | DeviceRevoked { user, device } => {
+                user == peer || device == peer
+            }
+            ContactCardReceived { from, .. }
+            | ChannelMessage { from, .. }
+            | RequestReceived { from, .. }
+            | HistorySynced { from, .. }
+            | MessageGap { from, .. }
+            | RealtimeReceived { from, .. } => from == peer,
+            TransferProgress { peer: other, .. }
+            | TransferComplete { peer: other, .. }
+            | TransferFailed { peer: other, .. } => other == peer,
+            Forwarded { next_hop, .. } => next_hop == peer,
+            PathChanged { event } => event.remote == *peer,
+
+            GroupInviteReceived { invite } => invite.inviter_id == *peer,
+            GroupMemberJoined { member, .. } => member.node_id == *peer,
+            GroupMessageReceived { message, .. } => message.sender_id == *peer,
+            GroupMentioned { sender_id, .. } => sender_id == peer,
+            GroupHubMigrated { new_hub_id, .. } | GroupShadowPromoted { new_hub_id, .. } => {
+                new_hub_id == peer
+            }
+            GroupMetadataChanged { updated_by, .. }
+            | GroupMessageTtlChanged { updated_by, .. }
+            | GroupSettingsChanged { updated_by, .. }
+            | SharedStateChanged { updated_by, .. } => updated_by == peer,
+            GroupJoinRequested { request, .. } => request.requester == *peer,
+            GroupJoinRequestResolved {
+                requester,
+                resolved_by,
+                ..
+            } => requester == peer || resolved_by == peer,
+            GroupDeliveryUpdated { member, .. } => member == peer,
+            GroupReactionChanged { reactor, .. } => reactor == peer,
+
+            ExactlyOnceCommitted { to, .. }
+            | ExactlyOnceExpired { to, .. }
+            | DeliveryRetry { to, .. }
+            | DeliveryTimeout { to, .. } => to == peer,
+            DeliveryEscalated { to, relays, .. } => to == peer || relays.contains(peer),
+
+            BackupStored { recipient_id, .. }
+            | BackupDelivered { recipient_id, .. }
+            | BackupExpired { recipient_id, .. }
+            | BackupEvicted { recipient_id, .. }
+            | BackupCorrupted { recipient_id, .. }
+            | ReplicationDegraded { recipient_id, .. } => recipient_id == peer,
+            StorageQuotaExceeded { sender_id, .. } => sender_id == peer,
+
+            SubnetFormed { members, .. } => members.contains(peer),
+            AbuseReported {
+                reporter, offender, ..
+            } => reporter == peer || offender == peer,
+
+            GroupCreated { .. }
+            | GroupJoined { .. }
+            | GroupJoinDenied { .. }
+            | GroupMessagesExpired { .. }
+            | GroupHistoryPage { .. }
+            | GroupMessageDeliveredToAll { .. }
+            | GroupPollUpdated { .. }
+            | GroupCandidateAssigned { .. }
+            | GroupHubChainRestored { .. }
+            | MessageRejected { .. }
+            | SubnetDissolved { .. }
+            | LocalRoleChanged { .. }
+            | LocalRolesChanged { .. }
+            | RuntimePaused { .. }
+            | EventsDropped { .. }
+            | RuntimeRestarted { .. }
+            | BootstrapCompleted { .. }
+            | Error { .. } => false,
+
+            Hosted { event, .. } => event.involves(peer),
+        }
+    }
+}
+
+/// Which events a subscriber receives. Empty lists match everything;
+/// otherwise an event must be in one of the categories and involve one
+/// of the peers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    categories: Vec<EventCategory>,
+    peers: Vec<NodeId>,
+}
+
+impl EventFilter {
+    /// Every event.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Also accept events of `category`.
+    pub fn category(mut self, category: EventCategory) -> Self {
+        if !self.categories.contains(&category) {
+            self.categories.push(category);
+        }
+        self
+    }
+
+    /// Also accept events involving `peer`.
+    pub fn peer(mut self, peer: NodeId) -> Self {
+        if !self.peers.contains(&peer) {
+            self.peers.push(peer);
+        }
+        self
+    }
+
+    pub fn matches(&self, event: &ProtocolEvent) -> bool {
+        (self.categories.is_empty() || self.categories.contains(&event.category()))
+            && (self.peers.is_empty() || self.peers.iter().any(|p| event.involves(p)))
+    }
+}
+
+/// Buffer size of a subscriber's receiver.
+pub const SUBSCRIBER_BUFFER: usize = 1024;
+
+type Subscribers = Arc<Mutex<Vec<(EventFilter, mpsc::Sender<ProtocolEvent>)>>>;
+
+/// Delivers events to the primary receiver and every matching subscriber.
+///
+/// Cheap to clone; clones share the subscriber list.
+#[derive(Clone)]
+pub(crate) struct EventBus {
+    primary: AppSender<ProtocolEvent>,
+    subscribers: Subscribers,
+}
+
+impl EventBus {
+    pub(crate) fn new(primary: AppSender<ProtocolEvent>) -> Self {
+        Self {
+            primary,
+            subscribers: Arc::default(),
+        }
+    }
+
+    /// Register a subscriber; events matching `filter` go to the returned receiver.
+    pub(crate) fn subscribe(&self, filter: EventFilter) -> mpsc::Receiver<ProtocolEvent> {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_BUFFER);
+        self.subscribers.lock().unwrap().push((filter, tx));
+        rx
+    }
+
+    /// Deliver by the primary receiver's overflow policy (waiting for room
+    /// under `Block`; subscribers still never block).
+    pub(crate) async fn send(&self, event: ProtocolEvent) -> bool {
+        self.fan_out(&event);
+        self.primary.send(event).await
+    }
+
+    fn fan_out(&self, event: &ProtocolEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|(filter, tx)| {
+            if tx.is_closed() {
+                return false;
+            }
+            if filter.matches(event) {
+                let _ = tx.try_send(event.clone());
+            }
+            true
+        });
+    }
+
+    #[cfg(test)]
+    pub(crate) fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn events_serialize_with_named_fields() {
+        use serde_json::json;
+
+        let bob = node_id(2);
+        let retry = ProtocolEvent::DeliveryRetry {
+            message_id: "m".into(),
+            to: bob,
+            attempt: 1,
+        };
+        assert_eq!(
+            serde_json::to_value(&retry).unwrap(),
+            json!({"kind": "DeliveryRetry", "fields": {"message_id": "m", "to": bob, "attempt": 1}})
+        );
+        assert_eq!(serde_json::to_value(retry.category()).unwrap(), json!("Delivery"));
+
+        let mut path = PathEvent::peer_unreachable(bob);
+        path.rtt = std::time::Duration::from_millis(42);
+        assert_eq!(
+            serde_json::to_value(ProtocolEvent::PathChanged { event: path }).unwrap()["fields"]["event"],
+            json!({"remote": bob, "kind": "UNREACHABLE", "rtt_ms": 42})
+        );
+    }
+
+    #[test]
+    fn filter_by_category_and_peer() {
+        let (alice, bob) = (node_id(1), node_id(2));
+        let online = ProtocolEvent::PeerOnline { node_id: alice };
+        let retry = ProtocolEvent::DeliveryRetry {
+            message_id: "m".into(),
+            to: bob,
+            attempt: 1,
+        };
+
+        assert!(EventFilter::all().matches(&online));
+        assert!(EventFilter::all().category(EventCategory::Peer).matches(&online));
+        assert!(!EventFilter::all().category(EventCategory::Peer).matches(&retry));
+        assert!(EventFilter::all().peer(bob).matches(&retry));
+        assert!(!EventFilter::all().peer(bob).matches(&online));
+
+        let both = EventFilter::all().category(EventCategory::Delivery).peer(alice);
+        assert!(!both.matches(&retry), "category matches but peer doesn't");
+
+        // A hosted identity's event filters like the event it wraps
+        let hosted = ProtocolEvent::Hosted {
+            identity: node_id(3),
+            event: Box::new(retry),
+        };
+        assert!(EventFilter::all().category(EventCategory::Delivery).peer(bob).matches(&hosted));
+    }
+
+    #[tokio::test]
+    async fn bus_fans_out_and_drops_closed_subscribers() {
+        let (tx, mut primary) = mpsc::channel(8);
+        let bus = EventBus::new(tx.into());
+        let mut everything = bus.subscribe(EventFilter::all());
+        let mut groups = bus.subscribe(EventFilter::all().category(EventCategory::Group));
+        let gone = bus.subscribe(EventFilter::all());
+        drop(gone);
+
+        assert!(bus.send(ProtocolEvent::PeerOnline { node_id: node_id(1) }).await);
+        bus.send(ProtocolEvent::GroupJoinDenied {
+            group_id: crate::group::GroupId::new(),
+        })
+        .await;
+
+        assert_eq!(bus.subscriber_count(), 2);
+        for rx in [&mut primary, &mut everything] {
+            assert!(matches!(rx.try_recv(), Ok(ProtocolEvent::PeerOnline { .. })));
+            assert!(matches!(rx.try_recv(), Ok(ProtocolEvent::GroupJoinDenied { .. })));
+        }
+        assert!(matches!(groups.try_recv(), Ok(ProtocolEvent::GroupJoinDenied { .. })));
+        assert!(groups.try_recv().is_err());
+    }
+}
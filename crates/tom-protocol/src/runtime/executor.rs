@@ -2,19 +2,23 @@
 //!
 //! Takes a list of RuntimeEffect and executes them concretely:
 //! - SendEnvelope / SendEnvelopeTo -> transport.send_raw()
-//! - DeliverMessage -> msg_tx.send()
-//! - StatusChange -> status_tx.send()
-//! - Emit -> event_tx.send()
+//! - DeliverMessage -> msg_tx (by its overflow policy)
+//! - StatusChange -> status_tx (by its overflow policy)
+//! - Emit -> event bus (primary receiver + subscribers)
 //! - SendWithBackupFallback -> try send, execute on_success or on_failure
+//! - SendDatagram -> transport.send_datagram() (once, failures ignored)
 
 use std::time::Duration;
 
-use tokio::sync::mpsc;
+use tracing::Instrument;
 
 use crate::envelope::Envelope;
+use crate::logging::envelope_span;
 use crate::types::NodeId;
 
+use super::channel::AppSender;
 use super::effect::RuntimeEffect;
+use super::events::EventBus;
 use super::metrics::ProtocolMetrics;
 use super::transport::Transport;
 use super::{DeliveredMessage, ProtocolEvent};
@@ -26,46 +30,56 @@ const RETRY_DELAYS: [Duration; 2] = [
     Duration::from_millis(1000),
 ];
 
+/// Whole budget of a SendWithBackupFallback send, retries included: once
+/// spent, the backup path runs instead of waiting on an unreachable peer.
+const BACKUP_FALLBACK_DEADLINE: Duration = Duration::from_secs(5);
+
 /// Execute a list of effects using the given transport and channels.
+///
+/// Returns the exactly-once messages the app's channel lost, so their
+/// inbox entries can be released for redelivery.
 pub(super) async fn execute_effects<T: Transport>(
     effects: Vec<RuntimeEffect>,
     transport: &T,
-    msg_tx: &mpsc::Sender<DeliveredMessage>,
-    status_tx: &mpsc::Sender<StatusChange>,
-    event_tx: &mpsc::Sender<ProtocolEvent>,
+    msg_tx: &AppSender<DeliveredMessage>,
+    status_tx: &AppSender<StatusChange>,
+    event_tx: &EventBus,
     metrics: &ProtocolMetrics,
-) {
+) -> Vec<DeliveredMessage> {
     tracing::trace!("execute_effects: {} effects to process", effects.len());
+    let mut undelivered = Vec::new();
     for (i, effect) in effects.into_iter().enumerate() {
         match effect {
             RuntimeEffect::SendEnvelope(ref envelope) => {
                 let target = envelope.via.first().copied().unwrap_or(envelope.to);
                 tracing::trace!("  effect[{}]: SendEnvelope to {}", i, target);
-                send_envelope(transport, envelope, event_tx, metrics).await;
+                send_envelope(transport, envelope, event_tx, metrics)
+                    .instrument(envelope_span(envelope))
+                    .await;
                 tracing::trace!("  effect[{}]: SendEnvelope done", i);
             }
             RuntimeEffect::SendEnvelopeTo { target, ref envelope } => {
                 tracing::trace!("  effect[{}]: SendEnvelopeTo {}", i, target);
-                send_envelope_to(transport, target, envelope, event_tx, metrics).await;
+                send_envelope_to(transport, target, envelope, event_tx, metrics)
+                    .instrument(envelope_span(envelope))
+                    .await;
                 tracing::trace!("  effect[{}]: SendEnvelopeTo done", i);
             }
             RuntimeEffect::DeliverMessage(msg) => {
-                // try_send: never block runtime, even with large buffer (16384)
-                // Consumer is responsible for draining fast enough
-                if msg_tx.try_send(msg).is_err() {
+                // Only a Block policy waits; the others drop when the app lags
+                let (delivered, lost) = msg_tx.send_reclaim(msg).await;
+                if !delivered {
                     metrics.inc_messages_dropped();
                 }
+                undelivered.extend(lost.filter(|m| m.exactly_once));
             }
             RuntimeEffect::StatusChange(change) => {
-                if status_tx.try_send(change).is_err() {
-                    // Status changes are less critical
-                }
+                // Status changes are less critical
+                status_tx.send(change).await;
             }
             RuntimeEffect::Emit(event) => {
-                // try_send even for critical events: large buffer + fast consumer = reliable
-                if event_tx.try_send(event).is_err() {
-                    // Events dropped, but don't count as delivered message loss
-                }
+                // Lost events are reported to the app with EventsDropped
+                event_tx.send(event).await;
             }
             RuntimeEffect::BroadcastRoleChange(announce) => {
                 // Handled in the runtime loop (needs gossip sender).
@@ -76,6 +90,41 @@ pub(super) async fn execute_effects<T: Transport>(
                     announce.new_role,
                 );
             }
+            RuntimeEffect::BroadcastAbuseReport(report) => {
+                tracing::debug!(
+                    "BroadcastAbuseReport reached executor (should be intercepted by loop): {:?} -> {:?}",
+                    report.offender,
+                    report.kind,
+                );
+            }
+            RuntimeEffect::BroadcastRelayAttestation(attestation) => {
+                tracing::debug!(
+                    "BroadcastRelayAttestation reached executor (should be intercepted by loop): {} relays",
+                    attestation.relays.len(),
+                );
+            }
+            RuntimeEffect::BroadcastAnnounce(_) => {
+                tracing::debug!("BroadcastAnnounce reached executor (should be intercepted by loop)");
+            }
+            RuntimeEffect::CompleteRequest(response) => {
+                // Handled in the runtime loop (owns the waiting callers).
+                tracing::debug!(
+                    "CompleteRequest reached executor (should be intercepted by loop): {}",
+                    response.request_id,
+                );
+            }
+            RuntimeEffect::SendDatagram { to, ref bytes } => {
+                // Unreliable by design: a lost datagram is not an error
+                if let Err(e) = transport.send_datagram(to, bytes).await {
+                    tracing::debug!(peer = %to, "datagram not sent: {e}");
+                }
+            }
+            RuntimeEffect::SubscribeChannel { .. }
+            | RuntimeEffect::UnsubscribeChannel { .. }
+            | RuntimeEffect::PublishChannel { .. } => {
+                // Handled in the runtime loop (needs gossip).
+                tracing::debug!("channel effect reached executor (should be intercepted by loop)");
+            }
             RuntimeEffect::SendWithBackupFallback {
                 ref envelope,
                 on_success,
@@ -83,33 +132,36 @@ pub(super) async fn execute_effects<T: Transport>(
             } => {
                 let target = envelope.via.first().copied().unwrap_or(envelope.to);
                 tracing::trace!("  effect[{}]: SendWithBackupFallback to {}", i, target);
+                let span = envelope_span(envelope);
                 let sent_ok = match envelope.to_bytes() {
-                    Ok(bytes) => send_with_retry(transport, target, &bytes).await,
+                    Ok(bytes) => send_with_retry(transport, target, &bytes).instrument(span.clone()).await,
                     Err(_) => false,
                 };
-                if sent_ok {
+                span.in_scope(|| tracing::debug!(peer = %target, sent_ok, "envelope sent with backup fallback"));
+                let follow_up = if sent_ok {
                     metrics.inc_messages_sent();
-                    Box::pin(execute_effects(
-                        on_success, transport, msg_tx, status_tx, event_tx, metrics,
-                    ))
-                    .await;
+                    on_success
                 } else {
                     metrics.inc_messages_failed();
+                    on_failure
+                };
+                undelivered.extend(
                     Box::pin(execute_effects(
-                        on_failure, transport, msg_tx, status_tx, event_tx, metrics,
+                        follow_up, transport, msg_tx, status_tx, event_tx, metrics,
                     ))
-                    .await;
-                }
+                    .await,
+                );
             }
         }
     }
+    undelivered
 }
 
 /// Send an envelope to its first hop (relay or direct to envelope.to).
 async fn send_envelope<T: Transport>(
     transport: &T,
     envelope: &Envelope,
-    event_tx: &mpsc::Sender<ProtocolEvent>,
+    event_tx: &EventBus,
     metrics: &ProtocolMetrics,
 ) {
     let target = envelope.via.first().copied().unwrap_or(envelope.to);
@@ -124,14 +176,14 @@ async fn send_envelope_to<T: Transport>(
     transport: &T,
     target: NodeId,
     envelope: &Envelope,
-    event_tx: &mpsc::Sender<ProtocolEvent>,
+    event_tx: &EventBus,
     metrics: &ProtocolMetrics,
 ) {
     let bytes = match envelope.to_bytes() {
         Ok(b) => b,
         Err(e) => {
             metrics.inc_messages_failed();
-            let _ = event_tx
+            event_tx
                 .send(ProtocolEvent::Error {
                     description: format!("serialize envelope failed: {e}"),
                 })
@@ -144,7 +196,7 @@ async fn send_envelope_to<T: Transport>(
     let mut last_err = match transport.send_raw(target, &bytes).await {
         Ok(()) => {
             metrics.inc_messages_sent();
-            tracing::trace!("send_envelope_to {}: OK (first attempt)", target);
+            tracing::debug!(peer = %target, "envelope sent");
             return;
         }
         Err(e) => {
@@ -161,6 +213,7 @@ async fn send_envelope_to<T: Transport>(
         match transport.send_raw(target, &bytes).await {
             Ok(()) => {
                 metrics.inc_messages_sent();
+                tracing::debug!(peer = %target, retries = i + 1, "envelope sent");
                 return;
             }
             Err(e) => last_err = e,
@@ -169,7 +222,7 @@ async fn send_envelope_to<T: Transport>(
 
     // All retries exhausted
     metrics.inc_messages_failed();
-    let _ = event_tx
+    event_tx
         .send(ProtocolEvent::Error {
             description: format!("send to {target} failed after {} attempts: {last_err}", 1 + RETRY_DELAYS.len()),
         })
@@ -177,14 +230,20 @@ async fn send_envelope_to<T: Transport>(
 }
 
 /// Raw send with retry (for SendWithBackupFallback). Returns true on success.
+///
+/// Attempts and backoff share `BACKUP_FALLBACK_DEADLINE`, so the caller
+/// falls back after a bounded time however the peer fails.
 async fn send_with_retry<T: Transport>(transport: &T, target: NodeId, bytes: &[u8]) -> bool {
-    if transport.send_raw(target, bytes).await.is_ok() {
-        return true;
-    }
+    let deadline = tokio::time::Instant::now() + BACKUP_FALLBACK_DEADLINE;
+    for delay in std::iter::once(Duration::ZERO).chain(RETRY_DELAYS) {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        tokio::time::sleep(delay.min(remaining)).await;
 
-    for delay in &RETRY_DELAYS {
-        tokio::time::sleep(*delay).await;
-        if transport.send_raw(target, bytes).await.is_ok() {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        if transport.send_raw_timeout(target, bytes, remaining).await.is_ok() {
             return true;
         }
     }
@@ -196,6 +255,7 @@ async fn send_with_retry<T: Transport>(transport: &T, target: NodeId, bytes: &[u
 mod tests {
     use super::*;
     use super::super::transport::mock::MockTransport;
+    use tokio::sync::mpsc;
     use crate::types::NodeId;
 
     fn test_node_id(seed: u8) -> NodeId {
@@ -240,12 +300,27 @@ mod tests {
         assert!(transport.sent().is_empty());
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn send_with_retry_falls_back_within_deadline_when_peer_hangs() {
+        let transport = MockTransport::new();
+        transport.set_hang_sends(true);
+        let target = test_node_id(1);
+
+        let started = tokio::time::Instant::now();
+        let ok = send_with_retry(&transport, target, b"hello").await;
+        assert!(!ok);
+        assert_eq!(started.elapsed(), BACKUP_FALLBACK_DEADLINE);
+        // The hung attempt used the whole budget: no retry after it
+        assert_eq!(*transport.send_attempts.lock().unwrap(), 1);
+    }
+
     #[tokio::test]
     async fn send_envelope_to_retries_on_transient_failure() {
         let transport = MockTransport::new();
         transport.set_fail_count(1); // fail once, then succeed
         let target = test_node_id(1);
         let (event_tx, mut event_rx) = mpsc::channel(16);
+        let event_tx = EventBus::new(event_tx.into());
         let metrics = ProtocolMetrics::new();
 
         let envelope = crate::envelope::EnvelopeBuilder::new(
@@ -272,6 +347,7 @@ mod tests {
         transport.set_fail_sends(true);
         let target = test_node_id(1);
         let (event_tx, mut event_rx) = mpsc::channel(16);
+        let event_tx = EventBus::new(event_tx.into());
         let metrics = ProtocolMetrics::new();
 
         let envelope = crate::envelope::EnvelopeBuilder::new(
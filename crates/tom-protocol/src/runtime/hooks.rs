@@ -0,0 +1,181 @@
+//! Runtime hooks — user code in the envelope and event pipeline.
+//!
+//! Hooks registered in `RuntimeConfig::hooks` run in order, and the first
+//! one to return [`HookAction::Drop`] stops the item:
+//! - `on_incoming_envelope`: every parsed envelope, after its signature is
+//!   checked and before it is dispatched (or relayed);
+//! - `on_outgoing_envelope`: every envelope just before it is sent;
+//! - `on_event`: every `ProtocolEvent` before it reaches subscribers.
+//!
+//! Envelopes are passed mutably for transforms. The signature was already
+//! verified on the way in and is checked again by the recipient on the way
+//! out, so an outgoing change to a signed field gets the envelope rejected.
+
+use crate::envelope::Envelope;
+
+use super::{ProtocolEvent, RuntimeEffect};
+
+/// What a hook wants done with the item it saw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HookAction {
+    /// Pass it on (to the next hook, then the runtime).
+    #[default]
+    Continue,
+    /// Drop it silently.
+    Drop,
+}
+
+/// Filtering, logging or transforms without forking the runtime loop.
+///
+/// Every method defaults to `Continue`, so a hook implements only what it
+/// needs. Hooks run on the runtime task: keep them fast and non-blocking.
+pub trait RuntimeHook: Send + Sync {
+    fn on_incoming_envelope(&self, _envelope: &mut Envelope) -> HookAction {
+        HookAction::Continue
+    }
+
+    fn on_outgoing_envelope(&self, _envelope: &mut Envelope) -> HookAction {
+        HookAction::Continue
+    }
+
+    fn on_event(&self, _event: &ProtocolEvent) -> HookAction {
+        HookAction::Continue
+    }
+}
+
+/// The registered hooks, in order.
+#[derive(Default)]
+pub(crate) struct Hooks(Vec<Box<dyn RuntimeHook>>);
+
+impl Hooks {
+    pub(crate) fn new(hooks: Vec<Box<dyn RuntimeHook>>) -> Self {
+        Self(hooks)
+    }
+
+    /// Run the incoming hooks; false if one dropped the envelope.
+    pub(crate) fn incoming(&self, envelope: &mut Envelope) -> bool {
+        self.0
+            .iter()
+            .all(|h| h.on_incoming_envelope(envelope) == HookAction::Continue)
+    }
+
+    /// Run the outgoing and event hooks over effects about to execute,
+    /// removing what they drop (a dropped fallback send takes its
+    /// success/failure effects with it).
+    pub(crate) fn outgoing(&self, effects: Vec<RuntimeEffect>) -> Vec<RuntimeEffect> {
+        if self.0.is_empty() {
+            return effects;
+        }
+        effects.into_iter().filter_map(|e| self.effect(e)).collect()
+    }
+
+    fn effect(&self, effect: RuntimeEffect) -> Option<RuntimeEffect> {
+        match effect {
+            RuntimeEffect::SendEnvelope(mut envelope) => {
+                self.send(&mut envelope).then_some(RuntimeEffect::SendEnvelope(envelope))
+            }
+            RuntimeEffect::SendEnvelopeTo {
+                target,
+                mut envelope,
+            } => self
+                .send(&mut envelope)
+                .then_some(RuntimeEffect::SendEnvelopeTo { target, envelope }),
+            RuntimeEffect::SendWithBackupFallback {
+                mut envelope,
+                on_success,
+                on_failure,
+            } => self
+                .send(&mut envelope)
+                .then(|| RuntimeEffect::SendWithBackupFallback {
+                    envelope,
+                    on_success: self.outgoing(on_success),
+                    on_failure: self.outgoing(on_failure),
+                }),
+            RuntimeEffect::Emit(event) => self
+                .0
+                .iter()
+                .all(|h| h.on_event(&event) == HookAction::Continue)
+                .then_some(RuntimeEffect::Emit(event)),
+            other => Some(other),
+        }
+    }
+
+    fn send(&self, envelope: &mut Envelope) -> bool {
+        self.0
+            .iter()
+            .all(|h| h.on_outgoing_envelope(envelope) == HookAction::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{MessageType, NodeId};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    fn envelope(payload: &[u8]) -> Envelope {
+        Envelope::new_via(node_id(1), node_id(2), Vec::new(), MessageType::Chat, payload.to_vec())
+    }
+
+    /// Drops envelopes whose payload is "spam", tags the rest, counts events.
+    struct Filter {
+        events: Arc<AtomicUsize>,
+    }
+
+    impl RuntimeHook for Filter {
+        fn on_outgoing_envelope(&self, envelope: &mut Envelope) -> HookAction {
+            if envelope.payload == b"spam" {
+                return HookAction::Drop;
+            }
+            envelope.payload.extend_from_slice(b"!");
+            HookAction::Continue
+        }
+
+        fn on_event(&self, event: &ProtocolEvent) -> HookAction {
+            self.events.fetch_add(1, Ordering::Relaxed);
+            match event {
+                ProtocolEvent::Error { .. } => HookAction::Drop,
+                _ => HookAction::Continue,
+            }
+        }
+    }
+
+    #[test]
+    fn outgoing_hooks_transform_and_drop() {
+        let events = Arc::new(AtomicUsize::new(0));
+        let hooks = Hooks::new(vec![Box::new(Filter { events: events.clone() })]);
+        let effects = hooks.outgoing(vec![
+            RuntimeEffect::SendEnvelope(envelope(b"hi")),
+            RuntimeEffect::SendWithBackupFallback {
+                envelope: envelope(b"spam"),
+                on_success: vec![RuntimeEffect::SendEnvelope(envelope(b"x"))],
+                on_failure: Vec::new(),
+            },
+            RuntimeEffect::Emit(ProtocolEvent::Error {
+                description: "boom".into(),
+            }),
+            RuntimeEffect::Emit(ProtocolEvent::PeerOnline { node_id: node_id(3) }),
+        ]);
+
+        assert_eq!(effects.len(), 2);
+        assert!(matches!(&effects[0], RuntimeEffect::SendEnvelope(env) if env.payload == b"hi!"));
+        assert!(matches!(&effects[1], RuntimeEffect::Emit(ProtocolEvent::PeerOnline { .. })));
+        assert_eq!(events.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn no_hooks_pass_everything() {
+        let hooks = Hooks::default();
+        let mut env = envelope(b"spam");
+        assert!(hooks.incoming(&mut env));
+        assert_eq!(hooks.outgoing(vec![RuntimeEffect::SendEnvelope(env)]).len(), 1);
+    }
+}
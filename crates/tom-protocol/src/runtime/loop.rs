@@ -3,57 +3,366 @@
 /// Owns RuntimeState + TomNode. Multiplexes over transport events,
 /// application commands, and timers. Delegates all logic to RuntimeState,
 /// executes effects via executor.
-use tokio::sync::{broadcast, mpsc};
-use tom_transport::TomNode;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
 
-use crate::types::NodeId;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tom_transport::{TomNode, TomStream};
 
+use crate::discovery::bootstrap::{run_lan_discovery, LanBeacon};
+use crate::discovery::{BootstrapConfig, BootstrapPipeline, BootstrapSource, BootstrapStep};
+use crate::envelope::Envelope;
+use crate::types::{now_ms, NodeId};
+
+use super::channel::AppSender;
 use super::effect::RuntimeEffect;
+use super::events::EventBus;
 use super::executor::execute_effects;
+use super::scheduler::EffectScheduler;
 use super::state::{GossipInput, RuntimeState};
-use super::{DeliveredMessage, ProtocolEvent, RuntimeCommand};
+use super::{DeliveredMessage, ProtocolEvent, RuntimeCommand, ShutdownReport};
 use crate::tracker::StatusChange;
+use crate::transfer::ReceiveBudget;
 
 use tom_gossip::Gossip;
-use tom_gossip::api::Event as GossipEvent;
-use n0_future::StreamExt;
+use tom_gossip::api::{Event as GossipEvent, GossipReceiver, GossipSender};
+use n0_future::{FutureExt, StreamExt};
 use tom_connect::TransportAddr;
 use tom_transport::PathEvent;
 
-use super::metrics::ProtocolMetrics;
+use super::metrics::{serve_metrics, ProtocolMetrics};
 
 /// Fixed gossip topic for ToM peer discovery (all nodes share this).
 const TOM_GOSSIP_TOPIC: [u8; 32] = *b"tom-protocol-gossip-discovery-v1";
 
-/// Main event loop — thin orchestrator.
+/// Buffered publications from all channel subscriptions.
+const CHANNEL_INBOX_CAPACITY: usize = 256;
+
+/// Buffered progress/outcome events from file transfer tasks.
+const TRANSFER_EVENT_CAPACITY: usize = 256;
+
+/// How often bootstrap stage timeouts are checked.
+const BOOTSTRAP_TICK: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Most rounds of envelopes passed between local identities per loop
+/// iteration (a message, its ACK, its read receipt...).
+const MAX_LOCAL_ROUNDS: usize = 8;
+
+/// Longest wait before restarting a panicked loop.
+const MAX_RESTART_BACKOFF: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A loop that ran this long before panicking starts the restart count over.
+const STABLE_RUN: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Callers waiting on a response, each failed at its deadline.
+#[derive(Default)]
+struct PendingRequests {
+    pending: HashMap<String, PendingRequest>,
+}
+
+struct PendingRequest {
+    target: NodeId,
+    deadline: tokio::time::Instant,
+    reply: oneshot::Sender<Result<crate::rpc::Response, crate::TomProtocolError>>,
+}
+
+impl PendingRequests {
+    /// Register a caller; returns the request id, or None (the caller is
+    /// told why) when too many requests are in flight.
+    fn start(
+        &mut self,
+        target: NodeId,
+        timeout: std::time::Duration,
+        reply: oneshot::Sender<Result<crate::rpc::Response, crate::TomProtocolError>>,
+    ) -> Option<String> {
+        if self.pending.len() >= crate::rpc::MAX_PENDING_REQUESTS {
+            let _ = reply.send(Err(crate::TomProtocolError::RequestFailed {
+                reason: "too many requests in flight".into(),
+            }));
+            return None;
+        }
+        let timeout = timeout.min(std::time::Duration::from_millis(crate::rpc::MAX_REQUEST_TIMEOUT_MS));
+        let request_id = uuid::Uuid::new_v4().to_string();
+        self.pending.insert(
+            request_id.clone(),
+            PendingRequest {
+                target,
+                deadline: tokio::time::Instant::now() + timeout,
+                reply,
+            },
+        );
+        Some(request_id)
+    }
+
+    /// Hand a response to its caller, if it came from the node asked.
+    fn complete(&mut self, response: crate::rpc::Response) {
+        if self
+            .pending
+            .get(&response.request_id)
+            .is_none_or(|p| p.target != response.from)
+        {
+            return;
+        }
+        if let Some(pending) = self.pending.remove(&response.request_id) {
+            let _ = pending.reply.send(Ok(response));
+        }
+    }
+
+    /// Fail a caller right away (the request couldn't be sent).
+    fn fail(&mut self, request_id: &str, reason: String) {
+        if let Some(pending) = self.pending.remove(request_id) {
+            let _ = pending.reply.send(Err(crate::TomProtocolError::RequestFailed { reason }));
+        }
+    }
+
+    fn next_deadline(&self) -> Option<tokio::time::Instant> {
+        self.pending.values().map(|p| p.deadline).min()
+    }
+
+    /// Fail every caller whose deadline passed.
+    fn expire(&mut self, now: tokio::time::Instant) {
+        let expired: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| p.deadline <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for request_id in expired {
+            if let Some(pending) = self.pending.remove(&request_id) {
+                let _ = pending
+                    .reply
+                    .send(Err(crate::TomProtocolError::RequestTimeout { request_id }));
+            }
+        }
+    }
+}
+
+/// A live pub/sub channel subscription.
+struct ChannelSubscription {
+    sender: GossipSender,
+    forwarder: tokio::task::JoinHandle<()>,
+}
+
+/// Everything the loop owns, kept by the supervisor across restarts.
+struct LoopContext {
+    node: TomNode,
+    state: RuntimeState,
+    hosted: Vec<RuntimeState>,
+    gossip_bootstrap_peers: Vec<NodeId>,
+    cmd_tx: mpsc::Sender<RuntimeCommand>,
+    /// Bulk-producing commands, not read while bulk sends are backlogged.
+    cmd_rx: mpsc::Receiver<RuntimeCommand>,
+    /// Every other command (shutdown, queries, peers, groups admin).
+    control_rx: mpsc::Receiver<RuntimeCommand>,
+    msg_tx: AppSender<DeliveredMessage>,
+    status_tx: AppSender<StatusChange>,
+    event_tx: EventBus,
+    path_rx: broadcast::Receiver<PathEvent>,
+    peer_present_rx: Option<mpsc::Receiver<(tom_connect::EndpointId, tom_connect::RelayUrl)>>,
+    incoming_streams: Option<mpsc::Receiver<TomStream>>,
+    incoming_datagrams: Option<mpsc::Receiver<(NodeId, Vec<u8>)>>,
+    transfer_tx: mpsc::Sender<ProtocolEvent>,
+    transfer_rx: mpsc::Receiver<ProtocolEvent>,
+    /// Incoming transfers in progress, against `receive_policy`.
+    receive_budget: ReceiveBudget,
+    /// Bulk sends waiting behind control traffic (kept across restarts).
+    scheduler: EffectScheduler,
+    /// Startup fallback chain (runs once, resumes across restarts).
+    bootstrap: BootstrapPipeline,
+    gossip: Gossip,
+    metrics: ProtocolMetrics,
+}
+
+/// Supervisor of the event loop.
 ///
-/// All protocol logic lives in `RuntimeState`. This function only:
-/// 1. Multiplexes I/O events via `tokio::select!`
-/// 2. Calls the appropriate `RuntimeState` method
-/// 3. Feeds resulting effects to the executor
+/// Runs [`run_loop`] and, if it panics, restarts it over the same
+/// `RuntimeState` and channels after a backoff (`restart_backoff`, doubled
+/// per attempt up to `MAX_RESTART_BACKOFF`), emitting `RuntimeRestarted`.
+/// A loop that stayed up `STABLE_RUN` starts the count over; past
+/// `max_restarts` in a row the node is shut down.
 #[allow(clippy::too_many_arguments)]
 pub(super) async fn runtime_loop(
     mut node: TomNode,
-    mut state: RuntimeState,
+    state: RuntimeState,
+    hosted: Vec<RuntimeState>,
     gossip_bootstrap_peers: Vec<NodeId>,
     cmd_tx: mpsc::Sender<RuntimeCommand>,
-    mut cmd_rx: mpsc::Receiver<RuntimeCommand>,
-    msg_tx: mpsc::Sender<DeliveredMessage>,
-    status_tx: mpsc::Sender<StatusChange>,
-    event_tx: mpsc::Sender<ProtocolEvent>,
-    mut path_rx: broadcast::Receiver<PathEvent>,
+    cmd_rx: mpsc::Receiver<RuntimeCommand>,
+    control_rx: mpsc::Receiver<RuntimeCommand>,
+    msg_tx: AppSender<DeliveredMessage>,
+    status_tx: AppSender<StatusChange>,
+    event_tx: EventBus,
+    path_rx: broadcast::Receiver<PathEvent>,
     gossip: Gossip,
     metrics: ProtocolMetrics,
 ) {
+    let peer_present_rx = node.take_peer_present_rx();
+    let incoming_streams = node.take_incoming_streams();
+    let incoming_datagrams = node.take_incoming_datagrams();
+    // Transfer tasks report here; the loop emits (outlives restarts)
+    let (transfer_tx, transfer_rx) = mpsc::channel(TRANSFER_EVENT_CAPACITY);
+    let receive_budget = ReceiveBudget::new(state.config.receive_policy.clone());
+
+    // ── Metrics exporter (optional, outlives restarts) ────────────────
+    let exporter = match state.config.metrics_addr {
+        Some(addr) => match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => {
+                tracing::info!("metrics exporter on http://{addr}/metrics");
+                Some(tokio::spawn(serve_metrics(listener, metrics.clone())))
+            }
+            Err(e) => {
+                tracing::warn!("metrics exporter: bind {addr} failed: {e}");
+                None
+            }
+        },
+        None => None,
+    };
+
+    let bootstrap = BootstrapPipeline::new(
+        state
+            .config
+            .bootstrap
+            .stages(state.dht().is_some(), !gossip_bootstrap_peers.is_empty()),
+    );
+    let mut cx = LoopContext {
+        node,
+        state,
+        hosted,
+        gossip_bootstrap_peers,
+        cmd_tx,
+        cmd_rx,
+        control_rx,
+        msg_tx,
+        status_tx,
+        event_tx,
+        path_rx,
+        peer_present_rx,
+        incoming_streams,
+        incoming_datagrams,
+        transfer_tx,
+        transfer_rx,
+        receive_budget,
+        scheduler: EffectScheduler::default(),
+        bootstrap,
+        gossip,
+        metrics,
+    };
+
+    let mut shutdown_reply = None;
+    let mut attempt = 0u32;
+    loop {
+        let started = tokio::time::Instant::now();
+        let panic = match AssertUnwindSafe(run_loop(&mut cx)).catch_unwind().await {
+            Ok(shutdown) => {
+                shutdown_reply = shutdown;
+                break;
+            }
+            Err(panic) => panic,
+        };
+        let reason = panic_message(panic.as_ref());
+        if started.elapsed() >= STABLE_RUN {
+            attempt = 0;
+        }
+        attempt += 1;
+        tracing::error!(attempt, "runtime loop panicked: {reason}");
+
+        if attempt > cx.state.config.max_restarts {
+            tracing::error!("runtime loop: giving up after {} restarts", attempt - 1);
+            cx.event_tx
+                .send(ProtocolEvent::Error {
+                    description: format!("runtime stopped after repeated panics: {reason}"),
+                })
+                .await;
+            cx.state.save_state();
+            cx.hosted.iter().for_each(RuntimeState::save_state);
+            break;
+        }
+
+        tokio::time::sleep(restart_backoff(cx.state.config.restart_backoff, attempt)).await;
+        cx.event_tx
+            .send(ProtocolEvent::RuntimeRestarted { attempt, reason })
+            .await;
+    }
+
+    if let Some(exporter) = exporter {
+        exporter.abort();
+    }
+
+    // Graceful shutdown
+    if let Err(e) = cx.node.shutdown().await {
+        tracing::warn!("runtime shutdown error: {e}");
+    }
+    if let Some((reply, report)) = shutdown_reply {
+        let _ = reply.send(report);
+    }
+}
+
+/// Delay before restart `attempt` (1-based): `base`, doubled per attempt.
+fn restart_backoff(base: std::time::Duration, attempt: u32) -> std::time::Duration {
+    base.saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(MAX_RESTART_BACKOFF)
+}
+
+/// The message a panic was raised with, if it's a string.
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Main event loop — thin orchestrator.
+///
+/// All protocol logic lives in `RuntimeState`. This function only:
+/// 1. Multiplexes I/O events via `tokio::select!`
+/// 2. Calls the appropriate `RuntimeState` method
+/// 3. Feeds resulting effects to the executor
+///
+/// Returns when the runtime stops (on request or when every handle is
+/// gone), state saved, with the caller to answer if shutdown was requested.
+async fn run_loop(
+    cx: &mut LoopContext,
+) -> Option<(oneshot::Sender<ShutdownReport>, ShutdownReport)> {
+    let LoopContext {
+        node,
+        state,
+        hosted,
+        gossip_bootstrap_peers,
+        cmd_tx,
+        cmd_rx,
+        control_rx,
+        msg_tx,
+        status_tx,
+        event_tx,
+        path_rx,
+        peer_present_rx,
+        incoming_streams,
+        incoming_datagrams,
+        transfer_tx,
+        transfer_rx,
+        receive_budget,
+        scheduler,
+        bootstrap,
+        gossip,
+        metrics,
+    } = cx;
+
     // ── Timers (read intervals from state.config) ───────────────────
     let mut cache_cleanup = tokio::time::interval(state.config.cache_cleanup_interval);
     let mut tracker_cleanup = tokio::time::interval(state.config.tracker_cleanup_interval);
-    let mut heartbeat_check = tokio::time::interval(state.config.heartbeat_interval);
+    let mut heartbeat_check = tokio::time::interval(std::time::Duration::from_millis(state.config.liveness.heartbeat_interval_ms));
     let mut group_hub_heartbeat = tokio::time::interval(state.config.group_hub_heartbeat_interval);
     let mut backup_tick = tokio::time::interval(state.config.backup_tick_interval);
-    let mut gossip_announce = tokio::time::interval(state.config.gossip_announce_interval);
+    let mut backup_delivery = tokio::time::interval(state.config.backup_delivery_interval);
+    let mut backup_integrity = tokio::time::interval(state.config.backup_integrity_interval);
+    let mut gossip_announce = tokio::time::interval(std::time::Duration::from_millis(state.config.liveness.announce_interval_ms));
     let mut shadow_ping = tokio::time::interval(state.config.shadow_ping_interval);
-    let mut subnet_eval = tokio::time::interval(std::time::Duration::from_secs(30));
+    let mut subnet_eval = tokio::time::interval(std::time::Duration::from_millis(
+        state.config.subnet.evaluation_interval_ms.max(1),
+    ));
     let mut role_eval = tokio::time::interval(std::time::Duration::from_secs(60));
     let mut state_save = tokio::time::interval(std::time::Duration::from_secs(30));
     let mut dht_republish = tokio::time::interval(std::time::Duration::from_secs(30 * 60));
@@ -66,6 +375,8 @@ pub(super) async fn runtime_loop(
     heartbeat_check.tick().await;
     group_hub_heartbeat.tick().await;
     backup_tick.tick().await;
+    backup_delivery.tick().await;
+    backup_integrity.tick().await;
     gossip_announce.tick().await;
     shadow_ping.tick().await;
     subnet_eval.tick().await;
@@ -77,12 +388,12 @@ pub(super) async fn runtime_loop(
 
     // ── Gossip subscription ──────────────────────────────────────────
     let topic_id = tom_gossip::TopicId::from_bytes(TOM_GOSSIP_TOPIC);
-    let bootstrap: Vec<tom_connect::EndpointId> = gossip_bootstrap_peers
+    let entry_points: Vec<tom_connect::EndpointId> = gossip_bootstrap_peers
         .iter()
         .map(|n| *n.as_endpoint_id())
         .collect();
 
-    let (gossip_sender, mut gossip_receiver) = match gossip.subscribe(topic_id, bootstrap).await {
+    let (gossip_sender, mut gossip_receiver) = match gossip.subscribe(topic_id, entry_points).await {
         Ok(topic) => {
             let (s, r) = topic.split();
             tracing::info!("gossip: subscribed to discovery topic");
@@ -94,6 +405,10 @@ pub(super) async fn runtime_loop(
         }
     };
 
+    // ── Pub/sub channel subscriptions ───────────────────────────────
+    let mut channel_subs: HashMap<[u8; 32], ChannelSubscription> = HashMap::new();
+    let (channel_tx, mut channel_rx) = mpsc::channel::<([u8; 32], Vec<u8>)>(CHANNEL_INBOX_CAPACITY);
+
     // ── DHT setup ──────────────────────────────────────────────────
     let secret_seed = node.secret_key_seed();
     // Clone the async DHT handle for spawned lookup tasks (cheap Arc clone)
@@ -102,28 +417,69 @@ pub(super) async fn runtime_loop(
 
     // Publish to DHT at startup (BEP-0044)
     {
-        let (relay_urls, direct_addrs) = extract_node_addrs(&node);
+        let (relay_urls, direct_addrs) = extract_node_addrs(node);
         state.publish_to_dht(&secret_seed, relay_urls, direct_addrs).await;
     }
 
-    // ── PeerPresent receiver from relay ────────────────────────────────
-    let mut peer_present_rx = node.take_peer_present_rx();
+    // ── Startup bootstrap (once; a restart resumes the current stage) ──
+    let mut bootstrap_tick = tokio::time::interval(BOOTSTRAP_TICK);
+    if !bootstrap.is_started() {
+        let step = bootstrap.start(now_ms());
+        run_bootstrap_step(
+            step,
+            bootstrap,
+            &state.config.bootstrap,
+            node,
+            gossip_bootstrap_peers,
+            gossip_sender.as_ref(),
+            dht_handle.as_ref(),
+            cmd_tx,
+        )
+        .await;
+    }
 
     // ── Rejoin groups after restart (one-shot) ────────────────────────
-    let rejoin_effects = state.build_rejoin_effects();
+    let rejoin_effects = on_all_states(state, hosted, |s| s.build_rejoin_effects());
     if !rejoin_effects.is_empty() {
-        execute_effects(rejoin_effects, &node, &msg_tx, &status_tx, &event_tx, &metrics).await;
+        let rejoin_effects = state.apply_hooks(rejoin_effects);
+        execute_effects(rejoin_effects, &*node, msg_tx, status_tx, event_tx, metrics).await;
     }
 
     // ── Main loop ────────────────────────────────────────────────────
+    let mut shutdown_reply: Option<oneshot::Sender<ShutdownReport>> = None;
+    let mut requests = PendingRequests::default();
     loop {
         let effects = tokio::select! {
             // ── 1. Incoming data from transport ─────────────────
             result = node.recv_raw() => {
                 match result {
-                    Ok((_from, data)) => {
+                    Ok((from, data)) => {
                         metrics.inc_messages_received();
-                        state.handle_incoming(&data)
+                        let mut effects =
+                            on_all_states(state, hosted, |s| s.set_connectivity(true));
+                        match hosted_recipient(hosted, &data) {
+                            Some(identity) => {
+                                let id = identity.local_id;
+                                effects.extend(tag_hosted(id, identity.handle_incoming(&data)));
+                            }
+                            None => effects.extend(state.handle_incoming(&data)),
+                        }
+                        if let Some(step) = bootstrap.on_peer(from) {
+                            effects.extend(
+                                run_bootstrap_step(
+                                    step,
+                                    bootstrap,
+                                    &state.config.bootstrap,
+                                    node,
+                                    gossip_bootstrap_peers,
+                                    gossip_sender.as_ref(),
+                                    dht_handle.as_ref(),
+                                    cmd_tx,
+                                )
+                                .await,
+                            );
+                        }
+                        effects
                     }
                     Err(e) => vec![RuntimeEffect::Emit(ProtocolEvent::Error {
                         description: format!("recv error: {e}"),
@@ -132,13 +488,22 @@ pub(super) async fn runtime_loop(
             }
 
             // ── 2. Commands from application ────────────────────
-            Some(cmd) = cmd_rx.recv() => {
+            // (bulk-producing ones held back while bulk sends are backlogged)
+            Some(cmd) = next_command(control_rx, cmd_rx, scheduler.is_backlogged()) => {
                 match cmd {
                     RuntimeCommand::GetConnectedPeers { reply } => {
                         let peers = node.connected_peers().await;
                         let _ = reply.send(peers);
                         Vec::new()
                     }
+                    RuntimeCommand::GetTransportStats { reply } => {
+                        let _ = reply.send(node.node_stats());
+                        Vec::new()
+                    }
+                    RuntimeCommand::GetPeerTransportStats { node_id, reply } => {
+                        let _ = reply.send(node.peer_stats(node_id));
+                        Vec::new()
+                    }
                     RuntimeCommand::AddPeerAddr { addr } => {
                         let node_id = NodeId::from_endpoint_id(addr.id);
                         let endpoint_id = addr.id;
@@ -154,22 +519,7 @@ pub(super) async fn runtime_loop(
                         // Spawn a DHT lookup for unknown peers (non-blocking)
                         if let Some(dht_client) = dht_handle.as_ref() {
                             if state.topology.get(&node_id).is_none() {
-                                let dht_clone = dht_client.clone();
-                                let pk = node_id.as_bytes();
-                                let tx = cmd_tx.clone();
-                                tokio::spawn(async move {
-                                    match tom_dht::dht_lookup(&dht_clone, &pk).await {
-                                        Ok(Some(addr)) => {
-                                            let _ = tx.send(
-                                                RuntimeCommand::DhtLookupResult { addr }
-                                            ).await;
-                                        }
-                                        Ok(None) => {}
-                                        Err(e) => {
-                                            tracing::debug!("DHT lookup failed: {e}");
-                                        }
-                                    }
-                                });
+                                spawn_dht_lookup(dht_client, node_id, cmd_tx);
                             }
                         }
                         state.handle_command(RuntimeCommand::AddPeer { node_id })
@@ -186,16 +536,172 @@ pub(super) async fn runtime_loop(
                         }
                         state.handle_command(cmd)
                     }
-                    RuntimeCommand::Shutdown => break,
+                    RuntimeCommand::GetPairingInfo { reply } => {
+                        let _ = reply.send(local_pairing_info(node, state.local_id));
+                        Vec::new()
+                    }
+                    RuntimeCommand::CreatePairingCode { reply } => {
+                        match dht_handle.as_ref() {
+                            Some(dht_client) => {
+                                let dht_clone = dht_client.clone();
+                                let info = local_pairing_info(node, state.local_id);
+                                tokio::spawn(async move {
+                                    let now = crate::types::now_ms();
+                                    let code = crate::pairing::PairingCode::new(now);
+                                    let published = match crate::pairing::PairingRecord::new(&info, now, &secret_seed)
+                                        .and_then(|r| r.to_bytes())
+                                    {
+                                        Ok(record) => tom_dht::dht_publish_pairing(
+                                            &dht_clone,
+                                            &code.code.seed(code.window),
+                                            &record,
+                                        )
+                                        .await
+                                        .map_err(|e| tracing::debug!("pairing publish failed: {e}"))
+                                        .is_ok(),
+                                        Err(_) => false,
+                                    };
+                                    let _ = reply.send(published.then_some(code));
+                                });
+                            }
+                            None => {
+                                let _ = reply.send(None);
+                            }
+                        }
+                        Vec::new()
+                    }
+                    RuntimeCommand::RedeemPairingCode { code, reply } => {
+                        match dht_handle.as_ref() {
+                            Some(dht_client) => {
+                                let dht_clone = dht_client.clone();
+                                let tx = cmd_tx.clone();
+                                tokio::spawn(async move {
+                                    let now = crate::types::now_ms();
+                                    let window = crate::pairing::rendezvous_window(now);
+                                    // Created in this window or the previous one
+                                    let mut found = None;
+                                    for w in [window, window.saturating_sub(1)] {
+                                        let Some(bytes) =
+                                            tom_dht::dht_lookup_pairing(&dht_clone, &code.public_key(w)).await
+                                        else {
+                                            continue;
+                                        };
+                                        match crate::pairing::PairingRecord::open(&bytes, now) {
+                                            Ok(info) => {
+                                                found = Some(info);
+                                                break;
+                                            }
+                                            Err(e) => tracing::debug!("pairing record rejected: {e}"),
+                                        }
+                                    }
+                                    let node_id = found.as_ref().map(|info| info.node_id);
+                                    if let Some(info) = found {
+                                        let _ = tx
+                                            .send(RuntimeCommand::AddPeerAddr { addr: info.to_endpoint_addr() })
+                                            .await;
+                                    }
+                                    let _ = reply.send(node_id);
+                                });
+                            }
+                            None => {
+                                let _ = reply.send(None);
+                            }
+                        }
+                        Vec::new()
+                    }
+                    RuntimeCommand::Request {
+                        to,
+                        payload,
+                        timeout,
+                        reply,
+                    } => match requests.start(to, timeout, reply) {
+                        Some(request_id) => {
+                            let effects = state.send_rpc(
+                                to,
+                                crate::types::MessageType::Request,
+                                request_id.clone(),
+                                payload,
+                            );
+                            if !effects.iter().any(|e| matches!(e, RuntimeEffect::SendEnvelope(_))) {
+                                requests.fail(&request_id, format!("cannot send a request to {to}"));
+                            }
+                            effects
+                        }
+                        None => Vec::new(),
+                    },
+                    RuntimeCommand::SendFile { to, path, reply } => {
+                        let sent = crate::transfer::spawn_send(node.streams(), to, path, transfer_tx.clone());
+                        let _ = reply.send(sent);
+                        Vec::new()
+                    }
+                    RuntimeCommand::Shutdown { reply } => {
+                        shutdown_reply = Some(reply);
+                        Vec::new()
+                    }
+                    RuntimeCommand::Pause => {
+                        on_all_states(state, hosted, |s| s.handle_command(RuntimeCommand::Pause))
+                    }
+                    RuntimeCommand::Resume => {
+                        on_all_states(state, hosted, |s| s.handle_command(RuntimeCommand::Resume))
+                    }
+                    RuntimeCommand::AsIdentity { identity, command } => {
+                        match hosted.iter_mut().find(|h| h.local_id == identity) {
+                            Some(h) => tag_hosted(identity, h.handle_command(*command)),
+                            None => state.handle_command(RuntimeCommand::AsIdentity { identity, command }),
+                        }
+                    }
                     other => state.handle_command(other),
                 }
             }
 
-            // ── 3. Path events from transport ───────────────────
-            Ok(event) = path_rx.recv() => {
-                vec![RuntimeEffect::Emit(ProtocolEvent::PathChanged { event })]
+            // ── 1b. Real-time lane datagrams ─────────────────────
+            datagram = async {
+                match incoming_datagrams.as_mut() {
+                    Some(rx) => rx.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                match datagram {
+                    Some((from, data)) => match hosted_recipient(hosted, &data) {
+                        Some(identity) => {
+                            let id = identity.local_id;
+                            tag_hosted(id, identity.handle_datagram(from, &data))
+                        }
+                        None => state.handle_datagram(from, &data),
+                    },
+                    None => {
+                        *incoming_datagrams = None;
+                        Vec::new()
+                    }
+                }
             }
 
+            // ── 2b. File transfers: peers' streams, task events ──
+            stream = async {
+                match incoming_streams.as_mut() {
+                    Some(rx) => rx.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                match stream {
+                    Some(stream) if state.contacts.is_blocked(&stream.peer()) => {
+                        tracing::debug!(peer = %stream.peer(), "stream from blocked peer dropped");
+                    }
+                    Some(stream) => crate::transfer::spawn_receive(
+                        stream,
+                        state.config.download_dir.clone(),
+                        receive_budget.clone(),
+                        transfer_tx.clone(),
+                    ),
+                    None => *incoming_streams = None,
+                }
+                Vec::new()
+            }
+            Some(event) = transfer_rx.recv() => vec![RuntimeEffect::Emit(event)],
+
+            // ── 3. Path events from transport ───────────────────
+            Ok(event) = path_rx.recv() => state.handle_path_event(event),
+
             // ── 3b. Relay PeerPresent: auto-discovery ──────────
             event = async {
                 match peer_present_rx.as_mut() {
@@ -220,26 +726,70 @@ pub(super) async fn runtime_loop(
                 }
             }
 
+            // ── 3c. Request deadlines ────────────────────────────
+            _ = async {
+                match requests.next_deadline() {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                requests.expire(tokio::time::Instant::now());
+                Vec::new()
+            }
+
+            // ── 3d. Bootstrap stage timeouts ─────────────────────
+            _ = bootstrap_tick.tick(), if bootstrap.is_running() => {
+                match bootstrap.tick(now_ms()) {
+                    Some(step) => {
+                        run_bootstrap_step(
+                            step,
+                            bootstrap,
+                            &state.config.bootstrap,
+                            node,
+                            gossip_bootstrap_peers,
+                            gossip_sender.as_ref(),
+                            dht_handle.as_ref(),
+                            cmd_tx,
+                        )
+                        .await
+                    }
+                    None => Vec::new(),
+                }
+            }
+
             // ── 4. Timer: cache cleanup ─────────────────────────
-            _ = cache_cleanup.tick() => state.tick_cache_cleanup(),
+            _ = cache_cleanup.tick() => on_all_states(state, hosted, |s| s.tick_cache_cleanup()),
 
             // ── 5. Timer: tracker eviction ──────────────────────
-            _ = tracker_cleanup.tick() => state.tick_tracker_cleanup(),
+            _ = tracker_cleanup.tick() => on_all_states(state, hosted, |s| s.tick_tracker_cleanup()),
 
             // ── 6. Timer: heartbeat liveness check ──────────────
-            _ = heartbeat_check.tick() => state.tick_heartbeat(),
+            _ = heartbeat_check.tick() => {
+                let online = !node.connected_peers().await.is_empty();
+                on_all_states(state, hosted, |s| {
+                    let mut effects = s.set_connectivity(online);
+                    effects.extend(s.tick_heartbeat());
+                    effects
+                })
+            }
 
             // ── 7. Timer: group hub heartbeat ───────────────────
-            _ = group_hub_heartbeat.tick() => state.tick_group_hub_heartbeat(),
+            _ = group_hub_heartbeat.tick() => on_all_states(state, hosted, |s| s.tick_group_hub_heartbeat()),
 
             // ── 7b. Timer: shadow ping watchdog ──────────────────
-            _ = shadow_ping.tick() => state.tick_shadow_ping(),
+            _ = shadow_ping.tick() => on_all_states(state, hosted, |s| s.tick_shadow_ping()),
 
             // ── 7c. Timer: hub message cleanup (24h TTL) ─────────
-            _ = hub_cleanup.tick() => state.tick_hub_cleanup(),
+            _ = hub_cleanup.tick() => on_all_states(state, hosted, |s| s.tick_hub_cleanup()),
 
             // ── 8. Timer: backup maintenance ────────────────────
-            _ = backup_tick.tick() => state.tick_backup(),
+            _ = backup_tick.tick() => on_all_states(state, hosted, |s| s.tick_backup()),
+
+            // ── 8b. Timer: paced backup delivery ────────────────
+            _ = backup_delivery.tick() => on_all_states(state, hosted, |s| s.tick_backup_delivery()),
+
+            // ── 8c. Timer: backup integrity + compaction ────────
+            _ = backup_integrity.tick() => on_all_states(state, hosted, |s| s.tick_backup_integrity()),
 
             // ── 9. Gossip events ────────────────────────────────
             event = async {
@@ -251,15 +801,30 @@ pub(super) async fn runtime_loop(
                 if let Some(Ok(event)) = event {
                     match event {
                         GossipEvent::Received(msg) => {
-                            state.handle_gossip_event(
-                                GossipInput::PeerAnnounce(msg.content.to_vec())
-                            )
+                            on_all_states(state, hosted, |s| {
+                                s.handle_gossip_event(GossipInput::PeerAnnounce(msg.content.to_vec()))
+                            })
                         }
                         GossipEvent::NeighborUp(endpoint_id) => {
                             let node_id = NodeId::from_endpoint_id(endpoint_id);
-                            let effects = state.handle_gossip_event(
-                                GossipInput::NeighborUp(node_id)
-                            );
+                            let mut effects = on_all_states(state, hosted, |s| {
+                                s.handle_gossip_event(GossipInput::NeighborUp(node_id))
+                            });
+                            if let Some(step) = bootstrap.on_peer(node_id) {
+                                effects.extend(
+                                    run_bootstrap_step(
+                                        step,
+                                        bootstrap,
+                                        &state.config.bootstrap,
+                                        node,
+                                        gossip_bootstrap_peers,
+                                        gossip_sender.as_ref(),
+                                        dht_handle.as_ref(),
+                                        cmd_tx,
+                                    )
+                                    .await,
+                                );
+                            }
                             // Re-broadcast announce on NeighborUp
                             // (key learning from PoC-3: initial broadcast has no neighbors)
                             if let Some(ref sender) = gossip_sender {
@@ -271,7 +836,9 @@ pub(super) async fn runtime_loop(
                         }
                         GossipEvent::NeighborDown(endpoint_id) => {
                             let node_id = NodeId::from_endpoint_id(endpoint_id);
-                            state.handle_gossip_event(GossipInput::NeighborDown(node_id))
+                            on_all_states(state, hosted, |s| {
+                                s.handle_gossip_event(GossipInput::NeighborDown(node_id))
+                            })
                         }
                         GossipEvent::Lagged => {
                             tracing::warn!("gossip: receiver lagged, missed events");
@@ -283,6 +850,11 @@ pub(super) async fn runtime_loop(
                 }
             }
 
+            // ── 9b. Pub/sub channel publications ────────────────
+            Some((topic, bytes)) = channel_rx.recv() => {
+                state.handle_channel_publication(topic, &bytes)
+            }
+
             // ── 10. Timer: subnet evaluation ────────────────────
             _ = subnet_eval.tick() => state.tick_subnets(),
 
@@ -304,50 +876,331 @@ pub(super) async fn runtime_loop(
             // ── 13. Timer: state persistence + metrics update ──
             _ = state_save.tick() => {
                 state.save_state();
+                hosted.iter().for_each(RuntimeState::save_state);
                 metrics.set_groups_count(state.group_manager.group_count() as u64);
+                metrics.set_hub_groups(state.group_hub.group_count() as u64);
                 metrics.set_peers_known(state.topology.len() as u64);
+                metrics.set_connected_peers(node.connected_peers().await.len() as u64);
+                metrics.set_gossip_neighbors(
+                    gossip_receiver.as_ref().map_or(0, |r| r.neighbors().count()) as u64,
+                );
+                metrics.set_messages_tracked(state.tracker.len() as u64);
+                metrics.set_backup_stats(&state.backup.stats());
+                metrics.set_envelopes_blocked(state.router.blocked_drops());
+                metrics.set_relays_refused(state.router.relays_refused());
                 Vec::new()
             }
 
             // ── 14. Timer: DHT re-publish (30 min) ───────────
             _ = dht_republish.tick() => {
-                let (relay_urls, direct_addrs) = extract_node_addrs(&node);
+                let (relay_urls, direct_addrs) = extract_node_addrs(node);
                 state.publish_to_dht(&secret_seed, relay_urls, direct_addrs).await;
                 Vec::new()
             }
 
-            // ── 15. Timer: delivery deadline check (5s) ────
-            _ = delivery_deadline.tick() => state.tick_delivery_deadlines(),
+            // ── 15. Timer: deadlines, scheduled releases, expiry (5s) ──────
+            _ = delivery_deadline.tick() => on_all_states(state, hosted, |s| {
+                let mut effects = s.tick_delivery_deadlines();
+                effects.extend(s.tick_exactly_once());
+                effects.extend(s.tick_reorder());
+                effects.extend(s.tick_scheduled_messages());
+                effects.extend(s.tick_ephemeral_messages());
+                effects
+            }),
+
+            // ── 16. Bulk sends still queued behind control traffic ──
+            // (yield so transport and timers run between drain rounds)
+            _ = tokio::task::yield_now(), if scheduler.pending() > 0 => Vec::new(),
 
             else => break,
         };
+        let effects = deliver_locally(state, hosted, effects);
 
-        // Intercept BroadcastRoleChange effects (need gossip sender)
+        // Intercept gossip effects (role announces, pub/sub channels)
         let mut regular_effects = Vec::with_capacity(effects.len());
         for effect in effects {
-            if let RuntimeEffect::BroadcastRoleChange(ref announce) = effect {
-                if let Some(ref sender) = gossip_sender {
-                    if let Ok(bytes) = rmp_serde::to_vec(announce) {
+            match effect {
+                RuntimeEffect::BroadcastRoleChange(ref announce) => {
+                    if let Some(ref sender) = gossip_sender {
+                        if let Ok(bytes) = rmp_serde::to_vec(announce) {
+                            if let Err(e) = sender.broadcast(bytes::Bytes::from(bytes)).await {
+                                tracing::debug!("gossip: role announce broadcast failed: {e}");
+                            }
+                        }
+                    }
+                }
+                RuntimeEffect::BroadcastAbuseReport(ref report) => {
+                    if let Some(ref sender) = gossip_sender {
+                        if let Ok(bytes) = rmp_serde::to_vec(report) {
+                            if let Err(e) = sender.broadcast(bytes::Bytes::from(bytes)).await {
+                                tracing::debug!("gossip: abuse report broadcast failed: {e}");
+                            }
+                        }
+                    }
+                }
+                RuntimeEffect::BroadcastRelayAttestation(ref attestation) => {
+                    if let Some(ref sender) = gossip_sender {
+                        if let Ok(bytes) = rmp_serde::to_vec(attestation) {
+                            if let Err(e) = sender.broadcast(bytes::Bytes::from(bytes)).await {
+                                tracing::debug!("gossip: relay attestation broadcast failed: {e}");
+                            }
+                        }
+                    }
+                }
+                RuntimeEffect::BroadcastAnnounce(bytes) => {
+                    if let Some(ref sender) = gossip_sender {
                         if let Err(e) = sender.broadcast(bytes::Bytes::from(bytes)).await {
-                            tracing::debug!("gossip: role announce broadcast failed: {e}");
+                            tracing::debug!("gossip: announce broadcast failed: {e}");
+                        }
+                    }
+                }
+                RuntimeEffect::SubscribeChannel { topic } => {
+                    if channel_subs.contains_key(&topic) {
+                        continue;
+                    }
+                    let bootstrap: Vec<tom_connect::EndpointId> = state
+                        .gossip_entry_points()
+                        .iter()
+                        .map(|n| *n.as_endpoint_id())
+                        .chain(gossip_bootstrap_peers.iter().map(|n| *n.as_endpoint_id()))
+                        .collect();
+                    match gossip
+                        .subscribe(tom_gossip::TopicId::from_bytes(topic), bootstrap)
+                        .await
+                    {
+                        Ok(sub) => {
+                            let (sender, receiver) = sub.split();
+                            let forwarder =
+                                tokio::spawn(forward_channel(topic, receiver, channel_tx.clone()));
+                            channel_subs.insert(topic, ChannelSubscription { sender, forwarder });
                         }
+                        Err(e) => regular_effects.push(RuntimeEffect::Emit(ProtocolEvent::Error {
+                            description: format!("channel subscribe failed: {e}"),
+                        })),
+                    }
+                }
+                RuntimeEffect::UnsubscribeChannel { topic } => {
+                    // Dropping both halves leaves the gossip topic.
+                    if let Some(sub) = channel_subs.remove(&topic) {
+                        sub.forwarder.abort();
+                    }
+                }
+                RuntimeEffect::CompleteRequest(response) => requests.complete(response),
+                RuntimeEffect::PublishChannel { topic, bytes } => {
+                    let Some(sub) = channel_subs.get(&topic) else {
+                        continue;
+                    };
+                    if let Err(e) = sub.sender.broadcast(bytes::Bytes::from(bytes)).await {
+                        tracing::debug!("gossip: channel publish failed: {e}");
                     }
                 }
-            } else {
-                regular_effects.push(effect);
+                other => regular_effects.push(other),
             }
         }
 
-        // Execute remaining effects
-        execute_effects(regular_effects, &node, &msg_tx, &status_tx, &event_tx, &metrics).await;
+        // Execute remaining effects, control traffic first
+        let regular_effects = state.apply_hooks(regular_effects);
+        let batch = scheduler.schedule(regular_effects);
+        let undelivered = execute_effects(batch, &*node, msg_tx, status_tx, event_tx, metrics).await;
+        for msg in undelivered {
+            match hosted.iter_mut().find(|h| h.local_id == msg.to) {
+                Some(h) => h.release_exactly_once(msg.from, &msg.envelope_id),
+                None => state.release_exactly_once(msg.from, &msg.envelope_id),
+            }
+        }
+
+        if shutdown_reply.is_some() {
+            break;
+        }
     }
 
+    // ── Drain (requested shutdown only) ─────────────────────────────
+    let report = match shutdown_reply {
+        Some(_) => {
+            // Stop accepting commands; anything still queued is dropped.
+            cmd_rx.close();
+            control_rx.close();
+            let deadline = tokio::time::Instant::now() + state.config.shutdown_drain_timeout;
+
+            // Flush queued sends and the outbox and hand off backups,
+            // within the deadline
+            let (mut effects, mut report) = state.prepare_shutdown();
+            for h in hosted.iter_mut() {
+                let (hosted_effects, hosted_report) = h.prepare_shutdown();
+                effects.extend(tag_hosted(h.local_id, hosted_effects));
+                report.unsent_messages += hosted_report.unsent_messages;
+                report.backups_handed_off += hosted_report.backups_handed_off;
+                report.backups_kept += hosted_report.backups_kept;
+            }
+            let effects = deliver_locally(state, hosted, effects);
+            let effects = state.apply_hooks(effects);
+            // Queued sends already went through the hooks
+            let mut queued = scheduler.drain();
+            queued.extend(effects);
+            let drained = tokio::time::timeout_at(
+                deadline,
+                execute_effects(queued, &*node, msg_tx, status_tx, event_tx, metrics),
+            )
+            .await;
+            report.timed_out = drained.is_err();
+
+            // Last word on gossip, then leave (dropping both halves leaves the topic)
+            if let Some(ref sender) = gossip_sender {
+                let departure = bytes::Bytes::from(state.build_departure_announce());
+                report.departure_announced =
+                    matches!(tokio::time::timeout_at(deadline, sender.broadcast(departure)).await, Ok(Ok(())));
+                for h in hosted.iter() {
+                    let departure = bytes::Bytes::from(h.build_departure_announce());
+                    let _ = tokio::time::timeout_at(deadline, sender.broadcast(departure)).await;
+                }
+            }
+            for (_, sub) in channel_subs.drain() {
+                sub.forwarder.abort();
+            }
+            drop(gossip_sender);
+            drop(gossip_receiver);
+
+            tracing::info!(?report, "shutdown drained");
+            Some(report)
+        }
+        None => None,
+    };
+
     // Save state before shutdown
     state.save_state();
+    hosted.iter().for_each(RuntimeState::save_state);
 
-    // Graceful shutdown
-    if let Err(e) = node.shutdown().await {
-        tracing::warn!("runtime shutdown error: {e}");
+    shutdown_reply.zip(report)
+}
+
+/// Run `f` on our state, then on each hosted identity's (tagging theirs).
+fn on_all_states(
+    state: &mut RuntimeState,
+    hosted: &mut [RuntimeState],
+    mut f: impl FnMut(&mut RuntimeState) -> Vec<RuntimeEffect>,
+) -> Vec<RuntimeEffect> {
+    let mut effects = f(state);
+    for h in hosted.iter_mut() {
+        let identity = h.local_id;
+        effects.extend(tag_hosted(identity, f(h)));
+    }
+    effects
+}
+
+/// Effects of a hosted identity's state: its events are wrapped in
+/// `ProtocolEvent::Hosted`. Channel subscriptions and request completions
+/// belong to the node's own identity and are dropped.
+fn tag_hosted(identity: NodeId, effects: Vec<RuntimeEffect>) -> Vec<RuntimeEffect> {
+    effects
+        .into_iter()
+        .filter_map(|effect| match effect {
+            RuntimeEffect::Emit(event) => Some(RuntimeEffect::Emit(ProtocolEvent::Hosted {
+                identity,
+                event: Box::new(event),
+            })),
+            RuntimeEffect::SendWithBackupFallback {
+                envelope,
+                on_success,
+                on_failure,
+            } => Some(RuntimeEffect::SendWithBackupFallback {
+                envelope,
+                on_success: tag_hosted(identity, on_success),
+                on_failure: tag_hosted(identity, on_failure),
+            }),
+            RuntimeEffect::SubscribeChannel { .. }
+            | RuntimeEffect::UnsubscribeChannel { .. }
+            | RuntimeEffect::CompleteRequest(_) => {
+                tracing::debug!(%identity, "hosted identity: node-only effect dropped");
+                None
+            }
+            other => Some(other),
+        })
+        .collect()
+}
+
+/// The hosted identity's state an incoming envelope is addressed to, if any.
+fn hosted_recipient<'a>(hosted: &'a mut [RuntimeState], data: &[u8]) -> Option<&'a mut RuntimeState> {
+    if hosted.is_empty() {
+        return None;
+    }
+    let to = Envelope::recipient(data).ok()?;
+    hosted.iter_mut().find(|h| h.local_id == to)
+}
+
+/// Envelopes between identities of this runtime never reach the network:
+/// each goes straight to its recipient's state (a fallback send counts as
+/// sent). Returns the effects left to execute.
+fn deliver_locally(
+    state: &mut RuntimeState,
+    hosted: &mut [RuntimeState],
+    effects: Vec<RuntimeEffect>,
+) -> Vec<RuntimeEffect> {
+    if hosted.is_empty() {
+        return effects;
+    }
+    let local_ids: Vec<NodeId> = std::iter::once(state.local_id)
+        .chain(hosted.iter().map(|h| h.local_id))
+        .collect();
+    let is_local = |envelope: &Envelope| envelope.from != envelope.to && local_ids.contains(&envelope.to);
+
+    let mut remaining = Vec::new();
+    let mut pending = effects;
+    for _ in 0..MAX_LOCAL_ROUNDS {
+        if pending.is_empty() {
+            break;
+        }
+        let mut next = Vec::new();
+        for effect in pending {
+            let (envelope, on_success) = match effect {
+                RuntimeEffect::SendEnvelope(envelope) | RuntimeEffect::SendEnvelopeTo { envelope, .. }
+                    if is_local(&envelope) =>
+                {
+                    (envelope, Vec::new())
+                }
+                RuntimeEffect::SendWithBackupFallback {
+                    envelope,
+                    on_success,
+                    ..
+                } if is_local(&envelope) => (envelope, on_success),
+                other => {
+                    remaining.push(other);
+                    continue;
+                }
+            };
+            next.extend(on_success);
+            let Ok(bytes) = envelope.to_bytes() else {
+                continue;
+            };
+            next.extend(match hosted.iter_mut().find(|h| h.local_id == envelope.to) {
+                Some(h) => tag_hosted(h.local_id, h.handle_incoming(&bytes)),
+                None => state.handle_incoming(&bytes),
+            });
+        }
+        pending = next;
+    }
+    remaining.extend(pending);
+    remaining
+}
+
+/// Forward publications from one channel subscription into the loop's inbox.
+async fn forward_channel(
+    topic: [u8; 32],
+    mut receiver: GossipReceiver,
+    inbox: mpsc::Sender<([u8; 32], Vec<u8>)>,
+) {
+    while let Some(event) = receiver.next().await {
+        match event {
+            Ok(GossipEvent::Received(msg)) => {
+                if inbox.send((topic, msg.content.to_vec())).await.is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::debug!("gossip: channel receiver closed: {e}");
+                break;
+            }
+        }
     }
 }
 
@@ -373,6 +1226,128 @@ fn extract_node_addrs(node: &TomNode) -> (Vec<String>, Vec<String>) {
     (relay_urls, direct_addrs)
 }
 
+/// Next application command: control commands always, bulk-producing ones
+/// only while the scheduler isn't backlogged. Control goes first.
+async fn next_command(
+    control_rx: &mut mpsc::Receiver<RuntimeCommand>,
+    cmd_rx: &mut mpsc::Receiver<RuntimeCommand>,
+    backlogged: bool,
+) -> Option<RuntimeCommand> {
+    if backlogged {
+        return control_rx.recv().await;
+    }
+    tokio::select! {
+        biased;
+        Some(cmd) = control_rx.recv() => Some(cmd),
+        Some(cmd) = cmd_rx.recv() => Some(cmd),
+        else => None,
+    }
+}
+
+/// Resolve `node_id` through the DHT in the background; a hit comes back
+/// as `DhtLookupResult`.
+fn spawn_dht_lookup(dht: &tom_dht::AsyncDht, node_id: NodeId, cmd_tx: &mpsc::Sender<RuntimeCommand>) {
+    let dht = dht.clone();
+    let pk = node_id.as_bytes();
+    let tx = cmd_tx.clone();
+    tokio::spawn(async move {
+        match tom_dht::dht_lookup(&dht, &pk).await {
+            Ok(Some(addr)) => {
+                let _ = tx.send(RuntimeCommand::DhtLookupResult { addr }).await;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::debug!("DHT lookup failed: {e}");
+            }
+        }
+    });
+}
+
+/// Carry out a bootstrap step: start a stage's discovery, or report the
+/// outcome. Addresses found go through `AddPeerAddr` like any other.
+#[allow(clippy::too_many_arguments)]
+async fn run_bootstrap_step(
+    step: BootstrapStep,
+    pipeline: &mut BootstrapPipeline,
+    config: &BootstrapConfig,
+    node: &TomNode,
+    gossip_bootstrap_peers: &[NodeId],
+    gossip_sender: Option<&GossipSender>,
+    dht: Option<&tom_dht::AsyncDht>,
+    cmd_tx: &mpsc::Sender<RuntimeCommand>,
+) -> Vec<RuntimeEffect> {
+    let source = match step {
+        BootstrapStep::Enter(source) => source,
+        BootstrapStep::Completed { source, peers_found } => {
+            tracing::info!(?source, peers_found, "bootstrap completed");
+            return vec![RuntimeEffect::Emit(ProtocolEvent::BootstrapCompleted {
+                peers_found,
+                source,
+            })];
+        }
+    };
+    tracing::debug!(?source, "bootstrap: entering stage");
+    match source {
+        BootstrapSource::Static => {
+            pipeline.expect(config.static_peers.iter().map(|a| NodeId::from_endpoint_id(a.id)));
+            for addr in &config.static_peers {
+                if cmd_tx
+                    .try_send(RuntimeCommand::AddPeerAddr { addr: addr.clone() })
+                    .is_err()
+                {
+                    tracing::warn!("bootstrap: command queue full, static peer skipped");
+                }
+            }
+        }
+        BootstrapSource::LocalNetwork => {
+            let (_, direct_addrs) = extract_node_addrs(node);
+            let beacon = LanBeacon {
+                node_id: node.id(),
+                direct_addrs: direct_addrs.iter().filter_map(|a| a.parse().ok()).collect(),
+            };
+            let (found_tx, mut found_rx) = mpsc::channel(16);
+            tokio::spawn(run_lan_discovery(
+                beacon,
+                config.local_network_port,
+                config.local_network_timeout,
+                found_tx,
+            ));
+            let tx = cmd_tx.clone();
+            tokio::spawn(async move {
+                while let Some(addr) = found_rx.recv().await {
+                    if tx.send(RuntimeCommand::AddPeerAddr { addr }).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        BootstrapSource::Dht => {
+            if let Some(dht) = dht {
+                pipeline.expect(gossip_bootstrap_peers.iter().copied());
+                for node_id in gossip_bootstrap_peers {
+                    spawn_dht_lookup(dht, *node_id, cmd_tx);
+                }
+            }
+        }
+        BootstrapSource::Gossip => {
+            if let Some(sender) = gossip_sender {
+                let peers: Vec<_> =
+                    gossip_bootstrap_peers.iter().map(|n| *n.as_endpoint_id()).collect();
+                if !peers.is_empty() {
+                    let _ = sender.join_peers(peers).await;
+                }
+            }
+        }
+    }
+    Vec::new()
+}
+
+/// Our pairing info from the node's current addresses (first relay only).
+fn local_pairing_info(node: &TomNode, local_id: NodeId) -> crate::pairing::PairingInfo {
+    let (relay_urls, direct_addrs) = extract_node_addrs(node);
+    crate::pairing::PairingInfo::new(local_id, relay_urls.into_iter().next(), direct_addrs)
+}
+
 /// Convert a DHT node address to an EndpointAddr for transport injection.
 fn dht_addr_to_endpoint_addr(addr: &tom_dht::DhtNodeAddr) -> Option<tom_connect::EndpointAddr> {
     let node_id: NodeId = addr.node_id.parse().ok()?;
@@ -394,3 +1369,54 @@ fn dht_addr_to_endpoint_addr(addr: &tom_dht::DhtNodeAddr) -> Option<tom_connect:
         addrs,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn restart_backoff_doubles_up_to_the_cap() {
+        let base = Duration::from_secs(1);
+        assert_eq!(restart_backoff(base, 1), Duration::from_secs(1));
+        assert_eq!(restart_backoff(base, 3), Duration::from_secs(4));
+        assert_eq!(restart_backoff(base, 40), MAX_RESTART_BACKOFF);
+    }
+
+    #[test]
+    fn panic_messages_are_recovered() {
+        let panic = std::panic::catch_unwind(|| panic!("bad state {}", 7)).unwrap_err();
+        assert_eq!(panic_message(panic.as_ref()), "bad state 7");
+        let panic = std::panic::catch_unwind(|| std::panic::panic_any(7u8)).unwrap_err();
+        assert_eq!(panic_message(panic.as_ref()), "unknown panic");
+    }
+
+    #[tokio::test]
+    async fn backlog_holds_bulk_commands_but_not_control() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(4);
+        let (control_tx, mut control_rx) = mpsc::channel(4);
+        let to: NodeId = tom_connect::SecretKey::from_bytes(&[1u8; 32]).public().to_string().parse().unwrap();
+        let send = RuntimeCommand::SendMessage { to, payload: b"bulk".to_vec(), reply: None };
+        assert!(send.is_bulk());
+        cmd_tx.send(send).await.unwrap();
+
+        let (reply, _) = oneshot::channel();
+        let shutdown = RuntimeCommand::Shutdown { reply };
+        assert!(!shutdown.is_bulk());
+        control_tx.send(shutdown).await.unwrap();
+
+        // Backlogged: the shutdown gets through, the send waits
+        let cmd = next_command(&mut control_rx, &mut cmd_rx, true).await;
+        assert!(matches!(cmd, Some(RuntimeCommand::Shutdown { .. })));
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(50),
+            next_command(&mut control_rx, &mut cmd_rx, true),
+        )
+        .await;
+        assert!(waiting.is_err(), "bulk command taken while backlogged");
+
+        // Drained: it's taken again
+        let cmd = next_command(&mut control_rx, &mut cmd_rx, false).await;
+        assert!(matches!(cmd, Some(RuntimeCommand::SendMessage { .. })));
+    }
+}
@@ -1,7 +1,10 @@
 /// Protocol runtime metrics — lightweight counters and gauges.
 ///
 /// All fields are atomic — safe to read from any thread without locking.
-/// Updated by the runtime loop; read by the application via RuntimeHandle.
+/// Updated by the runtime loop; read by the application via RuntimeHandle,
+/// or scraped in OpenMetrics text format from the optional HTTP exporter
+/// (`RuntimeConfig::metrics_addr`).
+use std::fmt::Write as _;
 use std::sync::Arc;
 use tom_metrics::{Counter, Gauge};
 
@@ -12,11 +15,62 @@ pub struct MetricsSnapshot {
     pub messages_received: u64,
     pub messages_failed: u64,
     pub messages_dropped: u64,  // Messages lost due to full buffer
+    pub envelopes_blocked: u64, // Envelopes from blocked senders dropped
+    pub relays_refused: u64,    // Forwards refused by the relay policy
     pub groups_count: u64,
+    pub hub_groups: u64,        // Groups this node hosts as hub
     pub peers_known: u64,
+    pub connected_peers: u64,   // Live transport connections
+    pub gossip_neighbors: u64,  // Direct neighbors on the discovery topic
+    pub messages_tracked: u64,  // Sent messages still tracked for status
+    pub backup_entries: u64,
+    pub backup_bytes: u64,
+    pub backup_under_replicated: u64, // Held with fewer holders than wanted
+    pub backup_queued_deliveries: u64,
     pub uptime_seconds: u64,
 }
 
+impl MetricsSnapshot {
+    /// Render in OpenMetrics text format (also accepted by Prometheus).
+    pub fn to_openmetrics(&self) -> String {
+        let counters = [
+            ("messages_sent", "Envelopes sent", self.messages_sent),
+            ("messages_received", "Envelopes received", self.messages_received),
+            ("messages_failed", "Sends that failed", self.messages_failed),
+            ("messages_dropped", "Messages lost to a full application buffer", self.messages_dropped),
+            ("envelopes_blocked", "Envelopes from blocked senders dropped", self.envelopes_blocked),
+            ("relays_refused", "Forwards refused by the relay policy", self.relays_refused),
+        ];
+        let gauges = [
+            ("groups", "Groups joined", self.groups_count),
+            ("hub_groups", "Groups hosted as hub", self.hub_groups),
+            ("peers_known", "Peers in the topology", self.peers_known),
+            ("connected_peers", "Live transport connections", self.connected_peers),
+            ("gossip_neighbors", "Direct gossip neighbors", self.gossip_neighbors),
+            ("messages_tracked", "Sent messages tracked for status", self.messages_tracked),
+            ("backup_entries", "Backup entries held", self.backup_entries),
+            ("backup_bytes", "Backup payload bytes held", self.backup_bytes),
+            ("backup_under_replicated", "Backup entries with too few holders", self.backup_under_replicated),
+            ("backup_queued_deliveries", "Held messages queued for delivery", self.backup_queued_deliveries),
+            ("uptime_seconds", "Seconds since the runtime started", self.uptime_seconds),
+        ];
+
+        let mut out = String::new();
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# TYPE tom_{name} counter");
+            let _ = writeln!(out, "# HELP tom_{name} {help}.");
+            let _ = writeln!(out, "tom_{name}_total {value}");
+        }
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# TYPE tom_{name} gauge");
+            let _ = writeln!(out, "# HELP tom_{name} {help}.");
+            let _ = writeln!(out, "tom_{name} {value}");
+        }
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
 /// Shared, clonable metrics handle.
 ///
 /// Internally uses `Arc` so the runtime and app can both hold references.
@@ -30,9 +84,19 @@ struct Inner {
     messages_received: Counter,
     messages_failed: Counter,
     messages_dropped: Counter,
+    envelopes_blocked: Gauge,
+    relays_refused: Gauge,
     groups_count: Gauge,
+    hub_groups: Gauge,
     peers_known: Gauge,
-    start_time: std::time::Instant,
+    connected_peers: Gauge,
+    gossip_neighbors: Gauge,
+    messages_tracked: Gauge,
+    backup_entries: Gauge,
+    backup_bytes: Gauge,
+    backup_under_replicated: Gauge,
+    backup_queued_deliveries: Gauge,
+    start_time: crate::time::Instant,
 }
 
 impl ProtocolMetrics {
@@ -43,9 +107,19 @@ impl ProtocolMetrics {
                 messages_received: Counter::new(),
                 messages_failed: Counter::new(),
                 messages_dropped: Counter::new(),
+                envelopes_blocked: Gauge::new(),
+                relays_refused: Gauge::new(),
                 groups_count: Gauge::new(),
+                hub_groups: Gauge::new(),
                 peers_known: Gauge::new(),
-                start_time: std::time::Instant::now(),
+                connected_peers: Gauge::new(),
+                gossip_neighbors: Gauge::new(),
+                messages_tracked: Gauge::new(),
+                backup_entries: Gauge::new(),
+                backup_bytes: Gauge::new(),
+                backup_under_replicated: Gauge::new(),
+                backup_queued_deliveries: Gauge::new(),
+                start_time: crate::time::Instant::now(),
             }),
         }
     }
@@ -68,14 +142,52 @@ impl ProtocolMetrics {
         self.inner.messages_dropped.inc();
     }
 
+    /// Mirror of the router's blocked-drop counter (set by the runtime).
+    pub fn set_envelopes_blocked(&self, n: u64) {
+        self.inner.envelopes_blocked.set(n);
+    }
+
+    /// Mirror of the router's relay-refusal counter (set by the runtime).
+    pub fn set_relays_refused(&self, n: u64) {
+        self.inner.relays_refused.set(n);
+    }
+
     pub fn set_groups_count(&self, n: u64) {
         self.inner.groups_count.set(n);
     }
 
+    pub fn set_hub_groups(&self, n: u64) {
+        self.inner.hub_groups.set(n);
+    }
+
     pub fn set_peers_known(&self, n: u64) {
         self.inner.peers_known.set(n);
     }
 
+    pub fn set_connected_peers(&self, n: u64) {
+        self.inner.connected_peers.set(n);
+    }
+
+    pub fn set_gossip_neighbors(&self, n: u64) {
+        self.inner.gossip_neighbors.set(n);
+    }
+
+    pub fn set_messages_tracked(&self, n: u64) {
+        self.inner.messages_tracked.set(n);
+    }
+
+    /// Mirror of the backup coordinator's stats (set by the runtime).
+    pub fn set_backup_stats(&self, stats: &crate::backup::BackupStats) {
+        self.inner.backup_entries.set(stats.entries as u64);
+        self.inner.backup_bytes.set(stats.bytes);
+        self.inner
+            .backup_under_replicated
+            .set((stats.under_replicated + stats.unreplicated) as u64);
+        self.inner
+            .backup_queued_deliveries
+            .set(stats.queued_deliveries as u64);
+    }
+
     // ── Read method (called by app via RuntimeHandle) ────────────────
 
     /// Take a consistent snapshot of all metrics.
@@ -85,13 +197,64 @@ impl ProtocolMetrics {
             messages_received: self.inner.messages_received.get(),
             messages_failed: self.inner.messages_failed.get(),
             messages_dropped: self.inner.messages_dropped.get(),
+            envelopes_blocked: self.inner.envelopes_blocked.get(),
+            relays_refused: self.inner.relays_refused.get(),
             groups_count: self.inner.groups_count.get(),
+            hub_groups: self.inner.hub_groups.get(),
             peers_known: self.inner.peers_known.get(),
+            connected_peers: self.inner.connected_peers.get(),
+            gossip_neighbors: self.inner.gossip_neighbors.get(),
+            messages_tracked: self.inner.messages_tracked.get(),
+            backup_entries: self.inner.backup_entries.get(),
+            backup_bytes: self.inner.backup_bytes.get(),
+            backup_under_replicated: self.inner.backup_under_replicated.get(),
+            backup_queued_deliveries: self.inner.backup_queued_deliveries.get(),
             uptime_seconds: self.inner.start_time.elapsed().as_secs(),
         }
     }
 }
 
+/// Content type of the exporter's responses.
+#[cfg(feature = "native")]
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Serve `GET /metrics` from `listener` until the task is aborted.
+#[cfg(feature = "native")]
+pub(crate) async fn serve_metrics(listener: tokio::net::TcpListener, metrics: ProtocolMetrics) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    loop {
+        let mut socket = match listener.accept().await {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                tracing::debug!("metrics exporter: accept failed: {e}");
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let read = tokio::time::timeout(std::time::Duration::from_secs(2), socket.read(&mut buf)).await;
+            let Ok(Ok(n)) = read else {
+                return;
+            };
+            let request = String::from_utf8_lossy(&buf[..n]);
+            let response = if request.starts_with("GET /metrics ") {
+                let body = metrics.snapshot().to_openmetrics();
+                format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: {OPENMETRICS_CONTENT_TYPE}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string()
+            };
+            let _ = socket.write_all(response.as_bytes()).await;
+            let _ = socket.shutdown().await;
+        });
+    }
+}
+
 impl Default for ProtocolMetrics {
     fn default() -> Self {
         Self::new()
@@ -137,6 +300,45 @@ mod tests {
         assert_eq!(m2.snapshot().messages_sent, 1);
     }
 
+    #[test]
+    fn openmetrics_rendering() {
+        let m = ProtocolMetrics::new();
+        m.inc_messages_sent();
+        m.set_gossip_neighbors(4);
+        let text = m.snapshot().to_openmetrics();
+        assert!(text.contains("# TYPE tom_messages_sent counter\n"));
+        assert!(text.contains("\ntom_messages_sent_total 1\n"));
+        assert!(text.contains("\ntom_gossip_neighbors 4\n"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[tokio::test]
+    async fn exporter_serves_metrics_over_http() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let m = ProtocolMetrics::new();
+        m.inc_messages_received();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve_metrics(listener, m));
+
+        let fetch = |path: &'static str| async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(format!("GET {path} HTTP/1.1\r\nhost: x\r\n\r\n").as_bytes())
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let response = fetch("/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("tom_messages_received_total 1"));
+        assert!(fetch("/other").await.starts_with("HTTP/1.1 404"));
+        server.abort();
+    }
+
     #[test]
     fn metrics_snapshot_serializes() {
         let m = ProtocolMetrics::new();
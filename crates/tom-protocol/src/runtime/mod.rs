@@ -3,41 +3,62 @@
 /// The runtime owns a `TomNode` (transport) and all protocol state (router,
 /// topology, tracker, heartbeat). It exposes a channel-based API so the
 /// application (TUI, bot, SDK) never touches raw bytes or protocol internals.
+mod admin;
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+mod channel;
 mod effect;
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+mod events;
+#[cfg(feature = "native")]
 mod executor;
+mod hooks;
+#[cfg(feature = "native")]
 mod r#loop;
 pub mod metrics;
+#[cfg(feature = "native")]
+mod scheduler;
 mod state;
+#[cfg(feature = "native")]
 mod transport;
 
+#[cfg(all(unix, feature = "native"))]
+pub use admin::admin_request;
+pub use admin::{AdminRequest, AdminResponse};
+pub use channel::{ChannelPolicy, OverflowPolicy};
 pub use effect::RuntimeEffect;
+pub use events::{EventCategory, EventFilter, SUBSCRIBER_BUFFER};
+pub use hooks::{HookAction, RuntimeHook};
 pub use metrics::{MetricsSnapshot, ProtocolMetrics};
 pub use state::{GossipInput, RuntimeState};
+#[cfg(feature = "native")]
 pub use transport::Transport;
 
 use std::path::PathBuf;
 use std::time::Duration;
 
+use serde::Serialize;
 use tokio::sync::{mpsc, oneshot};
-use tom_connect::EndpointAddr;
-use tom_transport::{PathEvent, TomNode};
+use tom_base::EndpointAddr;
+#[cfg(feature = "native")]
+use tom_transport::TomNode;
 
 use crate::discovery::DiscoverySource;
-use crate::group::{GroupId, GroupInfo, GroupInvite, GroupMember, GroupMessage, LeaveReason};
+use crate::group::{
+    GroupDeliveryStatus, GroupId, GroupInfo, GroupInvite, GroupMember, GroupMessage, LeaveReason,
+};
 use crate::relay::PeerInfo;
 use crate::tracker::StatusChange;
-use crate::types::NodeId;
+use crate::types::{NodeId, PathEvent, PathKind};
 
 // ── Configuration ─────────────────────────────────────────────────────
 
 /// Configuration for the protocol runtime.
 pub struct RuntimeConfig {
-    /// Enable E2E encryption for outbound messages.
+    /// Enable E2E encryption for outbound messages. Contacts may override
+    /// it per conversation (`RuntimeHandle::set_conversation_encryption`).
     pub encryption: bool,
     /// Interval for router cache cleanup.
     pub cache_cleanup_interval: Duration,
-    /// Interval for heartbeat liveness checks.
-    pub heartbeat_interval: Duration,
     /// Interval for message tracker eviction.
     pub tracker_cleanup_interval: Duration,
     /// Local username for group membership.
@@ -46,18 +67,95 @@ pub struct RuntimeConfig {
     pub group_hub_heartbeat_interval: Duration,
     /// Interval for backup maintenance ticks.
     pub backup_tick_interval: Duration,
-    /// Interval for gossip peer announcements.
-    pub gossip_announce_interval: Duration,
+    /// Interval between batches of held messages sent to a reconnected peer.
+    pub backup_delivery_interval: Duration,
+    /// Interval for the backup integrity pass (hash checks + store compaction).
+    pub backup_integrity_interval: Duration,
+    /// Failed delivery attempts (ACK timeouts) after which we push a
+    /// message's backup to the relays best placed to reach its recipient.
+    pub backup_escalation_attempts: u8,
+    /// Liveness timing: check and announce intervals, stale/offline
+    /// thresholds. See [`RuntimeConfig::for_profile`] for presets.
+    pub liveness: crate::discovery::LivenessConfig,
+    /// Ephemeral subnet clustering: formation/dissolution thresholds and
+    /// evaluation interval.
+    pub subnet: crate::discovery::SubnetConfig,
     /// Bootstrap peers to join the gossip discovery network.
     pub gossip_bootstrap_peers: Vec<crate::types::NodeId>,
+    /// Startup fallback chain: static peers, LAN, DHT, gossip (see
+    /// [`crate::discovery::bootstrap`]).
+    pub bootstrap: crate::discovery::BootstrapConfig,
+    /// Peer exchange: list recently verified peers in our announces and
+    /// learn unknown peers from others' lists.
+    pub enable_pex: bool,
     /// Interval for shadow ping (watchdog).
     pub shadow_ping_interval: Duration,
     /// Enable DHT-based peer discovery (Phase R7.1).
     pub enable_dht: bool,
     /// Directory for persistent state (SQLite). None = ephemeral (no persistence).
     pub data_dir: Option<PathBuf>,
+    /// Custom backend for runtime state, used instead of `state.db` under
+    /// `data_dir` (held backup messages still go to `data_dir`).
+    pub state_store: Option<Box<dyn crate::storage::StateStore>>,
+    /// Storage and time donated to messages held on behalf of offline peers.
+    pub backup_policy: crate::backup::BackupPolicy,
+    /// Who may hold our messages while we're offline (announced to peers).
+    pub backup_preference: crate::backup::BackupPreference,
     /// Anti-spam configuration (progressive rate limiting).
     pub antispam_config: crate::roles::AntiSpamConfig,
+    /// Per-sender budgets for 1:1 chat delivered to us and forwarded by us.
+    pub chat_rate_limits: crate::roles::ChatRateLimits,
+    /// Relay promotion/demotion thresholds and score decay. Invalid values
+    /// (see [`RuntimeConfig::validate`]) fall back to the defaults.
+    pub role_config: crate::roles::RoleConfig,
+    /// Content types accepted for typed chat payloads.
+    pub payload_registry: crate::payload::PayloadRegistry,
+    /// Who we relay for (default: peers we know).
+    pub relay_policy: crate::router::RelayPolicy,
+    /// Most peers kept in the topology; past it, long-departed peers are
+    /// evicted to make room.
+    pub topology_max_peers: usize,
+    /// How long an offline peer stays in the topology before being pruned.
+    pub departed_peer_ttl: Duration,
+    /// How long shutdown may spend flushing sends and handing off backups
+    /// before the node closes anyway.
+    pub shutdown_drain_timeout: Duration,
+    /// Hooks run on incoming/outgoing envelopes and events, in order.
+    pub hooks: Vec<Box<dyn RuntimeHook>>,
+    /// Serve metrics in OpenMetrics format at `http://<addr>/metrics`
+    /// (None = no exporter).
+    pub metrics_addr: Option<std::net::SocketAddr>,
+    /// Extra identities served over this node's endpoint, each with its own
+    /// keys, routing state, groups and events (see [`HostedIdentity`]).
+    pub identities: Vec<HostedIdentity>,
+    /// Certificate from our user key making this node one of the user's
+    /// linked devices (see [`crate::discovery::devices`]). Ignored unless it
+    /// names this node.
+    pub device_certificate: Option<crate::discovery::DeviceCertificate>,
+    /// Number our chat messages per recipient and deliver each sender's
+    /// in order (see [`crate::ordering`]). None = as they arrive.
+    pub ordered_delivery: Option<crate::ordering::OrderingConfig>,
+    /// Where files peers send us are saved (see [`crate::transfer`]).
+    /// None = incoming files are refused.
+    pub download_dir: Option<PathBuf>,
+    /// Which incoming files are taken, and how many at once (see
+    /// [`crate::transfer::ReceivePolicy`]).
+    #[cfg(feature = "native")]
+    pub receive_policy: crate::transfer::ReceivePolicy,
+    /// Capacity and overflow policy of `RuntimeChannels::messages`.
+    pub message_channel: ChannelPolicy,
+    /// Capacity and overflow policy of `RuntimeChannels::status_changes`.
+    pub status_channel: ChannelPolicy,
+    /// Capacity and overflow policy of `RuntimeChannels::events`.
+    pub event_channel: ChannelPolicy,
+    /// Wait before restarting the loop after a panic, doubled per
+    /// consecutive restart (up to 30s).
+    pub restart_backoff: Duration,
+    /// Consecutive restarts before the runtime gives up and shuts down.
+    pub max_restarts: u32,
+    /// Serve the admin socket here (Unix only; None = no socket). See
+    /// [`AdminRequest`] for what it answers.
+    pub admin_socket: Option<PathBuf>,
 }
 
 impl Default for RuntimeConfig {
@@ -65,17 +163,162 @@ impl Default for RuntimeConfig {
         Self {
             encryption: true,
             cache_cleanup_interval: Duration::from_secs(300),
-            heartbeat_interval: Duration::from_secs(5),
             tracker_cleanup_interval: Duration::from_secs(300),
             username: "anonymous".to_string(),
             group_hub_heartbeat_interval: Duration::from_secs(30),
             backup_tick_interval: Duration::from_secs(60),
-            gossip_announce_interval: Duration::from_secs(10),
+            backup_delivery_interval: Duration::from_secs(1),
+            backup_integrity_interval: Duration::from_secs(60 * 60),
+            backup_escalation_attempts: 2,
+            liveness: crate::discovery::LivenessConfig::default(),
+            subnet: crate::discovery::SubnetConfig::default(),
             gossip_bootstrap_peers: Vec::new(),
+            bootstrap: crate::discovery::BootstrapConfig::default(),
+            enable_pex: false,
             shadow_ping_interval: Duration::from_secs(3),
             enable_dht: true, // Phase R7.1: Enable by default
             data_dir: None,
+            state_store: None,
+            backup_policy: crate::backup::BackupPolicy::default(),
+            backup_preference: crate::backup::BackupPreference::Open,
             antispam_config: crate::roles::AntiSpamConfig::default(),
+            chat_rate_limits: crate::roles::ChatRateLimits::default(),
+            role_config: crate::roles::RoleConfig::default(),
+            payload_registry: crate::payload::PayloadRegistry::default(),
+            relay_policy: crate::router::RelayPolicy::default(),
+            topology_max_peers: crate::relay::MAX_PEERS,
+            departed_peer_ttl: Duration::from_secs(24 * 60 * 60),
+            shutdown_drain_timeout: Duration::from_secs(5),
+            hooks: Vec::new(),
+            metrics_addr: None,
+            identities: Vec::new(),
+            device_certificate: None,
+            ordered_delivery: None,
+            download_dir: None,
+            #[cfg(feature = "native")]
+            receive_policy: crate::transfer::ReceivePolicy::default(),
+            // Large buffers so bursts don't lose anything
+            message_channel: ChannelPolicy::new(16384, OverflowPolicy::DropNew),
+            status_channel: ChannelPolicy::new(4096, OverflowPolicy::DropNew),
+            event_channel: ChannelPolicy::new(4096, OverflowPolicy::DropNew),
+            restart_backoff: Duration::from_secs(1),
+            max_restarts: 5,
+            admin_socket: None,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// Defaults with the liveness timing of a deployment profile
+    /// (e.g. `Mobile` for slower, battery-friendly keepalives). `LowPower`
+    /// also stretches the maintenance timers.
+    pub fn for_profile(profile: crate::discovery::DeploymentProfile) -> Self {
+        let config = Self {
+            liveness: crate::discovery::LivenessConfig::for_profile(profile),
+            ..Self::default()
+        };
+        if profile != crate::discovery::DeploymentProfile::LowPower {
+            return config;
+        }
+        Self {
+            cache_cleanup_interval: Duration::from_secs(15 * 60),
+            tracker_cleanup_interval: Duration::from_secs(15 * 60),
+            group_hub_heartbeat_interval: Duration::from_secs(120),
+            backup_tick_interval: Duration::from_secs(5 * 60),
+            backup_delivery_interval: Duration::from_secs(5),
+            shadow_ping_interval: Duration::from_secs(30),
+            ..config
+        }
+    }
+
+    /// Check the settings that have constraints (role thresholds and decay).
+    pub fn validate(&self) -> Result<(), crate::TomProtocolError> {
+        self.role_config.validate()
+    }
+}
+
+/// Transport settings for a deployment profile. `Mobile` and `LowPower`
+/// opt in to slow QUIC keepalives so NAT bindings survive quiet spells,
+/// with an idle timeout to match; other profiles keep the defaults.
+#[cfg(feature = "native")]
+pub fn transport_config_for_profile(profile: crate::discovery::DeploymentProfile) -> tom_transport::TomNodeConfig {
+    use crate::discovery::DeploymentProfile;
+    let config = tom_transport::TomNodeConfig::new();
+    match profile {
+        DeploymentProfile::Mobile | DeploymentProfile::LowPower => config
+            .keep_alive_interval(Duration::from_secs(25))
+            .idle_timeout(Duration::from_secs(90)),
+        DeploymentProfile::Desktop | DeploymentProfile::Server => config,
+    }
+}
+
+// ── Hosted identities ────────────────────────────────────────────────
+
+/// An extra identity run by this runtime beside the node's own, so one
+/// device can be "personal" and "work" without running two nodes.
+///
+/// It gets its own `RuntimeState` (topology, groups, backups, events) but
+/// no endpoint: the node lists it in its announces with a binding signed by
+/// the identity, and peers reach it through the node. Drive it with
+/// [`RuntimeHandle::identity`]; its events arrive as
+/// [`ProtocolEvent::Hosted`], its messages with `DeliveredMessage::to` set.
+#[derive(Clone)]
+pub struct HostedIdentity {
+    /// Ed25519 secret key seed of the identity.
+    pub secret_seed: [u8; 32],
+    /// Display name (and group username) of the identity.
+    pub username: String,
+}
+
+impl HostedIdentity {
+    pub fn new(secret_seed: [u8; 32], username: impl Into<String>) -> Self {
+        Self {
+            secret_seed,
+            username: username.into(),
+        }
+    }
+
+    /// The identity's node id (its public key).
+    pub fn node_id(&self) -> NodeId {
+        NodeId::from_endpoint_id(tom_base::SecretKey::from_bytes(&self.secret_seed).public())
+    }
+
+    /// A fresh binding of the identity to `host`, for its announces.
+    pub(crate) fn binding(&self, host: NodeId, now: u64) -> crate::discovery::IdentityBinding {
+        crate::discovery::IdentityBinding::new(
+            self.node_id(),
+            host,
+            self.username.clone(),
+            now,
+            &self.secret_seed,
+        )
+    }
+
+    /// Config of the identity's own state: the host's timing and
+    /// encryption, persisted under `<data_dir>/identities/<id>`. No DHT
+    /// (the host publishes the endpoint) and no hosting of its own.
+    #[cfg(feature = "native")]
+    pub(crate) fn state_config(&self, host: &RuntimeConfig) -> RuntimeConfig {
+        RuntimeConfig {
+            encryption: host.encryption,
+            username: self.username.clone(),
+            liveness: host.liveness,
+            enable_dht: false,
+            data_dir: host
+                .data_dir
+                .as_ref()
+                .map(|dir| dir.join("identities").join(self.node_id().to_string())),
+            backup_policy: host.backup_policy,
+            backup_preference: host.backup_preference.clone(),
+            payload_registry: host.payload_registry.clone(),
+            ordered_delivery: host.ordered_delivery,
+            cache_cleanup_interval: host.cache_cleanup_interval,
+            tracker_cleanup_interval: host.tracker_cleanup_interval,
+            group_hub_heartbeat_interval: host.group_hub_heartbeat_interval,
+            backup_tick_interval: host.backup_tick_interval,
+            backup_delivery_interval: host.backup_delivery_interval,
+            shadow_ping_interval: host.shadow_ping_interval,
+            ..RuntimeConfig::default()
         }
     }
 }
@@ -84,13 +327,40 @@ impl Default for RuntimeConfig {
 
 /// Commands the application sends to the runtime event loop.
 pub enum RuntimeCommand {
-    /// Send a chat message to a peer.
-    SendMessage { to: NodeId, payload: Vec<u8> },
+    /// Send a chat message to a peer; `reply` gets its envelope id once
+    /// the envelope is built (dropped if it couldn't be).
+    SendMessage {
+        to: NodeId,
+        payload: Vec<u8>,
+        reply: Option<oneshot::Sender<String>>,
+    },
+    /// Send a chat message, asking backup holders to keep it for `backup_ttl`
+    /// if the peer is offline (bounded by each holder's policy).
+    SendMessageWithTtl {
+        to: NodeId,
+        payload: Vec<u8>,
+        backup_ttl: Duration,
+    },
+    /// Send a chat message with per-message delivery options.
+    SendMessageWithOptions {
+        to: NodeId,
+        payload: Vec<u8>,
+        options: SendOptions,
+    },
     /// Send a read receipt for a previously received message.
     SendReadReceipt {
         to: NodeId,
         original_message_id: String,
     },
+    /// Send a payload in exactly-once mode (replies with its message id,
+    /// None if it couldn't be built or persisted).
+    SendExactlyOnce {
+        to: NodeId,
+        payload: Vec<u8>,
+        reply: oneshot::Sender<Option<String>>,
+    },
+    /// Commit a delivered exactly-once message after processing it.
+    CommitMessage { from: NodeId, message_id: String },
     /// Register a peer in the network (triggers discovery via iroh).
     AddPeer { node_id: NodeId },
     /// Register a peer with its full network address (for direct connectivity).
@@ -99,10 +369,145 @@ pub enum RuntimeCommand {
     UpsertPeer { info: PeerInfo },
     /// Remove a peer from topology.
     RemovePeer { node_id: NodeId },
+    /// Pin (or with `None`, unpin) routing for a peer.
+    SetRoutingOverride {
+        node_id: NodeId,
+        policy: Option<crate::relay::RoutingOverride>,
+    },
+    /// Change our advertised presence (re-announced over gossip right away).
+    SetPresence { presence: crate::discovery::Presence },
+    /// Tell a conversation peer we started/stopped typing (rate-limited, untracked).
+    SendTyping { to: NodeId, typing: bool },
+    /// Send a payload on the real-time lane (one datagram, unreliable).
+    SendRealtime { to: NodeId, payload: Vec<u8> },
+    /// Query: real-time lane stats with a peer.
+    GetRealtimeStats {
+        peer: NodeId,
+        reply: oneshot::Sender<Option<crate::realtime::RealtimeStats>>,
+    },
+    /// Query: protocol version and features a peer announced.
+    GetPeerCapabilities {
+        peer: NodeId,
+        reply: oneshot::Sender<Option<crate::capabilities::PeerCapabilities>>,
+    },
+    /// Penalize a node for a violation and gossip a signed report, with the
+    /// offending envelope as evidence.
+    ReportAbuse {
+        offender: NodeId,
+        kind: crate::discovery::ViolationKind,
+        evidence: Vec<u8>,
+    },
+    /// Send a chat message to every linked device of a user (to `user`
+    /// itself when no device is known).
+    SendToUser { user: NodeId, payload: Vec<u8> },
+    /// Query: linked devices of a user, as this node knows them.
+    GetLinkedDevices {
+        user: NodeId,
+        reply: oneshot::Sender<Vec<NodeId>>,
+    },
+    /// Apply a device revocation signed by its user and gossip it.
+    RevokeDevice {
+        revocation: crate::discovery::DeviceRevocation,
+    },
+    /// Query: nodes claiming a username, owner (first seen) first.
+    ResolveUsername {
+        name: String,
+        reply: oneshot::Sender<Vec<NodeId>>,
+    },
+    /// Query: active ephemeral subnets, as this node sees them.
+    GetSubnets {
+        reply: oneshot::Sender<Vec<crate::discovery::SubnetInfo>>,
+    },
+    /// Query: subnet manager totals (formed, dissolved, churn).
+    GetSubnetStats {
+        reply: oneshot::Sender<crate::discovery::SubnetStats>,
+    },
+    /// Trace the route to `target`; replies with the trace id (None if the
+    /// trace couldn't start). The path arrives as `ProtocolEvent::RouteTraced`.
+    TraceRoute {
+        target: NodeId,
+        reply: oneshot::Sender<Option<String>>,
+    },
+    /// Send a request and wait up to `timeout` for its response. Handled in
+    /// the loop, which owns the waiting callers.
+    Request {
+        to: NodeId,
+        payload: Vec<u8>,
+        timeout: Duration,
+        reply: oneshot::Sender<Result<crate::rpc::Response, crate::TomProtocolError>>,
+    },
+    /// Send a file over a dedicated stream (see [`crate::transfer`]);
+    /// replies with the transfer id once the file checks out locally.
+    SendFile {
+        to: NodeId,
+        path: PathBuf,
+        reply: oneshot::Sender<Result<String, crate::TomProtocolError>>,
+    },
+    /// Answer a `ProtocolEvent::RequestReceived`.
+    Respond {
+        to: NodeId,
+        request_id: String,
+        payload: Vec<u8>,
+    },
+    /// Query: peers, roles, liveness, subnets and observed relay paths.
+    GetTopologySnapshot {
+        reply: oneshot::Sender<crate::relay::TopologySnapshot>,
+    },
     /// Request current connected peers.
     GetConnectedPeers {
         reply: oneshot::Sender<Vec<NodeId>>,
     },
+    /// Query: transport traffic totals over all peers.
+    #[cfg(feature = "native")]
+    GetTransportStats {
+        reply: oneshot::Sender<tom_transport::NodeStats>,
+    },
+    /// Query: transport traffic with one peer (None = nothing exchanged).
+    #[cfg(feature = "native")]
+    GetPeerTransportStats {
+        node_id: NodeId,
+        reply: oneshot::Sender<Option<tom_transport::PeerStats>>,
+    },
+    /// Query: our pairing info (NodeId + current addresses) for a QR code.
+    GetPairingInfo {
+        reply: oneshot::Sender<crate::pairing::PairingInfo>,
+    },
+    /// Publish our pairing info under a fresh rendezvous code (None without DHT).
+    CreatePairingCode {
+        reply: oneshot::Sender<Option<crate::pairing::PairingCode>>,
+    },
+    /// Look up a rendezvous code and add the peer found under it.
+    RedeemPairingCode {
+        code: crate::pairing::RendezvousCode,
+        reply: oneshot::Sender<Option<NodeId>>,
+    },
+    // ── Contacts ────────────────────────────────────
+    /// Add (or refresh) a contact card in the roster and topology.
+    ImportContact { card: crate::contact::ContactCard },
+    /// Query: our contact roster.
+    GetContacts {
+        reply: oneshot::Sender<Vec<crate::contact::Contact>>,
+    },
+    /// Remove a contact from the roster (lifts any block).
+    RemoveContact { node_id: NodeId },
+    /// Block (or unblock) a node: its envelopes are dropped on arrival.
+    SetContactBlocked { node_id: NodeId, blocked: bool },
+    /// Set or clear (None) a contact's local alias.
+    SetContactAlias {
+        node_id: NodeId,
+        alias: Option<String>,
+    },
+    /// Record whether a contact's fingerprint was verified out of band.
+    SetContactTrust {
+        node_id: NodeId,
+        trust: crate::contact::TrustLevel,
+    },
+    /// Set or clear (None = global `encryption` flag) the encryption we want
+    /// with a contact, and tell them in a handshake.
+    SetConversationEncryption {
+        node_id: NodeId,
+        mode: Option<crate::contact::EncryptionMode>,
+    },
     // ── Group commands ──────────────────────────────
     /// Create a new group. This node becomes a member; hub_relay_id hosts the group.
     CreateGroup {
@@ -119,6 +524,29 @@ pub enum RuntimeCommand {
     LeaveGroup { group_id: GroupId },
     /// Send a text message to a group.
     SendGroupMessage { group_id: GroupId, text: String },
+    /// Send a text message to a group as a reply to an earlier message.
+    SendGroupReply {
+        group_id: GroupId,
+        reply_to: String,
+        text: String,
+    },
+    /// Send a text message to a group, mentioning some of its members.
+    SendGroupMention {
+        group_id: GroupId,
+        text: String,
+        mentions: Vec<NodeId>,
+    },
+    /// Send a group message the hub holds until `deliver_at` (unix ms).
+    ///
+    /// Replies with the message id (for cancellation), None if rejected.
+    ScheduleGroupMessage {
+        group_id: GroupId,
+        text: String,
+        deliver_at: u64,
+        reply: oneshot::Sender<Option<String>>,
+    },
+    /// Withdraw one of our scheduled group messages before it goes out.
+    CancelScheduledGroupMessage { group_id: GroupId, message_id: String },
     /// Query: list groups we belong to.
     GetGroups {
         reply: oneshot::Sender<Vec<GroupInfo>>,
@@ -133,6 +561,155 @@ pub enum RuntimeCommand {
     },
     /// Admin invites a member to an existing group.
     InviteMember { group_id: GroupId, target_id: NodeId },
+    /// Admin bans a node (kicking it if present); `None` duration is permanent.
+    BanMember {
+        group_id: GroupId,
+        target_id: NodeId,
+        duration_ms: Option<u64>,
+    },
+    /// Admin lifts a ban.
+    UnbanMember { group_id: GroupId, target_id: NodeId },
+    /// Admin updates group name, description and avatar hash.
+    UpdateGroupMetadata {
+        group_id: GroupId,
+        name: String,
+        description: String,
+        avatar_hash: Option<[u8; 32]>,
+    },
+    /// Admin sets (or clears, with `None`) the disappearing-message timer.
+    SetGroupMessageTtl {
+        group_id: GroupId,
+        ttl_ms: Option<u64>,
+    },
+    /// Admin replaces the group's rate limit / history size settings.
+    UpdateGroupSettings {
+        group_id: GroupId,
+        settings: crate::group::GroupSettings,
+    },
+    /// Admin mints a shareable invite token (replies with the encoded token).
+    CreateInviteToken {
+        group_id: GroupId,
+        ttl_ms: u64,
+        reply: oneshot::Sender<Option<String>>,
+    },
+    /// Join a group by presenting an invite token to its hub.
+    JoinGroupByToken {
+        token: crate::group::GroupInviteToken,
+    },
+    /// Knock on a group: the hub queues us until an admin approves.
+    RequestJoinGroup { group_id: GroupId, hub_id: NodeId },
+    /// Admin approves a pending join request.
+    ApproveJoinRequest { group_id: GroupId, requester: NodeId },
+    /// Admin denies a pending join request.
+    DenyJoinRequest { group_id: GroupId, requester: NodeId },
+    /// Query: join requests awaiting a decision in a group we administer.
+    GetPendingJoinRequests {
+        group_id: GroupId,
+        reply: oneshot::Sender<Vec<crate::group::GroupJoinRequest>>,
+    },
+    /// Fetch a page of older group messages from the hub (`seq < before`).
+    FetchGroupHistory {
+        group_id: GroupId,
+        before: u64,
+        limit: u32,
+    },
+    /// Tell the hub we've displayed a received group message (read receipt).
+    MarkGroupMessageRead {
+        group_id: GroupId,
+        sender_id: NodeId,
+        message_id: String,
+    },
+    /// Query: what the backup system holds and how well it is replicated.
+    GetBackupStats {
+        reply: oneshot::Sender<crate::backup::BackupStats>,
+    },
+    /// Query: messages composed while offline, waiting for connectivity.
+    GetQueuedMessages {
+        reply: oneshot::Sender<Vec<crate::outbox::QueuedMessage>>,
+    },
+    /// Query: who has received / read one of our group messages.
+    GetGroupDeliveryStatus {
+        group_id: GroupId,
+        message_id: String,
+        reply: oneshot::Sender<Option<GroupDeliveryStatus>>,
+    },
+    /// Add (or remove, with `add: false`) an emoji reaction on a group message.
+    ReactToGroupMessage {
+        group_id: GroupId,
+        message_id: String,
+        emoji: String,
+        add: bool,
+    },
+    /// Query: reaction counts on a group message, most popular first.
+    GetGroupReactions {
+        group_id: GroupId,
+        message_id: String,
+        reply: oneshot::Sender<Vec<(String, usize)>>,
+    },
+    /// Open a poll in a group (replies with the poll id).
+    CreateGroupPoll {
+        group_id: GroupId,
+        question: String,
+        options: Vec<String>,
+        reply: oneshot::Sender<Option<String>>,
+    },
+    /// Vote for option `option` (0-based) of a group poll.
+    VoteGroupPoll {
+        group_id: GroupId,
+        poll_id: String,
+        option: u32,
+    },
+    /// Set local notification preferences for a group (replies false if not a member).
+    SetGroupNotificationPrefs {
+        group_id: GroupId,
+        prefs: crate::group::GroupNotificationPrefs,
+        reply: oneshot::Sender<bool>,
+    },
+    /// Query: local notification preferences for a group.
+    GetGroupNotificationPrefs {
+        group_id: GroupId,
+        reply: oneshot::Sender<crate::group::GroupNotificationPrefs>,
+    },
+    // ── Hubless mesh groups ───────────────────────
+    /// Create a hubless group (≤8 members); replies with its id on success.
+    ///
+    /// Send/leave/invite/kick commands on its id go to the mesh, not a hub.
+    CreateMeshGroup {
+        name: String,
+        members: Vec<NodeId>,
+        reply: oneshot::Sender<Option<GroupId>>,
+    },
+    /// Query: mesh groups we belong to.
+    GetMeshGroups {
+        reply: oneshot::Sender<Vec<crate::group::MeshGroup>>,
+    },
+    // ── Shared state (LWW CRDT) ───────────────────
+    /// Open (or add peers to) a shared document and sync it with them.
+    OpenSharedDoc { doc_id: String, peers: Vec<NodeId> },
+    /// Forget a shared document locally.
+    CloseSharedDoc { doc_id: String },
+    /// Set (or delete, with `None`) a key in a shared document.
+    SetSharedValue {
+        doc_id: String,
+        key: String,
+        value: Option<Vec<u8>>,
+    },
+    /// Query: live entries of a shared document.
+    #[allow(clippy::type_complexity)]
+    GetSharedDoc {
+        doc_id: String,
+        reply: oneshot::Sender<Option<Vec<(String, Vec<u8>)>>>,
+    },
+    // ── Pub/sub channels ──────────────────────────
+    /// Subscribe to a named channel (optional join secret for private ones).
+    JoinChannel {
+        name: String,
+        secret: Option<Vec<u8>>,
+    },
+    /// Unsubscribe from a channel.
+    LeaveChannel { name: String },
+    /// Publish a small signed payload on a joined channel.
+    PublishChannel { name: String, payload: Vec<u8> },
     /// Query: list pending invitations.
     GetPendingInvites {
         reply: oneshot::Sender<Vec<GroupInvite>>,
@@ -147,11 +724,145 @@ pub enum RuntimeCommand {
     GetAllRoleScores {
         reply: oneshot::Sender<Vec<(NodeId, f64, crate::relay::PeerRole)>>,
     },
+    // ── Liveness queries ──────────────────────────
+    /// Query: what we know about one peer's reachability (None if unknown).
+    GetPeerLiveness {
+        node_id: NodeId,
+        reply: oneshot::Sender<Option<PeerLiveness>>,
+    },
+    /// Query: reachability of every known peer.
+    GetAllPeers {
+        reply: oneshot::Sender<Vec<PeerLiveness>>,
+    },
     // ── DHT discovery ──────────────────────────────
     /// DHT lookup completed — inject discovered address into transport.
+    #[cfg(feature = "native")]
     DhtLookupResult { addr: tom_dht::DhtNodeAddr },
-    /// Graceful shutdown.
-    Shutdown,
+    /// Suspend gossip announces, hub heartbeats, shadow pings and backup
+    /// ticks; the endpoint stays up and incoming traffic is still handled.
+    Pause,
+    /// Undo `Pause`.
+    Resume,
+    /// Graceful shutdown: drain, announce our departure, close the node.
+    Shutdown {
+        reply: oneshot::Sender<ShutdownReport>,
+    },
+    /// Run `command` as a hosted identity (see `RuntimeHandle::identity`).
+    AsIdentity {
+        identity: NodeId,
+        command: Box<RuntimeCommand>,
+    },
+}
+
+impl RuntimeCommand {
+    /// Commands about the node rather than one identity (transport, pairing,
+    /// lifecycle); a hosted identity's handle sends them unwrapped. Requests
+    /// are correlated by the loop and go out as the node's own identity.
+    pub fn is_node_wide(&self) -> bool {
+        #[cfg(feature = "native")]
+        if matches!(
+            self,
+            RuntimeCommand::GetTransportStats { .. }
+                | RuntimeCommand::GetPeerTransportStats { .. }
+                | RuntimeCommand::DhtLookupResult { .. }
+        ) {
+            return true;
+        }
+        matches!(
+            self,
+            RuntimeCommand::GetConnectedPeers { .. }
+                | RuntimeCommand::AddPeerAddr { .. }
+                | RuntimeCommand::AddPeer { .. }
+                | RuntimeCommand::GetPairingInfo { .. }
+                | RuntimeCommand::CreatePairingCode { .. }
+                | RuntimeCommand::RedeemPairingCode { .. }
+                | RuntimeCommand::Request { .. }
+                | RuntimeCommand::SendFile { .. }
+                | RuntimeCommand::Pause
+                | RuntimeCommand::Resume
+                | RuntimeCommand::Shutdown { .. }
+                | RuntimeCommand::AsIdentity { .. }
+        )
+    }
+
+    /// Commands that queue app data for sending (bulk traffic). The loop
+    /// holds these back while bulk sends are backlogged; everything else,
+    /// shutdown and queries included, is always taken.
+    pub fn is_bulk(&self) -> bool {
+        match self {
+            RuntimeCommand::AsIdentity { command, .. } => command.is_bulk(),
+            _ => matches!(
+                self,
+                RuntimeCommand::SendMessage { .. }
+                    | RuntimeCommand::SendMessageWithTtl { .. }
+                    | RuntimeCommand::SendMessageWithOptions { .. }
+                    | RuntimeCommand::SendExactlyOnce { .. }
+                    | RuntimeCommand::SendToUser { .. }
+                    | RuntimeCommand::Request { .. }
+                    | RuntimeCommand::Respond { .. }
+                    | RuntimeCommand::SendFile { .. }
+                    | RuntimeCommand::SendGroupMessage { .. }
+                    | RuntimeCommand::SendGroupReply { .. }
+                    | RuntimeCommand::SendGroupMention { .. }
+                    | RuntimeCommand::ScheduleGroupMessage { .. }
+                    | RuntimeCommand::CreateGroupPoll { .. }
+                    | RuntimeCommand::SetSharedValue { .. }
+                    | RuntimeCommand::PublishChannel { .. }
+            ),
+        }
+    }
+}
+
+// ── Send options ─────────────────────────────────────────────────────
+
+/// Per-message delivery options for [`RuntimeHandle::send_message_with_options`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SendOptions {
+    /// How long backup holders keep the message if the peer is offline
+    /// (None = the holders' default).
+    pub backup_ttl: Option<Duration>,
+    /// Send the same envelope over two disjoint relay paths at once, for
+    /// latency-critical or must-deliver messages. The recipient's router
+    /// drops whichever copy arrives second.
+    pub redundant: bool,
+}
+
+// ── Shutdown report ──────────────────────────────────────────────────
+
+/// What a graceful shutdown couldn't finish.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Messages still in the offline outbox (kept on disk, sent on the
+    /// next start).
+    pub unsent_messages: usize,
+    /// Backup entries handed to another holder.
+    pub backups_handed_off: usize,
+    /// Backup entries no other holder could take.
+    pub backups_kept: usize,
+    /// Whether our signed departure went out on gossip.
+    pub departure_announced: bool,
+    /// The drain deadline passed before every send completed.
+    pub timed_out: bool,
+}
+
+// ── Peer liveness (query results) ────────────────────────────────────
+
+/// A peer's reachability as the runtime sees it, so applications don't
+/// have to rebuild it from events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerLiveness {
+    pub node_id: NodeId,
+    pub liveness: crate::discovery::LivenessState,
+    /// Last authenticated traffic or announce (Unix ms). None = not tracked.
+    pub last_seen: Option<u64>,
+    /// How we learned about the peer. None if only known from the topology.
+    pub source: Option<DiscoverySource>,
+    /// Role in our topology. None if it isn't in the topology.
+    pub role: Option<crate::relay::PeerRole>,
+    /// Current transport path (Unknown until the transport reports one).
+    pub path: PathKind,
+    /// Average measured round trip (ms). None until the transport reports one.
+    pub rtt_ms: Option<u64>,
 }
 
 // ── Events (runtime → app) ───────────────────────────────────────────
@@ -160,15 +871,38 @@ pub enum RuntimeCommand {
 #[derive(Debug, Clone)]
 pub struct DeliveredMessage {
     pub from: NodeId,
+    /// The identity it was addressed to: this node, or a hosted identity.
+    pub to: NodeId,
+    /// Raw bytes for untyped payloads, or the unwrapped body of a typed one.
     pub payload: Vec<u8>,
     pub envelope_id: String,
     pub timestamp: u64,
     pub signature_valid: bool,
     pub was_encrypted: bool,
+    /// Content type of a typed payload. None = legacy untyped bytes.
+    pub content_type: Option<String>,
+    /// Sent in exactly-once mode: call `commit_message` once processed.
+    pub exactly_once: bool,
+}
+
+impl DeliveredMessage {
+    /// Decode a typed payload as `T`.
+    ///
+    /// Fails if the message is untyped or carries another content type.
+    pub fn decode<T: crate::payload::PayloadSchema>(&self) -> Result<T, crate::TomProtocolError> {
+        let content_type = self.content_type.as_deref().ok_or_else(|| {
+            crate::TomProtocolError::Deserialization("untyped payload".into())
+        })?;
+        crate::payload::decode_body(content_type, &self.payload)
+    }
 }
 
 /// Protocol-level events the application may want to observe.
-#[derive(Debug, Clone)]
+///
+/// Serializes as `{"kind": "<variant>", "fields": {...}}`, with the
+/// variant's field names (for bindings; see `tom-protocol-ffi`).
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "fields")]
 pub enum ProtocolEvent {
     /// A new peer was discovered.
     PeerDiscovered {
@@ -180,8 +914,69 @@ pub enum ProtocolEvent {
     PeerStale { node_id: NodeId },
     /// A peer went offline (confirmed departed).
     PeerOffline { node_id: NodeId },
+    /// Departed peers dropped from the topology (TTL expired or capacity).
+    PeersEvicted { node_ids: Vec<NodeId> },
     /// A peer came back online after being stale/offline.
     PeerOnline { node_id: NodeId },
+    /// A peer announced a different presence (online/away/busy/custom).
+    PeerPresenceChanged {
+        node_id: NodeId,
+        presence: crate::discovery::Presence,
+    },
+    /// The runtime was paused (`paused: true`) or resumed.
+    RuntimePaused { paused: bool },
+    /// A trace we started came back: every hop from us to `target`, signed.
+    RouteTraced {
+        trace_id: String,
+        target: NodeId,
+        hops: Vec<crate::trace::TraceHop>,
+    },
+    /// A peer sent us a request; answer with `RuntimeHandle::respond`,
+    /// echoing `request_id`.
+    RequestReceived {
+        from: NodeId,
+        request_id: String,
+        payload: Vec<u8>,
+    },
+    /// A peer started (`typing: true`) or stopped typing to us. UIs clear a
+    /// started indicator after `typing::TYPING_TIMEOUT_MS` without a refresh.
+    PeerTyping { node_id: NodeId, typing: bool },
+    /// A peer claimed a username another node claimed first.
+    UsernameConflict {
+        name: String,
+        owner: NodeId,
+        claimant: NodeId,
+    },
+    /// Another device of our user was linked (its certificate verified).
+    DeviceLinked {
+        user: NodeId,
+        device: NodeId,
+        device_name: String,
+    },
+    /// A device of our user was revoked.
+    DeviceRevoked { user: NodeId, device: NodeId },
+    /// History another device of our user had and we didn't (oldest
+    /// first): a page of its answer to our sync request, or a message it
+    /// just sent or received. `done` marks the last page of the answer (it
+    /// may be empty).
+    HistorySynced {
+        from: NodeId,
+        entries: Vec<crate::history_sync::HistoryEntry>,
+        done: bool,
+    },
+    /// A valid contact card arrived, 1:1 (`group_id` None) or in a group.
+    /// Nothing is imported until the app calls `import_contact`.
+    ContactCardReceived {
+        from: NodeId,
+        group_id: Option<GroupId>,
+        card: crate::contact::ContactCard,
+    },
+    /// The negotiated encryption of our conversation with a contact changed
+    /// (our setting or their handshake).
+    ConversationEncryptionChanged {
+        node_id: NodeId,
+        mode: crate::contact::EncryptionMode,
+    },
     /// A message was rejected by the router.
     MessageRejected { reason: String },
     /// We forwarded a message as relay.
@@ -190,7 +985,10 @@ pub enum ProtocolEvent {
         next_hop: NodeId,
     },
     /// Path changed for a peer (relay ↔ direct).
-    PathChanged { event: PathEvent },
+    PathChanged {
+        #[serde(serialize_with = "events::serialize_path_event")]
+        event: PathEvent,
+    },
     /// Runtime encountered a non-fatal error.
     Error { description: String },
     // ── Group events ──────────────────────────────
@@ -216,7 +1014,17 @@ pub enum ProtocolEvent {
         reason: LeaveReason,
     },
     /// A group message was received.
-    GroupMessageReceived { message: GroupMessage },
+    ///
+    /// `muted` is set when our notification preferences for the group
+    /// (mute, mentions-only) say it shouldn't notify.
+    GroupMessageReceived { message: GroupMessage, muted: bool },
+    /// A received group message mentions us (follows `GroupMessageReceived`).
+    GroupMentioned {
+        group_id: GroupId,
+        message_id: String,
+        sender_id: NodeId,
+        sender_username: String,
+    },
     /// The hub for a group migrated to a new node.
     GroupHubMigrated {
         group_id: GroupId,
@@ -234,6 +1042,133 @@ pub enum ProtocolEvent {
         node_id: NodeId,
         new_role: crate::group::GroupMemberRole,
     },
+    /// Group metadata (name, description, avatar) was updated by an admin.
+    GroupMetadataChanged {
+        group_id: GroupId,
+        name: String,
+        description: String,
+        avatar_hash: Option<[u8; 32]>,
+        config_version: u64,
+        updated_by: NodeId,
+    },
+    /// The disappearing-message timer was set or cleared (`None`) by an admin.
+    GroupMessageTtlChanged {
+        group_id: GroupId,
+        ttl_ms: Option<u64>,
+        updated_by: NodeId,
+    },
+    /// An admin changed the group's rate limit / history size settings.
+    GroupSettingsChanged {
+        group_id: GroupId,
+        settings: crate::group::GroupSettings,
+        updated_by: NodeId,
+    },
+    /// Someone asked to join a group we administer.
+    GroupJoinRequested {
+        group_id: GroupId,
+        request: crate::group::GroupJoinRequest,
+    },
+    /// A pending join request was approved or denied by an admin.
+    GroupJoinRequestResolved {
+        group_id: GroupId,
+        requester: NodeId,
+        approved: bool,
+        resolved_by: NodeId,
+    },
+    /// An admin denied our request to join a group.
+    GroupJoinDenied { group_id: GroupId },
+    /// Messages outlived the group's disappearing timer and were removed
+    /// from local history — delete them from view.
+    GroupMessagesExpired {
+        group_id: GroupId,
+        message_ids: Vec<String>,
+    },
+    /// A page of older group messages arrived (ascending by seq).
+    GroupHistoryPage {
+        group_id: GroupId,
+        messages: Vec<GroupMessage>,
+        has_more: bool,
+    },
+    /// A member received (or read) one of our group messages.
+    GroupDeliveryUpdated {
+        group_id: GroupId,
+        message_id: String,
+        member: NodeId,
+        read: bool,
+    },
+    /// Every member one of our group messages was fanned out to has received it.
+    GroupMessageDeliveredToAll { group_id: GroupId, message_id: String },
+    /// A member added or removed a reaction on a group message.
+    /// `count` is the new number of reactors for `emoji`.
+    GroupReactionChanged {
+        group_id: GroupId,
+        message_id: String,
+        emoji: String,
+        reactor: NodeId,
+        added: bool,
+        count: usize,
+    },
+    /// A group poll was opened or its tally changed.
+    GroupPollUpdated {
+        group_id: GroupId,
+        poll: crate::group::GroupPoll,
+    },
+    /// The receiver committed one of our exactly-once messages.
+    ExactlyOnceCommitted { message_id: String, to: NodeId },
+    /// An exactly-once message was never committed before its max age.
+    /// It may or may not have been processed.
+    ExactlyOnceExpired { message_id: String, to: NodeId },
+    /// With ordered delivery, `count` messages from `from` (numbered from
+    /// `first`) didn't arrive in time; the ones after them were released.
+    /// A missing one that turns up later is still delivered.
+    MessageGap { from: NodeId, first: u64, count: u64 },
+    /// A real-time lane payload arrived (see `RuntimeHandle::send_realtime`).
+    /// Possibly late, duplicated or out of order: `seq` tells.
+    RealtimeReceived { from: NodeId, seq: u64, payload: Vec<u8> },
+    /// A file transfer moved on (at most once per percent).
+    /// `outgoing`: we're the sender.
+    TransferProgress {
+        transfer_id: String,
+        peer: NodeId,
+        outgoing: bool,
+        bytes_done: u64,
+        total: u64,
+    },
+    /// A file transfer finished and every chunk checked out. `path` is the
+    /// saved file (incoming) or the file sent (outgoing).
+    TransferComplete {
+        transfer_id: String,
+        peer: NodeId,
+        outgoing: bool,
+        name: String,
+        size: u64,
+        path: Option<PathBuf>,
+    },
+    /// A file transfer was refused, or gave up after its retries.
+    TransferFailed {
+        transfer_id: String,
+        peer: NodeId,
+        outgoing: bool,
+        reason: String,
+    },
+    /// A key in a shared document changed (local write or merged remote one).
+    /// `value: None` means the key was deleted.
+    SharedStateChanged {
+        doc_id: String,
+        key: String,
+        value: Option<Vec<u8>>,
+        updated_by: NodeId,
+    },
+    /// A publication arrived on a joined pub/sub channel.
+    ///
+    /// Typed payloads are unwrapped like chat messages (`content_type`).
+    ChannelMessage {
+        channel: String,
+        from: NodeId,
+        payload: Vec<u8>,
+        content_type: Option<String>,
+        sent_at: u64,
+    },
     /// Shadow promoted to primary hub for a group.
     GroupShadowPromoted {
         group_id: GroupId,
@@ -248,6 +1183,12 @@ pub enum ProtocolEvent {
     GossipNeighborUp { node_id: NodeId },
     /// A gossip neighbor disconnected.
     GossipNeighborDown { node_id: NodeId },
+    /// The startup bootstrap pipeline finished: the stage that first
+    /// reached peers (None = no stage did) and how many it reached.
+    BootstrapCompleted {
+        peers_found: usize,
+        source: Option<crate::discovery::BootstrapSource>,
+    },
     // ── Subnet events ─────────────────────────────
     /// An ephemeral subnet was formed from communication patterns.
     SubnetFormed {
@@ -263,6 +1204,9 @@ pub enum ProtocolEvent {
     RoleDemoted { node_id: NodeId, score: f64 },
     /// Our local role changed (update gossip announces).
     LocalRoleChanged { new_role: crate::relay::PeerRole },
+    /// We took on or dropped an extra role (Storage, Bootstrap); `roles`
+    /// is everything we now announce.
+    LocalRolesChanged { roles: Vec<crate::relay::PeerRole> },
     // ── Backup events ─────────────────────────────
     /// A message was stored as backup for an offline recipient.
     BackupStored {
@@ -279,6 +1223,29 @@ pub enum ProtocolEvent {
         message_id: String,
         recipient_id: NodeId,
     },
+    /// A backed-up message was evicted to stay within the backup quota.
+    BackupEvicted {
+        message_id: String,
+        recipient_id: NodeId,
+    },
+    /// A backed-up message failed its integrity check and was dropped.
+    BackupCorrupted {
+        message_id: String,
+        recipient_id: NodeId,
+    },
+    /// Some of a backed-up message's holders degraded and were dropped;
+    /// replacements are being sought.
+    ReplicationDegraded {
+        message_id: String,
+        recipient_id: NodeId,
+        remaining_holders: usize,
+    },
+    /// We refused to hold a backup because it would exceed a storage limit.
+    StorageQuotaExceeded {
+        message_id: String,
+        sender_id: NodeId,
+        limit: crate::backup::QuotaLimit,
+    },
     // ── Delivery events ─────────────────────────────
     /// A message delivery was retried after ACK timeout.
     DeliveryRetry {
@@ -286,6 +1253,13 @@ pub enum ProtocolEvent {
         to: NodeId,
         attempt: u8,
     },
+    /// A message kept failing to arrive: its backup was pushed to relays
+    /// near the recipient.
+    DeliveryEscalated {
+        message_id: String,
+        to: NodeId,
+        relays: Vec<NodeId>,
+    },
     /// A message delivery failed after all retries exhausted.
     DeliveryTimeout {
         message_id: String,
@@ -299,6 +1273,31 @@ pub enum ProtocolEvent {
         score: f64,
         current_rate: f64,
     },
+    /// A sender ran out of its 1:1 chat budget; its messages in `scope`
+    /// are dropped (unacked, so it retries later) until the budget refills.
+    /// Reported once per flood.
+    ChatThrottled {
+        node_id: NodeId,
+        scope: crate::roles::ThrottleScope,
+    },
+    /// A verified abuse report lowered a node's reputation.
+    AbuseReported {
+        reporter: NodeId,
+        offender: NodeId,
+        kind: crate::discovery::ViolationKind,
+    },
+    /// The app fell behind on `RuntimeChannels::events`: `count` events
+    /// were dropped (by `RuntimeConfig::event_channel`'s policy) just
+    /// before this one.
+    EventsDropped { count: u64 },
+    /// The runtime loop panicked and was restarted (`attempt` in a row),
+    /// keeping its state; work in progress at the time may be lost.
+    RuntimeRestarted { attempt: u32, reason: String },
+    /// An event of a hosted identity (see `RuntimeConfig::identities`).
+    Hosted {
+        identity: NodeId,
+        event: Box<ProtocolEvent>,
+    },
 }
 
 // ── RuntimeHandle (app-facing API) ───────────────────────────────────
@@ -308,40 +1307,208 @@ pub enum ProtocolEvent {
 /// Cheap to clone. All methods are non-blocking channel sends.
 #[derive(Clone)]
 pub struct RuntimeHandle {
-    cmd_tx: mpsc::Sender<RuntimeCommand>,
+    cmd_tx: CommandSender,
     local_id: NodeId,
+    /// The node's own identity, then the hosted ones.
+    identities: Vec<NodeId>,
     metrics: ProtocolMetrics,
+    events: events::EventBus,
+}
+
+/// Command channels of a handle. Scoped to a hosted identity, it wraps the
+/// identity's commands in `RuntimeCommand::AsIdentity`.
+///
+/// Bulk-producing commands (see [`RuntimeCommand::is_bulk`]) go on `tx`,
+/// which the loop stops reading while bulk sends are backlogged; the rest
+/// go on `control`, which it always reads. Order holds within each channel;
+/// a control command may overtake bulk ones sent before it.
+#[derive(Clone)]
+struct CommandSender {
+    tx: mpsc::Sender<RuntimeCommand>,
+    control: mpsc::Sender<RuntimeCommand>,
+    identity: Option<NodeId>,
+}
+
+impl CommandSender {
+    async fn send(&self, cmd: RuntimeCommand) -> Result<(), mpsc::error::SendError<RuntimeCommand>> {
+        let cmd = match self.identity {
+            Some(identity) if !cmd.is_node_wide() => RuntimeCommand::AsIdentity {
+                identity,
+                command: Box::new(cmd),
+            },
+            _ => cmd,
+        };
+        if cmd.is_bulk() {
+            self.tx.send(cmd).await
+        } else {
+            self.control.send(cmd).await
+        }
+    }
 }
 
 impl RuntimeHandle {
-    /// This node's identity.
+    /// The identity this handle acts as (the node's own, unless obtained
+    /// from [`identity`](Self::identity)).
     pub fn local_id(&self) -> NodeId {
         self.local_id
     }
 
-    /// Send a chat message to a peer.
+    /// The node's own identity, then those it hosts.
+    pub fn identities(&self) -> &[NodeId] {
+        &self.identities
+    }
+
+    /// A handle acting as `identity`: messages, groups and queries go
+    /// through that identity's state. None if this runtime doesn't run it.
+    pub fn identity(&self, identity: NodeId) -> Option<RuntimeHandle> {
+        let primary = *self.identities.first()?;
+        if !self.identities.contains(&identity) {
+            return None;
+        }
+        Some(RuntimeHandle {
+            cmd_tx: CommandSender {
+                tx: self.cmd_tx.tx.clone(),
+                control: self.cmd_tx.control.clone(),
+                identity: (identity != primary).then_some(identity),
+            },
+            local_id: identity,
+            ..self.clone()
+        })
+    }
+
+    /// Receive the events matching `filter` on a receiver of our own,
+    /// alongside `RuntimeChannels::events` and any other subscriber.
+    /// Events beyond `SUBSCRIBER_BUFFER` unread are lost; dropping the
+    /// receiver unsubscribes.
+    pub fn subscribe_events(&self, filter: EventFilter) -> mpsc::Receiver<ProtocolEvent> {
+        self.events.subscribe(filter)
+    }
+
+    /// Send a chat message to a peer. Returns its envelope id, the key of
+    /// its `StatusChange`s.
     ///
     /// The runtime handles relay selection, encryption, signing,
     /// serialization, transport, and status tracking.
-    pub async fn send_message(&self, to: NodeId, payload: Vec<u8>) -> Result<(), crate::TomProtocolError> {
+    pub async fn send_message(&self, to: NodeId, payload: Vec<u8>) -> Result<String, crate::TomProtocolError> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(RuntimeCommand::SendMessage {
+                to,
+                payload,
+                reply: Some(tx),
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })?;
+        // Dropped unanswered when the envelope couldn't be built (reported
+        // as a ProtocolEvent::Error)
+        rx.await.map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+            reason: "message not sent".into(),
+        })
+    }
+
+    /// Send a chat message whose backup copies expire after `backup_ttl`
+    /// (instead of the holders' default) if the peer can't be reached.
+    pub async fn send_message_with_ttl(
+        &self,
+        to: NodeId,
+        payload: Vec<u8>,
+        backup_ttl: Duration,
+    ) -> Result<(), crate::TomProtocolError> {
         self.cmd_tx
-            .send(RuntimeCommand::SendMessage { to, payload })
+            .send(RuntimeCommand::SendMessageWithTtl {
+                to,
+                payload,
+                backup_ttl,
+            })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
                 reason: "runtime shut down".into(),
             })
     }
 
-    /// Send a read receipt for a message we received.
-    pub async fn send_read_receipt(
+    /// Send a chat message with per-message [`SendOptions`], e.g. over two
+    /// disjoint relays at once for a message that must get through.
+    pub async fn send_message_with_options(
         &self,
         to: NodeId,
-        original_message_id: String,
+        payload: Vec<u8>,
+        options: SendOptions,
     ) -> Result<(), crate::TomProtocolError> {
         self.cmd_tx
-            .send(RuntimeCommand::SendReadReceipt {
+            .send(RuntimeCommand::SendMessageWithOptions {
                 to,
-                original_message_id,
+                payload,
+                options,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Send a typed payload to a peer, tagged with `T`'s content type.
+    /// Returns its envelope id, like [`send_message`](Self::send_message).
+    ///
+    /// The receiver's runtime rejects it unless `T` is in its payload registry.
+    pub async fn send_typed<T: crate::payload::PayloadSchema>(
+        &self,
+        to: NodeId,
+        value: &T,
+    ) -> Result<String, crate::TomProtocolError> {
+        let payload = crate::payload::TypedPayload::encode(value)?.to_bytes()?;
+        self.send_message(to, payload).await
+    }
+
+    /// Send a payload in exactly-once mode. Returns its message id.
+    ///
+    /// The runtime retries until the receiver commits it
+    /// (`ProtocolEvent::ExactlyOnceCommitted`) or it expires
+    /// (`ProtocolEvent::ExactlyOnceExpired`). Survives restarts with a `data_dir`.
+    pub async fn send_exactly_once(&self, to: NodeId, payload: Vec<u8>) -> Option<String> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::SendExactlyOnce {
+                to,
+                payload,
+                reply: tx,
+            })
+            .await;
+        rx.await.ok().flatten()
+    }
+
+    /// Commit an exactly-once message we received (`DeliveredMessage::exactly_once`).
+    ///
+    /// Call this only after the payload's effects are durable: until then the
+    /// message may be delivered again after a restart.
+    pub async fn commit_message(
+        &self,
+        from: NodeId,
+        message_id: impl Into<String>,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::CommitMessage {
+                from,
+                message_id: message_id.into(),
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Send a read receipt for a message we received.
+    pub async fn send_read_receipt(
+        &self,
+        to: NodeId,
+        original_message_id: String,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::SendReadReceipt {
+                to,
+                original_message_id,
             })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
@@ -353,62 +1520,1062 @@ impl RuntimeHandle {
     pub async fn add_peer(&self, node_id: NodeId) {
         let _ = self
             .cmd_tx
-            .send(RuntimeCommand::AddPeer { node_id })
+            .send(RuntimeCommand::AddPeer { node_id })
+            .await;
+    }
+
+    /// Register a peer with its full network address.
+    ///
+    /// Use this when you have the peer's `EndpointAddr` (e.g. from a local
+    /// peer exchange) to enable direct connectivity without relay discovery.
+    pub async fn add_peer_addr(&self, addr: EndpointAddr) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::AddPeerAddr { addr })
+            .await;
+    }
+
+    /// Update topology with peer information.
+    pub async fn upsert_peer(&self, info: PeerInfo) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::UpsertPeer { info })
+            .await;
+    }
+
+    /// Remove a peer from topology.
+    pub async fn remove_peer(&self, node_id: NodeId) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::RemovePeer { node_id })
+            .await;
+    }
+
+    /// Pin routing for a peer — always direct, always through one relay, or
+    /// never relay its traffic — or with `None`, go back to normal routing.
+    pub async fn set_routing_override(
+        &self,
+        node_id: NodeId,
+        policy: Option<crate::relay::RoutingOverride>,
+    ) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::SetRoutingOverride { node_id, policy })
+            .await;
+    }
+
+    /// Advertise our availability to peers (online/away/busy/custom).
+    pub async fn set_presence(&self, presence: crate::discovery::Presence) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::SetPresence { presence })
+            .await;
+    }
+
+    /// Report a node for a security violation. `evidence` is the offending
+    /// envelope as received; reports whose evidence doesn't back the claim
+    /// are dropped. Peers lower the offender's score — nobody is banned.
+    pub async fn report_abuse(
+        &self,
+        offender: NodeId,
+        kind: crate::discovery::ViolationKind,
+        evidence: Vec<u8>,
+    ) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::ReportAbuse {
+                offender,
+                kind,
+                evidence,
+            })
+            .await;
+    }
+
+    /// Send `payload` to `to` on the real-time lane (see [`crate::realtime`]):
+    /// one QUIC datagram, unreliable and unordered, never retried or backed
+    /// up. For audio frames or game state. The peer gets `RealtimeReceived`.
+    pub async fn send_realtime(&self, to: NodeId, payload: Vec<u8>) -> Result<(), crate::TomProtocolError> {
+        let max = crate::realtime::MAX_REALTIME_PAYLOAD;
+        if payload.len() > max {
+            return Err(crate::TomProtocolError::PayloadTooLarge {
+                size: payload.len(),
+                max,
+            });
+        }
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::SendRealtime { to, payload })
+            .await;
+        Ok(())
+    }
+
+    /// Real-time lane stats with `peer`: frames sent and received, loss,
+    /// reordering, jitter. None before any traffic.
+    pub async fn realtime_stats(&self, peer: NodeId) -> Option<crate::realtime::RealtimeStats> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetRealtimeStats { peer, reply: tx })
+            .await;
+        rx.await.ok().flatten()
+    }
+
+    /// Protocol version and features `peer` announced in its capability
+    /// handshake (exchanged on first contact). None until it answered.
+    pub async fn peer_capabilities(&self, peer: NodeId) -> Option<crate::capabilities::PeerCapabilities> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetPeerCapabilities { peer, reply: tx })
+            .await;
+        rx.await.ok().flatten()
+    }
+
+    /// Signal typing to a conversation peer. Safe to call on every keystroke:
+    /// signals beyond the rate limit are dropped.
+    pub async fn send_typing(&self, to: NodeId, typing: bool) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::SendTyping { to, typing })
+            .await;
+    }
+
+    /// Send a chat message to a user: one copy to each of its linked
+    /// devices, or to `user` itself when we know none.
+    pub async fn send_to_user(&self, user: NodeId, payload: Vec<u8>) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::SendToUser { user, payload })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Devices linked to a user, from their announced certificates. Empty
+    /// if none are known or the runtime has stopped.
+    pub async fn linked_devices(&self, user: NodeId) -> Vec<NodeId> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetLinkedDevices { user, reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// Revoke a lost device: peers stop fanning messages out to it, and it
+    /// is kicked from groups we admin. The revocation is signed offline
+    /// with the user key ([`crate::discovery::DeviceRevocation::new`]).
+    pub async fn revoke_device(
+        &self,
+        revocation: crate::discovery::DeviceRevocation,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::RevokeDevice { revocation })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Find peers by username (case-insensitive). The owner — the first
+    /// claimant this node saw — comes first; more entries mean a conflict.
+    /// Empty if nobody claimed it or the runtime has stopped.
+    pub async fn resolve_username(&self, name: &str) -> Vec<NodeId> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::ResolveUsername {
+                name: name.to_string(),
+                reply: tx,
+            })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// Active ephemeral subnets (clusters of nodes that talk a lot).
+    /// Messages to members of our subnet go direct; others prefer relays
+    /// inside a subnet. Empty if the runtime has stopped.
+    pub async fn subnets(&self) -> Vec<crate::discovery::SubnetInfo> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetSubnets { reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// The subnet `node_id` belongs to, if any.
+    pub async fn subnet_of(&self, node_id: NodeId) -> Option<crate::discovery::SubnetInfo> {
+        self.subnets()
+            .await
+            .into_iter()
+            .find(|s| s.members.contains(&node_id))
+    }
+
+    /// The subnet this node belongs to, if any.
+    pub async fn local_subnet(&self) -> Option<crate::discovery::SubnetInfo> {
+        self.subnet_of(self.local_id).await
+    }
+
+    /// Subnet clustering totals, for tuning `RuntimeConfig::subnet`.
+    /// Per-subnet volume, churn and age are on [`SubnetInfo`](crate::discovery::SubnetInfo).
+    pub async fn subnet_stats(&self) -> crate::discovery::SubnetStats {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetSubnetStats { reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// The network as this node sees it, for rendering live network maps
+    /// (see [`TopologySnapshot::to_dot`](crate::relay::TopologySnapshot::to_dot)).
+    pub async fn topology_snapshot(&self) -> crate::relay::TopologySnapshot {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetTopologySnapshot { reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// Trace the route to `target`. Returns the trace id; the signed path
+    /// comes back as `ProtocolEvent::RouteTraced`.
+    pub async fn trace_route(&self, target: NodeId) -> Option<String> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::TraceRoute { target, reply: tx })
+            .await;
+        rx.await.ok().flatten()
+    }
+
+    /// Send `payload` to `to` and wait for its response, at most `timeout`
+    /// (capped at `rpc::MAX_REQUEST_TIMEOUT_MS`).
+    pub async fn request(
+        &self,
+        to: NodeId,
+        payload: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<crate::rpc::Response, crate::TomProtocolError> {
+        let shut_down = || crate::TomProtocolError::RequestFailed {
+            reason: "runtime shut down".into(),
+        };
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(RuntimeCommand::Request {
+                to,
+                payload,
+                timeout,
+                reply: tx,
+            })
+            .await
+            .map_err(|_| shut_down())?;
+        rx.await.map_err(|_| shut_down())?
+    }
+
+    /// Send the file at `path` to `to`. Returns the transfer id; progress
+    /// and the outcome arrive as `TransferProgress`, then `TransferComplete`
+    /// or `TransferFailed`. Interrupted transfers resume where they stopped.
+    pub async fn send_file(
+        &self,
+        to: NodeId,
+        path: impl Into<PathBuf>,
+    ) -> Result<String, crate::TomProtocolError> {
+        let shut_down = || crate::TomProtocolError::Transfer {
+            reason: "runtime shut down".into(),
+        };
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(RuntimeCommand::SendFile {
+                to,
+                path: path.into(),
+                reply: tx,
+            })
+            .await
+            .map_err(|_| shut_down())?;
+        rx.await.map_err(|_| shut_down())?
+    }
+
+    /// Answer a request received as `ProtocolEvent::RequestReceived`.
+    pub async fn respond(&self, to: NodeId, request_id: String, payload: Vec<u8>) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::Respond {
+                to,
+                request_id,
+                payload,
+            })
+            .await;
+    }
+
+    /// Get currently connected peers.
+    pub async fn connected_peers(&self) -> Vec<NodeId> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetConnectedPeers { reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// Transport traffic totals (messages/bytes each way) over all peers.
+    #[cfg(feature = "native")]
+    pub async fn transport_stats(&self) -> tom_transport::NodeStats {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetTransportStats { reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// Transport traffic with one peer: messages/bytes each way, last
+    /// activity, path and RTT. None if nothing was exchanged yet.
+    #[cfg(feature = "native")]
+    pub async fn peer_transport_stats(&self, node_id: NodeId) -> Option<tom_transport::PeerStats> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(RuntimeCommand::GetPeerTransportStats { node_id, reply: tx })
+            .await
+            .ok()?;
+        rx.await.ok().flatten()
+    }
+
+    // ── Pairing methods ────────────────────────────
+
+    /// Our pairing info — encode it (`PairingInfo::encode`) for a QR code
+    /// or a pasteable string. None if the runtime has stopped.
+    pub async fn pairing_info(&self) -> Option<crate::pairing::PairingInfo> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetPairingInfo { reply: tx })
+            .await;
+        rx.await.ok()
+    }
+
+    /// Add a peer from a pairing string (`TOM1:...`) scanned or pasted by the user.
+    pub async fn pair_with(&self, encoded: &str) -> Result<NodeId, crate::TomProtocolError> {
+        let info = crate::pairing::PairingInfo::decode(encoded)?;
+        self.add_peer_addr(info.to_endpoint_addr()).await;
+        Ok(info.node_id)
+    }
+
+    /// Create a short numeric code for someone to redeem with
+    /// [`redeem_pairing_code`](Self::redeem_pairing_code). Waits for the DHT
+    /// publish; None if the DHT is disabled or the publish failed.
+    pub async fn create_pairing_code(&self) -> Option<crate::pairing::PairingCode> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::CreatePairingCode { reply: tx })
+            .await;
+        rx.await.ok().flatten()
+    }
+
+    /// Redeem a code read out by another user: fetch their pairing record
+    /// from the DHT, verify it and add them as a peer.
+    pub async fn redeem_pairing_code(&self, code: &str) -> Result<NodeId, crate::TomProtocolError> {
+        let code = crate::pairing::RendezvousCode::parse(code)?;
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::RedeemPairingCode { code, reply: tx })
+            .await;
+        rx.await
+            .ok()
+            .flatten()
+            .ok_or_else(|| crate::TomProtocolError::InvalidPairing {
+                reason: "no valid record for this code".into(),
+            })
+    }
+
+    // ── Contact methods ────────────────────────────
+
+    /// Send a contact card to a peer. It arrives as a typed chat payload and
+    /// surfaces as [`ProtocolEvent::ContactCardReceived`].
+    pub async fn share_contact(
+        &self,
+        to: NodeId,
+        card: &crate::contact::ContactCard,
+    ) -> Result<(), crate::TomProtocolError> {
+        card.validate()?;
+        self.send_typed(to, card).await.map(|_| ())
+    }
+
+    /// Post a contact card into a group (as `tom-contact:` text).
+    pub async fn share_contact_in_group(
+        &self,
+        group_id: GroupId,
+        card: &crate::contact::ContactCard,
+    ) -> Result<(), crate::TomProtocolError> {
+        card.validate()?;
+        self.send_group_message(group_id, card.encode()?).await
+    }
+
+    /// Import a received contact card into the roster (persisted with a
+    /// `data_dir`) and make the node known to the topology.
+    ///
+    /// Cards whose fingerprint doesn't match their NodeId are rejected.
+    pub async fn import_contact(
+        &self,
+        card: crate::contact::ContactCard,
+    ) -> Result<(), crate::TomProtocolError> {
+        card.validate()?;
+        self.cmd_tx
+            .send(RuntimeCommand::ImportContact { card })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Our contact roster.
+    pub async fn contacts(&self) -> Vec<crate::contact::Contact> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetContacts { reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// Remove a contact (lifts any block).
+    pub async fn remove_contact(&self, node_id: NodeId) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::RemoveContact { node_id })
+            .await;
+    }
+
+    /// Block or unblock a node, contact or not. Blocked nodes' envelopes
+    /// are dropped on arrival (see `MetricsSnapshot::envelopes_blocked`).
+    pub async fn set_contact_blocked(&self, node_id: NodeId, blocked: bool) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::SetContactBlocked { node_id, blocked })
+            .await;
+    }
+
+    /// Set or clear a contact's local alias.
+    pub async fn set_contact_alias(&self, node_id: NodeId, alias: Option<String>) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::SetContactAlias { node_id, alias })
+            .await;
+    }
+
+    /// Mark a contact verified after comparing fingerprints (or unverified).
+    pub async fn set_contact_trust(&self, node_id: NodeId, trust: crate::contact::TrustLevel) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::SetContactTrust { node_id, trust })
+            .await;
+    }
+
+    /// Choose how our conversation with a contact is encrypted (None falls
+    /// back to `RuntimeConfig::encryption`). The contact learns it in a
+    /// handshake and both sides use the stronger of the two choices; see
+    /// [`ProtocolEvent::ConversationEncryptionChanged`].
+    pub async fn set_conversation_encryption(
+        &self,
+        node_id: NodeId,
+        mode: Option<crate::contact::EncryptionMode>,
+    ) {
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::SetConversationEncryption { node_id, mode })
+            .await;
+    }
+
+    // ── Group methods ──────────────────────────────
+
+    /// Create a new group. hub_relay_id will host the group state.
+    pub async fn create_group(
+        &self,
+        name: String,
+        hub_relay_id: NodeId,
+        initial_members: Vec<NodeId>,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::CreateGroup {
+                name,
+                hub_relay_id,
+                initial_members,
+                invite_only: false,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Create an invite-only group. Only explicitly invited members can join.
+    pub async fn create_group_invite_only(
+        &self,
+        name: String,
+        hub_relay_id: NodeId,
+        initial_members: Vec<NodeId>,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::CreateGroup {
+                name,
+                hub_relay_id,
+                initial_members,
+                invite_only: true,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Accept a pending group invitation.
+    pub async fn accept_invite(&self, group_id: GroupId) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::AcceptInvite { group_id })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Decline a pending group invitation.
+    pub async fn decline_invite(&self, group_id: GroupId) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::DeclineInvite { group_id })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Leave a group.
+    pub async fn leave_group(&self, group_id: GroupId) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::LeaveGroup { group_id })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Send a text message to a group.
+    pub async fn send_group_message(
+        &self,
+        group_id: GroupId,
+        text: String,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::SendGroupMessage { group_id, text })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Reply to a group message (`reply_to` is its `message_id`).
+    pub async fn send_group_reply(
+        &self,
+        group_id: GroupId,
+        reply_to: String,
+        text: String,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::SendGroupReply {
+                group_id,
+                reply_to,
+                text,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Send a group message mentioning `mentions` (must be group members).
+    pub async fn send_group_mention(
+        &self,
+        group_id: GroupId,
+        text: String,
+        mentions: Vec<NodeId>,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::SendGroupMention {
+                group_id,
+                text,
+                mentions,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Send a group message the hub delivers at `deliver_at` (unix ms,
+    /// at most 12 hours ahead). Returns its id, None if rejected.
+    pub async fn schedule_group_message(
+        &self,
+        group_id: GroupId,
+        text: String,
+        deliver_at: u64,
+    ) -> Option<String> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::ScheduleGroupMessage {
+                group_id,
+                text,
+                deliver_at,
+                reply: tx,
+            })
+            .await;
+        rx.await.ok().flatten()
+    }
+
+    /// Cancel one of our scheduled group messages (no-op once delivered).
+    pub async fn cancel_scheduled_group_message(
+        &self,
+        group_id: GroupId,
+        message_id: String,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::CancelScheduledGroupMessage {
+                group_id,
+                message_id,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Get all groups we belong to.
+    pub async fn groups(&self) -> Vec<GroupInfo> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetGroups { reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// Kick a member from a group (admin only).
+    pub async fn kick_member(
+        &self,
+        group_id: GroupId,
+        target_id: NodeId,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::KickMember { group_id, target_id })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Change a member's role in a group (admin only).
+    pub async fn update_member_role(
+        &self,
+        group_id: GroupId,
+        target_id: NodeId,
+        new_role: crate::group::GroupMemberRole,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::UpdateMemberRole {
+                group_id,
+                target_id,
+                new_role,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Invite a member to an existing group (admin only).
+    pub async fn invite_member(
+        &self,
+        group_id: GroupId,
+        target_id: NodeId,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::InviteMember { group_id, target_id })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Ban a node from a group, kicking it if it's a member (admin only).
+    ///
+    /// `duration_ms: None` bans permanently.
+    pub async fn ban_member(
+        &self,
+        group_id: GroupId,
+        target_id: NodeId,
+        duration_ms: Option<u64>,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::BanMember {
+                group_id,
+                target_id,
+                duration_ms,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Lift a ban so the node can join again (admin only).
+    pub async fn unban_member(
+        &self,
+        group_id: GroupId,
+        target_id: NodeId,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::UnbanMember { group_id, target_id })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Rename a group and/or change its description and avatar (admin only).
+    pub async fn update_group_metadata(
+        &self,
+        group_id: GroupId,
+        name: String,
+        description: String,
+        avatar_hash: Option<[u8; 32]>,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::UpdateGroupMetadata {
+                group_id,
+                name,
+                description,
+                avatar_hash,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Set or clear the disappearing-message timer of a group (admin only).
+    ///
+    /// The timer must lie within `MIN_MESSAGE_TTL_MS..=MAX_MESSAGE_TTL_MS`
+    /// (at most 24h). Pass `None` to fall back to the 24h protocol lifespan.
+    pub async fn set_group_message_ttl(
+        &self,
+        group_id: GroupId,
+        ttl_ms: Option<u64>,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::SetGroupMessageTtl { group_id, ttl_ms })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Replace a group's rate limit / history size settings (admin only).
+    ///
+    /// `None` fields fall back to the protocol defaults. `anonymous` hides
+    /// authors from outsiders and the hub's stored history; in encrypted
+    /// groups members can still tell who posted (see
+    /// [`GroupSettings::anonymous`](crate::group::GroupSettings::anonymous)).
+    pub async fn update_group_settings(
+        &self,
+        group_id: GroupId,
+        settings: crate::group::GroupSettings,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::UpdateGroupSettings { group_id, settings })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Request older messages for a group (scroll-back past the sync window).
+    ///
+    /// Pass `before = u64::MAX` for the most recent page, then the lowest
+    /// `seq` received to go further back. The page arrives as
+    /// [`ProtocolEvent::GroupHistoryPage`].
+    pub async fn fetch_group_history(
+        &self,
+        group_id: GroupId,
+        before: u64,
+        limit: u32,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::FetchGroupHistory {
+                group_id,
+                before,
+                limit,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Send a read receipt for a group message we've displayed.
+    pub async fn mark_group_message_read(
+        &self,
+        group_id: GroupId,
+        sender_id: NodeId,
+        message_id: String,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::MarkGroupMessageRead {
+                group_id,
+                sender_id,
+                message_id,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Snapshot of the backups this node holds (entries, bytes, per-recipient
+    /// counts, replication health). None if the runtime has stopped.
+    pub async fn backup_stats(&self) -> Option<crate::backup::BackupStats> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetBackupStats { reply: tx })
             .await;
+        rx.await.ok()
     }
 
-    /// Register a peer with its full network address.
+    /// Messages composed while offline that wait for connectivity, oldest
+    /// first. Empty if the runtime has stopped.
+    pub async fn queued_messages(&self) -> Vec<crate::outbox::QueuedMessage> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetQueuedMessages { reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// Who has received / read one of our group messages so far.
     ///
-    /// Use this when you have the peer's `EndpointAddr` (e.g. from a local
-    /// peer exchange) to enable direct connectivity without relay discovery.
-    pub async fn add_peer_addr(&self, addr: EndpointAddr) {
+    /// None until the hub reported a first receipt for it.
+    pub async fn group_delivery_status(
+        &self,
+        group_id: GroupId,
+        message_id: String,
+    ) -> Option<GroupDeliveryStatus> {
+        let (tx, rx) = oneshot::channel();
         let _ = self
             .cmd_tx
-            .send(RuntimeCommand::AddPeerAddr { addr })
+            .send(RuntimeCommand::GetGroupDeliveryStatus {
+                group_id,
+                message_id,
+                reply: tx,
+            })
             .await;
+        rx.await.ok().flatten()
     }
 
-    /// Update topology with peer information.
-    pub async fn upsert_peer(&self, info: PeerInfo) {
+    /// React to a group message with an emoji.
+    pub async fn add_group_reaction(
+        &self,
+        group_id: GroupId,
+        message_id: String,
+        emoji: String,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.react_to_group_message(group_id, message_id, emoji, true).await
+    }
+
+    /// Withdraw one of our reactions on a group message.
+    pub async fn remove_group_reaction(
+        &self,
+        group_id: GroupId,
+        message_id: String,
+        emoji: String,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.react_to_group_message(group_id, message_id, emoji, false).await
+    }
+
+    async fn react_to_group_message(
+        &self,
+        group_id: GroupId,
+        message_id: String,
+        emoji: String,
+        add: bool,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::ReactToGroupMessage {
+                group_id,
+                message_id,
+                emoji,
+                add,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Current reaction counts on a group message, most popular first.
+    pub async fn group_reactions(
+        &self,
+        group_id: GroupId,
+        message_id: String,
+    ) -> Vec<(String, usize)> {
+        let (tx, rx) = oneshot::channel();
         let _ = self
             .cmd_tx
-            .send(RuntimeCommand::UpsertPeer { info })
+            .send(RuntimeCommand::GetGroupReactions {
+                group_id,
+                message_id,
+                reply: tx,
+            })
             .await;
+        rx.await.unwrap_or_default()
     }
 
-    /// Remove a peer from topology.
-    pub async fn remove_peer(&self, node_id: NodeId) {
+    /// Open a poll with 2–10 options. Tallies arrive as
+    /// [`ProtocolEvent::GroupPollUpdated`].
+    ///
+    /// Returns the poll id, or None if we're not in the group.
+    pub async fn create_group_poll(
+        &self,
+        group_id: GroupId,
+        question: String,
+        options: Vec<String>,
+    ) -> Option<String> {
+        let (tx, rx) = oneshot::channel();
         let _ = self
             .cmd_tx
-            .send(RuntimeCommand::RemovePeer { node_id })
+            .send(RuntimeCommand::CreateGroupPoll {
+                group_id,
+                question,
+                options,
+                reply: tx,
+            })
             .await;
+        rx.await.ok().flatten()
     }
 
-    /// Get currently connected peers.
-    pub async fn connected_peers(&self) -> Vec<NodeId> {
+    /// Vote for option `option` (0-based) of a group poll. One vote per member.
+    pub async fn vote_group_poll(
+        &self,
+        group_id: GroupId,
+        poll_id: String,
+        option: u32,
+    ) -> Result<(), crate::TomProtocolError> {
+        self.cmd_tx
+            .send(RuntimeCommand::VoteGroupPoll {
+                group_id,
+                poll_id,
+                option,
+            })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Set mute / mentions-only preferences for a group (local only).
+    ///
+    /// Returns false if we're not a member or the runtime shut down.
+    pub async fn set_group_notification_prefs(
+        &self,
+        group_id: GroupId,
+        prefs: crate::group::GroupNotificationPrefs,
+    ) -> bool {
         let (tx, rx) = oneshot::channel();
         let _ = self
             .cmd_tx
-            .send(RuntimeCommand::GetConnectedPeers { reply: tx })
+            .send(RuntimeCommand::SetGroupNotificationPrefs {
+                group_id,
+                prefs,
+                reply: tx,
+            })
+            .await;
+        rx.await.unwrap_or(false)
+    }
+
+    /// Mute a group until `until` (unix ms), or indefinitely with `None`.
+    ///
+    /// Keeps the group's mentions-only setting.
+    pub async fn mute_group(&self, group_id: GroupId, until: Option<u64>) -> bool {
+        let mut prefs = self.group_notification_prefs(group_id.clone()).await;
+        prefs.muted_until = Some(until.unwrap_or(u64::MAX));
+        self.set_group_notification_prefs(group_id, prefs).await
+    }
+
+    /// Lift a group mute (mentions-only is kept).
+    pub async fn unmute_group(&self, group_id: GroupId) -> bool {
+        let mut prefs = self.group_notification_prefs(group_id.clone()).await;
+        prefs.muted_until = None;
+        self.set_group_notification_prefs(group_id, prefs).await
+    }
+
+    /// Current notification preferences for a group (defaults if unset).
+    pub async fn group_notification_prefs(
+        &self,
+        group_id: GroupId,
+    ) -> crate::group::GroupNotificationPrefs {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetGroupNotificationPrefs { group_id, reply: tx })
             .await;
         rx.await.unwrap_or_default()
     }
 
-    // ── Group methods ──────────────────────────────
+    /// Create a hubless mesh group with up to 7 other members.
+    ///
+    /// Returns None if the runtime rejected it (name, member cap) or shut down.
+    /// Use the regular group methods (send, leave, invite, kick) on its id.
+    pub async fn create_mesh_group(
+        &self,
+        name: impl Into<String>,
+        members: Vec<NodeId>,
+    ) -> Option<GroupId> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::CreateMeshGroup {
+                name: name.into(),
+                members,
+                reply: tx,
+            })
+            .await;
+        rx.await.ok().flatten()
+    }
 
-    /// Create a new group. hub_relay_id will host the group state.
-    pub async fn create_group(
+    /// List the mesh groups we belong to.
+    pub async fn mesh_groups(&self) -> Vec<crate::group::MeshGroup> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetMeshGroups { reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// Open a shared document replicated with `peers`.
+    ///
+    /// Every peer must open the same `doc_id` listing us for writes to flow.
+    pub async fn open_shared_doc(
         &self,
-        name: String,
-        hub_relay_id: NodeId,
-        initial_members: Vec<NodeId>,
+        doc_id: impl Into<String>,
+        peers: Vec<NodeId>,
     ) -> Result<(), crate::TomProtocolError> {
         self.cmd_tx
-            .send(RuntimeCommand::CreateGroup {
-                name,
-                hub_relay_id,
-                initial_members,
-                invite_only: false,
+            .send(RuntimeCommand::OpenSharedDoc {
+                doc_id: doc_id.into(),
+                peers,
             })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
@@ -416,19 +2583,14 @@ impl RuntimeHandle {
             })
     }
 
-    /// Create an invite-only group. Only explicitly invited members can join.
-    pub async fn create_group_invite_only(
+    /// Stop replicating a shared document.
+    pub async fn close_shared_doc(
         &self,
-        name: String,
-        hub_relay_id: NodeId,
-        initial_members: Vec<NodeId>,
+        doc_id: impl Into<String>,
     ) -> Result<(), crate::TomProtocolError> {
         self.cmd_tx
-            .send(RuntimeCommand::CreateGroup {
-                name,
-                hub_relay_id,
-                initial_members,
-                invite_only: true,
+            .send(RuntimeCommand::CloseSharedDoc {
+                doc_id: doc_id.into(),
             })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
@@ -436,107 +2598,188 @@ impl RuntimeHandle {
             })
     }
 
-    /// Accept a pending group invitation.
-    pub async fn accept_invite(&self, group_id: GroupId) -> Result<(), crate::TomProtocolError> {
+    /// Set a key in a shared document (`None` deletes it).
+    pub async fn set_shared_value(
+        &self,
+        doc_id: impl Into<String>,
+        key: impl Into<String>,
+        value: Option<Vec<u8>>,
+    ) -> Result<(), crate::TomProtocolError> {
         self.cmd_tx
-            .send(RuntimeCommand::AcceptInvite { group_id })
+            .send(RuntimeCommand::SetSharedValue {
+                doc_id: doc_id.into(),
+                key: key.into(),
+                value,
+            })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
                 reason: "runtime shut down".into(),
             })
     }
 
-    /// Decline a pending group invitation.
-    pub async fn decline_invite(&self, group_id: GroupId) -> Result<(), crate::TomProtocolError> {
+    /// Get the live entries of a shared document (None if not open).
+    pub async fn shared_doc(&self, doc_id: impl Into<String>) -> Option<Vec<(String, Vec<u8>)>> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetSharedDoc {
+                doc_id: doc_id.into(),
+                reply: tx,
+            })
+            .await;
+        rx.await.ok().flatten()
+    }
+
+    /// Join a pub/sub channel.
+    ///
+    /// Channels with a `secret` map to a different gossip topic than the bare
+    /// name, and publications must prove knowledge of the secret.
+    pub async fn join_channel(
+        &self,
+        name: impl Into<String>,
+        secret: Option<Vec<u8>>,
+    ) -> Result<(), crate::TomProtocolError> {
+        let name = name.into();
+        crate::pubsub::validate_channel_name(&name)?;
         self.cmd_tx
-            .send(RuntimeCommand::DeclineInvite { group_id })
+            .send(RuntimeCommand::JoinChannel { name, secret })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
                 reason: "runtime shut down".into(),
             })
     }
 
-    /// Leave a group.
-    pub async fn leave_group(&self, group_id: GroupId) -> Result<(), crate::TomProtocolError> {
+    /// Leave a pub/sub channel.
+    pub async fn leave_channel(&self, name: impl Into<String>) -> Result<(), crate::TomProtocolError> {
         self.cmd_tx
-            .send(RuntimeCommand::LeaveGroup { group_id })
+            .send(RuntimeCommand::LeaveChannel { name: name.into() })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
                 reason: "runtime shut down".into(),
             })
     }
 
-    /// Send a text message to a group.
-    pub async fn send_group_message(
+    /// Publish raw bytes on a joined channel (max 4 KB).
+    pub async fn publish(
         &self,
-        group_id: GroupId,
-        text: String,
+        name: impl Into<String>,
+        payload: Vec<u8>,
     ) -> Result<(), crate::TomProtocolError> {
+        if payload.len() > crate::pubsub::MAX_CHANNEL_PAYLOAD {
+            return Err(crate::TomProtocolError::InvalidChannel {
+                reason: "payload too large".into(),
+            });
+        }
         self.cmd_tx
-            .send(RuntimeCommand::SendGroupMessage { group_id, text })
+            .send(RuntimeCommand::PublishChannel {
+                name: name.into(),
+                payload,
+            })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
                 reason: "runtime shut down".into(),
             })
     }
 
-    /// Get all groups we belong to.
-    pub async fn groups(&self) -> Vec<GroupInfo> {
+    /// Publish a typed payload on a joined channel.
+    pub async fn publish_typed<T: crate::payload::PayloadSchema>(
+        &self,
+        name: impl Into<String>,
+        value: &T,
+    ) -> Result<(), crate::TomProtocolError> {
+        let payload = crate::payload::TypedPayload::encode(value)?.to_bytes()?;
+        self.publish(name, payload).await
+    }
+
+    /// Create an invite token for a group we administer (24h max TTL).
+    ///
+    /// Returns `None` if we're not an admin of the group.
+    pub async fn create_invite_token(&self, group_id: GroupId, ttl_ms: u64) -> Option<String> {
         let (tx, rx) = oneshot::channel();
         let _ = self
             .cmd_tx
-            .send(RuntimeCommand::GetGroups { reply: tx })
+            .send(RuntimeCommand::CreateInviteToken {
+                group_id,
+                ttl_ms,
+                reply: tx,
+            })
             .await;
-        rx.await.unwrap_or_default()
+        rx.await.ok().flatten()
     }
 
-    /// Kick a member from a group (admin only).
-    pub async fn kick_member(
+    /// Join a group using an encoded invite token.
+    ///
+    /// The token is decoded and its signature/expiry checked before anything
+    /// is sent, so malformed or stale codes fail fast.
+    pub async fn join_group_by_token(&self, token: &str) -> Result<(), crate::TomProtocolError> {
+        let token = crate::group::GroupInviteToken::decode(token)?;
+        token.validate(crate::types::now_ms())?;
+        self.cmd_tx
+            .send(RuntimeCommand::JoinGroupByToken { token })
+            .await
+            .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
+                reason: "runtime shut down".into(),
+            })
+    }
+
+    /// Ask to join a group; an admin must approve before we're added.
+    ///
+    /// Approval arrives as [`ProtocolEvent::GroupJoined`], denial as
+    /// [`ProtocolEvent::GroupJoinDenied`].
+    pub async fn request_join_group(
         &self,
         group_id: GroupId,
-        target_id: NodeId,
+        hub_id: NodeId,
     ) -> Result<(), crate::TomProtocolError> {
         self.cmd_tx
-            .send(RuntimeCommand::KickMember { group_id, target_id })
+            .send(RuntimeCommand::RequestJoinGroup { group_id, hub_id })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
                 reason: "runtime shut down".into(),
             })
     }
 
-    /// Change a member's role in a group (admin only).
-    pub async fn update_member_role(
+    /// Approve a pending join request (admin only).
+    pub async fn approve_join_request(
         &self,
         group_id: GroupId,
-        target_id: NodeId,
-        new_role: crate::group::GroupMemberRole,
+        requester: NodeId,
     ) -> Result<(), crate::TomProtocolError> {
         self.cmd_tx
-            .send(RuntimeCommand::UpdateMemberRole {
-                group_id,
-                target_id,
-                new_role,
-            })
+            .send(RuntimeCommand::ApproveJoinRequest { group_id, requester })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
                 reason: "runtime shut down".into(),
             })
     }
 
-    /// Invite a member to an existing group (admin only).
-    pub async fn invite_member(
+    /// Deny a pending join request (admin only).
+    pub async fn deny_join_request(
         &self,
         group_id: GroupId,
-        target_id: NodeId,
+        requester: NodeId,
     ) -> Result<(), crate::TomProtocolError> {
         self.cmd_tx
-            .send(RuntimeCommand::InviteMember { group_id, target_id })
+            .send(RuntimeCommand::DenyJoinRequest { group_id, requester })
             .await
             .map_err(|_| crate::TomProtocolError::InvalidEnvelope {
                 reason: "runtime shut down".into(),
             })
     }
 
+    /// Join requests awaiting a decision in a group we administer, oldest first.
+    pub async fn pending_join_requests(
+        &self,
+        group_id: GroupId,
+    ) -> Vec<crate::group::GroupJoinRequest> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetPendingJoinRequests { group_id, reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
     /// Get pending group invitations.
     pub async fn pending_invites(&self) -> Vec<GroupInvite> {
         let (tx, rx) = oneshot::channel();
@@ -574,9 +2817,47 @@ impl RuntimeHandle {
         rx.await.unwrap_or_default()
     }
 
-    /// Graceful shutdown.
-    pub async fn shutdown(&self) {
-        let _ = self.cmd_tx.send(RuntimeCommand::Shutdown).await;
+    // ── Liveness queries ────────────────────────────
+
+    /// Liveness, last seen, discovery source, role and path of a peer.
+    /// None if the peer is unknown or the runtime has stopped.
+    pub async fn peer_liveness(&self, node_id: NodeId) -> Option<PeerLiveness> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetPeerLiveness { node_id, reply: tx })
+            .await;
+        rx.await.ok().flatten()
+    }
+
+    /// [`peer_liveness`](Self::peer_liveness) for every known peer.
+    pub async fn all_peers(&self) -> Vec<PeerLiveness> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .cmd_tx
+            .send(RuntimeCommand::GetAllPeers { reply: tx })
+            .await;
+        rx.await.unwrap_or_default()
+    }
+
+    /// Go quiet (e.g. app backgrounded): no announces, hub heartbeats,
+    /// shadow pings or backup work until `resume`. Connections stay open.
+    pub async fn pause(&self) {
+        let _ = self.cmd_tx.send(RuntimeCommand::Pause).await;
+    }
+
+    /// Resume after `pause`.
+    pub async fn resume(&self) {
+        let _ = self.cmd_tx.send(RuntimeCommand::Resume).await;
+    }
+
+    /// Graceful shutdown: stop taking commands, flush pending sends and
+    /// hand off backups (within `shutdown_drain_timeout`), announce our
+    /// departure, then close the node. None if the runtime already stopped.
+    pub async fn shutdown(&self) -> Option<ShutdownReport> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx.send(RuntimeCommand::Shutdown { reply: tx }).await.ok()?;
+        rx.await.ok()
     }
 
     /// Get a snapshot of all protocol metrics.
@@ -595,15 +2876,18 @@ pub struct RuntimeChannels {
     pub messages: mpsc::Receiver<DeliveredMessage>,
     /// Receive status changes for sent messages.
     pub status_changes: mpsc::Receiver<StatusChange>,
-    /// Receive protocol-level events.
+    /// Receive protocol-level events (all of them; see
+    /// `RuntimeHandle::subscribe_events` for more, filtered receivers).
     pub events: mpsc::Receiver<ProtocolEvent>,
 }
 
 // ── ProtocolRuntime ──────────────────────────────────────────────────
 
 /// The protocol runtime — spawn it and communicate via channels.
+#[cfg(feature = "native")]
 pub struct ProtocolRuntime;
 
+#[cfg(feature = "native")]
 impl ProtocolRuntime {
     /// Create and start the protocol runtime.
     ///
@@ -616,14 +2900,17 @@ impl ProtocolRuntime {
         // Shared metrics (Arc-backed, safe to clone)
         let metrics = ProtocolMetrics::new();
 
-        // Command channel (app -> runtime)
+        // Command channels (app -> runtime): bulk sends, and the rest
         let (cmd_tx, cmd_rx) = mpsc::channel::<RuntimeCommand>(512);
+        let (control_tx, control_rx) = mpsc::channel::<RuntimeCommand>(512);
 
-        // Event channels (runtime -> app)
-        // Very large buffers to prevent blocking even during burst phases
-        let (msg_tx, msg_rx) = mpsc::channel::<DeliveredMessage>(16384);
-        let (status_tx, status_rx) = mpsc::channel::<StatusChange>(4096);
-        let (event_tx, event_rx) = mpsc::channel::<ProtocolEvent>(4096);
+        // Event channels (runtime -> app), each with its overflow policy
+        let (msg_tx, msg_rx) = channel::app_channel::<DeliveredMessage>(config.message_channel, None);
+        let (status_tx, status_rx) = channel::app_channel::<StatusChange>(config.status_channel, None);
+        let (event_tx, event_rx) = channel::app_channel(
+            config.event_channel,
+            Some(|count| ProtocolEvent::EventsDropped { count }),
+        );
 
         // Subscribe to path events before moving node
         let path_rx = node.path_events();
@@ -632,28 +2919,62 @@ impl ProtocolRuntime {
         let gossip = node.gossip().clone();
         let gossip_bootstrap_peers = config.gossip_bootstrap_peers.clone();
 
-        // Create pure protocol state
+        // Create pure protocol state, one per identity
+        let hosted: Vec<RuntimeState> = config
+            .identities
+            .iter()
+            .map(|identity| {
+                RuntimeState::new(identity.node_id(), identity.secret_seed, identity.state_config(&config))
+            })
+            .collect();
+        let identities: Vec<NodeId> = std::iter::once(local_id)
+            .chain(config.identities.iter().map(HostedIdentity::node_id))
+            .collect();
+        let admin_socket = config.admin_socket.clone();
         let state = RuntimeState::new(local_id, secret_seed, config);
 
+        let events = events::EventBus::new(event_tx);
+
         // Spawn the event loop (thin orchestrator + executor)
         let loop_metrics = metrics.clone();
         let loop_cmd_tx = cmd_tx.clone();
         tokio::spawn(r#loop::runtime_loop(
             node,
             state,
+            hosted,
             gossip_bootstrap_peers,
             loop_cmd_tx,
             cmd_rx,
+            control_rx,
             msg_tx,
             status_tx,
-            event_tx,
+            events.clone(),
             path_rx,
             gossip,
             loop_metrics,
         ));
 
+        let handle = RuntimeHandle {
+            cmd_tx: CommandSender { tx: cmd_tx, control: control_tx, identity: None },
+            local_id,
+            identities,
+            metrics,
+            events,
+        };
+
+        // Admin socket (optional, closes with the runtime)
+        if let Some(path) = admin_socket {
+            #[cfg(unix)]
+            match admin::bind_admin_socket(&path, handle.clone()) {
+                Ok(()) => tracing::info!("admin socket on {}", path.display()),
+                Err(e) => tracing::warn!("admin socket: bind {} failed: {e}", path.display()),
+            }
+            #[cfg(not(unix))]
+            tracing::warn!("admin socket {}: Unix domain sockets unsupported here", path.display());
+        }
+
         RuntimeChannels {
-            handle: RuntimeHandle { cmd_tx, local_id, metrics },
+            handle,
             messages: msg_rx,
             status_changes: status_rx,
             events: event_rx,
@@ -0,0 +1,127 @@
+//! Effect scheduler — control traffic ahead of bulk sends.
+//!
+//! ACKs, heartbeats, hub pings and shadow syncs go out over the same
+//! transport as chat and group traffic, and each send is awaited (with
+//! retries) by the executor. Under load, a burst of bulk sends would hold
+//! them back long enough for peers to time out on us. The loop hands every
+//! batch of effects to the scheduler, which:
+//! - passes control-class effects straight through (control sends, see
+//!   `MessageType::is_control`, and everything that isn't a send);
+//! - queues bulk sends and releases at most `BULK_PER_ROUND` per loop
+//!   iteration, after that iteration's control effects.
+//!
+//! Order within each class is kept. Past `MAX_BULK_BACKLOG` queued sends
+//! the loop stops taking bulk-producing commands (`RuntimeCommand::is_bulk`)
+//! until the backlog drains, so app senders still feel backpressure;
+//! shutdown, queries and other control commands keep going through.
+use std::collections::VecDeque;
+
+use super::effect::RuntimeEffect;
+
+/// Bulk sends executed per loop iteration.
+pub(super) const BULK_PER_ROUND: usize = 32;
+
+/// Queued bulk sends past which new bulk-producing commands wait.
+pub(super) const MAX_BULK_BACKLOG: usize = 1024;
+
+/// Whether an effect may wait behind control traffic.
+fn is_bulk(effect: &RuntimeEffect) -> bool {
+    match effect {
+        RuntimeEffect::SendEnvelope(envelope) | RuntimeEffect::SendEnvelopeTo { envelope, .. } => {
+            !envelope.msg_type.is_control()
+        }
+        RuntimeEffect::SendWithBackupFallback { .. } => true,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct EffectScheduler {
+    bulk: VecDeque<RuntimeEffect>,
+}
+
+impl EffectScheduler {
+    /// Take a batch of effects; returns what to execute now: its control
+    /// effects, then the oldest queued bulk sends.
+    pub fn schedule(&mut self, effects: Vec<RuntimeEffect>) -> Vec<RuntimeEffect> {
+        let mut now = Vec::with_capacity(effects.len());
+        for effect in effects {
+            if is_bulk(&effect) {
+                self.bulk.push_back(effect);
+            } else {
+                now.push(effect);
+            }
+        }
+        let released = self.bulk.len().min(BULK_PER_ROUND);
+        now.extend(self.bulk.drain(..released));
+        now
+    }
+
+    /// Bulk sends still queued.
+    pub fn pending(&self) -> usize {
+        self.bulk.len()
+    }
+
+    /// Too much queued to take more commands.
+    pub fn is_backlogged(&self) -> bool {
+        self.bulk.len() >= MAX_BULK_BACKLOG
+    }
+
+    /// Everything still queued (shutdown).
+    pub fn drain(&mut self) -> Vec<RuntimeEffect> {
+        self.bulk.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::Envelope;
+    use crate::types::{MessageType, NodeId};
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        tom_connect::SecretKey::generate(&mut rng).public().to_string().parse().unwrap()
+    }
+
+    fn send(msg_type: MessageType, n: u8) -> RuntimeEffect {
+        RuntimeEffect::SendEnvelope(Envelope::new(node_id(1), node_id(2), msg_type, vec![n]))
+    }
+
+    fn payloads(effects: &[RuntimeEffect]) -> Vec<(MessageType, u8)> {
+        effects
+            .iter()
+            .map(|e| match e {
+                RuntimeEffect::SendEnvelope(env) => (env.msg_type, env.payload[0]),
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn control_overtakes_queued_bulk() {
+        let mut scheduler = EffectScheduler::default();
+        let burst: Vec<_> = (0..40).map(|n| send(MessageType::Chat, n)).collect();
+        let batch = scheduler.schedule(burst);
+        assert_eq!(batch.len(), BULK_PER_ROUND);
+        assert_eq!(scheduler.pending(), 40 - BULK_PER_ROUND);
+
+        // The next round's ACK and heartbeat go before the leftover chat
+        let batch = scheduler.schedule(vec![
+            send(MessageType::Chat, 40),
+            send(MessageType::Ack, 0),
+            send(MessageType::GroupHubPing, 1),
+        ]);
+        let order = payloads(&batch);
+        assert_eq!(order[..2], [(MessageType::Ack, 0), (MessageType::GroupHubPing, 1)]);
+        let chat: Vec<u8> = order[2..].iter().map(|(_, n)| *n).collect();
+        assert_eq!(chat, (BULK_PER_ROUND as u8..=40).collect::<Vec<_>>());
+        assert_eq!(scheduler.pending(), 0);
+        assert!(!scheduler.is_backlogged());
+
+        let _ = scheduler.schedule((0..MAX_BULK_BACKLOG + BULK_PER_ROUND).map(|n| send(MessageType::Chat, n as u8)).collect());
+        assert!(scheduler.is_backlogged());
+        assert_eq!(scheduler.drain().len(), MAX_BULK_BACKLOG);
+    }
+}
@@ -1,22 +1,47 @@
-use crate::backup::{BackupAction, BackupCoordinator, BackupEvent};
+use crate::backup::{BackupAction, BackupCoordinator, BackupEvent, BackupStore, HostFactors};
 use crate::discovery::{
-    DiscoveryEvent, DiscoverySource, EphemeralSubnetManager, HeartbeatTracker, PeerAnnounce,
-    SubnetEvent,
+    ClaimOutcome, DeviceCertificate, DeviceDirectory, DeviceRevocation, DiscoveryEvent,
+    DiscoverySource, EphemeralSubnetManager, HeartbeatTracker, IdentityBinding, PeerAnnounce, Presence, SubnetEvent, UsernameClaim, UsernameDirectory,
 };
 use crate::envelope::{Envelope, EnvelopeBuilder};
+use crate::exactly_once::{
+    CommitPayload, ExactlyOnceInbox, ExactlyOnceOutbox, InboxDecision,
+    EXACTLY_ONCE_DEDUP_RETENTION_MS,
+};
 use crate::group::{
     GroupAction, GroupEvent, GroupHub, GroupId, GroupManager, GroupMessage, GroupPayload,
+    MeshAction, MeshGroupManager, MeshPayload, MAX_SYNC_MESSAGES,
+};
+use crate::capabilities::{PeerCapabilities, CAPABILITY_RETRY_MS, MAX_CAPABILITY_OFFERS};
+use crate::contact::{
+    Contact, ContactCard, ContactStore, EncryptionHandshake, EncryptionMode, CONTENT_TYPE_CONTACT_CARD,
+    SUPPORTED_ENCRYPTION_MODES,
 };
-use crate::relay::{PeerInfo, PeerRole, PeerStatus, RelaySelector, Topology};
+use crate::history_sync::{ConversationLog, DirectEntry, HistoryEntry, HistorySyncPayload};
+use crate::ordering::{ReorderBuffer, Released, Sequence, Sequencer};
+use crate::realtime::{RealtimeLane, RealtimeStats, MAX_REALTIME_PAYLOAD};
+use crate::outbox::{OfflineOutbox, QueuedMessage};
+use crate::typing::{TypingLimiter, TypingPayload};
+use crate::payload::TypedPayload;
+use crate::relay::{PeerInfo, PeerRole, PeerStatus, RelayMetrics, RelaySelector, Topology};
 use crate::roles::{RoleAction, RoleManager};
-use crate::router::{AckType, ReadReceiptPayload, Router, RoutingAction};
+use crate::router::{AckType, ReadReceiptPayload, RelayStanding, Router, RoutingAction};
+use crate::shared_state::{SharedStateAction, SharedStateManager, SharedStatePayload};
+#[cfg(feature = "native")]
+use crate::storage::SqliteStateStore;
+use crate::storage::StateStore;
+use crate::trace::{PendingTraces, TraceHopKind, TracePayload};
 use crate::tracker::MessageTracker;
-use crate::types::{now_ms, MessageStatus, MessageType, NodeId};
+use crate::types::{now_ms, MessageStatus, MessageType, NodeId, PathEvent, PathKind};
 
 use super::effect::RuntimeEffect;
-use super::{DeliveredMessage, ProtocolEvent, RuntimeCommand, RuntimeConfig};
+use super::hooks::Hooks;
+use super::{
+    DeliveredMessage, PeerLiveness, ProtocolEvent, RuntimeCommand, RuntimeConfig, SendOptions, ShutdownReport,
+};
 
 // Phase R7.1: DHT discovery
+#[cfg(feature = "native")]
 use tom_dht::{DhtDiscovery, DhtNodeAddr};
 
 /// Gossip event input for RuntimeState (avoids leaking gossip types).
@@ -42,6 +67,12 @@ fn group_payload_to_message_type(payload: &GroupPayload) -> MessageType {
         GroupPayload::MemberJoined { .. } => MessageType::GroupMemberJoined,
         GroupPayload::MemberLeft { .. } => MessageType::GroupMemberLeft,
         GroupPayload::DeliveryAck { .. } => MessageType::GroupDeliveryAck,
+        GroupPayload::DeliveryReceipt { .. } => MessageType::GroupDeliveryReceipt,
+        GroupPayload::Reaction { .. } => MessageType::GroupReaction,
+        GroupPayload::ShardRelay { .. } => MessageType::GroupShardRelay,
+        GroupPayload::CancelScheduled { .. } => MessageType::GroupCancelScheduled,
+        GroupPayload::Poll { .. } => MessageType::GroupPoll,
+        GroupPayload::Vote { .. } => MessageType::GroupVote,
         GroupPayload::HubMigration { .. } => MessageType::GroupHubMigration,
         GroupPayload::HubHeartbeat { .. } => MessageType::GroupHubHeartbeat,
         GroupPayload::SenderKeyDistribution { .. } => MessageType::GroupSenderKeyDistribution,
@@ -54,8 +85,51 @@ fn group_payload_to_message_type(payload: &GroupPayload) -> MessageType {
         GroupPayload::UpdateMemberRole { .. } => MessageType::GroupUpdateMemberRole,
         GroupPayload::MemberRoleChanged { .. } => MessageType::GroupMemberRoleChanged,
         GroupPayload::InviteMember { .. } => MessageType::GroupInviteMember,
+        GroupPayload::BanMember { .. } => MessageType::GroupBanMember,
+        GroupPayload::UnbanMember { .. } => MessageType::GroupUnbanMember,
         GroupPayload::SyncRequest { .. } => MessageType::GroupSyncRequest,
         GroupPayload::SyncResponse { .. } => MessageType::GroupSyncResponse,
+        GroupPayload::FetchHistory { .. } => MessageType::GroupFetchHistory,
+        GroupPayload::HistoryPage { .. } => MessageType::GroupHistoryPage,
+        GroupPayload::UpdateMetadata { .. } => MessageType::GroupUpdateMetadata,
+        GroupPayload::MetadataChanged { .. } => MessageType::GroupMetadataChanged,
+        GroupPayload::SetMessageTtl { .. } => MessageType::GroupSetMessageTtl,
+        GroupPayload::MessageTtlChanged { .. } => MessageType::GroupMessageTtlChanged,
+        GroupPayload::UpdateSettings { .. } => MessageType::GroupUpdateSettings,
+        GroupPayload::SettingsChanged { .. } => MessageType::GroupSettingsChanged,
+        GroupPayload::RegisterInviteToken { .. } => MessageType::GroupRegisterInviteToken,
+        GroupPayload::JoinWithToken { .. } => MessageType::GroupJoinWithToken,
+        GroupPayload::JoinRequest { .. } => MessageType::GroupJoinRequest,
+        GroupPayload::JoinRequested { .. } => MessageType::GroupJoinRequested,
+        GroupPayload::ApproveJoin { .. } => MessageType::GroupApproveJoin,
+        GroupPayload::DenyJoin { .. } => MessageType::GroupDenyJoin,
+        GroupPayload::JoinRequestResolved { .. } => MessageType::GroupJoinRequestResolved,
+    }
+}
+
+/// Whether a backup replica is safe to hold: sealed to its recipient,
+/// never plaintext. (A shard is a fragment of a sealed payload; it can only
+/// be checked once rebuilt.)
+fn replica_is_sealed(payload: &crate::backup::ReplicationPayload) -> bool {
+    match payload.shard {
+        Some(info) => info.is_valid(),
+        None => crate::crypto::EncryptedPayload::from_bytes(&payload.payload).is_ok(),
+    }
+}
+
+/// How we regard a node asking us to relay: verified contacts are
+/// trusted, otherwise their topology role decides.
+fn relay_standing(topology: &Topology, contacts: &ContactStore, id: &NodeId) -> RelayStanding {
+    if contacts
+        .get(id)
+        .is_some_and(|c| c.trust == crate::contact::TrustLevel::Verified)
+    {
+        return RelayStanding::Trusted;
+    }
+    match topology.get(id) {
+        Some(peer) if peer.role == PeerRole::Relay => RelayStanding::Relay,
+        Some(_) => RelayStanding::Peer,
+        None => RelayStanding::Unknown,
     }
 }
 
@@ -71,6 +145,7 @@ pub struct RuntimeState {
     // Protocol modules
     pub(crate) router: Router,
     pub(crate) relay_selector: RelaySelector,
+    pub(crate) relay_metrics: RelayMetrics,
     pub(crate) topology: Topology,
     pub(crate) tracker: MessageTracker,
     pub(crate) heartbeat: HeartbeatTracker,
@@ -90,23 +165,91 @@ pub struct RuntimeState {
     /// Throttle role announcements (max 1 per peer per 30s).
     role_announce_throttle: std::collections::HashMap<NodeId, u64>,
 
+    /// Last counted abuse report per (reporter, offender), ours included.
+    abuse_reports: std::collections::HashMap<(NodeId, NodeId), u64>,
+
+    /// Relays that forwarded our messages since the last attestation.
+    relay_forwards: std::collections::HashMap<NodeId, u32>,
+    /// Last counted relay attestation per attester, ours included.
+    attestations: std::collections::HashMap<NodeId, u64>,
+
     // Phase R7.1: DHT-based peer discovery
+    #[cfg(feature = "native")]
     pub(crate) dht: Option<DhtDiscovery>,
 
     // Phase R8.2: State persistence
-    pub(crate) store: Option<crate::storage::StateStore>,
+    pub(crate) store: Option<Box<dyn StateStore>>,
 
     // Phase R9.2: Envelope cache for ACK-timeout retry
     pub(crate) pending_envelopes: std::collections::HashMap<String, crate::envelope::Envelope>,
 
     // Phase R11.1: Progressive anti-spam
     pub(crate) antispam: crate::roles::AntiSpam,
+    // Fixed per-sender budgets for 1:1 chat (delivery and forwarding)
+    chat_throttle: crate::roles::ChatThrottle,
+    // Capability handshakes we sent and when, awaiting an answer
+    capability_offers: std::collections::HashMap<NodeId, u64>,
+
+    // Pub/sub channels over gossip topics
+    pub(crate) channels: crate::pubsub::ChannelRegistry,
+
+    // Shared state documents (LWW CRDT)
+    pub(crate) shared_state: SharedStateManager,
+
+    // Hubless mesh groups (≤8 members, direct fan-out)
+    pub(crate) mesh_groups: MeshGroupManager,
+
+    // Contact roster (snapshotted; changes also written through)
+    pub(crate) contacts: ContactStore,
+
+    // Exactly-once delivery (written through to the store)
+    pub(crate) exactly_once_outbox: ExactlyOnceOutbox,
+    pub(crate) exactly_once_inbox: ExactlyOnceInbox,
+
+    // Chats composed while offline (written through to the store)
+    pub(crate) offline_outbox: OfflineOutbox,
+    /// Whether the transport has any live connection (assumed until told).
+    network_online: bool,
+
+    // Presence: ours (announced) and the last one each peer announced
+    local_presence: Presence,
+    peer_presence: std::collections::HashMap<NodeId, Presence>,
+
+    // Current transport path per peer (from the transport's path events)
+    peer_paths: std::collections::HashMap<NodeId, PathKind>,
+
+    // Typing indicators, rate-limited per peer in each direction
+    typing_out: TypingLimiter,
+    typing_in: TypingLimiter,
+    /// Real-time lane: sequence numbers out, loss/jitter stats in.
+    realtime: RealtimeLane,
+    /// Route traces we started, awaiting their reply.
+    traces: PendingTraces,
+    /// Paused by the app: announces, hub heartbeats, shadow pings and
+    /// backup ticks are skipped.
+    paused: bool,
+    /// User hooks on envelopes and events (from `RuntimeConfig::hooks`).
+    hooks: Hooks,
+
+    // Username directory (first-seen signed claims) and our own claim
+    usernames: UsernameDirectory,
+    username_claim: Option<UsernameClaim>,
+
+    // Linked devices (ours and peers'), from announced certificates
+    devices: DeviceDirectory,
+    // 1:1 messages sent and received, synced with our other devices
+    history: ConversationLog,
+
+    // Ordered delivery: our per-recipient numbering, held early arrivals
+    sequencer: Sequencer,
+    reorder: Option<ReorderBuffer<DeliveredMessage>>,
 }
 
 impl RuntimeState {
     /// Creer un nouvel etat de protocole.
-    pub fn new(local_id: NodeId, secret_seed: [u8; 32], config: RuntimeConfig) -> Self {
+    pub fn new(local_id: NodeId, secret_seed: [u8; 32], mut config: RuntimeConfig) -> Self {
         // Phase R7.1: Initialize DHT if enabled
+        #[cfg(feature = "native")]
         let dht = if config.enable_dht {
             match DhtDiscovery::new() {
                 Ok(d) => {
@@ -123,13 +266,18 @@ impl RuntimeState {
             None
         };
 
+        let hooks = Hooks::new(std::mem::take(&mut config.hooks));
+
         // Phase R8.2: Open state store and load persistent state
-        let store = config.data_dir.as_ref().and_then(|dir| {
+        let store = config.state_store.take();
+        #[cfg(feature = "native")]
+        let store = store.or_else(|| {
+            let dir = config.data_dir.as_ref()?;
             let db_path = dir.join("state.db");
-            match crate::storage::StateStore::open(&db_path) {
+            match SqliteStateStore::open(&db_path) {
                 Ok(s) => {
                     tracing::info!("State store opened: {}", db_path.display());
-                    Some(s)
+                    Some(Box::new(s) as Box<dyn StateStore>)
                 }
                 Err(e) => {
                     tracing::error!("Failed to open state store {}: {e}", db_path.display());
@@ -137,12 +285,57 @@ impl RuntimeState {
                 }
             }
         });
+        // Never load (or overwrite) another node's state
+        let store = store.filter(|s| match s.load_identity() {
+            Ok(Some(owner)) if owner != local_id => {
+                tracing::error!("State store belongs to {owner}, not {local_id}; running without persistence");
+                false
+            }
+            Ok(owner) => {
+                if owner.is_none() {
+                    if let Err(e) = s.save_identity(&local_id) {
+                        tracing::warn!("Failed to record state store identity: {e}");
+                    }
+                }
+                true
+            }
+            Err(e) => {
+                tracing::error!("Failed to read state store identity: {e}");
+                false
+            }
+        });
+
+        // Backup store: mirror held messages to backup.db (sealed with our
+        // key) so they survive restarts
+        #[cfg(feature = "native")]
+        let backup_store = config
+            .data_dir
+            .as_ref()
+            .and_then(|dir| {
+                let db_path = dir.join("backup.db");
+                match crate::backup::SqliteBackupPersistence::open(&db_path, &secret_seed) {
+                    Ok(p) => Some(p),
+                    Err(e) => {
+                        tracing::error!("Failed to open backup store {}: {e}", db_path.display());
+                        None
+                    }
+                }
+            })
+            .map(|p| {
+                BackupStore::with_persistence(Box::new(p), config.backup_policy.quota, now_ms())
+            })
+            .unwrap_or_else(|| BackupStore::with_quota(config.backup_policy.quota));
+        #[cfg(not(feature = "native"))]
+        let backup_store = BackupStore::with_quota(config.backup_policy.quota);
 
         let mut group_manager = GroupManager::new(local_id, config.username.clone());
         let mut group_hub = GroupHub::new(local_id);
-        let mut topology = Topology::new();
-        let mut role_manager = RoleManager::new(local_id);
+        let mut topology = Topology::with_max_peers(config.topology_max_peers);
+        let mut role_manager = RoleManager::with_config(local_id, config.role_config);
         let mut tracker = MessageTracker::new();
+        let mut shared_state = SharedStateManager::new(local_id);
+        let mut mesh_groups = MeshGroupManager::new(local_id, config.username.clone());
+        let mut contacts = ContactStore::new();
 
         if let Some(ref s) = store {
             match s.load() {
@@ -155,7 +348,22 @@ impl RuntimeState {
                     }
                     if let Some(hub_snap) = snapshot.hub {
                         let hub_count = hub_snap.groups.len();
+                        let hub_group_ids: Vec<GroupId> = hub_snap.groups.keys().cloned().collect();
                         group_hub.restore(hub_snap);
+                        for group_id in &hub_group_ids {
+                            let limit = group_hub
+                                .get_group(group_id)
+                                .and_then(|g| g.settings.history_limit)
+                                .map_or(MAX_SYNC_MESSAGES, |n| n as usize);
+                            let rows = s
+                                .load_hub_messages_before(group_id, u64::MAX, limit)
+                                .unwrap_or_default();
+                            let history = rows
+                                .iter()
+                                .filter_map(|(_, data)| rmp_serde::from_slice(data).ok())
+                                .collect();
+                            group_hub.restore_history(group_id, history);
+                        }
                         tracing::info!("Restored {hub_count} hub groups");
                     }
                     for peer in snapshot.peers.values() {
@@ -176,6 +384,20 @@ impl RuntimeState {
                         tracker.restore(snapshot.tracked_messages);
                         tracing::info!("Restored {count} tracked messages");
                     }
+                    if let Some(shared_snap) = snapshot.shared {
+                        let count = shared_snap.docs.len();
+                        shared_state.restore(shared_snap);
+                        tracing::info!("Restored {count} shared state documents");
+                    }
+                    if let Some(mesh_snap) = snapshot.mesh {
+                        let count = mesh_snap.groups.len();
+                        mesh_groups.restore(mesh_snap);
+                        tracing::info!("Restored {count} mesh groups");
+                    }
+                    if !snapshot.contacts.is_empty() {
+                        tracing::info!("Restored {} contacts", snapshot.contacts.len());
+                        contacts.restore(snapshot.contacts);
+                    }
                 }
                 Err(e) => {
                     tracing::error!("Failed to load state: {e}");
@@ -183,26 +405,127 @@ impl RuntimeState {
             }
         }
 
+        let mut exactly_once_outbox = ExactlyOnceOutbox::new();
+        let mut exactly_once_inbox = ExactlyOnceInbox::new();
+        if let Some(ref s) = store {
+            match s.load_outbox() {
+                Ok(entries) if !entries.is_empty() => {
+                    tracing::info!("Restored {} exactly-once outbox entries", entries.len());
+                    exactly_once_outbox.restore(entries);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to load exactly-once outbox: {e}"),
+            }
+            match s.load_inbox() {
+                Ok(records) => exactly_once_inbox.restore(records),
+                Err(e) => tracing::error!("Failed to load exactly-once inbox: {e}"),
+            }
+        }
+
+        let mut offline_outbox = OfflineOutbox::new();
+        if let Some(ref s) = store {
+            match s.load_queued_messages() {
+                Ok(messages) if !messages.is_empty() => {
+                    tracing::info!("Restored {} messages queued while offline", messages.len());
+                    for message in &messages {
+                        if tracker.status(&message.message_id).is_none() {
+                            tracker.track_queued(message.message_id.clone(), message.to);
+                        }
+                    }
+                    offline_outbox.restore(messages);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to load offline outbox: {e}"),
+            }
+        }
+
+        // Claim our username; recording it first means we own it locally
+        let username_claim = UsernameClaim::new(local_id, &config.username, now_ms(), &secret_seed);
+        let mut usernames = UsernameDirectory::new();
+        if let Some(claim) = &username_claim {
+            usernames.record(claim, now_ms());
+        }
+
+        let mut history = ConversationLog::new(crate::history_sync::MAX_HISTORY_ENTRIES);
+        if let Some(ref s) = store {
+            match s.load_history(crate::history_sync::MAX_HISTORY_ENTRIES) {
+                Ok(entries) => {
+                    for entry in entries {
+                        history.record(entry);
+                    }
+                }
+                Err(e) => tracing::error!("Failed to load history log: {e}"),
+            }
+        }
+
+        // Our own device certificate, if it names us
+        let mut devices = DeviceDirectory::new();
+        if let Some(cert) = config.device_certificate.clone() {
+            if cert.device != local_id || !devices.record_certificate(cert, now_ms()) {
+                tracing::warn!("device certificate ignored: not a valid certificate for this node");
+            }
+        }
+
+        let reorder = config.ordered_delivery.map(ReorderBuffer::new);
+
+        // Blocked contacts are dropped at the router
+        let mut router = Router::new(local_id);
+        router.set_relay_policy(config.relay_policy);
+        for node_id in contacts.blocked() {
+            router.set_blocked(node_id, true);
+        }
+
         Self {
-            router: Router::new(local_id),
+            router,
             relay_selector: RelaySelector::new(local_id),
+            relay_metrics: RelayMetrics::new(),
             topology,
             tracker,
-            heartbeat: HeartbeatTracker::new(),
+            heartbeat: HeartbeatTracker::with_config(&config.liveness),
             group_manager,
             group_hub,
-            backup: BackupCoordinator::new(local_id),
-            subnets: EphemeralSubnetManager::new(local_id),
+            backup: BackupCoordinator::with_store(local_id, backup_store)
+                .with_policy(config.backup_policy),
+            subnets: EphemeralSubnetManager::with_config(local_id, config.subnet),
             role_manager,
             local_roles: vec![PeerRole::Peer],
             role_announce_throttle: std::collections::HashMap::new(),
+            abuse_reports: std::collections::HashMap::new(),
+            relay_forwards: std::collections::HashMap::new(),
+            attestations: std::collections::HashMap::new(),
+            #[cfg(feature = "native")]
             dht,
             antispam: crate::roles::AntiSpam::new(config.antispam_config.clone()),
+            chat_throttle: crate::roles::ChatThrottle::new(config.chat_rate_limits),
+            capability_offers: std::collections::HashMap::new(),
             local_id,
             secret_seed,
             config,
             store,
             pending_envelopes: std::collections::HashMap::new(),
+            channels: crate::pubsub::ChannelRegistry::new(),
+            shared_state,
+            mesh_groups,
+            contacts,
+            exactly_once_outbox,
+            exactly_once_inbox,
+            offline_outbox,
+            network_online: true,
+            local_presence: Presence::Online,
+            peer_presence: std::collections::HashMap::new(),
+            peer_paths: std::collections::HashMap::new(),
+            typing_out: TypingLimiter::new(),
+            typing_in: TypingLimiter::new(),
+            realtime: RealtimeLane::new(),
+            traces: PendingTraces::new(),
+            paused: false,
+            hooks,
+            usernames,
+            username_claim,
+            devices,
+            history,
+            sequencer: Sequencer::new(),
+            reorder,
         }
     }
 
@@ -223,6 +546,31 @@ impl RuntimeState {
         &self.group_hub
     }
 
+    /// Access the shared state documents.
+    pub fn shared_state(&self) -> &SharedStateManager {
+        &self.shared_state
+    }
+
+    /// Access the hubless mesh groups.
+    pub fn mesh_groups(&self) -> &MeshGroupManager {
+        &self.mesh_groups
+    }
+
+    /// Access the contact roster.
+    pub fn contacts(&self) -> &ContactStore {
+        &self.contacts
+    }
+
+    /// Access the router (blocklist, drop counters).
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+
+    /// Access the exactly-once outbox (messages awaiting a commit).
+    pub fn exactly_once_outbox(&self) -> &ExactlyOnceOutbox {
+        &self.exactly_once_outbox
+    }
+
     /// Access the topology.
     pub fn topology(&self) -> &Topology {
         &self.topology
@@ -242,6 +590,7 @@ impl RuntimeState {
     ///
     /// Called at startup and periodically (every 30 min) to keep our
     /// DHT record fresh. The loop passes real addresses from TomNode.
+    #[cfg(feature = "native")]
     pub(crate) async fn publish_to_dht(
         &self,
         signing_key: &[u8; 32],
@@ -265,6 +614,7 @@ impl RuntimeState {
     }
 
     /// Check if DHT is enabled and return a reference for spawning lookups.
+    #[cfg(feature = "native")]
     pub(crate) fn dht(&self) -> Option<&DhtDiscovery> {
         self.dht.as_ref()
     }
@@ -281,12 +631,26 @@ impl RuntimeState {
             peers: self.topology.peers_map().clone(),
             metrics: self.role_manager.scores().clone(),
             tracked_messages: self.tracker.snapshot(),
+            shared: Some(self.shared_state.snapshot()),
+            mesh: Some(self.mesh_groups.snapshot()),
+            contacts: self.contacts.snapshot(),
         };
 
         if let Err(e) = store.save(&snapshot) {
             tracing::error!("Failed to save state: {e}");
         } else {
-            tracing::debug!("State saved to SQLite");
+            tracing::debug!("State saved");
+        }
+    }
+
+    /// Checkpoint hub state right away (hosted groups changed).
+    ///
+    /// Periodic saves alone could lose a fresh group or roster change if the
+    /// relay restarts before the next one.
+    fn checkpoint_hub(&self) {
+        let Some(ref store) = self.store else { return };
+        if let Err(e) = store.save_hub(&self.group_hub.snapshot()) {
+            tracing::error!("Failed to checkpoint hub state: {e}");
         }
     }
 
@@ -295,7 +659,34 @@ impl RuntimeState {
     /// Purge expired entries from the router dedup / ACK caches.
     pub fn tick_cache_cleanup(&mut self) -> Vec<RuntimeEffect> {
         self.router.cleanup_caches();
-        Vec::new()
+        let now = now_ms();
+        self.abuse_reports
+            .retain(|_, at| now.saturating_sub(*at) < crate::discovery::REPORT_INTERVAL_MS);
+        self.attestations
+            .retain(|_, at| now.saturating_sub(*at) < crate::discovery::ATTESTATION_INTERVAL_MS);
+
+        self.traces.expire(now);
+
+        let mut evicted = self.topology.take_evicted();
+        evicted.extend(
+            self.topology
+                .prune_departed(now, self.config.departed_peer_ttl.as_millis() as u64),
+        );
+        if evicted.is_empty() {
+            return Vec::new();
+        }
+        for node_id in &evicted {
+            self.forget_peer(node_id);
+        }
+        vec![RuntimeEffect::Emit(ProtocolEvent::PeersEvicted { node_ids: evicted })]
+    }
+
+    /// Drop per-peer bookkeeping for a peer no longer in the topology.
+    fn forget_peer(&mut self, node_id: &NodeId) {
+        self.heartbeat.untrack_peer(node_id);
+        self.peer_presence.remove(node_id);
+        self.peer_paths.remove(node_id);
+        self.usernames.forget(node_id);
     }
 
     // ── Tick: tracker cleanup ────────────────────────────────────────────
@@ -315,6 +706,11 @@ impl RuntimeState {
         let mut effects = Vec::new();
 
         for (message_id, to, retries_remaining) in expired {
+            self.record_relay_miss(&message_id);
+            let failed_attempts = crate::tracker::DEFAULT_MAX_RETRIES - retries_remaining + 1;
+            if failed_attempts >= self.config.backup_escalation_attempts {
+                effects.extend(self.escalate_backup(&message_id, to));
+            }
             if retries_remaining > 0 {
                 // Retry: resend cached envelope
                 if let Some(envelope) = self.pending_envelopes.get(&message_id).cloned() {
@@ -348,6 +744,65 @@ impl RuntimeState {
         effects
     }
 
+    /// Push a struggling message's backup to the relays best placed to
+    /// reach `to`, so it doesn't rest on our own uptime alone.
+    fn escalate_backup(&mut self, message_id: &str, to: NodeId) -> Vec<RuntimeEffect> {
+        let relays = self
+            .relay_metrics
+            .relays_toward(to, self.local_id, &self.topology);
+        let actions = self.backup.escalate(message_id, &relays, now_ms());
+        if actions.is_empty() {
+            return Vec::new();
+        }
+        let targets: Vec<NodeId> = actions
+            .iter()
+            .filter_map(|a| match a {
+                BackupAction::Replicate { target, .. } => Some(*target),
+                _ => None,
+            })
+            .collect();
+        let mut effects = self.backup_actions_to_effects(&actions);
+        effects.push(RuntimeEffect::Emit(ProtocolEvent::DeliveryEscalated {
+            message_id: message_id.to_string(),
+            to,
+            relays: targets,
+        }));
+        effects
+    }
+
+    /// A message's deadline passed without its first relay acknowledging
+    /// the forward: count it against that relay's success rate.
+    fn record_relay_miss(&mut self, message_id: &str) {
+        let relayed = self
+            .tracker
+            .status(message_id)
+            .is_some_and(|s| s >= MessageStatus::Relayed);
+        let relay = self
+            .pending_envelopes
+            .get(message_id)
+            .and_then(|e| e.via.first().copied());
+        if let (Some(relay), false) = (relay, relayed) {
+            self.relay_metrics.record_forward(relay, false);
+        }
+    }
+
+    /// A relay acknowledged forwarding our message: sample its round trip
+    /// (first attempt only — a retry's age says nothing about the relay).
+    fn record_relay_rtt(&mut self, message_id: &str, relay: NodeId) {
+        let Some(envelope) = self.pending_envelopes.get(message_id) else {
+            return;
+        };
+        if envelope.via.first() != Some(&relay) {
+            return;
+        }
+        let elapsed = now_ms().saturating_sub(envelope.timestamp);
+        if elapsed < crate::tracker::DEFAULT_ACK_DEADLINE_SECS * 1000 {
+            self.relay_metrics.record_rtt(relay, elapsed);
+        }
+        *self.relay_forwards.entry(relay).or_insert(0) += 1;
+        self.relay_metrics.record_forward(relay, true);
+    }
+
     // ── Tick: heartbeat liveness check ───────────────────────────────────
 
     /// Check all peers for liveness, handle all 4 discovery events.
@@ -375,14 +830,7 @@ impl RuntimeState {
                     }));
                 }
                 DiscoveryEvent::PeerOffline { node_id } => {
-                    let subnet_events = self.subnets.remove_node(&node_id);
-                    for se in &subnet_events {
-                        effects.extend(self.surface_subnet_event(se));
-                    }
-                    self.role_manager.remove_node(&node_id);
-                    effects.push(RuntimeEffect::Emit(ProtocolEvent::PeerOffline {
-                        node_id,
-                    }));
+                    effects.extend(self.peer_went_offline(node_id));
                 }
                 DiscoveryEvent::PeerOnline { node_id } => {
                     effects.push(RuntimeEffect::Emit(ProtocolEvent::PeerOnline {
@@ -397,6 +845,20 @@ impl RuntimeState {
         effects
     }
 
+    /// Drop a peer that went offline from backup delivery, subnets and
+    /// role scoring.
+    fn peer_went_offline(&mut self, node_id: NodeId) -> Vec<RuntimeEffect> {
+        let mut effects = Vec::new();
+        self.backup.cancel_delivery(&node_id);
+        let subnet_events = self.subnets.remove_node(&node_id);
+        for se in &subnet_events {
+            effects.extend(self.surface_subnet_event(se));
+        }
+        self.role_manager.remove_node(&node_id);
+        effects.push(RuntimeEffect::Emit(ProtocolEvent::PeerOffline { node_id }));
+        effects
+    }
+
     // ── Tick: subnet evaluation ──────────────────────────────────────────
 
     /// Evaluate communication patterns and form/dissolve ephemeral subnets.
@@ -411,68 +873,318 @@ impl RuntimeState {
 
     // ── Tick: role evaluation ────────────────────────────────────────────
 
-    /// Evaluate contribution scores and promote/demote peers.
+    /// Evaluate contribution scores and promote/demote peers, then attest
+    /// the relays that forwarded our messages since the last tick.
     pub fn tick_roles(&mut self) -> Vec<RuntimeEffect> {
-        let actions = self.role_manager.evaluate(&mut self.topology, now_ms());
+        let now = now_ms();
+        let stored = self.backup.store().total_bytes();
+        self.role_manager.record_bytes_stored(self.local_id, stored, now);
+        let actions = self.role_manager.evaluate(&mut self.topology, now);
         let mut effects = Vec::new();
         for action in &actions {
             effects.extend(self.surface_role_action(action));
         }
+        if !self.paused {
+            effects.extend(self.flush_relay_attestation(now));
+        }
         effects
     }
 
+    /// Sign and gossip the pending relay forward counts, busiest relays
+    /// first. At most one attestation per `ATTESTATION_INTERVAL_MS`.
+    fn flush_relay_attestation(&mut self, now: u64) -> Vec<RuntimeEffect> {
+        if self.relay_forwards.is_empty() || !self.attestation_due(self.local_id, now) {
+            return Vec::new();
+        }
+        let mut relays: Vec<(NodeId, u32)> = self.relay_forwards.drain().collect();
+        relays.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        self.attestations.insert(self.local_id, now);
+        let attestation =
+            crate::discovery::RelayAttestation::new(self.local_id, relays, now, &self.secret_seed);
+        vec![RuntimeEffect::BroadcastRelayAttestation(attestation)]
+    }
+
+    /// Whether an attestation by `attester` counts now.
+    fn attestation_due(&self, attester: NodeId, now: u64) -> bool {
+        self.attestations
+            .get(&attester)
+            .is_none_or(|&at| now.saturating_sub(at) >= crate::discovery::ATTESTATION_INTERVAL_MS)
+    }
+
+    /// Handle a relay attestation from gossip.
+    ///
+    /// Credits each listed relay, weighted by the attester's own score, at
+    /// most once per attester per `ATTESTATION_INTERVAL_MS`. Entries about
+    /// us are skipped: our score is what we observe, not what we're told.
+    pub fn handle_relay_attestation(
+        &mut self,
+        attestation: crate::discovery::RelayAttestation,
+    ) -> Vec<RuntimeEffect> {
+        let now = now_ms();
+        if attestation.attester == self.local_id
+            || !attestation.is_valid(now)
+            || !self.attestation_due(attestation.attester, now)
+        {
+            return Vec::new();
+        }
+        if !attestation.verify_signature() {
+            return vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                description: format!("Invalid relay attestation from {}", attestation.attester),
+            })];
+        }
+
+        self.attestations.insert(attestation.attester, now);
+        for &(relay, forwards) in &attestation.relays {
+            if relay != self.local_id {
+                self.role_manager
+                    .record_attestation(attestation.attester, relay, forwards, now);
+            }
+        }
+        Vec::new()
+    }
+
     // ── Tick: backup maintenance ─────────────────────────────────────────
 
-    /// Run periodic backup maintenance (expire, viability, replication cleanup).
+    /// Run periodic backup maintenance (expire, viability, replication
+    /// cleanup), sample peer availability and place replicas.
     pub fn tick_backup(&mut self) -> Vec<RuntimeEffect> {
-        let actions = self.backup.tick(now_ms());
+        if self.paused {
+            return Vec::new();
+        }
+        let now = now_ms();
+        let observed: Vec<(NodeId, bool)> = self
+            .topology
+            .peers()
+            .filter(|p| p.node_id != self.local_id)
+            .map(|p| (p.node_id, p.status == PeerStatus::Online))
+            .collect();
+        for (node_id, online) in observed {
+            self.backup.record_availability(node_id, online);
+        }
+
+        let mut actions = self.backup.tick(now);
+        let candidates = self.replica_candidates(now);
+        actions.extend(self.backup.plan_replication(&candidates, now));
+        self.backup_actions_to_effects(&actions)
+    }
+
+    // ── Tick: backup integrity ───────────────────────────────────────────
+
+    /// Verify held payloads against their hashes, drop corrupted ones and
+    /// compact the persistent backup store.
+    pub fn tick_backup_integrity(&mut self) -> Vec<RuntimeEffect> {
+        if self.paused {
+            return Vec::new();
+        }
+        let actions = self.backup.verify_store();
+        self.backup_actions_to_effects(&actions)
+    }
+
+    // ── Shutdown: backup handoff ─────────────────────────────────────────
+
+    /// Graceful shutdown: flush the offline outbox if we're online, and
+    /// hand every entry we hold to another online holder before the node
+    /// goes away. The report counts what stays behind.
+    pub fn prepare_shutdown(&mut self) -> (Vec<RuntimeEffect>, ShutdownReport) {
+        let now = now_ms();
+        let mut effects = self.set_connectivity(self.network_online);
+
+        let candidates = self.replica_candidates(now);
+        let actions = self.backup.hand_off(&candidates, now);
+        if !actions.is_empty() {
+            tracing::info!("handing off {} backup entries before shutdown", actions.len());
+        }
+        let store = self.backup.store();
+        let held = store
+            .message_ids()
+            .iter()
+            .filter_map(|id| store.get(id))
+            .filter(|e| e.recipient_id != self.local_id && !e.is_expired(now))
+            .count();
+        let report = ShutdownReport {
+            unsent_messages: self.offline_outbox.len(),
+            backups_handed_off: actions.len(),
+            backups_kept: held.saturating_sub(actions.len()),
+            ..ShutdownReport::default()
+        };
+        effects.extend(self.backup_actions_to_effects(&actions));
+        (effects, report)
+    }
+
+    /// Our signed departure, gossiped last on shutdown.
+    pub fn build_departure_announce(&self) -> Vec<u8> {
+        crate::discovery::DepartureAnnounce::new(self.local_id, now_ms(), &self.secret_seed).to_bytes()
+    }
+
+    /// A peer announced it is going offline: mark it offline now rather
+    /// than waiting out its offline threshold.
+    fn handle_departure(&mut self, departure: crate::discovery::DepartureAnnounce) -> Vec<RuntimeEffect> {
+        let node_id = departure.node_id;
+        if node_id == self.local_id
+            || !departure.is_valid(now_ms())
+            || !departure.verify_signature()
+        {
+            return Vec::new();
+        }
+        tracing::debug!(peer = %node_id, "peer announced its departure");
+        self.mark_peer_offline(node_id)
+    }
+
+    /// Take a known peer offline now rather than once its offline
+    /// threshold has passed. A later heartbeat brings it back as usual.
+    fn mark_peer_offline(&mut self, node_id: NodeId) -> Vec<RuntimeEffect> {
+        let Some(peer) = self.topology.get(&node_id) else {
+            return Vec::new();
+        };
+        if peer.status == PeerStatus::Offline {
+            return Vec::new();
+        }
+        let mut updated = peer.clone();
+        updated.status = PeerStatus::Offline;
+        self.topology.upsert(updated);
+        self.heartbeat.mark_departed(&node_id, now_ms());
+        self.peer_went_offline(node_id)
+    }
+
+    // ── Tick: paced backup delivery ──────────────────────────────────────
+
+    /// Send the next batch of held messages to each reconnected peer.
+    pub fn tick_backup_delivery(&mut self) -> Vec<RuntimeEffect> {
+        if self.paused {
+            return Vec::new();
+        }
+        let actions = self.backup.tick_deliveries(now_ms());
         self.backup_actions_to_effects(&actions)
     }
 
     // ── Tick: group hub heartbeat ────────────────────────────────────────
 
-    /// Send heartbeat probes to all group members (hub-side).
+    /// Send heartbeat probes to group members (hub-side), skipping those
+    /// our group traffic reached within the last interval.
+    ///
+    /// Also re-elects the sub-hubs large groups shard their fan-out across.
     pub fn tick_group_hub_heartbeat(&mut self) -> Vec<RuntimeEffect> {
-        let actions = self.group_hub.heartbeat_actions();
+        if self.paused {
+            return Vec::new();
+        }
+        self.group_hub.assign_sub_hubs(&self.topology);
+        let now = now_ms();
+        let heartbeat = &self.heartbeat;
+        let actions = self.group_hub.heartbeat_actions_at(now, |member| {
+            heartbeat.sent_within(member, now, crate::group::types::HUB_HEARTBEAT_INTERVAL_MS)
+        });
         let actions = self.intercept_self_group_actions(actions);
         self.group_actions_to_effects(&actions)
     }
 
+    // ── Tick: scheduled group messages ──────────────────────────────────
+
+    /// Fan out scheduled group messages that are due (hub-side).
+    pub fn tick_scheduled_messages(&mut self) -> Vec<RuntimeEffect> {
+        let actions = self.group_hub.release_scheduled(now_ms());
+        let actions = self.intercept_self_group_actions(actions);
+        self.group_actions_to_effects(&actions)
+    }
+
+    // ── Tick: disappearing messages ─────────────────────────────────────
+
+    /// Purge messages that outlived their group's disappearing timer.
+    ///
+    /// Hub history is dropped in memory and in SQLite; member history emits
+    /// `GroupMessagesExpired` so clients delete the same messages. SQLite
+    /// rows are expired by timestamp, so messages already evicted from
+    /// memory (or persisted before a restart) go too.
+    pub fn tick_ephemeral_messages(&mut self) -> Vec<RuntimeEffect> {
+        self.tick_ephemeral_messages_at(now_ms())
+    }
+
+    /// `tick_ephemeral_messages` with an explicit clock (tests).
+    pub fn tick_ephemeral_messages_at(&mut self, now: u64) -> Vec<RuntimeEffect> {
+        self.group_hub.purge_ephemeral_messages(now);
+        if let Some(ref store) = self.store {
+            for (group_id, info) in self.group_hub.groups() {
+                let Some(ttl) = info.message_ttl_ms else { continue };
+                if let Err(e) = store.expire_hub_messages(group_id, now.saturating_sub(ttl)) {
+                    tracing::warn!("failed to delete expired hub messages: {e}");
+                }
+            }
+        }
+        let actions = self.group_manager.expire_messages(now);
+        self.group_actions_to_effects(&actions)
+    }
+
     // ── Tick: shadow ping watchdog ──────────────────────────────────────
 
-    /// Shadow watchdog tick — send HubPing to primary for each group we shadow.
+    /// Failover watchdog tick.
+    ///
+    /// As shadow: ping the primary, counting unanswered pings; promote
+    /// ourselves once it's deemed dead. As member: report a silent hub to
+    /// its shadow.
     pub fn tick_shadow_ping(&mut self) -> Vec<RuntimeEffect> {
-        let shadow_groups: Vec<(crate::group::GroupId, NodeId)> = self
-            .group_manager
-            .shadow_groups()
-            .into_iter()
-            .map(|(gid, hub)| (gid.clone(), hub))
+        self.tick_shadow_ping_at(now_ms())
+    }
+
+    /// `tick_shadow_ping` with an explicit clock (simulation tests).
+    pub fn tick_shadow_ping_at(&mut self, now: u64) -> Vec<RuntimeEffect> {
+        if self.paused {
+            return Vec::new();
+        }
+        let mut actions = self.group_manager.shadow_ping_tick(now);
+        actions.extend(self.group_manager.check_hub_liveness(now));
+        let actions = self.take_over_promoted_groups(actions);
+        let actions = self.intercept_self_group_actions(actions);
+        self.group_actions_to_effects(&actions)
+    }
+
+    /// Start hosting the groups our manager just promoted us to primary for.
+    ///
+    /// Imports the replicated roster and bans plus our own message history
+    /// (re-sealed as the hub holds it), then elects a new shadow.
+    fn take_over_promoted_groups(&mut self, mut actions: Vec<GroupAction>) -> Vec<GroupAction> {
+        let promoted: Vec<GroupId> = actions
+            .iter()
+            .filter_map(|a| match a {
+                GroupAction::Broadcast {
+                    payload: GroupPayload::HubMigration { group_id, new_hub_id, .. },
+                    ..
+                } if *new_hub_id == self.local_id => Some(group_id.clone()),
+                _ => None,
+            })
             .collect();
 
-        let mut effects = Vec::new();
-        for (group_id, hub_id) in shadow_groups {
-            let payload = GroupPayload::HubPing {
-                group_id: group_id.clone(),
+        for group_id in promoted {
+            if self.group_hub.get_group(&group_id).is_some() {
+                continue;
+            }
+            let Some(info) = self.group_manager.get_group(&group_id).cloned() else {
+                continue;
             };
-            let payload_bytes =
-                rmp_serde::to_vec(&payload).expect("group payload serialization");
-            let via = self.relay_selector.select_path(hub_id, &self.topology);
-            let envelope = EnvelopeBuilder::new(
-                self.local_id,
-                hub_id,
-                MessageType::GroupHubPing,
-                payload_bytes,
-            )
-            .via(via)
-            .sign(&self.secret_seed);
-            effects.push(RuntimeEffect::SendEnvelope(envelope));
+            let messages: Vec<GroupMessage> = self
+                .group_manager
+                .message_history(&group_id)
+                .iter()
+                .cloned()
+                .map(|mut msg| {
+                    if msg.encrypted {
+                        msg.text.clear();
+                        msg.sender_username.clear();
+                    }
+                    msg
+                })
+                .collect();
+            for msg in &messages {
+                self.persist_hub_message(msg);
+            }
+            self.group_hub.import_group(info, messages);
+            tracing::info!("promoted to hub for group {group_id}");
+            actions.extend(self.group_hub.assign_shadow(&group_id));
         }
-        effects
+        actions
     }
 
     /// Purge expired hub messages (in-memory + SQLite). 24h TTL.
     pub fn tick_hub_cleanup(&mut self) -> Vec<RuntimeEffect> {
-        const TTL_MS: u64 = 24 * 60 * 60 * 1000; // 24 hours
+        const TTL_MS: u64 = crate::types::MESSAGE_LIFESPAN_MS;
         let now = now_ms();
 
         // In-memory cleanup
@@ -480,7 +1192,7 @@ impl RuntimeState {
 
         // SQLite cleanup
         let db_purged = if let Some(ref store) = self.store {
-            store.cleanup_hub_messages(TTL_MS).unwrap_or(0)
+            store.cleanup_hub_messages(now.saturating_sub(TTL_MS)).unwrap_or(0)
         } else {
             0
         };
@@ -506,46 +1218,368 @@ impl RuntimeState {
         Vec::new() // no effects
     }
 
+    // ── Hooks ────────────────────────────────────────────────────────────
+
+    /// Run the outgoing-envelope and event hooks over effects about to be
+    /// executed, dropping what they reject.
+    pub(crate) fn apply_hooks(&self, effects: Vec<RuntimeEffect>) -> Vec<RuntimeEffect> {
+        self.hooks.outgoing(effects)
+    }
+
+    // ── Pause / resume ───────────────────────────────────────────────────
+
+    /// Whether the app paused the runtime.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn set_paused(&mut self, paused: bool) -> Vec<RuntimeEffect> {
+        if self.paused == paused {
+            return Vec::new();
+        }
+        self.paused = paused;
+        tracing::info!("runtime {}", if paused { "paused" } else { "resumed" });
+        vec![RuntimeEffect::Emit(ProtocolEvent::RuntimePaused { paused })]
+    }
+
     // ── Gossip announce builder ──────────────────────────────────────────
 
     /// Build a PeerAnnounce and serialize it to MessagePack bytes.
     ///
-    /// Returns `None` if serialization fails (should never happen).
+    /// Returns `None` while paused, or if serialization fails (should
+    /// never happen).
     pub fn build_gossip_announce(&self) -> Option<Vec<u8>> {
+        if self.paused {
+            return None;
+        }
         let announce = PeerAnnounce::new(
             self.local_id,
             self.config.username.clone(),
             self.local_roles.clone(),
+        )
+        .with_backup_preference(self.config.backup_preference.clone())
+        .with_presence(self.local_presence.clone())
+        .with_username_claim(self.username_claim.clone())
+        .with_announce_interval(self.config.liveness.announce_interval_ms)
+        .with_relay_load(self.relay_metrics.local_load(now_ms()))
+        .with_hosted(
+            self.config
+                .identities
+                .iter()
+                .map(|identity| identity.binding(self.local_id, now_ms()))
+                .collect(),
+        )
+        .with_device(
+            self.devices.certificate(&self.local_id).cloned(),
+            self.config
+                .device_certificate
+                .as_ref()
+                .map(|cert| self.devices.revocations_of(&cert.user))
+                .unwrap_or_default(),
         );
+        let announce = if self.config.enable_pex {
+            announce.with_pex_peers(crate::discovery::select_pex_peers(
+                &self.topology,
+                &self.heartbeat,
+                self.local_id,
+                now_ms(),
+            ))
+        } else {
+            announce
+        };
         rmp_serde::to_vec(&announce).ok()
     }
 
-    /// Build effects to rejoin all restored groups (called once at startup).
-    ///
-    /// After a restart, groups are loaded from SQLite but the hub doesn't know
-    /// we're back online. This sends a Join to each group's hub, which triggers
-    /// a re-sync with current state and sender keys.
-    pub fn build_rejoin_effects(&mut self) -> Vec<RuntimeEffect> {
-        let actions = self.group_manager.rejoin_groups();
-        if actions.is_empty() {
-            return Vec::new();
-        }
-        tracing::info!("Rejoining {} groups after restart", actions.len());
-        let actions = self.intercept_self_group_actions(actions);
-        self.group_actions_to_effects(&actions)
+    /// Nodes claiming a username, owner (first seen) first.
+    pub fn resolve_username(&self, name: &str) -> Vec<NodeId> {
+        self.usernames.resolve(name)
     }
 
-    // ── Handle incoming role change announcement ──────────────────────
-
-    /// Handle incoming role change announcement from gossip.
-    ///
-    /// Validates signature, throttles spam, updates topology.
+    /// Learn the identities a peer hosts: each verified binding routes the
+    /// identity through its host and counts as a heartbeat from it.
+    fn record_hosted_identities(
+        &mut self,
+        host: NodeId,
+        announce_interval_ms: Option<u64>,
+        bindings: Vec<IdentityBinding>,
+    ) {
+        let now = now_ms();
+        for binding in bindings.into_iter().take(crate::discovery::MAX_HOSTED_IDENTITIES) {
+            if binding.host != host
+                || binding.identity == host
+                || binding.identity == self.local_id
+                || !binding.is_valid(now)
+                || !binding.verify_signature()
+            {
+                continue;
+            }
+            if let Some(interval) = announce_interval_ms {
+                self.heartbeat.set_peer_interval(binding.identity, interval);
+            }
+            self.heartbeat.record_heartbeat_with_source(
+                binding.identity,
+                DiscoverySource::Announce,
+                binding.username,
+            );
+            self.topology.upsert(PeerInfo {
+                node_id: binding.identity,
+                role: PeerRole::Peer,
+                status: PeerStatus::Online,
+                last_seen: now,
+            });
+            self.topology.set_host(binding.identity, host);
+        }
+    }
+
+    /// Learn a peer's device certificate and its user's revocations. A new
+    /// device of our own user is brought into our groups.
+    fn record_device_link(
+        &mut self,
+        node_id: NodeId,
+        device: Option<DeviceCertificate>,
+        revocations: Vec<DeviceRevocation>,
+    ) -> Vec<RuntimeEffect> {
+        let mut effects = Vec::new();
+        for revocation in revocations
+            .into_iter()
+            .take(crate::discovery::MAX_ANNOUNCED_REVOCATIONS)
+        {
+            effects.extend(self.apply_device_revocation(revocation));
+        }
+        let Some(cert) = device.filter(|c| c.device == node_id) else {
+            return effects;
+        };
+        let (user, device_name) = (cert.user, cert.device_name.clone());
+        if !self.devices.record_certificate(cert, now_ms())
+            || !self.devices.are_linked(&self.local_id, &node_id)
+        {
+            return effects;
+        }
+        effects.push(RuntimeEffect::Emit(ProtocolEvent::DeviceLinked {
+            user,
+            device: node_id,
+            device_name,
+        }));
+        effects.extend(self.share_groups_with_device(node_id));
+        // Catch up on what it has that we don't (everything, for a new device)
+        let since = self.history.newest_timestamp().unwrap_or(0);
+        effects.extend(self.send_untracked(
+            node_id,
+            MessageType::HistorySync,
+            HistorySyncPayload::Request { since }.to_bytes(),
+        ));
+        effects
+    }
+
+    /// Ask the hub of each of our groups to invite a new device of our user.
+    fn share_groups_with_device(&mut self, device: NodeId) -> Vec<RuntimeEffect> {
+        let groups: Vec<(GroupId, NodeId)> = self
+            .group_manager
+            .all_groups()
+            .into_iter()
+            .filter(|g| !g.is_member(&device))
+            .map(|g| (g.group_id.clone(), g.hub_relay_id))
+            .collect();
+        let mut effects = Vec::new();
+        for (group_id, hub) in groups {
+            if hub == self.local_id {
+                let actions = self
+                    .group_hub
+                    .invite_linked_device(&group_id, &self.local_id, device);
+                let actions = self.intercept_self_group_actions(actions);
+                effects.extend(self.group_actions_to_effects(&actions));
+            } else {
+                effects.extend(self.group_actions_to_effects(&[GroupAction::Send {
+                    to: hub,
+                    payload: GroupPayload::InviteMember {
+                        group_id,
+                        target_id: device,
+                    },
+                }]));
+            }
+        }
+        effects
+    }
+
+    /// Apply a signed device revocation. A revoked device of our own user is
+    /// kicked from the groups we admin.
+    fn apply_device_revocation(&mut self, revocation: DeviceRevocation) -> Vec<RuntimeEffect> {
+        let (user, device) = (revocation.user, revocation.device);
+        let ours = self.devices.are_linked(&self.local_id, &device);
+        if !self.devices.apply_revocation(revocation) || !ours {
+            return Vec::new();
+        }
+        let mut effects = vec![RuntimeEffect::Emit(ProtocolEvent::DeviceRevoked { user, device })];
+        let groups: Vec<GroupId> = self
+            .group_manager
+            .all_groups()
+            .into_iter()
+            .filter(|g| g.is_admin(&self.local_id) && g.is_member(&device))
+            .map(|g| g.group_id.clone())
+            .collect();
+        for group_id in groups {
+            effects.extend(self.handle_command(RuntimeCommand::KickMember {
+                group_id,
+                target_id: device,
+            }));
+        }
+        effects
+    }
+
+    /// Record a peer's username claim; emits an event when it collides.
+    fn record_username_claim(&mut self, node_id: NodeId, claim: Option<UsernameClaim>) -> Vec<RuntimeEffect> {
+        let Some(claim) = claim.filter(|c| c.node_id == node_id) else {
+            return Vec::new();
+        };
+        match self.usernames.record(&claim, now_ms()) {
+            ClaimOutcome::Conflict { owner } => {
+                vec![RuntimeEffect::Emit(ProtocolEvent::UsernameConflict {
+                    name: claim.name,
+                    owner,
+                    claimant: node_id,
+                })]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The network as we see it: topology, subnets and observed paths.
+    pub fn topology_snapshot(&self) -> crate::relay::TopologySnapshot {
+        self.topology
+            .snapshot()
+            .with_observer(self.local_id, now_ms())
+            .with_subnets(&self.subnets)
+            .with_paths(&self.relay_metrics)
+    }
+
+    /// Our advertised presence.
+    pub fn presence(&self) -> &Presence {
+        &self.local_presence
+    }
+
+    /// Last presence a peer announced (Online if it never said otherwise).
+    pub fn peer_presence(&self, node_id: &NodeId) -> Presence {
+        self.peer_presence.get(node_id).cloned().unwrap_or_default()
+    }
+
+    /// Change our presence and re-announce it right away.
+    pub fn set_presence(&mut self, presence: Presence) -> Vec<RuntimeEffect> {
+        let presence = presence.normalized();
+        if presence == self.local_presence {
+            return Vec::new();
+        }
+        self.local_presence = presence;
+        self.build_gossip_announce()
+            .map(RuntimeEffect::BroadcastAnnounce)
+            .into_iter()
+            .collect()
+    }
+
+    /// Note a peer's new transport path and surface it to the application.
+    /// An unreachable path (keepalives went unanswered) takes the peer
+    /// offline at once.
+    pub fn handle_path_event(&mut self, event: PathEvent) -> Vec<RuntimeEffect> {
+        if self.peer_paths.len() < crate::relay::MAX_PEERS || self.peer_paths.contains_key(&event.remote) {
+            self.peer_paths.insert(event.remote, event.kind);
+        }
+        // Transport RTT steers subnet formation and relay choice
+        if !event.rtt.is_zero() {
+            self.subnets
+                .record_rtt(event.remote, event.rtt.as_millis().min(u64::MAX as u128) as u64);
+        }
+        let unreachable = event.is_unreachable().then_some(event.remote);
+        let mut effects = vec![RuntimeEffect::Emit(ProtocolEvent::PathChanged { event })];
+        if let Some(remote) = unreachable {
+            tracing::debug!(peer = %remote, "transport lost the peer");
+            effects.extend(self.mark_peer_offline(remote));
+        }
+        effects
+    }
+
+    /// What we know about a peer's reachability. None if neither the
+    /// topology nor the heartbeat tracker knows it.
+    pub fn peer_liveness(&self, node_id: &NodeId) -> Option<PeerLiveness> {
+        let last_seen = self.heartbeat.last_seen(node_id);
+        let role = self.topology.get(node_id).map(|p| p.role);
+        if last_seen.is_none() && role.is_none() {
+            return None;
+        }
+        Some(PeerLiveness {
+            node_id: *node_id,
+            liveness: self.heartbeat.liveness(node_id),
+            last_seen,
+            source: self.heartbeat.source(node_id),
+            role,
+            path: self
+                .peer_paths
+                .get(node_id)
+                .copied()
+                .unwrap_or(PathKind::Unknown),
+            rtt_ms: self.subnets.rtt_ms(node_id),
+        })
+    }
+
+    /// [`peer_liveness`](Self::peer_liveness) of every known peer.
+    pub fn all_peer_liveness(&self) -> Vec<PeerLiveness> {
+        let known: std::collections::HashSet<NodeId> = self
+            .topology
+            .peers()
+            .map(|p| p.node_id)
+            .chain(self.heartbeat.tracked_peers().copied())
+            .filter(|id| *id != self.local_id)
+            .collect();
+        known.iter().filter_map(|id| self.peer_liveness(id)).collect()
+    }
+
+    /// Record a peer's announced presence; emits an event when it changed.
+    fn record_peer_presence(&mut self, node_id: NodeId, presence: Presence) -> Vec<RuntimeEffect> {
+        let presence = presence.normalized();
+        let previous = self.peer_presence(&node_id);
+        if presence == Presence::Online {
+            self.peer_presence.remove(&node_id);
+        } else {
+            self.peer_presence.insert(node_id, presence.clone());
+        }
+        if presence == previous {
+            return Vec::new();
+        }
+        vec![RuntimeEffect::Emit(ProtocolEvent::PeerPresenceChanged {
+            node_id,
+            presence,
+        })]
+    }
+
+    /// Build effects to rejoin all restored groups (called once at startup).
+    ///
+    /// After a restart, groups are loaded from SQLite but the hub doesn't know
+    /// we're back online. This sends a Join to each group's hub, which triggers
+    /// a re-sync with current state and sender keys.
+    pub fn build_rejoin_effects(&mut self) -> Vec<RuntimeEffect> {
+        let actions = self.group_manager.rejoin_groups();
+        if actions.is_empty() {
+            return Vec::new();
+        }
+        tracing::info!("Rejoining {} groups after restart", actions.len());
+        let actions = self.intercept_self_group_actions(actions);
+        self.group_actions_to_effects(&actions)
+    }
+
+    // ── Handle incoming role change announcement ──────────────────────
+
+    /// Handle incoming role change announcement from gossip.
+    ///
+    /// Validates signature, throttles spam, updates topology.
     pub fn handle_role_announce(
         &mut self,
         announce: crate::discovery::RoleChangeAnnounce,
     ) -> Vec<RuntimeEffect> {
         let now = now_ms();
 
+        // Extra roles travel in PeerAnnounce; these only flip Peer <-> Relay
+        if announce.new_role.is_extra() {
+            return Vec::new();
+        }
+
         // Throttle: max 1 announce per peer per 30s
         const THROTTLE_MS: u64 = 30_000;
         if let Some(&last_announce) = self.role_announce_throttle.get(&announce.node_id) {
@@ -588,7 +1622,7 @@ impl RuntimeState {
                 node_id: announce.node_id,
                 score: announce.score,
             },
-            PeerRole::Peer => ProtocolEvent::RoleDemoted {
+            _ => ProtocolEvent::RoleDemoted {
                 node_id: announce.node_id,
                 score: announce.score,
             },
@@ -597,6 +1631,113 @@ impl RuntimeState {
         vec![RuntimeEffect::Emit(event)]
     }
 
+    // ── Abuse reports ─────────────────────────────────────────────────
+
+    /// Whether a report by `reporter` against `offender` counts now.
+    fn abuse_report_due(&self, reporter: NodeId, offender: NodeId, now: u64) -> bool {
+        self.abuse_reports
+            .get(&(reporter, offender))
+            .is_none_or(|&at| now.saturating_sub(at) >= crate::discovery::REPORT_INTERVAL_MS)
+    }
+
+    /// Penalize `offender` for a violation we observed and gossip a signed
+    /// report, `evidence` being the offending envelope. At most one report
+    /// per offender per `REPORT_INTERVAL_MS`.
+    pub fn report_violation(
+        &mut self,
+        offender: NodeId,
+        kind: crate::discovery::ViolationKind,
+        evidence: Vec<u8>,
+    ) -> Vec<RuntimeEffect> {
+        let now = now_ms();
+        if offender == self.local_id || !self.abuse_report_due(self.local_id, offender, now) {
+            return Vec::new();
+        }
+        let report = crate::discovery::AbuseReport::new(
+            self.local_id,
+            offender,
+            kind,
+            evidence,
+            now,
+            &self.secret_seed,
+        );
+        if !report.evidence_matches() {
+            return vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                description: format!("abuse report against {offender} dropped: evidence doesn't show {kind:?}"),
+            })];
+        }
+        self.abuse_reports.insert((self.local_id, offender), now);
+        self.role_manager
+            .record_violation(offender, crate::roles::scoring::LOCAL_VIOLATION_PENALTY, now);
+        vec![RuntimeEffect::BroadcastAbuseReport(report)]
+    }
+
+    /// Handle an abuse report from gossip.
+    ///
+    /// Checks freshness, the reporter's signature and the evidence, then
+    /// applies a decaying penalty — once per reporter and offender per
+    /// `REPORT_INTERVAL_MS`, so a single reporter can't sink anyone.
+    pub fn handle_abuse_report(
+        &mut self,
+        report: crate::discovery::AbuseReport,
+    ) -> Vec<RuntimeEffect> {
+        let now = now_ms();
+        if report.reporter == self.local_id
+            || report.offender == self.local_id
+            || !report.is_timestamp_valid(now)
+            || !self.abuse_report_due(report.reporter, report.offender, now)
+        {
+            return Vec::new();
+        }
+        if !report.verify_signature() || !report.evidence_matches() {
+            return vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                description: format!(
+                    "Invalid abuse report from {} against {}",
+                    report.reporter, report.offender
+                ),
+            })];
+        }
+
+        self.abuse_reports.insert((report.reporter, report.offender), now);
+        self.role_manager.record_violation(
+            report.offender,
+            crate::roles::scoring::REPORTED_VIOLATION_PENALTY,
+            now,
+        );
+        vec![RuntimeEffect::Emit(ProtocolEvent::AbuseReported {
+            reporter: report.reporter,
+            offender: report.offender,
+            kind: report.kind,
+        })]
+    }
+
+    /// Charge a chat envelope to its sender's delivery or forwarding
+    /// budget. Some(effects) means drop it (with a report on the first drop
+    /// of a flood). Unsigned envelopes share one budget, so a forged `from`
+    /// can't spend a real sender's.
+    fn throttle_chat(&mut self, envelope: &Envelope, signature_valid: bool) -> Option<Vec<RuntimeEffect>> {
+        if envelope.msg_type != MessageType::Chat {
+            return None;
+        }
+        let scope = if envelope.to == self.local_id {
+            crate::roles::ThrottleScope::Delivery
+        } else {
+            crate::roles::ThrottleScope::Forwarding
+        };
+        let sender = if signature_valid { envelope.from } else { self.local_id };
+        match self.chat_throttle.check(scope, sender, now_ms()) {
+            crate::roles::ThrottleVerdict::Allowed => None,
+            crate::roles::ThrottleVerdict::Dropped { first: false } => Some(Vec::new()),
+            crate::roles::ThrottleVerdict::Dropped { first: true } => {
+                tracing::warn!(%sender, ?scope, "chat throttled");
+                Some(vec![RuntimeEffect::Emit(ProtocolEvent::ChatThrottled {
+                    node_id: envelope.from,
+                    scope,
+                })])
+            }
+        }
+    }
+
     // ── Task 7: handle_incoming_chat ───────────────────────────────────
 
     /// Handle an incoming Chat / Ack / ReadReceipt / Heartbeat envelope.
@@ -604,6 +1745,7 @@ impl RuntimeState {
     /// Routes through the Router, then converts the RoutingAction into effects:
     /// - Deliver: decrypt if needed, produce DeliverMessage + ACK envelope
     /// - Forward: record relay score, forward to next_hop, send relay ACK
+    ///   (requesters are vetted against the relay policy first)
     /// - Ack: update tracker status
     /// - ReadReceipt: update tracker status
     /// - Reject: emit error event
@@ -613,7 +1755,15 @@ impl RuntimeState {
         envelope: Envelope,
         signature_valid: bool,
     ) -> Vec<RuntimeEffect> {
-        let action = self.router.route(envelope);
+        // Before routing, so a dropped message isn't marked seen and the
+        // sender's retry can get through once the budget refills
+        if let Some(effects) = self.throttle_chat(&envelope, signature_valid) {
+            return effects;
+        }
+        let (topology, contacts) = (&self.topology, &self.contacts);
+        let action = self
+            .router
+            .route_with_standing(envelope, |id| relay_standing(topology, contacts, id));
 
         match action {
             RoutingAction::Deliver {
@@ -632,19 +1782,53 @@ impl RuntimeState {
                     }
                 }
 
-                let mut effects = vec![RuntimeEffect::DeliverMessage(DeliveredMessage {
+                let mut ack = response;
+                ack.sign(&self.secret_seed);
+
+                // Unknown or malformed typed payloads are still ACKed (they
+                // did arrive) but never reach the application.
+                let (sequence, payload) = crate::ordering::unwrap(envelope.payload);
+                let (payload, content_type) = match self.unwrap_typed_payload(payload) {
+                    Ok(unwrapped) => unwrapped,
+                    Err(e) => {
+                        return vec![
+                            RuntimeEffect::Emit(ProtocolEvent::MessageRejected {
+                                reason: format!("payload from {} rejected: {e}", envelope.from),
+                            }),
+                            RuntimeEffect::SendEnvelope(ack),
+                        ];
+                    }
+                };
+
+                // Contact cards also surface as their own event (not imported yet)
+                let card = if content_type.as_deref() == Some(CONTENT_TYPE_CONTACT_CARD) {
+                    rmp_serde::from_slice::<ContactCard>(&payload)
+                        .ok()
+                        .filter(|card| card.validate().is_ok())
+                } else {
+                    None
+                };
+
+                let message = DeliveredMessage {
                     from: envelope.from,
-                    payload: envelope.payload,
+                    to: self.local_id,
+                    payload,
                     envelope_id: envelope.id,
                     timestamp: envelope.timestamp,
                     signature_valid,
                     was_encrypted,
-                })];
-
-                let mut ack = response;
-                ack.sign(&self.secret_seed);
+                    content_type,
+                    exactly_once: false,
+                };
+                let mut effects = self.deliver_in_order(sequence, message);
                 effects.push(RuntimeEffect::SendEnvelope(ack));
-
+                if let Some(card) = card {
+                    effects.push(RuntimeEffect::Emit(ProtocolEvent::ContactCardReceived {
+                        from: envelope.from,
+                        group_id: None,
+                        card,
+                    }));
+                }
                 effects
             }
 
@@ -658,6 +1842,7 @@ impl RuntimeState {
                 let now = now_ms();
 
                 self.role_manager.record_relay(sender, now);
+                self.relay_metrics.record_local_forward(now);
 
                 // Track bandwidth: estimate size from serialized envelope
                 let bytes = envelope
@@ -690,10 +1875,11 @@ impl RuntimeState {
             RoutingAction::Ack {
                 original_message_id,
                 ack_type,
-                ..
+                from,
             } => {
                 let change = match ack_type {
                     AckType::RelayForwarded => {
+                        self.record_relay_rtt(&original_message_id, from);
                         self.tracker.mark_relayed(&original_message_id)
                     }
                     AckType::RecipientReceived => {
@@ -761,18 +1947,37 @@ impl RuntimeState {
             | GroupPayload::Leave { .. }
             | GroupPayload::KickMember { .. }
             | GroupPayload::UpdateMemberRole { .. }
-            | GroupPayload::InviteMember { .. } => {
+            | GroupPayload::InviteMember { .. }
+            | GroupPayload::BanMember { .. }
+            | GroupPayload::UnbanMember { .. }
+            | GroupPayload::JoinWithToken { .. }
+            | GroupPayload::JoinRequest { .. }
+            | GroupPayload::ApproveJoin { .. } => {
                 // Extract group_id from known payloads before consuming; for Create we find it after.
                 let known_group_id = match &group_payload {
                     GroupPayload::Join { group_id, .. }
+                    | GroupPayload::JoinWithToken { group_id, .. }
+                    | GroupPayload::JoinRequest { group_id, .. }
+                    | GroupPayload::ApproveJoin { group_id, .. }
                     | GroupPayload::Leave { group_id, .. }
                     | GroupPayload::KickMember { group_id, .. }
                     | GroupPayload::UpdateMemberRole { group_id, .. }
-                    | GroupPayload::InviteMember { group_id, .. } => Some(group_id.clone()),
+                    | GroupPayload::InviteMember { group_id, .. }
+                    | GroupPayload::BanMember { group_id, .. }
+                    | GroupPayload::UnbanMember { group_id, .. } => Some(group_id.clone()),
                     _ => None,
                 };
 
-                let mut actions = self.group_hub.handle_payload(group_payload, envelope.from);
+                let mut actions = match group_payload {
+                    // Any member may bring in another device of its user
+                    GroupPayload::InviteMember { group_id, target_id }
+                        if self.devices.are_linked(&envelope.from, &target_id) =>
+                    {
+                        self.group_hub
+                            .invite_linked_device(&group_id, &envelope.from, target_id)
+                    }
+                    payload => self.group_hub.handle_payload(payload, envelope.from),
+                };
 
                 // Determine the affected group_id (for Create, extract from the Created response)
                 let group_id = known_group_id.or_else(|| {
@@ -813,8 +2018,25 @@ impl RuntimeState {
                 }
             }
 
-            // DeliveryAck: hub if we host the group, ignore otherwise
-            GroupPayload::DeliveryAck { ref group_id, .. } => {
+            // DeliveryAck / CancelScheduled / Vote: hub if we host the group, ignore otherwise
+            GroupPayload::DeliveryAck { ref group_id, .. }
+            | GroupPayload::CancelScheduled { ref group_id, .. }
+            | GroupPayload::Vote { ref group_id, .. } => {
+                if self.group_hub.get_group(group_id).is_some() {
+                    self.group_hub
+                        .handle_payload(group_payload, envelope.from)
+                } else {
+                    vec![]
+                }
+            }
+
+            // UpdateMetadata / SetMessageTtl / UpdateSettings / RegisterInviteToken /
+            // DenyJoin: hub if we host the group (membership unchanged, no shadow reassignment)
+            GroupPayload::UpdateMetadata { ref group_id, .. }
+            | GroupPayload::SetMessageTtl { ref group_id, .. }
+            | GroupPayload::UpdateSettings { ref group_id, .. }
+            | GroupPayload::RegisterInviteToken { ref group_id, .. }
+            | GroupPayload::DenyJoin { ref group_id, .. } => {
                 if self.group_hub.get_group(group_id).is_some() {
                     self.group_hub
                         .handle_payload(group_payload, envelope.from)
@@ -832,13 +2054,21 @@ impl RuntimeState {
                 group_name,
                 inviter_id,
                 inviter_username,
-            } => self.group_manager.handle_invite(
-                group_id,
-                group_name,
-                inviter_id,
-                inviter_username,
-                envelope.from,
-            ),
+            } => {
+                let actions = self.group_manager.handle_invite(
+                    group_id.clone(),
+                    group_name,
+                    inviter_id,
+                    inviter_username,
+                    envelope.from,
+                );
+                // Invited by another device of our user: join right away
+                if !actions.is_empty() && self.devices.are_linked(&self.local_id, &inviter_id) {
+                    self.group_manager.accept_invite(&group_id)
+                } else {
+                    actions
+                }
+            }
             GroupPayload::Sync {
                 group,
                 recent_messages,
@@ -864,14 +2094,84 @@ impl RuntimeState {
             } => self.group_manager.handle_member_role_changed(
                 &group_id, &node_id, new_role,
             ),
+            GroupPayload::MetadataChanged {
+                group_id,
+                name,
+                description,
+                avatar_hash,
+                config_version,
+                updated_by,
+            } => self.group_manager.handle_metadata_changed(
+                envelope.from, &group_id, name, description, avatar_hash, config_version, updated_by,
+            ),
+            GroupPayload::MessageTtlChanged {
+                group_id,
+                ttl_ms,
+                config_version,
+                updated_by,
+            } => self.group_manager.handle_message_ttl_changed(
+                envelope.from, &group_id, ttl_ms, config_version, updated_by,
+            ),
+            GroupPayload::SettingsChanged {
+                group_id,
+                settings,
+                config_version,
+                updated_by,
+            } => self.group_manager.handle_settings_changed(
+                envelope.from, &group_id, settings, config_version, updated_by,
+            ),
+            GroupPayload::JoinRequested { group_id, request } => {
+                self.group_manager.handle_join_requested(&group_id, request)
+            }
+            GroupPayload::JoinRequestResolved {
+                group_id,
+                requester,
+                approved,
+                resolved_by,
+            } => self.group_manager.handle_join_request_resolved(
+                &group_id, requester, approved, resolved_by, envelope.from,
+            ),
+            // Only the promoted node itself announces a migration; re-home and
+            // gap-fill whatever was sent while the old hub was down
             GroupPayload::HubMigration {
                 group_id,
                 new_hub_id,
                 ..
-            } => self
-                .group_manager
-                .handle_hub_migration(&group_id, new_hub_id),
-            GroupPayload::HubHeartbeat { .. } => vec![],
+            } if new_hub_id == envelope.from
+                && self
+                    .group_manager
+                    .get_group(&group_id)
+                    .is_some_and(|g| g.is_member(&envelope.from)) =>
+            {
+                let mut actions = self
+                    .group_manager
+                    .handle_hub_migration(&group_id, new_hub_id);
+                actions.extend(self.group_manager.sync_request(&group_id));
+                actions
+            }
+            GroupPayload::HubMigration { .. } => vec![],
+            GroupPayload::HubHeartbeat {
+                ref group_id,
+                shadow_id,
+                ..
+            } => self.group_manager.handle_hub_heartbeat(
+                envelope.from,
+                group_id,
+                shadow_id,
+                now_ms(),
+            ),
+
+            // Hub delegated a shard of a large group's fan-out to us
+            GroupPayload::ShardRelay {
+                group_id,
+                recipients,
+                message,
+            } => self.group_manager.handle_shard_relay(
+                envelope.from,
+                &group_id,
+                recipients,
+                message,
+            ),
 
             // Shadow ping from shadow → primary responds with pong
             GroupPayload::HubPing { ref group_id } => {
@@ -894,12 +2194,14 @@ impl RuntimeState {
                 ref members,
                 candidate_id,
                 config_version,
+                ref bans,
             } => {
                 self.group_manager.handle_shadow_sync(
                     group_id,
                     members.clone(),
                     candidate_id,
                     config_version,
+                    bans.clone(),
                 )
             }
 
@@ -912,7 +2214,8 @@ impl RuntimeState {
 
             // Member reports hub unreachable to shadow
             GroupPayload::HubUnreachable { ref group_id } => {
-                self.group_manager.handle_hub_unreachable(group_id, envelope.from)
+                let actions = self.group_manager.handle_hub_unreachable(group_id, envelope.from);
+                self.take_over_promoted_groups(actions)
             }
 
             GroupPayload::SenderKeyDistribution {
@@ -944,15 +2247,76 @@ impl RuntimeState {
             GroupPayload::SyncResponse { group_id, messages, latest_seq } => {
                 self.handle_sync_response(&group_id, messages, latest_seq)
             }
+
+            // ── Paged history ───────────────────────────────────────────
+            GroupPayload::FetchHistory { ref group_id, before, limit } => {
+                self.handle_fetch_history(envelope.from, group_id, before, limit)
+            }
+
+            GroupPayload::HistoryPage { group_id, messages, has_more } => {
+                self.group_manager.handle_history_page(&group_id, messages, has_more)
+            }
+
+            GroupPayload::DeliveryReceipt {
+                group_id,
+                message_id,
+                member,
+                read,
+                all_delivered,
+            } => self.group_manager.handle_delivery_receipt(
+                envelope.from, &group_id, message_id, member, read, all_delivered,
+            ),
+
+            // Reaction: hub if we host the group, member otherwise (like messages)
+            GroupPayload::Reaction { ref group_id, .. }
+                if self.group_hub.get_group(group_id).is_some() =>
+            {
+                self.group_hub.handle_payload(group_payload, envelope.from)
+            }
+            GroupPayload::Reaction {
+                group_id,
+                message_id,
+                emoji,
+                add,
+                reactor,
+            } => self.group_manager.handle_reaction(
+                envelope.from, &group_id, message_id, emoji, add, reactor,
+            ),
+
+            // Poll: hub if we host the group, member otherwise (tally update)
+            GroupPayload::Poll { ref group_id, .. }
+                if self.group_hub.get_group(group_id).is_some() =>
+            {
+                self.group_hub.handle_payload(group_payload, envelope.from)
+            }
+            GroupPayload::Poll { group_id, poll } => {
+                self.group_manager.handle_poll(envelope.from, &group_id, poll)
+            }
         };
 
         // Intercept self-addressed group actions: when the hub sends to itself
         // (e.g. MemberJoined broadcast when hub is also a group member),
         // process locally via GroupManager instead of network round-trip.
         let actions = self.intercept_self_group_actions(actions);
+        let actions = self.with_delivery_acks(actions);
         self.group_actions_to_effects(&actions)
     }
 
+    /// Append a DeliveryAck to the hub for every group message we just received.
+    fn with_delivery_acks(&mut self, mut actions: Vec<GroupAction>) -> Vec<GroupAction> {
+        let acks: Vec<GroupAction> = actions
+            .iter()
+            .filter_map(|action| match action {
+                GroupAction::Event(GroupEvent::MessageReceived(msg)) => self
+                    .group_manager
+                    .delivery_ack(&msg.group_id, &msg.sender_id, &msg.message_id, false),
+                _ => None,
+            })
+            .collect();
+        actions.extend(self.intercept_self_group_actions(acks));
+        actions
+    }
+
     // ── R13: Offline delivery gap-fill ──────────────────────────────────
 
     /// Handle SyncRequest from a member (hub-side).
@@ -964,9 +2328,10 @@ impl RuntimeState {
         since_seq: u64,
     ) -> Vec<GroupAction> {
         // Only respond if we're the hub for this group
-        if self.group_hub.get_group(group_id).is_none() {
+        let Some(group) = self.group_hub.get_group(group_id) else {
             return vec![];
-        }
+        };
+        let now = now_ms();
 
         // Load missed messages from SQLite (if store is available)
         let mut messages = Vec::new();
@@ -977,6 +2342,9 @@ impl RuntimeState {
             if let Ok(rows) = store.load_hub_messages_since(group_id, since_seq, MAX_SYNC_RESPONSE) {
                 for (seq, data) in rows {
                     if let Ok(msg) = rmp_serde::from_slice::<GroupMessage>(&data) {
+                        if group.is_message_expired(&msg, now) {
+                            continue;
+                        }
                         if seq > latest_seq {
                             latest_seq = seq;
                         }
@@ -990,7 +2358,10 @@ impl RuntimeState {
         // (messages received since last persist cycle)
         if let Some(history) = self.group_hub.message_history(group_id) {
             for msg in history {
-                if msg.seq > since_seq && !messages.iter().any(|m| m.message_id == msg.message_id) {
+                if msg.seq > since_seq
+                    && !group.is_message_expired(msg, now)
+                    && !messages.iter().any(|m| m.message_id == msg.message_id)
+                {
                     if msg.seq > latest_seq {
                         latest_seq = msg.seq;
                     }
@@ -1015,6 +2386,65 @@ impl RuntimeState {
         }]
     }
 
+    /// Handle FetchHistory from a member (hub-side).
+    ///
+    /// Serves one page of `seq < before` from SQLite, topped up with the
+    /// in-memory history (not yet persisted, or no store at all).
+    fn handle_fetch_history(
+        &self,
+        from: NodeId,
+        group_id: &GroupId,
+        before: u64,
+        limit: u32,
+    ) -> Vec<GroupAction> {
+        let Some(group) = self.group_hub.get_group(group_id) else {
+            return vec![];
+        };
+        // History is for members only
+        if !group.is_member(&from) {
+            return vec![];
+        }
+
+        let limit = (limit as usize).clamp(1, crate::group::types::MAX_HISTORY_PAGE);
+        // Fetch one extra to know whether there's more to scroll back to.
+        let now = now_ms();
+        let mut messages: Vec<GroupMessage> = Vec::new();
+        if let Some(ref store) = self.store {
+            if let Ok(rows) = store.load_hub_messages_before(group_id, before, limit + 1) {
+                messages.extend(
+                    rows.iter()
+                        .filter_map(|(_, data)| rmp_serde::from_slice::<GroupMessage>(data).ok())
+                        .filter(|msg| !group.is_message_expired(msg, now)),
+                );
+            }
+        }
+        if let Some(history) = self.group_hub.message_history(group_id) {
+            for msg in history {
+                if msg.seq < before
+                    && !group.is_message_expired(msg, now)
+                    && !messages.iter().any(|m| m.message_id == msg.message_id)
+                {
+                    messages.push(msg.clone());
+                }
+            }
+        }
+
+        // Keep the newest `limit` messages below `before`, ascending.
+        messages.sort_by_key(|m| std::cmp::Reverse(m.seq));
+        let has_more = messages.len() > limit;
+        messages.truncate(limit);
+        messages.reverse();
+
+        vec![GroupAction::Send {
+            to: from,
+            payload: GroupPayload::HistoryPage {
+                group_id: group_id.clone(),
+                messages,
+                has_more,
+            },
+        }]
+    }
+
     /// Handle SyncResponse from hub (member-side).
     /// Delivers missed messages and updates last_seq.
     fn handle_sync_response(
@@ -1059,38 +2489,91 @@ impl RuntimeState {
                         Ok(p) => p,
                         Err(_) => return Vec::new(),
                     };
+                if !replica_is_sealed(&payload) {
+                    tracing::debug!("dropping unsealed backup replica {}", payload.message_id);
+                    return Vec::new();
+                }
+                // A held message handed to us — deliver instead of holding it.
+                if envelope.msg_type == MessageType::BackupDeliver
+                    && payload.recipient_id == self.local_id
+                {
+                    return self.receive_backed_up_messages(envelope, vec![payload]);
+                }
                 let actions =
                     self.backup
                         .handle_replication(&payload, envelope.from, now);
-                self.backup_actions_to_effects(&actions)
+                let mut effects = self.backup_actions_to_effects(&actions);
+                // ACK with a signed receipt so the origin counts us as a holder
+                effects.extend(self.storage_receipt_ack(&payload.message_id, envelope.from));
+                effects
+            }
+
+            MessageType::BackupHandoff => {
+                let handoff: crate::backup::BackupHandoffPayload =
+                    match rmp_serde::from_slice(&envelope.payload) {
+                        Ok(p) => p,
+                        Err(_) => return Vec::new(),
+                    };
+                let replica = handoff.replica;
+                if !replica_is_sealed(&replica) {
+                    tracing::debug!("dropping unsealed backup handoff {}", replica.message_id);
+                    return Vec::new();
+                }
+                if replica.recipient_id == self.local_id {
+                    return self.receive_backed_up_messages(envelope, vec![replica]);
+                }
+                let actions = self.backup.handle_handoff(&replica, envelope.from, now);
+                let mut effects = self.backup_actions_to_effects(&actions);
+                // The departing holder is going away — the receipt goes to
+                // the origin, which now counts us instead.
+                if replica.sender_id != self.local_id {
+                    effects.extend(self.storage_receipt_ack(&replica.message_id, replica.sender_id));
+                }
+                effects
+            }
+
+            MessageType::BackupDeliverBatch => {
+                let batch: crate::backup::BackupDeliverBatchPayload =
+                    match rmp_serde::from_slice(&envelope.payload) {
+                        Ok(p) => p,
+                        Err(_) => return Vec::new(),
+                    };
+                if envelope.to != self.local_id || batch.recipient_id != self.local_id {
+                    return Vec::new();
+                }
+                self.receive_backed_up_messages(envelope, batch.entries)
             }
 
             MessageType::BackupReplicateAck => {
-                let message_id: String =
+                let receipt: crate::backup::StorageReceipt =
                     match rmp_serde::from_slice(&envelope.payload) {
                         Ok(p) => p,
                         Err(_) => return Vec::new(),
                     };
                 let actions = self
                     .backup
-                    .handle_replication_ack(&message_id, envelope.from);
+                    .handle_storage_receipt(receipt, envelope.from);
                 self.backup_actions_to_effects(&actions)
             }
 
             MessageType::BackupQuery => {
-                let recipient_id: NodeId =
+                let query: crate::backup::BackupQueryPayload =
                     match rmp_serde::from_slice(&envelope.payload) {
                         Ok(p) => p,
                         Err(_) => return Vec::new(),
                     };
-                let local_msgs =
-                    self.backup.store().get_for_recipient(&recipient_id);
-                if local_msgs.is_empty() {
+                if !query.known.is_well_formed() {
+                    return Vec::new();
+                }
+                let message_ids = self.backup.answer_query(&query);
+                if message_ids.is_empty() {
                     return Vec::new();
                 }
-                let ids: Vec<String> =
-                    local_msgs.iter().map(|m| m.message_id.clone()).collect();
-                let response_bytes = rmp_serde::to_vec(&ids)
+                let response = crate::backup::BackupQueryResponsePayload {
+                    recipient_id: query.recipient_id,
+                    message_ids,
+                };
+                let response_bytes = rmp_serde::to_vec(&response)
                     .expect("backup query response serialization");
                 let response = EnvelopeBuilder::new(
                     self.local_id,
@@ -1103,14 +2586,14 @@ impl RuntimeState {
             }
 
             MessageType::BackupQueryResponse => {
-                let message_ids: Vec<String> =
+                let response: crate::backup::BackupQueryResponsePayload =
                     match rmp_serde::from_slice(&envelope.payload) {
                         Ok(p) => p,
                         Err(_) => return Vec::new(),
                     };
                 let _new_ids = self.backup.handle_query_response(
-                    &envelope.from,
-                    &message_ids,
+                    &response.recipient_id,
+                    &response.message_ids,
                     now,
                 );
                 Vec::new()
@@ -1131,6 +2614,102 @@ impl RuntimeState {
         }
     }
 
+    /// A BackupReplicateAck carrying our signed receipt for a held entry.
+    fn storage_receipt_ack(&self, message_id: &str, to: NodeId) -> Option<RuntimeEffect> {
+        let entry = self.backup.store().get(message_id)?;
+        let receipt = crate::backup::StorageReceipt::new(
+            self.local_id,
+            entry.message_id.clone(),
+            entry.expires_at,
+            &self.secret_seed,
+        );
+        let ack_bytes = rmp_serde::to_vec(&receipt).expect("backup replicate ack serialization");
+        let ack = EnvelopeBuilder::new(self.local_id, to, MessageType::BackupReplicateAck, ack_bytes)
+            .sign(&self.secret_seed);
+        Some(RuntimeEffect::SendEnvelope(ack))
+    }
+
+    /// Deliver messages a backup holder kept for us while we were offline
+    /// (whole, or rebuilt once enough shards arrive), then confirm them in
+    /// one go so every holder drops its copy.
+    fn receive_backed_up_messages(
+        &mut self,
+        envelope: &Envelope,
+        entries: Vec<crate::backup::ReplicationPayload>,
+    ) -> Vec<RuntimeEffect> {
+        let now = now_ms();
+        let mut effects = Vec::new();
+        let mut delivered = Vec::new();
+        for entry in entries {
+            if entry.recipient_id != self.local_id {
+                continue;
+            }
+            let entry = match entry.shard {
+                Some(info) if !info.is_valid() => continue,
+                Some(_) => match self.backup.collect_shard(&entry, now) {
+                    Some(rebuilt) => rebuilt,
+                    None => continue,
+                },
+                None => entry,
+            };
+            let (opened, reached_us) = self.open_backed_up_message(envelope, &entry);
+            effects.extend(opened);
+            if reached_us {
+                delivered.push(entry.message_id);
+            }
+        }
+        if !delivered.is_empty() {
+            let actions = self.backup.confirm_delivery(&delivered, self.local_id);
+            effects.extend(self.backup_actions_to_effects(&actions));
+        }
+        effects
+    }
+
+    /// Hand one backed-up message to the group manager or chat path.
+    /// Also returns whether it was delivered (and so should be confirmed).
+    fn open_backed_up_message(
+        &mut self,
+        envelope: &Envelope,
+        payload: &crate::backup::ReplicationPayload,
+    ) -> (Vec<RuntimeEffect>, bool) {
+        let backup_id = &payload.message_id;
+        let Ok(sealed) = crate::crypto::EncryptedPayload::from_bytes(&payload.payload) else {
+            tracing::debug!("dropping unsealed backed-up message {backup_id}");
+            return (Vec::new(), false);
+        };
+        match payload.msg_type {
+            MessageType::GroupMessage => {
+                let Ok(plaintext) = crate::crypto::decrypt(&sealed, &self.secret_seed) else {
+                    tracing::debug!("cannot open backed-up message {backup_id}");
+                    return (Vec::new(), false);
+                };
+                let Ok(GroupPayload::Message(msg)) = rmp_serde::from_slice(&plaintext) else {
+                    return (Vec::new(), false);
+                };
+                let actions = self.group_manager.handle_message(msg);
+                (self.group_actions_to_effects(&actions), true)
+            }
+            MessageType::Chat => {
+                // Rebuild the original chat: its ID and sender, so dedup and
+                // the delivery ACK refer to the message the sender tracks.
+                let mut chat = envelope.clone();
+                chat.id = backup_id.clone();
+                chat.from = payload.sender_id;
+                chat.via = Vec::new();
+                chat.msg_type = MessageType::Chat;
+                chat.payload = payload.payload.clone();
+                chat.encrypted = true;
+                let effects = self.handle_incoming_chat(chat, true);
+                // Not delivered (undecryptable, duplicate): nothing to confirm
+                let delivered = effects
+                    .iter()
+                    .any(|e| matches!(e, RuntimeEffect::DeliverMessage(_)));
+                (effects, delivered)
+            }
+            _ => (Vec::new(), false),
+        }
+    }
+
     // ── Task 8: handle_peer_announce ─────────────────────────────────────
 
     /// Handle a direct QUIC PeerAnnounce envelope.
@@ -1145,6 +2724,13 @@ impl RuntimeState {
             rmp_serde::from_slice::<PeerAnnounce>(&envelope.payload)
         {
             if announce.is_timestamp_valid(now_ms()) {
+                self.backup.set_backup_preference(announce.node_id, announce.backup);
+                if let Some(interval) = announce.announce_interval_ms {
+                    self.heartbeat.set_peer_interval(announce.node_id, interval);
+                }
+                if let Some(load) = announce.relay_load {
+                    self.relay_metrics.record_load(announce.node_id, load);
+                }
                 self.heartbeat.record_heartbeat_with_source(
                     announce.node_id,
                     DiscoverySource::Direct,
@@ -1156,6 +2742,28 @@ impl RuntimeState {
                     status: PeerStatus::Online,
                     last_seen: now_ms(),
                 });
+                if self.config.enable_pex {
+                    crate::discovery::merge_pex_peers(
+                        &mut self.topology,
+                        announce.node_id,
+                        &announce.peers,
+                        self.local_id,
+                        now_ms(),
+                    );
+                }
+                self.record_hosted_identities(
+                    announce.node_id,
+                    announce.announce_interval_ms,
+                    announce.hosted,
+                );
+                let mut effects = self.record_username_claim(announce.node_id, announce.claim);
+                effects.extend(self.record_peer_presence(announce.node_id, announce.presence));
+                effects.extend(self.record_device_link(
+                    announce.node_id,
+                    announce.device,
+                    announce.revocations,
+                ));
+                return effects;
             }
         }
         Vec::new()
@@ -1177,29 +2785,53 @@ impl RuntimeState {
         }
 
         // Parse envelope
-        let envelope = match Envelope::from_bytes(raw_data) {
+        let mut envelope = match Envelope::from_bytes(raw_data) {
             Ok(e) => e,
             Err(_) => return Vec::new(),
         };
 
+        let _span = crate::logging::envelope_span(&envelope).entered();
+        tracing::debug!(bytes = raw_data.len(), "envelope received");
+
+        // Blocked senders: dropped (and counted) before any other work
+        if self.router.drop_if_blocked(&envelope.from) {
+            return Vec::new();
+        }
+
         // Anti-spam: rate check only for payload-carrying message types.
-        // Protocol-internal messages (Ack, Heartbeat, ReadReceipt) are exempt — they
+        // Protocol-internal messages (Ack, Heartbeat, ReadReceipt, commits) are exempt — they
         // are generated by the protocol itself and throttling them breaks delivery
         // confirmation and peer liveness detection.
         let now = now_ms();
         let exempt = matches!(
             envelope.msg_type,
-            MessageType::Ack | MessageType::Heartbeat | MessageType::ReadReceipt
+            MessageType::Ack
+                | MessageType::Heartbeat
+                | MessageType::ReadReceipt
+                | MessageType::ExactlyOnceCommit
+                | MessageType::Typing
         );
         if !exempt {
             let sender_score = self.role_manager.score(&envelope.from, now);
             if let Err(_reason) = self.antispam.check_rate(envelope.from, sender_score, now) {
                 let current_rate = self.antispam.compute_rate(sender_score);
-                return vec![RuntimeEffect::Emit(ProtocolEvent::SenderThrottled {
+                let mut effects = vec![RuntimeEffect::Emit(ProtocolEvent::SenderThrottled {
                     node_id: envelope.from,
                     score: sender_score,
                     current_rate,
                 })];
+                // Report only what the sender provably signed
+                if raw_data.len() <= crate::discovery::MAX_EVIDENCE_BYTES
+                    && self.abuse_report_due(self.local_id, envelope.from, now)
+                    && envelope.verify_signature().is_ok()
+                {
+                    effects.extend(self.report_violation(
+                        envelope.from,
+                        crate::discovery::ViolationKind::Spam,
+                        raw_data.to_vec(),
+                    ));
+                }
+                return effects;
             }
         }
 
@@ -1214,8 +2846,26 @@ impl RuntimeState {
             false
         };
 
-        // Record heartbeat + auto-register
-        self.heartbeat.record_heartbeat(envelope.from);
+        if !self.hooks.incoming(&mut envelope) {
+            return Vec::new();
+        }
+
+        if signature_valid {
+            self.contacts.touch(&envelope.from, now);
+            self.group_manager.note_hub_activity(envelope.from, now);
+        }
+
+        // Remember which relay the sender reaches us through
+        if signature_valid && envelope.to == self.local_id {
+            if let Some(relay) = envelope.via.first() {
+                self.relay_metrics.record_path(envelope.from, *relay);
+            }
+        }
+
+        // Authenticated traffic proves liveness + auto-register
+        if signature_valid {
+            self.heartbeat.record_heartbeat(envelope.from);
+        }
         if self.topology.get(&envelope.from).is_none() {
             self.topology.upsert(PeerInfo {
                 node_id: envelope.from,
@@ -1225,8 +2875,10 @@ impl RuntimeState {
             });
         }
 
+        let (from, to_us, msg_type) = (envelope.from, envelope.to == self.local_id, envelope.msg_type);
+
         // Dispatch by message type
-        match envelope.msg_type {
+        let mut effects = match envelope.msg_type {
             MessageType::Chat
             | MessageType::Ack
             | MessageType::ReadReceipt
@@ -1237,6 +2889,8 @@ impl RuntimeState {
                         self.local_id,
                         now_ms(),
                     );
+                    // Their message ends the typing run
+                    self.typing_in.clear(&envelope.from);
                 }
                 self.handle_incoming_chat(envelope, signature_valid)
             }
@@ -1252,6 +2906,12 @@ impl RuntimeState {
             | MessageType::GroupMemberLeft
             | MessageType::GroupHubMigration
             | MessageType::GroupDeliveryAck
+            | MessageType::GroupDeliveryReceipt
+            | MessageType::GroupReaction
+            | MessageType::GroupShardRelay
+            | MessageType::GroupCancelScheduled
+            | MessageType::GroupPoll
+            | MessageType::GroupVote
             | MessageType::GroupHubHeartbeat
             | MessageType::GroupSenderKeyDistribution
             | MessageType::GroupHubPing
@@ -1263,8 +2923,25 @@ impl RuntimeState {
             | MessageType::GroupUpdateMemberRole
             | MessageType::GroupMemberRoleChanged
             | MessageType::GroupInviteMember
+            | MessageType::GroupBanMember
+            | MessageType::GroupUnbanMember
             | MessageType::GroupSyncRequest
-            | MessageType::GroupSyncResponse => {
+            | MessageType::GroupSyncResponse
+            | MessageType::GroupFetchHistory
+            | MessageType::GroupHistoryPage
+            | MessageType::GroupUpdateMetadata
+            | MessageType::GroupMetadataChanged
+            | MessageType::GroupSetMessageTtl
+            | MessageType::GroupMessageTtlChanged
+            | MessageType::GroupUpdateSettings
+            | MessageType::GroupSettingsChanged
+            | MessageType::GroupRegisterInviteToken
+            | MessageType::GroupJoinWithToken
+            | MessageType::GroupJoinRequest
+            | MessageType::GroupJoinRequested
+            | MessageType::GroupApproveJoin
+            | MessageType::GroupDenyJoin
+            | MessageType::GroupJoinRequestResolved => {
                 self.handle_incoming_group(envelope)
             }
 
@@ -1274,38 +2951,105 @@ impl RuntimeState {
             | MessageType::BackupReplicateAck
             | MessageType::BackupQuery
             | MessageType::BackupQueryResponse
-            | MessageType::BackupConfirmDelivery => {
+            | MessageType::BackupConfirmDelivery
+            | MessageType::BackupDeliverBatch
+            | MessageType::BackupHandoff => {
                 self.handle_incoming_backup(&envelope)
             }
 
+            MessageType::GroupMeshMembership
+            | MessageType::GroupMeshSenderKey
+            | MessageType::GroupMeshMessage => self.handle_incoming_mesh(envelope, signature_valid),
+
+            MessageType::SharedStateUpdate | MessageType::SharedStateSyncRequest => {
+                self.handle_incoming_shared_state(envelope, signature_valid)
+            }
+
+            MessageType::ExactlyOnce | MessageType::ExactlyOnceCommit => {
+                self.handle_incoming_exactly_once(envelope, signature_valid)
+            }
+
             MessageType::PeerAnnounce => self.handle_peer_announce(&envelope),
+
+            MessageType::Typing => self.handle_incoming_typing(&envelope, signature_valid),
+
+            MessageType::EncryptionHandshake => {
+                self.handle_encryption_handshake(&envelope, signature_valid)
+            }
+
+            MessageType::Capabilities => self.handle_capabilities(&envelope, signature_valid),
+
+            MessageType::Trace => self.handle_incoming_trace(envelope, signature_valid),
+
+            MessageType::Request | MessageType::Response => {
+                self.handle_incoming_rpc(envelope, signature_valid)
+            }
+
+            MessageType::HistorySync => self.handle_incoming_history_sync(envelope, signature_valid),
+
+            // Datagrams only (`handle_datagram`)
+            MessageType::Realtime => Vec::new(),
+        };
+        // First contact: tell them what we speak (their answer tells us)
+        if signature_valid && to_us && msg_type != MessageType::Capabilities {
+            effects.extend(self.offer_capabilities(from, now));
         }
+        self.record_received(&mut effects);
+        effects
     }
 
-    // ── Task 9: handle_send_message ──────────────────────────────────────
+    /// Our other devices get a copy of what we received.
+    fn record_received(&mut self, effects: &mut Vec<RuntimeEffect>) {
+        let received: Vec<DirectEntry> = effects
+            .iter()
+            .filter_map(|e| match e {
+                RuntimeEffect::DeliverMessage(msg) if msg.to == self.local_id => Some(DirectEntry {
+                    peer: msg.from,
+                    outgoing: false,
+                    message_id: msg.envelope_id.clone(),
+                    payload: msg.payload.clone(),
+                    content_type: msg.content_type.clone(),
+                    timestamp: msg.timestamp,
+                }),
+                _ => None,
+            })
+            .collect();
+        for entry in received {
+            effects.extend(self.record_history(entry));
+        }
+    }
 
-    /// Build and send a chat message to a peer.
-    ///
-    /// Returns a SendWithBackupFallback effect: on success the tracker
-    /// advances to Sent; on failure the message is stored as backup.
-    pub fn handle_send_message(
+    // ── Request/response RPC ─────────────────────────────────────────────
+
+    /// Send a `Request` or `Response` envelope to `to` along the path a chat
+    /// message would take, encrypted like chat. Untracked: the requester's
+    /// timeout covers loss. Nothing is sent to ourselves.
+    pub fn send_rpc(
         &mut self,
         to: NodeId,
-        payload: Vec<u8>,
+        msg_type: MessageType,
+        request_id: String,
+        body: Vec<u8>,
     ) -> Vec<RuntimeEffect> {
-        let via = self.relay_selector.select_path(to, &self.topology);
+        let payload = crate::rpc::RpcPayload { request_id, body }.to_bytes();
+        self.send_untracked(to, msg_type, payload)
+    }
 
-        let builder = EnvelopeBuilder::new(
-            self.local_id,
+    /// Send an untracked envelope to `to` along the path a chat message
+    /// would take, encrypted like chat. Nothing is sent to ourselves.
+    fn send_untracked(&mut self, to: NodeId, msg_type: MessageType, payload: Vec<u8>) -> Vec<RuntimeEffect> {
+        if to == self.local_id {
+            return Vec::new();
+        }
+        let via = self.relay_selector.select_path_weighted(
             to,
-            MessageType::Chat,
-            payload.clone(),
-        )
-        .via(via);
-
-        let envelope = if self.config.encryption {
-            let recipient_pk = to.as_bytes();
-            match builder.encrypt_and_sign(&self.secret_seed, &recipient_pk) {
+            &self.topology,
+            &self.subnets,
+            &self.relay_metrics,
+        );
+        let builder = EnvelopeBuilder::new(self.local_id, to, msg_type, payload).via(via);
+        let envelope = if self.encrypts_for(to) {
+            match builder.encrypt_and_sign(&self.secret_seed, &to.as_bytes()) {
                 Ok(env) => env,
                 Err(e) => {
                     return vec![RuntimeEffect::Emit(ProtocolEvent::Error {
@@ -1316,1843 +3060,7168 @@ impl RuntimeState {
         } else {
             builder.sign(&self.secret_seed)
         };
+        vec![RuntimeEffect::SendEnvelope(envelope)]
+    }
 
-        let envelope_id = envelope.id.clone();
-
-        // Track message in tracker
-        let mut on_success = Vec::new();
-        if let Some(change) = self.tracker.track(envelope_id.clone(), to) {
-            on_success.push(RuntimeEffect::StatusChange(change));
+    /// Handle a request (surfaced to the app) or a response (handed to the
+    /// waiting caller); relays forward both without an ACK.
+    fn handle_incoming_rpc(&mut self, mut envelope: Envelope, signature_valid: bool) -> Vec<RuntimeEffect> {
+        if envelope.to != self.local_id {
+            return self.forward_untracked(envelope);
         }
 
-        // On success: mark as sent
-        if let Some(change) = self.tracker.mark_sent(&envelope_id) {
-            on_success.push(RuntimeEffect::StatusChange(change));
+        // Correlation relies on `from`, so unsigned or forged envelopes are dropped
+        if !signature_valid {
+            return Vec::new();
         }
+        if envelope.encrypted && envelope.decrypt_payload(&self.secret_seed).is_err() {
+            return Vec::new();
+        }
+        let Ok(rpc) = crate::rpc::RpcPayload::from_bytes(&envelope.payload) else {
+            return Vec::new();
+        };
+        if envelope.msg_type == MessageType::Request {
+            vec![RuntimeEffect::Emit(ProtocolEvent::RequestReceived {
+                from: envelope.from,
+                request_id: rpc.request_id,
+                payload: rpc.body,
+            })]
+        } else {
+            vec![RuntimeEffect::CompleteRequest(crate::rpc::Response {
+                from: envelope.from,
+                request_id: rpc.request_id,
+                payload: rpc.body,
+            })]
+        }
+    }
 
-        // On failure: store backup + emit error
-        let backup_actions = self.backup.store_message(
-            envelope_id.clone(),
-            payload,
-            to,
-            self.local_id,
-            now_ms(),
-            None,
-        );
-        let mut on_failure = self.backup_actions_to_effects(&backup_actions);
-        on_failure.push(RuntimeEffect::Emit(ProtocolEvent::Error {
-            description: format!(
-                "send to {} failed (backed up)",
-                envelope.via.first().copied().unwrap_or(to)
-            ),
-        }));
+    /// Relay an untracked envelope addressed to someone else (no ACK).
+    fn forward_untracked(&mut self, envelope: Envelope) -> Vec<RuntimeEffect> {
+        let (topology, contacts) = (&self.topology, &self.contacts);
+        let action = self
+            .router
+            .route_with_standing(envelope, |id| relay_standing(topology, contacts, id));
+        match action {
+            RoutingAction::Forward {
+                envelope, next_hop, ..
+            } => {
+                self.relay_metrics.record_local_forward(now_ms());
+                vec![RuntimeEffect::SendEnvelopeTo {
+                    target: next_hop,
+                    envelope,
+                }]
+            }
+            RoutingAction::Reject { reason } => {
+                vec![RuntimeEffect::Emit(ProtocolEvent::MessageRejected { reason })]
+            }
+            _ => Vec::new(),
+        }
+    }
 
-        // Cache envelope for potential ACK-timeout retry (R9.2)
-        self.pending_envelopes
-            .insert(envelope_id, envelope.clone());
+    // ── History sync between our devices ─────────────────────────────────
 
-        vec![RuntimeEffect::SendWithBackupFallback {
-            envelope,
-            on_success,
-            on_failure,
-        }]
+    /// Our other linked devices.
+    fn sibling_devices(&self) -> Vec<NodeId> {
+        let user = self.devices.user_of(&self.local_id).unwrap_or(self.local_id);
+        self.devices
+            .devices_of(&user)
+            .into_iter()
+            .filter(|d| *d != self.local_id)
+            .collect()
     }
 
-    // ── Task 9: handle_send_group_message ────────────────────────────────
-
-    /// Build and send a text message to a group (via hub relay).
-    pub fn handle_send_group_message(
-        &mut self,
-        group_id: crate::group::GroupId,
-        text: String,
-    ) -> Vec<RuntimeEffect> {
-        let mut pre_effects = Vec::new();
+    /// Add a 1:1 message to the history log and forward it to our other
+    /// devices. Nothing happens for a message already logged.
+    fn record_history(&mut self, entry: DirectEntry) -> Vec<RuntimeEffect> {
+        if !self.log_history(entry.clone()) {
+            return Vec::new();
+        }
+        let payload = HistorySyncPayload::Entries {
+            entries: vec![HistoryEntry::Direct(entry)],
+            done: false,
+        }
+        .to_bytes();
+        let mut effects = Vec::new();
+        for device in self.sibling_devices() {
+            effects.extend(self.send_untracked(device, MessageType::HistorySync, payload.clone()));
+        }
+        effects
+    }
 
-        // R14.1 dual-trigger rotation before sending.
-        let rotation_actions = self.group_manager.maybe_rotate_local_sender_key(&group_id);
-        if !rotation_actions.is_empty() {
-            let rotation_actions = self.intercept_self_group_actions(rotation_actions);
-            pre_effects.extend(self.group_actions_to_effects(&rotation_actions));
+    /// Add an entry to the log and the store. Returns whether it was new.
+    fn log_history(&mut self, entry: DirectEntry) -> bool {
+        if let Some(store) = &self.store {
+            if !self.history.contains(&entry.message_id) {
+                if let Err(e) = store.save_history_entry(&entry) {
+                    tracing::warn!("history log write failed: {e}");
+                }
+            }
+        }
+        let full = self.history.len() >= crate::history_sync::MAX_HISTORY_ENTRIES;
+        if !self.history.record(entry) {
+            return false;
         }
+        if let (true, Some(store), Some(oldest)) = (full, &self.store, self.history.oldest_timestamp()) {
+            let _ = store.delete_history_before(oldest);
+        }
+        true
+    }
 
-        let Some(group) = self.group_manager.get_group(&group_id) else {
-            return vec![RuntimeEffect::Emit(ProtocolEvent::Error {
-                description: format!("not a member of group {group_id}"),
-            })];
+    /// Answer a sibling's sync request or take in the entries it sent.
+    /// Only our own linked devices are heard.
+    fn handle_incoming_history_sync(&mut self, mut envelope: Envelope, signature_valid: bool) -> Vec<RuntimeEffect> {
+        if envelope.to != self.local_id {
+            return self.forward_untracked(envelope);
+        }
+        if !signature_valid || !self.devices.are_linked(&self.local_id, &envelope.from) {
+            return Vec::new();
+        }
+        if envelope.encrypted && envelope.decrypt_payload(&self.secret_seed).is_err() {
+            return Vec::new();
+        }
+        let Ok(payload) = HistorySyncPayload::from_bytes(&envelope.payload) else {
+            return Vec::new();
         };
-
-        let hub_id = group.hub_relay_id;
-
-        // Build message — encrypted if we have a sender key, plaintext otherwise
-        let mut msg = if let Some(sender_key) = self.group_manager.local_sender_key(&group_id) {
-            let key = sender_key.key;
-            let epoch = sender_key.epoch;
-            GroupMessage::new_encrypted(
-                group_id.clone(),
-                self.local_id,
-                self.config.username.clone(),
-                text,
-                &key,
-                epoch,
-            )
-        } else {
-            GroupMessage::new(
-                group_id.clone(),
+        match payload {
+            HistorySyncPayload::Request { since } => {
+                let mut entries: Vec<HistoryEntry> = self
+                    .history
+                    .since(since)
+                    .cloned()
+                    .map(HistoryEntry::Direct)
+                    .collect();
+                for group in self.group_manager.all_groups() {
+                    entries.extend(
+                        self.group_manager
+                            .message_history(&group.group_id)
+                            .iter()
+                            .filter(|m| m.sent_at > since)
+                            .cloned()
+                            .map(HistoryEntry::Group),
+                    );
+                }
+                entries.sort_by_key(HistoryEntry::timestamp);
+                let skip = entries.len().saturating_sub(crate::history_sync::MAX_SYNC_ENTRIES);
+                let pages = crate::history_sync::paginate(entries.split_off(skip));
+                let mut effects = Vec::new();
+                for page in pages {
+                    effects.extend(self.send_untracked(envelope.from, MessageType::HistorySync, page.to_bytes()));
+                }
+                effects
+            }
+            HistorySyncPayload::Entries { entries, done } => {
+                let mut fresh = Vec::new();
+                for entry in entries {
+                    let new = match &entry {
+                        HistoryEntry::Direct(direct) => self.log_history(direct.clone()),
+                        HistoryEntry::Group(message) => !self
+                            .group_manager
+                            .message_history(&message.group_id)
+                            .iter()
+                            .any(|m| m.message_id == message.message_id),
+                    };
+                    if new {
+                        fresh.push(entry);
+                    }
+                }
+                if fresh.is_empty() && !done {
+                    return Vec::new();
+                }
+                vec![RuntimeEffect::Emit(ProtocolEvent::HistorySynced {
+                    from: envelope.from,
+                    entries: fresh,
+                    done,
+                })]
+            }
+        }
+    }
+
+    // ── Route tracing ────────────────────────────────────────────────────
+
+    /// Send a route trace to `target` along the path a chat message would
+    /// take. Returns the trace id, or None if `target` is us or too many
+    /// traces are in flight.
+    pub fn trace_route(&mut self, target: NodeId) -> (Option<String>, Vec<RuntimeEffect>) {
+        let now = now_ms();
+        let trace = TracePayload::new(self.local_id, now, &self.secret_seed);
+        if target == self.local_id || !self.traces.start(trace.trace_id.clone(), target, now) {
+            return (None, Vec::new());
+        }
+        let via = self.relay_selector.select_path_weighted(
+            target,
+            &self.topology,
+            &self.subnets,
+            &self.relay_metrics,
+        );
+        // Unsigned: relays extend the payload, our hop record vouches for it
+        let envelope = Envelope::new_via(
+            self.local_id,
+            target,
+            via,
+            MessageType::Trace,
+            trace.to_bytes(),
+        );
+        (Some(trace.trace_id), vec![RuntimeEffect::SendEnvelope(envelope)])
+    }
+
+    /// Handle a trace: relays append their hop and forward it, the
+    /// recipient signs the path back, and the sender surfaces the reply.
+    fn handle_incoming_trace(&mut self, envelope: Envelope, signature_valid: bool) -> Vec<RuntimeEffect> {
+        let Ok(mut trace) = TracePayload::from_bytes(&envelope.payload) else {
+            return Vec::new();
+        };
+        let now = now_ms();
+
+        if envelope.to == self.local_id && trace.reply {
+            let from_recipient = trace
+                .hops
+                .last()
+                .is_some_and(|h| h.node_id == envelope.from && h.kind == TraceHopKind::Destination);
+            if !signature_valid
+                || !from_recipient
+                || !trace.verify(&self.local_id)
+                || !self.traces.complete(&trace.trace_id, &envelope.from)
+            {
+                return Vec::new();
+            }
+            return vec![RuntimeEffect::Emit(ProtocolEvent::RouteTraced {
+                trace_id: trace.trace_id,
+                target: envelope.from,
+                hops: trace.hops,
+            })];
+        }
+
+        // Outbound traces are only as good as the sender's record
+        if !trace.reply && !trace.verify(&envelope.from) {
+            return Vec::new();
+        }
+
+        if envelope.to == self.local_id {
+            if !trace.append_hop(self.local_id, TraceHopKind::Destination, now, &self.secret_seed) {
+                return Vec::new();
+            }
+            trace.reply = true;
+            let via: Vec<NodeId> = envelope.via.iter().rev().copied().collect();
+            let reply = EnvelopeBuilder::new(
                 self.local_id,
-                self.config.username.clone(),
-                text,
+                envelope.from,
+                MessageType::Trace,
+                trace.to_bytes(),
             )
+            .via(via)
+            .sign(&self.secret_seed);
+            return vec![RuntimeEffect::SendEnvelope(reply)];
+        }
+
+        let (topology, contacts) = (&self.topology, &self.contacts);
+        let action = self
+            .router
+            .route_with_standing(envelope, |id| relay_standing(topology, contacts, id));
+        match action {
+            // No relay ACK: traces aren't tracked
+            RoutingAction::Forward {
+                mut envelope,
+                next_hop,
+                ..
+            } => {
+                if !trace.reply {
+                    if !trace.append_hop(self.local_id, TraceHopKind::Relay, now, &self.secret_seed) {
+                        return Vec::new();
+                    }
+                    envelope.payload = trace.to_bytes();
+                }
+                self.relay_metrics.record_local_forward(now);
+                vec![RuntimeEffect::SendEnvelopeTo {
+                    target: next_hop,
+                    envelope,
+                }]
+            }
+            RoutingAction::Reject { reason } => {
+                vec![RuntimeEffect::Emit(ProtocolEvent::MessageRejected { reason })]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    // ── Typing indicators ────────────────────────────────────────────────
+
+    /// Tell `to` we started (or stopped) typing.
+    ///
+    /// Sent directly and untracked; calls beyond the rate limit are no-ops,
+    /// so UIs may call this on every keystroke.
+    pub fn send_typing(&mut self, to: NodeId, typing: bool) -> Vec<RuntimeEffect> {
+        if to == self.local_id || !self.typing_out.allow(to, typing, now_ms()) {
+            return Vec::new();
+        }
+        let bytes = match rmp_serde::to_vec(&TypingPayload { typing }) {
+            Ok(b) => b,
+            Err(_) => return Vec::new(),
         };
+        let envelope = EnvelopeBuilder::new(self.local_id, to, MessageType::Typing, bytes)
+            .sign(&self.secret_seed);
+        vec![RuntimeEffect::SendEnvelope(envelope)]
+    }
 
-        msg.sign(&self.secret_seed);
-        self.group_manager.note_local_message_sent(&group_id);
-        let payload = GroupPayload::Message(msg);
+    /// Handle an incoming typing signal (dropped unless signed, for us, and within limits).
+    fn handle_incoming_typing(&mut self, envelope: &Envelope, signature_valid: bool) -> Vec<RuntimeEffect> {
+        if !signature_valid || envelope.to != self.local_id {
+            return Vec::new();
+        }
+        let Ok(payload) = rmp_serde::from_slice::<TypingPayload>(&envelope.payload) else {
+            return Vec::new();
+        };
+        if !self.typing_in.allow(envelope.from, payload.typing, now_ms()) {
+            return Vec::new();
+        }
+        vec![RuntimeEffect::Emit(ProtocolEvent::PeerTyping {
+            node_id: envelope.from,
+            typing: payload.typing,
+        })]
+    }
 
-        // If we ARE the hub, handle locally without network round-trip
-        if hub_id == self.local_id {
-            let actions = self.group_hub.handle_payload(payload, self.local_id);
-            let actions = self.intercept_self_group_actions(actions);
-            let mut effects = pre_effects;
-            effects.extend(self.group_actions_to_effects(&actions));
-            return effects;
+    // ── Capability handshake ─────────────────────────────────────────────
+
+    /// Send `peer` our capabilities unless we know theirs or asked lately.
+    fn offer_capabilities(&mut self, peer: NodeId, now: u64) -> Vec<RuntimeEffect> {
+        if self.topology.capabilities(&peer).is_some()
+            || self
+                .capability_offers
+                .get(&peer)
+                .is_some_and(|&at| now.saturating_sub(at) < CAPABILITY_RETRY_MS)
+        {
+            return Vec::new();
+        }
+        if self.capability_offers.len() >= MAX_CAPABILITY_OFFERS {
+            self.capability_offers
+                .retain(|_, at| now.saturating_sub(*at) < CAPABILITY_RETRY_MS);
+            if self.capability_offers.len() >= MAX_CAPABILITY_OFFERS {
+                return Vec::new();
+            }
         }
+        self.capability_offers.insert(peer, now);
+        self.send_capabilities(peer, false)
+    }
 
-        let payload_bytes =
-            rmp_serde::to_vec(&payload).expect("group msg serialization");
+    /// Our capabilities to `to`. Direct, signed, untracked.
+    fn send_capabilities(&self, to: NodeId, reply: bool) -> Vec<RuntimeEffect> {
+        let handshake = crate::capabilities::CapabilityHandshake {
+            capabilities: PeerCapabilities::local(),
+            reply,
+        };
+        let Ok(bytes) = rmp_serde::to_vec(&handshake) else {
+            return Vec::new();
+        };
+        let envelope = EnvelopeBuilder::new(self.local_id, to, MessageType::Capabilities, bytes)
+            .sign(&self.secret_seed);
+        vec![RuntimeEffect::SendEnvelope(envelope)]
+    }
 
-        let via = self.relay_selector.select_path(hub_id, &self.topology);
-        let envelope = EnvelopeBuilder::new(
-            self.local_id,
-            hub_id,
-            MessageType::GroupMessage,
-            payload_bytes,
-        )
-        .via(via)
-        .sign(&self.secret_seed);
+    /// Cache a peer's capabilities and answer with ours.
+    fn handle_capabilities(&mut self, envelope: &Envelope, signature_valid: bool) -> Vec<RuntimeEffect> {
+        if !signature_valid || envelope.to != self.local_id {
+            return Vec::new();
+        }
+        let Ok(handshake) =
+            rmp_serde::from_slice::<crate::capabilities::CapabilityHandshake>(&envelope.payload)
+        else {
+            return Vec::new();
+        };
+        let from = envelope.from;
+        tracing::debug!(
+            %from,
+            version = handshake.capabilities.protocol_version,
+            features = handshake.capabilities.features.bits(),
+            "peer capabilities"
+        );
+        self.topology.set_capabilities(from, handshake.capabilities);
+        self.capability_offers.remove(&from);
+        if handshake.reply {
+            Vec::new()
+        } else {
+            self.send_capabilities(from, true)
+        }
+    }
 
-        pre_effects.push(RuntimeEffect::SendEnvelope(envelope));
-        pre_effects
+    /// What `peer` announced it supports.
+    pub fn peer_capabilities(&self, peer: &NodeId) -> Option<PeerCapabilities> {
+        self.topology.capabilities(peer)
     }
 
-    // ── Task 9: handle_send_read_receipt ─────────────────────────────────
+    // ── Conversation encryption ──────────────────────────────────────────
 
-    /// Build and send a read receipt for a previously received message.
-    pub fn handle_send_read_receipt(
+    /// The negotiated encryption with `peer` (the global flag for strangers).
+    pub fn conversation_encryption(&self, peer: &NodeId) -> EncryptionMode {
+        self.contacts
+            .encryption_mode(peer, EncryptionMode::from_flag(self.config.encryption))
+    }
+
+    /// Whether envelopes to `to` are sealed.
+    fn encrypts_for(&self, to: NodeId) -> bool {
+        self.conversation_encryption(&to) != EncryptionMode::Plaintext
+    }
+
+    /// Set what we want with a contact and send them a handshake.
+    fn set_conversation_encryption(
         &mut self,
-        to: NodeId,
-        original_message_id: String,
+        node_id: NodeId,
+        mode: Option<EncryptionMode>,
     ) -> Vec<RuntimeEffect> {
-        let payload = ReadReceiptPayload {
-            original_message_id,
-            read_at: now_ms(),
+        let before = self.conversation_encryption(&node_id);
+        let result = self.contacts.set_encryption(&node_id, mode).cloned();
+        let updated = result.is_ok();
+        let mut effects = self.persist_contact_change(result);
+        if updated {
+            effects.extend(self.send_encryption_handshake(node_id, false));
+            effects.extend(self.encryption_changed(node_id, before));
         }
-        .to_bytes();
-
-        let via = self.relay_selector.select_path(to, &self.topology);
-        let envelope = EnvelopeBuilder::new(
-            self.local_id,
-            to,
-            MessageType::ReadReceipt,
-            payload,
-        )
-        .via(via)
-        .sign(&self.secret_seed);
+        effects
+    }
 
+    /// Tell `to` the mode we want with them. Direct, signed, untracked.
+    fn send_encryption_handshake(&mut self, to: NodeId, reply: bool) -> Vec<RuntimeEffect> {
+        let default = EncryptionMode::from_flag(self.config.encryption);
+        let handshake = EncryptionHandshake {
+            preferred: self
+                .contacts
+                .get(&to)
+                .and_then(|c| c.encryption)
+                .unwrap_or(default),
+            supported: SUPPORTED_ENCRYPTION_MODES.to_vec(),
+            reply,
+        };
+        let Ok(bytes) = rmp_serde::to_vec(&handshake) else {
+            return Vec::new();
+        };
+        let envelope = EnvelopeBuilder::new(self.local_id, to, MessageType::EncryptionHandshake, bytes)
+            .sign(&self.secret_seed);
         vec![RuntimeEffect::SendEnvelope(envelope)]
     }
 
-    // ── Task 9: handle_command (unified dispatcher) ──────────────────────
+    /// Remember a contact's wanted mode and answer with ours. Handshakes
+    /// from strangers, unsigned ones and modes we can't speak are ignored.
+    fn handle_encryption_handshake(&mut self, envelope: &Envelope, signature_valid: bool) -> Vec<RuntimeEffect> {
+        if !signature_valid || envelope.to != self.local_id {
+            return Vec::new();
+        }
+        let Ok(handshake) = rmp_serde::from_slice::<EncryptionHandshake>(&envelope.payload) else {
+            return Vec::new();
+        };
+        let from = envelope.from;
+        if self.contacts.get(&from).is_none()
+            || !SUPPORTED_ENCRYPTION_MODES.contains(&handshake.preferred)
+            || !handshake.supported.contains(&handshake.preferred)
+        {
+            return Vec::new();
+        }
+        let before = self.conversation_encryption(&from);
+        let mut effects = match self.contacts.set_peer_encryption(&from, handshake.preferred).cloned() {
+            Some(contact) => self.persist_contact_change(Ok(contact)),
+            None => Vec::new(),
+        };
+        if !handshake.reply {
+            effects.extend(self.send_encryption_handshake(from, true));
+        }
+        effects.extend(self.encryption_changed(from, before));
+        effects
+    }
 
-    /// Unified command dispatcher — processes a RuntimeCommand and returns effects.
+    fn encryption_changed(&self, node_id: NodeId, before: EncryptionMode) -> Vec<RuntimeEffect> {
+        let mode = self.conversation_encryption(&node_id);
+        if mode == before {
+            return Vec::new();
+        }
+        tracing::info!(%node_id, ?mode, "conversation encryption changed");
+        vec![RuntimeEffect::Emit(ProtocolEvent::ConversationEncryptionChanged { node_id, mode })]
+    }
+
+    // ── Real-time lane ───────────────────────────────────────────────────
+
+    /// Send `payload` to `to` as one datagram: signed, numbered, untracked.
+    /// Payloads over `MAX_REALTIME_PAYLOAD` are dropped.
+    pub fn send_realtime(&mut self, to: NodeId, payload: &[u8]) -> Vec<RuntimeEffect> {
+        if to == self.local_id || payload.len() > MAX_REALTIME_PAYLOAD {
+            return Vec::new();
+        }
+        // Peers that told us they have no datagram lane never get frames
+        if self
+            .peer_capabilities(&to)
+            .is_some_and(|c| !c.supports(crate::capabilities::Features::DATAGRAMS))
+        {
+            return vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                description: format!("real-time lane: {to} does not support datagrams"),
+            })];
+        }
+        let seq = self.realtime.next_seq(to);
+        let frame = crate::realtime::encode_frame(seq, payload);
+        let envelope = EnvelopeBuilder::new(self.local_id, to, MessageType::Realtime, frame)
+            .sign(&self.secret_seed);
+        match envelope.to_bytes() {
+            Ok(bytes) => vec![RuntimeEffect::SendDatagram { to, bytes }],
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Handle a datagram from `from` (authenticated by the transport):
+    /// dropped unless it's a signed real-time envelope from that peer to us.
+    pub fn handle_datagram(&mut self, from: NodeId, data: &[u8]) -> Vec<RuntimeEffect> {
+        let Ok(envelope) = Envelope::from_bytes(data) else {
+            return Vec::new();
+        };
+        if envelope.msg_type != MessageType::Realtime
+            || envelope.from != from
+            || envelope.to != self.local_id
+            || self.router.drop_if_blocked(&from)
+            || envelope.verify_signature().is_err()
+        {
+            return Vec::new();
+        }
+        let Some((seq, payload)) = crate::realtime::decode_frame(&envelope.payload) else {
+            return Vec::new();
+        };
+        self.realtime.on_receive(from, seq, envelope.timestamp, now_ms());
+        vec![RuntimeEffect::Emit(ProtocolEvent::RealtimeReceived {
+            from,
+            seq,
+            payload: payload.to_vec(),
+        })]
+    }
+
+    /// Real-time traffic stats with `peer` (None if none yet).
+    pub fn realtime_stats(&self, peer: &NodeId) -> Option<RealtimeStats> {
+        self.realtime.stats(peer)
+    }
+
+    // ── Shared state (LWW CRDT) ──────────────────────────────────────────
+
+    /// Handle an incoming shared state update / sync request.
     ///
-    /// Some commands (GetConnectedPeers, Shutdown) are handled in the loop
-    /// because they need transport access; they return empty effects here.
-    pub fn handle_command(
+    /// Peer-set checks rely on `from`, so unsigned or forged envelopes are dropped.
+    fn handle_incoming_shared_state(
         &mut self,
-        cmd: RuntimeCommand,
+        mut envelope: Envelope,
+        signature_valid: bool,
     ) -> Vec<RuntimeEffect> {
-        match cmd {
-            RuntimeCommand::SendMessage { to, payload } => {
-                self.subnets
-                    .record_communication(self.local_id, to, now_ms());
-                self.handle_send_message(to, payload)
-            }
+        if !signature_valid {
+            return Vec::new();
+        }
+        if envelope.encrypted && envelope.decrypt_payload(&self.secret_seed).is_err() {
+            return Vec::new();
+        }
+        let payload: SharedStatePayload = match rmp_serde::from_slice(&envelope.payload) {
+            Ok(p) => p,
+            Err(_) => return Vec::new(),
+        };
+        let actions = self.shared_state.handle_payload(envelope.from, payload);
+        self.shared_state_actions_to_effects(actions)
+    }
 
-            RuntimeCommand::SendGroupMessage { group_id, text } => {
-                self.handle_send_group_message(group_id, text)
+    /// Convert SharedStateActions into RuntimeEffects (encrypted like chat).
+    fn shared_state_actions_to_effects(
+        &self,
+        actions: Vec<SharedStateAction>,
+    ) -> Vec<RuntimeEffect> {
+        let mut effects = Vec::new();
+        for action in actions {
+            match action {
+                SharedStateAction::Send { to, payload } => {
+                    let msg_type = match payload {
+                        SharedStatePayload::Update { .. } => MessageType::SharedStateUpdate,
+                        SharedStatePayload::SyncRequest { .. } => {
+                            MessageType::SharedStateSyncRequest
+                        }
+                    };
+                    let bytes = rmp_serde::to_vec(&payload).expect("shared state serialization");
+                    let via = self.relay_selector.select_path_weighted(to, &self.topology, &self.subnets, &self.relay_metrics);
+                    let builder = EnvelopeBuilder::new(self.local_id, to, msg_type, bytes).via(via);
+                    let envelope = if self.encrypts_for(to) {
+                        match builder.encrypt_and_sign(&self.secret_seed, &to.as_bytes()) {
+                            Ok(env) => env,
+                            Err(e) => {
+                                effects.push(RuntimeEffect::Emit(ProtocolEvent::Error {
+                                    description: format!("encrypt failed for {to}: {e}"),
+                                }));
+                                continue;
+                            }
+                        }
+                    } else {
+                        builder.sign(&self.secret_seed)
+                    };
+                    effects.push(RuntimeEffect::SendEnvelope(envelope));
+                }
+                SharedStateAction::Changed(change) => {
+                    effects.push(RuntimeEffect::Emit(ProtocolEvent::SharedStateChanged {
+                        doc_id: change.doc_id,
+                        key: change.key,
+                        value: change.value,
+                        updated_by: change.updated_by,
+                    }));
+                }
             }
+        }
+        effects
+    }
 
-            RuntimeCommand::SendReadReceipt {
-                to,
-                original_message_id,
-            } => self.handle_send_read_receipt(to, original_message_id),
+    // ── Hubless mesh groups ──────────────────────────────────────────────
 
-            RuntimeCommand::AddPeer { node_id } => {
-                self.heartbeat.record_heartbeat_with_source(
-                    node_id,
-                    DiscoverySource::Direct,
-                    String::new(),
-                );
-                self.topology.upsert(PeerInfo {
-                    node_id,
-                    role: PeerRole::Peer,
-                    status: PeerStatus::Online,
-                    last_seen: now_ms(),
-                });
-                Vec::new()
-            }
+    /// Handle an incoming mesh group payload.
+    ///
+    /// Membership checks rely on `from`, so unsigned or forged envelopes are dropped.
+    fn handle_incoming_mesh(
+        &mut self,
+        mut envelope: Envelope,
+        signature_valid: bool,
+    ) -> Vec<RuntimeEffect> {
+        if !signature_valid {
+            return Vec::new();
+        }
+        if envelope.encrypted && envelope.decrypt_payload(&self.secret_seed).is_err() {
+            return Vec::new();
+        }
+        let payload: MeshPayload = match rmp_serde::from_slice(&envelope.payload) {
+            Ok(p) => p,
+            Err(_) => return Vec::new(),
+        };
+        let actions = self
+            .mesh_groups
+            .handle_payload(envelope.from, payload, &self.secret_seed);
+        self.mesh_actions_to_effects(actions)
+    }
 
-            RuntimeCommand::UpsertPeer { info } => {
-                self.heartbeat.record_heartbeat_with_source(
-                    info.node_id,
-                    DiscoverySource::Direct,
-                    String::new(),
-                );
-                self.topology.upsert(info);
-                Vec::new()
+    /// Convert MeshActions into RuntimeEffects (envelopes encrypted like chat).
+    fn mesh_actions_to_effects(&self, actions: Vec<MeshAction>) -> Vec<RuntimeEffect> {
+        let mut effects = Vec::new();
+        for action in actions {
+            match action {
+                MeshAction::Send { to, payload } => {
+                    let msg_type = match payload {
+                        MeshPayload::Membership { .. } => MessageType::GroupMeshMembership,
+                        MeshPayload::SenderKey { .. } => MessageType::GroupMeshSenderKey,
+                        MeshPayload::Message(_) => MessageType::GroupMeshMessage,
+                    };
+                    let bytes = rmp_serde::to_vec(&payload).expect("mesh payload serialization");
+                    let via = self.relay_selector.select_path_weighted(to, &self.topology, &self.subnets, &self.relay_metrics);
+                    let builder = EnvelopeBuilder::new(self.local_id, to, msg_type, bytes).via(via);
+                    let envelope = if self.encrypts_for(to) {
+                        match builder.encrypt_and_sign(&self.secret_seed, &to.as_bytes()) {
+                            Ok(env) => env,
+                            Err(e) => {
+                                effects.push(RuntimeEffect::Emit(ProtocolEvent::Error {
+                                    description: format!("encrypt failed for {to}: {e}"),
+                                }));
+                                continue;
+                            }
+                        }
+                    } else {
+                        builder.sign(&self.secret_seed)
+                    };
+                    effects.push(RuntimeEffect::SendEnvelope(envelope));
+                }
+                MeshAction::Event(event) => effects.extend(self.surface_group_event(&event)),
             }
+        }
+        effects
+    }
 
-            RuntimeCommand::RemovePeer { node_id } => {
-                self.topology.remove(&node_id);
-                self.heartbeat.untrack_peer(&node_id);
-                Vec::new()
-            }
+    /// Convert a fallible mesh operation into effects (errors surfaced as events).
+    fn mesh_result_to_effects(
+        &self,
+        result: Result<Vec<MeshAction>, crate::TomProtocolError>,
+    ) -> Vec<RuntimeEffect> {
+        match result {
+            Ok(actions) => self.mesh_actions_to_effects(actions),
+            Err(e) => vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                description: e.to_string(),
+            })],
+        }
+    }
 
-            RuntimeCommand::CreateGroup {
-                name,
-                hub_relay_id,
-                initial_members,
-                invite_only,
-            } => {
-                // If we ARE the hub, handle creation locally without network round-trip
-                if hub_relay_id == self.local_id {
-                    let payload = GroupPayload::Create {
-                        group_name: name.clone(),
-                        creator_username: self.config.username.clone(),
-                        initial_members: initial_members.clone(),
-                        invite_only,
-                    };
-                    let actions = self.group_hub.handle_payload(payload, self.local_id);
-                    // Intercept self-addressed actions (e.g. Created → self)
-                    let actions = self.intercept_self_group_actions(actions);
-                    self.group_actions_to_effects(&actions)
-                } else {
-                    // Remote hub — send GroupPayload::Create over network
-                    let actions = self.group_manager.create_group_with_options(
-                        name,
-                        hub_relay_id,
-                        initial_members,
-                        invite_only,
+    // ── Exactly-once delivery ────────────────────────────────────────────
+
+    /// Build, persist and send an exactly-once message.
+    ///
+    /// The outbox entry is written before the first send: if it can't be
+    /// persisted, nothing goes out. Returns the message id on success.
+    pub fn handle_send_exactly_once(
+        &mut self,
+        to: NodeId,
+        payload: Vec<u8>,
+    ) -> (Option<String>, Vec<RuntimeEffect>) {
+        let via = self.relay_selector.select_path_weighted(to, &self.topology, &self.subnets, &self.relay_metrics);
+        let builder =
+            EnvelopeBuilder::new(self.local_id, to, MessageType::ExactlyOnce, payload).via(via);
+        let envelope = if self.encrypts_for(to) {
+            match builder.encrypt_and_sign(&self.secret_seed, &to.as_bytes()) {
+                Ok(env) => env,
+                Err(e) => {
+                    return (
+                        None,
+                        vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                            description: format!("encrypt failed for {to}: {e}"),
+                        })],
                     );
-                    self.group_actions_to_effects(&actions)
                 }
             }
-
-            RuntimeCommand::AcceptInvite { group_id } => {
-                let actions =
-                    self.group_manager.accept_invite(&group_id);
-                let actions = self.intercept_self_group_actions(actions);
-                self.group_actions_to_effects(&actions)
+        } else {
+            builder.sign(&self.secret_seed)
+        };
+        let bytes = match envelope.to_bytes() {
+            Ok(b) => b,
+            Err(e) => {
+                return (
+                    None,
+                    vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                        description: format!("exactly-once envelope for {to}: {e}"),
+                    })],
+                );
             }
+        };
 
-            RuntimeCommand::DeclineInvite { group_id } => {
-                self.group_manager.decline_invite(&group_id);
-                Vec::new()
+        let message_id = envelope.id.clone();
+        let entry = self
+            .exactly_once_outbox
+            .insert(message_id.clone(), to, bytes, now_ms());
+        if let Some(ref store) = self.store {
+            if let Err(e) = store.save_outbox_entry(&entry) {
+                self.exactly_once_outbox.commit(&message_id, to);
+                return (
+                    None,
+                    vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                        description: format!("exactly-once outbox write failed: {e}"),
+                    })],
+                );
             }
+        }
+        (Some(message_id), vec![RuntimeEffect::SendEnvelope(envelope)])
+    }
 
-            RuntimeCommand::LeaveGroup { group_id } => {
-                let actions =
-                    self.group_manager.leave_group(&group_id);
-                let actions = self.intercept_self_group_actions(actions);
-                self.group_actions_to_effects(&actions)
-            }
+    /// Handle an incoming exactly-once message or commit ack.
+    ///
+    /// Envelopes addressed to someone else are relayed like chat. Ours skip
+    /// the router's in-memory dedup: the persistent inbox decides, so that
+    /// retries of committed messages get their commit re-sent.
+    fn handle_incoming_exactly_once(
+        &mut self,
+        mut envelope: Envelope,
+        signature_valid: bool,
+    ) -> Vec<RuntimeEffect> {
+        if envelope.to != self.local_id {
+            return self.handle_incoming_chat(envelope, signature_valid);
+        }
+        // Dedup and commits are keyed by `from`: forged senders are dropped.
+        if !signature_valid {
+            return Vec::new();
+        }
 
-            // ── Admin controls (R11.3) ──────────────────────────
-            RuntimeCommand::KickMember { group_id, target_id } => {
-                let hub_id = self
-                    .group_manager
-                    .get_group(&group_id)
-                    .map(|g| g.hub_relay_id);
-                let payload = GroupPayload::KickMember {
-                    group_id,
-                    target_id,
-                };
-                if hub_id == Some(self.local_id) {
-                    let actions = self.group_hub.handle_payload(payload, self.local_id);
-                    let actions = self.intercept_self_group_actions(actions);
-                    self.group_actions_to_effects(&actions)
-                } else if let Some(hub) = hub_id {
-                    self.group_actions_to_effects(&[GroupAction::Send {
-                        to: hub,
-                        payload,
-                    }])
-                } else {
-                    Vec::new()
+        if envelope.msg_type == MessageType::ExactlyOnceCommit {
+            let commit: CommitPayload = match rmp_serde::from_slice(&envelope.payload) {
+                Ok(c) => c,
+                Err(_) => return Vec::new(),
+            };
+            let Some(entry) = self
+                .exactly_once_outbox
+                .commit(&commit.message_id, envelope.from)
+            else {
+                return Vec::new();
+            };
+            if let Some(ref store) = self.store {
+                if let Err(e) = store.delete_outbox_entry(&entry.message_id) {
+                    tracing::warn!("exactly-once outbox delete failed: {e}");
                 }
             }
+            return vec![RuntimeEffect::Emit(ProtocolEvent::ExactlyOnceCommitted {
+                message_id: entry.message_id,
+                to: entry.to,
+            })];
+        }
 
-            RuntimeCommand::UpdateMemberRole {
-                group_id,
-                target_id,
-                new_role,
-            } => {
-                let hub_id = self
-                    .group_manager
-                    .get_group(&group_id)
-                    .map(|g| g.hub_relay_id);
-                let payload = GroupPayload::UpdateMemberRole {
-                    group_id,
-                    target_id,
-                    new_role,
-                };
-                if hub_id == Some(self.local_id) {
-                    let actions = self.group_hub.handle_payload(payload, self.local_id);
-                    let actions = self.intercept_self_group_actions(actions);
-                    self.group_actions_to_effects(&actions)
-                } else if let Some(hub) = hub_id {
-                    self.group_actions_to_effects(&[GroupAction::Send {
-                        to: hub,
-                        payload,
-                    }])
-                } else {
-                    Vec::new()
-                }
+        let from = envelope.from;
+        let message_id = envelope.id.clone();
+        match self.exactly_once_inbox.on_receive(from, &message_id, now_ms()) {
+            InboxDecision::Hold => Vec::new(),
+            InboxDecision::Refuse => {
+                tracing::warn!("exactly-once inbox full, refusing {message_id} from {from}");
+                Vec::new()
             }
-
-            RuntimeCommand::InviteMember { group_id, target_id } => {
-                let hub_id = self
-                    .group_manager
-                    .get_group(&group_id)
-                    .map(|g| g.hub_relay_id);
-                let payload = GroupPayload::InviteMember {
-                    group_id,
-                    target_id,
+            InboxDecision::AckAgain => self.send_exactly_once_commit(from, message_id),
+            InboxDecision::Deliver => {
+                let was_encrypted = envelope.encrypted;
+                if envelope.encrypted {
+                    if let Err(e) = envelope.decrypt_payload(&self.secret_seed) {
+                        self.exactly_once_inbox.forget(from, &message_id);
+                        return vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                            description: format!("decrypt failed from {from}: {e}"),
+                        })];
+                    }
+                }
+                let (payload, content_type) = match self.unwrap_typed_payload(envelope.payload) {
+                    Ok(unwrapped) => unwrapped,
+                    Err(e) => {
+                        self.exactly_once_inbox.forget(from, &message_id);
+                        return vec![RuntimeEffect::Emit(ProtocolEvent::MessageRejected {
+                            reason: format!("payload from {from} rejected: {e}"),
+                        })];
+                    }
                 };
-                if hub_id == Some(self.local_id) {
-                    let actions = self.group_hub.handle_payload(payload, self.local_id);
-                    let actions = self.intercept_self_group_actions(actions);
-                    self.group_actions_to_effects(&actions)
-                } else if let Some(hub) = hub_id {
-                    self.group_actions_to_effects(&[GroupAction::Send {
-                        to: hub,
-                        payload,
-                    }])
-                } else {
-                    Vec::new()
+                // Record the receive before the app sees it.
+                if let (Some(store), Some(record)) =
+                    (&self.store, self.exactly_once_inbox.record(from, &message_id))
+                {
+                    if let Err(e) = store.save_inbox_record(&from, &message_id, &record) {
+                        self.exactly_once_inbox.forget(from, &message_id);
+                        return vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                            description: format!("exactly-once inbox write failed: {e}"),
+                        })];
+                    }
                 }
+                vec![RuntimeEffect::DeliverMessage(DeliveredMessage {
+                    from,
+                    to: self.local_id,
+                    payload,
+                    envelope_id: message_id,
+                    timestamp: envelope.timestamp,
+                    signature_valid,
+                    was_encrypted,
+                    content_type,
+                    exactly_once: true,
+                })]
             }
+        }
+    }
 
-            RuntimeCommand::GetGroups { reply } => {
-                let groups = self
-                    .group_manager
-                    .all_groups()
-                    .into_iter()
-                    .cloned()
-                    .collect();
-                let _ = reply.send(groups);
-                Vec::new()
+    /// The application processed an exactly-once message: persist, then ack.
+    fn handle_commit_message(&mut self, from: NodeId, message_id: String) -> Vec<RuntimeEffect> {
+        let Some(record) = self.exactly_once_inbox.commit(from, &message_id) else {
+            return Vec::new();
+        };
+        if let Some(ref store) = self.store {
+            if let Err(e) = store.save_inbox_record(&from, &message_id, &record) {
+                return vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                    description: format!("exactly-once commit write failed: {e}"),
+                })];
             }
+        }
+        self.send_exactly_once_commit(from, message_id)
+    }
 
-            RuntimeCommand::GetPendingInvites { reply } => {
-                let invites = self
-                    .group_manager
-                    .pending_invites()
-                    .into_iter()
-                    .cloned()
-                    .collect();
-                let _ = reply.send(invites);
-                Vec::new()
+    /// An exactly-once message never reached the app (its channel dropped
+    /// it): deliver it again when the sender retries.
+    #[cfg(feature = "native")]
+    pub(crate) fn release_exactly_once(&mut self, from: NodeId, message_id: &str) {
+        self.exactly_once_inbox.release(from, message_id);
+    }
+
+    fn send_exactly_once_commit(&self, to: NodeId, message_id: String) -> Vec<RuntimeEffect> {
+        let bytes = rmp_serde::to_vec(&CommitPayload { message_id }).expect("commit serialization");
+        let via = self.relay_selector.select_path_weighted(to, &self.topology, &self.subnets, &self.relay_metrics);
+        let envelope = EnvelopeBuilder::new(self.local_id, to, MessageType::ExactlyOnceCommit, bytes)
+            .via(via)
+            .sign(&self.secret_seed);
+        vec![RuntimeEffect::SendEnvelope(envelope)]
+    }
+
+    // ── Ordered delivery ─────────────────────────────────────────────────
+
+    /// Deliver a chat message, through the reorder buffer when it's
+    /// numbered and we keep order.
+    fn deliver_in_order(
+        &mut self,
+        sequence: Option<Sequence>,
+        message: DeliveredMessage,
+    ) -> Vec<RuntimeEffect> {
+        match (sequence, self.reorder.as_mut()) {
+            (Some(sequence), Some(reorder)) => {
+                let released = reorder.push(message.from, sequence, message, now_ms());
+                Self::released_effects(released)
             }
+            _ => vec![RuntimeEffect::DeliverMessage(message)],
+        }
+    }
 
-            RuntimeCommand::GetRoleMetrics { node_id, reply } => {
-                let metrics =
-                    self.role_manager
-                        .get_metrics(&node_id, &self.topology, now_ms());
-                let _ = reply.send(metrics);
-                Vec::new()
+    fn released_effects(released: Released<DeliveredMessage>) -> Vec<RuntimeEffect> {
+        let gaps = released.gaps.into_iter().map(|gap| {
+            RuntimeEffect::Emit(ProtocolEvent::MessageGap {
+                from: gap.from,
+                first: gap.first,
+                count: gap.count,
+            })
+        });
+        // Gaps first: the messages released with them come after the hole
+        gaps.chain(released.ready.into_iter().map(RuntimeEffect::DeliverMessage))
+            .collect()
+    }
+
+    /// Release messages held behind gaps older than the ordering timeout.
+    pub fn tick_reorder(&mut self) -> Vec<RuntimeEffect> {
+        self.tick_reorder_at(now_ms())
+    }
+
+    /// `tick_reorder` with an explicit clock (simulation tests).
+    pub fn tick_reorder_at(&mut self, now: u64) -> Vec<RuntimeEffect> {
+        let Some(reorder) = self.reorder.as_mut() else {
+            return Vec::new();
+        };
+        let mut effects = Self::released_effects(reorder.tick(now));
+        self.record_received(&mut effects);
+        effects
+    }
+
+    /// Re-send uncommitted exactly-once messages, expire old ones, purge the inbox.
+    pub fn tick_exactly_once(&mut self) -> Vec<RuntimeEffect> {
+        self.tick_exactly_once_at(now_ms())
+    }
+
+    /// `tick_exactly_once` with an explicit clock (simulation tests).
+    pub fn tick_exactly_once_at(&mut self, now: u64) -> Vec<RuntimeEffect> {
+        let pass = self.exactly_once_outbox.retry_pass(now);
+        let mut effects = Vec::new();
+
+        for entry in pass.resend {
+            if let Some(ref store) = self.store {
+                let _ = store.save_outbox_entry(&entry);
+            }
+            match Envelope::from_bytes(&entry.envelope) {
+                Ok(envelope) => effects.push(RuntimeEffect::SendEnvelope(envelope)),
+                Err(e) => tracing::warn!("exactly-once entry {} unreadable: {e}", entry.message_id),
+            }
+        }
+        for entry in pass.expired {
+            if let Some(ref store) = self.store {
+                let _ = store.delete_outbox_entry(&entry.message_id);
             }
+            effects.push(RuntimeEffect::Emit(ProtocolEvent::ExactlyOnceExpired {
+                message_id: entry.message_id,
+                to: entry.to,
+            }));
+        }
 
-            RuntimeCommand::GetAllRoleScores { reply } => {
-                let scores =
-                    self.role_manager
-                        .get_all_scores(&self.topology, now_ms());
-                let _ = reply.send(scores);
-                Vec::new()
+        if self.exactly_once_inbox.purge(now) > 0 {
+            if let Some(ref store) = self.store {
+                let cutoff = now.saturating_sub(EXACTLY_ONCE_DEDUP_RETENTION_MS);
+                let _ = store.cleanup_inbox(cutoff);
             }
+        }
+        effects
+    }
 
-            // DHT lookup completed — register the discovered peer.
-            RuntimeCommand::DhtLookupResult { addr } => {
-                let Ok(node_id) = addr.node_id.parse::<NodeId>() else {
-                    tracing::warn!("DHT lookup result: invalid node_id '{}'", addr.node_id);
-                    return Vec::new();
-                };
-                self.heartbeat.record_heartbeat_with_source(
-                    node_id,
-                    DiscoverySource::Dht,
-                    String::new(),
-                );
-                self.topology.upsert(PeerInfo {
-                    node_id,
-                    role: PeerRole::Peer,
-                    status: PeerStatus::Online,
-                    last_seen: now_ms(),
-                });
-                tracing::info!(
-                    node_id = %node_id,
-                    relays = addr.relay_urls.len(),
-                    addrs = addr.direct_addrs.len(),
-                    "DHT lookup result applied"
-                );
-                Vec::new()
-            }
-
-            // Handled in the loop — needs transport access.
-            RuntimeCommand::GetConnectedPeers { .. } => Vec::new(),
-            RuntimeCommand::AddPeerAddr { .. } => Vec::new(),
+    // ── Task 9: handle_send_message ──────────────────────────────────────
 
-            // Handled in the loop — signals the loop to break.
-            RuntimeCommand::Shutdown => Vec::new(),
-        }
+    /// Build and send a chat message to a peer.
+    ///
+    /// Returns a SendWithBackupFallback effect: on success the tracker
+    /// advances to Sent; on failure the message is stored as backup.
+    pub fn handle_send_message(
+        &mut self,
+        to: NodeId,
+        payload: Vec<u8>,
+    ) -> Vec<RuntimeEffect> {
+        self.handle_send_message_with_ttl(to, payload, None)
     }
 
-    // ── Task 10: handle_gossip_event ─────────────────────────────────────
+    /// Like [`handle_send_message`](Self::handle_send_message), with the
+    /// sender's requested backup TTL (None = holders' default).
+    pub fn handle_send_message_with_ttl(
+        &mut self,
+        to: NodeId,
+        payload: Vec<u8>,
+        backup_ttl_ms: Option<u64>,
+    ) -> Vec<RuntimeEffect> {
+        self.send_chat(to, payload, backup_ttl_ms, false).1
+    }
 
-    /// Handle a gossip event (peer announce, neighbor up/down).
-    ///
-    /// For NeighborUp, the state method returns effects but does NOT re-broadcast
-    /// the gossip announce — that I/O is left to the loop.
-    pub fn handle_gossip_event(
+    /// Like [`handle_send_message`](Self::handle_send_message), with
+    /// per-message [`SendOptions`].
+    pub fn handle_send_message_with_options(
         &mut self,
-        input: GossipInput,
+        to: NodeId,
+        payload: Vec<u8>,
+        options: SendOptions,
     ) -> Vec<RuntimeEffect> {
-        match input {
-            GossipInput::PeerAnnounce(bytes) => {
-                // Try PeerAnnounce first (most common)
-                if let Ok(announce) =
-                    rmp_serde::from_slice::<PeerAnnounce>(&bytes)
-                {
-                    if announce.is_timestamp_valid(now_ms()) {
-                        let peer_id = announce.node_id;
-                        let role =
-                            if announce.roles.contains(&PeerRole::Relay) {
-                                PeerRole::Relay
-                            } else {
-                                PeerRole::Peer
-                            };
-                        // Record with Announce source — PeerDiscovered emitted from tick_heartbeat
-                        self.heartbeat.record_heartbeat_with_source(
-                            peer_id,
-                            DiscoverySource::Announce,
-                            announce.username,
-                        );
-                        self.topology.upsert(PeerInfo {
-                            node_id: peer_id,
-                            role,
-                            status: PeerStatus::Online,
-                            last_seen: now_ms(),
-                        });
-                        return vec![];
-                    }
-                }
+        let backup_ttl_ms = options.backup_ttl.map(|ttl| ttl.as_millis() as u64);
+        self.send_chat(to, payload, backup_ttl_ms, options.redundant).1
+    }
 
-                // Try RoleChangeAnnounce
-                if let Ok(role_announce) =
-                    rmp_serde::from_slice::<crate::discovery::RoleChangeAnnounce>(&bytes)
-                {
-                    return self.handle_role_announce(role_announce);
-                }
+    /// Build, sign and send a chat message; returns its envelope id (None
+    /// if it couldn't be built). `redundant` also sends a copy (same id,
+    /// re-signed for its own `via`) over a disjoint path when one exists;
+    /// only the first copy is backed up and retried.
+    fn send_chat(
+        &mut self,
+        to: NodeId,
+        payload: Vec<u8>,
+        backup_ttl_ms: Option<u64>,
+        redundant: bool,
+    ) -> (Option<String>, Vec<RuntimeEffect>) {
+        let mut paths = if redundant {
+            self.relay_selector
+                .select_disjoint_paths(to, &self.topology, &self.subnets, &self.relay_metrics)
+        } else {
+            vec![self.relay_selector.select_path_weighted(to, &self.topology, &self.subnets, &self.relay_metrics)]
+        };
+        let via = paths.remove(0);
 
-                Vec::new()
-            }
+        let wire_payload = match self.config.ordered_delivery {
+            Some(_) => crate::ordering::wrap(self.sequencer.next(to), &payload),
+            None => payload.clone(),
+        };
+        let builder = EnvelopeBuilder::new(self.local_id, to, MessageType::Chat, wire_payload)
+            .via(via);
 
-            GossipInput::NeighborUp(node_id) => {
-                self.heartbeat.record_heartbeat_with_source(
-                    node_id,
-                    DiscoverySource::Gossip,
-                    String::new(),
-                );
-                self.topology.upsert(PeerInfo {
-                    node_id,
-                    role: PeerRole::Peer,
-                    status: PeerStatus::Online,
-                    last_seen: now_ms(),
-                });
-                vec![RuntimeEffect::Emit(
-                    ProtocolEvent::GossipNeighborUp { node_id },
-                )]
+        let envelope = if self.encrypts_for(to) {
+            let recipient_pk = to.as_bytes();
+            match builder.encrypt_and_sign(&self.secret_seed, &recipient_pk) {
+                Ok(env) => env,
+                Err(e) => {
+                    return (
+                        None,
+                        vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                            description: format!("encrypt failed for {to}: {e}"),
+                        })],
+                    );
+                }
             }
+        } else {
+            builder.sign(&self.secret_seed)
+        };
+        let _span = crate::logging::envelope_span(&envelope).entered();
+        tracing::debug!(via = ?envelope.via, redundant, "message created");
+
+        let (body, content_type) = self
+            .unwrap_typed_payload(payload.clone())
+            .unwrap_or((payload, None));
+        let history = self.record_history(DirectEntry {
+            peer: to,
+            outgoing: true,
+            message_id: envelope.id.clone(),
+            payload: body,
+            content_type,
+            timestamp: envelope.timestamp,
+        });
 
-            GossipInput::NeighborDown(node_id) => {
-                vec![RuntimeEffect::Emit(
-                    ProtocolEvent::GossipNeighborDown { node_id },
-                )]
+        let envelope_id = envelope.id.clone();
+        if !self.network_online {
+            if let Some(mut effects) = self.queue_offline(&envelope, backup_ttl_ms) {
+                effects.extend(history);
+                return (Some(envelope_id), effects);
             }
         }
+
+        // Track message in tracker. The Pending change is reported on both
+        // paths so observers see every tracked message, in send order.
+        let tracked = self.tracker.track(envelope_id.clone(), to);
+        let mut on_success = Vec::new();
+        if let Some(ref change) = tracked {
+            on_success.push(RuntimeEffect::StatusChange(change.clone()));
+        }
+
+        // On success: mark as sent
+        if let Some(change) = self.tracker.mark_sent(&envelope_id) {
+            on_success.push(RuntimeEffect::StatusChange(change));
+        }
+
+        let on_failure = tracked.into_iter().map(RuntimeEffect::StatusChange).collect();
+        let copies: Vec<Envelope> = paths
+            .into_iter()
+            .map(|via| {
+                let mut copy = envelope.clone();
+                copy.via = via;
+                copy.sign(&self.secret_seed);
+                copy
+            })
+            .collect();
+        let mut effects = self.dispatch_chat(envelope, backup_ttl_ms, on_success, on_failure);
+        effects.extend(copies.into_iter().map(RuntimeEffect::SendEnvelope));
+        effects.extend(history);
+        (Some(envelope_id), effects)
     }
 
-    // ── Helper: surface subnet event ─────────────────────────────────────
+    /// Back up a signed chat envelope, cache it for ACK-timeout retry and
+    /// hand it to the transport. The backup effects and an error are added
+    /// to `on_failure`.
+    fn dispatch_chat(
+        &mut self,
+        envelope: Envelope,
+        backup_ttl_ms: Option<u64>,
+        on_success: Vec<RuntimeEffect>,
+        mut on_failure: Vec<RuntimeEffect>,
+    ) -> Vec<RuntimeEffect> {
+        let to = envelope.to;
+        let envelope_id = envelope.id.clone();
 
-    /// Convert a SubnetEvent into RuntimeEffects (only Formed/Dissolved surface).
-    fn surface_subnet_event(&self, event: &SubnetEvent) -> Vec<RuntimeEffect> {
-        let proto_event = match event {
-            SubnetEvent::SubnetFormed { subnet } => Some(ProtocolEvent::SubnetFormed {
-                subnet_id: subnet.subnet_id.clone(),
-                members: subnet.members.iter().copied().collect(),
-            }),
-            SubnetEvent::SubnetDissolved { subnet_id, reason } => {
-                Some(ProtocolEvent::SubnetDissolved {
-                    subnet_id: subnet_id.clone(),
-                    reason: format!("{reason:?}"),
-                })
-            }
-            // NodeJoined/Left are internal bookkeeping
-            _ => None,
+        // On failure: store backup (always sealed to the recipient) + emit error
+        let sealed = if envelope.encrypted {
+            Ok(envelope.payload.clone())
+        } else {
+            crate::crypto::encrypt(&envelope.payload, &to.as_bytes()).and_then(|p| p.to_bytes())
         };
-        proto_event
-            .into_iter()
-            .map(RuntimeEffect::Emit)
-            .collect()
+        match sealed {
+            Ok(sealed) => {
+                let backup_actions = self.backup.store_message(
+                    envelope_id.clone(),
+                    sealed,
+                    to,
+                    self.local_id,
+                    now_ms(),
+                    backup_ttl_ms,
+                );
+                on_failure.extend(self.backup_actions_to_effects(&backup_actions));
+            }
+            Err(e) => tracing::warn!("not backing up message for {to}: {e}"),
+        }
+        on_failure.push(RuntimeEffect::Emit(ProtocolEvent::Error {
+            description: format!(
+                "send to {} failed (backed up)",
+                envelope.via.first().copied().unwrap_or(to)
+            ),
+        }));
+
+        // Cache envelope for potential ACK-timeout retry (R9.2)
+        self.pending_envelopes
+            .insert(envelope_id, envelope.clone());
+
+        vec![RuntimeEffect::SendWithBackupFallback {
+            envelope,
+            on_success,
+            on_failure,
+        }]
     }
 
-    // ── Helper: prepare backup delivery for reconnected peer ─────────────
+    // ── Offline outbox ───────────────────────────────────────────────────
 
-    /// Build SendWithBackupFallback effects for each backed-up message
-    /// destined to the given peer.
-    fn prepare_backup_delivery(&mut self, peer_id: NodeId) -> Vec<RuntimeEffect> {
-        let entries: Vec<(String, Vec<u8>)> = self
-            .backup
-            .store()
-            .get_for_recipient(&peer_id)
-            .into_iter()
-            .map(|e| (e.message_id.clone(), e.payload.clone()))
-            .collect();
+    /// Hold a chat composed while fully offline. None if it can't be queued
+    /// (outbox full, or the store write failed): it is then sent as usual.
+    fn queue_offline(
+        &mut self,
+        envelope: &Envelope,
+        backup_ttl_ms: Option<u64>,
+    ) -> Option<Vec<RuntimeEffect>> {
+        let message = QueuedMessage {
+            message_id: envelope.id.clone(),
+            to: envelope.to,
+            envelope: envelope.to_bytes().ok()?,
+            backup_ttl_ms,
+            queued_ms: now_ms(),
+        };
+        if self.offline_outbox.len() >= crate::outbox::OFFLINE_OUTBOX_CAPACITY {
+            return None;
+        }
+        if let Some(ref store) = self.store {
+            if let Err(e) = store.save_queued_message(&message) {
+                tracing::warn!("offline outbox write failed, sending anyway: {e}");
+                return None;
+            }
+        }
+        let change = self.tracker.track_queued(message.message_id.clone(), message.to);
+        self.offline_outbox.push(message);
+        Some(change.into_iter().map(RuntimeEffect::StatusChange).collect())
+    }
 
-        if entries.is_empty() {
+    /// Connectivity as seen by the transport (any live connection counts).
+    /// While online, anything in the offline outbox is flushed, oldest first.
+    pub fn set_connectivity(&mut self, online: bool) -> Vec<RuntimeEffect> {
+        if online != self.network_online {
+            tracing::info!("connectivity {}", if online { "restored" } else { "lost" });
+            self.network_online = online;
+        }
+        if !online || self.offline_outbox.is_empty() {
             return Vec::new();
         }
 
+        let queued = self.offline_outbox.drain();
+        tracing::info!("flushing {} messages queued while offline", queued.len());
         let mut effects = Vec::new();
-
-        for (message_id, payload) in entries {
-            let via = self.relay_selector.select_path(peer_id, &self.topology);
-            let builder = EnvelopeBuilder::new(
-                self.local_id,
-                peer_id,
-                MessageType::Chat,
-                payload,
-            )
-            .via(via);
-
-            let envelope = if self.config.encryption {
-                let recipient_pk = peer_id.as_bytes();
-                match builder.encrypt_and_sign(&self.secret_seed, &recipient_pk) {
-                    Ok(env) => env,
-                    Err(_) => continue,
+        for message in queued {
+            if let Some(ref store) = self.store {
+                if let Err(e) = store.delete_queued_message(&message.message_id) {
+                    tracing::warn!("offline outbox delete failed: {e}");
+                }
+            }
+            let envelope = match Envelope::from_bytes(&message.envelope) {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    tracing::warn!("dropping unreadable queued message {}: {e}", message.message_id);
+                    continue;
                 }
-            } else {
-                builder.sign(&self.secret_seed)
             };
-
-            // On success: emit BackupDelivered.
-            // On failure: no action (message stays in backup store).
-            let on_success = vec![RuntimeEffect::Emit(ProtocolEvent::BackupDelivered {
-                message_id,
-                recipient_id: peer_id,
-            })];
-            let on_failure = Vec::new();
-
-            effects.push(RuntimeEffect::SendWithBackupFallback {
-                envelope,
-                on_success,
-                on_failure,
-            });
+            let on_success = self
+                .tracker
+                .release_queued(&message.message_id)
+                .into_iter()
+                .map(RuntimeEffect::StatusChange)
+                .collect();
+            effects.extend(self.dispatch_chat(envelope, message.backup_ttl_ms, on_success, Vec::new()));
         }
-
         effects
     }
 
-    // ── Helper: surface role action ──────────────────────────────────────
+    /// Messages composed while offline, oldest first.
+    pub fn queued_messages(&self) -> Vec<QueuedMessage> {
+        self.offline_outbox.messages().cloned().collect()
+    }
 
-    /// Convert a RoleAction into RuntimeEffects.
+    // ── Task 9: handle_send_group_message ────────────────────────────────
+
+    /// Build and send a text message to a group (via hub relay).
+    pub fn handle_send_group_message(
+        &mut self,
+        group_id: crate::group::GroupId,
+        text: String,
+    ) -> Vec<RuntimeEffect> {
+        self.send_group_text(group_id, text, None, Vec::new(), None).1
+    }
+
+    /// Build and send a group message the hub holds until `deliver_at`.
     ///
-    /// For local role changes, also broadcasts a signed `RoleChangeAnnounce` via gossip.
-    fn surface_role_action(&mut self, action: &RoleAction) -> Vec<RuntimeEffect> {
-        use crate::discovery::RoleChangeAnnounce;
+    /// Returns the message id (for cancellation), None if `deliver_at` is
+    /// not in the future or beyond `MAX_SCHEDULE_AHEAD_MS`.
+    pub fn handle_schedule_group_message(
+        &mut self,
+        group_id: crate::group::GroupId,
+        text: String,
+        deliver_at: u64,
+    ) -> (Option<String>, Vec<RuntimeEffect>) {
+        let now = now_ms();
+        if deliver_at <= now || deliver_at > now + crate::group::types::MAX_SCHEDULE_AHEAD_MS {
+            return (
+                None,
+                vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                    description: format!("cannot schedule group message at {deliver_at}"),
+                })],
+            );
+        }
+        self.send_group_text(group_id, text, None, Vec::new(), Some(deliver_at))
+    }
 
-        match action {
-            RoleAction::Promoted { node_id, score } => {
-                let mut effects = vec![RuntimeEffect::Emit(ProtocolEvent::RolePromoted {
-                    node_id: *node_id,
-                    score: *score,
-                })];
+    /// Build and send a group message that mentions some members.
+    pub fn handle_send_group_mention(
+        &mut self,
+        group_id: crate::group::GroupId,
+        text: String,
+        mentions: Vec<NodeId>,
+    ) -> Vec<RuntimeEffect> {
+        self.send_group_text(group_id, text, None, mentions, None).1
+    }
 
-                // Broadcast via gossip if it's our local promotion
-                if *node_id == self.local_id {
-                    let announce = RoleChangeAnnounce::new(
-                        *node_id,
-                        PeerRole::Relay,
-                        *score,
-                        now_ms(),
-                        &self.secret_seed,
-                    );
-                    effects.push(RuntimeEffect::BroadcastRoleChange(announce));
-                }
+    /// Build and send a reply to an earlier group message.
+    pub fn handle_send_group_reply(
+        &mut self,
+        group_id: crate::group::GroupId,
+        reply_to: String,
+        text: String,
+    ) -> Vec<RuntimeEffect> {
+        self.send_group_text(group_id, text, Some(reply_to), Vec::new(), None).1
+    }
 
-                effects
-            }
-            RoleAction::Demoted { node_id, score } => {
-                let mut effects = vec![RuntimeEffect::Emit(ProtocolEvent::RoleDemoted {
-                    node_id: *node_id,
-                    score: *score,
-                })];
+    fn send_group_text(
+        &mut self,
+        group_id: crate::group::GroupId,
+        text: String,
+        reply_to: Option<String>,
+        mentions: Vec<NodeId>,
+        deliver_at: Option<u64>,
+    ) -> (Option<String>, Vec<RuntimeEffect>) {
+        let mut pre_effects = Vec::new();
 
-                // Broadcast via gossip if it's our local demotion
-                if *node_id == self.local_id {
-                    let announce = RoleChangeAnnounce::new(
-                        *node_id,
-                        PeerRole::Peer,
-                        *score,
-                        now_ms(),
-                        &self.secret_seed,
-                    );
-                    effects.push(RuntimeEffect::BroadcastRoleChange(announce));
-                }
+        // R14.1 dual-trigger rotation before sending.
+        let rotation_actions = self.group_manager.maybe_rotate_local_sender_key(&group_id);
+        if !rotation_actions.is_empty() {
+            let rotation_actions = self.intercept_self_group_actions(rotation_actions);
+            pre_effects.extend(self.group_actions_to_effects(&rotation_actions));
+        }
 
-                effects
-            }
-            RoleAction::LocalRoleChanged { new_role } => {
-                self.local_roles = vec![*new_role];
-                let score = self.role_manager.score(&self.local_id, now_ms());
+        let Some(group) = self.group_manager.get_group(&group_id) else {
+            return (
+                None,
+                vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                    description: format!("not a member of group {group_id}"),
+                })],
+            );
+        };
 
-                let announce = RoleChangeAnnounce::new(
-                    self.local_id,
-                    *new_role,
-                    score,
-                    now_ms(),
-                    &self.secret_seed,
-                );
+        let hub_id = group.hub_relay_id;
+        // The hub strips our id in anonymous groups; the name is ours to leave out
+        let username = if group.settings.anonymous {
+            String::new()
+        } else {
+            self.config.username.clone()
+        };
 
-                vec![
-                    RuntimeEffect::Emit(ProtocolEvent::LocalRoleChanged {
-                        new_role: *new_role,
-                    }),
-                    RuntimeEffect::BroadcastRoleChange(announce),
-                ]
-            }
+        // Build message — encrypted if we have a sender key, plaintext otherwise
+        let mut msg = if let Some(sender_key) = self.group_manager.local_sender_key(&group_id) {
+            let key = sender_key.key;
+            let epoch = sender_key.epoch;
+            GroupMessage::new_encrypted(
+                group_id.clone(),
+                self.local_id,
+                username,
+                text,
+                &key,
+                epoch,
+            )
+        } else {
+            GroupMessage::new(group_id.clone(), self.local_id, username, text)
+        };
+
+        msg.reply_to = reply_to;
+        msg.deliver_at = deliver_at;
+        msg = msg.with_mentions(mentions);
+        msg.sign(&self.secret_seed);
+        self.group_manager.note_local_message_sent(&group_id);
+        let message_id = Some(msg.message_id.clone());
+        let payload = GroupPayload::Message(msg);
+
+        // If we ARE the hub, handle locally without network round-trip
+        if hub_id == self.local_id {
+            let actions = self.group_hub.handle_payload(payload, self.local_id);
+            let actions = self.intercept_self_group_actions(actions);
+            let mut effects = pre_effects;
+            effects.extend(self.group_actions_to_effects(&actions));
+            return (message_id, effects);
         }
+
+        let payload_bytes =
+            rmp_serde::to_vec(&payload).expect("group msg serialization");
+
+        let via = self.relay_selector.select_path_weighted(hub_id, &self.topology, &self.subnets, &self.relay_metrics);
+        let envelope = EnvelopeBuilder::new(
+            self.local_id,
+            hub_id,
+            MessageType::GroupMessage,
+            payload_bytes,
+        )
+        .via(via)
+        .sign(&self.secret_seed);
+
+        pre_effects.push(RuntimeEffect::SendEnvelope(envelope));
+        (message_id, pre_effects)
     }
 
-    // ── Helper: self-addressed group action interception ─────────────────
+    // ── Task 9: handle_send_read_receipt ─────────────────────────────────
 
-    /// Intercept Send/Broadcast actions that target `local_id` and process
-    /// them locally instead of sending over the network (QUIC self-sends).
-    /// Recursive: handles chains where local processing generates more self-sends
-    /// (e.g., MemberJoined → SenderKeyDistribution → hub fanout).
-    fn intercept_self_group_actions(&mut self, actions: Vec<GroupAction>) -> Vec<GroupAction> {
-        let mut result = Vec::new();
-        for action in actions {
-            match action {
-                GroupAction::Send { to, payload } if to == self.local_id => {
-                    let new_actions = self.handle_local_group_payload(payload);
-                    // Recursively intercept any resulting self-sends
-                    result.extend(self.intercept_self_group_actions(new_actions));
-                }
-                GroupAction::Broadcast { to, payload } if to.contains(&self.local_id) => {
-                    // Process locally for self
-                    let new_actions = self.handle_local_group_payload(payload.clone());
-                    result.extend(self.intercept_self_group_actions(new_actions));
-                    // Keep broadcast for remote targets
-                    let remote: Vec<NodeId> =
-                        to.into_iter().filter(|t| *t != self.local_id).collect();
-                    if !remote.is_empty() {
-                        result.push(GroupAction::Broadcast {
-                            to: remote,
-                            payload,
-                        });
-                    }
-                }
-                other => result.push(other),
-            }
+    /// Build and send a read receipt for a previously received message.
+    pub fn handle_send_read_receipt(
+        &mut self,
+        to: NodeId,
+        original_message_id: String,
+    ) -> Vec<RuntimeEffect> {
+        let payload = ReadReceiptPayload {
+            original_message_id,
+            read_at: now_ms(),
         }
-        result
+        .to_bytes();
+
+        let via = self.relay_selector.select_path_weighted(to, &self.topology, &self.subnets, &self.relay_metrics);
+        let envelope = EnvelopeBuilder::new(
+            self.local_id,
+            to,
+            MessageType::ReadReceipt,
+            payload,
+        )
+        .via(via)
+        .sign(&self.secret_seed);
+
+        vec![RuntimeEffect::SendEnvelope(envelope)]
     }
 
-    /// Dispatch a group payload locally. Routes hub-bound payloads to the
-    /// GroupHub for processing, and member-bound payloads to the GroupManager.
-    fn handle_local_group_payload(&mut self, payload: GroupPayload) -> Vec<GroupAction> {
-        match payload {
-            // ── Hub-bound payloads: route through GroupHub ─────────────
-            GroupPayload::Create { .. }
-            | GroupPayload::Join { .. }
-            | GroupPayload::Leave { .. }
-            | GroupPayload::KickMember { .. }
-            | GroupPayload::UpdateMemberRole { .. }
-            | GroupPayload::InviteMember { .. } => {
-                self.group_hub.handle_payload(payload, self.local_id)
+    // ── Task 9: handle_command (unified dispatcher) ──────────────────────
+
+    /// Unified command dispatcher — processes a RuntimeCommand and returns effects.
+    ///
+    /// Some commands (GetConnectedPeers, Shutdown) are handled in the loop
+    /// because they need transport access; they return empty effects here.
+    pub fn handle_command(
+        &mut self,
+        cmd: RuntimeCommand,
+    ) -> Vec<RuntimeEffect> {
+        match cmd {
+            RuntimeCommand::SendMessage { to, payload, reply } => {
+                self.subnets
+                    .record_communication(self.local_id, to, now_ms());
+                let (message_id, effects) = self.send_chat(to, payload, None, false);
+                if let (Some(reply), Some(message_id)) = (reply, message_id) {
+                    let _ = reply.send(message_id);
+                }
+                effects
             }
-            GroupPayload::SenderKeyDistribution {
-                group_id, from, epoch, encrypted_keys,
+            RuntimeCommand::SendMessageWithTtl {
+                to,
+                payload,
+                backup_ttl,
             } => {
-                let mut actions = Vec::new();
-                // Always try to store keys for self via manager
-                actions.extend(self.group_manager.handle_sender_key_distribution(
-                    &group_id, from, epoch, &encrypted_keys, &self.secret_seed,
-                ));
-                // If there are keys for OTHER members and we're the hub, fan out
-                let keys_for_others: Vec<_> = encrypted_keys
-                    .into_iter()
-                    .filter(|ek| ek.recipient_id != self.local_id)
-                    .collect();
-                if !keys_for_others.is_empty()
-                    && self.group_hub.get_group(&group_id).is_some()
-                {
-                    let fanout_payload = GroupPayload::SenderKeyDistribution {
-                        group_id,
-                        from,
-                        epoch,
-                        encrypted_keys: keys_for_others,
-                    };
-                    actions.extend(
-                        self.group_hub
-                            .handle_payload(fanout_payload, self.local_id),
-                    );
-                }
-                actions
+                self.subnets
+                    .record_communication(self.local_id, to, now_ms());
+                self.handle_send_message_with_ttl(to, payload, Some(backup_ttl.as_millis() as u64))
             }
-            GroupPayload::DeliveryAck { ref group_id, .. } => {
-                if self.group_hub.get_group(group_id).is_some() {
-                    self.group_hub.handle_payload(payload, self.local_id)
-                } else {
-                    vec![]
-                }
+            RuntimeCommand::SendMessageWithOptions {
+                to,
+                payload,
+                options,
+            } => {
+                self.subnets
+                    .record_communication(self.local_id, to, now_ms());
+                self.handle_send_message_with_options(to, payload, options)
             }
 
-            // ── Member-bound payloads: route to GroupManager ──────────
-            GroupPayload::MemberJoined { group_id, member } => {
-                self.group_manager.handle_member_joined(&group_id, member)
+            RuntimeCommand::SendGroupMessage { group_id, text }
+                if self.mesh_groups.is_mesh(&group_id) =>
+            {
+                let result = self
+                    .mesh_groups
+                    .send_message(&group_id, text, &self.secret_seed);
+                self.mesh_result_to_effects(result)
             }
-            GroupPayload::MemberLeft {
-                group_id,
-                node_id,
-                username,
-                reason,
-            } => self
-                .group_manager
-                .handle_member_left(&group_id, &node_id, username, reason),
-            GroupPayload::Message(msg) => self.group_manager.handle_message(msg),
-            GroupPayload::Sync {
-                group,
-                recent_messages,
-            } => self.group_manager.handle_group_sync(group, recent_messages),
-            GroupPayload::Created { group } => {
-                self.group_manager.handle_group_created(group)
+
+            RuntimeCommand::SendGroupMessage { group_id, text } => {
+                self.handle_send_group_message(group_id, text)
             }
-            GroupPayload::Invite {
+
+            RuntimeCommand::SendGroupReply {
                 group_id,
-                group_name,
-                inviter_id,
-                inviter_username,
-            } => self.group_manager.handle_invite(
+                reply_to,
+                text,
+            } => self.handle_send_group_reply(group_id, reply_to, text),
+
+            RuntimeCommand::SendGroupMention {
                 group_id,
-                group_name,
-                inviter_id,
-                inviter_username,
-                self.local_id,
-            ),
-            GroupPayload::MemberRoleChanged {
+                text,
+                mentions,
+            } => self.handle_send_group_mention(group_id, text, mentions),
+
+            RuntimeCommand::ScheduleGroupMessage {
                 group_id,
-                node_id,
-                new_role,
-            } => self.group_manager.handle_member_role_changed(
-                &group_id, &node_id, new_role,
-            ),
-            // Payloads that don't need local dispatch
-            _ => vec![],
-        }
-    }
+                text,
+                deliver_at,
+                reply,
+            } => {
+                let (message_id, effects) =
+                    self.handle_schedule_group_message(group_id, text, deliver_at);
+                let _ = reply.send(message_id);
+                effects
+            }
 
-    // ── Helper: group actions → effects ──────────────────────────────────
+            RuntimeCommand::CancelScheduledGroupMessage {
+                group_id,
+                message_id,
+            } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::CancelScheduled {
+                    group_id,
+                    message_id,
+                };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
 
-    /// Convert GroupActions into RuntimeEffects (Send, Broadcast, Event).
-    fn group_actions_to_effects(&self, actions: &[GroupAction]) -> Vec<RuntimeEffect> {
-        let mut effects = Vec::new();
-        for action in actions {
-            match action {
-                GroupAction::Send { to, payload } => {
-                    let msg_type = group_payload_to_message_type(payload);
-                    let payload_bytes =
-                        rmp_serde::to_vec(payload).expect("group payload serialization");
-                    let via = self.relay_selector.select_path(*to, &self.topology);
-                    let envelope =
-                        EnvelopeBuilder::new(self.local_id, *to, msg_type, payload_bytes)
-                            .via(via)
-                            .sign(&self.secret_seed);
-                    effects.push(RuntimeEffect::SendEnvelope(envelope));
+            RuntimeCommand::SendReadReceipt {
+                to,
+                original_message_id,
+            } => self.handle_send_read_receipt(to, original_message_id),
+
+            RuntimeCommand::SendExactlyOnce { to, payload, reply } => {
+                let (message_id, effects) = self.handle_send_exactly_once(to, payload);
+                let _ = reply.send(message_id);
+                effects
+            }
+
+            RuntimeCommand::CommitMessage { from, message_id } => {
+                self.handle_commit_message(from, message_id)
+            }
+
+            RuntimeCommand::AddPeer { node_id } => {
+                self.heartbeat.record_heartbeat_with_source(
+                    node_id,
+                    DiscoverySource::Direct,
+                    String::new(),
+                );
+                self.topology.upsert(PeerInfo {
+                    node_id,
+                    role: PeerRole::Peer,
+                    status: PeerStatus::Online,
+                    last_seen: now_ms(),
+                });
+                Vec::new()
+            }
+
+            RuntimeCommand::UpsertPeer { info } => {
+                self.heartbeat.record_heartbeat_with_source(
+                    info.node_id,
+                    DiscoverySource::Direct,
+                    String::new(),
+                );
+                self.topology.upsert(info);
+                Vec::new()
+            }
+
+            RuntimeCommand::RemovePeer { node_id } => {
+                self.topology.remove(&node_id);
+                self.forget_peer(&node_id);
+                Vec::new()
+            }
+
+            RuntimeCommand::SetRoutingOverride { node_id, policy } => {
+                self.topology.set_routing_override(node_id, policy);
+                self.router.set_never_relay_for(
+                    node_id,
+                    policy == Some(crate::relay::RoutingOverride::NeverRelayFor),
+                );
+                Vec::new()
+            }
+
+            RuntimeCommand::SetPresence { presence } => self.set_presence(presence),
+
+            RuntimeCommand::SendTyping { to, typing } => self.send_typing(to, typing),
+
+            RuntimeCommand::SendRealtime { to, payload } => self.send_realtime(to, &payload),
+
+            RuntimeCommand::GetRealtimeStats { peer, reply } => {
+                let _ = reply.send(self.realtime_stats(&peer));
+                Vec::new()
+            }
+
+            RuntimeCommand::GetPeerCapabilities { peer, reply } => {
+                let _ = reply.send(self.peer_capabilities(&peer));
+                Vec::new()
+            }
+
+            RuntimeCommand::ReportAbuse {
+                offender,
+                kind,
+                evidence,
+            } => self.report_violation(offender, kind, evidence),
+
+            RuntimeCommand::SendToUser { user, payload } => {
+                let mut devices = self.devices.devices_of(&user);
+                if devices.is_empty() {
+                    devices.push(user);
                 }
-                GroupAction::Broadcast { to, payload } => {
-                    // R13: persist group messages to SQLite for offline gap-fill
-                    if let GroupPayload::Message(ref msg) = payload {
-                        if let Some(ref store) = self.store {
-                            let data = rmp_serde::to_vec(msg).unwrap_or_default();
-                            let _ = store.save_hub_message(
-                                &msg.group_id, msg.seq, &data, crate::types::now_ms(),
-                            );
-                        }
+                let mut effects = Vec::new();
+                for to in devices {
+                    if to != self.local_id {
+                        effects.extend(self.handle_command(RuntimeCommand::SendMessage {
+                            to,
+                            payload: payload.clone(),
+                            reply: None,
+                        }));
                     }
+                }
+                effects
+            }
 
-                    let msg_type = group_payload_to_message_type(payload);
-                    let payload_bytes =
-                        rmp_serde::to_vec(payload).expect("group payload serialization");
-                    for target in to {
-                        let via = self.relay_selector.select_path(*target, &self.topology);
-                        let envelope = EnvelopeBuilder::new(
-                            self.local_id,
-                            *target,
-                            msg_type,
-                            payload_bytes.clone(),
-                        )
-                        .via(via)
-                        .sign(&self.secret_seed);
-                        effects.push(RuntimeEffect::SendEnvelope(envelope));
+            RuntimeCommand::GetLinkedDevices { user, reply } => {
+                let _ = reply.send(self.devices.devices_of(&user));
+                Vec::new()
+            }
+
+            RuntimeCommand::RevokeDevice { revocation } => {
+                let ours = self.config.device_certificate.as_ref().map(|c| c.user) == Some(revocation.user);
+                let mut effects = self.apply_device_revocation(revocation);
+                if ours {
+                    effects.extend(self.build_gossip_announce().map(RuntimeEffect::BroadcastAnnounce));
+                }
+                effects
+            }
+
+            RuntimeCommand::ResolveUsername { name, reply } => {
+                let _ = reply.send(self.resolve_username(&name));
+                Vec::new()
+            }
+
+            RuntimeCommand::GetSubnets { reply } => {
+                let _ = reply.send(self.subnets.all_subnets().into_iter().cloned().collect());
+                Vec::new()
+            }
+            RuntimeCommand::GetSubnetStats { reply } => {
+                let _ = reply.send(self.subnets.stats());
+                Vec::new()
+            }
+
+            RuntimeCommand::Respond {
+                to,
+                request_id,
+                payload,
+            } => self.send_rpc(to, MessageType::Response, request_id, payload),
+
+            RuntimeCommand::Pause => self.set_paused(true),
+            RuntimeCommand::Resume => self.set_paused(false),
+
+            RuntimeCommand::TraceRoute { target, reply } => {
+                let (trace_id, effects) = self.trace_route(target);
+                let _ = reply.send(trace_id);
+                effects
+            }
+
+            RuntimeCommand::GetTopologySnapshot { reply } => {
+                let _ = reply.send(self.topology_snapshot());
+                Vec::new()
+            }
+
+            RuntimeCommand::ImportContact { card } => {
+                self.import_contact(card);
+                Vec::new()
+            }
+
+            RuntimeCommand::GetContacts { reply } => {
+                let _ = reply.send(self.contacts.contacts().cloned().collect());
+                Vec::new()
+            }
+
+            RuntimeCommand::RemoveContact { node_id } => {
+                if self.contacts.remove(&node_id).is_some() {
+                    self.router.set_blocked(node_id, false);
+                    if let Some(ref store) = self.store {
+                        let _ = store.delete_contact(&node_id);
                     }
                 }
-                GroupAction::Event(event) => {
-                    effects.extend(self.surface_group_event(event));
+                Vec::new()
+            }
+
+            RuntimeCommand::SetContactBlocked { node_id, blocked } => {
+                self.set_contact_blocked(node_id, blocked);
+                Vec::new()
+            }
+
+            RuntimeCommand::SetContactAlias { node_id, alias } => {
+                let result = self.contacts.set_alias(&node_id, alias).cloned();
+                self.persist_contact_change(result)
+            }
+
+            RuntimeCommand::SetContactTrust { node_id, trust } => {
+                let result = self.contacts.set_trust(&node_id, trust).cloned();
+                self.persist_contact_change(result)
+            }
+
+            RuntimeCommand::SetConversationEncryption { node_id, mode } => {
+                self.set_conversation_encryption(node_id, mode)
+            }
+
+            RuntimeCommand::CreateGroup {
+                name,
+                hub_relay_id,
+                initial_members,
+                invite_only,
+            } => {
+                // If we ARE the hub, handle creation locally without network round-trip
+                if hub_relay_id == self.local_id {
+                    let payload = GroupPayload::Create {
+                        group_name: name.clone(),
+                        creator_username: self.config.username.clone(),
+                        initial_members: initial_members.clone(),
+                        invite_only,
+                    };
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    // Intercept self-addressed actions (e.g. Created → self)
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else {
+                    // Remote hub — send GroupPayload::Create over network
+                    let actions = self.group_manager.create_group_with_options(
+                        name,
+                        hub_relay_id,
+                        initial_members,
+                        invite_only,
+                    );
+                    self.group_actions_to_effects(&actions)
                 }
-                GroupAction::None => {}
             }
-        }
-        effects
+
+            RuntimeCommand::AcceptInvite { group_id } => {
+                let actions =
+                    self.group_manager.accept_invite(&group_id);
+                let actions = self.intercept_self_group_actions(actions);
+                self.group_actions_to_effects(&actions)
+            }
+
+            RuntimeCommand::DeclineInvite { group_id } => {
+                self.group_manager.decline_invite(&group_id);
+                Vec::new()
+            }
+
+            RuntimeCommand::LeaveGroup { group_id } if self.mesh_groups.is_mesh(&group_id) => {
+                let actions = self.mesh_groups.leave(&group_id);
+                self.mesh_actions_to_effects(actions)
+            }
+
+            RuntimeCommand::LeaveGroup { group_id } => {
+                let actions =
+                    self.group_manager.leave_group(&group_id);
+                let actions = self.intercept_self_group_actions(actions);
+                self.group_actions_to_effects(&actions)
+            }
+
+            // ── Admin controls (R11.3) ──────────────────────────
+            RuntimeCommand::KickMember { group_id, target_id }
+                if self.mesh_groups.is_mesh(&group_id) =>
+            {
+                let result = self.mesh_groups.remove_member(&group_id, target_id);
+                self.mesh_result_to_effects(result)
+            }
+
+            RuntimeCommand::KickMember { group_id, target_id } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::KickMember {
+                    group_id,
+                    target_id,
+                };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
+
+            RuntimeCommand::UpdateMemberRole {
+                group_id,
+                target_id,
+                new_role,
+            } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::UpdateMemberRole {
+                    group_id,
+                    target_id,
+                    new_role,
+                };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
+
+            RuntimeCommand::InviteMember { group_id, target_id }
+                if self.mesh_groups.is_mesh(&group_id) =>
+            {
+                let result = self.mesh_groups.add_member(&group_id, target_id);
+                self.mesh_result_to_effects(result)
+            }
+
+            RuntimeCommand::InviteMember { group_id, target_id } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::InviteMember {
+                    group_id,
+                    target_id,
+                };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
+
+            RuntimeCommand::BanMember {
+                group_id,
+                target_id,
+                duration_ms,
+            } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::BanMember {
+                    group_id,
+                    target_id,
+                    duration_ms,
+                };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
+
+            RuntimeCommand::UnbanMember { group_id, target_id } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::UnbanMember {
+                    group_id,
+                    target_id,
+                };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
+
+            RuntimeCommand::UpdateGroupMetadata {
+                group_id,
+                name,
+                description,
+                avatar_hash,
+            } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::UpdateMetadata {
+                    group_id,
+                    name,
+                    description,
+                    avatar_hash,
+                };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
+
+            RuntimeCommand::SetGroupMessageTtl { group_id, ttl_ms } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::SetMessageTtl { group_id, ttl_ms };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
+
+            RuntimeCommand::UpdateGroupSettings { group_id, settings } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::UpdateSettings { group_id, settings };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
+
+            RuntimeCommand::CreateMeshGroup {
+                name,
+                members,
+                reply,
+            } => match self.mesh_groups.create(&name, members) {
+                Ok((group_id, actions)) => {
+                    let _ = reply.send(Some(group_id));
+                    self.mesh_actions_to_effects(actions)
+                }
+                Err(e) => {
+                    let _ = reply.send(None);
+                    vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                        description: e.to_string(),
+                    })]
+                }
+            },
+
+            RuntimeCommand::GetMeshGroups { reply } => {
+                let _ = reply.send(self.mesh_groups.groups().cloned().collect());
+                Vec::new()
+            }
+
+            RuntimeCommand::OpenSharedDoc { doc_id, peers } => {
+                let actions = self.shared_state.open(&doc_id, peers);
+                self.shared_state_actions_to_effects(actions)
+            }
+
+            RuntimeCommand::CloseSharedDoc { doc_id } => {
+                self.shared_state.close(&doc_id);
+                Vec::new()
+            }
+
+            RuntimeCommand::SetSharedValue { doc_id, key, value } => {
+                match self.shared_state.set(&doc_id, key, value) {
+                    Ok(actions) => self.shared_state_actions_to_effects(actions),
+                    Err(e) => vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                        description: e.to_string(),
+                    })],
+                }
+            }
+
+            RuntimeCommand::GetSharedDoc { doc_id, reply } => {
+                let entries = self.shared_state.get(&doc_id).map(|doc| {
+                    doc.map
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.to_vec()))
+                        .collect()
+                });
+                let _ = reply.send(entries);
+                Vec::new()
+            }
+
+            RuntimeCommand::JoinChannel { name, secret } => {
+                match self.channels.join(&name, secret) {
+                    Ok((topic, previous)) => {
+                        let mut effects = Vec::new();
+                        if let Some(old) = previous {
+                            effects.push(RuntimeEffect::UnsubscribeChannel { topic: old });
+                        }
+                        effects.push(RuntimeEffect::SubscribeChannel { topic });
+                        effects
+                    }
+                    Err(e) => vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                        description: format!("join channel {name}: {e}"),
+                    })],
+                }
+            }
+
+            RuntimeCommand::LeaveChannel { name } => match self.channels.leave(&name) {
+                Some(topic) => vec![RuntimeEffect::UnsubscribeChannel { topic }],
+                None => Vec::new(),
+            },
+
+            RuntimeCommand::PublishChannel { name, payload } => {
+                match self.channels.build_publication(
+                    &name,
+                    self.local_id,
+                    &self.secret_seed,
+                    payload,
+                    now_ms(),
+                ) {
+                    Ok((topic, bytes)) => vec![RuntimeEffect::PublishChannel { topic, bytes }],
+                    Err(e) => vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                        description: format!("publish on {name}: {e}"),
+                    })],
+                }
+            }
+
+            RuntimeCommand::FetchGroupHistory {
+                group_id,
+                before,
+                limit,
+            } => {
+                let actions = self.group_manager.request_history(&group_id, before, limit);
+                let actions = self.intercept_self_group_actions(actions);
+                self.group_actions_to_effects(&actions)
+            }
+
+            RuntimeCommand::MarkGroupMessageRead {
+                group_id,
+                sender_id,
+                message_id,
+            } => {
+                let actions: Vec<GroupAction> = self
+                    .group_manager
+                    .delivery_ack(&group_id, &sender_id, &message_id, true)
+                    .into_iter()
+                    .collect();
+                let actions = self.intercept_self_group_actions(actions);
+                self.group_actions_to_effects(&actions)
+            }
+
+            RuntimeCommand::GetBackupStats { reply } => {
+                let _ = reply.send(self.backup.stats());
+                Vec::new()
+            }
+
+            RuntimeCommand::GetQueuedMessages { reply } => {
+                let _ = reply.send(self.queued_messages());
+                Vec::new()
+            }
+
+            RuntimeCommand::GetGroupDeliveryStatus {
+                group_id,
+                message_id,
+                reply,
+            } => {
+                let status = self
+                    .group_manager
+                    .delivery_status(&group_id, &message_id)
+                    .cloned();
+                let _ = reply.send(status);
+                Vec::new()
+            }
+
+            RuntimeCommand::ReactToGroupMessage {
+                group_id,
+                message_id,
+                emoji,
+                add,
+            } => {
+                let actions = match self
+                    .group_manager
+                    .reaction_request(&group_id, &message_id, &emoji, add)
+                {
+                    // We host the group: hand the request to our hub directly
+                    Some(GroupAction::Send { to, payload }) if to == self.local_id => {
+                        self.group_hub.handle_payload(payload, self.local_id)
+                    }
+                    other => other.into_iter().collect(),
+                };
+                let actions = self.intercept_self_group_actions(actions);
+                self.group_actions_to_effects(&actions)
+            }
+
+            RuntimeCommand::CreateGroupPoll {
+                group_id,
+                question,
+                options,
+                reply,
+            } => {
+                let Some((poll_id, action)) =
+                    self.group_manager.poll_request(&group_id, question, options)
+                else {
+                    let _ = reply.send(None);
+                    return Vec::new();
+                };
+                let _ = reply.send(Some(poll_id));
+                let actions = match action {
+                    // We host the group: hand the request to our hub directly
+                    GroupAction::Send { to, payload } if to == self.local_id => {
+                        self.group_hub.handle_payload(payload, self.local_id)
+                    }
+                    other => vec![other],
+                };
+                let actions = self.intercept_self_group_actions(actions);
+                self.group_actions_to_effects(&actions)
+            }
+
+            RuntimeCommand::VoteGroupPoll {
+                group_id,
+                poll_id,
+                option,
+            } => {
+                let actions = match self.group_manager.vote_request(&group_id, &poll_id, option) {
+                    Some(GroupAction::Send { to, payload }) if to == self.local_id => {
+                        self.group_hub.handle_payload(payload, self.local_id)
+                    }
+                    other => other.into_iter().collect(),
+                };
+                let actions = self.intercept_self_group_actions(actions);
+                self.group_actions_to_effects(&actions)
+            }
+
+            RuntimeCommand::SetGroupNotificationPrefs {
+                group_id,
+                prefs,
+                reply,
+            } => {
+                let _ = reply.send(self.group_manager.set_notification_prefs(&group_id, prefs));
+                Vec::new()
+            }
+
+            RuntimeCommand::GetGroupNotificationPrefs { group_id, reply } => {
+                let _ = reply.send(self.group_manager.notification_prefs(&group_id));
+                Vec::new()
+            }
+
+            RuntimeCommand::GetGroupReactions {
+                group_id,
+                message_id,
+                reply,
+            } => {
+                let _ = reply.send(self.group_manager.reaction_counts(&group_id, &message_id));
+                Vec::new()
+            }
+
+            RuntimeCommand::CreateInviteToken {
+                group_id,
+                ttl_ms,
+                reply,
+            } => {
+                let Some((token, actions)) =
+                    self.group_manager
+                        .create_invite_token(&group_id, &self.secret_seed, ttl_ms)
+                else {
+                    let _ = reply.send(None);
+                    return Vec::new();
+                };
+                let _ = reply.send(token.encode().ok());
+                let actions = self.intercept_self_group_actions(actions);
+                self.group_actions_to_effects(&actions)
+            }
+
+            RuntimeCommand::JoinGroupByToken { token } => {
+                if token.validate(now_ms()).is_err() {
+                    return vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                        description: format!("invalid invite token for {}", token.group_id),
+                    })];
+                }
+                // Make the hub reachable even if we've never seen it before.
+                // Only the token vouches for it, so it stays Stale until its
+                // first signed envelope lets the heartbeat promote it.
+                if self.topology.get(&token.hub_id).is_none() {
+                    self.topology.upsert(PeerInfo {
+                        node_id: token.hub_id,
+                        role: PeerRole::Peer,
+                        status: PeerStatus::Stale,
+                        last_seen: now_ms(),
+                    });
+                }
+                let actions = self.group_manager.join_by_token(&token);
+                let actions = self.intercept_self_group_actions(actions);
+                self.group_actions_to_effects(&actions)
+            }
+
+            RuntimeCommand::RequestJoinGroup { group_id, hub_id } => {
+                let actions = self.group_manager.request_join(&group_id, hub_id);
+                let actions = self.intercept_self_group_actions(actions);
+                self.group_actions_to_effects(&actions)
+            }
+
+            RuntimeCommand::ApproveJoinRequest { group_id, requester } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::ApproveJoin { group_id, requester };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
+
+            RuntimeCommand::DenyJoinRequest { group_id, requester } => {
+                let hub_id = self
+                    .group_manager
+                    .get_group(&group_id)
+                    .map(|g| g.hub_relay_id);
+                let payload = GroupPayload::DenyJoin { group_id, requester };
+                if hub_id == Some(self.local_id) {
+                    let actions = self.group_hub.handle_payload(payload, self.local_id);
+                    let actions = self.intercept_self_group_actions(actions);
+                    self.group_actions_to_effects(&actions)
+                } else if let Some(hub) = hub_id {
+                    self.group_actions_to_effects(&[GroupAction::Send {
+                        to: hub,
+                        payload,
+                    }])
+                } else {
+                    Vec::new()
+                }
+            }
+
+            RuntimeCommand::GetPendingJoinRequests { group_id, reply } => {
+                let _ = reply.send(self.group_manager.pending_join_requests(&group_id).to_vec());
+                Vec::new()
+            }
+
+            RuntimeCommand::GetGroups { reply } => {
+                let groups = self
+                    .group_manager
+                    .all_groups()
+                    .into_iter()
+                    .cloned()
+                    .collect();
+                let _ = reply.send(groups);
+                Vec::new()
+            }
+
+            RuntimeCommand::GetPendingInvites { reply } => {
+                let invites = self
+                    .group_manager
+                    .pending_invites()
+                    .into_iter()
+                    .cloned()
+                    .collect();
+                let _ = reply.send(invites);
+                Vec::new()
+            }
+
+            RuntimeCommand::GetRoleMetrics { node_id, reply } => {
+                let metrics =
+                    self.role_manager
+                        .get_metrics(&node_id, &self.topology, now_ms());
+                let _ = reply.send(metrics);
+                Vec::new()
+            }
+
+            RuntimeCommand::GetAllRoleScores { reply } => {
+                let scores =
+                    self.role_manager
+                        .get_all_scores(&self.topology, now_ms());
+                let _ = reply.send(scores);
+                Vec::new()
+            }
+
+            RuntimeCommand::GetPeerLiveness { node_id, reply } => {
+                let _ = reply.send(self.peer_liveness(&node_id));
+                Vec::new()
+            }
+
+            RuntimeCommand::GetAllPeers { reply } => {
+                let _ = reply.send(self.all_peer_liveness());
+                Vec::new()
+            }
+
+            // DHT lookup completed — register the discovered peer.
+            #[cfg(feature = "native")]
+            RuntimeCommand::DhtLookupResult { addr } => {
+                let Ok(node_id) = addr.node_id.parse::<NodeId>() else {
+                    tracing::warn!("DHT lookup result: invalid node_id '{}'", addr.node_id);
+                    return Vec::new();
+                };
+                self.heartbeat.record_heartbeat_with_source(
+                    node_id,
+                    DiscoverySource::Dht,
+                    String::new(),
+                );
+                self.topology.upsert(PeerInfo {
+                    node_id,
+                    role: PeerRole::Peer,
+                    status: PeerStatus::Online,
+                    last_seen: now_ms(),
+                });
+                tracing::info!(
+                    node_id = %node_id,
+                    relays = addr.relay_urls.len(),
+                    addrs = addr.direct_addrs.len(),
+                    "DHT lookup result applied"
+                );
+                Vec::new()
+            }
+
+            // Handled in the loop — needs transport access.
+            RuntimeCommand::GetConnectedPeers { .. } => Vec::new(),
+            #[cfg(feature = "native")]
+            RuntimeCommand::GetTransportStats { .. } => Vec::new(),
+            #[cfg(feature = "native")]
+            RuntimeCommand::GetPeerTransportStats { .. } => Vec::new(),
+            RuntimeCommand::AddPeerAddr { .. } => Vec::new(),
+            RuntimeCommand::GetPairingInfo { .. } => Vec::new(),
+            RuntimeCommand::CreatePairingCode { .. } => Vec::new(),
+            RuntimeCommand::RedeemPairingCode { .. } => Vec::new(),
+            RuntimeCommand::Request { .. } => Vec::new(),
+            RuntimeCommand::SendFile { .. } => Vec::new(),
+
+            // Handled in the loop — drains (prepare_shutdown), then breaks.
+            RuntimeCommand::Shutdown { .. } => Vec::new(),
+
+            // The loop routes these to the identity's state; ours if it's us.
+            RuntimeCommand::AsIdentity { identity, command } if identity == self.local_id => {
+                self.handle_command(*command)
+            }
+            RuntimeCommand::AsIdentity { .. } => Vec::new(),
+        }
+    }
+
+    // ── Contacts ─────────────────────────────────────────────────────────
+
+    /// Add or refresh a contact card. Unknown nodes enter the topology as
+    /// offline peers so they're routable once discovery finds them.
+    fn import_contact(&mut self, card: ContactCard) {
+        if card.node_id == self.local_id {
+            return;
+        }
+        let node_id = card.node_id;
+        let Ok(contact) = self.contacts.add(card) else {
+            return;
+        };
+        if let Some(ref store) = self.store {
+            let _ = store.save_contact(contact);
+        }
+        if self.topology.get(&node_id).is_none() {
+            self.topology.upsert(PeerInfo {
+                node_id,
+                role: PeerRole::Peer,
+                status: PeerStatus::Offline,
+                last_seen: 0,
+            });
+        }
+    }
+
+    /// Block or unblock a node; the router drops a blocked node's envelopes.
+    fn set_contact_blocked(&mut self, node_id: NodeId, blocked: bool) {
+        if node_id == self.local_id {
+            return;
+        }
+        let Some(contact) = self.contacts.set_blocked(node_id, blocked) else {
+            return;
+        };
+        if let Some(ref store) = self.store {
+            let _ = store.save_contact(contact);
+        }
+        self.router.set_blocked(node_id, blocked);
+    }
+
+    /// Write an edited contact through, or report why the edit failed.
+    fn persist_contact_change(
+        &self,
+        result: Result<Contact, crate::TomProtocolError>,
+    ) -> Vec<RuntimeEffect> {
+        match result {
+            Ok(contact) => {
+                if let Some(ref store) = self.store {
+                    let _ = store.save_contact(&contact);
+                }
+                Vec::new()
+            }
+            Err(e) => vec![RuntimeEffect::Emit(ProtocolEvent::Error {
+                description: format!("contact update failed: {e}"),
+            })],
+        }
+    }
+
+    // ── Typed payloads ───────────────────────────────────────────────────
+
+    /// Unwrap a typed payload and check it against the payload registry.
+    /// Untyped (legacy) bytes pass through with no content type.
+    fn unwrap_typed_payload(
+        &self,
+        bytes: Vec<u8>,
+    ) -> Result<(Vec<u8>, Option<String>), crate::TomProtocolError> {
+        if !TypedPayload::is_typed(&bytes) {
+            return Ok((bytes, None));
+        }
+        let typed = TypedPayload::from_bytes(&bytes)?;
+        self.config.payload_registry.validate(&typed)?;
+        Ok((typed.body, Some(typed.content_type)))
+    }
+
+    // ── Pub/sub channels ─────────────────────────────────────────────────
+
+    /// Handle a publication received on a channel's gossip topic.
+    ///
+    /// Bad signatures, wrong secrets, stale and replayed messages are
+    /// dropped quietly (anyone can push bytes to a topic); unknown content
+    /// types are reported like rejected chat payloads.
+    pub fn handle_channel_publication(&mut self, topic: [u8; 32], bytes: &[u8]) -> Vec<RuntimeEffect> {
+        let publication = match self.channels.accept(topic, bytes, now_ms()) {
+            Ok(p) => p,
+            Err(e) => {
+                tracing::debug!("channel publication dropped: {e}");
+                return Vec::new();
+            }
+        };
+        if publication.from == self.local_id {
+            return Vec::new();
+        }
+        match self.unwrap_typed_payload(publication.payload) {
+            Ok((payload, content_type)) => {
+                vec![RuntimeEffect::Emit(ProtocolEvent::ChannelMessage {
+                    channel: publication.channel,
+                    from: publication.from,
+                    payload,
+                    content_type,
+                    sent_at: publication.sent_at,
+                })]
+            }
+            Err(e) => vec![RuntimeEffect::Emit(ProtocolEvent::MessageRejected {
+                reason: format!(
+                    "channel {} payload from {} rejected: {e}",
+                    publication.channel, publication.from
+                ),
+            })],
+        }
+    }
+
+    // ── Task 10: handle_gossip_event ─────────────────────────────────────
+
+    /// Handle a gossip event (peer announce, neighbor up/down).
+    ///
+    /// For NeighborUp, the state method returns effects but does NOT re-broadcast
+    /// the gossip announce — that I/O is left to the loop.
+    pub fn handle_gossip_event(
+        &mut self,
+        input: GossipInput,
+    ) -> Vec<RuntimeEffect> {
+        match input {
+            GossipInput::PeerAnnounce(bytes) => {
+                // Try PeerAnnounce first (most common)
+                if let Ok(announce) =
+                    rmp_serde::from_slice::<PeerAnnounce>(&bytes)
+                {
+                    if announce.is_timestamp_valid(now_ms()) {
+                        let peer_id = announce.node_id;
+                        let role =
+                            if announce.roles.contains(&PeerRole::Relay) {
+                                PeerRole::Relay
+                            } else {
+                                PeerRole::Peer
+                            };
+                        self.backup.set_backup_preference(peer_id, announce.backup);
+                        if let Some(interval) = announce.announce_interval_ms {
+                            self.heartbeat.set_peer_interval(peer_id, interval);
+                        }
+                        if let Some(load) = announce.relay_load {
+                            self.relay_metrics.record_load(peer_id, load);
+                        }
+                        // Record with Announce source — PeerDiscovered emitted from tick_heartbeat
+                        self.heartbeat.record_heartbeat_with_source(
+                            peer_id,
+                            DiscoverySource::Announce,
+                            announce.username,
+                        );
+                        self.topology.upsert(PeerInfo {
+                            node_id: peer_id,
+                            role,
+                            status: PeerStatus::Online,
+                            last_seen: now_ms(),
+                        });
+                        self.topology.set_extra_roles(peer_id, &announce.roles);
+                        if self.config.enable_pex {
+                            crate::discovery::merge_pex_peers(
+                                &mut self.topology,
+                                peer_id,
+                                &announce.peers,
+                                self.local_id,
+                                now_ms(),
+                            );
+                        }
+                        self.record_hosted_identities(
+                            peer_id,
+                            announce.announce_interval_ms,
+                            announce.hosted,
+                        );
+                        let mut effects = self.record_username_claim(peer_id, announce.claim);
+                        effects.extend(self.record_peer_presence(peer_id, announce.presence));
+                        effects.extend(self.record_device_link(
+                            peer_id,
+                            announce.device,
+                            announce.revocations,
+                        ));
+                        return effects;
+                    }
+                }
+
+                // Try RoleChangeAnnounce
+                if let Ok(role_announce) =
+                    rmp_serde::from_slice::<crate::discovery::RoleChangeAnnounce>(&bytes)
+                {
+                    return self.handle_role_announce(role_announce);
+                }
+
+                // Try AbuseReport
+                if let Ok(report) =
+                    rmp_serde::from_slice::<crate::discovery::AbuseReport>(&bytes)
+                {
+                    return self.handle_abuse_report(report);
+                }
+
+                // Try RelayAttestation
+                if let Ok(attestation) =
+                    rmp_serde::from_slice::<crate::discovery::RelayAttestation>(&bytes)
+                {
+                    return self.handle_relay_attestation(attestation);
+                }
+
+                // Try DepartureAnnounce
+                if let Ok(departure) =
+                    rmp_serde::from_slice::<crate::discovery::DepartureAnnounce>(&bytes)
+                {
+                    return self.handle_departure(departure);
+                }
+
+                Vec::new()
+            }
+
+            GossipInput::NeighborUp(node_id) => {
+                // A newcomer reaching an established node joined through it
+                if self.topology.get(&node_id).is_none() && !self.topology.is_empty() {
+                    self.role_manager.record_join_assist(self.local_id, now_ms());
+                }
+                self.heartbeat.record_heartbeat_with_source(
+                    node_id,
+                    DiscoverySource::Gossip,
+                    String::new(),
+                );
+                self.topology.upsert(PeerInfo {
+                    node_id,
+                    role: PeerRole::Peer,
+                    status: PeerStatus::Online,
+                    last_seen: now_ms(),
+                });
+                vec![RuntimeEffect::Emit(
+                    ProtocolEvent::GossipNeighborUp { node_id },
+                )]
+            }
+
+            GossipInput::NeighborDown(node_id) => {
+                vec![RuntimeEffect::Emit(
+                    ProtocolEvent::GossipNeighborDown { node_id },
+                )]
+            }
+        }
+    }
+
+    // ── Helper: surface subnet event ─────────────────────────────────────
+
+    /// Convert a SubnetEvent into RuntimeEffects (only Formed/Dissolved surface).
+    fn surface_subnet_event(&self, event: &SubnetEvent) -> Vec<RuntimeEffect> {
+        let proto_event = match event {
+            SubnetEvent::SubnetFormed { subnet } => Some(ProtocolEvent::SubnetFormed {
+                subnet_id: subnet.subnet_id.clone(),
+                members: subnet.members.iter().copied().collect(),
+            }),
+            SubnetEvent::SubnetDissolved { subnet_id, reason } => {
+                Some(ProtocolEvent::SubnetDissolved {
+                    subnet_id: subnet_id.clone(),
+                    reason: format!("{reason:?}"),
+                })
+            }
+            // NodeJoined/Left are internal bookkeeping
+            _ => None,
+        };
+        proto_event
+            .into_iter()
+            .map(RuntimeEffect::Emit)
+            .collect()
+    }
+
+    // ── Helper: replica candidates ───────────────────────────────────────
+
+    /// Describe every known peer as a potential backup holder.
+    ///
+    /// HostFactors come from contribution metrics: uptime (24h = full
+    /// stability), give/take bandwidth ratio, and the contribution score.
+    fn replica_candidates(&self, now: u64) -> Vec<crate::backup::ReplicaCandidate> {
+        self.topology
+            .peers()
+            .filter(|p| p.node_id != self.local_id)
+            .map(|peer| {
+                let factors = self
+                    .role_manager
+                    .get_metrics(&peer.node_id, &self.topology, now)
+                    .map(|m| HostFactors {
+                        stability: (m.uptime_hours / 24.0 * 100.0).min(100.0) as u8,
+                        bandwidth: (m.bandwidth_ratio * 100.0).min(100.0) as u8,
+                        contribution: (m.score / (m.score + 10.0) * 100.0) as u8,
+                    })
+                    .unwrap_or_default();
+                crate::backup::ReplicaCandidate {
+                    node_id: peer.node_id,
+                    factors,
+                    is_relay: peer.role == PeerRole::Relay,
+                    is_storage: self.topology.has_role(&peer.node_id, PeerRole::Storage),
+                    online: peer.status == PeerStatus::Online,
+                    availability: self.backup.availability(&peer.node_id),
+                    cluster: self
+                        .subnets
+                        .get_node_subnet(&peer.node_id)
+                        .map(|s| s.subnet_id.clone()),
+                }
+            })
+            .collect()
+    }
+
+    // ── Helper: prepare backup delivery for reconnected peer ─────────────
+
+    /// Queue everything we hold for a reconnected peer and send the first
+    /// batch; the rest follow on [`tick_backup_delivery`](Self::tick_backup_delivery).
+    ///
+    /// Batches go out as BackupDeliverBatch, which the recipient confirms
+    /// once it has the messages.
+    fn prepare_backup_delivery(&mut self, peer_id: NodeId) -> Vec<RuntimeEffect> {
+        let actions = self.backup.queue_delivery(peer_id, now_ms());
+        self.backup_actions_to_effects(&actions)
+    }
+
+    // ── Helper: surface role action ──────────────────────────────────────
+
+    /// Convert a RoleAction into RuntimeEffects.
+    ///
+    /// For local role changes, also broadcasts a signed `RoleChangeAnnounce` via gossip.
+    fn surface_role_action(&mut self, action: &RoleAction) -> Vec<RuntimeEffect> {
+        use crate::discovery::RoleChangeAnnounce;
+
+        match action {
+            RoleAction::Promoted { node_id, score } => {
+                let mut effects = vec![RuntimeEffect::Emit(ProtocolEvent::RolePromoted {
+                    node_id: *node_id,
+                    score: *score,
+                })];
+
+                // Broadcast via gossip if it's our local promotion
+                if *node_id == self.local_id {
+                    let announce = RoleChangeAnnounce::new(
+                        *node_id,
+                        PeerRole::Relay,
+                        *score,
+                        now_ms(),
+                        &self.secret_seed,
+                    );
+                    effects.push(RuntimeEffect::BroadcastRoleChange(announce));
+                }
+
+                effects
+            }
+            RoleAction::Demoted { node_id, score } => {
+                let mut effects = vec![RuntimeEffect::Emit(ProtocolEvent::RoleDemoted {
+                    node_id: *node_id,
+                    score: *score,
+                })];
+
+                // Broadcast via gossip if it's our local demotion
+                if *node_id == self.local_id {
+                    let announce = RoleChangeAnnounce::new(
+                        *node_id,
+                        PeerRole::Peer,
+                        *score,
+                        now_ms(),
+                        &self.secret_seed,
+                    );
+                    effects.push(RuntimeEffect::BroadcastRoleChange(announce));
+                }
+
+                effects
+            }
+            RoleAction::LocalRoleChanged { new_role } => {
+                // Replace the primary role, keeping extras
+                self.local_roles.retain(PeerRole::is_extra);
+                self.local_roles.insert(0, *new_role);
+                let score = self.role_manager.score(&self.local_id, now_ms());
+
+                let announce = RoleChangeAnnounce::new(
+                    self.local_id,
+                    *new_role,
+                    score,
+                    now_ms(),
+                    &self.secret_seed,
+                );
+
+                vec![
+                    RuntimeEffect::Emit(ProtocolEvent::LocalRoleChanged {
+                        new_role: *new_role,
+                    }),
+                    RuntimeEffect::BroadcastRoleChange(announce),
+                ]
+            }
+            RoleAction::LocalRoleGranted { role } | RoleAction::LocalRoleRevoked { role } => {
+                self.local_roles.retain(|r| r != role);
+                if matches!(action, RoleAction::LocalRoleGranted { .. }) {
+                    self.local_roles.push(*role);
+                }
+                // Re-announce right away so peers can pick us for the role
+                let mut effects = vec![RuntimeEffect::Emit(ProtocolEvent::LocalRolesChanged {
+                    roles: self.local_roles.clone(),
+                })];
+                effects.extend(self.build_gossip_announce().map(RuntimeEffect::BroadcastAnnounce));
+                effects
+            }
+        }
+    }
+
+    /// Peers to join a gossip topic through: online Bootstrap nodes first,
+    /// then other online peers, most recently seen first.
+    pub fn gossip_entry_points(&self) -> Vec<NodeId> {
+        let mut peers: Vec<&PeerInfo> = self
+            .topology
+            .peers()
+            .filter(|p| p.status == PeerStatus::Online && p.node_id != self.local_id)
+            .collect();
+        peers.sort_by_key(|p| {
+            (
+                !self.topology.has_role(&p.node_id, PeerRole::Bootstrap),
+                std::cmp::Reverse(p.last_seen),
+            )
+        });
+        peers.into_iter().map(|p| p.node_id).collect()
+    }
+
+    // ── Helper: self-addressed group action interception ─────────────────
+
+    /// Intercept Send/Broadcast actions that target `local_id` and process
+    /// them locally instead of sending over the network (QUIC self-sends).
+    /// Recursive: handles chains where local processing generates more self-sends
+    /// (e.g., MemberJoined → SenderKeyDistribution → hub fanout).
+    fn intercept_self_group_actions(&mut self, actions: Vec<GroupAction>) -> Vec<GroupAction> {
+        let mut result = Vec::new();
+        for action in actions {
+            match action {
+                GroupAction::Send { to, payload } if to == self.local_id => {
+                    self.persist_hosted_message(&payload);
+                    let new_actions = self.handle_local_group_payload(payload);
+                    // Recursively intercept any resulting self-sends
+                    result.extend(self.intercept_self_group_actions(new_actions));
+                }
+                GroupAction::Broadcast { to, payload } if to.contains(&self.local_id) => {
+                    self.persist_hosted_message(&payload);
+                    // Process locally for self
+                    let new_actions = self.handle_local_group_payload(payload.clone());
+                    result.extend(self.intercept_self_group_actions(new_actions));
+                    // Keep broadcast for remote targets
+                    let remote: Vec<NodeId> =
+                        to.into_iter().filter(|t| *t != self.local_id).collect();
+                    if !remote.is_empty() {
+                        result.push(GroupAction::Broadcast {
+                            to: remote,
+                            payload,
+                        });
+                    }
+                }
+                other => result.push(other),
+            }
+        }
+        result
+    }
+
+    /// Dispatch a group payload locally. Routes hub-bound payloads to the
+    /// GroupHub for processing, and member-bound payloads to the GroupManager.
+    fn handle_local_group_payload(&mut self, payload: GroupPayload) -> Vec<GroupAction> {
+        match payload {
+            // ── Hub-bound payloads: route through GroupHub ─────────────
+            GroupPayload::Create { .. }
+            | GroupPayload::Join { .. }
+            | GroupPayload::Leave { .. }
+            | GroupPayload::KickMember { .. }
+            | GroupPayload::UpdateMemberRole { .. }
+            | GroupPayload::InviteMember { .. }
+            | GroupPayload::BanMember { .. }
+            | GroupPayload::UnbanMember { .. }
+            | GroupPayload::JoinWithToken { .. }
+            | GroupPayload::JoinRequest { .. }
+            | GroupPayload::ApproveJoin { .. } => {
+                self.group_hub.handle_payload(payload, self.local_id)
+            }
+            GroupPayload::SenderKeyDistribution {
+                group_id, from, epoch, encrypted_keys,
+            } => {
+                let mut actions = Vec::new();
+                // Always try to store keys for self via manager
+                actions.extend(self.group_manager.handle_sender_key_distribution(
+                    &group_id, from, epoch, &encrypted_keys, &self.secret_seed,
+                ));
+                // If there are keys for OTHER members and we're the hub, fan out
+                let keys_for_others: Vec<_> = encrypted_keys
+                    .into_iter()
+                    .filter(|ek| ek.recipient_id != self.local_id)
+                    .collect();
+                if !keys_for_others.is_empty()
+                    && self.group_hub.get_group(&group_id).is_some()
+                {
+                    let fanout_payload = GroupPayload::SenderKeyDistribution {
+                        group_id,
+                        from,
+                        epoch,
+                        encrypted_keys: keys_for_others,
+                    };
+                    actions.extend(
+                        self.group_hub
+                            .handle_payload(fanout_payload, self.local_id),
+                    );
+                }
+                actions
+            }
+            GroupPayload::DeliveryAck { ref group_id, .. }
+            | GroupPayload::UpdateMetadata { ref group_id, .. }
+            | GroupPayload::SetMessageTtl { ref group_id, .. }
+            | GroupPayload::UpdateSettings { ref group_id, .. }
+            | GroupPayload::RegisterInviteToken { ref group_id, .. }
+            | GroupPayload::DenyJoin { ref group_id, .. } => {
+                if self.group_hub.get_group(group_id).is_some() {
+                    self.group_hub.handle_payload(payload, self.local_id)
+                } else {
+                    vec![]
+                }
+            }
+
+            // ── Member-bound payloads: route to GroupManager ──────────
+            GroupPayload::MemberJoined { group_id, member } => {
+                self.group_manager.handle_member_joined(&group_id, member)
+            }
+            GroupPayload::MemberLeft {
+                group_id,
+                node_id,
+                username,
+                reason,
+            } => self
+                .group_manager
+                .handle_member_left(&group_id, &node_id, username, reason),
+            GroupPayload::Message(msg) => self.group_manager.handle_message(msg),
+            GroupPayload::DeliveryReceipt {
+                group_id,
+                message_id,
+                member,
+                read,
+                all_delivered,
+            } => self.group_manager.handle_delivery_receipt(
+                self.local_id, &group_id, message_id, member, read, all_delivered,
+            ),
+            // Our own hub's fan-out (requests to it are handed over directly)
+            GroupPayload::Reaction {
+                group_id,
+                message_id,
+                emoji,
+                add,
+                reactor,
+            } => self.group_manager.handle_reaction(
+                self.local_id, &group_id, message_id, emoji, add, reactor,
+            ),
+            GroupPayload::Poll { group_id, poll } => {
+                self.group_manager.handle_poll(self.local_id, &group_id, poll)
+            }
+            GroupPayload::Sync {
+                group,
+                recent_messages,
+            } => self.group_manager.handle_group_sync(group, recent_messages),
+            GroupPayload::Created { group } => {
+                self.group_manager.handle_group_created(group)
+            }
+            GroupPayload::Invite {
+                group_id,
+                group_name,
+                inviter_id,
+                inviter_username,
+            } => self.group_manager.handle_invite(
+                group_id,
+                group_name,
+                inviter_id,
+                inviter_username,
+                self.local_id,
+            ),
+            GroupPayload::MemberRoleChanged {
+                group_id,
+                node_id,
+                new_role,
+            } => self.group_manager.handle_member_role_changed(
+                &group_id, &node_id, new_role,
+            ),
+            GroupPayload::MetadataChanged {
+                group_id,
+                name,
+                description,
+                avatar_hash,
+                config_version,
+                updated_by,
+            } => self.group_manager.handle_metadata_changed(
+                self.local_id, &group_id, name, description, avatar_hash, config_version, updated_by,
+            ),
+            GroupPayload::MessageTtlChanged {
+                group_id,
+                ttl_ms,
+                config_version,
+                updated_by,
+            } => self.group_manager.handle_message_ttl_changed(
+                self.local_id, &group_id, ttl_ms, config_version, updated_by,
+            ),
+            GroupPayload::SettingsChanged {
+                group_id,
+                settings,
+                config_version,
+                updated_by,
+            } => self.group_manager.handle_settings_changed(
+                self.local_id, &group_id, settings, config_version, updated_by,
+            ),
+            GroupPayload::JoinRequested { group_id, request } => {
+                self.group_manager.handle_join_requested(&group_id, request)
+            }
+            GroupPayload::JoinRequestResolved {
+                group_id,
+                requester,
+                approved,
+                resolved_by,
+            } => self.group_manager.handle_join_request_resolved(
+                &group_id, requester, approved, resolved_by, self.local_id,
+            ),
+            GroupPayload::FetchHistory {
+                ref group_id,
+                before,
+                limit,
+            } => self.handle_fetch_history(self.local_id, group_id, before, limit),
+            GroupPayload::HistoryPage {
+                group_id,
+                messages,
+                has_more,
+            } => self
+                .group_manager
+                .handle_history_page(&group_id, messages, has_more),
+            // Payloads that don't need local dispatch
+            _ => vec![],
+        }
+    }
+
+    // ── Helper: group actions → effects ──────────────────────────────────
+
+    /// R13: persist a message we fan out as hub to SQLite for offline gap-fill.
+    ///
+    /// Sub-hubs relaying a shard don't host the group and store nothing.
+    fn persist_hub_message(&self, msg: &GroupMessage) {
+        let Some(ref store) = self.store else { return };
+        if self.group_hub.get_group(&msg.group_id).is_none() {
+            return;
+        }
+        let data = rmp_serde::to_vec(msg).unwrap_or_default();
+        // Stamp with `sent_at` so expiry by `stored_at` matches the message's
+        // lifespan, even for scheduled messages and history re-persisted on
+        // promotion.
+        let stored_at = msg.sent_at.min(crate::types::now_ms());
+        let _ = store.save_hub_message(&msg.group_id, msg.seq, &data, stored_at);
+    }
+
+    /// Persist the hub's stored copy of a message it fans out.
+    ///
+    /// Taken from hub history rather than the payload: admins get the
+    /// attributed original of an anonymous post, but only the stripped copy
+    /// is kept. Called on every delivery path, so a post that only reaches
+    /// admins (or ourselves) survives a hub restart too.
+    fn persist_hosted_message(&self, payload: &GroupPayload) {
+        let message = match payload {
+            GroupPayload::Message(message) | GroupPayload::ShardRelay { message, .. } => message,
+            _ => return,
+        };
+        let stored = self
+            .group_hub
+            .message_history(&message.group_id)
+            .and_then(|history| history.iter().rev().find(|m| m.message_id == message.message_id));
+        if let Some(stored) = stored {
+            self.persist_hub_message(stored);
+        }
+    }
+
+    /// Keep a hosted group's message for an offline member, sealed to them.
+    ///
+    /// One backup entry per member, keyed `<message_id>:<member>`.
+    fn backup_group_message(
+        &mut self,
+        msg: &GroupMessage,
+        member: NodeId,
+        payload_bytes: &[u8],
+        ttl_ms: Option<u64>,
+    ) -> Vec<RuntimeEffect> {
+        let sealed = match crate::crypto::encrypt(payload_bytes, &member.as_bytes())
+            .and_then(|p| p.to_bytes())
+        {
+            Ok(sealed) => sealed,
+            Err(e) => {
+                tracing::warn!("cannot seal group message for {member}: {e}");
+                return Vec::new();
+            }
+        };
+        let actions = self.backup.store_group_message(
+            format!("{}:{member}", msg.message_id),
+            sealed,
+            member,
+            msg.sender_id,
+            now_ms(),
+            ttl_ms,
+        );
+        self.backup_actions_to_effects(&actions)
+    }
+
+    /// Convert GroupActions into RuntimeEffects (Send, Broadcast, Event).
+    fn group_actions_to_effects(&mut self, actions: &[GroupAction]) -> Vec<RuntimeEffect> {
+        let mut effects = Vec::new();
+        for action in actions {
+            match action {
+                GroupAction::Send { to, payload } => {
+                    self.persist_hosted_message(payload);
+                    let msg_type = group_payload_to_message_type(payload);
+                    let payload_bytes =
+                        rmp_serde::to_vec(payload).expect("group payload serialization");
+                    if !matches!(payload, GroupPayload::HubHeartbeat { .. }) {
+                        self.heartbeat.record_sent(*to, now_ms());
+                    }
+                    let via = self.relay_selector.select_path_weighted(*to, &self.topology, &self.subnets, &self.relay_metrics);
+                    let envelope =
+                        EnvelopeBuilder::new(self.local_id, *to, msg_type, payload_bytes)
+                            .via(via)
+                            .sign(&self.secret_seed);
+                    effects.push(RuntimeEffect::SendEnvelope(envelope));
+                }
+                GroupAction::Broadcast { to, payload } => {
+                    let hosted_message = match payload {
+                        GroupPayload::Message(msg) => {
+                            self.persist_hosted_message(payload);
+                            self.group_hub.get_group(&msg.group_id).map(|g| (msg, g.message_ttl_ms))
+                        }
+                        _ => None,
+                    };
+
+                    let msg_type = group_payload_to_message_type(payload);
+                    let payload_bytes =
+                        rmp_serde::to_vec(payload).expect("group payload serialization");
+                    for target in to {
+                        // Hub fan-out to an offline member: hold it in backup
+                        // until they reconnect instead of sending into the void.
+                        if let Some((msg, ttl_ms)) = hosted_message {
+                            if self.topology.get(target).map(|p| p.status) == Some(PeerStatus::Offline) {
+                                effects.extend(self.backup_group_message(msg, *target, &payload_bytes, ttl_ms));
+                                continue;
+                            }
+                        }
+                        if !matches!(payload, GroupPayload::HubHeartbeat { .. }) {
+                            self.heartbeat.record_sent(*target, now_ms());
+                        }
+                        let via = self.relay_selector.select_path_weighted(*target, &self.topology, &self.subnets, &self.relay_metrics);
+                        let envelope = EnvelopeBuilder::new(
+                            self.local_id,
+                            *target,
+                            msg_type,
+                            payload_bytes.clone(),
+                        )
+                        .via(via)
+                        .sign(&self.secret_seed);
+                        effects.push(RuntimeEffect::SendEnvelope(envelope));
+                    }
+                }
+                GroupAction::Event(event) => {
+                    effects.extend(self.surface_group_event(event));
+                }
+                GroupAction::None => {}
+            }
+        }
+        if actions.iter().any(|a| self.changes_hub_state(a)) {
+            self.checkpoint_hub();
+        }
+        effects
+    }
+
+    /// Whether an action reflects a change to a hosted group's persistent
+    /// state (roster, roles, bans, config, shadow assignment).
+    fn changes_hub_state(&self, action: &GroupAction) -> bool {
+        match action {
+            GroupAction::Send { payload, .. } | GroupAction::Broadcast { payload, .. } => matches!(
+                payload,
+                GroupPayload::Created { .. }
+                    | GroupPayload::MemberJoined { .. }
+                    | GroupPayload::MemberLeft { .. }
+                    | GroupPayload::MemberRoleChanged { .. }
+                    | GroupPayload::MetadataChanged { .. }
+                    | GroupPayload::MessageTtlChanged { .. }
+                    | GroupPayload::SettingsChanged { .. }
+                    | GroupPayload::HubShadowSync { .. }
+                    | GroupPayload::HubMigration { .. }
+            ),
+            // A group we created and host alone never leaves the node
+            GroupAction::Event(GroupEvent::GroupCreated(info)) => {
+                self.group_hub.get_group(&info.group_id).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    // ── Helper: backup actions → effects ─────────────────────────────────
+
+    /// Convert BackupActions into RuntimeEffects.
+    fn backup_actions_to_effects(&self, actions: &[BackupAction]) -> Vec<RuntimeEffect> {
+        let mut effects = Vec::new();
+        for action in actions {
+            match action {
+                BackupAction::Replicate { target, payload } => {
+                    let bytes =
+                        rmp_serde::to_vec(payload).expect("backup replication serialization");
+                    let via = self.relay_selector.select_path_weighted(*target, &self.topology, &self.subnets, &self.relay_metrics);
+                    let envelope = EnvelopeBuilder::new(
+                        self.local_id,
+                        *target,
+                        MessageType::BackupReplicate,
+                        bytes,
+                    )
+                    .via(via)
+                    .sign(&self.secret_seed);
+                    effects.push(RuntimeEffect::SendEnvelope(envelope));
+                }
+                BackupAction::Handoff { target, payload } => {
+                    let handoff = crate::backup::BackupHandoffPayload {
+                        replica: payload.clone(),
+                    };
+                    let bytes = rmp_serde::to_vec(&handoff).expect("backup handoff serialization");
+                    let via = self.relay_selector.select_path_weighted(*target, &self.topology, &self.subnets, &self.relay_metrics);
+                    let envelope = EnvelopeBuilder::new(
+                        self.local_id,
+                        *target,
+                        MessageType::BackupHandoff,
+                        bytes,
+                    )
+                    .via(via)
+                    .sign(&self.secret_seed);
+                    effects.push(RuntimeEffect::SendEnvelope(envelope));
+                }
+                BackupAction::DeliverBatch {
+                    recipient_id,
+                    entries,
+                } => {
+                    let batch = crate::backup::BackupDeliverBatchPayload {
+                        recipient_id: *recipient_id,
+                        entries: entries.clone(),
+                    };
+                    let bytes = rmp_serde::to_vec(&batch).expect("backup deliver serialization");
+                    let via = self.relay_selector.select_path_weighted(*recipient_id, &self.topology, &self.subnets, &self.relay_metrics);
+                    let envelope = EnvelopeBuilder::new(
+                        self.local_id,
+                        *recipient_id,
+                        MessageType::BackupDeliverBatch,
+                        bytes,
+                    )
+                    .via(via)
+                    .sign(&self.secret_seed);
+
+                    // On failure: no action (messages stay in the backup store).
+                    let on_success = entries
+                        .iter()
+                        .map(|entry| {
+                            RuntimeEffect::Emit(ProtocolEvent::BackupDelivered {
+                                message_id: entry.message_id.clone(),
+                                recipient_id: *recipient_id,
+                            })
+                        })
+                        .collect();
+                    effects.push(RuntimeEffect::SendWithBackupFallback {
+                        envelope,
+                        on_success,
+                        on_failure: Vec::new(),
+                    });
+                }
+                BackupAction::ConfirmDelivery {
+                    message_ids,
+                    recipient_id: _,
+                } => {
+                    let bytes =
+                        rmp_serde::to_vec(message_ids).expect("backup confirm serialization");
+                    for peer in self.topology.peers() {
+                        if peer.node_id != self.local_id && peer.status == PeerStatus::Online {
+                            let envelope = EnvelopeBuilder::new(
+                                self.local_id,
+                                peer.node_id,
+                                MessageType::BackupConfirmDelivery,
+                                bytes.clone(),
+                            )
+                            .sign(&self.secret_seed);
+                            effects.push(RuntimeEffect::SendEnvelope(envelope));
+                        }
+                    }
+                }
+                BackupAction::QueryPending { recipient_id, known } => {
+                    let query = crate::backup::BackupQueryPayload {
+                        recipient_id: *recipient_id,
+                        known: known.clone(),
+                    };
+                    let bytes = rmp_serde::to_vec(&query).expect("backup query serialization");
+                    for peer in self.topology.peers() {
+                        if peer.node_id != self.local_id && peer.status == PeerStatus::Online {
+                            let envelope = EnvelopeBuilder::new(
+                                self.local_id,
+                                peer.node_id,
+                                MessageType::BackupQuery,
+                                bytes.clone(),
+                            )
+                            .sign(&self.secret_seed);
+                            effects.push(RuntimeEffect::SendEnvelope(envelope));
+                        }
+                    }
+                }
+                BackupAction::Event(event) => {
+                    effects.extend(self.surface_backup_event(event));
+                }
+            }
+        }
+        effects
+    }
+
+    // ── Helper: surface group event ──────────────────────────────────────
+
+    /// Map a GroupEvent to a ProtocolEvent wrapped in RuntimeEffect::Emit.
+    fn surface_group_event(&self, event: &GroupEvent) -> Vec<RuntimeEffect> {
+        let proto_event = match event {
+            GroupEvent::GroupCreated(info) => ProtocolEvent::GroupCreated {
+                group: info.clone(),
+            },
+            GroupEvent::InviteReceived(invite) => ProtocolEvent::GroupInviteReceived {
+                invite: invite.clone(),
+            },
+            GroupEvent::Joined {
+                group_id,
+                group_name,
+            } => ProtocolEvent::GroupJoined {
+                group_id: group_id.clone(),
+                group_name: group_name.clone(),
+            },
+            GroupEvent::MemberJoined { group_id, member } => ProtocolEvent::GroupMemberJoined {
+                group_id: group_id.clone(),
+                member: member.clone(),
+            },
+            GroupEvent::MemberLeft {
+                group_id,
+                node_id,
+                username,
+                reason,
+            } => ProtocolEvent::GroupMemberLeft {
+                group_id: group_id.clone(),
+                node_id: *node_id,
+                username: username.clone(),
+                reason: *reason,
+            },
+            GroupEvent::MemberRoleChanged {
+                group_id,
+                node_id,
+                new_role,
+            } => ProtocolEvent::GroupMemberRoleChanged {
+                group_id: group_id.clone(),
+                node_id: *node_id,
+                new_role: *new_role,
+            },
+            GroupEvent::MetadataChanged {
+                group_id,
+                name,
+                description,
+                avatar_hash,
+                config_version,
+                updated_by,
+            } => ProtocolEvent::GroupMetadataChanged {
+                group_id: group_id.clone(),
+                name: name.clone(),
+                description: description.clone(),
+                avatar_hash: *avatar_hash,
+                config_version: *config_version,
+                updated_by: *updated_by,
+            },
+            GroupEvent::MessageTtlChanged {
+                group_id,
+                ttl_ms,
+                updated_by,
+            } => ProtocolEvent::GroupMessageTtlChanged {
+                group_id: group_id.clone(),
+                ttl_ms: *ttl_ms,
+                updated_by: *updated_by,
+            },
+            GroupEvent::SettingsChanged {
+                group_id,
+                settings,
+                updated_by,
+            } => ProtocolEvent::GroupSettingsChanged {
+                group_id: group_id.clone(),
+                settings: *settings,
+                updated_by: *updated_by,
+            },
+            GroupEvent::MessagesExpired {
+                group_id,
+                message_ids,
+            } => ProtocolEvent::GroupMessagesExpired {
+                group_id: group_id.clone(),
+                message_ids: message_ids.clone(),
+            },
+            GroupEvent::MessageReceived(msg) => ProtocolEvent::GroupMessageReceived {
+                message: msg.clone(),
+                muted: self.group_manager.is_muted(msg, now_ms()),
+            },
+            GroupEvent::HistoryPage {
+                group_id,
+                messages,
+                has_more,
+            } => ProtocolEvent::GroupHistoryPage {
+                group_id: group_id.clone(),
+                messages: messages.clone(),
+                has_more: *has_more,
+            },
+            GroupEvent::DeliveryUpdated {
+                group_id,
+                message_id,
+                member,
+                read,
+            } => ProtocolEvent::GroupDeliveryUpdated {
+                group_id: group_id.clone(),
+                message_id: message_id.clone(),
+                member: *member,
+                read: *read,
+            },
+            GroupEvent::AllDelivered {
+                group_id,
+                message_id,
+            } => ProtocolEvent::GroupMessageDeliveredToAll {
+                group_id: group_id.clone(),
+                message_id: message_id.clone(),
+            },
+            GroupEvent::Mentioned {
+                group_id,
+                message_id,
+                sender_id,
+                sender_username,
+            } => ProtocolEvent::GroupMentioned {
+                group_id: group_id.clone(),
+                message_id: message_id.clone(),
+                sender_id: *sender_id,
+                sender_username: sender_username.clone(),
+            },
+            GroupEvent::ReactionChanged {
+                group_id,
+                message_id,
+                emoji,
+                reactor,
+                added,
+                count,
+            } => ProtocolEvent::GroupReactionChanged {
+                group_id: group_id.clone(),
+                message_id: message_id.clone(),
+                emoji: emoji.clone(),
+                reactor: *reactor,
+                added: *added,
+                count: *count,
+            },
+            GroupEvent::PollUpdated { group_id, poll } => ProtocolEvent::GroupPollUpdated {
+                group_id: group_id.clone(),
+                poll: poll.clone(),
+            },
+            GroupEvent::JoinRequested { group_id, request } => {
+                ProtocolEvent::GroupJoinRequested {
+                    group_id: group_id.clone(),
+                    request: request.clone(),
+                }
+            }
+            GroupEvent::JoinRequestResolved {
+                group_id,
+                requester,
+                approved,
+                resolved_by,
+            } => ProtocolEvent::GroupJoinRequestResolved {
+                group_id: group_id.clone(),
+                requester: *requester,
+                approved: *approved,
+                resolved_by: *resolved_by,
+            },
+            GroupEvent::JoinDenied { group_id } => ProtocolEvent::GroupJoinDenied {
+                group_id: group_id.clone(),
+            },
+            GroupEvent::ContactShared {
+                group_id,
+                sender_id,
+                card,
+            } => ProtocolEvent::ContactCardReceived {
+                from: *sender_id,
+                group_id: Some(group_id.clone()),
+                card: card.clone(),
+            },
+            GroupEvent::HubMigrated {
+                group_id,
+                new_hub_id,
+            } => ProtocolEvent::GroupHubMigrated {
+                group_id: group_id.clone(),
+                new_hub_id: *new_hub_id,
+            },
+            GroupEvent::SecurityViolation {
+                group_id,
+                node_id,
+                reason,
+            } => ProtocolEvent::GroupSecurityViolation {
+                group_id: group_id.clone(),
+                node_id: *node_id,
+                reason: reason.clone(),
+            },
+        };
+        vec![RuntimeEffect::Emit(proto_event)]
+    }
+
+    // ── Helper: surface backup event ─────────────────────────────────────
+
+    /// Map a BackupEvent to a ProtocolEvent (storage lifecycle events surface to app).
+    fn surface_backup_event(&self, event: &BackupEvent) -> Vec<RuntimeEffect> {
+        let proto_event = match event {
+            BackupEvent::MessageStored {
+                message_id,
+                recipient_id,
+            } => Some(ProtocolEvent::BackupStored {
+                message_id: message_id.clone(),
+                recipient_id: *recipient_id,
+            }),
+            BackupEvent::MessageDelivered {
+                message_id,
+                recipient_id,
+            } => Some(ProtocolEvent::BackupDelivered {
+                message_id: message_id.clone(),
+                recipient_id: *recipient_id,
+            }),
+            BackupEvent::MessageExpired {
+                message_id,
+                recipient_id,
+            } => Some(ProtocolEvent::BackupExpired {
+                message_id: message_id.clone(),
+                recipient_id: *recipient_id,
+            }),
+            BackupEvent::MessageEvicted {
+                message_id,
+                recipient_id,
+            } => Some(ProtocolEvent::BackupEvicted {
+                message_id: message_id.clone(),
+                recipient_id: *recipient_id,
+            }),
+            BackupEvent::MessageCorrupted {
+                message_id,
+                recipient_id,
+            } => Some(ProtocolEvent::BackupCorrupted {
+                message_id: message_id.clone(),
+                recipient_id: *recipient_id,
+            }),
+            BackupEvent::ReplicationDegraded {
+                message_id,
+                recipient_id,
+                remaining_holders,
+            } => Some(ProtocolEvent::ReplicationDegraded {
+                message_id: message_id.clone(),
+                recipient_id: *recipient_id,
+                remaining_holders: *remaining_holders,
+            }),
+            BackupEvent::StoreQuotaExceeded {
+                message_id,
+                sender_id,
+            } => Some(ProtocolEvent::StorageQuotaExceeded {
+                message_id: message_id.clone(),
+                sender_id: *sender_id,
+                limit: crate::backup::QuotaLimit::Store,
+            }),
+            BackupEvent::SenderQuotaExceeded {
+                message_id,
+                sender_id,
+            } => Some(ProtocolEvent::StorageQuotaExceeded {
+                message_id: message_id.clone(),
+                sender_id: *sender_id,
+                limit: crate::backup::QuotaLimit::Sender,
+            }),
+            // Internal events — don't surface to application
+            BackupEvent::ReplicationNeeded { .. }
+            | BackupEvent::SelfDeleteRecommended { .. }
+            | BackupEvent::MessageReplicated { .. } => None,
+        };
+        proto_event
+            .into_iter()
+            .map(RuntimeEffect::Emit)
+            .collect()
+    }
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::RuntimeConfig;
+    use crate::relay::PeerStatus;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    fn keypair(seed: u8) -> (NodeId, [u8; 32]) {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        let node_id: NodeId = secret.public().to_string().parse().unwrap();
+        let seed_bytes = secret.to_bytes();
+        (node_id, seed_bytes)
+    }
+
+    fn default_state(seed: u8) -> RuntimeState {
+        let (id, secret) = keypair(seed);
+        RuntimeState::new(id, secret, RuntimeConfig::default())
+    }
+
+    // ── Task 4 tests ─────────────────────────────────────────────────────
+
+    #[test]
+    fn tick_cache_cleanup_returns_no_effects() {
+        let mut state = default_state(1);
+        let effects = state.tick_cache_cleanup();
+        assert!(effects.is_empty());
+    }
+
+    #[test]
+    fn tick_cache_cleanup_evicts_departed_peers() {
+        let (id, secret) = keypair(1);
+        let mut state = RuntimeState::new(
+            id,
+            secret,
+            RuntimeConfig {
+                topology_max_peers: 2,
+                departed_peer_ttl: std::time::Duration::from_secs(60),
+                ..Default::default()
+            },
+        );
+        let now = now_ms();
+        for (seed, status, last_seen) in [
+            (2, PeerStatus::Offline, now - 120_000),
+            (3, PeerStatus::Offline, now - 1_000),
+            (4, PeerStatus::Online, now),
+        ] {
+            state.handle_command(RuntimeCommand::UpsertPeer {
+                info: PeerInfo {
+                    node_id: node_id(seed),
+                    role: PeerRole::Peer,
+                    status,
+                    last_seen,
+                },
+            });
+        }
+        // Peer 4 pushed out peer 2 at capacity; nothing else has expired.
+        assert_eq!(state.topology().len(), 2);
+
+        let effects = state.tick_cache_cleanup();
+        let evicted: Vec<_> = effects
+            .iter()
+            .filter_map(|e| match e {
+                RuntimeEffect::Emit(ProtocolEvent::PeersEvicted { node_ids }) => Some(node_ids),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(evicted, vec![&vec![node_id(2)]]);
+        assert!(state.tick_cache_cleanup().is_empty());
+    }
+
+    #[test]
+    fn tick_tracker_cleanup_returns_no_effects() {
+        let mut state = default_state(1);
+        let effects = state.tick_tracker_cleanup();
+        assert!(effects.is_empty());
+    }
+
+    // ── Task 5 tests ─────────────────────────────────────────────────────
+
+    #[test]
+    fn tick_heartbeat_empty_state_no_effects() {
+        let mut state = default_state(1);
+        let effects = state.tick_heartbeat();
+        assert!(effects.is_empty());
+    }
+
+    #[test]
+    fn tick_heartbeat_peer_offline_emits_event() {
+        let mut state = default_state(1);
+        let peer = node_id(2);
+
+        // Register peer with a very old heartbeat so it goes offline
+        state.heartbeat.record_heartbeat_at(peer, 0);
+        state.topology.upsert(crate::relay::PeerInfo {
+            node_id: peer,
+            role: PeerRole::Peer,
+            status: PeerStatus::Online,
+            last_seen: 0,
+        });
+
+        let effects = state.tick_heartbeat();
+
+        // Should emit PeerOffline event
+        let has_offline = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::Emit(ProtocolEvent::PeerOffline { node_id }) if *node_id == peer)
+        });
+        assert!(has_offline, "expected PeerOffline event, got: {effects:?}");
+    }
+
+    #[test]
+    fn tick_heartbeat_peer_reconnect_emits_online() {
+        let mut state = default_state(1);
+        let peer = node_id(2);
+
+        // First, discover the peer so it's in the discovered set
+        state.heartbeat.record_heartbeat(peer);
+        state.topology.upsert(crate::relay::PeerInfo {
+            node_id: peer,
+            role: PeerRole::Peer,
+            status: PeerStatus::Online,
+            last_seen: 0,
+        });
+        let _ = state.tick_heartbeat(); // emits PeerDiscovered (first time)
+
+        // Now put peer in Offline status in topology, then give it a recent heartbeat
+        // so check_all sees it as alive → PeerOnline (reconnect).
+        state.topology.upsert(crate::relay::PeerInfo {
+            node_id: peer,
+            role: PeerRole::Peer,
+            status: PeerStatus::Offline,
+            last_seen: 0,
+        });
+        state.heartbeat.record_heartbeat(peer);
+
+        let effects = state.tick_heartbeat();
+
+        let has_online = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::Emit(ProtocolEvent::PeerOnline { node_id }) if *node_id == peer)
+        });
+        assert!(
+            has_online,
+            "expected PeerOnline event on reconnect, got: {effects:?}"
+        );
+    }
+
+    // ── Task 6 tests ─────────────────────────────────────────────────────
+
+    #[test]
+    fn tick_subnets_empty_state_no_effects() {
+        let mut state = default_state(1);
+        let effects = state.tick_subnets();
+        assert!(effects.is_empty());
+    }
+
+    #[test]
+    fn tick_roles_empty_state_no_effects() {
+        let mut state = default_state(1);
+        let effects = state.tick_roles();
+        assert!(effects.is_empty());
+    }
+
+    #[test]
+    fn tick_backup_empty_state_no_effects() {
+        let mut state = default_state(1);
+        let effects = state.tick_backup();
+        assert!(effects.is_empty());
+    }
+
+    #[test]
+    fn tick_group_hub_heartbeat_empty_state_no_effects() {
+        let mut state = default_state(1);
+        let effects = state.tick_group_hub_heartbeat();
+        assert!(effects.is_empty());
+    }
+
+    #[test]
+    fn build_gossip_announce_returns_bytes() {
+        let state = default_state(1);
+        let bytes = state.build_gossip_announce();
+        assert!(bytes.is_some(), "should produce announce bytes");
+
+        // Verify it's valid MsgPack PeerAnnounce
+        let bytes = bytes.unwrap();
+        let announce: PeerAnnounce =
+            rmp_serde::from_slice(&bytes).expect("should deserialize PeerAnnounce");
+        assert_eq!(announce.node_id, state.local_id);
+        assert_eq!(announce.username, "anonymous");
+        assert_eq!(announce.roles, vec![PeerRole::Peer]);
+    }
+
+    // ── Task 7 tests ─────────────────────────────────────────────────────
+
+    /// Build a signed chat envelope from sender (with known secret) to recipient.
+    fn make_signed_chat(
+        sender_seed: u8,
+        recipient_id: NodeId,
+        payload: &[u8],
+    ) -> (crate::envelope::Envelope, bool) {
+        let (sender_id, sender_secret) = keypair(sender_seed);
+        let env = crate::envelope::EnvelopeBuilder::new(
+            sender_id,
+            recipient_id,
+            MessageType::Chat,
+            payload.to_vec(),
+        )
+        .sign(&sender_secret);
+        let sig_valid = env.verify_signature().is_ok();
+        (env, sig_valid)
+    }
+
+    #[test]
+    fn handle_incoming_chat_delivers_and_acks() {
+        let mut state = default_state(1);
+        let (sender_id, _) = keypair(2);
+
+        let (env, sig_valid) = make_signed_chat(2, state.local_id, b"hello");
+
+        let effects = state.handle_incoming_chat(env, sig_valid);
+
+        // Should have DeliverMessage + SendEnvelope(ACK)
+        let has_deliver = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::DeliverMessage(msg) if msg.from == sender_id && msg.payload == b"hello")
+        });
+        let has_ack = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::Ack && env.to == sender_id)
+        });
+        assert!(has_deliver, "expected DeliverMessage, got: {effects:?}");
+        assert!(has_ack, "expected ACK SendEnvelope, got: {effects:?}");
+    }
+
+    #[test]
+    fn handle_incoming_chat_unwraps_typed_payload() {
+        let mut state = default_state(1);
+        let typed = TypedPayload::encode(&crate::payload::TextPayload { text: "hi".into() })
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+
+        let (env, sig_valid) = make_signed_chat(2, state.local_id, &typed);
+        let effects = state.handle_incoming_chat(env, sig_valid);
+
+        let delivered = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::DeliverMessage(msg) => Some(msg),
+                _ => None,
+            })
+            .expect("typed text should be delivered");
+        assert_eq!(delivered.content_type.as_deref(), Some("text/plain"));
+        let text: crate::payload::TextPayload = delivered.decode().unwrap();
+        assert_eq!(text.text, "hi");
+    }
+
+    #[test]
+    fn contact_card_received_then_imported() {
+        let mut state = default_state(1);
+        let (sender_id, _) = keypair(2);
+        let carol = node_id(3);
+        let card = ContactCard::new(carol, "carol".into());
+        let typed = TypedPayload::encode(&card).unwrap().to_bytes().unwrap();
+
+        let (env, sig_valid) = make_signed_chat(2, state.local_id, &typed);
+        let effects = state.handle_incoming_chat(env, sig_valid);
+        let received = effects.iter().find_map(|e| match e {
+            RuntimeEffect::Emit(ProtocolEvent::ContactCardReceived { from, group_id, card }) => {
+                Some((*from, group_id.clone(), card.clone()))
+            }
+            _ => None,
+        });
+        assert_eq!(received, Some((sender_id, None, card.clone())));
+        assert!(state.contacts().is_empty(), "not imported until asked");
+
+        state.handle_command(RuntimeCommand::ImportContact { card: card.clone() });
+        assert_eq!(state.contacts().get(&carol).unwrap().card, card);
+        assert_eq!(state.topology.get(&carol).unwrap().status, PeerStatus::Offline);
+
+        // Forged fingerprint and our own card are ignored
+        let mut forged = ContactCard::new(node_id(4), "mallory".into());
+        forged.node_id = node_id(5);
+        state.handle_command(RuntimeCommand::ImportContact { card: forged });
+        let own = ContactCard::new(state.local_id, "me".into());
+        state.handle_command(RuntimeCommand::ImportContact { card: own });
+        assert_eq!(state.contacts().len(), 1);
+    }
+
+    #[test]
+    fn handle_incoming_chat_rejects_unknown_content_type() {
+        let mut state = default_state(1);
+        let typed = TypedPayload {
+            content_type: "app/unknown".into(),
+            body: vec![0x90],
+        }
+        .to_bytes()
+        .unwrap();
+
+        let (env, sig_valid) = make_signed_chat(2, state.local_id, &typed);
+        let effects = state.handle_incoming_chat(env, sig_valid);
+
+        assert!(
+            !effects.iter().any(|e| matches!(e, RuntimeEffect::DeliverMessage(_))),
+            "unknown content type must not reach the app"
+        );
+        assert!(effects.iter().any(|e| matches!(e, RuntimeEffect::Emit(ProtocolEvent::MessageRejected { .. }))));
+        assert!(
+            effects.iter().any(|e| matches!(e, RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::Ack)),
+            "rejected payloads are still acknowledged"
+        );
+    }
+
+    #[test]
+    fn handle_incoming_chat_encrypted_decrypts() {
+        // Create state with encryption
+        let (local_id, local_secret) = keypair(1);
+        let mut state = RuntimeState::new(
+            local_id,
+            local_secret,
+            RuntimeConfig {
+                encryption: true,
+                ..Default::default()
+            },
+        );
+
+        let (sender_id, sender_secret) = keypair(2);
+        let plaintext = b"secret message";
+        let recipient_pk = local_id.as_bytes();
+
+        let env = crate::envelope::EnvelopeBuilder::new(
+            sender_id,
+            local_id,
+            MessageType::Chat,
+            plaintext.to_vec(),
+        )
+        .encrypt_and_sign(&sender_secret, &recipient_pk)
+        .expect("encrypt_and_sign");
+
+        let sig_valid = env.verify_signature().is_ok();
+        let effects = state.handle_incoming_chat(env, sig_valid);
+
+        // Find the delivered message
+        let delivered = effects.iter().find_map(|e| {
+            if let RuntimeEffect::DeliverMessage(msg) = e {
+                Some(msg)
+            } else {
+                None
+            }
+        });
+        assert!(delivered.is_some(), "expected DeliverMessage");
+        let msg = delivered.unwrap();
+        assert_eq!(msg.payload, plaintext);
+        assert!(msg.was_encrypted);
+        assert!(msg.signature_valid);
+    }
+
+    #[test]
+    fn handle_incoming_chat_forward_when_not_recipient() {
+        let mut state = default_state(1);
+        let (sender_id, sender_secret) = keypair(2);
+        let recipient_id = node_id(3);
+        state.topology.upsert(PeerInfo {
+            node_id: sender_id,
+            role: PeerRole::Peer,
+            status: PeerStatus::Online,
+            last_seen: now_ms(),
+        });
+
+        // Build envelope from sender to recipient, routed via our node
+        let env = crate::envelope::EnvelopeBuilder::new(
+            sender_id,
+            recipient_id,
+            MessageType::Chat,
+            b"relayed".to_vec(),
+        )
+        .via(vec![state.local_id])
+        .sign(&sender_secret);
+
+        let sig_valid = env.verify_signature().is_ok();
+        let effects = state.handle_incoming_chat(env, sig_valid);
+
+        // Should have SendEnvelopeTo for next_hop + SendEnvelopeTo for ACK + Forwarded event
+        let has_forward = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::SendEnvelopeTo { target, .. } if *target == recipient_id)
+        });
+        let has_relay_ack = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::SendEnvelopeTo { target, envelope } if *target == sender_id && envelope.msg_type == MessageType::Ack)
+        });
+        let has_forwarded_event = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::Emit(ProtocolEvent::Forwarded { next_hop, .. }) if *next_hop == recipient_id)
+        });
+        assert!(has_forward, "expected forward to recipient, got: {effects:?}");
+        assert!(has_relay_ack, "expected relay ACK to sender, got: {effects:?}");
+        assert!(
+            has_forwarded_event,
+            "expected Forwarded event, got: {effects:?}"
+        );
+    }
+
+    #[test]
+    fn handle_incoming_chat_dedup_drops() {
+        let mut state = default_state(1);
+        let (env, sig_valid) = make_signed_chat(2, state.local_id, b"once");
+        let env2 = env.clone();
+
+        let effects1 = state.handle_incoming_chat(env, sig_valid);
+        assert!(
+            !effects1.is_empty(),
+            "first delivery should produce effects"
+        );
+
+        let effects2 = state.handle_incoming_chat(env2, sig_valid);
+        assert!(
+            effects2.is_empty(),
+            "duplicate should be dropped, got: {effects2:?}"
+        );
+    }
+
+    // ── Task 8 tests ─────────────────────────────────────────────────────
+
+    #[test]
+    fn handle_incoming_parses_and_dispatches_chat() {
+        let mut state = default_state(1);
+        let (sender_id, sender_secret) = keypair(2);
+
+        let env = crate::envelope::EnvelopeBuilder::new(
+            sender_id,
+            state.local_id,
+            MessageType::Chat,
+            b"raw bytes test".to_vec(),
+        )
+        .sign(&sender_secret);
+        let raw = env.to_bytes().expect("serialize");
+
+        let effects = state.handle_incoming(raw.as_slice());
+
+        let has_deliver = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::DeliverMessage(msg) if msg.from == sender_id)
+        });
+        assert!(
+            has_deliver,
+            "should dispatch chat and deliver, got: {effects:?}"
+        );
+    }
+
+    #[test]
+    fn handle_incoming_auto_registers_unknown_peer() {
+        let mut state = default_state(1);
+        let (sender_id, sender_secret) = keypair(2);
+
+        // Verify peer is not in topology yet
+        assert!(state.topology.get(&sender_id).is_none());
+
+        let env = crate::envelope::EnvelopeBuilder::new(
+            sender_id,
+            state.local_id,
+            MessageType::Chat,
+            b"auto-register".to_vec(),
+        )
+        .sign(&sender_secret);
+        let raw = env.to_bytes().expect("serialize");
+
+        state.handle_incoming(raw.as_slice());
+
+        // Peer should now be in topology
+        let peer = state.topology.get(&sender_id);
+        assert!(peer.is_some(), "peer should be auto-registered in topology");
+        assert_eq!(peer.unwrap().status, PeerStatus::Online);
+    }
+
+    // ── Task 9 tests ─────────────────────────────────────────────────────
+
+    #[test]
+    fn redundant_send_uses_two_disjoint_relays_and_recipient_keeps_one() {
+        let mut state = default_state(1);
+        let recipient = node_id(2);
+        for seed in [10, 11] {
+            state.topology.upsert(PeerInfo {
+                node_id: node_id(seed),
+                role: PeerRole::Relay,
+                status: PeerStatus::Online,
+                last_seen: now_ms(),
+            });
+        }
+
+        let options = SendOptions {
+            redundant: true,
+            ..Default::default()
+        };
+        let effects = state.handle_send_message_with_options(recipient, b"urgent".to_vec(), options);
+        let [RuntimeEffect::SendWithBackupFallback { envelope: first, .. }, RuntimeEffect::SendEnvelope(second)] =
+            &effects[..]
+        else {
+            panic!("expected fallback send plus a redundant copy, got: {effects:?}");
+        };
+        assert_eq!(first.id, second.id);
+        assert_eq!(first.via.len(), 1);
+        assert_eq!(second.via.len(), 1);
+        assert_ne!(first.via, second.via, "paths must be disjoint");
+        assert!(first.verify_signature().is_ok());
+        assert!(second.verify_signature().is_ok());
+
+        // The recipient delivers whichever copy arrives first, drops the other
+        let mut router = Router::new(recipient);
+        assert!(matches!(router.route(second.clone()), RoutingAction::Deliver { .. }));
+        assert!(matches!(router.route(first.clone()), RoutingAction::Drop));
+    }
+
+    #[test]
+    fn handle_send_message_produces_fallback_effect() {
+        let mut state = default_state(1);
+        let recipient = node_id(2);
+
+        let effects = state.handle_send_message(recipient, b"hello".to_vec());
+
+        assert_eq!(effects.len(), 1, "expected exactly one effect");
+        assert!(
+            matches!(&effects[0], RuntimeEffect::SendWithBackupFallback { .. }),
+            "expected SendWithBackupFallback, got: {:?}",
+            effects[0]
+        );
+
+        // Verify on_success has StatusChange effects
+        if let RuntimeEffect::SendWithBackupFallback {
+            on_success,
+            on_failure,
+            envelope,
+            ..
+        } = &effects[0]
+        {
+            assert!(
+                !on_success.is_empty(),
+                "on_success should have status changes"
+            );
+            assert!(
+                !on_failure.is_empty(),
+                "on_failure should have backup + error effects"
+            );
+            assert!(envelope.is_signed(), "envelope should be signed");
+
+            // Both paths report the initial Pending tracking change
+            for effects in [on_success, on_failure] {
+                assert!(
+                    matches!(
+                        effects.first(),
+                        Some(RuntimeEffect::StatusChange(change))
+                            if change.current == MessageStatus::Pending
+                                && change.message_id == envelope.id
+                    ),
+                    "expected leading Pending status change, got: {effects:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn send_message_backup_ttl_bounded_by_policy() {
+        let (local_id, local_secret) = keypair(1);
+        let mut state = RuntimeState::new(
+            local_id,
+            local_secret,
+            RuntimeConfig {
+                backup_policy: crate::backup::BackupPolicy {
+                    default_ttl_ms: 60_000,
+                    max_ttl_ms: 3_600_000,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let ttl_of = |state: &RuntimeState, recipient: NodeId| {
+            let entry = &state.backup.store().get_for_recipient(&recipient)[0];
+            entry.expires_at - entry.stored_at
+        };
+
+        state.handle_command(RuntimeCommand::SendMessageWithTtl {
+            to: node_id(2),
+            payload: b"short-lived".to_vec(),
+            backup_ttl: std::time::Duration::from_secs(5),
+        });
+        assert_eq!(ttl_of(&state, node_id(2)), 5_000);
+
+        state.handle_command(RuntimeCommand::SendMessageWithTtl {
+            to: node_id(3),
+            payload: b"too long".to_vec(),
+            backup_ttl: std::time::Duration::from_secs(24 * 3600),
+        });
+        assert_eq!(ttl_of(&state, node_id(3)), 3_600_000);
+
+        state.handle_send_message(node_id(4), b"default".to_vec());
+        assert_eq!(ttl_of(&state, node_id(4)), 60_000);
+    }
+
+    #[test]
+    fn handle_send_message_encrypted_when_config_enabled() {
+        let (local_id, local_secret) = keypair(1);
+        let mut state = RuntimeState::new(
+            local_id,
+            local_secret,
+            RuntimeConfig {
+                encryption: true,
+                ..Default::default()
+            },
+        );
+        let recipient = node_id(2);
+
+        let effects = state.handle_send_message(recipient, b"encrypted".to_vec());
+
+        if let RuntimeEffect::SendWithBackupFallback { envelope, .. } = &effects[0] {
+            assert!(
+                envelope.encrypted,
+                "envelope should be encrypted when config.encryption is true"
+            );
+        } else {
+            panic!("expected SendWithBackupFallback");
+        }
+    }
+
+    #[test]
+    fn backup_is_sealed_to_recipient_even_without_transport_encryption() {
+        let (local_id, local_secret) = keypair(1);
+        let (recipient, recipient_secret) = keypair(2);
+        let mut state = RuntimeState::new(
+            local_id,
+            local_secret,
+            RuntimeConfig {
+                encryption: false,
+                ..Default::default()
+            },
+        );
+
+        let effects = state.handle_send_message(recipient, b"offline hello".to_vec());
+        let RuntimeEffect::SendWithBackupFallback { envelope, .. } = &effects[0] else {
+            panic!("expected SendWithBackupFallback");
+        };
+        assert!(!envelope.encrypted);
+
+        let stored = state.backup.store().get(&envelope.id).unwrap().payload.clone();
+        assert_ne!(stored, b"offline hello".to_vec());
+        let sealed = crate::crypto::EncryptedPayload::from_bytes(&stored).unwrap();
+        assert_eq!(
+            crate::crypto::decrypt(&sealed, &recipient_secret).unwrap(),
+            b"offline hello"
+        );
+
+        // Redelivery hands over the sealed bytes, never the plaintext.
+        let effects = state.prepare_backup_delivery(recipient);
+        let RuntimeEffect::SendWithBackupFallback { envelope, .. } = &effects[0] else {
+            panic!("expected SendWithBackupFallback");
+        };
+        assert_eq!(envelope.msg_type, MessageType::BackupDeliverBatch);
+        assert!(envelope.verify_signature().is_ok());
+        let batch: crate::backup::BackupDeliverBatchPayload =
+            rmp_serde::from_slice(&envelope.payload).unwrap();
+        assert_eq!(batch.entries[0].payload, stored);
+    }
+
+    #[test]
+    fn shutdown_hands_held_backups_to_another_holder() {
+        let (origin_id, origin_secret) = keypair(1);
+        let mut origin = RuntimeState::new(origin_id, origin_secret, RuntimeConfig::default());
+        let mut departing = default_state(3);
+        let mut successor = default_state(4);
+        let recipient = node_id(2);
+
+        let sealed = crate::crypto::encrypt(b"keep me", &recipient.as_bytes())
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        origin
+            .backup
+            .store_message("msg-1".into(), sealed, recipient, origin_id, now_ms(), None);
+        let replica = origin.backup.store().create_replication_payload("msg-1").unwrap();
+        departing.backup.handle_replication(&replica, origin_id, now_ms());
+        departing.topology.upsert(PeerInfo {
+            node_id: successor.local_id,
+            role: PeerRole::Peer,
+            status: PeerStatus::Online,
+            last_seen: now_ms(),
+        });
+
+        let (effects, report) = departing.prepare_shutdown();
+        assert_eq!(report.backups_handed_off, 1);
+        assert_eq!(report.backups_kept, 0);
+        let handoff = match &effects[..] {
+            [RuntimeEffect::SendEnvelope(env)] => env.clone(),
+            other => panic!("expected one handoff, got: {other:?}"),
+        };
+        assert_eq!(handoff.msg_type, MessageType::BackupHandoff);
+        assert_eq!(handoff.to, successor.local_id);
+
+        // The successor holds it and sends its receipt to the origin
+        let effects = successor.handle_incoming(&handoff.to_bytes().unwrap());
+        assert!(successor.backup.store().has("msg-1"));
+        let ack = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::BackupReplicateAck => {
+                    Some(env.clone())
+                }
+                _ => None,
+            })
+            .expect("successor should acknowledge");
+        assert_eq!(ack.to, origin_id);
+
+        origin.handle_incoming_backup(&ack);
+        assert!(origin
+            .backup
+            .store()
+            .get("msg-1")
+            .unwrap()
+            .replicated_to
+            .contains(&successor.local_id));
+    }
+
+    #[test]
+    fn backup_stats_and_quota_refusals_are_observable() {
+        let (local_id, local_secret) = keypair(1);
+        let mut state = RuntimeState::new(
+            local_id,
+            local_secret,
+            RuntimeConfig {
+                backup_policy: crate::backup::BackupPolicy {
+                    quota: crate::backup::BackupQuota {
+                        max_bytes: 1024,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let (relay, relay_secret) = keypair(3);
+        let recipient = node_id(2);
+        let replicate = |id: &str, text: &[u8]| {
+            let sealed = crate::crypto::encrypt(text, &recipient.as_bytes())
+                .unwrap()
+                .to_bytes()
+                .unwrap();
+            let replica = crate::backup::ReplicationPayload {
+                message_id: id.into(),
+                payload: sealed,
+                recipient_id: recipient,
+                sender_id: relay,
+                expires_at: now_ms() + 60_000,
+                viability_score: 50,
+                replicated_to: vec![],
+                msg_type: MessageType::Chat,
+                shard: None,
+            };
+            EnvelopeBuilder::new(relay, local_id, MessageType::BackupReplicate, rmp_serde::to_vec(&replica).unwrap())
+                .sign(&relay_secret)
+        };
+
+        state.handle_incoming_backup(&replicate("small", b"hello"));
+        let effects = state.handle_incoming_backup(&replicate("huge", &[7; 2048]));
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::StorageQuotaExceeded {
+                message_id,
+                limit: crate::backup::QuotaLimit::Store,
+                ..
+            }) if message_id == "huge")));
+
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        state.handle_command(RuntimeCommand::GetBackupStats { reply: tx });
+        let stats = rx.try_recv().unwrap();
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.per_recipient, vec![(recipient, 1)]);
+        assert_eq!(stats.quota.max_bytes, 1024);
+        // The relay that sent it counts as a holder
+        assert_eq!(stats.under_replicated, 1);
+    }
+
+    #[test]
+    fn unsealed_backup_replica_is_dropped() {
+        let mut state = default_state(1);
+        let local_id = state.local_id;
+        let (relay, relay_secret) = keypair(3);
+        let mut replica = crate::backup::ReplicationPayload {
+            message_id: "msg-plain".into(),
+            payload: b"plaintext".to_vec(),
+            recipient_id: node_id(2),
+            sender_id: node_id(4),
+            expires_at: now_ms() + 60_000,
+            viability_score: 100,
+            replicated_to: vec![],
+            msg_type: MessageType::Chat,
+            shard: None,
+        };
+        let build = |replica: &crate::backup::ReplicationPayload| {
+            EnvelopeBuilder::new(
+                relay,
+                local_id,
+                MessageType::BackupReplicate,
+                rmp_serde::to_vec(replica).unwrap(),
+            )
+            .sign(&relay_secret)
+        };
+
+        let env = build(&replica);
+        state.handle_incoming_backup(&env);
+        assert!(!state.backup.store().has("msg-plain"));
+
+        replica.message_id = "msg-sealed".into();
+        replica.payload = crate::crypto::encrypt(b"hi", &node_id(2).as_bytes())
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        let env = build(&replica);
+        let effects = state.handle_incoming_backup(&env);
+        assert!(state.backup.store().has("msg-sealed"));
+
+        // The holder ACKs so the origin counts it as a replica
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::SendEnvelope(ack)
+                if ack.msg_type == MessageType::BackupReplicateAck && ack.to == relay
+        )));
+    }
+
+    #[test]
+    fn replica_ack_carries_verifiable_storage_receipt() {
+        let (origin_id, origin_secret) = keypair(1);
+        let mut origin = RuntimeState::new(origin_id, origin_secret, RuntimeConfig::default());
+        let (holder_id, holder_secret) = keypair(3);
+        let mut holder = RuntimeState::new(holder_id, holder_secret, RuntimeConfig::default());
+        let sealed = crate::crypto::encrypt(b"hi", &node_id(2).as_bytes())
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        origin
+            .backup
+            .store_message("msg-1".into(), sealed, node_id(2), origin_id, now_ms(), None);
+
+        let replica = origin.backup.store().create_replication_payload("msg-1").unwrap();
+        let env = EnvelopeBuilder::new(
+            origin_id,
+            holder_id,
+            MessageType::BackupReplicate,
+            rmp_serde::to_vec(&replica).unwrap(),
+        )
+        .sign(&origin_secret);
+        let ack = holder
+            .handle_incoming_backup(&env)
+            .into_iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendEnvelope(ack) if ack.msg_type == MessageType::BackupReplicateAck => {
+                    Some(ack)
+                }
+                _ => None,
+            })
+            .expect("holder should acknowledge");
+
+        origin.handle_incoming_backup(&ack);
+        let receipts = origin.backup.receipts("msg-1");
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].holder_id, holder_id);
+        assert_eq!(receipts[0].expires_at, replica.expires_at);
+        assert!(origin.backup.store().get("msg-1").unwrap().replicated_to.contains(&holder_id));
+
+        // A receipt relayed by someone other than its holder doesn't count
+        let (mallory, mallory_secret) = keypair(4);
+        let relayed = EnvelopeBuilder::new(mallory, origin_id, MessageType::BackupReplicateAck, ack.payload)
+            .sign(&mallory_secret);
+        origin.handle_incoming_backup(&relayed);
+        assert!(!origin.backup.store().get("msg-1").unwrap().replicated_to.contains(&mallory));
+        assert_eq!(origin.backup.receipts("msg-1").len(), 1);
+    }
+
+    #[test]
+    fn sharded_backup_rebuilt_by_recipient() {
+        let erasure = crate::backup::ErasureConfig {
+            min_payload_bytes: 1024,
+            ..Default::default()
+        };
+        let (origin_id, origin_secret) = keypair(1);
+        let mut origin = RuntimeState::new(
+            origin_id,
+            origin_secret,
+            RuntimeConfig {
+                backup_policy: crate::backup::BackupPolicy {
+                    erasure: Some(erasure),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let no_dht = || RuntimeConfig {
+            enable_dht: false,
+            ..Default::default()
+        };
+        let (recipient_id, recipient_secret) = keypair(2);
+        let mut recipient = RuntimeState::new(recipient_id, recipient_secret, no_dht());
+        let mut holders: Vec<RuntimeState> = (10..15)
+            .map(|seed| {
+                let (id, secret) = keypair(seed);
+                RuntimeState::new(id, secret, no_dht())
+            })
+            .collect();
+
+        let text: Vec<u8> = (0..4000).map(|i| (i % 251) as u8).collect();
+        origin.handle_send_message(recipient_id, text.clone());
+        let message_id = origin.backup.store().message_ids().remove(0);
+        origin.backup.store_mut().update_viability(&message_id, 20);
+
+        let candidates: Vec<crate::backup::ReplicaCandidate> = holders
+            .iter()
+            .map(|h| crate::backup::ReplicaCandidate {
+                node_id: h.local_id,
+                factors: crate::backup::HostFactors {
+                    stability: 90,
+                    bandwidth: 90,
+                    contribution: 90,
+                },
+                is_relay: false,
+                is_storage: false,
+                online: true,
+                availability: 90,
+                cluster: None,
+            })
+            .collect();
+        let actions = origin.backup.plan_replication(&candidates, now_ms());
+        for effect in origin.backup_actions_to_effects(&actions) {
+            let RuntimeEffect::SendEnvelope(env) = effect else { continue };
+            let holder = holders.iter_mut().find(|h| h.local_id == env.to).unwrap();
+            holder.handle_incoming_backup(&env);
+        }
+        assert!(holders.iter().all(|h| h.backup.store().get(&message_id).unwrap().shard.is_some()));
+
+        // Any three holders seeing the recipient online are enough
+        let mut delivered = Vec::new();
+        for holder in holders.iter_mut().skip(2) {
+            for effect in holder.prepare_backup_delivery(recipient_id) {
+                let RuntimeEffect::SendWithBackupFallback { envelope, .. } = effect else {
+                    panic!("expected BackupDeliverBatch");
+                };
+                assert_eq!(envelope.msg_type, MessageType::BackupDeliverBatch);
+                delivered.extend(recipient.handle_incoming(&envelope.to_bytes().unwrap()));
+            }
+        }
+        let payloads: Vec<&Vec<u8>> = delivered
+            .iter()
+            .filter_map(|e| match e {
+                RuntimeEffect::DeliverMessage(msg) => Some(&msg.payload),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(payloads, vec![&text]);
+        assert!(delivered.iter().any(|e| matches!(e,
+            RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::BackupConfirmDelivery)));
+    }
+
+    #[test]
+    fn reconnect_delivers_held_messages_in_paced_batches() {
+        let (holder_id, holder_secret) = keypair(1);
+        let mut holder = RuntimeState::new(
+            holder_id,
+            holder_secret,
+            RuntimeConfig {
+                backup_policy: crate::backup::BackupPolicy {
+                    delivery: crate::backup::DeliveryPacing {
+                        max_batch_entries: 2,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let mut recipient = default_state(2);
+        let recipient_id = recipient.local_id;
+        let sender = node_id(3);
+        for i in 0..5u64 {
+            let text = format!("held {i}");
+            let sealed = crate::crypto::encrypt(text.as_bytes(), &recipient_id.as_bytes())
+                .unwrap()
+                .to_bytes()
+                .unwrap();
+            holder
+                .backup
+                .store_message(format!("msg-{i}"), sealed, recipient_id, sender, now_ms() - 10_000 + i, None);
+        }
+
+        let batch_of = |effects: Vec<RuntimeEffect>| -> Envelope {
+            match &effects[..] {
+                [RuntimeEffect::SendWithBackupFallback { envelope, on_success, .. }] => {
+                    assert_eq!(envelope.msg_type, MessageType::BackupDeliverBatch);
+                    assert!(on_success.iter().all(|e| matches!(e,
+                        RuntimeEffect::Emit(ProtocolEvent::BackupDelivered { .. }))));
+                    envelope.clone()
+                }
+                other => panic!("expected one batch, got: {other:?}"),
+            }
+        };
+
+        // Reconnect: only the first batch goes out right away
+        let first = batch_of(holder.prepare_backup_delivery(recipient_id));
+        let effects = recipient.handle_incoming(&first.to_bytes().unwrap());
+        let delivered: Vec<(NodeId, Vec<u8>)> = effects
+            .iter()
+            .filter_map(|e| match e {
+                RuntimeEffect::DeliverMessage(msg) => Some((msg.from, msg.payload.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            delivered,
+            vec![(sender, b"held 0".to_vec()), (sender, b"held 1".to_vec())]
+        );
+        // One confirmation covers the whole batch
+        let confirms: Vec<Vec<String>> = effects
+            .iter()
+            .filter_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::BackupConfirmDelivery => {
+                    rmp_serde::from_slice(&env.payload).ok()
+                }
+                _ => None,
+            })
+            .collect();
+        assert!(!confirms.is_empty());
+        assert!(confirms.iter().all(|ids| ids == &["msg-0", "msg-1"]));
+
+        // The rest follow one batch per delivery tick
+        let second = batch_of(holder.tick_backup_delivery());
+        let third = batch_of(holder.tick_backup_delivery());
+        assert!(holder.tick_backup_delivery().is_empty());
+        let mut texts = Vec::new();
+        for batch in [second, third] {
+            for effect in recipient.handle_incoming(&batch.to_bytes().unwrap()) {
+                if let RuntimeEffect::DeliverMessage(msg) = effect {
+                    texts.push(String::from_utf8(msg.payload).unwrap());
+                }
+            }
+        }
+        assert_eq!(texts, vec!["held 2", "held 3", "held 4"]);
+    }
+
+    #[test]
+    fn batch_entries_for_someone_else_are_ignored() {
+        let mut recipient = default_state(2);
+        let recipient_id = recipient.local_id;
+        let (holder, holder_secret) = keypair(1);
+        let other = node_id(4);
+        let sealed = crate::crypto::encrypt(b"not yours", &other.as_bytes())
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        let batch = crate::backup::BackupDeliverBatchPayload {
+            recipient_id,
+            entries: vec![crate::backup::ReplicationPayload {
+                message_id: "msg-x".into(),
+                payload: sealed,
+                recipient_id: other,
+                sender_id: node_id(3),
+                expires_at: now_ms() + 60_000,
+                viability_score: 50,
+                replicated_to: vec![],
+                msg_type: MessageType::Chat,
+                shard: None,
+            }],
+        };
+        let env = EnvelopeBuilder::new(
+            holder,
+            recipient_id,
+            MessageType::BackupDeliverBatch,
+            rmp_serde::to_vec(&batch).unwrap(),
+        )
+        .sign(&holder_secret);
+        // Nothing but the first-contact capability handshake
+        let effects = recipient.handle_incoming(&env.to_bytes().unwrap());
+        assert!(effects.iter().all(|e| matches!(
+            e,
+            RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::Capabilities
+        )));
+    }
+
+    #[test]
+    fn backup_query_answers_only_missing_ids() {
+        let mut holder = default_state(1);
+        let holder_id = holder.local_id;
+        let (querier, querier_secret) = keypair(3);
+        let alice = node_id(2);
+        let now = now_ms();
+        for id in ["msg-1", "msg-2"] {
+            holder.backup.store_message(id.into(), vec![], alice, querier, now, None);
+        }
+
+        let mut known = crate::backup::BloomFilter::new(1);
+        known.insert("msg-1");
+        let query = crate::backup::BackupQueryPayload {
+            recipient_id: alice,
+            known,
+        };
+        let env = EnvelopeBuilder::new(
+            querier,
+            holder_id,
+            MessageType::BackupQuery,
+            rmp_serde::to_vec(&query).unwrap(),
+        )
+        .sign(&querier_secret);
+
+        let effects = holder.handle_incoming_backup(&env);
+        let [RuntimeEffect::SendEnvelope(reply)] = effects.as_slice() else {
+            panic!("expected one reply, got {effects:?}");
+        };
+        assert_eq!(reply.msg_type, MessageType::BackupQueryResponse);
+        let response: crate::backup::BackupQueryResponsePayload =
+            rmp_serde::from_slice(&reply.payload).unwrap();
+        assert_eq!(response.recipient_id, alice);
+        assert_eq!(response.message_ids, vec!["msg-2".to_string()]);
+    }
+
+    #[test]
+    fn handle_command_add_peer_updates_topology() {
+        let mut state = default_state(1);
+        let peer = node_id(2);
+
+        assert!(state.topology.get(&peer).is_none());
+
+        let effects =
+            state.handle_command(RuntimeCommand::AddPeer { node_id: peer });
+
+        assert!(effects.is_empty(), "AddPeer returns no effects");
+        assert!(
+            state.topology.get(&peer).is_some(),
+            "peer should be in topology after AddPeer"
+        );
+    }
+
+    #[test]
+    fn handle_command_remove_peer_cleans_topology() {
+        let mut state = default_state(1);
+        let peer = node_id(2);
+
+        // Add peer first
+        state.handle_command(RuntimeCommand::AddPeer { node_id: peer });
+        assert!(state.topology.get(&peer).is_some());
+
+        // Remove peer
+        let effects =
+            state.handle_command(RuntimeCommand::RemovePeer { node_id: peer });
+
+        assert!(effects.is_empty(), "RemovePeer returns no effects");
+        assert!(
+            state.topology.get(&peer).is_none(),
+            "peer should be removed from topology"
+        );
+    }
+
+    // ── Task 10 tests ────────────────────────────────────────────────────
+
+    #[test]
+    fn handle_gossip_neighbor_up_registers_peer() {
+        let mut state = default_state(1);
+        let peer = node_id(2);
+
+        assert!(state.topology.get(&peer).is_none());
+
+        let effects =
+            state.handle_gossip_event(super::GossipInput::NeighborUp(peer));
+
+        let has_event = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::Emit(ProtocolEvent::GossipNeighborUp { node_id }) if *node_id == peer)
+        });
+        assert!(has_event, "expected GossipNeighborUp event, got: {effects:?}");
+
+        let topo_peer = state.topology.get(&peer);
+        assert!(
+            topo_peer.is_some(),
+            "peer should be registered in topology after NeighborUp"
+        );
+        assert_eq!(topo_peer.unwrap().status, PeerStatus::Online);
+    }
+
+    #[test]
+    fn handle_gossip_announce_registers_peer() {
+        let mut state = default_state(1);
+        let peer = node_id(2);
+
+        // Build a PeerAnnounce
+        let announce = PeerAnnounce::new(peer, "bob".to_string(), vec![PeerRole::Peer]);
+        let bytes = rmp_serde::to_vec(&announce).expect("serialize announce");
+
+        assert!(state.topology.get(&peer).is_none());
+
+        let effects =
+            state.handle_gossip_event(super::GossipInput::PeerAnnounce(bytes));
+
+        // PeerAnnounce no longer emits immediately — PeerDiscovered comes from tick_heartbeat
+        assert!(
+            effects.is_empty(),
+            "PeerAnnounce should not emit directly, got: {effects:?}"
+        );
+
+        let topo_peer = state.topology.get(&peer);
+        assert!(
+            topo_peer.is_some(),
+            "peer should be registered in topology after gossip announce"
+        );
+
+        // Verify PeerDiscovered emitted on next heartbeat tick
+        let tick_effects = state.tick_heartbeat();
+        let has_discovered = tick_effects.iter().any(|e| {
+            matches!(
+                e,
+                RuntimeEffect::Emit(ProtocolEvent::PeerDiscovered { node_id, username, source })
+                    if *node_id == peer && username == "bob" && *source == DiscoverySource::Announce
+            )
+        });
+        assert!(
+            has_discovered,
+            "expected PeerDiscovered from tick_heartbeat, got: {tick_effects:?}"
+        );
+    }
+
+    // ── Task 13: Integration tests ──────────────────────────────────────
+
+    #[test]
+    fn message_e2e_encrypt_decrypt_roundtrip() {
+        // Alice encrypts a chat message for Bob. Bob's RuntimeState handles
+        // it via handle_incoming(). Verify: plaintext recovered, was_encrypted,
+        // signature_valid.
+        let (alice_id, alice_secret) = keypair(10);
+        let (bob_id, bob_secret) = keypair(11);
+
+        let mut bob_state = RuntimeState::new(
+            bob_id,
+            bob_secret,
+            RuntimeConfig {
+                encryption: true,
+                ..Default::default()
+            },
+        );
+
+        let plaintext = b"Hello Bob, this is Alice!";
+        let bob_pk = bob_id.as_bytes();
+        let env = EnvelopeBuilder::new(
+            alice_id,
+            bob_id,
+            MessageType::Chat,
+            plaintext.to_vec(),
+        )
+        .encrypt_and_sign(&alice_secret, &bob_pk)
+        .expect("encrypt_and_sign should succeed");
+
+        // Sanity: envelope is encrypted and signed
+        assert!(env.encrypted);
+        assert!(env.is_signed());
+
+        let raw = env.to_bytes().expect("serialize");
+        let effects = bob_state.handle_incoming(&raw);
+
+        let delivered = effects.iter().find_map(|e| {
+            if let RuntimeEffect::DeliverMessage(msg) = e {
+                Some(msg)
+            } else {
+                None
+            }
+        });
+        assert!(delivered.is_some(), "expected DeliverMessage, got: {effects:?}");
+        let msg = delivered.unwrap();
+        assert_eq!(msg.payload, plaintext, "plaintext should match after decryption");
+        assert!(msg.was_encrypted, "message should report was_encrypted=true");
+        assert!(msg.signature_valid, "signature should be valid");
+        assert_eq!(msg.from, alice_id, "sender should be Alice");
+    }
+
+    #[test]
+    fn ack_updates_tracker_status() {
+        // Send a message, then simulate relay ACK and recipient ACK.
+        // Verify StatusChange effects progress through expected states.
+        let (alice_id, alice_secret) = keypair(20);
+        let (bob_id, bob_secret) = keypair(21);
+
+        let mut alice_state = RuntimeState::new(
+            alice_id,
+            alice_secret,
+            RuntimeConfig {
+                encryption: false,
+                ..Default::default()
+            },
+        );
+
+        // Send message from Alice to Bob
+        let send_effects = alice_state.handle_send_message(bob_id, b"hi bob".to_vec());
+        assert_eq!(send_effects.len(), 1);
+        let envelope = match &send_effects[0] {
+            RuntimeEffect::SendWithBackupFallback { envelope, on_success, .. } => {
+                // on_success should contain Pending and Sent status changes
+                let has_status = on_success.iter().any(|e| matches!(e, RuntimeEffect::StatusChange(_)));
+                assert!(has_status, "on_success should have StatusChange effects");
+                envelope.clone()
+            }
+            other => panic!("expected SendWithBackupFallback, got: {other:?}"),
+        };
+        let msg_id = envelope.id.clone();
+
+        // Simulate relay ACK (RelayForwarded)
+        use crate::router::{AckPayload, AckType};
+        let relay_id = node_id(22);
+        let relay_ack_payload = AckPayload {
+            original_message_id: msg_id.clone(),
+            ack_type: AckType::RelayForwarded,
+        };
+        let relay_ack_env = EnvelopeBuilder::new(
+            relay_id,
+            alice_id,
+            MessageType::Ack,
+            relay_ack_payload.to_bytes(),
+        )
+        .build();
+        // We use the relay_ack_env unsigned — that's fine, sig_valid=false
+        let relay_effects = alice_state.handle_incoming_chat(relay_ack_env, false);
+        let relay_status = relay_effects.iter().find_map(|e| {
+            if let RuntimeEffect::StatusChange(sc) = e { Some(sc) } else { None }
+        });
+        assert!(relay_status.is_some(), "relay ACK should produce StatusChange, got: {relay_effects:?}");
+        let sc = relay_status.unwrap();
+        assert_eq!(sc.current, crate::types::MessageStatus::Relayed);
+
+        // Simulate recipient ACK (RecipientReceived)
+        let recipient_ack_payload = AckPayload {
+            original_message_id: msg_id.clone(),
+            ack_type: AckType::RecipientReceived,
+        };
+        let recipient_ack_env = EnvelopeBuilder::new(
+            bob_id,
+            alice_id,
+            MessageType::Ack,
+            recipient_ack_payload.to_bytes(),
+        )
+        .sign(&bob_secret);
+        let sig_valid = recipient_ack_env.verify_signature().is_ok();
+        let recv_effects = alice_state.handle_incoming_chat(recipient_ack_env, sig_valid);
+        let recv_status = recv_effects.iter().find_map(|e| {
+            if let RuntimeEffect::StatusChange(sc) = e { Some(sc) } else { None }
+        });
+        assert!(recv_status.is_some(), "recipient ACK should produce StatusChange, got: {recv_effects:?}");
+        let sc = recv_status.unwrap();
+        assert_eq!(sc.current, crate::types::MessageStatus::Delivered);
+    }
+
+    #[test]
+    fn failing_delivery_escalates_backup_to_relays_near_recipient() {
+        let mut alice = default_state(1);
+        let alice_id = alice.local_id;
+        let (bob_id, bob_secret) = keypair(2);
+        let (home, fast, slow, far) = (node_id(3), node_id(4), node_id(5), node_id(6));
+        let upsert = |state: &mut RuntimeState, relay, role, last_seen| {
+            state.topology.upsert(PeerInfo {
+                node_id: relay,
+                role,
+                status: PeerStatus::Online,
+                last_seen,
+            });
+        };
+        upsert(&mut alice, home, PeerRole::Peer, 1_000);
+        upsert(&mut alice, fast, PeerRole::Relay, 4_000);
+
+        // Bob last reached us through `home`
+        let hello = EnvelopeBuilder::new(bob_id, alice_id, MessageType::Heartbeat, Vec::new())
+            .via(vec![home])
+            .sign(&bob_secret);
+        alice.handle_incoming(&hello.to_bytes().unwrap());
+        assert_eq!(alice.relay_metrics.last_relay(&bob_id), Some(home));
+
+        // Our message goes via `fast` (the only relay yet), whose forwarding
+        // ACK gives us its RTT
+        let effects = alice.handle_send_message(bob_id, b"are you there".to_vec());
+        let RuntimeEffect::SendWithBackupFallback { envelope, .. } = &effects[0] else {
+            panic!("expected SendWithBackupFallback");
+        };
+        assert_eq!(envelope.via, vec![fast]);
+        let message_id = envelope.id.clone();
+        use crate::router::{AckPayload, AckType};
+        let relay_ack = EnvelopeBuilder::new(
+            fast,
+            alice_id,
+            MessageType::Ack,
+            AckPayload {
+                original_message_id: message_id.clone(),
+                ack_type: AckType::RelayForwarded,
+            }
+            .to_bytes(),
+        )
+        .build();
+        alice.handle_incoming_chat(relay_ack, false);
+        assert!(alice.relay_metrics.rtt_ms(&fast).is_some());
+        upsert(&mut alice, slow, PeerRole::Relay, 3_000);
+        upsert(&mut alice, far, PeerRole::Relay, 2_000);
+        alice.relay_metrics.record_rtt(slow, 30_000);
+
+        let effects = alice.escalate_backup(&message_id, bob_id);
+        let mut targets: Vec<NodeId> = effects
+            .iter()
+            .filter_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::BackupReplicate => Some(env.to),
+                _ => None,
+            })
+            .collect();
+        targets.sort_by_key(|n| n.to_string());
+        let mut expected = vec![home, fast, slow];
+        expected.sort_by_key(|n| n.to_string());
+        assert_eq!(targets, expected);
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::DeliveryEscalated { to, relays, .. })
+                if *to == bob_id && relays.len() == 3)));
+
+        // Already in flight: escalating again sends nothing
+        assert!(alice.escalate_backup(&message_id, bob_id).is_empty());
+    }
+
+    #[test]
+    fn read_receipt_produces_status_read() {
+        // Track a message, then handle an incoming ReadReceipt envelope.
+        // Verify the StatusChange marks it as Read.
+        let (alice_id, alice_secret) = keypair(30);
+        let (bob_id, bob_secret) = keypair(31);
+
+        let mut alice_state = RuntimeState::new(
+            alice_id,
+            alice_secret,
+            RuntimeConfig {
+                encryption: false,
+                ..Default::default()
+            },
+        );
+
+        // Send a message to get an envelope_id and track it
+        let send_effects = alice_state.handle_send_message(bob_id, b"read me".to_vec());
+        let envelope = match &send_effects[0] {
+            RuntimeEffect::SendWithBackupFallback { envelope, .. } => envelope.clone(),
+            other => panic!("expected SendWithBackupFallback, got: {other:?}"),
+        };
+        let msg_id = envelope.id.clone();
+
+        // Advance to Delivered first (Read requires Delivered or earlier)
+        alice_state.tracker.mark_delivered(&msg_id);
+
+        // Build a ReadReceipt envelope from Bob
+        use crate::router::ReadReceiptPayload;
+        let rr_payload = ReadReceiptPayload {
+            original_message_id: msg_id.clone(),
+            read_at: crate::types::now_ms(),
+        };
+        let rr_env = EnvelopeBuilder::new(
+            bob_id,
+            alice_id,
+            MessageType::ReadReceipt,
+            rr_payload.to_bytes(),
+        )
+        .sign(&bob_secret);
+        let sig_valid = rr_env.verify_signature().is_ok();
+        let effects = alice_state.handle_incoming_chat(rr_env, sig_valid);
+
+        let read_status = effects.iter().find_map(|e| {
+            if let RuntimeEffect::StatusChange(sc) = e { Some(sc) } else { None }
+        });
+        assert!(read_status.is_some(), "ReadReceipt should produce StatusChange, got: {effects:?}");
+        let sc = read_status.unwrap();
+        assert_eq!(sc.current, crate::types::MessageStatus::Read);
+        assert_eq!(sc.message_id, msg_id);
+    }
+
+    #[test]
+    fn group_create_produces_send_effects() {
+        // Call handle_command with CreateGroup. Verify it produces
+        // SendEnvelope effects (the GroupCreate payload to the hub).
+        let mut state = default_state(40);
+        let hub_id = node_id(41);
+        let member1 = node_id(42);
+        let member2 = node_id(43);
+
+        let effects = state.handle_command(RuntimeCommand::CreateGroup {
+            name: "Test Group".to_string(),
+            hub_relay_id: hub_id,
+            initial_members: vec![member1, member2],
+            invite_only: false,
+        });
+
+        // Should produce at least one SendEnvelope (the GroupCreate to hub)
+        let send_envelopes: Vec<_> = effects.iter().filter(|e| {
+            matches!(e, RuntimeEffect::SendEnvelope(env) if env.to == hub_id && env.msg_type == MessageType::GroupCreate)
+        }).collect();
+        assert!(
+            !send_envelopes.is_empty(),
+            "CreateGroup should produce SendEnvelope to hub, got: {effects:?}"
+        );
+
+        // Verify the envelope is signed and directed to the hub
+        if let RuntimeEffect::SendEnvelope(env) = &send_envelopes[0] {
+            assert!(env.is_signed(), "group create envelope should be signed");
+            assert_eq!(env.to, hub_id);
+            assert_eq!(env.from, state.local_id);
+        }
+    }
+
+    #[test]
+    fn peer_add_then_remove_cleans_state() {
+        // Add a peer via AddPeer, verify topology and heartbeat.
+        // Remove via RemovePeer, verify both are cleaned.
+        let mut state = default_state(50);
+        let peer = node_id(51);
+
+        // Initially: not in topology or heartbeat
+        assert!(state.topology.get(&peer).is_none());
+        assert_eq!(state.heartbeat.liveness(&peer), crate::discovery::LivenessState::Departed);
+
+        // Add peer
+        state.handle_command(RuntimeCommand::AddPeer { node_id: peer });
+        assert!(state.topology.get(&peer).is_some(), "peer should be in topology after AddPeer");
+        assert_ne!(
+            state.heartbeat.liveness(&peer),
+            crate::discovery::LivenessState::Departed,
+            "peer should be tracked in heartbeat after AddPeer"
+        );
+
+        // Remove peer
+        state.handle_command(RuntimeCommand::RemovePeer { node_id: peer });
+        assert!(
+            state.topology.get(&peer).is_none(),
+            "peer should be removed from topology after RemovePeer"
+        );
+        assert_eq!(
+            state.heartbeat.liveness(&peer),
+            crate::discovery::LivenessState::Departed,
+            "peer should be untracked from heartbeat after RemovePeer"
+        );
+    }
+
+    #[test]
+    fn gossip_announce_carries_backup_preference() {
+        let (local_id, local_secret) = keypair(61);
+        let holder = node_id(62);
+        let sender = RuntimeState::new(
+            local_id,
+            local_secret,
+            RuntimeConfig {
+                backup_preference: crate::backup::BackupPreference::Allowlist(vec![holder]),
+                enable_dht: false,
+                ..Default::default()
+            },
+        );
+        let bytes = sender.build_gossip_announce().unwrap();
+
+        let mut state = default_state(1);
+        state.handle_gossip_event(super::GossipInput::PeerAnnounce(bytes));
+        assert!(state.backup.may_hold(&local_id, &holder));
+        assert!(!state.backup.may_hold(&local_id, &node_id(63)));
+
+        // Announces from before the field existed read as Open
+        let legacy = rmp_serde::to_vec(&(node_id(2), "bob", vec![PeerRole::Peer], None::<[u8; 32]>, now_ms()))
+            .unwrap();
+        let announce: PeerAnnounce = rmp_serde::from_slice(&legacy).unwrap();
+        assert_eq!(announce.backup, crate::backup::BackupPreference::Open);
     }
 
-    // ── Helper: backup actions → effects ─────────────────────────────────
+    #[test]
+    fn presence_change_is_announced_and_surfaced_once() {
+        let mut sender = default_state(64);
+        let sender_id = sender.local_id;
+        assert!(sender.set_presence(Presence::Online).is_empty());
+
+        let effects = sender.set_presence(Presence::Custom("in a meeting".into()));
+        let Some(RuntimeEffect::BroadcastAnnounce(bytes)) = effects.into_iter().next() else {
+            panic!("presence change should re-announce");
+        };
 
-    /// Convert BackupActions into RuntimeEffects.
-    fn backup_actions_to_effects(&self, actions: &[BackupAction]) -> Vec<RuntimeEffect> {
-        let mut effects = Vec::new();
-        for action in actions {
-            match action {
-                BackupAction::Replicate { target, payload } => {
-                    let bytes =
-                        rmp_serde::to_vec(payload).expect("backup replication serialization");
-                    let via = self.relay_selector.select_path(*target, &self.topology);
-                    let envelope = EnvelopeBuilder::new(
-                        self.local_id,
-                        *target,
-                        MessageType::BackupReplicate,
-                        bytes,
-                    )
-                    .via(via)
-                    .sign(&self.secret_seed);
-                    effects.push(RuntimeEffect::SendEnvelope(envelope));
-                }
-                BackupAction::ConfirmDelivery {
-                    message_ids,
-                    recipient_id: _,
-                } => {
-                    let bytes =
-                        rmp_serde::to_vec(message_ids).expect("backup confirm serialization");
-                    for peer in self.topology.peers() {
-                        if peer.node_id != self.local_id && peer.status == PeerStatus::Online {
-                            let envelope = EnvelopeBuilder::new(
-                                self.local_id,
-                                peer.node_id,
-                                MessageType::BackupConfirmDelivery,
-                                bytes.clone(),
-                            )
-                            .sign(&self.secret_seed);
-                            effects.push(RuntimeEffect::SendEnvelope(envelope));
-                        }
-                    }
-                }
-                BackupAction::QueryPending { recipient_id } => {
-                    let bytes =
-                        rmp_serde::to_vec(recipient_id).expect("backup query serialization");
-                    for peer in self.topology.peers() {
-                        if peer.node_id != self.local_id && peer.status == PeerStatus::Online {
-                            let envelope = EnvelopeBuilder::new(
-                                self.local_id,
-                                peer.node_id,
-                                MessageType::BackupQuery,
-                                bytes.clone(),
-                            )
-                            .sign(&self.secret_seed);
-                            effects.push(RuntimeEffect::SendEnvelope(envelope));
-                        }
+        let mut state = default_state(1);
+        let presence_events = |effects: &[RuntimeEffect]| {
+            effects
+                .iter()
+                .filter(|e| matches!(e, RuntimeEffect::Emit(ProtocolEvent::PeerPresenceChanged { .. })))
+                .count()
+        };
+        let effects = state.handle_gossip_event(super::GossipInput::PeerAnnounce(bytes.clone()));
+        assert_eq!(presence_events(&effects), 1);
+        assert_eq!(state.peer_presence(&sender_id), Presence::Custom("in a meeting".into()));
+
+        // Periodic re-announce with the same presence stays quiet
+        let effects = state.handle_gossip_event(super::GossipInput::PeerAnnounce(bytes));
+        assert_eq!(presence_events(&effects), 0);
+
+        // Back to online is a change too
+        sender.set_presence(Presence::Online);
+        let bytes = sender.build_gossip_announce().unwrap();
+        let effects = state.handle_gossip_event(super::GossipInput::PeerAnnounce(bytes));
+        assert_eq!(presence_events(&effects), 1);
+        assert_eq!(state.peer_presence(&sender_id), Presence::Online);
+    }
+
+    #[test]
+    fn typing_signal_is_direct_untracked_and_rate_limited() {
+        let mut alice = default_state(65);
+        let mut bob = default_state(66);
+        let bob_id = bob.local_id;
+
+        let effects = alice.send_typing(bob_id, true);
+        let [RuntimeEffect::SendEnvelope(envelope)] = effects.as_slice() else {
+            panic!("expected one direct envelope, got {effects:?}");
+        };
+        assert_eq!(envelope.msg_type, MessageType::Typing);
+        assert!(envelope.via.is_empty());
+        assert!(alice.tracker.is_empty());
+
+        // Keystrokes within the interval send nothing
+        assert!(alice.send_typing(bob_id, true).is_empty());
+
+        let effects = bob.handle_incoming(&envelope.to_bytes().unwrap());
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::PeerTyping { typing: true, .. })
+        )));
+        // A replayed start inside the interval is dropped
+        let effects = bob.handle_incoming(&envelope.to_bytes().unwrap());
+        assert!(!effects.iter().any(|e| matches!(e, RuntimeEffect::Emit(ProtocolEvent::PeerTyping { .. }))));
+
+        // Stop goes out once; a second stop is suppressed
+        let effects = alice.send_typing(bob_id, false);
+        let [RuntimeEffect::SendEnvelope(stop)] = effects.as_slice() else {
+            panic!("expected stop envelope");
+        };
+        assert!(alice.send_typing(bob_id, false).is_empty());
+        let effects = bob.handle_incoming(&stop.to_bytes().unwrap());
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::PeerTyping { typing: false, .. })
+        )));
+    }
+
+    #[test]
+    fn capabilities_are_exchanged_once_on_first_contact() {
+        let mut alice = default_state(71);
+        let mut bob = default_state(72);
+        let (alice_id, bob_id) = (alice.local_id, bob.local_id);
+        let handshakes = |effects: &[RuntimeEffect]| -> Vec<Vec<u8>> {
+            effects
+                .iter()
+                .filter_map(|e| match e {
+                    RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::Capabilities => {
+                        Some(env.to_bytes().unwrap())
                     }
-                }
-                BackupAction::Event(event) => {
-                    effects.extend(self.surface_backup_event(event));
-                }
-            }
-        }
-        effects
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Bob hears from Alice first and offers his; a second message doesn't
+        let typing = |state: &mut RuntimeState, on: bool| match state.send_typing(bob_id, on).as_slice() {
+            [RuntimeEffect::SendEnvelope(env)] => env.to_bytes().unwrap(),
+            other => panic!("expected typing envelope, got {other:?}"),
+        };
+        let offer = handshakes(&bob.handle_incoming(&typing(&mut alice, true)));
+        assert_eq!(offer.len(), 1);
+        assert!(handshakes(&bob.handle_incoming(&typing(&mut alice, false))).is_empty());
+
+        // Alice caches them and answers; the answer isn't answered
+        let answer = handshakes(&alice.handle_incoming(&offer[0]));
+        assert_eq!(answer.len(), 1);
+        assert_eq!(alice.peer_capabilities(&bob_id), Some(PeerCapabilities::local()));
+        assert!(handshakes(&bob.handle_incoming(&answer[0])).is_empty());
+        assert_eq!(bob.peer_capabilities(&alice_id), Some(PeerCapabilities::local()));
+
+        // A peer without datagrams gets no real-time frames
+        alice.topology.set_capabilities(
+            bob_id,
+            PeerCapabilities { protocol_version: 1, features: crate::capabilities::Features::empty() },
+        );
+        assert!(!alice
+            .send_realtime(bob_id, b"frame")
+            .iter()
+            .any(|e| matches!(e, RuntimeEffect::SendDatagram { .. })));
     }
 
-    // ── Helper: surface group event ──────────────────────────────────────
+    #[test]
+    fn conversation_encryption_is_negotiated_per_contact() {
+        let mut alice = default_state(68);
+        let mut bob = default_state(69);
+        let (alice_id, bob_id) = (alice.local_id, bob.local_id);
+        alice.handle_command(RuntimeCommand::ImportContact { card: ContactCard::new(bob_id, "bob".into()) });
+        bob.handle_command(RuntimeCommand::ImportContact { card: ContactCard::new(alice_id, "alice".into()) });
+        let handshake = |effects: &[RuntimeEffect]| -> Vec<u8> {
+            effects
+                .iter()
+                .find_map(|e| match e {
+                    RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::EncryptionHandshake => {
+                        Some(env.to_bytes().unwrap())
+                    }
+                    _ => None,
+                })
+                .expect("handshake envelope")
+        };
+        let changed = |effects: &[RuntimeEffect]| {
+            effects.iter().find_map(|e| match e {
+                RuntimeEffect::Emit(ProtocolEvent::ConversationEncryptionChanged { mode, .. }) => Some(*mode),
+                _ => None,
+            })
+        };
 
-    /// Map a GroupEvent to a ProtocolEvent wrapped in RuntimeEffect::Emit.
-    fn surface_group_event(&self, event: &GroupEvent) -> Vec<RuntimeEffect> {
-        let proto_event = match event {
-            GroupEvent::GroupCreated(info) => ProtocolEvent::GroupCreated {
-                group: info.clone(),
-            },
-            GroupEvent::InviteReceived(invite) => ProtocolEvent::GroupInviteReceived {
-                invite: invite.clone(),
-            },
-            GroupEvent::Joined {
-                group_id,
-                group_name,
-            } => ProtocolEvent::GroupJoined {
-                group_id: group_id.clone(),
-                group_name: group_name.clone(),
-            },
-            GroupEvent::MemberJoined { group_id, member } => ProtocolEvent::GroupMemberJoined {
-                group_id: group_id.clone(),
-                member: member.clone(),
-            },
-            GroupEvent::MemberLeft {
-                group_id,
-                node_id,
-                username,
-                reason,
-            } => ProtocolEvent::GroupMemberLeft {
-                group_id: group_id.clone(),
-                node_id: *node_id,
-                username: username.clone(),
-                reason: *reason,
-            },
-            GroupEvent::MemberRoleChanged {
-                group_id,
-                node_id,
-                new_role,
-            } => ProtocolEvent::GroupMemberRoleChanged {
-                group_id: group_id.clone(),
-                node_id: *node_id,
-                new_role: *new_role,
-            },
-            GroupEvent::MessageReceived(msg) => ProtocolEvent::GroupMessageReceived {
-                message: msg.clone(),
-            },
-            GroupEvent::HubMigrated {
-                group_id,
-                new_hub_id,
-            } => ProtocolEvent::GroupHubMigrated {
-                group_id: group_id.clone(),
-                new_hub_id: *new_hub_id,
-            },
-            GroupEvent::SecurityViolation {
-                group_id,
-                node_id,
-                reason,
-            } => ProtocolEvent::GroupSecurityViolation {
-                group_id: group_id.clone(),
-                node_id: *node_id,
-                reason: reason.clone(),
-            },
+        // Alice alone turning it off changes nothing once Bob answers
+        let effects = alice.handle_command(RuntimeCommand::SetConversationEncryption {
+            node_id: bob_id,
+            mode: Some(EncryptionMode::Plaintext),
+        });
+        assert_eq!(changed(&effects), Some(EncryptionMode::Plaintext));
+        let effects = bob.handle_incoming(&handshake(&effects));
+        assert_eq!(changed(&effects), None);
+        assert_eq!(bob.contacts.get(&alice_id).unwrap().peer_encryption, Some(EncryptionMode::Plaintext));
+        let effects = alice.handle_incoming(&handshake(&effects));
+        assert_eq!(changed(&effects), Some(EncryptionMode::Static));
+        assert!(alice.encrypts_for(bob_id));
+
+        // Bob agrees: plaintext both ways; no reply to a reply
+        let effects = bob.handle_command(RuntimeCommand::SetConversationEncryption {
+            node_id: alice_id,
+            mode: Some(EncryptionMode::Plaintext),
+        });
+        assert_eq!(changed(&effects), Some(EncryptionMode::Plaintext));
+        let effects = alice.handle_incoming(&handshake(&effects));
+        assert_eq!(changed(&effects), Some(EncryptionMode::Plaintext));
+        let effects = bob.handle_incoming(&handshake(&effects));
+        assert!(effects.iter().all(|e| !matches!(e, RuntimeEffect::SendEnvelope(_))));
+
+        let effects = alice.handle_send_message(bob_id, b"hi".to_vec());
+        let RuntimeEffect::SendWithBackupFallback { envelope, .. } = &effects[0] else {
+            panic!("expected SendWithBackupFallback");
         };
-        vec![RuntimeEffect::Emit(proto_event)]
+        assert!(!envelope.encrypted);
+        // Other peers keep the global setting
+        let effects = alice.handle_send_message(node_id(70), b"hi".to_vec());
+        let RuntimeEffect::SendWithBackupFallback { envelope, .. } = &effects[0] else {
+            panic!("expected SendWithBackupFallback");
+        };
+        assert!(envelope.encrypted);
     }
 
-    // ── Helper: surface backup event ─────────────────────────────────────
+    #[test]
+    fn realtime_frames_fit_a_datagram_and_check_the_sender() {
+        let mut alice = default_state(67);
+        let mut bob = default_state(68);
+        let (alice_id, bob_id) = (alice.local_id, bob.local_id);
+
+        let effects = alice.send_realtime(bob_id, &[7; MAX_REALTIME_PAYLOAD]);
+        let [RuntimeEffect::SendDatagram { to, bytes }] = effects.as_slice() else {
+            panic!("expected one datagram, got {effects:?}");
+        };
+        assert_eq!(*to, bob_id);
+        assert!(bytes.len() <= 1200, "{} bytes", bytes.len());
+        assert!(alice.tracker.is_empty());
+        assert!(alice.send_realtime(bob_id, &[0; MAX_REALTIME_PAYLOAD + 1]).is_empty());
+
+        // Only from the peer the transport authenticated, and never via streams
+        assert!(bob.handle_datagram(bob_id, bytes).is_empty());
+        assert!(bob.handle_incoming(bytes).iter().all(|e| !matches!(e, RuntimeEffect::Emit(ProtocolEvent::RealtimeReceived { .. }))));
+        let effects = bob.handle_datagram(alice_id, bytes);
+        assert!(matches!(
+            effects.as_slice(),
+            [RuntimeEffect::Emit(ProtocolEvent::RealtimeReceived { from, seq: 1, payload })]
+                if *from == alice_id && payload.len() == MAX_REALTIME_PAYLOAD
+        ));
+        assert_eq!(bob.realtime_stats(&alice_id).unwrap().received, 1);
+    }
 
-    /// Map a BackupEvent to a ProtocolEvent (only first 3 variants surface to app).
-    fn surface_backup_event(&self, event: &BackupEvent) -> Vec<RuntimeEffect> {
-        let proto_event = match event {
-            BackupEvent::MessageStored {
-                message_id,
-                recipient_id,
-            } => Some(ProtocolEvent::BackupStored {
-                message_id: message_id.clone(),
-                recipient_id: *recipient_id,
-            }),
-            BackupEvent::MessageDelivered {
-                message_id,
-                recipient_id,
-            } => Some(ProtocolEvent::BackupDelivered {
-                message_id: message_id.clone(),
-                recipient_id: *recipient_id,
-            }),
-            BackupEvent::MessageExpired {
-                message_id,
-                recipient_id,
-            } => Some(ProtocolEvent::BackupExpired {
-                message_id: message_id.clone(),
-                recipient_id: *recipient_id,
-            }),
-            // Internal events — don't surface to application
-            BackupEvent::ReplicationNeeded { .. }
-            | BackupEvent::SelfDeleteRecommended { .. }
-            | BackupEvent::MessageReplicated { .. } => None,
+    #[test]
+    fn username_claims_resolve_first_seen_and_report_conflicts() {
+        let named = |seed: u8, username: &str| {
+            let (id, secret) = keypair(seed);
+            RuntimeState::new(
+                id,
+                secret,
+                RuntimeConfig {
+                    username: username.into(),
+                    enable_dht: false,
+                    ..Default::default()
+                },
+            )
         };
-        proto_event
-            .into_iter()
-            .map(RuntimeEffect::Emit)
-            .collect()
+        let alice = named(67, "alice");
+        let impostor = named(68, "Alice");
+        let mut bob = named(69, "bob");
+
+        let effects = bob.handle_gossip_event(super::GossipInput::PeerAnnounce(
+            alice.build_gossip_announce().unwrap(),
+        ));
+        assert!(effects.is_empty());
+        let effects = bob.handle_gossip_event(super::GossipInput::PeerAnnounce(
+            impostor.build_gossip_announce().unwrap(),
+        ));
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::UsernameConflict { name, owner, claimant })
+                if name == "alice" && *owner == alice.local_id && *claimant == impostor.local_id
+        )));
+        assert_eq!(bob.resolve_username("ALICE"), vec![alice.local_id, impostor.local_id]);
+        assert_eq!(bob.resolve_username("bob"), vec![bob.local_id]);
+
+        // Claims relayed under someone else's announce are ignored
+        let mut relayed: PeerAnnounce = rmp_serde::from_slice(&alice.build_gossip_announce().unwrap()).unwrap();
+        relayed.node_id = node_id(70);
+        relayed.username = "carol".into();
+        let mut dave = named(71, "dave");
+        dave.handle_gossip_event(super::GossipInput::PeerAnnounce(rmp_serde::to_vec(&relayed).unwrap()));
+        assert!(dave.resolve_username("alice").is_empty());
     }
-}
 
-// ── Tests ────────────────────────────────────────────────────────────────
+    #[test]
+    fn pex_shares_verified_peers_and_receivers_learn_hints() {
+        let with_pex = |seed: u8, enable_pex: bool| {
+            let (id, secret) = keypair(seed);
+            RuntimeState::new(
+                id,
+                secret,
+                RuntimeConfig {
+                    enable_pex,
+                    enable_dht: false,
+                    ..Default::default()
+                },
+            )
+        };
+        let mut alice = with_pex(72, true);
+        let carol = with_pex(73, true);
+        let carol_id = carol.local_id;
+
+        // Alice hears carol's announce directly: carol is verified for her
+        alice.handle_gossip_event(super::GossipInput::PeerAnnounce(
+            carol.build_gossip_announce().unwrap(),
+        ));
+        let bytes = alice.build_gossip_announce().unwrap();
+        let announce: PeerAnnounce = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(announce.peers.len(), 1);
+        assert_eq!(announce.peers[0].node_id, carol_id);
+
+        let mut bob = with_pex(74, true);
+        bob.handle_gossip_event(super::GossipInput::PeerAnnounce(bytes.clone()));
+        assert_eq!(bob.topology.get(&carol_id).unwrap().status, PeerStatus::Offline);
+
+        // PEX off: lists are neither sent nor trusted
+        let mut dave = with_pex(75, false);
+        dave.handle_gossip_event(super::GossipInput::PeerAnnounce(bytes));
+        assert!(dave.topology.get(&carol_id).is_none());
+        dave.handle_gossip_event(super::GossipInput::PeerAnnounce(
+            carol.build_gossip_announce().unwrap(),
+        ));
+        let announce: PeerAnnounce =
+            rmp_serde::from_slice(&dave.build_gossip_announce().unwrap()).unwrap();
+        assert!(announce.peers.is_empty());
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use super::super::RuntimeConfig;
-    use crate::relay::PeerStatus;
+    #[test]
+    fn messages_to_subnet_members_go_direct() {
+        let mut alice = default_state(67);
+        let (bob_id, carol_id, relay) = (node_id(68), node_id(69), node_id(70));
+        for (peer, role) in [
+            (bob_id, PeerRole::Peer),
+            (carol_id, PeerRole::Peer),
+            (relay, PeerRole::Relay),
+        ] {
+            alice.topology.upsert(PeerInfo {
+                node_id: peer,
+                role,
+                status: PeerStatus::Online,
+                last_seen: now_ms(),
+            });
+        }
 
-    fn node_id(seed: u8) -> NodeId {
-        use rand::SeedableRng;
-        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
-        let secret = tom_connect::SecretKey::generate(&mut rng);
-        secret.public().to_string().parse().unwrap()
+        let via_of = |effects: &[RuntimeEffect]| match &effects[0] {
+            RuntimeEffect::SendWithBackupFallback { envelope, .. } => envelope.via.clone(),
+            other => panic!("expected SendWithBackupFallback, got {other:?}"),
+        };
+        assert_eq!(via_of(&alice.handle_send_message(bob_id, b"hi".to_vec())), vec![relay]);
+
+        // A busy three-way conversation forms a subnet
+        let now = now_ms();
+        for _ in 0..5 {
+            alice.subnets.record_communication(alice.local_id, bob_id, now);
+            alice.subnets.record_communication(alice.local_id, carol_id, now);
+            alice.subnets.record_communication(bob_id, carol_id, now);
+        }
+        alice.tick_subnets();
+
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        alice.handle_command(RuntimeCommand::GetSubnets { reply: tx });
+        let subnets = rx.try_recv().unwrap();
+        assert_eq!(subnets.len(), 1);
+        assert!(subnets[0].members.contains(&alice.local_id));
+
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        alice.handle_command(RuntimeCommand::GetSubnetStats { reply: tx });
+        let stats = rx.try_recv().unwrap();
+        assert_eq!((stats.active, stats.formed), (1, 1));
+
+        assert!(via_of(&alice.handle_send_message(bob_id, b"hi again".to_vec())).is_empty());
     }
 
-    fn keypair(seed: u8) -> (NodeId, [u8; 32]) {
-        use rand::SeedableRng;
-        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
-        let secret = tom_connect::SecretKey::generate(&mut rng);
-        let node_id: NodeId = secret.public().to_string().parse().unwrap();
-        let seed_bytes = secret.to_bytes();
-        (node_id, seed_bytes)
+    #[test]
+    fn only_authenticated_traffic_proves_liveness() {
+        let mut state = default_state(71);
+        let (peer, peer_secret) = keypair(72);
+        let impostor = node_id(73);
+
+        // Unsigned envelope claiming to come from `impostor`
+        let forged = EnvelopeBuilder::new(impostor, state.local_id, MessageType::Heartbeat, Vec::new()).build();
+        state.handle_incoming(&forged.to_bytes().unwrap());
+        assert_eq!(state.heartbeat.liveness(&impostor), crate::discovery::LivenessState::Departed);
+
+        let signed = EnvelopeBuilder::new(peer, state.local_id, MessageType::Heartbeat, Vec::new())
+            .sign(&peer_secret);
+        state.handle_incoming(&signed.to_bytes().unwrap());
+        assert_eq!(state.heartbeat.liveness(&peer), crate::discovery::LivenessState::Alive);
+    }
+
+    #[test]
+    fn liveness_queries_combine_heartbeat_topology_and_path() {
+        let mut state = default_state(74);
+        let peer = node_id(75);
+        assert!(state.peer_liveness(&peer).is_none());
+
+        state.handle_command(RuntimeCommand::AddPeer { node_id: peer });
+        state.tick_heartbeat();
+        state.handle_path_event(tom_transport::PathEvent {
+            kind: tom_transport::PathKind::Direct,
+            rtt: std::time::Duration::from_millis(12),
+            remote: peer,
+            timestamp: std::time::Instant::now(),
+        });
+
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        state.handle_command(RuntimeCommand::GetPeerLiveness { node_id: peer, reply: tx });
+        let liveness = rx.try_recv().unwrap().expect("known peer");
+        assert_eq!(liveness.liveness, crate::discovery::LivenessState::Alive);
+        assert!(liveness.last_seen.is_some());
+        assert_eq!(liveness.source, Some(DiscoverySource::Direct));
+        assert_eq!(liveness.role, Some(PeerRole::Peer));
+        assert_eq!(liveness.path, tom_transport::PathKind::Direct);
+        assert_eq!(liveness.rtt_ms, Some(12));
+
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        state.handle_command(RuntimeCommand::GetAllPeers { reply: tx });
+        assert_eq!(rx.try_recv().unwrap(), vec![liveness]);
+
+        state.handle_command(RuntimeCommand::RemovePeer { node_id: peer });
+        assert!(state.peer_liveness(&peer).is_none());
     }
 
-    fn default_state(seed: u8) -> RuntimeState {
-        let (id, secret) = keypair(seed);
-        RuntimeState::new(id, secret, RuntimeConfig::default())
-    }
+    #[test]
+    fn extra_roles_are_announced_and_used_by_peers() {
+        let mut alice = default_state(80);
+        let mut bob = default_state(81);
+        let carol = node_id(82);
+        for _ in 0..5 {
+            alice.role_manager.record_join_assist(alice.local_id, now_ms());
+        }
 
-    // ── Task 4 tests ─────────────────────────────────────────────────────
+        let effects = alice.tick_roles();
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::LocalRolesChanged { roles })
+                if roles == &vec![PeerRole::Peer, PeerRole::Bootstrap]
+        )));
+        let announce = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::BroadcastAnnounce(bytes) => Some(bytes.clone()),
+                _ => None,
+            })
+            .expect("re-announced");
 
-    #[test]
-    fn tick_cache_cleanup_returns_no_effects() {
-        let mut state = default_state(1);
-        let effects = state.tick_cache_cleanup();
-        assert!(effects.is_empty());
+        // Bob learns alice is a Bootstrap node and joins topics through her first
+        bob.topology.upsert(PeerInfo {
+            node_id: carol,
+            role: PeerRole::Peer,
+            status: PeerStatus::Online,
+            last_seen: now_ms() + 1_000,
+        });
+        bob.handle_gossip_event(GossipInput::PeerAnnounce(announce));
+        assert!(bob.topology.has_role(&alice.local_id, PeerRole::Bootstrap));
+        assert_eq!(bob.topology.get(&alice.local_id).unwrap().role, PeerRole::Peer);
+        assert_eq!(bob.gossip_entry_points(), vec![alice.local_id, carol]);
+
+        // Extra roles can't be pushed through role change announces
+        let (remote, remote_seed) = keypair(83);
+        let bogus = crate::discovery::RoleChangeAnnounce::new(
+            remote, PeerRole::Storage, 50.0, now_ms(), &remote_seed,
+        );
+        assert!(bob.handle_role_announce(bogus).is_empty());
+        assert!(bob.topology.get(&remote).is_none());
     }
 
     #[test]
-    fn tick_tracker_cleanup_returns_no_effects() {
+    fn relay_attestations_flush_and_credit_by_attester_standing() {
         let mut state = default_state(1);
-        let effects = state.tick_tracker_cleanup();
-        assert!(effects.is_empty());
-    }
+        let relay = node_id(2);
 
-    // ── Task 5 tests ─────────────────────────────────────────────────────
+        // Forwards we saw are attested once per interval
+        state.relay_forwards.insert(relay, 3);
+        let effects = state.tick_roles();
+        let attestation = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::BroadcastRelayAttestation(a) => Some(a.clone()),
+                _ => None,
+            })
+            .expect("attestation broadcast");
+        assert_eq!(attestation.relays, vec![(relay, 3)]);
+        assert!(attestation.verify_signature());
+        state.relay_forwards.insert(relay, 1);
+        assert!(!state
+            .tick_roles()
+            .iter()
+            .any(|e| matches!(e, RuntimeEffect::BroadcastRelayAttestation(_))));
+
+        // A receiver credits the relay only if the attester has standing
+        let mut receiver = default_state(3);
+        let (attester, attester_seed) = keypair(4);
+        let sighted = crate::discovery::RelayAttestation::new(
+            attester,
+            vec![(relay, 10)],
+            now_ms(),
+            &attester_seed,
+        );
+        let bytes = rmp_serde::to_vec(&sighted).unwrap();
+        receiver.handle_gossip_event(GossipInput::PeerAnnounce(bytes.clone()));
+        assert_eq!(receiver.role_manager.score(&relay, now_ms()), 0.0);
 
-    #[test]
-    fn tick_heartbeat_empty_state_no_effects() {
-        let mut state = default_state(1);
-        let effects = state.tick_heartbeat();
-        assert!(effects.is_empty());
+        for _ in 0..20 {
+            receiver.role_manager.record_relay(attester, now_ms());
+        }
+        receiver.attestations.clear(); // next interval
+        receiver.handle_gossip_event(GossipInput::PeerAnnounce(bytes));
+        assert!(receiver.role_manager.score(&relay, now_ms()) > 0.0);
     }
 
     #[test]
-    fn tick_heartbeat_peer_offline_emits_event() {
+    fn handle_incoming_chat_refuses_relay_for_unknown_peer() {
         let mut state = default_state(1);
-        let peer = node_id(2);
+        let (stranger, stranger_secret) = keypair(2);
+        let env = crate::envelope::EnvelopeBuilder::new(
+            stranger,
+            node_id(3),
+            MessageType::Chat,
+            b"free ride".to_vec(),
+        )
+        .via(vec![state.local_id])
+        .sign(&stranger_secret);
 
-        // Register peer with a very old heartbeat so it goes offline
-        state.heartbeat.record_heartbeat_at(peer, 0);
-        state.topology.upsert(crate::relay::PeerInfo {
-            node_id: peer,
-            role: PeerRole::Peer,
-            status: PeerStatus::Online,
-            last_seen: 0,
-        });
+        let effects = state.handle_incoming_chat(env.clone(), true);
+        assert!(matches!(
+            &effects[..],
+            [RuntimeEffect::Emit(ProtocolEvent::MessageRejected { reason })] if reason.contains("unknown peer")
+        ));
+        assert_eq!(state.router.relays_refused(), 1);
 
-        let effects = state.tick_heartbeat();
+        // A verified contact is trusted even before we've seen it online
+        state.contacts.add(crate::contact::ContactCard::new(stranger, "stranger".into())).unwrap();
+        state
+            .contacts
+            .set_trust(&stranger, crate::contact::TrustLevel::Verified)
+            .unwrap();
+        state.router.set_relay_policy(crate::router::RelayPolicy::RelaysAndTrusted);
+        let effects = state.handle_incoming_chat(env, true);
+        assert!(effects.iter().any(|e| matches!(e, RuntimeEffect::Emit(ProtocolEvent::Forwarded { .. }))));
+    }
 
-        // Should emit PeerOffline event
-        let has_offline = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::Emit(ProtocolEvent::PeerOffline { node_id }) if *node_id == peer)
+    #[test]
+    fn topology_snapshot_via_command_shows_peers_and_paths() {
+        let mut alice = default_state(1);
+        let (bob_id, bob_secret) = keypair(2);
+        let relay = node_id(3);
+        alice.topology.upsert(PeerInfo {
+            node_id: relay,
+            role: PeerRole::Relay,
+            status: PeerStatus::Online,
+            last_seen: now_ms(),
         });
-        assert!(has_offline, "expected PeerOffline event, got: {effects:?}");
+        let hello = EnvelopeBuilder::new(bob_id, alice.local_id, MessageType::Heartbeat, Vec::new())
+            .via(vec![relay])
+            .sign(&bob_secret);
+        alice.handle_incoming(&hello.to_bytes().unwrap());
+
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        assert!(alice.handle_command(RuntimeCommand::GetTopologySnapshot { reply: tx }).is_empty());
+        let snapshot = rx.try_recv().unwrap();
+        assert_eq!(snapshot.observer, Some(alice.local_id));
+        assert_eq!(snapshot.peer(&relay).unwrap().role, PeerRole::Relay);
+        assert_eq!(snapshot.peer(&bob_id).unwrap().status, PeerStatus::Online);
+        assert_eq!(snapshot.paths, vec![crate::relay::ObservedPath { peer: bob_id, relay }]);
     }
 
     #[test]
-    fn tick_heartbeat_peer_reconnect_emits_online() {
+    fn routing_overrides_steer_sends_and_refuse_relaying() {
+        use crate::relay::RoutingOverride;
+
         let mut state = default_state(1);
-        let peer = node_id(2);
+        let (bob, bob_secret) = keypair(2);
+        let (relay, pinned) = (node_id(3), node_id(4));
+        for node_id in [bob, relay] {
+            state.topology.upsert(PeerInfo {
+                node_id,
+                role: if node_id == relay { PeerRole::Relay } else { PeerRole::Peer },
+                status: PeerStatus::Online,
+                last_seen: now_ms(),
+            });
+        }
+        let sent_via = |effects: &[RuntimeEffect]| match effects {
+            [RuntimeEffect::SendWithBackupFallback { envelope, .. }] => envelope.via.clone(),
+            other => panic!("expected SendWithBackupFallback, got: {other:?}"),
+        };
+        assert_eq!(sent_via(&state.handle_send_message(bob, b"1".to_vec())), vec![relay]);
 
-        // First, discover the peer so it's in the discovered set
-        state.heartbeat.record_heartbeat(peer);
-        state.topology.upsert(crate::relay::PeerInfo {
-            node_id: peer,
-            role: PeerRole::Peer,
-            status: PeerStatus::Online,
-            last_seen: 0,
+        state.handle_command(RuntimeCommand::SetRoutingOverride {
+            node_id: bob,
+            policy: Some(RoutingOverride::AlwaysDirect),
         });
-        let _ = state.tick_heartbeat(); // emits PeerDiscovered (first time)
+        assert!(sent_via(&state.handle_send_message(bob, b"2".to_vec())).is_empty());
 
-        // Now put peer in Offline status in topology, then give it a recent heartbeat
-        // so check_all sees it as alive → PeerOnline (reconnect).
-        state.topology.upsert(crate::relay::PeerInfo {
-            node_id: peer,
-            role: PeerRole::Peer,
-            status: PeerStatus::Offline,
-            last_seen: 0,
+        state.handle_command(RuntimeCommand::SetRoutingOverride {
+            node_id: bob,
+            policy: Some(RoutingOverride::AlwaysVia(pinned)),
         });
-        state.heartbeat.record_heartbeat(peer);
+        assert_eq!(sent_via(&state.handle_send_message(bob, b"3".to_vec())), vec![pinned]);
 
-        let effects = state.tick_heartbeat();
+        // Never relaying for Bob: his traffic through us is refused
+        state.handle_command(RuntimeCommand::SetRoutingOverride {
+            node_id: bob,
+            policy: Some(RoutingOverride::NeverRelayFor),
+        });
+        let env = EnvelopeBuilder::new(bob, node_id(5), MessageType::Chat, b"relay me".to_vec())
+            .via(vec![state.local_id])
+            .sign(&bob_secret);
+        let effects = state.handle_incoming_chat(env.clone(), true);
+        assert!(matches!(
+            &effects[..],
+            [RuntimeEffect::Emit(ProtocolEvent::MessageRejected { reason })] if reason.contains("never relaying")
+        ));
+
+        state.handle_command(RuntimeCommand::SetRoutingOverride { node_id: bob, policy: None });
+        let effects = state.handle_incoming_chat(env, true);
+        assert!(effects.iter().any(|e| matches!(e, RuntimeEffect::Emit(ProtocolEvent::Forwarded { .. }))));
+    }
 
-        let has_online = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::Emit(ProtocolEvent::PeerOnline { node_id }) if *node_id == peer)
+    #[test]
+    fn custom_state_store_reloads_and_refuses_foreign_identity() {
+        let (id, secret) = keypair(1);
+        let with_store = |store: Box<dyn StateStore>| RuntimeConfig {
+            state_store: Some(store),
+            ..Default::default()
+        };
+        let mut state = RuntimeState::new(
+            id,
+            secret,
+            with_store(Box::new(SqliteStateStore::open_memory().unwrap())),
+        );
+        state.handle_command(RuntimeCommand::UpsertPeer {
+            info: PeerInfo {
+                node_id: node_id(2),
+                role: PeerRole::Relay,
+                status: PeerStatus::Online,
+                last_seen: now_ms(),
+            },
         });
-        assert!(
-            has_online,
-            "expected PeerOnline event on reconnect, got: {effects:?}"
+        state.save_state();
+        let store = state.store.take().unwrap();
+        assert_eq!(store.load_identity().unwrap(), Some(id));
+
+        let mut restored = RuntimeState::new(id, secret, with_store(store));
+        assert_eq!(restored.topology().get(&node_id(2)).unwrap().role, PeerRole::Relay);
+
+        let (other, other_secret) = keypair(9);
+        let foreign = RuntimeState::new(other, other_secret, with_store(restored.store.take().unwrap()));
+        assert!(foreign.store.is_none(), "another node's state is never used");
+        assert!(foreign.topology().get(&node_id(2)).is_none());
+    }
+
+    #[test]
+    fn trace_route_records_every_hop_and_returns_to_sender() {
+        let mut alice = default_state(1);
+        let mut relay = default_state(2);
+        let mut bob = default_state(3);
+        let (alice_id, relay_id, bob_id) = (alice.local_id, relay.local_id, bob.local_id);
+
+        let (trace_id, effects) = alice.trace_route(bob_id);
+        let trace_id = trace_id.unwrap();
+        let mut outbound = match &effects[..] {
+            [RuntimeEffect::SendEnvelope(env)] => env.clone(),
+            other => panic!("expected one trace envelope, got: {other:?}"),
+        };
+        outbound.via = vec![relay_id];
+
+        let forward = |effects: Vec<RuntimeEffect>, to: NodeId| -> Envelope {
+            effects
+                .into_iter()
+                .find_map(|e| match e {
+                    RuntimeEffect::SendEnvelopeTo { target, envelope } if target == to => Some(envelope),
+                    RuntimeEffect::SendEnvelope(envelope) if envelope.via.first() == Some(&relay_id) => {
+                        Some(envelope)
+                    }
+                    _ => None,
+                })
+                .expect("trace should move on")
+        };
+
+        let at_bob = forward(relay.handle_incoming(&outbound.to_bytes().unwrap()), bob_id);
+        let reply = forward(bob.handle_incoming(&at_bob.to_bytes().unwrap()), relay_id);
+        assert!(reply.verify_signature().is_ok(), "reply is signed by the recipient");
+        let back = forward(relay.handle_incoming(&reply.to_bytes().unwrap()), alice_id);
+
+        let effects = alice.handle_incoming(&back.to_bytes().unwrap());
+        let hops = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::Emit(ProtocolEvent::RouteTraced { trace_id: id, target, hops })
+                    if *id == trace_id && *target == bob_id =>
+                {
+                    Some(hops.clone())
+                }
+                _ => None,
+            })
+            .expect("expected RouteTraced");
+        let path: Vec<_> = hops.iter().map(|h| (h.node_id, h.kind)).collect();
+        assert_eq!(
+            path,
+            vec![
+                (alice_id, TraceHopKind::Origin),
+                (relay_id, TraceHopKind::Relay),
+                (bob_id, TraceHopKind::Destination),
+            ]
         );
+
+        // A replayed reply is ignored
+        assert!(alice.handle_incoming(&back.to_bytes().unwrap()).is_empty());
     }
 
-    // ── Task 6 tests ─────────────────────────────────────────────────────
+    #[test]
+    fn forged_trace_is_not_relayed() {
+        let mut relay = default_state(2);
+        let (alice_id, _) = keypair(1);
+        let (_, mallory_secret) = keypair(4);
+        let trace = TracePayload::new(alice_id, now_ms(), &mallory_secret);
+        let envelope = Envelope::new_via(
+            alice_id,
+            node_id(3),
+            vec![relay.local_id],
+            MessageType::Trace,
+            trace.to_bytes(),
+        );
+        assert!(relay.handle_incoming(&envelope.to_bytes().unwrap()).is_empty());
+    }
 
     #[test]
-    fn tick_subnets_empty_state_no_effects() {
-        let mut state = default_state(1);
-        let effects = state.tick_subnets();
-        assert!(effects.is_empty());
+    fn request_reaches_callee_and_response_completes_it() {
+        let mut alice = default_state(1);
+        let mut bob = default_state(2);
+        let (alice_id, bob_id) = (alice.local_id, bob.local_id);
+
+        let sent = |effects: Vec<RuntimeEffect>| -> Envelope {
+            match &effects[..] {
+                [RuntimeEffect::SendEnvelope(env)] => env.clone(),
+                other => panic!("expected one envelope, got: {other:?}"),
+            }
+        };
+        let request = sent(alice.send_rpc(bob_id, MessageType::Request, "req-1".into(), b"ping".to_vec()));
+        assert_eq!(request.msg_type, MessageType::Request);
+        assert!(request.encrypted);
+
+        let effects = bob.handle_incoming(&request.to_bytes().unwrap());
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::RequestReceived { from, request_id, payload })
+                if *from == alice_id && request_id == "req-1" && payload == b"ping"
+        )));
+
+        let response = sent(bob.handle_command(RuntimeCommand::Respond {
+            to: alice_id,
+            request_id: "req-1".into(),
+            payload: b"pong".to_vec(),
+        }));
+        let effects = alice.handle_incoming(&response.to_bytes().unwrap());
+        let completed = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::CompleteRequest(response) => Some(response.clone()),
+                _ => None,
+            })
+            .expect("response should reach the waiting caller");
+        assert_eq!(completed.from, bob_id);
+        assert_eq!(completed.request_id, "req-1");
+        assert_eq!(completed.payload, b"pong");
+
+        assert!(alice.send_rpc(alice_id, MessageType::Request, "self".into(), Vec::new()).is_empty());
     }
 
     #[test]
-    fn tick_roles_empty_state_no_effects() {
+    fn incoming_hooks_can_drop_envelopes() {
+        struct DropTyping;
+        impl crate::runtime::RuntimeHook for DropTyping {
+            fn on_incoming_envelope(&self, envelope: &mut Envelope) -> crate::runtime::HookAction {
+                if envelope.msg_type == MessageType::Typing {
+                    crate::runtime::HookAction::Drop
+                } else {
+                    crate::runtime::HookAction::Continue
+                }
+            }
+        }
+
+        let mut alice = default_state(1);
+        let (bob_id, bob_secret) = keypair(2);
+        let mut bob = RuntimeState::new(
+            bob_id,
+            bob_secret,
+            RuntimeConfig {
+                hooks: vec![Box::new(DropTyping)],
+                ..Default::default()
+            },
+        );
+
+        let typing = match &alice.send_typing(bob_id, true)[..] {
+            [RuntimeEffect::SendEnvelope(env)] => env.clone(),
+            other => panic!("expected a typing envelope, got: {other:?}"),
+        };
+        assert!(bob.handle_incoming(&typing.to_bytes().unwrap()).is_empty());
+
+        let request = match &alice.send_rpc(bob_id, MessageType::Request, "r".into(), Vec::new())[..] {
+            [RuntimeEffect::SendEnvelope(env)] => env.clone(),
+            other => panic!("expected a request envelope, got: {other:?}"),
+        };
+        assert!(!bob.handle_incoming(&request.to_bytes().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn pause_silences_announces_and_maintenance_ticks() {
         let mut state = default_state(1);
-        let effects = state.tick_roles();
-        assert!(effects.is_empty());
+        assert!(state.build_gossip_announce().is_some());
+
+        let effects = state.handle_command(RuntimeCommand::Pause);
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::RuntimePaused { paused: true })
+        )));
+        assert!(state.is_paused());
+        assert!(state.build_gossip_announce().is_none());
+        assert!(state.tick_backup().is_empty());
+        assert!(state.tick_group_hub_heartbeat().is_empty());
+        assert!(state.tick_shadow_ping().is_empty());
+        assert!(state.handle_command(RuntimeCommand::Pause).is_empty(), "already paused");
+
+        let effects = state.handle_command(RuntimeCommand::Resume);
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::RuntimePaused { paused: false })
+        )));
+        assert!(!state.is_paused());
+        assert!(state.build_gossip_announce().is_some());
     }
 
     #[test]
-    fn tick_backup_empty_state_no_effects() {
+    fn low_power_profile_stretches_timers() {
+        use crate::discovery::DeploymentProfile;
+        let low = RuntimeConfig::for_profile(DeploymentProfile::LowPower);
+        let desktop = RuntimeConfig::for_profile(DeploymentProfile::Desktop);
+        assert!(low.backup_tick_interval > desktop.backup_tick_interval);
+        assert!(low.group_hub_heartbeat_interval > desktop.group_hub_heartbeat_interval);
+        assert!(low.shadow_ping_interval > desktop.shadow_ping_interval);
+        assert_eq!(desktop.backup_tick_interval, RuntimeConfig::default().backup_tick_interval);
+    }
+
+    #[test]
+    fn signed_departure_marks_peer_offline_at_once() {
         let mut state = default_state(1);
-        let effects = state.tick_backup();
-        assert!(effects.is_empty());
+        let (peer, peer_secret) = keypair(2);
+        let announce = PeerAnnounce::new(peer, "bob".to_string(), vec![PeerRole::Peer]);
+        state.handle_gossip_event(GossipInput::PeerAnnounce(rmp_serde::to_vec(&announce).unwrap()));
+        state.tick_heartbeat();
+
+        // Signed by someone else: ignored
+        let (_, forger_secret) = keypair(3);
+        let forged = crate::discovery::DepartureAnnounce::new(peer, now_ms(), &forger_secret);
+        assert!(state.handle_gossip_event(GossipInput::PeerAnnounce(forged.to_bytes())).is_empty());
+        assert_eq!(state.topology.get(&peer).unwrap().status, PeerStatus::Online);
+
+        let departure = crate::discovery::DepartureAnnounce::new(peer, now_ms(), &peer_secret);
+        let effects = state.handle_gossip_event(GossipInput::PeerAnnounce(departure.to_bytes()));
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::PeerOffline { node_id }) if *node_id == peer
+        )));
+        assert_eq!(state.topology.get(&peer).unwrap().status, PeerStatus::Offline);
+        assert!(
+            !state.tick_heartbeat().iter().any(|e| matches!(e, RuntimeEffect::Emit(_))),
+            "no second offline transition from the heartbeat check"
+        );
+
+        // Coming back is an ordinary announce
+        state.handle_gossip_event(GossipInput::PeerAnnounce(rmp_serde::to_vec(&announce).unwrap()));
+        state.tick_heartbeat();
+        assert_eq!(state.topology.get(&peer).unwrap().status, PeerStatus::Online);
     }
 
     #[test]
-    fn tick_group_hub_heartbeat_empty_state_no_effects() {
+    fn unreachable_path_marks_peer_offline_at_once() {
+        let mut state = default_state(74);
+        let peer = node_id(75);
+        state.handle_command(RuntimeCommand::AddPeer { node_id: peer });
+        state.tick_heartbeat();
+
+        let effects = state.handle_path_event(tom_transport::PathEvent::peer_unreachable(peer));
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::PeerOffline { node_id }) if *node_id == peer
+        )));
+        assert_eq!(state.topology.get(&peer).unwrap().status, PeerStatus::Offline);
+        assert_eq!(state.peer_liveness(&peer).unwrap().path, tom_transport::PathKind::Unreachable);
+        assert!(!state.tick_heartbeat().iter().any(|e| matches!(e, RuntimeEffect::Emit(_))));
+
+        // Already offline: only the path change is surfaced
+        let effects = state.handle_path_event(tom_transport::PathEvent::peer_unreachable(peer));
+        assert_eq!(effects.len(), 1);
+    }
+
+    #[test]
+    fn shutdown_report_counts_messages_left_in_the_outbox() {
         let mut state = default_state(1);
-        let effects = state.tick_group_hub_heartbeat();
-        assert!(effects.is_empty());
+        let bob = node_id(2);
+        state.set_connectivity(false);
+        state.handle_command(RuntimeCommand::SendMessage {
+            to: bob,
+            payload: b"later".to_vec(),
+            reply: None,
+        });
+        assert_eq!(state.queued_messages().len(), 1);
+
+        let (_, report) = state.prepare_shutdown();
+        assert_eq!(report.unsent_messages, 1, "offline: nothing could be flushed");
+        assert!(!report.timed_out && !report.departure_announced, "set by the loop");
     }
 
     #[test]
-    fn build_gossip_announce_returns_bytes() {
-        let state = default_state(1);
+    fn hosted_identities_are_announced_and_reached_through_the_host() {
+        let (host_id, host_secret) = keypair(61);
+        let (_, work_seed) = keypair(62);
+        let work = crate::runtime::HostedIdentity::new(work_seed, "alice-work");
+        let work_id = work.node_id();
+        let host = RuntimeState::new(
+            host_id,
+            host_secret,
+            RuntimeConfig {
+                identities: vec![work.clone()],
+                ..Default::default()
+            },
+        );
+        let mut peer = default_state(63);
+
+        let announce = host.build_gossip_announce().unwrap();
+        peer.handle_gossip_event(GossipInput::PeerAnnounce(announce));
+        assert_eq!(peer.topology.host_of(&work_id), Some(host_id));
+        assert_eq!(peer.topology.get(&work_id).unwrap().status, PeerStatus::Online);
+
+        // Messages to the identity go through its host
+        let effects = peer.handle_command(RuntimeCommand::SendMessage {
+            to: work_id,
+            payload: b"hi".to_vec(),
+            reply: None,
+        });
+        let envelope = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendWithBackupFallback { envelope, .. } | RuntimeEffect::SendEnvelope(envelope) => {
+                    Some(envelope)
+                }
+                _ => None,
+            })
+            .expect("message sent");
+        assert_eq!(envelope.to, work_id);
+        assert_eq!(envelope.via, vec![host_id]);
+
+        // The identity's own state delivers it, tagged with the recipient
+        let mut hosted = RuntimeState::new(work_id, work_seed, work.state_config(&RuntimeConfig::default()));
+        let effects = hosted.handle_incoming(&envelope.to_bytes().unwrap());
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::DeliverMessage(msg) if msg.to == work_id && msg.payload == b"hi"
+        )));
+
+        // A binding to another host is ignored
+        let (other_id, _) = keypair(64);
+        let mut forged: PeerAnnounce = PeerAnnounce::new(other_id, "mallory".into(), vec![PeerRole::Peer]);
+        forged.hosted = vec![work.binding(host_id, crate::types::now_ms())];
+        let mut fresh = default_state(65);
+        fresh.handle_gossip_event(GossipInput::PeerAnnounce(rmp_serde::to_vec(&forged).unwrap()));
+        assert_eq!(fresh.topology.host_of(&work_id), None);
+    }
+
+    #[test]
+    fn linked_devices_share_groups_and_receive_user_messages() {
+        use crate::discovery::{DeviceCertificate, DeviceRevocation};
+
+        let (_, user_seed) = keypair(66);
+        let (laptop_id, laptop_secret) = keypair(67);
+        let (phone_id, phone_secret) = keypair(68);
+        let device_config = |device, name: &str| RuntimeConfig {
+            encryption: false,
+            device_certificate: Some(DeviceCertificate::new(
+                &user_seed,
+                device,
+                name.into(),
+                crate::types::now_ms(),
+            )),
+            ..Default::default()
+        };
+        let mut laptop = RuntimeState::new(laptop_id, laptop_secret, device_config(laptop_id, "laptop"));
+        let mut phone = RuntimeState::new(phone_id, phone_secret, device_config(phone_id, "phone"));
+        let user = laptop.config.device_certificate.as_ref().unwrap().user;
+
+        laptop.handle_command(RuntimeCommand::CreateGroup {
+            name: "Family".into(),
+            hub_relay_id: laptop_id,
+            initial_members: vec![],
+            invite_only: true,
+        });
+        let gid = laptop.group_hub.groups().next().unwrap().0.clone();
+
+        // Each learns the other; the laptop invites the phone into its group
+        let laptop_announce = laptop.build_gossip_announce().unwrap();
+        let phone_announce = phone.build_gossip_announce().unwrap();
+        phone.handle_gossip_event(GossipInput::PeerAnnounce(laptop_announce.clone()));
+        let effects = laptop.handle_gossip_event(GossipInput::PeerAnnounce(phone_announce.clone()));
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::DeviceLinked { device, .. }) if *device == phone_id
+        )));
+        let sent = |effects: &[RuntimeEffect], msg_type: MessageType| {
+            effects.iter().find_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env) if env.msg_type == msg_type => Some(env.clone()),
+                _ => None,
+            })
+        };
+        let invite = sent(&effects, MessageType::GroupInvite).expect("phone invited");
+
+        // The phone joins without asking
+        let effects = phone.handle_incoming_group(invite);
+        let join = sent(&effects, MessageType::GroupJoin).expect("phone joins");
+        laptop.handle_incoming_group(join);
+        assert!(laptop.group_hub.get_group(&gid).unwrap().is_member(&phone_id));
+
+        // Messages to the user reach both devices
+        let mut peer = default_state(69);
+        peer.handle_gossip_event(GossipInput::PeerAnnounce(laptop_announce));
+        peer.handle_gossip_event(GossipInput::PeerAnnounce(phone_announce));
+        let effects = peer.handle_command(RuntimeCommand::SendToUser {
+            user,
+            payload: b"hi".to_vec(),
+        });
+        let mut recipients: Vec<NodeId> = effects
+            .iter()
+            .filter_map(|e| match e {
+                RuntimeEffect::SendWithBackupFallback { envelope, .. } | RuntimeEffect::SendEnvelope(envelope) => {
+                    Some(envelope.to)
+                }
+                _ => None,
+            })
+            .collect();
+        recipients.sort_by_key(|n| n.as_bytes());
+        assert_eq!(recipients, peer.devices.devices_of(&user));
+        assert_eq!(recipients.len(), 2);
+
+        // Revoking the phone kicks it from the laptop's group and is gossiped
+        let revocation = DeviceRevocation::new(&user_seed, phone_id, crate::types::now_ms());
+        let effects = laptop.handle_command(RuntimeCommand::RevokeDevice { revocation });
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::DeviceRevoked { device, .. }) if *device == phone_id
+        )));
+        assert!(!laptop.group_hub.get_group(&gid).unwrap().is_member(&phone_id));
+        let announce = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::BroadcastAnnounce(bytes) => Some(bytes.clone()),
+                _ => None,
+            })
+            .expect("revocation announced");
+        peer.handle_gossip_event(GossipInput::PeerAnnounce(announce));
+        assert_eq!(peer.devices.devices_of(&user), vec![laptop_id]);
+    }
+
+    #[test]
+    fn linked_devices_sync_history() {
+        use crate::discovery::DeviceCertificate;
+        use crate::history_sync::HistoryEntry;
+
+        let (_, user_seed) = keypair(70);
+        let (laptop_id, laptop_secret) = keypair(71);
+        let (phone_id, phone_secret) = keypair(72);
+        let (bob_id, bob_secret) = keypair(73);
+        let device_config = |device, name: &str| RuntimeConfig {
+            device_certificate: Some(DeviceCertificate::new(
+                &user_seed,
+                device,
+                name.into(),
+                crate::types::now_ms(),
+            )),
+            ..Default::default()
+        };
+        let sync_envelope = |effects: &[RuntimeEffect], to: NodeId| {
+            effects.iter().find_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::HistorySync && env.to == to => {
+                    Some(env.to_bytes().unwrap())
+                }
+                _ => None,
+            })
+        };
+        let synced = |effects: &[RuntimeEffect]| {
+            effects.iter().find_map(|e| match e {
+                RuntimeEffect::Emit(ProtocolEvent::HistorySynced { from, entries, .. }) => Some((*from, entries.clone())),
+                _ => None,
+            })
+        };
+
+        // The laptop has history before the phone is linked
+        let mut laptop = RuntimeState::new(laptop_id, laptop_secret, device_config(laptop_id, "laptop"));
+        laptop.handle_command(RuntimeCommand::SendMessage {
+            to: bob_id,
+            payload: b"before".to_vec(),
+            reply: None,
+        });
+
+        // The new phone asks the laptop for it
+        let mut phone = RuntimeState::new(phone_id, phone_secret, device_config(phone_id, "phone"));
+        let effects = phone.handle_gossip_event(GossipInput::PeerAnnounce(laptop.build_gossip_announce().unwrap()));
+        let request = sync_envelope(&effects, laptop_id).expect("history requested");
+        laptop.handle_gossip_event(GossipInput::PeerAnnounce(phone.build_gossip_announce().unwrap()));
+        let page = sync_envelope(&laptop.handle_incoming(&request), phone_id).expect("history sent");
+        let (from, entries) = synced(&phone.handle_incoming(&page)).expect("history received");
+        assert_eq!(from, laptop_id);
+        assert!(matches!(
+            &entries[..],
+            [HistoryEntry::Direct(e)] if e.peer == bob_id && e.outgoing && e.payload == b"before"
+        ));
+
+        // Messages the laptop receives reach the phone as they come
+        let mut bob = RuntimeState::new(bob_id, bob_secret, RuntimeConfig::default());
+        let message = bob
+            .handle_command(RuntimeCommand::SendMessage {
+                to: laptop_id,
+                payload: b"live".to_vec(),
+                reply: None,
+            })
+            .into_iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendWithBackupFallback { envelope, .. } => Some(envelope),
+                _ => None,
+            })
+            .unwrap();
+        let forward = sync_envelope(&laptop.handle_incoming(&message.to_bytes().unwrap()), phone_id)
+            .expect("forwarded to the phone");
+        let (_, entries) = synced(&phone.handle_incoming(&forward)).unwrap();
+        assert!(matches!(
+            &entries[..],
+            [HistoryEntry::Direct(e)] if e.peer == bob_id && !e.outgoing && e.payload == b"live"
+        ));
+        assert!(synced(&phone.handle_incoming(&forward)).is_none(), "already known");
+
+        // Other nodes can't feed the phone history
+        let forged = bob.send_untracked(phone_id, MessageType::HistorySync, HistorySyncPayload::Request { since: 0 }.to_bytes());
+        let forged = sync_envelope(&forged, phone_id).unwrap();
+        assert!(sync_envelope(&phone.handle_incoming(&forged), bob_id).is_none());
+    }
+
+    #[test]
+    fn ordered_delivery_reorders_and_reports_gaps() {
+        let (alice_id, alice_secret) = keypair(74);
+        let (bob_id, bob_secret) = keypair(75);
+        let ordered = || RuntimeConfig {
+            ordered_delivery: Some(crate::ordering::OrderingConfig {
+                window: 8,
+                gap_timeout: std::time::Duration::from_secs(5),
+            }),
+            ..Default::default()
+        };
+        let mut alice = RuntimeState::new(alice_id, alice_secret, ordered());
+        let mut bob = RuntimeState::new(bob_id, bob_secret, ordered());
+        let sent: Vec<Vec<u8>> = (1..=5u8)
+            .map(|n| {
+                alice
+                    .handle_command(RuntimeCommand::SendMessage { to: bob_id, payload: vec![n], reply: None })
+                    .into_iter()
+                    .find_map(|e| match e {
+                        RuntimeEffect::SendWithBackupFallback { envelope, .. } => envelope.to_bytes().ok(),
+                        _ => None,
+                    })
+                    .unwrap()
+            })
+            .collect();
+        let delivered = |effects: Vec<RuntimeEffect>| -> Vec<u8> {
+            effects
+                .into_iter()
+                .filter_map(|e| match e {
+                    RuntimeEffect::DeliverMessage(msg) => Some(msg.payload[0]),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // 3 waits for 2; the header never reaches the application
+        assert_eq!(delivered(bob.handle_incoming(&sent[0])), [1]);
+        assert!(delivered(bob.handle_incoming(&sent[2])).is_empty());
+        assert_eq!(delivered(bob.handle_incoming(&sent[1])), [2, 3]);
+
+        // 5 waits for 4, which doesn't come in time
+        assert!(delivered(bob.handle_incoming(&sent[4])).is_empty());
+        assert!(delivered(bob.tick_reorder_at(now_ms() + 1_000)).is_empty());
+        let effects = bob.tick_reorder_at(now_ms() + 10_000);
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            RuntimeEffect::Emit(ProtocolEvent::MessageGap { from, first: 4, count: 1 }) if *from == alice_id
+        )));
+        assert_eq!(delivered(effects), [5]);
+
+        // Late 4 still gets through
+        assert_eq!(delivered(bob.handle_incoming(&sent[3])), [4]);
+
+        // Without ordered delivery, numbered payloads are just unwrapped
+        let (carol_id, carol_secret) = keypair(76);
+        let mut carol = RuntimeState::new(carol_id, carol_secret, RuntimeConfig::default());
+        let envelope = alice
+            .handle_command(RuntimeCommand::SendMessage { to: carol_id, payload: b"hi".to_vec(), reply: None })
+            .into_iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendWithBackupFallback { envelope, .. } => envelope.to_bytes().ok(),
+                _ => None,
+            })
+            .unwrap();
+        assert!(carol.handle_incoming(&envelope).iter().any(|e| matches!(
+            e,
+            RuntimeEffect::DeliverMessage(msg) if msg.payload == b"hi"
+        )));
+    }
+
+    #[test]
+    fn build_gossip_announce_roundtrip() {
+        // Build gossip announce bytes, deserialize them back,
+        // verify fields match (node_id, username, roles).
+        let (local_id, local_secret) = keypair(60);
+        let state = RuntimeState::new(
+            local_id,
+            local_secret,
+            RuntimeConfig {
+                username: "alice_test".to_string(),
+                ..Default::default()
+            },
+        );
+
         let bytes = state.build_gossip_announce();
         assert!(bytes.is_some(), "should produce announce bytes");
-
-        // Verify it's valid MsgPack PeerAnnounce
         let bytes = bytes.unwrap();
+
         let announce: PeerAnnounce =
             rmp_serde::from_slice(&bytes).expect("should deserialize PeerAnnounce");
-        assert_eq!(announce.node_id, state.local_id);
-        assert_eq!(announce.username, "anonymous");
+        assert_eq!(announce.node_id, local_id);
+        assert_eq!(announce.username, "alice_test");
         assert_eq!(announce.roles, vec![PeerRole::Peer]);
-    }
-
-    // ── Task 7 tests ─────────────────────────────────────────────────────
-
-    /// Build a signed chat envelope from sender (with known secret) to recipient.
-    fn make_signed_chat(
-        sender_seed: u8,
-        recipient_id: NodeId,
-        payload: &[u8],
-    ) -> (crate::envelope::Envelope, bool) {
-        let (sender_id, sender_secret) = keypair(sender_seed);
-        let env = crate::envelope::EnvelopeBuilder::new(
-            sender_id,
-            recipient_id,
-            MessageType::Chat,
-            payload.to_vec(),
-        )
-        .sign(&sender_secret);
-        let sig_valid = env.verify_signature().is_ok();
-        (env, sig_valid)
+        assert!(
+            announce.is_timestamp_valid(crate::types::now_ms()),
+            "announce timestamp should be valid at current time"
+        );
     }
 
     #[test]
-    fn handle_incoming_chat_delivers_and_acks() {
-        let mut state = default_state(1);
-        let (sender_id, _) = keypair(2);
+    fn handle_incoming_rejects_garbage_bytes() {
+        // Pass garbage bytes to handle_incoming(). Verify it returns
+        // empty effects (graceful handling, no panic).
+        let mut state = default_state(70);
 
-        let (env, sig_valid) = make_signed_chat(2, state.local_id, b"hello");
+        let garbage = b"this is not valid msgpack at all!!! \x00\xff\xfe";
+        let effects = state.handle_incoming(garbage);
+        assert!(
+            effects.is_empty(),
+            "garbage input should produce no effects (graceful drop), got: {effects:?}"
+        );
 
-        let effects = state.handle_incoming_chat(env, sig_valid);
+        // Also test empty bytes
+        let effects = state.handle_incoming(&[]);
+        assert!(
+            effects.is_empty(),
+            "empty input should produce no effects, got: {effects:?}"
+        );
 
-        // Should have DeliverMessage + SendEnvelope(ACK)
-        let has_deliver = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::DeliverMessage(msg) if msg.from == sender_id && msg.payload == b"hello")
-        });
-        let has_ack = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::Ack && env.to == sender_id)
-        });
-        assert!(has_deliver, "expected DeliverMessage, got: {effects:?}");
-        assert!(has_ack, "expected ACK SendEnvelope, got: {effects:?}");
+        // Also test partially valid but corrupted msgpack
+        let effects = state.handle_incoming(&[0x93, 0x01, 0x02]);
+        assert!(
+            effects.is_empty(),
+            "corrupted msgpack should produce no effects, got: {effects:?}"
+        );
     }
 
     #[test]
-    fn handle_incoming_chat_encrypted_decrypts() {
-        // Create state with encryption
-        let (local_id, local_secret) = keypair(1);
+    fn send_message_unencrypted_when_config_disabled() {
+        // Create state with config.encryption = false. Call handle_send_message.
+        // Verify the envelope is NOT encrypted.
+        let (local_id, local_secret) = keypair(80);
         let mut state = RuntimeState::new(
             local_id,
             local_secret,
             RuntimeConfig {
-                encryption: true,
+                encryption: false,
                 ..Default::default()
             },
         );
+        let recipient = node_id(81);
 
-        let (sender_id, sender_secret) = keypair(2);
-        let plaintext = b"secret message";
-        let recipient_pk = local_id.as_bytes();
-
-        let env = crate::envelope::EnvelopeBuilder::new(
-            sender_id,
-            local_id,
-            MessageType::Chat,
-            plaintext.to_vec(),
-        )
-        .encrypt_and_sign(&sender_secret, &recipient_pk)
-        .expect("encrypt_and_sign");
-
-        let sig_valid = env.verify_signature().is_ok();
-        let effects = state.handle_incoming_chat(env, sig_valid);
+        let effects = state.handle_send_message(recipient, b"plaintext msg".to_vec());
+        assert_eq!(effects.len(), 1);
 
-        // Find the delivered message
-        let delivered = effects.iter().find_map(|e| {
-            if let RuntimeEffect::DeliverMessage(msg) = e {
-                Some(msg)
-            } else {
-                None
+        match &effects[0] {
+            RuntimeEffect::SendWithBackupFallback { envelope, .. } => {
+                assert!(
+                    !envelope.encrypted,
+                    "envelope should NOT be encrypted when config.encryption=false"
+                );
+                assert!(envelope.is_signed(), "envelope should still be signed");
+                assert_eq!(envelope.to, recipient);
+                assert_eq!(envelope.from, local_id);
+                // Payload should be the original plaintext (not ciphertext)
+                assert_eq!(envelope.payload, b"plaintext msg");
             }
-        });
-        assert!(delivered.is_some(), "expected DeliverMessage");
-        let msg = delivered.unwrap();
-        assert_eq!(msg.payload, plaintext);
-        assert!(msg.was_encrypted);
-        assert!(msg.signature_valid);
+            other => panic!("expected SendWithBackupFallback, got: {other:?}"),
+        }
     }
 
+    // ── Task 5 (failover): shadow auto-assignment ────────────────────
+
     #[test]
-    fn handle_incoming_chat_forward_when_not_recipient() {
-        let mut state = default_state(1);
-        let (sender_id, sender_secret) = keypair(2);
-        let recipient_id = node_id(3);
+    fn handle_incoming_group_create_triggers_shadow_assignment() {
+        // When the hub processes a GroupCreate, it should automatically
+        // call assign_shadow after the group is created.
+        let (hub_id, hub_secret) = keypair(90);
+        let (alice_id, alice_secret) = keypair(91);
+        let (bob_id, bob_secret) = keypair(92);
 
-        // Build envelope from sender to recipient, routed via our node
-        let env = crate::envelope::EnvelopeBuilder::new(
-            sender_id,
-            recipient_id,
-            MessageType::Chat,
-            b"relayed".to_vec(),
+        let mut hub_state = RuntimeState::new(
+            hub_id,
+            hub_secret,
+            RuntimeConfig {
+                encryption: false,
+                ..Default::default()
+            },
+        );
+
+        // Build a GroupCreate envelope from Alice to the hub
+        let create_payload = crate::group::GroupPayload::Create {
+            group_name: "Shadow Auto Test".into(),
+            creator_username: "alice".into(),
+            initial_members: vec![bob_id],
+            invite_only: false,
+        };
+        let payload_bytes = rmp_serde::to_vec(&create_payload).unwrap();
+        let create_env = EnvelopeBuilder::new(
+            alice_id,
+            hub_id,
+            MessageType::GroupCreate,
+            payload_bytes,
         )
-        .via(vec![state.local_id])
-        .sign(&sender_secret);
+        .sign(&alice_secret);
 
-        let sig_valid = env.verify_signature().is_ok();
-        let effects = state.handle_incoming_chat(env, sig_valid);
+        let effects = hub_state.handle_incoming_group(create_env);
 
-        // Should have SendEnvelopeTo for next_hop + SendEnvelopeTo for ACK + Forwarded event
-        let has_forward = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::SendEnvelopeTo { target, .. } if *target == recipient_id)
-        });
-        let has_relay_ack = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::SendEnvelopeTo { target, envelope } if *target == sender_id && envelope.msg_type == MessageType::Ack)
+        // After Create, the only member is Alice (the creator).
+        // assign_shadow should have been called — it picks the lowest non-hub member.
+        // Since Alice is the only member and she is not the hub, she becomes shadow.
+        let has_shadow_sync = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::GroupHubShadowSync)
         });
-        let has_forwarded_event = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::Emit(ProtocolEvent::Forwarded { next_hop, .. }) if *next_hop == recipient_id)
+        assert!(
+            has_shadow_sync,
+            "GroupCreate should trigger shadow assignment and send HubShadowSync, got: {effects:?}"
+        );
+
+        // Now Bob joins — shadow should be reassigned/updated
+        let gid = hub_state.group_hub.groups().next().unwrap().0.clone();
+        let join_payload = crate::group::GroupPayload::Join {
+            group_id: gid.clone(),
+            username: "bob".into(),
+        };
+        let join_bytes = rmp_serde::to_vec(&join_payload).unwrap();
+        let join_env = EnvelopeBuilder::new(
+            bob_id,
+            hub_id,
+            MessageType::GroupJoin,
+            join_bytes,
+        )
+        .sign(&bob_secret);
+
+        let join_effects = hub_state.handle_incoming_group(join_env);
+
+        // After join, assign_shadow should run again
+        let has_shadow_sync_after_join = join_effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::GroupHubShadowSync)
         });
-        assert!(has_forward, "expected forward to recipient, got: {effects:?}");
-        assert!(has_relay_ack, "expected relay ACK to sender, got: {effects:?}");
         assert!(
-            has_forwarded_event,
-            "expected Forwarded event, got: {effects:?}"
+            has_shadow_sync_after_join,
+            "GroupJoin should trigger shadow reassignment, got: {join_effects:?}"
+        );
+
+        // Verify the group now has a shadow_id set
+        let group = hub_state.group_hub.get_group(&gid).unwrap();
+        assert!(
+            group.shadow_id.is_some(),
+            "group should have a shadow after join"
         );
     }
 
+    // ── Bandwidth tracking tests ────────────────────────────────────────
+
     #[test]
-    fn handle_incoming_chat_dedup_drops() {
+    fn role_manager_bandwidth_tracking_via_runtime_state() {
         let mut state = default_state(1);
-        let (env, sig_valid) = make_signed_chat(2, state.local_id, b"once");
-        let env2 = env.clone();
+        let peer = node_id(2);
 
-        let effects1 = state.handle_incoming_chat(env, sig_valid);
+        // Directly record bandwidth through the role_manager
+        state.role_manager.record_relay(peer, 1000);
+        state
+            .role_manager
+            .record_bytes_relayed(peer, 50 * 1_048_576, 1000); // 50 MB
+
+        let score = state.role_manager.score(&peer, 1000);
+        // Score should include bandwidth: relay(1) + success(5) + bandwidth_mb(50*0.2=10) = 16+
         assert!(
-            !effects1.is_empty(),
-            "first delivery should produce effects"
+            score > 15.0,
+            "Score should reflect bandwidth contribution, got {score}"
+        );
+    }
+
+    #[test]
+    fn local_role_change_broadcasts_announce() {
+        let mut state = default_state(1);
+
+        // Simulate local promotion
+        let action = RoleAction::LocalRoleChanged {
+            new_role: PeerRole::Relay,
+        };
+
+        let effects = state.surface_role_action(&action);
+
+        // Should emit event + broadcast announce
+        assert!(
+            effects
+                .iter()
+                .any(|e| matches!(e, RuntimeEffect::Emit(ProtocolEvent::LocalRoleChanged { .. }))),
+            "Should emit LocalRoleChanged event"
         );
-
-        let effects2 = state.handle_incoming_chat(env2, sig_valid);
         assert!(
-            effects2.is_empty(),
-            "duplicate should be dropped, got: {effects2:?}"
+            effects
+                .iter()
+                .any(|e| matches!(e, RuntimeEffect::BroadcastRoleChange(_))),
+            "Should broadcast role change announce"
         );
     }
 
-    // ── Task 8 tests ─────────────────────────────────────────────────────
-
     #[test]
-    fn handle_incoming_parses_and_dispatches_chat() {
+    fn handle_role_announce_updates_topology() {
+        use crate::discovery::RoleChangeAnnounce;
+
         let mut state = default_state(1);
-        let (sender_id, sender_secret) = keypair(2);
+        let (remote, remote_seed) = keypair(2);
 
-        let env = crate::envelope::EnvelopeBuilder::new(
-            sender_id,
-            state.local_id,
-            MessageType::Chat,
-            b"raw bytes test".to_vec(),
-        )
-        .sign(&sender_secret);
-        let raw = env.to_bytes().expect("serialize");
+        let announce = RoleChangeAnnounce::new(
+            remote,
+            PeerRole::Relay,
+            15.0,
+            now_ms(),
+            &remote_seed,
+        );
 
-        let effects = state.handle_incoming(raw.as_slice());
+        let effects = state.handle_role_announce(announce);
 
-        let has_deliver = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::DeliverMessage(msg) if msg.from == sender_id)
-        });
+        // Should emit RolePromoted
         assert!(
-            has_deliver,
-            "should dispatch chat and deliver, got: {effects:?}"
+            effects.iter().any(|e| matches!(
+                e,
+                RuntimeEffect::Emit(ProtocolEvent::RolePromoted { node_id, .. })
+                if *node_id == remote
+            )),
+            "Should emit RolePromoted: {effects:?}"
         );
+
+        // Topology should be updated
+        let peer = state.topology.get(&remote).expect("peer in topology");
+        assert_eq!(peer.role, PeerRole::Relay);
     }
 
     #[test]
-    fn handle_incoming_auto_registers_unknown_peer() {
-        let mut state = default_state(1);
-        let (sender_id, sender_secret) = keypair(2);
+    fn handle_role_announce_throttle() {
+        use crate::discovery::RoleChangeAnnounce;
 
-        // Verify peer is not in topology yet
-        assert!(state.topology.get(&sender_id).is_none());
+        let mut state = default_state(1);
+        let (remote, remote_seed) = keypair(2);
 
-        let env = crate::envelope::EnvelopeBuilder::new(
-            sender_id,
-            state.local_id,
-            MessageType::Chat,
-            b"auto-register".to_vec(),
-        )
-        .sign(&sender_secret);
-        let raw = env.to_bytes().expect("serialize");
+        let announce1 = RoleChangeAnnounce::new(
+            remote, PeerRole::Relay, 15.0, now_ms(), &remote_seed,
+        );
+        let announce2 = RoleChangeAnnounce::new(
+            remote, PeerRole::Peer, 1.0, now_ms(), &remote_seed,
+        );
 
-        state.handle_incoming(raw.as_slice());
+        // First announce accepted
+        let effects1 = state.handle_role_announce(announce1);
+        assert!(!effects1.is_empty(), "First announce should be accepted");
 
-        // Peer should now be in topology
-        let peer = state.topology.get(&sender_id);
-        assert!(peer.is_some(), "peer should be auto-registered in topology");
-        assert_eq!(peer.unwrap().status, PeerStatus::Online);
+        // Second announce within 30s throttled
+        let effects2 = state.handle_role_announce(announce2);
+        assert!(effects2.is_empty(), "Second announce should be throttled");
     }
 
-    // ── Task 9 tests ─────────────────────────────────────────────────────
-
     #[test]
-    fn handle_send_message_produces_fallback_effect() {
+    fn handle_role_announce_rejects_invalid_signature() {
+        use crate::discovery::RoleChangeAnnounce;
+
         let mut state = default_state(1);
-        let recipient = node_id(2);
+        let remote = node_id(2);
 
-        let effects = state.handle_send_message(recipient, b"hello".to_vec());
+        // Sign with WRONG key (node 3's key, not node 2's)
+        let (_, wrong_seed) = keypair(3);
 
-        assert_eq!(effects.len(), 1, "expected exactly one effect");
-        assert!(
-            matches!(&effects[0], RuntimeEffect::SendWithBackupFallback { .. }),
-            "expected SendWithBackupFallback, got: {:?}",
-            effects[0]
+        let announce = RoleChangeAnnounce::new(
+            remote, PeerRole::Relay, 15.0, now_ms(), &wrong_seed,
         );
 
-        // Verify on_success has StatusChange effects
-        if let RuntimeEffect::SendWithBackupFallback {
-            on_success,
-            on_failure,
-            envelope,
-            ..
-        } = &effects[0]
-        {
-            assert!(
-                !on_success.is_empty(),
-                "on_success should have status changes"
-            );
-            assert!(
-                !on_failure.is_empty(),
-                "on_failure should have backup + error effects"
-            );
-            assert!(envelope.is_signed(), "envelope should be signed");
-        }
-    }
+        let effects = state.handle_role_announce(announce);
 
-    #[test]
-    fn handle_send_message_encrypted_when_config_enabled() {
-        let (local_id, local_secret) = keypair(1);
-        let mut state = RuntimeState::new(
-            local_id,
-            local_secret,
-            RuntimeConfig {
-                encryption: true,
-                ..Default::default()
-            },
+        // Should emit Error
+        assert!(
+            effects.iter().any(|e| matches!(
+                e,
+                RuntimeEffect::Emit(ProtocolEvent::Error { description })
+                if description.contains("Invalid signature")
+            )),
+            "Should reject invalid signature: {effects:?}"
         );
-        let recipient = node_id(2);
 
-        let effects = state.handle_send_message(recipient, b"encrypted".to_vec());
-
-        if let RuntimeEffect::SendWithBackupFallback { envelope, .. } = &effects[0] {
-            assert!(
-                envelope.encrypted,
-                "envelope should be encrypted when config.encryption is true"
-            );
-        } else {
-            panic!("expected SendWithBackupFallback");
-        }
+        // Topology should NOT be updated
+        assert!(state.topology.get(&remote).is_none());
     }
 
+    // ── r4: Role validation integration tests ───────────────────────────
+
     #[test]
-    fn handle_command_add_peer_updates_topology() {
+    fn tick_roles_promotes_active_peer() {
         let mut state = default_state(1);
         let peer = node_id(2);
+        let now = now_ms();
 
-        assert!(state.topology.get(&peer).is_none());
+        // Register peer in topology
+        state.topology.upsert(PeerInfo {
+            node_id: peer,
+            role: PeerRole::Peer,
+            status: PeerStatus::Online,
+            last_seen: now,
+        });
 
-        let effects =
-            state.handle_command(RuntimeCommand::AddPeer { node_id: peer });
+        // Simulate 20 relays (enough to exceed PROMOTION_THRESHOLD=10.0)
+        for i in 0..20 {
+            state.role_manager.record_relay(peer, now + i * 1000);
+        }
 
-        assert!(effects.is_empty(), "AddPeer returns no effects");
+        let effects = state.tick_roles();
+
+        let has_promoted = effects.iter().any(|e| {
+            matches!(
+                e,
+                RuntimeEffect::Emit(ProtocolEvent::RolePromoted { node_id, .. })
+                if *node_id == peer
+            )
+        });
         assert!(
-            state.topology.get(&peer).is_some(),
-            "peer should be in topology after AddPeer"
+            has_promoted,
+            "expected RolePromoted after 20 relays, got: {effects:?}"
         );
+
+        // Topology should now show Relay
+        assert_eq!(state.topology.get(&peer).unwrap().role, PeerRole::Relay);
     }
 
     #[test]
-    fn handle_command_remove_peer_cleans_topology() {
+    fn tick_roles_demotes_idle_relay() {
         let mut state = default_state(1);
         let peer = node_id(2);
+        let now = now_ms();
 
-        // Add peer first
-        state.handle_command(RuntimeCommand::AddPeer { node_id: peer });
-        assert!(state.topology.get(&peer).is_some());
+        // Register and promote peer
+        state.topology.upsert(PeerInfo {
+            node_id: peer,
+            role: PeerRole::Peer,
+            status: PeerStatus::Online,
+            last_seen: now,
+        });
+        for i in 0..20 {
+            state.role_manager.record_relay(peer, now + i * 1000);
+        }
+        let _ = state.tick_roles(); // Promotes
+        assert_eq!(state.topology.get(&peer).unwrap().role, PeerRole::Relay);
 
-        // Remove peer
-        let effects =
-            state.handle_command(RuntimeCommand::RemovePeer { node_id: peer });
+        // 100 hours of idleness — score should decay below DEMOTION_THRESHOLD=2.0
+        let future = now + 100 * 3_600_000;
+        let score = state.role_manager.score(&peer, future);
+        assert!(
+            score < 2.0,
+            "score should be below demotion threshold after 100h idle: {score}"
+        );
 
-        assert!(effects.is_empty(), "RemovePeer returns no effects");
+        // tick_roles uses now_ms() (can't fake time), so test via evaluate directly
+        let actions = state.role_manager.evaluate(&mut state.topology, future);
         assert!(
-            state.topology.get(&peer).is_none(),
-            "peer should be removed from topology"
+            actions.iter().any(|a| matches!(
+                a,
+                crate::roles::RoleAction::Demoted { node_id, .. }
+                if *node_id == peer
+            )),
+            "expected demotion after 100h idle: {actions:?}"
         );
+        assert_eq!(state.topology.get(&peer).unwrap().role, PeerRole::Peer);
     }
 
-    // ── Task 10 tests ────────────────────────────────────────────────────
-
     #[test]
-    fn handle_gossip_neighbor_up_registers_peer() {
+    fn get_role_metrics_via_command() {
         let mut state = default_state(1);
         let peer = node_id(2);
+        let now = now_ms();
 
-        assert!(state.topology.get(&peer).is_none());
+        state.topology.upsert(PeerInfo {
+            node_id: peer,
+            role: PeerRole::Peer,
+            status: PeerStatus::Online,
+            last_seen: now,
+        });
 
-        let effects =
-            state.handle_gossip_event(super::GossipInput::NeighborUp(peer));
+        // Record some activity
+        for i in 0..5 {
+            state.role_manager.record_relay(peer, now + i * 1000);
+        }
+        state.role_manager.record_bytes_relayed(peer, 10 * 1_048_576, now + 5000);
 
-        let has_event = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::Emit(ProtocolEvent::GossipNeighborUp { node_id }) if *node_id == peer)
+        // Query via command handler
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        let effects = state.handle_command(RuntimeCommand::GetRoleMetrics {
+            node_id: peer,
+            reply: tx,
         });
-        assert!(has_event, "expected GossipNeighborUp event, got: {effects:?}");
+        assert!(effects.is_empty(), "GetRoleMetrics should not emit effects");
 
-        let topo_peer = state.topology.get(&peer);
+        let metrics = rx.try_recv().expect("should receive response");
+        let metrics = metrics.expect("metrics should exist for tracked peer");
+
+        assert_eq!(metrics.node_id, peer);
+        assert_eq!(metrics.role, PeerRole::Peer);
+        assert_eq!(metrics.relay_count, 5);
+        assert_eq!(metrics.relay_failures, 0);
+        assert!(metrics.score > 0.0, "score should be positive");
+        assert_eq!(metrics.bytes_relayed, 10 * 1_048_576);
         assert!(
-            topo_peer.is_some(),
-            "peer should be registered in topology after NeighborUp"
+            (metrics.success_rate - 1.0).abs() < f64::EPSILON,
+            "100% success rate"
         );
-        assert_eq!(topo_peer.unwrap().status, PeerStatus::Online);
     }
 
     #[test]
-    fn handle_gossip_announce_registers_peer() {
+    fn get_all_role_scores_via_command() {
         let mut state = default_state(1);
-        let peer = node_id(2);
+        let peer_a = node_id(2);
+        let peer_b = node_id(3);
+        let now = now_ms();
 
-        // Build a PeerAnnounce
-        let announce = PeerAnnounce::new(peer, "bob".to_string(), vec![PeerRole::Peer]);
-        let bytes = rmp_serde::to_vec(&announce).expect("serialize announce");
+        for &peer in &[peer_a, peer_b] {
+            state.topology.upsert(PeerInfo {
+                node_id: peer,
+                role: PeerRole::Peer,
+                status: PeerStatus::Online,
+                last_seen: now,
+            });
+        }
 
-        assert!(state.topology.get(&peer).is_none());
+        // Only peer_a has relay activity
+        state.role_manager.record_relay(peer_a, now);
 
-        let effects =
-            state.handle_gossip_event(super::GossipInput::PeerAnnounce(bytes));
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        let effects = state.handle_command(RuntimeCommand::GetAllRoleScores {
+            reply: tx,
+        });
+        assert!(effects.is_empty());
 
-        // PeerAnnounce no longer emits immediately — PeerDiscovered comes from tick_heartbeat
-        assert!(
-            effects.is_empty(),
-            "PeerAnnounce should not emit directly, got: {effects:?}"
-        );
+        let scores = rx.try_recv().expect("should receive response");
+        assert_eq!(scores.len(), 2, "should list both peers");
 
-        let topo_peer = state.topology.get(&peer);
-        assert!(
-            topo_peer.is_some(),
-            "peer should be registered in topology after gossip announce"
-        );
+        let a_entry = scores.iter().find(|(id, _, _)| *id == peer_a);
+        let b_entry = scores.iter().find(|(id, _, _)| *id == peer_b);
 
-        // Verify PeerDiscovered emitted on next heartbeat tick
-        let tick_effects = state.tick_heartbeat();
-        let has_discovered = tick_effects.iter().any(|e| {
-            matches!(
-                e,
-                RuntimeEffect::Emit(ProtocolEvent::PeerDiscovered { node_id, username, source })
-                    if *node_id == peer && username == "bob" && *source == DiscoverySource::Announce
-            )
-        });
-        assert!(
-            has_discovered,
-            "expected PeerDiscovered from tick_heartbeat, got: {tick_effects:?}"
-        );
-    }
+        assert!(a_entry.is_some(), "peer_a should be in scores");
+        assert!(b_entry.is_some(), "peer_b should be in scores");
 
-    // ── Task 13: Integration tests ──────────────────────────────────────
+        let (_, a_score, _) = a_entry.unwrap();
+        let (_, b_score, _) = b_entry.unwrap();
+        assert!(a_score > b_score, "active peer should score higher");
+    }
 
     #[test]
-    fn message_e2e_encrypt_decrypt_roundtrip() {
-        // Alice encrypts a chat message for Bob. Bob's RuntimeState handles
-        // it via handle_incoming(). Verify: plaintext recovered, was_encrypted,
-        // signature_valid.
-        let (alice_id, alice_secret) = keypair(10);
-        let (bob_id, bob_secret) = keypair(11);
-
-        let mut bob_state = RuntimeState::new(
-            bob_id,
-            bob_secret,
-            RuntimeConfig {
-                encryption: true,
-                ..Default::default()
-            },
-        );
-
-        let plaintext = b"Hello Bob, this is Alice!";
-        let bob_pk = bob_id.as_bytes();
-        let env = EnvelopeBuilder::new(
-            alice_id,
-            bob_id,
-            MessageType::Chat,
-            plaintext.to_vec(),
-        )
-        .encrypt_and_sign(&alice_secret, &bob_pk)
-        .expect("encrypt_and_sign should succeed");
+    fn bandwidth_tracking_via_role_metrics_command() {
+        let mut state = default_state(1);
+        let relay = node_id(2);
+        let now = now_ms();
 
-        // Sanity: envelope is encrypted and signed
-        assert!(env.encrypted);
-        assert!(env.is_signed());
+        state.topology.upsert(PeerInfo {
+            node_id: relay,
+            role: PeerRole::Peer,
+            status: PeerStatus::Online,
+            last_seen: now,
+        });
 
-        let raw = env.to_bytes().expect("serialize");
-        let effects = bob_state.handle_incoming(&raw);
+        state.role_manager.record_bytes_relayed(relay, 100 * 1_048_576, now);
+        state.role_manager.record_bytes_received(relay, 50 * 1_048_576, now);
+        state.tick_roles();
 
-        let delivered = effects.iter().find_map(|e| {
-            if let RuntimeEffect::DeliverMessage(msg) = e {
-                Some(msg)
-            } else {
-                None
-            }
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        state.handle_command(RuntimeCommand::GetRoleMetrics {
+            node_id: relay,
+            reply: tx,
         });
-        assert!(delivered.is_some(), "expected DeliverMessage, got: {effects:?}");
-        let msg = delivered.unwrap();
-        assert_eq!(msg.payload, plaintext, "plaintext should match after decryption");
-        assert!(msg.was_encrypted, "message should report was_encrypted=true");
-        assert!(msg.signature_valid, "signature should be valid");
-        assert_eq!(msg.from, alice_id, "sender should be Alice");
+
+        let metrics = rx.try_recv().unwrap().unwrap();
+        assert_eq!(metrics.bytes_relayed, 100 * 1_048_576);
+        assert_eq!(metrics.bytes_received, 50 * 1_048_576);
+        assert!(
+            (metrics.bandwidth_ratio - 2.0).abs() < f64::EPSILON,
+            "bandwidth ratio should be 2.0 (100/50): {}",
+            metrics.bandwidth_ratio
+        );
+        assert_eq!(metrics.score_history.len(), 1, "sampled by the role tick");
     }
 
+    // ── Hub-as-member self-send interception ───────────────────────────
+
     #[test]
-    fn ack_updates_tracker_status() {
-        // Send a message, then simulate relay ACK and recipient ACK.
-        // Verify StatusChange effects progress through expected states.
-        let (alice_id, alice_secret) = keypair(20);
-        let (bob_id, bob_secret) = keypair(21);
+    fn hub_as_member_join_emits_local_member_joined_event() {
+        // When local node is BOTH hub and group creator, a remote Join should
+        // produce a local GroupMemberJoined event (not a SendEnvelope to self).
+        let (hub_id, hub_secret) = keypair(200);
+        let (bob_id, bob_secret) = keypair(201);
 
-        let mut alice_state = RuntimeState::new(
-            alice_id,
-            alice_secret,
+        let mut state = RuntimeState::new(
+            hub_id,
+            hub_secret,
             RuntimeConfig {
                 encryption: false,
                 ..Default::default()
             },
         );
 
-        // Send message from Alice to Bob
-        let send_effects = alice_state.handle_send_message(bob_id, b"hi bob".to_vec());
-        assert_eq!(send_effects.len(), 1);
-        let envelope = match &send_effects[0] {
-            RuntimeEffect::SendWithBackupFallback { envelope, on_success, .. } => {
-                // on_success should contain Pending and Sent status changes
-                let has_status = on_success.iter().any(|e| matches!(e, RuntimeEffect::StatusChange(_)));
-                assert!(has_status, "on_success should have StatusChange effects");
-                envelope.clone()
-            }
-            other => panic!("expected SendWithBackupFallback, got: {other:?}"),
-        };
-        let msg_id = envelope.id.clone();
-
-        // Simulate relay ACK (RelayForwarded)
-        use crate::router::{AckPayload, AckType};
-        let relay_id = node_id(22);
-        let relay_ack_payload = AckPayload {
-            original_message_id: msg_id.clone(),
-            ack_type: AckType::RelayForwarded,
-        };
-        let relay_ack_env = EnvelopeBuilder::new(
-            relay_id,
-            alice_id,
-            MessageType::Ack,
-            relay_ack_payload.to_bytes(),
-        )
-        .build();
-        // We use the relay_ack_env unsigned — that's fine, sig_valid=false
-        let relay_effects = alice_state.handle_incoming_chat(relay_ack_env, false);
-        let relay_status = relay_effects.iter().find_map(|e| {
-            if let RuntimeEffect::StatusChange(sc) = e { Some(sc) } else { None }
+        // Create group locally (hub=self)
+        let effects = state.handle_command(RuntimeCommand::CreateGroup {
+            name: "SelfHubTest".to_string(),
+            hub_relay_id: hub_id,
+            initial_members: vec![bob_id],
+            invite_only: false,
         });
-        assert!(relay_status.is_some(), "relay ACK should produce StatusChange, got: {relay_effects:?}");
-        let sc = relay_status.unwrap();
-        assert_eq!(sc.current, crate::types::MessageStatus::Relayed);
+        // Should emit GroupCreated event locally (no SendEnvelope to self)
+        let has_created_event = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::Emit(ProtocolEvent::GroupCreated { .. }))
+        });
+        assert!(
+            has_created_event,
+            "CreateGroup with hub=self should emit GroupCreated locally, got: {effects:?}"
+        );
+        // Should NOT have SendEnvelope to self
+        let has_self_send = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::SendEnvelope(env) if env.to == hub_id)
+        });
+        assert!(
+            !has_self_send,
+            "CreateGroup with hub=self should NOT produce SendEnvelope to self"
+        );
 
-        // Simulate recipient ACK (RecipientReceived)
-        let recipient_ack_payload = AckPayload {
-            original_message_id: msg_id.clone(),
-            ack_type: AckType::RecipientReceived,
+        // Get group_id
+        let gid = state.group_hub.groups().next().unwrap().0.clone();
+
+        // Bob joins via network
+        let join_payload = crate::group::GroupPayload::Join {
+            group_id: gid.clone(),
+            username: "bob".into(),
         };
-        let recipient_ack_env = EnvelopeBuilder::new(
+        let join_bytes = rmp_serde::to_vec(&join_payload).unwrap();
+        let join_env = EnvelopeBuilder::new(
             bob_id,
-            alice_id,
-            MessageType::Ack,
-            recipient_ack_payload.to_bytes(),
+            hub_id,
+            MessageType::GroupJoin,
+            join_bytes,
         )
         .sign(&bob_secret);
-        let sig_valid = recipient_ack_env.verify_signature().is_ok();
-        let recv_effects = alice_state.handle_incoming_chat(recipient_ack_env, sig_valid);
-        let recv_status = recv_effects.iter().find_map(|e| {
-            if let RuntimeEffect::StatusChange(sc) = e { Some(sc) } else { None }
+
+        let join_effects = state.handle_incoming_group(join_env);
+
+        // Should emit GroupMemberJoined event LOCALLY (via interception)
+        let has_member_joined = join_effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::Emit(ProtocolEvent::GroupMemberJoined { member, .. })
+                if member.node_id == bob_id)
+        });
+        assert!(
+            has_member_joined,
+            "Join when hub=self should emit local GroupMemberJoined, got: {join_effects:?}"
+        );
+
+        // Should NOT have SendEnvelope(GroupMemberJoined) to self
+        let has_member_joined_self_send = join_effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::SendEnvelope(env)
+                if env.to == hub_id && env.msg_type == MessageType::GroupMemberJoined)
         });
-        assert!(recv_status.is_some(), "recipient ACK should produce StatusChange, got: {recv_effects:?}");
-        let sc = recv_status.unwrap();
-        assert_eq!(sc.current, crate::types::MessageStatus::Delivered);
+        assert!(
+            !has_member_joined_self_send,
+            "Join when hub=self should NOT SendEnvelope(MemberJoined) to self"
+        );
+
+        // Should still send Sync to bob (over network)
+        let has_sync_to_bob = join_effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::SendEnvelope(env)
+                if env.to == bob_id && env.msg_type == MessageType::GroupSync)
+        });
+        assert!(
+            has_sync_to_bob,
+            "Join should send GroupSync to joiner (bob), got: {join_effects:?}"
+        );
     }
 
     #[test]
-    fn read_receipt_produces_status_read() {
-        // Track a message, then handle an incoming ReadReceipt envelope.
-        // Verify the StatusChange marks it as Read.
-        let (alice_id, alice_secret) = keypair(30);
-        let (bob_id, bob_secret) = keypair(31);
+    fn hub_as_member_group_message_fans_out_not_self_send() {
+        // When hub=self sends a group message, it should fan out to other
+        // members via network (not self-send to hub).
+        let (hub_id, hub_secret) = keypair(210);
+        let (bob_id, bob_secret) = keypair(211);
 
-        let mut alice_state = RuntimeState::new(
-            alice_id,
-            alice_secret,
+        let mut state = RuntimeState::new(
+            hub_id,
+            hub_secret,
             RuntimeConfig {
                 encryption: false,
                 ..Default::default()
             },
         );
 
-        // Send a message to get an envelope_id and track it
-        let send_effects = alice_state.handle_send_message(bob_id, b"read me".to_vec());
-        let envelope = match &send_effects[0] {
-            RuntimeEffect::SendWithBackupFallback { envelope, .. } => envelope.clone(),
-            other => panic!("expected SendWithBackupFallback, got: {other:?}"),
-        };
-        let msg_id = envelope.id.clone();
-
-        // Advance to Delivered first (Read requires Delivered or earlier)
-        alice_state.tracker.mark_delivered(&msg_id);
+        // Create group locally (hub=self)
+        state.handle_command(RuntimeCommand::CreateGroup {
+            name: "MsgTest".to_string(),
+            hub_relay_id: hub_id,
+            initial_members: vec![bob_id],
+            invite_only: false,
+        });
+        let gid = state.group_hub.groups().next().unwrap().0.clone();
 
-        // Build a ReadReceipt envelope from Bob
-        use crate::router::ReadReceiptPayload;
-        let rr_payload = ReadReceiptPayload {
-            original_message_id: msg_id.clone(),
-            read_at: crate::types::now_ms(),
+        // Bob joins via network (full flow to populate both hub and manager)
+        let join_payload = crate::group::GroupPayload::Join {
+            group_id: gid.clone(),
+            username: "bob".into(),
         };
-        let rr_env = EnvelopeBuilder::new(
+        let join_bytes = rmp_serde::to_vec(&join_payload).unwrap();
+        let join_env = EnvelopeBuilder::new(
             bob_id,
-            alice_id,
-            MessageType::ReadReceipt,
-            rr_payload.to_bytes(),
+            hub_id,
+            MessageType::GroupJoin,
+            join_bytes,
         )
         .sign(&bob_secret);
-        let sig_valid = rr_env.verify_signature().is_ok();
-        let effects = alice_state.handle_incoming_chat(rr_env, sig_valid);
+        state.handle_incoming_group(join_env);
 
-        let read_status = effects.iter().find_map(|e| {
-            if let RuntimeEffect::StatusChange(sc) = e { Some(sc) } else { None }
+        // Send group message from hub (who is also a member)
+        let effects = state.handle_command(RuntimeCommand::SendGroupMessage {
+            group_id: gid.clone(),
+            text: "hello from hub".to_string(),
         });
-        assert!(read_status.is_some(), "ReadReceipt should produce StatusChange, got: {effects:?}");
-        let sc = read_status.unwrap();
-        assert_eq!(sc.current, crate::types::MessageStatus::Read);
-        assert_eq!(sc.message_id, msg_id);
+
+        // Should send to bob (network)
+        let sends_to_bob: Vec<_> = effects.iter().filter(|e| {
+            matches!(e, RuntimeEffect::SendEnvelope(env)
+                if env.to == bob_id && env.msg_type == MessageType::GroupMessage)
+        }).collect();
+        assert!(
+            !sends_to_bob.is_empty(),
+            "Group message from hub should fan out to bob, got: {effects:?}"
+        );
+
+        // Should NOT self-send to hub
+        let self_sends: Vec<_> = effects.iter().filter(|e| {
+            matches!(e, RuntimeEffect::SendEnvelope(env)
+                if env.to == hub_id)
+        }).collect();
+        assert!(
+            self_sends.is_empty(),
+            "Group message from hub should NOT produce SendEnvelope to self, got self-sends: {self_sends:?}"
+        );
     }
 
     #[test]
-    fn group_create_produces_send_effects() {
-        // Call handle_command with CreateGroup. Verify it produces
-        // SendEnvelope effects (the GroupCreate payload to the hub).
-        let mut state = default_state(40);
-        let hub_id = node_id(41);
-        let member1 = node_id(42);
-        let member2 = node_id(43);
+    fn hub_backs_up_group_messages_for_offline_members() {
+        let (hub_id, hub_secret) = keypair(212);
+        let (bob_id, bob_secret) = keypair(213);
+        let config = || RuntimeConfig {
+            encryption: false,
+            ..Default::default()
+        };
+        let mut hub = RuntimeState::new(hub_id, hub_secret, config());
+        let mut bob = RuntimeState::new(bob_id, bob_secret, config());
 
-        let effects = state.handle_command(RuntimeCommand::CreateGroup {
-            name: "Test Group".to_string(),
+        hub.handle_command(RuntimeCommand::CreateGroup {
+            name: "Offline".to_string(),
             hub_relay_id: hub_id,
-            initial_members: vec![member1, member2],
+            initial_members: vec![bob_id],
             invite_only: false,
         });
-
-        // Should produce at least one SendEnvelope (the GroupCreate to hub)
-        let send_envelopes: Vec<_> = effects.iter().filter(|e| {
-            matches!(e, RuntimeEffect::SendEnvelope(env) if env.to == hub_id && env.msg_type == MessageType::GroupCreate)
-        }).collect();
-        assert!(
-            !send_envelopes.is_empty(),
-            "CreateGroup should produce SendEnvelope to hub, got: {effects:?}"
+        let gid = hub.group_hub.groups().next().unwrap().0.clone();
+        let join_bytes = rmp_serde::to_vec(&GroupPayload::Join {
+            group_id: gid.clone(),
+            username: "bob".into(),
+        })
+        .unwrap();
+        let join_effects = hub.handle_incoming_group(
+            EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupJoin, join_bytes)
+                .sign(&bob_secret),
         );
-
-        // Verify the envelope is signed and directed to the hub
-        if let RuntimeEffect::SendEnvelope(env) = &send_envelopes[0] {
-            assert!(env.is_signed(), "group create envelope should be signed");
-            assert_eq!(env.to, hub_id);
-            assert_eq!(env.from, state.local_id);
+        for effect in join_effects {
+            if let RuntimeEffect::SendEnvelope(env) = effect {
+                if env.to == bob_id {
+                    bob.handle_incoming_group(env);
+                }
+            }
         }
-    }
-
-    #[test]
-    fn peer_add_then_remove_cleans_state() {
-        // Add a peer via AddPeer, verify topology and heartbeat.
-        // Remove via RemovePeer, verify both are cleaned.
-        let mut state = default_state(50);
-        let peer = node_id(51);
+        assert!(bob.group_manager.get_group(&gid).is_some());
 
-        // Initially: not in topology or heartbeat
-        assert!(state.topology.get(&peer).is_none());
-        assert_eq!(state.heartbeat.liveness(&peer), crate::discovery::LivenessState::Departed);
+        // Bob drops off: the hub holds the message instead of sending it
+        hub.topology.upsert(PeerInfo {
+            node_id: bob_id,
+            role: PeerRole::Peer,
+            status: PeerStatus::Offline,
+            last_seen: 0,
+        });
+        let effects = hub.handle_command(RuntimeCommand::SendGroupMessage {
+            group_id: gid.clone(),
+            text: "while you were away".to_string(),
+        });
+        assert!(!effects.iter().any(|e| matches!(e,
+            RuntimeEffect::SendEnvelope(env) if env.to == bob_id)));
+        let held = hub.backup.store().get_for_recipient(&bob_id);
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].msg_type, MessageType::GroupMessage);
+
+        // Bob reconnects: the hub hands it over in a delivery batch
+        let effects = hub.prepare_backup_delivery(bob_id);
+        let deliver = match &effects[..] {
+            [RuntimeEffect::SendWithBackupFallback { envelope, .. }] => envelope.clone(),
+            other => panic!("expected one BackupDeliverBatch, got: {other:?}"),
+        };
+        assert_eq!(deliver.msg_type, MessageType::BackupDeliverBatch);
+
+        let raw = deliver.to_bytes().unwrap();
+        let effects = bob.handle_incoming(&raw);
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::GroupMessageReceived { message, .. })
+                if message.text == "while you were away")));
+        let confirm = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env)
+                    if env.to == hub_id && env.msg_type == MessageType::BackupConfirmDelivery =>
+                {
+                    Some(env.clone())
+                }
+                _ => None,
+            })
+            .expect("bob should confirm delivery to the hub");
 
-        // Add peer
-        state.handle_command(RuntimeCommand::AddPeer { node_id: peer });
-        assert!(state.topology.get(&peer).is_some(), "peer should be in topology after AddPeer");
-        assert_ne!(
-            state.heartbeat.liveness(&peer),
-            crate::discovery::LivenessState::Departed,
-            "peer should be tracked in heartbeat after AddPeer"
-        );
+        // A second copy (e.g. from another holder) isn't delivered twice
+        let effects = bob.handle_incoming(&raw);
+        assert!(!effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::GroupMessageReceived { .. }))));
 
-        // Remove peer
-        state.handle_command(RuntimeCommand::RemovePeer { node_id: peer });
-        assert!(
-            state.topology.get(&peer).is_none(),
-            "peer should be removed from topology after RemovePeer"
-        );
-        assert_eq!(
-            state.heartbeat.liveness(&peer),
-            crate::discovery::LivenessState::Departed,
-            "peer should be untracked from heartbeat after RemovePeer"
-        );
+        hub.handle_incoming_backup(&confirm);
+        assert!(hub.backup.store().get_for_recipient(&bob_id).is_empty());
     }
 
     #[test]
-    fn build_gossip_announce_roundtrip() {
-        // Build gossip announce bytes, deserialize them back,
-        // verify fields match (node_id, username, roles).
-        let (local_id, local_secret) = keypair(60);
-        let state = RuntimeState::new(
-            local_id,
-            local_secret,
+    fn banned_member_cannot_rejoin() {
+        let (hub_id, hub_secret) = keypair(207);
+        let (bob_id, bob_secret) = keypair(208);
+
+        let mut state = RuntimeState::new(
+            hub_id,
+            hub_secret,
             RuntimeConfig {
-                username: "alice_test".to_string(),
+                encryption: false,
                 ..Default::default()
             },
         );
+        state.handle_command(RuntimeCommand::CreateGroup {
+            name: "Bans".to_string(),
+            hub_relay_id: hub_id,
+            initial_members: vec![bob_id],
+            invite_only: false,
+        });
+        let gid = state.group_hub.groups().next().unwrap().0.clone();
+        let join = |state: &mut RuntimeState| {
+            let join_bytes = rmp_serde::to_vec(&GroupPayload::Join {
+                group_id: gid.clone(),
+                username: "bob".into(),
+            })
+            .unwrap();
+            state.handle_incoming_group(
+                EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupJoin, join_bytes)
+                    .sign(&bob_secret),
+            )
+        };
+        join(&mut state);
+        assert!(state.group_hub.get_group(&gid).unwrap().is_member(&bob_id));
 
-        let bytes = state.build_gossip_announce();
-        assert!(bytes.is_some(), "should produce announce bytes");
-        let bytes = bytes.unwrap();
-
-        let announce: PeerAnnounce =
-            rmp_serde::from_slice(&bytes).expect("should deserialize PeerAnnounce");
-        assert_eq!(announce.node_id, local_id);
-        assert_eq!(announce.username, "alice_test");
-        assert_eq!(announce.roles, vec![PeerRole::Peer]);
-        assert!(
-            announce.is_timestamp_valid(crate::types::now_ms()),
-            "announce timestamp should be valid at current time"
-        );
+        let effects = state.handle_command(RuntimeCommand::BanMember {
+            group_id: gid.clone(),
+            target_id: bob_id,
+            duration_ms: None,
+        });
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::SendEnvelope(env)
+                if env.to == bob_id && env.msg_type == MessageType::GroupMemberLeft)));
+        assert!(!state.group_hub.get_group(&gid).unwrap().is_member(&bob_id));
+
+        // Rejoin attempt: no sync sent back
+        let effects = join(&mut state);
+        assert!(!effects.iter().any(|e| matches!(e,
+            RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::GroupSync)));
+        assert!(!state.group_hub.get_group(&gid).unwrap().is_member(&bob_id));
+
+        state.handle_command(RuntimeCommand::UnbanMember {
+            group_id: gid.clone(),
+            target_id: bob_id,
+        });
+        join(&mut state);
+        assert!(state.group_hub.get_group(&gid).unwrap().is_member(&bob_id));
     }
 
     #[test]
-    fn handle_incoming_rejects_garbage_bytes() {
-        // Pass garbage bytes to handle_incoming(). Verify it returns
-        // empty effects (graceful handling, no panic).
-        let mut state = default_state(70);
+    fn hub_as_member_is_notified_of_mentions() {
+        let (hub_id, hub_secret) = keypair(209);
+        let (bob_id, bob_secret) = keypair(210);
 
-        let garbage = b"this is not valid msgpack at all!!! \x00\xff\xfe";
-        let effects = state.handle_incoming(garbage);
-        assert!(
-            effects.is_empty(),
-            "garbage input should produce no effects (graceful drop), got: {effects:?}"
+        let mut state = RuntimeState::new(
+            hub_id,
+            hub_secret,
+            RuntimeConfig {
+                encryption: false,
+                ..Default::default()
+            },
         );
-
-        // Also test empty bytes
-        let effects = state.handle_incoming(&[]);
-        assert!(
-            effects.is_empty(),
-            "empty input should produce no effects, got: {effects:?}"
+        state.handle_command(RuntimeCommand::CreateGroup {
+            name: "Mentions".to_string(),
+            hub_relay_id: hub_id,
+            initial_members: vec![bob_id],
+            invite_only: false,
+        });
+        let gid = state.group_hub.groups().next().unwrap().0.clone();
+        let join_bytes = rmp_serde::to_vec(&GroupPayload::Join {
+            group_id: gid.clone(),
+            username: "bob".into(),
+        })
+        .unwrap();
+        state.handle_incoming_group(
+            EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupJoin, join_bytes)
+                .sign(&bob_secret),
         );
 
-        // Also test partially valid but corrupted msgpack
-        let effects = state.handle_incoming(&[0x93, 0x01, 0x02]);
-        assert!(
-            effects.is_empty(),
-            "corrupted msgpack should produce no effects, got: {effects:?}"
+        // Our mention of bob reaches him intact
+        let effects = state.handle_command(RuntimeCommand::SendGroupMention {
+            group_id: gid.clone(),
+            text: "@bob standup?".to_string(),
+            mentions: vec![bob_id],
+        });
+        let sent = effects.iter().find_map(|e| match e {
+            RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::GroupMessage => {
+                match rmp_serde::from_slice(&env.payload) {
+                    Ok(GroupPayload::Message(msg)) => Some(msg),
+                    _ => None,
+                }
+            }
+            _ => None,
+        });
+        assert_eq!(sent.unwrap().mentions, vec![bob_id]);
+
+        // Bob mentions us back: we get a notification hint
+        let mut msg = GroupMessage::new(gid.clone(), bob_id, "bob".into(), "@hub sure".into())
+            .with_mentions(vec![hub_id]);
+        msg.sign(&bob_secret);
+        let bytes = rmp_serde::to_vec(&GroupPayload::Message(msg)).unwrap();
+        let effects = state.handle_incoming_group(
+            EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupMessage, bytes)
+                .sign(&bob_secret),
         );
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::GroupMentioned { sender_id, .. })
+                if *sender_id == bob_id)));
     }
 
     #[test]
-    fn send_message_unencrypted_when_config_disabled() {
-        // Create state with config.encryption = false. Call handle_send_message.
-        // Verify the envelope is NOT encrypted.
-        let (local_id, local_secret) = keypair(80);
+    fn group_reply_carries_reference_to_members() {
+        let (hub_id, hub_secret) = keypair(213);
+        let (bob_id, bob_secret) = keypair(214);
+
         let mut state = RuntimeState::new(
-            local_id,
-            local_secret,
+            hub_id,
+            hub_secret,
             RuntimeConfig {
                 encryption: false,
                 ..Default::default()
             },
         );
-        let recipient = node_id(81);
+        state.handle_command(RuntimeCommand::CreateGroup {
+            name: "Threads".to_string(),
+            hub_relay_id: hub_id,
+            initial_members: vec![bob_id],
+            invite_only: false,
+        });
+        let gid = state.group_hub.groups().next().unwrap().0.clone();
+        let join_bytes = rmp_serde::to_vec(&GroupPayload::Join {
+            group_id: gid.clone(),
+            username: "bob".into(),
+        })
+        .unwrap();
+        state.handle_incoming_group(
+            EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupJoin, join_bytes)
+                .sign(&bob_secret),
+        );
 
-        let effects = state.handle_send_message(recipient, b"plaintext msg".to_vec());
-        assert_eq!(effects.len(), 1);
+        let fanned_out = |effects: &[RuntimeEffect]| {
+            effects
+                .iter()
+                .find_map(|e| match e {
+                    RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::GroupMessage => {
+                        match rmp_serde::from_slice(&env.payload) {
+                            Ok(GroupPayload::Message(msg)) => Some(msg),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                })
+                .expect("message fanned out to bob")
+        };
 
-        match &effects[0] {
-            RuntimeEffect::SendWithBackupFallback { envelope, .. } => {
-                assert!(
-                    !envelope.encrypted,
-                    "envelope should NOT be encrypted when config.encryption=false"
-                );
-                assert!(envelope.is_signed(), "envelope should still be signed");
-                assert_eq!(envelope.to, recipient);
-                assert_eq!(envelope.from, local_id);
-                // Payload should be the original plaintext (not ciphertext)
-                assert_eq!(envelope.payload, b"plaintext msg");
-            }
-            other => panic!("expected SendWithBackupFallback, got: {other:?}"),
-        }
-    }
+        let effects = state.handle_command(RuntimeCommand::SendGroupMessage {
+            group_id: gid.clone(),
+            text: "lunch?".to_string(),
+        });
+        let original = fanned_out(&effects);
+        assert_eq!(original.reply_to, None);
 
-    // ── Task 5 (failover): shadow auto-assignment ────────────────────
+        let effects = state.handle_command(RuntimeCommand::SendGroupReply {
+            group_id: gid.clone(),
+            reply_to: original.message_id.clone(),
+            text: "12:30".to_string(),
+        });
+        let reply = fanned_out(&effects);
+        assert_eq!(reply.reply_to, Some(original.message_id));
+        assert!(reply.verify_signature());
+    }
 
     #[test]
-    fn handle_incoming_group_create_triggers_shadow_assignment() {
-        // When the hub processes a GroupCreate, it should automatically
-        // call assign_shadow after the group is created.
-        let (hub_id, hub_secret) = keypair(90);
-        let (alice_id, alice_secret) = keypair(91);
-        let (bob_id, bob_secret) = keypair(92);
+    fn hub_as_member_fans_out_reactions() {
+        let (hub_id, hub_secret) = keypair(211);
+        let (bob_id, bob_secret) = keypair(212);
 
-        let mut hub_state = RuntimeState::new(
+        let mut state = RuntimeState::new(
             hub_id,
             hub_secret,
             RuntimeConfig {
@@ -3160,402 +10229,482 @@ mod tests {
                 ..Default::default()
             },
         );
-
-        // Build a GroupCreate envelope from Alice to the hub
-        let create_payload = crate::group::GroupPayload::Create {
-            group_name: "Shadow Auto Test".into(),
-            creator_username: "alice".into(),
+        state.handle_command(RuntimeCommand::CreateGroup {
+            name: "Reactions".to_string(),
+            hub_relay_id: hub_id,
             initial_members: vec![bob_id],
             invite_only: false,
-        };
-        let payload_bytes = rmp_serde::to_vec(&create_payload).unwrap();
-        let create_env = EnvelopeBuilder::new(
-            alice_id,
-            hub_id,
-            MessageType::GroupCreate,
-            payload_bytes,
-        )
-        .sign(&alice_secret);
-
-        let effects = hub_state.handle_incoming_group(create_env);
-
-        // After Create, the only member is Alice (the creator).
-        // assign_shadow should have been called — it picks the lowest non-hub member.
-        // Since Alice is the only member and she is not the hub, she becomes shadow.
-        let has_shadow_sync = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::GroupHubShadowSync)
         });
-        assert!(
-            has_shadow_sync,
-            "GroupCreate should trigger shadow assignment and send HubShadowSync, got: {effects:?}"
-        );
-
-        // Now Bob joins — shadow should be reassigned/updated
-        let gid = hub_state.group_hub.groups().next().unwrap().0.clone();
-        let join_payload = crate::group::GroupPayload::Join {
+        let gid = state.group_hub.groups().next().unwrap().0.clone();
+        let join_bytes = rmp_serde::to_vec(&GroupPayload::Join {
             group_id: gid.clone(),
             username: "bob".into(),
-        };
-        let join_bytes = rmp_serde::to_vec(&join_payload).unwrap();
-        let join_env = EnvelopeBuilder::new(
-            bob_id,
-            hub_id,
-            MessageType::GroupJoin,
-            join_bytes,
-        )
-        .sign(&bob_secret);
-
-        let join_effects = hub_state.handle_incoming_group(join_env);
+        })
+        .unwrap();
+        state.handle_incoming_group(
+            EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupJoin, join_bytes)
+                .sign(&bob_secret),
+        );
 
-        // After join, assign_shadow should run again
-        let has_shadow_sync_after_join = join_effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::GroupHubShadowSync)
+        let effects = state.handle_command(RuntimeCommand::SendGroupMessage {
+            group_id: gid.clone(),
+            text: "release today?".to_string(),
         });
-        assert!(
-            has_shadow_sync_after_join,
-            "GroupJoin should trigger shadow reassignment, got: {join_effects:?}"
-        );
+        let message_id = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::GroupMessage => {
+                    match rmp_serde::from_slice(&env.payload) {
+                        Ok(GroupPayload::Message(msg)) => Some(msg.message_id),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .unwrap();
 
-        // Verify the group now has a shadow_id set
-        let group = hub_state.group_hub.get_group(&gid).unwrap();
-        assert!(
-            group.shadow_id.is_some(),
-            "group should have a shadow after join"
+        // Bob reacts: the hub fans out to him and updates its own member view.
+        let reaction = rmp_serde::to_vec(&GroupPayload::Reaction {
+            group_id: gid.clone(),
+            message_id: message_id.clone(),
+            emoji: "🚀".into(),
+            add: true,
+            reactor: bob_id,
+        })
+        .unwrap();
+        let effects = state.handle_incoming_group(
+            EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupReaction, reaction.clone())
+                .sign(&bob_secret),
         );
-    }
-
-    // ── Bandwidth tracking tests ────────────────────────────────────────
-
-    #[test]
-    fn role_manager_bandwidth_tracking_via_runtime_state() {
-        let mut state = default_state(1);
-        let peer = node_id(2);
-
-        // Directly record bandwidth through the role_manager
-        state.role_manager.record_relay(peer, 1000);
-        state
-            .role_manager
-            .record_bytes_relayed(peer, 50 * 1_048_576, 1000); // 50 MB
-
-        let score = state.role_manager.score(&peer, 1000);
-        // Score should include bandwidth: relay(1) + success(5) + bandwidth_mb(50*0.2=10) = 16+
-        assert!(
-            score > 15.0,
-            "Score should reflect bandwidth contribution, got {score}"
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::SendEnvelope(env)
+                if env.to == bob_id && env.msg_type == MessageType::GroupReaction)));
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::GroupReactionChanged { reactor, count: 1, .. })
+                if *reactor == bob_id)));
+
+        // Our own reaction goes straight to our hub.
+        let effects = state.handle_command(RuntimeCommand::ReactToGroupMessage {
+            group_id: gid.clone(),
+            message_id: message_id.clone(),
+            emoji: "🚀".into(),
+            add: true,
+        });
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::GroupReactionChanged { count: 2, .. }))));
+        assert_eq!(
+            state.group_manager.reaction_counts(&gid, &message_id),
+            vec![("🚀".to_string(), 2)]
         );
     }
 
     #[test]
-    fn local_role_change_broadcasts_announce() {
-        let mut state = default_state(1);
-
-        // Simulate local promotion
-        let action = RoleAction::LocalRoleChanged {
-            new_role: PeerRole::Relay,
-        };
-
-        let effects = state.surface_role_action(&action);
+    fn hub_as_member_tracks_group_delivery_receipts() {
+        let (hub_id, hub_secret) = keypair(215);
+        let (bob_id, bob_secret) = keypair(216);
 
-        // Should emit event + broadcast announce
-        assert!(
-            effects
-                .iter()
-                .any(|e| matches!(e, RuntimeEffect::Emit(ProtocolEvent::LocalRoleChanged { .. }))),
-            "Should emit LocalRoleChanged event"
+        let mut state = RuntimeState::new(
+            hub_id,
+            hub_secret,
+            RuntimeConfig {
+                encryption: false,
+                ..Default::default()
+            },
         );
-        assert!(
-            effects
-                .iter()
-                .any(|e| matches!(e, RuntimeEffect::BroadcastRoleChange(_))),
-            "Should broadcast role change announce"
+        state.handle_command(RuntimeCommand::CreateGroup {
+            name: "Receipts".to_string(),
+            hub_relay_id: hub_id,
+            initial_members: vec![bob_id],
+            invite_only: false,
+        });
+        let gid = state.group_hub.groups().next().unwrap().0.clone();
+        let join_bytes = rmp_serde::to_vec(&GroupPayload::Join {
+            group_id: gid.clone(),
+            username: "bob".into(),
+        })
+        .unwrap();
+        state.handle_incoming_group(
+            EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupJoin, join_bytes)
+                .sign(&bob_secret),
         );
-    }
 
-    #[test]
-    fn handle_role_announce_updates_topology() {
-        use crate::discovery::RoleChangeAnnounce;
+        // Our message reaches bob; his ack comes back as a receipt.
+        let effects = state.handle_command(RuntimeCommand::SendGroupMessage {
+            group_id: gid.clone(),
+            text: "seen?".to_string(),
+        });
+        let message_id = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::GroupMessage => {
+                    match rmp_serde::from_slice(&env.payload) {
+                        Ok(GroupPayload::Message(msg)) => Some(msg.message_id),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .expect("message fanned out to bob");
 
-        let mut state = default_state(1);
-        let (remote, remote_seed) = keypair(2);
+        let ack_bytes = rmp_serde::to_vec(&GroupPayload::DeliveryAck {
+            group_id: gid.clone(),
+            message_id: message_id.clone(),
+            read: true,
+        })
+        .unwrap();
+        let effects = state.handle_incoming_group(
+            EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupDeliveryAck, ack_bytes)
+                .sign(&bob_secret),
+        );
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::GroupDeliveryUpdated { member, read: true, .. })
+                if *member == bob_id)));
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::GroupMessageDeliveredToAll { message_id: m, .. })
+                if *m == message_id)));
 
-        let announce = RoleChangeAnnounce::new(
-            remote,
-            PeerRole::Relay,
-            15.0,
-            now_ms(),
-            &remote_seed,
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        state.handle_command(RuntimeCommand::GetGroupDeliveryStatus {
+            group_id: gid.clone(),
+            message_id,
+            reply: tx,
+        });
+        let status = rx.try_recv().unwrap().expect("status tracked");
+        assert_eq!(status.read, vec![bob_id]);
+        assert!(status.all_delivered);
+
+        // Bob's message is auto-acked by our local member, receipt goes to bob.
+        let mut msg = GroupMessage::new(gid.clone(), bob_id, "bob".into(), "hi hub".into());
+        msg.sign(&bob_secret);
+        let msg_bytes = rmp_serde::to_vec(&GroupPayload::Message(msg)).unwrap();
+        let effects = state.handle_incoming_group(
+            EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupMessage, msg_bytes)
+                .sign(&bob_secret),
         );
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::SendEnvelope(env)
+                if env.to == bob_id && env.msg_type == MessageType::GroupDeliveryReceipt)));
+    }
 
-        let effects = state.handle_role_announce(announce);
+    #[test]
+    fn hub_heartbeat_does_not_self_send() {
+        // When hub=self ticks heartbeat, it should broadcast to other members
+        // but NOT produce a SendEnvelope to self.
+        let (hub_id, hub_secret) = keypair(220);
+        let (bob_id, bob_secret) = keypair(221);
 
-        // Should emit RolePromoted
-        assert!(
-            effects.iter().any(|e| matches!(
-                e,
-                RuntimeEffect::Emit(ProtocolEvent::RolePromoted { node_id, .. })
-                if *node_id == remote
-            )),
-            "Should emit RolePromoted: {effects:?}"
+        let mut state = RuntimeState::new(
+            hub_id,
+            hub_secret,
+            RuntimeConfig {
+                encryption: false,
+                ..Default::default()
+            },
         );
 
-        // Topology should be updated
-        let peer = state.topology.get(&remote).expect("peer in topology");
-        assert_eq!(peer.role, PeerRole::Relay);
-    }
+        // Create group locally (hub=self)
+        state.handle_command(RuntimeCommand::CreateGroup {
+            name: "HBTest".to_string(),
+            hub_relay_id: hub_id,
+            initial_members: vec![bob_id],
+            invite_only: false,
+        });
+        let gid = state.group_hub.groups().next().unwrap().0.clone();
 
-    #[test]
-    fn handle_role_announce_throttle() {
-        use crate::discovery::RoleChangeAnnounce;
+        // Bob joins
+        let join_payload = crate::group::GroupPayload::Join {
+            group_id: gid.clone(),
+            username: "bob".into(),
+        };
+        let join_bytes = rmp_serde::to_vec(&join_payload).unwrap();
+        let join_env = EnvelopeBuilder::new(
+            bob_id,
+            hub_id,
+            MessageType::GroupJoin,
+            join_bytes,
+        )
+        .sign(&bob_secret);
+        state.handle_incoming_group(join_env);
 
-        let mut state = default_state(1);
-        let (remote, remote_seed) = keypair(2);
+        // Tick heartbeat
+        let effects = state.tick_group_hub_heartbeat();
 
-        let announce1 = RoleChangeAnnounce::new(
-            remote, PeerRole::Relay, 15.0, now_ms(), &remote_seed,
-        );
-        let announce2 = RoleChangeAnnounce::new(
-            remote, PeerRole::Peer, 1.0, now_ms(), &remote_seed,
+        // Should send heartbeat to bob (network)
+        let sends_to_bob: Vec<_> = effects
+            .iter()
+            .filter(|e| {
+                matches!(e, RuntimeEffect::SendEnvelope(env)
+                    if env.to == bob_id && env.msg_type == MessageType::GroupHubHeartbeat)
+            })
+            .collect();
+        assert!(
+            !sends_to_bob.is_empty(),
+            "Heartbeat should reach bob, got: {effects:?}"
         );
 
-        // First announce accepted
-        let effects1 = state.handle_role_announce(announce1);
-        assert!(!effects1.is_empty(), "First announce should be accepted");
-
-        // Second announce within 30s throttled
-        let effects2 = state.handle_role_announce(announce2);
-        assert!(effects2.is_empty(), "Second announce should be throttled");
+        // Should NOT self-send heartbeat to hub
+        let self_sends: Vec<_> = effects
+            .iter()
+            .filter(|e| {
+                matches!(e, RuntimeEffect::SendEnvelope(env)
+                    if env.to == hub_id)
+            })
+            .collect();
+        assert!(
+            self_sends.is_empty(),
+            "Heartbeat should NOT produce SendEnvelope to self, got: {self_sends:?}"
+        );
     }
 
     #[test]
-    fn handle_role_announce_rejects_invalid_signature() {
-        use crate::discovery::RoleChangeAnnounce;
+    fn hub_as_admin_metadata_update_emits_and_broadcasts() {
+        let (hub_id, hub_secret) = keypair(220);
+        let (bob_id, bob_secret) = keypair(221);
 
-        let mut state = default_state(1);
-        let remote = node_id(2);
+        let mut state = RuntimeState::new(
+            hub_id,
+            hub_secret,
+            RuntimeConfig {
+                encryption: false,
+                ..Default::default()
+            },
+        );
 
-        // Sign with WRONG key (node 3's key, not node 2's)
-        let (_, wrong_seed) = keypair(3);
+        state.handle_command(RuntimeCommand::CreateGroup {
+            name: "Before".to_string(),
+            hub_relay_id: hub_id,
+            initial_members: vec![bob_id],
+            invite_only: false,
+        });
+        let gid = state.group_hub.groups().next().unwrap().0.clone();
 
-        let announce = RoleChangeAnnounce::new(
-            remote, PeerRole::Relay, 15.0, now_ms(), &wrong_seed,
-        );
+        let join_bytes = rmp_serde::to_vec(&crate::group::GroupPayload::Join {
+            group_id: gid.clone(),
+            username: "bob".into(),
+        })
+        .unwrap();
+        let join_env = EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupJoin, join_bytes)
+            .sign(&bob_secret);
+        state.handle_incoming_group(join_env);
 
-        let effects = state.handle_role_announce(announce);
+        let effects = state.handle_command(RuntimeCommand::UpdateGroupMetadata {
+            group_id: gid.clone(),
+            name: "After".to_string(),
+            description: "Renamed".to_string(),
+            avatar_hash: None,
+        });
 
-        // Should emit Error
-        assert!(
-            effects.iter().any(|e| matches!(
-                e,
-                RuntimeEffect::Emit(ProtocolEvent::Error { description })
-                if description.contains("Invalid signature")
-            )),
-            "Should reject invalid signature: {effects:?}"
-        );
+        let has_local_event = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::Emit(ProtocolEvent::GroupMetadataChanged { name, config_version: 1, .. })
+                if name == "After")
+        });
+        assert!(has_local_event, "admin=hub should see GroupMetadataChanged, got: {effects:?}");
 
-        // Topology should NOT be updated
-        assert!(state.topology.get(&remote).is_none());
+        let has_broadcast_to_bob = effects.iter().any(|e| {
+            matches!(e, RuntimeEffect::SendEnvelope(env)
+                if env.to == bob_id && env.msg_type == MessageType::GroupMetadataChanged)
+        });
+        assert!(has_broadcast_to_bob, "metadata change should be broadcast to bob");
+        assert_eq!(state.group_manager.get_group(&gid).unwrap().name, "After");
     }
 
-    // ── r4: Role validation integration tests ───────────────────────────
-
     #[test]
-    fn tick_roles_promotes_active_peer() {
-        let mut state = default_state(1);
-        let peer = node_id(2);
-        let now = now_ms();
+    fn channel_join_publish_and_receive() {
+        let mut alice = default_state(1);
+        let mut bob = default_state(2);
 
-        // Register peer in topology
-        state.topology.upsert(PeerInfo {
-            node_id: peer,
-            role: PeerRole::Peer,
-            status: PeerStatus::Online,
-            last_seen: now,
+        let effects = alice.handle_command(RuntimeCommand::JoinChannel {
+            name: "sensors".into(),
+            secret: None,
+        });
+        let topic = match effects.as_slice() {
+            [RuntimeEffect::SubscribeChannel { topic }] => *topic,
+            other => panic!("expected SubscribeChannel, got {other:?}"),
+        };
+        bob.handle_command(RuntimeCommand::JoinChannel {
+            name: "sensors".into(),
+            secret: None,
         });
 
-        // Simulate 20 relays (enough to exceed PROMOTION_THRESHOLD=10.0)
-        for i in 0..20 {
-            state.role_manager.record_relay(peer, now + i * 1000);
-        }
+        let effects = bob.handle_command(RuntimeCommand::PublishChannel {
+            name: "sensors".into(),
+            payload: b"21C".to_vec(),
+        });
+        let bytes = match effects.into_iter().next() {
+            Some(RuntimeEffect::PublishChannel { topic: t, bytes }) if t == topic => bytes,
+            other => panic!("expected PublishChannel, got {other:?}"),
+        };
 
-        let effects = state.tick_roles();
+        let effects = alice.handle_channel_publication(topic, &bytes);
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::ChannelMessage { channel, from, payload, content_type: None, .. })
+                if channel == "sensors" && *from == bob.local_id && payload == b"21C")));
 
-        let has_promoted = effects.iter().any(|e| {
-            matches!(
-                e,
-                RuntimeEffect::Emit(ProtocolEvent::RolePromoted { node_id, .. })
-                if *node_id == peer
-            )
-        });
-        assert!(
-            has_promoted,
-            "expected RolePromoted after 20 relays, got: {effects:?}"
-        );
+        // Our own publications echoed back are ignored
+        assert!(bob.handle_channel_publication(topic, &bytes).is_empty());
 
-        // Topology should now show Relay
-        assert_eq!(state.topology.get(&peer).unwrap().role, PeerRole::Relay);
+        let effects = alice.handle_command(RuntimeCommand::LeaveChannel { name: "sensors".into() });
+        assert!(matches!(effects.as_slice(), [RuntimeEffect::UnsubscribeChannel { topic: t }] if *t == topic));
+        assert!(alice.handle_channel_publication(topic, &bytes).is_empty());
     }
 
     #[test]
-    fn tick_roles_demotes_idle_relay() {
+    fn channel_publish_without_join_emits_error() {
         let mut state = default_state(1);
-        let peer = node_id(2);
-        let now = now_ms();
-
-        // Register and promote peer
-        state.topology.upsert(PeerInfo {
-            node_id: peer,
-            role: PeerRole::Peer,
-            status: PeerStatus::Online,
-            last_seen: now,
+        let effects = state.handle_command(RuntimeCommand::PublishChannel {
+            name: "nowhere".into(),
+            payload: vec![1],
         });
-        for i in 0..20 {
-            state.role_manager.record_relay(peer, now + i * 1000);
-        }
-        let _ = state.tick_roles(); // Promotes
-        assert_eq!(state.topology.get(&peer).unwrap().role, PeerRole::Relay);
-
-        // 100 hours of idleness — score should decay below DEMOTION_THRESHOLD=2.0
-        let future = now + 100 * 3_600_000;
-        let score = state.role_manager.score(&peer, future);
-        assert!(
-            score < 2.0,
-            "score should be below demotion threshold after 100h idle: {score}"
-        );
-
-        // tick_roles uses now_ms() (can't fake time), so test via evaluate directly
-        let actions = state.role_manager.evaluate(&mut state.topology, future);
-        assert!(
-            actions.iter().any(|a| matches!(
-                a,
-                crate::roles::RoleAction::Demoted { node_id, .. }
-                if *node_id == peer
-            )),
-            "expected demotion after 100h idle: {actions:?}"
-        );
-        assert_eq!(state.topology.get(&peer).unwrap().role, PeerRole::Peer);
+        assert!(matches!(effects.as_slice(), [RuntimeEffect::Emit(ProtocolEvent::Error { .. })]));
     }
 
     #[test]
-    fn get_role_metrics_via_command() {
-        let mut state = default_state(1);
-        let peer = node_id(2);
-        let now = now_ms();
-
-        state.topology.upsert(PeerInfo {
-            node_id: peer,
-            role: PeerRole::Peer,
-            status: PeerStatus::Online,
-            last_seen: now,
+    fn shared_doc_update_syncs_between_listed_peers_only() {
+        let mut alice = default_state(1);
+        let mut bob = default_state(2);
+        let mut eve = default_state(3);
+        let doc = "board".to_string();
+
+        bob.handle_command(RuntimeCommand::OpenSharedDoc {
+            doc_id: doc.clone(),
+            peers: vec![alice.local_id],
+        });
+        alice.handle_command(RuntimeCommand::OpenSharedDoc {
+            doc_id: doc.clone(),
+            peers: vec![bob.local_id],
         });
 
-        // Record some activity
-        for i in 0..5 {
-            state.role_manager.record_relay(peer, now + i * 1000);
-        }
-        state.role_manager.record_bytes_relayed(peer, 10 * 1_048_576, now + 5000);
-
-        // Query via command handler
-        let (tx, mut rx) = tokio::sync::oneshot::channel();
-        let effects = state.handle_command(RuntimeCommand::GetRoleMetrics {
-            node_id: peer,
-            reply: tx,
+        let effects = alice.handle_command(RuntimeCommand::SetSharedValue {
+            doc_id: doc.clone(),
+            key: "title".into(),
+            value: Some(b"Roadmap".to_vec()),
         });
-        assert!(effects.is_empty(), "GetRoleMetrics should not emit effects");
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::SharedStateChanged { key, .. }) if key == "title")));
+        let update = effects
+            .into_iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env)
+                    if env.msg_type == MessageType::SharedStateUpdate =>
+                {
+                    Some(env)
+                }
+                _ => None,
+            })
+            .expect("update should be sent to bob");
+        assert_eq!(update.to, bob.local_id);
 
-        let metrics = rx.try_recv().expect("should receive response");
-        let metrics = metrics.expect("metrics should exist for tracked peer");
+        let effects = bob.handle_incoming_shared_state(update, true);
+        assert!(effects.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::SharedStateChanged { value: Some(v), updated_by, .. })
+                if v == b"Roadmap" && *updated_by == alice.local_id)));
+        assert_eq!(
+            bob.shared_state().get(&doc).unwrap().map.get("title"),
+            Some(&b"Roadmap"[..])
+        );
 
-        assert_eq!(metrics.node_id, peer);
-        assert_eq!(metrics.role, PeerRole::Peer);
-        assert_eq!(metrics.relay_count, 5);
-        assert_eq!(metrics.relay_failures, 0);
-        assert!(metrics.score > 0.0, "score should be positive");
-        assert_eq!(metrics.bytes_relayed, 10 * 1_048_576);
-        assert!(
-            (metrics.success_rate - 1.0).abs() < f64::EPSILON,
-            "100% success rate"
+        // Eve isn't in bob's peer set: her writes are ignored.
+        eve.handle_command(RuntimeCommand::OpenSharedDoc {
+            doc_id: doc.clone(),
+            peers: vec![bob.local_id],
+        });
+        let forged = eve
+            .handle_command(RuntimeCommand::SetSharedValue {
+                doc_id: doc.clone(),
+                key: "title".into(),
+                value: Some(b"pwned".to_vec()),
+            })
+            .into_iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env) => Some(env),
+                _ => None,
+            })
+            .unwrap();
+        assert!(bob.handle_incoming_shared_state(forged, true).is_empty());
+        assert_eq!(
+            bob.shared_state().get(&doc).unwrap().map.get("title"),
+            Some(&b"Roadmap"[..])
         );
     }
 
-    #[test]
-    fn get_all_role_scores_via_command() {
-        let mut state = default_state(1);
-        let peer_a = node_id(2);
-        let peer_b = node_id(3);
-        let now = now_ms();
-
-        for &peer in &[peer_a, peer_b] {
-            state.topology.upsert(PeerInfo {
-                node_id: peer,
-                role: PeerRole::Peer,
-                status: PeerStatus::Online,
-                last_seen: now,
-            });
+    /// Hand every envelope in `effects` to its recipient's mesh handler until quiet.
+    fn pump_mesh(states: &mut [&mut RuntimeState], effects: Vec<RuntimeEffect>) -> Vec<RuntimeEffect> {
+        let mut emitted = Vec::new();
+        let mut pending = effects;
+        while let Some(effect) = pending.pop() {
+            match effect {
+                RuntimeEffect::SendEnvelope(env) => {
+                    if let Some(target) = states.iter_mut().find(|s| s.local_id == env.to) {
+                        pending.extend(target.handle_incoming_mesh(env, true));
+                    }
+                }
+                other => emitted.push(other),
+            }
         }
+        emitted
+    }
 
-        // Only peer_a has relay activity
-        state.role_manager.record_relay(peer_a, now);
+    #[test]
+    fn mesh_group_fans_out_without_hub() {
+        let mut alice = default_state(200);
+        let mut bob = default_state(201);
+        let mut carol = default_state(202);
 
         let (tx, mut rx) = tokio::sync::oneshot::channel();
-        let effects = state.handle_command(RuntimeCommand::GetAllRoleScores {
+        let effects = alice.handle_command(RuntimeCommand::CreateMeshGroup {
+            name: "trio".into(),
+            members: vec![bob.local_id, carol.local_id],
             reply: tx,
         });
-        assert!(effects.is_empty());
-
-        let scores = rx.try_recv().expect("should receive response");
-        assert_eq!(scores.len(), 2, "should list both peers");
-
-        let a_entry = scores.iter().find(|(id, _, _)| *id == peer_a);
-        let b_entry = scores.iter().find(|(id, _, _)| *id == peer_b);
-
-        assert!(a_entry.is_some(), "peer_a should be in scores");
-        assert!(b_entry.is_some(), "peer_b should be in scores");
-
-        let (_, a_score, _) = a_entry.unwrap();
-        let (_, b_score, _) = b_entry.unwrap();
-        assert!(a_score > b_score, "active peer should score higher");
-    }
-
-    #[test]
-    fn bandwidth_tracking_via_role_metrics_command() {
-        let mut state = default_state(1);
-        let relay = node_id(2);
-        let now = now_ms();
-
-        state.topology.upsert(PeerInfo {
-            node_id: relay,
-            role: PeerRole::Peer,
-            status: PeerStatus::Online,
-            last_seen: now,
+        let group_id = rx.try_recv().unwrap().expect("mesh group created");
+        let emitted = pump_mesh(&mut [&mut alice, &mut bob, &mut carol], effects);
+        assert!(emitted.iter().any(|e| matches!(e,
+            RuntimeEffect::Emit(ProtocolEvent::GroupJoined { group_name, .. }) if group_name == "trio")));
+        assert_eq!(bob.mesh_groups().get(&group_id).unwrap().members().len(), 3);
+
+        // Regular send command goes straight to both members, no hub involved
+        let effects = bob.handle_command(RuntimeCommand::SendGroupMessage {
+            group_id: group_id.clone(),
+            text: "no hub here".into(),
         });
+        assert!(effects.iter().all(|e| matches!(e,
+            RuntimeEffect::SendEnvelope(env) if env.msg_type == MessageType::GroupMeshMessage)));
+        assert_eq!(effects.len(), 2);
+        let emitted = pump_mesh(&mut [&mut alice, &mut bob, &mut carol], effects);
+        let received = emitted
+            .iter()
+            .filter(|e| matches!(e,
+                RuntimeEffect::Emit(ProtocolEvent::GroupMessageReceived { message, .. })
+                    if message.text == "no hub here"))
+            .count();
+        assert_eq!(received, 2);
+
+        // Unsigned envelopes are dropped
+        let effects = carol.handle_command(RuntimeCommand::SendGroupMessage {
+            group_id: group_id.clone(),
+            text: "x".into(),
+        });
+        let RuntimeEffect::SendEnvelope(env) = effects.into_iter().next().unwrap() else {
+            panic!("expected envelope");
+        };
+        assert!(alice.handle_incoming_mesh(env, false).is_empty());
 
-        state.role_manager.record_bytes_relayed(relay, 100 * 1_048_576, now);
-        state.role_manager.record_bytes_received(relay, 50 * 1_048_576, now);
-
+        // Over the member cap is refused
+        let others: Vec<NodeId> = (203..211).map(|s| keypair(s).0).collect();
         let (tx, mut rx) = tokio::sync::oneshot::channel();
-        state.handle_command(RuntimeCommand::GetRoleMetrics {
-            node_id: relay,
+        alice.handle_command(RuntimeCommand::CreateMeshGroup {
+            name: "crowd".into(),
+            members: others,
             reply: tx,
         });
-
-        let metrics = rx.try_recv().unwrap().unwrap();
-        assert_eq!(metrics.bytes_relayed, 100 * 1_048_576);
-        assert_eq!(metrics.bytes_received, 50 * 1_048_576);
-        assert!(
-            (metrics.bandwidth_ratio - 2.0).abs() < f64::EPSILON,
-            "bandwidth ratio should be 2.0 (100/50): {}",
-            metrics.bandwidth_ratio
-        );
+        assert!(rx.try_recv().unwrap().is_none());
     }
 
-    // ── Hub-as-member self-send interception ───────────────────────────
-
     #[test]
-    fn hub_as_member_join_emits_local_member_joined_event() {
-        // When local node is BOTH hub and group creator, a remote Join should
-        // produce a local GroupMemberJoined event (not a SendEnvelope to self).
-        let (hub_id, hub_secret) = keypair(200);
-        let (bob_id, bob_secret) = keypair(201);
+    fn hub_serves_history_pages_to_members_only() {
+        let (hub_id, hub_secret) = keypair(240);
+        let (bob_id, bob_secret) = keypair(241);
+        let (eve_id, eve_secret) = keypair(242);
 
         let mut state = RuntimeState::new(
             hub_id,
@@ -3566,153 +10715,185 @@ mod tests {
             },
         );
 
-        // Create group locally (hub=self)
-        let effects = state.handle_command(RuntimeCommand::CreateGroup {
-            name: "SelfHubTest".to_string(),
+        state.handle_command(RuntimeCommand::CreateGroup {
+            name: "History".to_string(),
             hub_relay_id: hub_id,
             initial_members: vec![bob_id],
             invite_only: false,
         });
-        // Should emit GroupCreated event locally (no SendEnvelope to self)
-        let has_created_event = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::Emit(ProtocolEvent::GroupCreated { .. }))
-        });
-        assert!(
-            has_created_event,
-            "CreateGroup with hub=self should emit GroupCreated locally, got: {effects:?}"
-        );
-        // Should NOT have SendEnvelope to self
-        let has_self_send = effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::SendEnvelope(env) if env.to == hub_id)
-        });
-        assert!(
-            !has_self_send,
-            "CreateGroup with hub=self should NOT produce SendEnvelope to self"
-        );
-
-        // Get group_id
         let gid = state.group_hub.groups().next().unwrap().0.clone();
 
-        // Bob joins via network
-        let join_payload = crate::group::GroupPayload::Join {
-            group_id: gid.clone(),
-            username: "bob".into(),
+        let send = |state: &mut RuntimeState, from, secret: &[u8; 32], msg_type, payload: &GroupPayload| {
+            let bytes = rmp_serde::to_vec(payload).unwrap();
+            let env = EnvelopeBuilder::new(from, hub_id, msg_type, bytes).sign(secret);
+            state.handle_incoming_group(env)
         };
-        let join_bytes = rmp_serde::to_vec(&join_payload).unwrap();
-        let join_env = EnvelopeBuilder::new(
+
+        send(
+            &mut state,
             bob_id,
-            hub_id,
+            &bob_secret,
             MessageType::GroupJoin,
-            join_bytes,
-        )
-        .sign(&bob_secret);
-
-        let join_effects = state.handle_incoming_group(join_env);
-
-        // Should emit GroupMemberJoined event LOCALLY (via interception)
-        let has_member_joined = join_effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::Emit(ProtocolEvent::GroupMemberJoined { member, .. })
-                if member.node_id == bob_id)
-        });
-        assert!(
-            has_member_joined,
-            "Join when hub=self should emit local GroupMemberJoined, got: {join_effects:?}"
+            &GroupPayload::Join { group_id: gid.clone(), username: "bob".into() },
         );
+        for i in 0..3 {
+            let mut msg = GroupMessage::new(gid.clone(), bob_id, "bob".into(), format!("m{i}"));
+            msg.sign(&bob_secret);
+            send(&mut state, bob_id, &bob_secret, MessageType::GroupMessage, &GroupPayload::Message(msg));
+        }
 
-        // Should NOT have SendEnvelope(GroupMemberJoined) to self
-        let has_member_joined_self_send = join_effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::SendEnvelope(env)
-                if env.to == hub_id && env.msg_type == MessageType::GroupMemberJoined)
-        });
-        assert!(
-            !has_member_joined_self_send,
-            "Join when hub=self should NOT SendEnvelope(MemberJoined) to self"
-        );
+        let fetch = GroupPayload::FetchHistory { group_id: gid.clone(), before: u64::MAX, limit: 2 };
+        let effects = send(&mut state, bob_id, &bob_secret, MessageType::GroupFetchHistory, &fetch);
+        let page = effects
+            .iter()
+            .find_map(|e| match e {
+                RuntimeEffect::SendEnvelope(env)
+                    if env.to == bob_id && env.msg_type == MessageType::GroupHistoryPage =>
+                {
+                    rmp_serde::from_slice::<GroupPayload>(&env.payload).ok()
+                }
+                _ => None,
+            })
+            .expect("member should get a history page");
+        match page {
+            GroupPayload::HistoryPage { messages, has_more, .. } => {
+                assert!(has_more);
+                let texts: Vec<_> = messages.iter().map(|m| m.text.as_str()).collect();
+                assert_eq!(texts, vec!["m1", "m2"], "newest page, ascending");
+            }
+            other => panic!("expected HistoryPage, got {other:?}"),
+        }
 
-        // Should still send Sync to bob (over network)
-        let has_sync_to_bob = join_effects.iter().any(|e| {
-            matches!(e, RuntimeEffect::SendEnvelope(env)
-                if env.to == bob_id && env.msg_type == MessageType::GroupSync)
-        });
+        let effects = send(&mut state, eve_id, &eve_secret, MessageType::GroupFetchHistory, &fetch);
         assert!(
-            has_sync_to_bob,
-            "Join should send GroupSync to joiner (bob), got: {join_effects:?}"
+            !effects.iter().any(|e| matches!(e, RuntimeEffect::SendEnvelope(env)
+                if env.msg_type == MessageType::GroupHistoryPage)),
+            "non-members get nothing"
         );
     }
 
     #[test]
-    fn hub_as_member_group_message_fans_out_not_self_send() {
-        // When hub=self sends a group message, it should fan out to other
-        // members via network (not self-send to hub).
-        let (hub_id, hub_secret) = keypair(210);
-        let (bob_id, bob_secret) = keypair(211);
+    fn hub_never_serves_expired_disappearing_messages_from_store() {
+        let (hub_id, hub_secret) = keypair(243);
+        let (bob_id, bob_secret) = keypair(244);
 
         let mut state = RuntimeState::new(
             hub_id,
             hub_secret,
             RuntimeConfig {
                 encryption: false,
+                state_store: Some(Box::new(SqliteStateStore::open_memory().unwrap())),
                 ..Default::default()
             },
         );
-
-        // Create group locally (hub=self)
         state.handle_command(RuntimeCommand::CreateGroup {
-            name: "MsgTest".to_string(),
+            name: "Ephemeral".to_string(),
             hub_relay_id: hub_id,
             initial_members: vec![bob_id],
             invite_only: false,
         });
         let gid = state.group_hub.groups().next().unwrap().0.clone();
+        let join = rmp_serde::to_vec(&GroupPayload::Join { group_id: gid.clone(), username: "bob".into() })
+            .unwrap();
+        state.handle_incoming_group(
+            EnvelopeBuilder::new(bob_id, hub_id, MessageType::GroupJoin, join).sign(&bob_secret),
+        );
+        assert!(!state.group_hub.set_message_ttl(&gid, &hub_id, Some(60_000)).is_empty());
 
-        // Bob joins via network (full flow to populate both hub and manager)
-        let join_payload = crate::group::GroupPayload::Join {
-            group_id: gid.clone(),
-            username: "bob".into(),
+        // Persisted but not in memory: evicted, or stored before a restart.
+        let now = now_ms();
+        for (seq, sent_at) in [(1, now - 120_000), (2, now)] {
+            let mut msg = GroupMessage::new(gid.clone(), bob_id, "bob".into(), format!("m{seq}"));
+            msg.seq = seq;
+            msg.sent_at = sent_at;
+            state.persist_hub_message(&msg);
+        }
+        assert!(state.group_hub.message_history(&gid).unwrap().is_empty());
+
+        let served = |state: &RuntimeState| -> (Vec<u64>, Vec<u64>) {
+            let synced = match state.handle_sync_request(bob_id, &gid, 0).pop() {
+                Some(GroupAction::Send { payload: GroupPayload::SyncResponse { messages, .. }, .. }) => {
+                    messages.iter().map(|m| m.seq).collect()
+                }
+                _ => Vec::new(),
+            };
+            let fetched = match state.handle_fetch_history(bob_id, &gid, u64::MAX, 10).pop() {
+                Some(GroupAction::Send { payload: GroupPayload::HistoryPage { messages, .. }, .. }) => {
+                    messages.iter().map(|m| m.seq).collect()
+                }
+                _ => Vec::new(),
+            };
+            (synced, fetched)
         };
-        let join_bytes = rmp_serde::to_vec(&join_payload).unwrap();
-        let join_env = EnvelopeBuilder::new(
-            bob_id,
-            hub_id,
-            MessageType::GroupJoin,
-            join_bytes,
-        )
-        .sign(&bob_secret);
-        state.handle_incoming_group(join_env);
 
-        // Send group message from hub (who is also a member)
-        let effects = state.handle_command(RuntimeCommand::SendGroupMessage {
-            group_id: gid.clone(),
-            text: "hello from hub".to_string(),
-        });
+        // The expired row is filtered out even before the tick deletes it
+        assert_eq!(served(&state), (vec![2], vec![2]));
 
-        // Should send to bob (network)
-        let sends_to_bob: Vec<_> = effects.iter().filter(|e| {
-            matches!(e, RuntimeEffect::SendEnvelope(env)
-                if env.to == bob_id && env.msg_type == MessageType::GroupMessage)
-        }).collect();
-        assert!(
-            !sends_to_bob.is_empty(),
-            "Group message from hub should fan out to bob, got: {effects:?}"
-        );
+        let stored = |state: &RuntimeState| {
+            state.store.as_ref().unwrap().load_hub_messages_since(&gid, 0, 100).unwrap().len()
+        };
+        state.tick_ephemeral_messages_at(now);
+        assert_eq!(stored(&state), 1);
 
-        // Should NOT self-send to hub
-        let self_sends: Vec<_> = effects.iter().filter(|e| {
-            matches!(e, RuntimeEffect::SendEnvelope(env)
-                if env.to == hub_id)
-        }).collect();
-        assert!(
-            self_sends.is_empty(),
-            "Group message from hub should NOT produce SendEnvelope to self, got self-sends: {self_sends:?}"
+        // Past the TTL every row goes, whether or not it was in memory
+        state.tick_ephemeral_messages_at(now + 60_000);
+        assert_eq!(stored(&state), 0);
+        assert_eq!(served(&state), (vec![], vec![]));
+    }
+
+    #[test]
+    fn anonymous_post_to_admins_only_is_persisted_stripped() {
+        let (hub_id, hub_secret) = keypair(245);
+        let (alice_id, alice_secret) = keypair(246);
+        let (bob_id, bob_secret) = keypair(247);
+
+        let mut state = RuntimeState::new(
+            hub_id,
+            hub_secret,
+            RuntimeConfig {
+                encryption: false,
+                state_store: Some(Box::new(SqliteStateStore::open_memory().unwrap())),
+                ..Default::default()
+            },
         );
+        state.handle_command(RuntimeCommand::CreateGroup {
+            name: "Confessions".to_string(),
+            hub_relay_id: hub_id,
+            initial_members: vec![alice_id, bob_id],
+            invite_only: false,
+        });
+        let gid = state.group_hub.groups().next().unwrap().0.clone();
+        let send = |state: &mut RuntimeState, from, secret: &[u8; 32], msg_type, payload: &GroupPayload| {
+            let bytes = rmp_serde::to_vec(payload).unwrap();
+            state.handle_incoming_group(EnvelopeBuilder::new(from, hub_id, msg_type, bytes).sign(secret))
+        };
+        for (id, secret, name) in [(alice_id, &alice_secret, "alice"), (bob_id, &bob_secret, "bob")] {
+            let join = GroupPayload::Join { group_id: gid.clone(), username: name.into() };
+            send(&mut state, id, secret, MessageType::GroupJoin, &join);
+        }
+        state.group_hub.update_member_role(&gid, &hub_id, &alice_id, crate::group::GroupMemberRole::Admin);
+        let anonymous = crate::group::GroupSettings { anonymous: true, ..Default::default() };
+        assert!(!state.group_hub.update_settings(&gid, &hub_id, anonymous).is_empty());
+
+        // Every other member is an admin: no stripped broadcast goes out
+        let mut msg = GroupMessage::new(gid.clone(), bob_id, "bob".into(), "psst".into());
+        msg.sign(&bob_secret);
+        send(&mut state, bob_id, &bob_secret, MessageType::GroupMessage, &GroupPayload::Message(msg));
+
+        let rows = state.store.as_ref().unwrap().load_hub_messages_before(&gid, u64::MAX, 100).unwrap();
+        let [(_, data)] = &rows[..] else {
+            panic!("expected the post persisted once, got {} rows", rows.len());
+        };
+        let stored: GroupMessage = rmp_serde::from_slice(data).unwrap();
+        assert_eq!(stored.text, "psst");
+        assert_eq!(stored.sender_id, hub_id, "only the stripped copy is stored");
+        assert!(stored.sender_username.is_empty());
     }
 
     #[test]
-    fn hub_heartbeat_does_not_self_send() {
-        // When hub=self ticks heartbeat, it should broadcast to other members
-        // but NOT produce a SendEnvelope to self.
-        let (hub_id, hub_secret) = keypair(220);
-        let (bob_id, bob_secret) = keypair(221);
+    fn hub_as_admin_invite_token_admits_stranger() {
+        let (hub_id, hub_secret) = keypair(230);
+        let (carol_id, carol_secret) = keypair(231);
 
         let mut state = RuntimeState::new(
             hub_id,
@@ -3723,58 +10904,77 @@ mod tests {
             },
         );
 
-        // Create group locally (hub=self)
         state.handle_command(RuntimeCommand::CreateGroup {
-            name: "HBTest".to_string(),
+            name: "Private".to_string(),
             hub_relay_id: hub_id,
-            initial_members: vec![bob_id],
-            invite_only: false,
+            initial_members: vec![],
+            invite_only: true,
         });
         let gid = state.group_hub.groups().next().unwrap().0.clone();
 
-        // Bob joins
-        let join_payload = crate::group::GroupPayload::Join {
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        state.handle_command(RuntimeCommand::CreateInviteToken {
             group_id: gid.clone(),
-            username: "bob".into(),
-        };
-        let join_bytes = rmp_serde::to_vec(&join_payload).unwrap();
-        let join_env = EnvelopeBuilder::new(
-            bob_id,
-            hub_id,
-            MessageType::GroupJoin,
-            join_bytes,
-        )
-        .sign(&bob_secret);
+            ttl_ms: 60_000,
+            reply: tx,
+        });
+        let encoded = rx.try_recv().unwrap().expect("admin should get a token");
+        let token = crate::group::GroupInviteToken::decode(&encoded).unwrap();
+        assert!(token.validate(now_ms()).is_ok());
+        assert_eq!(token.hub_id, hub_id);
+        assert_eq!(state.group_hub.invite_token_count(&gid), 1);
+
+        // Carol was never invited, but presents the secret.
+        let join_bytes = rmp_serde::to_vec(&crate::group::GroupPayload::JoinWithToken {
+            group_id: gid.clone(),
+            username: "carol".into(),
+            join_secret: token.join_secret,
+        })
+        .unwrap();
+        let join_env =
+            EnvelopeBuilder::new(carol_id, hub_id, MessageType::GroupJoinWithToken, join_bytes)
+                .sign(&carol_secret);
         state.handle_incoming_group(join_env);
 
-        // Tick heartbeat
-        let effects = state.tick_group_hub_heartbeat();
-
-        // Should send heartbeat to bob (network)
-        let sends_to_bob: Vec<_> = effects
-            .iter()
-            .filter(|e| {
-                matches!(e, RuntimeEffect::SendEnvelope(env)
-                    if env.to == bob_id && env.msg_type == MessageType::GroupHubHeartbeat)
-            })
-            .collect();
-        assert!(
-            !sends_to_bob.is_empty(),
-            "Heartbeat should reach bob, got: {effects:?}"
-        );
+        let group = state.group_hub.get_group(&gid).unwrap();
+        assert!(group.is_member(&carol_id), "token holder should be admitted");
+    }
 
-        // Should NOT self-send heartbeat to hub
-        let self_sends: Vec<_> = effects
-            .iter()
-            .filter(|e| {
-                matches!(e, RuntimeEffect::SendEnvelope(env)
-                    if env.to == hub_id)
-            })
-            .collect();
-        assert!(
-            self_sends.is_empty(),
-            "Heartbeat should NOT produce SendEnvelope to self, got: {self_sends:?}"
+    #[test]
+    fn hub_from_invite_token_stays_unverified_until_it_signs() {
+        let (carol_id, carol_secret) = keypair(232);
+        let (admin_id, admin_secret) = keypair(233);
+        let (hub_id, hub_secret) = keypair(234);
+        let mut state = RuntimeState::new(carol_id, carol_secret, RuntimeConfig::default());
+
+        let mut token = crate::group::GroupInviteToken::new(
+            GroupId::from("grp-token-hub".to_string()),
+            "Private".into(),
+            hub_id,
+            admin_id,
+            now_ms(),
+            60_000,
         );
+        token.sign(&admin_secret);
+        state.handle_command(RuntimeCommand::JoinGroupByToken { token });
+
+        // Known (so the join can be routed) but not vouched for
+        assert_eq!(state.topology.get(&hub_id).unwrap().status, PeerStatus::Stale);
+        state.tick_heartbeat();
+        assert_eq!(state.topology.get(&hub_id).unwrap().status, PeerStatus::Stale);
+        assert!(!state.gossip_entry_points().contains(&hub_id));
+
+        // An unsigned envelope claiming to be the hub proves nothing
+        let forged = EnvelopeBuilder::new(hub_id, carol_id, MessageType::Heartbeat, Vec::new()).build();
+        state.handle_incoming(&forged.to_bytes().unwrap());
+        state.tick_heartbeat();
+        assert_eq!(state.topology.get(&hub_id).unwrap().status, PeerStatus::Stale);
+
+        let signed = EnvelopeBuilder::new(hub_id, carol_id, MessageType::Heartbeat, Vec::new())
+            .sign(&hub_secret);
+        state.handle_incoming(&signed.to_bytes().unwrap());
+        state.tick_heartbeat();
+        assert_eq!(state.topology.get(&hub_id).unwrap().status, PeerStatus::Online);
     }
 
     // ── Anti-spam integration tests (R11.1) ─────────────────────────────
@@ -3822,6 +11022,144 @@ mod tests {
         // We only assert that throttling does happen (progressive anti-spam active).
     }
 
+    #[test]
+    fn throttled_spammer_is_penalized_and_reported_once() {
+        let mut state = default_state(1);
+        let (sender_id, sender_secret) = keypair(42);
+        let raw = crate::envelope::EnvelopeBuilder::new(
+            sender_id,
+            state.local_id,
+            MessageType::Chat,
+            b"spam".to_vec(),
+        )
+        .sign(&sender_secret)
+        .to_bytes()
+        .expect("serialize");
+
+        let mut reports = Vec::new();
+        for _ in 0..60 {
+            for effect in state.handle_incoming(raw.as_slice()) {
+                if let RuntimeEffect::BroadcastAbuseReport(report) = effect {
+                    reports.push(report);
+                }
+            }
+        }
+
+        assert_eq!(reports.len(), 1, "one report per offender per interval");
+        let report = &reports[0];
+        assert_eq!((report.reporter, report.offender), (state.local_id, sender_id));
+        assert_eq!(report.kind, crate::discovery::ViolationKind::Spam);
+        assert!(report.verify_signature() && report.evidence_matches());
+        let penalty = state.role_manager.scores()[&sender_id].penalty_at(now_ms());
+        assert!(penalty > 0.0);
+    }
+
+    #[test]
+    fn gossiped_abuse_report_penalizes_offender_once_per_reporter() {
+        use crate::discovery::{AbuseReport, ViolationKind};
+
+        let mut state = default_state(1);
+        let (reporter, reporter_seed) = keypair(2);
+        let (offender, offender_seed) = keypair(3);
+        let evidence = crate::envelope::EnvelopeBuilder::new(
+            offender,
+            reporter,
+            MessageType::Chat,
+            b"spam".to_vec(),
+        )
+        .sign(&offender_seed)
+        .to_bytes()
+        .unwrap();
+        let report = AbuseReport::new(
+            reporter,
+            offender,
+            ViolationKind::Spam,
+            evidence.clone(),
+            now_ms(),
+            &reporter_seed,
+        );
+        let bytes = rmp_serde::to_vec(&report).unwrap();
+
+        let effects = state.handle_gossip_event(GossipInput::PeerAnnounce(bytes.clone()));
+        assert!(matches!(
+            &effects[..],
+            [RuntimeEffect::Emit(ProtocolEvent::AbuseReported { offender: o, kind: ViolationKind::Spam, .. })]
+                if *o == offender
+        ));
+        let penalty = state.role_manager.scores()[&offender].penalty_at(now_ms());
+        assert!(penalty > 0.0);
+
+        // The same reporter again within the interval: ignored
+        assert!(state.handle_gossip_event(GossipInput::PeerAnnounce(bytes)).is_empty());
+
+        // Evidence that doesn't back the claim: rejected, no penalty
+        let (other, other_seed) = keypair(4);
+        let bogus = AbuseReport::new(
+            other,
+            reporter,
+            ViolationKind::Spam,
+            evidence,
+            now_ms(),
+            &other_seed,
+        );
+        let effects = state.handle_abuse_report(bogus);
+        assert!(matches!(&effects[..], [RuntimeEffect::Emit(ProtocolEvent::Error { .. })]));
+        assert!(!state.role_manager.scores().contains_key(&reporter));
+    }
+
+    #[test]
+    fn chat_throttle_drops_floods_per_sender_and_reports_once() {
+        let (id, secret) = keypair(1);
+        let mut state = RuntimeState::new(
+            id,
+            secret,
+            RuntimeConfig {
+                encryption: false,
+                chat_rate_limits: crate::roles::ChatRateLimits {
+                    delivery_rate: 2.0,
+                    forward_rate: 1.0,
+                },
+                ..Default::default()
+            },
+        );
+        let throttled = |effects: &[RuntimeEffect]| {
+            effects.iter().find_map(|e| match e {
+                RuntimeEffect::Emit(ProtocolEvent::ChatThrottled { scope, .. }) => Some(*scope),
+                _ => None,
+            })
+        };
+
+        // Burst of 4 delivered, then dropped unacked, reported once
+        let mut delivered = 0;
+        let mut reports = Vec::new();
+        for n in 0..8u8 {
+            let (env, sig_valid) = make_signed_chat(2, id, &[n]);
+            let effects = state.handle_incoming_chat(env, sig_valid);
+            delivered += effects.iter().filter(|e| matches!(e, RuntimeEffect::DeliverMessage(_))).count();
+            reports.extend(throttled(&effects));
+            if n >= 4 {
+                assert!(!effects.iter().any(|e| matches!(e, RuntimeEffect::SendEnvelope(_))));
+            }
+        }
+        assert_eq!(delivered, 4);
+        assert_eq!(reports, vec![crate::roles::ThrottleScope::Delivery]);
+
+        // Another sender has its own budget
+        let (env, sig_valid) = make_signed_chat(3, id, b"hi");
+        let effects = state.handle_incoming_chat(env, sig_valid);
+        assert!(effects.iter().any(|e| matches!(e, RuntimeEffect::DeliverMessage(_))));
+
+        // Relaying for a sender spends its forwarding budget (burst 2)
+        let elsewhere = node_id(9);
+        let reports: Vec<_> = (0..4u8)
+            .filter_map(|n| {
+                let (env, sig_valid) = make_signed_chat(2, elsewhere, &[n]);
+                throttled(&state.handle_incoming_chat(env, sig_valid))
+            })
+            .collect();
+        assert_eq!(reports, vec![crate::roles::ThrottleScope::Forwarding]);
+    }
+
     #[test]
     fn antispam_handle_incoming_records_bytes_received() {
         let mut state = default_state(1);
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use crate::types::NodeId;
 
 /// Abstraction reseau pour le runtime.
@@ -9,6 +11,13 @@ pub trait Transport: Send {
     /// Envoyer des bytes bruts a un noeud cible.
     async fn send_raw(&self, target: NodeId, data: &[u8]) -> Result<(), String>;
 
+    /// Envoyer des bytes bruts en abandonnant au-dela de `timeout`
+    /// (connexion comprise).
+    async fn send_raw_timeout(&self, target: NodeId, data: &[u8], timeout: Duration) -> Result<(), String>;
+
+    /// Envoyer un datagramme non fiable (voie temps reel).
+    async fn send_datagram(&self, target: NodeId, data: &[u8]) -> Result<(), String>;
+
     /// Lister les peers actuellement connectes.
     async fn connected_peers(&self) -> Vec<NodeId>;
 }
@@ -23,6 +32,19 @@ impl Transport for tom_transport::TomNode {
             .map_err(|e| e.to_string())
     }
 
+    async fn send_raw_timeout(&self, target: NodeId, data: &[u8], timeout: Duration) -> Result<(), String> {
+        let cancel = tom_transport::CancellationToken::new();
+        tom_transport::TomNode::send_raw_with(self, target, data, timeout, &cancel)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn send_datagram(&self, target: NodeId, data: &[u8]) -> Result<(), String> {
+        tom_transport::TomNode::send_datagram(self, target, data)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
     async fn connected_peers(&self) -> Vec<NodeId> {
         tom_transport::TomNode::connected_peers(self).await
     }
@@ -47,8 +69,12 @@ pub mod mock {
         /// Number of times send_raw() will fail before succeeding.
         /// Decrements on each failure. 0 = succeed immediately.
         fail_count: Arc<Mutex<u32>>,
+        /// Sends never complete (unreachable peer): timed sends run out
+        /// their timeout.
+        hang_sends: Arc<Mutex<bool>>,
         /// Total number of send_raw() calls (for retry verification).
         pub send_attempts: Arc<Mutex<u32>>,
+        datagrams: Arc<Mutex<SentLog>>,
     }
 
     impl MockTransport {
@@ -58,7 +84,9 @@ pub mod mock {
                 peers: Arc::new(Mutex::new(Vec::new())),
                 fail_sends: Arc::new(Mutex::new(false)),
                 fail_count: Arc::new(Mutex::new(0)),
+                hang_sends: Arc::new(Mutex::new(false)),
                 send_attempts: Arc::new(Mutex::new(0)),
+                datagrams: Arc::new(Mutex::new(Vec::new())),
             }
         }
 
@@ -66,6 +94,10 @@ pub mod mock {
             self.sent.lock().unwrap().clone()
         }
 
+        pub fn datagrams(&self) -> SentLog {
+            self.datagrams.lock().unwrap().clone()
+        }
+
         pub fn set_peers(&self, peers: Vec<NodeId>) {
             *self.peers.lock().unwrap() = peers;
         }
@@ -79,6 +111,10 @@ pub mod mock {
             *self.fail_count.lock().unwrap() = n;
         }
 
+        pub fn set_hang_sends(&self, hang: bool) {
+            *self.hang_sends.lock().unwrap() = hang;
+        }
+
         pub fn clear_sent(&self) {
             self.sent.lock().unwrap().clear();
         }
@@ -104,6 +140,20 @@ pub mod mock {
             Ok(())
         }
 
+        async fn send_raw_timeout(&self, target: NodeId, data: &[u8], timeout: Duration) -> Result<(), String> {
+            if *self.hang_sends.lock().unwrap() {
+                *self.send_attempts.lock().unwrap() += 1;
+                tokio::time::sleep(timeout).await;
+                return Err("mock: send timed out".to_string());
+            }
+            self.send_raw(target, data).await
+        }
+
+        async fn send_datagram(&self, target: NodeId, data: &[u8]) -> Result<(), String> {
+            self.datagrams.lock().unwrap().push((target, data.to_vec()));
+            Ok(())
+        }
+
         async fn connected_peers(&self) -> Vec<NodeId> {
             self.peers.lock().unwrap().clone()
         }
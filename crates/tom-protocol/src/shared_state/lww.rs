@@ -0,0 +1,188 @@
+/// Last-writer-wins map CRDT.
+///
+/// Every key carries the stamp of its latest write. Merging two replicas
+/// keeps, per key, the entry with the highest stamp — so merges are
+/// commutative, associative and idempotent, and replicas converge no matter
+/// the order updates arrive in. Deletes are tombstones (`value: None`) so a
+/// late, older write can't resurrect a removed key.
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::NodeId;
+
+/// Write stamp: wall-clock millis, tie-broken by writer id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LwwStamp {
+    pub millis: u64,
+    pub writer: NodeId,
+}
+
+impl LwwStamp {
+    fn key(&self) -> (u64, [u8; 32]) {
+        (self.millis, self.writer.as_bytes())
+    }
+}
+
+impl PartialOrd for LwwStamp {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LwwStamp {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// One key's latest write. `value: None` is a tombstone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LwwEntry {
+    pub value: Option<Vec<u8>>,
+    pub stamp: LwwStamp,
+}
+
+/// A replicated key → bytes map with last-writer-wins semantics.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LwwMap {
+    entries: BTreeMap<String, LwwEntry>,
+}
+
+impl LwwMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current value for a key (None if absent or deleted).
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.entries.get(key)?.value.as_deref()
+    }
+
+    /// Live (non-deleted) key/value pairs, sorted by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.entries
+            .iter()
+            .filter_map(|(k, e)| e.value.as_deref().map(|v| (k.as_str(), v)))
+    }
+
+    /// Number of live keys.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Whether there are no live keys.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of stored entries, including tombstones.
+    pub fn entry_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Highest stamp seen so far (to keep local writes monotonic).
+    pub fn max_millis(&self) -> u64 {
+        self.entries
+            .values()
+            .map(|e| e.stamp.millis)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Apply one entry. Returns true if it won (changed the map).
+    pub fn apply(&mut self, key: String, entry: LwwEntry) -> bool {
+        match self.entries.get(&key) {
+            Some(current) if current.stamp >= entry.stamp => false,
+            _ => {
+                self.entries.insert(key, entry);
+                true
+            }
+        }
+    }
+
+    /// All entries, tombstones included (for full-state sync).
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &LwwEntry)> {
+        self.entries.iter()
+    }
+
+    /// Merge another replica into this one. Returns the keys that changed.
+    pub fn merge(&mut self, other: &LwwMap) -> Vec<String> {
+        other
+            .entries
+            .iter()
+            .filter(|(k, e)| self.apply((*k).clone(), (*e).clone()))
+            .map(|(k, _)| k.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    fn write(
+        map: &mut LwwMap,
+        key: &str,
+        value: Option<&[u8]>,
+        millis: u64,
+        writer: NodeId,
+    ) -> bool {
+        map.apply(
+            key.into(),
+            LwwEntry {
+                value: value.map(<[u8]>::to_vec),
+                stamp: LwwStamp { millis, writer },
+            },
+        )
+    }
+
+    #[test]
+    fn newer_write_wins_older_is_ignored() {
+        let a = node_id(1);
+        let mut map = LwwMap::new();
+        assert!(write(&mut map, "title", Some(b"v2"), 20, a));
+        assert!(!write(&mut map, "title", Some(b"v1"), 10, a));
+        assert_eq!(map.get("title"), Some(&b"v2"[..]));
+    }
+
+    #[test]
+    fn tombstone_blocks_stale_resurrection() {
+        let a = node_id(1);
+        let mut map = LwwMap::new();
+        write(&mut map, "k", Some(b"x"), 10, a);
+        write(&mut map, "k", None, 20, a);
+        assert!(!write(&mut map, "k", Some(b"late"), 15, a));
+        assert_eq!(map.get("k"), None);
+        assert!(map.is_empty());
+        assert_eq!(map.entry_count(), 1);
+    }
+
+    #[test]
+    fn merge_converges_regardless_of_order() {
+        let (a, b) = (node_id(1), node_id(2));
+        let mut left = LwwMap::new();
+        let mut right = LwwMap::new();
+        write(&mut left, "x", Some(b"left"), 10, a);
+        write(&mut right, "x", Some(b"right"), 10, b); // same millis: writer breaks tie
+        write(&mut right, "y", Some(b"only-right"), 5, b);
+
+        let mut l2 = left.clone();
+        l2.merge(&right);
+        let mut r2 = right.clone();
+        r2.merge(&left);
+        assert_eq!(l2, r2);
+
+        // Idempotent
+        let before = l2.clone();
+        assert!(l2.merge(&before).is_empty());
+        assert_eq!(l2, before);
+    }
+}
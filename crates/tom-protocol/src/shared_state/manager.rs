@@ -0,0 +1,448 @@
+/// SharedStateManager — replicated documents between a fixed set of peers.
+///
+/// Pure decision engine (same as GroupManager → GroupAction): every method
+/// returns `Vec<SharedStateAction>` and the runtime turns them into signed
+/// envelopes. Each document is an [`LwwMap`]; writes are pushed to all
+/// peers, and opening a document exchanges full state so late joiners and
+/// nodes coming back online converge.
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::lww::{LwwEntry, LwwMap, LwwStamp};
+use crate::types::{now_ms, NodeId};
+use crate::TomProtocolError;
+
+/// Max key length in bytes.
+pub const MAX_SHARED_KEY_LEN: usize = 128;
+
+/// Max value size in bytes (lightweight state only).
+pub const MAX_SHARED_VALUE_LEN: usize = 16 * 1024;
+
+/// Max entries per document, tombstones included.
+pub const MAX_SHARED_ENTRIES: usize = 1024;
+
+/// How far ahead of our clock a peer's write stamp may be (ms). A stamp
+/// further out would win every later write.
+pub const MAX_STAMP_SKEW_MS: u64 = 5 * 60 * 1000;
+
+/// Wire payloads between replicas.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SharedStatePayload {
+    /// Entries to merge (one write, or the full state on sync).
+    Update {
+        doc_id: String,
+        entries: Vec<(String, LwwEntry)>,
+    },
+    /// Ask a peer for its full state.
+    SyncRequest { doc_id: String },
+}
+
+/// A key changed (locally or through a merge).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedStateChange {
+    pub doc_id: String,
+    pub key: String,
+    pub value: Option<Vec<u8>>,
+    pub updated_by: NodeId,
+}
+
+/// Actions returned by SharedStateManager.
+#[derive(Debug)]
+pub enum SharedStateAction {
+    /// Send a payload to one peer.
+    Send {
+        to: NodeId,
+        payload: SharedStatePayload,
+    },
+    /// Notify the application.
+    Changed(SharedStateChange),
+}
+
+/// One replicated document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedDoc {
+    pub doc_id: String,
+    /// Peers we replicate with (never includes ourselves).
+    pub peers: HashSet<NodeId>,
+    pub map: LwwMap,
+}
+
+/// Serializable state for persistence.
+#[derive(Debug, Clone, Default)]
+pub struct SharedStateSnapshot {
+    pub docs: HashMap<String, SharedDoc>,
+}
+
+/// Replicated documents this node takes part in.
+pub struct SharedStateManager {
+    local_id: NodeId,
+    docs: HashMap<String, SharedDoc>,
+}
+
+impl SharedStateManager {
+    pub fn new(local_id: NodeId) -> Self {
+        Self {
+            local_id,
+            docs: HashMap::new(),
+        }
+    }
+
+    /// Get a document.
+    pub fn get(&self, doc_id: &str) -> Option<&SharedDoc> {
+        self.docs.get(doc_id)
+    }
+
+    /// Number of documents.
+    pub fn doc_count(&self) -> usize {
+        self.docs.len()
+    }
+
+    /// Open (or extend) a document shared with `peers`.
+    ///
+    /// Pushes our full state to every peer and asks each for theirs.
+    pub fn open(&mut self, doc_id: &str, peers: Vec<NodeId>) -> Vec<SharedStateAction> {
+        let local_id = self.local_id;
+        let doc = self
+            .docs
+            .entry(doc_id.to_string())
+            .or_insert_with(|| SharedDoc {
+                doc_id: doc_id.to_string(),
+                peers: HashSet::new(),
+                map: LwwMap::new(),
+            });
+        doc.peers
+            .extend(peers.into_iter().filter(|p| *p != local_id));
+
+        let full_state = full_state(doc);
+        doc.peers
+            .iter()
+            .flat_map(|peer| {
+                [
+                    SharedStateAction::Send {
+                        to: *peer,
+                        payload: SharedStatePayload::Update {
+                            doc_id: doc_id.to_string(),
+                            entries: full_state.clone(),
+                        },
+                    },
+                    SharedStateAction::Send {
+                        to: *peer,
+                        payload: SharedStatePayload::SyncRequest {
+                            doc_id: doc_id.to_string(),
+                        },
+                    },
+                ]
+            })
+            .collect()
+    }
+
+    /// Stop replicating a document and forget it locally.
+    pub fn close(&mut self, doc_id: &str) -> bool {
+        self.docs.remove(doc_id).is_some()
+    }
+
+    /// Write (or delete, with `None`) a key and push it to all peers.
+    pub fn set(
+        &mut self,
+        doc_id: &str,
+        key: String,
+        value: Option<Vec<u8>>,
+    ) -> Result<Vec<SharedStateAction>, TomProtocolError> {
+        let Some(doc) = self.docs.get_mut(doc_id) else {
+            return Err(TomProtocolError::InvalidSharedState {
+                reason: format!("unknown document {doc_id}"),
+            });
+        };
+        check_entry(&doc.map, &key, value.as_deref()).map_err(|reason| {
+            TomProtocolError::InvalidSharedState {
+                reason: reason.into(),
+            }
+        })?;
+
+        // Monotonic even if our clock is behind a peer's.
+        let millis = now_ms().max(doc.map.max_millis().saturating_add(1));
+        let entry = LwwEntry {
+            value,
+            stamp: LwwStamp {
+                millis,
+                writer: self.local_id,
+            },
+        };
+        doc.map.apply(key.clone(), entry.clone());
+
+        let mut actions: Vec<SharedStateAction> = doc
+            .peers
+            .iter()
+            .map(|peer| SharedStateAction::Send {
+                to: *peer,
+                payload: SharedStatePayload::Update {
+                    doc_id: doc_id.to_string(),
+                    entries: vec![(key.clone(), entry.clone())],
+                },
+            })
+            .collect();
+        actions.push(SharedStateAction::Changed(SharedStateChange {
+            doc_id: doc_id.to_string(),
+            key,
+            value: entry.value,
+            updated_by: self.local_id,
+        }));
+        Ok(actions)
+    }
+
+    /// Handle a payload from a peer. Only listed peers are accepted.
+    pub fn handle_payload(
+        &mut self,
+        from: NodeId,
+        payload: SharedStatePayload,
+    ) -> Vec<SharedStateAction> {
+        match payload {
+            SharedStatePayload::Update { doc_id, entries } => {
+                let Some(doc) = self.docs.get_mut(&doc_id) else {
+                    return vec![];
+                };
+                if !doc.peers.contains(&from) {
+                    return vec![];
+                }
+                let mut actions = Vec::new();
+                let latest = now_ms().saturating_add(MAX_STAMP_SKEW_MS);
+                for (key, entry) in entries {
+                    if entry.stamp.millis > latest
+                        || check_entry(&doc.map, &key, entry.value.as_deref()).is_err()
+                    {
+                        continue;
+                    }
+                    let updated_by = entry.stamp.writer;
+                    let value = entry.value.clone();
+                    if doc.map.apply(key.clone(), entry) {
+                        actions.push(SharedStateAction::Changed(SharedStateChange {
+                            doc_id: doc_id.clone(),
+                            key,
+                            value,
+                            updated_by,
+                        }));
+                    }
+                }
+                actions
+            }
+            SharedStatePayload::SyncRequest { doc_id } => {
+                let Some(doc) = self.docs.get(&doc_id) else {
+                    return vec![];
+                };
+                if !doc.peers.contains(&from) {
+                    return vec![];
+                }
+                vec![SharedStateAction::Send {
+                    to: from,
+                    payload: SharedStatePayload::Update {
+                        doc_id,
+                        entries: full_state(doc),
+                    },
+                }]
+            }
+        }
+    }
+
+    /// Export state for persistence.
+    pub fn snapshot(&self) -> SharedStateSnapshot {
+        SharedStateSnapshot {
+            docs: self.docs.clone(),
+        }
+    }
+
+    /// Restore persisted state (replaces current documents).
+    pub fn restore(&mut self, snapshot: SharedStateSnapshot) {
+        self.docs = snapshot.docs;
+    }
+}
+
+/// All entries (tombstones included) for a full-state push.
+fn full_state(doc: &SharedDoc) -> Vec<(String, LwwEntry)> {
+    doc.map
+        .entries()
+        .map(|(k, e)| (k.clone(), e.clone()))
+        .collect()
+}
+
+/// Enforce key/value/entry-count limits.
+fn check_entry(map: &LwwMap, key: &str, value: Option<&[u8]>) -> Result<(), &'static str> {
+    if key.is_empty() || key.len() > MAX_SHARED_KEY_LEN {
+        return Err("key length out of range");
+    }
+    if value.is_some_and(|v| v.len() > MAX_SHARED_VALUE_LEN) {
+        return Err("value too large");
+    }
+    let is_new = !map.entries().any(|(k, _)| k == key);
+    if is_new && map.entry_count() >= MAX_SHARED_ENTRIES {
+        return Err("document full");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    /// Deliver every Send action to the matching manager until quiet.
+    fn pump(
+        managers: &mut [&mut SharedStateManager],
+        mut pending: Vec<(NodeId, SharedStateAction)>,
+    ) {
+        while let Some((from, action)) = pending.pop() {
+            if let SharedStateAction::Send { to, payload } = action {
+                if let Some(target) = managers.iter_mut().find(|m| m.local_id == to) {
+                    let out = target.handle_payload(from, payload);
+                    pending.extend(out.into_iter().map(|a| (to, a)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn set_pushes_to_peers_and_emits_change() {
+        let (alice, bob) = (node_id(1), node_id(2));
+        let mut mgr = SharedStateManager::new(alice);
+        mgr.open("topic", vec![bob, alice]);
+        assert!(!mgr.get("topic").unwrap().peers.contains(&alice));
+
+        let actions = mgr
+            .set("topic", "title".into(), Some(b"Hello".to_vec()))
+            .unwrap();
+        assert!(actions.iter().any(|a| matches!(a,
+            SharedStateAction::Send { to, payload: SharedStatePayload::Update { entries, .. } }
+                if *to == bob && entries.len() == 1)));
+        assert!(actions
+            .iter()
+            .any(|a| matches!(a, SharedStateAction::Changed(c) if c.updated_by == alice)));
+        assert_eq!(
+            mgr.get("topic").unwrap().map.get("title"),
+            Some(&b"Hello"[..])
+        );
+    }
+
+    #[test]
+    fn replicas_converge_after_open() {
+        let (alice, bob) = (node_id(1), node_id(2));
+        let mut a = SharedStateManager::new(alice);
+        let mut b = SharedStateManager::new(bob);
+
+        // Alice writes before Bob has opened the doc: Bob drops the push.
+        a.open("list", vec![bob]);
+        let out = a.set("list", "milk".into(), Some(b"1".to_vec())).unwrap();
+        pump(
+            &mut [&mut a, &mut b],
+            out.into_iter().map(|x| (alice, x)).collect(),
+        );
+        assert!(b.get("list").is_none());
+
+        // Bob opens: full-state exchange catches him up.
+        let mut out = b.open("list", vec![alice]);
+        out.extend(b.set("list", "eggs".into(), Some(b"12".to_vec())).unwrap());
+        pump(
+            &mut [&mut a, &mut b],
+            out.into_iter().map(|x| (bob, x)).collect(),
+        );
+
+        assert_eq!(b.get("list").unwrap().map.get("milk"), Some(&b"1"[..]));
+        assert_eq!(a.get("list").unwrap().map, b.get("list").unwrap().map);
+    }
+
+    #[test]
+    fn rejects_strangers_and_oversized_values() {
+        let (alice, bob, eve) = (node_id(1), node_id(2), node_id(3));
+        let mut mgr = SharedStateManager::new(alice);
+        mgr.open("doc", vec![bob]);
+
+        let entry = LwwEntry {
+            value: Some(b"pwned".to_vec()),
+            stamp: LwwStamp {
+                millis: u64::MAX,
+                writer: eve,
+            },
+        };
+        let actions = mgr.handle_payload(
+            eve,
+            SharedStatePayload::Update {
+                doc_id: "doc".into(),
+                entries: vec![("k".into(), entry)],
+            },
+        );
+        assert!(actions.is_empty());
+        assert!(mgr.get("doc").unwrap().map.is_empty());
+
+        assert!(mgr
+            .set("doc", "big".into(), Some(vec![0; MAX_SHARED_VALUE_LEN + 1]))
+            .is_err());
+        assert!(mgr.set("missing", "k".into(), None).is_err());
+    }
+
+    #[test]
+    fn far_future_stamps_are_rejected() {
+        let (alice, bob) = (node_id(1), node_id(2));
+        let mut mgr = SharedStateManager::new(alice);
+        mgr.open("doc", vec![bob]);
+
+        let poisoned = LwwEntry {
+            value: Some(b"forever".to_vec()),
+            stamp: LwwStamp {
+                millis: u64::MAX,
+                writer: bob,
+            },
+        };
+        let skewed = LwwEntry {
+            value: Some(b"later".to_vec()),
+            stamp: LwwStamp {
+                millis: now_ms() + MAX_STAMP_SKEW_MS + 60_000,
+                writer: bob,
+            },
+        };
+        let actions = mgr.handle_payload(
+            bob,
+            SharedStatePayload::Update {
+                doc_id: "doc".into(),
+                entries: vec![("k".into(), poisoned), ("j".into(), skewed)],
+            },
+        );
+        assert!(actions.is_empty());
+        assert!(mgr.get("doc").unwrap().map.is_empty());
+
+        // Within the skew bound a peer's write is merged, and ours still wins after it
+        let ahead = LwwEntry {
+            value: Some(b"theirs".to_vec()),
+            stamp: LwwStamp {
+                millis: now_ms() + 60_000,
+                writer: bob,
+            },
+        };
+        mgr.handle_payload(
+            bob,
+            SharedStatePayload::Update {
+                doc_id: "doc".into(),
+                entries: vec![("k".into(), ahead)],
+            },
+        );
+        mgr.set("doc", "k".into(), Some(b"ours".to_vec())).unwrap();
+        assert_eq!(mgr.get("doc").unwrap().map.get("k"), Some(&b"ours"[..]));
+    }
+
+    #[test]
+    fn snapshot_restore_roundtrip() {
+        let (alice, bob) = (node_id(1), node_id(2));
+        let mut mgr = SharedStateManager::new(alice);
+        mgr.open("doc", vec![bob]);
+        mgr.set("doc", "k".into(), Some(b"v".to_vec())).unwrap();
+
+        let mut restored = SharedStateManager::new(alice);
+        restored.restore(mgr.snapshot());
+        assert_eq!(restored.get("doc").unwrap().map.get("k"), Some(&b"v"[..]));
+        assert!(restored.get("doc").unwrap().peers.contains(&bob));
+    }
+}
@@ -0,0 +1,19 @@
+/// Shared state — lightweight replicated documents for applications.
+///
+/// Apps share small key/value state (topic titles, shared lists, settings)
+/// without writing their own sync protocol. Each document is a
+/// last-writer-wins map CRDT replicated between an explicit set of peers
+/// over regular signed envelopes, and persisted with the rest of the state.
+///
+/// Two layers:
+/// - **Lww**: the CRDT itself (stamps, tombstones, merge)
+/// - **Manager**: documents, peer sets, wire payloads, actions
+pub mod lww;
+pub mod manager;
+
+pub use lww::{LwwEntry, LwwMap, LwwStamp};
+pub use manager::{
+    SharedDoc, SharedStateAction, SharedStateChange, SharedStateManager, SharedStatePayload,
+    SharedStateSnapshot, MAX_SHARED_ENTRIES, MAX_SHARED_KEY_LEN, MAX_SHARED_VALUE_LEN,
+    MAX_STAMP_SKEW_MS,
+};
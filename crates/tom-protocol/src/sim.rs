@@ -0,0 +1,586 @@
+/// Deterministic simulation of several `RuntimeState`s, without sockets.
+///
+/// A [`SimNetwork`] owns N in-memory nodes and plays the runtime loop's
+/// part: it runs each node's timers, routes envelopes, gossip and channel
+/// publications between them, and records what reaches the application.
+/// Time is virtual — `now_ms()` reads the simulation clock on its thread —
+/// so a heartbeat timeout or a backup TTL takes no wall time to reach.
+///
+/// The network is scripted and seeded: latency plus jitter (which reorders
+/// packets), random loss, partitions between pairs of nodes, and nodes
+/// going offline. Two runs with the same seed and script make the same
+/// network decisions. Randomness inside the states (message ids, keys,
+/// weighted relay picks) is not seeded, nor are the `Instant`-based ACK
+/// deadlines and dedup caches, which follow wall time.
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+use crate::envelope::Envelope;
+use crate::rpc::Response;
+use crate::runtime::{
+    DeliveredMessage, GossipInput, ProtocolEvent, RuntimeCommand, RuntimeConfig, RuntimeEffect,
+    RuntimeState,
+};
+use crate::tracker::StatusChange;
+use crate::types::{set_sim_clock, NodeId};
+
+/// Virtual Unix time (ms) a simulation starts at.
+pub const SIM_EPOCH_MS: u64 = 1_700_000_000_000;
+
+/// Fixed role evaluation period of the runtime loop.
+const ROLE_EVAL_MS: u64 = 60_000;
+
+/// Fixed delivery deadline / scheduled release period of the runtime loop.
+const DELIVERY_DEADLINE_MS: u64 = 5_000;
+
+/// Fixed hub message cleanup period of the runtime loop.
+const HUB_CLEANUP_MS: u64 = 60_000;
+
+/// One simulated node: its state and everything it handed the application.
+pub struct SimNode {
+    pub state: RuntimeState,
+    pub messages: Vec<DeliveredMessage>,
+    pub status_changes: Vec<StatusChange>,
+    pub events: Vec<ProtocolEvent>,
+    pub responses: Vec<Response>,
+    online: bool,
+    channels: HashSet<[u8; 32]>,
+    timers: Vec<Timer>,
+}
+
+impl SimNode {
+    pub fn id(&self) -> NodeId {
+        self.state.local_id
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online
+    }
+}
+
+/// One of the runtime loop's timer handlers.
+type Tick = fn(&mut RuntimeState) -> Vec<RuntimeEffect>;
+
+/// A periodic tick of the runtime loop.
+struct Timer {
+    every: u64,
+    next: u64,
+    tick: Tick,
+}
+
+/// Something in flight between two nodes.
+enum Packet {
+    Envelope(Vec<u8>),
+    Gossip(Vec<u8>),
+    Channel { topic: [u8; 32], bytes: Vec<u8> },
+    Datagram(Vec<u8>),
+}
+
+/// SplitMix64: small, seedable, good enough for network dice.
+struct SimRng(u64);
+
+impl SimRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform roll in `[0, 1)`.
+    fn roll(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// In-memory network of `RuntimeState`s on a virtual clock.
+///
+/// Pins this thread's `now_ms()` while alive.
+pub struct SimNetwork {
+    now: u64,
+    rng: SimRng,
+    nodes: Vec<SimNode>,
+    /// In flight, by (arrival time, send order): (from, to, packet).
+    queue: BTreeMap<(u64, u64), (NodeId, NodeId, Packet)>,
+    seq: u64,
+    latency_ms: u64,
+    jitter_ms: u64,
+    loss: f64,
+    /// Cut links, both directions.
+    partitions: HashSet<(NodeId, NodeId)>,
+}
+
+impl SimNetwork {
+    /// Empty network: 10ms links, no jitter, no loss.
+    pub fn new(seed: u64) -> Self {
+        set_sim_clock(Some(SIM_EPOCH_MS));
+        Self {
+            now: SIM_EPOCH_MS,
+            rng: SimRng(seed),
+            nodes: Vec::new(),
+            queue: BTreeMap::new(),
+            seq: 0,
+            latency_ms: 10,
+            jitter_ms: 0,
+            loss: 0.0,
+            partitions: HashSet::new(),
+        }
+    }
+
+    /// Every packet takes `latency_ms` plus up to `jitter_ms` more (so
+    /// packets sent close together can arrive out of order).
+    pub fn with_latency(mut self, latency_ms: u64, jitter_ms: u64) -> Self {
+        self.latency_ms = latency_ms;
+        self.jitter_ms = jitter_ms;
+        self
+    }
+
+    /// Drop each packet with probability `loss` (a dropped fallback send
+    /// fails, like an unreachable peer).
+    pub fn set_loss(&mut self, loss: f64) {
+        self.loss = loss.clamp(0.0, 1.0);
+    }
+
+    /// Add a node, built on the virtual clock (DHT off). Returns its id.
+    pub fn add_node(&mut self, node_id: NodeId, secret_seed: [u8; 32], config: RuntimeConfig) -> NodeId {
+        let config = RuntimeConfig {
+            enable_dht: false,
+            ..config
+        };
+        let state = RuntimeState::new(node_id, secret_seed, config);
+        let timers = Self::timers(&state, self.now);
+        self.nodes.push(SimNode {
+            state,
+            messages: Vec::new(),
+            status_changes: Vec::new(),
+            events: Vec::new(),
+            responses: Vec::new(),
+            online: true,
+            channels: HashSet::new(),
+            timers,
+        });
+        node_id
+    }
+
+    /// The runtime loop's timers, first firing one period from `now`.
+    fn timers(state: &RuntimeState, now: u64) -> Vec<Timer> {
+        let config = &state.config;
+        let ms = |d: Duration| (d.as_millis() as u64).max(1);
+        let timers: [(u64, Tick); 13] = [
+            (ms(config.cache_cleanup_interval), RuntimeState::tick_cache_cleanup),
+            (ms(config.tracker_cleanup_interval), RuntimeState::tick_tracker_cleanup),
+            (config.liveness.heartbeat_interval_ms.max(1), RuntimeState::tick_heartbeat),
+            (ms(config.group_hub_heartbeat_interval), RuntimeState::tick_group_hub_heartbeat),
+            (ms(config.shadow_ping_interval), RuntimeState::tick_shadow_ping),
+            (HUB_CLEANUP_MS, RuntimeState::tick_hub_cleanup),
+            (ms(config.backup_tick_interval), RuntimeState::tick_backup),
+            (ms(config.backup_delivery_interval), RuntimeState::tick_backup_delivery),
+            (ms(config.backup_integrity_interval), RuntimeState::tick_backup_integrity),
+            (config.liveness.announce_interval_ms.max(1), |s| {
+                s.build_gossip_announce().map(RuntimeEffect::BroadcastAnnounce).into_iter().collect()
+            }),
+            (config.subnet.evaluation_interval_ms.max(1), RuntimeState::tick_subnets),
+            (ROLE_EVAL_MS, RuntimeState::tick_roles),
+            (DELIVERY_DEADLINE_MS, |s| {
+                let mut effects = s.tick_delivery_deadlines();
+                effects.extend(s.tick_exactly_once());
+                effects.extend(s.tick_scheduled_messages());
+                effects.extend(s.tick_ephemeral_messages());
+                effects
+            }),
+        ];
+        timers
+            .into_iter()
+            .map(|(every, tick)| Timer {
+                every,
+                next: now + every,
+                tick,
+            })
+            .collect()
+    }
+
+    /// Current virtual time (Unix ms).
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn nodes(&self) -> &[SimNode] {
+        &self.nodes
+    }
+
+    pub fn node(&self, id: NodeId) -> &SimNode {
+        &self.nodes[self.index(id)]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut SimNode {
+        let i = self.index(id);
+        &mut self.nodes[i]
+    }
+
+    fn index(&self, id: NodeId) -> usize {
+        self.nodes
+            .iter()
+            .position(|n| n.id() == id)
+            .unwrap_or_else(|| panic!("no simulated node {id}"))
+    }
+
+    /// Have every node announce itself now, so they know each other
+    /// without waiting for the announce timer.
+    pub fn announce_all(&mut self) {
+        for i in 0..self.nodes.len() {
+            let node = &self.nodes[i];
+            if !node.online {
+                continue;
+            }
+            if let Some(bytes) = node.state.build_gossip_announce() {
+                let from = node.id();
+                self.gossip(from, bytes);
+            }
+        }
+    }
+
+    /// Run an application command on `node`.
+    pub fn command(&mut self, node: NodeId, command: RuntimeCommand) {
+        let i = self.index(node);
+        let effects = self.nodes[i].state.handle_command(command);
+        self.execute(i, effects);
+    }
+
+    /// Cut the link between `a` and `b` (both ways).
+    pub fn partition(&mut self, a: NodeId, b: NodeId) {
+        self.partitions.insert((a, b));
+        self.partitions.insert((b, a));
+    }
+
+    /// Restore the link between `a` and `b`.
+    pub fn heal(&mut self, a: NodeId, b: NodeId) {
+        self.partitions.remove(&(a, b));
+        self.partitions.remove(&(b, a));
+    }
+
+    /// Take a node off the network (no timers, nothing in or out) or bring
+    /// it back.
+    pub fn set_online(&mut self, node: NodeId, online: bool) {
+        let i = self.index(node);
+        self.nodes[i].online = online;
+        let effects = self.nodes[i].state.set_connectivity(online);
+        if online {
+            self.execute(i, effects);
+        }
+    }
+
+    /// Advance the clock by `duration`, delivering packets and firing
+    /// timers in time order (packets first on a tie, then nodes in the
+    /// order they were added).
+    pub fn run_for(&mut self, duration: Duration) {
+        let end = self.now + duration.as_millis() as u64;
+        loop {
+            let next_packet = self.queue.keys().next().map(|(at, _)| *at);
+            let next_timer = self
+                .nodes
+                .iter()
+                .filter(|n| n.online)
+                .flat_map(|n| n.timers.iter().map(|t| t.next))
+                .min();
+            let next = match (next_packet, next_timer) {
+                (Some(p), Some(t)) => p.min(t),
+                (p, t) => match p.or(t) {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+            if next > end {
+                break;
+            }
+            self.set_now(next);
+            if next_packet == Some(next) {
+                let (_, (from, to, packet)) = self.queue.pop_first().expect("peeked");
+                self.deliver(from, to, packet);
+            } else {
+                self.fire_timers(next);
+            }
+        }
+        self.set_now(end);
+    }
+
+    fn set_now(&mut self, now: u64) {
+        self.now = now;
+        set_sim_clock(Some(now));
+    }
+
+    fn fire_timers(&mut self, now: u64) {
+        for i in 0..self.nodes.len() {
+            if !self.nodes[i].online {
+                continue;
+            }
+            for t in 0..self.nodes[i].timers.len() {
+                let timer = &mut self.nodes[i].timers[t];
+                if timer.next != now {
+                    continue;
+                }
+                timer.next += timer.every;
+                let tick = timer.tick;
+                let effects = tick(&mut self.nodes[i].state);
+                self.execute(i, effects);
+            }
+        }
+    }
+
+    fn deliver(&mut self, from: NodeId, to: NodeId, packet: Packet) {
+        let Some(i) = self.nodes.iter().position(|n| n.id() == to) else {
+            return;
+        };
+        if !self.nodes[i].online || self.partitions.contains(&(from, to)) {
+            return;
+        }
+        let state = &mut self.nodes[i].state;
+        let effects = match packet {
+            Packet::Envelope(bytes) => state.handle_incoming(&bytes),
+            Packet::Gossip(bytes) => state.handle_gossip_event(GossipInput::PeerAnnounce(bytes)),
+            Packet::Channel { topic, bytes } => state.handle_channel_publication(topic, &bytes),
+            Packet::Datagram(bytes) => state.handle_datagram(from, &bytes),
+        };
+        self.execute(i, effects);
+    }
+
+    /// Whether a packet from `from` to `to` gets on the wire (rolls loss).
+    fn reachable(&mut self, from: NodeId, to: NodeId) -> bool {
+        let up = self.nodes.iter().any(|n| n.id() == to && n.online)
+            && !self.partitions.contains(&(from, to));
+        up && (self.loss == 0.0 || self.rng.roll() >= self.loss)
+    }
+
+    fn enqueue(&mut self, from: NodeId, to: NodeId, packet: Packet) {
+        let jitter = match self.jitter_ms {
+            0 => 0,
+            j => self.rng.next_u64() % (j + 1),
+        };
+        let at = self.now + self.latency_ms + jitter;
+        self.seq += 1;
+        self.queue.insert((at, self.seq), (from, to, packet));
+    }
+
+    /// Send an envelope to its next hop; false if it can't get there.
+    fn send(&mut self, from: NodeId, target: NodeId, envelope: &Envelope) -> bool {
+        let Ok(bytes) = envelope.to_bytes() else {
+            return false;
+        };
+        if !self.reachable(from, target) {
+            return false;
+        }
+        self.enqueue(from, target, Packet::Envelope(bytes));
+        true
+    }
+
+    /// Gossip to every other node (the overlay is modelled as a full mesh).
+    fn gossip(&mut self, from: NodeId, bytes: Vec<u8>) {
+        let targets: Vec<NodeId> = self.nodes.iter().map(SimNode::id).filter(|id| *id != from).collect();
+        for to in targets {
+            if self.reachable(from, to) {
+                self.enqueue(from, to, Packet::Gossip(bytes.clone()));
+            }
+        }
+    }
+
+    /// Play the runtime loop and executor for effects of node `i`.
+    fn execute(&mut self, i: usize, effects: Vec<RuntimeEffect>) {
+        let from = self.nodes[i].id();
+        let effects = self.nodes[i].state.apply_hooks(effects);
+        for effect in effects {
+            match effect {
+                RuntimeEffect::SendEnvelope(envelope) => {
+                    let target = envelope.via.first().copied().unwrap_or(envelope.to);
+                    self.send(from, target, &envelope);
+                }
+                RuntimeEffect::SendEnvelopeTo { target, envelope } => {
+                    self.send(from, target, &envelope);
+                }
+                RuntimeEffect::SendWithBackupFallback {
+                    envelope,
+                    on_success,
+                    on_failure,
+                } => {
+                    let target = envelope.via.first().copied().unwrap_or(envelope.to);
+                    let next = if self.send(from, target, &envelope) {
+                        on_success
+                    } else {
+                        on_failure
+                    };
+                    self.execute(i, next);
+                }
+                RuntimeEffect::DeliverMessage(message) => self.nodes[i].messages.push(message),
+                RuntimeEffect::StatusChange(change) => self.nodes[i].status_changes.push(change),
+                RuntimeEffect::Emit(event) => self.nodes[i].events.push(event),
+                RuntimeEffect::BroadcastRoleChange(announce) => {
+                    if let Ok(bytes) = rmp_serde::to_vec(&announce) {
+                        self.gossip(from, bytes);
+                    }
+                }
+                RuntimeEffect::BroadcastAbuseReport(report) => {
+                    if let Ok(bytes) = rmp_serde::to_vec(&report) {
+                        self.gossip(from, bytes);
+                    }
+                }
+                RuntimeEffect::BroadcastRelayAttestation(attestation) => {
+                    if let Ok(bytes) = rmp_serde::to_vec(&attestation) {
+                        self.gossip(from, bytes);
+                    }
+                }
+                RuntimeEffect::BroadcastAnnounce(bytes) => self.gossip(from, bytes),
+                RuntimeEffect::SubscribeChannel { topic } => {
+                    self.nodes[i].channels.insert(topic);
+                }
+                RuntimeEffect::UnsubscribeChannel { topic } => {
+                    self.nodes[i].channels.remove(&topic);
+                }
+                RuntimeEffect::PublishChannel { topic, bytes } => {
+                    let subscribers: Vec<NodeId> = self
+                        .nodes
+                        .iter()
+                        .filter(|n| n.id() != from && n.channels.contains(&topic))
+                        .map(SimNode::id)
+                        .collect();
+                    for to in subscribers {
+                        if self.reachable(from, to) {
+                            let bytes = bytes.clone();
+                            self.enqueue(from, to, Packet::Channel { topic, bytes });
+                        }
+                    }
+                }
+                RuntimeEffect::CompleteRequest(response) => self.nodes[i].responses.push(response),
+                RuntimeEffect::SendDatagram { to, bytes } => {
+                    if self.reachable(from, to) {
+                        self.enqueue(from, to, Packet::Datagram(bytes));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for SimNetwork {
+    fn drop(&mut self) {
+        set_sim_clock(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::OFFLINE_THRESHOLD_MS;
+    use rand::SeedableRng;
+
+    fn keypair(seed: u64) -> (NodeId, [u8; 32]) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        (secret.public().to_string().parse().unwrap(), secret.to_bytes())
+    }
+
+    fn network(seed: u64, nodes: u64) -> (SimNetwork, Vec<NodeId>) {
+        let mut sim = SimNetwork::new(seed).with_latency(20, 30);
+        let ids = (1..=nodes)
+            .map(|n| {
+                let (id, secret) = keypair(n);
+                sim.add_node(id, secret, RuntimeConfig::default())
+            })
+            .collect();
+        sim.announce_all();
+        sim.run_for(Duration::from_secs(1));
+        (sim, ids)
+    }
+
+    #[test]
+    fn messages_are_delivered_on_the_virtual_clock() {
+        let (mut sim, ids) = network(7, 2);
+        assert_eq!(crate::types::now_ms(), sim.now());
+        for n in 0..5u8 {
+            sim.command(ids[0], RuntimeCommand::SendMessage { to: ids[1], payload: vec![n], reply: None });
+        }
+        sim.run_for(Duration::from_secs(1));
+
+        let received = &sim.node(ids[1]).messages;
+        assert_eq!(received.len(), 5);
+        assert!(received.iter().all(|m| m.from == ids[0] && m.signature_valid));
+        assert!(
+            sim.node(ids[0]).status_changes.iter().any(|c| c.current == crate::types::MessageStatus::Delivered),
+            "ACKs came back"
+        );
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let run = |seed| {
+            let (mut sim, ids) = network(seed, 3);
+            sim.set_loss(0.3);
+            for n in 0..20u8 {
+                sim.command(ids[0], RuntimeCommand::SendMessage { to: ids[1], payload: vec![n], reply: None });
+            }
+            sim.run_for(Duration::from_secs(2));
+            let payloads: Vec<u8> = sim.node(ids[1]).messages.iter().map(|m| m.payload[0]).collect();
+            payloads
+        };
+        assert_eq!(run(42), run(42));
+    }
+
+    #[test]
+    fn offline_node_is_detected_without_waiting() {
+        let (mut sim, ids) = network(1, 3);
+        sim.set_online(ids[2], false);
+        let started = std::time::Instant::now();
+        sim.run_for(Duration::from_millis(OFFLINE_THRESHOLD_MS * 2));
+        assert!(started.elapsed() < Duration::from_secs(30));
+
+        for watcher in &ids[..2] {
+            assert!(sim
+                .node(*watcher)
+                .events
+                .iter()
+                .any(|e| matches!(e, ProtocolEvent::PeerOffline { node_id } if *node_id == ids[2])));
+        }
+        assert!(!sim
+            .node(ids[0])
+            .events
+            .iter()
+            .any(|e| matches!(e, ProtocolEvent::PeerOffline { node_id } if *node_id == ids[1])));
+    }
+
+    #[test]
+    fn realtime_lane_reports_loss_reordering_and_jitter() {
+        let (mut sim, ids) = network(5, 2);
+        sim.set_loss(0.1);
+        for n in 0..200u8 {
+            sim.command(ids[0], RuntimeCommand::SendRealtime { to: ids[1], payload: vec![n] });
+            sim.run_for(Duration::from_millis(20));
+        }
+        sim.run_for(Duration::from_secs(1));
+
+        let received = sim
+            .node(ids[1])
+            .events
+            .iter()
+            .filter(|e| matches!(e, ProtocolEvent::RealtimeReceived { from, .. } if *from == ids[0]))
+            .count() as u64;
+        let stats = sim.node(ids[1]).state.realtime_stats(&ids[0]).unwrap();
+        assert_eq!(stats.received, received);
+        assert!(stats.lost > 0 && stats.received + stats.lost <= 200, "{stats:?}");
+        assert!(stats.reordered > 0 && stats.jitter_ms > 0.0, "{stats:?}");
+        assert_eq!(sim.node(ids[0]).state.realtime_stats(&ids[1]).unwrap().sent, 200);
+        // Nothing retried or tracked
+        assert!(sim.node(ids[0]).status_changes.is_empty());
+    }
+
+    #[test]
+    fn partitions_block_traffic_until_healed() {
+        let (mut sim, ids) = network(3, 2);
+        sim.partition(ids[0], ids[1]);
+        sim.command(ids[0], RuntimeCommand::SendMessage { to: ids[1], payload: b"cut".to_vec(), reply: None });
+        sim.run_for(Duration::from_secs(1));
+        assert!(sim.node(ids[1]).messages.is_empty());
+
+        sim.heal(ids[0], ids[1]);
+        sim.command(ids[0], RuntimeCommand::SendMessage { to: ids[1], payload: b"up".to_vec(), reply: None });
+        sim.run_for(Duration::from_secs(1));
+        assert_eq!(sim.node(ids[1]).messages.len(), 1);
+    }
+}
@@ -1,28 +1,192 @@
 /// State persistence for the ToM protocol runtime.
 ///
-/// Stores groups, sender keys, contacts, and hub state in SQLite.
+/// Stores groups, sender keys, contacts, and hub state behind the
+/// [`StateStore`] trait; [`SqliteStateStore`] is the built-in backend.
 /// Designed for fast reads on startup and periodic batched writes.
+#[cfg(feature = "native")]
 mod schema;
 
 use std::collections::HashMap;
+#[cfg(feature = "native")]
 use std::path::Path;
 
+#[cfg(feature = "native")]
 use std::sync::Mutex;
 
-use rusqlite::Connection;
-
-use crate::group::{GroupHubSnapshot, GroupId, GroupInfo, GroupManagerSnapshot};
-use crate::group::SenderKeyEntry;
-use crate::relay::{PeerInfo, PeerRole, PeerStatus};
+#[cfg(feature = "native")]
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::group::{GroupHubSnapshot, GroupId, GroupManagerSnapshot};
+#[cfg(feature = "native")]
+use crate::group::{GroupInfo, GroupMessage, GroupNotificationPrefs};
+use crate::contact::Contact;
+use crate::exactly_once::{InboxRecord, OutboxEntry};
+use crate::history_sync::DirectEntry;
+use crate::group::MeshGroupSnapshot;
+#[cfg(feature = "native")]
+use crate::group::{MeshGroup, SenderKeyEntry};
+use crate::outbox::QueuedMessage;
+use crate::relay::PeerInfo;
+#[cfg(feature = "native")]
+use crate::relay::{PeerRole, PeerStatus};
 use crate::roles::ContributionMetrics;
+use crate::shared_state::SharedStateSnapshot;
+#[cfg(feature = "native")]
+use crate::shared_state::SharedDoc;
 use crate::tracker::TrackedMessageRecord;
-use crate::types::{MessageStatus, NodeId};
+use crate::types::NodeId;
+#[cfg(feature = "native")]
+use crate::types::MessageStatus;
+
+/// Failure reported by a [`StateStore`] backend.
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    /// The backend failed to read or write (I/O, SQL, lock...).
+    #[error("storage backend error: {0}")]
+    Backend(String),
+
+    /// Stored data could not be decoded.
+    #[error("corrupt stored state: {0}")]
+    Corrupt(String),
+}
+
+#[cfg(feature = "native")]
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::FromSqlConversionFailure(..)
+            | rusqlite::Error::InvalidColumnType(..)
+            | rusqlite::Error::IntegralValueOutOfRange(..) => StorageError::Corrupt(e.to_string()),
+            e => StorageError::Backend(e.to_string()),
+        }
+    }
+}
+
+/// Persistent runtime state: groups, hub history, topology, scores,
+/// tracked messages, contacts, exactly-once and offline queues.
+///
+/// `RuntimeState` keeps everything in memory and mirrors it here, with
+/// periodic snapshots (`save`) plus write-through for the few things that
+/// must survive a crash. [`SqliteStateStore`] is the built-in backend; set
+/// `RuntimeConfig::state_store` to plug in another.
+pub trait StateStore: Send + Sync {
+    /// The node this state belongs to, if recorded.
+    fn load_identity(&self) -> Result<Option<NodeId>, StorageError>;
+
+    /// Record the node this state belongs to.
+    fn save_identity(&self, node_id: &NodeId) -> Result<(), StorageError>;
+
+    // ── Save methods ────────────────────────────────────────────────────
+
+    /// Save all persistent state in a single transaction.
+    fn save(&self, snapshot: &StateSnapshot) -> Result<(), StorageError>;
+
+    /// Checkpoint hub state alone (called whenever a hosted group changes).
+    fn save_hub(&self, hub: &GroupHubSnapshot) -> Result<(), StorageError>;
+
+    // ── Hub message history (R13) ────────────────────────────────────
+
+    /// Save a single hub message to history (called after each handle_message).
+    fn save_hub_message(
+        &self,
+        group_id: &GroupId,
+        seq: u64,
+        message_data: &[u8],
+        stored_at: u64,
+    ) -> Result<(), StorageError>;
+
+    /// Load hub messages for a group since a given sequence number.
+    /// Returns messages ordered by seq ascending, limited to `max_count`.
+    fn load_hub_messages_since(
+        &self,
+        group_id: &GroupId,
+        since_seq: u64,
+        max_count: usize,
+    ) -> Result<Vec<(u64, Vec<u8>)>, StorageError>;
+
+    /// Load up to `max_count` hub messages with `seq < before_seq` (the
+    /// newest ones first, for scroll-back). Returned ascending by seq.
+    fn load_hub_messages_before(
+        &self,
+        group_id: &GroupId,
+        before_seq: u64,
+        max_count: usize,
+    ) -> Result<Vec<(u64, Vec<u8>)>, StorageError>;
+
+    /// Delete expired hub messages (TTL cleanup).
+    fn cleanup_hub_messages(&self, cutoff_ms: u64) -> Result<usize, StorageError>;
+
+    /// Delete one group's hub messages stored at or before `cutoff_ms`
+    /// (disappearing-message expiry).
+    fn expire_hub_messages(&self, group_id: &GroupId, cutoff_ms: u64) -> Result<usize, StorageError>;
+
+    // ── Exactly-once delivery (written through, not part of snapshots) ──
+
+    /// Persist (or update) an outbox entry.
+    fn save_outbox_entry(&self, entry: &OutboxEntry) -> Result<(), StorageError>;
+
+    /// Remove a committed or expired outbox entry.
+    fn delete_outbox_entry(&self, message_id: &str) -> Result<(), StorageError>;
+
+    /// Load all pending outbox entries.
+    fn load_outbox(&self) -> Result<Vec<OutboxEntry>, StorageError>;
+
+    // ── Contacts (also written through so blocks survive a crash) ───────
+
+    /// Persist one contact.
+    fn save_contact(&self, contact: &Contact) -> Result<(), StorageError>;
+
+    /// Remove one contact.
+    fn delete_contact(&self, node_id: &NodeId) -> Result<(), StorageError>;
+
+    // ── Offline outbox (written through, not part of snapshots) ─────────
+
+    /// Persist a message queued while offline.
+    fn save_queued_message(&self, message: &QueuedMessage) -> Result<(), StorageError>;
+
+    /// Remove a flushed queued message.
+    fn delete_queued_message(&self, message_id: &str) -> Result<(), StorageError>;
 
-/// SQLite-backed state store.
+    /// Load all messages still queued.
+    fn load_queued_messages(&self) -> Result<Vec<QueuedMessage>, StorageError>;
+
+    /// Persist (or update) an inbox dedup record.
+    fn save_inbox_record(
+        &self,
+        from: &NodeId,
+        message_id: &str,
+        record: &InboxRecord,
+    ) -> Result<(), StorageError>;
+
+    /// Load all inbox dedup records.
+    fn load_inbox(&self) -> Result<Vec<(NodeId, String, InboxRecord)>, StorageError>;
+
+    /// Delete inbox records received before `cutoff_ms`.
+    fn cleanup_inbox(&self, cutoff_ms: u64) -> Result<usize, StorageError>;
+
+    // ── 1:1 history log (written through, not part of snapshots) ────────
+
+    /// Persist one entry of the 1:1 history log.
+    fn save_history_entry(&self, entry: &DirectEntry) -> Result<(), StorageError>;
+
+    /// Load the newest `max_count` history entries, oldest first.
+    fn load_history(&self, max_count: usize) -> Result<Vec<DirectEntry>, StorageError>;
+
+    /// Delete history entries older than `timestamp`.
+    fn delete_history_before(&self, timestamp: u64) -> Result<usize, StorageError>;
+
+    // ── Load methods ────────────────────────────────────────────────────
+
+    /// Load all persistent state.
+    fn load(&self) -> Result<StateSnapshot, StorageError>;
+}
+
+/// SQLite-backed [`StateStore`].
 ///
 /// Wraps Connection in Mutex for Sync (required because RuntimeState
 /// holds &self across .await points in tokio::spawn).
-pub struct StateStore {
+#[cfg(feature = "native")]
+pub struct SqliteStateStore {
     conn: Mutex<Connection>,
 }
 
@@ -34,11 +198,15 @@ pub struct StateSnapshot {
     pub peers: HashMap<NodeId, PeerInfo>,
     pub metrics: HashMap<NodeId, ContributionMetrics>,
     pub tracked_messages: HashMap<String, TrackedMessageRecord>,
+    pub shared: Option<SharedStateSnapshot>,
+    pub mesh: Option<MeshGroupSnapshot>,
+    pub contacts: HashMap<NodeId, Contact>,
 }
 
-impl StateStore {
+#[cfg(feature = "native")]
+impl SqliteStateStore {
     /// Open (or create) a state database at the given path.
-    pub fn open(path: &Path) -> Result<Self, rusqlite::Error> {
+    pub fn open(path: &Path) -> Result<Self, StorageError> {
         // Create parent directories if needed
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent).ok();
@@ -57,36 +225,12 @@ impl StateStore {
 
     /// Open an in-memory database (for testing).
     #[cfg(test)]
-    pub fn open_memory() -> Result<Self, rusqlite::Error> {
+    pub fn open_memory() -> Result<Self, StorageError> {
         let conn = Connection::open_in_memory()?;
         schema::initialize(&conn)?;
         Ok(Self { conn: Mutex::new(conn) })
     }
 
-    // ── Save methods ────────────────────────────────────────────────────
-
-    /// Save all persistent state in a single transaction.
-    pub fn save(&self, snapshot: &StateSnapshot) -> Result<(), rusqlite::Error> {
-        let conn = self.conn.lock().unwrap();
-        let tx = conn.unchecked_transaction()?;
-
-        if let Some(ref mgr) = snapshot.manager {
-            self.save_groups_tx(&tx, &mgr.groups, &mgr.last_seqs)?;
-            self.save_sender_keys_tx(&tx, &mgr.local_sender_keys, &mgr.sender_keys)?;
-        }
-
-        if let Some(ref hub) = snapshot.hub {
-            self.save_hub_groups_tx(&tx, hub)?;
-        }
-
-        self.save_peers_tx(&tx, &snapshot.peers)?;
-        self.save_metrics_tx(&tx, &snapshot.metrics)?;
-        self.save_tracked_messages_tx(&tx, &snapshot.tracked_messages)?;
-
-        tx.commit()?;
-        Ok(())
-    }
-
     fn save_groups_tx(
         &self,
         tx: &rusqlite::Transaction,
@@ -105,6 +249,35 @@ impl StateStore {
         Ok(())
     }
 
+    fn save_notification_prefs_tx(
+        &self,
+        tx: &rusqlite::Transaction,
+        prefs: &HashMap<GroupId, GroupNotificationPrefs>,
+    ) -> Result<(), rusqlite::Error> {
+        tx.execute("DELETE FROM group_notification_prefs", [])?;
+        let mut stmt =
+            tx.prepare("INSERT INTO group_notification_prefs (group_id, data) VALUES (?1, ?2)")?;
+        for (gid, p) in prefs {
+            let json = serde_json::to_string(p).unwrap_or_default();
+            stmt.execute(rusqlite::params![gid.to_string(), json])?;
+        }
+        Ok(())
+    }
+
+    fn save_contacts_tx(
+        &self,
+        tx: &rusqlite::Transaction,
+        contacts: &HashMap<NodeId, Contact>,
+    ) -> Result<(), rusqlite::Error> {
+        tx.execute("DELETE FROM contacts", [])?;
+        let mut stmt = tx.prepare("INSERT INTO contacts (node_id, data) VALUES (?1, ?2)")?;
+        for (nid, contact) in contacts {
+            let json = serde_json::to_string(contact).unwrap_or_default();
+            stmt.execute(rusqlite::params![nid.to_string(), json])?;
+        }
+        Ok(())
+    }
+
     fn save_sender_keys_tx(
         &self,
         tx: &rusqlite::Transaction,
@@ -155,6 +328,19 @@ impl StateStore {
             let next_seq = hub.next_seqs.get(gid).copied().unwrap_or(0) as i64;
             stmt.execute(rusqlite::params![gid.to_string(), json, invited_json, next_seq])?;
         }
+
+        tx.execute("DELETE FROM hub_scheduled_messages", [])?;
+        let mut stmt = tx.prepare(
+            "INSERT INTO hub_scheduled_messages (message_id, group_id, deliver_at, message_data) \
+             VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for (gid, messages) in &hub.scheduled {
+            for msg in messages {
+                let data = rmp_serde::to_vec(msg).unwrap_or_default();
+                let deliver_at = msg.deliver_at.unwrap_or(0) as i64;
+                stmt.execute(rusqlite::params![msg.message_id, gid.to_string(), deliver_at, data])?;
+            }
+        }
         Ok(())
     }
 
@@ -169,8 +355,9 @@ impl StateStore {
         )?;
         for (nid, info) in peers {
             let role = match info.role {
-                PeerRole::Peer => "Peer",
                 PeerRole::Relay => "Relay",
+                // Primary roles are only Peer/Relay; extras aren't persisted
+                PeerRole::Peer | PeerRole::Storage | PeerRole::Bootstrap => "Peer",
             };
             let status = match info.status {
                 PeerStatus::Online => "Online",
@@ -217,7 +404,7 @@ impl StateStore {
             stmt.execute(rusqlite::params![
                 msg_id,
                 record.to.to_string(),
-                record.status as i32,
+                status_code(record.status),
                 record.created_ms as i64,
                 record.retries_remaining as i32
             ])?;
@@ -225,107 +412,32 @@ impl StateStore {
         Ok(())
     }
 
-    // ── Hub message history (R13) ────────────────────────────────────
-
-    /// Save a single hub message to history (called after each handle_message).
-    pub fn save_hub_message(
+    fn save_shared_docs_tx(
         &self,
-        group_id: &GroupId,
-        seq: u64,
-        message_data: &[u8],
-        stored_at: u64,
+        tx: &rusqlite::Transaction,
+        shared: &SharedStateSnapshot,
     ) -> Result<(), rusqlite::Error> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "INSERT OR REPLACE INTO hub_message_history (group_id, seq, message_data, stored_at) \
-             VALUES (?1, ?2, ?3, ?4)",
-            rusqlite::params![group_id.to_string(), seq as i64, message_data, stored_at as i64],
-        )?;
+        tx.execute("DELETE FROM shared_docs", [])?;
+        let mut stmt = tx.prepare("INSERT INTO shared_docs (doc_id, data) VALUES (?1, ?2)")?;
+        for (doc_id, doc) in &shared.docs {
+            let json = serde_json::to_string(doc).unwrap_or_default();
+            stmt.execute(rusqlite::params![doc_id, json])?;
+        }
         Ok(())
     }
 
-    /// Load hub messages for a group since a given sequence number.
-    /// Returns messages ordered by seq ascending, limited to `max_count`.
-    pub fn load_hub_messages_since(
+    fn save_mesh_groups_tx(
         &self,
-        group_id: &GroupId,
-        since_seq: u64,
-        max_count: usize,
-    ) -> Result<Vec<(u64, Vec<u8>)>, rusqlite::Error> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT seq, message_data FROM hub_message_history \
-             WHERE group_id = ?1 AND seq > ?2 \
-             ORDER BY seq ASC LIMIT ?3",
-        )?;
-        let rows = stmt.query_map(
-            rusqlite::params![group_id.to_string(), since_seq as i64, max_count as i64],
-            |row| {
-                let seq: i64 = row.get(0)?;
-                let data: Vec<u8> = row.get(1)?;
-                Ok((seq as u64, data))
-            },
-        )?;
-        let mut result = Vec::new();
-        for row in rows {
-            result.push(row?);
+        tx: &rusqlite::Transaction,
+        mesh: &MeshGroupSnapshot,
+    ) -> Result<(), rusqlite::Error> {
+        tx.execute("DELETE FROM mesh_groups", [])?;
+        let mut stmt = tx.prepare("INSERT INTO mesh_groups (group_id, data) VALUES (?1, ?2)")?;
+        for (group_id, group) in &mesh.groups {
+            let json = serde_json::to_string(group).unwrap_or_default();
+            stmt.execute(rusqlite::params![group_id.0, json])?;
         }
-        Ok(result)
-    }
-
-    /// Delete expired hub messages (TTL cleanup).
-    pub fn cleanup_hub_messages(&self, cutoff_ms: u64) -> Result<usize, rusqlite::Error> {
-        let conn = self.conn.lock().unwrap();
-        let deleted = conn.execute(
-            "DELETE FROM hub_message_history WHERE stored_at < ?1",
-            rusqlite::params![cutoff_ms as i64],
-        )?;
-        Ok(deleted)
-    }
-
-    // ── Load methods ────────────────────────────────────────────────────
-
-    /// Load all persistent state.
-    pub fn load(&self) -> Result<StateSnapshot, rusqlite::Error> {
-        let conn = self.conn.lock().unwrap();
-        let (groups, member_last_seqs) = Self::load_groups(&conn)?;
-        let (local_keys, remote_keys) = Self::load_sender_keys(&conn)?;
-        let (hub_groups, hub_invited_sets, hub_next_seqs) = Self::load_hub_groups(&conn)?;
-        let peers = Self::load_peers(&conn)?;
-        let metrics = Self::load_metrics(&conn)?;
-        let tracked_messages = Self::load_tracked_messages(&conn)?;
-
-        let manager = if !groups.is_empty() || !local_keys.is_empty() {
-            Some(GroupManagerSnapshot {
-                groups,
-                local_sender_keys: local_keys,
-                sender_keys: remote_keys,
-                previous_sender_keys: HashMap::new(),
-                local_sender_message_counts: HashMap::new(),
-                message_history: HashMap::new(), // Not persisted (rebuilt via hub sync)
-                last_seqs: member_last_seqs,
-            })
-        } else {
-            None
-        };
-
-        let hub = if !hub_groups.is_empty() {
-            Some(GroupHubSnapshot {
-                groups: hub_groups,
-                invited_sets: hub_invited_sets,
-                next_seqs: hub_next_seqs,
-            })
-        } else {
-            None
-        };
-
-        Ok(StateSnapshot {
-            manager,
-            hub,
-            peers,
-            metrics,
-            tracked_messages,
-        })
+        Ok(())
     }
 
     #[allow(clippy::type_complexity)]
@@ -427,107 +539,601 @@ impl StateStore {
         Ok((groups, invited_sets, next_seqs))
     }
 
-    fn load_peers(conn: &Connection) -> Result<HashMap<NodeId, PeerInfo>, rusqlite::Error> {
-        let mut stmt = conn.prepare("SELECT node_id, role, status, last_seen FROM peers")?;
-        let mut peers = HashMap::new();
+    fn load_peers(conn: &Connection) -> Result<HashMap<NodeId, PeerInfo>, rusqlite::Error> {
+        let mut stmt = conn.prepare("SELECT node_id, role, status, last_seen FROM peers")?;
+        let mut peers = HashMap::new();
+        let rows = stmt.query_map([], |row| {
+            let nid: String = row.get(0)?;
+            let role: String = row.get(1)?;
+            let status: String = row.get(2)?;
+            let last_seen: i64 = row.get(3)?;
+            Ok((nid, role, status, last_seen))
+        })?;
+        for row in rows {
+            let (nid, role, status, last_seen) = row?;
+            let Ok(node_id) = nid.parse::<NodeId>() else {
+                continue;
+            };
+            let role = match role.as_str() {
+                "Relay" => PeerRole::Relay,
+                _ => PeerRole::Peer,
+            };
+            let status = match status.as_str() {
+                "Online" => PeerStatus::Online,
+                "Stale" => PeerStatus::Stale,
+                _ => PeerStatus::Offline, // All peers start offline after restart
+            };
+            peers.insert(
+                node_id,
+                PeerInfo {
+                    node_id,
+                    role,
+                    status,
+                    last_seen: last_seen as u64,
+                },
+            );
+        }
+        Ok(peers)
+    }
+
+    fn load_shared_docs(conn: &Connection) -> Result<HashMap<String, SharedDoc>, rusqlite::Error> {
+        let mut stmt = conn.prepare("SELECT doc_id, data FROM shared_docs")?;
+        let mut docs = HashMap::new();
+        let rows = stmt.query_map([], |row| {
+            let doc_id: String = row.get(0)?;
+            let json: String = row.get(1)?;
+            Ok((doc_id, json))
+        })?;
+        for row in rows {
+            let (doc_id, json) = row?;
+            if let Ok(doc) = serde_json::from_str::<SharedDoc>(&json) {
+                docs.insert(doc_id, doc);
+            }
+        }
+        Ok(docs)
+    }
+
+    fn load_hub_scheduled(
+        conn: &Connection,
+    ) -> Result<HashMap<GroupId, Vec<GroupMessage>>, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT group_id, message_data FROM hub_scheduled_messages ORDER BY deliver_at",
+        )?;
+        let mut scheduled: HashMap<GroupId, Vec<GroupMessage>> = HashMap::new();
+        let rows = stmt.query_map([], |row| {
+            let gid: String = row.get(0)?;
+            let data: Vec<u8> = row.get(1)?;
+            Ok((gid, data))
+        })?;
+        for row in rows {
+            let (gid, data) = row?;
+            if let Ok(msg) = rmp_serde::from_slice::<GroupMessage>(&data) {
+                scheduled.entry(GroupId::from(gid)).or_default().push(msg);
+            }
+        }
+        Ok(scheduled)
+    }
+
+    fn load_notification_prefs(
+        conn: &Connection,
+    ) -> Result<HashMap<GroupId, GroupNotificationPrefs>, rusqlite::Error> {
+        let mut stmt = conn.prepare("SELECT group_id, data FROM group_notification_prefs")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut prefs = HashMap::new();
+        for row in rows {
+            let (gid, json) = row?;
+            if let Ok(p) = serde_json::from_str::<GroupNotificationPrefs>(&json) {
+                prefs.insert(GroupId::from(gid), p);
+            }
+        }
+        Ok(prefs)
+    }
+
+    /// Rows written before the contact store hold plain cards; they load
+    /// as unverified, unblocked contacts.
+    fn load_contacts(conn: &Connection) -> Result<HashMap<NodeId, Contact>, rusqlite::Error> {
+        let mut stmt = conn.prepare("SELECT data FROM contacts")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut contacts = HashMap::new();
+        for row in rows {
+            if let Ok(contact) = serde_json::from_str::<Contact>(&row?) {
+                contacts.insert(contact.node_id(), contact);
+            }
+        }
+        Ok(contacts)
+    }
+
+    fn load_mesh_groups(
+        conn: &Connection,
+    ) -> Result<HashMap<GroupId, MeshGroup>, rusqlite::Error> {
+        let mut stmt = conn.prepare("SELECT data FROM mesh_groups")?;
+        let mut groups = HashMap::new();
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        for row in rows {
+            if let Ok(group) = serde_json::from_str::<MeshGroup>(&row?) {
+                groups.insert(group.group_id.clone(), group);
+            }
+        }
+        Ok(groups)
+    }
+
+    fn load_metrics(conn: &Connection) -> Result<HashMap<NodeId, ContributionMetrics>, rusqlite::Error> {
+        let mut stmt = conn.prepare("SELECT node_id, data FROM contribution_metrics")?;
+        let mut metrics = HashMap::new();
+        let rows = stmt.query_map([], |row| {
+            let nid: String = row.get(0)?;
+            let json: String = row.get(1)?;
+            Ok((nid, json))
+        })?;
+        for row in rows {
+            let (nid, json) = row?;
+            let Ok(node_id) = nid.parse::<NodeId>() else {
+                continue;
+            };
+            if let Ok(m) = serde_json::from_str::<ContributionMetrics>(&json) {
+                metrics.insert(node_id, m);
+            }
+        }
+        Ok(metrics)
+    }
+
+    fn load_tracked_messages(
+        conn: &Connection,
+    ) -> Result<HashMap<String, TrackedMessageRecord>, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT message_id, to_node_id, status, created_ms, retries_remaining FROM tracked_messages",
+        )?;
+        let mut messages = HashMap::new();
+        let rows = stmt.query_map([], |row| {
+            let msg_id: String = row.get(0)?;
+            let to: String = row.get(1)?;
+            let status: i32 = row.get(2)?;
+            let created_ms: i64 = row.get(3)?;
+            let retries: i32 = row.get(4)?;
+            Ok((msg_id, to, status, created_ms, retries))
+        })?;
+        for row in rows {
+            let (msg_id, to, status_int, created_ms, retries) = row?;
+            let Ok(to_id) = to.parse::<NodeId>() else {
+                continue;
+            };
+            let status = status_from_code(status_int);
+            messages.insert(
+                msg_id,
+                TrackedMessageRecord {
+                    to: to_id,
+                    status,
+                    created_ms: created_ms as u64,
+                    retries_remaining: retries as u8,
+                },
+            );
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(feature = "native")]
+impl StateStore for SqliteStateStore {
+    fn load_identity(&self) -> Result<Option<NodeId>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let stored: Option<String> = conn
+            .query_row("SELECT node_id FROM identity WHERE id = 0", [], |row| row.get(0))
+            .optional()?;
+        Ok(stored.and_then(|id| id.parse().ok()))
+    }
+
+    fn save_identity(&self, node_id: &NodeId) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO identity (id, node_id) VALUES (0, ?1)",
+            rusqlite::params![node_id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    fn save(&self, snapshot: &StateSnapshot) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        if let Some(ref mgr) = snapshot.manager {
+            self.save_groups_tx(&tx, &mgr.groups, &mgr.last_seqs)?;
+            self.save_sender_keys_tx(&tx, &mgr.local_sender_keys, &mgr.sender_keys)?;
+            self.save_notification_prefs_tx(&tx, &mgr.notification_prefs)?;
+        }
+
+        if let Some(ref hub) = snapshot.hub {
+            self.save_hub_groups_tx(&tx, hub)?;
+        }
+
+        self.save_peers_tx(&tx, &snapshot.peers)?;
+        self.save_contacts_tx(&tx, &snapshot.contacts)?;
+        self.save_metrics_tx(&tx, &snapshot.metrics)?;
+        self.save_tracked_messages_tx(&tx, &snapshot.tracked_messages)?;
+
+        if let Some(ref shared) = snapshot.shared {
+            self.save_shared_docs_tx(&tx, shared)?;
+        }
+
+        if let Some(ref mesh) = snapshot.mesh {
+            self.save_mesh_groups_tx(&tx, mesh)?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_hub(&self, hub: &GroupHubSnapshot) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        self.save_hub_groups_tx(&tx, hub)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_hub_message(
+        &self,
+        group_id: &GroupId,
+        seq: u64,
+        message_data: &[u8],
+        stored_at: u64,
+    ) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO hub_message_history (group_id, seq, message_data, stored_at) \
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![group_id.to_string(), seq as i64, message_data, stored_at as i64],
+        )?;
+        Ok(())
+    }
+
+    fn load_hub_messages_since(
+        &self,
+        group_id: &GroupId,
+        since_seq: u64,
+        max_count: usize,
+    ) -> Result<Vec<(u64, Vec<u8>)>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT seq, message_data FROM hub_message_history \
+             WHERE group_id = ?1 AND seq > ?2 \
+             ORDER BY seq ASC LIMIT ?3",
+        )?;
+        let rows = stmt.query_map(
+            rusqlite::params![group_id.to_string(), since_seq as i64, max_count as i64],
+            |row| {
+                let seq: i64 = row.get(0)?;
+                let data: Vec<u8> = row.get(1)?;
+                Ok((seq as u64, data))
+            },
+        )?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    fn load_hub_messages_before(
+        &self,
+        group_id: &GroupId,
+        before_seq: u64,
+        max_count: usize,
+    ) -> Result<Vec<(u64, Vec<u8>)>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT seq, message_data FROM hub_message_history \
+             WHERE group_id = ?1 AND seq < ?2 \
+             ORDER BY seq DESC LIMIT ?3",
+        )?;
+        // SQLite integers are signed: clamp so u64::MAX means "latest".
+        let before = before_seq.min(i64::MAX as u64) as i64;
+        let rows = stmt.query_map(
+            rusqlite::params![group_id.to_string(), before, max_count as i64],
+            |row| {
+                let seq: i64 = row.get(0)?;
+                let data: Vec<u8> = row.get(1)?;
+                Ok((seq as u64, data))
+            },
+        )?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        result.reverse();
+        Ok(result)
+    }
+
+    fn cleanup_hub_messages(&self, cutoff_ms: u64) -> Result<usize, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM hub_message_history WHERE stored_at < ?1",
+            rusqlite::params![cutoff_ms as i64],
+        )?;
+        Ok(deleted)
+    }
+
+    fn expire_hub_messages(&self, group_id: &GroupId, cutoff_ms: u64) -> Result<usize, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM hub_message_history WHERE group_id = ?1 AND stored_at <= ?2",
+            rusqlite::params![group_id.to_string(), cutoff_ms as i64],
+        )?;
+        Ok(deleted)
+    }
+
+    fn save_outbox_entry(&self, entry: &OutboxEntry) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let json = serde_json::to_string(entry).unwrap_or_default();
+        conn.execute(
+            "INSERT OR REPLACE INTO exactly_once_outbox (message_id, data) VALUES (?1, ?2)",
+            rusqlite::params![entry.message_id, json],
+        )?;
+        Ok(())
+    }
+
+    fn delete_outbox_entry(&self, message_id: &str) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM exactly_once_outbox WHERE message_id = ?1",
+            rusqlite::params![message_id],
+        )?;
+        Ok(())
+    }
+
+    fn load_outbox(&self) -> Result<Vec<OutboxEntry>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT data FROM exactly_once_outbox")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut entries = Vec::new();
+        for row in rows {
+            if let Ok(entry) = serde_json::from_str::<OutboxEntry>(&row?) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    fn save_contact(&self, contact: &Contact) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let json = serde_json::to_string(contact).unwrap_or_default();
+        conn.execute(
+            "INSERT OR REPLACE INTO contacts (node_id, data) VALUES (?1, ?2)",
+            rusqlite::params![contact.node_id().to_string(), json],
+        )?;
+        Ok(())
+    }
+
+    fn delete_contact(&self, node_id: &NodeId) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM contacts WHERE node_id = ?1",
+            rusqlite::params![node_id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    fn save_queued_message(&self, message: &QueuedMessage) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let json = serde_json::to_string(message).unwrap_or_default();
+        conn.execute(
+            "INSERT OR REPLACE INTO offline_outbox (message_id, data) VALUES (?1, ?2)",
+            rusqlite::params![message.message_id, json],
+        )?;
+        Ok(())
+    }
+
+    fn delete_queued_message(&self, message_id: &str) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM offline_outbox WHERE message_id = ?1",
+            rusqlite::params![message_id],
+        )?;
+        Ok(())
+    }
+
+    fn load_queued_messages(&self) -> Result<Vec<QueuedMessage>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT data FROM offline_outbox")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut messages = Vec::new();
+        for row in rows {
+            if let Ok(message) = serde_json::from_str::<QueuedMessage>(&row?) {
+                messages.push(message);
+            }
+        }
+        Ok(messages)
+    }
+
+    fn save_inbox_record(
+        &self,
+        from: &NodeId,
+        message_id: &str,
+        record: &InboxRecord,
+    ) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO exactly_once_inbox (sender, message_id, received_ms, committed) \
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![
+                from.to_string(),
+                message_id,
+                record.received_ms as i64,
+                record.committed as i32
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn load_inbox(&self) -> Result<Vec<(NodeId, String, InboxRecord)>, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT sender, message_id, received_ms, committed FROM exactly_once_inbox",
+        )?;
         let rows = stmt.query_map([], |row| {
-            let nid: String = row.get(0)?;
-            let role: String = row.get(1)?;
-            let status: String = row.get(2)?;
-            let last_seen: i64 = row.get(3)?;
-            Ok((nid, role, status, last_seen))
+            let sender: String = row.get(0)?;
+            let message_id: String = row.get(1)?;
+            let received_ms: i64 = row.get(2)?;
+            let committed: i32 = row.get(3)?;
+            Ok((sender, message_id, received_ms, committed))
         })?;
+        let mut records = Vec::new();
         for row in rows {
-            let (nid, role, status, last_seen) = row?;
-            let Ok(node_id) = nid.parse::<NodeId>() else {
+            let (sender, message_id, received_ms, committed) = row?;
+            let Ok(from) = sender.parse::<NodeId>() else {
                 continue;
             };
-            let role = match role.as_str() {
-                "Relay" => PeerRole::Relay,
-                _ => PeerRole::Peer,
-            };
-            let status = match status.as_str() {
-                "Online" => PeerStatus::Online,
-                "Stale" => PeerStatus::Stale,
-                _ => PeerStatus::Offline, // All peers start offline after restart
-            };
-            peers.insert(
-                node_id,
-                PeerInfo {
-                    node_id,
-                    role,
-                    status,
-                    last_seen: last_seen as u64,
+            records.push((
+                from,
+                message_id,
+                InboxRecord {
+                    received_ms: received_ms as u64,
+                    committed: committed != 0,
                 },
-            );
+            ));
         }
-        Ok(peers)
+        Ok(records)
     }
 
-    fn load_metrics(conn: &Connection) -> Result<HashMap<NodeId, ContributionMetrics>, rusqlite::Error> {
-        let mut stmt = conn.prepare("SELECT node_id, data FROM contribution_metrics")?;
-        let mut metrics = HashMap::new();
-        let rows = stmt.query_map([], |row| {
-            let nid: String = row.get(0)?;
-            let json: String = row.get(1)?;
-            Ok((nid, json))
-        })?;
-        for row in rows {
-            let (nid, json) = row?;
-            let Ok(node_id) = nid.parse::<NodeId>() else {
-                continue;
-            };
-            if let Ok(m) = serde_json::from_str::<ContributionMetrics>(&json) {
-                metrics.insert(node_id, m);
-            }
-        }
-        Ok(metrics)
+    fn cleanup_inbox(&self, cutoff_ms: u64) -> Result<usize, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM exactly_once_inbox WHERE received_ms < ?1",
+            rusqlite::params![cutoff_ms as i64],
+        )?;
+        Ok(deleted)
     }
 
-    fn load_tracked_messages(
-        conn: &Connection,
-    ) -> Result<HashMap<String, TrackedMessageRecord>, rusqlite::Error> {
+    fn save_history_entry(&self, entry: &DirectEntry) -> Result<(), StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let json = serde_json::to_string(entry).unwrap_or_default();
+        conn.execute(
+            "INSERT OR REPLACE INTO direct_history (message_id, timestamp, data) VALUES (?1, ?2, ?3)",
+            rusqlite::params![entry.message_id, entry.timestamp as i64, json],
+        )?;
+        Ok(())
+    }
+
+    fn load_history(&self, max_count: usize) -> Result<Vec<DirectEntry>, StorageError> {
+        let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT message_id, to_node_id, status, created_ms, retries_remaining FROM tracked_messages",
+            "SELECT data FROM direct_history ORDER BY timestamp DESC LIMIT ?1",
         )?;
-        let mut messages = HashMap::new();
-        let rows = stmt.query_map([], |row| {
-            let msg_id: String = row.get(0)?;
-            let to: String = row.get(1)?;
-            let status: i32 = row.get(2)?;
-            let created_ms: i64 = row.get(3)?;
-            let retries: i32 = row.get(4)?;
-            Ok((msg_id, to, status, created_ms, retries))
-        })?;
+        let rows = stmt.query_map(rusqlite::params![max_count as i64], |row| row.get::<_, String>(0))?;
+        let mut entries = Vec::new();
         for row in rows {
-            let (msg_id, to, status_int, created_ms, retries) = row?;
-            let Ok(to_id) = to.parse::<NodeId>() else {
-                continue;
-            };
-            let status = match status_int {
-                0 => MessageStatus::Pending,
-                1 => MessageStatus::Sent,
-                2 => MessageStatus::Relayed,
-                3 => MessageStatus::Delivered,
-                4 => MessageStatus::Read,
-                5 => MessageStatus::Failed,
-                _ => MessageStatus::Pending,
-            };
-            messages.insert(
-                msg_id,
-                TrackedMessageRecord {
-                    to: to_id,
-                    status,
-                    created_ms: created_ms as u64,
-                    retries_remaining: retries as u8,
-                },
-            );
+            if let Ok(entry) = serde_json::from_str::<DirectEntry>(&row?) {
+                entries.push(entry);
+            }
         }
-        Ok(messages)
+        entries.reverse();
+        Ok(entries)
+    }
+
+    fn delete_history_before(&self, timestamp: u64) -> Result<usize, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM direct_history WHERE timestamp < ?1",
+            rusqlite::params![timestamp as i64],
+        )?;
+        Ok(deleted)
+    }
+
+    fn load(&self) -> Result<StateSnapshot, StorageError> {
+        let conn = self.conn.lock().unwrap();
+        let (groups, member_last_seqs) = Self::load_groups(&conn)?;
+        let (local_keys, remote_keys) = Self::load_sender_keys(&conn)?;
+        let notification_prefs = Self::load_notification_prefs(&conn)?;
+        let (hub_groups, hub_invited_sets, hub_next_seqs) = Self::load_hub_groups(&conn)?;
+        let hub_scheduled = Self::load_hub_scheduled(&conn)?;
+        let peers = Self::load_peers(&conn)?;
+        let contacts = Self::load_contacts(&conn)?;
+        let metrics = Self::load_metrics(&conn)?;
+        let tracked_messages = Self::load_tracked_messages(&conn)?;
+        let shared_docs = Self::load_shared_docs(&conn)?;
+        let mesh_groups = Self::load_mesh_groups(&conn)?;
+
+        let manager = if !groups.is_empty() || !local_keys.is_empty() {
+            Some(GroupManagerSnapshot {
+                groups,
+                local_sender_keys: local_keys,
+                sender_keys: remote_keys,
+                previous_sender_keys: HashMap::new(),
+                local_sender_message_counts: HashMap::new(),
+                message_history: HashMap::new(), // Not persisted (rebuilt via hub sync)
+                last_seqs: member_last_seqs,
+                notification_prefs,
+            })
+        } else {
+            None
+        };
+
+        let hub = if !hub_groups.is_empty() {
+            Some(GroupHubSnapshot {
+                groups: hub_groups,
+                invited_sets: hub_invited_sets,
+                next_seqs: hub_next_seqs,
+                scheduled: hub_scheduled,
+            })
+        } else {
+            None
+        };
+
+        let shared = if !shared_docs.is_empty() {
+            Some(SharedStateSnapshot { docs: shared_docs })
+        } else {
+            None
+        };
+
+        let mesh = if !mesh_groups.is_empty() {
+            Some(MeshGroupSnapshot { groups: mesh_groups })
+        } else {
+            None
+        };
+
+        Ok(StateSnapshot {
+            manager,
+            hub,
+            peers,
+            metrics,
+            tracked_messages,
+            shared,
+            mesh,
+            contacts,
+        })
+    }
+}
+
+/// Stored status code. Fixed per status (not the enum discriminant), so
+/// databases written before `Queued` existed read back unchanged.
+#[cfg(feature = "native")]
+fn status_code(status: MessageStatus) -> i32 {
+    match status {
+        MessageStatus::Pending => 0,
+        MessageStatus::Sent => 1,
+        MessageStatus::Relayed => 2,
+        MessageStatus::Delivered => 3,
+        MessageStatus::Read => 4,
+        MessageStatus::Failed => 5,
+        MessageStatus::Queued => 6,
     }
 }
 
-#[cfg(test)]
+#[cfg(feature = "native")]
+fn status_from_code(code: i32) -> MessageStatus {
+    match code {
+        1 => MessageStatus::Sent,
+        2 => MessageStatus::Relayed,
+        3 => MessageStatus::Delivered,
+        4 => MessageStatus::Read,
+        5 => MessageStatus::Failed,
+        6 => MessageStatus::Queued,
+        _ => MessageStatus::Pending,
+    }
+}
+
+#[cfg(all(test, feature = "native"))]
 mod tests {
     use super::*;
     use crate::group::types::*;
@@ -558,12 +1164,19 @@ mod tests {
             shadow_id: None,
             candidate_id: None,
             invite_only: false,
+            description: String::new(),
+            avatar_hash: None,
+            config_version: 0,
+            bans: Vec::new(),
+            message_ttl_ms: None,
+            polls: Vec::new(),
+            settings: GroupSettings::default(),
         }
     }
 
     #[test]
     fn roundtrip_groups() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let hub = node_id(10);
         let alice = node_id(1);
 
@@ -585,6 +1198,7 @@ mod tests {
                 local_sender_message_counts: HashMap::new(),
                 message_history: HashMap::new(),
                 last_seqs: HashMap::new(),
+                notification_prefs: HashMap::new(),
             }),
             ..Default::default()
         };
@@ -598,9 +1212,38 @@ mod tests {
         assert_eq!(mgr.groups[&gid2].name, "Group B");
     }
 
+    #[test]
+    fn roundtrip_notification_prefs() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let g = make_group_info("Quiet", node_id(10), node_id(1));
+        let gid = g.group_id.clone();
+        let prefs = GroupNotificationPrefs {
+            muted_until: Some(u64::MAX),
+            mentions_only: true,
+        };
+
+        let snapshot = StateSnapshot {
+            manager: Some(GroupManagerSnapshot {
+                groups: HashMap::from([(gid.clone(), g)]),
+                local_sender_keys: HashMap::new(),
+                sender_keys: HashMap::new(),
+                previous_sender_keys: HashMap::new(),
+                local_sender_message_counts: HashMap::new(),
+                message_history: HashMap::new(),
+                last_seqs: HashMap::new(),
+                notification_prefs: HashMap::from([(gid.clone(), prefs)]),
+            }),
+            ..Default::default()
+        };
+
+        store.save(&snapshot).unwrap();
+        let loaded = store.load().unwrap().manager.unwrap();
+        assert_eq!(loaded.notification_prefs[&gid], prefs);
+    }
+
     #[test]
     fn roundtrip_sender_keys() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let alice = node_id(1);
         let bob = node_id(2);
         let gid = GroupId::from("grp-1".to_string());
@@ -636,6 +1279,7 @@ mod tests {
                 local_sender_message_counts: HashMap::new(),
                 message_history: HashMap::new(),
                 last_seqs: HashMap::new(),
+                notification_prefs: HashMap::new(),
             }),
             ..Default::default()
         };
@@ -651,7 +1295,7 @@ mod tests {
 
     #[test]
     fn roundtrip_peers() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let alice = node_id(1);
         let bob = node_id(2);
 
@@ -679,9 +1323,36 @@ mod tests {
         assert_eq!(loaded.peers[&bob].last_seen, 88000);
     }
 
+    #[test]
+    fn roundtrip_contacts() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let carol = node_id(3);
+        let mut contact = Contact::new(crate::contact::ContactCard::new(carol, "carol".into()));
+        contact.alias = Some("C".into());
+
+        let snapshot = StateSnapshot {
+            contacts: HashMap::from([(carol, contact.clone())]),
+            ..Default::default()
+        };
+        store.save(&snapshot).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.contacts.len(), 1);
+        assert_eq!(loaded.contacts[&carol], contact);
+
+        // Written-through changes replace or remove single rows
+        let mallory = node_id(4);
+        let mut blocked = Contact::new(crate::contact::ContactCard::new(mallory, String::new()));
+        blocked.blocked = true;
+        store.save_contact(&blocked).unwrap();
+        store.delete_contact(&carol).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.contacts.len(), 1);
+        assert!(loaded.contacts[&mallory].blocked);
+    }
+
     #[test]
     fn roundtrip_hub_groups() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let hub = node_id(10);
         let alice = node_id(1);
 
@@ -692,7 +1363,7 @@ mod tests {
         groups.insert(gid.clone(), info);
 
         let snapshot = StateSnapshot {
-            hub: Some(GroupHubSnapshot { groups, invited_sets: HashMap::new(), next_seqs: HashMap::new() }),
+            hub: Some(GroupHubSnapshot { groups, invited_sets: HashMap::new(), next_seqs: HashMap::new(), scheduled: HashMap::new() }),
             ..Default::default()
         };
 
@@ -704,9 +1375,43 @@ mod tests {
         assert_eq!(hub_snap.groups[&gid].name, "Hub Group");
     }
 
+    #[test]
+    fn save_hub_checkpoints_only_hub_state() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let info = make_group_info("Hub Group", node_id(10), node_id(1));
+        let gid = info.group_id.clone();
+
+        let mut groups = HashMap::new();
+        groups.insert(gid.clone(), info);
+        let hub = GroupHubSnapshot {
+            groups,
+            invited_sets: HashMap::new(),
+            next_seqs: HashMap::from([(gid.clone(), 7)]),
+            scheduled: HashMap::new(),
+        };
+        store.save_hub(&hub).unwrap();
+
+        let loaded = store.load().unwrap();
+        let hub_snap = loaded.hub.unwrap();
+        assert!(hub_snap.groups.contains_key(&gid));
+        assert_eq!(hub_snap.next_seqs[&gid], 7);
+        assert!(loaded.manager.is_none());
+
+        // An empty checkpoint drops groups we no longer host
+        store
+            .save_hub(&GroupHubSnapshot {
+                groups: HashMap::new(),
+                invited_sets: HashMap::new(),
+                next_seqs: HashMap::new(),
+                scheduled: HashMap::new(),
+            })
+            .unwrap();
+        assert!(store.load().unwrap().hub.is_none());
+    }
+
     #[test]
     fn roundtrip_hub_invited_sets() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let hub = node_id(10);
         let alice = node_id(1);
         let bob = node_id(2);
@@ -726,7 +1431,7 @@ mod tests {
         invited_sets.insert(gid.clone(), set);
 
         let snapshot = StateSnapshot {
-            hub: Some(GroupHubSnapshot { groups, invited_sets, next_seqs: HashMap::new() }),
+            hub: Some(GroupHubSnapshot { groups, invited_sets, next_seqs: HashMap::new(), scheduled: HashMap::new() }),
             ..Default::default()
         };
 
@@ -743,7 +1448,7 @@ mod tests {
 
     #[test]
     fn roundtrip_metrics() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let alice = node_id(1);
 
         let mut m = ContributionMetrics::new(1000);
@@ -764,7 +1469,7 @@ mod tests {
 
     #[test]
     fn save_overwrites_previous() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let alice = node_id(1);
 
         // Save with 1 peer
@@ -786,7 +1491,7 @@ mod tests {
 
     #[test]
     fn roundtrip_tracked_messages() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let bob = node_id(2);
 
         let mut tracked = HashMap::new();
@@ -823,9 +1528,18 @@ mod tests {
         assert_eq!(loaded.tracked_messages["msg-002"].created_ms, 1000500);
     }
 
+    #[test]
+    fn identity_roundtrip() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        assert_eq!(store.load_identity().unwrap(), None);
+        store.save_identity(&node_id(1)).unwrap();
+        store.save_identity(&node_id(2)).unwrap();
+        assert_eq!(store.load_identity().unwrap(), Some(node_id(2)));
+    }
+
     #[test]
     fn empty_database_loads_empty() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let loaded = store.load().unwrap();
         assert!(loaded.manager.is_none());
         assert!(loaded.hub.is_none());
@@ -843,7 +1557,7 @@ mod tests {
 
         // Write
         {
-            let store = StateStore::open(&db_path).unwrap();
+            let store = SqliteStateStore::open(&db_path).unwrap();
             let mut peers = HashMap::new();
             peers.insert(alice, PeerInfo {
                 node_id: alice,
@@ -856,7 +1570,7 @@ mod tests {
 
         // Read from a new connection
         {
-            let store = StateStore::open(&db_path).unwrap();
+            let store = SqliteStateStore::open(&db_path).unwrap();
             let loaded = store.load().unwrap();
             assert_eq!(loaded.peers.len(), 1);
             assert_eq!(loaded.peers[&alice].last_seen, 42000);
@@ -867,7 +1581,7 @@ mod tests {
 
     #[test]
     fn hub_message_history_save_and_load() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let gid = GroupId::from("grp-hist".to_string());
 
         // Save 3 messages
@@ -887,9 +1601,30 @@ mod tests {
         // For gap-fill, member sends last_seq they have, so this is correct behavior
     }
 
+    #[test]
+    fn expire_hub_messages_by_cutoff_within_group() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let gid = GroupId::from("grp-ttl".to_string());
+        let other = GroupId::from("grp-other".to_string());
+        for seq in 1..=4u64 {
+            store.save_hub_message(&gid, seq, b"data", 1000 + seq).unwrap();
+        }
+        store.save_hub_message(&other, 1, b"data", 1000).unwrap();
+
+        assert_eq!(store.expire_hub_messages(&gid, 1002).unwrap(), 2);
+        let remaining: Vec<u64> = store
+            .load_hub_messages_since(&gid, 0, 100)
+            .unwrap()
+            .into_iter()
+            .map(|(seq, _)| seq)
+            .collect();
+        assert_eq!(remaining, vec![3, 4]);
+        assert_eq!(store.load_hub_messages_since(&other, 0, 100).unwrap().len(), 1);
+    }
+
     #[test]
     fn hub_message_history_limit() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let gid = GroupId::from("grp-limit".to_string());
 
         for seq in 0..100u64 {
@@ -903,9 +1638,141 @@ mod tests {
         assert_eq!(msgs[9].0, 60);
     }
 
+    #[test]
+    fn hub_message_history_load_before() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let gid = GroupId::from("grp-page".to_string());
+
+        for seq in 0..30u64 {
+            store.save_hub_message(&gid, seq, b"data", 1000 + seq).unwrap();
+        }
+
+        // Latest page
+        let page = store.load_hub_messages_before(&gid, u64::MAX, 10).unwrap();
+        let seqs: Vec<u64> = page.iter().map(|(s, _)| *s).collect();
+        assert_eq!(seqs, (20..30).collect::<Vec<_>>());
+
+        // Next page back
+        let page = store.load_hub_messages_before(&gid, 20, 10).unwrap();
+        assert_eq!(page.first().unwrap().0, 10);
+        assert_eq!(page.last().unwrap().0, 19);
+
+        // Partial last page
+        let page = store.load_hub_messages_before(&gid, 5, 10).unwrap();
+        assert_eq!(page.len(), 5);
+    }
+
+    #[test]
+    fn exactly_once_outbox_and_inbox_roundtrip() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let (alice, bob) = (node_id(1), node_id(2));
+
+        let entry = OutboxEntry {
+            message_id: "m1".into(),
+            to: bob,
+            envelope: vec![1, 2, 3],
+            created_ms: 1000,
+            attempts: 1,
+            next_retry_ms: 6000,
+        };
+        store.save_outbox_entry(&entry).unwrap();
+        assert_eq!(store.load_outbox().unwrap(), vec![entry]);
+        store.delete_outbox_entry("m1").unwrap();
+        assert!(store.load_outbox().unwrap().is_empty());
+
+        let record = InboxRecord { received_ms: 1000, committed: false };
+        store.save_inbox_record(&alice, "m1", &record).unwrap();
+        let committed = InboxRecord { committed: true, ..record };
+        store.save_inbox_record(&alice, "m1", &committed).unwrap();
+        assert_eq!(store.load_inbox().unwrap(), vec![(alice, "m1".to_string(), committed)]);
+
+        assert_eq!(store.cleanup_inbox(1001).unwrap(), 1);
+        assert!(store.load_inbox().unwrap().is_empty());
+    }
+
+    #[test]
+    fn offline_outbox_roundtrip() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let message = QueuedMessage {
+            message_id: "m1".into(),
+            to: node_id(2),
+            envelope: vec![1, 2, 3],
+            backup_ttl_ms: Some(60_000),
+            queued_ms: 1000,
+        };
+        store.save_queued_message(&message).unwrap();
+        assert_eq!(store.load_queued_messages().unwrap(), vec![message]);
+        store.delete_queued_message("m1").unwrap();
+        assert!(store.load_queued_messages().unwrap().is_empty());
+    }
+
+    #[test]
+    fn history_log_keeps_the_newest() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let entry = |id: &str, timestamp| DirectEntry {
+            peer: node_id(2),
+            outgoing: true,
+            message_id: id.into(),
+            payload: b"hi".to_vec(),
+            content_type: Some("text/plain".into()),
+            timestamp,
+        };
+        for (id, timestamp) in [("b", 20), ("a", 10), ("c", 30)] {
+            store.save_history_entry(&entry(id, timestamp)).unwrap();
+        }
+        assert_eq!(store.load_history(2).unwrap(), vec![entry("b", 20), entry("c", 30)]);
+        assert_eq!(store.delete_history_before(20).unwrap(), 1);
+        assert_eq!(store.load_history(10).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn queued_status_survives_snapshot_roundtrip() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let mut snapshot = StateSnapshot::default();
+        for (id, status) in [("q", MessageStatus::Queued), ("s", MessageStatus::Sent)] {
+            snapshot.tracked_messages.insert(
+                id.into(),
+                TrackedMessageRecord { to: node_id(2), status, created_ms: 1000, retries_remaining: 2 },
+            );
+        }
+        store.save(&snapshot).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.tracked_messages["q"].status, MessageStatus::Queued);
+        assert_eq!(loaded.tracked_messages["s"].status, MessageStatus::Sent);
+    }
+
+    #[test]
+    fn shared_docs_roundtrip() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let (alice, bob) = (node_id(1), node_id(2));
+        let mut mgr = crate::shared_state::SharedStateManager::new(alice);
+        mgr.open("settings", vec![bob]);
+        mgr.set("settings", "theme".into(), Some(b"dark".to_vec())).unwrap();
+
+        store.save(&StateSnapshot { shared: Some(mgr.snapshot()), ..Default::default() }).unwrap();
+        let loaded = store.load().unwrap().shared.unwrap();
+        let doc = &loaded.docs["settings"];
+        assert_eq!(doc.map.get("theme"), Some(&b"dark"[..]));
+        assert!(doc.peers.contains(&bob));
+    }
+
+    #[test]
+    fn mesh_groups_roundtrip() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let (alice, bob) = (node_id(1), node_id(2));
+        let mut mgr = crate::group::MeshGroupManager::new(alice, "alice".into());
+        let (gid, _) = mgr.create("duo", vec![bob]).unwrap();
+
+        store.save(&StateSnapshot { mesh: Some(mgr.snapshot()), ..Default::default() }).unwrap();
+        let loaded = store.load().unwrap().mesh.unwrap();
+        let group = &loaded.groups[&gid];
+        assert_eq!(group.name(), "duo");
+        assert!(group.is_member(&bob));
+    }
+
     #[test]
     fn hub_message_cleanup_expired() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let gid = GroupId::from("grp-cleanup".to_string());
 
         // Store messages with different timestamps
@@ -925,7 +1792,7 @@ mod tests {
 
     #[test]
     fn hub_next_seq_persisted() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let alice = node_id(1);
         let hub = node_id(10);
 
@@ -938,7 +1805,7 @@ mod tests {
         next_seqs.insert(gid.clone(), 42u64);
 
         let snapshot = StateSnapshot {
-            hub: Some(GroupHubSnapshot { groups, invited_sets: HashMap::new(), next_seqs }),
+            hub: Some(GroupHubSnapshot { groups, invited_sets: HashMap::new(), next_seqs, scheduled: HashMap::new() }),
             ..Default::default()
         };
 
@@ -949,9 +1816,37 @@ mod tests {
         assert_eq!(hub_snap.next_seqs[&gid], 42);
     }
 
+    #[test]
+    fn hub_scheduled_messages_persisted() {
+        let store = SqliteStateStore::open_memory().unwrap();
+        let alice = node_id(1);
+        let hub = node_id(10);
+
+        let info = make_group_info("Later", hub, alice);
+        let gid = info.group_id.clone();
+        let mut groups = HashMap::new();
+        groups.insert(gid.clone(), info);
+        let late = GroupMessage::new(gid.clone(), alice, "alice".into(), "late".into())
+            .with_deliver_at(9_000);
+        let early = GroupMessage::new(gid.clone(), alice, "alice".into(), "early".into())
+            .with_deliver_at(5_000);
+        let mut scheduled = HashMap::new();
+        scheduled.insert(gid.clone(), vec![late, early]);
+
+        let snapshot = StateSnapshot {
+            hub: Some(GroupHubSnapshot { groups, invited_sets: HashMap::new(), next_seqs: HashMap::new(), scheduled }),
+            ..Default::default()
+        };
+        store.save(&snapshot).unwrap();
+
+        let loaded = store.load().unwrap().hub.unwrap();
+        let texts: Vec<_> = loaded.scheduled[&gid].iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, vec!["early", "late"]);
+    }
+
     #[test]
     fn member_last_seq_persisted() {
-        let store = StateStore::open_memory().unwrap();
+        let store = SqliteStateStore::open_memory().unwrap();
         let alice = node_id(1);
         let hub = node_id(10);
 
@@ -972,6 +1867,7 @@ mod tests {
                 local_sender_message_counts: HashMap::new(),
                 message_history: HashMap::new(),
                 last_seqs,
+                notification_prefs: HashMap::new(),
             }),
             ..Default::default()
         };
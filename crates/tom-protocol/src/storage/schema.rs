@@ -4,7 +4,7 @@
 use rusqlite::Connection;
 
 #[cfg(test)]
-const CURRENT_VERSION: i64 = 4;
+const CURRENT_VERSION: i64 = 13;
 
 /// Initialize the database schema (create tables if not exist, run migrations).
 pub fn initialize(conn: &Connection) -> Result<(), rusqlite::Error> {
@@ -35,6 +35,33 @@ pub fn initialize(conn: &Connection) -> Result<(), rusqlite::Error> {
     if version < 4 {
         migrate_v4(conn)?;
     }
+    if version < 5 {
+        migrate_v5(conn)?;
+    }
+    if version < 6 {
+        migrate_v6(conn)?;
+    }
+    if version < 7 {
+        migrate_v7(conn)?;
+    }
+    if version < 8 {
+        migrate_v8(conn)?;
+    }
+    if version < 9 {
+        migrate_v9(conn)?;
+    }
+    if version < 10 {
+        migrate_v10(conn)?;
+    }
+    if version < 11 {
+        migrate_v11(conn)?;
+    }
+    if version < 12 {
+        migrate_v12(conn)?;
+    }
+    if version < 13 {
+        migrate_v13(conn)?;
+    }
 
     Ok(())
 }
@@ -142,6 +169,154 @@ fn migrate_v4(conn: &Connection) -> Result<(), rusqlite::Error> {
     Ok(())
 }
 
+/// V5: Shared state documents (LWW CRDT).
+fn migrate_v5(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS shared_docs (
+            doc_id TEXT PRIMARY KEY,
+            data TEXT NOT NULL
+        );
+
+        INSERT OR REPLACE INTO schema_version (version) VALUES (5);
+        ",
+    )?;
+    Ok(())
+}
+
+/// V6: Exactly-once delivery outbox (sender) and dedup inbox (receiver).
+fn migrate_v6(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS exactly_once_outbox (
+            message_id TEXT PRIMARY KEY,
+            data TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS exactly_once_inbox (
+            sender TEXT NOT NULL,
+            message_id TEXT NOT NULL,
+            received_ms INTEGER NOT NULL,
+            committed INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (sender, message_id)
+        );
+
+        INSERT OR REPLACE INTO schema_version (version) VALUES (6);
+        ",
+    )?;
+    Ok(())
+}
+
+/// V7: Hubless mesh groups.
+fn migrate_v7(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS mesh_groups (
+            group_id TEXT PRIMARY KEY,
+            data TEXT NOT NULL
+        );
+
+        INSERT OR REPLACE INTO schema_version (version) VALUES (7);
+        ",
+    )?;
+    Ok(())
+}
+
+/// V8: Hub-held scheduled group messages.
+fn migrate_v8(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS hub_scheduled_messages (
+            message_id TEXT PRIMARY KEY,
+            group_id TEXT NOT NULL,
+            deliver_at INTEGER NOT NULL,
+            message_data BLOB NOT NULL
+        );
+
+        INSERT OR REPLACE INTO schema_version (version) VALUES (8);
+        ",
+    )?;
+    Ok(())
+}
+
+/// V9: Local per-group notification preferences.
+fn migrate_v9(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS group_notification_prefs (
+            group_id TEXT PRIMARY KEY,
+            data TEXT NOT NULL
+        );
+
+        INSERT OR REPLACE INTO schema_version (version) VALUES (9);
+        ",
+    )?;
+    Ok(())
+}
+
+/// V10: Imported contact cards.
+fn migrate_v10(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS contacts (
+            node_id TEXT PRIMARY KEY,
+            data TEXT NOT NULL
+        );
+
+        INSERT OR REPLACE INTO schema_version (version) VALUES (10);
+        ",
+    )?;
+    Ok(())
+}
+
+/// V11: Messages composed while offline, waiting for connectivity.
+fn migrate_v11(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS offline_outbox (
+            message_id TEXT PRIMARY KEY,
+            data TEXT NOT NULL
+        );
+
+        INSERT OR REPLACE INTO schema_version (version) VALUES (11);
+        ",
+    )?;
+    Ok(())
+}
+
+/// V12: The node the stored state belongs to.
+fn migrate_v12(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS identity (
+            id INTEGER PRIMARY KEY CHECK (id = 0),
+            node_id TEXT NOT NULL
+        );
+
+        INSERT OR REPLACE INTO schema_version (version) VALUES (12);
+        ",
+    )?;
+    Ok(())
+}
+
+/// V13: 1:1 history log, synced between a user's devices.
+fn migrate_v13(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS direct_history (
+            message_id TEXT PRIMARY KEY,
+            timestamp INTEGER NOT NULL,
+            data TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_direct_history_timestamp
+            ON direct_history(timestamp);
+
+        INSERT OR REPLACE INTO schema_version (version) VALUES (13);
+        ",
+    )?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,6 +341,16 @@ mod tests {
         assert!(tables.contains(&"peers".to_string()));
         assert!(tables.contains(&"contribution_metrics".to_string()));
         assert!(tables.contains(&"tracked_messages".to_string()));
+        assert!(tables.contains(&"shared_docs".to_string()));
+        assert!(tables.contains(&"exactly_once_outbox".to_string()));
+        assert!(tables.contains(&"exactly_once_inbox".to_string()));
+        assert!(tables.contains(&"mesh_groups".to_string()));
+        assert!(tables.contains(&"hub_scheduled_messages".to_string()));
+        assert!(tables.contains(&"group_notification_prefs".to_string()));
+        assert!(tables.contains(&"contacts".to_string()));
+        assert!(tables.contains(&"offline_outbox".to_string()));
+        assert!(tables.contains(&"identity".to_string()));
+        assert!(tables.contains(&"direct_history".to_string()));
         assert!(tables.contains(&"schema_version".to_string()));
     }
 
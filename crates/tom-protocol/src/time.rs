@@ -0,0 +1,18 @@
+/// Clock used by the pure state machines (router, tracker, group hub).
+///
+/// `std::time::Instant::now()` panics on `wasm32-unknown-unknown`, so browser
+/// builds take `web-time`'s clock through `n0_future` — the same source the
+/// gossip proto uses. Native builds keep `std`.
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+pub use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(all(target_family = "wasm", target_os = "unknown"))]
+pub use n0_future::time::{Duration, Instant, SystemTime};
+
+/// Current Unix time in milliseconds from the platform clock.
+pub fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,277 @@
+/// Route tracing — traceroute for envelopes.
+///
+/// A trace is a `MessageType::Trace` envelope routed like a chat message.
+/// The sender, every relay and the recipient each append a signed hop
+/// record (who, when, in which role) before passing it on; the recipient
+/// then sends the full path back to the sender, who surfaces it as a
+/// `RouteTraced` event.
+///
+/// Each record signs the previous record's signature, so a relay can't
+/// rewrite or reorder the hops before it. Because relays change the
+/// payload on the way out, the outbound envelope itself is unsigned: the
+/// sender's own record (always first) authenticates it. The reply is
+/// signed by the recipient and not touched by relays.
+///
+/// Pure state: callers pass the clock.
+use std::collections::HashMap;
+
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+
+use crate::error::TomProtocolError;
+use crate::router::MAX_RELAY_DEPTH;
+use crate::types::NodeId;
+
+/// Most hop records in one trace: sender, every relay, recipient.
+pub const MAX_TRACE_HOPS: usize = MAX_RELAY_DEPTH + 2;
+
+/// How long we wait for a trace to come back before forgetting it.
+pub const TRACE_TIMEOUT_MS: u64 = 30_000;
+
+/// Most traces awaiting their reply at once.
+const MAX_PENDING_TRACES: usize = 64;
+
+/// The part a node played on the traced path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceHopKind {
+    /// Sent the trace.
+    Origin,
+    /// Forwarded it towards the recipient.
+    Relay,
+    /// Received it and sent the path back.
+    Destination,
+}
+
+/// One signed hop record.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceHop {
+    pub node_id: NodeId,
+    /// When the hop handled the trace (Unix ms, its own clock).
+    pub at: u64,
+    pub kind: TraceHopKind,
+    /// Ed25519 signature by `node_id`, chained to the previous hop.
+    pub signature: Vec<u8>,
+}
+
+/// Wire payload of a `MessageType::Trace` envelope.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TracePayload {
+    pub trace_id: String,
+    pub hops: Vec<TraceHop>,
+    /// false on the way out (relays append), true on the way back.
+    pub reply: bool,
+}
+
+impl TracePayload {
+    /// Start a trace, signing the origin record.
+    pub fn new(origin: NodeId, at: u64, secret_seed: &[u8; 32]) -> Self {
+        let mut payload = Self {
+            trace_id: uuid::Uuid::new_v4().to_string(),
+            hops: Vec::new(),
+            reply: false,
+        };
+        payload.append_hop(origin, TraceHopKind::Origin, at, secret_seed);
+        payload
+    }
+
+    /// Append our signed record. Returns false (appending nothing) once the
+    /// trace holds `MAX_TRACE_HOPS` records.
+    pub fn append_hop(
+        &mut self,
+        node_id: NodeId,
+        kind: TraceHopKind,
+        at: u64,
+        secret_seed: &[u8; 32],
+    ) -> bool {
+        if self.hops.len() >= MAX_TRACE_HOPS {
+            return false;
+        }
+        let previous = self.hops.last().map(|h| h.signature.as_slice()).unwrap_or_default();
+        let bytes = hop_signing_bytes(&self.trace_id, previous, &node_id, at, kind);
+        let signature = SigningKey::from_bytes(secret_seed).sign(&bytes).to_bytes().to_vec();
+        self.hops.push(TraceHop {
+            node_id,
+            at,
+            kind,
+            signature,
+        });
+        true
+    }
+
+    /// Whether `origin` started this trace and every record is intact.
+    pub fn verify(&self, origin: &NodeId) -> bool {
+        use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+        let starts_at_origin = self
+            .hops
+            .first()
+            .is_some_and(|h| h.node_id == *origin && h.kind == TraceHopKind::Origin);
+        if !starts_at_origin || self.hops.len() > MAX_TRACE_HOPS {
+            return false;
+        }
+        let mut previous: &[u8] = &[];
+        for hop in &self.hops {
+            let Ok(verifying_key) = VerifyingKey::from_bytes(&hop.node_id.as_bytes()) else {
+                return false;
+            };
+            let Ok(sig_bytes) = <[u8; 64]>::try_from(hop.signature.as_slice()) else {
+                return false;
+            };
+            let bytes = hop_signing_bytes(&self.trace_id, previous, &hop.node_id, hop.at, hop.kind);
+            if verifying_key
+                .verify(&bytes, &Signature::from_bytes(&sig_bytes))
+                .is_err()
+            {
+                return false;
+            }
+            previous = &hop.signature;
+        }
+        true
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        rmp_serde::to_vec(self).expect("TracePayload serialization cannot fail")
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, TomProtocolError> {
+        rmp_serde::from_slice(data).map_err(Into::into)
+    }
+}
+
+/// Bytes a hop signs: the trace, the previous record's signature, and its own record.
+fn hop_signing_bytes(
+    trace_id: &str,
+    previous: &[u8],
+    node_id: &NodeId,
+    at: u64,
+    kind: TraceHopKind,
+) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(trace_id.len() + previous.len() + 32 + 9);
+    bytes.extend_from_slice(trace_id.as_bytes());
+    bytes.extend_from_slice(previous);
+    bytes.extend_from_slice(&node_id.as_bytes());
+    bytes.extend_from_slice(&at.to_le_bytes());
+    bytes.push(kind as u8);
+    bytes
+}
+
+/// Traces we started and are waiting on.
+#[derive(Debug, Default)]
+pub struct PendingTraces {
+    /// trace_id → (target, started at).
+    pending: HashMap<String, (NodeId, u64)>,
+}
+
+impl PendingTraces {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember a trace sent to `target`. Returns false when too many are
+    /// already in flight.
+    pub fn start(&mut self, trace_id: String, target: NodeId, now: u64) -> bool {
+        if self.pending.len() >= MAX_PENDING_TRACES {
+            return false;
+        }
+        self.pending.insert(trace_id, (target, now));
+        true
+    }
+
+    /// Claim the reply to `trace_id` coming back from `from`. True once,
+    /// and only if `from` is the node the trace was sent to.
+    pub fn complete(&mut self, trace_id: &str, from: &NodeId) -> bool {
+        if self.pending.get(trace_id).is_none_or(|(target, _)| target != from) {
+            return false;
+        }
+        self.pending.remove(trace_id);
+        true
+    }
+
+    /// Forget traces older than `TRACE_TIMEOUT_MS`.
+    pub fn expire(&mut self, now: u64) {
+        self.pending
+            .retain(|_, (_, started)| now.saturating_sub(*started) < TRACE_TIMEOUT_MS);
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn identity(seed: u64) -> (NodeId, [u8; 32]) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        (secret.public().to_string().parse().unwrap(), secret.to_bytes())
+    }
+
+    #[test]
+    fn hops_chain_and_verify() {
+        let (alice, alice_seed) = identity(1);
+        let (relay, relay_seed) = identity(2);
+        let (bob, bob_seed) = identity(3);
+
+        let mut trace = TracePayload::new(alice, 1_000, &alice_seed);
+        assert!(trace.append_hop(relay, TraceHopKind::Relay, 1_010, &relay_seed));
+        assert!(trace.append_hop(bob, TraceHopKind::Destination, 1_020, &bob_seed));
+
+        let decoded = TracePayload::from_bytes(&trace.to_bytes()).unwrap();
+        assert!(decoded.verify(&alice));
+        assert!(!decoded.verify(&bob), "must start at the claimed origin");
+        let path: Vec<NodeId> = decoded.hops.iter().map(|h| h.node_id).collect();
+        assert_eq!(path, vec![alice, relay, bob]);
+    }
+
+    #[test]
+    fn tampered_or_reordered_hops_fail() {
+        let (alice, alice_seed) = identity(1);
+        let (relay, relay_seed) = identity(2);
+        let (other, other_seed) = identity(3);
+
+        let mut trace = TracePayload::new(alice, 1_000, &alice_seed);
+        trace.append_hop(relay, TraceHopKind::Relay, 1_010, &relay_seed);
+        trace.append_hop(other, TraceHopKind::Relay, 1_020, &other_seed);
+
+        let mut retimed = trace.clone();
+        retimed.hops[1].at = 5_000;
+        assert!(!retimed.verify(&alice));
+
+        let mut reordered = trace.clone();
+        reordered.hops.swap(1, 2);
+        assert!(!reordered.verify(&alice));
+    }
+
+    #[test]
+    fn append_stops_at_max_hops() {
+        let (alice, alice_seed) = identity(1);
+        let (relay, relay_seed) = identity(2);
+        let mut trace = TracePayload::new(alice, 1_000, &alice_seed);
+        while trace.append_hop(relay, TraceHopKind::Relay, 1_000, &relay_seed) {}
+        assert_eq!(trace.hops.len(), MAX_TRACE_HOPS);
+        assert!(trace.verify(&alice));
+    }
+
+    #[test]
+    fn pending_traces_complete_once_and_expire() {
+        let (target, _) = identity(1);
+        let (other, _) = identity(2);
+        let mut pending = PendingTraces::new();
+        assert!(pending.start("a".into(), target, 1_000));
+        assert!(pending.start("b".into(), target, 1_000));
+
+        assert!(!pending.complete("a", &other), "reply from the wrong node");
+        assert!(pending.complete("a", &target));
+        assert!(!pending.complete("a", &target));
+
+        pending.expire(1_000 + TRACE_TIMEOUT_MS);
+        assert!(pending.is_empty());
+    }
+}
@@ -1,16 +1,17 @@
 /// Message lifecycle tracker for ToM protocol.
 ///
-/// Tracks the status pipeline: Pending → Sent → Relayed → Delivered → Read.
+/// Tracks the status pipeline: Pending → Sent → Relayed → Delivered → Read
+/// (Queued → Sent → … for messages composed while offline).
 /// Status is monotonically increasing — no regression allowed.
 /// `Failed` is a terminal state set explicitly after ACK timeout + retries.
 ///
 /// Pure logic, no I/O. The caller feeds events (ACKs, read receipts),
 /// the tracker updates status and reports transitions.
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 
+use crate::time::{Duration, Instant};
 use crate::types::{now_ms, MessageStatus, NodeId};
 
 /// Maximum number of tracked messages (DoS protection).
@@ -90,6 +91,31 @@ impl MessageTracker {
         })
     }
 
+    /// Start tracking a message held in the offline outbox. No ACK deadline
+    /// runs until it is released.
+    pub fn track_queued(&mut self, message_id: String, to: NodeId) -> Option<StatusChange> {
+        let change = self.track(message_id.clone(), to)?;
+        let entry = self.messages.get_mut(&message_id)?;
+        entry.status = MessageStatus::Queued;
+        entry.deadline = None;
+        Some(StatusChange {
+            current: MessageStatus::Queued,
+            ..change
+        })
+    }
+
+    /// A queued message goes out: mark it sent and start its ACK deadline.
+    pub fn release_queued(&mut self, message_id: &str) -> Option<StatusChange> {
+        if self.status(message_id)? != MessageStatus::Queued {
+            return None;
+        }
+        let change = self.advance(message_id, MessageStatus::Sent)?;
+        if let Some(entry) = self.messages.get_mut(message_id) {
+            entry.deadline = Some(Instant::now() + Duration::from_secs(DEFAULT_ACK_DEADLINE_SECS));
+        }
+        Some(change)
+    }
+
     /// Mark a message as sent (transport confirmed it left this node).
     pub fn mark_sent(&mut self, message_id: &str) -> Option<StatusChange> {
         self.advance(message_id, MessageStatus::Sent)
@@ -256,7 +282,8 @@ impl MessageTracker {
     /// Restore tracked messages from persistence.
     ///
     /// Deadlines are reset to `now + DEFAULT_ACK_DEADLINE_SECS` since wall-clock
-    /// time has passed during the downtime. Retries are preserved as-is.
+    /// time has passed during the downtime (queued messages still have none).
+    /// Retries are preserved as-is.
     pub fn restore(&mut self, records: HashMap<String, TrackedMessageRecord>) {
         let now = Instant::now();
         for (id, record) in records {
@@ -266,7 +293,8 @@ impl MessageTracker {
                     status: record.status,
                     to: record.to,
                     created: now,
-                    deadline: Some(now + Duration::from_secs(DEFAULT_ACK_DEADLINE_SECS)),
+                    deadline: (record.status != MessageStatus::Queued)
+                        .then(|| now + Duration::from_secs(DEFAULT_ACK_DEADLINE_SECS)),
                     retries_remaining: record.retries_remaining,
                 },
             );
@@ -321,6 +349,22 @@ mod tests {
         assert_eq!(c4.current, MessageStatus::Read);
     }
 
+    #[test]
+    fn queued_lifecycle() {
+        let mut tracker = MessageTracker::new();
+        let change = tracker.track_queued("msg-1".into(), node_id(2)).unwrap();
+        assert_eq!(change.previous, MessageStatus::Pending);
+        assert_eq!(change.current, MessageStatus::Queued);
+
+        // Sent only through release; no deadline while queued
+        assert!(tracker.release_queued("unknown").is_none());
+        let change = tracker.release_queued("msg-1").unwrap();
+        assert_eq!(change.previous, MessageStatus::Queued);
+        assert_eq!(change.current, MessageStatus::Sent);
+        assert!(tracker.release_queued("msg-1").is_none());
+        assert!(tracker.messages["msg-1"].deadline.is_some());
+    }
+
     #[test]
     fn no_regression() {
         let mut tracker = MessageTracker::new();
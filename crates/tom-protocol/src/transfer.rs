@@ -0,0 +1,851 @@
+/// File transfer over dedicated streams, resumable, with progress events.
+///
+/// Files don't go through envelopes: the sender opens a `TomStream` to the
+/// recipient (its own QUIC connection, encrypted and authenticated by
+/// QUIC) and the two exchange frames:
+///
+/// ```text
+/// sender → Offer { id, name, size, chunk_size, chunk hashes }
+///        ← Accept { from_chunk } | Reject { reason }
+///        → Chunk { index, data } ...   (from `from_chunk` on)
+///        ← Complete
+/// ```
+///
+/// The receiver checks each chunk against its SHA-256 from the offer and
+/// appends it to `<download_dir>/.<peer>-<id>.part`; the file gets its real
+/// name once complete. If the stream breaks, the sender reconnects (up to
+/// `MAX_SEND_ATTEMPTS`, backing off) and offers again; the receiver
+/// re-checks the chunks its partial file holds against the new offer and
+/// answers with how many still match.
+///
+/// Both ends report `TransferProgress` (at most once per percent),
+/// then `TransferComplete` or `TransferFailed`. Nodes without a
+/// `download_dir` refuse files; the others apply their [`ReceivePolicy`]
+/// (an accept callback, plus limits on transfers at once and on the bytes
+/// they may write). A partial file whose sender gave up stays on disk.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tom_transport::{StreamOpener, TomStream};
+
+use crate::error::TomProtocolError;
+use crate::runtime::ProtocolEvent;
+use crate::types::NodeId;
+
+/// Size of the chunks files are hashed and sent in.
+pub const CHUNK_SIZE: u32 = 256 * 1024;
+
+/// Largest chunk size a receiver accepts in an offer.
+const MAX_CHUNK_SIZE: u32 = 512 * 1024;
+
+/// Largest file sent or accepted (4 GiB keeps the offer's chunk hash list
+/// within a stream frame).
+pub const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Connection attempts before a sender gives up.
+pub const MAX_SEND_ATTEMPTS: u32 = 5;
+
+/// First delay before reconnecting; doubles per attempt.
+const RETRY_BASE: Duration = Duration::from_secs(2);
+
+/// How long either side waits for the other's next frame.
+const FRAME_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default [`ReceivePolicy::max_per_peer`].
+pub const DEFAULT_MAX_TRANSFERS_PER_PEER: usize = 2;
+
+/// Default [`ReceivePolicy::max_total`].
+pub const DEFAULT_MAX_TRANSFERS: usize = 8;
+
+/// Default [`ReceivePolicy::max_bytes`].
+pub const DEFAULT_MAX_RECEIVE_BYTES: u64 = 2 * MAX_FILE_SIZE;
+
+/// Frame tags: control frames are MessagePack, chunks raw bytes.
+const TAG_CONTROL: u8 = 0;
+const TAG_CHUNK: u8 = 1;
+
+/// What a sender proposes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOffer {
+    pub transfer_id: String,
+    /// File name only (no directories).
+    pub name: String,
+    pub size: u64,
+    pub chunk_size: u32,
+    /// SHA-256 of each chunk, in order.
+    pub chunk_hashes: Vec<[u8; 32]>,
+}
+
+impl FileOffer {
+    /// Hash `path` into an offer. Blocking: reads the whole file.
+    pub fn from_file(path: &Path, transfer_id: String) -> Result<Self, TomProtocolError> {
+        use std::io::Read;
+
+        let io_err = |e: std::io::Error| TomProtocolError::Transfer {
+            reason: format!("{}: {e}", path.display()),
+        };
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| TomProtocolError::Transfer {
+                reason: format!("{}: not a file name", path.display()),
+            })?
+            .to_string();
+        let mut file = std::fs::File::open(path).map_err(io_err)?;
+        let mut chunk_hashes = Vec::new();
+        let mut size = 0u64;
+        let mut buf = vec![0u8; CHUNK_SIZE as usize];
+        loop {
+            let n = read_full(&mut file, &mut buf).map_err(io_err)?;
+            if n == 0 {
+                break;
+            }
+            size += n as u64;
+            chunk_hashes.push(Sha256::digest(&buf[..n]).into());
+            if n < buf.len() {
+                break;
+            }
+        }
+        if size > MAX_FILE_SIZE {
+            return Err(TomProtocolError::Transfer {
+                reason: format!("{}: larger than {MAX_FILE_SIZE} bytes", path.display()),
+            });
+        }
+        return Ok(Self {
+            transfer_id,
+            name,
+            size,
+            chunk_size: CHUNK_SIZE,
+            chunk_hashes,
+        });
+
+        fn read_full(file: &mut std::fs::File, buf: &mut [u8]) -> std::io::Result<usize> {
+            let mut filled = 0;
+            while filled < buf.len() {
+                match file.read(&mut buf[filled..])? {
+                    0 => break,
+                    n => filled += n,
+                }
+            }
+            Ok(filled)
+        }
+    }
+
+    /// Check an offer from a peer: sane id, name, sizes and hash count.
+    pub fn validate(&self) -> Result<(), String> {
+        let id_ok = !self.transfer_id.is_empty()
+            && self.transfer_id.len() <= 64
+            && self.transfer_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !id_ok {
+            return Err("bad transfer id".into());
+        }
+        if safe_file_name(&self.name).is_none() {
+            return Err("bad file name".into());
+        }
+        if self.size > MAX_FILE_SIZE {
+            return Err("file too large".into());
+        }
+        if self.chunk_size == 0 || self.chunk_size > MAX_CHUNK_SIZE {
+            return Err("bad chunk size".into());
+        }
+        if self.chunk_hashes.len() as u64 != self.size.div_ceil(self.chunk_size as u64) {
+            return Err("chunk hashes don't match the size".into());
+        }
+        Ok(())
+    }
+
+    /// Length of chunk `index`.
+    fn chunk_len(&self, index: u64) -> u64 {
+        let start = index * self.chunk_size as u64;
+        (self.size - start).min(self.chunk_size as u64)
+    }
+}
+
+/// Control frames (everything but chunks).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlFrame {
+    Offer(FileOffer),
+    /// Send chunks from this index on (what the receiver lacks).
+    Accept { from_chunk: u64 },
+    Reject { reason: String },
+    /// Every chunk arrived and checked out.
+    Complete,
+}
+
+/// One frame on a transfer stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferFrame {
+    Control(ControlFrame),
+    Chunk { index: u64, data: Vec<u8> },
+}
+
+impl TransferFrame {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Control(frame) => {
+                let mut buf = vec![TAG_CONTROL];
+                buf.extend(rmp_serde::to_vec(frame).expect("ControlFrame serialization cannot fail"));
+                buf
+            }
+            Self::Chunk { index, data } => {
+                let mut buf = Vec::with_capacity(9 + data.len());
+                buf.push(TAG_CHUNK);
+                buf.extend_from_slice(&index.to_be_bytes());
+                buf.extend_from_slice(data);
+                buf
+            }
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TomProtocolError> {
+        match bytes.split_first() {
+            Some((&TAG_CONTROL, rest)) => Ok(Self::Control(rmp_serde::from_slice(rest)?)),
+            Some((&TAG_CHUNK, rest)) if rest.len() >= 8 => {
+                let (index, data) = rest.split_at(8);
+                Ok(Self::Chunk {
+                    index: u64::from_be_bytes(index.try_into().expect("8 bytes")),
+                    data: data.to_vec(),
+                })
+            }
+            _ => Err(TomProtocolError::Deserialization("bad transfer frame".into())),
+        }
+    }
+}
+
+/// The last component of a peer-supplied name, if it's a plain file name.
+fn safe_file_name(name: &str) -> Option<&str> {
+    let ok = !name.is_empty()
+        && name.len() <= 255
+        && name != "."
+        && name != ".."
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\', '\0']);
+    ok.then_some(name)
+}
+
+/// Where a finished file goes: `name`, or `name (id)` if taken.
+fn final_path(dir: &Path, offer: &FileOffer) -> PathBuf {
+    let path = dir.join(&offer.name);
+    if !path.exists() {
+        return path;
+    }
+    let short_id = &offer.transfer_id[..offer.transfer_id.len().min(8)];
+    let renamed = match offer.name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{stem} ({short_id}).{ext}"),
+        _ => format!("{} ({short_id})", offer.name),
+    };
+    dir.join(renamed)
+}
+
+/// Reports progress at most once per percent.
+struct ProgressMeter {
+    transfer_id: String,
+    peer: NodeId,
+    outgoing: bool,
+    total: u64,
+    last_percent: Option<u64>,
+}
+
+impl ProgressMeter {
+    fn new(transfer_id: &str, peer: NodeId, outgoing: bool, total: u64) -> Self {
+        Self {
+            transfer_id: transfer_id.to_string(),
+            peer,
+            outgoing,
+            total,
+            last_percent: None,
+        }
+    }
+
+    fn update(&mut self, bytes_done: u64) -> Option<ProtocolEvent> {
+        let percent = bytes_done.saturating_mul(100).checked_div(self.total).unwrap_or(100);
+        if self.last_percent == Some(percent) {
+            return None;
+        }
+        self.last_percent = Some(percent);
+        Some(ProtocolEvent::TransferProgress {
+            transfer_id: self.transfer_id.clone(),
+            peer: self.peer,
+            outgoing: self.outgoing,
+            bytes_done,
+            total: self.total,
+        })
+    }
+}
+
+/// Decides whether an offered file is taken (after it validated).
+pub type AcceptFile = Arc<dyn Fn(NodeId, &FileOffer) -> bool + Send + Sync>;
+
+/// Which incoming files a node takes, and how many at once.
+#[derive(Clone)]
+pub struct ReceivePolicy {
+    /// Asked about each offer; false refuses it. None = take every file.
+    pub accept: Option<AcceptFile>,
+    /// Incoming transfers at once from one peer.
+    pub max_per_peer: usize,
+    /// Incoming transfers at once, all peers together.
+    pub max_total: usize,
+    /// Bytes the transfers in progress may write, all together (each
+    /// reserves its file's size).
+    pub max_bytes: u64,
+}
+
+impl Default for ReceivePolicy {
+    fn default() -> Self {
+        Self {
+            accept: None,
+            max_per_peer: DEFAULT_MAX_TRANSFERS_PER_PEER,
+            max_total: DEFAULT_MAX_TRANSFERS,
+            max_bytes: DEFAULT_MAX_RECEIVE_BYTES,
+        }
+    }
+}
+
+impl std::fmt::Debug for ReceivePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReceivePolicy")
+            .field("accept", &self.accept.as_ref().map(|_| "<callback>"))
+            .field("max_per_peer", &self.max_per_peer)
+            .field("max_total", &self.max_total)
+            .field("max_bytes", &self.max_bytes)
+            .finish()
+    }
+}
+
+/// The policy plus what incoming transfers currently hold, shared by
+/// every receive task of a node.
+#[derive(Clone, Default)]
+pub(crate) struct ReceiveBudget {
+    policy: ReceivePolicy,
+    held: Arc<Mutex<Held>>,
+}
+
+#[derive(Default)]
+struct Held {
+    per_peer: HashMap<NodeId, usize>,
+    transfers: usize,
+    bytes: u64,
+}
+
+/// One admitted transfer; gives its share back when dropped.
+struct Admission {
+    held: Arc<Mutex<Held>>,
+    peer: NodeId,
+    bytes: u64,
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        let mut held = self.held.lock().unwrap();
+        if let Some(count) = held.per_peer.get_mut(&self.peer) {
+            *count -= 1;
+            if *count == 0 {
+                held.per_peer.remove(&self.peer);
+            }
+        }
+        held.transfers -= 1;
+        held.bytes -= self.bytes;
+    }
+}
+
+impl ReceiveBudget {
+    pub(crate) fn new(policy: ReceivePolicy) -> Self {
+        Self { policy, held: Arc::default() }
+    }
+
+    /// Ask the policy about `offer`, then take a slot for it; the reason
+    /// to give the sender otherwise.
+    fn admit(&self, peer: NodeId, offer: &FileOffer) -> Result<Admission, String> {
+        if let Some(accept) = &self.policy.accept {
+            if !accept(peer, offer) {
+                return Err("file refused".into());
+            }
+        }
+        let mut held = self.held.lock().unwrap();
+        if held.per_peer.get(&peer).copied().unwrap_or(0) >= self.policy.max_per_peer {
+            return Err("too many transfers from you".into());
+        }
+        if held.transfers >= self.policy.max_total {
+            return Err("too many transfers".into());
+        }
+        if held.bytes.saturating_add(offer.size) > self.policy.max_bytes {
+            return Err("not enough room for the file".into());
+        }
+        *held.per_peer.entry(peer).or_default() += 1;
+        held.transfers += 1;
+        held.bytes += offer.size;
+        Ok(Admission { held: self.held.clone(), peer, bytes: offer.size })
+    }
+}
+
+/// Why one attempt at sending stopped.
+enum Interrupted {
+    /// Worth reconnecting: the stream broke or timed out.
+    Retry(String),
+    /// Final: refused by the peer, or our file is unreadable.
+    Fail(String),
+}
+
+async fn recv_frame(stream: &mut TomStream) -> Result<TransferFrame, String> {
+    match tokio::time::timeout(FRAME_TIMEOUT, stream.recv_frame()).await {
+        Err(_) => Err("timed out".into()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Ok(Ok(None)) => Err("stream closed".into()),
+        Ok(Ok(Some(bytes))) => TransferFrame::from_bytes(&bytes).map_err(|e| e.to_string()),
+    }
+}
+
+async fn send_frame(stream: &mut TomStream, frame: TransferFrame) -> Result<(), String> {
+    stream.send_frame(&frame.to_bytes()).await.map_err(|e| e.to_string())
+}
+
+/// Start sending `path` to `to` in the background. Returns the transfer id;
+/// progress and outcome arrive on `events`.
+pub fn spawn_send(
+    opener: StreamOpener,
+    to: NodeId,
+    path: PathBuf,
+    events: mpsc::Sender<ProtocolEvent>,
+) -> Result<String, TomProtocolError> {
+    let metadata = std::fs::metadata(&path).map_err(|e| TomProtocolError::Transfer {
+        reason: format!("{}: {e}", path.display()),
+    })?;
+    if !metadata.is_file() || metadata.len() > MAX_FILE_SIZE {
+        return Err(TomProtocolError::Transfer {
+            reason: format!("{}: not a file of at most {MAX_FILE_SIZE} bytes", path.display()),
+        });
+    }
+    let transfer_id = uuid::Uuid::new_v4().to_string();
+    tokio::spawn(run_send(opener, to, path, transfer_id.clone(), events));
+    Ok(transfer_id)
+}
+
+async fn run_send(
+    opener: StreamOpener,
+    to: NodeId,
+    path: PathBuf,
+    transfer_id: String,
+    events: mpsc::Sender<ProtocolEvent>,
+) {
+    let failed = |reason: String| ProtocolEvent::TransferFailed {
+        transfer_id: transfer_id.clone(),
+        peer: to,
+        outgoing: true,
+        reason,
+    };
+    let hashing = {
+        let (path, transfer_id) = (path.clone(), transfer_id.clone());
+        tokio::task::spawn_blocking(move || FileOffer::from_file(&path, transfer_id)).await
+    };
+    let offer = match hashing {
+        Ok(Ok(offer)) => offer,
+        Ok(Err(e)) => return drop(events.send(failed(e.to_string())).await),
+        Err(e) => return drop(events.send(failed(e.to_string())).await),
+    };
+
+    let mut meter = ProgressMeter::new(&transfer_id, to, true, offer.size);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let outcome = send_attempt(&opener, to, &path, &offer, &mut meter, &events).await;
+        let event = match outcome {
+            Ok(()) => ProtocolEvent::TransferComplete {
+                transfer_id: transfer_id.clone(),
+                peer: to,
+                outgoing: true,
+                name: offer.name.clone(),
+                size: offer.size,
+                path: Some(path.clone()),
+            },
+            Err(Interrupted::Retry(reason)) if attempt < MAX_SEND_ATTEMPTS => {
+                tracing::debug!(%transfer_id, attempt, "file transfer interrupted: {reason}");
+                tokio::time::sleep(RETRY_BASE * (1 << (attempt - 1))).await;
+                continue;
+            }
+            Err(Interrupted::Retry(reason) | Interrupted::Fail(reason)) => failed(reason),
+        };
+        let _ = events.send(event).await;
+        return;
+    }
+}
+
+async fn send_attempt(
+    opener: &StreamOpener,
+    to: NodeId,
+    path: &Path,
+    offer: &FileOffer,
+    meter: &mut ProgressMeter,
+    events: &mpsc::Sender<ProtocolEvent>,
+) -> Result<(), Interrupted> {
+    let mut stream = opener.open(to).await.map_err(|e| Interrupted::Retry(e.to_string()))?;
+    send_frame(&mut stream, TransferFrame::Control(ControlFrame::Offer(offer.clone())))
+        .await
+        .map_err(Interrupted::Retry)?;
+    let from_chunk = match recv_frame(&mut stream).await.map_err(Interrupted::Retry)? {
+        TransferFrame::Control(ControlFrame::Accept { from_chunk }) => from_chunk,
+        TransferFrame::Control(ControlFrame::Reject { reason }) => {
+            return Err(Interrupted::Fail(format!("refused: {reason}")))
+        }
+        other => return Err(Interrupted::Fail(format!("unexpected frame {other:?}"))),
+    };
+    let chunks = offer.chunk_hashes.len() as u64;
+    if from_chunk > chunks {
+        return Err(Interrupted::Fail("peer asked for chunks past the end".into()));
+    }
+
+    let io_err = |e: std::io::Error| Interrupted::Fail(format!("{}: {e}", path.display()));
+    let mut file = tokio::fs::File::open(path).await.map_err(io_err)?;
+    let start = from_chunk * offer.chunk_size as u64;
+    file.seek(std::io::SeekFrom::Start(start)).await.map_err(io_err)?;
+    if let Some(event) = meter.update(start) {
+        let _ = events.send(event).await;
+    }
+    let mut done = start;
+    for index in from_chunk..chunks {
+        let mut data = vec![0u8; offer.chunk_len(index) as usize];
+        file.read_exact(&mut data).await.map_err(io_err)?;
+        if <[u8; 32]>::from(Sha256::digest(&data)) != offer.chunk_hashes[index as usize] {
+            return Err(Interrupted::Fail("file changed while sending".into()));
+        }
+        done += data.len() as u64;
+        send_frame(&mut stream, TransferFrame::Chunk { index, data })
+            .await
+            .map_err(Interrupted::Retry)?;
+        if let Some(event) = meter.update(done) {
+            let _ = events.send(event).await;
+        }
+    }
+
+    match recv_frame(&mut stream).await.map_err(Interrupted::Retry)? {
+        TransferFrame::Control(ControlFrame::Complete) => Ok(()),
+        TransferFrame::Control(ControlFrame::Reject { reason }) => Err(Interrupted::Fail(reason)),
+        other => Err(Interrupted::Fail(format!("unexpected frame {other:?}"))),
+    }
+}
+
+/// Serve a stream a peer opened: receive the file it offers into
+/// `download_dir` if `budget` admits it (refuse it without a directory).
+/// Runs in the background.
+pub(crate) fn spawn_receive(
+    stream: TomStream,
+    download_dir: Option<PathBuf>,
+    budget: ReceiveBudget,
+    events: mpsc::Sender<ProtocolEvent>,
+) {
+    tokio::spawn(run_receive(stream, download_dir, budget, events));
+}
+
+async fn run_receive(
+    mut stream: TomStream,
+    download_dir: Option<PathBuf>,
+    budget: ReceiveBudget,
+    events: mpsc::Sender<ProtocolEvent>,
+) {
+    let peer = stream.peer();
+    let offer = match recv_frame(&mut stream).await {
+        Ok(TransferFrame::Control(ControlFrame::Offer(offer))) => offer,
+        Ok(_) => return,
+        Err(e) => return tracing::debug!(%peer, "file offer not received: {e}"),
+    };
+    let admitted = match (&download_dir, offer.validate()) {
+        (None, _) => Err("not accepting files".to_string()),
+        (_, Err(reason)) => Err(reason),
+        _ => budget.admit(peer, &offer),
+    };
+    let _admission = match admitted {
+        Ok(admission) => admission,
+        Err(reason) => {
+            tracing::debug!(%peer, transfer_id = %offer.transfer_id, "file refused: {reason}");
+            let _ = send_frame(&mut stream, TransferFrame::Control(ControlFrame::Reject { reason })).await;
+            return stream.close().await;
+        }
+    };
+    let dir = download_dir.expect("checked above");
+
+    match receive_file(&mut stream, &dir, &offer, &events).await {
+        Ok(path) => {
+            let _ = send_frame(&mut stream, TransferFrame::Control(ControlFrame::Complete)).await;
+            let _ = events
+                .send(ProtocolEvent::TransferComplete {
+                    transfer_id: offer.transfer_id.clone(),
+                    peer,
+                    outgoing: false,
+                    name: offer.name.clone(),
+                    size: offer.size,
+                    path: Some(path),
+                })
+                .await;
+            stream.close().await;
+        }
+        Err(Interrupted::Retry(reason)) => {
+            // The sender reconnects; the partial file stays for it
+            tracing::debug!(%peer, transfer_id = %offer.transfer_id, "file transfer interrupted: {reason}");
+        }
+        Err(Interrupted::Fail(reason)) => {
+            let _ = send_frame(
+                &mut stream,
+                TransferFrame::Control(ControlFrame::Reject { reason: reason.clone() }),
+            )
+            .await;
+            let _ = events
+                .send(ProtocolEvent::TransferFailed {
+                    transfer_id: offer.transfer_id.clone(),
+                    peer,
+                    outgoing: false,
+                    reason,
+                })
+                .await;
+            stream.close().await;
+        }
+    }
+}
+
+/// Partial file of `offer` from `peer`: per peer, so two senders can't
+/// write into each other's file by reusing a transfer id.
+fn part_path(dir: &Path, peer: NodeId, offer: &FileOffer) -> PathBuf {
+    dir.join(format!(".{peer}-{}.part", offer.transfer_id))
+}
+
+/// Take the chunks into the partial file, resuming after what it holds,
+/// then move it to its final name.
+async fn receive_file(
+    stream: &mut TomStream,
+    dir: &Path,
+    offer: &FileOffer,
+    events: &mpsc::Sender<ProtocolEvent>,
+) -> Result<PathBuf, Interrupted> {
+    let io_err = |e: std::io::Error| Interrupted::Fail(format!("cannot write the file: {e}"));
+    tokio::fs::create_dir_all(dir).await.map_err(io_err)?;
+    let part_path = part_path(dir, stream.peer(), offer);
+    let mut part = tokio::fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .write(true)
+        .truncate(false)
+        .open(&part_path)
+        .await
+        .map_err(io_err)?;
+
+    // Keep the leading chunks that match this offer: the ones held may
+    // come from an earlier offer of other content under the same id
+    let chunks = offer.chunk_hashes.len() as u64;
+    let held = part.metadata().await.map_err(io_err)?.len();
+    let mut from_chunk = 0;
+    let mut done = 0;
+    while from_chunk < chunks && done + offer.chunk_len(from_chunk) <= held {
+        let mut data = vec![0u8; offer.chunk_len(from_chunk) as usize];
+        part.read_exact(&mut data).await.map_err(io_err)?;
+        if <[u8; 32]>::from(Sha256::digest(&data)) != offer.chunk_hashes[from_chunk as usize] {
+            break;
+        }
+        done += data.len() as u64;
+        from_chunk += 1;
+    }
+    part.set_len(done).await.map_err(io_err)?;
+    part.seek(std::io::SeekFrom::Start(done)).await.map_err(io_err)?;
+    send_frame(stream, TransferFrame::Control(ControlFrame::Accept { from_chunk }))
+        .await
+        .map_err(Interrupted::Retry)?;
+
+    let mut meter = ProgressMeter::new(&offer.transfer_id, stream.peer(), false, offer.size);
+    if let Some(event) = meter.update(done) {
+        let _ = events.send(event).await;
+    }
+    for expected in from_chunk..chunks {
+        let (index, data) = match recv_frame(stream).await.map_err(Interrupted::Retry)? {
+            TransferFrame::Chunk { index, data } => (index, data),
+            other => return Err(Interrupted::Fail(format!("unexpected frame {other:?}"))),
+        };
+        if index != expected
+            || data.len() as u64 != offer.chunk_len(index)
+            || <[u8; 32]>::from(Sha256::digest(&data)) != offer.chunk_hashes[index as usize]
+        {
+            return Err(Interrupted::Fail(format!("chunk {index} is corrupt")));
+        }
+        part.write_all(&data).await.map_err(io_err)?;
+        done += data.len() as u64;
+        if let Some(event) = meter.update(done) {
+            let _ = events.send(event).await;
+        }
+    }
+    part.sync_all().await.map_err(io_err)?;
+    drop(part);
+
+    let path = final_path(dir, offer);
+    tokio::fs::rename(&part_path, &path).await.map_err(io_err)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offers_hash_chunks_and_validate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, vec![7u8; CHUNK_SIZE as usize + 10]).unwrap();
+
+        let offer = FileOffer::from_file(&path, "t-1".into()).unwrap();
+        assert_eq!((offer.name.as_str(), offer.size), ("notes.txt", CHUNK_SIZE as u64 + 10));
+        assert_eq!(offer.chunk_hashes.len(), 2);
+        assert_eq!(offer.chunk_len(1), 10);
+        assert!(offer.validate().is_ok());
+
+        for name in ["../x", ".hidden", "a/b", ""] {
+            let bad = FileOffer { name: name.into(), ..offer.clone() };
+            assert!(bad.validate().is_err(), "{name:?} accepted");
+        }
+        let bad = FileOffer { chunk_hashes: vec![[0; 32]], ..offer.clone() };
+        assert!(bad.validate().is_err());
+        let bad = FileOffer { transfer_id: "../t".into(), ..offer.clone() };
+        assert!(bad.validate().is_err());
+
+        // Taken names get the transfer id
+        std::fs::write(dir.path().join("notes.txt"), b"").unwrap();
+        assert_eq!(final_path(dir.path(), &offer), dir.path().join("notes (t-1).txt"));
+    }
+
+    #[test]
+    fn frames_roundtrip() {
+        let frames = [
+            TransferFrame::Control(ControlFrame::Accept { from_chunk: 3 }),
+            TransferFrame::Control(ControlFrame::Complete),
+            TransferFrame::Chunk { index: 9, data: vec![1, 2, 3] },
+        ];
+        for frame in frames {
+            assert_eq!(TransferFrame::from_bytes(&frame.to_bytes()).unwrap(), frame);
+        }
+        assert!(TransferFrame::from_bytes(&[TAG_CHUNK, 1]).is_err());
+    }
+
+    #[tokio::test]
+    async fn receiver_resumes_after_verified_chunks() {
+        let content: Vec<u8> = (0..CHUNK_SIZE as usize * 2 + 5).map(|i| i as u8).collect();
+        // An earlier attempt left chunk 0 and part of chunk 1
+        resume_with_part(&content, content[..CHUNK_SIZE as usize + 100].to_vec(), 1).await;
+    }
+
+    #[tokio::test]
+    async fn receiver_rechecks_held_chunks_against_the_offer() {
+        let content: Vec<u8> = (0..CHUNK_SIZE as usize * 2 + 5).map(|i| i as u8).collect();
+        // A different file was offered under the same id before
+        let mut stale = content[..CHUNK_SIZE as usize * 2].to_vec();
+        stale[10] ^= 0xff;
+        resume_with_part(&content, stale, 0).await;
+    }
+
+    /// Offer `content` to a receiver whose partial file holds `held`;
+    /// check it resumes at `from_chunk` and ends up with `content`.
+    async fn resume_with_part(content: &[u8], held: Vec<u8>, from_chunk: u64) {
+        use tom_transport::{TomNode, TomNodeConfig};
+
+        let sender = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+        let mut receiver = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+        sender.add_peer_addr(receiver.addr()).await;
+        let mut incoming = receiver.take_incoming_streams().unwrap();
+
+        let src = tempfile::tempdir().unwrap();
+        let path = src.path().join("photo.jpg");
+        std::fs::write(&path, content).unwrap();
+        let offer = FileOffer::from_file(&path, "resume-1".into()).unwrap();
+
+        let downloads = tempfile::tempdir().unwrap();
+        let part = part_path(downloads.path(), sender.id(), &offer);
+        std::fs::write(&part, held).unwrap();
+
+        let (events_tx, mut events) = mpsc::channel(64);
+        let mut stream = sender.streams().open(receiver.id()).await.unwrap();
+        send_frame(&mut stream, TransferFrame::Control(ControlFrame::Offer(offer.clone())))
+            .await
+            .unwrap();
+        spawn_receive(
+            incoming.recv().await.unwrap(),
+            Some(downloads.path().into()),
+            ReceiveBudget::default(),
+            events_tx,
+        );
+        assert_eq!(
+            recv_frame(&mut stream).await.unwrap(),
+            TransferFrame::Control(ControlFrame::Accept { from_chunk })
+        );
+        for index in from_chunk..3 {
+            let start = (index * CHUNK_SIZE as u64) as usize;
+            let data = content[start..start + offer.chunk_len(index) as usize].to_vec();
+            send_frame(&mut stream, TransferFrame::Chunk { index, data }).await.unwrap();
+        }
+        assert_eq!(
+            recv_frame(&mut stream).await.unwrap(),
+            TransferFrame::Control(ControlFrame::Complete)
+        );
+        drop(stream);
+
+        let saved = loop {
+            match events.recv().await.unwrap() {
+                ProtocolEvent::TransferComplete { path, outgoing: false, .. } => break path.unwrap(),
+                ProtocolEvent::TransferProgress { .. } => {}
+                other => panic!("unexpected {other:?}"),
+            }
+        };
+        assert_eq!(saved, downloads.path().join("photo.jpg"));
+        assert_eq!(std::fs::read(saved).unwrap(), content);
+        assert!(!part.exists());
+
+        sender.shutdown().await.unwrap();
+        receiver.shutdown().await.unwrap();
+    }
+
+    fn node_id(seed: u64) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        tom_connect::SecretKey::generate(&mut rng).public().to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn budget_bounds_transfers_and_bytes() {
+        let offer = |size: u64| FileOffer {
+            transfer_id: "t".into(),
+            name: "f".into(),
+            size,
+            chunk_size: CHUNK_SIZE,
+            chunk_hashes: Vec::new(),
+        };
+        let (alice, bob, carol) = (node_id(1), node_id(2), node_id(3));
+        let budget = ReceiveBudget::new(ReceivePolicy {
+            accept: Some(Arc::new(move |peer, _: &FileOffer| peer != carol)),
+            max_per_peer: 1,
+            max_total: 2,
+            max_bytes: 100,
+        });
+
+        assert_eq!(budget.admit(carol, &offer(1)).err().unwrap(), "file refused");
+        let first = budget.admit(alice, &offer(60)).unwrap();
+        assert!(budget.admit(alice, &offer(1)).is_err(), "second from alice");
+        assert!(budget.admit(bob, &offer(41)).is_err(), "over the byte budget");
+        let second = budget.admit(bob, &offer(40)).unwrap();
+        assert!(budget.admit(node_id(4), &offer(0)).is_err(), "over the total");
+
+        // Finished transfers give their share back
+        drop(first);
+        let _third = budget.admit(alice, &offer(60)).unwrap();
+        drop(second);
+        assert_eq!(budget.held.lock().unwrap().per_peer.len(), 1);
+    }
+
+    #[test]
+    fn progress_reports_each_percent_once() {
+        let peer = node_id(1);
+        let mut meter = ProgressMeter::new("t", peer, true, 1000);
+        assert!(meter.update(0).is_some());
+        assert!(meter.update(5).is_none());
+        assert!(meter.update(10).is_some());
+        assert!(meter.update(1000).is_some());
+        assert!(ProgressMeter::new("t", peer, true, 0).update(0).is_some());
+    }
+}
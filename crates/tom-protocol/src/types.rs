@@ -1,6 +1,22 @@
 use serde::{Deserialize, Serialize};
 
-pub use tom_transport::{now_ms, NodeId};
+pub use tom_transport::{NodeId, PathEvent, PathKind};
+
+thread_local! {
+    /// Virtual time of a `sim::SimNetwork` running on this thread.
+    static SIM_CLOCK: std::cell::Cell<Option<u64>> = const { std::cell::Cell::new(None) };
+}
+
+/// Current Unix time in milliseconds — the virtual clock instead while a
+/// simulation runs on this thread.
+pub fn now_ms() -> u64 {
+    SIM_CLOCK.with(|clock| clock.get()).unwrap_or_else(crate::time::unix_ms)
+}
+
+/// Pin (or with `None`, release) this thread's clock for a simulation.
+pub(crate) fn set_sim_clock(now: Option<u64>) {
+    SIM_CLOCK.with(|clock| clock.set(now));
+}
 
 /// Message type — determines how the protocol handles the envelope.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -37,30 +53,119 @@ pub enum MessageType {
     // Offline delivery gap-fill (R13)
     GroupSyncRequest,
     GroupSyncResponse,
+    // Group metadata
+    GroupUpdateMetadata,
+    GroupMetadataChanged,
+    // Paged history
+    GroupFetchHistory,
+    GroupHistoryPage,
+    // Invite tokens
+    GroupRegisterInviteToken,
+    GroupJoinWithToken,
+    // Per-member delivery receipts
+    GroupDeliveryReceipt,
+    // Reactions
+    GroupReaction,
+    // Bans
+    GroupBanMember,
+    GroupUnbanMember,
+    // Sharded fan-out (hub → sub-hub)
+    GroupShardRelay,
+    // Scheduled messages
+    GroupCancelScheduled,
+    // Polls
+    GroupPoll,
+    GroupVote,
+    // Disappearing messages
+    GroupSetMessageTtl,
+    GroupMessageTtlChanged,
+    // Per-group settings
+    GroupUpdateSettings,
+    GroupSettingsChanged,
+    // Join approval
+    GroupJoinRequest,
+    GroupJoinRequested,
+    GroupApproveJoin,
+    GroupDenyJoin,
+    GroupJoinRequestResolved,
+    // Hubless mesh groups
+    GroupMeshMembership,
+    GroupMeshSenderKey,
+    GroupMeshMessage,
+    // Shared state (LWW CRDT)
+    SharedStateUpdate,
+    SharedStateSyncRequest,
+    // Exactly-once delivery
+    ExactlyOnce,
+    ExactlyOnceCommit,
     // Backup
     BackupStore,
     BackupDeliver,
+    BackupDeliverBatch,
     BackupReplicate,
     BackupReplicateAck,
     BackupQuery,
     BackupQueryResponse,
     BackupConfirmDelivery,
+    BackupHandoff,
     // Network
     PeerAnnounce,
+    // Ephemeral signals (untracked, direct only)
+    Typing,
+    // Diagnostics: route tracing
+    Trace,
+    // Request/response RPC (untracked, correlated by request id)
+    Request,
+    Response,
+    // History sync between a user's devices
+    HistorySync,
+    // Real-time lane (QUIC datagrams: untracked, unordered, direct only)
+    Realtime,
+    // Per-conversation encryption negotiation (untracked, direct only)
+    EncryptionHandshake,
+    // Protocol version and features, exchanged on first contact
+    Capabilities,
+}
+
+impl MessageType {
+    /// Small protocol-internal traffic others wait on (acks, keepalives,
+    /// hub liveness, shadow syncs): sent ahead of bulk traffic under load.
+    pub fn is_control(&self) -> bool {
+        matches!(
+            self,
+            MessageType::Ack
+                | MessageType::ReadReceipt
+                | MessageType::Heartbeat
+                | MessageType::GroupDeliveryAck
+                | MessageType::GroupHubHeartbeat
+                | MessageType::GroupHubPing
+                | MessageType::GroupHubPong
+                | MessageType::GroupHubShadowSync
+                | MessageType::GroupHubUnreachable
+                | MessageType::ExactlyOnceCommit
+                | MessageType::BackupReplicateAck
+                | MessageType::BackupConfirmDelivery
+                | MessageType::Typing
+                | MessageType::EncryptionHandshake
+                | MessageType::Capabilities
+        )
+    }
 }
 
 /// Delivery status pipeline for a message.
 ///
 /// Follows the progression: Pending -> Sent -> Relayed -> Delivered -> Read.
+/// Messages composed while fully offline wait as `Queued` before `Sent`.
 /// `Failed` is a terminal state set explicitly after ACK timeout + retries exhausted.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum MessageStatus {
     Pending = 0,
-    Sent = 1,
-    Relayed = 2,
-    Delivered = 3,
-    Read = 4,
-    Failed = 5,
+    Queued = 1,
+    Sent = 2,
+    Relayed = 3,
+    Delivered = 4,
+    Read = 5,
+    Failed = 6,
 }
 
 /// Maximum relay depth (hops) for a message.
@@ -69,6 +174,10 @@ pub const MAX_TTL: u32 = 4;
 /// Default TTL for new envelopes.
 pub const DEFAULT_TTL: u32 = 4;
 
+/// Lifespan of any message, counted from when it was written (24 hours,
+/// design decision #2 — no exceptions).
+pub const MESSAGE_LIFESPAN_MS: u64 = 24 * 60 * 60 * 1000;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,14 +213,54 @@ mod tests {
             MessageType::GroupInviteMember,
             MessageType::GroupSyncRequest,
             MessageType::GroupSyncResponse,
+            MessageType::GroupUpdateMetadata,
+            MessageType::GroupMetadataChanged,
+            MessageType::GroupRegisterInviteToken,
+            MessageType::GroupJoinWithToken,
+            MessageType::GroupFetchHistory,
+            MessageType::GroupHistoryPage,
+            MessageType::GroupDeliveryReceipt,
+            MessageType::GroupReaction,
+            MessageType::GroupBanMember,
+            MessageType::GroupUnbanMember,
+            MessageType::GroupShardRelay,
+            MessageType::GroupCancelScheduled,
+            MessageType::GroupPoll,
+            MessageType::GroupVote,
+            MessageType::GroupSetMessageTtl,
+            MessageType::GroupMessageTtlChanged,
+            MessageType::GroupUpdateSettings,
+            MessageType::GroupSettingsChanged,
+            MessageType::GroupJoinRequest,
+            MessageType::GroupJoinRequested,
+            MessageType::GroupApproveJoin,
+            MessageType::GroupDenyJoin,
+            MessageType::GroupJoinRequestResolved,
+            MessageType::GroupMeshMembership,
+            MessageType::GroupMeshSenderKey,
+            MessageType::GroupMeshMessage,
+            MessageType::SharedStateUpdate,
+            MessageType::SharedStateSyncRequest,
+            MessageType::ExactlyOnce,
+            MessageType::ExactlyOnceCommit,
             MessageType::BackupStore,
             MessageType::BackupDeliver,
+            MessageType::BackupDeliverBatch,
             MessageType::BackupReplicate,
             MessageType::BackupReplicateAck,
             MessageType::BackupQuery,
             MessageType::BackupQueryResponse,
             MessageType::BackupConfirmDelivery,
+            MessageType::BackupHandoff,
             MessageType::PeerAnnounce,
+            MessageType::Typing,
+            MessageType::Trace,
+            MessageType::Request,
+            MessageType::Response,
+            MessageType::HistorySync,
+            MessageType::Realtime,
+            MessageType::EncryptionHandshake,
+            MessageType::Capabilities,
         ];
 
         for msg_type in &types {
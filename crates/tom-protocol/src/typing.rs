@@ -0,0 +1,129 @@
+/// Typing indicators — "peer is typing…" for 1:1 conversations.
+///
+/// A typing signal is a tiny signed envelope sent directly to the
+/// conversation peer: no relay path, no tracking, no ACK, no backup.
+/// Losing one is harmless, so both ends rate-limit hard instead:
+/// - the sender emits at most one start per peer every `TYPING_MIN_INTERVAL_MS`
+///   (a UI calling on every keystroke is fine) and a stop only after a start;
+/// - the receiver drops starts arriving faster than that and stops for a peer
+///   it doesn't consider typing.
+///
+/// An indicator is not a session: UIs clear it after `TYPING_TIMEOUT_MS`
+/// without a fresh start, or when the peer's message arrives.
+///
+/// Pure state: callers pass the clock.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::NodeId;
+
+/// Minimum spacing between two start signals to the same peer.
+pub const TYPING_MIN_INTERVAL_MS: u64 = 3_000;
+
+/// How long an indicator stays up without a fresh start.
+pub const TYPING_TIMEOUT_MS: u64 = 3 * TYPING_MIN_INTERVAL_MS;
+
+/// Most peers tracked per direction (oldest entries are pruned past it).
+const MAX_TRACKED_PEERS: usize = 1_024;
+
+/// Wire payload of a `MessageType::Typing` envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypingPayload {
+    /// true = started (or still) typing, false = stopped.
+    pub typing: bool,
+}
+
+/// Per-peer rate limiter for typing signals, one instance per direction.
+#[derive(Debug, Default)]
+pub struct TypingLimiter {
+    /// Peer → when its current typing run last let a start through.
+    active: HashMap<NodeId, u64>,
+}
+
+impl TypingLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a signal for `peer` may pass now; records it if so.
+    ///
+    /// Starts pass once per `TYPING_MIN_INTERVAL_MS`; a stop passes only
+    /// while the peer is typing and ends its run.
+    pub fn allow(&mut self, peer: NodeId, typing: bool, now: u64) -> bool {
+        if !typing {
+            return self.active.remove(&peer).is_some();
+        }
+        if let Some(&last) = self.active.get(&peer) {
+            if now.saturating_sub(last) < TYPING_MIN_INTERVAL_MS {
+                return false;
+            }
+        }
+        if self.active.len() >= MAX_TRACKED_PEERS && !self.active.contains_key(&peer) {
+            self.prune(now);
+            if self.active.len() >= MAX_TRACKED_PEERS {
+                return false;
+            }
+        }
+        self.active.insert(peer, now);
+        true
+    }
+
+    /// Forget a peer's run without signalling (e.g. its message arrived).
+    pub fn clear(&mut self, peer: &NodeId) {
+        self.active.remove(peer);
+    }
+
+    /// Whether `peer` is typing as of `now`.
+    pub fn is_typing(&self, peer: &NodeId, now: u64) -> bool {
+        self.active
+            .get(peer)
+            .is_some_and(|&last| now.saturating_sub(last) < TYPING_TIMEOUT_MS)
+    }
+
+    /// Drop runs that timed out.
+    fn prune(&mut self, now: u64) {
+        self.active
+            .retain(|_, last| now.saturating_sub(*last) < TYPING_TIMEOUT_MS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+        let secret = tom_connect::SecretKey::generate(&mut rng);
+        secret.public().to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn starts_are_spaced_and_stop_needs_a_start() {
+        let mut limiter = TypingLimiter::new();
+        let peer = node_id(1);
+
+        assert!(!limiter.allow(peer, false, 0));
+        assert!(limiter.allow(peer, true, 1_000));
+        assert!(!limiter.allow(peer, true, 1_500));
+        assert!(limiter.allow(peer, true, 1_000 + TYPING_MIN_INTERVAL_MS));
+        assert!(limiter.is_typing(&peer, 1_000 + TYPING_MIN_INTERVAL_MS));
+
+        assert!(limiter.allow(peer, false, 5_000));
+        assert!(!limiter.allow(peer, false, 5_001));
+        assert!(!limiter.is_typing(&peer, 5_001));
+
+        // A new run may start right after a stop
+        assert!(limiter.allow(peer, true, 5_002));
+    }
+
+    #[test]
+    fn indicator_times_out() {
+        let mut limiter = TypingLimiter::new();
+        let peer = node_id(1);
+        limiter.allow(peer, true, 0);
+        assert!(limiter.is_typing(&peer, TYPING_TIMEOUT_MS - 1));
+        assert!(!limiter.is_typing(&peer, TYPING_TIMEOUT_MS));
+    }
+}
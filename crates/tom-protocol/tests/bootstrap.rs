@@ -0,0 +1,59 @@
+//! Startup bootstrap pipeline between real runtimes.
+
+use std::time::Duration;
+
+use tokio::time::timeout;
+use tom_protocol::discovery::{BootstrapConfig, BootstrapSource};
+use tom_protocol::{ProtocolEvent, ProtocolRuntime, RuntimeChannels, RuntimeConfig};
+use tom_transport::{TomNode, TomNodeConfig};
+
+async fn spawn(bootstrap: BootstrapConfig) -> (RuntimeChannels, tom_connect::EndpointAddr) {
+    let node = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+    let addr = node.addr();
+    let config = RuntimeConfig {
+        enable_dht: false,
+        bootstrap,
+        ..RuntimeConfig::default()
+    };
+    (ProtocolRuntime::spawn(node, config), addr)
+}
+
+async fn completion(runtime: &mut RuntimeChannels) -> (usize, Option<BootstrapSource>) {
+    loop {
+        match timeout(Duration::from_secs(15), runtime.events.recv()).await {
+            Ok(Some(ProtocolEvent::BootstrapCompleted { peers_found, source })) => {
+                return (peers_found, source)
+            }
+            Ok(Some(_)) => {}
+            other => panic!("expected BootstrapCompleted, got {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn static_peers_complete_bootstrap() {
+    let (alice, alice_addr) = spawn(BootstrapConfig::default()).await;
+    let (mut bob, _) = spawn(BootstrapConfig {
+        static_peers: vec![alice_addr],
+        ..BootstrapConfig::default()
+    })
+    .await;
+
+    assert_eq!(completion(&mut bob).await, (1, Some(BootstrapSource::Static)));
+
+    alice.handle.shutdown().await;
+    bob.handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn isolated_node_completes_without_source() {
+    let (mut alone, _) = spawn(BootstrapConfig {
+        gossip_timeout: Duration::from_millis(500),
+        ..BootstrapConfig::default()
+    })
+    .await;
+
+    assert_eq!(completion(&mut alone).await, (0, None));
+
+    alone.handle.shutdown().await;
+}
@@ -0,0 +1,243 @@
+/// Exactly-once delivery simulation tests.
+///
+/// Two RuntimeStates backed by SQLite exchange envelopes in-memory. The
+/// "network" is the test: envelopes can be dropped, duplicated, or replayed,
+/// and nodes can be restarted on the same data_dir.
+use std::path::Path;
+
+use tom_protocol::exactly_once::{CommitPayload, EXACTLY_ONCE_MAX_AGE_MS};
+use tom_protocol::{
+    now_ms, DeliveredMessage, EnvelopeBuilder, MessageType, NodeId, ProtocolEvent,
+    RuntimeCommand, RuntimeConfig, RuntimeEffect, RuntimeState,
+};
+
+fn keypair(seed: u8) -> (NodeId, [u8; 32]) {
+    use rand::SeedableRng;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+    let secret = tom_connect::SecretKey::generate(&mut rng);
+    let node_id: NodeId = secret.public().to_string().parse().unwrap();
+    (node_id, secret.to_bytes())
+}
+
+/// A node persisting to `dir` (restart = call again with the same dir).
+fn node(seed: u8, dir: &Path) -> RuntimeState {
+    let (id, secret) = keypair(seed);
+    RuntimeState::new(
+        id,
+        secret,
+        RuntimeConfig {
+            username: format!("node-{seed}"),
+            enable_dht: false,
+            data_dir: Some(dir.to_path_buf()),
+            ..Default::default()
+        },
+    )
+}
+
+fn send(state: &mut RuntimeState, to: NodeId, payload: &[u8]) -> (String, Vec<u8>) {
+    let (tx, mut rx) = tokio::sync::oneshot::channel();
+    let effects = state.handle_command(RuntimeCommand::SendExactlyOnce {
+        to,
+        payload: payload.to_vec(),
+        reply: tx,
+    });
+    let message_id = rx.try_recv().unwrap().expect("send should be accepted");
+    (message_id, single_envelope(&effects))
+}
+
+fn commit(state: &mut RuntimeState, from: NodeId, message_id: &str) -> Vec<u8> {
+    let effects = state.handle_command(RuntimeCommand::CommitMessage {
+        from,
+        message_id: message_id.to_string(),
+    });
+    single_envelope(&effects)
+}
+
+/// Envelopes sent, first-contact capability handshakes aside.
+fn envelopes(effects: &[RuntimeEffect]) -> Vec<Vec<u8>> {
+    effects
+        .iter()
+        .filter_map(|e| match e {
+            RuntimeEffect::SendEnvelope(env) if env.msg_type != MessageType::Capabilities => {
+                Some(env.to_bytes().unwrap())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn single_envelope(effects: &[RuntimeEffect]) -> Vec<u8> {
+    let mut all = envelopes(effects);
+    assert_eq!(all.len(), 1, "expected one envelope, got: {effects:?}");
+    all.remove(0)
+}
+
+fn delivered(effects: &[RuntimeEffect]) -> Vec<&DeliveredMessage> {
+    effects
+        .iter()
+        .filter_map(|e| match e {
+            RuntimeEffect::DeliverMessage(msg) => Some(msg),
+            _ => None,
+        })
+        .collect()
+}
+
+fn committed(effects: &[RuntimeEffect]) -> Vec<String> {
+    effects
+        .iter()
+        .filter_map(|e| match e {
+            RuntimeEffect::Emit(ProtocolEvent::ExactlyOnceCommitted { message_id, .. }) => {
+                Some(message_id.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn duplicates_and_lost_commit_deliver_once() {
+    let (alice_dir, bob_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let mut alice = node(1, alice_dir.path());
+    let mut bob = node(2, bob_dir.path());
+
+    let (id, bytes) = send(&mut alice, bob.local_id(), b"pay 10");
+
+    let effects = bob.handle_incoming(&bytes);
+    let msgs = delivered(&effects);
+    assert_eq!(msgs.len(), 1);
+    assert_eq!(msgs[0].payload, b"pay 10");
+    assert_eq!(msgs[0].envelope_id, id);
+    assert!(msgs[0].exactly_once);
+    assert!(msgs[0].was_encrypted);
+
+    // Network duplicate before the app commits: held, not delivered.
+    assert!(bob.handle_incoming(&bytes).is_empty());
+
+    // The commit is lost on the way back.
+    let _lost = commit(&mut bob, alice.local_id(), &id);
+    assert!(alice.exactly_once_outbox().contains(&id));
+
+    // Alice retries; Bob re-acks without delivering again.
+    let retry = single_envelope(&alice.tick_exactly_once_at(now_ms() + 10_000));
+    let effects = bob.handle_incoming(&retry);
+    assert!(delivered(&effects).is_empty());
+    let ack = single_envelope(&effects);
+
+    assert_eq!(committed(&alice.handle_incoming(&ack)), vec![id.clone()]);
+    assert!(alice.exactly_once_outbox().is_empty());
+
+    // A duplicated commit is harmless, and nothing is retried any more.
+    assert!(committed(&alice.handle_incoming(&ack)).is_empty());
+    assert!(envelopes(&alice.tick_exactly_once_at(now_ms() + 600_000)).is_empty());
+}
+
+#[test]
+fn receiver_restart_keeps_committed_dedup() {
+    let (alice_dir, bob_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let mut alice = node(1, alice_dir.path());
+    let mut bob = node(2, bob_dir.path());
+
+    let (id, bytes) = send(&mut alice, bob.local_id(), b"ship order 7");
+    assert_eq!(delivered(&bob.handle_incoming(&bytes)).len(), 1);
+    let _lost = commit(&mut bob, alice.local_id(), &id);
+
+    // Bob restarts long after the router's in-memory dedup would be gone.
+    drop(bob);
+    let mut bob = node(2, bob_dir.path());
+
+    let retry = single_envelope(&alice.tick_exactly_once_at(now_ms() + 10_000));
+    let effects = bob.handle_incoming(&retry);
+    assert!(delivered(&effects).is_empty(), "committed message redelivered");
+    let ack = single_envelope(&effects);
+    assert_eq!(committed(&alice.handle_incoming(&ack)), vec![id]);
+}
+
+#[test]
+fn receiver_crash_before_commit_redelivers() {
+    let (alice_dir, bob_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let mut alice = node(1, alice_dir.path());
+    let mut bob = node(2, bob_dir.path());
+
+    let (id, bytes) = send(&mut alice, bob.local_id(), b"charge card");
+    assert_eq!(delivered(&bob.handle_incoming(&bytes)).len(), 1);
+
+    // Bob crashes before committing: documented boundary, the message
+    // comes back once after restart so the app can finish processing it.
+    drop(bob);
+    let mut bob = node(2, bob_dir.path());
+
+    let retry = single_envelope(&alice.tick_exactly_once_at(now_ms() + 10_000));
+    let msgs = bob.handle_incoming(&retry);
+    assert_eq!(delivered(&msgs).len(), 1);
+    assert_eq!(delivered(&msgs)[0].envelope_id, id);
+    assert!(bob.handle_incoming(&retry).is_empty());
+
+    let ack = commit(&mut bob, alice.local_id(), &id);
+    assert_eq!(committed(&alice.handle_incoming(&ack)), vec![id]);
+}
+
+#[test]
+fn sender_restart_resumes_retries() {
+    let (alice_dir, bob_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let mut alice = node(1, alice_dir.path());
+    let mut bob = node(2, bob_dir.path());
+
+    // First attempt lost in transit, then Alice restarts.
+    let (id, _lost) = send(&mut alice, bob.local_id(), b"refund");
+    drop(alice);
+    let mut alice = node(1, alice_dir.path());
+    assert!(alice.exactly_once_outbox().contains(&id));
+
+    let retry = single_envelope(&alice.tick_exactly_once_at(now_ms() + 10_000));
+    let effects = bob.handle_incoming(&retry);
+    assert_eq!(delivered(&effects)[0].envelope_id, id, "retry must keep the id");
+
+    let ack = commit(&mut bob, alice.local_id(), &id);
+    assert_eq!(committed(&alice.handle_incoming(&ack)), vec![id]);
+
+    // The commit is persisted too: nothing left after another restart.
+    drop(alice);
+    let alice = node(1, alice_dir.path());
+    assert!(alice.exactly_once_outbox().is_empty());
+}
+
+#[test]
+fn uncommitted_message_expires() {
+    let (alice_dir, bob_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let mut alice = node(1, alice_dir.path());
+    let bob = node(2, bob_dir.path());
+
+    let (id, _lost) = send(&mut alice, bob.local_id(), b"late");
+    let effects = alice.tick_exactly_once_at(now_ms() + EXACTLY_ONCE_MAX_AGE_MS + 1);
+    assert!(envelopes(&effects).is_empty());
+    assert!(effects.iter().any(|e| matches!(
+        e,
+        RuntimeEffect::Emit(ProtocolEvent::ExactlyOnceExpired { message_id, to })
+            if *message_id == id && *to == bob.local_id()
+    )));
+
+    drop(alice);
+    let alice = node(1, alice_dir.path());
+    assert!(alice.exactly_once_outbox().is_empty());
+}
+
+#[test]
+fn only_the_recipient_can_commit() {
+    let (alice_dir, bob_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    let mut alice = node(1, alice_dir.path());
+    let bob = node(2, bob_dir.path());
+    let (carol_id, carol_secret) = keypair(3);
+
+    let (id, _bytes) = send(&mut alice, bob.local_id(), b"for bob");
+
+    // Carol knows the id (e.g. she relayed it) and signs a commit for it.
+    let payload = rmp_serde::to_vec(&CommitPayload { message_id: id.clone() }).unwrap();
+    let to = alice.local_id();
+    let forged = EnvelopeBuilder::new(carol_id, to, MessageType::ExactlyOnceCommit, payload)
+        .sign(&carol_secret)
+        .to_bytes()
+        .unwrap();
+
+    assert!(committed(&alice.handle_incoming(&forged)).is_empty());
+    assert!(alice.exactly_once_outbox().contains(&id));
+}
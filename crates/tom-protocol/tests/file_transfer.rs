@@ -0,0 +1,91 @@
+//! File transfer between two real runtimes over local QUIC.
+
+use std::time::Duration;
+
+use tokio::time::timeout;
+use tom_protocol::{AntiSpamConfig, ProtocolEvent, ProtocolRuntime, RuntimeChannels, RuntimeConfig};
+use tom_transport::{TomNode, TomNodeConfig};
+
+async fn spawn_runtime(download_dir: Option<std::path::PathBuf>) -> (RuntimeChannels, tom_transport::EndpointAddr) {
+    let node = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+    let addr = node.addr();
+    let config = RuntimeConfig {
+        enable_dht: false,
+        antispam_config: AntiSpamConfig {
+            min_rate: 1000.0,
+            ..AntiSpamConfig::default()
+        },
+        download_dir,
+        ..RuntimeConfig::default()
+    };
+    (ProtocolRuntime::spawn(node, config), addr)
+}
+
+/// The first transfer outcome (complete or failed) on `events`, and how
+/// many progress events came before it.
+async fn outcome(events: &mut tokio::sync::mpsc::Receiver<ProtocolEvent>) -> (ProtocolEvent, usize) {
+    let mut progress = 0;
+    loop {
+        let event = timeout(Duration::from_secs(30), events.recv())
+            .await
+            .expect("transfer never finished")
+            .unwrap();
+        match event {
+            ProtocolEvent::TransferProgress { .. } => progress += 1,
+            ProtocolEvent::TransferComplete { .. } | ProtocolEvent::TransferFailed { .. } => {
+                return (event, progress)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn send_file_reaches_download_dir_with_progress() {
+    let downloads = tempfile::tempdir().unwrap();
+    let (mut alice, _) = spawn_runtime(None).await;
+    let (mut bob, bob_addr) = spawn_runtime(Some(downloads.path().into())).await;
+    alice.handle.add_peer_addr(bob_addr).await;
+
+    let src = tempfile::tempdir().unwrap();
+    let path = src.path().join("report.pdf");
+    let content: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, &content).unwrap();
+
+    let bob_id = bob.handle.local_id();
+    let transfer_id = alice.handle.send_file(bob_id, &path).await.unwrap();
+
+    let (sent, progress) = outcome(&mut alice.events).await;
+    match sent {
+        ProtocolEvent::TransferComplete { transfer_id: id, peer, outgoing, size, .. } => {
+            assert_eq!((id.as_str(), peer, outgoing, size), (transfer_id.as_str(), bob_id, true, 1_000_000));
+        }
+        other => panic!("sender: {other:?}"),
+    }
+    assert!(progress >= 2, "sender reported {progress} progress events");
+
+    let (received, _) = outcome(&mut bob.events).await;
+    let saved = match received {
+        ProtocolEvent::TransferComplete { name, outgoing: false, path, .. } => {
+            assert_eq!(name, "report.pdf");
+            path.unwrap()
+        }
+        other => panic!("receiver: {other:?}"),
+    };
+    assert_eq!(saved, downloads.path().join("report.pdf"));
+    assert_eq!(std::fs::read(saved).unwrap(), content);
+
+    // Bob takes no files: Alice's transfer is refused, not retried
+    let transfer_id = bob.handle.send_file(alice.handle.local_id(), &path).await.unwrap();
+    match outcome(&mut bob.events).await.0 {
+        ProtocolEvent::TransferFailed { transfer_id: id, outgoing: true, reason, .. } => {
+            assert_eq!(id, transfer_id);
+            assert!(reason.contains("not accepting files"), "{reason}");
+        }
+        other => panic!("refused transfer: {other:?}"),
+    }
+
+    assert!(alice.handle.send_file(bob_id, src.path().join("missing")).await.is_err());
+    alice.handle.shutdown().await;
+    bob.handle.shutdown().await;
+}
@@ -8,7 +8,7 @@
 /// Bob receives it. Bob leaves. Hub election on failure.
 use tom_protocol::{
     elect_hub, ElectionReason, GroupAction, GroupEvent, GroupHub, GroupId, GroupInfo, GroupManager,
-    GroupMemberRole, GroupMessage, GroupPayload, LeaveReason, NodeId, PeerInfo, PeerRole,
+    GroupMemberRole, GroupMessage, GroupPayload, GroupSettings, LeaveReason, NodeId, PeerInfo, PeerRole,
     PeerStatus, Topology,
 };
 
@@ -191,6 +191,13 @@ fn hub_election_on_failure() {
         shadow_id: None,
         candidate_id: None,
         invite_only: false,
+        description: String::new(),
+        avatar_hash: None,
+        config_version: 0,
+        bans: Vec::new(),
+        message_ttl_ms: None,
+        polls: Vec::new(),
+        settings: GroupSettings::default(),
     };
 
     let mut topology = Topology::new();
@@ -986,11 +993,18 @@ fn hub_failover_shadow_promotes_on_primary_death() {
                 members,
                 candidate_id: cand,
                 config_version,
+                bans,
             },
         } = action
         {
             if *to == shadow_id {
-                shadow_mgr.handle_shadow_sync(group_id, members.clone(), *cand, *config_version);
+                shadow_mgr.handle_shadow_sync(
+                    group_id,
+                    members.clone(),
+                    *cand,
+                    *config_version,
+                    bans.clone(),
+                );
             }
         }
     }
@@ -0,0 +1,53 @@
+//! Real-time lane between two real runtimes over local QUIC datagrams.
+
+use std::time::Duration;
+
+use tokio::time::timeout;
+use tom_protocol::{EventCategory, EventFilter, ProtocolEvent, ProtocolRuntime, RuntimeConfig, TomProtocolError};
+use tom_transport::{TomNode, TomNodeConfig};
+
+#[tokio::test]
+async fn realtime_payloads_arrive_with_stats() {
+    let spawn = || async {
+        let node = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+        let addr = node.addr();
+        let config = RuntimeConfig {
+            enable_dht: false,
+            ..RuntimeConfig::default()
+        };
+        (ProtocolRuntime::spawn(node, config), addr)
+    };
+    let (alice, _) = spawn().await;
+    let (bob, bob_addr) = spawn().await;
+    alice.handle.add_peer_addr(bob_addr).await;
+    let (alice_id, bob_id) = (alice.handle.local_id(), bob.handle.local_id());
+    let mut frames = bob
+        .handle
+        .subscribe_events(EventFilter::all().category(EventCategory::Realtime));
+
+    // Well past the chat anti-spam rate: the lane isn't throttled
+    for n in 0..50u8 {
+        alice.handle.send_realtime(bob_id, vec![n; 160]).await.unwrap();
+    }
+    let mut received = 0;
+    while received < 40 {
+        match timeout(Duration::from_secs(10), frames.recv()).await {
+            Ok(Some(ProtocolEvent::RealtimeReceived { from, payload, .. })) => {
+                assert_eq!((from, payload.len()), (alice_id, 160));
+                received += 1;
+            }
+            other => panic!("expected frames, got {other:?} after {received}"),
+        }
+    }
+
+    let stats = bob.handle.realtime_stats(alice_id).await.unwrap();
+    assert!(stats.received >= 40);
+    assert_eq!(alice.handle.realtime_stats(bob_id).await.unwrap().sent, 50);
+    assert!(matches!(
+        alice.handle.send_realtime(bob_id, vec![0; 4096]).await,
+        Err(TomProtocolError::PayloadTooLarge { .. })
+    ));
+
+    alice.handle.shutdown().await;
+    bob.handle.shutdown().await;
+}
@@ -3,8 +3,8 @@
 /// RuntimeState instances exchange envelopes in-memory.
 /// No transport, no tokio — pure effect-based testing.
 use tom_protocol::{
-    GroupMemberRole, MessageStatus, MessageType, ProtocolEvent, RuntimeCommand, RuntimeConfig,
-    RuntimeEffect, RuntimeState, StateSnapshot, StateStore,
+    ContactCard, GroupMemberRole, MessageStatus, MessageType, ProtocolEvent, RuntimeCommand, RuntimeConfig,
+    RuntimeEffect, RuntimeState, SqliteStateStore, StateSnapshot, StateStore, TrustLevel,
 };
 
 fn keypair(seed: u8) -> (tom_protocol::NodeId, [u8; 32]) {
@@ -355,13 +355,16 @@ fn persistence_roundtrip_with_groups() {
 
     // Save state to temp SQLite
     let dir = tempfile::tempdir().unwrap();
-    let store = StateStore::open(&dir.path().join("test.db")).unwrap();
+    let store = SqliteStateStore::open(&dir.path().join("test.db")).unwrap();
     let snapshot = StateSnapshot {
         manager: Some(alice.group_manager().snapshot()),
         hub: Some(alice.group_hub().snapshot()),
         peers: alice.topology().peers_map().clone(),
         metrics: alice.role_manager().scores().clone(),
         tracked_messages: alice.tracker().snapshot(),
+        shared: Some(alice.shared_state().snapshot()),
+        mesh: Some(alice.mesh_groups().snapshot()),
+        contacts: alice.contacts().snapshot(),
     };
     store.save(&snapshot).unwrap();
 
@@ -731,3 +734,300 @@ fn antispam_throttles_rapid_sender() {
         "Spammer should be throttled after burst capacity, got {throttled}/30"
     );
 }
+
+// ── Test 10: Hub failover with a killed hub ──────────────────────────
+
+#[test]
+fn killed_hub_fails_over_to_shadow_and_members_rehome() {
+    use tom_protocol::group::SHADOW_PING_TIMEOUT_MS;
+
+    let mut alice = state_with(1, false);
+    let mut bob = state_with(2, false);
+    let mut carol = state_with(3, false);
+    let alice_id = alice.local_id();
+    let bob_id = bob.local_id();
+    let carol_id = carol.local_id();
+
+    let create_effects = alice.handle_command(RuntimeCommand::CreateGroup {
+        name: "Failover".to_string(),
+        hub_relay_id: alice_id,
+        initial_members: vec![bob_id, carol_id],
+        invite_only: false,
+    });
+    let bob_invite = extract_bytes_for(&create_effects, bob_id);
+    let carol_invite = extract_bytes_for(&create_effects, carol_id);
+    let group_id = join_member(&mut alice, &mut bob, &bob_invite[0]);
+
+    // Carol joins; deliver everything the hub sends so the shadow gets its sync
+    carol.handle_incoming(&carol_invite[0]);
+    let accept = carol.handle_command(RuntimeCommand::AcceptInvite {
+        group_id: group_id.clone(),
+    });
+    let join_effects = alice.handle_incoming(&extract_envelope_bytes(&accept));
+    for bytes in extract_bytes_for(&join_effects, carol_id) {
+        carol.handle_incoming(&bytes);
+    }
+    for bytes in extract_bytes_for(&join_effects, bob_id) {
+        bob.handle_incoming(&bytes);
+    }
+
+    let shadow_id = alice
+        .group_hub()
+        .get_group(&group_id)
+        .and_then(|g| g.shadow_id)
+        .expect("hub should elect a shadow");
+    let (shadow, member) = if shadow_id == bob_id {
+        (&mut bob, &mut carol)
+    } else {
+        (&mut carol, &mut bob)
+    };
+    assert!(shadow.group_manager().is_shadow_for(&group_id));
+
+    // Alice dies: nothing reaches her from now on. Two unanswered pings promote.
+    let t0 = tom_protocol::now_ms();
+    shadow.tick_shadow_ping_at(t0);
+    shadow.tick_shadow_ping_at(t0 + SHADOW_PING_TIMEOUT_MS);
+    let promote_effects = shadow.tick_shadow_ping_at(t0 + 2 * SHADOW_PING_TIMEOUT_MS);
+
+    assert!(
+        shadow.group_hub().get_group(&group_id).is_some(),
+        "shadow should now host the group"
+    );
+    assert!(promote_effects.iter().any(|e| matches!(
+        e,
+        RuntimeEffect::Emit(ProtocolEvent::GroupHubMigrated { new_hub_id, .. }) if *new_hub_id == shadow_id
+    )));
+    assert!(extract_bytes_for(&promote_effects, alice_id).is_empty());
+
+    // The remaining member re-homes and asks the new hub for missed messages
+    let member_id = member.local_id();
+    let mut sync_requests = Vec::new();
+    for bytes in extract_bytes_for(&promote_effects, member_id) {
+        let effects = member.handle_incoming(&bytes);
+        sync_requests.extend(effects.into_iter().filter(|e| match e {
+            RuntimeEffect::SendEnvelope(envelope)
+            | RuntimeEffect::SendEnvelopeTo { envelope, .. }
+            | RuntimeEffect::SendWithBackupFallback { envelope, .. } => {
+                envelope.msg_type == MessageType::GroupSyncRequest && envelope.to == shadow_id
+            }
+            _ => false,
+        }));
+    }
+    let info = member.group_manager().get_group(&group_id).unwrap();
+    assert_eq!(info.hub_relay_id, shadow_id);
+    assert!(!info.is_member(&alice_id), "dead hub dropped from the roster");
+    assert_eq!(sync_requests.len(), 1, "member should gap-fill from the new hub");
+    assert!(member.group_manager().is_shadow_for(&group_id), "member becomes the new shadow");
+
+    // The new hub accepts and stores the member's messages
+    let msg_effects = member.handle_command(RuntimeCommand::SendGroupMessage {
+        group_id: group_id.clone(),
+        text: "still here".into(),
+    });
+    let to_hub = extract_bytes_for(&msg_effects, shadow_id);
+    assert!(!to_hub.is_empty(), "member should send to the new hub");
+    shadow.handle_incoming(&to_hub[0]);
+    let history = shadow.group_hub().message_history(&group_id).unwrap();
+    assert!(history.iter().any(|m| m.sender_id == member_id));
+}
+
+// ── Test 11: Hub restart restores hosted groups ──────────────────────
+
+#[test]
+fn hub_restart_restores_hosted_groups_without_periodic_save() {
+    let dir = tempfile::tempdir().unwrap();
+    let persistent_hub = || {
+        let (id, secret) = keypair(1);
+        RuntimeState::new(
+            id,
+            secret,
+            RuntimeConfig {
+                username: "node-1".into(),
+                enable_dht: false,
+                data_dir: Some(dir.path().to_path_buf()),
+                ..Default::default()
+            },
+        )
+    };
+
+    let mut alice = persistent_hub();
+    let mut bob = state_with(2, false);
+    let mut carol = state_with(3, false);
+    let alice_id = alice.local_id();
+    let bob_id = bob.local_id();
+    let carol_id = carol.local_id();
+
+    let create_effects = alice.handle_command(RuntimeCommand::CreateGroup {
+        name: "Durable".to_string(),
+        hub_relay_id: alice_id,
+        initial_members: vec![bob_id, carol_id],
+        invite_only: false,
+    });
+    let bob_invite = extract_bytes_for(&create_effects, bob_id);
+    let carol_invite = extract_bytes_for(&create_effects, carol_id);
+    let group_id = join_member(&mut alice, &mut bob, &bob_invite[0]);
+    join_member(&mut alice, &mut carol, &carol_invite[0]);
+
+    let msg_effects = bob.handle_command(RuntimeCommand::SendGroupMessage {
+        group_id: group_id.clone(),
+        text: "before restart".into(),
+    });
+    alice.handle_incoming(&extract_envelope_bytes(&msg_effects));
+    let before = alice.group_hub().get_group(&group_id).unwrap().clone();
+    assert!(before.shadow_id.is_some());
+
+    // Restart: no save_state() call, only the on-change checkpoints
+    drop(alice);
+    let mut alice = persistent_hub();
+
+    let restored = alice
+        .group_hub()
+        .get_group(&group_id)
+        .expect("hosted group should survive the restart");
+    assert!(restored.is_member(&bob_id));
+    assert!(restored.is_member(&carol_id));
+    assert_eq!(restored.shadow_id, before.shadow_id);
+    let history = alice.group_hub().message_history(&group_id).unwrap();
+    assert_eq!(history.len(), 1, "recent history reloaded");
+    let last_seq = history[0].seq;
+
+    // Sequence numbers keep increasing after the restart
+    let msg_effects = bob.handle_command(RuntimeCommand::SendGroupMessage {
+        group_id: group_id.clone(),
+        text: "after restart".into(),
+    });
+    alice.handle_incoming(&extract_envelope_bytes(&msg_effects));
+    let history = alice.group_hub().message_history(&group_id).unwrap();
+    assert_eq!(history.len(), 2);
+    assert!(history[1].seq > last_seq);
+}
+
+#[test]
+fn offline_outbox_survives_restart_and_flushes_on_reconnect() {
+    let dir = tempfile::tempdir().unwrap();
+    let persistent_alice = || {
+        let (id, secret) = keypair(1);
+        RuntimeState::new(
+            id,
+            secret,
+            RuntimeConfig {
+                username: "node-1".into(),
+                enable_dht: false,
+                data_dir: Some(dir.path().to_path_buf()),
+                ..Default::default()
+            },
+        )
+    };
+
+    let mut alice = persistent_alice();
+    let mut bob = state_with(2, true);
+    let bob_id = bob.local_id();
+
+    // Fully offline: the message is queued, nothing goes to the transport
+    assert!(alice.set_connectivity(false).is_empty());
+    let effects = alice.handle_command(RuntimeCommand::SendMessage {
+        to: bob_id,
+        payload: b"written on a plane".to_vec(),
+        reply: None,
+    });
+    let [RuntimeEffect::StatusChange(queued)] = &effects[..] else {
+        panic!("expected only a Queued status change, got {effects:?}");
+    };
+    assert_eq!(queued.current, MessageStatus::Queued);
+
+    // Restart: the queue is reloaded from disk
+    drop(alice);
+    let mut alice = persistent_alice();
+    let waiting = alice.queued_messages();
+    assert_eq!(waiting.len(), 1);
+    assert_eq!(waiting[0].message_id, queued.message_id);
+
+    // Connectivity returns: sent with the same id, Queued → Sent
+    let flush = alice.set_connectivity(true);
+    let Some(RuntimeEffect::SendWithBackupFallback { envelope, on_success, .. }) = flush.first() else {
+        panic!("expected a send, got {flush:?}");
+    };
+    assert_eq!(envelope.id, queued.message_id);
+    assert!(on_success.iter().any(|e| matches!(e,
+        RuntimeEffect::StatusChange(sc)
+            if sc.previous == MessageStatus::Queued && sc.current == MessageStatus::Sent)));
+    assert!(alice.queued_messages().is_empty());
+
+    let delivered = bob
+        .handle_incoming(&extract_envelope_bytes(&flush))
+        .into_iter()
+        .find_map(|e| match e {
+            RuntimeEffect::DeliverMessage(msg) => Some(msg),
+            _ => None,
+        })
+        .expect("bob receives the queued message");
+    assert_eq!(delivered.payload, b"written on a plane");
+
+    // Nothing is left to flush after another restart
+    drop(alice);
+    let mut alice = persistent_alice();
+    assert!(alice.queued_messages().is_empty());
+    assert!(alice.set_connectivity(true).is_empty());
+}
+
+#[test]
+fn blocked_contact_is_dropped_and_block_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let persistent_alice = || {
+        let (id, secret) = keypair(1);
+        RuntimeState::new(
+            id,
+            secret,
+            RuntimeConfig {
+                username: "node-1".into(),
+                enable_dht: false,
+                data_dir: Some(dir.path().to_path_buf()),
+                ..Default::default()
+            },
+        )
+    };
+
+    let mut alice = persistent_alice();
+    let alice_id = alice.local_id();
+    let mut bob = state_with(2, false);
+    let bob_id = bob.local_id();
+
+    alice.handle_command(RuntimeCommand::ImportContact {
+        card: ContactCard::new(bob_id, "bob".into()),
+    });
+    alice.handle_command(RuntimeCommand::SetContactTrust {
+        node_id: bob_id,
+        trust: TrustLevel::Verified,
+    });
+    alice.handle_command(RuntimeCommand::SetContactBlocked {
+        node_id: bob_id,
+        blocked: true,
+    });
+
+    let send = |bob: &mut RuntimeState| {
+        extract_envelope_bytes(&bob.handle_command(RuntimeCommand::SendMessage {
+            to: alice_id,
+            payload: b"let me in".to_vec(),
+            reply: None,
+        }))
+    };
+    assert!(alice.handle_incoming(&send(&mut bob)).is_empty());
+    assert_eq!(alice.router().blocked_drops(), 1);
+
+    // Restart without a periodic save: the block was written through
+    drop(alice);
+    let mut alice = persistent_alice();
+    let contact = alice.contacts().get(&bob_id).cloned().expect("bob persisted");
+    assert!(contact.blocked);
+    assert_eq!(contact.trust, TrustLevel::Verified);
+    assert!(alice.handle_incoming(&send(&mut bob)).is_empty());
+
+    // Unblocked: delivered again, and last seen is recorded
+    alice.handle_command(RuntimeCommand::SetContactBlocked {
+        node_id: bob_id,
+        blocked: false,
+    });
+    let effects = alice.handle_incoming(&send(&mut bob));
+    assert!(effects.iter().any(|e| matches!(e, RuntimeEffect::DeliverMessage(_))));
+    assert!(alice.contacts().get(&bob_id).unwrap().last_seen.is_some());
+}
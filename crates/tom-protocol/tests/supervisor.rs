@@ -0,0 +1,104 @@
+//! The runtime supervisor: a loop that panics restarts over the same
+//! state, and the node stops after `max_restarts` restarts in a row.
+
+use std::time::Duration;
+
+use tokio::time::timeout;
+use tom_protocol::{HookAction, ProtocolEvent, ProtocolRuntime, RuntimeChannels, RuntimeConfig, RuntimeHook};
+use tom_transport::{TomNode, TomNodeConfig};
+
+/// Panics the loop whenever the runtime is paused.
+struct PanicOnPause;
+
+impl RuntimeHook for PanicOnPause {
+    fn on_event(&self, event: &ProtocolEvent) -> HookAction {
+        if matches!(event, ProtocolEvent::RuntimePaused { paused: true }) {
+            panic!("injected panic");
+        }
+        HookAction::Continue
+    }
+}
+
+async fn spawn_runtime(max_restarts: u32) -> RuntimeChannels {
+    let node = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+    let config = RuntimeConfig {
+        enable_dht: false,
+        hooks: vec![Box::new(PanicOnPause)],
+        restart_backoff: Duration::from_millis(10),
+        max_restarts,
+        ..RuntimeConfig::default()
+    };
+    ProtocolRuntime::spawn(node, config)
+}
+
+/// Panic the loop once; the resume runs on the restarted loop, so the
+/// next pause panics again.
+async fn inject_panic(channels: &RuntimeChannels) {
+    channels.handle.pause().await;
+    channels.handle.resume().await;
+}
+
+/// The next restart or error event.
+async fn next_supervisor_event(channels: &mut RuntimeChannels) -> ProtocolEvent {
+    loop {
+        let event = timeout(Duration::from_secs(10), channels.events.recv())
+            .await
+            .expect("no supervisor event")
+            .expect("events closed");
+        if matches!(event, ProtocolEvent::RuntimeRestarted { .. } | ProtocolEvent::Error { .. }) {
+            return event;
+        }
+    }
+}
+
+fn node_id(seed: u64) -> tom_protocol::NodeId {
+    use rand::SeedableRng;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    tom_connect::SecretKey::generate(&mut rng).public().to_string().parse().unwrap()
+}
+
+#[tokio::test]
+async fn panicked_loop_restarts_with_its_state() {
+    let mut channels = spawn_runtime(3).await;
+    let blocked = node_id(7);
+    channels.handle.set_contact_blocked(blocked, true).await;
+
+    inject_panic(&channels).await;
+    match next_supervisor_event(&mut channels).await {
+        ProtocolEvent::RuntimeRestarted { attempt, reason } => {
+            assert_eq!((attempt, reason.as_str()), (1, "injected panic"));
+        }
+        other => panic!("expected a restart, got {other:?}"),
+    }
+
+    // The restarted loop answers, over the state from before the panic
+    let contacts = channels.handle.contacts().await;
+    assert!(contacts.iter().any(|c| c.card.node_id == blocked && c.blocked));
+
+    assert!(channels.handle.shutdown().await.is_some(), "shutdown after a restart");
+}
+
+#[tokio::test]
+async fn supervisor_gives_up_after_max_restarts() {
+    let mut channels = spawn_runtime(2).await;
+
+    for expected in 1..=2 {
+        inject_panic(&channels).await;
+        match next_supervisor_event(&mut channels).await {
+            ProtocolEvent::RuntimeRestarted { attempt, .. } => assert_eq!(attempt, expected),
+            other => panic!("expected restart {expected}, got {other:?}"),
+        }
+    }
+
+    inject_panic(&channels).await;
+    match next_supervisor_event(&mut channels).await {
+        ProtocolEvent::Error { description } => {
+            assert!(description.contains("repeated panics"), "{description}");
+        }
+        other => panic!("expected the supervisor to give up, got {other:?}"),
+    }
+
+    // The runtime is gone: nothing answers anymore
+    let stopped = timeout(Duration::from_secs(10), channels.handle.shutdown()).await;
+    assert!(stopped.expect("shutdown hung").is_none());
+}
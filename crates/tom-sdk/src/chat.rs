@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use tom_protocol::payload::CONTENT_TYPE_TEXT;
+use tom_protocol::{DeliveredMessage, NodeId, RuntimeHandle, TextPayload};
+
+use crate::client::Router;
+use crate::stream::MessageStream;
+use crate::SdkError;
+
+/// A 1:1 message we received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub from: NodeId,
+    pub message_id: String,
+    /// The text of a [`TextPayload`], or of untyped UTF-8 bytes (what
+    /// older clients send). None for anything else.
+    pub text: Option<String>,
+    /// Raw bytes for untyped payloads, or the unwrapped body of a typed one.
+    pub payload: Vec<u8>,
+    /// Content type of a typed payload. None = legacy untyped bytes.
+    pub content_type: Option<String>,
+    pub timestamp: u64,
+}
+
+impl From<DeliveredMessage> for ChatMessage {
+    fn from(message: DeliveredMessage) -> Self {
+        let text = match message.content_type.as_deref() {
+            Some(CONTENT_TYPE_TEXT) => message.decode::<TextPayload>().ok().map(|p| p.text),
+            Some(_) => None,
+            None => String::from_utf8(message.payload.clone()).ok(),
+        };
+        Self {
+            from: message.from,
+            message_id: message.envelope_id,
+            text,
+            payload: message.payload,
+            content_type: message.content_type,
+            timestamp: message.timestamp,
+        }
+    }
+}
+
+/// Entry point to 1:1 conversations (`TomClient::chats`).
+pub struct Chats {
+    handle: RuntimeHandle,
+    router: Arc<Router>,
+}
+
+impl Chats {
+    pub(crate) fn new(handle: RuntimeHandle, router: Arc<Router>) -> Self {
+        Self { handle, router }
+    }
+
+    /// The conversation with `peer`. Opening one costs nothing until it
+    /// is used.
+    pub fn open(&self, peer: NodeId) -> Chat {
+        Chat {
+            peer,
+            handle: self.handle.clone(),
+            router: Arc::clone(&self.router),
+        }
+    }
+
+    /// Every 1:1 message received, whoever it's from; the way to notice
+    /// new conversations.
+    pub fn incoming(&self) -> MessageStream<ChatMessage> {
+        self.router.chats.subscribe(None)
+    }
+}
+
+/// The conversation with one peer.
+#[derive(Clone)]
+pub struct Chat {
+    peer: NodeId,
+    handle: RuntimeHandle,
+    router: Arc<Router>,
+}
+
+impl Chat {
+    pub fn peer(&self) -> NodeId {
+        self.peer
+    }
+
+    /// Send a text message, as a [`TextPayload`]; returns its envelope id.
+    pub async fn send_text(&self, text: impl Into<String>) -> Result<String, SdkError> {
+        let payload = TextPayload { text: text.into() };
+        self.handle.send_typed(self.peer, &payload).await.map_err(Into::into)
+    }
+
+    /// Send raw bytes; the runtime encrypts and routes them like text.
+    /// Returns the envelope id, which delivery status updates refer to.
+    pub async fn send(&self, payload: Vec<u8>) -> Result<String, SdkError> {
+        self.handle.send_message(self.peer, payload).await.map_err(Into::into)
+    }
+
+    /// Tell the peer whether we're typing.
+    pub async fn typing(&self, typing: bool) {
+        self.handle.send_typing(self.peer, typing).await;
+    }
+
+    /// Messages from the peer, from now on.
+    pub fn messages(&self) -> MessageStream<ChatMessage> {
+        self.router.chats.subscribe(Some(self.peer))
+    }
+}
@@ -0,0 +1,267 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+use tom_protocol::{
+    DeliveredMessage, GroupId, GroupInvite, GroupMessage, NodeId, ProtocolEvent, ProtocolRuntime,
+    RuntimeConfig, RuntimeHandle,
+};
+use tom_transport::{EndpointAddr, Passphrase, TomNode, TomNodeConfig};
+
+use crate::chat::{ChatMessage, Chats};
+use crate::group::Groups;
+use crate::stream::Fanout;
+use crate::SdkError;
+
+/// Who the client runs as: a username and a node key, either throwaway or
+/// kept in an identity file.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    username: String,
+    path: Option<PathBuf>,
+    passphrase: Option<Passphrase>,
+}
+
+impl Identity {
+    /// A fresh key for this session only.
+    pub fn ephemeral(username: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            path: None,
+            passphrase: None,
+        }
+    }
+
+    /// The key stored at `path`, created there on first use.
+    pub fn persistent(username: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            username: username.into(),
+            path: Some(path.into()),
+            passphrase: None,
+        }
+    }
+
+    /// Encrypt the identity file with `passphrase` (see `KeyStore`).
+    pub fn passphrase(mut self, passphrase: impl Into<Passphrase>) -> Self {
+        self.passphrase = Some(passphrase.into());
+        self
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+}
+
+/// Routes what the runtime delivers to the matching conversation streams.
+/// `None` keys subscribe to every conversation.
+pub(crate) struct Router {
+    pub(crate) chats: Fanout<Option<NodeId>, ChatMessage>,
+    pub(crate) groups: Fanout<Option<GroupId>, GroupMessage>,
+    pub(crate) invites: Fanout<(), GroupInvite>,
+}
+
+impl Router {
+    fn deliver(&self, message: DeliveredMessage) {
+        let message = ChatMessage::from(message);
+        self.chats.publish(&Some(message.from), &message);
+        self.chats.publish(&None, &message);
+    }
+
+    fn handle_event(&self, event: ProtocolEvent) {
+        match event {
+            ProtocolEvent::GroupMessageReceived { message, .. } => {
+                self.groups.publish(&Some(message.group_id.clone()), &message);
+                self.groups.publish(&None, &message);
+            }
+            ProtocolEvent::GroupInviteReceived { invite } => self.invites.publish(&(), &invite),
+            _ => {}
+        }
+    }
+
+    fn close(&self) {
+        self.chats.close();
+        self.groups.close();
+        self.invites.close();
+    }
+}
+
+/// A running ToM node with a chat-and-groups API.
+///
+/// Cheap to clone; every clone drives the same node.
+#[derive(Clone)]
+pub struct TomClient {
+    handle: RuntimeHandle,
+    addr: EndpointAddr,
+    router: Arc<Router>,
+}
+
+impl TomClient {
+    /// Start a node for `identity` with the default transport and runtime
+    /// settings.
+    pub async fn connect(identity: Identity) -> Result<Self, SdkError> {
+        Self::connect_with(identity, TomNodeConfig::new(), RuntimeConfig::default()).await
+    }
+
+    /// Start a node with custom settings. `identity` overrides the key
+    /// location and username they carry.
+    pub async fn connect_with(
+        identity: Identity,
+        mut node_config: TomNodeConfig,
+        mut config: RuntimeConfig,
+    ) -> Result<Self, SdkError> {
+        if let Some(path) = identity.path {
+            node_config = node_config.identity_path(path);
+        }
+        if let Some(passphrase) = identity.passphrase {
+            node_config = node_config.identity_passphrase(passphrase);
+        }
+        config.username = identity.username;
+
+        let node = TomNode::bind(node_config).await?;
+        let addr = node.addr();
+        let channels = ProtocolRuntime::spawn(node, config);
+
+        let router = Arc::new(Router {
+            chats: Fanout::new(),
+            groups: Fanout::new(),
+            invites: Fanout::new(),
+        });
+        tokio::spawn(dispatch(
+            Arc::clone(&router),
+            channels.messages,
+            channels.status_changes,
+            channels.events,
+        ));
+
+        Ok(Self {
+            handle: channels.handle,
+            addr,
+            router,
+        })
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.handle.local_id()
+    }
+
+    /// Our address as bound at startup, to hand to peers out of band.
+    pub fn addr(&self) -> EndpointAddr {
+        self.addr.clone()
+    }
+
+    /// Connect directly to a peer whose address we got out of band.
+    pub async fn add_peer(&self, addr: EndpointAddr) {
+        self.handle.add_peer_addr(addr).await;
+    }
+
+    /// 1:1 conversations.
+    pub fn chats(&self) -> Chats {
+        Chats::new(self.handle.clone(), Arc::clone(&self.router))
+    }
+
+    /// Group conversations.
+    pub fn groups(&self) -> Groups {
+        Groups::new(self.handle.clone(), Arc::clone(&self.router))
+    }
+
+    /// The underlying runtime, for what this API doesn't cover.
+    pub fn runtime(&self) -> &RuntimeHandle {
+        &self.handle
+    }
+
+    /// Stop the node gracefully; every stream then ends.
+    pub async fn shutdown(self) {
+        self.handle.shutdown().await;
+        self.router.close();
+    }
+}
+
+/// Drain the runtime's channels into the router until the runtime stops.
+async fn dispatch(
+    router: Arc<Router>,
+    mut messages: mpsc::Receiver<DeliveredMessage>,
+    mut status_changes: mpsc::Receiver<tom_protocol::StatusChange>,
+    mut events: mpsc::Receiver<ProtocolEvent>,
+) {
+    loop {
+        tokio::select! {
+            Some(message) = messages.recv() => router.deliver(message),
+            Some(event) = events.recv() => router.handle_event(event),
+            Some(_) = status_changes.recv() => {}
+            else => break,
+        }
+    }
+    router.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+    use tom_protocol::AntiSpamConfig;
+
+    use super::*;
+
+    async fn local_client(username: &str) -> TomClient {
+        let config = RuntimeConfig {
+            enable_dht: false,
+            antispam_config: AntiSpamConfig {
+                min_rate: 1000.0,
+                ..AntiSpamConfig::default()
+            },
+            ..RuntimeConfig::default()
+        };
+        TomClient::connect_with(
+            Identity::ephemeral(username),
+            TomNodeConfig::new().n0_discovery(false),
+            config,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn chats_and_groups_round_trip() {
+        let alice = local_client("alice").await;
+        let bob = local_client("bob").await;
+        alice.add_peer(bob.addr()).await;
+        bob.add_peer(alice.addr()).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        // 1:1: Bob's stream for Alice and his catch-all both get the text
+        let mut from_alice = bob.chats().open(alice.id()).messages();
+        let mut all_chats = bob.chats().incoming();
+        alice.chats().open(bob.id()).send_text("salut").await.unwrap();
+        let message = timeout(Duration::from_secs(10), from_alice.next())
+            .await
+            .expect("Bob never got the chat")
+            .unwrap();
+        assert_eq!((message.from, message.text.as_deref()), (alice.id(), Some("salut")));
+        assert_eq!(all_chats.next().await.unwrap().message_id, message.message_id);
+
+        // Groups: Bob accepts the invite, then reads Alice's message
+        let mut invites = bob.groups().invites();
+        let group = alice.groups().create("friends", vec![bob.id()]).await.unwrap();
+        assert_eq!(group.name(), "friends");
+        let invite = timeout(Duration::from_secs(10), invites.next())
+            .await
+            .expect("Bob never got the invite")
+            .unwrap();
+        assert_eq!(invite.group_id, *group.id());
+
+        let mut group_messages = bob.groups().get(group.id().clone()).messages();
+        bob.groups().accept(invite.group_id).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        group.send_text("hi all").await.unwrap();
+        let message = timeout(Duration::from_secs(10), group_messages.next())
+            .await
+            .expect("Bob never got the group message")
+            .unwrap();
+        assert_eq!((message.sender_id, message.text.as_str()), (alice.id(), "hi all"));
+
+        alice.shutdown().await;
+        bob.shutdown().await;
+        assert!(from_alice.next().await.is_none());
+    }
+}
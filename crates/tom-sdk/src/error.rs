@@ -0,0 +1,18 @@
+use thiserror::Error;
+
+/// Errors returned by the SDK.
+#[derive(Debug, Error)]
+pub enum SdkError {
+    #[error("transport error: {0}")]
+    Transport(#[from] tom_transport::TomTransportError),
+
+    #[error("protocol error: {0}")]
+    Protocol(#[from] tom_protocol::TomProtocolError),
+
+    /// The runtime stopped (shut down or crashed).
+    #[error("client closed")]
+    Closed,
+
+    #[error("timed out waiting for {0}")]
+    Timeout(&'static str),
+}
@@ -0,0 +1,165 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tom_protocol::{
+    EventCategory, EventFilter, GroupId, GroupInfo, GroupInvite, GroupMessage, NodeId,
+    ProtocolEvent, RuntimeHandle,
+};
+
+use crate::client::Router;
+use crate::stream::MessageStream;
+use crate::SdkError;
+
+/// How long `Groups::create` waits for the group to exist.
+const CREATE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Entry point to group conversations (`TomClient::groups`).
+pub struct Groups {
+    handle: RuntimeHandle,
+    router: Arc<Router>,
+}
+
+impl Groups {
+    pub(crate) fn new(handle: RuntimeHandle, router: Arc<Router>) -> Self {
+        Self { handle, router }
+    }
+
+    /// Create a group hosted on this node and invite `members` to it.
+    pub async fn create(
+        &self,
+        name: impl Into<String>,
+        members: Vec<NodeId>,
+    ) -> Result<Group, SdkError> {
+        let name = name.into();
+        let local_id = self.handle.local_id();
+        let mut events = self
+            .handle
+            .subscribe_events(EventFilter::all().category(EventCategory::Group));
+        self.handle.create_group(name.clone(), local_id, members).await?;
+
+        let created = tokio::time::timeout(CREATE_TIMEOUT, async {
+            while let Some(event) = events.recv().await {
+                if let ProtocolEvent::GroupCreated { group } = event {
+                    if group.name == name && group.created_by == local_id {
+                        return Ok(group);
+                    }
+                }
+            }
+            Err(SdkError::Closed)
+        })
+        .await
+        .map_err(|_| SdkError::Timeout("group creation"))??;
+        Ok(self.group(created))
+    }
+
+    /// The groups we belong to.
+    pub async fn list(&self) -> Vec<Group> {
+        let groups = self.handle.groups().await;
+        groups.into_iter().map(|info| self.group(info)).collect()
+    }
+
+    /// A group we belong to, by id; None if we don't.
+    pub async fn find(&self, group_id: &GroupId) -> Option<Group> {
+        self.list().await.into_iter().find(|group| group.id() == group_id)
+    }
+
+    /// A group by id, whether or not we're in it yet (e.g. to subscribe
+    /// to its messages before accepting an invite).
+    pub fn get(&self, group_id: GroupId) -> Group {
+        Group {
+            id: group_id,
+            info: None,
+            handle: self.handle.clone(),
+            router: Arc::clone(&self.router),
+        }
+    }
+
+    /// Invitations received from now on.
+    pub fn invites(&self) -> MessageStream<GroupInvite> {
+        self.router.invites.subscribe(())
+    }
+
+    /// Invitations waiting for an answer.
+    pub async fn pending_invites(&self) -> Vec<GroupInvite> {
+        self.handle.pending_invites().await
+    }
+
+    pub async fn accept(&self, group_id: GroupId) -> Result<(), SdkError> {
+        self.handle.accept_invite(group_id).await.map_err(Into::into)
+    }
+
+    pub async fn decline(&self, group_id: GroupId) -> Result<(), SdkError> {
+        self.handle.decline_invite(group_id).await.map_err(Into::into)
+    }
+
+    /// Every group message received, whatever the group.
+    pub fn incoming(&self) -> MessageStream<GroupMessage> {
+        self.router.groups.subscribe(None)
+    }
+
+    fn group(&self, info: GroupInfo) -> Group {
+        Group {
+            id: info.group_id.clone(),
+            info: Some(info),
+            handle: self.handle.clone(),
+            router: Arc::clone(&self.router),
+        }
+    }
+}
+
+/// One group conversation.
+#[derive(Clone)]
+pub struct Group {
+    id: GroupId,
+    /// Snapshot taken when this handle was made (None from `Groups::get`).
+    info: Option<GroupInfo>,
+    handle: RuntimeHandle,
+    router: Arc<Router>,
+}
+
+impl Group {
+    pub fn id(&self) -> &GroupId {
+        &self.id
+    }
+
+    /// The group's name when this handle was made ("" from `Groups::get`).
+    pub fn name(&self) -> &str {
+        self.info.as_ref().map_or("", |info| info.name.as_str())
+    }
+
+    /// Membership and settings when this handle was made.
+    pub fn info(&self) -> Option<&GroupInfo> {
+        self.info.as_ref()
+    }
+
+    pub async fn send_text(&self, text: impl Into<String>) -> Result<(), SdkError> {
+        self.handle
+            .send_group_message(self.id.clone(), text.into())
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Reply to one of the group's messages.
+    pub async fn reply(&self, message_id: impl Into<String>, text: impl Into<String>) -> Result<(), SdkError> {
+        self.handle
+            .send_group_reply(self.id.clone(), message_id.into(), text.into())
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Messages from the other members, from now on.
+    pub fn messages(&self) -> MessageStream<GroupMessage> {
+        self.router.groups.subscribe(Some(self.id.clone()))
+    }
+
+    pub async fn invite(&self, member: NodeId) -> Result<(), SdkError> {
+        self.handle
+            .invite_member(self.id.clone(), member)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn leave(&self) -> Result<(), SdkError> {
+        self.handle.leave_group(self.id.clone()).await.map_err(Into::into)
+    }
+}
@@ -0,0 +1,43 @@
+//! tom-sdk — a batteries-included client for the ToM protocol.
+//!
+//! Wraps [`ProtocolRuntime`](tom_protocol::ProtocolRuntime) so apps deal in
+//! chats and groups instead of `RuntimeCommand`s and `ProtocolEvent`s:
+//!
+//! ```no_run
+//! # async fn demo(peer: tom_sdk::NodeId) -> Result<(), tom_sdk::SdkError> {
+//! use tom_sdk::{Identity, TomClient};
+//!
+//! let client = TomClient::connect(Identity::ephemeral("alice")).await?;
+//!
+//! let chat = client.chats().open(peer);
+//! chat.send_text("hello").await?;
+//! let mut replies = chat.messages();
+//! while let Some(message) = replies.next().await {
+//!     println!("{}: {}", message.from, message.text.unwrap_or_default());
+//! }
+//!
+//! let group = client.groups().create("friends", vec![peer]).await?;
+//! group.send_text("hi all").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each conversation gets its own [`MessageStream`]; a stream that falls
+//! more than `STREAM_BUFFER` messages behind loses the overflow. The
+//! underlying [`RuntimeHandle`](tom_protocol::RuntimeHandle) stays
+//! reachable through [`TomClient::runtime`] for anything not covered here.
+
+mod chat;
+mod client;
+mod error;
+mod group;
+mod stream;
+
+pub use chat::{Chat, ChatMessage, Chats};
+pub use client::{Identity, TomClient};
+pub use error::SdkError;
+pub use group::{Group, Groups};
+pub use stream::{MessageStream, STREAM_BUFFER};
+
+pub use tom_protocol::{GroupId, GroupInfo, GroupInvite, GroupMessage, NodeId};
+pub use tom_transport::EndpointAddr;
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+use tokio::sync::mpsc;
+
+/// Most items a [`MessageStream`] holds unread before dropping new ones.
+pub const STREAM_BUFFER: usize = 256;
+
+/// Messages of one conversation (or of all of them), as they arrive.
+///
+/// Use [`next`](Self::next), or the [`Stream`](futures_core::Stream) impl
+/// with any stream combinators. Ends when the client shuts down; dropping
+/// it unsubscribes.
+#[derive(Debug)]
+pub struct MessageStream<T> {
+    rx: mpsc::Receiver<T>,
+}
+
+impl<T> MessageStream<T> {
+    /// The next item, or None once the client is gone.
+    pub async fn next(&mut self) -> Option<T> {
+        self.rx.recv().await
+    }
+
+    /// The next item if one is already waiting.
+    pub fn try_next(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl<T> futures_core::Stream for MessageStream<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().rx.poll_recv(cx)
+    }
+}
+
+/// Subscribers keyed by conversation, fed by the client's dispatcher.
+pub(crate) struct Fanout<K, T> {
+    subscribers: Mutex<HashMap<K, Vec<mpsc::Sender<T>>>>,
+}
+
+impl<K: Eq + Hash, T: Clone> Fanout<K, T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            subscribers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn subscribe(&self, key: K) -> MessageStream<T> {
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        self.subscribers.lock().unwrap().entry(key).or_default().push(tx);
+        MessageStream { rx }
+    }
+
+    /// Hand `item` to every subscriber of `key`, forgetting dropped ones.
+    pub(crate) fn publish(&self, key: &K, item: &T) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let Some(senders) = subscribers.get_mut(key) else {
+            return;
+        };
+        senders.retain(|tx| match tx.try_send(item.clone()) {
+            Ok(()) | Err(mpsc::error::TrySendError::Full(_)) => true,
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        });
+        if senders.is_empty() {
+            subscribers.remove(key);
+        }
+    }
+
+    /// End every stream.
+    pub(crate) fn close(&self) {
+        self.subscribers.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn fanout_delivers_per_key_and_forgets_dropped_streams() {
+        let fanout = Fanout::<u8, u32>::new();
+        let mut one = fanout.subscribe(1);
+        let mut also_one = fanout.subscribe(1);
+        let two = fanout.subscribe(2);
+
+        fanout.publish(&1, &7);
+        assert_eq!(one.next().await, Some(7));
+        assert_eq!(also_one.try_next(), Some(7));
+
+        drop(two);
+        fanout.publish(&2, &8);
+        assert!(fanout.subscribers.lock().unwrap().get(&2).is_none());
+
+        fanout.close();
+        assert_eq!(one.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn full_streams_drop_the_overflow() {
+        let fanout = Fanout::<(), usize>::new();
+        let mut stream = fanout.subscribe(());
+        for i in 0..STREAM_BUFFER + 10 {
+            fanout.publish(&(), &i);
+        }
+        let mut received = 0;
+        while stream.try_next().is_some() {
+            received += 1;
+        }
+        assert_eq!(received, STREAM_BUFFER);
+    }
+}
@@ -49,6 +49,7 @@ async fn run_single_burst(
     let mut pending: HashMap<String, Instant> = HashMap::new();
     let mut rtts: Vec<f64> = Vec::new();
     let mut send_failures = 0u32;
+    let before = node.peer_stats(config.target).unwrap_or_default();
     let burst_start = Instant::now();
 
     // Phase 1: Send all envelopes
@@ -71,7 +72,10 @@ async fn run_single_burst(
     }
 
     let send_elapsed = burst_start.elapsed();
-    let messages_sent = config.count - send_failures;
+    // Counted by the transport, not re-derived from send results
+    let after = node.peer_stats(config.target).unwrap_or_default();
+    let messages_sent = (after.messages_sent - before.messages_sent) as u32;
+    let bytes_sent = after.bytes_sent - before.bytes_sent;
     eprintln!(
         "    sent {messages_sent}/{} in {:.1}ms",
         config.count,
@@ -93,7 +97,7 @@ async fn run_single_burst(
                 last_recv = Instant::now();
                 if envelope.msg_type == "stress-pong" {
                     if let Some(echo_id) =
-                        envelope.payload.get("echo_id").and_then(|v| v.as_str())
+                        envelope.payload.as_json().and_then(|p| p.get("echo_id")).and_then(|v| v.as_str())
                     {
                         if let Some(send_time) = pending.remove(echo_id) {
                             rtts.push(send_time.elapsed().as_secs_f64() * 1000.0);
@@ -129,6 +133,7 @@ async fn run_single_burst(
         event: "burst_result",
         round,
         messages_sent,
+        bytes_sent,
         messages_acked,
         lost,
         payload_size: config.payload_size,
@@ -713,7 +713,7 @@ async fn phase_group(
         let msg_deadline = Instant::now() + Duration::from_secs(10);
         while Instant::now() < msg_deadline {
             match recv_timeout(events, Duration::from_secs(2)).await {
-                Ok(ProtocolEvent::GroupMessageReceived { message }) => {
+                Ok(ProtocolEvent::GroupMessageReceived { message, .. }) => {
                     if message.text.starts_with("GROUP-ECHO:") {
                         let rtt = send_time.elapsed().as_secs_f64() * 1000.0;
                         stats.record_rtt(rtt);
@@ -810,7 +810,7 @@ async fn phase_failover(
         let msg_deadline = Instant::now() + Duration::from_secs(10);
         while Instant::now() < msg_deadline {
             match recv_timeout(events, Duration::from_secs(2)).await {
-                Ok(ProtocolEvent::GroupMessageReceived { message }) => {
+                Ok(ProtocolEvent::GroupMessageReceived { message, .. }) => {
                     if message.text.starts_with("GROUP-ECHO:") {
                         let rtt = send_time.elapsed().as_secs_f64() * 1000.0;
                         stats.record_rtt(rtt);
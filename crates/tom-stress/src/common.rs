@@ -25,6 +25,7 @@ pub fn spawn_path_monitor(node: &TomNode, start: Instant) {
                 PathKind::Direct => "DIRECT",
                 PathKind::Relay => "RELAY",
                 PathKind::Unknown => "UNKNOWN",
+                PathKind::Unreachable => "UNREACHABLE",
             };
             emit(&EventPathChange {
                 event: "path_change",
@@ -107,6 +107,8 @@ pub struct EventBurstResult {
     pub event: &'static str,
     pub round: u32,
     pub messages_sent: u32,
+    /// Bytes on the wire (envelopes included), from transport stats.
+    pub bytes_sent: u64,
     pub messages_acked: u32,
     pub lost: u32,
     pub payload_size: usize,
@@ -76,7 +76,7 @@ pub async fn run(
                 last_recv = Instant::now();
                 if envelope.msg_type == "stress-pong" {
                     if let Some(echo_id) =
-                        envelope.payload.get("echo_id").and_then(|v| v.as_str())
+                        envelope.payload.as_json().and_then(|p| p.get("echo_id")).and_then(|v| v.as_str())
                     {
                         if let Some(send_time) = pending.remove(echo_id) {
                             rtts.push(send_time.elapsed().as_secs_f64() * 1000.0);
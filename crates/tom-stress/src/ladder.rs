@@ -144,7 +144,7 @@ async fn wait_for_pong(
             Ok(Ok((_from, envelope))) => {
                 if envelope.msg_type == "stress-pong" {
                     if let Some(echo_id) =
-                        envelope.payload.get("echo_id").and_then(|v| v.as_str())
+                        envelope.payload.as_json().and_then(|p| p.get("echo_id")).and_then(|v| v.as_str())
                     {
                         if echo_id == expected_id {
                             return Ok(pong_start.elapsed());
@@ -172,7 +172,7 @@ async fn wait_for_pong(
         match result {
             Ok(Ok((_from, envelope))) => {
                 if envelope.msg_type == "stress-pong" {
-                    if let Some(echo_id) = envelope.payload.get("echo_id").and_then(|v| v.as_str())
+                    if let Some(echo_id) = envelope.payload.as_json().and_then(|p| p.get("echo_id")).and_then(|v| v.as_str())
                     {
                         if echo_id == expected_id {
                             return Ok(start.elapsed());
@@ -106,7 +106,7 @@ pub async fn run(config: ResponderConfig) -> anyhow::Result<()> {
                     ProtocolEvent::GroupJoined { group_id, group_name } => {
                         eprintln!("[{:>7.1}s] Joined group: {} ({})", start.elapsed().as_secs_f64(), group_name, group_id);
                     }
-                    ProtocolEvent::GroupMessageReceived { message } => {
+                    ProtocolEvent::GroupMessageReceived { message, .. } => {
                         group_msg_count += 1;
                         let text = if message.encrypted {
                             format!("[encrypted] {}", message.text)
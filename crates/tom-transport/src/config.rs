@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Fallback relay list (public relays) used when discovery fails
 /// and no static relay is configured.
@@ -13,6 +14,16 @@ pub const DEFAULT_RELAY_URLS: &[&str] = &[
 /// TXT records should contain one or more relay URLs.
 pub const DEFAULT_DNS_FALLBACK_DOMAIN: &str = "_relay._tcp.tom-protocol.org";
 
+/// Default number of incoming messages buffered per peer.
+pub const DEFAULT_PEER_RECV_BUFFER: usize = 32;
+
+/// Default bound on one send, connecting included.
+pub const DEFAULT_SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default silence after which a connection is declared dead (QUIC's
+/// usual 30 s).
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub(crate) fn fallback_relay_urls() -> Vec<tom_connect::RelayUrl> {
     DEFAULT_RELAY_URLS
         .iter()
@@ -37,8 +48,19 @@ pub struct TomNodeConfig {
     pub(crate) alpn: Vec<u8>,
     /// Maximum incoming message size in bytes.
     pub(crate) max_message_size: usize,
-    /// Channel buffer size for incoming messages.
+    /// Most incoming messages buffered across all peers.
     pub(crate) recv_buffer: usize,
+    /// Most incoming messages buffered per peer; `recv()` drains peers
+    /// round-robin so one chatty peer can't starve the others.
+    pub(crate) peer_recv_buffer: usize,
+    /// Longest a send may take, connecting included, before it fails.
+    pub(crate) send_timeout: Duration,
+    /// Interval between keepalives on an otherwise idle connection;
+    /// `None` leaves the endpoint's own setting.
+    pub(crate) keep_alive_interval: Option<Duration>,
+    /// Silence after which a connection is closed and its peer reported
+    /// unreachable (`PathKind::Unreachable`).
+    pub(crate) idle_timeout: Duration,
     /// Custom relay URL. If set, only this relay is used instead of the n0 defaults.
     pub(crate) relay_url: Option<tom_connect::RelayUrl>,
     /// Custom relay URL list (priority order).
@@ -69,6 +91,10 @@ pub struct TomNodeConfig {
     /// If set, the node loads its identity from this file (creating it on first run).
     /// If unset, a fresh ephemeral identity is generated on each bind.
     pub(crate) identity_path: Option<PathBuf>,
+    /// Passphrase encrypting the identity file (see [`KeyStore`](crate::KeyStore)).
+    ///
+    /// If unset, the identity file holds the raw key.
+    pub(crate) identity_passphrase: Option<crate::Passphrase>,
 }
 
 impl Default for TomNodeConfig {
@@ -115,6 +141,10 @@ impl TomNodeConfig {
             .ok()
             .map(PathBuf::from);
 
+        let identity_passphrase = std::env::var("TOM_IDENTITY_PASSPHRASE")
+            .ok()
+            .map(crate::Passphrase::new);
+
         let relay_discovery_url = std::env::var("TOM_RELAY_DISCOVERY_URL").ok();
         let relay_dns_fallback_domain = std::env::var("TOM_RELAY_DNS_FALLBACK_DOMAIN").ok();
 
@@ -122,12 +152,17 @@ impl TomNodeConfig {
             alpn: crate::TOM_ALPN.to_vec(),
             max_message_size: 1024 * 1024, // 1 MB
             recv_buffer: 256,
+            peer_recv_buffer: DEFAULT_PEER_RECV_BUFFER,
+            send_timeout: DEFAULT_SEND_TIMEOUT,
+            keep_alive_interval: None,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
             relay_url,
             relay_urls,
             relay_discovery_url,
             relay_dns_fallback_domain,
             n0_discovery: true,
             identity_path,
+            identity_passphrase,
         }
     }
 
@@ -143,12 +178,44 @@ impl TomNodeConfig {
         self
     }
 
-    /// Set the channel buffer size for incoming messages (default: 256).
+    /// Set how many incoming messages are buffered across all peers
+    /// (default: 256).
     pub fn recv_buffer(mut self, capacity: usize) -> Self {
         self.recv_buffer = capacity;
         self
     }
 
+    /// Set how many incoming messages are buffered per peer (default: 32).
+    /// A peer with a full buffer waits until `recv()` gets back to it.
+    pub fn peer_recv_buffer(mut self, capacity: usize) -> Self {
+        self.peer_recv_buffer = capacity;
+        self
+    }
+
+    /// Set how long a send may take, connecting included, before failing
+    /// with `Timeout` (default: 10 s). Per-call override: `send_raw_with`.
+    pub fn send_timeout(mut self, timeout: Duration) -> Self {
+        self.send_timeout = timeout;
+        self
+    }
+
+    /// Send QUIC keepalives at this interval on an idle connection
+    /// (default: off, the endpoint's own setting applies). Must be shorter
+    /// than the idle timeout.
+    pub fn keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Set how long a connection may stay silent before it is closed and
+    /// its peer reported unreachable through `path_events` (default: 30 s).
+    ///
+    /// The effective timeout is the lower of both sides' values.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
     /// Use a custom relay server instead of the default n0 relays.
     ///
     /// ```rust
@@ -236,6 +303,25 @@ impl TomNodeConfig {
         self.identity_path = Some(path);
         self
     }
+
+    /// Encrypt the identity file with a passphrase.
+    ///
+    /// The file at [`identity_path`](Self::identity_path) is then a
+    /// [`KeyStore`](crate::KeyStore) file; an existing raw key file is
+    /// encrypted in place on the next bind. If unset, checks the
+    /// `TOM_IDENTITY_PASSPHRASE` environment variable.
+    ///
+    /// ```rust
+    /// use tom_transport::TomNodeConfig;
+    ///
+    /// let config = TomNodeConfig::new()
+    ///     .identity_path("/home/user/.tom/identity.json".into())
+    ///     .identity_passphrase("correct horse battery staple");
+    /// ```
+    pub fn identity_passphrase(mut self, passphrase: impl Into<crate::Passphrase>) -> Self {
+        self.identity_passphrase = Some(passphrase.into());
+        self
+    }
 }
 
 #[cfg(test)]
@@ -293,6 +379,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn send_timeout_defaults_and_overrides() {
+        assert_eq!(TomNodeConfig::new().send_timeout, super::DEFAULT_SEND_TIMEOUT);
+        let cfg = TomNodeConfig::new().send_timeout(std::time::Duration::from_secs(2));
+        assert_eq!(cfg.send_timeout, std::time::Duration::from_secs(2));
+    }
+
+    #[test]
+    fn keepalive_and_idle_timeout_default_and_override() {
+        let cfg = TomNodeConfig::new();
+        assert_eq!(cfg.keep_alive_interval, None);
+        assert_eq!(cfg.idle_timeout, super::DEFAULT_IDLE_TIMEOUT);
+
+        let cfg = TomNodeConfig::new()
+            .keep_alive_interval(std::time::Duration::from_millis(500))
+            .idle_timeout(std::time::Duration::from_secs(2));
+        assert_eq!(cfg.keep_alive_interval, Some(std::time::Duration::from_millis(500)));
+        assert_eq!(cfg.idle_timeout, std::time::Duration::from_secs(2));
+    }
+
     #[test]
     fn fallback_relay_urls_contains_default_public_relays() {
         let parsed = fallback_relay_urls();
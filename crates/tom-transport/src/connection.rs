@@ -48,11 +48,11 @@ impl ConnectionPool {
         self.addresses.lock().await.insert(id, addr);
     }
 
-    /// Get an existing connection or create a new one.
+    /// Get an existing connection or create a new one (then `true`).
     pub async fn get_or_connect(
         &self,
         target: NodeId,
-    ) -> Result<Connection, TomTransportError> {
+    ) -> Result<(Connection, bool), TomTransportError> {
         let mut conns = self.connections.lock().await;
 
         // Check if we have a cached connection that's still alive
@@ -60,7 +60,7 @@ impl ConnectionPool {
             // connection.close_reason() returns Some if closed
             if conn.close_reason().is_none() {
                 tracing::debug!("Reusing existing connection for {}", target);
-                return Ok(conn.clone());
+                return Ok((conn.clone(), false));
             }
             // Connection is dead, remove it
             tracing::debug!("Connection for {} is dead, removing", target);
@@ -69,6 +69,14 @@ impl ConnectionPool {
             tracing::debug!("No cached connection for {}, will create new", target);
         }
 
+        let conn = self.dial(target, &self.alpn).await?;
+        conns.insert(target, conn.clone());
+        Ok((conn, true))
+    }
+
+    /// Connect to `target` on `alpn` (stored address first, else the
+    /// default relays), without caching.
+    pub async fn dial(&self, target: NodeId, alpn: &[u8]) -> Result<Connection, TomTransportError> {
         // Create new connection candidates — use stored address first, or
         // fallback to configured relay list (when n0 discovery is disabled).
         let stored_addr = {
@@ -93,7 +101,7 @@ impl ConnectionPool {
         let mut last_err = None;
         let mut established = None;
         for addr in candidates {
-            match self.endpoint.connect(addr, &self.alpn).await {
+            match self.endpoint.connect(addr, alpn).await {
                 Ok(conn) => {
                     established = Some(conn);
                     break;
@@ -104,19 +112,12 @@ impl ConnectionPool {
             }
         }
 
-        let conn = if let Some(conn) = established {
-            conn
-        } else {
-            return Err(TomTransportError::Connect {
-                node_id: target,
-                source: last_err
-                    .expect("at least one connect attempt should have been made")
-                    .into(),
-            });
-        };
-
-        conns.insert(target, conn.clone());
-        Ok(conn)
+        established.ok_or_else(|| TomTransportError::Connect {
+            node_id: target,
+            source: last_err
+                .expect("at least one connect attempt should have been made")
+                .into(),
+        })
     }
 
     /// Remove a connection from the cache (e.g., after send failure).
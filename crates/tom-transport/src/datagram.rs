@@ -0,0 +1,103 @@
+use crate::connection::ConnectionPool;
+use crate::{NodeId, TomTransportError};
+
+use tom_connect::endpoint::Connection;
+use tom_connect::protocol::AcceptError;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+/// ALPN of unreliable datagram connections (real-time payloads).
+pub const TOM_DATAGRAM_ALPN: &[u8] = b"tom-protocol/datagram/0";
+
+/// Sends QUIC datagrams, each peer on its own cached connection.
+///
+/// Datagrams may be lost, duplicated or reordered and are never retried;
+/// each must fit in one packet (`max_datagram_size`, at least ~1 KiB).
+pub(crate) struct DatagramLinks {
+    pool: Arc<ConnectionPool>,
+    connections: Mutex<HashMap<NodeId, Connection>>,
+}
+
+impl DatagramLinks {
+    pub fn new(pool: Arc<ConnectionPool>) -> Self {
+        Self {
+            pool,
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn send(&self, to: NodeId, data: &[u8]) -> Result<(), TomTransportError> {
+        let connection = {
+            let mut connections = self.connections.lock().await;
+            match connections.get(&to) {
+                Some(conn) if conn.close_reason().is_none() => conn.clone(),
+                _ => {
+                    let conn = self.pool.dial(to, TOM_DATAGRAM_ALPN).await?;
+                    connections.insert(to, conn.clone());
+                    conn
+                }
+            }
+        };
+        let max = connection.max_datagram_size().unwrap_or(0);
+        if data.len() > max {
+            return Err(TomTransportError::MessageTooLarge {
+                size: data.len(),
+                max,
+            });
+        }
+        connection
+            .send_datagram(bytes::Bytes::copy_from_slice(data))
+            .map_err(|e| TomTransportError::Send {
+                node_id: to,
+                source: e.into(),
+            })
+    }
+}
+
+/// Reads datagrams from connections peers opened and hands them to
+/// `TomNode`. Drops them when nobody keeps up: late real-time data is
+/// worthless.
+#[derive(Debug, Clone)]
+pub(crate) struct DatagramHandler {
+    pub incoming_tx: mpsc::Sender<(NodeId, Vec<u8>)>,
+}
+
+impl tom_connect::protocol::ProtocolHandler for DatagramHandler {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        let peer = NodeId::from_endpoint_id(connection.remote_id());
+        while let Ok(data) = connection.read_datagram().await {
+            if self.incoming_tx.try_send((peer, data.to_vec())).is_err() {
+                tracing::trace!(%peer, "datagram dropped: receiver lagging");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{TomNode, TomNodeConfig, TomTransportError};
+
+    #[tokio::test]
+    async fn datagrams_reach_peer() {
+        let alice = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+        let mut bob = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+        alice.add_peer_addr(bob.addr()).await;
+        let mut incoming = bob.take_incoming_datagrams().unwrap();
+        assert!(bob.take_incoming_datagrams().is_none());
+
+        alice.send_datagram(bob.id(), b"frame-1").await.unwrap();
+        alice.send_datagram(bob.id(), b"frame-2").await.unwrap();
+        let (from, data) = incoming.recv().await.unwrap();
+        assert_eq!(from, alice.id());
+        assert!(data.starts_with(b"frame-"));
+
+        // Never fragmented: too large is an error
+        let err = alice.send_datagram(bob.id(), &[0u8; 64 * 1024]).await.unwrap_err();
+        assert!(matches!(err, TomTransportError::MessageTooLarge { .. }));
+
+        alice.shutdown().await.unwrap();
+        bob.shutdown().await.unwrap();
+    }
+}
@@ -1,10 +1,26 @@
 use crate::NodeId;
+use bytes::Bytes;
+use data_encoding::BASE64;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use n0_future::time::SystemTime;
+#[cfg(feature = "native")]
+use std::collections::HashSet;
+#[cfg(feature = "native")]
+use std::sync::Mutex;
 
-/// Message envelope — wire-compatible with TypeScript `MessageEnvelope`.
+/// Most peers remembered as reading MessagePack; past that, new peers get
+/// JSON (always safe).
+#[cfg(feature = "native")]
+const MAX_MSGPACK_PEERS: usize = 65_536;
+
+/// Message envelope.
 ///
-/// See `packages/core/src/types/envelope.ts` for the canonical definition.
+/// On the wire (`to_wire`), a peer gets MessagePack only once it is known
+/// to read it; everyone else gets the JSON shape shared with the
+/// TypeScript `MessageEnvelope` (see `packages/core/src/types/envelope.ts`),
+/// through [`JsonEnvelope`]. Those JSON frames carry `acceptsMsgpack`, which
+/// older decoders ignore as an unknown field, so two new nodes switch to
+/// MessagePack after the first exchange while older peers keep working.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageEnvelope {
     /// Unique message identifier (UUID v4).
@@ -23,8 +39,8 @@ pub struct MessageEnvelope {
     #[serde(rename = "type")]
     pub msg_type: String,
 
-    /// Application payload (arbitrary JSON).
-    pub payload: serde_json::Value,
+    /// Application payload (JSON value or raw bytes).
+    pub payload: Payload,
 
     /// Unix timestamp in milliseconds.
     pub timestamp: u64,
@@ -55,13 +71,78 @@ pub struct MessageEnvelope {
     pub route_type: Option<String>,
 }
 
+/// Envelope payload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Payload {
+    /// Arbitrary JSON.
+    Json(serde_json::Value),
+    /// Raw bytes tagged with a MIME-style content type
+    /// (e.g. "application/octet-stream", "image/png").
+    ///
+    /// Decoded by [`MessageEnvelope::from_wire`], `data` shares the received
+    /// frame's buffer rather than copying out of it.
+    Bytes {
+        #[serde(rename = "contentType")]
+        content_type: String,
+        data: Bytes,
+    },
+}
+
+impl Payload {
+    /// The JSON value, if this is a JSON payload.
+    pub fn as_json(&self) -> Option<&serde_json::Value> {
+        match self {
+            Payload::Json(value) => Some(value),
+            Payload::Bytes { .. } => None,
+        }
+    }
+
+    /// The raw bytes, if this is a bytes payload.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Payload::Json(_) => None,
+            Payload::Bytes { data, .. } => Some(data),
+        }
+    }
+
+    /// Content type of a bytes payload ("application/json" for JSON).
+    pub fn content_type(&self) -> &str {
+        match self {
+            Payload::Json(_) => "application/json",
+            Payload::Bytes { content_type, .. } => content_type,
+        }
+    }
+}
+
+impl From<serde_json::Value> for Payload {
+    fn from(value: serde_json::Value) -> Self {
+        Payload::Json(value)
+    }
+}
+
+/// Envelope encoding or decoding failure.
+#[derive(Debug, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error("msgpack encoding failed: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+
+    #[error("msgpack decoding failed: {0}")]
+    Decode(#[from] rmp_serde::decode::Error),
+
+    #[error("json conversion failed: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("bytes payload is not valid base64: {0}")]
+    Base64(#[from] data_encoding::DecodeError),
+}
+
 impl MessageEnvelope {
     /// Create a new envelope with a random UUID and current timestamp.
     pub fn new(
         from: NodeId,
         to: NodeId,
         msg_type: &str,
-        payload: serde_json::Value,
+        payload: impl Into<Payload>,
     ) -> Self {
         Self {
             id: uuid::Uuid::new_v4().to_string(),
@@ -69,7 +150,7 @@ impl MessageEnvelope {
             to,
             via: Vec::new(),
             msg_type: msg_type.to_string(),
-            payload,
+            payload: payload.into(),
             timestamp: now_ms(),
             signature: String::new(),
             encrypted_payload: None,
@@ -80,27 +161,272 @@ impl MessageEnvelope {
         }
     }
 
+    /// Create with a raw bytes payload.
+    pub fn new_bytes(
+        from: NodeId,
+        to: NodeId,
+        msg_type: &str,
+        content_type: &str,
+        data: impl Into<Bytes>,
+    ) -> Self {
+        let payload = Payload::Bytes {
+            content_type: content_type.to_string(),
+            data: data.into(),
+        };
+        Self::new(from, to, msg_type, payload)
+    }
+
     /// Create with a relay chain.
     pub fn new_via(
         from: NodeId,
         to: NodeId,
         via: Vec<NodeId>,
         msg_type: &str,
-        payload: serde_json::Value,
+        payload: impl Into<Payload>,
     ) -> Self {
         let mut envelope = Self::new(from, to, msg_type, payload);
         envelope.via = via;
         envelope
     }
 
-    /// Serialize to JSON bytes.
-    pub fn to_bytes(&self) -> Result<Vec<u8>, serde_json::Error> {
-        serde_json::to_vec(self)
+    /// Serialize to MessagePack bytes.
+    ///
+    /// Only for peers known to read it: older nodes decode JSON alone (see
+    /// [`to_wire`](Self::to_wire)).
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EnvelopeError> {
+        Ok(rmp_serde::to_vec_named(self)?)
+    }
+
+    /// Encode for a peer: MessagePack if it reads it, otherwise JSON
+    /// flagged `acceptsMsgpack` so the peer can answer in MessagePack.
+    pub fn to_wire(&self, peer_reads_msgpack: bool) -> Result<Vec<u8>, EnvelopeError> {
+        if peer_reads_msgpack {
+            return self.to_bytes();
+        }
+        let mut json = JsonEnvelope::from(self.clone());
+        json.accepts_msgpack = true;
+        Ok(serde_json::to_vec(&json)?)
+    }
+
+    /// Deserialize from MessagePack bytes or the JSON shape (a JSON object
+    /// always starts with `{`, which no MessagePack map does).
+    ///
+    /// A bytes payload is copied out of `data`; a received frame goes
+    /// through [`from_wire`](Self::from_wire) instead.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, EnvelopeError> {
+        if data.first() == Some(&b'{') {
+            return Self::from_json(data);
+        }
+        Ok(rmp_serde::from_slice(data)?)
+    }
+
+    /// Decode a received frame, also telling whether the sender reads
+    /// MessagePack: it sent some, or flagged its JSON.
+    ///
+    /// A MessagePack bytes payload is a slice of `frame` (no copy); from
+    /// JSON it is the decoded base64.
+    pub fn from_wire(frame: &Bytes) -> Result<(Self, bool), EnvelopeError> {
+        if frame.first() == Some(&b'{') {
+            let json: JsonEnvelope = serde_json::from_slice(frame)?;
+            let reads_msgpack = json.accepts_msgpack;
+            return Ok((json.try_into()?, reads_msgpack));
+        }
+        let wire: WireEnvelope<'_> = rmp_serde::from_slice(frame)?;
+        let payload = match wire.payload {
+            WirePayload::Json(value) => Payload::Json(value),
+            WirePayload::Bytes { content_type, data } => Payload::Bytes {
+                content_type,
+                data: frame.slice_ref(data),
+            },
+        };
+        let envelope = Self {
+            id: wire.id,
+            from: wire.from,
+            to: wire.to,
+            via: wire.via,
+            msg_type: wire.msg_type,
+            payload,
+            timestamp: wire.timestamp,
+            signature: wire.signature,
+            encrypted_payload: wire.encrypted_payload,
+            ephemeral_public_key: wire.ephemeral_public_key,
+            nonce: wire.nonce,
+            hop_timestamps: wire.hop_timestamps,
+            route_type: wire.route_type,
+        };
+        Ok((envelope, true))
+    }
+
+    /// Whether `data` may be an encoded envelope (a MessagePack map or a
+    /// JSON object), judged from its first byte without decoding.
+    ///
+    /// Protocol-layer frames (MessagePack arrays) never are, so they skip
+    /// a doomed decode on the receive path.
+    pub fn is_encoded(data: &[u8]) -> bool {
+        matches!(data.first(), Some(0x80..=0x8f | 0xde | 0xdf | b'{'))
+    }
+
+    /// Serialize to the TypeScript-compatible JSON shape.
+    pub fn to_json(&self) -> Result<Vec<u8>, EnvelopeError> {
+        Ok(serde_json::to_vec(&JsonEnvelope::from(self.clone()))?)
     }
 
-    /// Deserialize from JSON bytes.
-    pub fn from_bytes(data: &[u8]) -> Result<Self, serde_json::Error> {
-        serde_json::from_slice(data)
+    /// Deserialize from the TypeScript-compatible JSON shape.
+    pub fn from_json(data: &[u8]) -> Result<Self, EnvelopeError> {
+        serde_json::from_slice::<JsonEnvelope>(data)?.try_into()
+    }
+}
+
+/// MessagePack view of [`MessageEnvelope`] whose bytes payload stays
+/// borrowed from the input. Same layout as `MessageEnvelope`.
+#[derive(Deserialize)]
+struct WireEnvelope<'a> {
+    id: String,
+    from: NodeId,
+    to: NodeId,
+    via: Vec<NodeId>,
+    #[serde(rename = "type")]
+    msg_type: String,
+    #[serde(borrow)]
+    payload: WirePayload<'a>,
+    timestamp: u64,
+    signature: String,
+    #[serde(rename = "encryptedPayload")]
+    encrypted_payload: Option<String>,
+    #[serde(rename = "ephemeralPublicKey")]
+    ephemeral_public_key: Option<String>,
+    nonce: Option<String>,
+    #[serde(rename = "hopTimestamps")]
+    hop_timestamps: Option<Vec<u64>>,
+    #[serde(rename = "routeType")]
+    route_type: Option<String>,
+}
+
+/// [`Payload`] with the bytes left in the input.
+#[derive(Deserialize)]
+enum WirePayload<'a> {
+    Json(serde_json::Value),
+    Bytes {
+        #[serde(rename = "contentType")]
+        content_type: String,
+        data: &'a [u8],
+    },
+}
+
+/// JSON shape of [`MessageEnvelope`], wire-compatible with TypeScript.
+///
+/// A bytes payload travels as a base64 string, flagged by `contentType`;
+/// without it the payload is plain JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonEnvelope {
+    pub id: String,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub via: Vec<NodeId>,
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub payload: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "contentType")]
+    pub content_type: Option<String>,
+    pub timestamp: u64,
+    pub signature: String,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "encryptedPayload")]
+    pub encrypted_payload: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "ephemeralPublicKey")]
+    pub ephemeral_public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "hopTimestamps")]
+    pub hop_timestamps: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "routeType")]
+    pub route_type: Option<String>,
+    /// Sender reads MessagePack envelopes (absent from older nodes).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not", rename = "acceptsMsgpack")]
+    pub accepts_msgpack: bool,
+}
+
+impl From<MessageEnvelope> for JsonEnvelope {
+    fn from(envelope: MessageEnvelope) -> Self {
+        let (payload, content_type) = match envelope.payload {
+            Payload::Json(value) => (value, None),
+            Payload::Bytes { content_type, data } => {
+                (serde_json::Value::String(BASE64.encode(&data)), Some(content_type))
+            }
+        };
+        Self {
+            id: envelope.id,
+            from: envelope.from,
+            to: envelope.to,
+            via: envelope.via,
+            msg_type: envelope.msg_type,
+            payload,
+            content_type,
+            timestamp: envelope.timestamp,
+            signature: envelope.signature,
+            encrypted_payload: envelope.encrypted_payload,
+            ephemeral_public_key: envelope.ephemeral_public_key,
+            nonce: envelope.nonce,
+            hop_timestamps: envelope.hop_timestamps,
+            route_type: envelope.route_type,
+            accepts_msgpack: false,
+        }
+    }
+}
+
+impl TryFrom<JsonEnvelope> for MessageEnvelope {
+    type Error = EnvelopeError;
+
+    fn try_from(json: JsonEnvelope) -> Result<Self, EnvelopeError> {
+        let payload = match json.content_type {
+            None => Payload::Json(json.payload),
+            Some(content_type) => {
+                let encoded: String = serde_json::from_value(json.payload)?;
+                Payload::Bytes {
+                    content_type,
+                    data: BASE64.decode(encoded.as_bytes())?.into(),
+                }
+            }
+        };
+        Ok(Self {
+            id: json.id,
+            from: json.from,
+            to: json.to,
+            via: json.via,
+            msg_type: json.msg_type,
+            payload,
+            timestamp: json.timestamp,
+            signature: json.signature,
+            encrypted_payload: json.encrypted_payload,
+            ephemeral_public_key: json.ephemeral_public_key,
+            nonce: json.nonce,
+            hop_timestamps: json.hop_timestamps,
+            route_type: json.route_type,
+        })
+    }
+}
+
+/// Peers known to read MessagePack envelopes, learned from what they send.
+#[cfg(feature = "native")]
+#[derive(Debug, Default)]
+pub(crate) struct WireFormats {
+    msgpack: Mutex<HashSet<NodeId>>,
+}
+
+#[cfg(feature = "native")]
+impl WireFormats {
+    /// Record what a frame from `peer` told us about it.
+    pub fn note(&self, peer: NodeId, reads_msgpack: bool) {
+        let mut msgpack = self.msgpack.lock().unwrap();
+        if !reads_msgpack {
+            msgpack.remove(&peer);
+        } else if msgpack.len() < MAX_MSGPACK_PEERS {
+            msgpack.insert(peer);
+        }
+    }
+
+    /// Whether `peer` may be sent MessagePack.
+    pub fn reads_msgpack(&self, peer: &NodeId) -> bool {
+        self.msgpack.lock().unwrap().contains(peer)
     }
 }
 
@@ -108,7 +434,7 @@ impl MessageEnvelope {
 #[inline]
 pub fn now_ms() -> u64 {
     SystemTime::now()
-        .duration_since(UNIX_EPOCH)
+        .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap_or_default()
         .as_millis() as u64
 }
@@ -132,10 +458,10 @@ mod tests {
             "signature": ""
         }"#;
 
-        let envelope: MessageEnvelope = serde_json::from_str(json).unwrap();
+        let envelope = MessageEnvelope::from_json(json.as_bytes()).unwrap();
         assert_eq!(envelope.id, "test-123");
         assert_eq!(envelope.msg_type, "chat");
-        assert_eq!(envelope.payload["text"], "Hello!");
+        assert_eq!(envelope.payload.as_json().unwrap()["text"], "Hello!");
         assert!(envelope.encrypted_payload.is_none());
 
         // Round-trip
@@ -143,6 +469,15 @@ mod tests {
         let decoded = MessageEnvelope::from_bytes(&bytes).unwrap();
         assert_eq!(decoded.id, "test-123");
         assert_eq!(decoded.msg_type, "chat");
+        assert_eq!(decoded.payload, envelope.payload);
+
+        assert!(MessageEnvelope::is_encoded(&bytes));
+        assert!(!MessageEnvelope::is_encoded(&rmp_serde::to_vec(&("id", 1u8)).unwrap()));
+
+        // Legacy JSON still decodes through from_bytes
+        assert!(MessageEnvelope::is_encoded(json.as_bytes()));
+        let legacy = MessageEnvelope::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(legacy.payload, envelope.payload);
     }
 
     #[test]
@@ -163,7 +498,7 @@ mod tests {
             "routeType": "direct"
         }"#;
 
-        let envelope: MessageEnvelope = serde_json::from_str(json).unwrap();
+        let envelope = MessageEnvelope::from_json(json.as_bytes()).unwrap();
         assert_eq!(envelope.encrypted_payload.as_deref(), Some("cipher"));
         assert_eq!(envelope.hop_timestamps, Some(vec![100, 200]));
         assert_eq!(envelope.route_type.as_deref(), Some("direct"));
@@ -182,8 +517,8 @@ mod tests {
             "signature": ""
         }"#;
 
-        let envelope: MessageEnvelope = serde_json::from_str(json).unwrap();
-        let serialized = serde_json::to_string(&envelope).unwrap();
+        let envelope = MessageEnvelope::from_json(json.as_bytes()).unwrap();
+        let serialized = String::from_utf8(envelope.to_json().unwrap()).unwrap();
 
         // Optional fields with None should not appear in output
         assert!(!serialized.contains("encryptedPayload"));
@@ -191,5 +526,145 @@ mod tests {
         assert!(!serialized.contains("nonce"));
         assert!(!serialized.contains("hopTimestamps"));
         assert!(!serialized.contains("routeType"));
+        assert!(!serialized.contains("contentType"));
+    }
+
+    #[test]
+    fn bytes_payload_round_trip() {
+        let json = r#"{
+            "id": "test-789",
+            "from": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "to": "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+            "via": [],
+            "type": "file",
+            "payload": null,
+            "timestamp": 0,
+            "signature": ""
+        }"#;
+        let mut envelope = MessageEnvelope::from_json(json.as_bytes()).unwrap();
+        let data: Vec<u8> = (0..=255).collect();
+        envelope.payload = Payload::Bytes {
+            content_type: "application/octet-stream".into(),
+            data: data.clone().into(),
+        };
+
+        // MessagePack carries the bytes as-is
+        let packed = envelope.to_bytes().unwrap();
+        let decoded = MessageEnvelope::from_bytes(&packed).unwrap();
+        assert_eq!(decoded.payload.as_bytes(), Some(&data[..]));
+        assert_eq!(decoded.payload.content_type(), "application/octet-stream");
+
+        // JSON carries them as base64, flagged by contentType
+        let json = envelope.to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["contentType"], "application/octet-stream");
+        assert_eq!(value["payload"], BASE64.encode(&data));
+        assert!(packed.len() < json.len());
+
+        let decoded = MessageEnvelope::from_bytes(&json).unwrap();
+        assert_eq!(decoded.payload, envelope.payload);
+    }
+
+    #[test]
+    fn received_bytes_payload_shares_the_frame() {
+        let mut envelope = sample();
+        envelope.payload = Payload::Bytes {
+            content_type: "application/octet-stream".into(),
+            data: Bytes::from(vec![9u8; 4096]),
+        };
+        envelope.encrypted_payload = Some("cipher".into());
+        let frame = Bytes::from(envelope.to_wire(true).unwrap());
+
+        let (decoded, _) = MessageEnvelope::from_wire(&frame).unwrap();
+        let data = decoded.payload.as_bytes().unwrap();
+        assert_eq!(data, &[9u8; 4096][..]);
+        let frame_range = frame.as_ptr_range();
+        assert!(frame_range.contains(&data.as_ptr()), "payload should point into the frame");
+        assert_eq!(decoded.encrypted_payload.as_deref(), Some("cipher"));
+        assert_eq!(decoded.id, envelope.id);
+
+        // Same envelope as the copying decoder
+        let copied = MessageEnvelope::from_bytes(&frame).unwrap();
+        assert_eq!(copied.payload, decoded.payload);
+        assert_eq!(copied.hop_timestamps, decoded.hop_timestamps);
+
+        // JSON payloads decode the same way
+        let (decoded, _) = MessageEnvelope::from_wire(&Bytes::from(sample().to_wire(true).unwrap())).unwrap();
+        assert_eq!(decoded.payload, sample().payload);
+    }
+
+    /// The envelope as nodes predating MessagePack decode it.
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct BaselineEnvelope {
+        id: String,
+        from: NodeId,
+        to: NodeId,
+        via: Vec<NodeId>,
+        #[serde(rename = "type")]
+        msg_type: String,
+        payload: serde_json::Value,
+        timestamp: u64,
+        signature: String,
+    }
+
+    fn sample() -> MessageEnvelope {
+        let json = r#"{
+            "id": "test-wire",
+            "from": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "to": "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+            "via": [],
+            "type": "chat",
+            "payload": {"text": "Hello!"},
+            "timestamp": 1700000000000,
+            "signature": ""
+        }"#;
+        MessageEnvelope::from_json(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn unknown_peer_gets_json_older_nodes_decode() {
+        let envelope = sample();
+        let frame = envelope.to_wire(false).unwrap();
+
+        let old: BaselineEnvelope = serde_json::from_slice(&frame).unwrap();
+        assert_eq!(old.id, "test-wire");
+        assert_eq!(old.msg_type, "chat");
+        assert_eq!(old.payload["text"], "Hello!");
+        assert_eq!(old.timestamp, 1700000000000);
+
+        // MessagePack is what they could not read
+        assert!(serde_json::from_slice::<BaselineEnvelope>(&envelope.to_wire(true).unwrap()).is_err());
+    }
+
+    #[test]
+    fn wire_format_follows_what_the_peer_sent() {
+        let envelope = sample();
+        let peer = envelope.from;
+        let formats = WireFormats::default();
+        assert!(!formats.reads_msgpack(&peer));
+
+        // Older node: plain JSON, no flag
+        let plain = serde_json::to_vec(&JsonEnvelope::from(envelope.clone())).unwrap();
+        let (_, reads) = MessageEnvelope::from_wire(&plain.into()).unwrap();
+        assert!(!reads);
+
+        // New node: flagged JSON, then MessagePack
+        let flagged = Bytes::from(envelope.to_wire(false).unwrap());
+        let (decoded, reads) = MessageEnvelope::from_wire(&flagged).unwrap();
+        assert!(reads);
+        assert_eq!(decoded.payload, envelope.payload);
+        formats.note(peer, reads);
+        assert!(formats.reads_msgpack(&peer));
+        let packed = Bytes::from(envelope.to_wire(true).unwrap());
+        let (_, reads) = MessageEnvelope::from_wire(&packed).unwrap();
+        assert!(reads);
+
+        // Back to unflagged JSON (downgraded peer): JSON again
+        formats.note(peer, false);
+        assert!(!formats.reads_msgpack(&peer));
+
+        // The flag never leaks into the TypeScript shape
+        assert!(!String::from_utf8(envelope.to_json().unwrap()).unwrap().contains("acceptsMsgpack"));
     }
 }
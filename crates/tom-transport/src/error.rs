@@ -1,3 +1,4 @@
+use crate::envelope::EnvelopeError;
 use crate::NodeId;
 
 /// Errors returned by the ToM transport layer.
@@ -24,10 +25,16 @@ pub enum TomTransportError {
     Receive(#[source] anyhow::Error),
 
     #[error("envelope serialization failed: {0}")]
-    Serialization(#[source] serde_json::Error),
+    Serialization(#[source] EnvelopeError),
 
     #[error("envelope deserialization failed: {0}")]
-    Deserialization(#[source] serde_json::Error),
+    Deserialization(#[source] EnvelopeError),
+
+    #[error("send to {node_id} timed out after {after:?}")]
+    Timeout { node_id: NodeId, after: std::time::Duration },
+
+    #[error("send to {node_id} cancelled")]
+    Cancelled { node_id: NodeId },
 
     #[error("message too large: {size} bytes (max {max})")]
     MessageTooLarge { size: usize, max: usize },
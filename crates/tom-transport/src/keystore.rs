@@ -0,0 +1,384 @@
+use std::path::{Path, PathBuf};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use data_encoding::HEXLOWER;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tom_connect::SecretKey;
+use zeroize::Zeroizing;
+
+use crate::TomTransportError;
+
+/// PBKDF2 rounds for new key files (OWASP's figure for HMAC-SHA256).
+pub const DEFAULT_KDF_ITERATIONS: u32 = 600_000;
+
+/// Most PBKDF2 rounds a key file may ask for.
+const MAX_KDF_ITERATIONS: u32 = 10_000_000;
+
+const FILE_VERSION: u8 = 1;
+const KDF_PBKDF2_SHA256: &str = "pbkdf2-sha256";
+const SALT_LEN: usize = 16;
+
+/// A passphrase, wiped from memory on drop and never printed.
+#[derive(Clone)]
+pub struct Passphrase(Zeroizing<String>);
+
+impl Passphrase {
+    pub fn new(passphrase: impl Into<String>) -> Self {
+        Self(Zeroizing::new(passphrase.into()))
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl std::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Passphrase(***)")
+    }
+}
+
+impl From<&str> for Passphrase {
+    fn from(passphrase: &str) -> Self {
+        Self::new(passphrase)
+    }
+}
+
+impl From<String> for Passphrase {
+    fn from(passphrase: String) -> Self {
+        Self::new(passphrase)
+    }
+}
+
+/// On-disk form of an encrypted identity (JSON).
+///
+/// The secret key seed is sealed with XChaCha20-Poly1305 under a key derived
+/// from the passphrase (PBKDF2-HMAC-SHA256, salted). The node id is kept in
+/// clear, so the identity can be read without the passphrase, and is bound
+/// to the ciphertext as associated data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyFile {
+    version: u8,
+    node_id: String,
+    kdf: String,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// A node identity kept in a passphrase-encrypted file.
+///
+/// ```rust,no_run
+/// use tom_transport::KeyStore;
+///
+/// # fn example() -> Result<(), tom_transport::TomTransportError> {
+/// let store = KeyStore::new("/home/user/.tom/identity.json");
+/// let key = store.load_or_create(&"correct horse".into())?;
+/// // Back up the seed, restore it elsewhere:
+/// let seed = store.export(&"correct horse".into())?;
+/// KeyStore::new("/mnt/usb/identity.json").import(&seed, &"other passphrase".into())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyStore {
+    path: PathBuf,
+    iterations: u32,
+}
+
+impl KeyStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            iterations: DEFAULT_KDF_ITERATIONS,
+        }
+    }
+
+    /// PBKDF2 rounds used when writing (default [`DEFAULT_KDF_ITERATIONS`]).
+    /// Reading uses the rounds recorded in the file.
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations.clamp(1, MAX_KDF_ITERATIONS);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Generate a new identity and save it. Fails if the file exists.
+    pub fn create(&self, passphrase: &Passphrase) -> Result<SecretKey, TomTransportError> {
+        let key = SecretKey::generate(&mut rand::rng());
+        self.save_new(&key, passphrase)?;
+        Ok(key)
+    }
+
+    /// Decrypt the identity.
+    pub fn load(&self, passphrase: &Passphrase) -> Result<SecretKey, TomTransportError> {
+        let bytes = std::fs::read(&self.path)
+            .map_err(|e| self.error(format_args!("failed to read: {e}")))?;
+        if bytes.len() == 32 {
+            return Err(self.error("not encrypted (raw key file): import it first"));
+        }
+        let file: KeyFile = serde_json::from_slice(&bytes)
+            .map_err(|e| self.error(format_args!("invalid key file: {e}")))?;
+        self.open(&file, passphrase)
+    }
+
+    /// Load the identity, creating it on first run. A raw 32-byte key file
+    /// (as written by `TomNodeConfig::identity_path` without a passphrase)
+    /// is encrypted in place.
+    pub fn load_or_create(&self, passphrase: &Passphrase) -> Result<SecretKey, TomTransportError> {
+        if !self.exists() {
+            return self.create(passphrase);
+        }
+        if let Some(key) = self.read_raw()? {
+            self.write(&key, passphrase)?;
+            tracing::info!(path = %self.path.display(), "identity file encrypted");
+            return Ok(key);
+        }
+        self.load(passphrase)
+    }
+
+    /// The secret key seed, hex encoded, for backup or another device.
+    /// Treat it like the key itself.
+    pub fn export(&self, passphrase: &Passphrase) -> Result<String, TomTransportError> {
+        let key = self.load(passphrase)?;
+        Ok(HEXLOWER.encode(&Zeroizing::new(key.to_bytes())[..]))
+    }
+
+    /// Save an exported seed under `passphrase`. Fails if the file exists.
+    pub fn import(&self, exported: &str, passphrase: &Passphrase) -> Result<SecretKey, TomTransportError> {
+        let seed = Zeroizing::new(
+            HEXLOWER
+                .decode(exported.trim().to_ascii_lowercase().as_bytes())
+                .map_err(|e| self.error(format_args!("invalid exported key: {e}")))?,
+        );
+        let seed: &[u8; 32] = seed[..]
+            .try_into()
+            .map_err(|_| self.error(format_args!("invalid exported key: expected 32 bytes, got {}", seed.len())))?;
+        let key = SecretKey::from_bytes(seed);
+        self.save_new(&key, passphrase)?;
+        Ok(key)
+    }
+
+    /// Re-encrypt the identity under a new passphrase.
+    pub fn change_passphrase(&self, old: &Passphrase, new: &Passphrase) -> Result<(), TomTransportError> {
+        let key = self.load(old)?;
+        self.write(&key, new)
+    }
+
+    fn save_new(&self, key: &SecretKey, passphrase: &Passphrase) -> Result<(), TomTransportError> {
+        if self.exists() {
+            return Err(self.error("already exists"));
+        }
+        self.write(key, passphrase)
+    }
+
+    /// A legacy unencrypted key file, if that's what the file is.
+    fn read_raw(&self) -> Result<Option<SecretKey>, TomTransportError> {
+        let bytes = Zeroizing::new(
+            std::fs::read(&self.path).map_err(|e| self.error(format_args!("failed to read: {e}")))?,
+        );
+        Ok(<&[u8; 32]>::try_from(&bytes[..]).ok().map(SecretKey::from_bytes))
+    }
+
+    fn seal(&self, key: &SecretKey, passphrase: &Passphrase) -> KeyFile {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; 24];
+        rand::rng().fill_bytes(&mut salt);
+        rand::rng().fill_bytes(&mut nonce);
+        let node_id = key.public().to_string();
+
+        let cipher_key = derive_key(passphrase, &salt, self.iterations);
+        let cipher = XChaCha20Poly1305::new(cipher_key.as_ref().into());
+        let seed = Zeroizing::new(key.to_bytes());
+        let ciphertext = cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &seed[..],
+                    aad: node_id.as_bytes(),
+                },
+            )
+            .expect("XChaCha20-Poly1305 encryption cannot fail");
+
+        KeyFile {
+            version: FILE_VERSION,
+            node_id,
+            kdf: KDF_PBKDF2_SHA256.into(),
+            iterations: self.iterations,
+            salt: HEXLOWER.encode(&salt),
+            nonce: HEXLOWER.encode(&nonce),
+            ciphertext: HEXLOWER.encode(&ciphertext),
+        }
+    }
+
+    fn open(&self, file: &KeyFile, passphrase: &Passphrase) -> Result<SecretKey, TomTransportError> {
+        if file.version != FILE_VERSION || file.kdf != KDF_PBKDF2_SHA256 {
+            return Err(self.error(format_args!(
+                "unsupported key file (version {}, kdf {})",
+                file.version, file.kdf
+            )));
+        }
+        if file.iterations == 0 || file.iterations > MAX_KDF_ITERATIONS {
+            return Err(self.error(format_args!("invalid kdf iterations {}", file.iterations)));
+        }
+        let decode = |field: &str| {
+            HEXLOWER
+                .decode(field.as_bytes())
+                .map_err(|e| self.error(format_args!("invalid key file: {e}")))
+        };
+        let salt = decode(&file.salt)?;
+        let nonce = decode(&file.nonce)?;
+        let ciphertext = decode(&file.ciphertext)?;
+        if nonce.len() != 24 {
+            return Err(self.error("invalid key file: bad nonce"));
+        }
+
+        let cipher_key = derive_key(passphrase, &salt, file.iterations);
+        let cipher = XChaCha20Poly1305::new(cipher_key.as_ref().into());
+        let seed = Zeroizing::new(
+            cipher
+                .decrypt(
+                    XNonce::from_slice(&nonce),
+                    Payload {
+                        msg: &ciphertext,
+                        aad: file.node_id.as_bytes(),
+                    },
+                )
+                .map_err(|_| self.error("wrong passphrase or corrupted file"))?,
+        );
+        let seed: &[u8; 32] = seed[..]
+            .try_into()
+            .map_err(|_| self.error("invalid key file: bad key length"))?;
+        let key = SecretKey::from_bytes(seed);
+        if key.public().to_string() != file.node_id {
+            return Err(self.error("invalid key file: node id mismatch"));
+        }
+        Ok(key)
+    }
+
+    /// Write atomically (temp file + rename), owner-only on Unix.
+    fn write(&self, key: &SecretKey, passphrase: &Passphrase) -> Result<(), TomTransportError> {
+        let json = serde_json::to_vec_pretty(&self.seal(key, passphrase))
+            .map_err(|e| self.error(format_args!("failed to encode: {e}")))?;
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| self.error(format_args!("failed to create directory: {e}")))?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, json).map_err(|e| self.error(format_args!("failed to write: {e}")))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))
+                .map_err(|e| self.error(format_args!("failed to set permissions: {e}")))?;
+        }
+        std::fs::rename(&tmp, &self.path).map_err(|e| self.error(format_args!("failed to write: {e}")))
+    }
+
+    fn error(&self, reason: impl std::fmt::Display) -> TomTransportError {
+        TomTransportError::Identity(format!("{}: {reason}", self.path.display()))
+    }
+}
+
+/// PBKDF2-HMAC-SHA256 with a 32-byte output.
+fn derive_key(passphrase: &Passphrase, salt: &[u8], iterations: u32) -> Zeroizing<[u8; 32]> {
+    let mut out = Zeroizing::new([0u8; 32]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut out[..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(dir: &tempfile::TempDir) -> KeyStore {
+        KeyStore::new(dir.path().join("identity.json")).iterations(2)
+    }
+
+    #[test]
+    fn pbkdf2_known_answers() {
+        // RFC 7914 §11 / common PBKDF2-HMAC-SHA256 vectors
+        let pass = Passphrase::from("password");
+        assert_eq!(
+            HEXLOWER.encode(&derive_key(&pass, b"salt", 1)[..]),
+            "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b"
+        );
+        assert_eq!(
+            HEXLOWER.encode(&derive_key(&pass, b"salt", 4096)[..]),
+            "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a"
+        );
+    }
+
+    #[test]
+    fn create_load_and_wrong_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(&dir);
+        let key = store.create(&"hunter2".into()).unwrap();
+        assert!(store.create(&"hunter2".into()).is_err(), "never overwrites");
+
+        assert_eq!(store.load(&"hunter2".into()).unwrap().public(), key.public());
+        assert_eq!(store.load_or_create(&"hunter2".into()).unwrap().public(), key.public());
+        let err = store.load(&"hunter3".into()).unwrap_err();
+        assert!(err.to_string().contains("wrong passphrase"));
+
+        let text = std::fs::read_to_string(store.path()).unwrap();
+        assert!(text.contains(&key.public().to_string()), "node id readable in clear");
+        assert!(!text.contains(&HEXLOWER.encode(&key.to_bytes())));
+    }
+
+    #[test]
+    fn export_import_and_change_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(&dir);
+        let key = store.create(&"a".into()).unwrap();
+        let exported = store.export(&"a".into()).unwrap();
+
+        let copy = KeyStore::new(dir.path().join("copy.json")).iterations(2);
+        assert_eq!(copy.import(&exported, &"b".into()).unwrap().public(), key.public());
+        assert!(copy.import("zz", &"b".into()).is_err());
+
+        copy.change_passphrase(&"b".into(), &"c".into()).unwrap();
+        assert!(copy.load(&"b".into()).is_err());
+        assert_eq!(copy.load(&"c".into()).unwrap().public(), key.public());
+    }
+
+    #[test]
+    fn raw_key_file_is_encrypted_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(&dir);
+        let key = SecretKey::generate(&mut rand::rng());
+        std::fs::write(store.path(), key.to_bytes()).unwrap();
+        assert!(store.load(&"p".into()).is_err());
+
+        assert_eq!(store.load_or_create(&"p".into()).unwrap().public(), key.public());
+        assert_ne!(std::fs::read(store.path()).unwrap().len(), 32);
+        assert_eq!(store.load(&"p".into()).unwrap().public(), key.public());
+    }
+
+    #[test]
+    fn tampered_node_id_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(&dir);
+        store.create(&"p".into()).unwrap();
+        let other = SecretKey::generate(&mut rand::rng()).public().to_string();
+        let mut file: KeyFile = serde_json::from_slice(&std::fs::read(store.path()).unwrap()).unwrap();
+        file.node_id = other;
+        std::fs::write(store.path(), serde_json::to_vec(&file).unwrap()).unwrap();
+        assert!(store.load(&"p".into()).is_err());
+    }
+
+    #[test]
+    fn passphrase_is_not_printed() {
+        assert_eq!(format!("{:?}", Passphrase::from("secret")), "Passphrase(***)");
+    }
+}
@@ -3,6 +3,11 @@
 //! Wraps QUIC connectivity (hole punching, relay fallback, E2E encryption)
 //! via tom-connect behind a stable API.
 //!
+//! Without the default `native` feature only the I/O-free types build
+//! (`NodeId`, `MessageEnvelope`, `PathEvent`, `TomTransportError`): the ones
+//! tom-protocol's state machines share with the node, on every target
+//! including `wasm32-unknown-unknown`.
+//!
 //! # Quick start
 //!
 //! ```rust,no_run
@@ -26,24 +31,62 @@
 //! # }
 //! ```
 
+#[cfg(feature = "native")]
 mod config;
+#[cfg(feature = "native")]
 mod connection;
+#[cfg(feature = "native")]
+mod datagram;
 mod envelope;
 mod error;
+#[cfg(feature = "native")]
+mod keystore;
+#[cfg(feature = "native")]
 mod node;
 mod path;
+#[cfg(feature = "native")]
 mod protocol;
-
-pub use config::TomNodeConfig;
-pub use envelope::{now_ms, MessageEnvelope};
+#[cfg(feature = "native")]
+mod recv_queue;
+#[cfg(feature = "native")]
+mod stats;
+#[cfg(feature = "native")]
+mod stream;
+
+#[cfg(feature = "native")]
+pub use config::{
+    TomNodeConfig, DEFAULT_IDLE_TIMEOUT, DEFAULT_PEER_RECV_BUFFER, DEFAULT_SEND_TIMEOUT,
+};
+#[cfg(feature = "native")]
+pub use datagram::TOM_DATAGRAM_ALPN;
+pub use envelope::{now_ms, EnvelopeError, JsonEnvelope, MessageEnvelope, Payload};
 pub use error::TomTransportError;
+#[cfg(feature = "native")]
+pub use keystore::{KeyStore, Passphrase, DEFAULT_KDF_ITERATIONS};
+#[cfg(feature = "native")]
 pub use node::TomNode;
 pub use path::{PathEvent, PathKind};
+#[cfg(feature = "native")]
+pub use stats::{NodeStats, PeerStats, MAX_TRACKED_PEERS};
+#[cfg(feature = "native")]
+pub use stream::{
+    RawStream, StreamOpener, TomStream, MAX_PROTOCOL_TAG, MAX_STREAM_FRAME, TOM_RAW_STREAM_ALPN,
+    TOM_STREAM_ALPN,
+};
+
+// Cancellation of in-flight sends (`TomNode::send_raw_with`)
+#[cfg(feature = "native")]
+pub use tokio_util::sync::CancellationToken;
+
+// Received frames (`TomNode::recv_raw`) and bytes payloads, shared without copying
+pub use bytes::Bytes;
 
 // Re-export gossip types for protocol layer
+#[cfg(feature = "native")]
 pub use tom_gossip;
 
 // Re-export connect types for custom relay configuration and address exchange
+#[cfg(feature = "native")]
 pub use tom_connect::{EndpointAddr, RelayUrl};
 
 use std::fmt;
@@ -53,16 +96,16 @@ use std::str::FromStr;
 ///
 /// Wraps `EndpointId`. Displayed and parsed as hex string.
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub struct NodeId(tom_connect::EndpointId);
+pub struct NodeId(tom_base::EndpointId);
 
 impl NodeId {
     /// Create from an EndpointId.
-    pub fn from_endpoint_id(id: tom_connect::EndpointId) -> Self {
+    pub fn from_endpoint_id(id: tom_base::EndpointId) -> Self {
         Self(id)
     }
 
     /// Access the underlying EndpointId.
-    pub fn as_endpoint_id(&self) -> &tom_connect::EndpointId {
+    pub fn as_endpoint_id(&self) -> &tom_base::EndpointId {
         &self.0
     }
 
@@ -90,7 +133,7 @@ impl FromStr for NodeId {
     type Err = TomTransportError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let id: tom_connect::EndpointId = s
+        let id: tom_base::EndpointId = s
             .parse()
             .map_err(|_| TomTransportError::InvalidNodeId(s.to_string()))?;
         Ok(Self(id))
@@ -1,15 +1,26 @@
 use crate::config::TomNodeConfig;
 use crate::connection::ConnectionPool;
-use crate::envelope::MessageEnvelope;
+use crate::datagram::{DatagramHandler, DatagramLinks, TOM_DATAGRAM_ALPN};
+use crate::envelope::{MessageEnvelope, WireFormats};
+use crate::keystore::KeyStore;
 use crate::path::{PathEvent, PathKind};
 use crate::protocol::{self, HandlerState, TomProtocolHandler};
-use crate::{NodeId, TomTransportError};
-
+use crate::recv_queue::{self, FairReceiver};
+use crate::stats::{NodeStats, PeerStats, StatsRegistry};
+use crate::stream::{
+    RawStream, RawStreamHandler, StreamHandler, StreamOpener, TomStream, TOM_RAW_STREAM_ALPN,
+    TOM_STREAM_ALPN,
+};
+use crate::{CancellationToken, NodeId, TomTransportError};
+
+use bytes::Bytes;
 use tom_base::SecretKey;
 use tom_connect::address_lookup::memory::MemoryLookup;
+use tom_connect::endpoint::{IdleTimeout, QuicTransportConfig};
 use tom_connect::protocol::Router;
 use tom_connect::{Endpoint, RelayMode};
 use tom_gossip::Gossip;
+use n0_watcher::Watcher;
 use serde::Deserialize;
 use std::path::Path;
 use std::sync::Arc;
@@ -85,6 +96,25 @@ async fn fetch_dns_fallback_relays(
     Ok(parse_dns_txt_relays(&lines))
 }
 
+/// QUIC transport settings: idle timeout and, if set, keepalive pace, so
+/// dead connections are detected within `idle_timeout` instead of on next
+/// send.
+fn quic_transport_config(config: &TomNodeConfig) -> Result<QuicTransportConfig, TomTransportError> {
+    let idle_timeout = IdleTimeout::try_from(config.idle_timeout)
+        .map_err(|e| TomTransportError::Config(format!("invalid idle_timeout: {e}")))?;
+    let mut builder = QuicTransportConfig::builder().max_idle_timeout(Some(idle_timeout));
+    if let Some(interval) = config.keep_alive_interval {
+        if interval >= config.idle_timeout {
+            return Err(TomTransportError::Config(format!(
+                "keep_alive_interval ({:?}) must be shorter than idle_timeout ({:?})",
+                interval, config.idle_timeout
+            )));
+        }
+        builder = builder.keep_alive_interval(interval);
+    }
+    Ok(builder.build())
+}
+
 async fn fetch_discovery_relays(
     discovery_base_url: &str,
 ) -> Result<DiscoverySnapshot, TomTransportError> {
@@ -126,17 +156,29 @@ pub struct TomNode {
     id: NodeId,
     pool: Arc<ConnectionPool>,
     memory_lookup: MemoryLookup,
-    incoming_rx: mpsc::Receiver<(NodeId, MessageEnvelope)>,
-    incoming_raw_rx: mpsc::Receiver<(NodeId, Vec<u8>)>,
+    incoming_rx: FairReceiver<MessageEnvelope>,
+    incoming_raw_rx: FairReceiver<Bytes>,
     path_event_tx: broadcast::Sender<PathEvent>,
+    /// Per-peer traffic counters (see `peer_stats`).
+    stats: Arc<StatsRegistry>,
+    /// Peers that read MessagePack envelopes (see `send`).
+    wire_formats: Arc<WireFormats>,
     _router: Router,
     endpoint: Endpoint,
     gossip: Gossip,
     max_message_size: usize,
+    send_timeout: Duration,
     discovery_refresh_stop_tx: Option<oneshot::Sender<()>>,
     discovery_refresh_task: Option<JoinHandle<()>>,
     /// Receiver for PeerPresent events from relay servers.
     peer_present_rx: Option<mpsc::Receiver<(tom_connect::EndpointId, tom_connect::RelayUrl)>>,
+    /// Streams opened by peers (see `take_incoming_streams`).
+    incoming_streams_rx: Option<mpsc::Receiver<TomStream>>,
+    /// Raw streams opened by peers (see `recv_stream`).
+    incoming_raw_streams_rx: mpsc::Receiver<RawStream>,
+    datagrams: DatagramLinks,
+    /// Datagrams from peers (see `take_incoming_datagrams`).
+    incoming_datagrams_rx: Option<mpsc::Receiver<(NodeId, Vec<u8>)>>,
 }
 
 impl TomNode {
@@ -145,9 +187,14 @@ impl TomNode {
     /// If `identity_path` is configured, loads or creates a persistent identity.
     /// Otherwise, generates a fresh ephemeral Ed25519 identity.
     pub async fn bind(config: TomNodeConfig) -> Result<Self, TomTransportError> {
+        let transport_config = quic_transport_config(&config)?;
+
         // Load or generate identity
         let secret_key = match &config.identity_path {
-            Some(path) => Some(load_or_create_identity(path)?),
+            Some(path) => Some(match &config.identity_passphrase {
+                Some(passphrase) => KeyStore::new(path).load_or_create(passphrase)?,
+                None => load_or_create_identity(path)?,
+            }),
             None => None,
         };
 
@@ -250,6 +297,7 @@ impl TomNode {
         // to the Endpoint's address resolution (used by gossip's endpoint.connect()).
         let memory_lookup = MemoryLookup::new();
         builder = builder.address_lookup(memory_lookup.clone());
+        builder = builder.transport_config(transport_config);
 
         if let Some(key) = secret_key {
             builder = builder.secret_key(key);
@@ -260,8 +308,10 @@ impl TomNode {
 
         let id = NodeId::from_endpoint_id(endpoint.id());
 
-        let (incoming_tx, incoming_rx) = mpsc::channel(config.recv_buffer);
-        let (incoming_raw_tx, incoming_raw_rx) = mpsc::channel(config.recv_buffer);
+        let (incoming_tx, incoming_rx) =
+            recv_queue::channel(config.peer_recv_buffer, config.recv_buffer);
+        let (incoming_raw_tx, incoming_raw_rx) =
+            recv_queue::channel(config.peer_recv_buffer, config.recv_buffer);
         let (path_event_tx, _) = broadcast::channel(64);
 
         // Create pool first so we can share it with the handler
@@ -272,10 +322,14 @@ impl TomNode {
         };
         let pool = Arc::new(ConnectionPool::new(endpoint.clone(), config.alpn.clone(), default_relays));
 
+        let stats = Arc::new(StatsRegistry::default());
+        let wire_formats = Arc::new(WireFormats::default());
         let handler_state = Arc::new(HandlerState {
             incoming_tx,
             incoming_raw_tx,
             path_event_tx: path_event_tx.clone(),
+            stats: Arc::clone(&stats),
+            wire_formats: Arc::clone(&wire_formats),
             max_message_size: config.max_message_size,
         });
 
@@ -285,9 +339,27 @@ impl TomNode {
 
         let gossip = Gossip::builder().spawn(endpoint.clone());
 
+        let (incoming_streams_tx, incoming_streams_rx) = mpsc::channel(16);
+        let stream_handler = StreamHandler {
+            incoming_tx: incoming_streams_tx,
+        };
+
+        let (incoming_raw_streams_tx, incoming_raw_streams_rx) = mpsc::channel(16);
+        let raw_stream_handler = RawStreamHandler {
+            incoming_tx: incoming_raw_streams_tx,
+        };
+
+        let (incoming_datagrams_tx, incoming_datagrams_rx) = mpsc::channel(config.recv_buffer);
+        let datagram_handler = DatagramHandler {
+            incoming_tx: incoming_datagrams_tx,
+        };
+
         let router = Router::builder(endpoint.clone())
             .accept(config.alpn.clone(), Arc::new(handler))
             .accept(tom_gossip::ALPN, gossip.clone())
+            .accept(TOM_STREAM_ALPN, Arc::new(stream_handler))
+            .accept(TOM_RAW_STREAM_ALPN, Arc::new(raw_stream_handler))
+            .accept(TOM_DATAGRAM_ALPN, Arc::new(datagram_handler))
             .spawn();
 
         let (discovery_refresh_stop_tx, discovery_refresh_task) =
@@ -331,6 +403,7 @@ impl TomNode {
             };
 
         let peer_present_rx = endpoint.take_peer_present_rx();
+        let datagrams = DatagramLinks::new(Arc::clone(&pool));
 
         Ok(Self {
             id,
@@ -339,13 +412,20 @@ impl TomNode {
             incoming_rx,
             incoming_raw_rx,
             path_event_tx,
+            stats,
+            wire_formats,
             _router: router,
             endpoint,
             gossip,
             max_message_size: config.max_message_size,
+            send_timeout: config.send_timeout,
             discovery_refresh_stop_tx,
             discovery_refresh_task,
             peer_present_rx,
+            incoming_streams_rx: Some(incoming_streams_rx),
+            incoming_raw_streams_rx,
+            datagrams,
+            incoming_datagrams_rx: Some(incoming_datagrams_rx),
         })
     }
 
@@ -400,23 +480,57 @@ impl TomNode {
     /// Send an envelope to a peer.
     ///
     /// The connection is established on first use and cached for subsequent sends.
+    /// The envelope goes as JSON until the peer has shown it reads MessagePack
+    /// (see [`MessageEnvelope::to_wire`]).
     pub async fn send(
         &self,
         to: NodeId,
         envelope: &MessageEnvelope,
     ) -> Result<(), TomTransportError> {
         let data = envelope
-            .to_bytes()
+            .to_wire(self.wire_formats.reads_msgpack(&to))
             .map_err(TomTransportError::Serialization)?;
         self.send_raw(to, &data).await
     }
 
-    /// Send raw bytes to a peer.
+    /// Send raw bytes to a peer, failing with `Timeout` past the
+    /// configured send timeout.
     pub async fn send_raw(
         &self,
         to: NodeId,
         data: &[u8],
     ) -> Result<(), TomTransportError> {
+        self.send_raw_with(to, data, self.send_timeout, &CancellationToken::new())
+            .await
+    }
+
+    /// Send raw bytes to a peer within `timeout` (connecting included),
+    /// giving up with `Cancelled` as soon as `cancel` fires. Either way the
+    /// cached connection is dropped, so the next send dials afresh.
+    #[tracing::instrument(name = "send", level = "debug", skip_all, fields(peer = %to, bytes = data.len()))]
+    pub async fn send_raw_with(
+        &self,
+        to: NodeId,
+        data: &[u8],
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<(), TomTransportError> {
+        let result = tokio::select! {
+            result = tokio::time::timeout(timeout, self.send_raw_inner(to, data)) => {
+                result.unwrap_or(Err(TomTransportError::Timeout { node_id: to, after: timeout }))
+            }
+            _ = cancel.cancelled() => Err(TomTransportError::Cancelled { node_id: to }),
+        };
+        if matches!(
+            result,
+            Err(TomTransportError::Timeout { .. } | TomTransportError::Cancelled { .. })
+        ) {
+            self.pool.remove(&to).await;
+        }
+        result
+    }
+
+    async fn send_raw_inner(&self, to: NodeId, data: &[u8]) -> Result<(), TomTransportError> {
         if data.len() > self.max_message_size {
             return Err(TomTransportError::MessageTooLarge {
                 size: data.len(),
@@ -424,7 +538,7 @@ impl TomNode {
             });
         }
 
-        let conn = self.pool.get_or_connect(to).await?;
+        let conn = self.connection_to(to).await?;
 
         tracing::trace!("send_raw: opening bi-stream to {}", to);
         let (mut send, recv) = match conn.open_bi().await {
@@ -457,10 +571,43 @@ impl TomNode {
         // no accept_bi() loop for its outgoing connections).
         drop(recv);
 
+        self.stats.record_sent(to, data.len());
+        let (kind, rtt) = protocol::classify_path(&conn.paths().get());
+        self.stats.record_path(to, kind, rtt);
+
         Ok(())
     }
 
+    /// Establish a connection to `to` ahead of the first message and keep
+    /// it in the pool: address discovery and hole punching happen now, not
+    /// on the first send. Resolves once the connection is up (within the
+    /// send timeout) with the path it uses at that point; later upgrades
+    /// (relay → direct) show up in `path_events` and `peer_stats`.
+    pub async fn connect_eager(&self, to: NodeId) -> Result<PathKind, TomTransportError> {
+        let conn = tokio::time::timeout(self.send_timeout, self.connection_to(to))
+            .await
+            .map_err(|_| TomTransportError::Timeout {
+                node_id: to,
+                after: self.send_timeout,
+            })??;
+        let (kind, rtt) = protocol::classify_path(&conn.paths().get());
+        self.stats.record_path(to, kind, rtt);
+        Ok(kind)
+    }
+
+    /// Pooled connection to `to`; a freshly dialed one gets a path watcher.
+    async fn connection_to(&self, to: NodeId) -> Result<tom_connect::endpoint::Connection, TomTransportError> {
+        let (conn, fresh) = self.pool.get_or_connect(to).await?;
+        if fresh {
+            protocol::spawn_path_watcher(&conn, to, self.path_event_tx.clone(), Arc::clone(&self.stats));
+        }
+        Ok(conn)
+    }
+
     /// Receive the next incoming envelope. Blocks until one arrives.
+    ///
+    /// Peers are served in turn, one message each, so a chatty peer can't
+    /// starve quiet ones (see [`TomNodeConfig::peer_recv_buffer`]).
     pub async fn recv(&mut self) -> Result<(NodeId, MessageEnvelope), TomTransportError> {
         self.incoming_rx
             .recv()
@@ -469,22 +616,78 @@ impl TomNode {
     }
 
     /// Receive the next incoming raw message. Blocks until one arrives.
-    pub async fn recv_raw(&mut self) -> Result<(NodeId, Vec<u8>), TomTransportError> {
+    ///
+    /// Like [`recv`](Self::recv), peers are served in turn.
+    pub async fn recv_raw(&mut self) -> Result<(NodeId, Bytes), TomTransportError> {
         self.incoming_raw_rx
             .recv()
             .await
             .ok_or(TomTransportError::Shutdown)
     }
 
+    /// Opener for dedicated streams to peers (see [`TomStream`]), usable
+    /// from other tasks.
+    pub fn streams(&self) -> StreamOpener {
+        StreamOpener {
+            pool: Arc::clone(&self.pool),
+        }
+    }
+
+    /// Take the receiver of streams opened by peers. Returns None after
+    /// the first call. Until taken, peers can open up to 16 streams that
+    /// nobody reads.
+    pub fn take_incoming_streams(&mut self) -> Option<mpsc::Receiver<TomStream>> {
+        self.incoming_streams_rx.take()
+    }
+
+    /// Open a raw bidirectional stream to `to`, tagged with the
+    /// sub-protocol `protocol` so the peer knows how to read it (see
+    /// [`RawStream`]). Each stream gets its own QUIC connection.
+    pub async fn send_stream(&self, to: NodeId, protocol: &[u8]) -> Result<RawStream, TomTransportError> {
+        self.streams().open_raw(to, protocol).await
+    }
+
+    /// Next raw stream opened by a peer. Until called, peers can open up
+    /// to 16 streams that nobody reads.
+    pub async fn recv_stream(&mut self) -> Result<RawStream, TomTransportError> {
+        self.incoming_raw_streams_rx
+            .recv()
+            .await
+            .ok_or(TomTransportError::Shutdown)
+    }
+
+    /// Send an unreliable datagram: no retransmission, no ordering, and
+    /// it must fit in one packet (`MessageTooLarge` otherwise). For
+    /// real-time data where late is as bad as lost.
+    pub async fn send_datagram(&self, to: NodeId, data: &[u8]) -> Result<(), TomTransportError> {
+        self.datagrams.send(to, data).await
+    }
+
+    /// Take the receiver of datagrams from peers. Returns None after the
+    /// first call. Datagrams arriving while it's full are dropped.
+    pub fn take_incoming_datagrams(&mut self) -> Option<mpsc::Receiver<(NodeId, Vec<u8>)>> {
+        self.incoming_datagrams_rx.take()
+    }
+
     /// Subscribe to path change events.
     pub fn path_events(&self) -> broadcast::Receiver<PathEvent> {
         self.path_event_tx.subscribe()
     }
 
     /// Get the current path kind for a connected peer.
-    pub fn path_kind(&self, _peer: NodeId) -> Option<PathKind> {
-        // TODO: Track per-peer path state from path watcher events
-        None
+    pub fn path_kind(&self, peer: NodeId) -> Option<PathKind> {
+        self.stats.peer(&peer).map(|stats| stats.path)
+    }
+
+    /// Traffic with `peer` (messages/bytes each way, last activity, path
+    /// and RTT), or None if nothing was exchanged yet.
+    pub fn peer_stats(&self, peer: NodeId) -> Option<PeerStats> {
+        self.stats.peer(&peer)
+    }
+
+    /// Traffic totals over all peers.
+    pub fn node_stats(&self) -> NodeStats {
+        self.stats.totals()
     }
 
     /// Force-evict a peer connection from the pool.
@@ -520,6 +723,12 @@ fn load_or_create_identity(path: &Path) -> Result<SecretKey, TomTransportError>
         let bytes = std::fs::read(path).map_err(|e| {
             TomTransportError::Identity(format!("failed to read {}: {e}", path.display()))
         })?;
+        if bytes.first() == Some(&b'{') {
+            return Err(TomTransportError::Identity(format!(
+                "identity file {} is encrypted: set identity_passphrase",
+                path.display()
+            )));
+        }
         let key_bytes: [u8; 32] = bytes.try_into().map_err(|v: Vec<u8>| {
             TomTransportError::Identity(format!(
                 "invalid identity file {}: expected 32 bytes, got {}",
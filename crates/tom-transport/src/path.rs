@@ -1,5 +1,10 @@
 use crate::NodeId;
+// `std::time::Instant::now()` panics on wasm32-unknown-unknown: browsers
+// take `web-time`'s clock through `n0_future`.
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
 use std::time::{Duration, Instant};
+#[cfg(all(target_family = "wasm", target_os = "unknown"))]
+use n0_future::time::{Duration, Instant};
 
 /// The kind of network path to a peer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -10,6 +15,9 @@ pub enum PathKind {
     Direct,
     /// Path type not yet determined.
     Unknown,
+    /// Keepalives went unanswered until the idle timeout: the connection
+    /// is dead and the peer unreachable until it is dialed again.
+    Unreachable,
 }
 
 impl std::fmt::Display for PathKind {
@@ -18,6 +26,7 @@ impl std::fmt::Display for PathKind {
             PathKind::Relay => write!(f, "RELAY"),
             PathKind::Direct => write!(f, "DIRECT"),
             PathKind::Unknown => write!(f, "UNKNOWN"),
+            PathKind::Unreachable => write!(f, "UNREACHABLE"),
         }
     }
 }
@@ -34,3 +43,19 @@ pub struct PathEvent {
     /// When this event occurred.
     pub timestamp: Instant,
 }
+
+impl PathEvent {
+    /// The connection to `remote` timed out (see [`PathKind::Unreachable`]).
+    pub fn peer_unreachable(remote: NodeId) -> Self {
+        Self {
+            kind: PathKind::Unreachable,
+            rtt: Duration::ZERO,
+            remote,
+            timestamp: Instant::now(),
+        }
+    }
+
+    pub fn is_unreachable(&self) -> bool {
+        self.kind == PathKind::Unreachable
+    }
+}
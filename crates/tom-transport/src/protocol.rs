@@ -1,14 +1,18 @@
-use crate::envelope::MessageEnvelope;
+use crate::envelope::{MessageEnvelope, WireFormats};
 use crate::path::{PathEvent, PathKind};
+use crate::recv_queue::FairSender;
+use crate::stats::StatsRegistry;
 use crate::{NodeId, TomTransportError};
 
-use tom_connect::endpoint::Connection;
+use tom_connect::endpoint::{Connection, ConnectionError};
 use tom_connect::protocol::AcceptError;
 use n0_future::StreamExt;
 use n0_watcher::Watcher;
+use bytes::Bytes;
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::broadcast;
+use tracing::Instrument;
 
 /// Write a length-prefixed message to a QUIC send stream.
 pub(crate) async fn write_framed(
@@ -23,10 +27,13 @@ pub(crate) async fn write_framed(
 }
 
 /// Read a length-prefixed message from a QUIC receive stream.
+///
+/// The frame is read into one buffer handed on as `Bytes`: no copy
+/// between here and `recv_raw()`.
 pub(crate) async fn read_framed(
     recv: &mut tom_connect::endpoint::RecvStream,
     max_size: usize,
-) -> Result<Vec<u8>, TomTransportError> {
+) -> Result<Bytes, TomTransportError> {
     let mut len_buf = [0u8; 4];
     recv.read_exact(&mut len_buf)
         .await
@@ -45,14 +52,17 @@ pub(crate) async fn read_framed(
         .await
         .map_err(|e| TomTransportError::Receive(e.into()))?;
 
-    Ok(buf)
+    Ok(Bytes::from(buf))
 }
 
 /// Internal state shared with the protocol handler.
 pub(crate) struct HandlerState {
-    pub incoming_tx: mpsc::Sender<(NodeId, MessageEnvelope)>,
-    pub incoming_raw_tx: mpsc::Sender<(NodeId, Vec<u8>)>,
+    pub incoming_tx: FairSender<MessageEnvelope>,
+    pub incoming_raw_tx: FairSender<Bytes>,
     pub path_event_tx: broadcast::Sender<PathEvent>,
+    pub stats: Arc<StatsRegistry>,
+    /// Which peers read MessagePack, shared with the send path.
+    pub wire_formats: Arc<WireFormats>,
     pub max_message_size: usize,
 }
 
@@ -72,6 +82,7 @@ impl tom_connect::protocol::ProtocolHandler for TomProtocolHandler {
     async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
         let remote = NodeId::from_endpoint_id(connection.remote_id());
         let state = self.state.clone();
+        let span = tracing::debug_span!("connection", peer = %remote);
 
         // Notify pool of the remote peer's endpoint ID. We do NOT store the
         // connection's remote_address() because in MagicSock it is an internal
@@ -83,7 +94,7 @@ impl tom_connect::protocol::ProtocolHandler for TomProtocolHandler {
         tracing::debug!("Accepted connection from {}", remote);
 
         // Spawn path watcher for this connection
-        spawn_path_watcher(&connection, remote, state.path_event_tx.clone());
+        spawn_path_watcher(&connection, remote, state.path_event_tx.clone(), state.stats.clone());
 
         // Accept loop: handle multiple bi-directional streams from this connection
         loop {
@@ -96,14 +107,21 @@ impl tom_connect::protocol::ProtocolHandler for TomProtocolHandler {
             tokio::spawn(async move {
                 match read_framed(&mut recv, state.max_message_size).await {
                     Ok(data) => {
-                        // Try to parse as envelope
-                        match MessageEnvelope::from_bytes(&data) {
-                            Ok(envelope) => {
-                                let _ = state.incoming_tx.send((remote, envelope)).await;
+                        tracing::debug!(bytes = data.len(), "stream received");
+                        state.stats.record_received(remote, data.len());
+                        // Try to parse as envelope (protocol frames are
+                        // told apart by their first byte, not a failed decode)
+                        let envelope = MessageEnvelope::is_encoded(&data)
+                            .then(|| MessageEnvelope::from_wire(&data).ok())
+                            .flatten();
+                        match envelope {
+                            Some((envelope, reads_msgpack)) => {
+                                state.wire_formats.note(remote, reads_msgpack);
+                                let _ = state.incoming_tx.send(remote, envelope).await;
                             }
-                            Err(_) => {
+                            None => {
                                 // Not a valid envelope — deliver as raw
-                                let _ = state.incoming_raw_tx.send((remote, data)).await;
+                                let _ = state.incoming_raw_tx.send(remote, data).await;
                             }
                         }
                         // Acknowledge receipt by closing our send stream
@@ -113,26 +131,41 @@ impl tom_connect::protocol::ProtocolHandler for TomProtocolHandler {
                         tracing::warn!("Failed to read from {remote}: {e}");
                     }
                 }
-            });
+            }.instrument(span.clone()));
         }
 
         Ok(())
     }
 }
 
-/// Spawn a background task that monitors path changes for a connection.
-fn spawn_path_watcher(
+/// Spawn a background task that monitors path changes for a connection,
+/// ending with a `PathKind::Unreachable` event if it idles out (keepalives
+/// unanswered) rather than being closed by either side.
+pub(crate) fn spawn_path_watcher(
     connection: &Connection,
     remote: NodeId,
     tx: broadcast::Sender<PathEvent>,
+    stats: Arc<StatsRegistry>,
 ) {
     let paths = connection.paths();
     let mut stream = paths.stream();
     let mut last_kind = PathKind::Unknown;
+    // Weak handle: watching must not keep a dropped connection open
+    let info = connection.to_info();
 
     tokio::spawn(async move {
-        while let Some(path_info) = stream.next().await {
+        let closed = info.closed();
+        tokio::pin!(closed);
+        let reason = loop {
+            let path_info = tokio::select! {
+                next = stream.next() => match next {
+                    Some(path_info) => path_info,
+                    None => break (&mut closed).await,
+                },
+                reason = &mut closed => break reason,
+            };
             let (kind, rtt) = classify_path(&path_info);
+            stats.record_path(remote, kind, rtt);
 
             if kind != last_kind {
                 last_kind = kind;
@@ -145,12 +178,18 @@ fn spawn_path_watcher(
                 // Ignore send errors (no subscribers)
                 let _ = tx.send(event);
             }
+        };
+
+        if let Some((ConnectionError::TimedOut, _)) = reason {
+            tracing::debug!(peer = %remote, "connection idled out, peer unreachable");
+            stats.record_path(remote, PathKind::Unreachable, std::time::Duration::ZERO);
+            let _ = tx.send(PathEvent::peer_unreachable(remote));
         }
     });
 }
 
 /// Classify the current path from the PathInfoList.
-fn classify_path(
+pub(crate) fn classify_path(
     paths: &tom_connect::endpoint::PathInfoList,
 ) -> (PathKind, std::time::Duration) {
     for path in paths.iter() {
@@ -0,0 +1,207 @@
+//! Fair receive queue behind `recv()` / `recv_raw()`.
+//!
+//! One bounded FIFO per peer, drained round-robin: a chatty peer fills its
+//! own queue and then waits (backpressure on its QUIC streams) while quiet
+//! peers' messages keep getting through.
+
+use crate::NodeId;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Create a fair queue holding at most `per_peer` items per peer and
+/// `total` items overall.
+pub(crate) fn channel<T>(per_peer: usize, total: usize) -> (FairSender<T>, FairReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queues: HashMap::new(),
+            ready: VecDeque::new(),
+            len: 0,
+            senders: 1,
+            receiver_alive: true,
+        }),
+        readable: Notify::new(),
+        writable: Notify::new(),
+        per_peer: per_peer.max(1),
+        total: total.max(1),
+    });
+    (
+        FairSender {
+            shared: Arc::clone(&shared),
+        },
+        FairReceiver { shared },
+    )
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    /// An item was queued, or the last sender left.
+    readable: Notify,
+    /// An item was taken, or the receiver left.
+    writable: Notify,
+    per_peer: usize,
+    total: usize,
+}
+
+struct State<T> {
+    /// Pending items per peer; a peer is removed once drained.
+    queues: HashMap<NodeId, VecDeque<T>>,
+    /// Peers with pending items, in service order.
+    ready: VecDeque<NodeId>,
+    /// Items queued across all peers.
+    len: usize,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+pub(crate) struct FairSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> FairSender<T> {
+    /// Queue `item` from `peer`, waiting while that peer's queue (or the
+    /// whole queue) is full. Gives the item back if the receiver is gone.
+    pub async fn send(&self, peer: NodeId, item: T) -> Result<(), T> {
+        loop {
+            let writable = self.shared.writable.notified();
+            tokio::pin!(writable);
+            // Registered before checking, so a concurrent pop isn't missed
+            writable.as_mut().enable();
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if !state.receiver_alive {
+                    return Err(item);
+                }
+                let queued = state.queues.get(&peer).map_or(0, VecDeque::len);
+                if queued < self.shared.per_peer && state.len < self.shared.total {
+                    if queued == 0 {
+                        state.ready.push_back(peer);
+                    }
+                    state.queues.entry(peer).or_default().push_back(item);
+                    state.len += 1;
+                    drop(state);
+                    self.shared.readable.notify_one();
+                    return Ok(());
+                }
+            }
+            writable.await;
+        }
+    }
+}
+
+impl<T> Clone for FairSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for FairSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.shared.readable.notify_one();
+        }
+    }
+}
+
+pub(crate) struct FairReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> FairReceiver<T> {
+    /// Next item, taking one from each peer in turn. `None` once every
+    /// sender is gone and the queue is drained.
+    pub async fn recv(&mut self) -> Option<(NodeId, T)> {
+        loop {
+            // notify_one keeps a permit, so a send racing this check still wakes us
+            let readable = self.shared.readable.notified();
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(peer) = state.ready.pop_front() {
+                    let queue = state.queues.get_mut(&peer).expect("ready peer has a queue");
+                    let item = queue.pop_front().expect("ready queue is not empty");
+                    if queue.is_empty() {
+                        state.queues.remove(&peer);
+                    } else {
+                        state.ready.push_back(peer);
+                    }
+                    state.len -= 1;
+                    drop(state);
+                    self.shared.writable.notify_waiters();
+                    return Some((peer, item));
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            readable.await;
+        }
+    }
+}
+
+impl<T> Drop for FairReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receiver_alive = false;
+        self.shared.writable.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn peer(seed: u8) -> NodeId {
+        NodeId::from_endpoint_id(tom_base::SecretKey::from_bytes(&[seed; 32]).public())
+    }
+
+    #[tokio::test]
+    async fn drains_peers_round_robin() {
+        let (tx, mut rx) = channel(8, 64);
+        let (chatty, quiet) = (peer(1), peer(2));
+        for i in 0..5 {
+            tx.send(chatty, i).await.unwrap();
+        }
+        tx.send(quiet, 100).await.unwrap();
+
+        // The quiet peer is served second, not after the whole burst
+        assert_eq!(rx.recv().await, Some((chatty, 0)));
+        assert_eq!(rx.recv().await, Some((quiet, 100)));
+        for i in 1..5 {
+            assert_eq!(rx.recv().await, Some((chatty, i)));
+        }
+
+        drop(tx);
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn full_peer_waits_while_others_get_through() {
+        let (tx, mut rx) = channel(2, 64);
+        let (chatty, quiet) = (peer(1), peer(2));
+        tx.send(chatty, 0).await.unwrap();
+        tx.send(chatty, 1).await.unwrap();
+
+        let blocked = tokio::time::timeout(Duration::from_millis(50), tx.send(chatty, 2)).await;
+        assert!(blocked.is_err(), "third item from a full peer must wait");
+        tx.send(quiet, 100).await.unwrap();
+
+        let sender = {
+            let tx = tx.clone();
+            tokio::spawn(async move { tx.send(chatty, 2).await })
+        };
+        assert_eq!(rx.recv().await, Some((chatty, 0)));
+        sender.await.unwrap().unwrap();
+        assert_eq!(rx.recv().await, Some((quiet, 100)));
+        assert_eq!(rx.recv().await, Some((chatty, 1)));
+        assert_eq!(rx.recv().await, Some((chatty, 2)));
+
+        drop(rx);
+        assert_eq!(tx.send(quiet, 101).await, Err(101));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::path::PathKind;
+use crate::NodeId;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Most peers with individual counters; the least recently active is
+/// forgotten first (node totals keep counting).
+pub const MAX_TRACKED_PEERS: usize = 4096;
+
+/// Traffic with one peer, as seen by the transport.
+///
+/// Messages are framed sends/receives (`send_raw`/`recv_raw` and envelopes);
+/// streams and datagrams aren't counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerStats {
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+    /// Last message either way.
+    pub last_activity: Option<Instant>,
+    /// Path of the last connection used with this peer.
+    pub path: PathKind,
+    /// Round-trip time on that path, once measured.
+    pub rtt: Option<Duration>,
+}
+
+impl Default for PeerStats {
+    fn default() -> Self {
+        Self {
+            messages_sent: 0,
+            bytes_sent: 0,
+            messages_received: 0,
+            bytes_received: 0,
+            last_activity: None,
+            path: PathKind::Unknown,
+            rtt: None,
+        }
+    }
+}
+
+/// Traffic totals for the whole node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeStats {
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+    /// Peers with individual counters (see `MAX_TRACKED_PEERS`).
+    pub peers_tracked: usize,
+}
+
+/// Per-peer counters shared by the node and its protocol handler.
+#[derive(Debug, Default)]
+pub(crate) struct StatsRegistry {
+    inner: Mutex<Registry>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    peers: HashMap<NodeId, PeerStats>,
+    totals: NodeStats,
+}
+
+impl Registry {
+    fn peer_mut(&mut self, peer: NodeId) -> &mut PeerStats {
+        if !self.peers.contains_key(&peer) && self.peers.len() >= MAX_TRACKED_PEERS {
+            let idlest = self
+                .peers
+                .iter()
+                .min_by_key(|(_, stats)| stats.last_activity)
+                .map(|(id, _)| *id);
+            if let Some(idlest) = idlest {
+                self.peers.remove(&idlest);
+            }
+        }
+        self.peers.entry(peer).or_default()
+    }
+}
+
+impl StatsRegistry {
+    pub fn record_sent(&self, peer: NodeId, bytes: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.totals.messages_sent += 1;
+        inner.totals.bytes_sent += bytes as u64;
+        let stats = inner.peer_mut(peer);
+        stats.messages_sent += 1;
+        stats.bytes_sent += bytes as u64;
+        stats.last_activity = Some(Instant::now());
+    }
+
+    pub fn record_received(&self, peer: NodeId, bytes: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.totals.messages_received += 1;
+        inner.totals.bytes_received += bytes as u64;
+        let stats = inner.peer_mut(peer);
+        stats.messages_received += 1;
+        stats.bytes_received += bytes as u64;
+        stats.last_activity = Some(Instant::now());
+    }
+
+    /// Current path to a peer; an unknown or dead path doesn't erase a
+    /// known RTT.
+    pub fn record_path(&self, peer: NodeId, kind: PathKind, rtt: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        let stats = inner.peer_mut(peer);
+        stats.path = kind;
+        if !matches!(kind, PathKind::Unknown | PathKind::Unreachable) {
+            stats.rtt = Some(rtt);
+        }
+    }
+
+    pub fn peer(&self, peer: &NodeId) -> Option<PeerStats> {
+        self.inner.lock().unwrap().peers.get(peer).cloned()
+    }
+
+    pub fn totals(&self) -> NodeStats {
+        let inner = self.inner.lock().unwrap();
+        NodeStats {
+            peers_tracked: inner.peers.len(),
+            ..inner.totals.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(seed: u8) -> NodeId {
+        NodeId::from_endpoint_id(tom_base::SecretKey::from_bytes(&[seed; 32]).public())
+    }
+
+    #[test]
+    fn counts_per_peer_and_in_total() {
+        let registry = StatsRegistry::default();
+        let (a, b) = (node_id(1), node_id(2));
+
+        registry.record_sent(a, 100);
+        registry.record_sent(a, 50);
+        registry.record_received(b, 10);
+        registry.record_path(a, PathKind::Direct, Duration::from_millis(12));
+        registry.record_path(a, PathKind::Unknown, Duration::ZERO);
+
+        let stats = registry.peer(&a).unwrap();
+        assert_eq!((stats.messages_sent, stats.bytes_sent), (2, 150));
+        assert_eq!(stats.messages_received, 0);
+        assert_eq!(stats.path, PathKind::Unknown);
+        assert_eq!(stats.rtt, Some(Duration::from_millis(12)));
+        assert!(stats.last_activity.is_some());
+
+        let totals = registry.totals();
+        assert_eq!((totals.messages_sent, totals.bytes_sent), (2, 150));
+        assert_eq!((totals.messages_received, totals.bytes_received), (1, 10));
+        assert_eq!(totals.peers_tracked, 2);
+        assert!(registry.peer(&node_id(3)).is_none());
+    }
+}
@@ -0,0 +1,373 @@
+use crate::connection::ConnectionPool;
+use crate::{NodeId, TomTransportError};
+
+use tom_connect::endpoint::{Connection, ReadExactError, RecvStream, SendStream, VarInt};
+use tom_connect::protocol::AcceptError;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+
+/// ALPN of dedicated streams (bulk transfers), apart from envelopes.
+pub const TOM_STREAM_ALPN: &[u8] = b"tom-protocol/stream/0";
+
+/// Largest frame accepted on a stream.
+pub const MAX_STREAM_FRAME: usize = 1024 * 1024;
+
+/// ALPN of raw streams: a sub-protocol tag, then bytes the application
+/// reads and writes itself (no framing, nothing buffered whole).
+pub const TOM_RAW_STREAM_ALPN: &[u8] = b"tom-protocol/raw-stream/0";
+
+/// Longest sub-protocol tag (sent with a one-byte length prefix).
+pub const MAX_PROTOCOL_TAG: usize = u8::MAX as usize;
+
+/// How long `TomStream::close` waits for the peer to hang up.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long an accepted raw stream may take to announce its sub-protocol.
+const TAG_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A dedicated bidirectional stream to a peer, for bulk data that doesn't
+/// belong in envelopes. Carries length-prefixed frames both ways; QUIC
+/// encrypts it and authenticates the peer.
+///
+/// One stream per connection: the connection closes with the stream.
+pub struct TomStream {
+    peer: NodeId,
+    send: SendStream,
+    recv: RecvStream,
+    connection: Connection,
+}
+
+impl std::fmt::Debug for TomStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TomStream").field("peer", &self.peer).finish()
+    }
+}
+
+impl TomStream {
+    /// The authenticated remote node.
+    pub fn peer(&self) -> NodeId {
+        self.peer
+    }
+
+    /// Send one frame (at most `MAX_STREAM_FRAME` bytes).
+    pub async fn send_frame(&mut self, data: &[u8]) -> Result<(), TomTransportError> {
+        if data.len() > MAX_STREAM_FRAME {
+            return Err(TomTransportError::MessageTooLarge {
+                size: data.len(),
+                max: MAX_STREAM_FRAME,
+            });
+        }
+        let send_err = |e: tom_connect::endpoint::WriteError| TomTransportError::Send {
+            node_id: self.peer,
+            source: e.into(),
+        };
+        self.send
+            .write_all(&(data.len() as u32).to_be_bytes())
+            .await
+            .map_err(send_err)?;
+        self.send.write_all(data).await.map_err(send_err)
+    }
+
+    /// The next frame, or None once the peer finished its side.
+    pub async fn recv_frame(&mut self) -> Result<Option<Vec<u8>>, TomTransportError> {
+        let mut len_buf = [0u8; 4];
+        match self.recv.read_exact(&mut len_buf).await {
+            Ok(()) => {}
+            Err(ReadExactError::FinishedEarly(0)) => return Ok(None),
+            Err(e) => return Err(TomTransportError::Receive(e.into())),
+        }
+        let len = u32::from_be_bytes(len_buf) as usize;
+        if len > MAX_STREAM_FRAME {
+            return Err(TomTransportError::MessageTooLarge {
+                size: len,
+                max: MAX_STREAM_FRAME,
+            });
+        }
+        let mut buf = vec![0u8; len];
+        self.recv
+            .read_exact(&mut buf)
+            .await
+            .map_err(|e| TomTransportError::Receive(e.into()))?;
+        Ok(Some(buf))
+    }
+
+    /// Finish our side, then wait (briefly) for the peer to hang up, so
+    /// the last frames get there before the connection closes.
+    pub async fn close(mut self) {
+        let _ = self.send.finish();
+        let _ = tokio::time::timeout(CLOSE_TIMEOUT, self.connection.closed()).await;
+    }
+}
+
+impl Drop for TomStream {
+    fn drop(&mut self) {
+        self.connection.close(VarInt::from_u32(0), b"stream closed");
+    }
+}
+
+/// A raw bidirectional stream to a peer, tagged with the sub-protocol the
+/// opener chose (e.g. `b"file/1"`, `b"sync/1"`). Implements `AsyncRead` and
+/// `AsyncWrite`, so higher layers stream payloads of any size through it.
+///
+/// One stream per connection: the connection closes with the stream.
+pub struct RawStream {
+    peer: NodeId,
+    protocol: Vec<u8>,
+    send: SendStream,
+    recv: RecvStream,
+    connection: Connection,
+}
+
+impl std::fmt::Debug for RawStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RawStream")
+            .field("peer", &self.peer)
+            .field("protocol", &String::from_utf8_lossy(&self.protocol))
+            .finish()
+    }
+}
+
+impl RawStream {
+    /// The authenticated remote node.
+    pub fn peer(&self) -> NodeId {
+        self.peer
+    }
+
+    /// Sub-protocol tag given by the opener.
+    pub fn protocol(&self) -> &[u8] {
+        &self.protocol
+    }
+
+    /// Finish our side, then wait (briefly) for the peer to hang up, so
+    /// the last bytes get there before the connection closes.
+    pub async fn close(mut self) {
+        let _ = self.send.finish();
+        let _ = tokio::time::timeout(CLOSE_TIMEOUT, self.connection.closed()).await;
+    }
+}
+
+impl AsyncRead for RawStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.recv).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for RawStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.send).poll_write(cx, buf).map_err(Into::into)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.send).poll_flush(cx)
+    }
+
+    /// Finishes our side; the peer reads EOF.
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.send).poll_shutdown(cx)
+    }
+}
+
+impl Drop for RawStream {
+    fn drop(&mut self) {
+        self.connection.close(VarInt::from_u32(0), b"stream closed");
+    }
+}
+
+/// Opens streams to peers. Cheap to clone, usable from any task.
+#[derive(Clone)]
+pub struct StreamOpener {
+    pub(crate) pool: Arc<ConnectionPool>,
+}
+
+impl StreamOpener {
+    /// Connect to `to` on its own QUIC connection and open a stream.
+    pub async fn open(&self, to: NodeId) -> Result<TomStream, TomTransportError> {
+        let connection = self.pool.dial(to, TOM_STREAM_ALPN).await?;
+        let (send, recv) = connection.open_bi().await.map_err(|e| TomTransportError::Send {
+            node_id: to,
+            source: e.into(),
+        })?;
+        Ok(TomStream {
+            peer: to,
+            send,
+            recv,
+            connection,
+        })
+    }
+
+    /// Connect to `to` on its own QUIC connection and open a raw stream
+    /// tagged with `protocol` (at most `MAX_PROTOCOL_TAG` bytes).
+    pub async fn open_raw(&self, to: NodeId, protocol: &[u8]) -> Result<RawStream, TomTransportError> {
+        if protocol.len() > MAX_PROTOCOL_TAG {
+            return Err(TomTransportError::MessageTooLarge {
+                size: protocol.len(),
+                max: MAX_PROTOCOL_TAG,
+            });
+        }
+        let connection = self.pool.dial(to, TOM_RAW_STREAM_ALPN).await?;
+        let send_err = |e: anyhow::Error| TomTransportError::Send {
+            node_id: to,
+            source: e,
+        };
+        let (mut send, recv) = connection.open_bi().await.map_err(|e| send_err(e.into()))?;
+        let mut tag = Vec::with_capacity(1 + protocol.len());
+        tag.push(protocol.len() as u8);
+        tag.extend_from_slice(protocol);
+        send.write_all(&tag).await.map_err(|e| send_err(e.into()))?;
+        Ok(RawStream {
+            peer: to,
+            protocol: protocol.to_vec(),
+            send,
+            recv,
+            connection,
+        })
+    }
+}
+
+/// Accepts streams opened by peers and hands them to `TomNode`.
+#[derive(Debug, Clone)]
+pub(crate) struct StreamHandler {
+    pub incoming_tx: mpsc::Sender<TomStream>,
+}
+
+impl tom_connect::protocol::ProtocolHandler for StreamHandler {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        let peer = NodeId::from_endpoint_id(connection.remote_id());
+        let Ok((send, recv)) = connection.accept_bi().await else {
+            return Ok(());
+        };
+        let stream = TomStream {
+            peer,
+            send,
+            recv,
+            connection,
+        };
+        if self.incoming_tx.send(stream).await.is_err() {
+            tracing::debug!(%peer, "stream refused: nobody accepts streams");
+        }
+        Ok(())
+    }
+}
+
+/// Accepts raw streams opened by peers, reads their sub-protocol tag and
+/// hands them to `TomNode`.
+#[derive(Debug, Clone)]
+pub(crate) struct RawStreamHandler {
+    pub incoming_tx: mpsc::Sender<RawStream>,
+}
+
+impl tom_connect::protocol::ProtocolHandler for RawStreamHandler {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        let peer = NodeId::from_endpoint_id(connection.remote_id());
+        let Ok((send, mut recv)) = connection.accept_bi().await else {
+            return Ok(());
+        };
+        let read_tag = async {
+            let len = recv.read_u8().await? as usize;
+            let mut protocol = vec![0u8; len];
+            AsyncReadExt::read_exact(&mut recv, &mut protocol).await?;
+            Ok::<_, std::io::Error>(protocol)
+        };
+        let protocol = match tokio::time::timeout(TAG_TIMEOUT, read_tag).await {
+            Ok(Ok(protocol)) => protocol,
+            _ => {
+                tracing::debug!(%peer, "raw stream dropped: no sub-protocol tag");
+                return Ok(());
+            }
+        };
+        let stream = RawStream {
+            peer,
+            protocol,
+            send,
+            recv,
+            connection,
+        };
+        if self.incoming_tx.send(stream).await.is_err() {
+            tracing::debug!(%peer, "raw stream refused: nobody accepts raw streams");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{TomNode, TomNodeConfig};
+
+    #[tokio::test]
+    async fn frames_flow_both_ways() {
+        let alice = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+        let mut bob = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+        alice.add_peer_addr(bob.addr()).await;
+        let mut incoming = bob.take_incoming_streams().unwrap();
+        assert!(bob.take_incoming_streams().is_none());
+
+        let mut stream = alice.streams().open(bob.id()).await.unwrap();
+        stream.send_frame(b"offer").await.unwrap();
+        let mut accepted = incoming.recv().await.unwrap();
+        assert_eq!(accepted.peer(), alice.id());
+        assert_eq!(accepted.recv_frame().await.unwrap().as_deref(), Some(&b"offer"[..]));
+
+        accepted.send_frame(b"accept").await.unwrap();
+        assert_eq!(stream.recv_frame().await.unwrap().as_deref(), Some(&b"accept"[..]));
+
+        // Bob finishes; Alice sees the end and hangs up, releasing Bob
+        let closing = tokio::spawn(accepted.close());
+        assert_eq!(stream.recv_frame().await.unwrap(), None);
+        drop(stream);
+        closing.await.unwrap();
+
+        alice.shutdown().await.unwrap();
+        bob.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn raw_streams_carry_their_sub_protocol() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let alice = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+        let mut bob = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+        alice.add_peer_addr(bob.addr()).await;
+
+        let too_long = vec![b'x'; crate::MAX_PROTOCOL_TAG + 1];
+        assert!(alice.send_stream(bob.id(), &too_long).await.is_err());
+
+        // Larger than one stream frame: no framing, no size limit
+        let payload: Vec<u8> = (0..3 * crate::MAX_STREAM_FRAME).map(|i| i as u8).collect();
+        let mut stream = alice.send_stream(bob.id(), b"sync/1").await.unwrap();
+        let sending = {
+            let payload = payload.clone();
+            tokio::spawn(async move {
+                stream.write_all(&payload).await.unwrap();
+                stream.shutdown().await.unwrap();
+                stream
+            })
+        };
+
+        let mut accepted = bob.recv_stream().await.unwrap();
+        assert_eq!(accepted.peer(), alice.id());
+        assert_eq!(accepted.protocol(), b"sync/1");
+        let mut received = Vec::new();
+        accepted.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, payload);
+
+        let stream = sending.await.unwrap();
+        let closing = tokio::spawn(accepted.close());
+        drop(stream);
+        closing.await.unwrap();
+
+        alice.shutdown().await.unwrap();
+        bob.shutdown().await.unwrap();
+    }
+}
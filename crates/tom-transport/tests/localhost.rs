@@ -41,7 +41,7 @@ async fn two_nodes_exchange_envelope() {
 
     assert_eq!(from, id_a);
     assert_eq!(received.msg_type, "chat");
-    assert_eq!(received.payload["text"], "Hello from A!");
+    assert_eq!(received.payload.as_json().unwrap()["text"], "Hello from A!");
     assert_eq!(received.from, id_a);
     assert_eq!(received.to, id_b);
     assert!(received.via.is_empty());
@@ -82,9 +82,20 @@ async fn raw_bytes_exchange() {
             .unwrap();
 
     assert_eq!(from, id_a);
-    assert_eq!(data, payload);
+    assert_eq!(data, &payload[..]);
 
     let node_a = send_handle.await.unwrap();
+
+    // Both sides count the message once, per peer and in total
+    let sent = node_a.peer_stats(id_b).expect("stats for B");
+    assert_eq!((sent.messages_sent, sent.bytes_sent), (1, payload.len() as u64));
+    assert!(sent.last_activity.is_some());
+    assert_eq!(node_a.path_kind(id_b), Some(sent.path));
+    let received = node_b.peer_stats(id_a).expect("stats for A");
+    assert_eq!((received.messages_received, received.bytes_received), (1, payload.len() as u64));
+    assert_eq!(node_b.node_stats().messages_received, 1);
+    assert_eq!(node_a.node_stats().peers_tracked, 1);
+
     node_a.shutdown().await.unwrap();
     node_b.shutdown().await.unwrap();
 }
@@ -172,3 +183,150 @@ async fn bidirectional_exchange() {
     node_a.shutdown().await.unwrap();
     node_b.shutdown().await.unwrap();
 }
+
+/// Sends to a peer that stopped answering fail fast: on timeout or on
+/// cancellation, instead of waiting out the QUIC handshake.
+#[tokio::test]
+async fn send_to_unreachable_peer_times_out_or_cancels() {
+    use std::time::Duration;
+    use tom_transport::CancellationToken;
+
+    let node_a = TomNode::bind(TomNodeConfig::new().n0_discovery(false).send_timeout(Duration::from_millis(300)))
+        .await
+        .unwrap();
+    let gone = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+    let (gone_id, gone_addr) = (gone.id(), gone.addr());
+    gone.shutdown().await.unwrap();
+    node_a.add_peer_addr(gone_addr).await;
+
+    let started = std::time::Instant::now();
+    let result = node_a.send_raw(gone_id, b"hello?").await;
+    assert!(matches!(result, Err(TomTransportError::Timeout { .. })), "{result:?}");
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let cancel = CancellationToken::new();
+    let canceller = {
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            cancel.cancel();
+        })
+    };
+    let result = node_a
+        .send_raw_with(gone_id, b"hello?", Duration::from_secs(60), &cancel)
+        .await;
+    assert!(matches!(result, Err(TomTransportError::Cancelled { .. })), "{result:?}");
+    canceller.await.unwrap();
+
+    node_a.shutdown().await.unwrap();
+}
+
+/// A pre-warmed connection is pooled and reused by the first send.
+#[tokio::test]
+async fn connect_eager_prewarms_the_connection() {
+    use std::time::Duration;
+
+    let node_a = TomNode::bind(TomNodeConfig::new().n0_discovery(false).send_timeout(Duration::from_secs(5)))
+        .await
+        .unwrap();
+    let mut node_b = TomNode::bind(TomNodeConfig::new().n0_discovery(false)).await.unwrap();
+    let (id_a, id_b) = (node_a.id(), node_b.id());
+    node_a.add_peer_addr(node_b.addr()).await;
+
+    let path = node_a.connect_eager(id_b).await.unwrap();
+    assert_eq!(node_a.path_kind(id_b), Some(path));
+    assert!(node_a.connected_peers().await.contains(&id_b));
+    // Nothing sent yet: warming up isn't traffic
+    let stats = node_a.peer_stats(id_b).unwrap();
+    assert_eq!(stats.messages_sent, 0);
+
+    node_a.send_raw(id_b, b"first").await.unwrap();
+    let (from, data) = tokio::time::timeout(Duration::from_secs(10), node_b.recv_raw())
+        .await
+        .expect("recv_raw timed out")
+        .unwrap();
+    assert_eq!((from, data.as_ref()), (id_a, &b"first"[..]));
+
+    node_a.shutdown().await.unwrap();
+    node_b.shutdown().await.unwrap();
+}
+
+/// A peer that vanishes without closing its connection is reported
+/// unreachable once keepalives go unanswered for the idle timeout.
+#[test]
+fn vanished_dialed_peer_is_reported_unreachable() {
+    // A dialed B: the watcher sits on A's outgoing connection
+    assert_vanished_peer_reported(true);
+}
+
+#[test]
+fn vanished_dialer_is_reported_unreachable() {
+    // B dialed A: the watcher sits on A's accepted connection
+    assert_vanished_peer_reported(false);
+}
+
+/// Kill B without a CONNECTION_CLOSE and check A reports it unreachable.
+fn assert_vanished_peer_reported(a_dials: bool) {
+    use std::time::Duration;
+    use tom_transport::PathKind;
+
+    let config = || {
+        TomNodeConfig::new()
+            .n0_discovery(false)
+            .keep_alive_interval(Duration::from_millis(200))
+            .idle_timeout(Duration::from_secs(1))
+    };
+
+    // B lives on its own runtime: dropping it kills B without a CONNECTION_CLOSE
+    let b_runtime = tokio::runtime::Runtime::new().unwrap();
+    let node_b = b_runtime.block_on(TomNode::bind(config())).unwrap();
+    let (id_b, addr_b) = (node_b.id(), node_b.addr());
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let node_a = runtime.block_on(TomNode::bind(config())).unwrap();
+    let mut events = node_a.path_events();
+    if a_dials {
+        runtime.block_on(async {
+            node_a.add_peer_addr(addr_b).await;
+            node_a.connect_eager(id_b).await.unwrap();
+        });
+    } else {
+        let (id_a, addr_a) = (node_a.id(), node_a.addr());
+        b_runtime.block_on(async {
+            node_b.add_peer_addr(addr_a).await;
+            node_b.connect_eager(id_a).await.unwrap();
+        });
+        // Wait until A has accepted the connection and watches it
+        runtime.block_on(async {
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while node_a.peer_stats(id_b).is_none() {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+            .await
+            .expect("B's connection never reached A");
+        });
+    }
+
+    runtime.block_on(async {
+        std::mem::forget(node_b);
+        b_runtime.shutdown_background();
+
+        let started = std::time::Instant::now();
+        let event = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let event = events.recv().await.unwrap();
+                if event.remote == id_b && event.is_unreachable() {
+                    return event;
+                }
+            }
+        })
+        .await
+        .expect("no unreachable event");
+        assert_eq!(event.kind, PathKind::Unreachable);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(node_a.peer_stats(id_b).unwrap().path, PathKind::Unreachable);
+
+        node_a.shutdown().await.unwrap();
+    });
+}
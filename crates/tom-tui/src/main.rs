@@ -8,6 +8,7 @@
 ///   tom-chat <peer-node-id>      # Start and connect to peer (TUI)
 ///   tom-chat --username alice     # Set username for gossip discovery
 ///   tom-chat --bot               # Headless bot — auto-responds to messages
+use std::collections::HashMap;
 use std::io;
 use std::time::{Duration, Instant};
 
@@ -17,8 +18,8 @@ use crossterm::ExecutableCommand;
 use ratatui::prelude::*;
 use ratatui::widgets::*;
 use tom_protocol::{
-    DeliveredMessage, NodeId, ProtocolEvent, ProtocolRuntime, RuntimeChannels, RuntimeConfig,
-    RuntimeHandle,
+    DeliveredMessage, MessageStatus, NodeId, ProtocolEvent, ProtocolRuntime, RuntimeChannels,
+    RuntimeConfig, RuntimeHandle, StatusChange, TextPayload,
 };
 use tom_transport::{TomNode, TomNodeConfig};
 
@@ -41,8 +42,8 @@ struct App {
     scroll: u16,
     /// Our short ID for display.
     short_id: String,
-    /// Total messages sent/received.
-    stats: Stats,
+    /// Envelope id of an outgoing message → index into `messages`.
+    by_message_id: HashMap<String, usize>,
 }
 
 struct ChatMessage {
@@ -50,12 +51,10 @@ struct ChatMessage {
     from: String,
     text: String,
     is_system: bool,
-}
-
-#[derive(Default)]
-struct Stats {
-    sent: u64,
-    received: u64,
+    /// Delivery status (outgoing messages only).
+    status: Option<MessageStatus>,
+    /// Sender signature check (incoming messages only).
+    signature_valid: Option<bool>,
 }
 
 impl App {
@@ -70,7 +69,7 @@ impl App {
             quit: false,
             scroll: 0,
             short_id,
-            stats: Stats::default(),
+            by_message_id: HashMap::new(),
         }
     }
 
@@ -80,20 +79,47 @@ impl App {
             from: "system".into(),
             text,
             is_system: true,
+            status: None,
+            signature_valid: None,
         });
         self.scroll_to_bottom();
     }
 
-    fn add_chat_message(&mut self, from: &str, text: String) {
+    fn add_chat_message(&mut self, from: &str, text: String, signature_valid: bool) {
         self.messages.push(ChatMessage {
             timestamp: now_hms(),
             from: from.to_string(),
             text,
             is_system: false,
+            status: None,
+            signature_valid: Some(signature_valid),
+        });
+        self.scroll_to_bottom();
+    }
+
+    fn add_outgoing_message(&mut self, message_id: String, text: String) {
+        self.by_message_id.insert(message_id, self.messages.len());
+        self.messages.push(ChatMessage {
+            timestamp: now_hms(),
+            from: self.short_id.clone(),
+            text,
+            is_system: false,
+            status: Some(MessageStatus::Pending),
+            signature_valid: None,
         });
         self.scroll_to_bottom();
     }
 
+    /// Apply a tracker status change to the matching outgoing message.
+    fn apply_status_change(&mut self, change: &StatusChange) {
+        let Some(&idx) = self.by_message_id.get(&change.message_id) else {
+            return;
+        };
+        if let Some(msg) = self.messages.get_mut(idx) {
+            msg.status = Some(change.current);
+        }
+    }
+
     fn scroll_to_bottom(&mut self) {
         if self.messages.len() > 20 {
             self.scroll = (self.messages.len() as u16).saturating_sub(20);
@@ -159,7 +185,7 @@ async fn main() -> anyhow::Result<()> {
     let RuntimeChannels {
         handle,
         mut messages,
-        status_changes: _status_changes,
+        mut status_changes,
         mut events,
     } = ProtocolRuntime::spawn(node, config);
 
@@ -243,6 +269,11 @@ async fn main() -> anyhow::Result<()> {
             handle_incoming(&mut app, &msg);
         }
 
+        // Process delivery status updates for our outgoing messages
+        while let Ok(change) = status_changes.try_recv() {
+            app.apply_status_change(&change);
+        }
+
         // Process protocol events
         while let Ok(evt) = events.try_recv() {
             handle_protocol_event(&mut app, &evt);
@@ -253,16 +284,21 @@ async fn main() -> anyhow::Result<()> {
         }
 
         if app.quit {
-            handle.shutdown().await;
             break;
         }
     }
 
+    let stats = handle.transport_stats().await;
+    handle.shutdown().await;
+
     // Cleanup
     disable_raw_mode()?;
     io::stdout().execute(LeaveAlternateScreen)?;
 
-    eprintln!("\n  Stats: {} sent, {} received", app.stats.sent, app.stats.received);
+    eprintln!(
+        "\n  Stats: {} sent, {} received",
+        stats.messages_sent, stats.messages_received
+    );
     Ok(())
 }
 
@@ -271,7 +307,7 @@ async fn main() -> anyhow::Result<()> {
 async fn handle_input(app: &mut App, text: &str, handle: &RuntimeHandle) {
     // Commands
     if text.starts_with('/') {
-        handle_command(app, text);
+        handle_command(app, text, handle).await;
         return;
     }
 
@@ -283,9 +319,8 @@ async fn handle_input(app: &mut App, text: &str, handle: &RuntimeHandle) {
 
     // Send via protocol runtime (handles envelope, signing, encryption, relay selection)
     match handle.send_message(peer_id, text.as_bytes().to_vec()).await {
-        Ok(()) => {
-            app.stats.sent += 1;
-            app.add_chat_message(&app.short_id.clone(), text.to_string());
+        Ok(message_id) => {
+            app.add_outgoing_message(message_id, text.to_string());
             app.status = format!("Sent to {}", short_node_id(&peer_id));
         }
         Err(e) => {
@@ -294,7 +329,7 @@ async fn handle_input(app: &mut App, text: &str, handle: &RuntimeHandle) {
     }
 }
 
-fn handle_command(app: &mut App, cmd: &str) {
+async fn handle_command(app: &mut App, cmd: &str, handle: &RuntimeHandle) {
     let parts: Vec<&str> = cmd.splitn(2, ' ').collect();
     match parts[0] {
         "/connect" | "/c" => {
@@ -317,13 +352,37 @@ fn handle_command(app: &mut App, cmd: &str) {
             app.add_system_message(format!("Your ID: {}", app.local_id));
         }
         "/stats" => {
+            let stats = handle.transport_stats().await;
             app.add_system_message(format!(
-                "Sent: {} msgs | Received: {} msgs",
-                app.stats.sent, app.stats.received
+                "Sent: {} msgs ({} B) | Received: {} msgs ({} B) | {} peers",
+                stats.messages_sent,
+                stats.bytes_sent,
+                stats.messages_received,
+                stats.bytes_received,
+                stats.peers_tracked
             ));
+            if let Some(peer_id) = app.peer_id {
+                match handle.peer_transport_stats(peer_id).await {
+                    Some(peer) => app.add_system_message(format!(
+                        "  {}: {} sent / {} received | {} | RTT {} | last activity {}",
+                        short_node_id(&peer_id),
+                        peer.messages_sent,
+                        peer.messages_received,
+                        peer.path,
+                        peer.rtt.map_or("?".into(), |rtt| format!("{}ms", rtt.as_millis())),
+                        peer.last_activity
+                            .map_or("never".into(), |at| format!("{}s ago", at.elapsed().as_secs())),
+                    )),
+                    None => app.add_system_message(format!(
+                        "  {}: no traffic yet",
+                        short_node_id(&peer_id)
+                    )),
+                }
+            }
         }
         "/clear" => {
             app.messages.clear();
+            app.by_message_id.clear();
             app.scroll = 0;
         }
         "/help" | "/h" => {
@@ -334,6 +393,10 @@ fn handle_command(app: &mut App, cmd: &str) {
             app.add_system_message("  /clear         — clear messages".into());
             app.add_system_message("  /quit          — exit".into());
             app.add_system_message("  Ctrl+C / Esc   — exit".into());
+            app.add_system_message(
+                "Status: ⋯ pending  ✓ sent  ⇢ relayed  ✓✓ delivered  ◉ read  ✗ failed  ⚠ unverified"
+                    .into(),
+            );
         }
         "/quit" | "/q" => {
             app.quit = true;
@@ -346,17 +409,27 @@ fn handle_command(app: &mut App, cmd: &str) {
 
 // ── Incoming message handling ────────────────────────────────────────────
 
+/// Displayable text for a delivered message (legacy bytes or typed text).
+fn message_text(msg: &DeliveredMessage) -> String {
+    match msg.content_type.as_deref() {
+        None => String::from_utf8_lossy(&msg.payload).into_owned(),
+        Some(content_type) => match msg.decode::<TextPayload>() {
+            Ok(p) => p.text,
+            Err(_) => format!("[{content_type}]"),
+        },
+    }
+}
+
 fn handle_incoming(app: &mut App, msg: &DeliveredMessage) {
-    let sig_label = if msg.signature_valid { "verified" } else { "unverified" };
-    let enc_label = if msg.was_encrypted { "encrypted" } else { "plain" };
+    let enc_label = if msg.was_encrypted { "" } else { " [plain]" };
 
     let from_short = short_node_id(&msg.from);
-    let text = String::from_utf8_lossy(&msg.payload);
+    let text = message_text(msg);
 
-    app.stats.received += 1;
     app.add_chat_message(
         &from_short,
-        format!("{} [{}, {}]", text, sig_label, enc_label),
+        format!("{}{}", text, enc_label),
+        msg.signature_valid,
     );
 
     // Auto-set peer if not set
@@ -394,6 +467,9 @@ fn handle_protocol_event(app: &mut App, event: &ProtocolEvent) {
         ProtocolEvent::PeerOnline { node_id } => {
             app.add_system_message(format!("Peer online: {}", short_node_id(node_id)));
         }
+        ProtocolEvent::PeerPresenceChanged { node_id, presence } => {
+            app.add_system_message(format!("Peer {} is now {:?}", short_node_id(node_id), presence));
+        }
         ProtocolEvent::PathChanged { event } => {
             app.add_system_message(format!("Path changed: {:?}", event));
         }
@@ -449,7 +525,7 @@ fn draw_ui(f: &mut Frame, app: &App) {
             } else {
                 let is_self = m.from == app.short_id;
                 let name_color = if is_self { Color::Cyan } else { Color::Green };
-                Line::from(vec![
+                let mut spans = vec![
                     Span::styled(
                         format!("[{}] ", m.timestamp),
                         Style::default().fg(Color::DarkGray),
@@ -459,7 +535,18 @@ fn draw_ui(f: &mut Frame, app: &App) {
                         Style::default().fg(name_color).bold(),
                     ),
                     Span::raw(&m.text),
-                ])
+                ];
+                if let Some(status) = m.status {
+                    let (glyph, color) = status_glyph(status);
+                    spans.push(Span::styled(format!(" {glyph}"), Style::default().fg(color)));
+                }
+                if m.signature_valid == Some(false) {
+                    spans.push(Span::styled(
+                        " ⚠ unverified",
+                        Style::default().fg(Color::Red).bold(),
+                    ));
+                }
+                Line::from(spans)
             }
         })
         .collect();
@@ -515,7 +602,7 @@ async fn run_bot(
         };
 
         let sig_label = if msg.signature_valid { "ok" } else { "bad" };
-        let text = String::from_utf8_lossy(&msg.payload);
+        let text = message_text(&msg);
         count += 1;
 
         println!(
@@ -529,7 +616,7 @@ async fn run_bot(
         // Auto-reply via runtime (handles signing, encryption, relay selection)
         let reply = format!("recu 5/5 malik (msg #{})", count);
         match handle.send_message(msg.from, reply.as_bytes().to_vec()).await {
-            Ok(()) => println!("[bot] replied: \"{}\"", reply),
+            Ok(_) => println!("[bot] replied: \"{}\"", reply),
             Err(e) => println!("[bot] send error: {}", e),
         }
     }
@@ -539,6 +626,19 @@ async fn run_bot(
 
 // ── Helpers ──────────────────────────────────────────────────────────────
 
+/// Glyph and color for an outgoing message's delivery status.
+fn status_glyph(status: MessageStatus) -> (&'static str, Color) {
+    match status {
+        MessageStatus::Pending => ("⋯", Color::DarkGray),
+        MessageStatus::Queued => ("⏸", Color::DarkGray),
+        MessageStatus::Sent => ("✓", Color::DarkGray),
+        MessageStatus::Relayed => ("⇢", Color::Gray),
+        MessageStatus::Delivered => ("✓✓", Color::Green),
+        MessageStatus::Read => ("◉", Color::Cyan),
+        MessageStatus::Failed => ("✗", Color::Red),
+    }
+}
+
 fn short_node_id(id: &NodeId) -> String {
     let s = id.to_string();
     if s.len() > 8 {